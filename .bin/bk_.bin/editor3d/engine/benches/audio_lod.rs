@@ -0,0 +1,87 @@
+//! Coste de mezclar un bloque con cada vez más fuentes cuando la mayoría
+//! está más allá de `far_distance`. Las fuentes lejanas solo avanzan su
+//! cursor, así que el tiempo por fuente baja al crecer la escena en vez de
+//! mantenerse como con todas las fuentes cerca del oyente
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::{Quat, Vec3};
+use metaverso_engine::audio::mixer::{AudioBus, AudioMixer, MixListener, VoiceParams};
+use metaverso_engine::audio::AudioClip;
+use metaverso_engine::ecs::SpatialAudioConfig;
+
+/// Frecuencia de salida
+const SAMPLE_RATE: u32 = 48_000;
+/// Frames por bloque de mezcla
+const BLOCK_FRAMES: usize = 512;
+/// Fuentes cercanas al oyente en cada escena
+const NEAR_SOURCES: usize = 16;
+
+fn spatial_config() -> SpatialAudioConfig {
+    SpatialAudioConfig {
+        min_distance: 1.0,
+        max_distance: 100.0,
+        rolloff: 1.0,
+        near_distance: 20.0,
+        far_distance: 80.0,
+        min_gain: 0.0,
+    }
+}
+
+/// Mezclador con `NEAR_SOURCES` fuentes en la banda HRTF y el resto
+/// repartidas en un anillo a 200 m del oyente
+fn scene(sources: usize) -> AudioMixer {
+    let mut mixer = AudioMixer::new(SAMPLE_RATE);
+    let samples = (0..SAMPLE_RATE as usize)
+        .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / SAMPLE_RATE as f32).sin())
+        .collect();
+    mixer.load_clip(AudioClip {
+        id: "tono".to_string(),
+        sample_rate: SAMPLE_RATE,
+        channels: 1,
+        samples,
+    });
+
+    for i in 0..sources {
+        let angle = i as f32 * 0.618 * std::f32::consts::TAU;
+        let radius = if i < NEAR_SOURCES { 5.0 + i as f32 } else { 200.0 };
+        mixer.play(VoiceParams {
+            clip_id: "tono".to_string(),
+            bus: AudioBus::Sfx,
+            volume: 1.0,
+            pitch: 1.0,
+            looped: true,
+            position: Vec3::new(angle.cos() * radius, 0.0, angle.sin() * radius),
+            spatial: Some(spatial_config()),
+            entity_id: None,
+            source_id: None,
+            reverb_send: 1.0,
+            effects: Vec::new(),
+            velocity: Vec3::ZERO,
+            doppler: true,
+        });
+    }
+    mixer
+}
+
+fn audio_lod(c: &mut Criterion) {
+    let listener = MixListener {
+        position: Vec3::ZERO,
+        orientation: Quat::IDENTITY,
+        velocity: Vec3::ZERO,
+        volume: 1.0,
+        hrtf: true,
+        hrtf_interpolation: true,
+    };
+
+    let mut group = c.benchmark_group("audio_lod/mix_bloque");
+    for sources in [64, 256, 1024, 4096] {
+        let mut mixer = scene(sources);
+        group.bench_with_input(BenchmarkId::from_parameter(sources), &sources, |b, _| {
+            b.iter(|| mixer.mix(&listener, BLOCK_FRAMES))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, audio_lod);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+//! Ratio y velocidad de la compresión de paquetes sobre una actualización de
+//! estado de 50 KB (unas 500 entidades con transformación y velocidad)
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use metaverso_engine::networking::compression::{compress_packet, decompress_packet, CompressionMode};
+
+/// Tamaño del payload de estado
+const PAYLOAD_SIZE: usize = 50 * 1024;
+
+/// Actualización de estado serializada como la replicación: por entidad su
+/// ID, posición, rotación y velocidad, con movimientos parecidos entre
+/// entidades vecinas
+fn state_update() -> Vec<u8> {
+    let mut payload = Vec::with_capacity(PAYLOAD_SIZE);
+    let mut entity_id: u64 = 1000;
+    while payload.len() < PAYLOAD_SIZE {
+        let t = entity_id as f32 * 0.01;
+        let position = [t.sin() * 40.0, 1.5 + (t * 0.5).cos(), t.cos() * 40.0];
+        let rotation = [0.0f32, (t * 0.5).sin(), 0.0, (t * 0.5).cos()];
+        let velocity = [(t * 3.0).cos() * 8.0, 2.0, (t * 3.0).sin() * 8.0];
+        payload.extend_from_slice(&entity_id.to_le_bytes());
+        for value in position.iter().chain(&rotation).chain(&velocity) {
+            payload.extend_from_slice(&value.to_le_bytes());
+        }
+        payload.extend_from_slice(&1u64.to_le_bytes());
+        entity_id += 1;
+    }
+    payload.truncate(PAYLOAD_SIZE);
+    payload
+}
+
+fn packet_compression(c: &mut Criterion) {
+    let payload = state_update();
+    let modes = [
+        ("none", CompressionMode::None),
+        ("lz4", CompressionMode::Lz4),
+        ("zstd-3", CompressionMode::Zstd(3)),
+    ];
+
+    for (name, mode) in modes {
+        let packet = compress_packet(mode, &payload).unwrap();
+        println!(
+            "{}: {} -> {} bytes (ratio {:.2})",
+            name,
+            payload.len(),
+            packet.len(),
+            payload.len() as f64 / packet.len() as f64,
+        );
+    }
+
+    let mut group = c.benchmark_group("packet_compression");
+    group.throughput(Throughput::Bytes(payload.len() as u64));
+    for (name, mode) in modes {
+        group.bench_with_input(BenchmarkId::new("compress", name), &payload, |b, payload| {
+            b.iter(|| compress_packet(mode, payload).unwrap())
+        });
+        let packet = compress_packet(mode, &payload).unwrap();
+        group.bench_with_input(BenchmarkId::new("decompress", name), &packet, |b, packet| {
+            b.iter(|| decompress_packet(packet).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, packet_compression);
+criterion_main!(benches);
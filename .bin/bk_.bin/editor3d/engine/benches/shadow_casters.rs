@@ -0,0 +1,88 @@
+//! Draw calls de sombra con y sin selección de casters en una escena
+//! exterior: un terreno de 1 km con un árbol o roca cada 10 m, la cámara a
+//! la altura de los ojos y el sol de media tarde. Sin selección cada entidad
+//! visible se dibuja en las cuatro cascadas
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use glam::{Mat4, Vec3};
+use metaverso_engine::lighting::casters::{shadow_caster_set, AabbCache};
+use metaverso_engine::renderer::culling::{Aabb, Frustum};
+use metaverso_engine::renderer::shadows::{compute_cascades, ShadowSettings};
+
+/// Lado del terreno en metros
+const TERRAIN_SIZE: f32 = 1000.0;
+/// Separación entre objetos en metros
+const SPACING: f32 = 10.0;
+
+/// Árboles (altos) y rocas (bajas) en una rejilla con algo de desorden
+fn outdoor_scene() -> AabbCache {
+    let mut cache = AabbCache::new();
+    let cells = (TERRAIN_SIZE / SPACING) as i32;
+    let mut entity_id = 0;
+    for x in 0..cells {
+        for z in 0..cells {
+            let jitter = ((x * 7919 + z * 104_729) % 100) as f32 / 100.0;
+            let position = Vec3::new(
+                (x as f32 + jitter) * SPACING - TERRAIN_SIZE * 0.5,
+                0.0,
+                (z as f32 + 1.0 - jitter) * SPACING - TERRAIN_SIZE * 0.5,
+            );
+            let size = if (x + z) % 3 == 0 { Vec3::new(1.0, 0.8, 1.0) } else { Vec3::new(2.0, 8.0, 2.0) };
+            cache.insert(entity_id, Aabb::new(position - Vec3::new(size.x, 0.0, size.z) * 0.5, position + Vec3::new(size.x * 0.5, size.y, size.z * 0.5)));
+            entity_id += 1;
+        }
+    }
+    cache
+}
+
+fn shadow_casters(c: &mut Criterion) {
+    let cache = outdoor_scene();
+    let (near, far) = (0.1, 1000.0);
+    let view = Mat4::look_at_rh(Vec3::new(0.0, 1.7, 0.0), Vec3::new(100.0, 1.0, 30.0), Vec3::Y);
+    let projection = Mat4::perspective_rh(60f32.to_radians(), 16.0 / 9.0, near, far);
+    let view_projection = projection * view;
+    let light_direction = Vec3::new(-0.4, -1.0, -0.3).normalize();
+    let settings = ShadowSettings {
+        resolution: 2048,
+        cascade_count: 4,
+        split_factor: 0.75,
+        constant_bias: 0.0005,
+        slope_bias: 1.5,
+        pcf: true,
+    };
+    let shadows = compute_cascades(settings, view, &view_projection, near, far, light_direction);
+    let camera_frustum = Frustum::from_view_projection(&view_projection);
+
+    // El renderer solo encola las entidades visibles, y cada cascada dibuja todas
+    let mut visible: Vec<u64> = cache.iter()
+        .filter(|(_, aabb)| camera_frustum.intersects(aabb))
+        .map(|(entity_id, _)| entity_id)
+        .collect();
+    visible.sort_unstable();
+    let without = visible.len() * shadows.cascades.len();
+    let with: usize = shadows.cascades.iter()
+        .map(|cascade| {
+            let set = shadow_caster_set(&cache, &camera_frustum, light_direction, cascade);
+            visible.iter().filter(|entity_id| set.binary_search(entity_id).is_ok()).count()
+        })
+        .sum();
+    println!(
+        "{} entidades, {} visibles: {} draw calls de sombra sin selección, {} con selección ({:.1}% menos)",
+        cache.len(),
+        visible.len(),
+        without,
+        with,
+        100.0 * (1.0 - with as f64 / without.max(1) as f64),
+    );
+
+    c.bench_function("shadow_caster_set/4_cascadas", |b| {
+        b.iter(|| {
+            shadows.cascades.iter()
+                .map(|cascade| shadow_caster_set(&cache, &camera_frustum, light_direction, cascade).len())
+                .sum::<usize>()
+        })
+    });
+}
+
+criterion_group!(benches, shadow_casters);
+criterion_main!(benches);
@@ -61,6 +61,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     },
                 },
             },
+            variable_rate_shading: None,
+            skinning_mode: Default::default(),
+            dynamic_resolution: None,
+            bloom: None,
         },
         physics_config: engine_3d::physics::PhysicsConfig {
             physics_enabled: true,
@@ -215,6 +219,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             scene_graph: true,
             spatial_indexing: true,
         },
+        terrain_config: engine_3d::terrain::TerrainConfig::default(),
         camera_config: engine_3d::camera::CameraConfig {
             camera_management: true,
             camera_types: vec![
@@ -222,6 +227,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 engine_3d::camera::CameraType::Orthographic,
             ],
         },
+        vr_config: Default::default(),
         lighting_config: engine_3d::lighting::LightingConfig {
             lighting_enabled: true,
             light_types: vec![
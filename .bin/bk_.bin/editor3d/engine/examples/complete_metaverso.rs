@@ -6,7 +6,7 @@
 use metaverso_engine::{
     Engine3D, EngineConfig, GeneralConfig, PerformanceConfig, GraphicsConfig,
     initialize_engine, run_engine_loop,
-    ecs::{ECSSystem, ECSConfig, Entity, TransformComponent, MeshComponent, MaterialComponent},
+    ecs::{ECSSystem, ECSConfig, Entity, TransformComponent, MeshComponent, MaterialComponent, RENDER_LAYER_AVATAR, RENDER_LAYER_DEFAULT},
     physics::{PhysicsSystem, PhysicsConfig, PhysicsBody, BodyType},
     networking::{NetworkingSystem, NetworkingConfig, NetworkType},
     audio::{AudioSystem, AudioConfig, AudioSource, AudioSourceType},
@@ -92,6 +92,10 @@ fn create_engine_config() -> EngineConfig {
                     },
                 },
             },
+            variable_rate_shading: None,
+            skinning_mode: Default::default(),
+            dynamic_resolution: None,
+            bloom: None,
         },
         physics_config: PhysicsConfig {
             enabled: true,
@@ -158,6 +162,13 @@ fn create_engine_config() -> EngineConfig {
                     buffer_size: 1024,
                 },
             },
+            authority_config: metaverso_engine::physics::authority::AuthorityConfig {
+                enabled: true,
+                claim_radius: 3.0,
+                idle_timeout: 2.0,
+                idle_velocity_threshold: 0.05,
+                is_server: false,
+            },
         },
         networking_config: NetworkingConfig {
             enabled: true,
@@ -236,12 +247,19 @@ fn create_engine_config() -> EngineConfig {
                     rotation: true,
                 },
             },
+            relays: vec![],
+            relay_upgrade_interval: 30,
+            transfer_config: metaverso_engine::networking::transfer::TransferConfig::default(),
+            collab_enabled: false,
+            packet_compression: metaverso_engine::networking::compression::CompressionMode::Lz4,
+            replication_tick_rate: 0.0,
         },
         wasm_config: metaverso_engine::wasm::WASMConfig {
             enabled: true,
             hot_reloading: true,
             sandboxing: true,
             memory_limit: 1024 * 1024 * 100, // 100MB
+            scripting_max_ops_per_frame: 100_000,
         },
         renderer_config: RendererConfig {
             enabled: true,
@@ -249,6 +267,7 @@ fn create_engine_config() -> EngineConfig {
             quality_config: metaverso_engine::renderer::QualityConfig {
                 quality_level: metaverso_engine::renderer::QualityLevel::High,
                 resolution: [1920, 1080],
+                vsync: true,
                 antialiasing: metaverso_engine::renderer::AntialiasingConfig {
                     antialiasing_type: metaverso_engine::renderer::AntialiasingType::MSAA,
                     antialiasing_level: 4,
@@ -316,7 +335,16 @@ fn create_engine_config() -> EngineConfig {
                 instancing: true,
                 batching: true,
                 lod: true,
+                distance_culling: true,
+                max_draw_distance: 500.0,
+                instancing_threshold: 8,
+                max_decals: 32,
             },
+            texture_budget_bytes: 512 * 1024 * 1024,
+            shader_cache_dir: Some(std::path::PathBuf::from("cache")),
+            precompile_on_startup: true,
+            enable_atlas_packing: true,
+            atlas_max_size: 2048,
         },
         scene_config: SceneConfig {
             enabled: true,
@@ -324,6 +352,7 @@ fn create_engine_config() -> EngineConfig {
             scene_graph: true,
             spatial_indexing: true,
         },
+        terrain_config: Default::default(),
         camera_config: CameraConfig {
             enabled: true,
             camera_type: CameraType::Perspective,
@@ -332,12 +361,15 @@ fn create_engine_config() -> EngineConfig {
             near_plane: 0.1,
             far_plane: 1000.0,
         },
+        vr_config: Default::default(),
         lighting_config: LightingConfig {
             enabled: true,
             ambient_lighting: true,
             directional_lighting: true,
             point_lighting: true,
             spot_lighting: true,
+            environment: Default::default(),
+            clustering: Default::default(),
         },
         material_config: MaterialConfig {
             enabled: true,
@@ -396,7 +428,12 @@ fn create_engine_config() -> EngineConfig {
                         num_rays: 8,
                         max_distance: 50.0,
                     },
+                    thickness_factor: 0.5,
+                    max_attenuation_db: 18.0,
+                    min_cutoff: 600.0,
                 },
+                near_distance: 25.0,
+                far_distance: 150.0,
             },
             effects_config: metaverso_engine::audio::EffectsConfig {
                 reverb: metaverso_engine::audio::ReverbConfig {
@@ -460,6 +497,14 @@ fn create_engine_config() -> EngineConfig {
                     },
                 },
             },
+            voice_chat_enabled: true,
+            voice_bitrate: 24000,
+            device_output: true,
+            stream_read_ahead: 2.0,
+            ducking: vec![metaverso_engine::audio::bus::DuckingRule::voice_over_music()],
+            doppler_factor: 1.0,
+            doppler_pitch_range: (0.5, 2.0),
+            max_ambient_voices: 8,
         },
         crypto_config: CryptoConfig {
             enabled: true,
@@ -614,6 +659,10 @@ async fn create_terrain(engine: &mut Engine3D) -> Result<()> {
         indices: vec![0, 1, 2, 0, 2, 3],
         material_id: Some("grass_material".to_string()),
         lod_level: 0,
+        lod_indices: Vec::new(),
+        joint_indices: Vec::new(),
+        joint_weights: Vec::new(),
+        render_layers: RENDER_LAYER_DEFAULT,
     };
     ecs_system.add_component(terrain_id, Box::new(mesh)).await?;
 
@@ -688,6 +737,10 @@ async fn create_buildings(engine: &mut Engine3D) -> Result<()> {
             ],
             material_id: Some("building_material".to_string()),
             lod_level: 0,
+            lod_indices: Vec::new(),
+            joint_indices: Vec::new(),
+            joint_weights: Vec::new(),
+            render_layers: RENDER_LAYER_DEFAULT,
         };
         ecs_system.add_component(building_id, Box::new(mesh)).await?;
 
@@ -743,6 +796,10 @@ async fn create_avatars(engine: &mut Engine3D) -> Result<()> {
         indices: vec![0, 1, 2, 0, 2, 3],
         material_id: Some("avatar_material".to_string()),
         lod_level: 0,
+        lod_indices: Vec::new(),
+        joint_indices: Vec::new(),
+        joint_weights: Vec::new(),
+        render_layers: RENDER_LAYER_AVATAR,
     };
     ecs_system.add_component(avatar_id, Box::new(mesh)).await?;
 
@@ -814,6 +871,10 @@ async fn create_interactive_objects(engine: &mut Engine3D) -> Result<()> {
         indices: vec![0, 1, 2, 0, 2, 3],
         material_id: Some("portal_material".to_string()),
         lod_level: 0,
+        lod_indices: Vec::new(),
+        joint_indices: Vec::new(),
+        joint_weights: Vec::new(),
+        render_layers: RENDER_LAYER_DEFAULT,
     };
     ecs_system.add_component(portal_id, Box::new(mesh)).await?;
 
@@ -837,6 +898,7 @@ async fn create_interactive_objects(engine: &mut Engine3D) -> Result<()> {
             running: true,
             error: None,
         },
+        pending_calls: vec![],
     };
     ecs_system.add_component(portal_id, Box::new(script)).await?;
 
@@ -869,6 +931,7 @@ async fn create_audio_sources(engine: &mut Engine3D) -> Result<()> {
             playback_time: 0.0,
             position: Vec3::ZERO,
             velocity: Vec3::ZERO,
+            distance_gain: 1.0,
         },
         effects: vec!["reverb".to_string()],
     };
@@ -900,6 +963,7 @@ async fn create_audio_sources(engine: &mut Engine3D) -> Result<()> {
             playback_time: 0.0,
             position: Vec3::new(0.0, 0.0, 0.0),
             velocity: Vec3::ZERO,
+            distance_gain: 1.0,
         },
         effects: vec![],
     };
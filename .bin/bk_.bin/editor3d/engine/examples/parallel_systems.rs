@@ -0,0 +1,103 @@
+//! Benchmark de ejecución paralela de sistemas del ECS
+//!
+//! Registra ocho sistemas independientes con carga de CPU y compara el tiempo
+//! por frame en serie y con el pool de jobs. En una máquina de ocho núcleos la
+//! ejecución paralela debería ser unas cuatro veces más rápida o más.
+
+use metaverso_engine::ecs::{
+    self, ECSConfig, EntityConfig, ComponentConfig, SystemConfig, OptimizationConfig,
+};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+
+/// Sistemas independientes del benchmark
+const SYSTEM_COUNT: usize = 8;
+
+/// Frames medidos por modo
+const FRAMES: u32 = 50;
+
+/// Trabajo de CPU por sistema y frame
+const WORK_PER_SYSTEM: Duration = Duration::from_millis(4);
+
+/// Sistema que ocupa la CPU durante un tiempo fijo
+struct BusySystem {
+    name: String,
+}
+
+impl ecs::ECSSystem for BusySystem {
+    fn execute(&self, _world: &ecs::ECSSystem) -> Result<()> {
+        let start = Instant::now();
+        let mut acc = 0u64;
+        while start.elapsed() < WORK_PER_SYSTEM {
+            acc = std::hint::black_box(acc.wrapping_mul(6364136223846793005).wrapping_add(1));
+        }
+        Ok(())
+    }
+
+    fn get_priority(&self) -> u32 {
+        100
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+fn create_config(parallel: bool) -> ECSConfig {
+    ECSConfig {
+        enabled: true,
+        entity_config: EntityConfig {
+            max_entities: 1000,
+            entity_pool: false,
+            id_reuse: false,
+        },
+        component_config: ComponentConfig {
+            max_components_per_entity: 16,
+            component_cache: false,
+            auto_serialization: false,
+        },
+        system_config: SystemConfig {
+            parallel_execution: parallel,
+            system_priority: true,
+            hot_reloading: false,
+        },
+        optimization_config: OptimizationConfig {
+            cache_friendly: true,
+            memory_pooling: false,
+            batch_processing: false,
+        },
+        max_parallel_systems: SYSTEM_COUNT,
+        system_hot_reloading: false,
+        systems_directory: "systems".into(),
+    }
+}
+
+/// Tiempo medio por frame
+async fn measure(parallel: bool) -> Result<Duration> {
+    let mut world = ecs::ECSSystem::new(create_config(parallel));
+    world.initialize().await?;
+    for i in 0..SYSTEM_COUNT {
+        world.add_system(Box::new(BusySystem { name: format!("BusySystem{}", i) }));
+    }
+
+    // Calentamiento: construye el DAG y arranca los workers
+    world.update(0.016).await?;
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        world.update(0.016).await?;
+    }
+    Ok(start.elapsed() / FRAMES)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let sequential = measure(false).await?;
+    let parallel = measure(true).await?;
+
+    println!("Sistemas: {} | núcleos: {}", SYSTEM_COUNT, std::thread::available_parallelism().map_or(1, |n| n.get()));
+    println!("Secuencial: {:?} por frame", sequential);
+    println!("Paralelo:   {:?} por frame", parallel);
+    println!("Aceleración: {:.2}x", sequential.as_secs_f64() / parallel.as_secs_f64());
+    Ok(())
+}
@@ -0,0 +1,102 @@
+//! # Macros del motor
+//!
+//! `#[derive(Component)]` implementa `metaverso_engine::ecs::Component` para
+//! un struct `Clone + Serialize + Deserialize`:
+//!
+//! - `get_type` devuelve la variante de `ComponentType` del nombre del struct
+//!   (`TransformComponent` es `ComponentType::Transform`, etc.) o
+//!   `ComponentType::Custom("Nombre")` si no es uno de los tipos del motor.
+//! - `clone_box` clona el struct.
+//! - `serialize` y `deserialize` usan bincode.
+//!
+//! También implementa `ComponentTypeOf`, que da el tipo sin instancia y es lo
+//! que usa `register_component!` para registrar el deserializador.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Ident};
+
+/// Componentes del motor: prefijo del struct y variante de `ComponentType`
+const BUILTIN_COMPONENTS: &[&str] = &[
+    "Transform", "Mesh", "Material", "Light", "Camera", "Physics", "Audio", "Animation", "Script", "Network",
+    "Decal", "Label", "AmbientZone",
+];
+
+/// Implementa `Component` y `ComponentTypeOf` para un struct
+#[proc_macro_derive(Component)]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_component(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_component(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    if !matches!(input.data, Data::Struct(_)) {
+        return Err(Error::new_spanned(&input.ident, "#[derive(Component)] solo admite structs"));
+    }
+
+    let name = &input.ident;
+    let engine = quote!(::metaverso_engine);
+    let ecs = quote!(#engine::ecs);
+    let private = quote!(#engine::__private);
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let type_name = name.to_string();
+    let component_type = match type_name.strip_suffix("Component").filter(|prefix| BUILTIN_COMPONENTS.contains(prefix)) {
+        Some(prefix) => {
+            let variant = Ident::new(prefix, Span::call_site());
+            quote!(#ecs::ComponentType::#variant)
+        }
+        None => quote!(#ecs::ComponentType::Custom(::std::string::String::from(#type_name))),
+    };
+
+    // Comprobar los requisitos aparte para que el error señale el struct y
+    // no el código generado
+    let bounds_check = quote! {
+        const _: fn() = || {
+            fn assert_component_bounds<T>()
+            where
+                T: ::core::clone::Clone
+                    + #private::serde::Serialize
+                    + #private::serde::de::DeserializeOwned
+                    + ::core::marker::Send
+                    + ::core::marker::Sync
+                    + 'static,
+            {}
+            fn check #impl_generics () #where_clause {
+                assert_component_bounds::<#name #ty_generics>();
+            }
+        };
+    };
+
+    Ok(quote! {
+        #bounds_check
+
+        impl #impl_generics #ecs::ComponentTypeOf for #name #ty_generics #where_clause {
+            fn static_type() -> #ecs::ComponentType {
+                #component_type
+            }
+        }
+
+        impl #impl_generics #ecs::Component for #name #ty_generics #where_clause {
+            fn get_type(&self) -> #ecs::ComponentType {
+                <Self as #ecs::ComponentTypeOf>::static_type()
+            }
+
+            fn clone_box(&self) -> ::std::boxed::Box<dyn #ecs::Component> {
+                ::std::boxed::Box::new(::core::clone::Clone::clone(self))
+            }
+
+            fn serialize(&self) -> #private::anyhow::Result<::std::vec::Vec<u8>> {
+                Ok(#private::bincode::serialize(self)?)
+            }
+
+            fn deserialize(data: &[u8]) -> #private::anyhow::Result<::std::boxed::Box<dyn #ecs::Component>> {
+                let component: Self = #private::bincode::deserialize(data)?;
+                Ok(::std::boxed::Box::new(component))
+            }
+        }
+    })
+}
@@ -0,0 +1,394 @@
+//! # Blend trees
+//!
+//! Un estado del controlador puede mezclar varios clips según sus
+//! parámetros en lugar de reproducir una sola animación. En un árbol 1D los
+//! clips se colocan sobre un eje (p. ej. la velocidad: andar, trotar,
+//! correr) y el valor del parámetro reparte el peso entre los dos clips que
+//! lo rodean. En un árbol 2D cada clip ocupa un punto del plano de dos
+//! parámetros (p. ej. avance y desplazamiento lateral) y los pesos salen de
+//! la interpolación por bandas de gradiente, en coordenadas cartesianas o
+//! polares (direccional, para clips que difieren sobre todo en dirección).
+//!
+//! Los clips de un árbol se reproducen sincronizados: el estado guarda una
+//! fase normalizada que avanza con la duración media ponderada del ciclo y
+//! cada clip se muestrea en esa fase de su propia duración, así que los
+//! pasos de un ciclo de andar y uno de correr caen a la vez.
+
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use super::sampling::{BonePose, ClipSampler, SkeletonPose};
+use super::{AnimationClip, ClipData, SkeletalData};
+
+/// Distancia mínima entre puntos del árbol para considerarlos distintos
+const MIN_DISTANCE: f32 = 1e-5;
+
+/// Clip de un árbol 1D
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlendChild1D {
+    /// Clip de esqueleto
+    pub clip_id: String,
+    /// Valor del parámetro en el que el clip pesa 1
+    pub threshold: f32,
+}
+
+/// Clip de un árbol 2D
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlendChild2D {
+    /// Clip de esqueleto
+    pub clip_id: String,
+    /// Punto de los dos parámetros en el que el clip pesa 1
+    pub position: [f32; 2],
+}
+
+/// Interpolación de un árbol 2D
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Blend2DMode {
+    /// Bandas de gradiente en coordenadas cartesianas
+    #[default]
+    FreeformCartesian,
+    /// Bandas de gradiente en coordenadas polares (magnitud y ángulo)
+    FreeformDirectional,
+}
+
+/// Árbol de mezcla de un estado
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BlendTree {
+    /// Clips sobre el eje de un parámetro
+    Blend1D {
+        /// Parámetro del controlador
+        parameter: String,
+        /// Clips del árbol
+        children: Vec<BlendChild1D>,
+    },
+    /// Clips sobre el plano de dos parámetros
+    Blend2D {
+        /// Interpolación
+        #[serde(default)]
+        mode: Blend2DMode,
+        /// Parámetros del controlador (eje X y eje Y)
+        parameters: [String; 2],
+        /// Clips del árbol
+        children: Vec<BlendChild2D>,
+    },
+}
+
+impl BlendTree {
+    /// Clips del árbol
+    pub fn clip_ids(&self) -> Vec<&str> {
+        match self {
+            BlendTree::Blend1D { children, .. } => children.iter().map(|child| child.clip_id.as_str()).collect(),
+            BlendTree::Blend2D { children, .. } => children.iter().map(|child| child.clip_id.as_str()).collect(),
+        }
+    }
+
+    /// Clips con peso mayor que cero y su peso, que suman 1
+    pub fn weights(&self, parameters: &HashMap<String, f32>) -> Vec<(&str, f32)> {
+        let value = |name: &str| parameters.get(name).copied().unwrap_or(0.0);
+        let (ids, weights): (Vec<&str>, Vec<f32>) = match self {
+            BlendTree::Blend1D { parameter, children } => {
+                let thresholds: Vec<f32> = children.iter().map(|child| child.threshold).collect();
+                (children.iter().map(|child| child.clip_id.as_str()).collect(), weights_1d(&thresholds, value(parameter)))
+            }
+            BlendTree::Blend2D { mode, parameters, children } => {
+                let points: Vec<Vec2> = children.iter().map(|child| Vec2::from(child.position)).collect();
+                let point = Vec2::new(value(&parameters[0]), value(&parameters[1]));
+                (children.iter().map(|child| child.clip_id.as_str()).collect(), weights_2d(&points, point, *mode))
+            }
+        };
+        ids.into_iter().zip(weights).filter(|(_, weight)| *weight > 0.0).collect()
+    }
+
+    /// Duración del ciclo mezclado: la media de las duraciones de los clips
+    /// ponderada por sus pesos
+    pub fn cycle_duration(&self, parameters: &HashMap<String, f32>, clips: &HashMap<String, AnimationClip>) -> f32 {
+        self.weights(parameters).into_iter()
+            .filter_map(|(clip_id, weight)| clips.get(clip_id).map(|clip| clip.config.duration * weight))
+            .sum()
+    }
+
+    /// Fase normalizada tras avanzar `delta_time` segundos desde `phase`
+    pub fn advance_phase(&self, parameters: &HashMap<String, f32>, clips: &HashMap<String, AnimationClip>, phase: f32, delta_time: f32) -> f32 {
+        let duration = self.cycle_duration(parameters, clips);
+        if duration <= 0.0 {
+            return phase;
+        }
+        (phase + delta_time / duration).rem_euclid(1.0)
+    }
+
+    /// Mezclar en `pose` los clips del árbol en la fase `phase`. Devuelve el
+    /// esqueleto del primer clip con peso (None si no hay ninguno cargado)
+    pub fn sample<'a>(
+        &self,
+        parameters: &HashMap<String, f32>,
+        phase: f32,
+        clips: &'a HashMap<String, AnimationClip>,
+        samplers: &HashMap<String, ClipSampler>,
+        pose: &mut SkeletonPose,
+    ) -> Option<&'a SkeletalData> {
+        let contributors: Vec<_> = self.weights(parameters).into_iter()
+            .filter_map(|(clip_id, weight)| {
+                let (clip, sampler) = (clips.get(clip_id)?, samplers.get(clip_id)?);
+                let ClipData::Skeletal(skeletal) = &clip.data else {
+                    return None;
+                };
+                Some((clip, skeletal, sampler, weight, phase * clip.config.duration))
+            })
+            .collect();
+        let skeletal = contributors.first().map(|(_, skeletal, ..)| *skeletal)?;
+
+        pose.bones.resize(skeletal.bones.len(), BonePose::IDENTITY);
+        for (i, bone) in pose.bones.iter_mut().enumerate() {
+            // Mezcla progresiva: cada clip entra con su parte del peso acumulado
+            let mut total = 0.0;
+            for (index, (clip, skeletal, sampler, weight, time)) in contributors.iter().enumerate() {
+                let sample = sampler.sample_bone(skeletal, &clip.config, i, *time);
+                total += weight;
+                *bone = if index == 0 {
+                    sample
+                } else {
+                    let t = weight / total;
+                    BonePose {
+                        translation: bone.translation.lerp(sample.translation, t),
+                        rotation: bone.rotation.slerp(sample.rotation, t).normalize(),
+                        scale: bone.scale.lerp(sample.scale, t),
+                    }
+                };
+            }
+        }
+        Some(skeletal)
+    }
+}
+
+/// Pesos 1D: los dos umbrales que rodean al valor se reparten el peso; fuera
+/// del rango pesa todo el extremo más cercano
+fn weights_1d(thresholds: &[f32], value: f32) -> Vec<f32> {
+    let mut weights = vec![0.0; thresholds.len()];
+    let mut order: Vec<usize> = (0..thresholds.len()).collect();
+    order.sort_by(|&a, &b| thresholds[a].total_cmp(&thresholds[b]));
+    let (Some(&first), Some(&last)) = (order.first(), order.last()) else {
+        return weights;
+    };
+
+    if value <= thresholds[first] {
+        weights[first] = 1.0;
+    } else if value >= thresholds[last] {
+        weights[last] = 1.0;
+    } else {
+        for pair in order.windows(2) {
+            let (low, high) = (pair[0], pair[1]);
+            if value >= thresholds[low] && value <= thresholds[high] {
+                let span = thresholds[high] - thresholds[low];
+                let t = if span > MIN_DISTANCE { (value - thresholds[low]) / span } else { 0.0 };
+                weights[low] = 1.0 - t;
+                weights[high] = t;
+                break;
+            }
+        }
+    }
+    weights
+}
+
+/// Pesos 2D por bandas de gradiente: el peso de cada punto es el mínimo de
+/// su influencia frente a cada uno de los demás, y después se normalizan
+fn weights_2d(points: &[Vec2], point: Vec2, mode: Blend2DMode) -> Vec<f32> {
+    if points.len() == 1 {
+        return vec![1.0];
+    }
+    let mut weights: Vec<f32> = points.iter().enumerate()
+        .map(|(i, &pi)| {
+            points.iter().enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, &pj)| {
+                    let (to_point, to_other) = match mode {
+                        Blend2DMode::FreeformCartesian => (point - pi, pj - pi),
+                        Blend2DMode::FreeformDirectional => polar_offsets(pi, pj, point),
+                    };
+                    let length_squared = to_other.length_squared();
+                    if length_squared < MIN_DISTANCE * MIN_DISTANCE {
+                        return 1.0;
+                    }
+                    (1.0 - to_point.dot(to_other) / length_squared).clamp(0.0, 1.0)
+                })
+                .fold(1.0_f32, f32::min)
+        })
+        .collect();
+
+    let total: f32 = weights.iter().sum();
+    if total > 0.0 {
+        weights.iter_mut().for_each(|weight| *weight /= total);
+    }
+    weights
+}
+
+/// Desplazamientos de `pi` a `point` y de `pi` a `pj` en el espacio polar:
+/// diferencia de magnitud relativa a la media y ángulo entre direcciones
+fn polar_offsets(pi: Vec2, pj: Vec2, point: Vec2) -> (Vec2, Vec2) {
+    // Peso del ángulo frente a la magnitud
+    const ANGLE_SCALE: f32 = 2.0;
+    let (mi, mj) = (pi.length(), pj.length());
+    let mean = ((mi + mj) * 0.5).max(MIN_DISTANCE);
+    let angle = |a: Vec2, b: Vec2| {
+        if a.length_squared() < MIN_DISTANCE * MIN_DISTANCE || b.length_squared() < MIN_DISTANCE * MIN_DISTANCE {
+            0.0
+        } else {
+            a.perp_dot(b).atan2(a.dot(b))
+        }
+    };
+    (
+        Vec2::new((point.length() - mi) / mean, angle(pi, point) * ANGLE_SCALE),
+        Vec2::new((mj - mi) / mean, angle(pi, pj) * ANGLE_SCALE),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::{
+        Bone, ClipConfig, ClipState, ClipType, EasingConfig, EasingType, FalloffConfig, FalloffType, InfluenceConfig,
+        InterpolationType, KeyframeInterpolation, RotationInterpolation, Transform, TransformKeyframe,
+    };
+    use glam::{Quat, Vec3};
+
+    /// Ciclo de un solo hueso: la pierna va de -`swing` a +`swing` grados a
+    /// mitad del ciclo y vuelve, a la altura `height`
+    fn cycle(id: &str, duration: f32, swing: f32, height: f32) -> AnimationClip {
+        let rest = Transform { position: [0.0; 3], rotation: [0.0, 0.0, 0.0, 1.0], scale: [1.0; 3] };
+        let leg = Bone {
+            id: "leg".to_string(),
+            name: "leg".to_string(),
+            parent_id: None,
+            local_transform: rest.clone(),
+            world_transform: rest,
+            influence_config: InfluenceConfig {
+                influence_radius: 1.0,
+                influence_weight: 1.0,
+                falloff_config: FalloffConfig { falloff_type: FalloffType::Linear, falloff_exponent: 1.0 },
+            },
+        };
+        let keyframes = [(0.0, -swing), (0.5, swing), (1.0, -swing)]
+            .into_iter()
+            .map(|(phase, degrees)| TransformKeyframe {
+                time: phase * duration,
+                bone_id: "leg".to_string(),
+                transform: Transform {
+                    position: [0.0, height, 0.0],
+                    rotation: Quat::from_rotation_x(degrees.to_radians()).to_array(),
+                    scale: [1.0; 3],
+                },
+                interpolation: KeyframeInterpolation {
+                    interpolation_type: InterpolationType::Linear,
+                    tangents: None,
+                    easing: EasingConfig { easing_type: EasingType::None, parameters: [0.0; 4] },
+                },
+            })
+            .collect();
+        AnimationClip {
+            id: id.to_string(),
+            name: id.to_string(),
+            clip_type: ClipType::Skeletal,
+            config: ClipConfig {
+                duration,
+                fps: 30.0,
+                looped: true,
+                compression: None,
+                optimization: None,
+                rotation_interpolation: RotationInterpolation::Slerp,
+            },
+            data: ClipData::Skeletal(SkeletalData {
+                bones: vec![leg],
+                keyframes,
+                constraints: vec![],
+                root_bone_id: None,
+                compressed: None,
+            }),
+            state: ClipState { active: true, loaded: true, compiled: false, load_time: 0.0, raw_size: 0, compressed_size: 0 },
+        }
+    }
+
+    /// Andar (1 s, umbral 1 m/s) y correr (0.6 s, umbral 3 m/s)
+    fn locomotion() -> (BlendTree, HashMap<String, AnimationClip>, HashMap<String, ClipSampler>) {
+        let tree = BlendTree::Blend1D {
+            parameter: "speed".to_string(),
+            children: vec![
+                BlendChild1D { clip_id: "walk".to_string(), threshold: 1.0 },
+                BlendChild1D { clip_id: "run".to_string(), threshold: 3.0 },
+            ],
+        };
+        let clips: HashMap<String, AnimationClip> = [cycle("walk", 1.0, 30.0, 1.0), cycle("run", 0.6, 60.0, 0.9)]
+            .into_iter()
+            .map(|clip| (clip.id.clone(), clip))
+            .collect();
+        let samplers = clips.iter()
+            .map(|(id, clip)| {
+                let ClipData::Skeletal(skeletal) = &clip.data else { unreachable!() };
+                (id.clone(), ClipSampler::new(skeletal))
+            })
+            .collect();
+        (tree, clips, samplers)
+    }
+
+    fn speed(value: f32) -> HashMap<String, f32> {
+        HashMap::from([("speed".to_string(), value)])
+    }
+
+    /// Pose de la pierna de un clip en una fase de su ciclo
+    fn clip_pose(clips: &HashMap<String, AnimationClip>, samplers: &HashMap<String, ClipSampler>, id: &str, phase: f32) -> BonePose {
+        let clip = &clips[id];
+        let ClipData::Skeletal(skeletal) = &clip.data else { unreachable!() };
+        samplers[id].sample_bone(skeletal, &clip.config, 0, phase * clip.config.duration)
+    }
+
+    fn leg_pose(tree: &BlendTree, parameters: &HashMap<String, f32>, phase: f32,
+        clips: &HashMap<String, AnimationClip>, samplers: &HashMap<String, ClipSampler>) -> BonePose {
+        let mut pose = SkeletonPose::default();
+        tree.sample(parameters, phase, clips, samplers, &mut pose).expect("clips cargados");
+        pose.bones[0]
+    }
+
+    #[test]
+    fn speed_on_a_threshold_plays_only_that_clip() {
+        let (tree, clips, samplers) = locomotion();
+        for (value, id) in [(1.0, "walk"), (3.0, "run")] {
+            assert_eq!(tree.weights(&speed(value)), vec![(id, 1.0)]);
+            for phase in [0.0, 0.2, 0.5, 0.9] {
+                assert_eq!(leg_pose(&tree, &speed(value), phase, &clips, &samplers), clip_pose(&clips, &samplers, id, phase));
+            }
+        }
+    }
+
+    #[test]
+    fn midpoint_is_the_even_blend_of_both_clips() {
+        let (tree, clips, samplers) = locomotion();
+        assert_eq!(tree.weights(&speed(2.0)), vec![("walk", 0.5), ("run", 0.5)]);
+
+        // A mitad de ciclo la pierna está a 30° andando y a 60° corriendo
+        let pose = leg_pose(&tree, &speed(2.0), 0.5, &clips, &samplers);
+        let expected = Quat::from_rotation_x(45f32.to_radians());
+        assert!(pose.rotation.angle_between(expected) < 1e-4, "{:?}", pose.rotation);
+        assert!(pose.translation.abs_diff_eq(Vec3::new(0.0, 0.95, 0.0), 1e-5), "{:?}", pose.translation);
+    }
+
+    #[test]
+    fn blended_clips_stay_in_phase() {
+        let (tree, clips, samplers) = locomotion();
+        let parameters = speed(2.0);
+
+        // El ciclo mezclado dura la media de 1 s y 0.6 s
+        assert!((tree.cycle_duration(&parameters, &clips) - 0.8).abs() < 1e-6);
+        let mut phase = 0.0;
+        for _ in 0..10 {
+            phase = tree.advance_phase(&parameters, &clips, phase, 0.02);
+        }
+        assert!((phase - 0.25).abs() < 1e-5, "fase {}", phase);
+
+        // Cada clip se muestrea en la misma fase de su propio ciclo: a un
+        // cuarto los dos pasan por la vertical a la vez
+        let (walk, run) = (clip_pose(&clips, &samplers, "walk", phase), clip_pose(&clips, &samplers, "run", phase));
+        assert!(walk.rotation.angle_between(Quat::IDENTITY) < 1e-3);
+        assert!(run.rotation.angle_between(Quat::IDENTITY) < 1e-3);
+        let pose = leg_pose(&tree, &parameters, phase, &clips, &samplers);
+        assert!(pose.rotation.angle_between(walk.rotation.slerp(run.rotation, 0.5)) < 1e-4);
+    }
+}
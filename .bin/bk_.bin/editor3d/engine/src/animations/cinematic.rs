@@ -0,0 +1,396 @@
+//! # Cinemáticas
+//!
+//! Una cinemática es una línea de tiempo que reproduce clips de cámara y de
+//! luz sobre entidades. En cada frame se muestrean sus keyframes con la
+//! interpolación y el easing de cada keyframe (como los de esqueleto):
+//! transformación, FOV y planos de la cámara; transformación, color,
+//! intensidad y rango de la luz. El motor copia el resultado a los
+//! `CameraComponent` y `LightComponent` de las entidades.
+//!
+//! La línea de tiempo se controla con play, pausa, seek y velocidad. Un
+//! seek coloca el tiempo sin recorrer el tramo intermedio. Al llegar al
+//! final sin bucle la cinemática se detiene en el último frame y emite
+//! `CinematicFinished` una sola vez.
+
+use glam::{Quat, Vec3};
+use serde::{Serialize, Deserialize};
+
+use super::morphing::ease;
+use super::sampling::hermite;
+use super::{CameraData, InterpolationType, KeyframeInterpolation, LightData, Transform};
+use crate::ecs::EntityId;
+
+/// Pista de una cinemática: un clip de cámara o de luz sobre una entidad
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CinematicTrack {
+    /// Clip de cámara o de luz
+    pub clip_id: String,
+    /// Entidad animada
+    pub entity_id: EntityId,
+}
+
+/// Estado de reproducción de una línea de tiempo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineState {
+    /// Tiempo actual
+    pub time: f32,
+    /// Velocidad de reproducción (negativa hacia atrás)
+    pub speed: f32,
+    /// Reproduciendo
+    pub playing: bool,
+    /// Se muestrea en cada frame (también en pausa)
+    pub active: bool,
+}
+
+impl Default for TimelineState {
+    fn default() -> Self {
+        Self { time: 0.0, speed: 1.0, playing: false, active: false }
+    }
+}
+
+/// Cinemática
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cinematic {
+    /// ID de la cinemática
+    pub id: String,
+    /// Duración (0 para tomarla del último keyframe de sus clips)
+    pub duration: f32,
+    /// Vuelve a empezar al terminar
+    pub looped: bool,
+    /// Pistas
+    pub tracks: Vec<CinematicTrack>,
+    /// Estado de reproducción
+    #[serde(default)]
+    pub state: TimelineState,
+}
+
+impl Cinematic {
+    /// Avanzar la línea de tiempo. Devuelve true si terminó en este paso
+    pub fn advance(&mut self, delta_time: f32) -> bool {
+        let state = &mut self.state;
+        if !state.playing {
+            return false;
+        }
+        state.time += delta_time * state.speed;
+        if self.looped {
+            if self.duration > 0.0 {
+                state.time = state.time.rem_euclid(self.duration);
+            }
+            return false;
+        }
+        let finished = (state.speed >= 0.0 && state.time >= self.duration) || (state.speed < 0.0 && state.time <= 0.0);
+        if finished {
+            state.time = state.time.clamp(0.0, self.duration);
+            state.playing = false;
+        }
+        finished
+    }
+
+    /// Colocar el tiempo sin recorrer el tramo intermedio
+    pub fn seek(&mut self, time: f32) {
+        self.state.time = if self.looped && self.duration > 0.0 {
+            time.rem_euclid(self.duration)
+        } else {
+            time.clamp(0.0, self.duration)
+        };
+    }
+}
+
+/// Evento emitido al terminar una cinemática sin bucle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CinematicFinished {
+    /// ID de la cinemática
+    pub cinematic_id: String,
+}
+
+/// Muestra de una pista de cámara
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraSample {
+    /// Posición
+    pub position: Vec3,
+    /// Rotación
+    pub rotation: Quat,
+    /// Campo de visión
+    pub fov: f32,
+    /// Plano cercano
+    pub near_plane: f32,
+    /// Plano lejano
+    pub far_plane: f32,
+}
+
+/// Muestra de una pista de luz
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSample {
+    /// Posición
+    pub position: Vec3,
+    /// Rotación
+    pub rotation: Quat,
+    /// Color
+    pub color: Vec3,
+    /// Intensidad
+    pub intensity: f32,
+    /// Rango
+    pub range: f32,
+}
+
+/// Tramo de keyframes que rodea a un tiempo
+struct Segment {
+    before: usize,
+    from: usize,
+    to: usize,
+    after: usize,
+    /// Duración del tramo
+    span: f32,
+    /// Avance en el tramo sin easing
+    t: f32,
+}
+
+impl Segment {
+    /// Tramo de `time` en keyframes ordenados por tiempo (None sin keyframes)
+    fn find(times: &[f32], time: f32) -> Option<Self> {
+        let last = times.len().checked_sub(1)?;
+        let next = times.partition_point(|&key| key <= time);
+        if next == 0 || next > last {
+            let index = if next == 0 { 0 } else { last };
+            return Some(Self { before: index, from: index, to: index, after: index, span: 0.0, t: 0.0 });
+        }
+        let (from, to) = (next - 1, next);
+        let span = times[to] - times[from];
+        let t = if span > f32::EPSILON { (time - times[from]) / span } else { 1.0 };
+        Some(Self { before: from.saturating_sub(1), from, to, after: (to + 1).min(last), span, t })
+    }
+}
+
+/// Valores de un canal interpolados en un tramo. `slopes` son las
+/// pendientes por segundo de salida del primer keyframe y de entrada del
+/// segundo, para `Bezier`
+fn interpolate(value: impl Fn(usize) -> Vec3, segment: &Segment, interpolation: &KeyframeInterpolation, slopes: (Vec3, Vec3)) -> Vec3 {
+    let (from, to) = (value(segment.from), value(segment.to));
+    let t = ease(segment.t, &interpolation.easing);
+    match &interpolation.interpolation_type {
+        InterpolationType::Step => from,
+        InterpolationType::Linear | InterpolationType::Custom(_) => from.lerp(to, t),
+        InterpolationType::Smooth => from.lerp(to, t * t * (3.0 - 2.0 * t)),
+        InterpolationType::Bezier => hermite(from, slopes.0 * segment.span, to, slopes.1 * segment.span, t),
+        InterpolationType::CatmullRom => hermite(
+            from,
+            (to - value(segment.before)) * 0.5,
+            to,
+            (value(segment.after) - from) * 0.5,
+            t,
+        ),
+    }
+}
+
+/// Rotación interpolada en un tramo
+fn interpolate_rotation(rotation: impl Fn(usize) -> Quat, segment: &Segment, interpolation: &KeyframeInterpolation) -> Quat {
+    let t = ease(segment.t, &interpolation.easing);
+    let t = match &interpolation.interpolation_type {
+        InterpolationType::Step => 0.0,
+        InterpolationType::Smooth => t * t * (3.0 - 2.0 * t),
+        _ => t,
+    };
+    rotation(segment.from).slerp(rotation(segment.to), t).normalize()
+}
+
+/// Pendientes de las tangentes de posición de un tramo (planas sin tangentes)
+fn position_slopes(out_key: &KeyframeInterpolation, in_key: &KeyframeInterpolation) -> (Vec3, Vec3) {
+    (
+        out_key.tangents.as_ref().map_or(Vec3::ZERO, |tangents| Vec3::from(tangents.out_tangent)),
+        in_key.tangents.as_ref().map_or(Vec3::ZERO, |tangents| Vec3::from(tangents.in_tangent)),
+    )
+}
+
+fn rotation(transform: &Transform) -> Quat {
+    Quat::from_array(transform.rotation).normalize()
+}
+
+/// Muestrear un clip de cámara en `time`
+pub fn sample_camera(data: &CameraData, time: f32) -> Option<CameraSample> {
+    let keys = &data.keyframes;
+    let times: Vec<f32> = keys.iter().map(|key| key.time).collect();
+    let segment = Segment::find(&times, time)?;
+    let (from, to) = (&keys[segment.from], &keys[segment.to]);
+    let interpolation = &from.interpolation;
+
+    let position = interpolate(|i| Vec3::from(keys[i].transform.position), &segment, interpolation,
+        position_slopes(interpolation, &to.interpolation));
+    let lens = interpolate(
+        |i| Vec3::new(keys[i].camera_config.fov, keys[i].camera_config.near_plane, keys[i].camera_config.far_plane),
+        &segment,
+        interpolation,
+        (Vec3::ZERO, Vec3::ZERO),
+    );
+    Some(CameraSample {
+        position,
+        rotation: interpolate_rotation(|i| rotation(&keys[i].transform), &segment, interpolation),
+        fov: lens.x,
+        near_plane: lens.y,
+        far_plane: lens.z,
+    })
+}
+
+/// Muestrear un clip de luz en `time`
+pub fn sample_light(data: &LightData, time: f32) -> Option<LightSample> {
+    let keys = &data.keyframes;
+    let times: Vec<f32> = keys.iter().map(|key| key.time).collect();
+    let segment = Segment::find(&times, time)?;
+    let (from, to) = (&keys[segment.from], &keys[segment.to]);
+    let interpolation = &from.interpolation;
+
+    let position = interpolate(|i| Vec3::from(keys[i].transform.position), &segment, interpolation,
+        position_slopes(interpolation, &to.interpolation));
+    let color = interpolate(|i| Vec3::from(keys[i].light_config.color), &segment, interpolation, (Vec3::ZERO, Vec3::ZERO));
+    let power = interpolate(
+        |i| Vec3::new(keys[i].light_config.intensity, keys[i].light_config.range, 0.0),
+        &segment,
+        interpolation,
+        (Vec3::ZERO, Vec3::ZERO),
+    );
+    Some(LightSample {
+        position,
+        rotation: interpolate_rotation(|i| rotation(&keys[i].transform), &segment, interpolation),
+        color: color.max(Vec3::ZERO),
+        intensity: power.x.max(0.0),
+        range: power.y.max(0.0),
+    })
+}
+
+/// Último tiempo de keyframe de un clip de cámara
+pub fn camera_duration(data: &CameraData) -> f32 {
+    data.keyframes.iter().map(|key| key.time).fold(0.0, f32::max)
+}
+
+/// Último tiempo de keyframe de un clip de luz
+pub fn light_duration(data: &LightData) -> f32 {
+    data.keyframes.iter().map(|key| key.time).fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::{
+        AnimationClip, AnimationSystem, CameraConfig, CameraKeyframe, ClipConfig, ClipData, ClipState, ClipType,
+        EasingConfig, EasingType,
+    };
+
+    const CAMERA: EntityId = 7;
+
+    fn lens(fov: f32) -> CameraConfig {
+        CameraConfig { fov, aspect_ratio: 16.0 / 9.0, near_plane: 0.1, far_plane: 1000.0 }
+    }
+
+    /// Plano de 2 s: el FOV abre de 60° a 90° y cierra a 40° mientras la
+    /// cámara avanza 10 m por segundo
+    fn dolly() -> CameraData {
+        let keyframes = [(0.0, 60.0), (1.0, 90.0), (2.0, 40.0)]
+            .into_iter()
+            .map(|(time, fov)| CameraKeyframe {
+                time,
+                transform: Transform { position: [10.0 * time, 2.0, 0.0], rotation: [0.0, 0.0, 0.0, 1.0], scale: [1.0; 3] },
+                camera_config: lens(fov),
+                interpolation: KeyframeInterpolation {
+                    interpolation_type: InterpolationType::Linear,
+                    tangents: None,
+                    easing: EasingConfig { easing_type: EasingType::None, parameters: [0.0; 4] },
+                },
+            })
+            .collect();
+        CameraData { keyframes, camera_config: lens(60.0) }
+    }
+
+    async fn cinematic_system(looped: bool) -> AnimationSystem {
+        let mut system = AnimationSystem::new();
+        system.initialize().await.unwrap();
+        system.create_clip(AnimationClip {
+            id: "dolly".to_string(),
+            name: "Dolly".to_string(),
+            clip_type: ClipType::Camera,
+            config: ClipConfig {
+                duration: 2.0,
+                fps: 30.0,
+                looped: false,
+                compression: None,
+                optimization: None,
+                rotation_interpolation: Default::default(),
+            },
+            data: ClipData::Camera(dolly()),
+            state: ClipState { active: true, loaded: true, compiled: false, load_time: 0.0, raw_size: 0, compressed_size: 0 },
+        }).await.unwrap();
+        system.create_cinematic(Cinematic {
+            id: "intro".to_string(),
+            duration: 0.0,
+            looped,
+            tracks: vec![CinematicTrack { clip_id: "dolly".to_string(), entity_id: CAMERA }],
+            state: TimelineState::default(),
+        });
+        system
+    }
+
+    fn fov(system: &AnimationSystem) -> f32 {
+        system.camera_samples()[&CAMERA].fov
+    }
+
+    #[test]
+    fn fov_is_interpolated_between_three_keyframes() {
+        let dolly = dolly();
+        for (time, expected) in [(-1.0, 60.0), (0.0, 60.0), (0.5, 75.0), (1.0, 90.0), (1.5, 65.0), (2.0, 40.0), (3.0, 40.0)] {
+            let sample = sample_camera(&dolly, time).unwrap();
+            assert!((sample.fov - expected).abs() < 1e-4, "t={}: fov {}", time, sample.fov);
+            assert!((sample.position.x - 10.0 * time.clamp(0.0, 2.0)).abs() < 1e-4);
+        }
+        assert_eq!(camera_duration(&dolly), 2.0);
+    }
+
+    #[tokio::test]
+    async fn seek_jumps_to_the_new_time() {
+        let mut system = cinematic_system(false).await;
+        assert!(system.play_cinematic("intro"));
+        system.update(0.25).await.unwrap();
+        assert!((fov(&system) - 67.5).abs() < 1e-3);
+
+        // Hacia delante y hacia atrás sin pasar por el tramo intermedio
+        for (time, expected) in [(1.5, 65.0), (0.5, 75.0), (1.0, 90.0)] {
+            assert!(system.seek_cinematic("intro", time));
+            system.update(0.0).await.unwrap();
+            assert!((fov(&system) - expected).abs() < 1e-3, "seek a {}: fov {}", time, fov(&system));
+        }
+        assert!(system.drain_finished_cinematics().is_empty());
+
+        // Sigue reproduciendo desde donde saltó
+        system.update(0.5).await.unwrap();
+        assert!((fov(&system) - 65.0).abs() < 1e-3);
+        assert!(system.get_cinematic("intro").unwrap().state.playing);
+
+        // Fuera de rango se recorta a la duración
+        system.seek_cinematic("intro", 10.0);
+        assert_eq!(system.get_cinematic("intro").unwrap().state.time, 2.0);
+    }
+
+    #[tokio::test]
+    async fn finished_event_fires_once_at_the_end() {
+        let mut system = cinematic_system(false).await;
+        system.play_cinematic("intro");
+
+        let mut finished = Vec::new();
+        for _ in 0..12 {
+            system.update(0.3).await.unwrap();
+            finished.extend(system.drain_finished_cinematics());
+        }
+        assert_eq!(finished, vec![CinematicFinished { cinematic_id: "intro".to_string() }]);
+        let cinematic = system.get_cinematic("intro").unwrap();
+        assert!(!cinematic.state.playing);
+        assert_eq!(cinematic.state.time, 2.0);
+        // Se queda en el último frame
+        assert!((fov(&system) - 40.0).abs() < 1e-4);
+
+        // Con bucle nunca termina
+        let mut looped = cinematic_system(true).await;
+        looped.play_cinematic("intro");
+        for _ in 0..12 {
+            looped.update(0.3).await.unwrap();
+        }
+        assert!(looped.drain_finished_cinematics().is_empty());
+        assert!(looped.get_cinematic("intro").unwrap().state.playing);
+    }
+}
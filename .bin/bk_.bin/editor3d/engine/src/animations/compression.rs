@@ -0,0 +1,475 @@
+//! # Compresión de clips de esqueleto
+//!
+//! Los clips con `CompressionConfig` (o con `keyframe_reduction` en su
+//! `OptimizationConfig`) se comprimen al registrarse: cada canal de cada
+//! hueso (traslación, rotación y escala) se queda solo con los keyframes
+//! que no se pueden reconstruir desde sus vecinos dentro de la precisión
+//! pedida, y las rotaciones se guardan en 48 bits con la codificación
+//! "smallest three" (las tres componentes menores en 15 bits y el índice de
+//! la mayor en los bits sobrantes).
+//!
+//! La reducción parte de los keyframes extremos y va añadiendo el de mayor
+//! error hasta que todos los keyframes originales se reconstruyen dentro de
+//! la precisión, medida con las rotaciones ya cuantizadas: distancia para
+//! traslación y escala y ángulo en radianes para la rotación. `Linear`
+//! reconstruye con interpolación lineal y `Spline` con Catmull-Rom (las
+//! rotaciones siempre con slerp).
+//!
+//! El resultado se guarda en `SkeletalData::compressed` y sustituye a los
+//! keyframes; `ClipSampler` lo descomprime al muestrear sin que el resto del
+//! sistema lo note. `ClipState` guarda el tamaño antes y después.
+
+use glam::{Quat, Vec3};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::f32::consts::FRAC_1_SQRT_2;
+use tracing::warn;
+
+use super::sampling::{hermite, BonePose};
+use super::{AnimationClip, ClipConfig, ClipData, CompressionType, SkeletalData, TransformKeyframe};
+
+/// Bytes de un keyframe sin comprimir: tiempo, posición, rotación y escala
+pub const RAW_KEYFRAME_SIZE: usize = 4 * (1 + 3 + 4 + 3);
+
+/// Bytes de un keyframe comprimido de traslación o escala
+const VEC3_KEY_SIZE: usize = 4 * (1 + 3);
+
+/// Bytes de un keyframe comprimido de rotación
+const ROTATION_KEY_SIZE: usize = 4 + 6;
+
+/// Pasos de cada componente cuantizada (15 bits)
+const QUANTIZATION_STEPS: f32 = 32767.0;
+
+/// Tramo mínimo entre keyframes
+const MIN_SPAN: f32 = 1e-6;
+
+/// Interpolación con la que se reconstruyen los canales comprimidos
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CurveInterpolation {
+    /// Lineal entre keyframes
+    Linear,
+    /// Catmull-Rom con las pendientes de los vecinos
+    CatmullRom,
+}
+
+/// Rotación en 48 bits: tres componentes de 15 bits y el índice de la
+/// componente omitida en el bit bajo de las dos primeras palabras
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackedQuat(pub [u16; 3]);
+
+impl PackedQuat {
+    /// Cuantizar una rotación
+    pub fn pack(rotation: Quat) -> Self {
+        let mut components = rotation.normalize().to_array();
+        let largest = (0..4)
+            .max_by(|&a, &b| components[a].abs().total_cmp(&components[b].abs()))
+            .unwrap_or(3);
+        // q y -q son la misma rotación: la omitida siempre es positiva
+        if components[largest] < 0.0 {
+            components.iter_mut().for_each(|component| *component = -*component);
+        }
+        let mut words = [0u16; 3];
+        for (word, component) in words.iter_mut().zip((0..4).filter(|&i| i != largest).map(|i| components[i])) {
+            let normalized = ((component + FRAC_1_SQRT_2) / (2.0 * FRAC_1_SQRT_2)).clamp(0.0, 1.0);
+            *word = ((normalized * QUANTIZATION_STEPS).round() as u16) << 1;
+        }
+        words[0] |= (largest & 1) as u16;
+        words[1] |= (largest >> 1) as u16;
+        Self(words)
+    }
+
+    /// Recuperar la rotación
+    pub fn unpack(self) -> Quat {
+        let largest = (self.0[0] & 1) as usize | (((self.0[1] & 1) as usize) << 1);
+        let mut stored = self.0.iter().map(|word| {
+            (word >> 1) as f32 / QUANTIZATION_STEPS * 2.0 * FRAC_1_SQRT_2 - FRAC_1_SQRT_2
+        });
+        let mut components = [0.0f32; 4];
+        for (i, component) in components.iter_mut().enumerate() {
+            if i != largest {
+                *component = stored.next().unwrap_or(0.0);
+            }
+        }
+        let rest: f32 = components.iter().map(|component| component * component).sum();
+        components[largest] = (1.0 - rest).max(0.0).sqrt();
+        Quat::from_array(components).normalize()
+    }
+}
+
+/// Keyframes conservados de un canal
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompressedChannel<T> {
+    /// Tiempos, ordenados
+    pub times: Vec<f32>,
+    /// Valor por tiempo
+    pub values: Vec<T>,
+}
+
+impl<T> CompressedChannel<T> {
+    fn len(&self) -> usize {
+        self.times.len()
+    }
+}
+
+/// Canales comprimidos de un hueso
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompressedTrack {
+    /// Traslación
+    pub translation: CompressedChannel<[f32; 3]>,
+    /// Rotación cuantizada
+    pub rotation: CompressedChannel<PackedQuat>,
+    /// Escala
+    pub scale: CompressedChannel<[f32; 3]>,
+}
+
+/// Keyframes comprimidos de un clip de esqueleto
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressedClip {
+    /// Interpolación de reconstrucción
+    pub interpolation: CurveInterpolation,
+    /// Error máximo permitido al reducir
+    pub precision: f32,
+    /// Pistas en el orden de `SkeletalData::bones` (vacías sin keyframes)
+    pub tracks: Vec<CompressedTrack>,
+}
+
+impl CompressedClip {
+    /// Pose de un hueso en `time`; None si el hueso no tiene keyframes
+    pub fn sample_bone(&self, bone: usize, time: f32) -> Option<BonePose> {
+        let track = self.tracks.get(bone)?;
+        if track.rotation.times.is_empty() {
+            return None;
+        }
+        let translation = evaluate_vec3(&track.translation, track.translation.len(), |i| i, time, self.interpolation);
+        let scale = evaluate_vec3(&track.scale, track.scale.len(), |i| i, time, self.interpolation);
+        let rotation = evaluate_rotation(&track.rotation.times, track.rotation.len(), |i| i, |i| track.rotation.values[i].unpack(), time);
+        Some(BonePose { translation, rotation, scale })
+    }
+
+    /// Tamaño en bytes de los keyframes conservados
+    pub fn size_bytes(&self) -> usize {
+        self.tracks.iter()
+            .map(|track| {
+                (track.translation.len() + track.scale.len()) * VEC3_KEY_SIZE + track.rotation.len() * ROTATION_KEY_SIZE
+            })
+            .sum()
+    }
+
+    /// Keyframes conservados en todos los canales
+    pub fn key_count(&self) -> usize {
+        self.tracks.iter()
+            .map(|track| track.translation.len() + track.rotation.len() + track.scale.len())
+            .sum()
+    }
+}
+
+/// Interpolación y precisión con las que se comprime un clip, o None si
+/// su configuración no pide compresión ni reducción de keyframes
+pub fn compression_settings(config: &ClipConfig) -> Option<(CurveInterpolation, f32)> {
+    if let Some(compression) = &config.compression {
+        let interpolation = match &compression.compression_type {
+            CompressionType::None => return None,
+            CompressionType::Linear => CurveInterpolation::Linear,
+            CompressionType::Spline => CurveInterpolation::CatmullRom,
+            other => {
+                warn!("Compresión {:?} no soportada, se usa la lineal", other);
+                CurveInterpolation::Linear
+            }
+        };
+        return Some((interpolation, compression.precision));
+    }
+    config.optimization.as_ref()
+        .filter(|optimization| optimization.keyframe_reduction)
+        .map(|optimization| (CurveInterpolation::Linear, optimization.reduction_threshold))
+}
+
+/// Comprimir los keyframes de un clip de esqueleto según su configuración.
+/// Los keyframes originales se sustituyen por la versión comprimida y el
+/// estado del clip guarda los tamaños. Devuelve false si no había nada que
+/// comprimir
+pub fn compress_clip(clip: &mut AnimationClip) -> bool {
+    let Some((interpolation, precision)) = compression_settings(&clip.config) else {
+        return false;
+    };
+    let ClipData::Skeletal(skeletal) = &mut clip.data else {
+        return false;
+    };
+    if skeletal.compressed.is_some() || skeletal.keyframes.is_empty() {
+        return false;
+    }
+    let compressed = compress_skeletal(skeletal, interpolation, precision);
+    clip.state.raw_size = skeletal.keyframes.len() * RAW_KEYFRAME_SIZE;
+    clip.state.compressed_size = compressed.size_bytes();
+    skeletal.keyframes = Vec::new();
+    skeletal.compressed = Some(compressed);
+    true
+}
+
+/// Comprimir los keyframes de un esqueleto. Los de huesos desconocidos se
+/// descartan, como al muestrear
+pub fn compress_skeletal(skeletal: &SkeletalData, interpolation: CurveInterpolation, precision: f32) -> CompressedClip {
+    let index: HashMap<&str, usize> = skeletal.bones.iter()
+        .enumerate()
+        .map(|(i, bone)| (bone.id.as_str(), i))
+        .collect();
+    let mut keyframes: Vec<Vec<&TransformKeyframe>> = vec![Vec::new(); skeletal.bones.len()];
+    for keyframe in &skeletal.keyframes {
+        if let Some(&bone) = index.get(keyframe.bone_id.as_str()) {
+            keyframes[bone].push(keyframe);
+        }
+    }
+
+    let tracks = keyframes.iter_mut()
+        .map(|track| {
+            track.sort_by(|a, b| a.time.total_cmp(&b.time));
+            compress_track(track, interpolation, precision)
+        })
+        .collect();
+    CompressedClip { interpolation, precision, tracks }
+}
+
+/// Reducir los tres canales de un hueso
+fn compress_track(keyframes: &[&TransformKeyframe], interpolation: CurveInterpolation, precision: f32) -> CompressedTrack {
+    let times: Vec<f32> = keyframes.iter().map(|keyframe| keyframe.time).collect();
+    let translations: Vec<Vec3> = keyframes.iter().map(|keyframe| Vec3::from(keyframe.transform.position)).collect();
+    let scales: Vec<Vec3> = keyframes.iter().map(|keyframe| Vec3::from(keyframe.transform.scale)).collect();
+    let rotations: Vec<Quat> = keyframes.iter().map(|keyframe| Quat::from_array(keyframe.transform.rotation).normalize()).collect();
+    let packed: Vec<PackedQuat> = rotations.iter().map(|&rotation| PackedQuat::pack(rotation)).collect();
+    let quantized: Vec<Quat> = packed.iter().map(|rotation| rotation.unpack()).collect();
+
+    let reduce_vec3 = |values: &[Vec3]| {
+        let channel = CompressedChannel { times: times.clone(), values: values.iter().map(|value| value.to_array()).collect() };
+        let kept = reduce(times.len(), precision, |kept, sample| {
+            evaluate_vec3(&channel, kept.len(), |i| kept[i], times[sample], interpolation).distance(values[sample])
+        });
+        CompressedChannel {
+            times: kept.iter().map(|&i| times[i]).collect(),
+            values: kept.iter().map(|&i| channel.values[i]).collect(),
+        }
+    };
+    let translation = reduce_vec3(&translations);
+    let scale = reduce_vec3(&scales);
+
+    let kept = reduce(times.len(), precision, |kept, sample| {
+        let reconstructed = evaluate_rotation(&times, kept.len(), |i| kept[i], |i| quantized[i], times[sample]);
+        reconstructed.angle_between(rotations[sample])
+    });
+    let rotation = CompressedChannel {
+        times: kept.iter().map(|&i| times[i]).collect(),
+        values: kept.iter().map(|&i| packed[i]).collect(),
+    };
+
+    CompressedTrack { translation, rotation, scale }
+}
+
+/// Índices de los keyframes que se conservan: empieza por los extremos y
+/// añade el de mayor error (`error(conservados, keyframe)`) mientras supere
+/// la precisión
+fn reduce(len: usize, precision: f32, error: impl Fn(&[usize], usize) -> f32) -> Vec<usize> {
+    let mut kept: Vec<usize> = if len <= 2 { (0..len).collect() } else { vec![0, len - 1] };
+    loop {
+        let worst = (0..len)
+            .filter(|sample| kept.binary_search(sample).is_err())
+            .map(|sample| (sample, error(&kept, sample)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match worst {
+            Some((sample, error)) if error > precision => {
+                if let Err(position) = kept.binary_search(&sample) {
+                    kept.insert(position, sample);
+                }
+            }
+            _ => return kept,
+        }
+    }
+}
+
+/// Tramo de `time` en una curva de `len` keyframes, donde `key(i)` es el
+/// índice del keyframe i en `times`
+fn segment(times: &[f32], len: usize, key: &impl Fn(usize) -> usize, time: f32) -> usize {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let middle = (low + high) / 2;
+        if times[key(middle)] <= time {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+/// Valor en `time` de un canal de vectores formado por los keyframes
+/// `key(0..len)`
+fn evaluate_vec3(
+    channel: &CompressedChannel<[f32; 3]>,
+    len: usize,
+    key: impl Fn(usize) -> usize,
+    time: f32,
+    interpolation: CurveInterpolation,
+) -> Vec3 {
+    let (times, value) = (&channel.times, |i: usize| Vec3::from(channel.values[i]));
+    if len == 0 {
+        return Vec3::ZERO;
+    }
+    let next = segment(times, len, &key, time);
+    if next == 0 {
+        return value(key(0));
+    }
+    if next == len {
+        return value(key(len - 1));
+    }
+    let (a, b) = (key(next - 1), key(next));
+    let span = times[b] - times[a];
+    if span <= MIN_SPAN {
+        return value(b);
+    }
+    let t = (time - times[a]) / span;
+    match interpolation {
+        CurveInterpolation::Linear => value(a).lerp(value(b), t),
+        CurveInterpolation::CatmullRom => {
+            let (before, after) = (key(next.saturating_sub(2)), key((next + 1).min(len - 1)));
+            // Pendientes por segundo de las diferencias centradas
+            let slope = |from: usize, to: usize| {
+                let dt = times[to] - times[from];
+                if dt > MIN_SPAN { (value(to) - value(from)) / dt } else { Vec3::ZERO }
+            };
+            hermite(value(a), slope(before, b) * span, value(b), slope(a, after) * span, t)
+        }
+    }
+}
+
+/// Rotación en `time` de un canal formado por los keyframes `key(0..len)`
+fn evaluate_rotation(
+    times: &[f32],
+    len: usize,
+    key: impl Fn(usize) -> usize,
+    value: impl Fn(usize) -> Quat,
+    time: f32,
+) -> Quat {
+    if len == 0 {
+        return Quat::IDENTITY;
+    }
+    let next = segment(times, len, &key, time);
+    if next == 0 {
+        return value(key(0));
+    }
+    if next == len {
+        return value(key(len - 1));
+    }
+    let (a, b) = (key(next - 1), key(next));
+    let span = times[b] - times[a];
+    if span <= MIN_SPAN {
+        return value(b);
+    }
+    value(a).slerp(value(b), (time - times[a]) / span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::{
+        Bone, ClipState, ClipType, CompressionConfig, EasingConfig, EasingType, FalloffConfig, FalloffType,
+        InfluenceConfig, InterpolationType, KeyframeInterpolation, RotationInterpolation, Transform,
+    };
+    use std::f32::consts::TAU;
+
+    const FPS: f32 = 120.0;
+    const DURATION: f32 = 2.0;
+    const PRECISION: f32 = 0.002;
+
+    /// Brazo que oscila a 0.5 Hz: sube y baja 0.3 m y gira 0.3 rad en Z,
+    /// muestreado a 120 FPS como una captura de movimiento
+    fn sine_clip(compression_type: CompressionType) -> AnimationClip {
+        let rest = Transform { position: [0.0; 3], rotation: [0.0, 0.0, 0.0, 1.0], scale: [1.0; 3] };
+        let arm = Bone {
+            id: "arm".to_string(),
+            name: "arm".to_string(),
+            parent_id: None,
+            local_transform: rest.clone(),
+            world_transform: rest,
+            influence_config: InfluenceConfig {
+                influence_radius: 1.0,
+                influence_weight: 1.0,
+                falloff_config: FalloffConfig { falloff_type: FalloffType::Linear, falloff_exponent: 1.0 },
+            },
+        };
+        let keyframes = (0..=(DURATION * FPS) as usize)
+            .map(|frame| {
+                let time = frame as f32 / FPS;
+                let wave = (TAU * 0.5 * time).sin() * 0.3;
+                TransformKeyframe {
+                    time,
+                    bone_id: "arm".to_string(),
+                    transform: Transform {
+                        position: [0.0, wave, 0.0],
+                        rotation: Quat::from_rotation_z(wave).to_array(),
+                        scale: [1.0; 3],
+                    },
+                    interpolation: KeyframeInterpolation {
+                        interpolation_type: InterpolationType::Linear,
+                        tangents: None,
+                        easing: EasingConfig { easing_type: EasingType::None, parameters: [0.0; 4] },
+                    },
+                }
+            })
+            .collect();
+        AnimationClip {
+            id: "wave".to_string(),
+            name: "Wave".to_string(),
+            clip_type: ClipType::Skeletal,
+            config: ClipConfig {
+                duration: DURATION,
+                fps: FPS,
+                looped: true,
+                compression: Some(CompressionConfig { compression_type, compression_factor: 1.0, precision: PRECISION }),
+                optimization: None,
+                rotation_interpolation: RotationInterpolation::Slerp,
+            },
+            data: ClipData::Skeletal(SkeletalData {
+                bones: vec![arm],
+                keyframes,
+                constraints: vec![],
+                root_bone_id: Some("arm".to_string()),
+                compressed: None,
+            }),
+            state: ClipState { active: true, loaded: true, compiled: false, load_time: 0.0, raw_size: 0, compressed_size: 0 },
+        }
+    }
+
+    #[test]
+    fn dense_sine_clip_loses_most_keys_within_precision() {
+        for compression_type in [CompressionType::Linear, CompressionType::Spline] {
+            let mut clip = sine_clip(compression_type.clone());
+            let ClipData::Skeletal(original) = clip.data.clone() else { unreachable!() };
+            assert!(compress_clip(&mut clip));
+
+            let ClipData::Skeletal(skeletal) = &clip.data else { unreachable!() };
+            assert!(skeletal.keyframes.is_empty());
+            let compressed = skeletal.compressed.as_ref().expect("clip comprimido");
+
+            // Cada keyframe original tiene tres canales
+            let raw_keys = original.keyframes.len() * 3;
+            let removed = 1.0 - compressed.key_count() as f32 / raw_keys as f32;
+            assert!(removed >= 0.7, "{:?}: solo se quitó el {:.0}% de las claves", compression_type, removed * 100.0);
+            assert_eq!(clip.state.raw_size, original.keyframes.len() * RAW_KEYFRAME_SIZE);
+            assert_eq!(clip.state.compressed_size, compressed.size_bytes());
+            assert!(clip.state.compressed_size < clip.state.raw_size);
+
+            for keyframe in &original.keyframes {
+                let pose = compressed.sample_bone(0, keyframe.time).expect("pista del brazo");
+                let translation_error = pose.translation.distance(Vec3::from(keyframe.transform.position));
+                let rotation_error = pose.rotation.angle_between(Quat::from_array(keyframe.transform.rotation));
+                assert!(translation_error <= PRECISION + 1e-6, "{:?} t={}: {} m", compression_type, keyframe.time, translation_error);
+                assert!(rotation_error <= PRECISION + 1e-6, "{:?} t={}: {} rad", compression_type, keyframe.time, rotation_error);
+            }
+        }
+    }
+
+    #[test]
+    fn packed_rotations_round_trip_within_quantization() {
+        for (axis, angle) in [(Vec3::X, 0.3), (Vec3::Y, -2.0), (Vec3::new(1.0, 2.0, 3.0).normalize(), 3.0)] {
+            let rotation = Quat::from_axis_angle(axis, angle);
+            assert!(PackedQuat::pack(rotation).unpack().angle_between(rotation) < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! # Eventos de animación
+//!
+//! Un evento se dispara cuando la reproducción cruza su tiempo. Cada frame
+//! cubre un tramo semiabierto del clip: `[desde, hasta)` hacia delante y
+//! `(hasta, desde]` marcha atrás, así que dos frames seguidos no comparten
+//! ningún instante y un evento justo en la frontera se dispara una sola vez.
+//! Si un frame largo atraviesa el final de un clip en bucle el tramo se
+//! parte por ciclos y el evento se dispara una vez por cada vuelta, sin
+//! saltarse ninguno aunque el frame pase por encima de su tiempo.
+//!
+//! Al final de un clip sin bucle el tramo se cierra: los eventos en
+//! `duration` (o en 0 marcha atrás) se disparan al llegar. En bucle el
+//! tiempo `duration` es el mismo instante que 0.
+//!
+//! Las partículas y los callbacks registrados se atienden dentro del
+//! sistema de animaciones; los sonidos y los callbacks hacia scripts quedan
+//! en cola para el motor (`AnimationSystem::drain_animation_events`).
+
+use super::AnimationEvent;
+use crate::ecs::EntityId;
+
+/// Vueltas máximas de un clip en bucle que se recorren en un frame. Un
+/// frame más largo dispara los eventos de las primeras vueltas
+pub const MAX_EVENT_CYCLES: usize = 64;
+
+/// Evento disparado por una animación
+#[derive(Debug, Clone)]
+pub struct FiredAnimationEvent {
+    /// Animación que lo disparó
+    pub animation_id: String,
+    /// Entidad animada
+    pub entity_id: Option<EntityId>,
+    /// Evento
+    pub event: AnimationEvent,
+}
+
+/// Callback de Rust para los eventos `Callback` con su nombre de función
+pub type AnimationCallback = Box<dyn Fn(&FiredAnimationEvent) + Send + Sync>;
+
+/// Índices de los eventos que cruza la reproducción al avanzar `advance`
+/// segundos (negativo marcha atrás) desde `from`, en el orden en que se
+/// alcanzan
+pub fn crossed_events(events: &[AnimationEvent], duration: f32, looped: bool, from: f32, advance: f32) -> Vec<usize> {
+    let mut crossed = Vec::new();
+    if advance == 0.0 || duration <= 0.0 || events.is_empty() {
+        return crossed;
+    }
+
+    // En bucle los eventos en `duration` caen en 0
+    let times: Vec<f32> = events.iter()
+        .map(|event| if looped { event.time.rem_euclid(duration) } else { event.time })
+        .collect();
+    let push_segment = |crossed: &mut Vec<usize>, inside: &dyn Fn(f32) -> bool, forward: bool| {
+        let mut segment: Vec<usize> = (0..times.len()).filter(|&i| inside(times[i])).collect();
+        // Orden estable: los eventos simultáneos conservan su orden
+        segment.sort_by(|&a, &b| {
+            let order = times[a].total_cmp(&times[b]);
+            if forward { order } else { order.reverse() }
+        });
+        crossed.extend(segment);
+    };
+
+    if !looped {
+        // Parado en un extremo no hay nada más que cruzar en esa dirección
+        let from = from.clamp(0.0, duration);
+        let to = from + advance;
+        if (advance > 0.0 && from >= duration) || (advance < 0.0 && from <= 0.0) {
+            return crossed;
+        }
+        if advance > 0.0 {
+            if to >= duration {
+                push_segment(&mut crossed, &|t| t >= from && t <= duration, true);
+            } else {
+                push_segment(&mut crossed, &|t| t >= from && t < to, true);
+            }
+        } else if to <= 0.0 {
+            push_segment(&mut crossed, &|t| t >= 0.0 && t <= from, false);
+        } else {
+            push_segment(&mut crossed, &|t| t > to && t <= from, false);
+        }
+        return crossed;
+    }
+
+    let mut start = from.rem_euclid(duration);
+    let mut remaining = advance.abs();
+    if advance > 0.0 {
+        for _ in 0..MAX_EVENT_CYCLES {
+            let end = start + remaining;
+            if end < duration {
+                push_segment(&mut crossed, &|t| t >= start && t < end, true);
+                break;
+            }
+            push_segment(&mut crossed, &|t| t >= start, true);
+            remaining -= duration - start;
+            start = 0.0;
+            if remaining <= 0.0 {
+                break;
+            }
+        }
+    } else {
+        // Tras dar la vuelta se parte de `duration`, que ya se cruzó como 0
+        let mut top_included = true;
+        for _ in 0..MAX_EVENT_CYCLES {
+            let end = start - remaining;
+            let below_top = |t: f32| if top_included { t <= start } else { t < start };
+            if end >= 0.0 {
+                push_segment(&mut crossed, &|t| t > end && below_top(t), false);
+                break;
+            }
+            push_segment(&mut crossed, &|t| below_top(t), false);
+            remaining -= start;
+            start = duration;
+            top_included = false;
+            if remaining <= 0.0 {
+                break;
+            }
+        }
+    }
+    crossed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::AnimationEventData;
+
+    fn event_at(name: &str, time: f32) -> AnimationEvent {
+        AnimationEvent { name: name.to_string(), time, data: AnimationEventData::Custom(serde_json::Value::Null) }
+    }
+
+    /// Veces que se dispara un evento en t = 0.5 de un clip de 1 s en bucle
+    /// al reproducir `frames` frames de `delta_time` a `speed`
+    fn fires(speed: f32, delta_time: f32, frames: usize) -> usize {
+        let events = [event_at("step", 0.5)];
+        let mut time = 0.0;
+        let mut fired = 0;
+        for _ in 0..frames {
+            let advance = delta_time * speed;
+            fired += crossed_events(&events, 1.0, true, time, advance).len();
+            time += advance;
+        }
+        fired
+    }
+
+    #[test]
+    fn event_fires_once_per_loop_at_normal_and_double_speed() {
+        assert_eq!(fires(1.0, 1.0 / 60.0, 120), 2);
+        assert_eq!(fires(2.0, 1.0 / 60.0, 120), 4);
+        // El evento cae justo en la frontera entre dos frames
+        assert_eq!(fires(1.0, 0.125, 16), 2);
+    }
+
+    #[test]
+    fn frames_that_jump_over_the_event_still_fire_it() {
+        let events = [event_at("step", 0.5)];
+        assert_eq!(crossed_events(&events, 1.0, true, 0.4, 0.2), vec![0]);
+        assert_eq!(fires(1.0, 0.9, 10), 9);
+        // Un frame de tres vueltas lo dispara tres veces
+        assert_eq!(crossed_events(&events, 1.0, true, 0.0, 3.0), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn reverse_playback_fires_once_per_loop() {
+        assert_eq!(fires(-1.0, 1.0 / 60.0, 120), 2);
+        assert_eq!(fires(-1.0, 0.125, 16), 2);
+    }
+
+    #[test]
+    fn events_fire_in_the_order_they_are_reached() {
+        let events = [event_at("late", 0.75), event_at("early", 0.25), event_at("end", 1.0)];
+        assert_eq!(crossed_events(&events, 1.0, true, 0.0, 1.0), vec![2, 1, 0]);
+        assert_eq!(crossed_events(&events, 1.0, false, 0.0, 1.5), vec![1, 0, 2]);
+        assert_eq!(crossed_events(&events, 1.0, false, 1.0, -1.0), vec![2, 0, 1]);
+        // Parado al final de un clip sin bucle no se dispara nada más
+        assert!(crossed_events(&events, 1.0, false, 1.0, 0.5).is_empty());
+    }
+}
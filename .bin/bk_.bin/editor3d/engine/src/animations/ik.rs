@@ -0,0 +1,379 @@
+//! # IK de dos huesos
+//!
+//! Los constraints `ConstraintType::IK` con tres huesos en `target_bones`
+//! (superior, inferior y efector, p. ej. muslo, espinilla y pie) se
+//! resuelven de forma analítica: el ángulo de la articulación intermedia
+//! sale de la ley de los cosenos para que la cadena mida lo mismo que la
+//! distancia al objetivo, el hueso superior apunta la cadena al objetivo y
+//! un giro alrededor de ese eje lleva la articulación intermedia hacia el
+//! polo. Un objetivo fuera de alcance estira la cadena por completo hacia
+//! él.
+//!
+//! Los límites de rotación del constraint (en grados, sobre los ángulos
+//! XYZ de la rotación local) se aplican a la articulación intermedia, que es
+//! la que se hiperextiende; el hueso superior se reorienta después para que
+//! el efector siga apuntando al objetivo. El resultado se mezcla con la
+//! pose animada con el peso del objetivo por el del constraint.
+//!
+//! Los constraints corren después de la mezcla de clips y de los
+//! modificadores procedurales, justo antes del skinning. La colocación de
+//! pies lanza un rayo bajo cada pie (`FootProbe`) y planta el efector sobre
+//! el terreno conservando la altura animada del pie.
+
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use serde::{Serialize, Deserialize};
+
+use super::sampling::SkeletonPose;
+use super::skinning;
+use super::{Constraint, ConstraintType, RotationLimits, SkeletalData};
+use crate::ecs::EntityId;
+
+/// Longitud mínima de un hueso o distancia para que la cadena sea resoluble
+const MIN_LENGTH: f32 = 1e-5;
+
+/// Objetivo de un constraint IK en espacio mundo
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IkGoal {
+    /// Posición que debe alcanzar el efector
+    pub position: Vec3,
+    /// Punto hacia el que se dobla la articulación intermedia
+    pub pole: Vec3,
+    /// Peso de la mezcla con la pose animada (0..1)
+    pub weight: f32,
+}
+
+/// Cadena de dos huesos por índice en el esqueleto
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TwoBoneChain {
+    /// Hueso superior (hombro, cadera)
+    pub upper: usize,
+    /// Hueso inferior (codo, rodilla)
+    pub lower: usize,
+    /// Efector (mano, pie)
+    pub end: usize,
+}
+
+impl TwoBoneChain {
+    /// Cadena de un constraint IK: tres huesos encadenados de padre a hijo
+    pub fn from_constraint(skeletal: &SkeletalData, constraint: &Constraint) -> Option<Self> {
+        if !matches!(constraint.constraint_type, ConstraintType::IK) {
+            return None;
+        }
+        let [upper, lower, end] = constraint.config.target_bones.as_slice() else {
+            return None;
+        };
+        let index = |id: &str| skeletal.bones.iter().position(|bone| bone.id == id);
+        let chain = Self { upper: index(upper)?, lower: index(lower)?, end: index(end)? };
+        let parent_of = |child: usize| skeletal.bones[child].parent_id.as_deref();
+        let linked = parent_of(chain.lower) == Some(upper.as_str()) && parent_of(chain.end) == Some(lower.as_str());
+        linked.then_some(chain)
+    }
+}
+
+/// Resolver una cadena de dos huesos hacia `target` con la articulación
+/// intermedia hacia `pole`, ambos en espacio del modelo
+pub fn solve_two_bone(
+    pose: &mut SkeletonPose,
+    skeletal: &SkeletalData,
+    chain: TwoBoneChain,
+    target: Vec3,
+    pole: Vec3,
+    weight: f32,
+    limits: Option<&RotationLimits>,
+) {
+    let weight = weight.clamp(0.0, 1.0);
+    let bone_count = pose.bones.len();
+    if weight <= 0.0 || chain.upper >= bone_count || chain.lower >= bone_count || chain.end >= bone_count {
+        return;
+    }
+    let animated_upper = pose.bones[chain.upper].rotation;
+    let animated_lower = pose.bones[chain.lower].rotation;
+
+    let models = model_matrices(pose, skeletal);
+    let (a, b, c) = (position(&models[chain.upper]), position(&models[chain.lower]), position(&models[chain.end]));
+    let (upper_length, lower_length) = (a.distance(b), b.distance(c));
+    if upper_length < MIN_LENGTH || lower_length < MIN_LENGTH {
+        return;
+    }
+    // Fuera de alcance la cadena se estira; demasiado cerca se pliega
+    let reach = a.distance(target).clamp((upper_length - lower_length).abs() + MIN_LENGTH, upper_length + lower_length);
+
+    // Plano de flexión actual; con la cadena recta, el del polo
+    let chain_direction = (c - a).normalize_or_zero();
+    let bend_axis = chain_direction.cross(b - a).try_normalize()
+        .or_else(|| chain_direction.cross(pole - a).try_normalize())
+        .or_else(|| chain_direction.any_orthonormal_vector().try_normalize());
+    let Some(bend_axis) = bend_axis else {
+        return;
+    };
+
+    // Ángulos de la cadena ahora y los que dan la distancia al objetivo
+    let angle = |u: Vec3, v: Vec3| u.normalize_or_zero().dot(v.normalize_or_zero()).clamp(-1.0, 1.0).acos();
+    let upper_angle = angle(c - a, b - a);
+    let lower_angle = angle(a - b, c - b);
+    let solved_upper_angle = law_of_cosines(upper_length, reach, lower_length);
+    let solved_lower_angle = law_of_cosines(upper_length, lower_length, reach);
+
+    let upper_global = rotation(&models[chain.upper]);
+    let lower_global = rotation(&models[chain.lower]);
+    let bend_upper = Quat::from_axis_angle(bend_axis, solved_upper_angle - upper_angle);
+    let bend_lower = Quat::from_axis_angle(bend_axis, solved_lower_angle - lower_angle);
+
+    // Articulación intermedia, recortada a sus límites
+    let mut lower_local = (animated_lower * lower_global.inverse() * bend_lower * lower_global).normalize();
+    if let Some(limits) = limits {
+        lower_local = clamp_rotation(lower_local, limits);
+    }
+    pose.bones[chain.lower].rotation = lower_local;
+    pose.bones[chain.upper].rotation = (animated_upper * upper_global.inverse() * bend_upper * upper_global).normalize();
+
+    // Apuntar el efector al objetivo y girar la cadena hacia el polo
+    let models = model_matrices(pose, skeletal);
+    let (b, c) = (position(&models[chain.lower]), position(&models[chain.end]));
+    let to_target = (target - a).normalize_or_zero();
+    let aim = Quat::from_rotation_arc((c - a).normalize_or_zero(), to_target);
+    let knee = aim * (b - a);
+    let twist = match (
+        (knee - to_target * knee.dot(to_target)).try_normalize(),
+        ((pole - a) - to_target * (pole - a).dot(to_target)).try_normalize(),
+    ) {
+        (Some(from), Some(to)) => Quat::from_rotation_arc(from, to),
+        _ => Quat::IDENTITY,
+    };
+    let upper_global = rotation(&models[chain.upper]);
+    let upper_local = pose.bones[chain.upper].rotation;
+    let solved_upper = (upper_local * upper_global.inverse() * (twist * aim) * upper_global).normalize();
+
+    pose.bones[chain.upper].rotation = animated_upper.slerp(solved_upper, weight).normalize();
+    pose.bones[chain.lower].rotation = animated_lower.slerp(lower_local, weight).normalize();
+}
+
+/// Ángulo opuesto al lado `opposite` de un triángulo de lados `a` y `b`
+fn law_of_cosines(a: f32, b: f32, opposite: f32) -> f32 {
+    ((a * a + b * b - opposite * opposite) / (2.0 * a * b)).clamp(-1.0, 1.0).acos()
+}
+
+/// Recortar los ángulos XYZ (pitch, yaw, roll) de una rotación local
+pub fn clamp_rotation(rotation: Quat, limits: &RotationLimits) -> Quat {
+    let clamp = |angle: f32, limits: [f32; 2]| {
+        let (min, max) = (limits[0].min(limits[1]), limits[0].max(limits[1]));
+        angle.clamp(min.to_radians(), max.to_radians())
+    };
+    let (pitch, yaw, roll) = rotation.to_euler(EulerRot::XYZ);
+    Quat::from_euler(
+        EulerRot::XYZ,
+        clamp(pitch, limits.pitch_limits),
+        clamp(yaw, limits.yaw_limits),
+        clamp(roll, limits.roll_limits),
+    )
+}
+
+/// Matrices en espacio del modelo de una pose
+pub fn model_matrices(pose: &SkeletonPose, skeletal: &SkeletalData) -> Vec<Mat4> {
+    let locals: Vec<Mat4> = pose.bones.iter().map(|bone| bone.to_mat4()).collect();
+    skinning::compose_hierarchy(&skeletal.bones[..locals.len().min(skeletal.bones.len())], &locals)
+}
+
+fn position(matrix: &Mat4) -> Vec3 {
+    matrix.w_axis.truncate()
+}
+
+fn rotation(matrix: &Mat4) -> Quat {
+    matrix.to_scale_rotation_translation().1
+}
+
+/// Configuración de la colocación de pies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FootPlacementConfig {
+    /// Constraints IK de las piernas
+    pub constraints: Vec<String>,
+    /// Altura sobre el pie desde la que se lanza el rayo
+    #[serde(default = "default_ray_height")]
+    pub ray_height: f32,
+    /// Distancia máxima bajo el pie a la que se busca el suelo
+    #[serde(default = "default_max_drop")]
+    pub max_drop: f32,
+    /// Dirección de las rodillas en el espacio de la entidad
+    #[serde(default = "default_knee_direction")]
+    pub knee_direction: [f32; 3],
+    /// Peso de la colocación
+    #[serde(default = "default_foot_weight")]
+    pub weight: f32,
+}
+
+fn default_ray_height() -> f32 {
+    0.5
+}
+
+fn default_max_drop() -> f32 {
+    0.6
+}
+
+fn default_knee_direction() -> [f32; 3] {
+    [0.0, 0.0, 1.0]
+}
+
+fn default_foot_weight() -> f32 {
+    1.0
+}
+
+/// Rayo bajo un pie para buscar el suelo
+#[derive(Debug, Clone)]
+pub struct FootProbe {
+    /// Entidad del personaje
+    pub entity_id: EntityId,
+    /// Constraint IK de la pierna
+    pub constraint_id: String,
+    /// Origen del rayo (sobre el pie)
+    pub origin: Vec3,
+    /// Longitud del rayo hacia abajo
+    pub max_distance: f32,
+    /// Altura animada del pie sobre el origen de la entidad
+    pub foot_height: f32,
+    /// Polo de la rodilla en espacio mundo
+    pub pole: Vec3,
+    /// Peso de la colocación
+    pub weight: f32,
+}
+
+impl FootProbe {
+    /// Objetivo del pie sobre el suelo encontrado en `ground`
+    pub fn goal(&self, ground: Vec3) -> IkGoal {
+        IkGoal {
+            position: Vec3::new(self.origin.x, ground.y + self.foot_height.max(0.0), self.origin.z),
+            pole: self.pole,
+            weight: self.weight,
+        }
+    }
+}
+
+/// Rayos de los pies de una entidad con la pose actual
+pub fn foot_probes(
+    entity_id: EntityId,
+    entity_world: Mat4,
+    pose: &SkeletonPose,
+    skeletal: &SkeletalData,
+    config: &FootPlacementConfig,
+) -> Vec<FootProbe> {
+    let models = model_matrices(pose, skeletal);
+    let entity_origin = entity_world.w_axis.truncate();
+    let knee_direction = entity_world.transform_vector3(Vec3::from(config.knee_direction)).normalize_or_zero();
+
+    config.constraints.iter()
+        .filter_map(|constraint_id| {
+            let constraint = skeletal.constraints.iter().find(|constraint| &constraint.id == constraint_id)?;
+            let chain = TwoBoneChain::from_constraint(skeletal, constraint)?;
+            let foot = entity_world.transform_point3(position(models.get(chain.end)?));
+            let knee = entity_world.transform_point3(position(models.get(chain.lower)?));
+            Some(FootProbe {
+                entity_id,
+                constraint_id: constraint_id.clone(),
+                origin: foot + Vec3::Y * config.ray_height,
+                max_distance: config.ray_height + config.max_drop,
+                foot_height: foot.y - entity_origin.y,
+                pole: knee + knee_direction,
+                weight: config.weight,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::sampling::BonePose;
+    use crate::animations::{Bone, FalloffConfig, FalloffType, InfluenceConfig, Transform};
+
+    const CHAIN: TwoBoneChain = TwoBoneChain { upper: 1, lower: 2, end: 3 };
+
+    /// Pierna recta hacia abajo: pelvis, muslo y espinilla de 1 m y pie
+    fn leg() -> (SkeletalData, SkeletonPose) {
+        let joints = [
+            ("pelvis", None, [0.0, 1.0, 0.0]),
+            ("thigh", Some("pelvis"), [0.2, 0.0, 0.0]),
+            ("shin", Some("thigh"), [0.0, -1.0, 0.0]),
+            ("foot", Some("shin"), [0.0, -1.0, 0.0]),
+        ];
+        let bones = joints.iter()
+            .map(|&(id, parent, position)| {
+                let transform = Transform { position, rotation: [0.0, 0.0, 0.0, 1.0], scale: [1.0; 3] };
+                Bone {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    parent_id: parent.map(str::to_string),
+                    local_transform: transform.clone(),
+                    world_transform: transform,
+                    influence_config: InfluenceConfig {
+                        influence_radius: 1.0,
+                        influence_weight: 1.0,
+                        falloff_config: FalloffConfig { falloff_type: FalloffType::Linear, falloff_exponent: 1.0 },
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        let pose = SkeletonPose { bones: bones.iter().map(|bone| BonePose::from_transform(&bone.local_transform)).collect() };
+        (SkeletalData { bones, keyframes: vec![], constraints: vec![], root_bone_id: Some("pelvis".to_string()), compressed: None }, pose)
+    }
+
+    /// Rodilla solo hacia delante
+    fn knee_limits() -> RotationLimits {
+        RotationLimits { pitch_limits: [0.0, 150.0], yaw_limits: [0.0, 0.0], roll_limits: [0.0, 0.0] }
+    }
+
+    fn joints(pose: &SkeletonPose, skeletal: &SkeletalData) -> (Vec3, Vec3, Vec3) {
+        let models = model_matrices(pose, skeletal);
+        (position(&models[CHAIN.upper]), position(&models[CHAIN.lower]), position(&models[CHAIN.end]))
+    }
+
+    #[test]
+    fn reachable_target_is_reached_within_a_millimetre() {
+        let (skeletal, mut pose) = leg();
+        let target = Vec3::new(0.2, 0.3, 0.4);
+        let pole = Vec3::new(0.2, 0.0, 1.0);
+        solve_two_bone(&mut pose, &skeletal, CHAIN, target, pole, 1.0, Some(&knee_limits()));
+
+        let (hip, knee, foot) = joints(&pose, &skeletal);
+        assert!(foot.distance(target) < 1e-3, "el pie quedó en {:?}", foot);
+        // Los huesos conservan su longitud y la rodilla va hacia el polo
+        assert!((hip.distance(knee) - 1.0).abs() < 1e-4);
+        assert!((knee.distance(foot) - 1.0).abs() < 1e-4);
+        let axis = (target - hip).normalize();
+        let bend = (knee - hip) - axis * (knee - hip).dot(axis);
+        assert!(bend.dot(pole - hip) > 0.0, "la rodilla se dobló hacia atrás: {:?}", knee);
+    }
+
+    #[test]
+    fn unreachable_target_fully_extends_the_chain_toward_it() {
+        let (skeletal, mut pose) = leg();
+        let (hip, _, _) = joints(&pose, &skeletal);
+        let target = hip + Vec3::new(3.0, -4.0, 0.0);
+        solve_two_bone(&mut pose, &skeletal, CHAIN, target, Vec3::new(0.2, 0.0, 1.0), 1.0, None);
+
+        let (hip, knee, foot) = joints(&pose, &skeletal);
+        let direction = (target - hip).normalize();
+        assert!(foot.distance(hip + direction * 2.0) < 1e-3, "el pie quedó en {:?}", foot);
+        assert!(knee.distance(hip + direction) < 1e-3, "la rodilla quedó en {:?}", knee);
+    }
+
+    #[test]
+    fn knee_limits_prevent_hyperextension() {
+        // Con el polo detrás la rodilla tendría que doblarse al revés
+        let target = Vec3::new(0.2, -0.5, 0.0);
+        let pole = Vec3::new(0.2, 0.0, -1.0);
+
+        let (skeletal, mut free) = leg();
+        solve_two_bone(&mut free, &skeletal, CHAIN, target, pole, 1.0, None);
+        let (free_pitch, _, _) = free.bones[CHAIN.lower].rotation.to_euler(EulerRot::XYZ);
+        assert!(free_pitch < -0.1, "sin límites la rodilla debía hiperextenderse: {}", free_pitch);
+
+        let (skeletal, mut limited) = leg();
+        solve_two_bone(&mut limited, &skeletal, CHAIN, target, pole, 1.0, Some(&knee_limits()));
+        let (pitch, yaw, roll) = limited.bones[CHAIN.lower].rotation.to_euler(EulerRot::XYZ);
+        assert!(pitch >= -1e-4 && pitch <= 150f32.to_radians() + 1e-4, "pitch {} fuera de límites", pitch);
+        assert!(yaw.abs() < 1e-4 && roll.abs() < 1e-4, "yaw {} roll {}", yaw, roll);
+        // La pierna queda recta apuntando al objetivo en lugar de doblarse
+        let (hip, _, foot) = joints(&limited, &skeletal);
+        assert!((hip.distance(foot) - 2.0).abs() < 1e-3);
+        assert!((foot - hip).normalize().abs_diff_eq((target - hip).normalize(), 1e-3));
+    }
+}
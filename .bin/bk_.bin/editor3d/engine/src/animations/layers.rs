@@ -0,0 +1,235 @@
+//! # Capas de animación
+//!
+//! Un controlador con entidad combina su máquina de estados principal (la
+//! capa base) con capas adicionales, cada una con su propia máquina de
+//! estados, un peso y una `BlendMask` opcional que limita los huesos que
+//! modifica. Así un personaje puede saludar con la parte superior del
+//! cuerpo mientras las piernas siguen el ciclo de andar.
+//!
+//! Las capas se aplican en orden sobre la pose base. El peso efectivo de un
+//! hueso es el de la capa (o el del parámetro que la controla) por el de la
+//! máscara. En modo `Override` la pose de la capa sustituye a la acumulada
+//! en esa proporción; en modo `Additive` se suma la diferencia entre la
+//! pose de la capa y la pose de reposo del hueso.
+
+use glam::{Quat, Vec3};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use super::sampling::{BonePose, SkeletonPose};
+use super::{BlendMask, ControllerState, ControllerTransition, SkeletalData};
+
+/// Modo de mezcla de una capa sobre las inferiores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LayerBlendMode {
+    /// Sustituir la pose acumulada
+    #[default]
+    Override,
+    /// Sumar la diferencia con la pose de reposo
+    Additive,
+}
+
+/// Capa de animación de un controlador
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnimationLayer {
+    /// Nombre de la capa
+    pub name: String,
+    /// Estados de la capa
+    pub states: HashMap<String, ControllerState>,
+    /// Transiciones de la capa
+    pub transitions: Vec<ControllerTransition>,
+    /// Estado actual
+    pub current_state: Option<String>,
+    /// Peso de la capa en [0, 1]
+    pub weight: f32,
+    /// Parámetro del controlador que fija el peso (sustituye a `weight`)
+    #[serde(default)]
+    pub weight_parameter: Option<String>,
+    /// Huesos que modifica la capa (todos si no hay máscara)
+    #[serde(default)]
+    pub mask: Option<BlendMask>,
+    /// Modo de mezcla
+    #[serde(default)]
+    pub blend_mode: LayerBlendMode,
+}
+
+impl AnimationLayer {
+    /// Peso de la capa con los valores actuales de los parámetros
+    pub fn effective_weight(&self, parameters: &HashMap<String, f32>) -> f32 {
+        self.weight_parameter.as_ref()
+            .and_then(|parameter| parameters.get(parameter))
+            .copied()
+            .unwrap_or(self.weight)
+            .clamp(0.0, 1.0)
+    }
+}
+
+impl BlendMask {
+    /// Peso de un hueso en la máscara. Los huesos que no aparecen pesan 0 y
+    /// los que no tienen peso propio, 1
+    pub fn bone_weight(&self, bone_id: &str) -> f32 {
+        self.bones.iter()
+            .position(|bone| bone == bone_id)
+            .map_or(0.0, |i| self.weights.get(i).copied().unwrap_or(1.0))
+    }
+}
+
+/// Mezclar la pose de una capa sobre `base`. Las dos poses siguen el orden
+/// de los huesos de `skeletal`; los huesos que falten en la capa no cambian
+pub fn blend_layer(
+    base: &mut SkeletonPose,
+    layer: &SkeletonPose,
+    skeletal: &SkeletalData,
+    weight: f32,
+    mask: Option<&BlendMask>,
+    mode: LayerBlendMode,
+) {
+    for (i, bone) in skeletal.bones.iter().enumerate() {
+        let (Some(target), Some(current)) = (layer.bones.get(i), base.bones.get_mut(i)) else {
+            continue;
+        };
+        let weight = weight * mask.map_or(1.0, |mask| mask.bone_weight(&bone.id));
+        if weight <= 0.0 {
+            continue;
+        }
+
+        *current = match mode {
+            LayerBlendMode::Override => BonePose {
+                translation: current.translation.lerp(target.translation, weight),
+                rotation: current.rotation.slerp(target.rotation, weight),
+                scale: current.scale.lerp(target.scale, weight),
+            },
+            LayerBlendMode::Additive => {
+                let rest = BonePose::from_transform(&bone.local_transform);
+                let rotation = (rest.rotation.inverse() * target.rotation).normalize();
+                let scale = target.scale / rest.scale.max(Vec3::splat(f32::EPSILON));
+                BonePose {
+                    translation: current.translation + (target.translation - rest.translation) * weight,
+                    rotation: (current.rotation * Quat::IDENTITY.slerp(rotation, weight)).normalize(),
+                    scale: current.scale * Vec3::ONE.lerp(scale, weight),
+                }
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::{Bone, FalloffConfig, FalloffType, InfluenceConfig, Transform};
+
+    const BONES: [&str; 5] = ["hips", "left_leg", "right_leg", "spine", "right_arm"];
+
+    fn skeleton() -> SkeletalData {
+        let rest = || Transform { position: [0.0; 3], rotation: [0.0, 0.0, 0.0, 1.0], scale: [1.0; 3] };
+        let bones = BONES.iter()
+            .map(|id| Bone {
+                id: id.to_string(),
+                name: id.to_string(),
+                parent_id: None,
+                local_transform: rest(),
+                world_transform: rest(),
+                influence_config: InfluenceConfig {
+                    influence_radius: 1.0,
+                    influence_weight: 1.0,
+                    falloff_config: FalloffConfig { falloff_type: FalloffType::Linear, falloff_exponent: 1.0 },
+                },
+            })
+            .collect();
+        SkeletalData { bones, keyframes: vec![], constraints: vec![], root_bone_id: None, compressed: None }
+    }
+
+    /// Pose con cada hueso girado `degrees[i]` alrededor de `axis`
+    fn pose(axis: Vec3, degrees: [f32; 5], height: f32) -> SkeletonPose {
+        SkeletonPose {
+            bones: degrees.iter()
+                .map(|degrees| BonePose {
+                    translation: Vec3::new(0.0, height, 0.0),
+                    rotation: Quat::from_axis_angle(axis, degrees.to_radians()),
+                    scale: Vec3::ONE,
+                })
+                .collect(),
+        }
+    }
+
+    /// Fotograma del ciclo de andar: piernas adelantada y atrasada
+    fn walk() -> SkeletonPose {
+        pose(Vec3::X, [0.0, 30.0, -30.0, 5.0, -20.0], 1.0)
+    }
+
+    /// Saludo: el brazo derecho levantado, las piernas rectas
+    fn wave() -> SkeletonPose {
+        pose(Vec3::Z, [0.0, 0.0, 0.0, 10.0, 90.0], 0.9)
+    }
+
+    fn upper_body() -> BlendMask {
+        BlendMask { bones: vec!["spine".to_string(), "right_arm".to_string()], weights: vec![1.0, 1.0] }
+    }
+
+    fn assert_bone_near(actual: &BonePose, expected: &BonePose) {
+        assert!(actual.translation.abs_diff_eq(expected.translation, 1e-5), "{:?} != {:?}", actual, expected);
+        assert!(actual.rotation.abs_diff_eq(expected.rotation, 1e-5) || actual.rotation.abs_diff_eq(-expected.rotation, 1e-5),
+            "{:?} != {:?}", actual, expected);
+        assert!(actual.scale.abs_diff_eq(expected.scale, 1e-5), "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn upper_body_wave_leaves_the_legs_of_the_walk() {
+        let skeletal = skeleton();
+        let mut blended = walk();
+        blend_layer(&mut blended, &wave(), &skeletal, 1.0, Some(&upper_body()), LayerBlendMode::Override);
+
+        let (walk, wave) = (walk(), wave());
+        for bone in 0..3 {
+            assert_eq!(blended.bones[bone], walk.bones[bone], "{} cambió", BONES[bone]);
+        }
+        for bone in 3..5 {
+            assert_bone_near(&blended.bones[bone], &wave.bones[bone]);
+        }
+    }
+
+    #[test]
+    fn half_weight_gives_the_halfway_blend() {
+        let skeletal = skeleton();
+        let mut blended = walk();
+        blend_layer(&mut blended, &wave(), &skeletal, 0.5, Some(&upper_body()), LayerBlendMode::Override);
+
+        let (walk, wave) = (walk(), wave());
+        assert_eq!(blended.bones[1], walk.bones[1]);
+        let arm = 4;
+        assert_bone_near(&blended.bones[arm], &BonePose {
+            translation: Vec3::new(0.0, 0.95, 0.0),
+            rotation: walk.bones[arm].rotation.slerp(wave.bones[arm].rotation, 0.5),
+            scale: Vec3::ONE,
+        });
+    }
+
+    #[test]
+    fn weight_zero_is_a_no_op() {
+        let skeletal = skeleton();
+        for mode in [LayerBlendMode::Override, LayerBlendMode::Additive] {
+            for mask in [None, Some(upper_body())] {
+                let mut blended = walk();
+                blend_layer(&mut blended, &wave(), &skeletal, 0.0, mask.as_ref(), mode);
+                assert_eq!(blended, walk(), "{:?} con máscara {:?}", mode, mask.is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn layer_weight_follows_its_parameter() {
+        let layer = AnimationLayer {
+            name: "wave".to_string(),
+            states: HashMap::new(),
+            transitions: vec![],
+            current_state: None,
+            weight: 1.0,
+            weight_parameter: Some("wave_weight".to_string()),
+            mask: Some(upper_body()),
+            blend_mode: LayerBlendMode::Override,
+        };
+        assert_eq!(layer.effective_weight(&HashMap::new()), 1.0);
+        assert_eq!(layer.effective_weight(&HashMap::from([("wave_weight".to_string(), 0.25)])), 0.25);
+        assert_eq!(layer.effective_weight(&HashMap::from([("wave_weight".to_string(), 3.0)])), 1.0);
+    }
+}
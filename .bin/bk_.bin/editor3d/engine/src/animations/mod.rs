@@ -2,10 +2,53 @@
 //! 
 //! Sistema de gestión de animaciones 3D para el metaverso.
 //! Proporciona animaciones de esqueleto, morphing, y procedurales.
+//! Los eventos de las animaciones se disparan al cruzar su tiempo (ver
+//! `events`): los `Particle` lanzan ráfagas en los emisores de `particles`.
+//! Las animaciones de esqueleto con root motion quitan de la
+//! pose el movimiento del hueso raíz y lo dejan en `get_root_motion_deltas`
+//! para que lo aplique la entidad (o su controlador de personaje).
+//! Los modificadores de `procedural` (ruido, muelles, look-at) retocan la
+//! pose ya mezclada antes de calcular las paletas.
+//! Los clips de esqueleto con compresión se reducen al registrarse (ver
+//! `compression`) y se descomprimen al muestrear. `retarget` pasa clips
+//! entre esqueletos con proporciones distintas.
+//! Después, los constraints IK de dos huesos de `ik` llevan manos y pies a
+//! sus objetivos, incluidos los pies plantados sobre el terreno.
+//! Un estado del controlador puede mezclar clips según sus parámetros con
+//! un `blend_tree` en lugar de reproducir una animación.
+//! Las cinemáticas de `cinematic` reproducen clips de cámara y de luz en una
+//! línea de tiempo; el motor copia sus muestras a los componentes.
+
+pub mod skinning;
+pub mod morphing;
+pub mod sampling;
+pub mod layers;
+pub mod particles;
+pub mod events;
+pub mod procedural;
+pub mod ik;
+pub mod compression;
+pub mod retarget;
+pub mod blend_tree;
+pub mod cinematic;
 
 use serde::{Serialize, Deserialize};
 use tracing::{info, debug};
 use std::collections::HashMap;
+use glam::Mat4;
+use crate::ecs::{ComponentType, ECSSystem, EntityId, TransformComponent};
+use skinning::{SkinPalette, SkinningMode};
+use morphing::MorphWeights;
+use sampling::{ClipSampler, RootMotion, RotationInterpolation, SkeletonPose};
+use layers::AnimationLayer;
+use particles::{ParticleBatch, ParticleEmitter, ParticleSystem};
+use events::{AnimationCallback, FiredAnimationEvent};
+use procedural::{ProceduralModifier, ProceduralStack};
+use compression::CompressedClip;
+use retarget::RetargetMap;
+use ik::{FootPlacementConfig, FootProbe, IkGoal, TwoBoneChain};
+use blend_tree::BlendTree;
+use cinematic::{CameraSample, Cinematic, CinematicFinished, LightSample};
 
 /// Sistema de animaciones principal
 pub struct AnimationSystem {
@@ -15,10 +58,61 @@ pub struct AnimationSystem {
     clips: HashMap<String, AnimationClip>,
     /// Controladores de animación
     controllers: HashMap<String, AnimationController>,
+    /// Root motion del último frame por entidad
+    root_motion: HashMap<EntityId, RootMotion>,
+    /// Callbacks de Rust de los eventos `Callback`, por nombre de función
+    event_callbacks: HashMap<String, AnimationCallback>,
+    /// Eventos disparados que atiende el motor (sonidos y scripts)
+    pending_events: Vec<FiredAnimationEvent>,
+    /// Keyframes ordenados de los clips de esqueleto
+    samplers: HashMap<String, ClipSampler>,
+    /// Pose local del frame por animación de esqueleto activa
+    animation_poses: HashMap<String, SkeletonPose>,
+    /// Pose local del frame por entidad con animación de esqueleto
+    poses: HashMap<EntityId, SkeletonPose>,
+    /// Modificadores procedurales por entidad
+    procedural: HashMap<EntityId, ProceduralStack>,
+    /// Objetivos de los constraints IK por entidad e ID de constraint
+    ik_goals: HashMap<EntityId, HashMap<String, IkGoal>>,
+    /// Colocación de pies por entidad
+    foot_placement: HashMap<EntityId, FootPlacementConfig>,
+    /// Skins importados
+    skins: HashMap<String, Skin>,
+    /// Paletas de huesos del frame por entidad con skin
+    skin_palettes: HashMap<EntityId, SkinPalette>,
+    /// Método de mezcla de las paletas
+    skinning_mode: SkinningMode,
+    /// Pesos de los morph targets del frame por entidad
+    morph_weights: HashMap<EntityId, MorphWeights>,
+    /// Emisores de partículas
+    particles: ParticleSystem,
+    /// Cinemáticas de cámara y luz
+    cinematics: HashMap<String, Cinematic>,
+    /// Muestras de cámara del frame por entidad
+    camera_samples: HashMap<EntityId, CameraSample>,
+    /// Muestras de luz del frame por entidad
+    light_samples: HashMap<EntityId, LightSample>,
+    /// Cinemáticas terminadas pendientes de atender
+    finished_cinematics: Vec<CinematicFinished>,
     /// Estado del sistema
     running: bool,
 }
 
+/// Skin de una malla animada por esqueleto
+#[derive(Debug, Clone)]
+pub struct Skin {
+    /// ID del skin
+    pub id: String,
+    /// Entidad con la malla deformada
+    pub entity_id: Option<EntityId>,
+    /// Entidades articulación
+    pub joints: Vec<EntityId>,
+    /// Matrices de bind inversas (una por articulación)
+    pub inverse_bind_matrices: Vec<Mat4>,
+    /// Entidad raíz del esqueleto
+    pub skeleton_root: Option<EntityId>,
+}
+
 /// Animación principal
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Animation {
@@ -32,6 +126,9 @@ pub struct Animation {
     pub config: AnimationConfig,
     /// Clips de la animación
     pub clips: Vec<String>,
+    /// Entidad animada
+    #[serde(default)]
+    pub entity_id: Option<EntityId>,
     /// Estado de la animación
     pub state: AnimationState,
 }
@@ -63,6 +160,12 @@ pub struct AnimationConfig {
     pub blending: Option<BlendingConfig>,
     /// Configuración de eventos
     pub events: Vec<AnimationEvent>,
+    /// Extraer el movimiento del hueso raíz hacia la entidad
+    #[serde(default)]
+    pub extract_root_motion: bool,
+    /// Hueso raíz del que se extrae el movimiento (sustituye al del esqueleto)
+    #[serde(default)]
+    pub root_bone_id: Option<String>,
 }
 
 /// Configuración de interpolación
@@ -274,6 +377,9 @@ pub struct ClipConfig {
     pub compression: Option<CompressionConfig>,
     /// Configuración de optimización
     pub optimization: Option<OptimizationConfig>,
+    /// Interpolación de las rotaciones de los huesos
+    #[serde(default)]
+    pub rotation_interpolation: RotationInterpolation,
 }
 
 /// Configuración de compresión
@@ -383,6 +489,12 @@ pub struct SkeletalData {
     pub keyframes: Vec<TransformKeyframe>,
     /// Configuración de constraints
     pub constraints: Vec<Constraint>,
+    /// Hueso raíz del esqueleto para el root motion
+    #[serde(default)]
+    pub root_bone_id: Option<String>,
+    /// Keyframes comprimidos, que sustituyen a `keyframes`
+    #[serde(default)]
+    pub compressed: Option<CompressedClip>,
 }
 
 /// Hueso
@@ -661,6 +773,14 @@ pub struct ParticleConfig {
     pub color_config: ColorConfig,
     /// Configuración de tamaño
     pub size_config: SizeConfig,
+    /// Distancia en la que las partículas se desvanecen al acercarse a la
+    /// geometría (0 las dibuja con borde duro)
+    #[serde(default = "default_soft_distance")]
+    pub soft_distance: f32,
+}
+
+fn default_soft_distance() -> f32 {
+    0.5
 }
 
 /// Configuración de velocidad
@@ -806,6 +926,12 @@ pub struct ClipState {
     pub compiled: bool,
     /// Tiempo de carga
     pub load_time: f32,
+    /// Bytes de los keyframes antes de comprimir
+    #[serde(default)]
+    pub raw_size: usize,
+    /// Bytes de los keyframes comprimidos (0 sin comprimir)
+    #[serde(default)]
+    pub compressed_size: usize,
 }
 
 /// Controlador de animación
@@ -825,6 +951,15 @@ pub struct AnimationController {
     pub current_state: Option<String>,
     /// Estado del controlador
     pub state: ControllerState,
+    /// Entidad cuya pose componen las capas
+    #[serde(default)]
+    pub entity_id: Option<EntityId>,
+    /// Capas sobre la máquina de estados principal, en orden de aplicación
+    #[serde(default)]
+    pub layers: Vec<AnimationLayer>,
+    /// Valores actuales de los parámetros
+    #[serde(default)]
+    pub parameters: HashMap<String, f32>,
 }
 
 /// Configuración del controlador
@@ -943,6 +1078,9 @@ pub struct ControllerState {
     pub name: String,
     /// Animaciones del estado
     pub animations: Vec<String>,
+    /// Clips mezclados según los parámetros (sustituye a `animations` en la pose)
+    #[serde(default)]
+    pub blend_tree: Option<BlendTree>,
     /// Configuración del estado
     pub config: StateConfig,
     /// Estado del estado
@@ -958,6 +1096,14 @@ pub struct StateConfig {
     pub events_config: EventsConfig,
     /// Configuración de parámetros
     pub parameters_config: ParametersConfig,
+    /// Extraer el root motion de las animaciones del estado. Con false el
+    /// movimiento se queda en la pose (p. ej. un salto en el sitio)
+    #[serde(default = "default_state_root_motion")]
+    pub root_motion: bool,
+}
+
+fn default_state_root_motion() -> bool {
+    true
 }
 
 /// Estado del estado
@@ -967,6 +1113,9 @@ pub struct StateState {
     pub active: bool,
     /// Tiempo en el estado
     pub time_in_state: f32,
+    /// Fase normalizada (0..1) del ciclo del blend tree
+    #[serde(default)]
+    pub normalized_time: f32,
     /// Configuración de transición
     pub transition_config: Option<TransitionConfig>,
 }
@@ -1013,6 +1162,24 @@ impl AnimationSystem {
             animations: HashMap::new(),
             clips: HashMap::new(),
             controllers: HashMap::new(),
+            root_motion: HashMap::new(),
+            event_callbacks: HashMap::new(),
+            pending_events: Vec::new(),
+            samplers: HashMap::new(),
+            animation_poses: HashMap::new(),
+            poses: HashMap::new(),
+            procedural: HashMap::new(),
+            ik_goals: HashMap::new(),
+            foot_placement: HashMap::new(),
+            skins: HashMap::new(),
+            skin_palettes: HashMap::new(),
+            skinning_mode: SkinningMode::default(),
+            morph_weights: HashMap::new(),
+            particles: ParticleSystem::new(),
+            cinematics: HashMap::new(),
+            camera_samples: HashMap::new(),
+            light_samples: HashMap::new(),
+            finished_cinematics: Vec::new(),
             running: false,
         }
     }
@@ -1042,19 +1209,32 @@ impl AnimationSystem {
             return Ok(());
         }
         
+        self.root_motion.clear();
+
+        // Las partículas avanzan antes de que los eventos del frame emitan
+        // las nuevas, que así se dibujan recién nacidas
+        self.particles.update(delta_time);
+
         // Actualizar animaciones
-        for animation in self.animations.values_mut() {
-            if animation.state.active && animation.state.playing {
-                self.update_animation(animation, delta_time).await?;
-            }
+        let animation_ids: Vec<String> = self.animations.iter()
+            .filter(|(_, animation)| animation.state.active && animation.state.playing)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for animation_id in animation_ids {
+            self.update_animation(&animation_id, delta_time).await?;
         }
-        
-        // Actualizar controladores
+
+        // Los controladores avanzan antes de las poses, que usan la fase de
+        // sus blend trees
         for controller in self.controllers.values_mut() {
             if controller.state.active {
                 self.update_controller(controller, delta_time).await?;
             }
         }
+
+        self.update_poses();
+        self.update_morph_weights();
+        self.update_cinematics(delta_time);
         
         Ok(())
     }
@@ -1067,6 +1247,19 @@ impl AnimationSystem {
         self.animations.clear();
         self.clips.clear();
         self.controllers.clear();
+        self.root_motion.clear();
+        self.event_callbacks.clear();
+        self.pending_events.clear();
+        self.procedural.clear();
+        self.ik_goals.clear();
+        self.foot_placement.clear();
+        self.skin_palettes.clear();
+        self.morph_weights.clear();
+        self.particles.clear();
+        self.cinematics.clear();
+        self.camera_samples.clear();
+        self.light_samples.clear();
+        self.finished_cinematics.clear();
         
         info!("✅ Sistema de animaciones limpiado correctamente");
         Ok(())
@@ -1093,8 +1286,11 @@ impl AnimationSystem {
                 },
                 blending: None,
                 events: vec![],
+                extract_root_motion: false,
+                root_bone_id: None,
             },
             clips: vec!["idle_clip".to_string()],
+            entity_id: None,
             state: AnimationState {
                 active: true,
                 playing: false,
@@ -1123,21 +1319,26 @@ impl AnimationSystem {
                 looped: true,
                 compression: None,
                 optimization: None,
+                rotation_interpolation: RotationInterpolation::Slerp,
             },
             data: ClipData::Skeletal(SkeletalData {
                 bones: vec![],
                 keyframes: vec![],
                 constraints: vec![],
+                root_bone_id: None,
+                compressed: None,
             }),
             state: ClipState {
                 active: true,
                 loaded: true,
                 compiled: false,
                 load_time: 0.0,
+                raw_size: 0,
+                compressed_size: 0,
             },
         };
         
-        self.clips.insert(idle_clip.id.clone(), idle_clip);
+        self.insert_clip(idle_clip);
         
         Ok(())
     }
@@ -1171,6 +1372,9 @@ impl AnimationSystem {
                 current_state: None,
                 time: 0.0,
             },
+            entity_id: None,
+            layers: vec![],
+            parameters: HashMap::new(),
         };
         
         self.controllers.insert(basic_controller.id.clone(), basic_controller);
@@ -1179,32 +1383,112 @@ impl AnimationSystem {
     }
 
     /// Actualiza una animación
-    async fn update_animation(&self, animation: &mut Animation, delta_time: f32) -> Result<(), Box<dyn std::error::Error>> {
+    async fn update_animation(&mut self, animation_id: &str, delta_time: f32) -> Result<(), Box<dyn std::error::Error>> {
+        let animation = match self.animations.get_mut(animation_id) {
+            Some(animation) => animation,
+            None => return Ok(()),
+        };
+
         // Actualizar tiempo de la animación
-        animation.state.current_time += delta_time * animation.state.speed;
+        let previous_time = animation.state.current_time;
+        let advance = delta_time * animation.state.speed;
+        animation.state.current_time += advance;
         
-        // Verificar loop
-        if animation.state.current_time >= animation.config.duration {
-            if animation.config.looped {
-                animation.state.current_time = 0.0;
-            } else {
-                animation.state.playing = false;
-                animation.state.current_time = animation.config.duration;
+        // Verificar loop (también marcha atrás y con varias vueltas por frame)
+        let duration = animation.config.duration;
+        if animation.config.looped {
+            if duration > 0.0 {
+                animation.state.current_time = animation.state.current_time.rem_euclid(duration);
+            }
+        } else if animation.state.current_time >= duration {
+            animation.state.playing = false;
+            animation.state.current_time = duration;
+        } else if animation.state.current_time <= 0.0 && advance < 0.0 {
+            animation.state.playing = false;
+            animation.state.current_time = 0.0;
+        }
+
+        let animation = animation.clone();
+
+        // Extraer root motion
+        if let Some(entity_id) = animation.entity_id {
+            if let Some(motion) = self.sample_root_motion(&animation, previous_time, advance) {
+                let total = self.root_motion.entry(entity_id).or_default();
+                *total = total.then(motion);
             }
         }
         
         // Procesar eventos
-        self.process_animation_events(animation).await?;
+        self.process_animation_events(&animation, previous_time, advance).await?;
         
         Ok(())
     }
 
-    /// Procesa eventos de animación
-    async fn process_animation_events(&self, animation: &Animation) -> Result<(), Box<dyn std::error::Error>> {
-        for event in &animation.config.events {
-            if event.time <= animation.state.current_time {
-                // Procesar evento
-                debug!("🎬 Evento de animación: {} en tiempo {}", event.name, event.time);
+    /// Root motion de una animación al avanzar `advance` segundos desde
+    /// `previous_time` (None si no extrae root motion)
+    fn sample_root_motion(&self, animation: &Animation, previous_time: f32, advance: f32) -> Option<RootMotion> {
+        let root = self.root_motion_bone(animation)?;
+        let clip_id = animation.clips.first()?;
+        let (clip, sampler) = (self.clips.get(clip_id)?, self.samplers.get(clip_id)?);
+        let ClipData::Skeletal(skeletal) = &clip.data else {
+            return None;
+        };
+        Some(sampler.root_motion(skeletal, &clip.config, root, previous_time, advance))
+    }
+
+    /// Índice del hueso raíz de una animación que extrae root motion: el de
+    /// su configuración o el del esqueleto, salvo que el estado del
+    /// controlador que la reproduce lo desactive
+    fn root_motion_bone(&self, animation: &Animation) -> Option<usize> {
+        if !animation.config.extract_root_motion || !self.state_root_motion(animation) {
+            return None;
+        }
+        let ClipData::Skeletal(skeletal) = &self.clips.get(animation.clips.first()?)?.data else {
+            return None;
+        };
+        let root_bone_id = animation.config.root_bone_id.as_ref().or(skeletal.root_bone_id.as_ref())?;
+        skeletal.bones.iter().position(|bone| &bone.id == root_bone_id)
+    }
+
+    /// Interruptor de root motion del estado actual que reproduce la
+    /// animación en los controladores de su entidad (true sin estado)
+    fn state_root_motion(&self, animation: &Animation) -> bool {
+        let Some(entity_id) = animation.entity_id else {
+            return true;
+        };
+        self.controllers.values()
+            .filter(|controller| controller.state.active && controller.entity_id == Some(entity_id))
+            .filter_map(|controller| controller.current_state.as_ref().and_then(|id| controller.states.get(id)))
+            .find(|state| state.animations.contains(&animation.id))
+            .map_or(true, |state| state.config.root_motion)
+    }
+
+    /// Procesa los eventos de animación que cruza la reproducción en este
+    /// paso: las partículas y los callbacks registrados se atienden aquí y
+    /// el resto queda en cola para `drain_animation_events`
+    async fn process_animation_events(&mut self, animation: &Animation, previous_time: f32, advance: f32) -> Result<(), Box<dyn std::error::Error>> {
+        let config = &animation.config;
+        for index in events::crossed_events(&config.events, config.duration, config.looped, previous_time, advance) {
+            let event = &config.events[index];
+            debug!("🎬 Evento de animación: {} en tiempo {}", event.name, event.time);
+            let fired = FiredAnimationEvent {
+                animation_id: animation.id.clone(),
+                entity_id: animation.entity_id,
+                event: event.clone(),
+            };
+            match &event.data {
+                AnimationEventData::Particle(particle_event) => {
+                    if self.particles.trigger(particle_event).is_none() {
+                        debug!("Evento de partículas sin emisor: {}", particle_event.particle_system_id);
+                    }
+                }
+                AnimationEventData::Callback(callback_event) => {
+                    match self.event_callbacks.get(&callback_event.function_name) {
+                        Some(callback) => callback(&fired),
+                        None => self.pending_events.push(fired),
+                    }
+                }
+                AnimationEventData::Sound(_) | AnimationEventData::Custom(_) => self.pending_events.push(fired),
             }
         }
         
@@ -1222,19 +1506,61 @@ impl AnimationSystem {
         // Actualizar estado actual
         if let Some(current_state_id) = &controller.current_state {
             if let Some(state) = controller.states.get_mut(current_state_id) {
-                state.state.time_in_state += delta_time;
+                self.advance_state(state, &controller.parameters, delta_time);
+            }
+        }
+        for layer in &mut controller.layers {
+            if let Some(state) = layer.current_state.as_ref().and_then(|id| layer.states.get_mut(id)) {
+                self.advance_state(state, &controller.parameters, delta_time);
             }
         }
         
         Ok(())
     }
 
+    /// Avanza el tiempo de un estado y la fase de su blend tree
+    fn advance_state(&self, state: &mut ControllerState, parameters: &HashMap<String, f32>, delta_time: f32) {
+        state.state.time_in_state += delta_time;
+        if let Some(tree) = &state.blend_tree {
+            state.state.normalized_time = tree.advance_phase(parameters, &self.clips, state.state.normalized_time, delta_time);
+        }
+    }
+
     /// Procesa transiciones del controlador
     async fn process_controller_transitions(&self, controller: &mut AnimationController) -> Result<(), Box<dyn std::error::Error>> {
         // Implementar lógica de transiciones
         Ok(())
     }
 
+    /// Fija el valor de un parámetro de un controlador (p. ej. el peso de
+    /// una capa), limitado a su rango si lo tiene
+    pub fn set_controller_parameter(&mut self, controller_id: &str, name: &str, value: f32) -> bool {
+        let Some(controller) = self.controllers.get_mut(controller_id) else {
+            return false;
+        };
+        let value = match controller.config.parameters_config.parameters.get(name).and_then(|p| p.limits.as_ref()) {
+            Some(limits) => value.clamp(limits.min, limits.max),
+            None => value,
+        };
+        controller.parameters.insert(name.to_string(), value);
+        true
+    }
+
+    /// Cambia el estado actual de una capa de un controlador
+    pub fn set_layer_state(&mut self, controller_id: &str, layer_name: &str, state_id: &str) -> bool {
+        let Some(layer) = self.controllers.get_mut(controller_id)
+            .and_then(|controller| controller.layers.iter_mut().find(|layer| layer.name == layer_name))
+        else {
+            return false;
+        };
+        let Some(state) = layer.states.get_mut(state_id) else {
+            return false;
+        };
+        state.state.time_in_state = 0.0;
+        layer.current_state = Some(state_id.to_string());
+        true
+    }
+
     /// Crea una animación
     pub async fn create_animation(&mut self, animation: Animation) -> Result<(), Box<dyn std::error::Error>> {
         let id = animation.id.clone();
@@ -1252,20 +1578,64 @@ impl AnimationSystem {
     /// Crea un clip
     pub async fn create_clip(&mut self, clip: AnimationClip) -> Result<(), Box<dyn std::error::Error>> {
         let id = clip.id.clone();
-        self.clips.insert(id.clone(), clip);
+        self.insert_clip(clip);
         
         debug!("➕ Clip creado: {} ({})", id, id);
         Ok(())
     }
 
+    /// Registra un clip, lo comprime si su configuración lo pide y prepara
+    /// el muestreo de sus keyframes de esqueleto
+    fn insert_clip(&mut self, mut clip: AnimationClip) {
+        if compression::compress_clip(&mut clip) {
+            debug!(
+                "🗜️ Clip comprimido: {} ({} -> {} bytes)",
+                clip.id, clip.state.raw_size, clip.state.compressed_size
+            );
+        }
+        // Las cinemáticas buscan el tramo por tiempo
+        match &mut clip.data {
+            ClipData::Camera(camera) => camera.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time)),
+            ClipData::Light(light) => light.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time)),
+            _ => {}
+        }
+        match &clip.data {
+            ClipData::Skeletal(skeletal) => {
+                self.samplers.insert(clip.id.clone(), ClipSampler::new(skeletal));
+            }
+            _ => {
+                self.samplers.remove(&clip.id);
+            }
+        }
+        self.clips.insert(clip.id.clone(), clip);
+    }
+
+    /// Pasa un clip de esqueleto registrado al esqueleto `target` y registra
+    /// el resultado. Devuelve el ID del clip nuevo
+    pub fn retarget_clip(&mut self, clip_id: &str, target: &SkeletalData, map: &RetargetMap) -> Option<String> {
+        let clip = self.clips.get(clip_id)?;
+        let ClipData::Skeletal(source) = &clip.data else {
+            return None;
+        };
+        let retargeted = retarget::retarget_clip(clip, source, target, map);
+        let id = retargeted.id.clone();
+        self.insert_clip(retargeted);
+        
+        debug!("🦴 Clip {} retargeteado como {}", clip_id, id);
+        Some(id)
+    }
+
     /// Obtiene un clip
     pub fn get_clip(&self, id: &str) -> Option<&AnimationClip> {
         self.clips.get(id)
     }
 
     /// Crea un controlador
-    pub async fn create_controller(&mut self, controller: AnimationController) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn create_controller(&mut self, mut controller: AnimationController) -> Result<(), Box<dyn std::error::Error>> {
         let id = controller.id.clone();
+        for (name, parameter) in &controller.config.parameters_config.parameters {
+            controller.parameters.entry(name.clone()).or_insert(parameter.default_value);
+        }
         self.controllers.insert(id.clone(), controller);
         
         debug!("➕ Controlador creado: {} ({})", id, id);
@@ -1277,11 +1647,543 @@ impl AnimationSystem {
         self.controllers.get(id)
     }
 
+    /// Obtiene el root motion del último frame para una entidad: traslación
+    /// en el espacio local de la entidad y giro alrededor de Y
+    pub fn get_root_motion_delta(&self, entity: EntityId) -> RootMotion {
+        self.root_motion.get(&entity).copied().unwrap_or_default()
+    }
+
+    /// Obtiene el root motion del último frame de todas las entidades
+    pub fn get_root_motion_deltas(&self) -> &HashMap<EntityId, RootMotion> {
+        &self.root_motion
+    }
+
+    /// Registra un skin
+    pub fn register_skin(&mut self, skin: Skin) {
+        self.skins.insert(skin.id.clone(), skin);
+    }
+
+    /// Obtiene un skin
+    pub fn get_skin(&self, id: &str) -> Option<&Skin> {
+        self.skins.get(id)
+    }
+
+    /// Obtiene el skin de una entidad
+    pub fn get_entity_skin(&self, entity: EntityId) -> Option<&Skin> {
+        self.skins.values().find(|skin| skin.entity_id == Some(entity))
+    }
+
+    /// Recalcula las paletas de huesos en espacio mundo. Los skins glTF se
+    /// evalúan con las transformaciones de sus articulaciones en el ECS; las
+    /// animaciones de esqueleto activas con entidad, con su primer clip
+    pub fn update_skin_palettes(&mut self, world: &ECSSystem) {
+        self.skin_palettes.clear();
+        let mode = self.skinning_mode;
+
+        for skin in self.skins.values() {
+            let Some(entity_id) = skin.entity_id else {
+                continue;
+            };
+            let matrices = skin.joints.iter()
+                .zip(skin.inverse_bind_matrices.iter())
+                .map(|(&joint, inverse_bind)| skinning::entity_world_matrix(world, joint) * *inverse_bind)
+                .collect();
+            self.skin_palettes.insert(entity_id, SkinPalette { mode, matrices });
+        }
+
+        for animation in self.animations.values() {
+            if !animation.state.active || !matches!(animation.animation_type, AnimationType::Skeletal) {
+                continue;
+            }
+            let Some(entity_id) = animation.entity_id else {
+                continue;
+            };
+            let Some(ClipData::Skeletal(skeletal)) = animation.clips.first()
+                .and_then(|clip_id| self.clips.get(clip_id))
+                .map(|clip| &clip.data)
+            else {
+                continue;
+            };
+            let Some(pose) = self.poses.get(&entity_id) else {
+                continue;
+            };
+            if skeletal.bones.is_empty() {
+                continue;
+            }
+
+            let entity_world = skinning::entity_world_matrix(world, entity_id);
+            let matrices = skinning::skeletal_palette(skeletal, pose)
+                .into_iter()
+                .map(|matrix| entity_world * matrix)
+                .collect();
+            self.skin_palettes.insert(entity_id, SkinPalette { mode, matrices });
+        }
+    }
+
+    /// Añade un modificador procedural a la pose de una entidad, o reemplaza
+    /// al del mismo ID. Los modificadores se aplican en orden de llegada
+    pub fn add_procedural_modifier(&mut self, entity_id: EntityId, modifier: ProceduralModifier) {
+        self.procedural.entry(entity_id).or_default().insert(modifier);
+    }
+
+    /// Quita un modificador procedural de una entidad
+    pub fn remove_procedural_modifier(&mut self, entity_id: EntityId, modifier_id: &str) -> bool {
+        let Some(stack) = self.procedural.get_mut(&entity_id) else {
+            return false;
+        };
+        let removed = stack.remove(modifier_id);
+        if stack.modifiers.is_empty() {
+            self.procedural.remove(&entity_id);
+        }
+        removed
+    }
+
+    /// Aplica los modificadores procedurales a las poses del frame, ya
+    /// mezcladas por las máquinas de estados y las capas. Se llama con las
+    /// transformaciones del ECS actualizadas, antes de `update_skin_palettes`
+    pub fn apply_procedural_modifiers(&mut self, world: &ECSSystem, delta_time: f32) {
+        for (entity_id, stack) in self.procedural.iter_mut() {
+            let Some(pose) = self.poses.get_mut(entity_id) else {
+                continue;
+            };
+            let Some(skeletal) = entity_skeleton(&self.animations, &self.controllers, &self.clips, *entity_id) else {
+                continue;
+            };
+            let entity_world = skinning::entity_world_matrix(world, *entity_id);
+            stack.apply(pose, skeletal, entity_world, delta_time, |target| {
+                world.get_component::<TransformComponent>(target, ComponentType::Transform)
+                    .map(|_| skinning::entity_world_matrix(world, target).w_axis.truncate())
+            });
+        }
+    }
+
+    /// Fija el objetivo en espacio mundo de un constraint IK de la entidad
+    pub fn set_ik_target(&mut self, entity_id: EntityId, constraint_id: &str, goal: IkGoal) {
+        self.ik_goals.entry(entity_id).or_default().insert(constraint_id.to_string(), goal);
+    }
+
+    /// Quita el objetivo de un constraint IK; la cadena vuelve a la pose animada
+    pub fn clear_ik_target(&mut self, entity_id: EntityId, constraint_id: &str) -> bool {
+        let Some(goals) = self.ik_goals.get_mut(&entity_id) else {
+            return false;
+        };
+        let removed = goals.remove(constraint_id).is_some();
+        if goals.is_empty() {
+            self.ik_goals.remove(&entity_id);
+        }
+        removed
+    }
+
+    /// Activa la colocación de pies de una entidad con los constraints IK
+    /// de sus piernas
+    pub fn set_foot_placement(&mut self, entity_id: EntityId, config: FootPlacementConfig) {
+        self.foot_placement.insert(entity_id, config);
+    }
+
+    /// Desactiva la colocación de pies y suelta los objetivos de las piernas
+    pub fn clear_foot_placement(&mut self, entity_id: EntityId) {
+        if let Some(config) = self.foot_placement.remove(&entity_id) {
+            for constraint_id in &config.constraints {
+                self.clear_ik_target(entity_id, constraint_id);
+            }
+        }
+    }
+
+    /// Rayos bajo los pies con la pose del frame. El motor los lanza contra
+    /// la física y fija los objetivos con `FootProbe::goal`, o los quita con
+    /// `clear_ik_target` si no hay suelo
+    pub fn foot_probes(&self, world: &ECSSystem) -> Vec<FootProbe> {
+        let mut probes = Vec::new();
+        for (entity_id, config) in &self.foot_placement {
+            let (Some(pose), Some(skeletal)) = (
+                self.poses.get(entity_id),
+                entity_skeleton(&self.animations, &self.controllers, &self.clips, *entity_id),
+            ) else {
+                continue;
+            };
+            let entity_world = skinning::entity_world_matrix(world, *entity_id);
+            probes.extend(ik::foot_probes(*entity_id, entity_world, pose, skeletal, config));
+        }
+        probes
+    }
+
+    /// Resuelve los constraints IK con objetivo sobre las poses del frame.
+    /// Se llama después de `apply_procedural_modifiers` y antes de
+    /// `update_skin_palettes`
+    pub fn apply_ik_constraints(&mut self, world: &ECSSystem) {
+        for (entity_id, goals) in &self.ik_goals {
+            let Some(pose) = self.poses.get_mut(entity_id) else {
+                continue;
+            };
+            let Some(skeletal) = entity_skeleton(&self.animations, &self.controllers, &self.clips, *entity_id) else {
+                continue;
+            };
+            let to_model = skinning::entity_world_matrix(world, *entity_id).inverse();
+            for constraint in &skeletal.constraints {
+                let Some(goal) = goals.get(&constraint.id) else {
+                    continue;
+                };
+                let Some(chain) = TwoBoneChain::from_constraint(skeletal, constraint) else {
+                    continue;
+                };
+                let limits = constraint.config.limits.as_ref().and_then(|limits| limits.rotation_limits.as_ref());
+                ik::solve_two_bone(
+                    pose,
+                    skeletal,
+                    chain,
+                    to_model.transform_point3(goal.position),
+                    to_model.transform_point3(goal.pole),
+                    goal.weight * constraint.config.weight,
+                    limits,
+                );
+            }
+        }
+    }
+
+    /// Paletas de huesos del último `update_skin_palettes`
+    pub fn skin_palettes(&self) -> &HashMap<EntityId, SkinPalette> {
+        &self.skin_palettes
+    }
+
+    /// Muestrea el primer clip de las animaciones de esqueleto activas con
+    /// entidad. La pose de la entidad es la de su animación salvo que un
+    /// controlador la componga por capas. Las poses se reutilizan entre frames
+    /// y no llevan el root motion, que ya mueve a la entidad
+    fn update_poses(&mut self) {
+        for animation in self.animations.values() {
+            if !is_posed(animation) {
+                continue;
+            }
+            let Some(clip_id) = animation.clips.first() else {
+                continue;
+            };
+            let (Some(clip), Some(sampler)) = (self.clips.get(clip_id), self.samplers.get(clip_id)) else {
+                continue;
+            };
+            let ClipData::Skeletal(skeletal) = &clip.data else {
+                continue;
+            };
+            let root = self.root_motion_bone(animation);
+            let pose = self.animation_poses.entry(animation.id.clone()).or_default();
+            sampler.sample(skeletal, &clip.config, animation.state.current_time, pose);
+            if let Some(root) = root {
+                let reference = sampler.sample_bone(skeletal, &clip.config, root, 0.0);
+                sampling::remove_root_motion(pose, &reference, root);
+            }
+        }
+        let animations = &self.animations;
+        self.animation_poses.retain(|id, _| animations.get(id).is_some_and(is_posed));
+
+        for (id, pose) in &self.animation_poses {
+            if let Some(entity_id) = self.animations.get(id).and_then(|animation| animation.entity_id) {
+                self.poses.entry(entity_id).or_default().clone_from(pose);
+            }
+        }
+        self.poses.retain(|entity_id, _| animations.values().any(|animation| {
+            is_posed(animation) && animation.entity_id == Some(*entity_id)
+        }));
+
+        self.apply_controller_layers();
+    }
+
+    /// Componer la pose de las entidades de los controladores con capas o
+    /// con blend trees: la máquina de estados principal da la pose base y
+    /// cada capa se mezcla encima con su peso y su máscara
+    fn apply_controller_layers(&mut self) {
+        let mut base_tree = SkeletonPose::default();
+        let mut layer_tree = SkeletonPose::default();
+        for controller in self.controllers.values() {
+            if !controller.state.active {
+                continue;
+            }
+            let Some(entity_id) = controller.entity_id else {
+                continue;
+            };
+            let Some(state) = controller.current_state.as_ref().and_then(|id| controller.states.get(id)) else {
+                continue;
+            };
+            if controller.layers.is_empty() && state.blend_tree.is_none() {
+                continue;
+            }
+
+            // La máscara se resuelve con el esqueleto del clip base
+            let (base_pose, skeletal) = match &state.blend_tree {
+                Some(tree) => {
+                    let parameters = &controller.parameters;
+                    let Some(skeletal) = tree.sample(parameters, state.state.normalized_time, &self.clips, &self.samplers, &mut base_tree) else {
+                        continue;
+                    };
+                    (&base_tree, skeletal)
+                }
+                None => {
+                    let Some((base_id, base_pose)) = state_pose(&self.animation_poses, &state.animations) else {
+                        continue;
+                    };
+                    let Some(ClipData::Skeletal(skeletal)) = self.animations.get(base_id)
+                        .and_then(|animation| animation.clips.first())
+                        .and_then(|clip_id| self.clips.get(clip_id))
+                        .map(|clip| &clip.data)
+                    else {
+                        continue;
+                    };
+                    (base_pose, skeletal)
+                }
+            };
+            let pose = self.poses.entry(entity_id).or_default();
+            pose.clone_from(base_pose);
+
+            for layer in &controller.layers {
+                let weight = layer.effective_weight(&controller.parameters);
+                let Some(state) = layer.current_state.as_ref().and_then(|id| layer.states.get(id)) else {
+                    continue;
+                };
+                let layer_pose = match &state.blend_tree {
+                    Some(tree) => tree.sample(&controller.parameters, state.state.normalized_time, &self.clips, &self.samplers, &mut layer_tree)
+                        .map(|_| &layer_tree),
+                    None => state_pose(&self.animation_poses, &state.animations).map(|(_, pose)| pose),
+                };
+                if let Some(layer_pose) = layer_pose {
+                    layers::blend_layer(pose, layer_pose, skeletal, weight, layer.mask.as_ref(), layer.blend_mode);
+                }
+            }
+        }
+    }
+
+    /// Pose local de los huesos de una entidad en el último `update`
+    pub fn get_pose(&self, entity: EntityId) -> Option<&SkeletonPose> {
+        self.poses.get(&entity)
+    }
+
+    /// Calcula los pesos de los morph targets de las animaciones de morphing
+    /// activas con entidad. Las animaciones de una misma entidad suman sus
+    /// pesos antes de elegir los targets activos
+    fn update_morph_weights(&mut self) {
+        let mut samples: HashMap<EntityId, Vec<(String, f32)>> = HashMap::new();
+        for animation in self.animations.values() {
+            if !animation.state.active || !matches!(animation.animation_type, AnimationType::Morphing) {
+                continue;
+            }
+            let Some(entity_id) = animation.entity_id else {
+                continue;
+            };
+            let weight = animation.state.weight;
+            for clip_id in &animation.clips {
+                let Some(ClipData::Morphing(morphing)) = self.clips.get(clip_id).map(|clip| &clip.data) else {
+                    continue;
+                };
+                samples.entry(entity_id).or_default().extend(
+                    morphing::sample_morph_weights(morphing, animation.state.current_time)
+                        .into_iter()
+                        .map(|(target_id, target_weight)| (target_id, target_weight * weight)),
+                );
+            }
+        }
+
+        self.morph_weights = samples.into_iter()
+            .map(|(entity_id, samples)| (entity_id, MorphWeights::from_samples(samples)))
+            .filter(|(_, weights)| !weights.is_empty())
+            .collect();
+    }
+
+    /// Pesos de los morph targets del último `update`
+    pub fn morph_weights(&self) -> &HashMap<EntityId, MorphWeights> {
+        &self.morph_weights
+    }
+
+    /// Paleta de huesos de una entidad
+    pub fn get_skin_palette(&self, entity: EntityId) -> Option<&SkinPalette> {
+        self.skin_palettes.get(&entity)
+    }
+
+    /// Establece el método de mezcla de las articulaciones
+    pub fn set_skinning_mode(&mut self, mode: SkinningMode) {
+        self.skinning_mode = mode;
+    }
+
+    /// Método de mezcla de las articulaciones
+    pub fn skinning_mode(&self) -> SkinningMode {
+        self.skinning_mode
+    }
+
+    /// Registra el callback de Rust de los eventos `Callback` con ese nombre
+    /// de función. Sin callback el evento va al script de la entidad
+    pub fn register_event_callback(&mut self, function_name: &str, callback: AnimationCallback) {
+        self.event_callbacks.insert(function_name.to_string(), callback);
+    }
+
+    /// Quita un callback de eventos
+    pub fn unregister_event_callback(&mut self, function_name: &str) -> bool {
+        self.event_callbacks.remove(function_name).is_some()
+    }
+
+    /// Eventos disparados que no se atienden dentro del sistema: sonidos,
+    /// callbacks sin registrar y eventos personalizados, en orden
+    pub fn drain_animation_events(&mut self) -> Vec<FiredAnimationEvent> {
+        std::mem::take(&mut self.pending_events)
+    }
+
+    /// Registra un emisor de partículas (reemplaza al del mismo ID)
+    pub fn create_particle_emitter(&mut self, emitter: ParticleEmitter) {
+        self.particles.add_emitter(emitter);
+    }
+
+    /// Quita un emisor de partículas
+    pub fn remove_particle_emitter(&mut self, id: &str) -> Option<ParticleEmitter> {
+        self.particles.remove_emitter(id)
+    }
+
+    /// Obtiene un emisor de partículas
+    pub fn get_particle_emitter(&self, id: &str) -> Option<&ParticleEmitter> {
+        self.particles.get(id)
+    }
+
+    /// Obtiene un emisor de partículas (mutable)
+    pub fn get_particle_emitter_mut(&mut self, id: &str) -> Option<&mut ParticleEmitter> {
+        self.particles.get_mut(id)
+    }
+
+    /// Lanza un evento de partículas fuera de una animación. Devuelve las
+    /// partículas de la ráfaga (None si el emisor no existe)
+    pub fn trigger_particle_event(&mut self, event: &ParticleEvent) -> Option<u32> {
+        self.particles.trigger(event)
+    }
+
+    /// Lleva los emisores con entidad a su posición en el ECS
+    pub fn update_particle_emitters(&mut self, world: &ECSSystem) {
+        self.particles.follow_entities(world);
+    }
+
+    /// Partículas vivas de cada emisor para el renderer
+    pub fn particle_batches(&self) -> Vec<ParticleBatch> {
+        self.particles.batches()
+    }
+
     /// Obtiene el estado de salud del sistema
     pub async fn health_check(&self) -> bool {
         self.running
     }
 
+    /// Registra una cinemática (reemplaza la del mismo ID). Sin duración se
+    /// toma la del último keyframe de sus clips
+    pub fn create_cinematic(&mut self, mut cinematic: Cinematic) {
+        if cinematic.duration <= 0.0 {
+            cinematic.duration = cinematic.tracks.iter()
+                .filter_map(|track| self.clips.get(&track.clip_id))
+                .map(|clip| match &clip.data {
+                    ClipData::Camera(camera) => cinematic::camera_duration(camera),
+                    ClipData::Light(light) => cinematic::light_duration(light),
+                    _ => 0.0,
+                })
+                .fold(0.0, f32::max);
+        }
+        self.cinematics.insert(cinematic.id.clone(), cinematic);
+    }
+
+    /// Cinemática registrada
+    pub fn get_cinematic(&self, id: &str) -> Option<&Cinematic> {
+        self.cinematics.get(id)
+    }
+
+    /// Reproduce una cinemática. Si había terminado vuelve a empezar
+    pub fn play_cinematic(&mut self, id: &str) -> bool {
+        let Some(cinematic) = self.cinematics.get_mut(id) else {
+            return false;
+        };
+        let state = &cinematic.state;
+        let at_end = if state.speed < 0.0 { state.time <= 0.0 } else { state.time >= cinematic.duration };
+        if !cinematic.looped && at_end {
+            let start = if state.speed < 0.0 { cinematic.duration } else { 0.0 };
+            cinematic.seek(start);
+        }
+        cinematic.state.playing = true;
+        cinematic.state.active = true;
+        true
+    }
+
+    /// Pausa una cinemática; sus pistas mantienen el frame actual
+    pub fn pause_cinematic(&mut self, id: &str) -> bool {
+        let Some(cinematic) = self.cinematics.get_mut(id) else {
+            return false;
+        };
+        cinematic.state.playing = false;
+        true
+    }
+
+    /// Detiene una cinemática y la rebobina; sus pistas dejan de escribirse
+    pub fn stop_cinematic(&mut self, id: &str) -> bool {
+        let Some(cinematic) = self.cinematics.get_mut(id) else {
+            return false;
+        };
+        cinematic.state.playing = false;
+        cinematic.state.active = false;
+        cinematic.seek(0.0);
+        true
+    }
+
+    /// Lleva una cinemática a `time` sin recorrer el tramo intermedio
+    pub fn seek_cinematic(&mut self, id: &str, time: f32) -> bool {
+        let Some(cinematic) = self.cinematics.get_mut(id) else {
+            return false;
+        };
+        cinematic.seek(time);
+        true
+    }
+
+    /// Cambia la velocidad de reproducción de una cinemática
+    pub fn set_cinematic_speed(&mut self, id: &str, speed: f32) -> bool {
+        let Some(cinematic) = self.cinematics.get_mut(id) else {
+            return false;
+        };
+        cinematic.state.speed = speed;
+        true
+    }
+
+    /// Avanza las cinemáticas activas y muestrea sus pistas
+    fn update_cinematics(&mut self, delta_time: f32) {
+        self.camera_samples.clear();
+        self.light_samples.clear();
+        for cinematic in self.cinematics.values_mut() {
+            if !cinematic.state.active {
+                continue;
+            }
+            if cinematic.advance(delta_time) {
+                debug!("🎬 Cinemática terminada: {}", cinematic.id);
+                self.finished_cinematics.push(CinematicFinished { cinematic_id: cinematic.id.clone() });
+            }
+            let time = cinematic.state.time;
+            for track in &cinematic.tracks {
+                match self.clips.get(&track.clip_id).map(|clip| &clip.data) {
+                    Some(ClipData::Camera(camera)) => {
+                        if let Some(sample) = cinematic::sample_camera(camera, time) {
+                            self.camera_samples.insert(track.entity_id, sample);
+                        }
+                    }
+                    Some(ClipData::Light(light)) => {
+                        if let Some(sample) = cinematic::sample_light(light, time) {
+                            self.light_samples.insert(track.entity_id, sample);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Muestras de cámara del último `update` por entidad
+    pub fn camera_samples(&self) -> &HashMap<EntityId, CameraSample> {
+        &self.camera_samples
+    }
+
+    /// Muestras de luz del último `update` por entidad
+    pub fn light_samples(&self) -> &HashMap<EntityId, LightSample> {
+        &self.light_samples
+    }
+
+    /// Saca las cinemáticas terminadas desde la última llamada
+    pub fn drain_finished_cinematics(&mut self) -> Vec<CinematicFinished> {
+        std::mem::take(&mut self.finished_cinematics)
+    }
+
     /// Obtiene estadísticas del sistema
     pub fn get_stats(&self) -> AnimationStats {
         AnimationStats {
@@ -1290,10 +2192,46 @@ impl AnimationSystem {
             controller_count: self.controllers.len(),
             active_animations: self.animations.values().filter(|a| a.state.active).count(),
             playing_animations: self.animations.values().filter(|a| a.state.playing).count(),
+            skinned_entities: self.skin_palettes.len(),
+            morphed_entities: self.morph_weights.len(),
+            particle_emitters: self.particles.len(),
+            live_particles: self.particles.live_particles(),
         }
     }
 }
 
+/// Animación de esqueleto activa con entidad, que produce pose
+fn is_posed(animation: &Animation) -> bool {
+    animation.state.active && matches!(animation.animation_type, AnimationType::Skeletal) && animation.entity_id.is_some()
+}
+
+/// Esqueleto de la primera animación de esqueleto activa de una entidad o,
+/// si no tiene, el del blend tree del estado actual de su controlador
+fn entity_skeleton<'a>(
+    animations: &'a HashMap<String, Animation>,
+    controllers: &'a HashMap<String, AnimationController>,
+    clips: &'a HashMap<String, AnimationClip>,
+    entity_id: EntityId,
+) -> Option<&'a SkeletalData> {
+    let skeletal = |clip_id: &str| match clips.get(clip_id).map(|clip| &clip.data) {
+        Some(ClipData::Skeletal(skeletal)) => Some(skeletal),
+        _ => None,
+    };
+    animations.values()
+        .filter(|animation| is_posed(animation) && animation.entity_id == Some(entity_id))
+        .find_map(|animation| animation.clips.first().and_then(|clip_id| skeletal(clip_id)))
+        .or_else(|| controllers.values()
+            .filter(|controller| controller.state.active && controller.entity_id == Some(entity_id))
+            .filter_map(|controller| controller.current_state.as_ref().and_then(|id| controller.states.get(id)))
+            .filter_map(|state| state.blend_tree.as_ref())
+            .find_map(|tree| tree.clip_ids().into_iter().find_map(skeletal)))
+}
+
+/// Pose de la primera animación de un estado que tenga pose este frame
+fn state_pose<'a>(poses: &'a HashMap<String, SkeletonPose>, animations: &'a [String]) -> Option<(&'a str, &'a SkeletonPose)> {
+    animations.iter().find_map(|id| poses.get(id).map(|pose| (id.as_str(), pose)))
+}
+
 /// Estadísticas del sistema de animaciones
 #[derive(Debug, Clone)]
 pub struct AnimationStats {
@@ -1307,4 +2245,147 @@ pub struct AnimationStats {
     pub active_animations: usize,
     /// Número de animaciones reproduciéndose
     pub playing_animations: usize,
-} 
\ No newline at end of file
+    /// Entidades con paleta de huesos
+    pub skinned_entities: usize,
+    /// Entidades con morph targets activos
+    pub morphed_entities: usize,
+    /// Emisores de partículas
+    pub particle_emitters: usize,
+    /// Partículas vivas
+    pub live_particles: usize,
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform_at(position: [f32; 3]) -> Transform {
+        Transform { position, rotation: [0.0, 0.0, 0.0, 1.0], scale: [1.0; 3] }
+    }
+
+    fn linear_interpolation() -> KeyframeInterpolation {
+        KeyframeInterpolation {
+            interpolation_type: InterpolationType::Linear,
+            tangents: None,
+            easing: EasingConfig { easing_type: EasingType::None, parameters: [0.0; 4] },
+        }
+    }
+
+    /// Clip de caminar en bucle: la raíz avanza 2 m en +Z en 2 s (1 m/s)
+    fn walk_clip() -> AnimationClip {
+        let root = Bone {
+            id: "root".to_string(),
+            name: "Root".to_string(),
+            parent_id: None,
+            local_transform: transform_at([0.0; 3]),
+            world_transform: transform_at([0.0; 3]),
+            influence_config: InfluenceConfig {
+                influence_radius: 1.0,
+                influence_weight: 1.0,
+                falloff_config: FalloffConfig { falloff_type: FalloffType::Linear, falloff_exponent: 1.0 },
+            },
+        };
+        let keyframes = [(0.0, 0.0), (2.0, 2.0)]
+            .into_iter()
+            .map(|(time, z)| TransformKeyframe {
+                time,
+                bone_id: "root".to_string(),
+                transform: transform_at([0.0, 0.0, z]),
+                interpolation: linear_interpolation(),
+            })
+            .collect();
+        AnimationClip {
+            id: "walk_clip".to_string(),
+            name: "Walk Clip".to_string(),
+            clip_type: ClipType::Skeletal,
+            config: ClipConfig {
+                duration: 2.0,
+                fps: 30.0,
+                looped: true,
+                compression: None,
+                optimization: None,
+                rotation_interpolation: RotationInterpolation::Slerp,
+            },
+            data: ClipData::Skeletal(SkeletalData {
+                bones: vec![root],
+                keyframes,
+                constraints: vec![],
+                root_bone_id: Some("root".to_string()),
+                compressed: None,
+            }),
+            state: ClipState {
+                active: true,
+                loaded: true,
+                compiled: false,
+                load_time: 0.0,
+                raw_size: 0,
+                compressed_size: 0,
+            },
+        }
+    }
+
+    fn walk_animation(entity_id: EntityId, speed: f32) -> Animation {
+        Animation {
+            id: "walk".to_string(),
+            name: "Walk".to_string(),
+            animation_type: AnimationType::Skeletal,
+            config: AnimationConfig {
+                duration: 2.0,
+                fps: 30.0,
+                looped: true,
+                interpolation: InterpolationConfig {
+                    interpolation_type: InterpolationType::Linear,
+                    easing: EasingConfig { easing_type: EasingType::None, parameters: [0.0; 4] },
+                    tangents: None,
+                },
+                blending: None,
+                events: vec![],
+                extract_root_motion: true,
+                root_bone_id: None,
+            },
+            clips: vec!["walk_clip".to_string()],
+            entity_id: Some(entity_id),
+            state: AnimationState {
+                active: true,
+                playing: true,
+                paused: false,
+                current_time: 0.0,
+                speed,
+                weight: 1.0,
+            },
+        }
+    }
+
+    async fn walking_system(speed: f32) -> AnimationSystem {
+        let mut system = AnimationSystem::new();
+        system.initialize().await.unwrap();
+        system.create_clip(walk_clip()).await.unwrap();
+        system.create_animation(walk_animation(1, speed)).await.unwrap();
+        system
+    }
+
+    #[tokio::test]
+    async fn root_motion_moves_one_meter_per_second_at_normal_speed() {
+        let mut system = walking_system(1.0).await;
+
+        // Ocho frames de 0.25 s cruzan el final del bucle a los 2 s
+        let mut travelled = 0.0;
+        for _ in 0..8 {
+            system.update(0.25).await.unwrap();
+            let delta = system.get_root_motion_delta(1);
+            assert!((delta.translation.z - 0.25).abs() < 1e-4, "delta {:?}", delta.translation);
+            assert!(delta.translation.x.abs() < 1e-4 && delta.translation.y == 0.0);
+            travelled += delta.translation.z;
+        }
+        assert!((travelled - 2.0).abs() < 1e-3);
+
+        // Sin animación la entidad no se mueve
+        assert_eq!(system.get_root_motion_delta(2).translation, glam::Vec3::ZERO);
+    }
+
+    #[tokio::test]
+    async fn root_motion_scales_with_playback_speed() {
+        let mut system = walking_system(2.0).await;
+        system.update(0.25).await.unwrap();
+        assert!((system.get_root_motion_delta(1).translation.z - 0.5).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,268 @@
+//! # Morph targets
+//!
+//! Pesos de los morph targets (blendshapes) por entidad. Cada frame se
+//! muestrean los `MorphKeyframe` de los clips de morphing activos, respetando
+//! la interpolación y el easing del keyframe de salida, y se quedan los
+//! `MAX_ACTIVE_MORPH_TARGETS` de mayor peso. Si la suma de los pesos supera
+//! 1 se normalizan, de modo que varios targets completos a la vez no
+//! desplacen los vértices más que uno solo.
+//!
+//! El renderer suma los desplazamientos de los targets ponderados por estos
+//! pesos sobre los vértices del mesh, antes del skinning.
+
+use glam::Vec3;
+use std::collections::HashMap;
+
+use super::{EasingConfig, EasingType, InterpolationType, MorphKeyframe, MorphTarget, MorphingData};
+
+/// Targets que deforman como máximo una malla a la vez
+pub const MAX_ACTIVE_MORPH_TARGETS: usize = 8;
+
+/// Pesos por debajo de este valor no deforman la malla
+const MIN_MORPH_WEIGHT: f32 = 1e-4;
+
+/// Peso de un target activo
+#[derive(Debug, Clone, PartialEq)]
+pub struct MorphWeight {
+    /// ID del target (el de `MorphTarget` o el índice del target glTF)
+    pub target_id: String,
+    /// Peso del target
+    pub weight: f32,
+}
+
+/// Targets activos de una entidad en el frame actual
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MorphWeights {
+    /// Pesos ordenados de mayor a menor, como mucho `MAX_ACTIVE_MORPH_TARGETS`
+    pub weights: Vec<MorphWeight>,
+}
+
+impl MorphWeights {
+    /// Quedarse con los targets de mayor peso y normalizar si la suma pasa de 1.
+    /// Los pesos repetidos de un mismo target se suman
+    pub fn from_samples(samples: impl IntoIterator<Item = (String, f32)>) -> Self {
+        let mut merged: HashMap<String, f32> = HashMap::new();
+        for (target_id, weight) in samples {
+            *merged.entry(target_id).or_insert(0.0) += weight;
+        }
+        let mut weights: Vec<MorphWeight> = merged.into_iter()
+            .filter(|(_, weight)| weight.abs() >= MIN_MORPH_WEIGHT)
+            .map(|(target_id, weight)| MorphWeight { target_id, weight })
+            .collect();
+        // El ID desempata para que el orden no dependa del HashMap
+        weights.sort_by(|a, b| {
+            b.weight.abs().total_cmp(&a.weight.abs()).then_with(|| a.target_id.cmp(&b.target_id))
+        });
+        weights.truncate(MAX_ACTIVE_MORPH_TARGETS);
+
+        let total: f32 = weights.iter().map(|weight| weight.weight.abs()).sum();
+        if total > 1.0 {
+            for weight in &mut weights {
+                weight.weight /= total;
+            }
+        }
+        Self { weights }
+    }
+
+    /// Sin targets activos
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    /// Peso de un target (0 si no está activo)
+    pub fn weight(&self, target_id: &str) -> f32 {
+        self.weights.iter()
+            .find(|weight| weight.target_id == target_id)
+            .map_or(0.0, |weight| weight.weight)
+    }
+}
+
+/// Peso de cada target del clip en `time`. Un target sin keyframes pesa 0
+pub fn sample_morph_weights(morphing: &MorphingData, time: f32) -> Vec<(String, f32)> {
+    morphing.targets.iter()
+        .map(|target| (target.id.clone(), sample_target_weight(&morphing.keyframes, &target.id, time)))
+        .collect()
+}
+
+/// Peso de un target interpolando sus keyframes
+fn sample_target_weight(keyframes: &[MorphKeyframe], target_id: &str, time: f32) -> f32 {
+    let mut track: Vec<&MorphKeyframe> = keyframes.iter().filter(|k| k.target_id == target_id).collect();
+    track.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    let next = track.partition_point(|keyframe| keyframe.time <= time);
+    if next == 0 {
+        return track.first().map_or(0.0, |keyframe| keyframe.weight);
+    }
+    if next == track.len() {
+        return track[next - 1].weight;
+    }
+
+    let (a, b) = (track[next - 1], track[next]);
+    let span = b.time - a.time;
+    if span <= f32::EPSILON {
+        return b.weight;
+    }
+    let t = ease((time - a.time) / span, &a.interpolation.easing);
+
+    match &a.interpolation.interpolation_type {
+        InterpolationType::Step => a.weight,
+        InterpolationType::Linear | InterpolationType::Custom(_) => a.weight + (b.weight - a.weight) * t,
+        InterpolationType::Smooth => a.weight + (b.weight - a.weight) * t * t * (3.0 - 2.0 * t),
+        InterpolationType::Bezier => {
+            // Hermite con las pendientes de las tangentes (planas sin tangentes)
+            let out_slope = a.interpolation.tangents.as_ref().map_or(0.0, |tangents| tangents.out_tangent[0]);
+            let in_slope = b.interpolation.tangents.as_ref().map_or(0.0, |tangents| tangents.in_tangent[0]);
+            hermite(a.weight, out_slope * span, b.weight, in_slope * span, t)
+        }
+        InterpolationType::CatmullRom => {
+            let before = if next >= 2 { track[next - 2].weight } else { a.weight };
+            let after = track.get(next + 1).map_or(b.weight, |keyframe| keyframe.weight);
+            hermite(a.weight, (b.weight - before) * 0.5, b.weight, (after - a.weight) * 0.5, t)
+        }
+    }
+}
+
+/// Spline cúbica de Hermite entre `p0` y `p1` con pendientes `m0` y `m1`
+fn hermite(p0: f32, m0: f32, p1: f32, m1: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p0 + (t3 - 2.0 * t2 + t) * m0 + (-2.0 * t3 + 3.0 * t2) * p1 + (t3 - t2) * m1
+}
+
+/// Aplicar una curva de easing al progreso entre dos keyframes
+pub fn ease(t: f32, easing: &EasingConfig) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    match easing.easing_type {
+        EasingType::None | EasingType::Custom(_) => t,
+        EasingType::EaseIn => t * t,
+        EasingType::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+        EasingType::EaseInOut => t * t * (3.0 - 2.0 * t),
+        EasingType::Elastic => {
+            if t <= 0.0 || t >= 1.0 {
+                return t;
+            }
+            let period = if easing.parameters[0] > 0.0 { easing.parameters[0] } else { 0.3 };
+            2f32.powf(-10.0 * t) * ((t - period / 4.0) * std::f32::consts::TAU / period).sin() + 1.0
+        }
+        EasingType::Bounce => bounce(t),
+        EasingType::Back => {
+            let overshoot = if easing.parameters[0] > 0.0 { easing.parameters[0] } else { 1.70158 };
+            t * t * ((overshoot + 1.0) * t - overshoot)
+        }
+    }
+}
+
+/// Rebote al final del recorrido
+fn bounce(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+/// Deformar un vértice en CPU con los targets de un clip, con las mismas
+/// reglas que el renderer. Los vértices del target son desplazamientos
+pub fn morph_vertex(
+    position: Vec3,
+    normal: Vec3,
+    index: usize,
+    targets: &[MorphTarget],
+    weights: &MorphWeights,
+) -> (Vec3, Vec3) {
+    let (mut morphed_position, mut morphed_normal) = (position, normal);
+    for weight in &weights.weights {
+        let Some(target) = targets.iter().find(|target| target.id == weight.target_id) else {
+            continue;
+        };
+        if let Some(delta) = target.vertices.get(index) {
+            morphed_position += Vec3::from(*delta) * weight.weight;
+        }
+        if let Some(delta) = target.normals.get(index) {
+            morphed_normal += Vec3::from(*delta) * weight.weight;
+        }
+    }
+    let morphed_normal = morphed_normal.try_normalize().unwrap_or(normal);
+    (morphed_position, morphed_normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::KeyframeInterpolation;
+
+    /// Keyframe de "sonrisa" con interpolación y easing dados
+    fn keyframe(time: f32, weight: f32, interpolation_type: InterpolationType, easing_type: EasingType) -> MorphKeyframe {
+        MorphKeyframe {
+            time,
+            target_id: "sonrisa".to_string(),
+            weight,
+            interpolation: KeyframeInterpolation {
+                interpolation_type,
+                tangents: None,
+                easing: EasingConfig { easing_type, parameters: [0.0; 4] },
+            },
+        }
+    }
+
+    /// Mayor salto entre muestras consecutivas cada 10 ms de 0 a 2 s
+    fn largest_step(keyframes: &[MorphKeyframe]) -> f32 {
+        let samples: Vec<f32> = (0..=200).map(|i| sample_target_weight(keyframes, "sonrisa", i as f32 * 0.01)).collect();
+        samples.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn weights_animate_smoothly_across_keyframes() {
+        let linear = [
+            keyframe(0.0, 0.0, InterpolationType::Linear, EasingType::None),
+            keyframe(1.0, 1.0, InterpolationType::Linear, EasingType::None),
+            keyframe(2.0, 0.0, InterpolationType::Linear, EasingType::None),
+        ];
+        assert!((sample_target_weight(&linear, "sonrisa", 0.5) - 0.5).abs() < 1e-6);
+        assert!((sample_target_weight(&linear, "sonrisa", 1.0) - 1.0).abs() < 1e-6);
+        assert!((sample_target_weight(&linear, "sonrisa", 1.25) - 0.75).abs() < 1e-6);
+        // Fuera del clip se mantiene el keyframe más cercano
+        assert_eq!(sample_target_weight(&linear, "sonrisa", 5.0), 0.0);
+        assert!(largest_step(&linear) <= 0.0101);
+
+        // Con easing de entrada y salida el peso arranca y llega sin saltos
+        let eased = [
+            keyframe(0.0, 0.0, InterpolationType::Linear, EasingType::EaseInOut),
+            keyframe(1.0, 1.0, InterpolationType::Linear, EasingType::EaseInOut),
+            keyframe(2.0, 0.0, InterpolationType::Smooth, EasingType::None),
+        ];
+        assert!((sample_target_weight(&eased, "sonrisa", 0.5) - 0.5).abs() < 1e-6);
+        assert!(sample_target_weight(&eased, "sonrisa", 0.1) < 0.1);
+        assert!(largest_step(&eased) < 0.02);
+
+        // Step salta de golpe, que es lo que pide
+        let step = [
+            keyframe(0.0, 0.0, InterpolationType::Step, EasingType::None),
+            keyframe(1.0, 1.0, InterpolationType::Step, EasingType::None),
+        ];
+        assert_eq!(sample_target_weight(&step, "sonrisa", 0.99), 0.0);
+        assert_eq!(sample_target_weight(&step, "sonrisa", 1.0), 1.0);
+    }
+
+    #[test]
+    fn sampled_weights_are_normalized_and_capped() {
+        let weights = MorphWeights::from_samples((0..10).map(|i| (format!("target_{}", i), 0.5)));
+        assert_eq!(weights.weights.len(), MAX_ACTIVE_MORPH_TARGETS);
+        let total: f32 = weights.weights.iter().map(|weight| weight.weight).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+
+        // Por debajo de 1 no se tocan
+        let weights = MorphWeights::from_samples([("a".to_string(), 0.3), ("b".to_string(), 0.2)]);
+        assert_eq!(weights.weight("a"), 0.3);
+        assert_eq!(weights.weight("b"), 0.2);
+    }
+}
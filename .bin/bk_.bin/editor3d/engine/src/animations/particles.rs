@@ -0,0 +1,542 @@
+//! # Partículas
+//!
+//! Emisores creados a partir de `ParticleConfig`. Cada emisor guarda sus
+//! partículas como estructura de arrays (posición, velocidad, edad y vida) y
+//! emite de forma continua a `emission_rate` partículas por segundo, en
+//! ráfagas (`burst`) o durante un tiempo con los `ParticleEvent` de las
+//! animaciones. Las partículas mueren al alcanzar su vida.
+//!
+//! Con `ParticleSimulation::Gpu` el emisor solo envejece las partículas: el
+//! vertex shader integra velocidad y aceleración en forma cerrada desde el
+//! estado de emisión. Con `ParticleSimulation::Cpu` (el modo de wasm) el
+//! emisor integra cada paso y el renderer recibe las posiciones finales. En
+//! ambos modos el color y el tamaño salen de las curvas de la configuración,
+//! muestreadas en `CURVE_SAMPLES` puntos para el shader.
+
+use glam::{Vec3, Vec4};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use super::{ColorCurve, EmissionConfig, ParticleConfig, ParticleEvent, SizeCurve};
+use crate::ecs::{ECSSystem, EntityId};
+
+/// Muestras de las curvas de color y tamaño que recibe el shader
+pub const CURVE_SAMPLES: usize = 16;
+
+/// Dónde se integra el movimiento de las partículas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParticleSimulation {
+    /// En el vertex shader, desde el estado de emisión
+    Gpu,
+    /// En el emisor, paso a paso
+    Cpu,
+}
+
+impl Default for ParticleSimulation {
+    fn default() -> Self {
+        if cfg!(target_arch = "wasm32") {
+            Self::Cpu
+        } else {
+            Self::Gpu
+        }
+    }
+}
+
+/// Partícula lista para el renderer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticleInstance {
+    /// Posición (la de emisión con simulación en GPU)
+    pub position: Vec3,
+    /// Velocidad (la de emisión con simulación en GPU)
+    pub velocity: Vec3,
+    /// Edad en segundos
+    pub age: f32,
+    /// Vida en segundos
+    pub lifetime: f32,
+}
+
+/// Partículas de un emisor para un frame
+#[derive(Debug, Clone)]
+pub struct ParticleBatch {
+    /// ID del emisor
+    pub emitter_id: String,
+    /// Dónde se integra el movimiento
+    pub simulation: ParticleSimulation,
+    /// Aceleración constante
+    pub acceleration: Vec3,
+    /// Color a lo largo de la vida
+    pub colors: [Vec4; CURVE_SAMPLES],
+    /// Tamaño a lo largo de la vida
+    pub sizes: [f32; CURVE_SAMPLES],
+    /// Distancia en la que las partículas se desvanecen al acercarse a la
+    /// geometría (0 desactiva las partículas suaves)
+    pub soft_distance: f32,
+    /// Partículas vivas
+    pub particles: Vec<ParticleInstance>,
+}
+
+impl ParticleBatch {
+    /// Posición de una partícula en su edad actual
+    pub fn position(&self, particle: &ParticleInstance) -> Vec3 {
+        match self.simulation {
+            ParticleSimulation::Cpu => particle.position,
+            ParticleSimulation::Gpu => {
+                particle.position + particle.velocity * particle.age
+                    + self.acceleration * (0.5 * particle.age * particle.age)
+            }
+        }
+    }
+}
+
+/// Emisión temporal lanzada por un evento
+#[derive(Debug, Clone)]
+struct TimedEmission {
+    /// Partículas por segundo
+    rate: f32,
+    /// Segundos restantes
+    remaining: f32,
+    /// Fracción de partícula pendiente
+    accumulator: f32,
+}
+
+/// Emisor de partículas
+#[derive(Debug, Clone)]
+pub struct ParticleEmitter {
+    /// ID del emisor (el `particle_system_id` de los eventos)
+    pub id: String,
+    /// Configuración
+    pub config: ParticleConfig,
+    /// Origen de las partículas en mundo
+    pub position: Vec3,
+    /// Entidad a la que sigue el origen
+    pub entity_id: Option<EntityId>,
+    /// Dónde se integra el movimiento
+    pub simulation: ParticleSimulation,
+    /// Emisión continua a `config.emission_rate`
+    pub emitting: bool,
+    /// Posiciones
+    positions: Vec<Vec3>,
+    /// Velocidades
+    velocities: Vec<Vec3>,
+    /// Edades
+    ages: Vec<f32>,
+    /// Vidas
+    lifetimes: Vec<f32>,
+    /// Fracción de partícula pendiente de la emisión continua
+    accumulator: f32,
+    /// Emisiones de eventos en curso
+    emissions: Vec<TimedEmission>,
+    /// Estado del generador aleatorio
+    rng: u64,
+}
+
+impl ParticleEmitter {
+    /// Crear emisor en una posición. Emite de forma continua si
+    /// `emission_rate` es positiva
+    pub fn new(id: &str, config: ParticleConfig, position: Vec3) -> Self {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        Self {
+            id: id.to_string(),
+            emitting: config.emission_rate > 0.0,
+            config,
+            position,
+            entity_id: None,
+            simulation: ParticleSimulation::default(),
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            ages: Vec::new(),
+            lifetimes: Vec::new(),
+            accumulator: 0.0,
+            emissions: Vec::new(),
+            rng: hasher.finish() | 1,
+        }
+    }
+
+    /// Partículas vivas
+    pub fn live_particles(&self) -> usize {
+        self.ages.len()
+    }
+
+    /// Quitar todas las partículas y emisiones pendientes
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.velocities.clear();
+        self.ages.clear();
+        self.lifetimes.clear();
+        self.accumulator = 0.0;
+        self.emissions.clear();
+    }
+
+    /// Avanzar `delta_time`: envejecer (e integrar en CPU), retirar las
+    /// partículas que alcanzan su vida y emitir las nuevas
+    pub fn update(&mut self, delta_time: f32) {
+        if delta_time <= 0.0 {
+            return;
+        }
+
+        let acceleration = Vec3::from(self.config.velocity_config.acceleration);
+        for age in &mut self.ages {
+            *age += delta_time;
+        }
+        if self.simulation == ParticleSimulation::Cpu {
+            for (position, velocity) in self.positions.iter_mut().zip(&mut self.velocities) {
+                *velocity += acceleration * delta_time;
+                *position += *velocity * delta_time;
+            }
+        }
+
+        for index in (0..self.ages.len()).rev() {
+            if self.ages[index] >= self.lifetimes[index] {
+                self.positions.swap_remove(index);
+                self.velocities.swap_remove(index);
+                self.ages.swap_remove(index);
+                self.lifetimes.swap_remove(index);
+            }
+        }
+
+        if self.emitting {
+            self.accumulator += self.config.emission_rate.max(0.0) * delta_time;
+            let count = self.accumulator.floor();
+            self.accumulator -= count;
+            self.emit(count as u32, delta_time);
+        }
+
+        let mut emissions = std::mem::take(&mut self.emissions);
+        for emission in &mut emissions {
+            let active = emission.remaining.min(delta_time);
+            emission.remaining -= delta_time;
+            emission.accumulator += emission.rate * active;
+            let count = emission.accumulator.floor();
+            emission.accumulator -= count;
+            self.emit(count as u32, active);
+        }
+        emissions.retain(|emission| emission.remaining > 0.0);
+        self.emissions = emissions;
+    }
+
+    /// Emitir `count` partículas de golpe. Devuelve las emitidas (menos si
+    /// se alcanza `max_particles`)
+    pub fn burst(&mut self, count: u32) -> u32 {
+        self.emit(count, 0.0)
+    }
+
+    /// Lanzar la emisión de un evento: una ráfaga de `particle_count` y,
+    /// con tasa y duración, emisión durante `emission_duration` segundos.
+    /// Devuelve las partículas de la ráfaga
+    pub fn trigger(&mut self, emission: &EmissionConfig) -> u32 {
+        let emitted = self.burst(emission.particle_count);
+        if emission.emission_rate > 0.0 && emission.emission_duration > 0.0 {
+            self.emissions.push(TimedEmission {
+                rate: emission.emission_rate,
+                remaining: emission.emission_duration,
+                accumulator: 0.0,
+            });
+        }
+        emitted
+    }
+
+    /// Emitir `count` partículas repartidas a lo largo de los últimos
+    /// `span` segundos, para que la emisión continua no salga a saltos
+    fn emit(&mut self, count: u32, span: f32) -> u32 {
+        let capacity = (self.config.max_particles as usize).saturating_sub(self.ages.len());
+        let count = (count as usize).min(capacity);
+        let lifetime = self.config.particle_lifetime.max(0.0);
+        if lifetime <= 0.0 {
+            return 0;
+        }
+
+        let velocity_config = &self.config.velocity_config;
+        let initial = Vec3::from(velocity_config.initial_velocity);
+        let variation = Vec3::from(velocity_config.velocity_variation);
+        let acceleration = Vec3::from(velocity_config.acceleration);
+        for i in 0..count {
+            let jitter = Vec3::new(self.next_signed(), self.next_signed(), self.next_signed());
+            let velocity = initial + variation * jitter;
+            let age = (span * (count - i) as f32 / count as f32 - span / (2.0 * count as f32)).max(0.0);
+            // En CPU la partícula empieza ya integrada hasta su edad
+            let (position, velocity) = match self.simulation {
+                ParticleSimulation::Gpu => (self.position, velocity),
+                ParticleSimulation::Cpu => (
+                    self.position + velocity * age + acceleration * (0.5 * age * age),
+                    velocity + acceleration * age,
+                ),
+            };
+            self.positions.push(position);
+            self.velocities.push(velocity);
+            self.ages.push(age);
+            self.lifetimes.push(lifetime);
+        }
+        count as u32
+    }
+
+    /// Número aleatorio en [-1, 1] (xorshift64*)
+    fn next_signed(&mut self) -> f32 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40;
+        bits as f32 / (1u64 << 24) as f32 * 2.0 - 1.0
+    }
+
+    /// Color en una fracción de la vida (0 al nacer, 1 al morir)
+    pub fn color_at(&self, t: f32) -> Vec4 {
+        let config = &self.config.color_config;
+        let t = t.clamp(0.0, 1.0);
+        let (initial, last) = (Vec4::from(config.initial_color), Vec4::from(config.final_color));
+        match &config.color_curve {
+            ColorCurve::Linear => initial.lerp(last, t),
+            ColorCurve::EaseIn => initial.lerp(last, ease_in(t)),
+            ColorCurve::EaseOut => initial.lerp(last, ease_out(t)),
+            ColorCurve::EaseInOut => initial.lerp(last, ease_in_out(t)),
+            ColorCurve::Custom(stops) => {
+                let stops: Vec<Vec4> = stops.iter().copied().map(Vec4::from).collect();
+                sample_stops(&stops, t).unwrap_or_else(|| initial.lerp(last, t))
+            }
+        }
+    }
+
+    /// Tamaño en una fracción de la vida
+    pub fn size_at(&self, t: f32) -> f32 {
+        let config = &self.config.size_config;
+        let t = t.clamp(0.0, 1.0);
+        let lerp = |factor: f32| config.initial_size + (config.final_size - config.initial_size) * factor;
+        match &config.size_curve {
+            SizeCurve::Linear => lerp(t),
+            SizeCurve::EaseIn => lerp(ease_in(t)),
+            SizeCurve::EaseOut => lerp(ease_out(t)),
+            SizeCurve::EaseInOut => lerp(ease_in_out(t)),
+            SizeCurve::Custom(stops) => {
+                let stops: Vec<Vec4> = stops.iter().map(|&size| Vec4::splat(size)).collect();
+                sample_stops(&stops, t).map_or_else(|| lerp(t), |size| size.x)
+            }
+        }
+    }
+
+    /// Partículas vivas con las curvas muestreadas
+    pub fn batch(&self) -> ParticleBatch {
+        let sample = |i: usize| i as f32 / (CURVE_SAMPLES - 1) as f32;
+        ParticleBatch {
+            emitter_id: self.id.clone(),
+            simulation: self.simulation,
+            acceleration: Vec3::from(self.config.velocity_config.acceleration),
+            colors: std::array::from_fn(|i| self.color_at(sample(i))),
+            sizes: std::array::from_fn(|i| self.size_at(sample(i))),
+            soft_distance: self.config.soft_distance,
+            particles: (0..self.ages.len())
+                .map(|i| ParticleInstance {
+                    position: self.positions[i],
+                    velocity: self.velocities[i],
+                    age: self.ages[i],
+                    lifetime: self.lifetimes[i],
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Emisores registrados por ID
+#[derive(Debug, Clone, Default)]
+pub struct ParticleSystem {
+    /// Emisores
+    emitters: HashMap<String, ParticleEmitter>,
+}
+
+impl ParticleSystem {
+    /// Crear sistema vacío
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registrar un emisor (reemplaza al del mismo ID)
+    pub fn add_emitter(&mut self, emitter: ParticleEmitter) {
+        self.emitters.insert(emitter.id.clone(), emitter);
+    }
+
+    /// Quitar un emisor
+    pub fn remove_emitter(&mut self, id: &str) -> Option<ParticleEmitter> {
+        self.emitters.remove(id)
+    }
+
+    /// Emisor por ID
+    pub fn get(&self, id: &str) -> Option<&ParticleEmitter> {
+        self.emitters.get(id)
+    }
+
+    /// Emisor por ID (mutable)
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut ParticleEmitter> {
+        self.emitters.get_mut(id)
+    }
+
+    /// Número de emisores
+    pub fn len(&self) -> usize {
+        self.emitters.len()
+    }
+
+    /// No hay emisores
+    pub fn is_empty(&self) -> bool {
+        self.emitters.is_empty()
+    }
+
+    /// Partículas vivas de todos los emisores
+    pub fn live_particles(&self) -> usize {
+        self.emitters.values().map(ParticleEmitter::live_particles).sum()
+    }
+
+    /// Lanzar un evento de partículas en su emisor. Devuelve las partículas
+    /// de la ráfaga (None si el emisor no existe)
+    pub fn trigger(&mut self, event: &ParticleEvent) -> Option<u32> {
+        let emitter = self.emitters.get_mut(&event.particle_system_id)?;
+        Some(emitter.trigger(&event.emission_config))
+    }
+
+    /// Avanzar todos los emisores
+    pub fn update(&mut self, delta_time: f32) {
+        for emitter in self.emitters.values_mut() {
+            emitter.update(delta_time);
+        }
+    }
+
+    /// Llevar el origen de los emisores con entidad a su posición en mundo
+    pub fn follow_entities(&mut self, world: &ECSSystem) {
+        for emitter in self.emitters.values_mut() {
+            if let Some(entity_id) = emitter.entity_id {
+                emitter.position = super::skinning::entity_world_matrix(world, entity_id).w_axis.truncate();
+            }
+        }
+    }
+
+    /// Lotes de los emisores con partículas vivas
+    pub fn batches(&self) -> Vec<ParticleBatch> {
+        self.emitters.values()
+            .filter(|emitter| emitter.live_particles() > 0)
+            .map(ParticleEmitter::batch)
+            .collect()
+    }
+
+    /// Quitar todos los emisores
+    pub fn clear(&mut self) {
+        self.emitters.clear();
+    }
+}
+
+/// Curva que empieza despacio
+fn ease_in(t: f32) -> f32 {
+    t * t
+}
+
+/// Curva que termina despacio
+fn ease_out(t: f32) -> f32 {
+    1.0 - (1.0 - t) * (1.0 - t)
+}
+
+/// Curva que empieza y termina despacio
+fn ease_in_out(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Interpolación lineal entre valores repartidos uniformemente a lo largo
+/// de la vida (None sin valores)
+fn sample_stops(stops: &[Vec4], t: f32) -> Option<Vec4> {
+    match stops {
+        [] => None,
+        [single] => Some(*single),
+        _ => {
+            let position = t * (stops.len() - 1) as f32;
+            let index = (position.floor() as usize).min(stops.len() - 2);
+            Some(stops[index].lerp(stops[index + 1], position - index as f32))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::{ColorConfig, SizeConfig, VelocityConfig};
+
+    /// Configuración de humo: sube a 1 m/s con algo de variación
+    fn config(emission_rate: f32, particle_lifetime: f32) -> ParticleConfig {
+        ParticleConfig {
+            max_particles: 1000,
+            emission_rate,
+            particle_lifetime,
+            velocity_config: VelocityConfig {
+                initial_velocity: [0.0, 1.0, 0.0],
+                velocity_variation: [0.2, 0.2, 0.2],
+                acceleration: [0.0, -0.5, 0.0],
+            },
+            color_config: ColorConfig {
+                initial_color: [1.0, 1.0, 1.0, 1.0],
+                final_color: [1.0, 1.0, 1.0, 0.0],
+                color_curve: ColorCurve::Linear,
+            },
+            size_config: SizeConfig {
+                initial_size: 0.1,
+                final_size: 0.5,
+                size_curve: SizeCurve::Linear,
+            },
+            soft_distance: 0.5,
+        }
+    }
+
+    #[test]
+    fn continuous_rate_gives_the_expected_live_count() {
+        for simulation in [ParticleSimulation::Gpu, ParticleSimulation::Cpu] {
+            let mut emitter = ParticleEmitter::new("humo", config(100.0, 2.0), Vec3::ZERO);
+            emitter.simulation = simulation;
+            for _ in 0..60 {
+                emitter.update(1.0 / 60.0);
+            }
+            let live = emitter.live_particles();
+            assert!((98..=102).contains(&live), "{:?}: {} partículas vivas", simulation, live);
+        }
+    }
+
+    #[test]
+    fn particles_die_at_their_lifetime() {
+        let mut emitter = ParticleEmitter::new("chispas", config(0.0, 0.5), Vec3::ZERO);
+        assert!(!emitter.emitting);
+        assert_eq!(emitter.burst(10), 10);
+
+        // Pasos exactos en binario: la edad llega justo a la vida
+        for _ in 0..3 {
+            emitter.update(0.125);
+            assert_eq!(emitter.live_particles(), 10);
+        }
+        emitter.update(0.125);
+        assert_eq!(emitter.live_particles(), 0);
+
+        // Con emisión continua el número de vivas se estabiliza en
+        // tasa * vida
+        let mut emitter = ParticleEmitter::new("chispas", config(100.0, 0.5), Vec3::ZERO);
+        for _ in 0..120 {
+            emitter.update(1.0 / 60.0);
+            assert!(emitter.live_particles() <= 52);
+        }
+        assert!(emitter.live_particles() >= 48);
+        assert!(emitter.batch().particles.iter().all(|particle| particle.age < particle.lifetime));
+    }
+
+    #[test]
+    fn burst_event_adds_exactly_particle_count() {
+        let mut system = ParticleSystem::new();
+        system.add_emitter(ParticleEmitter::new("fuente", config(100.0, 2.0), Vec3::ZERO));
+        for _ in 0..30 {
+            system.update(1.0 / 60.0);
+        }
+        let before = system.live_particles();
+
+        let event = ParticleEvent {
+            particle_system_id: "fuente".to_string(),
+            emission_config: EmissionConfig { particle_count: 25, emission_rate: 0.0, emission_duration: 0.0 },
+        };
+        assert_eq!(system.trigger(&event), Some(25));
+        assert_eq!(system.live_particles(), before + 25);
+
+        let unknown = ParticleEvent { particle_system_id: "otra".to_string(), ..event };
+        assert_eq!(system.trigger(&unknown), None);
+        assert_eq!(system.live_particles(), before + 25);
+    }
+}
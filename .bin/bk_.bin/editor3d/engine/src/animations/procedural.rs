@@ -0,0 +1,471 @@
+//! # Animación procedural
+//!
+//! Los modificadores procedurales retocan la pose de una entidad después de
+//! la máquina de estados y de las capas, justo antes de calcular las paletas
+//! de huesos. Se aplican en el orden en que se añadieron, cada uno sobre el
+//! resultado del anterior y mezclado con su peso:
+//!
+//! - `Noise`: gira los huesos con ruido fractal (Perlin o Simplex con las
+//!   octavas de `NoiseConfig`). La amplitud está en radianes y cada hueso y
+//!   eje usa su propia semilla, así que no tiemblan al unísono.
+//! - `Wave`: oscilación senoidal alrededor del eje X del hueso.
+//! - `Spring`: el hueso persigue la rotación de la pose con un muelle
+//!   amortiguado (`stiffness`, `damping`); sin `damping` el muelle es
+//!   críticamente amortiguado.
+//! - `LookAt`: orienta los huesos hacia una entidad con los límites de yaw y
+//!   pitch de `ConstraintLimits`, repartiendo el giro a lo largo de la cadena.
+//!
+//! `Pendulum` y los procedimientos `Custom` todavía no se evalúan.
+
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use super::sampling::SkeletonPose;
+use super::skinning;
+use super::{ConstraintLimits, NoiseConfig, NoiseType, ProceduralData, ProcedureType, SkeletalData};
+use crate::ecs::EntityId;
+
+/// Paso máximo de la integración de los muelles, en segundos
+pub const SPRING_SUBSTEP: f32 = 1.0 / 120.0;
+
+/// Rigidez por defecto de los muelles
+pub const DEFAULT_STIFFNESS: f32 = 100.0;
+
+/// Modificador procedural sobre algunos huesos de una entidad
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProceduralModifier {
+    /// ID del modificador
+    pub id: String,
+    /// Huesos afectados (ID o nombre), de la raíz hacia las puntas
+    pub bones: Vec<String>,
+    /// Peso de la mezcla con la pose (0..1)
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    /// Nodo que evalúa
+    pub node: ProceduralNode,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// Nodo de un modificador procedural
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ProceduralNode {
+    /// Procedimiento de `ProceduralData`
+    Procedure(ProceduralData),
+    /// Mirar hacia una entidad
+    LookAt(LookAtConfig),
+}
+
+/// Configuración de un nodo look-at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LookAtConfig {
+    /// Entidad a la que mirar
+    pub target: EntityId,
+    /// Dirección hacia la que mira el hueso en su espacio local
+    #[serde(default = "default_forward")]
+    pub forward: [f32; 3],
+    /// Límites de giro (`rotation_limits`, en grados)
+    #[serde(default)]
+    pub limits: Option<ConstraintLimits>,
+}
+
+fn default_forward() -> [f32; 3] {
+    [0.0, 0.0, 1.0]
+}
+
+/// Estado de un muelle de rotación
+#[derive(Debug, Clone, Copy)]
+struct SpringState {
+    /// Rotación actual
+    rotation: Quat,
+    /// Velocidad angular (eje por radianes por segundo)
+    velocity: Vec3,
+}
+
+/// Modificadores de una entidad con su estado entre frames
+#[derive(Debug, Clone, Default)]
+pub struct ProceduralStack {
+    /// Modificadores en orden de aplicación
+    pub modifiers: Vec<ProceduralModifier>,
+    /// Muelles por modificador y hueso
+    springs: HashMap<(String, usize), SpringState>,
+    /// Tiempo acumulado para el ruido y las ondas
+    time: f32,
+}
+
+impl ProceduralStack {
+    /// Añade un modificador o reemplaza al del mismo ID
+    pub fn insert(&mut self, modifier: ProceduralModifier) {
+        self.springs.retain(|(id, _), _| id != &modifier.id);
+        match self.modifiers.iter_mut().find(|existing| existing.id == modifier.id) {
+            Some(existing) => *existing = modifier,
+            None => self.modifiers.push(modifier),
+        }
+    }
+
+    /// Quita un modificador
+    pub fn remove(&mut self, modifier_id: &str) -> bool {
+        self.springs.retain(|(id, _), _| id != modifier_id);
+        let count = self.modifiers.len();
+        self.modifiers.retain(|modifier| modifier.id != modifier_id);
+        self.modifiers.len() != count
+    }
+
+    /// Aplica los modificadores a la pose. `entity_world` es la matriz mundo
+    /// de la entidad y `target_position` da la posición mundo de las
+    /// entidades que miran los nodos look-at
+    pub fn apply(
+        &mut self,
+        pose: &mut SkeletonPose,
+        skeletal: &SkeletalData,
+        entity_world: Mat4,
+        delta_time: f32,
+        target_position: impl Fn(EntityId) -> Option<Vec3>,
+    ) {
+        self.time += delta_time.max(0.0);
+        let time = self.time;
+
+        for modifier in &self.modifiers {
+            let weight = modifier.weight.clamp(0.0, 1.0);
+            if weight <= 0.0 {
+                continue;
+            }
+            let bones: Vec<usize> = modifier.bones.iter()
+                .filter_map(|name| skeletal.bones.iter().position(|bone| &bone.id == name || &bone.name == name))
+                .filter(|&index| index < pose.bones.len())
+                .collect();
+            if bones.is_empty() {
+                continue;
+            }
+
+            match &modifier.node {
+                ProceduralNode::Procedure(data) => match data.procedure_type {
+                    ProcedureType::Noise => apply_noise(pose, &bones, data, time, weight),
+                    ProcedureType::Wave => apply_wave(pose, &bones, data, time, weight),
+                    ProcedureType::Spring => {
+                        for &bone in &bones {
+                            let state = self.springs.entry((modifier.id.clone(), bone)).or_insert(SpringState {
+                                rotation: pose.bones[bone].rotation,
+                                velocity: Vec3::ZERO,
+                            });
+                            let target = pose.bones[bone].rotation;
+                            step_spring(state, target, data, delta_time);
+                            pose.bones[bone].rotation = target.slerp(state.rotation, weight).normalize();
+                        }
+                    }
+                    ProcedureType::Pendulum | ProcedureType::Custom(_) => {}
+                },
+                ProceduralNode::LookAt(config) => {
+                    if let Some(target) = target_position(config.target) {
+                        apply_look_at(pose, skeletal, &bones, config, entity_world, target, weight);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parámetro de un procedimiento con valor por defecto
+fn parameter(data: &ProceduralData, name: &str, default: f32) -> f32 {
+    data.parameters.get(name).copied().unwrap_or(default)
+}
+
+/// Ruido: cada eje del hueso gira `amplitude * ruido(t * frequency + phase)`
+/// radianes, escalado por los parámetros `axis_x`, `axis_y` y `axis_z`
+/// (1 por defecto). `seed` desplaza las semillas
+fn apply_noise(pose: &mut SkeletonPose, bones: &[usize], data: &ProceduralData, time: f32, weight: f32) {
+    let config = &data.config;
+    let axes = Vec3::new(
+        parameter(data, "axis_x", 1.0),
+        parameter(data, "axis_y", 1.0),
+        parameter(data, "axis_z", 1.0),
+    );
+    let seed = parameter(data, "seed", 0.0) as u32;
+    let x = time * config.frequency + config.phase;
+
+    for &bone in bones {
+        let angle = |axis: u32| {
+            let seed = seed.wrapping_add((bone as u32).wrapping_mul(3)).wrapping_add(axis);
+            fractal_noise(config.noise_config.as_ref(), x, seed) * config.amplitude * weight
+        };
+        let angles = Vec3::new(angle(0), angle(1), angle(2)) * axes;
+        let offset = Quat::from_euler(EulerRot::XYZ, angles.x, angles.y, angles.z);
+        pose.bones[bone].rotation = (pose.bones[bone].rotation * offset).normalize();
+    }
+}
+
+/// Onda: giro senoidal de `amplitude` radianes alrededor de los ejes
+/// `axis_x` (1 por defecto), `axis_y` y `axis_z` (0 por defecto)
+fn apply_wave(pose: &mut SkeletonPose, bones: &[usize], data: &ProceduralData, time: f32, weight: f32) {
+    let config = &data.config;
+    let axes = Vec3::new(
+        parameter(data, "axis_x", 1.0),
+        parameter(data, "axis_y", 0.0),
+        parameter(data, "axis_z", 0.0),
+    );
+    let angle = (std::f32::consts::TAU * config.frequency * time + config.phase).sin() * config.amplitude * weight;
+    let angles = axes * angle;
+    let offset = Quat::from_euler(EulerRot::XYZ, angles.x, angles.y, angles.z);
+    for &bone in bones {
+        pose.bones[bone].rotation = (pose.bones[bone].rotation * offset).normalize();
+    }
+}
+
+/// Avanza un muelle hacia `target` con Euler semi-implícito en subpasos de
+/// como mucho `SPRING_SUBSTEP`. Con rigidez k y amortiguamiento c el factor
+/// de amortiguamiento es ζ = c / (2√k) y el muelle se asienta en unos
+/// 4 / (ζ·√k) segundos
+fn step_spring(state: &mut SpringState, target: Quat, data: &ProceduralData, delta_time: f32) {
+    let stiffness = parameter(data, "stiffness", DEFAULT_STIFFNESS).max(0.0);
+    let damping = parameter(data, "damping", 2.0 * stiffness.sqrt()).max(0.0);
+    if delta_time <= 0.0 {
+        return;
+    }
+
+    let steps = (delta_time / SPRING_SUBSTEP).ceil().max(1.0) as u32;
+    let h = delta_time / steps as f32;
+    for _ in 0..steps {
+        // Error como giro más corto de la rotación actual a la objetivo
+        let mut error = target * state.rotation.inverse();
+        if error.w < 0.0 {
+            error = -error;
+        }
+        let error = error.to_scaled_axis();
+        state.velocity += (error * stiffness - state.velocity * damping) * h;
+        state.rotation = (Quat::from_scaled_axis(state.velocity * h) * state.rotation).normalize();
+    }
+}
+
+/// Orienta la cadena hacia `target` (posición mundo). Cada hueso se lleva
+/// una parte igual del giro que falta y el último lo completa; los giros
+/// de cada hueso se recortan a los límites de yaw y pitch
+fn apply_look_at(
+    pose: &mut SkeletonPose,
+    skeletal: &SkeletalData,
+    bones: &[usize],
+    config: &LookAtConfig,
+    entity_world: Mat4,
+    target: Vec3,
+    weight: f32,
+) {
+    let forward = Vec3::from(config.forward).normalize_or_zero();
+    if forward == Vec3::ZERO {
+        return;
+    }
+    // Giro que lleva `forward` a +Z para medir yaw y pitch
+    let align = Quat::from_rotation_arc(forward, Vec3::Z);
+    let limits = config.limits.as_ref().and_then(|limits| limits.rotation_limits.as_ref());
+    let parents: Vec<Option<usize>> = skeletal.bones.iter()
+        .map(|bone| bone.parent_id.as_deref().and_then(|parent| skeletal.bones.iter().position(|other| other.id == parent)))
+        .collect();
+
+    for (i, &bone) in bones.iter().enumerate() {
+        let locals: Vec<Mat4> = pose.bones.iter().map(|bone| bone.to_mat4()).collect();
+        let models = skinning::compose_hierarchy(&skeletal.bones, &locals);
+        let parent_world = entity_world * parents[bone].map_or(Mat4::IDENTITY, |parent| models[parent]);
+
+        // Dirección al objetivo en el espacio local actual del hueso
+        let local = pose.bones[bone];
+        let in_parent = parent_world.inverse().transform_point3(target) - local.translation;
+        let direction = align * (local.rotation.inverse() * in_parent);
+        if direction.length_squared() <= f32::EPSILON {
+            continue;
+        }
+
+        let share = 1.0 / (bones.len() - i) as f32;
+        let mut yaw = direction.x.atan2(direction.z) * share;
+        let mut pitch = (-direction.y).atan2(direction.x.hypot(direction.z)) * share;
+        if let Some(limits) = limits {
+            yaw = clamp_degrees(yaw, limits.yaw_limits);
+            pitch = clamp_degrees(pitch, limits.pitch_limits);
+        }
+
+        let aligned = Quat::from_rotation_y(yaw) * Quat::from_rotation_x(pitch);
+        let offset = Quat::IDENTITY.slerp(align.inverse() * aligned * align, weight);
+        pose.bones[bone].rotation = (local.rotation * offset).normalize();
+    }
+}
+
+/// Recorta un ángulo en radianes a unos límites en grados
+fn clamp_degrees(angle: f32, limits: [f32; 2]) -> f32 {
+    let (min, max) = (limits[0].min(limits[1]), limits[0].max(limits[1]));
+    angle.clamp(min.to_radians(), max.to_radians())
+}
+
+/// Ruido fractal en [-1, 1]: suma de octavas normalizada por sus amplitudes
+pub fn fractal_noise(config: Option<&NoiseConfig>, x: f32, seed: u32) -> f32 {
+    let (simplex, octaves, persistence, lacunarity) = match config {
+        Some(config) => (
+            matches!(config.noise_type, NoiseType::Simplex),
+            config.octaves.clamp(1, 8),
+            config.persistence,
+            config.lacunarity,
+        ),
+        None => (false, 1, 0.5, 2.0),
+    };
+
+    let mut sum = 0.0;
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    for octave in 0..octaves {
+        let seed = seed.wrapping_add(octave.wrapping_mul(0x9E37_79B9));
+        let value = if simplex { simplex_noise(x * frequency, seed) } else { perlin_noise(x * frequency, seed) };
+        sum += value * amplitude;
+        total += amplitude;
+        amplitude *= persistence;
+        frequency *= lacunarity;
+    }
+    if total > 0.0 { (sum / total).clamp(-1.0, 1.0) } else { 0.0 }
+}
+
+/// Ruido de Perlin 1D en [-1, 1]
+pub fn perlin_noise(x: f32, seed: u32) -> f32 {
+    let cell = x.floor();
+    let f = x - cell;
+    let i = cell as i32;
+    let a = gradient(i, seed) * f;
+    let b = gradient(i.wrapping_add(1), seed) * (f - 1.0);
+    let fade = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    // Un gradiente 1D alcanza como mucho 0.5 entre dos puntos de la red
+    ((a + (b - a) * fade) * 2.0).clamp(-1.0, 1.0)
+}
+
+/// Ruido simplex 1D en [-1, 1]
+pub fn simplex_noise(x: f32, seed: u32) -> f32 {
+    let cell = x.floor();
+    let i = cell as i32;
+    let contribution = |point: i32, distance: f32| {
+        let t = 1.0 - distance * distance;
+        if t <= 0.0 { 0.0 } else { t.powi(4) * gradient(point, seed) * distance }
+    };
+    let f = x - cell;
+    // 0.395 lleva el máximo teórico (≈2.53) a 1
+    ((contribution(i, f) + contribution(i.wrapping_add(1), f - 1.0)) * 0.395).clamp(-1.0, 1.0)
+}
+
+/// Gradiente pseudoaleatorio en [-1, 1] de un punto de la red
+fn gradient(point: i32, seed: u32) -> f32 {
+    let mut hash = (point as u32).wrapping_mul(0x27D4_EB2D) ^ seed.wrapping_mul(0x1656_67B1);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x85EB_CA6B);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xC2B2_AE35);
+    hash ^= hash >> 16;
+    (hash & 0xFFFF) as f32 / 32767.5 - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::{
+        Bone, FalloffConfig, FalloffType, InfluenceConfig, ProcedureConfig, RotationLimits, Transform,
+    };
+    use crate::animations::sampling::BonePose;
+
+    const FRAME: f32 = 1.0 / 60.0;
+
+    fn skeleton() -> SkeletalData {
+        let rest = || Transform { position: [0.0; 3], rotation: [0.0, 0.0, 0.0, 1.0], scale: [1.0; 3] };
+        let bone = Bone {
+            id: "head".to_string(),
+            name: "head".to_string(),
+            parent_id: None,
+            local_transform: rest(),
+            world_transform: rest(),
+            influence_config: InfluenceConfig {
+                influence_radius: 1.0,
+                influence_weight: 1.0,
+                falloff_config: FalloffConfig { falloff_type: FalloffType::Linear, falloff_exponent: 1.0 },
+            },
+        };
+        SkeletalData { bones: vec![bone], keyframes: vec![], constraints: vec![], root_bone_id: None, compressed: None }
+    }
+
+    /// Pose del único hueso con un giro alrededor de Y
+    fn pose(rotation: Quat) -> SkeletonPose {
+        SkeletonPose { bones: vec![BonePose { rotation, ..BonePose::IDENTITY }] }
+    }
+
+    fn modifier(node: ProceduralNode) -> ProceduralModifier {
+        ProceduralModifier { id: "test".to_string(), bones: vec!["head".to_string()], weight: 1.0, node }
+    }
+
+    fn spring(stiffness: f32, damping: f32) -> ProceduralModifier {
+        modifier(ProceduralNode::Procedure(ProceduralData {
+            procedure_type: ProcedureType::Spring,
+            config: ProcedureConfig { frequency: 0.0, amplitude: 0.0, phase: 0.0, noise_config: None },
+            parameters: HashMap::from([("stiffness".to_string(), stiffness), ("damping".to_string(), damping)]),
+        }))
+    }
+
+    #[test]
+    fn spring_settles_on_a_step_target_in_the_analytic_time() {
+        // k = 100 y ζ = 0.5: se asienta al 2% en 4 / (ζ·√k) = 0.8 s
+        let (stiffness, zeta) = (100.0f32, 0.5f32);
+        let settle_time = 4.0 / (zeta * stiffness.sqrt());
+        let skeletal = skeleton();
+        let mut stack = ProceduralStack::default();
+        stack.insert(spring(stiffness, 2.0 * zeta * stiffness.sqrt()));
+        stack.apply(&mut pose(Quat::IDENTITY), &skeletal, Mat4::IDENTITY, FRAME, |_| None);
+
+        // Escalón de 60° en la pose
+        let target = Quat::from_rotation_y(60f32.to_radians());
+        let step = 60f32.to_radians();
+        let mut max_error_before = 0.0f32;
+        let mut max_error_after = 0.0f32;
+        let frames = (2.0 * settle_time / FRAME).round() as u32;
+        for frame in 1..=frames {
+            let mut current = pose(target);
+            stack.apply(&mut current, &skeletal, Mat4::IDENTITY, FRAME, |_| None);
+            let error = current.bones[0].rotation.angle_between(target) / step;
+            if frame as f32 * FRAME < 0.5 * settle_time {
+                max_error_before = max_error_before.max(error);
+            } else if frame as f32 * FRAME >= settle_time {
+                max_error_after = max_error_after.max(error);
+            }
+        }
+
+        assert!(max_error_before > 0.1, "el muelle saltó al objetivo: {}", max_error_before);
+        assert!(max_error_after < 0.03, "sin asentar tras {} s: {}", settle_time, max_error_after);
+    }
+
+    #[test]
+    fn look_at_clamps_at_its_yaw_limit_when_the_target_passes_behind() {
+        let skeletal = skeleton();
+        let mut stack = ProceduralStack::default();
+        stack.insert(modifier(ProceduralNode::LookAt(LookAtConfig {
+            target: 1,
+            forward: [0.0, 0.0, 1.0],
+            limits: Some(ConstraintLimits {
+                position_limits: None,
+                rotation_limits: Some(RotationLimits {
+                    pitch_limits: [-30.0, 30.0],
+                    yaw_limits: [-60.0, 60.0],
+                    roll_limits: [0.0, 0.0],
+                }),
+                scale_limits: None,
+            }),
+        })));
+
+        // El objetivo rodea la cabeza por la derecha y por la izquierda hasta
+        // quedar casi detrás
+        for side in [1.0f32, -1.0] {
+            for degrees in (0..=165).step_by(15) {
+                let angle = (degrees as f32).to_radians() * side;
+                let target = Vec3::new(angle.sin(), 0.0, angle.cos()) * 5.0;
+                let mut current = pose(Quat::IDENTITY);
+                stack.apply(&mut current, &skeletal, Mat4::IDENTITY, FRAME, |_| Some(target));
+
+                let forward = current.bones[0].rotation * Vec3::Z;
+                let yaw = forward.x.atan2(forward.z).to_degrees();
+                let expected = (degrees as f32).min(60.0) * side;
+                assert!((yaw - expected).abs() < 0.1, "objetivo a {}°: yaw {} en vez de {}", degrees as f32 * side, yaw, expected);
+                assert!(forward.y.abs() < 1e-4, "el objetivo a la altura de la cabeza no debe cabecear");
+            }
+        }
+    }
+}
@@ -0,0 +1,410 @@
+//! # Retargeting de clips entre esqueletos
+//!
+//! Un clip de esqueleto se pasa a otro esqueleto con proporciones distintas
+//! (p. ej. avatares del marketplace que comparten los clips de andar y de
+//! reposo) con una tabla de huesos origen → destino (`RetargetMap`).
+//!
+//! El clip se remuestrea a sus FPS. En cada frame, el giro de cada hueso
+//! respecto a su pose de reposo se mide en espacio del modelo y se aplica
+//! sobre la pose de reposo del destino, así que dos esqueletos con reposos
+//! distintos (T-pose y A-pose) hacen el mismo movimiento. Los huesos sin
+//! correspondencia siguen rígidamente a su antepasado mapeado más cercano.
+//! Las traslaciones animadas se escalan con la proporción entre la longitud
+//! del hueso en reposo de cada esqueleto (la altura de la cadera para la
+//! raíz).
+//!
+//! Como las piernas del destino no miden lo mismo, los pies pueden quedar
+//! flotando o hundidos; con `foot_ik` cada pie se lleva con IK de dos
+//! huesos (ver `ik`) a la altura del pie del origen, medida desde la altura
+//! en reposo del pie de cada esqueleto y escalada como la cadera.
+
+use glam::{Mat4, Quat, Vec3};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use super::ik::{self, TwoBoneChain};
+use super::sampling::{BonePose, ClipSampler, SkeletonPose};
+use super::skinning;
+use super::{
+    AnimationClip, ClipData, EasingConfig, EasingType, InterpolationType, KeyframeInterpolation, RotationLimits,
+    SkeletalData, Transform, TransformKeyframe,
+};
+
+/// Longitud mínima de un hueso para medir su proporción
+const MIN_BONE_LENGTH: f32 = 1e-4;
+
+/// Diferencia de altura del pie a partir de la que se corrige con IK
+const FOOT_TOLERANCE: f32 = 1e-3;
+
+/// Correspondencia entre los huesos de dos esqueletos
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetargetMap {
+    /// Hueso del destino por hueso del origen
+    pub bones: HashMap<String, String>,
+    /// Pies del destino corregidos con IK
+    #[serde(default)]
+    pub foot_ik: Vec<FootRetarget>,
+}
+
+/// Pie corregido con IK al hacer retargeting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FootRetarget {
+    /// Hueso del pie en el origen
+    pub source_bone: String,
+    /// Constraint IK de la pierna en el destino
+    pub target_constraint: String,
+}
+
+impl RetargetMap {
+    /// Mapa entre los huesos con el mismo nombre (sin distinguir mayúsculas)
+    pub fn by_name(source: &SkeletalData, target: &SkeletalData) -> Self {
+        let bones = source.bones.iter()
+            .filter_map(|bone| {
+                target.bones.iter()
+                    .find(|other| other.name.eq_ignore_ascii_case(&bone.name))
+                    .map(|other| (bone.id.clone(), other.id.clone()))
+            })
+            .collect();
+        Self { bones, foot_ik: Vec::new() }
+    }
+}
+
+/// Pose de reposo de un esqueleto
+struct RestPose {
+    /// Transformación local por hueso
+    locals: Vec<BonePose>,
+    /// Rotación en espacio del modelo por hueso
+    rotations: Vec<Quat>,
+    /// Posición en espacio del modelo por hueso
+    positions: Vec<Vec3>,
+    /// Índice del padre por hueso
+    parents: Vec<Option<usize>>,
+}
+
+impl RestPose {
+    fn new(skeletal: &SkeletalData) -> Self {
+        let locals: Vec<BonePose> = skeletal.bones.iter().map(|bone| BonePose::from_transform(&bone.local_transform)).collect();
+        let matrices: Vec<Mat4> = locals.iter().map(BonePose::to_mat4).collect();
+        let models = skinning::compose_hierarchy(&skeletal.bones, &matrices);
+        let (rotations, positions) = models.iter()
+            .map(|model| {
+                let (_, rotation, position) = model.to_scale_rotation_translation();
+                (rotation, position)
+            })
+            .unzip();
+        Self { locals, rotations, positions, parents: parent_indices(skeletal) }
+    }
+
+    fn parent_rotation(&self, bone: usize) -> Quat {
+        self.parents[bone].map_or(Quat::IDENTITY, |parent| self.rotations[parent])
+    }
+}
+
+/// Pasar un clip de esqueleto de `source` a `target`. El clip devuelto usa
+/// el esqueleto y los constraints del destino, con keyframes lineales a los
+/// FPS del original para los huesos mapeados (y las piernas corregidas).
+/// Un clip que no es de esqueleto se devuelve sin cambios
+pub fn retarget_clip(clip: &AnimationClip, source: &SkeletalData, target: &SkeletalData, map: &RetargetMap) -> AnimationClip {
+    let mut retargeted = clip.clone();
+    retargeted.id = format!("{}_retargeted", clip.id);
+    let ClipData::Skeletal(animated) = &clip.data else {
+        return retargeted;
+    };
+
+    let source_rest = RestPose::new(source);
+    let target_rest = RestPose::new(target);
+    let source_index = bone_indices(source);
+    let animated_index = bone_indices(animated);
+
+    // Hueso del origen por hueso del destino
+    let mapping: Vec<Option<usize>> = target.bones.iter()
+        .map(|bone| {
+            map.bones.iter()
+                .find(|(_, target_bone)| **target_bone == bone.id)
+                .and_then(|(source_bone, _)| source_index.get(source_bone.as_str()).copied())
+        })
+        .collect();
+    // Antepasado mapeado más cercano (o el propio hueso) de cada hueso del destino
+    let driver: Vec<Option<usize>> = (0..target.bones.len())
+        .map(|bone| {
+            let mut current = Some(bone);
+            let mut depth = 0;
+            while let Some(index) = current.filter(|_| depth <= target.bones.len()) {
+                if mapping[index].is_some() {
+                    return Some(index);
+                }
+                current = target_rest.parents[index];
+                depth += 1;
+            }
+            None
+        })
+        .collect();
+
+    let height_ratio = height_ratio(target, &source_rest, &target_rest, &mapping);
+    let feet: Vec<(usize, TwoBoneChain, Option<&RotationLimits>)> = map.foot_ik.iter()
+        .filter_map(|foot| {
+            let source_foot = *source_index.get(foot.source_bone.as_str())?;
+            let constraint = target.constraints.iter().find(|constraint| constraint.id == foot.target_constraint)?;
+            let chain = TwoBoneChain::from_constraint(target, constraint)?;
+            let limits = constraint.config.limits.as_ref().and_then(|limits| limits.rotation_limits.as_ref());
+            Some((source_foot, chain, limits))
+        })
+        .collect();
+
+    let sampler = ClipSampler::new(animated);
+    let fps = if clip.config.fps > 0.0 { clip.config.fps } else { 30.0 };
+    let frames = (clip.config.duration.max(0.0) * fps).round() as usize;
+    let mut animated_pose = SkeletonPose::default();
+    let mut target_pose = SkeletonPose { bones: target_rest.locals.clone() };
+    let mut keyframes = Vec::new();
+
+    for frame in 0..=frames {
+        let time = (frame as f32 / fps).min(clip.config.duration.max(0.0));
+        sampler.sample(animated, &clip.config, time, &mut animated_pose);
+
+        // Pose local del origen: la animada donde hay hueso y la de reposo si no
+        let source_locals: Vec<BonePose> = source.bones.iter()
+            .enumerate()
+            .map(|(i, bone)| {
+                animated_index.get(bone.id.as_str())
+                    .and_then(|&index| animated_pose.bones.get(index).copied())
+                    .unwrap_or(source_rest.locals[i])
+            })
+            .collect();
+        let matrices: Vec<Mat4> = source_locals.iter().map(BonePose::to_mat4).collect();
+        let source_models = skinning::compose_hierarchy(&source.bones, &matrices);
+
+        // Giro de cada hueso respecto al reposo en espacio del modelo
+        let delta = |target_bone: usize| {
+            driver[target_bone].and_then(|driver| mapping[driver]).map_or(Quat::IDENTITY, |source_bone| {
+                let rotation = source_models[source_bone].to_scale_rotation_translation().1;
+                rotation * source_rest.rotations[source_bone].inverse()
+            })
+        };
+
+        for (bone, pose) in target_pose.bones.iter_mut().enumerate() {
+            let global = delta(bone) * target_rest.rotations[bone];
+            let parent_global = target_rest.parents[bone]
+                .map_or(Quat::IDENTITY, |parent| delta(parent) * target_rest.rotations[parent]);
+            let rest = target_rest.locals[bone];
+            pose.rotation = (parent_global.inverse() * global).normalize();
+            pose.scale = rest.scale;
+            pose.translation = match mapping[bone] {
+                Some(source_bone) => {
+                    // Desplazamiento respecto al reposo, del padre del origen al del destino
+                    let offset = source_locals[source_bone].translation - source_rest.locals[source_bone].translation;
+                    let world = source_rest.parent_rotation(source_bone) * offset;
+                    let ratio = bone_ratio(source_rest.locals[source_bone].translation, rest.translation, height_ratio);
+                    rest.translation + target_rest.parent_rotation(bone).inverse() * world * ratio
+                }
+                None => rest.translation,
+            };
+        }
+
+        for &(source_foot, chain, limits) in &feet {
+            let source_height = source_models[source_foot].w_axis.y - source_rest.positions[source_foot].y;
+            let models = ik::model_matrices(&target_pose, target);
+            let Some(foot) = models.get(chain.end).map(|model| model.w_axis.truncate()) else {
+                continue;
+            };
+            let desired = Vec3::new(foot.x, target_rest.positions[chain.end].y + source_height * height_ratio, foot.z);
+            if (desired.y - foot.y).abs() <= FOOT_TOLERANCE {
+                continue;
+            }
+            let knee = models[chain.lower].w_axis.truncate();
+            let forward = models[chain.upper].transform_vector3(Vec3::Z).normalize_or_zero();
+            ik::solve_two_bone(&mut target_pose, target, chain, desired, knee + forward, 1.0, limits);
+        }
+
+        let mut keyed: Vec<usize> = (0..target.bones.len()).filter(|&bone| mapping[bone].is_some()).collect();
+        for (_, chain, _) in &feet {
+            keyed.extend([chain.upper, chain.lower]);
+        }
+        keyed.sort_unstable();
+        keyed.dedup();
+        keyframes.extend(keyed.into_iter().map(|bone| linear_keyframe(time, &target.bones[bone].id, &target_pose.bones[bone])));
+    }
+
+    retargeted.data = ClipData::Skeletal(SkeletalData {
+        bones: target.bones.clone(),
+        keyframes,
+        constraints: target.constraints.clone(),
+        root_bone_id: target.root_bone_id.clone()
+            .or_else(|| animated.root_bone_id.as_ref().and_then(|root| map.bones.get(root).cloned())),
+        compressed: None,
+    });
+    retargeted.state.raw_size = 0;
+    retargeted.state.compressed_size = 0;
+    retargeted
+}
+
+/// Proporción de altura entre los esqueletos: la de sus raíces de root
+/// motion o, si no hay, la del hueso mapeado más alto en reposo
+fn height_ratio(
+    target: &SkeletalData,
+    source_rest: &RestPose,
+    target_rest: &RestPose,
+    mapping: &[Option<usize>],
+) -> f32 {
+    let roots = target.root_bone_id.as_ref()
+        .and_then(|root| target.bones.iter().position(|bone| &bone.id == root))
+        .and_then(|target_root| mapping[target_root].map(|source_root| (source_root, target_root)));
+    let pair = roots.or_else(|| {
+        mapping.iter()
+            .enumerate()
+            .filter_map(|(target_bone, source_bone)| source_bone.map(|source_bone| (source_bone, target_bone)))
+            .max_by(|a, b| source_rest.positions[a.0].y.total_cmp(&source_rest.positions[b.0].y))
+    });
+    match pair {
+        Some((source_bone, target_bone)) if source_rest.positions[source_bone].y.abs() > MIN_BONE_LENGTH => {
+            target_rest.positions[target_bone].y / source_rest.positions[source_bone].y
+        }
+        _ => 1.0,
+    }
+}
+
+/// Proporción entre las longitudes en reposo de un hueso en cada esqueleto
+fn bone_ratio(source: Vec3, target: Vec3, fallback: f32) -> f32 {
+    let length = source.length();
+    if length > MIN_BONE_LENGTH {
+        target.length() / length
+    } else {
+        fallback
+    }
+}
+
+fn bone_indices(skeletal: &SkeletalData) -> HashMap<&str, usize> {
+    skeletal.bones.iter().enumerate().map(|(i, bone)| (bone.id.as_str(), i)).collect()
+}
+
+fn parent_indices(skeletal: &SkeletalData) -> Vec<Option<usize>> {
+    let index = bone_indices(skeletal);
+    skeletal.bones.iter()
+        .map(|bone| bone.parent_id.as_deref().and_then(|parent| index.get(parent).copied()))
+        .collect()
+}
+
+/// Keyframe lineal de una pose de hueso
+fn linear_keyframe(time: f32, bone_id: &str, pose: &BonePose) -> TransformKeyframe {
+    TransformKeyframe {
+        time,
+        bone_id: bone_id.to_string(),
+        transform: Transform {
+            position: pose.translation.to_array(),
+            rotation: pose.rotation.to_array(),
+            scale: pose.scale.to_array(),
+        },
+        interpolation: KeyframeInterpolation {
+            interpolation_type: InterpolationType::Linear,
+            tangents: None,
+            easing: EasingConfig { easing_type: EasingType::None, parameters: [0.0; 4] },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::{Bone, ClipConfig, ClipState, ClipType, FalloffConfig, FalloffType, InfluenceConfig, RotationInterpolation};
+
+    const FPS: f32 = 30.0;
+
+    /// Cadera, columna y pierna derecha, con las longitudes multiplicadas
+    /// por `scale`
+    fn skeleton(scale: f32) -> SkeletalData {
+        let joints = [
+            ("hips", None, [0.0, 1.0, 0.0]),
+            ("spine", Some("hips"), [0.0, 0.5, 0.0]),
+            ("thigh", Some("hips"), [0.1, 0.0, 0.0]),
+            ("shin", Some("thigh"), [0.0, -0.5, 0.0]),
+            ("foot", Some("shin"), [0.0, -0.5, 0.0]),
+        ];
+        let bones = joints.iter()
+            .map(|&(id, parent, position)| {
+                let transform = Transform {
+                    position: (Vec3::from(position) * scale).to_array(),
+                    rotation: [0.0, 0.0, 0.0, 1.0],
+                    scale: [1.0; 3],
+                };
+                Bone {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    parent_id: parent.map(str::to_string),
+                    local_transform: transform.clone(),
+                    world_transform: transform,
+                    influence_config: InfluenceConfig {
+                        influence_radius: 1.0,
+                        influence_weight: 1.0,
+                        falloff_config: FalloffConfig { falloff_type: FalloffType::Linear, falloff_exponent: 1.0 },
+                    },
+                }
+            })
+            .collect();
+        SkeletalData { bones, keyframes: vec![], constraints: vec![], root_bone_id: Some("hips".to_string()), compressed: None }
+    }
+
+    /// Paso de 1 s: la cadera baja y avanza, la columna gira y la pierna se
+    /// dobla a mitad del clip
+    fn step_clip(source: &SkeletalData) -> AnimationClip {
+        let key = |time: f32, bone: &str, position: [f32; 3], rotation: Quat| {
+            linear_keyframe(time, bone, &BonePose { translation: Vec3::from(position), rotation, scale: Vec3::ONE })
+        };
+        let mut keyframes = Vec::new();
+        for (time, bend) in [(0.0, 0.0f32), (0.5, 1.0), (1.0, 0.0)] {
+            keyframes.extend([
+                key(time, "hips", [0.0, 1.0 - 0.1 * bend, 0.3 * bend], Quat::IDENTITY),
+                key(time, "spine", [0.0, 0.5, 0.0], Quat::from_rotation_y(10f32.to_radians() * bend)),
+                key(time, "thigh", [0.1, 0.0, 0.0], Quat::from_rotation_x(-30f32.to_radians() * bend)),
+                key(time, "shin", [0.0, -0.5, 0.0], Quat::from_rotation_x(45f32.to_radians() * bend)),
+                key(time, "foot", [0.0, -0.5, 0.0], Quat::IDENTITY),
+            ]);
+        }
+        AnimationClip {
+            id: "step".to_string(),
+            name: "Step".to_string(),
+            clip_type: ClipType::Skeletal,
+            config: ClipConfig {
+                duration: 1.0,
+                fps: FPS,
+                looped: false,
+                compression: None,
+                optimization: None,
+                rotation_interpolation: RotationInterpolation::Slerp,
+            },
+            data: ClipData::Skeletal(SkeletalData { keyframes, ..source.clone() }),
+            state: ClipState { active: true, loaded: true, compiled: false, load_time: 0.0, raw_size: 0, compressed_size: 0 },
+        }
+    }
+
+    #[test]
+    fn twice_as_tall_skeleton_doubles_hip_translation_and_keeps_rotations() {
+        let (source, target) = (skeleton(1.0), skeleton(2.0));
+        let clip = step_clip(&source);
+        let retargeted = retarget_clip(&clip, &source, &target, &RetargetMap::by_name(&source, &target));
+
+        let (ClipData::Skeletal(original), ClipData::Skeletal(result)) = (&clip.data, &retargeted.data) else {
+            panic!("los dos clips deben ser de esqueleto");
+        };
+        assert_eq!(result.bones.len(), target.bones.len());
+        let (source_sampler, target_sampler) = (ClipSampler::new(original), ClipSampler::new(result));
+        let (mut source_pose, mut target_pose) = (SkeletonPose::default(), SkeletonPose::default());
+        let hips_rest = (Vec3::Y, Vec3::Y * 2.0);
+
+        for frame in 0..=FPS as usize {
+            let time = frame as f32 / FPS;
+            source_sampler.sample(original, &clip.config, time, &mut source_pose);
+            target_sampler.sample(result, &retargeted.config, time, &mut target_pose);
+
+            // La cadera se desplaza el doble respecto a su reposo
+            let source_offset = source_pose.bones[0].translation - hips_rest.0;
+            let target_offset = target_pose.bones[0].translation - hips_rest.1;
+            assert!(target_offset.abs_diff_eq(source_offset * 2.0, 1e-4),
+                "t={}: {:?} frente a {:?}", time, target_offset, source_offset);
+
+            for (bone, (source_bone, target_bone)) in source_pose.bones.iter().zip(&target_pose.bones).enumerate() {
+                let error = source_bone.rotation.angle_between(target_bone.rotation);
+                assert!(error < 1e-3, "t={} {}: {} rad", time, source.bones[bone].id, error);
+            }
+        }
+    }
+}
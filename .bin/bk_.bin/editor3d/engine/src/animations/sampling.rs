@@ -0,0 +1,415 @@
+//! # Muestreo de clips de esqueleto
+//!
+//! Evalúa los `TransformKeyframe` de un clip en un instante y produce la
+//! pose local de cada hueso. Al registrar el clip se ordenan una vez los
+//! keyframes de cada hueso por tiempo; cada frame se busca el tramo con una
+//! búsqueda binaria y se escribe sobre una `SkeletonPose` ya existente, sin
+//! reservar memoria.
+//!
+//! Cada tramo usa la interpolación del keyframe de salida: posición y
+//! escala siguen el tipo de interpolación (con tangentes en `Bezier` y
+//! vecinos en `CatmullRom`) y la rotación se interpola con slerp o nlerp
+//! según `ClipConfig::rotation_interpolation`. El easing del keyframe se
+//! aplica al progreso del tramo antes de interpolar. Los clips comprimidos
+//! (ver `compression`) se muestrean desde sus canales reducidos.
+//!
+//! El root motion es el movimiento del hueso raíz en el plano del suelo
+//! (traslación en X/Z y giro alrededor de Y). `ClipSampler::root_motion` lo
+//! mide entre dos instantes, sumando los ciclos completos cuando un clip en
+//! bucle da la vuelta, y `remove_root_motion` lo quita de la pose para que
+//! lo aplique la entidad: el hueso se queda con la posición horizontal y el
+//! giro del primer frame del clip.
+
+use glam::{Mat4, Quat, Vec3};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use super::morphing::ease;
+use super::{ClipConfig, InterpolationType, SkeletalData, Transform, TransformKeyframe};
+
+/// Interpolación de las rotaciones entre keyframes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RotationInterpolation {
+    /// Interpolación esférica: velocidad angular constante
+    #[default]
+    Slerp,
+    /// Interpolación lineal normalizada: más barata, casi igual en tramos cortos
+    Nlerp,
+}
+
+/// Transformación local de un hueso
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BonePose {
+    /// Traslación
+    pub translation: Vec3,
+    /// Rotación
+    pub rotation: Quat,
+    /// Escala
+    pub scale: Vec3,
+}
+
+impl BonePose {
+    /// Transformación identidad
+    pub const IDENTITY: Self = Self { translation: Vec3::ZERO, rotation: Quat::IDENTITY, scale: Vec3::ONE };
+
+    /// Pose de una transformación de hueso
+    pub fn from_transform(transform: &Transform) -> Self {
+        Self {
+            translation: Vec3::from(transform.position),
+            rotation: Quat::from_array(transform.rotation).normalize(),
+            scale: Vec3::from(transform.scale),
+        }
+    }
+
+    /// Matriz local
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+impl Default for BonePose {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Pose local de un esqueleto, en el orden de `SkeletalData::bones`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SkeletonPose {
+    /// Transformación local por hueso
+    pub bones: Vec<BonePose>,
+}
+
+/// Movimiento del hueso raíz en el plano del suelo
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RootMotion {
+    /// Traslación en el espacio de la entidad (Y siempre 0)
+    pub translation: Vec3,
+    /// Giro alrededor de Y
+    pub rotation: Quat,
+}
+
+impl RootMotion {
+    /// Sin movimiento
+    pub const IDENTITY: Self = Self { translation: Vec3::ZERO, rotation: Quat::IDENTITY };
+
+    /// Este movimiento seguido de `next`, expresado tras el giro de este
+    pub fn then(self, next: RootMotion) -> Self {
+        Self {
+            translation: self.translation + self.rotation * next.translation,
+            rotation: (self.rotation * next.rotation).normalize(),
+        }
+    }
+
+    /// Movimiento que deshace este
+    pub fn inverse(self) -> Self {
+        let rotation = self.rotation.inverse();
+        Self { translation: -(rotation * self.translation), rotation }
+    }
+
+    /// Movimiento del hueso raíz de `from` a `to`. La traslación se expresa
+    /// respecto al giro acumulado desde `reference` (la pose del primer
+    /// frame), que es el que ya ha tomado la entidad
+    fn between(reference: &BonePose, from: &BonePose, to: &BonePose) -> Self {
+        let heading = yaw_of(reference.rotation) * yaw_of(from.rotation).inverse();
+        let delta = to.translation - from.translation;
+        Self {
+            translation: heading * Vec3::new(delta.x, 0.0, delta.z),
+            rotation: (yaw_of(to.rotation) * yaw_of(from.rotation).inverse()).normalize(),
+        }
+    }
+}
+
+impl Default for RootMotion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Keyframes de un clip ordenados por hueso y tiempo
+#[derive(Debug, Clone, Default)]
+pub struct ClipSampler {
+    /// Índices en `SkeletalData::keyframes` por hueso, ordenados por tiempo
+    tracks: Vec<Vec<usize>>,
+}
+
+impl ClipSampler {
+    /// Preparar el muestreo de un clip. Los keyframes de huesos desconocidos
+    /// se ignoran
+    pub fn new(skeletal: &SkeletalData) -> Self {
+        let index: HashMap<&str, usize> = skeletal.bones.iter()
+            .enumerate()
+            .map(|(i, bone)| (bone.id.as_str(), i))
+            .collect();
+        let mut tracks = vec![Vec::new(); skeletal.bones.len()];
+        for (k, keyframe) in skeletal.keyframes.iter().enumerate() {
+            if let Some(&bone) = index.get(keyframe.bone_id.as_str()) {
+                tracks[bone].push(k);
+            }
+        }
+        for track in &mut tracks {
+            track.sort_by(|&a, &b| skeletal.keyframes[a].time.total_cmp(&skeletal.keyframes[b].time));
+        }
+        Self { tracks }
+    }
+
+    /// Escribir en `pose` la pose del clip en `time` (en segundos de
+    /// reproducción; se envuelve o se limita según `config`). Los huesos sin
+    /// keyframes quedan en su transformación local
+    pub fn sample(&self, skeletal: &SkeletalData, config: &ClipConfig, time: f32, pose: &mut SkeletonPose) {
+        let time = clip_time(config, time);
+        pose.bones.resize(skeletal.bones.len(), BonePose::IDENTITY);
+
+        for (i, bone) in pose.bones.iter_mut().enumerate() {
+            *bone = self.sample_bone(skeletal, config, i, time);
+        }
+    }
+
+    /// Pose de un hueso en `time`, sin envolver: el final de un clip en
+    /// bucle da el último keyframe y no el primero
+    pub fn sample_bone(&self, skeletal: &SkeletalData, config: &ClipConfig, bone: usize, time: f32) -> BonePose {
+        let track = self.tracks.get(bone).map_or(&[][..], |track| &track[..]);
+        let compressed = skeletal.compressed.as_ref().and_then(|compressed| compressed.sample_bone(bone, time));
+        if let Some(pose) = compressed {
+            pose
+        } else if track.is_empty() {
+            skeletal.bones.get(bone).map_or(BonePose::IDENTITY, |bone| BonePose::from_transform(&bone.local_transform))
+        } else {
+            sample_track(&skeletal.keyframes, track, time, config.rotation_interpolation)
+        }
+    }
+
+    /// Movimiento del hueso `root` al avanzar `advance` segundos desde
+    /// `from` (tiempo de reproducción). En bucle, cada vuelta suma el tramo
+    /// hasta el final, los ciclos completos y el tramo desde el principio;
+    /// sin bucle el avance se detiene al final del clip. Un avance negativo
+    /// deshace el movimiento
+    pub fn root_motion(&self, skeletal: &SkeletalData, config: &ClipConfig, root: usize, from: f32, advance: f32) -> RootMotion {
+        if advance < 0.0 {
+            return self.root_motion(skeletal, config, root, from + advance, -advance).inverse();
+        }
+        if config.duration <= 0.0 || advance == 0.0 {
+            return RootMotion::IDENTITY;
+        }
+        let at = |time: f32| self.sample_bone(skeletal, config, root, time);
+        let reference = at(0.0);
+        let start = clip_time(config, from);
+        if !config.looped {
+            let end = (start + advance).min(config.duration);
+            return RootMotion::between(&reference, &at(start), &at(end));
+        }
+
+        let end = start + advance;
+        let cycles = (end / config.duration).floor();
+        if cycles < 1.0 {
+            return RootMotion::between(&reference, &at(start), &at(end));
+        }
+        let (first, last) = (at(0.0), at(config.duration));
+        let full = RootMotion::between(&reference, &first, &last);
+        let mut motion = RootMotion::between(&reference, &at(start), &last);
+        for _ in 1..cycles as u32 {
+            motion = motion.then(full);
+        }
+        motion.then(RootMotion::between(&reference, &first, &at(end - cycles * config.duration)))
+    }
+}
+
+/// Quitar de la pose el movimiento del hueso `root`: conserva la altura y la
+/// inclinación animadas, pero la posición horizontal y el giro alrededor de
+/// Y vuelven a los de `reference` (la pose del primer frame)
+pub fn remove_root_motion(pose: &mut SkeletonPose, reference: &BonePose, root: usize) {
+    let Some(bone) = pose.bones.get_mut(root) else {
+        return;
+    };
+    bone.translation.x = reference.translation.x;
+    bone.translation.z = reference.translation.z;
+    bone.rotation = (yaw_of(reference.rotation) * yaw_of(bone.rotation).inverse() * bone.rotation).normalize();
+}
+
+/// Giro alrededor de Y de una rotación (el twist de la descomposición
+/// swing-twist). Sin componente en Y es la identidad
+pub fn yaw_of(rotation: Quat) -> Quat {
+    let twist = Quat::from_xyzw(0.0, rotation.y, 0.0, rotation.w);
+    if twist.length_squared() < 1e-12 {
+        Quat::IDENTITY
+    } else {
+        twist.normalize()
+    }
+}
+
+/// Tiempo dentro del clip: los clips en bucle dan la vuelta y el resto se
+/// queda en el primer o el último frame
+pub fn clip_time(config: &ClipConfig, time: f32) -> f32 {
+    if config.duration <= 0.0 {
+        return 0.0;
+    }
+    if config.looped {
+        time.rem_euclid(config.duration)
+    } else {
+        time.clamp(0.0, config.duration)
+    }
+}
+
+/// Pose de un hueso en `time` a partir de sus keyframes ordenados
+fn sample_track(keyframes: &[TransformKeyframe], track: &[usize], time: f32, rotation: RotationInterpolation) -> BonePose {
+    let key = |i: usize| &keyframes[track[i]];
+    let next = track.partition_point(|&k| keyframes[k].time <= time);
+    if next == 0 {
+        return BonePose::from_transform(&key(0).transform);
+    }
+    if next == track.len() {
+        return BonePose::from_transform(&key(next - 1).transform);
+    }
+
+    let (a, b) = (key(next - 1), key(next));
+    let span = b.time - a.time;
+    if span <= f32::EPSILON {
+        return BonePose::from_transform(&b.transform);
+    }
+    let t = ease((time - a.time) / span, &a.interpolation.easing);
+    let (from, to) = (BonePose::from_transform(&a.transform), BonePose::from_transform(&b.transform));
+
+    let (translation, scale, t) = match &a.interpolation.interpolation_type {
+        InterpolationType::Step => return from,
+        InterpolationType::Linear | InterpolationType::Custom(_) => {
+            (from.translation.lerp(to.translation, t), from.scale.lerp(to.scale, t), t)
+        }
+        InterpolationType::Smooth => {
+            let t = t * t * (3.0 - 2.0 * t);
+            (from.translation.lerp(to.translation, t), from.scale.lerp(to.scale, t), t)
+        }
+        InterpolationType::Bezier => {
+            // Pendientes por segundo de las tangentes (planas sin tangentes)
+            let out_slope = a.interpolation.tangents.as_ref().map_or(Vec3::ZERO, |tangents| Vec3::from(tangents.out_tangent));
+            let in_slope = b.interpolation.tangents.as_ref().map_or(Vec3::ZERO, |tangents| Vec3::from(tangents.in_tangent));
+            let translation = hermite(from.translation, out_slope * span, to.translation, in_slope * span, t);
+            (translation, from.scale.lerp(to.scale, t), t)
+        }
+        InterpolationType::CatmullRom => {
+            let before = BonePose::from_transform(&key(next.saturating_sub(2)).transform);
+            let after = BonePose::from_transform(&key((next + 1).min(track.len() - 1)).transform);
+            let translation = hermite(
+                from.translation,
+                (to.translation - before.translation) * 0.5,
+                to.translation,
+                (after.translation - from.translation) * 0.5,
+                t,
+            );
+            let scale = hermite(from.scale, (to.scale - before.scale) * 0.5, to.scale, (after.scale - from.scale) * 0.5, t);
+            (translation, scale, t)
+        }
+    };
+
+    let rotation = match rotation {
+        RotationInterpolation::Slerp => from.rotation.slerp(to.rotation, t),
+        RotationInterpolation::Nlerp => from.rotation.lerp(to.rotation, t),
+    };
+    BonePose { translation, rotation, scale }
+}
+
+/// Spline cúbica de Hermite entre `p0` y `p1` con pendientes `m0` y `m1`
+pub(super) fn hermite(p0: Vec3, m0: Vec3, p1: Vec3, m1: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    p0 * (2.0 * t3 - 3.0 * t2 + 1.0) + m0 * (t3 - 2.0 * t2 + t) + p1 * (-2.0 * t3 + 3.0 * t2) + m1 * (t3 - t2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::{Bone, EasingConfig, EasingType, FalloffConfig, FalloffType, InfluenceConfig, KeyframeInterpolation};
+
+    /// Tolerancia de los ángulos (acos pierde precisión cerca de 0°)
+    const EPSILON_DEGREES: f32 = 0.1;
+
+    fn transform_rotated(rotation: Quat) -> Transform {
+        Transform { position: [0.0; 3], rotation: rotation.to_array(), scale: [1.0; 3] }
+    }
+
+    /// Clip de 1 s con un hueso que gira 90° alrededor de Y
+    fn quarter_turn(interpolation_type: InterpolationType) -> SkeletalData {
+        let bone = Bone {
+            id: "spin".to_string(),
+            name: "Spin".to_string(),
+            parent_id: None,
+            local_transform: transform_rotated(Quat::IDENTITY),
+            world_transform: transform_rotated(Quat::IDENTITY),
+            influence_config: InfluenceConfig {
+                influence_radius: 1.0,
+                influence_weight: 1.0,
+                falloff_config: FalloffConfig { falloff_type: FalloffType::Linear, falloff_exponent: 1.0 },
+            },
+        };
+        let keyframes = [(0.0, 0.0), (1.0, 90.0)]
+            .into_iter()
+            .map(|(time, degrees): (f32, f32)| TransformKeyframe {
+                time,
+                bone_id: "spin".to_string(),
+                transform: transform_rotated(Quat::from_rotation_y(degrees.to_radians())),
+                interpolation: KeyframeInterpolation {
+                    interpolation_type: interpolation_type.clone(),
+                    tangents: None,
+                    easing: EasingConfig { easing_type: EasingType::None, parameters: [0.0; 4] },
+                },
+            })
+            .collect();
+        SkeletalData { bones: vec![bone], keyframes, constraints: vec![], root_bone_id: None, compressed: None }
+    }
+
+    fn clip_config(looped: bool, rotation_interpolation: RotationInterpolation) -> ClipConfig {
+        ClipConfig { duration: 1.0, fps: 30.0, looped, compression: None, optimization: None, rotation_interpolation }
+    }
+
+    /// Giro en grados del hueso en `time`
+    fn degrees_at(skeletal: &SkeletalData, config: &ClipConfig, time: f32) -> f32 {
+        let mut pose = SkeletonPose::default();
+        ClipSampler::new(skeletal).sample(skeletal, config, time, &mut pose);
+        pose.bones[0].rotation.angle_between(Quat::IDENTITY).to_degrees()
+    }
+
+    #[test]
+    fn half_of_a_90_degree_clip_is_45_degrees() {
+        let skeletal = quarter_turn(InterpolationType::Linear);
+        for rotation in [RotationInterpolation::Slerp, RotationInterpolation::Nlerp] {
+            let degrees = degrees_at(&skeletal, &clip_config(false, rotation), 0.5);
+            assert!((degrees - 45.0).abs() < EPSILON_DEGREES, "{:?}: {}°", rotation, degrees);
+        }
+    }
+
+    #[test]
+    fn looped_clips_wrap_and_others_clamp() {
+        let skeletal = quarter_turn(InterpolationType::Linear);
+        let looped = clip_config(true, RotationInterpolation::Slerp);
+        assert!((degrees_at(&skeletal, &looped, 1.25) - 22.5).abs() < EPSILON_DEGREES);
+        assert!((degrees_at(&skeletal, &looped, 3.5) - 45.0).abs() < EPSILON_DEGREES);
+        assert!((degrees_at(&skeletal, &looped, -0.25) - 67.5).abs() < EPSILON_DEGREES);
+
+        let clamped = clip_config(false, RotationInterpolation::Slerp);
+        assert!((degrees_at(&skeletal, &clamped, 1.5) - 90.0).abs() < EPSILON_DEGREES);
+        assert!(degrees_at(&skeletal, &clamped, -1.0).abs() < EPSILON_DEGREES);
+    }
+
+    #[test]
+    fn step_interpolation_holds_the_previous_key() {
+        let skeletal = quarter_turn(InterpolationType::Step);
+        let config = clip_config(false, RotationInterpolation::Slerp);
+        assert!(degrees_at(&skeletal, &config, 0.5).abs() < EPSILON_DEGREES);
+        assert!(degrees_at(&skeletal, &config, 0.99).abs() < EPSILON_DEGREES);
+        assert!((degrees_at(&skeletal, &config, 1.0) - 90.0).abs() < EPSILON_DEGREES);
+    }
+
+    #[test]
+    fn sampling_reuses_the_pose_buffer() {
+        let skeletal = quarter_turn(InterpolationType::Linear);
+        let config = clip_config(true, RotationInterpolation::Slerp);
+        let sampler = ClipSampler::new(&skeletal);
+        let mut pose = SkeletonPose::default();
+        sampler.sample(&skeletal, &config, 0.0, &mut pose);
+        let buffer = pose.bones.as_ptr();
+
+        for frame in 1..60 {
+            sampler.sample(&skeletal, &config, frame as f32 / 30.0, &mut pose);
+        }
+        assert_eq!(pose.bones.as_ptr(), buffer);
+    }
+}
@@ -0,0 +1,210 @@
+//! # Skinning
+//!
+//! Paletas de huesos por entidad para deformar mallas con skin. Cada matriz de
+//! la paleta lleva un vértice del bind pose a su posición animada en espacio
+//! mundo (`mundo_articulación * bind_inversa`), así que el renderer dibuja la
+//! malla deformada sin transformación de modelo. Las paletas salen de las
+//! poses muestreadas de los clips de esqueleto (huesos de `SkeletalData`) o
+//! de los skins importados de glTF (articulaciones como entidades del ECS).
+//!
+//! Con `SkinningMode::DualQuaternion` las articulaciones se mezclan como
+//! cuaterniones duales, lo que evita el colapso de volumen del linear blend
+//! skinning en giros pronunciados (codos, hombros) a cambio de ignorar la
+//! escala de los huesos.
+
+use glam::{Mat4, Quat, Vec3};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use super::sampling::{BonePose, SkeletonPose};
+use super::{Bone, SkeletalData, Transform};
+use crate::ecs::{ComponentType, ECSSystem, EntityId, TransformComponent};
+
+/// Articulaciones que influyen como máximo en un vértice
+pub const MAX_SKIN_INFLUENCES: usize = 4;
+
+/// Método de mezcla de las articulaciones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SkinningMode {
+    /// Linear blend skinning: suma ponderada de matrices
+    #[default]
+    Linear,
+    /// Dual quaternion skinning: mezcla de transformaciones rígidas
+    DualQuaternion,
+}
+
+/// Cuaternión dual de una transformación rígida
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DualQuat {
+    /// Parte real (rotación)
+    pub real: Quat,
+    /// Parte dual (traslación)
+    pub dual: Quat,
+}
+
+impl DualQuat {
+    /// Cuaternión dual de una rotación seguida de una traslación
+    pub fn from_rotation_translation(rotation: Quat, translation: Vec3) -> Self {
+        let real = rotation.normalize();
+        let dual = Quat::from_xyzw(translation.x, translation.y, translation.z, 0.0) * real * 0.5;
+        Self { real, dual }
+    }
+
+    /// Parte rígida de una matriz (la escala se descarta)
+    pub fn from_mat4(matrix: &Mat4) -> Self {
+        let (_, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self::from_rotation_translation(rotation, translation)
+    }
+
+    /// Traslación codificada
+    pub fn translation(&self) -> Vec3 {
+        let t = self.dual * self.real.conjugate() * 2.0;
+        Vec3::new(t.x, t.y, t.z)
+    }
+
+    /// Transformar un punto
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.real * point + self.translation()
+    }
+}
+
+/// Paleta de huesos de una entidad en el frame actual
+#[derive(Debug, Clone)]
+pub struct SkinPalette {
+    /// Método de mezcla
+    pub mode: SkinningMode,
+    /// Matriz de skinning por articulación, en espacio mundo
+    pub matrices: Vec<Mat4>,
+}
+
+impl SkinPalette {
+    /// Cuaterniones duales de la paleta
+    pub fn dual_quaternions(&self) -> Vec<DualQuat> {
+        self.matrices.iter().map(DualQuat::from_mat4).collect()
+    }
+
+    /// Deformar un vértice en CPU con las mismas reglas que el shader de
+    /// skinning. Devuelve posición y normal en espacio mundo
+    pub fn skin_vertex(&self, position: Vec3, normal: Vec3, joints: [u16; 4], weights: [f32; 4]) -> (Vec3, Vec3) {
+        let influences = joints.iter().zip(weights.iter())
+            .filter(|(_, weight)| **weight > 0.0)
+            .filter_map(|(joint, weight)| self.matrices.get(*joint as usize).map(|matrix| (matrix, *weight)));
+
+        match self.mode {
+            SkinningMode::Linear => {
+                let (mut skinned_position, mut skinned_normal, mut total) = (Vec3::ZERO, Vec3::ZERO, 0.0);
+                for (matrix, weight) in influences {
+                    skinned_position += matrix.transform_point3(position) * weight;
+                    skinned_normal += matrix.transform_vector3(normal) * weight;
+                    total += weight;
+                }
+                if total <= 0.0 {
+                    return (position, normal);
+                }
+                (skinned_position / total, skinned_normal.normalize_or_zero())
+            }
+            SkinningMode::DualQuaternion => {
+                let (mut real, mut dual) = (Quat::from_xyzw(0.0, 0.0, 0.0, 0.0), Quat::from_xyzw(0.0, 0.0, 0.0, 0.0));
+                let mut pivot: Option<Quat> = None;
+                for (matrix, weight) in influences {
+                    let dq = DualQuat::from_mat4(matrix);
+                    // Se alinea el hemisferio con la primera articulación para
+                    // que la mezcla tome el camino corto
+                    let pivot = *pivot.get_or_insert(dq.real);
+                    let weight = if pivot.dot(dq.real) < 0.0 { -weight } else { weight };
+                    real = real + dq.real * weight;
+                    dual = dual + dq.dual * weight;
+                }
+                let sum = DualQuat { real, dual };
+                let length = sum.real.length();
+                if length <= f32::EPSILON {
+                    return (position, normal);
+                }
+                let dq = DualQuat { real: sum.real / length, dual: sum.dual / length };
+                (dq.transform_point(position), (dq.real * normal).normalize_or_zero())
+            }
+        }
+    }
+}
+
+/// Matriz local de una transformación de hueso
+fn transform_matrix(transform: &Transform) -> Mat4 {
+    Mat4::from_scale_rotation_translation(
+        Vec3::from(transform.scale),
+        Quat::from_array(transform.rotation).normalize(),
+        Vec3::from(transform.position),
+    )
+}
+
+/// Componer las matrices locales de los huesos a lo largo de la jerarquía.
+/// Los huesos pueden venir en cualquier orden; un padre desconocido o un
+/// ciclo dejan al hueso como raíz
+pub(crate) fn compose_hierarchy(bones: &[Bone], locals: &[Mat4]) -> Vec<Mat4> {
+    let index: HashMap<&str, usize> = bones.iter().enumerate().map(|(i, bone)| (bone.id.as_str(), i)).collect();
+    let mut models: Vec<Option<Mat4>> = vec![None; bones.len()];
+
+    for start in 0..bones.len() {
+        // Subir hasta el primer antepasado ya resuelto
+        let mut chain = vec![start];
+        let mut current = start;
+        while models[current].is_none() && chain.len() <= bones.len() {
+            match bones[current].parent_id.as_deref().and_then(|parent| index.get(parent)) {
+                Some(&parent) if !chain.contains(&parent) => {
+                    chain.push(parent);
+                    current = parent;
+                }
+                _ => break,
+            }
+        }
+        // Bajar resolviendo desde el antepasado
+        for &bone in chain.iter().rev() {
+            if models[bone].is_some() {
+                continue;
+            }
+            let parent = bones[bone].parent_id.as_deref()
+                .and_then(|parent| index.get(parent))
+                .and_then(|&parent| models[parent]);
+            models[bone] = Some(parent.map_or(locals[bone], |parent| parent * locals[bone]));
+        }
+    }
+
+    models.into_iter().map(|model| model.unwrap_or(Mat4::IDENTITY)).collect()
+}
+
+/// Paleta en espacio del modelo de una pose de esqueleto. El índice de
+/// articulación de cada vértice es la posición del hueso en `bones`, y el
+/// bind pose es la transformación local de los huesos. La pose ya llega sin
+/// el root motion extraído
+pub fn skeletal_palette(skeletal: &SkeletalData, pose: &SkeletonPose) -> Vec<Mat4> {
+    let rest: Vec<Mat4> = skeletal.bones.iter().map(|bone| transform_matrix(&bone.local_transform)).collect();
+    let animated: Vec<Mat4> = skeletal.bones.iter()
+        .enumerate()
+        .map(|(i, bone)| {
+            pose.bones.get(i).copied().unwrap_or_else(|| BonePose::from_transform(&bone.local_transform)).to_mat4()
+        })
+        .collect();
+
+    let bind = compose_hierarchy(&skeletal.bones, &rest);
+    let animated = compose_hierarchy(&skeletal.bones, &animated);
+    animated.iter().zip(bind.iter()).map(|(pose, bind)| *pose * bind.inverse()).collect()
+}
+
+/// Matriz mundo de una entidad, componiendo las transformaciones de sus padres
+pub fn entity_world_matrix(world: &ECSSystem, entity_id: EntityId) -> Mat4 {
+    let mut matrix = Mat4::IDENTITY;
+    let mut current = Some(entity_id);
+    let mut depth = 0;
+    while let Some(id) = current {
+        let Some(transform) = world.get_component::<TransformComponent>(id, ComponentType::Transform) else {
+            break;
+        };
+        matrix = Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position) * matrix;
+        current = transform.parent;
+        // Protección contra jerarquías cíclicas
+        depth += 1;
+        if depth > 256 {
+            break;
+        }
+    }
+    matrix
+}
@@ -0,0 +1,272 @@
+//! Paisajes sonoros ambientales
+//!
+//! Cada entidad con `AmbientZoneComponent` aporta capas en bucle (viento,
+//! olas, fauna) al bus Ambient. La ganancia de una capa es su volumen por el
+//! peso de la zona para el oyente (1 dentro de la caja y 0 a
+//! `blend_distance` fuera, lineal entre medias) y por su curva de hora del
+//! día, de modo que al pasar de una zona a la vecina una se apaga mientras
+//! la otra entra y, al caer la noche, los pájaros dejan paso a los grillos.
+//!
+//! Hay un límite global de voces ambientales: las capas audibles se ordenan
+//! por prioridad de su zona y después por ganancia, y las que quedan fuera
+//! del límite pierden su voz en favor de las más importantes.
+
+use glam::Vec3;
+use std::collections::{HashMap, HashSet};
+
+use super::mixer::{AudioBus, AudioMixer, VoiceId, VoiceParams};
+use crate::ecs::{AmbientZoneComponent, EntityId};
+
+/// Ganancia por debajo de la cual una capa no ocupa voz
+const AUDIBLE_GAIN: f32 = 1e-3;
+
+/// Ganancia de una curva de hora del día en `day_time` (en [0, 1)), con
+/// interpolación lineal que da la vuelta a medianoche
+pub fn day_curve_gain(curve: &[(f32, f32)], day_time: f32) -> f32 {
+    let mut keys: Vec<(f32, f32)> = curve.iter().map(|&(time, gain)| (time.rem_euclid(1.0), gain)).collect();
+    keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (Some(&first), Some(&last)) = (keys.first(), keys.last()) else {
+        return 1.0;
+    };
+    let t = day_time.rem_euclid(1.0);
+    // Tramo que contiene `t`; antes de la primera clave o después de la
+    // última se interpola entre la última y la primera
+    let (a, b) = match keys.iter().position(|&(time, _)| time > t) {
+        Some(0) | None => (last, (first.0 + 1.0, first.1)),
+        Some(index) => (keys[index - 1], keys[index]),
+    };
+    let t = if t < a.0 { t + 1.0 } else { t };
+    let span = b.0 - a.0;
+    if span <= f32::EPSILON {
+        return a.1;
+    }
+    a.1 + (b.1 - a.1) * ((t - a.0) / span).clamp(0.0, 1.0)
+}
+
+/// Peso de una zona para el oyente: 1 dentro de la caja y 0 a
+/// `blend_distance` fuera
+pub fn zone_weight(zone: &AmbientZoneComponent, center: Vec3, listener: Vec3) -> f32 {
+    let distance = ((listener - center).abs() - zone.half_extents).max(Vec3::ZERO).length();
+    if zone.blend_distance <= f32::EPSILON {
+        return if distance <= 0.0 { 1.0 } else { 0.0 };
+    }
+    (1.0 - distance / zone.blend_distance).clamp(0.0, 1.0)
+}
+
+/// Capa audible de una zona
+#[derive(Debug, Clone)]
+struct LayerCandidate {
+    key: (EntityId, usize),
+    clip_id: String,
+    gain: f32,
+    priority: u32,
+}
+
+/// Voces de las capas ambientales
+#[derive(Debug, Default)]
+pub struct AmbientSoundscape {
+    /// Voz de cada capa (entidad, índice de capa)
+    voices: HashMap<(EntityId, usize), VoiceId>,
+    /// Voces robadas por el límite desde el inicio
+    stolen: u64,
+}
+
+impl AmbientSoundscape {
+    /// Sin voces
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Voces ambientales activas
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Voces quitadas a capas audibles por el límite
+    pub fn stolen_voices(&self) -> u64 {
+        self.stolen
+    }
+
+    /// Voz de una capa de una zona
+    pub fn layer_voice(&self, entity_id: EntityId, layer: usize) -> Option<VoiceId> {
+        self.voices.get(&(entity_id, layer)).copied()
+    }
+
+    /// Ajustar las voces a las zonas `(entidad, posición, zona)`, el oyente
+    /// y la hora del día, con como mucho `max_voices` voces
+    pub fn update(
+        &mut self,
+        mixer: &mut AudioMixer,
+        zones: &[(EntityId, Vec3, AmbientZoneComponent)],
+        listener: Vec3,
+        day_time: f32,
+        max_voices: usize,
+    ) {
+        let mut candidates: Vec<LayerCandidate> = Vec::new();
+        for (entity_id, center, zone) in zones {
+            let weight = zone_weight(zone, *center, listener);
+            if weight <= 0.0 {
+                continue;
+            }
+            for (index, layer) in zone.layers.iter().enumerate() {
+                let gain = layer.volume.max(0.0) * weight * day_curve_gain(&layer.day_curve, day_time).max(0.0);
+                if gain < AUDIBLE_GAIN || !mixer.has_clip(&layer.clip_id) {
+                    continue;
+                }
+                candidates.push(LayerCandidate { key: (*entity_id, index), clip_id: layer.clip_id.clone(), gain, priority: zone.priority });
+            }
+        }
+        candidates.sort_by(|a, b| b.priority.cmp(&a.priority).then(b.gain.total_cmp(&a.gain)));
+        let audible = candidates.len();
+        candidates.truncate(max_voices);
+        self.stolen += audible.saturating_sub(candidates.len()) as u64;
+
+        let kept: HashSet<(EntityId, usize)> = candidates.iter().map(|candidate| candidate.key).collect();
+        self.voices.retain(|key, voice_id| {
+            let keep = kept.contains(key) && mixer.voice(*voice_id).is_some_and(|voice| !voice.is_finished());
+            if !keep {
+                mixer.stop(*voice_id);
+            }
+            keep
+        });
+
+        for candidate in candidates {
+            match self.voices.get(&candidate.key).and_then(|voice_id| mixer.voice_mut(*voice_id)) {
+                Some(voice) if voice.params.clip_id == candidate.clip_id => voice.params.volume = candidate.gain,
+                _ => {
+                    if let Some(voice_id) = self.voices.remove(&candidate.key) {
+                        mixer.stop(voice_id);
+                    }
+                    let params = VoiceParams {
+                        clip_id: candidate.clip_id,
+                        bus: AudioBus::Ambient,
+                        volume: candidate.gain,
+                        pitch: 1.0,
+                        looped: true,
+                        position: listener,
+                        spatial: None,
+                        entity_id: Some(candidate.key.0),
+                        source_id: None,
+                        reverb_send: 0.0,
+                        effects: Vec::new(),
+                        velocity: Vec3::ZERO,
+                        doppler: false,
+                    };
+                    if let Some(voice_id) = mixer.play(params) {
+                        self.voices.insert(candidate.key, voice_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parar todas las voces ambientales
+    pub fn clear(&mut self, mixer: &mut AudioMixer) {
+        for (_, voice_id) in self.voices.drain() {
+            mixer.stop(voice_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::mixer::AudioClip;
+    use crate::ecs::AmbientLayer;
+
+    const BEACH: EntityId = 1;
+    const FOREST: EntityId = 2;
+    /// Pájaros de día, con el alba a 0,25-0,35 y el anochecer a 0,75-0,85
+    const BIRDS_CURVE: [(f32, f32); 4] = [(0.25, 0.0), (0.35, 1.0), (0.75, 1.0), (0.85, 0.0)];
+    /// Grillos de noche, el reverso de los pájaros
+    const CRICKETS_CURVE: [(f32, f32); 4] = [(0.75, 0.0), (0.85, 1.0), (0.25, 1.0), (0.35, 0.0)];
+    const NOON: f32 = 0.5;
+
+    fn layer(clip_id: &str, volume: f32, day_curve: &[(f32, f32)]) -> AmbientLayer {
+        AmbientLayer { clip_id: clip_id.to_string(), volume, day_curve: day_curve.to_vec() }
+    }
+
+    /// Playa en x ∈ [-10, 10] y bosque en x ∈ [20, 40]; cada una se funde a
+    /// lo largo de 10 m, así que entre las dos se cruzan
+    fn islands() -> Vec<(EntityId, Vec3, AmbientZoneComponent)> {
+        let zone = |layers| AmbientZoneComponent { half_extents: Vec3::new(10.0, 5.0, 10.0), blend_distance: 10.0, priority: 0, layers };
+        vec![
+            (BEACH, Vec3::ZERO, zone(vec![
+                layer("waves", 0.8, &[]),
+                layer("birds", 0.5, &BIRDS_CURVE),
+                layer("crickets", 0.4, &CRICKETS_CURVE),
+            ])),
+            (FOREST, Vec3::new(30.0, 0.0, 0.0), zone(vec![layer("wind", 0.6, &[])])),
+        ]
+    }
+
+    fn mixer() -> AudioMixer {
+        let mut mixer = AudioMixer::new(48_000);
+        for id in ["waves", "birds", "crickets", "wind"] {
+            mixer.load_clip(AudioClip { id: id.to_string(), sample_rate: 48_000, channels: 1, samples: vec![0.1; 4800] });
+        }
+        mixer
+    }
+
+    /// Volumen de la voz de una capa (0 sin voz)
+    fn layer_gain(soundscape: &AmbientSoundscape, mixer: &AudioMixer, entity_id: EntityId, layer: usize) -> f32 {
+        soundscape.layer_voice(entity_id, layer)
+            .and_then(|voice_id| mixer.voice(voice_id))
+            .map_or(0.0, |voice| voice.params.volume)
+    }
+
+    #[test]
+    fn walking_into_the_adjacent_zone_crossfades_layers_monotonically() {
+        let (mut mixer, zones) = (mixer(), islands());
+        let mut soundscape = AmbientSoundscape::new();
+        let mut gains = Vec::new();
+        for step in 0..=120 {
+            let listener = Vec3::new(step as f32 * 0.25, 0.0, 0.0);
+            soundscape.update(&mut mixer, &zones, listener, NOON, 8);
+            gains.push((layer_gain(&soundscape, &mixer, BEACH, 0), layer_gain(&soundscape, &mixer, FOREST, 0)));
+        }
+
+        assert_eq!(gains[0], (0.8, 0.0));
+        // A medio camino (x = 15) las dos zonas pesan la mitad
+        let (waves, wind) = gains[60];
+        assert!((waves - 0.4).abs() < 1e-5 && (wind - 0.3).abs() < 1e-5, "{} y {}", waves, wind);
+        assert_eq!(gains[120], (0.0, 0.6));
+        for (step, pair) in gains.windows(2).enumerate() {
+            assert!(pair[1].0 <= pair[0].0, "las olas suben en el paso {}: {:?}", step + 1, pair);
+            assert!(pair[1].1 >= pair[0].1, "el viento baja en el paso {}: {:?}", step + 1, pair);
+        }
+        // Fuera de la playa sus capas no ocupan voz
+        assert!(soundscape.layer_voice(BEACH, 0).is_none());
+        assert_eq!(soundscape.active_voices(), 1);
+    }
+
+    #[test]
+    fn nightfall_swaps_birds_for_crickets() {
+        let (mut mixer, zones) = (mixer(), islands());
+        let mut soundscape = AmbientSoundscape::new();
+        let on_the_beach = Vec3::ZERO;
+
+        soundscape.update(&mut mixer, &zones, on_the_beach, NOON, 8);
+        assert_eq!(layer_gain(&soundscape, &mixer, BEACH, 1), 0.5);
+        assert!(soundscape.layer_voice(BEACH, 2).is_none());
+
+        // Al anochecer suenan las dos a media curva
+        soundscape.update(&mut mixer, &zones, on_the_beach, 0.8, 8);
+        assert!((layer_gain(&soundscape, &mixer, BEACH, 1) - 0.25).abs() < 1e-5);
+        assert!((layer_gain(&soundscape, &mixer, BEACH, 2) - 0.2).abs() < 1e-5);
+
+        // De noche, también pasada la medianoche, solo los grillos
+        for night in [0.95, 0.1] {
+            soundscape.update(&mut mixer, &zones, on_the_beach, night, 8);
+            assert!(soundscape.layer_voice(BEACH, 1).is_none(), "pájaros a las {}", night);
+            assert_eq!(layer_gain(&soundscape, &mixer, BEACH, 2), 0.4);
+        }
+        // Las olas no dependen de la hora
+        assert_eq!(layer_gain(&soundscape, &mixer, BEACH, 0), 0.8);
+
+        // Y al amanecer vuelven los pájaros
+        soundscape.update(&mut mixer, &zones, on_the_beach, NOON, 8);
+        assert_eq!(layer_gain(&soundscape, &mixer, BEACH, 1), 0.5);
+        assert!(soundscape.layer_voice(BEACH, 2).is_none());
+    }
+}
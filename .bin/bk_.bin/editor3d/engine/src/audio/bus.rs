@@ -0,0 +1,162 @@
+//! Grafo de buses
+//!
+//! Los buses forman un árbol con el master en la raíz (Master → Music, SFX,
+//! Voice, Ambient). La ganancia de un bus es el producto de los volúmenes
+//! del camino hasta el master, 0 si alguno está silenciado, por la
+//! atenuación de ducking del bus.
+//!
+//! Una regla de ducking baja un bus objetivo `amount_db` decibelios mientras
+//! su bus disparador está activo (tiene voces sonando o actividad externa,
+//! como el chat de voz). La atenuación avanza en línea recta en dB: llega al
+//! objetivo en `attack` segundos y vuelve a 0 en `release`. Si varias reglas
+//! bajan el mismo bus manda la mayor.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+
+use super::mixer::AudioBus;
+
+/// Regla de ducking por cadena lateral
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuckingRule {
+    /// Bus cuya actividad baja al objetivo
+    pub trigger: AudioBus,
+    /// Bus atenuado
+    pub target: AudioBus,
+    /// Atenuación en dB con el disparador activo
+    pub amount_db: f32,
+    /// Segundos hasta alcanzar la atenuación
+    pub attack: f32,
+    /// Segundos hasta recuperar el volumen
+    pub release: f32,
+}
+
+impl DuckingRule {
+    /// La voz baja la música
+    pub fn voice_over_music() -> Self {
+        Self { trigger: AudioBus::Voice, target: AudioBus::Music, amount_db: 9.0, attack: 0.15, release: 0.8 }
+    }
+}
+
+/// Estado de un bus
+#[derive(Debug, Clone, Copy)]
+struct BusState {
+    volume: f32,
+    muted: bool,
+}
+
+impl Default for BusState {
+    fn default() -> Self {
+        Self { volume: 1.0, muted: false }
+    }
+}
+
+/// Regla con su atenuación actual
+#[derive(Debug, Clone)]
+struct Ducker {
+    rule: DuckingRule,
+    level_db: f32,
+}
+
+/// Volumen, silencio y ducking de los buses
+#[derive(Debug, Clone, Default)]
+pub struct BusGraph {
+    buses: HashMap<AudioBus, BusState>,
+    duckers: Vec<Ducker>,
+    /// Buses activos por fuentes que no pasan por el mezclador
+    external_activity: HashSet<AudioBus>,
+}
+
+impl BusGraph {
+    /// Buses a volumen 1 sin ducking
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Volumen propio de un bus
+    pub fn set_volume(&mut self, bus: AudioBus, volume: f32) {
+        self.buses.entry(bus).or_default().volume = volume.max(0.0);
+    }
+
+    /// Volumen propio de un bus
+    pub fn volume(&self, bus: AudioBus) -> f32 {
+        self.buses.get(&bus).map_or(1.0, |state| state.volume)
+    }
+
+    /// Silenciar un bus (y los que cuelgan de él)
+    pub fn set_muted(&mut self, bus: AudioBus, muted: bool) {
+        self.buses.entry(bus).or_default().muted = muted;
+    }
+
+    /// Bus silenciado
+    pub fn is_muted(&self, bus: AudioBus) -> bool {
+        self.buses.get(&bus).is_some_and(|state| state.muted)
+    }
+
+    /// Añadir una regla de ducking
+    pub fn add_ducking(&mut self, rule: DuckingRule) {
+        self.duckers.push(Ducker { rule, level_db: 0.0 });
+    }
+
+    /// Quitar las reglas de un disparador sobre un objetivo
+    pub fn remove_ducking(&mut self, trigger: AudioBus, target: AudioBus) {
+        self.duckers.retain(|ducker| ducker.rule.trigger != trigger || ducker.rule.target != target);
+    }
+
+    /// Reglas de ducking
+    pub fn ducking_rules(&self) -> impl Iterator<Item = &DuckingRule> {
+        self.duckers.iter().map(|ducker| &ducker.rule)
+    }
+
+    /// Marcar un bus activo por una fuente externa al mezclador
+    pub fn set_external_activity(&mut self, bus: AudioBus, active: bool) {
+        if active {
+            self.external_activity.insert(bus);
+        } else {
+            self.external_activity.remove(&bus);
+        }
+    }
+
+    /// Atenuación de ducking actual de un bus en dB
+    pub fn ducking_db(&self, bus: AudioBus) -> f32 {
+        self.duckers.iter()
+            .filter(|ducker| ducker.rule.target == bus)
+            .map(|ducker| ducker.level_db)
+            .fold(0.0, f32::max)
+    }
+
+    /// Ganancia de un bus con sus padres, el silencio y el ducking
+    pub fn gain(&self, bus: AudioBus) -> f32 {
+        let mut gain = 1.0;
+        let mut current = Some(bus);
+        while let Some(bus) = current {
+            if self.is_muted(bus) {
+                return 0.0;
+            }
+            gain *= self.volume(bus) * 10f32.powf(-self.ducking_db(bus) / 20.0);
+            current = bus.parent();
+        }
+        gain
+    }
+
+    /// Avanzar el ducking `delta_time` segundos con los buses que tienen
+    /// voces sonando
+    pub fn update(&mut self, delta_time: f32, active: &HashSet<AudioBus>) {
+        for ducker in &mut self.duckers {
+            let rule = &ducker.rule;
+            let amount = rule.amount_db.max(0.0);
+            let triggered = active.contains(&rule.trigger) || self.external_activity.contains(&rule.trigger);
+            let (target, time) = if triggered { (amount, rule.attack) } else { (0.0, rule.release) };
+            if time <= f32::EPSILON || amount <= f32::EPSILON {
+                ducker.level_db = target;
+                continue;
+            }
+            let step = amount / time * delta_time.max(0.0);
+            ducker.level_db = if ducker.level_db < target {
+                (ducker.level_db + step).min(target)
+            } else {
+                (ducker.level_db - step).max(target)
+            };
+        }
+    }
+}
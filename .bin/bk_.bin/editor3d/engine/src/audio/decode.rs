@@ -0,0 +1,310 @@
+//! Decodificación de audio
+//!
+//! Los clips llegan como bytes (del disco, de la red o empaquetados en el
+//! build de wasm) en WAV (PCM entero o flotante) u OGG/Vorbis; el formato se
+//! reconoce por la cabecera. Los decodificadores entregan bloques
+//! intercalados en [-1, 1] de como mucho dos canales (los demás se
+//! descartan) y el `Resampler` los lleva a la frecuencia del motor.
+//!
+//! El resampler es lineal y lleva la posición como fracción exacta de las
+//! dos frecuencias, así que un segundo a 44,1 kHz da exactamente 48000
+//! frames a 48 kHz y los bloques de un stream se enlazan sin saltos.
+
+use anyhow::{Result, anyhow};
+use std::io::Cursor;
+use std::sync::Arc;
+
+use super::mixer::AudioClip;
+
+/// Frames por bloque decodificado
+pub const DECODE_BLOCK_FRAMES: usize = 4096;
+
+/// Formato de un archivo de audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    Wav,
+    Vorbis,
+}
+
+impl AudioFormat {
+    /// Reconocer el formato por la cabecera
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WAVE" {
+            Some(AudioFormat::Wav)
+        } else if bytes.starts_with(b"OggS") {
+            Some(AudioFormat::Vorbis)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decodificador por bloques
+pub trait BlockDecoder: Send {
+    /// Canales de los bloques (1 o 2)
+    fn channels(&self) -> usize;
+    /// Frecuencia de muestreo del archivo
+    fn sample_rate(&self) -> u32;
+    /// Siguiente bloque intercalado (None al final)
+    fn next_block(&mut self) -> Result<Option<Vec<f32>>>;
+    /// Volver al principio
+    fn rewind(&mut self) -> Result<()>;
+}
+
+/// Abrir un decodificador para unos bytes
+pub fn open_decoder(bytes: Arc<[u8]>) -> Result<Box<dyn BlockDecoder>> {
+    match AudioFormat::detect(&bytes) {
+        Some(AudioFormat::Wav) => Ok(Box::new(WavDecoder::new(bytes)?)),
+        Some(AudioFormat::Vorbis) => Ok(Box::new(VorbisDecoder::new(bytes)?)),
+        None => Err(anyhow!("Formato de audio no reconocido")),
+    }
+}
+
+/// Decodificar un archivo completo a la frecuencia `sample_rate`
+pub fn decode_audio(id: &str, bytes: &[u8], sample_rate: u32) -> Result<AudioClip> {
+    let mut decoder = open_decoder(Arc::from(bytes))?;
+    let channels = decoder.channels();
+    let mut resampler = Resampler::new(channels, decoder.sample_rate(), sample_rate);
+    let mut samples = Vec::new();
+    while let Some(block) = decoder.next_block()? {
+        resampler.process(&block, &mut samples);
+    }
+    resampler.finish(&mut samples);
+    Ok(AudioClip { id: id.to_string(), sample_rate, channels: channels as u16, samples })
+}
+
+/// Quedarse con los dos primeros canales de un bloque intercalado
+fn keep_two_channels(samples: Vec<f32>, channels: usize) -> Vec<f32> {
+    if channels <= 2 {
+        return samples;
+    }
+    samples.chunks_exact(channels).flat_map(|frame| [frame[0], frame[1]]).collect()
+}
+
+/// WAV PCM entero (8-32 bits) o flotante de 32 bits
+struct WavDecoder {
+    reader: hound::WavReader<Cursor<Arc<[u8]>>>,
+    spec: hound::WavSpec,
+}
+
+impl WavDecoder {
+    fn new(bytes: Arc<[u8]>) -> Result<Self> {
+        let reader = hound::WavReader::new(Cursor::new(bytes))?;
+        let spec = reader.spec();
+        if spec.channels == 0 {
+            return Err(anyhow!("WAV sin canales"));
+        }
+        Ok(Self { reader, spec })
+    }
+}
+
+impl BlockDecoder for WavDecoder {
+    fn channels(&self) -> usize {
+        (self.spec.channels as usize).min(2)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.spec.sample_rate
+    }
+
+    fn next_block(&mut self) -> Result<Option<Vec<f32>>> {
+        let count = DECODE_BLOCK_FRAMES * self.spec.channels as usize;
+        let samples: Vec<f32> = match self.spec.sample_format {
+            hound::SampleFormat::Float => self.reader.samples::<f32>().take(count).collect::<Result<_, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1i64 << (self.spec.bits_per_sample - 1)) as f32;
+                self.reader.samples::<i32>().take(count)
+                    .map(|sample| sample.map(|sample| sample as f32 * scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        if samples.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(keep_two_channels(samples, self.spec.channels as usize)))
+    }
+
+    fn rewind(&mut self) -> Result<()> {
+        self.reader.seek(0)?;
+        Ok(())
+    }
+}
+
+/// OGG/Vorbis
+struct VorbisDecoder {
+    bytes: Arc<[u8]>,
+    reader: lewton::inside_ogg::OggStreamReader<Cursor<Arc<[u8]>>>,
+}
+
+impl VorbisDecoder {
+    fn new(bytes: Arc<[u8]>) -> Result<Self> {
+        let reader = lewton::inside_ogg::OggStreamReader::new(Cursor::new(bytes.clone()))?;
+        Ok(Self { bytes, reader })
+    }
+}
+
+impl BlockDecoder for VorbisDecoder {
+    fn channels(&self) -> usize {
+        (self.reader.ident_hdr.audio_channels as usize).min(2)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.reader.ident_hdr.audio_sample_rate
+    }
+
+    fn next_block(&mut self) -> Result<Option<Vec<f32>>> {
+        // Los paquetes pueden venir vacíos (p. ej. el primero)
+        loop {
+            let Some(packet) = self.reader.read_dec_packet_itl()? else {
+                return Ok(None);
+            };
+            if packet.is_empty() {
+                continue;
+            }
+            let samples = packet.into_iter().map(|sample| sample as f32 / 32768.0).collect();
+            return Ok(Some(keep_two_channels(samples, self.reader.ident_hdr.audio_channels as usize)));
+        }
+    }
+
+    fn rewind(&mut self) -> Result<()> {
+        self.reader = lewton::inside_ogg::OggStreamReader::new(Cursor::new(self.bytes.clone()))?;
+        Ok(())
+    }
+}
+
+/// Conversión lineal de frecuencia de muestreo por bloques
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    channels: usize,
+    input_rate: u64,
+    output_rate: u64,
+    /// Frames de salida producidos
+    output_index: u64,
+    /// Índice de entrada del primer frame de `buffer`
+    base: u64,
+    /// Frames de entrada aún necesarios para interpolar
+    buffer: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(channels: usize, input_rate: u32, output_rate: u32) -> Self {
+        Self {
+            channels: channels.max(1),
+            input_rate: input_rate.max(1) as u64,
+            output_rate: output_rate.max(1) as u64,
+            output_index: 0,
+            base: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Posición de entrada del siguiente frame de salida: índice y fracción
+    fn position(&self) -> (u64, f32) {
+        let numerator = self.output_index * self.input_rate;
+        (numerator / self.output_rate, (numerator % self.output_rate) as f32 / self.output_rate as f32)
+    }
+
+    /// Convertir un bloque intercalado, añadiendo a `output` los frames que
+    /// ya se pueden interpolar
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let channels = self.channels;
+        self.buffer.extend_from_slice(input);
+        let frames = self.buffer.len() / channels;
+        loop {
+            let (index, t) = self.position();
+            let local = (index - self.base) as usize;
+            if local + 1 >= frames {
+                break;
+            }
+            for channel in 0..channels {
+                let a = self.buffer[local * channels + channel];
+                let b = self.buffer[(local + 1) * channels + channel];
+                output.push(a + (b - a) * t);
+            }
+            self.output_index += 1;
+        }
+
+        // Descartar los frames que ya no se interpolan
+        let (index, _) = self.position();
+        let consumed = ((index - self.base) as usize).min(frames.saturating_sub(1));
+        self.buffer.drain(..consumed * channels);
+        self.base += consumed as u64;
+    }
+
+    /// Terminar el flujo: los frames que caen en el último frame de entrada
+    pub fn finish(&mut self, output: &mut Vec<f32>) {
+        let channels = self.channels;
+        let frames = self.buffer.len() / channels;
+        loop {
+            let (index, _) = self.position();
+            let local = (index - self.base) as usize;
+            if local >= frames {
+                break;
+            }
+            output.extend_from_slice(&self.buffer[local * channels..(local + 1) * channels]);
+            self.output_index += 1;
+        }
+        self.base += frames as u64;
+        self.buffer.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1 s de seno de 440 Hz a media amplitud, 44,1 kHz, mono de 16 bits
+    const TONE_WAV: &[u8] = include_bytes!("../../tests/fixtures/audio/tone_44k1.wav");
+    /// 1 s de silencio a 44,1 kHz en OGG/Vorbis mono con bloques de 256
+    const SILENCE_OGG: &[u8] = include_bytes!("../../tests/fixtures/audio/silence_44k1.ogg");
+
+    /// Frames de todos los bloques de un decodificador
+    fn decoded_frames(decoder: &mut dyn BlockDecoder) -> Vec<f32> {
+        let mut samples = Vec::new();
+        while let Some(block) = decoder.next_block().unwrap() {
+            samples.extend(block);
+        }
+        samples
+    }
+
+    #[test]
+    fn one_second_wav_at_44k1_resamples_to_48000_frames() {
+        assert_eq!(AudioFormat::detect(TONE_WAV), Some(AudioFormat::Wav));
+        let mut decoder = open_decoder(Arc::from(TONE_WAV)).unwrap();
+        assert_eq!(decoder.channels(), 1);
+        assert_eq!(decoder.sample_rate(), 44100);
+        assert_eq!(decoded_frames(decoder.as_mut()).len(), 44100);
+
+        let clip = decode_audio("tone", TONE_WAV, 48000).unwrap();
+        assert_eq!(clip.sample_rate, 48000);
+        assert_eq!(clip.channels, 1);
+        assert_eq!(clip.samples.len(), 48000);
+        let peak = clip.samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((peak - 0.5).abs() < 0.01, "pico {}", peak);
+    }
+
+    #[test]
+    fn vorbis_fixture_decodes_one_second_of_silence() {
+        assert_eq!(AudioFormat::detect(SILENCE_OGG), Some(AudioFormat::Vorbis));
+        let mut decoder = open_decoder(Arc::from(SILENCE_OGG)).unwrap();
+        assert_eq!(decoder.channels(), 1);
+        assert_eq!(decoder.sample_rate(), 44100);
+
+        // El último paquete se recorta a la posición de la página; como
+        // mucho sobra medio bloque
+        let samples = decoded_frames(decoder.as_mut());
+        assert!((44100..=44100 + 128).contains(&samples.len()), "{} frames", samples.len());
+        assert!(samples.iter().all(|&sample| sample == 0.0));
+        decoder.rewind().unwrap();
+        assert_eq!(decoded_frames(decoder.as_mut()).len(), samples.len());
+
+        let clip = decode_audio("silence", SILENCE_OGG, 48000).unwrap();
+        assert!((48000..=48000 + 140).contains(&clip.samples.len()), "{} frames", clip.samples.len());
+    }
+
+    #[test]
+    fn unknown_bytes_are_rejected() {
+        assert_eq!(AudioFormat::detect(b"ID3\x04"), None);
+        assert!(open_decoder(Arc::from(&b"ID3\x04 no es audio"[..])).is_err());
+    }
+}
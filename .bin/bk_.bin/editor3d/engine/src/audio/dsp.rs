@@ -0,0 +1,306 @@
+//! Efectos por fuente
+//!
+//! Cada `AudioComponent` lleva una cadena de efectos (`effect_chain`) que el
+//! mezclador aplica en orden a la señal decodificada de su voz, antes de la
+//! oclusión y la espacialización. Los efectos procesan bloques estéreo
+//! intercalados y guardan su estado (filtros, líneas de retardo, envolvente)
+//! entre bloques; todo el estado es `Send` para vivir en el hilo de audio.
+//!
+//! - Paso bajo y paso alto: de primer orden (transformada bilineal), que
+//!   caen 6 dB por octava pasado el corte. Con `q` por encima de 1/√2 un
+//!   pico resonante en el corte (Audio EQ Cookbook) lleva allí la ganancia
+//!   a `q`, como en un filtro de segundo orden, sin cambiar la pendiente.
+//! - Distorsión: saturación `tanh` normalizada para que la ganancia de pico
+//!   no cambie con `drive`.
+//! - Chorus: retardo modulado por un LFO senoidal, en cuadratura entre los
+//!   dos canales.
+//! - Compresor: detector de pico enlazado en estéreo con ataque y
+//!   liberación, y reducción por encima del umbral según el ratio.
+
+use serde::{Serialize, Deserialize};
+use std::f32::consts::PI;
+
+/// Retardo central del chorus en milisegundos
+const CHORUS_BASE_DELAY_MS: f32 = 20.0;
+/// Modulación del retardo del chorus con profundidad 1, en milisegundos
+const CHORUS_MAX_DEPTH_MS: f32 = 8.0;
+
+/// Efecto de una cadena
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AudioEffect {
+    /// Paso bajo resonante
+    LowPassFilter { cutoff_hz: f32, q: f32 },
+    /// Paso alto resonante
+    HighPassFilter { cutoff_hz: f32, q: f32 },
+    /// Saturación (`drive` 1 casi lineal)
+    Distortion { drive: f32 },
+    /// Chorus: frecuencia del LFO, profundidad en [0, 1] y mezcla húmeda
+    Chorus { rate_hz: f32, depth: f32, mix: f32 },
+    /// Compresor: umbral en dBFS, ratio y tiempos en milisegundos
+    Compressor { threshold: f32, ratio: f32, attack_ms: f32, release_ms: f32 },
+}
+
+/// Coeficientes de un biquad normalizados por a0
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadCoefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl BiquadCoefficients {
+    /// Sin efecto
+    const IDENTITY: Self = Self { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 };
+
+    /// Frecuencia angular del corte, por debajo de Nyquist
+    fn angular(cutoff_hz: f32, sample_rate: u32) -> f32 {
+        let nyquist = sample_rate as f32 * 0.5;
+        2.0 * PI * cutoff_hz.clamp(1.0, nyquist * 0.99) / sample_rate as f32
+    }
+
+    /// Paso bajo o paso alto de primer orden por transformada bilineal
+    fn first_order(cutoff_hz: f32, sample_rate: u32, high_pass: bool) -> Self {
+        let k = (Self::angular(cutoff_hz, sample_rate) * 0.5).tan();
+        let norm = 1.0 / (1.0 + k);
+        let (b0, b1) = if high_pass { (norm, -norm) } else { (k * norm, k * norm) };
+        Self { b0, b1, b2: 0.0, a1: (k - 1.0) * norm, a2: 0.0 }
+    }
+
+    /// Pico del Audio EQ Cookbook en el corte que, sobre los -3 dB del
+    /// primer orden, deja la ganancia en `q` (sin efecto hasta 1/√2)
+    fn resonance(cutoff_hz: f32, q: f32, sample_rate: u32) -> Self {
+        let peak = q * std::f32::consts::SQRT_2;
+        if peak <= 1.0 {
+            return Self::IDENTITY;
+        }
+        let w0 = Self::angular(cutoff_hz, sample_rate);
+        let a = peak.sqrt();
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha / a;
+        Self {
+            b0: (1.0 + alpha * a) / a0,
+            b1: -2.0 * cos / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha / a) / a0,
+        }
+    }
+
+    /// Filtro y resonancia de un paso bajo o paso alto
+    fn filter(cutoff_hz: f32, q: f32, sample_rate: u32, high_pass: bool) -> [Self; 2] {
+        [Self::first_order(cutoff_hz, sample_rate, high_pass), Self::resonance(cutoff_hz, q, sample_rate)]
+    }
+}
+
+/// Estado de un biquad en forma directa II transpuesta
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadState {
+    z1: f32,
+    z2: f32,
+}
+
+impl BiquadState {
+    fn process(&mut self, c: &BiquadCoefficients, input: f32) -> f32 {
+        let output = c.b0 * input + self.z1;
+        self.z1 = c.b1 * input - c.a1 * output + self.z2;
+        self.z2 = c.b2 * input - c.a2 * output;
+        output
+    }
+}
+
+/// Estado de un efecto
+#[derive(Debug, Clone)]
+enum EffectState {
+    /// Filtro y resonancia, con su estado por canal
+    Biquad { coefficients: [BiquadCoefficients; 2], channels: [[BiquadState; 2]; 2] },
+    Distortion,
+    Chorus { lines: [Vec<f32>; 2], write: usize, phase: f32 },
+    Compressor { envelope: f32 },
+}
+
+/// Efecto con su estado
+#[derive(Debug, Clone)]
+pub struct EffectProcessor {
+    effect: AudioEffect,
+    sample_rate: u32,
+    state: EffectState,
+}
+
+impl EffectProcessor {
+    /// Efecto sin historia a una frecuencia de muestreo
+    pub fn new(effect: AudioEffect, sample_rate: u32) -> Self {
+        let state = match &effect {
+            AudioEffect::LowPassFilter { cutoff_hz, q } => EffectState::Biquad {
+                coefficients: BiquadCoefficients::filter(*cutoff_hz, *q, sample_rate, false),
+                channels: Default::default(),
+            },
+            AudioEffect::HighPassFilter { cutoff_hz, q } => EffectState::Biquad {
+                coefficients: BiquadCoefficients::filter(*cutoff_hz, *q, sample_rate, true),
+                channels: Default::default(),
+            },
+            AudioEffect::Distortion { .. } => EffectState::Distortion,
+            AudioEffect::Chorus { .. } => {
+                let length = ((CHORUS_BASE_DELAY_MS + CHORUS_MAX_DEPTH_MS) * 0.001 * sample_rate as f32).ceil() as usize + 2;
+                EffectState::Chorus { lines: [vec![0.0; length], vec![0.0; length]], write: 0, phase: 0.0 }
+            }
+            AudioEffect::Compressor { .. } => EffectState::Compressor { envelope: 0.0 },
+        };
+        Self { effect, sample_rate, state }
+    }
+
+    /// Efecto configurado
+    pub fn effect(&self) -> &AudioEffect {
+        &self.effect
+    }
+
+    /// Procesar en su sitio un bloque estéreo intercalado
+    pub fn process_block(&mut self, samples: &mut [f32], sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            *self = Self::new(self.effect.clone(), sample_rate);
+        }
+        match (&self.effect, &mut self.state) {
+            (_, EffectState::Biquad { coefficients, channels }) => {
+                for frame in samples.chunks_exact_mut(2) {
+                    for (sample, stages) in frame.iter_mut().zip(channels.iter_mut()) {
+                        for (state, coefficients) in stages.iter_mut().zip(coefficients.iter()) {
+                            *sample = state.process(coefficients, *sample);
+                        }
+                    }
+                }
+            }
+            (AudioEffect::Distortion { drive }, EffectState::Distortion) => {
+                let drive = drive.max(1e-3);
+                let normalization = 1.0 / drive.tanh();
+                for sample in samples.iter_mut() {
+                    *sample = (*sample * drive).tanh() * normalization;
+                }
+            }
+            (AudioEffect::Chorus { rate_hz, depth, mix }, EffectState::Chorus { lines, write, phase }) => {
+                let rate = self.sample_rate as f32;
+                let length = lines[0].len();
+                let step = 2.0 * PI * rate_hz.max(0.0) / rate;
+                let (mix, depth) = (mix.clamp(0.0, 1.0), depth.clamp(0.0, 1.0));
+                for frame in samples.chunks_exact_mut(2) {
+                    for (channel, sample) in frame.iter_mut().enumerate() {
+                        let line = &mut lines[channel];
+                        line[*write] = *sample;
+                        // Canal derecho desfasado 90°
+                        let lfo = (*phase + channel as f32 * PI * 0.5).sin();
+                        let delay = (CHORUS_BASE_DELAY_MS + CHORUS_MAX_DEPTH_MS * depth * lfo) * 0.001 * rate;
+                        let position = (*write as f32 - delay).rem_euclid(length as f32);
+                        let index = position.floor() as usize % length;
+                        let t = position.fract();
+                        let delayed = line[index] + (line[(index + 1) % length] - line[index]) * t;
+                        *sample = *sample * (1.0 - mix) + delayed * mix;
+                    }
+                    *write = (*write + 1) % length;
+                    *phase = (*phase + step) % (2.0 * PI);
+                }
+            }
+            (AudioEffect::Compressor { threshold, ratio, attack_ms, release_ms }, EffectState::Compressor { envelope }) => {
+                let rate = self.sample_rate as f32;
+                let coefficient = |ms: f32| (-1.0 / (ms.max(0.01) * 0.001 * rate)).exp();
+                let (attack, release) = (coefficient(*attack_ms), coefficient(*release_ms));
+                let slope = 1.0 - 1.0 / ratio.max(1.0);
+                for frame in samples.chunks_exact_mut(2) {
+                    let peak = frame[0].abs().max(frame[1].abs());
+                    let coefficient = if peak > *envelope { attack } else { release };
+                    *envelope = peak + (*envelope - peak) * coefficient;
+                    let level = 20.0 * envelope.max(1e-6).log10();
+                    let reduction = (level - threshold).max(0.0) * slope;
+                    let gain = 10f32.powf(-reduction / 20.0);
+                    frame[0] *= gain;
+                    frame[1] *= gain;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Cadena de efectos de una voz
+#[derive(Debug, Clone, Default)]
+pub struct EffectChain {
+    processors: Vec<EffectProcessor>,
+}
+
+impl EffectChain {
+    /// Cadena con los efectos en orden
+    pub fn new(effects: &[AudioEffect], sample_rate: u32) -> Self {
+        Self { processors: effects.iter().map(|effect| EffectProcessor::new(effect.clone(), sample_rate)).collect() }
+    }
+
+    /// La cadena tiene estos efectos (con los mismos parámetros)
+    pub fn matches(&self, effects: &[AudioEffect]) -> bool {
+        self.processors.len() == effects.len()
+            && self.processors.iter().zip(effects).all(|(processor, effect)| processor.effect() == effect)
+    }
+
+    /// Sin efectos
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    /// Pasar un bloque estéreo intercalado por los efectos en orden
+    pub fn process_block(&mut self, samples: &mut [f32], sample_rate: u32) {
+        for processor in &mut self.processors {
+            processor.process_block(samples, sample_rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+    /// Frames medidos: 0,1 s, ciclos enteros de todas las frecuencias
+    const WINDOW: usize = 4800;
+
+    /// Amplitud de salida de un seno de amplitud 1 ya estable
+    fn response(effect: &AudioEffect, frequency: f32) -> f32 {
+        let mut processor = EffectProcessor::new(effect.clone(), SAMPLE_RATE);
+        let cycles = (frequency * WINDOW as f32 / SAMPLE_RATE as f32) as usize;
+        let phase = |i: usize| 2.0 * PI * ((cycles * i) % WINDOW) as f32 / WINDOW as f32;
+        let mut samples: Vec<f32> = (0..2 * WINDOW).flat_map(|i| [phase(i).sin(); 2]).collect();
+        processor.process_block(&mut samples, SAMPLE_RATE);
+
+        // Componente de la frecuencia en la segunda ventana (canal izquierdo)
+        let (re, im) = samples[2 * WINDOW..].chunks_exact(2).enumerate().fold((0.0, 0.0), |(re, im), (i, frame)| {
+            (re + frame[0] * phase(i).cos(), im + frame[0] * phase(i).sin())
+        });
+        2.0 * (re * re + im * im).sqrt() / WINDOW as f32
+    }
+
+    fn db(gain: f32) -> f32 {
+        20.0 * gain.log10()
+    }
+
+    #[test]
+    fn low_pass_rolls_off_six_db_per_octave_above_cutoff() {
+        let low_pass = AudioEffect::LowPassFilter { cutoff_hz: 200.0, q: std::f32::consts::FRAC_1_SQRT_2 };
+        assert!(db(response(&low_pass, 20.0)).abs() < 0.1);
+        assert!((db(response(&low_pass, 200.0)) + 3.0).abs() < 0.1);
+        for octave in [800.0, 1600.0] {
+            let drop = db(response(&low_pass, octave)) - db(response(&low_pass, octave * 2.0));
+            assert!((drop - 6.0).abs() < 0.5, "{} dB de {} a {} Hz", drop, octave, octave * 2.0);
+        }
+
+        // La resonancia lleva el corte a `q` sin cambiar la pendiente
+        let resonant = AudioEffect::LowPassFilter { cutoff_hz: 200.0, q: 2.0 };
+        assert!((response(&resonant, 200.0) - 2.0).abs() < 0.05);
+        let drop = db(response(&resonant, 1600.0)) - db(response(&resonant, 3200.0));
+        assert!((drop - 6.0).abs() < 0.5, "{} dB con resonancia", drop);
+    }
+
+    #[test]
+    fn high_pass_mirrors_the_low_pass() {
+        let high_pass = AudioEffect::HighPassFilter { cutoff_hz: 4000.0, q: std::f32::consts::FRAC_1_SQRT_2 };
+        assert!((db(response(&high_pass, 4000.0)) + 3.0).abs() < 0.1);
+        let drop = db(response(&high_pass, 500.0)) - db(response(&high_pass, 250.0));
+        assert!((drop - 6.0).abs() < 0.5, "{} dB de 500 a 250 Hz", drop);
+    }
+}
@@ -0,0 +1,382 @@
+//! Eventos de audio con nombre
+//!
+//! Un evento describe un sonido del juego ("footstep", "door_open") en vez
+//! de un clip concreto: un conjunto de clips del que se elige uno al azar o
+//! en secuencia, el bus por el que suena y rangos de volumen y pitch de los
+//! que se sortea cada disparo.
+//!
+//! Los parámetros globales (por ejemplo "intensity") se asignan a volumen o
+//! pitch con un mapeo lineal por evento; al cambiar un parámetro las voces
+//! vivas de los eventos que lo usan se actualizan.
+
+use anyhow::{Result, anyhow};
+use glam::Vec3;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use tracing::debug;
+
+use super::dsp::AudioEffect;
+use super::mixer::{AudioBus, AudioMixer, VoiceId, VoiceParams};
+use crate::ecs::{EntityId, SpatialAudioConfig};
+
+/// Parámetro de la intensidad del juego (también mueve la música adaptativa)
+pub const PARAMETER_INTENSITY: &str = "intensity";
+
+/// Elección del clip de cada disparo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClipSelection {
+    /// Al azar, sin repetir el anterior si hay más de uno
+    Random,
+    /// En orden, volviendo al primero
+    Sequence,
+}
+
+/// Propiedad de una voz que controla un parámetro
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventProperty {
+    Volume,
+    Pitch,
+}
+
+/// Mapeo lineal de un parámetro a un multiplicador de volumen o pitch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParameterMapping {
+    /// Nombre del parámetro
+    pub parameter: String,
+    /// Propiedad afectada
+    pub property: EventProperty,
+    /// Rango del parámetro (se satura fuera)
+    pub input_range: (f32, f32),
+    /// Multiplicador en los extremos del rango
+    pub output_range: (f32, f32),
+}
+
+impl ParameterMapping {
+    /// Multiplicador para un valor del parámetro
+    pub fn evaluate(&self, value: f32) -> f32 {
+        let (low, high) = self.input_range;
+        let t = if (high - low).abs() > f32::EPSILON { ((value - low) / (high - low)).clamp(0.0, 1.0) } else { 0.0 };
+        self.output_range.0 + (self.output_range.1 - self.output_range.0) * t
+    }
+}
+
+/// Evento de audio
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioEventDefinition {
+    /// Nombre del evento
+    pub id: String,
+    /// Clips posibles
+    pub clips: Vec<String>,
+    /// Elección del clip
+    pub selection: ClipSelection,
+    /// Bus de mezcla
+    pub bus: AudioBus,
+    /// Rango de volumen de cada disparo
+    pub volume_range: (f32, f32),
+    /// Rango de pitch de cada disparo
+    pub pitch_range: (f32, f32),
+    /// Vuelve a empezar al terminar
+    #[serde(default)]
+    pub looped: bool,
+    /// Atenuación y LOD (None para sonido 2D)
+    #[serde(default)]
+    pub spatial: Option<SpatialAudioConfig>,
+    /// Efectos de las voces del evento
+    #[serde(default)]
+    pub effects: Vec<AudioEffect>,
+    /// Parámetros que modulan volumen o pitch
+    #[serde(default)]
+    pub parameters: Vec<ParameterMapping>,
+}
+
+impl AudioEventDefinition {
+    /// Evento 2D con un rango de pitch y volumen 1
+    pub fn new(id: &str, clips: Vec<String>, bus: AudioBus) -> Self {
+        Self {
+            id: id.to_string(),
+            clips,
+            selection: ClipSelection::Random,
+            bus,
+            volume_range: (1.0, 1.0),
+            pitch_range: (1.0, 1.0),
+            looped: false,
+            spatial: None,
+            effects: Vec::new(),
+            parameters: Vec::new(),
+        }
+    }
+}
+
+/// Voz viva de un evento, con el volumen y el pitch sorteados
+#[derive(Debug, Clone)]
+struct EventInstance {
+    event_id: String,
+    volume: f32,
+    pitch: f32,
+}
+
+/// Eventos registrados, parámetros y voces vivas
+#[derive(Debug, Clone)]
+pub struct AudioEventBank {
+    events: HashMap<String, AudioEventDefinition>,
+    /// Último clip elegido por evento
+    cursors: HashMap<String, usize>,
+    parameters: HashMap<String, f32>,
+    instances: HashMap<VoiceId, EventInstance>,
+    /// Estado del generador xorshift64*
+    rng: u64,
+}
+
+impl Default for AudioEventBank {
+    fn default() -> Self {
+        Self::new(0x9E37_79B9_7F4A_7C15)
+    }
+}
+
+impl AudioEventBank {
+    /// Banco vacío con una semilla para el sorteo
+    pub fn new(seed: u64) -> Self {
+        Self {
+            events: HashMap::new(),
+            cursors: HashMap::new(),
+            parameters: HashMap::new(),
+            instances: HashMap::new(),
+            rng: seed.max(1),
+        }
+    }
+
+    /// Registrar un evento (reemplaza el del mismo nombre)
+    pub fn register(&mut self, event: AudioEventDefinition) {
+        self.cursors.remove(&event.id);
+        self.events.insert(event.id.clone(), event);
+    }
+
+    /// Quitar un evento
+    pub fn remove(&mut self, event_id: &str) -> Option<AudioEventDefinition> {
+        self.cursors.remove(event_id);
+        self.events.remove(event_id)
+    }
+
+    /// Evento registrado
+    pub fn get(&self, event_id: &str) -> Option<&AudioEventDefinition> {
+        self.events.get(event_id)
+    }
+
+    /// Valor de un parámetro (0 si no se ha fijado)
+    pub fn parameter(&self, name: &str) -> f32 {
+        self.parameters.get(name).copied().unwrap_or(0.0)
+    }
+
+    /// Fijar un parámetro y actualizar las voces vivas que lo usan
+    pub fn set_parameter(&mut self, mixer: &mut AudioMixer, name: &str, value: f32) {
+        self.parameters.insert(name.to_string(), value);
+        self.instances.retain(|voice_id, _| mixer.voice(*voice_id).is_some_and(|voice| !voice.is_finished()));
+        for (voice_id, instance) in &self.instances {
+            let Some(event) = self.events.get(&instance.event_id) else {
+                continue;
+            };
+            if !event.parameters.iter().any(|mapping| mapping.parameter == name) {
+                continue;
+            }
+            let volume = instance.volume * self.modulation(event, EventProperty::Volume);
+            let pitch = instance.pitch * self.modulation(event, EventProperty::Pitch);
+            if let Some(voice) = mixer.voice_mut(*voice_id) {
+                voice.params.volume = volume;
+                voice.params.pitch = pitch;
+            }
+        }
+    }
+
+    /// Disparar un evento en una posición. None si su clip no está cargado
+    pub fn trigger(
+        &mut self,
+        mixer: &mut AudioMixer,
+        event_id: &str,
+        position: Vec3,
+        entity_id: Option<EntityId>,
+    ) -> Result<Option<VoiceId>> {
+        let event = self.events.get(event_id).ok_or_else(|| anyhow!("Evento de audio no encontrado: {}", event_id))?;
+        if event.clips.is_empty() {
+            return Err(anyhow!("El evento de audio {} no tiene clips", event_id));
+        }
+
+        let count = event.clips.len();
+        let previous = self.cursors.get(event_id).copied();
+        let index = match (event.selection, previous) {
+            (ClipSelection::Sequence, Some(previous)) => (previous + 1) % count,
+            (ClipSelection::Sequence, None) => 0,
+            (ClipSelection::Random, Some(previous)) if count > 1 => {
+                // Uno de los otros clips, para no repetir el anterior
+                let offset = 1 + (next_random(&mut self.rng) * (count - 1) as f32) as usize % (count - 1);
+                (previous + offset) % count
+            }
+            (ClipSelection::Random, _) => (next_random(&mut self.rng) * count as f32) as usize % count,
+        };
+        self.cursors.insert(event_id.to_string(), index);
+
+        let volume = sample_range(&mut self.rng, event.volume_range);
+        let pitch = sample_range(&mut self.rng, event.pitch_range);
+        let params = VoiceParams {
+            clip_id: event.clips[index].clone(),
+            bus: event.bus,
+            volume: volume * self.modulation(event, EventProperty::Volume),
+            pitch: pitch * self.modulation(event, EventProperty::Pitch),
+            looped: event.looped,
+            position,
+            spatial: event.spatial.clone(),
+            entity_id,
+            source_id: None,
+            reverb_send: if event.spatial.is_some() { 1.0 } else { 0.0 },
+            effects: event.effects.clone(),
+            velocity: Vec3::ZERO,
+            doppler: true,
+        };
+        let clip_id = params.clip_id.clone();
+        match mixer.play(params) {
+            Some(voice_id) => {
+                self.instances.insert(voice_id, EventInstance { event_id: event_id.to_string(), volume, pitch });
+                Ok(Some(voice_id))
+            }
+            None => {
+                debug!("Clip {} sin cargar para el evento {}", clip_id, event_id);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Producto de los mapeos de una propiedad con los parámetros actuales
+    fn modulation(&self, event: &AudioEventDefinition, property: EventProperty) -> f32 {
+        event.parameters.iter()
+            .filter(|mapping| mapping.property == property)
+            .map(|mapping| mapping.evaluate(self.parameter(&mapping.parameter)))
+            .product()
+    }
+}
+
+/// Número aleatorio en [0, 1) (xorshift64*)
+fn next_random(state: &mut u64) -> f32 {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    let bits = state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40;
+    bits as f32 / (1u64 << 24) as f32
+}
+
+/// Valor uniforme en un rango (en cualquier orden)
+fn sample_range(state: &mut u64, (low, high): (f32, f32)) -> f32 {
+    low + (high - low) * next_random(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::bus::DuckingRule;
+    use crate::audio::mixer::{AudioClip, MixListener};
+    use glam::Quat;
+
+    const SAMPLE_RATE: u32 = 48_000;
+    const BLOCK: usize = 512;
+
+    /// Mezclador con clips mono de 1 s a un nivel constante
+    fn mixer(clips: &[(&str, f32)]) -> AudioMixer {
+        let mut mixer = AudioMixer::new(SAMPLE_RATE);
+        for (id, level) in clips {
+            mixer.load_clip(AudioClip { id: id.to_string(), sample_rate: SAMPLE_RATE, channels: 1, samples: vec![*level; SAMPLE_RATE as usize] });
+        }
+        mixer
+    }
+
+    fn listener() -> MixListener {
+        MixListener { position: Vec3::ZERO, orientation: Quat::IDENTITY, velocity: Vec3::ZERO, volume: 1.0, hrtf: false, hrtf_interpolation: false }
+    }
+
+    #[test]
+    fn triggering_picks_a_pool_clip_within_the_pitch_range() {
+        let pool = ["step-1", "step-2", "step-3"];
+        let mut mixer = mixer(&pool.map(|id| (id, 0.1)));
+        let mut bank = AudioEventBank::new(42);
+        bank.register(AudioEventDefinition {
+            volume_range: (0.5, 0.8),
+            pitch_range: (0.9, 1.1),
+            ..AudioEventDefinition::new("footstep", pool.iter().map(|id| id.to_string()).collect(), AudioBus::Sfx)
+        });
+
+        let mut picked = Vec::new();
+        for _ in 0..200 {
+            let voice_id = bank.trigger(&mut mixer, "footstep", Vec3::ZERO, None).unwrap().unwrap();
+            let params = &mixer.voice(voice_id).unwrap().params;
+            assert!(pool.contains(&params.clip_id.as_str()), "clip {}", params.clip_id);
+            assert!((0.9..=1.1).contains(&params.pitch), "pitch {}", params.pitch);
+            assert!((0.5..=0.8).contains(&params.volume), "volumen {}", params.volume);
+            assert_eq!(params.bus, AudioBus::Sfx);
+            picked.push(params.clip_id.clone());
+        }
+        // Al azar sin repetir el anterior, y con todos los clips
+        assert!(picked.windows(2).all(|pair| pair[0] != pair[1]));
+        assert!(pool.iter().all(|id| picked.iter().any(|clip| clip == id)));
+        // Los pitch no son todos iguales
+        let pitches: Vec<f32> = mixer.voices().iter().map(|voice| voice.params.pitch).collect();
+        let (low, high) = pitches.iter().fold((f32::MAX, f32::MIN), |(low, high), pitch| (low.min(*pitch), high.max(*pitch)));
+        assert!(high - low > 0.1, "pitch de {} a {}", low, high);
+    }
+
+    #[test]
+    fn sequence_selection_cycles_through_the_pool() {
+        let pool = ["a", "b", "c"];
+        let mut mixer = mixer(&pool.map(|id| (id, 0.1)));
+        let mut bank = AudioEventBank::default();
+        bank.register(AudioEventDefinition {
+            selection: ClipSelection::Sequence,
+            ..AudioEventDefinition::new("door", pool.iter().map(|id| id.to_string()).collect(), AudioBus::Sfx)
+        });
+        let picked: Vec<String> = (0..6)
+            .map(|_| {
+                let voice_id = bank.trigger(&mut mixer, "door", Vec3::ZERO, None).unwrap().unwrap();
+                mixer.voice(voice_id).unwrap().params.clip_id.clone()
+            })
+            .collect();
+        assert_eq!(picked, ["a", "b", "c", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn voice_activity_ducks_music_to_the_target_within_the_attack_time() {
+        let rule = DuckingRule::voice_over_music();
+        let mut mixer = mixer(&[("music", 0.5), ("speech", 0.0)]);
+        mixer.buses_mut().add_ducking(rule.clone());
+        let mut bank = AudioEventBank::default();
+        bank.register(AudioEventDefinition { looped: true, ..AudioEventDefinition::new("music", vec!["music".to_string()], AudioBus::Music) });
+        bank.register(AudioEventDefinition { looped: true, ..AudioEventDefinition::new("speech", vec!["speech".to_string()], AudioBus::Voice) });
+        bank.trigger(&mut mixer, "music", Vec3::ZERO, None).unwrap().unwrap();
+        mixer.mix(&listener(), BLOCK);
+        assert_eq!(mixer.bus_gain(AudioBus::Music), 1.0);
+
+        // La voz empieza a sonar: la música baja hasta el objetivo en los
+        // bloques que cubren el ataque, no antes
+        let speech = bank.trigger(&mut mixer, "speech", Vec3::ZERO, None).unwrap().unwrap();
+        let target = 10f32.powf(-rule.amount_db / 20.0);
+        let blocks = (rule.attack * SAMPLE_RATE as f32 / BLOCK as f32).ceil() as usize;
+        let mut output = Vec::new();
+        for block in 1..=blocks {
+            output = mixer.mix(&listener(), BLOCK);
+            if block < blocks {
+                assert!(mixer.bus_gain(AudioBus::Music) > target + 1e-4, "objetivo antes del bloque {}", block);
+            }
+        }
+        assert!((mixer.bus_gain(AudioBus::Music) - target).abs() < 1e-5);
+        assert!((output[output.len() - 2] - 0.5 * target).abs() < 1e-4, "música a {}", output[output.len() - 2]);
+        assert_eq!(mixer.bus_gain(AudioBus::Voice), 1.0);
+
+        // El chat de voz, sin voces en el mezclador, mantiene el ducking; sin
+        // actividad la música vuelve en el tiempo de liberación
+        mixer.stop(speech);
+        mixer.buses_mut().set_external_activity(AudioBus::Voice, true);
+        mixer.mix(&listener(), BLOCK);
+        assert!((mixer.bus_gain(AudioBus::Music) - target).abs() < 1e-5);
+        mixer.buses_mut().set_external_activity(AudioBus::Voice, false);
+        let blocks = (rule.release * SAMPLE_RATE as f32 / BLOCK as f32).ceil() as usize;
+        for _ in 0..=blocks {
+            mixer.mix(&listener(), BLOCK);
+        }
+        assert_eq!(mixer.bus_gain(AudioBus::Music), 1.0);
+    }
+}
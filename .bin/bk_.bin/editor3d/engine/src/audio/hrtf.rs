@@ -0,0 +1,146 @@
+//! Respuestas al impulso de cabeza (HRTF)
+//!
+//! Un `HrtfSet` guarda pares de respuestas al impulso (oído izquierdo y
+//! derecho) medidas en varias direcciones. Para una dirección se toma la
+//! medida más cercana o, con interpolación, la mezcla de las dos más
+//! cercanas, y el mezclador convoluciona con ellas la señal mono de la voz.
+//!
+//! El conjunto incluido (`spherical_head`) se genera con el modelo de cabeza
+//! esférica: retardo interaural de Woodworth, diferencia de nivel según el
+//! seno del ángulo lateral y un filtro paso bajo de sombra en el oído
+//! lejano. Los conjuntos medidos (p. ej. exportados de un archivo SOFA) se
+//! cargan con `HrtfSet::new`.
+
+use anyhow::{Result, anyhow};
+
+/// Radio de la cabeza en metros
+const HEAD_RADIUS: f32 = 0.0875;
+/// Velocidad del sonido en m/s
+pub(crate) const SPEED_OF_SOUND: f32 = 343.0;
+/// Diferencia de nivel máxima (fuente a 90°) en dB
+const MAX_ILD_DB: f32 = 10.0;
+/// Separación en grados entre las direcciones del conjunto incluido
+const SPHERICAL_HEAD_STEP_DEGREES: usize = 15;
+
+/// Respuesta al impulso de una dirección
+#[derive(Debug, Clone)]
+pub struct HrtfEntry {
+    /// Azimut en radianes (0 delante, positivo a la derecha)
+    pub azimuth: f32,
+    /// Elevación en radianes (positiva hacia arriba)
+    pub elevation: f32,
+    /// Respuesta del oído izquierdo
+    pub left: Vec<f32>,
+    /// Respuesta del oído derecho
+    pub right: Vec<f32>,
+}
+
+/// Conjunto de respuestas al impulso
+#[derive(Debug, Clone)]
+pub struct HrtfSet {
+    /// Frecuencia de muestreo de las respuestas
+    sample_rate: u32,
+    /// Longitud de las respuestas
+    taps: usize,
+    /// Direcciones medidas
+    entries: Vec<HrtfEntry>,
+}
+
+impl HrtfSet {
+    /// Conjunto a partir de respuestas medidas. Todas deben tener la misma
+    /// longitud
+    pub fn new(sample_rate: u32, entries: Vec<HrtfEntry>) -> Result<Self> {
+        let taps = entries.first().map(|entry| entry.left.len()).ok_or_else(|| anyhow!("Conjunto HRTF vacío"))?;
+        if taps == 0 || entries.iter().any(|entry| entry.left.len() != taps || entry.right.len() != taps) {
+            return Err(anyhow!("Las respuestas HRTF deben tener la misma longitud"));
+        }
+        Ok(Self { sample_rate, taps, entries })
+    }
+
+    /// Conjunto incluido del modelo de cabeza esférica, en el plano
+    /// horizontal cada 15°
+    pub fn spherical_head(sample_rate: u32) -> Self {
+        let max_delay = HEAD_RADIUS / SPEED_OF_SOUND * (std::f32::consts::FRAC_PI_2 + 1.0) * sample_rate as f32;
+        let taps = max_delay.ceil() as usize + 3;
+        let entries = (0..360).step_by(SPHERICAL_HEAD_STEP_DEGREES)
+            .map(|degrees| {
+                let azimuth = (degrees as f32).to_radians();
+                let (left, right) = spherical_head_response(azimuth, sample_rate, taps);
+                HrtfEntry { azimuth: wrap_angle(azimuth), elevation: 0.0, left, right }
+            })
+            .collect();
+        Self { sample_rate, taps, entries }
+    }
+
+    /// Frecuencia de muestreo
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Longitud de las respuestas
+    pub fn taps(&self) -> usize {
+        self.taps
+    }
+
+    /// Respuestas de una dirección: la medida más cercana o, con
+    /// `interpolate`, la mezcla de las dos más cercanas
+    pub fn filters(&self, azimuth: f32, elevation: f32, interpolate: bool) -> (Vec<f32>, Vec<f32>) {
+        let mut nearest: Vec<(f32, &HrtfEntry)> = self.entries.iter()
+            .map(|entry| (angular_distance(azimuth, elevation, entry.azimuth, entry.elevation), entry))
+            .collect();
+        nearest.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let (first_distance, first) = nearest[0];
+        let Some(&(second_distance, second)) = nearest.get(1).filter(|_| interpolate && first_distance > f32::EPSILON) else {
+            return (first.left.clone(), first.right.clone());
+        };
+        let t = first_distance / (first_distance + second_distance);
+        let mix = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(a, b)| a + (b - a) * t).collect();
+        (mix(&first.left, &second.left), mix(&first.right, &second.right))
+    }
+}
+
+/// Respuestas del modelo de cabeza esférica para un azimut
+fn spherical_head_response(azimuth: f32, sample_rate: u32, taps: usize) -> (Vec<f32>, Vec<f32>) {
+    // Ángulo lateral: delante y detrás suenan igual en este modelo
+    let lateral = azimuth.sin().clamp(-1.0, 1.0).asin();
+    let itd = HEAD_RADIUS / SPEED_OF_SOUND * (lateral.abs() + lateral.abs().sin());
+    let far_gain = 10f32.powf(-MAX_ILD_DB * lateral.abs().sin() / 20.0);
+
+    // Con la fuente delante los dos oídos suman la potencia de un paneo central
+    let scale = std::f32::consts::FRAC_1_SQRT_2;
+    let mut near = vec![0.0; taps];
+    near[0] = scale;
+    let mut far = vec![0.0; taps];
+    let delay = itd * sample_rate as f32;
+    let (index, fraction) = (delay.floor() as usize, delay.fract());
+    // Retardo fraccional y sombra de la cabeza (paso bajo de tres coeficientes)
+    let shadow = lateral.abs() / std::f32::consts::FRAC_PI_2;
+    let kernel = [shadow * 0.25, 1.0 - shadow * 0.5, shadow * 0.25];
+    for (k, weight) in kernel.iter().enumerate() {
+        for (offset, split) in [(0, 1.0 - fraction), (1, fraction)] {
+            if let Some(tap) = far.get_mut(index + k + offset) {
+                *tap += weight * split * far_gain * scale;
+            }
+        }
+    }
+
+    if lateral >= 0.0 {
+        (far, near)
+    } else {
+        (near, far)
+    }
+}
+
+/// Ángulo en (-π, π]
+fn wrap_angle(angle: f32) -> f32 {
+    let wrapped = (angle + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+    if wrapped <= -std::f32::consts::PI { wrapped + std::f32::consts::TAU } else { wrapped }
+}
+
+/// Ángulo entre dos direcciones dadas por azimut y elevación
+fn angular_distance(azimuth_a: f32, elevation_a: f32, azimuth_b: f32, elevation_b: f32) -> f32 {
+    let cos = elevation_a.sin() * elevation_b.sin()
+        + elevation_a.cos() * elevation_b.cos() * (azimuth_a - azimuth_b).cos();
+    cos.clamp(-1.0, 1.0).acos()
+}
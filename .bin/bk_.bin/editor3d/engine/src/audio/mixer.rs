@@ -0,0 +1,1078 @@
+//! Mezclador por software
+//!
+//! Cada voz reproduce un `AudioClip` (PCM decodificado) con su volumen y su
+//! pitch, y el mezclador suma todas las voces en un buffer estéreo
+//! intercalado a la frecuencia de salida. Las voces espaciales se colocan en
+//! el mundo (normalmente en la posición de su entidad):
+//!
+//! - Atenuación por distancia con la `SpatialAudioConfig` de la voz: ganancia
+//!   1 hasta `min_distance` que cae hasta `min_gain` en `max_distance` con
+//!   la curva `(1 - t)^rolloff`, y se queda en `min_gain` más allá.
+//! - Banda de LOD por `near_distance`/`far_distance`: cerca se aplica HRTF si
+//!   está habilitado, en medio un paneo estéreo de potencia constante según
+//!   el azimut respecto al oyente y lejos la voz solo avanza.
+//!
+//! Las voces espaciales con `doppler` cambian su pitch según la velocidad
+//! relativa entre la fuente y el oyente a lo largo de la línea que los une
+//! (el modelo de OpenAL, escalado por `DopplerSettings::factor` y saturado
+//! a su rango). El factor se suaviza con una constante de tiempo para que
+//! una velocidad ruidosa no haga vibrar el tono.
+//!
+//! Las voces de un `StreamingClip` leen sus frames del stream (ya a la
+//! frecuencia de salida, sin pitch ni Doppler) en lugar de un clip cargado.
+//!
+//! La ganancia final sigue la jerarquía de volúmenes: voz × bus × master ×
+//! volumen maestro del oyente, con el silencio y el ducking de cada bus del
+//! grafo (`bus`). Los cambios de ganancia se interpolan a lo largo de cada
+//! bloque para no producir chasquidos.
+//!
+//! Antes de espacializar, la señal de cada voz pasa por su cadena de
+//! efectos (`VoiceParams::effects`, ver `dsp`).
+//!
+//! La oclusión de cada voz (0 sin obstáculos, 1 del todo ocluida) atenúa y
+//! pasa por un paso bajo la señal antes de espacializarla; se acerca a su
+//! objetivo con una constante de tiempo y se interpola muestra a muestra.
+//! Las voces mandan `reverb_send` de su señal a un bus de envío que alimenta
+//! un `Freeverb` por zona de reverb, fundidos según el peso de cada zona.
+
+use glam::{Quat, Vec3};
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use super::bus::BusGraph;
+use super::dsp::{AudioEffect, EffectChain};
+use super::hrtf::{HrtfSet, SPEED_OF_SOUND};
+use super::reverb::{Freeverb, ReverbPreset};
+use super::stream::StreamingClip;
+use super::{AudioLODBand, AudioSourceType};
+use crate::ecs::{AudioType, EntityId, SpatialAudioConfig};
+
+/// PCM decodificado de un clip
+#[derive(Debug, Clone)]
+pub struct AudioClip {
+    /// ID del clip
+    pub id: String,
+    /// Frecuencia de muestreo
+    pub sample_rate: u32,
+    /// Canales (1 mono, 2 estéreo intercalado)
+    pub channels: u16,
+    /// Muestras en [-1, 1]
+    pub samples: Vec<f32>,
+}
+
+impl AudioClip {
+    /// Frames del clip
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    /// Frame (izquierda, derecha) en una posición fraccional, con
+    /// interpolación lineal
+    fn frame_at(&self, position: f64) -> (f32, f32) {
+        let frames = self.frames();
+        if frames == 0 {
+            return (0.0, 0.0);
+        }
+        let index = position.floor() as usize;
+        let t = (position - index as f64) as f32;
+        let read = |frame: usize| {
+            let frame = frame.min(frames - 1);
+            match self.channels {
+                1 => (self.samples[frame], self.samples[frame]),
+                channels => {
+                    let base = frame * channels as usize;
+                    (self.samples[base], self.samples[base + 1])
+                }
+            }
+        };
+        let (a, b) = (read(index), read(index + 1));
+        (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+    }
+}
+
+/// Bus de mezcla
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AudioBus {
+    Master,
+    Music,
+    Sfx,
+    Voice,
+    Ambient,
+}
+
+impl AudioBus {
+    /// Todos los buses
+    pub const ALL: [AudioBus; 5] = [AudioBus::Master, AudioBus::Music, AudioBus::Sfx, AudioBus::Voice, AudioBus::Ambient];
+
+    /// Bus al que va la salida (None para el master)
+    pub fn parent(self) -> Option<AudioBus> {
+        match self {
+            AudioBus::Master => None,
+            _ => Some(AudioBus::Master),
+        }
+    }
+}
+
+impl From<&AudioType> for AudioBus {
+    fn from(audio_type: &AudioType) -> Self {
+        match audio_type {
+            AudioType::Music => AudioBus::Music,
+            AudioType::SFX => AudioBus::Sfx,
+            AudioType::Voice => AudioBus::Voice,
+            AudioType::Ambient => AudioBus::Ambient,
+        }
+    }
+}
+
+impl From<&AudioSourceType> for AudioBus {
+    fn from(source_type: &AudioSourceType) -> Self {
+        match source_type {
+            AudioSourceType::Music => AudioBus::Music,
+            AudioSourceType::Voice => AudioBus::Voice,
+            AudioSourceType::Ambient => AudioBus::Ambient,
+            AudioSourceType::SFX | AudioSourceType::Custom(_) => AudioBus::Sfx,
+        }
+    }
+}
+
+/// ID de una voz
+pub type VoiceId = u64;
+
+/// Parámetros de una voz nueva
+#[derive(Debug, Clone)]
+pub struct VoiceParams {
+    /// Clip a reproducir
+    pub clip_id: String,
+    /// Bus de mezcla
+    pub bus: AudioBus,
+    /// Volumen
+    pub volume: f32,
+    /// Pitch (1 a velocidad normal)
+    pub pitch: f32,
+    /// Vuelve a empezar al terminar
+    pub looped: bool,
+    /// Posición en el mundo
+    pub position: Vec3,
+    /// Atenuación y LOD (None para sonido 2D)
+    pub spatial: Option<SpatialAudioConfig>,
+    /// Entidad que la reproduce (su transformación la coloca)
+    pub entity_id: Option<EntityId>,
+    /// Fuente de audio que la reproduce
+    pub source_id: Option<String>,
+    /// Parte de la señal que va al bus de reverb
+    pub reverb_send: f32,
+    /// Efectos aplicados a la señal antes de la oclusión y el paneo
+    pub effects: Vec<AudioEffect>,
+    /// Velocidad en el mundo (m/s)
+    pub velocity: Vec3,
+    /// Aplicar Doppler si la voz es espacial
+    pub doppler: bool,
+}
+
+/// Constante de tiempo con la que el factor Doppler sigue a su objetivo
+const DOPPLER_SMOOTHING_SECONDS: f32 = 0.08;
+/// Constante de tiempo con la que la oclusión sigue a su objetivo
+const OCCLUSION_SMOOTHING_SECONDS: f32 = 0.1;
+/// Constante de tiempo del fundido entre zonas de reverb
+const REVERB_CROSSFADE_SECONDS: f32 = 0.5;
+/// Peso por debajo del cual una reverb sin objetivo se descarta
+const REVERB_SILENT_WEIGHT: f32 = 1e-4;
+
+/// Efecto de la oclusión máxima
+#[derive(Debug, Clone, Copy)]
+pub struct OcclusionSettings {
+    /// Atenuación en dB con oclusión 1
+    pub max_attenuation_db: f32,
+    /// Corte del paso bajo sin oclusión (Hz)
+    pub max_cutoff: f32,
+    /// Corte del paso bajo con oclusión 1 (Hz)
+    pub min_cutoff: f32,
+}
+
+impl Default for OcclusionSettings {
+    fn default() -> Self {
+        Self { max_attenuation_db: 18.0, max_cutoff: 20_000.0, min_cutoff: 600.0 }
+    }
+}
+
+/// Efecto Doppler
+#[derive(Debug, Clone, Copy)]
+pub struct DopplerSettings {
+    /// Escala de las velocidades (0 desactiva el Doppler)
+    pub factor: f32,
+    /// Factor de pitch mínimo
+    pub min_pitch: f32,
+    /// Factor de pitch máximo
+    pub max_pitch: f32,
+}
+
+impl Default for DopplerSettings {
+    fn default() -> Self {
+        Self { factor: 1.0, min_pitch: 0.5, max_pitch: 2.0 }
+    }
+}
+
+impl DopplerSettings {
+    /// Factor de pitch de una fuente para un oyente
+    pub fn shift(&self, source_position: Vec3, source_velocity: Vec3, listener_position: Vec3, listener_velocity: Vec3) -> f32 {
+        let offset = listener_position - source_position;
+        let distance = offset.length();
+        if self.factor <= 0.0 || distance <= f32::EPSILON {
+            return 1.0;
+        }
+        let direction = offset / distance;
+        // Velocidades hacia el otro extremo, por debajo de la del sonido
+        let limit = SPEED_OF_SOUND / self.factor * 0.99;
+        let listener_speed = listener_velocity.dot(direction).min(limit);
+        let source_speed = source_velocity.dot(direction).min(limit);
+        let shift = (SPEED_OF_SOUND - self.factor * listener_speed) / (SPEED_OF_SOUND - self.factor * source_speed);
+        shift.clamp(self.min_pitch, self.max_pitch.max(self.min_pitch))
+    }
+}
+
+/// Oclusión de una voz
+#[derive(Debug, Clone, Copy, Default)]
+struct VoiceOcclusion {
+    /// Objetivo en [0, 1]
+    target: f32,
+    /// Valor al final del último bloque
+    amount: f32,
+    /// Estado del paso bajo por canal
+    lowpass: (f32, f32),
+}
+
+/// Reverb de una zona en el bus de envío
+struct ZoneReverb {
+    reverb: Freeverb,
+    preset: ReverbPreset,
+    /// Peso al final del último bloque
+    weight: f32,
+    /// Peso objetivo
+    target: f32,
+}
+
+/// Voz en reproducción
+#[derive(Debug)]
+pub struct Voice {
+    /// ID de la voz
+    pub id: VoiceId,
+    /// Parámetros
+    pub params: VoiceParams,
+    /// Stream del que lee (None: el clip `params.clip_id`)
+    stream: Option<StreamingClip>,
+    /// Posición en frames del clip
+    cursor: f64,
+    /// Terminó (sin bucle)
+    finished: bool,
+    /// Últimas muestras de entrada para la convolución HRTF
+    history: Vec<f32>,
+    /// Ganancias (izquierda, derecha) al final del último bloque
+    gains: Option<(f32, f32)>,
+    /// Oclusión entre la voz y el oyente
+    occlusion: VoiceOcclusion,
+    /// Estado de los efectos de `params.effects`
+    effects: EffectChain,
+    /// Factor Doppler suavizado
+    doppler: f32,
+}
+
+impl Voice {
+    /// Terminó de sonar
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Oclusión actual en [0, 1]
+    pub fn occlusion(&self) -> f32 {
+        self.occlusion.amount
+    }
+
+    /// Factor Doppler actual
+    pub fn doppler(&self) -> f32 {
+        self.doppler
+    }
+
+    /// Velocidad de reproducción efectiva (pitch por Doppler)
+    pub fn playback_rate(&self) -> f32 {
+        if self.stream.is_some() {
+            return 1.0;
+        }
+        self.params.pitch.max(0.0) * self.doppler
+    }
+}
+
+/// Ganancia de distancia: 1 hasta `min_distance`, `min_gain` desde
+/// `max_distance` y la curva `(1 - t)^rolloff` entre ambas
+pub fn distance_gain(distance: f32, config: &SpatialAudioConfig) -> f32 {
+    let floor = config.min_gain.clamp(0.0, 1.0);
+    let span = config.max_distance - config.min_distance;
+    if distance <= config.min_distance || span <= f32::EPSILON {
+        return if distance <= config.min_distance { 1.0 } else { floor };
+    }
+    let t = ((distance - config.min_distance) / span).clamp(0.0, 1.0);
+    floor + (1.0 - floor) * (1.0 - t).powf(config.rolloff.max(0.0))
+}
+
+/// Ganancia de una fuente puntual con atenuación inversa al cuadrado: 1
+/// hasta `reference_distance` y `(reference_distance / distance)^2` después
+pub fn inverse_square_gain(distance: f32, reference_distance: f32) -> f32 {
+    let reference_distance = reference_distance.max(f32::EPSILON);
+    let ratio = reference_distance / distance.max(reference_distance);
+    (ratio * ratio).clamp(0.0, 1.0)
+}
+
+/// Azimut de una posición respecto al oyente (0 delante, positivo a la
+/// derecha) y su elevación. El oyente mira hacia -Z
+pub fn listener_direction(offset: Vec3, orientation: Quat) -> (f32, f32) {
+    let local = orientation.inverse() * offset;
+    let length = local.length();
+    if length <= f32::EPSILON {
+        return (0.0, 0.0);
+    }
+    (local.x.atan2(-local.z), (local.y / length).clamp(-1.0, 1.0).asin())
+}
+
+/// Ganancias (izquierda, derecha) de un paneo de potencia constante para un
+/// azimut
+pub fn pan_gains(azimuth: f32) -> (f32, f32) {
+    let pan = azimuth.sin().clamp(-1.0, 1.0);
+    let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+    (angle.cos(), angle.sin())
+}
+
+/// Estado del oyente que usa la mezcla
+#[derive(Debug, Clone, Copy)]
+pub struct MixListener {
+    pub position: Vec3,
+    pub orientation: Quat,
+    /// Velocidad del oyente (m/s)
+    pub velocity: Vec3,
+    /// Volumen maestro del oyente
+    pub volume: f32,
+    /// HRTF en la banda cercana
+    pub hrtf: bool,
+    /// Interpolar entre direcciones HRTF
+    pub hrtf_interpolation: bool,
+}
+
+/// Mezclador de voces
+pub struct AudioMixer {
+    /// Frecuencia de salida
+    sample_rate: u32,
+    /// Clips cargados
+    clips: HashMap<String, Arc<AudioClip>>,
+    /// Voces activas
+    voices: Vec<Voice>,
+    /// Siguiente ID de voz
+    next_voice: VoiceId,
+    /// Volumen, silencio y ducking de los buses
+    buses: BusGraph,
+    /// Respuestas HRTF
+    hrtf: HrtfSet,
+    /// Efecto de la oclusión
+    occlusion: OcclusionSettings,
+    /// Efecto Doppler
+    doppler: DopplerSettings,
+    /// Reverb de cada zona
+    reverbs: HashMap<String, ZoneReverb>,
+}
+
+impl AudioMixer {
+    /// Nuevo mezclador a una frecuencia de salida, con el conjunto HRTF incluido
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            clips: HashMap::new(),
+            voices: Vec::new(),
+            next_voice: 1,
+            buses: BusGraph::new(),
+            hrtf: HrtfSet::spherical_head(sample_rate),
+            occlusion: OcclusionSettings::default(),
+            doppler: DopplerSettings::default(),
+            reverbs: HashMap::new(),
+        }
+    }
+
+    /// Frecuencia de salida
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Cambiar las respuestas HRTF (deben estar a la frecuencia de salida)
+    pub fn set_hrtf(&mut self, hrtf: HrtfSet) {
+        self.hrtf = hrtf;
+        for voice in &mut self.voices {
+            voice.history.clear();
+        }
+    }
+
+    /// Cargar un clip (reemplaza el del mismo ID)
+    pub fn load_clip(&mut self, clip: AudioClip) {
+        self.clips.insert(clip.id.clone(), Arc::new(clip));
+    }
+
+    /// Clip cargado
+    pub fn has_clip(&self, clip_id: &str) -> bool {
+        self.clips.contains_key(clip_id)
+    }
+
+    /// Descargar un clip y parar sus voces
+    pub fn unload_clip(&mut self, clip_id: &str) {
+        self.clips.remove(clip_id);
+        self.voices.retain(|voice| voice.stream.is_some() || voice.params.clip_id != clip_id);
+    }
+
+    /// Crear una voz. None si el clip no está cargado
+    pub fn play(&mut self, params: VoiceParams) -> Option<VoiceId> {
+        if !self.clips.contains_key(&params.clip_id) {
+            return None;
+        }
+        Some(self.push_voice(params, None))
+    }
+
+    /// Crear una voz que reproduce un stream. El ID del stream queda como
+    /// `clip_id` de la voz
+    pub fn play_stream(&mut self, stream: StreamingClip, mut params: VoiceParams) -> VoiceId {
+        params.clip_id = stream.id().to_string();
+        self.push_voice(params, Some(stream))
+    }
+
+    fn push_voice(&mut self, params: VoiceParams, stream: Option<StreamingClip>) -> VoiceId {
+        let id = self.next_voice;
+        self.next_voice += 1;
+        let effects = EffectChain::new(&params.effects, self.sample_rate);
+        self.voices.push(Voice {
+            id,
+            params,
+            stream,
+            cursor: 0.0,
+            finished: false,
+            history: Vec::new(),
+            gains: None,
+            occlusion: VoiceOcclusion::default(),
+            effects,
+            doppler: 1.0,
+        });
+        id
+    }
+
+    /// Parar una voz
+    pub fn stop(&mut self, voice_id: VoiceId) {
+        self.voices.retain(|voice| voice.id != voice_id);
+    }
+
+    /// Parar las voces que cumplen una condición
+    pub fn stop_where(&mut self, predicate: impl Fn(&Voice) -> bool) {
+        self.voices.retain(|voice| !predicate(voice));
+    }
+
+    /// Voz activa
+    pub fn voice(&self, voice_id: VoiceId) -> Option<&Voice> {
+        self.voices.iter().find(|voice| voice.id == voice_id)
+    }
+
+    /// Voz activa, para moverla o cambiar sus parámetros
+    pub fn voice_mut(&mut self, voice_id: VoiceId) -> Option<&mut Voice> {
+        self.voices.iter_mut().find(|voice| voice.id == voice_id)
+    }
+
+    /// Voces activas
+    pub fn voices(&self) -> &[Voice] {
+        &self.voices
+    }
+
+    /// Mover las voces de una fuente a su posición y velocidad actuales
+    pub fn move_source_voices(&mut self, source_id: &str, position: Vec3, velocity: Vec3) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.params.source_id.as_deref() == Some(source_id)) {
+            voice.params.position = position;
+            voice.params.velocity = velocity;
+        }
+    }
+
+    /// Volumen master
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.buses.set_volume(AudioBus::Master, volume);
+    }
+
+    /// Volumen de un bus
+    pub fn set_bus_volume(&mut self, bus: AudioBus, volume: f32) {
+        self.buses.set_volume(bus, volume);
+    }
+
+    /// Ganancia de un bus con sus padres, el silencio y el ducking aplicados
+    pub fn bus_gain(&self, bus: AudioBus) -> f32 {
+        self.buses.gain(bus)
+    }
+
+    /// Grafo de buses
+    pub fn buses(&self) -> &BusGraph {
+        &self.buses
+    }
+
+    /// Grafo de buses, para silenciar buses o cambiar el ducking
+    pub fn buses_mut(&mut self) -> &mut BusGraph {
+        &mut self.buses
+    }
+
+    /// Efecto de la oclusión máxima
+    pub fn set_occlusion_settings(&mut self, settings: OcclusionSettings) {
+        self.occlusion = settings;
+    }
+
+    /// Efecto Doppler
+    pub fn set_doppler_settings(&mut self, settings: DopplerSettings) {
+        self.doppler = settings;
+    }
+
+    /// Objetivo de oclusión de una voz en [0, 1]
+    pub fn set_voice_occlusion(&mut self, voice_id: VoiceId, amount: f32) {
+        if let Some(voice) = self.voice_mut(voice_id) {
+            voice.occlusion.target = amount.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Pesos objetivo de las reverbs por ID de zona. Las zonas que no están
+    /// se funden a 0 y se descartan cuando ya no suenan; una zona cuyo
+    /// preset cambia empieza una reverb nueva
+    pub fn set_reverb_targets(&mut self, targets: &[(String, ReverbPreset, f32)]) {
+        for reverb in self.reverbs.values_mut() {
+            reverb.target = 0.0;
+        }
+        for (id, preset, weight) in targets {
+            let sample_rate = self.sample_rate;
+            let reverb = self.reverbs.entry(id.clone()).or_insert_with(|| ZoneReverb {
+                reverb: Freeverb::new(preset, sample_rate),
+                preset: *preset,
+                weight: 0.0,
+                target: 0.0,
+            });
+            if reverb.preset != *preset {
+                reverb.reverb = Freeverb::new(preset, sample_rate);
+                reverb.preset = *preset;
+            }
+            reverb.target = weight.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Peso actual de la reverb de una zona
+    pub fn reverb_weight(&self, zone_id: &str) -> f32 {
+        self.reverbs.get(zone_id).map(|reverb| reverb.weight).unwrap_or(0.0)
+    }
+
+    /// Mezclar `frames` frames de todas las voces en estéreo intercalado.
+    /// Las voces sin bucle que terminan se quitan después de la mezcla
+    pub fn mix(&mut self, listener: &MixListener, frames: usize) -> Vec<f32> {
+        let mut output = vec![0.0; frames * 2];
+        let mut send = vec![0.0; frames];
+        // El ducking avanza un bloque con los buses que tienen voces sonando
+        let active: HashSet<AudioBus> = self.voices.iter()
+            .filter(|voice| !voice.finished)
+            .map(|voice| voice.params.bus)
+            .collect();
+        self.buses.update(frames as f32 / self.sample_rate as f32, &active);
+        let mut voices = std::mem::take(&mut self.voices);
+        for voice in &mut voices {
+            self.mix_voice(voice, listener, &mut output, &mut send);
+        }
+        voices.retain(|voice| !voice.finished || voice.params.entity_id.is_some());
+        self.voices = voices;
+        self.mix_reverbs(&send, &mut output);
+        output
+    }
+
+    /// Sumar la cola de cada zona, con su peso acercándose al objetivo
+    fn mix_reverbs(&mut self, send: &[f32], output: &mut [f32]) {
+        let frames = send.len();
+        let retain = (-(frames as f32 / self.sample_rate as f32) / REVERB_CROSSFADE_SECONDS).exp();
+        for reverb in self.reverbs.values_mut() {
+            let start = reverb.weight;
+            let end = reverb.target + (start - reverb.target) * retain;
+            reverb.weight = end;
+            for (i, input) in send.iter().enumerate() {
+                let t = (i + 1) as f32 / frames.max(1) as f32;
+                let gain = (start + (end - start) * t) * reverb.preset.wet_level;
+                let (left, right) = reverb.reverb.process(*input);
+                output[i * 2] += left * gain;
+                output[i * 2 + 1] += right * gain;
+            }
+        }
+        self.reverbs.retain(|_, reverb| reverb.target > 0.0 || reverb.weight > REVERB_SILENT_WEIGHT);
+    }
+
+    /// Atenuar y filtrar un bloque según la oclusión de la voz, que se
+    /// acerca a su objetivo a lo largo del bloque
+    fn apply_occlusion(&self, voice: &mut Voice, block: &mut [(f32, f32)]) {
+        let occlusion = &mut voice.occlusion;
+        let frames = block.len();
+        if frames == 0 {
+            return;
+        }
+        let start = occlusion.amount;
+        let retain = (-(frames as f32 / self.sample_rate as f32) / OCCLUSION_SMOOTHING_SECONDS).exp();
+        let end = occlusion.target + (start - occlusion.target) * retain;
+        occlusion.amount = end;
+        if start <= 1e-4 && end <= 1e-4 {
+            // Sin oclusión: el filtro sigue a la señal para entrar sin salto
+            occlusion.lowpass = block[frames - 1];
+            return;
+        }
+
+        let settings = &self.occlusion;
+        let coefficient = |amount: f32| {
+            let ratio = (settings.min_cutoff / settings.max_cutoff.max(1.0)).clamp(1e-4, 1.0);
+            let cutoff = settings.max_cutoff * ratio.powf(amount);
+            1.0 - (-2.0 * std::f32::consts::PI * cutoff / self.sample_rate as f32).exp()
+        };
+        let gain = |amount: f32| 10f32.powf(-settings.max_attenuation_db * amount / 20.0);
+        let (start_coefficient, end_coefficient) = (coefficient(start), coefficient(end));
+        let (start_gain, end_gain) = (gain(start), gain(end));
+        let (mut left, mut right) = occlusion.lowpass;
+        for (i, frame) in block.iter_mut().enumerate() {
+            let t = (i + 1) as f32 / frames as f32;
+            let a = start_coefficient + (end_coefficient - start_coefficient) * t;
+            let g = start_gain + (end_gain - start_gain) * t;
+            left += a * (frame.0 - left);
+            right += a * (frame.1 - right);
+            *frame = (left * g, right * g);
+        }
+        occlusion.lowpass = (left, right);
+    }
+
+    /// Pasar el bloque por la cadena de efectos de la voz (se rehace con
+    /// historia limpia si cambiaron sus efectos)
+    fn apply_effects(&self, voice: &mut Voice, block: &mut [(f32, f32)]) {
+        if !voice.effects.matches(&voice.params.effects) {
+            voice.effects = EffectChain::new(&voice.params.effects, self.sample_rate);
+        }
+        if voice.effects.is_empty() || block.is_empty() {
+            return;
+        }
+        let mut samples: Vec<f32> = block.iter().flat_map(|&(left, right)| [left, right]).collect();
+        voice.effects.process_block(&mut samples, self.sample_rate);
+        for (frame, pair) in block.iter_mut().zip(samples.chunks_exact(2)) {
+            *frame = (pair[0], pair[1]);
+        }
+    }
+
+    /// Mezclar una voz en la salida
+    fn mix_voice(&self, voice: &mut Voice, listener: &MixListener, output: &mut [f32], send: &mut [f32]) {
+        let clip = self.clips.get(&voice.params.clip_id).cloned();
+        if voice.finished || (clip.is_none() && voice.stream.is_none()) {
+            voice.finished = true;
+            return;
+        }
+        let frames = output.len() / 2;
+        let gain = voice.params.volume * self.bus_gain(voice.params.bus) * listener.volume;
+
+        // Doppler suavizado hacia el factor de esta posición y velocidad
+        let doppler = match &voice.params.spatial {
+            Some(_) if voice.params.doppler => self.doppler.shift(
+                voice.params.position,
+                voice.params.velocity,
+                listener.position,
+                listener.velocity,
+            ),
+            _ => 1.0,
+        };
+        let retain = (-(frames as f32 / self.sample_rate as f32) / DOPPLER_SMOOTHING_SECONDS).exp();
+        voice.doppler = doppler + (voice.doppler - doppler) * retain;
+
+        // Banda, ganancias y filtros de la posición de la voz
+        let mut hrtf_filters = None;
+        let mut reverb_send = voice.params.reverb_send.max(0.0);
+        let target = match &voice.params.spatial {
+            None => (gain, gain),
+            Some(config) => {
+                let offset = voice.params.position - listener.position;
+                let distance = offset.length();
+                match AudioLODBand::from_distance(distance, config.near_distance, config.far_distance) {
+                    AudioLODBand::Culled => {
+                        voice.gains = None;
+                        voice.history.clear();
+                        // La voz sigue avanzando aunque no suene, sin leer muestras
+                        self.skip_block(voice, clip.as_deref(), frames);
+                        return;
+                    }
+                    AudioLODBand::Mono => {
+                        // Fuente puntual centrada, sin HRTF, paneo ni reverb
+                        voice.history.clear();
+                        reverb_send = 0.0;
+                        let gain = gain * inverse_square_gain(distance, config.near_distance);
+                        (gain, gain)
+                    }
+                    AudioLODBand::Spatialized => {
+                        let (azimuth, elevation) = listener_direction(offset, listener.orientation);
+                        let gain = gain * distance_gain(distance, config);
+                        if listener.hrtf {
+                            hrtf_filters = Some(self.hrtf.filters(azimuth, elevation, listener.hrtf_interpolation));
+                            (gain, gain)
+                        } else {
+                            let (left, right) = pan_gains(azimuth);
+                            (gain * left, gain * right)
+                        }
+                    }
+                }
+            }
+        };
+        let start = voice.gains.unwrap_or(target);
+        voice.gains = Some(target);
+
+        let mut block = self.read_block(voice, clip.as_deref(), frames);
+        self.apply_effects(voice, &mut block);
+        self.apply_occlusion(voice, &mut block);
+
+        let ramp = |i: usize| {
+            let t = (i + 1) as f32 / frames.max(1) as f32;
+            (start.0 + (target.0 - start.0) * t, start.1 + (target.1 - start.1) * t)
+        };
+        match hrtf_filters {
+            Some((left_ir, right_ir)) => {
+                // Convolución de la señal mono con las dos respuestas
+                let taps = left_ir.len();
+                voice.history.resize(taps.saturating_sub(1), 0.0);
+                let mut input = std::mem::take(&mut voice.history);
+                input.extend(block.iter().enumerate().map(|(i, (l, r))| (l + r) * 0.5 * ramp(i).0));
+                for i in 0..block.len() {
+                    let window = &input[i..i + taps];
+                    let (mut left, mut right) = (0.0, 0.0);
+                    for k in 0..taps {
+                        let sample = window[taps - 1 - k];
+                        left += left_ir[k] * sample;
+                        right += right_ir[k] * sample;
+                    }
+                    output[i * 2] += left;
+                    output[i * 2 + 1] += right;
+                    send[i] += (left + right) * 0.5 * reverb_send;
+                }
+                voice.history = input.split_off(input.len() - (taps - 1));
+            }
+            None => {
+                let spatial = voice.params.spatial.is_some();
+                for (i, (left, right)) in block.iter().enumerate() {
+                    let (gain_left, gain_right) = ramp(i);
+                    // Las voces espaciales suenan en mono antes del paneo
+                    let (left, right) = if spatial { let mono = (left + right) * 0.5; (mono, mono) } else { (*left, *right) };
+                    output[i * 2] += left * gain_left;
+                    output[i * 2 + 1] += right * gain_right;
+                    send[i] += (left * gain_left + right * gain_right) * 0.5 * reverb_send;
+                }
+            }
+        }
+    }
+
+    /// Avanzar la voz un bloque sin generar sus frames. Los streams se
+    /// siguen decodificando para no perder su posición
+    fn skip_block(&self, voice: &mut Voice, clip: Option<&AudioClip>, frames: usize) {
+        if voice.stream.is_some() {
+            self.read_block(voice, clip, frames);
+            return;
+        }
+        let Some(clip) = clip else {
+            return;
+        };
+        let step = voice.playback_rate() as f64 * clip.sample_rate as f64 / self.sample_rate as f64;
+        self.advance_cursor(voice, clip.frames() as f64, step * frames as f64);
+    }
+
+    /// Mover el cursor de la voz, volviendo al inicio en bucle o marcándola
+    /// terminada al pasar del final
+    fn advance_cursor(&self, voice: &mut Voice, length: f64, distance: f64) {
+        voice.cursor += distance;
+        if voice.cursor >= length {
+            if voice.params.looped && length > 0.0 {
+                voice.cursor %= length;
+            } else {
+                voice.finished = true;
+            }
+        }
+    }
+
+    /// Frames de la voz en este bloque (del stream o del clip con su
+    /// pitch y su Doppler). Avanza la voz y la marca terminada al pasar del final
+    fn read_block(&self, voice: &mut Voice, clip: Option<&AudioClip>, frames: usize) -> Vec<(f32, f32)> {
+        if let Some(stream) = &mut voice.stream {
+            let block = stream.read_frames(frames);
+            voice.finished = stream.is_finished();
+            return block;
+        }
+        let Some(clip) = clip else {
+            return Vec::new();
+        };
+
+        let step = voice.playback_rate() as f64 * clip.sample_rate as f64 / self.sample_rate as f64;
+        let length = clip.frames() as f64;
+        let mut block = Vec::with_capacity(frames);
+        let mut cursor = voice.cursor;
+        for _ in 0..frames {
+            if cursor >= length {
+                if !voice.params.looped || length == 0.0 {
+                    break;
+                }
+                cursor %= length;
+            }
+            block.push(clip.frame_at(cursor));
+            cursor += step;
+        }
+
+        self.advance_cursor(voice, length, step * frames as f64);
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+    const BLOCK: usize = 512;
+    /// Nivel de la señal continua de prueba
+    const LEVEL: f32 = 0.5;
+
+    /// Atenuación de 1 m a 20 m hasta 0.1, dentro de la banda espacial
+    fn spatial() -> SpatialAudioConfig {
+        SpatialAudioConfig {
+            min_distance: 1.0,
+            max_distance: 20.0,
+            rolloff: 1.0,
+            near_distance: 50.0,
+            far_distance: 100.0,
+            min_gain: 0.1,
+        }
+    }
+
+    /// Mezclador con un clip mono continuo en bucle
+    fn mixer() -> AudioMixer {
+        let mut mixer = AudioMixer::new(SAMPLE_RATE);
+        mixer.load_clip(AudioClip { id: "dc".to_string(), sample_rate: SAMPLE_RATE, channels: 1, samples: vec![LEVEL; SAMPLE_RATE as usize] });
+        mixer
+    }
+
+    fn voice(position: Vec3) -> VoiceParams {
+        VoiceParams {
+            clip_id: "dc".to_string(),
+            bus: AudioBus::Sfx,
+            volume: 1.0,
+            pitch: 1.0,
+            looped: true,
+            position,
+            spatial: Some(spatial()),
+            entity_id: None,
+            source_id: None,
+            reverb_send: 0.0,
+            effects: Vec::new(),
+            velocity: Vec3::ZERO,
+            doppler: false,
+        }
+    }
+
+    fn listener(orientation: Quat, hrtf: bool) -> MixListener {
+        MixListener { position: Vec3::ZERO, orientation, velocity: Vec3::ZERO, volume: 1.0, hrtf, hrtf_interpolation: false }
+    }
+
+    /// Nivel medio (izquierda, derecha) del último de `blocks` bloques
+    fn levels(mixer: &mut AudioMixer, listener: &MixListener, blocks: usize) -> (f32, f32) {
+        let mut output = Vec::new();
+        for _ in 0..blocks {
+            output = mixer.mix(listener, BLOCK);
+        }
+        let sum = output.chunks_exact(2).fold((0.0, 0.0), |(left, right), frame| (left + frame[0], right + frame[1]));
+        (sum.0 / BLOCK as f32, sum.1 / BLOCK as f32)
+    }
+
+    #[test]
+    fn source_at_max_distance_is_attenuated_to_the_floor() {
+        let config = spatial();
+        assert!((distance_gain(config.max_distance, &config) - config.min_gain).abs() < 1e-6);
+        assert!((distance_gain(2.0 * config.max_distance, &config) - config.min_gain).abs() < 1e-6);
+        assert_eq!(distance_gain(config.min_distance, &config), 1.0);
+
+        // Delante del oyente, que mira hacia -Z
+        let center = std::f32::consts::FRAC_1_SQRT_2;
+        for (distance, gain) in [(config.min_distance, 1.0), (config.max_distance, config.min_gain), (30.0, config.min_gain)] {
+            let mut mixer = mixer();
+            mixer.play(voice(Vec3::new(0.0, 0.0, -distance))).unwrap();
+            let (left, right) = levels(&mut mixer, &listener(Quat::IDENTITY, false), 2);
+            let expected = LEVEL * gain * center * mixer.bus_gain(AudioBus::Sfx);
+            assert!((left - expected).abs() < 1e-5 && (right - expected).abs() < 1e-5,
+                "a {} m: ({}, {}) en vez de {}", distance, left, right, expected);
+        }
+    }
+
+    #[test]
+    fn source_to_the_right_pans_fully_right() {
+        // A la derecha del oyente sin girar, y del oyente girado 90° a la
+        // izquierda (que mira hacia -X)
+        for (orientation, position) in [
+            (Quat::IDENTITY, Vec3::new(5.0, 0.0, 0.0)),
+            (Quat::from_rotation_y(std::f32::consts::FRAC_PI_2), Vec3::new(0.0, 0.0, -5.0)),
+        ] {
+            let mut mixer = mixer();
+            mixer.play(voice(position)).unwrap();
+            let (left, right) = levels(&mut mixer, &listener(orientation, false), 2);
+            let expected = LEVEL * distance_gain(5.0, &spatial());
+            assert!(left.abs() < 1e-6, "izquierda {}", left);
+            assert!((right - expected).abs() < 1e-5, "derecha {} en vez de {}", right, expected);
+        }
+        let (left, right) = pan_gains(-std::f32::consts::FRAC_PI_2);
+        assert!((left - 1.0).abs() < 1e-6 && right.abs() < 1e-6);
+    }
+
+    #[test]
+    fn hrtf_source_to_the_right_has_the_spherical_head_ild() {
+        let mut mixer = mixer();
+        mixer.play(voice(Vec3::new(5.0, 0.0, 0.0))).unwrap();
+        // Con la historia de la convolución llena la señal continua es estable
+        let (left, right) = levels(&mut mixer, &listener(Quat::IDENTITY, true), 3);
+        let ild = 20.0 * (right / left).log10();
+        assert!((ild - 10.0).abs() < 0.5, "ILD de {:.2} dB", ild);
+        assert!(right > left);
+    }
+
+    /// Amplitud de la componente de frecuencia `bin` de un bloque
+    fn bin_level(samples: &[f32], bin: usize) -> f32 {
+        let n = samples.len() as f32;
+        let (re, im) = samples.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, sample)| {
+            let phase = std::f32::consts::TAU * ((bin * i) % samples.len()) as f32 / n;
+            (re + sample * phase.cos(), im - sample * phase.sin())
+        });
+        2.0 * (re * re + im * im).sqrt() / n
+    }
+
+    #[test]
+    fn wall_between_source_and_listener_removes_high_frequencies() {
+        // Un grave (bin 2 de un bloque, 187,5 Hz) y un agudo (bin 80, 7,5 kHz)
+        // a la misma amplitud; 6144 frames son ciclos enteros de los dos
+        let (low, high) = (2, 80);
+        let samples: Vec<f32> = (0..6144)
+            .map(|i| {
+                let phase = |bin: usize| (std::f32::consts::TAU * ((bin * i) % BLOCK) as f32 / BLOCK as f32).sin();
+                0.25 * phase(low) + 0.25 * phase(high)
+            })
+            .collect();
+
+        // Bloque izquierdo tras 60 bloques (6 constantes de suavizado)
+        let render = |occlusion: f32| {
+            let mut mixer = AudioMixer::new(SAMPLE_RATE);
+            mixer.load_clip(AudioClip { id: "tones".to_string(), sample_rate: SAMPLE_RATE, channels: 1, samples: samples.clone() });
+            let id = mixer.play(VoiceParams { clip_id: "tones".to_string(), spatial: None, ..voice(Vec3::ZERO) }).unwrap();
+            mixer.set_voice_occlusion(id, occlusion);
+            let mut output = Vec::new();
+            for _ in 0..60 {
+                output = mixer.mix(&listener(Quat::IDENTITY, false), BLOCK);
+            }
+            let left: Vec<f32> = output.chunks_exact(2).map(|frame| frame[0]).collect();
+            (bin_level(&left, low), bin_level(&left, high))
+        };
+        let (clear_low, clear_high) = render(0.0);
+        let (wall_low, wall_high) = render(1.0);
+        assert!((clear_low - clear_high).abs() < 1e-3 * clear_low);
+
+        // Los graves bajan la atenuación; los agudos, además, el paso bajo
+        let db = |wall: f32, clear: f32| 20.0 * (wall / clear).log10();
+        let (low_drop, high_drop) = (db(wall_low, clear_low), db(wall_high, clear_high));
+        let attenuation = OcclusionSettings::default().max_attenuation_db;
+        assert!((low_drop + attenuation).abs() < 1.0, "graves {:.1} dB", low_drop);
+        assert!(high_drop < low_drop - 15.0, "agudos {:.1} dB, graves {:.1} dB", high_drop, low_drop);
+    }
+
+    #[test]
+    fn walking_between_reverb_zones_crossfades_wet_levels_monotonically() {
+        use crate::audio::reverb::{ReverbZone, ReverbZoneShape};
+
+        let zones = [
+            ReverbZone {
+                id: "room".to_string(),
+                shape: ReverbZoneShape::Box { center: Vec3::ZERO, half_extents: Vec3::splat(5.0) },
+                preset: ReverbPreset::room(),
+                blend_distance: 2.0,
+            },
+            ReverbZone {
+                id: "cave".to_string(),
+                shape: ReverbZoneShape::Sphere { center: Vec3::new(20.0, 0.0, 0.0), radius: 5.0 },
+                preset: ReverbPreset::cave(),
+                blend_distance: 2.0,
+            },
+        ];
+        let mut mixer = mixer();
+        mixer.play(VoiceParams { spatial: None, reverb_send: 0.5, ..voice(Vec3::ZERO) }).unwrap();
+
+        // Quieto 3 s en la sala, a 10 cm por bloque hasta la cueva y quieto
+        // hasta que la sala se apaga
+        let mut history = Vec::new();
+        for step in 0..1100 {
+            let position = Vec3::new(((step as f32 - 300.0) * 0.1).clamp(0.0, 20.0), 0.0, 0.0);
+            let targets: Vec<_> = zones.iter()
+                .map(|zone| (zone.id.clone(), zone.preset, zone.weight(position)))
+                .filter(|(_, _, weight)| *weight > 0.0)
+                .collect();
+            mixer.set_reverb_targets(&targets);
+            mixer.mix(&listener(Quat::IDENTITY, false), BLOCK);
+            history.push((position.x, (mixer.reverb_weight("room"), mixer.reverb_weight("cave"))));
+        }
+        let (_, (room, cave)) = history[299];
+        assert!(room > 0.99 && cave == 0.0, "en la sala: {} y {}", room, cave);
+
+        // Al salir de la sala su peso solo baja y el de la cueva solo sube
+        let crossing: Vec<(f32, f32)> = history.iter().filter(|(x, _)| *x > 5.0).map(|(_, weights)| *weights).collect();
+        for (block, pair) in crossing.windows(2).enumerate() {
+            assert!(pair[1].0 <= pair[0].0, "la sala sube en el bloque {}: {:?}", block + 1, pair);
+            assert!(pair[1].1 >= pair[0].1, "la cueva baja en el bloque {}: {:?}", block + 1, pair);
+        }
+        let (_, (room, cave)) = history[history.len() - 1];
+        assert_eq!(room, 0.0);
+        assert!(cave > 0.99, "cueva {}", cave);
+    }
+
+    /// Voz con Doppler a 20 m delante del oyente (hacia -Z)
+    fn moving_voice(velocity: Vec3) -> VoiceParams {
+        VoiceParams { velocity, doppler: true, ..voice(Vec3::new(0.0, 0.0, -20.0)) }
+    }
+
+    #[test]
+    fn source_approaching_at_34_m_s_plays_about_eleven_percent_faster() {
+        let expected = SPEED_OF_SOUND / (SPEED_OF_SOUND - 34.0);
+        assert!((expected - 1.11).abs() < 0.005);
+        let mut mixer = mixer();
+        let approaching = mixer.play(moving_voice(Vec3::new(0.0, 0.0, 34.0))).unwrap();
+        let receding = mixer.play(moving_voice(Vec3::new(0.0, 0.0, -34.0))).unwrap();
+
+        // El factor se suaviza: tras un bloque aún no ha llegado
+        let listener = listener(Quat::IDENTITY, false);
+        mixer.mix(&listener, BLOCK);
+        let rate = mixer.voice(approaching).unwrap().playback_rate();
+        assert!(rate > 1.0 && rate < expected - 0.05, "{} tras un bloque", rate);
+        for _ in 0..60 {
+            mixer.mix(&listener, BLOCK);
+        }
+        let rate = mixer.voice(approaching).unwrap().playback_rate();
+        assert!((rate - expected).abs() < 1e-3, "se acerca a {}", rate);
+        let rate = mixer.voice(receding).unwrap().playback_rate();
+        assert!((rate - SPEED_OF_SOUND / (SPEED_OF_SOUND + 34.0)).abs() < 1e-3, "se aleja a {}", rate);
+
+        // El oyente que se acerca a una fuente quieta también la sube
+        let shift = DopplerSettings::default().shift(Vec3::new(0.0, 0.0, -20.0), Vec3::ZERO, Vec3::ZERO, Vec3::new(0.0, 0.0, -34.0));
+        assert!((shift - (SPEED_OF_SOUND + 34.0) / SPEED_OF_SOUND).abs() < 1e-5);
+    }
+
+    #[test]
+    fn stationary_source_shows_no_shift() {
+        let mut mixer = mixer();
+        let still = mixer.play(moving_voice(Vec3::ZERO)).unwrap();
+        // Moviéndose de lado no cambia la distancia
+        let sideways = mixer.play(moving_voice(Vec3::new(34.0, 0.0, 0.0))).unwrap();
+        for _ in 0..10 {
+            mixer.mix(&listener(Quat::IDENTITY, false), BLOCK);
+        }
+        for id in [still, sideways] {
+            let voice = mixer.voice(id).unwrap();
+            assert_eq!(voice.doppler(), 1.0);
+            assert_eq!(voice.playback_rate(), voice.params.pitch);
+        }
+
+        // Fuente y oyente a la misma velocidad tampoco
+        let velocity = Vec3::new(0.0, 0.0, 34.0);
+        let shift = DopplerSettings::default().shift(Vec3::new(0.0, 0.0, -20.0), velocity, Vec3::ZERO, velocity);
+        assert!((shift - 1.0).abs() < 1e-6);
+    }
+}
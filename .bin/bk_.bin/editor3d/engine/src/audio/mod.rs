@@ -2,6 +2,22 @@
 //! 
 //! Proporciona audio espacial con HRTF, efectos de sonido avanzados,
 //! música de fondo dinámica e integración con WebAudio API.
+//! El chat de voz (`voice`) mezcla la voz de los demás usuarios en la
+//! posición de sus entidades.
+//! El mezclador (`mixer`) reproduce los clips de las fuentes y de los
+//! `AudioComponent` con atenuación, paneo y HRTF, y su salida va al
+//! dispositivo a través de `output`.
+//! Los clips se decodifican de WAV u OGG/Vorbis (`decode`) a la frecuencia
+//! del motor, y las pistas largas se reproducen en streaming (`stream`).
+//! Las voces espaciales se ocluyen con rayos de la física entre la fuente y
+//! el oyente, y las zonas de reverb (`reverb`) dan eco a cuevas y salas.
+//! Cada `AudioComponent` puede llevar una cadena de efectos (`dsp`: filtros,
+//! distorsión, chorus y compresor) que se aplica antes de espacializar.
+//! Las entidades con `AmbientZoneComponent` suenan como paisajes sonoros
+//! (`ambient`) que se funden por cercanía y cambian con la hora del día.
+//! Los buses (`bus`) cuelgan del master con volumen, silencio y ducking, y
+//! los eventos con nombre (`events`) eligen clip, volumen y pitch de cada
+//! disparo y responden a parámetros globales como la intensidad.
 
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
@@ -13,6 +29,35 @@ use glam::{Vec3, Vec4, Mat4, Quat};
 use wasm_bindgen::prelude::*;
 use web_sys::{AudioContext, AudioBuffer, AudioBufferSourceNode, AudioDestinationNode, GainNode, PannerNode, BiquadFilterNode, AudioParam};
 
+pub mod ambient;
+pub mod bus;
+pub mod decode;
+pub mod dsp;
+pub mod events;
+pub mod hrtf;
+pub mod mixer;
+pub mod music;
+pub mod output;
+pub mod reverb;
+pub mod stream;
+pub mod voice;
+
+pub use mixer::AudioClip;
+
+use ambient::AmbientSoundscape;
+use bus::DuckingRule;
+use events::{AudioEventBank, AudioEventDefinition, PARAMETER_INTENSITY};
+use hrtf::HrtfSet;
+use mixer::{AudioBus, AudioMixer, DopplerSettings, MixListener, OcclusionSettings, VoiceId, VoiceParams};
+use music::MusicManager;
+use output::AudioRingBuffer;
+use reverb::{ReverbPreset, ReverbZone};
+use stream::{LoopPoints, StreamingClip};
+use voice::{DecodedVoiceFrame, EncodedVoiceFrame, VoiceChat, VoiceStats, VOICE_FRAME_SAMPLES};
+
+/// ID de la reverb global fuera de las zonas
+const AMBIENT_REVERB_ID: &str = "ambient";
+
 /// Sistema de audio principal
 pub struct AudioSystem {
     /// Configuración del sistema
@@ -25,8 +70,38 @@ pub struct AudioSystem {
     effects: Arc<RwLock<HashMap<String, AudioEffect>>>,
     /// Música de fondo
     music: Arc<RwLock<HashMap<String, BackgroundMusic>>>,
+    /// Música adaptativa por intensidad
+    music_manager: MusicManager,
     /// Listener (oyente)
     listener: Arc<RwLock<AudioListener>>,
+    /// Chat de voz (si está habilitado)
+    voice: Option<VoiceChat>,
+    /// Voz mezclada en estéreo intercalado pendiente de reproducir
+    voice_output: Vec<f32>,
+    /// Mezclador de clips
+    mixer: AudioMixer,
+    /// Voz de cada entidad con `AudioComponent`
+    entity_voices: HashMap<crate::ecs::EntityId, VoiceId>,
+    /// Última posición de las entidades con voz y de la cámara, para su
+    /// velocidad
+    entity_positions: HashMap<crate::ecs::EntityId, Vec3>,
+    /// El listener sigue a una cámara (su velocidad sale de la cámara)
+    listener_tracked: bool,
+    /// Zonas de reverb
+    reverb_zones: Vec<ReverbZone>,
+    /// Eventos de audio y parámetros
+    events: AudioEventBank,
+    /// Capas de las zonas ambientales
+    ambient: AmbientSoundscape,
+    /// Hora del día normalizada (0 medianoche, 0.5 mediodía)
+    day_time: f32,
+    /// Salida de la mezcla hacia el dispositivo
+    output_ring: Arc<AudioRingBuffer>,
+    /// Dispositivo de salida nativo (None sin dispositivo)
+    #[cfg(not(target_arch = "wasm32"))]
+    native_output: Option<output::NativeOutput>,
+    /// Fracción de frame pendiente de mezclar
+    mix_carry: f64,
     /// Estadísticas del sistema
     stats: AudioStats,
     /// Estado del sistema
@@ -46,6 +121,59 @@ pub struct AudioConfig {
     pub effects_config: EffectsConfig,
     /// Configuración de música
     pub music_config: MusicConfig,
+    /// Chat de voz entre usuarios
+    #[serde(default)]
+    pub voice_chat_enabled: bool,
+    /// Bitrate de la voz en bits por segundo
+    #[serde(default = "default_voice_bitrate")]
+    pub voice_bitrate: u32,
+    /// Abrir el dispositivo de salida nativo (sin él la mezcla queda en el
+    /// buffer de salida)
+    #[serde(default = "default_device_output")]
+    pub device_output: bool,
+    /// Segundos decodificados por delante en los streams
+    #[serde(default = "default_stream_read_ahead")]
+    pub stream_read_ahead: f32,
+    /// Reglas de ducking entre buses
+    #[serde(default = "default_ducking")]
+    pub ducking: Vec<DuckingRule>,
+    /// Escala del efecto Doppler (0 lo desactiva)
+    #[serde(default = "default_doppler_factor")]
+    pub doppler_factor: f32,
+    /// Factores de pitch mínimo y máximo del Doppler
+    #[serde(default = "default_doppler_pitch_range")]
+    pub doppler_pitch_range: (f32, f32),
+    /// Voces ambientales simultáneas como mucho
+    #[serde(default = "default_max_ambient_voices")]
+    pub max_ambient_voices: usize,
+}
+
+fn default_voice_bitrate() -> u32 {
+    24_000
+}
+
+fn default_device_output() -> bool {
+    true
+}
+
+fn default_stream_read_ahead() -> f32 {
+    2.0
+}
+
+fn default_ducking() -> Vec<DuckingRule> {
+    vec![DuckingRule::voice_over_music()]
+}
+
+fn default_doppler_factor() -> f32 {
+    1.0
+}
+
+fn default_doppler_pitch_range() -> (f32, f32) {
+    (0.5, 2.0)
+}
+
+fn default_max_ambient_voices() -> usize {
+    8
 }
 
 /// Configuración de contexto
@@ -105,6 +233,34 @@ pub struct SpatialConfig {
     pub distance_config: DistanceConfig,
     /// Configuración de occlusión
     pub occlusion_config: OcclusionConfig,
+    /// Distancia bajo la cual se aplica HRTF y reverb completos
+    pub near_distance: f32,
+    /// Distancia a partir de la cual la fuente deja de procesarse
+    pub far_distance: f32,
+}
+
+/// Banda de LOD de una fuente de audio según su distancia al oyente
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AudioLODBand {
+    /// HRTF, occlusión y efectos completos
+    Spatialized,
+    /// Fuente puntual mono con atenuación inversa al cuadrado
+    Mono,
+    /// Sin procesamiento
+    Culled,
+}
+
+impl AudioLODBand {
+    /// Clasificar una distancia en su banda de LOD
+    pub fn from_distance(distance: f32, near_distance: f32, far_distance: f32) -> Self {
+        if distance < near_distance {
+            AudioLODBand::Spatialized
+        } else if distance < far_distance {
+            AudioLODBand::Mono
+        } else {
+            AudioLODBand::Culled
+        }
+    }
 }
 
 /// Configuración de HRTF
@@ -172,10 +328,41 @@ pub enum RolloffType {
 pub struct OcclusionConfig {
     /// Habilitado
     pub enabled: bool,
-    /// Factor de occlusión
+    /// Factor de occlusión: oclusión que añade cada obstáculo
     pub occlusion_factor: f32,
     /// Configuración de raycast
     pub raycast_config: RaycastConfig,
+    /// Oclusión que añade cada metro de obstáculo atravesado
+    #[serde(default = "default_occlusion_thickness_factor")]
+    pub thickness_factor: f32,
+    /// Atenuación en dB de una voz del todo ocluida
+    #[serde(default = "default_occlusion_attenuation_db")]
+    pub max_attenuation_db: f32,
+    /// Corte del paso bajo de una voz del todo ocluida (Hz)
+    #[serde(default = "default_occlusion_cutoff")]
+    pub min_cutoff: f32,
+}
+
+fn default_occlusion_thickness_factor() -> f32 {
+    0.5
+}
+
+fn default_occlusion_attenuation_db() -> f32 {
+    18.0
+}
+
+fn default_occlusion_cutoff() -> f32 {
+    600.0
+}
+
+impl From<&OcclusionConfig> for OcclusionSettings {
+    fn from(config: &OcclusionConfig) -> Self {
+        Self {
+            max_attenuation_db: config.max_attenuation_db,
+            min_cutoff: config.min_cutoff,
+            ..OcclusionSettings::default()
+        }
+    }
 }
 
 /// Configuración de raycast
@@ -424,6 +611,10 @@ pub struct AudioSourceState {
     pub position: Vec3,
     /// Velocidad
     pub velocity: Vec3,
+    /// Ganancia de distancia de su banda de LOD en el último frame
+    /// (0 fuera de `far_distance`)
+    #[serde(default)]
+    pub distance_gain: f32,
 }
 
 /// Efecto de audio
@@ -580,19 +771,48 @@ pub struct AudioStats {
     pub latency: f32,
     /// CPU usage
     pub cpu_usage: f32,
+    /// Fuentes con espacialización completa
+    pub spatialized_sources: u32,
+    /// Fuentes colapsadas a mono
+    pub mono_sources: u32,
+    /// Fuentes descartadas por distancia
+    pub culled_sources: u32,
+    /// Chat de voz
+    #[serde(default)]
+    pub voice: VoiceStats,
+    /// Voces del mezclador sonando
+    #[serde(default)]
+    pub active_voices: u32,
 }
 
 impl AudioSystem {
     /// Crear nuevo sistema de audio
     pub fn new(config: AudioConfig) -> Self {
         info!("Inicializando sistema de audio");
+        let music_manager = MusicManager::new(config.music_config.transition_config.transition_time);
+        let context_config = &config.context_config;
+        let sample_rate = context_config.sample_rate.max(1.0) as u32;
+        let buffered_frames = (context_config.buffer_config.buffer_size * context_config.buffer_config.num_buffers)
+            .max(sample_rate as usize / 10);
         
+        let mut mixer = AudioMixer::new(sample_rate);
+        mixer.set_occlusion_settings(OcclusionSettings::from(&config.spatial_config.occlusion_config));
+        mixer.set_doppler_settings(DopplerSettings {
+            factor: config.doppler_factor.max(0.0),
+            min_pitch: config.doppler_pitch_range.0,
+            max_pitch: config.doppler_pitch_range.1,
+        });
+        for rule in &config.ducking {
+            mixer.buses_mut().add_ducking(rule.clone());
+        }
+
         Self {
             config,
             context: None,
             sources: Arc::new(RwLock::new(HashMap::new())),
             effects: Arc::new(RwLock::new(HashMap::new())),
             music: Arc::new(RwLock::new(HashMap::new())),
+            music_manager,
             listener: Arc::new(RwLock::new(AudioListener {
                 position: Vec3::ZERO,
                 orientation: Quat::IDENTITY,
@@ -603,6 +823,20 @@ impl AudioSystem {
                     effects_enabled: true,
                 },
             })),
+            voice: None,
+            voice_output: Vec::new(),
+            mixer,
+            entity_voices: HashMap::new(),
+            entity_positions: HashMap::new(),
+            listener_tracked: false,
+            reverb_zones: Vec::new(),
+            events: AudioEventBank::default(),
+            ambient: AmbientSoundscape::new(),
+            day_time: 0.5,
+            output_ring: Arc::new(AudioRingBuffer::new(buffered_frames * 2)),
+            #[cfg(not(target_arch = "wasm32"))]
+            native_output: None,
+            mix_carry: 0.0,
             stats: AudioStats {
                 source_count: 0,
                 effect_count: 0,
@@ -611,6 +845,11 @@ impl AudioSystem {
                 memory_usage: 0,
                 latency: 0.0,
                 cpu_usage: 0.0,
+                spatialized_sources: 0,
+                mono_sources: 0,
+                culled_sources: 0,
+                voice: VoiceStats::default(),
+                active_voices: 0,
             },
             running: false,
         }
@@ -634,6 +873,26 @@ impl AudioSystem {
         // Configurar listener
         self.setup_listener().await?;
 
+        // Abrir el dispositivo de salida
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.config.device_output {
+            match output::NativeOutput::start(self.output_ring.clone(), self.mixer.sample_rate()) {
+                Ok(output) => self.native_output = Some(output),
+                Err(e) => warn!("Sin salida de audio: {}", e),
+            }
+        }
+
+        // Preparar el chat de voz
+        if self.config.voice_chat_enabled {
+            match VoiceChat::new(self.config.voice_bitrate) {
+                Ok(voice) => {
+                    self.voice = Some(voice);
+                    info!("Chat de voz habilitado a {} bps", self.config.voice_bitrate);
+                }
+                Err(e) => warn!("Sin chat de voz: {}", e),
+            }
+        }
+
         self.running = true;
         info!("Sistema de audio inicializado correctamente");
         
@@ -724,10 +983,17 @@ impl AudioSystem {
 
         // Actualizar música
         self.update_music(delta_time).await?;
+        self.music_manager.tick(delta_time);
 
         // Actualizar listener
         self.update_listener(delta_time).await?;
 
+        // Decodificar y mezclar la voz
+        self.update_voice(delta_time)?;
+
+        // Mezclar los clips y enviarlos al dispositivo
+        self.mix_output(delta_time);
+
         // Actualizar estadísticas
         self.update_stats(start_time.elapsed().as_secs_f32());
 
@@ -737,25 +1003,471 @@ impl AudioSystem {
     /// Actualizar fuentes de audio
     async fn update_audio_sources(&mut self, delta_time: f32) -> Result<()> {
         let mut sources = self.sources.write().unwrap();
-        
+        let listener_position = self.listener.read().unwrap().position;
+        let near_distance = self.config.spatial_config.near_distance;
+        let far_distance = self.config.spatial_config.far_distance;
+
+        let mut spatialized_sources = 0;
+        let mut mono_sources = 0;
+        let mut culled_sources = 0;
+        let mut moved = Vec::new();
+
         for source in sources.values_mut() {
             if source.state.playing {
                 // Actualizar tiempo de reproducción
                 source.state.playback_time += delta_time;
 
-                // Actualizar posición espacial si es necesario
-                if source.config.spatial {
-                    self.update_spatial_audio(source).await?;
+                if !source.config.spatial {
+                    // Aplicar efectos
+                    source.state.distance_gain = 1.0;
+                    self.apply_effects_to_source(source).await?;
+                    continue;
                 }
 
-                // Aplicar efectos
-                self.apply_effects_to_source(source).await?;
+                // Seleccionar banda de LOD antes de entrar en el pipeline HRTF;
+                // el mezclador aplica la misma ganancia a las voces de la fuente
+                let distance = source.state.position.distance(listener_position);
+                match AudioLODBand::from_distance(distance, near_distance, far_distance) {
+                    AudioLODBand::Spatialized => {
+                        spatialized_sources += 1;
+                        source.state.distance_gain = mixer::distance_gain(distance, &self.source_spatial_config(source));
+                        self.update_spatial_audio(source).await?;
+                        self.apply_effects_to_source(source).await?;
+                    }
+                    AudioLODBand::Mono => {
+                        mono_sources += 1;
+                        source.state.distance_gain = mixer::inverse_square_gain(distance, near_distance);
+                    }
+                    AudioLODBand::Culled => {
+                        culled_sources += 1;
+                        source.state.distance_gain = 0.0;
+                    }
+                }
+                moved.push((source.id.clone(), source.state.position, source.state.velocity));
             }
         }
 
+        drop(sources);
+        for (source_id, position, velocity) in moved {
+            self.mixer.move_source_voices(&source_id, position, velocity);
+        }
+
+        self.stats.spatialized_sources = spatialized_sources;
+        self.stats.mono_sources = mono_sources;
+        self.stats.culled_sources = culled_sources;
+
         Ok(())
     }
 
+    /// Decodificar las tramas de voz de este frame y mezclarlas en estéreo
+    /// con la atenuación y el paneo de su posición respecto al listener
+    fn update_voice(&mut self, delta_time: f32) -> Result<()> {
+        let frames = match &mut self.voice {
+            Some(voice) => voice.tick(delta_time)?,
+            None => return Ok(()),
+        };
+        // La voz de los demás cuenta como actividad del bus Voice (ducking)
+        let speaking = self.voice.as_ref().is_some_and(|voice| voice.has_active_speakers());
+        self.mixer.buses_mut().set_external_activity(AudioBus::Voice, speaking);
+        let base = self.voice_output.len();
+        for frame in frames {
+            self.mix_voice_frame(base, &frame);
+        }
+        Ok(())
+    }
+
+    /// Mezclar una trama de voz en la salida
+    fn mix_voice_frame(&mut self, base: usize, frame: &DecodedVoiceFrame) {
+        let listener = self.listener.read().unwrap();
+        let offset = frame.position - listener.position;
+        let distance = offset.length();
+        if distance > self.config.spatial_config.far_distance {
+            return;
+        }
+        let gain = mixer::inverse_square_gain(distance, self.config.spatial_config.near_distance)
+            * listener.config.master_volume
+            * self.mixer.bus_gain(AudioBus::Voice);
+        // Paneo de potencia constante según el lado del listener
+        let local = listener.orientation.inverse() * offset;
+        let pan = if distance > f32::EPSILON { (local.x / distance).clamp(-1.0, 1.0) } else { 0.0 };
+        let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+        let (left, right) = (angle.cos() * gain, angle.sin() * gain);
+        drop(listener);
+
+        let start = base + frame.slot * VOICE_FRAME_SAMPLES * 2;
+        let end = start + frame.pcm.len() * 2;
+        if self.voice_output.len() < end {
+            self.voice_output.resize(end, 0.0);
+        }
+        for (i, sample) in frame.pcm.iter().enumerate() {
+            let sample = *sample as f32 / i16::MAX as f32;
+            self.voice_output[start + i * 2] += sample * left;
+            self.voice_output[start + i * 2 + 1] += sample * right;
+        }
+    }
+
+    /// Mezclar los frames que corresponden a `delta_time` y pasarlos a la
+    /// salida. Si el dispositivo no consume, lo que no cabe se descarta
+    fn mix_output(&mut self, delta_time: f32) {
+        self.mix_carry += delta_time.max(0.0) as f64 * self.mixer.sample_rate() as f64;
+        let frames = self.mix_carry.floor() as usize;
+        self.mix_carry -= frames as f64;
+        if frames == 0 {
+            return;
+        }
+
+        let listener = {
+            let listener = self.listener.read().unwrap();
+            let spatial = &self.config.spatial_config;
+            MixListener {
+                position: listener.position,
+                orientation: listener.orientation,
+                velocity: listener.velocity,
+                volume: listener.config.master_volume,
+                hrtf: spatial.enabled && spatial.hrtf_config.enabled && listener.config.hrtf_enabled,
+                hrtf_interpolation: spatial.hrtf_config.interpolation,
+            }
+        };
+        let targets = self.reverb_targets(listener.position);
+        self.mixer.set_reverb_targets(&targets);
+        let mixed = self.mixer.mix(&listener, frames);
+        let written = self.output_ring.write(&mixed);
+        if written < mixed.len() {
+            debug!("Salida de audio llena: {} muestras descartadas", mixed.len() - written);
+        }
+        self.stats.active_voices = self.mixer.voices().iter().filter(|voice| !voice.is_finished()).count() as u32;
+    }
+
+    /// Peso de cada zona de reverb para el oyente. Si suman más de 1 se
+    /// normalizan; si no, el resto va a la reverb global de los efectos
+    fn reverb_targets(&self, listener: Vec3) -> Vec<(String, ReverbPreset, f32)> {
+        let mut targets: Vec<(String, ReverbPreset, f32)> = self.reverb_zones.iter()
+            .map(|zone| (zone.id.clone(), zone.preset, zone.weight(listener)))
+            .filter(|(_, _, weight)| *weight > 0.0)
+            .collect();
+        let total: f32 = targets.iter().map(|(_, _, weight)| weight).sum();
+        if total > 1.0 {
+            for (_, _, weight) in &mut targets {
+                *weight /= total;
+            }
+        }
+        let ambient = &self.config.effects_config.reverb;
+        if ambient.enabled && total < 1.0 {
+            targets.push((AMBIENT_REVERB_ID.to_string(), ReverbPreset::from(ambient), 1.0 - total));
+        }
+        targets
+    }
+
+    /// Atenuación de una fuente: su configuración de distancia o la global,
+    /// con la banda de LOD global. El rolloff lineal cae con exponente 1; el
+    /// inverso y el logarítmico, con exponente 2
+    fn source_spatial_config(&self, source: &AudioSource) -> crate::ecs::SpatialAudioConfig {
+        let spatial = &self.config.spatial_config;
+        let distance = source.config.distance_config.as_ref().unwrap_or(&spatial.distance_config);
+        crate::ecs::SpatialAudioConfig {
+            min_distance: distance.min_distance,
+            max_distance: distance.max_distance,
+            rolloff: match distance.rolloff {
+                RolloffType::Linear | RolloffType::Custom(_) => 1.0,
+                RolloffType::Logarithmic | RolloffType::Inverse => 2.0,
+            },
+            near_distance: spatial.near_distance,
+            far_distance: spatial.far_distance,
+            min_gain: 0.0,
+        }
+    }
+
+    /// Colocar el oyente en la cámara activa y una voz en cada entidad con
+    /// `AudioComponent` cuyo clip está cargado, y ajustar las capas de las
+    /// zonas ambientales. Las voces de las entidades que pierden el
+    /// componente se paran. La velocidad de cada entidad (y
+    /// de la cámara) es la de su `PhysicsComponent` si lo tiene o, si no, el
+    /// cambio de posición desde la última llamada
+    pub fn sync_audio_components(&mut self, world: &crate::ecs::ECSSystem, delta_time: f32) {
+        use crate::ecs::{AmbientZoneComponent, AudioComponent, ComponentType, PhysicsComponent, TransformComponent};
+
+        let mut positions = std::mem::take(&mut self.entity_positions);
+        let mut velocity_of = |entity_id: crate::ecs::EntityId, position: Vec3| {
+            let previous = positions.insert(entity_id, position);
+            if let Some(physics) = world.get_component::<PhysicsComponent>(entity_id, ComponentType::Physics) {
+                return physics.velocity;
+            }
+            match previous {
+                Some(previous) if delta_time > f32::EPSILON => (position - previous) / delta_time,
+                _ => Vec3::ZERO,
+            }
+        };
+
+        let mut seen = std::collections::HashSet::new();
+        let camera = world.get_entities_with_component(ComponentType::Camera)
+            .into_iter()
+            .find_map(|camera_id| {
+                world.get_component::<TransformComponent>(camera_id, ComponentType::Transform)
+                    .map(|transform| (camera_id, transform))
+            });
+        self.listener_tracked = camera.is_some();
+        if let Some((camera_id, camera)) = camera {
+            seen.insert(camera_id);
+            let velocity = velocity_of(camera_id, camera.position);
+            let mut listener = self.listener.write().unwrap();
+            listener.position = camera.position;
+            listener.orientation = camera.rotation;
+            listener.velocity = velocity;
+        }
+
+        for entity_id in world.get_entities_with_component(ComponentType::Audio) {
+            let Some(audio) = world.get_component::<AudioComponent>(entity_id, ComponentType::Audio) else {
+                continue;
+            };
+            seen.insert(entity_id);
+            let position = world.get_component::<TransformComponent>(entity_id, ComponentType::Transform)
+                .map_or(Vec3::ZERO, |transform| transform.position);
+            let velocity = velocity_of(entity_id, position);
+            let params = VoiceParams {
+                clip_id: audio.audio_id.clone(),
+                bus: AudioBus::from(&audio.audio_type),
+                volume: audio.volume,
+                pitch: audio.pitch,
+                looped: audio.looped,
+                position,
+                spatial: audio.spatial.then(|| audio.spatial_config.clone()),
+                entity_id: Some(entity_id),
+                source_id: None,
+                reverb_send: if audio.spatial { 1.0 } else { 0.0 },
+                effects: audio.effect_chain.clone(),
+                velocity,
+                doppler: audio.doppler,
+            };
+
+            let current = self.entity_voices.get(&entity_id).copied();
+            match current.and_then(|voice_id| self.mixer.voice_mut(voice_id)) {
+                Some(voice) if voice.params.clip_id == params.clip_id => voice.params = params,
+                _ => {
+                    if let Some(voice_id) = current {
+                        self.mixer.stop(voice_id);
+                    }
+                    match self.mixer.play(params) {
+                        Some(voice_id) => {
+                            self.entity_voices.insert(entity_id, voice_id);
+                        }
+                        None => {
+                            self.entity_voices.remove(&entity_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        let listener_position = self.listener.read().unwrap().position;
+        let zones: Vec<(crate::ecs::EntityId, Vec3, AmbientZoneComponent)> = world
+            .get_entities_with_component(ComponentType::AmbientZone)
+            .into_iter()
+            .filter_map(|entity_id| {
+                let zone = world.get_component::<AmbientZoneComponent>(entity_id, ComponentType::AmbientZone)?;
+                let center = world.get_component::<TransformComponent>(entity_id, ComponentType::Transform)
+                    .map_or(Vec3::ZERO, |transform| transform.position);
+                Some((entity_id, center, zone))
+            })
+            .collect();
+        self.ambient.update(&mut self.mixer, &zones, listener_position, self.day_time, self.config.max_ambient_voices);
+
+        let mixer = &mut self.mixer;
+        self.entity_voices.retain(|entity_id, voice_id| {
+            let keep = seen.contains(entity_id);
+            if !keep {
+                mixer.stop(*voice_id);
+            }
+            keep
+        });
+        positions.retain(|entity_id, _| seen.contains(entity_id));
+        self.entity_positions = positions;
+    }
+
+    /// Cargar un clip en el mezclador
+    pub fn load_clip(&mut self, clip: AudioClip) {
+        self.mixer.load_clip(clip);
+    }
+
+    /// Cargar un clip WAV u OGG/Vorbis desde un archivo o una URL. El ID
+    /// del clip es el nombre del archivo sin extensión
+    pub async fn load_audio(&mut self, path: &str) -> Result<AudioClip> {
+        let bytes = fetch_audio(path).await?;
+        let file = path.split(['?', '#']).next().unwrap_or(path);
+        let id = std::path::Path::new(file).file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let clip = {
+            let sample_rate = self.mixer.sample_rate();
+            tokio::task::spawn_blocking(move || decode::decode_audio(&id, &bytes, sample_rate)).await??
+        };
+        #[cfg(target_arch = "wasm32")]
+        let clip = decode::decode_audio(&id, &bytes, self.mixer.sample_rate())?;
+
+        self.mixer.load_clip(clip.clone());
+        info!("Clip de audio cargado: {} ({} frames)", clip.id, clip.frames());
+        Ok(clip)
+    }
+
+    /// Decodificar y cargar un clip desde bytes (p. ej. empaquetados en el
+    /// build de wasm)
+    pub fn load_audio_bytes(&mut self, id: &str, bytes: &[u8]) -> Result<AudioClip> {
+        let clip = decode::decode_audio(id, bytes, self.mixer.sample_rate())?;
+        self.mixer.load_clip(clip.clone());
+        Ok(clip)
+    }
+
+    /// Abrir un stream sobre los bytes de una pista larga, opcionalmente
+    /// en bucle
+    pub fn open_stream(&self, id: &str, bytes: Arc<[u8]>, looping: Option<LoopPoints>) -> Result<StreamingClip> {
+        StreamingClip::open(id, bytes, self.mixer.sample_rate(), self.config.stream_read_ahead, looping)
+    }
+
+    /// Reproducir un stream
+    pub fn play_stream(&mut self, stream: StreamingClip, params: VoiceParams) -> VoiceId {
+        self.mixer.play_stream(stream, params)
+    }
+
+    /// Descargar un clip y parar sus voces
+    pub fn unload_clip(&mut self, clip_id: &str) {
+        self.mixer.unload_clip(clip_id);
+    }
+
+    /// Reproducir un clip. None si no está cargado
+    pub fn play_clip(&mut self, params: VoiceParams) -> Option<VoiceId> {
+        self.mixer.play(params)
+    }
+
+    /// Parar una voz
+    pub fn stop_voice(&mut self, voice_id: VoiceId) {
+        self.mixer.stop(voice_id);
+    }
+
+    /// Volumen master del mezclador
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.mixer.set_master_volume(volume);
+    }
+
+    /// Volumen de un bus
+    pub fn set_bus_volume(&mut self, bus: AudioBus, volume: f32) {
+        self.mixer.set_bus_volume(bus, volume);
+    }
+
+    /// Hora del día normalizada en [0, 1) (0 medianoche, 0.5 mediodía) con
+    /// la que varían las capas ambientales
+    pub fn set_day_time(&mut self, day_time: f32) {
+        self.day_time = day_time.rem_euclid(1.0);
+    }
+
+    /// Hora del día normalizada
+    pub fn day_time(&self) -> f32 {
+        self.day_time
+    }
+
+    /// Capas de las zonas ambientales
+    pub fn ambient(&self) -> &AmbientSoundscape {
+        &self.ambient
+    }
+
+    /// Silenciar un bus
+    pub fn set_bus_muted(&mut self, bus: AudioBus, muted: bool) {
+        self.mixer.buses_mut().set_muted(bus, muted);
+    }
+
+    /// Añadir una regla de ducking entre buses
+    pub fn add_ducking_rule(&mut self, rule: DuckingRule) {
+        self.mixer.buses_mut().add_ducking(rule);
+    }
+
+    /// Registrar un evento de audio
+    pub fn register_audio_event(&mut self, event: AudioEventDefinition) {
+        self.events.register(event);
+    }
+
+    /// Disparar un evento de audio. None si el clip elegido no está cargado
+    pub fn trigger_audio_event(&mut self, event_id: &str, position: Vec3, entity_id: Option<crate::ecs::EntityId>) -> Result<Option<VoiceId>> {
+        self.events.trigger(&mut self.mixer, event_id, position, entity_id)
+    }
+
+    /// Fijar un parámetro de los eventos
+    pub fn set_audio_parameter(&mut self, name: &str, value: f32) {
+        self.events.set_parameter(&mut self.mixer, name, value);
+    }
+
+    /// Valor de un parámetro de los eventos
+    pub fn audio_parameter(&self, name: &str) -> f32 {
+        self.events.parameter(name)
+    }
+
+    /// Añadir una zona de reverb (reemplaza la del mismo ID)
+    pub fn add_reverb_zone(&mut self, zone: ReverbZone) {
+        self.reverb_zones.retain(|existing| existing.id != zone.id);
+        self.reverb_zones.push(zone);
+    }
+
+    /// Quitar una zona de reverb; su cola se apaga con el fundido
+    pub fn remove_reverb_zone(&mut self, zone_id: &str) -> Option<ReverbZone> {
+        let index = self.reverb_zones.iter().position(|zone| zone.id == zone_id)?;
+        Some(self.reverb_zones.remove(index))
+    }
+
+    /// Zonas de reverb
+    pub fn reverb_zones(&self) -> &[ReverbZone] {
+        &self.reverb_zones
+    }
+
+    /// Rayos de oclusión pendientes: (voz, posición de la fuente, posición
+    /// del oyente) de las voces espaciales que suenan al alcance del raycast
+    pub fn occlusion_queries(&self) -> Vec<(VoiceId, Vec3, Vec3)> {
+        let occlusion = &self.config.spatial_config.occlusion_config;
+        if !occlusion.enabled || !occlusion.raycast_config.enabled {
+            return Vec::new();
+        }
+        let listener = self.listener.read().unwrap().position;
+        let max_distance = occlusion.raycast_config.max_distance;
+        self.mixer.voices().iter()
+            .filter(|voice| !voice.is_finished())
+            .filter(|voice| match &voice.params.spatial {
+                Some(spatial) => {
+                    let distance = voice.params.position.distance(listener);
+                    distance <= max_distance && distance < spatial.far_distance
+                }
+                None => false,
+            })
+            .map(|voice| (voice.id, voice.params.position, listener))
+            .collect()
+    }
+
+    /// Aplicar el resultado de un rayo de oclusión: número de obstáculos y
+    /// metros atravesados entre la fuente y el oyente
+    pub fn set_voice_occlusion(&mut self, voice_id: VoiceId, occluders: u32, thickness: f32) {
+        let config = &self.config.spatial_config.occlusion_config;
+        let amount = occluders as f32 * config.occlusion_factor + thickness.max(0.0) * config.thickness_factor;
+        self.mixer.set_voice_occlusion(voice_id, amount.min(1.0));
+    }
+
+    /// Cambiar las respuestas HRTF
+    pub fn set_hrtf(&mut self, hrtf: HrtfSet) {
+        self.mixer.set_hrtf(hrtf);
+    }
+
+    /// Mezclador
+    pub fn mixer(&self) -> &AudioMixer {
+        &self.mixer
+    }
+
+    /// Buffer de salida de la mezcla
+    pub fn output_ring(&self) -> Arc<AudioRingBuffer> {
+        self.output_ring.clone()
+    }
+
+    /// Acceso del AudioWorklet a la mezcla
+    #[cfg(target_arch = "wasm32")]
+    pub fn output_handle(&self) -> output::AudioOutputHandle {
+        output::AudioOutputHandle::new(self.output_ring.clone())
+    }
+
     /// Actualizar audio espacial
     async fn update_spatial_audio(&mut self, source: &mut AudioSource) -> Result<()> {
         if let Some(context) = &self.context {
@@ -1025,8 +1737,10 @@ impl AudioSystem {
     async fn update_listener(&mut self, delta_time: f32) -> Result<()> {
         let mut listener = self.listener.write().unwrap();
         
-        // Actualizar posición del listener
-        listener.position += listener.velocity * delta_time;
+        // Actualizar posición del listener (la cámara ya lo coloca si lo sigue)
+        if !self.listener_tracked {
+            listener.position += listener.velocity * delta_time;
+        }
         
         // Aplicar orientación
         // listener.orientation = ... (actualizar según input del usuario)
@@ -1051,14 +1765,59 @@ impl AudioSystem {
     /// Reproducir fuente de audio
     pub async fn play_audio_source(&mut self, id: &str) -> Result<()> {
         let mut sources = self.sources.write().unwrap();
-        if let Some(source) = sources.get_mut(id) {
-            source.state.playing = true;
-            source.state.paused = false;
-            source.state.playback_time = 0.0;
-        }
+        let Some(source) = sources.get_mut(id) else {
+            return Ok(());
+        };
+        source.state.playing = true;
+        source.state.paused = false;
+        source.state.playback_time = 0.0;
+        let source = source.clone();
+        drop(sources);
+        self.stop_source_voices(id);
+        self.play_source_voice(&source);
+        Ok(())
+    }
+
+    /// Reproducir desde el principio una fuente de audio en una posición,
+    /// con el volumen y el pitch indicados (sonidos puntuales como pasos)
+    pub async fn play_audio(&mut self, id: &str, position: Vec3, volume: f32, pitch: f32) -> Result<()> {
+        let mut sources = self.sources.write().unwrap();
+        let source = sources.get_mut(id).ok_or_else(|| anyhow!("Fuente de audio no encontrada: {}", id))?;
+        source.config.volume = volume;
+        source.config.pitch = pitch;
+        source.state.position = position;
+        source.state.velocity = Vec3::ZERO;
+        source.state.playing = true;
+        source.state.paused = false;
+        source.state.playback_time = 0.0;
+        let source = source.clone();
+        drop(sources);
+        self.play_source_voice(&source);
         Ok(())
     }
 
+    /// Crear la voz de una fuente si su archivo está cargado como clip
+    fn play_source_voice(&mut self, source: &AudioSource) {
+        let params = VoiceParams {
+            clip_id: source.config.audio_file.clone(),
+            bus: AudioBus::from(&source.source_type),
+            volume: source.config.volume,
+            pitch: source.config.pitch,
+            looped: source.config.looped,
+            position: source.state.position,
+            spatial: source.config.spatial.then(|| self.source_spatial_config(source)),
+            entity_id: None,
+            source_id: Some(source.id.clone()),
+            reverb_send: if source.config.spatial { 1.0 } else { 0.0 },
+            effects: Vec::new(),
+            velocity: source.state.velocity,
+            doppler: true,
+        };
+        if self.mixer.play(params).is_none() {
+            debug!("Clip {} sin cargar para la fuente {}", source.config.audio_file, source.id);
+        }
+    }
+
     /// Pausar fuente de audio
     pub async fn pause_audio_source(&mut self, id: &str) -> Result<()> {
         let mut sources = self.sources.write().unwrap();
@@ -1066,6 +1825,8 @@ impl AudioSystem {
             source.state.playing = false;
             source.state.paused = true;
         }
+        drop(sources);
+        self.stop_source_voices(id);
         Ok(())
     }
 
@@ -1077,9 +1838,16 @@ impl AudioSystem {
             source.state.paused = false;
             source.state.playback_time = 0.0;
         }
+        drop(sources);
+        self.stop_source_voices(id);
         Ok(())
     }
 
+    /// Parar las voces de una fuente
+    fn stop_source_voices(&mut self, source_id: &str) {
+        self.mixer.stop_where(|voice| voice.params.source_id.as_deref() == Some(source_id));
+    }
+
     /// Crear efecto de audio
     pub async fn create_audio_effect(&mut self, effect: AudioEffect) -> Result<()> {
         let mut effects = self.effects.write().unwrap();
@@ -1117,12 +1885,87 @@ impl AudioSystem {
         Ok(())
     }
 
+    /// Añadir track a la playlist de música adaptativa
+    pub fn add_music_track(&mut self, track: music::MusicTrack) {
+        self.music_manager.add_track(track);
+    }
+
+    /// Cambiar la intensidad de la música adaptativa (y el parámetro
+    /// `intensity` de los eventos)
+    pub fn set_music_intensity(&mut self, intensity: f32) {
+        self.music_manager.set_intensity(intensity);
+        self.events.set_parameter(&mut self.mixer, PARAMETER_INTENSITY, intensity);
+    }
+
+    /// Pausar música adaptativa
+    pub fn pause_music(&mut self) {
+        self.music_manager.pause();
+    }
+
+    /// Reanudar música adaptativa
+    pub fn resume_music(&mut self) {
+        self.music_manager.resume();
+    }
+
+    /// Suscribir la música adaptativa a los eventos del juego
+    pub fn subscribe_music_events(&self, events: &mut crate::ecs::EventSystem) {
+        self.music_manager.subscribe(events);
+    }
+
+    /// Gestor de música adaptativa
+    pub fn music_manager(&self) -> &MusicManager {
+        &self.music_manager
+    }
+
+    /// Añadir muestras del micrófono (mono, 48 kHz) al chat de voz
+    pub fn push_microphone_frame(&mut self, pcm: &[i16]) -> Result<()> {
+        match &mut self.voice {
+            Some(voice) => voice.push_microphone_frame(pcm),
+            None => Ok(()),
+        }
+    }
+
+    /// Tramas de voz codificadas pendientes de enviar por la red
+    pub fn drain_voice_packets(&mut self) -> Vec<EncodedVoiceFrame> {
+        self.voice.as_mut().map(|voice| voice.drain_outgoing()).unwrap_or_default()
+    }
+
+    /// Recibir un paquete de voz de un hablante que suena en `entity_id`
+    pub fn receive_voice_packet(&mut self, speaker: &str, entity_id: Option<crate::ecs::EntityId>, sequence: u32, data: Vec<u8>) -> Result<()> {
+        match &mut self.voice {
+            Some(voice) => voice.receive_packet(speaker, entity_id, sequence, data),
+            None => Ok(()),
+        }
+    }
+
+    /// Entidades de los hablantes de voz
+    pub fn voice_speaker_entities(&self) -> Vec<(String, crate::ecs::EntityId)> {
+        self.voice.as_ref().map(|voice| voice.speaker_entities()).unwrap_or_default()
+    }
+
+    /// Colocar la voz de un hablante
+    pub fn set_voice_speaker_position(&mut self, speaker: &str, position: Vec3) {
+        if let Some(voice) = &mut self.voice {
+            voice.set_speaker_position(speaker, position);
+        }
+    }
+
+    /// Extraer la voz mezclada (estéreo intercalado, 48 kHz, en [-1, 1])
+    pub fn take_voice_output(&mut self) -> Vec<f32> {
+        let mut output = std::mem::take(&mut self.voice_output);
+        for sample in &mut output {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+        output
+    }
+
     /// Actualizar estadísticas
     fn update_stats(&mut self, processing_time: f32) {
         self.stats.processing_time = processing_time;
         self.stats.source_count = self.sources.read().unwrap().len();
         self.stats.effect_count = self.effects.read().unwrap().len();
         self.stats.track_count = self.music.read().unwrap().len();
+        self.stats.voice = self.voice.as_ref().map(|voice| voice.stats()).unwrap_or_default();
         
         // Calcular uso de memoria
         self.stats.memory_usage = std::mem::size_of_val(self);
@@ -1142,8 +1985,29 @@ impl AudioSystem {
         self.sources.write().unwrap().clear();
         self.effects.write().unwrap().clear();
         self.music.write().unwrap().clear();
+        self.voice = None;
+        self.voice_output.clear();
+        self.mixer.stop_where(|_| true);
+        self.entity_voices.clear();
+        self.entity_positions.clear();
+        self.ambient.clear(&mut self.mixer);
+        self.reverb_zones.clear();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.native_output = None;
+        }
         
         info!("Sistema de audio limpiado");
         Ok(())
     }
-} 
\ No newline at end of file
+} 
+
+/// Leer los bytes de un archivo de audio local o remoto
+async fn fetch_audio(path: &str) -> Result<Vec<u8>> {
+    if path.starts_with("http://") || path.starts_with("https://") {
+        let response = reqwest::get(path).await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    } else {
+        Ok(tokio::fs::read(path).await?)
+    }
+}
@@ -0,0 +1,454 @@
+//! Música ambiental adaptativa
+//!
+//! Mantiene una playlist ordenada por intensidad y hace crossfade al track
+//! más cercano cuando cambia la intensidad del juego, alineando el inicio
+//! de la transición con el siguiente beat del track que suena.
+
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_2;
+use std::sync::{Arc, Mutex};
+use serde::{Serialize, Deserialize};
+use tracing::{debug, warn};
+
+use crate::ecs::{Event, EventHandler, EventSystem, EventType};
+
+/// Evento de entrada en combate
+pub const MUSIC_EVENT_COMBAT: &str = "combat";
+/// Evento de exploración
+pub const MUSIC_EVENT_EXPLORING: &str = "exploring";
+/// Evento de comercio
+pub const MUSIC_EVENT_TRADING: &str = "trading";
+
+/// Track de música adaptativa
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MusicTrack {
+    /// ID del track
+    pub id: String,
+    /// Ruta del audio
+    pub audio_path: String,
+    /// Tempo en pulsos por minuto
+    pub bpm: f32,
+    /// Inicio y fin del bucle en segundos
+    pub loop_points: (f32, f32),
+    /// Intensidad que representa (0.0 calma - 1.0 máxima)
+    pub intensity: f32,
+}
+
+impl MusicTrack {
+    /// Duración de un beat en segundos
+    pub fn beat_duration(&self) -> f32 {
+        if self.bpm > 0.0 {
+            60.0 / self.bpm
+        } else {
+            0.0
+        }
+    }
+
+    /// Segundos hasta el siguiente beat desde `position` (0 si cae justo en uno)
+    pub fn time_to_next_beat(&self, position: f32) -> f32 {
+        let beat = self.beat_duration();
+        if beat <= 0.0 {
+            return 0.0;
+        }
+
+        let phase = position.rem_euclid(beat);
+        if phase < 1e-4 || beat - phase < 1e-4 {
+            0.0
+        } else {
+            beat - phase
+        }
+    }
+
+    /// Avanza la posición respetando los puntos de bucle
+    fn advance(&self, position: f32, delta_time: f32) -> f32 {
+        let (loop_start, loop_end) = self.loop_points;
+        let mut position = position + delta_time;
+        let loop_length = loop_end - loop_start;
+        if loop_length > 0.0 && position >= loop_end {
+            position = loop_start + (position - loop_end).rem_euclid(loop_length);
+        }
+        position
+    }
+}
+
+/// Track sonando
+#[derive(Debug, Clone)]
+pub struct PlayingTrack {
+    /// Índice en la playlist
+    pub track: usize,
+    /// Posición de reproducción en segundos
+    pub position: f32,
+    /// Ganancia actual
+    pub gain: f32,
+}
+
+/// Crossfade en curso o programado
+#[derive(Debug, Clone)]
+struct Crossfade {
+    /// Track entrante (índice en la playlist)
+    incoming: usize,
+    /// Segundos hasta el beat en que empieza la transición
+    delay: f32,
+    /// Segundos transcurridos de la transición
+    elapsed: f32,
+    /// Ganancia del track saliente al empezar
+    from_gain: f32,
+}
+
+/// Gestor de música adaptativa
+pub struct MusicManager {
+    /// Playlist ordenada por intensidad
+    playlist: Vec<MusicTrack>,
+    /// Duración del crossfade en segundos
+    crossfade_duration_secs: f32,
+    /// Track principal
+    current: Option<PlayingTrack>,
+    /// Track que se desvanece
+    outgoing: Option<PlayingTrack>,
+    /// Transición pendiente
+    crossfade: Option<Crossfade>,
+    /// Intensidad objetivo
+    intensity: f32,
+    /// Intensidad pedida desde eventos del ECS
+    pending_intensity: Arc<Mutex<Option<f32>>>,
+    /// Pausado
+    paused: bool,
+}
+
+impl MusicManager {
+    /// Crear gestor de música
+    pub fn new(crossfade_duration_secs: f32) -> Self {
+        Self {
+            playlist: Vec::new(),
+            crossfade_duration_secs: crossfade_duration_secs.max(0.0),
+            current: None,
+            outgoing: None,
+            crossfade: None,
+            intensity: 0.0,
+            pending_intensity: Arc::new(Mutex::new(None)),
+            paused: false,
+        }
+    }
+
+    /// Añadir track a la playlist
+    pub fn add_track(&mut self, track: MusicTrack) {
+        let current_id = self.current.as_ref().map(|p| self.playlist[p.track].id.clone());
+        let outgoing_id = self.outgoing.as_ref().map(|p| self.playlist[p.track].id.clone());
+        let incoming_id = self.crossfade.as_ref().map(|c| self.playlist[c.incoming].id.clone());
+
+        self.playlist.retain(|t| t.id != track.id);
+        let index = self.playlist.partition_point(|t| t.intensity <= track.intensity);
+        self.playlist.insert(index, track);
+
+        // Reubicar índices tras la inserción
+        let find = |playlist: &[MusicTrack], id: &Option<String>| {
+            id.as_ref().and_then(|id| playlist.iter().position(|t| &t.id == id))
+        };
+        let current = find(&self.playlist, &current_id);
+        let outgoing = find(&self.playlist, &outgoing_id);
+        let incoming = find(&self.playlist, &incoming_id);
+        match (self.current.as_mut(), current) {
+            (Some(playing), Some(index)) => playing.track = index,
+            (Some(_), None) => self.current = None,
+            _ => {}
+        }
+        match (self.outgoing.as_mut(), outgoing) {
+            (Some(playing), Some(index)) => playing.track = index,
+            (Some(_), None) => self.outgoing = None,
+            _ => {}
+        }
+        match (self.crossfade.as_mut(), incoming) {
+            (Some(crossfade), Some(index)) => crossfade.incoming = index,
+            (Some(_), None) => self.crossfade = None,
+            _ => {}
+        }
+    }
+
+    /// Playlist ordenada por intensidad
+    pub fn playlist(&self) -> &[MusicTrack] {
+        &self.playlist
+    }
+
+    /// Cambiar la duración del crossfade
+    pub fn set_crossfade_duration(&mut self, secs: f32) {
+        self.crossfade_duration_secs = secs.max(0.0);
+    }
+
+    /// Intensidad objetivo actual
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    /// Índice del track con intensidad más cercana
+    fn closest_track(&self, intensity: f32) -> Option<usize> {
+        self.playlist
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                (a.intensity - intensity)
+                    .abs()
+                    .total_cmp(&(b.intensity - intensity).abs())
+            })
+            .map(|(index, _)| index)
+    }
+
+    /// Fijar intensidad y programar crossfade al track más cercano
+    pub fn set_intensity(&mut self, value: f32) {
+        self.intensity = value.clamp(0.0, 1.0);
+        let Some(target) = self.closest_track(self.intensity) else {
+            warn!("Playlist de música vacía, intensidad {} ignorada", self.intensity);
+            return;
+        };
+
+        // Sin música sonando: entra con fade desde silencio
+        let Some(current) = self.current.as_ref() else {
+            let start = self.playlist[target].loop_points.0.max(0.0);
+            self.current = Some(PlayingTrack { track: target, position: start, gain: 0.0 });
+            self.crossfade = Some(Crossfade { incoming: target, delay: 0.0, elapsed: 0.0, from_gain: 0.0 });
+            return;
+        };
+
+        let current_track = current.track;
+        if let Some(crossfade) = self.crossfade.as_mut() {
+            // Ya vamos hacia ese track
+            if crossfade.incoming == target {
+                return;
+            }
+            // Transición aún no empezada: solo cambia el destino
+            if crossfade.incoming != current_track {
+                if target == current_track {
+                    self.crossfade = None;
+                } else {
+                    crossfade.incoming = target;
+                }
+                return;
+            }
+            // Transición a medias: el entrante ya es el principal
+            self.outgoing = None;
+        } else if target == current_track {
+            return;
+        }
+
+        let current = self.current.as_ref().expect("track actual");
+        let delay = self.playlist[current.track].time_to_next_beat(current.position);
+        debug!(
+            "Crossfade '{}' -> '{}' en {:.3}s",
+            self.playlist[current.track].id, self.playlist[target].id, delay
+        );
+        self.crossfade = Some(Crossfade {
+            incoming: target,
+            delay,
+            elapsed: 0.0,
+            from_gain: current.gain,
+        });
+    }
+
+    /// Segundos hasta que empiece el crossfade programado
+    pub fn crossfade_delay(&self) -> Option<f32> {
+        self.crossfade.as_ref().map(|c| c.delay)
+    }
+
+    /// Avanzar reproducción y envolvente del crossfade
+    pub fn tick(&mut self, delta_time: f32) {
+        let pending = self.pending_intensity.lock().ok().and_then(|mut p| p.take());
+        if let Some(intensity) = pending {
+            self.set_intensity(intensity);
+        }
+
+        if self.paused || delta_time <= 0.0 {
+            return;
+        }
+
+        for playing in self.current.iter_mut().chain(self.outgoing.iter_mut()) {
+            playing.position = self.playlist[playing.track].advance(playing.position, delta_time);
+        }
+
+        let Some(crossfade) = self.crossfade.as_mut() else {
+            return;
+        };
+
+        // Esperar al beat; el sobrante del frame cuenta como transición
+        let mut remaining = delta_time;
+        if crossfade.delay > 0.0 {
+            let consumed = crossfade.delay.min(remaining);
+            crossfade.delay -= consumed;
+            remaining -= consumed;
+            if crossfade.delay > 0.0 {
+                return;
+            }
+        }
+
+        // Arranque del track entrante en el beat
+        let incoming_is_current = self.current.as_ref().map(|p| p.track) == Some(crossfade.incoming);
+        if !incoming_is_current && crossfade.elapsed == 0.0 {
+            let track = &self.playlist[crossfade.incoming];
+            let start = track.loop_points.0.max(0.0);
+            self.outgoing = self.current.take();
+            self.current = Some(PlayingTrack {
+                track: crossfade.incoming,
+                position: track.advance(start, remaining),
+                gain: 0.0,
+            });
+        }
+
+        crossfade.elapsed += remaining;
+        let t = if self.crossfade_duration_secs > 0.0 {
+            (crossfade.elapsed / self.crossfade_duration_secs).min(1.0)
+        } else {
+            1.0
+        };
+
+        // Curvas de igual potencia
+        if let Some(current) = self.current.as_mut() {
+            current.gain = (t * FRAC_PI_2).sin();
+        }
+        if let Some(outgoing) = self.outgoing.as_mut() {
+            outgoing.gain = crossfade.from_gain * (t * FRAC_PI_2).cos();
+        }
+
+        if t >= 1.0 {
+            self.crossfade = None;
+            self.outgoing = None;
+        }
+    }
+
+    /// Pausar música
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Reanudar música
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Música pausada
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Tracks sonando con su ganancia y posición
+    pub fn active_tracks(&self) -> Vec<(&MusicTrack, &PlayingTrack)> {
+        self.current
+            .iter()
+            .chain(self.outgoing.iter())
+            .map(|playing| (&self.playlist[playing.track], playing))
+            .collect()
+    }
+
+    /// Manejador que traduce eventos del juego a intensidad
+    pub fn event_handler(&self) -> MusicIntensityHandler {
+        MusicIntensityHandler {
+            intensities: default_event_intensities(),
+            pending: self.pending_intensity.clone(),
+        }
+    }
+
+    /// Suscribir la música a los eventos de combate, exploración y comercio
+    pub fn subscribe(&self, events: &mut EventSystem) {
+        for name in [MUSIC_EVENT_COMBAT, MUSIC_EVENT_EXPLORING, MUSIC_EVENT_TRADING] {
+            events.subscribe(EventType::Custom(name.to_string()), Box::new(self.event_handler()));
+        }
+    }
+}
+
+/// Intensidad asociada a cada evento del juego
+fn default_event_intensities() -> HashMap<String, f32> {
+    HashMap::from([
+        (MUSIC_EVENT_COMBAT.to_string(), 1.0),
+        (MUSIC_EVENT_EXPLORING.to_string(), 0.3),
+        (MUSIC_EVENT_TRADING.to_string(), 0.1),
+    ])
+}
+
+/// Manejador de eventos del ECS para la música adaptativa
+pub struct MusicIntensityHandler {
+    /// Intensidad por nombre de evento
+    intensities: HashMap<String, f32>,
+    /// Intensidad pendiente, la consume `MusicManager::tick`
+    pending: Arc<Mutex<Option<f32>>>,
+}
+
+impl MusicIntensityHandler {
+    /// Cambiar la intensidad asociada a un evento
+    pub fn with_intensity(mut self, event: &str, intensity: f32) -> Self {
+        self.intensities.insert(event.to_string(), intensity);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler for MusicIntensityHandler {
+    async fn handle(&self, event: &Event) -> Result<(), Box<dyn std::error::Error>> {
+        if let EventType::Custom(name) = &event.event_type {
+            if let Some(intensity) = self.intensities.get(name) {
+                *self.pending.lock().map_err(|e| e.to_string())? = Some(*intensity);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: &str, bpm: f32, intensity: f32) -> MusicTrack {
+        MusicTrack {
+            id: id.to_string(),
+            audio_path: format!("music/{}.ogg", id),
+            bpm,
+            loop_points: (0.0, 16.0),
+            intensity,
+        }
+    }
+
+    /// Ganancia de un track sonando (None si no suena)
+    fn gain(music: &MusicManager, id: &str) -> Option<f32> {
+        music.active_tracks().into_iter().find(|(track, _)| track.id == id).map(|(_, playing)| playing.gain)
+    }
+
+    #[test]
+    fn crossfade_starts_on_the_next_beat_of_the_playing_track() {
+        let mut music = MusicManager::new(1.0);
+        music.add_track(track("calm", 120.0, 0.0));
+        music.add_track(track("combat", 90.0, 1.0));
+
+        // Entrada desde silencio y 1.25 s de reproducción (beats cada 0.5 s)
+        music.set_intensity(0.0);
+        for _ in 0..10 {
+            music.tick(0.125);
+        }
+        assert!((gain(&music, "calm").unwrap() - 1.0).abs() < 1e-6);
+
+        music.set_intensity(1.0);
+        assert_eq!(music.crossfade_delay(), Some(0.25));
+
+        // Antes del beat solo suena el track tranquilo
+        music.tick(0.125);
+        assert_eq!(music.active_tracks().len(), 1);
+        assert_eq!(music.crossfade_delay(), Some(0.125));
+
+        // El frame que cruza el beat arranca el entrante con el sobrante
+        music.tick(0.1875);
+        let tracks = music.active_tracks();
+        let position = |id: &str| tracks.iter().find(|(track, _)| track.id == id).unwrap().1.position;
+        assert_eq!(position("combat"), 0.0625);
+        assert_eq!(position("calm") - position("combat"), 1.5);
+
+        // Curvas de igual potencia hasta completar el segundo de transición
+        let (mut previous_in, mut previous_out) = (0.0, 1.0);
+        let mut elapsed = 0.0625;
+        while elapsed < 1.0 {
+            let (incoming, outgoing) = (gain(&music, "combat").unwrap(), gain(&music, "calm").unwrap());
+            assert!(incoming > previous_in && outgoing < previous_out);
+            assert!((incoming * incoming + outgoing * outgoing - 1.0).abs() < 1e-4);
+            (previous_in, previous_out) = (incoming, outgoing);
+            music.tick(0.125);
+            elapsed += 0.125;
+        }
+        assert!((gain(&music, "combat").unwrap() - 1.0).abs() < 1e-6);
+        assert_eq!(gain(&music, "calm"), None);
+        assert_eq!(music.crossfade_delay(), None);
+    }
+}
@@ -0,0 +1,155 @@
+//! Salida de la mezcla
+//!
+//! El mezclador escribe en un `AudioRingBuffer` (estéreo intercalado) y el
+//! dispositivo lo vacía desde su propio hilo: en nativo un stream de cpal y
+//! en wasm un AudioWorklet, que lee con `AudioOutputHandle::read`. Si el
+//! dispositivo no consume, las muestras que no caben se descartan; si se
+//! queda sin muestras, reproduce silencio.
+
+use crossbeam::queue::ArrayQueue;
+use std::sync::Arc;
+
+/// Buffer circular de muestras entre el mezclador y el dispositivo
+pub struct AudioRingBuffer {
+    samples: ArrayQueue<f32>,
+}
+
+impl AudioRingBuffer {
+    /// Buffer con capacidad para `capacity` muestras
+    pub fn new(capacity: usize) -> Self {
+        Self { samples: ArrayQueue::new(capacity.max(2)) }
+    }
+
+    /// Añadir muestras. Devuelve las que cupieron
+    pub fn write(&self, samples: &[f32]) -> usize {
+        samples.iter().take_while(|sample| self.samples.push(**sample).is_ok()).count()
+    }
+
+    /// Llenar `out` con las muestras pendientes y silencio detrás. Devuelve
+    /// las muestras leídas
+    pub fn read(&self, out: &mut [f32]) -> usize {
+        let mut read = 0;
+        for slot in out.iter_mut() {
+            match self.samples.pop() {
+                Some(sample) => {
+                    *slot = sample;
+                    read += 1;
+                }
+                None => *slot = 0.0,
+            }
+        }
+        read
+    }
+
+    /// Muestras pendientes
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Sin muestras pendientes
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Muestras que caben
+    pub fn free(&self) -> usize {
+        self.samples.capacity() - self.samples.len()
+    }
+}
+
+/// Stream de cpal en un hilo propio (los streams no se pueden mover entre
+/// hilos)
+#[cfg(not(target_arch = "wasm32"))]
+pub struct NativeOutput {
+    stop: Arc<std::sync::atomic::AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl NativeOutput {
+    /// Abrir el dispositivo de salida por defecto en estéreo
+    pub fn start(ring: Arc<AudioRingBuffer>, sample_rate: u32) -> anyhow::Result<Self> {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel::<anyhow::Result<()>>();
+        let thread_stop = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("audio-output".to_string())
+            .spawn(move || {
+                let stream = (|| -> anyhow::Result<cpal::Stream> {
+                    let device = cpal::default_host()
+                        .default_output_device()
+                        .ok_or_else(|| anyhow::anyhow!("No hay dispositivo de salida de audio"))?;
+                    let config = cpal::StreamConfig {
+                        channels: 2,
+                        sample_rate: cpal::SampleRate(sample_rate),
+                        buffer_size: cpal::BufferSize::Default,
+                    };
+                    let stream = device.build_output_stream(
+                        &config,
+                        move |data: &mut [f32], _| {
+                            ring.read(data);
+                        },
+                        |err| tracing::error!("Error en la salida de audio: {}", err),
+                        None,
+                    )?;
+                    stream.play()?;
+                    Ok(stream)
+                })();
+                let stream = match stream {
+                    Ok(stream) => {
+                        let _ = ready_tx.send(Ok(()));
+                        stream
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+                while !thread_stop.load(Ordering::Acquire) {
+                    std::thread::park();
+                }
+                drop(stream);
+            })?;
+
+        ready_rx.recv().map_err(|_| anyhow::anyhow!("El hilo de audio terminó sin abrir el dispositivo"))??;
+        Ok(Self { stop, thread: Some(thread) })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for NativeOutput {
+    fn drop(&mut self) {
+        self.stop.store(true, std::sync::atomic::Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Acceso del AudioWorklet a la mezcla
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub struct AudioOutputHandle {
+    ring: Arc<AudioRingBuffer>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl AudioOutputHandle {
+    pub(crate) fn new(ring: Arc<AudioRingBuffer>) -> Self {
+        Self { ring }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+impl AudioOutputHandle {
+    /// Llenar un bloque estéreo intercalado del worklet. Devuelve las
+    /// muestras leídas (el resto es silencio)
+    pub fn read(&self, out: &mut [f32]) -> usize {
+        self.ring.read(out)
+    }
+}
@@ -0,0 +1,225 @@
+//! Reverb por zonas
+//!
+//! Las zonas de reverb son cajas o esferas del mundo con un preset (tiempo
+//! de decaimiento, amortiguación, pre-delay y nivel húmedo). El peso de una
+//! zona es 1 con el oyente dentro y cae a 0 a lo largo de `blend_distance`
+//! fuera de ella; el mezclador mantiene un `Freeverb` por zona alimentado
+//! por el bus de envío de las voces y funde su salida según el peso, de
+//! modo que al pasar de una zona a otra una cola se apaga mientras la otra
+//! entra.
+//!
+//! `Freeverb` es el reverberador de Jezar: ocho filtros peine con paso bajo
+//! en la realimentación en paralelo y cuatro all-pass en serie por canal,
+//! con el canal derecho desplazado unas muestras para abrir la imagen
+//! estéreo. La realimentación de cada peine se calcula para que la cola
+//! caiga 60 dB en el tiempo de decaimiento del preset.
+
+use glam::Vec3;
+use serde::{Serialize, Deserialize};
+
+use super::ReverbConfig;
+
+/// Longitudes de los peines a 44,1 kHz
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+/// Longitudes de los all-pass a 44,1 kHz
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+/// Desplazamiento del canal derecho a 44,1 kHz
+const STEREO_SPREAD: usize = 23;
+/// Frecuencia de referencia de las longitudes
+const TUNING_SAMPLE_RATE: f32 = 44_100.0;
+/// Ganancia de entrada de los peines
+const FIXED_GAIN: f32 = 0.015;
+/// Realimentación de los all-pass
+const ALLPASS_FEEDBACK: f32 = 0.5;
+/// Amortiguación máxima del paso bajo de los peines
+const MAX_DAMPING: f32 = 0.4;
+
+/// Parámetros de una reverb
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReverbPreset {
+    /// Tiempo en segundos en que la cola cae 60 dB
+    pub decay: f32,
+    /// Amortiguación de los agudos en [0, 1]
+    pub damping: f32,
+    /// Retardo antes de la cola en segundos
+    pub pre_delay: f32,
+    /// Nivel de la señal reverberada
+    pub wet_level: f32,
+}
+
+impl ReverbPreset {
+    /// Habitación pequeña
+    pub fn room() -> Self {
+        Self { decay: 0.6, damping: 0.5, pre_delay: 0.005, wet_level: 0.25 }
+    }
+
+    /// Sala grande
+    pub fn hall() -> Self {
+        Self { decay: 2.5, damping: 0.3, pre_delay: 0.03, wet_level: 0.35 }
+    }
+
+    /// Cueva
+    pub fn cave() -> Self {
+        Self { decay: 4.5, damping: 0.15, pre_delay: 0.05, wet_level: 0.5 }
+    }
+}
+
+impl From<&ReverbConfig> for ReverbPreset {
+    fn from(config: &ReverbConfig) -> Self {
+        Self { decay: config.decay, damping: 0.5, pre_delay: config.pre_delay, wet_level: config.wet_level }
+    }
+}
+
+/// Volumen de una zona
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ReverbZoneShape {
+    /// Caja alineada con los ejes
+    Box { center: Vec3, half_extents: Vec3 },
+    /// Esfera
+    Sphere { center: Vec3, radius: f32 },
+}
+
+impl ReverbZoneShape {
+    /// Distancia de un punto al volumen (0 dentro)
+    pub fn distance(&self, point: Vec3) -> f32 {
+        match *self {
+            ReverbZoneShape::Box { center, half_extents } => {
+                ((point - center).abs() - half_extents).max(Vec3::ZERO).length()
+            }
+            ReverbZoneShape::Sphere { center, radius } => (point.distance(center) - radius).max(0.0),
+        }
+    }
+}
+
+/// Zona de reverb
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReverbZone {
+    /// ID de la zona
+    pub id: String,
+    /// Volumen
+    pub shape: ReverbZoneShape,
+    /// Reverb dentro de la zona
+    pub preset: ReverbPreset,
+    /// Distancia fuera del volumen a lo largo de la que se funde
+    #[serde(default = "default_blend_distance")]
+    pub blend_distance: f32,
+}
+
+fn default_blend_distance() -> f32 {
+    2.0
+}
+
+impl ReverbZone {
+    /// Peso de la zona para un oyente: 1 dentro y 0 a `blend_distance`
+    pub fn weight(&self, listener: Vec3) -> f32 {
+        let distance = self.shape.distance(listener);
+        if self.blend_distance <= f32::EPSILON {
+            return if distance <= 0.0 { 1.0 } else { 0.0 };
+        }
+        (1.0 - distance / self.blend_distance).clamp(0.0, 1.0)
+    }
+}
+
+/// Filtro peine con paso bajo en la realimentación
+#[derive(Debug, Clone)]
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    feedback: f32,
+    filter_store: f32,
+}
+
+impl Comb {
+    fn process(&mut self, input: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.index];
+        self.filter_store = output * (1.0 - damping) + self.filter_store * damping;
+        self.buffer[self.index] = input + self.filter_store * self.feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+}
+
+/// Filtro all-pass
+#[derive(Debug, Clone)]
+struct Allpass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.index];
+        self.buffer[self.index] = input + delayed * ALLPASS_FEEDBACK;
+        self.index = (self.index + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+/// Reverberador Freeverb estéreo
+#[derive(Debug, Clone)]
+pub struct Freeverb {
+    /// Peines de cada canal
+    combs: [Vec<Comb>; 2],
+    /// All-pass de cada canal
+    allpasses: [Vec<Allpass>; 2],
+    /// Línea de pre-delay
+    pre_delay: Vec<f32>,
+    pre_delay_index: usize,
+    /// Amortiguación del paso bajo de los peines
+    damping: f32,
+}
+
+impl Freeverb {
+    /// Reverberador con un preset a una frecuencia de muestreo
+    pub fn new(preset: &ReverbPreset, sample_rate: u32) -> Self {
+        let scale = sample_rate as f32 / TUNING_SAMPLE_RATE;
+        let length = |samples: usize, spread: usize| (((samples + spread) as f32 * scale).round() as usize).max(1);
+        let decay_samples = preset.decay.max(0.01) * sample_rate as f32;
+        let channel = |spread: usize| {
+            let combs = COMB_TUNING.iter()
+                .map(|&samples| {
+                    let len = length(samples, spread);
+                    Comb {
+                        buffer: vec![0.0; len],
+                        index: 0,
+                        // -60 dB tras `decay` segundos de recirculación
+                        feedback: 10f32.powf(-3.0 * len as f32 / decay_samples).min(0.98),
+                        filter_store: 0.0,
+                    }
+                })
+                .collect();
+            let allpasses = ALLPASS_TUNING.iter()
+                .map(|&samples| Allpass { buffer: vec![0.0; length(samples, spread)], index: 0 })
+                .collect();
+            (combs, allpasses)
+        };
+        let (left_combs, left_allpasses) = channel(0);
+        let (right_combs, right_allpasses) = channel(STEREO_SPREAD);
+        Self {
+            combs: [left_combs, right_combs],
+            allpasses: [left_allpasses, right_allpasses],
+            pre_delay: vec![0.0; ((preset.pre_delay.max(0.0) * sample_rate as f32).round() as usize).max(1)],
+            pre_delay_index: 0,
+            damping: preset.damping.clamp(0.0, 1.0) * MAX_DAMPING,
+        }
+    }
+
+    /// Procesar una muestra mono y devolver la cola (izquierda, derecha)
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let delayed = self.pre_delay[self.pre_delay_index];
+        self.pre_delay[self.pre_delay_index] = input;
+        self.pre_delay_index = (self.pre_delay_index + 1) % self.pre_delay.len();
+
+        let input = delayed * FIXED_GAIN;
+        let damping = self.damping;
+        let mut output = [0.0; 2];
+        for channel in 0..2 {
+            let mut sample: f32 = self.combs[channel].iter_mut().map(|comb| comb.process(input, damping)).sum();
+            for allpass in &mut self.allpasses[channel] {
+                sample = allpass.process(sample);
+            }
+            output[channel] = sample;
+        }
+        (output[0], output[1])
+    }
+}
@@ -0,0 +1,353 @@
+//! Clips en streaming
+//!
+//! Las pistas largas (música ambiente) no se decodifican enteras: un
+//! `StreamingClip` guarda los bytes comprimidos y un worker los decodifica y
+//! convierte a la frecuencia del motor por bloques, con un margen de lectura
+//! anticipada (`read_ahead` segundos) en un canal acotado. En wasm no hay
+//! hilos y los bloques se decodifican al consumirse.
+//!
+//! Con puntos de bucle, al llegar al final del bucle el decodificador vuelve
+//! al principio y descarta hasta el inicio del bucle; los frames de los dos
+//! lados pasan por el mismo resampler, así que la costura no tiene huecos.
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+use tracing::warn;
+
+use super::decode::{open_decoder, BlockDecoder, Resampler, DECODE_BLOCK_FRAMES};
+
+/// Puntos de bucle en segundos del archivo
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoopPoints {
+    /// Inicio del bucle
+    pub start: f32,
+    /// Final del bucle (None: el final del archivo)
+    pub end: Option<f32>,
+}
+
+impl Default for LoopPoints {
+    fn default() -> Self {
+        Self { start: 0.0, end: None }
+    }
+}
+
+/// Decodificación con bucle y conversión de frecuencia
+struct StreamSource {
+    decoder: Box<dyn BlockDecoder>,
+    resampler: Resampler,
+    channels: usize,
+    /// Frames del archivo ya leídos desde el principio
+    source_frame: u64,
+    /// Bucle en frames del archivo
+    loop_frames: Option<(u64, Option<u64>)>,
+    /// Resto del bloque que contiene el inicio del bucle
+    pending: Option<Vec<f32>>,
+    finished: bool,
+}
+
+impl StreamSource {
+    fn new(bytes: Arc<[u8]>, sample_rate: u32, looping: Option<LoopPoints>) -> Result<Self> {
+        let decoder = open_decoder(bytes)?;
+        let channels = decoder.channels();
+        let rate = decoder.sample_rate() as f32;
+        let loop_frames = looping.map(|points| {
+            let start = (points.start.max(0.0) * rate) as u64;
+            (start, points.end.map(|end| ((end * rate) as u64).max(start + 1)))
+        });
+        Ok(Self {
+            resampler: Resampler::new(channels, decoder.sample_rate(), sample_rate),
+            decoder,
+            channels,
+            source_frame: 0,
+            loop_frames,
+            pending: None,
+            finished: false,
+        })
+    }
+
+    /// Siguiente bloque a la frecuencia del motor (None al terminar)
+    fn next_chunk(&mut self) -> Result<Option<Vec<f32>>> {
+        let mut output = Vec::new();
+        while output.is_empty() {
+            if self.finished {
+                return Ok(None);
+            }
+            match self.next_source_block()? {
+                Some(block) => self.resampler.process(&block, &mut output),
+                None => {
+                    self.resampler.finish(&mut output);
+                    self.finished = true;
+                }
+            }
+        }
+        Ok(Some(output))
+    }
+
+    /// Bloque del decodificador con el frame del archivo en que empieza
+    fn decode_block(&mut self) -> Result<Option<(u64, Vec<f32>)>> {
+        let Some(block) = self.decoder.next_block()? else {
+            return Ok(None);
+        };
+        let start = self.source_frame;
+        self.source_frame += (block.len() / self.channels) as u64;
+        Ok(Some((start, block)))
+    }
+
+    /// Siguiente bloque del archivo, con el bucle aplicado
+    fn next_source_block(&mut self) -> Result<Option<Vec<f32>>> {
+        if let Some(block) = self.pending.take() {
+            return Ok(Some(block));
+        }
+        let Some((block_start, mut block)) = self.decode_block()? else {
+            // Final del archivo: vuelve al inicio del bucle si está dentro
+            return match self.loop_frames {
+                Some((start, _)) if self.source_frame > start => {
+                    self.seek_loop_start(start)?;
+                    self.next_source_block()
+                }
+                _ => Ok(None),
+            };
+        };
+        if let Some((start, Some(end))) = self.loop_frames {
+            if self.source_frame >= end {
+                // Cortar en el final del bucle; el inicio sigue a continuación
+                block.truncate((end - block_start) as usize * self.channels);
+                self.seek_loop_start(start)?;
+            }
+        }
+        Ok(Some(block))
+    }
+
+    /// Volver al inicio del bucle: desde el principio del archivo se
+    /// descarta hasta `start` y el resto de ese bloque queda pendiente
+    fn seek_loop_start(&mut self, start: u64) -> Result<()> {
+        self.decoder.rewind()?;
+        self.source_frame = 0;
+        while let Some((block_start, block)) = self.decode_block()? {
+            if self.source_frame > start {
+                let skip = (start - block_start) as usize * self.channels;
+                self.pending = Some(block[skip..].to_vec());
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Flujo de bloques ya convertidos
+enum Feed {
+    /// Worker con lectura anticipada
+    #[cfg(not(target_arch = "wasm32"))]
+    Worker {
+        receiver: crossbeam::channel::Receiver<Vec<f32>>,
+        worker: Option<std::thread::JoinHandle<()>>,
+    },
+    /// Decodificación al consumir
+    #[cfg(target_arch = "wasm32")]
+    Inline(StreamSource),
+}
+
+/// Clip decodificado por bloques mientras suena
+pub struct StreamingClip {
+    id: String,
+    channels: usize,
+    feed: Feed,
+    /// Bloque actual y posición de lectura
+    current: Vec<f32>,
+    offset: usize,
+    finished: bool,
+}
+
+impl std::fmt::Debug for StreamingClip {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamingClip")
+            .field("id", &self.id)
+            .field("channels", &self.channels)
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+
+impl StreamingClip {
+    /// Abrir un stream sobre los bytes de un WAV u OGG/Vorbis, convertido a
+    /// `sample_rate` y con `read_ahead` segundos decodificados por delante
+    pub fn open(id: &str, bytes: Arc<[u8]>, sample_rate: u32, read_ahead: f32, looping: Option<LoopPoints>) -> Result<Self> {
+        let source = StreamSource::new(bytes, sample_rate, looping)?;
+        let channels = source.channels;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let feed = {
+            let chunk_frames = DECODE_BLOCK_FRAMES as f32;
+            let chunks = ((read_ahead.max(0.0) * sample_rate as f32 / chunk_frames).ceil() as usize).max(1);
+            let (sender, receiver) = crossbeam::channel::bounded(chunks);
+            let name = id.to_string();
+            let worker = std::thread::Builder::new()
+                .name(format!("audio-stream-{}", id))
+                .spawn(move || {
+                    let mut source = source;
+                    loop {
+                        match source.next_chunk() {
+                            // El canal se cierra al soltar el clip
+                            Ok(Some(chunk)) => {
+                                if sender.send(chunk).is_err() {
+                                    break;
+                                }
+                            }
+                            Ok(None) => break,
+                            Err(e) => {
+                                warn!("Error decodificando el stream {}: {}", name, e);
+                                break;
+                            }
+                        }
+                    }
+                })?;
+            Feed::Worker { receiver, worker: Some(worker) }
+        };
+        #[cfg(target_arch = "wasm32")]
+        let feed = {
+            let _ = read_ahead;
+            Feed::Inline(source)
+        };
+
+        Ok(Self { id: id.to_string(), channels, feed, current: Vec::new(), offset: 0, finished: false })
+    }
+
+    /// ID del clip
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Canales (1 o 2)
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Terminó (sin bucle, al acabar el archivo)
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Siguiente bloque del flujo. None si no hay ninguno listo todavía
+    fn next_chunk(&mut self) -> Option<Vec<f32>> {
+        match &mut self.feed {
+            #[cfg(not(target_arch = "wasm32"))]
+            Feed::Worker { receiver, .. } => match receiver.try_recv() {
+                Ok(chunk) => Some(chunk),
+                Err(crossbeam::channel::TryRecvError::Empty) => None,
+                Err(crossbeam::channel::TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    None
+                }
+            },
+            #[cfg(target_arch = "wasm32")]
+            Feed::Inline(source) => match source.next_chunk() {
+                Ok(Some(chunk)) => Some(chunk),
+                Ok(None) => {
+                    self.finished = true;
+                    None
+                }
+                Err(e) => {
+                    warn!("Error decodificando el stream {}: {}", self.id, e);
+                    self.finished = true;
+                    None
+                }
+            },
+        }
+    }
+
+    /// Leer hasta `frames` frames (izquierda, derecha). Si el worker va por
+    /// detrás se devuelven menos y el resto suena en silencio
+    pub fn read_frames(&mut self, frames: usize) -> Vec<(f32, f32)> {
+        let channels = self.channels;
+        let mut output = Vec::with_capacity(frames);
+        while output.len() < frames {
+            if self.offset >= self.current.len() {
+                match self.next_chunk() {
+                    Some(chunk) => {
+                        self.current = chunk;
+                        self.offset = 0;
+                    }
+                    None => break,
+                }
+                continue;
+            }
+            let frame = &self.current[self.offset..self.offset + channels];
+            output.push(if channels == 1 { (frame[0], frame[0]) } else { (frame[0], frame[1]) });
+            self.offset += channels;
+        }
+        output
+    }
+}
+
+impl Drop for StreamingClip {
+    fn drop(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Feed::Worker { receiver, worker } = &mut self.feed {
+            // Cerrar el canal desbloquea al worker
+            drop(std::mem::replace(receiver, crossbeam::channel::never()));
+            if let Some(worker) = worker.take() {
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// 1 s de seno de 440 Hz a media amplitud, 44,1 kHz: 440 ciclos exactos,
+    /// así que el archivo entero enlaza consigo mismo sin salto
+    const TONE_WAV: &[u8] = include_bytes!("../../tests/fixtures/audio/tone_44k1.wav");
+
+    /// Leer `frames` frames esperando al worker cuando va por detrás
+    fn read_all(clip: &mut StreamingClip, frames: usize) -> Vec<f32> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut output = Vec::with_capacity(frames);
+        while output.len() < frames {
+            let read = clip.read_frames(frames - output.len());
+            if read.is_empty() {
+                assert!(!clip.is_finished(), "el stream terminó tras {} frames", output.len());
+                assert!(Instant::now() < deadline, "el worker no entrega frames");
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            output.extend(read.into_iter().map(|(left, right)| {
+                assert_eq!(left, right);
+                left
+            }));
+        }
+        output
+    }
+
+    #[test]
+    fn looping_stream_crosses_the_loop_point_without_a_gap() {
+        let mut clip = StreamingClip::open("tone", Arc::from(TONE_WAV), 48000, 0.1, Some(LoopPoints::default())).unwrap();
+        assert_eq!(clip.channels(), 1);
+        let frames = read_all(&mut clip, 3 * 48000);
+
+        // Cada vuelta son 48000 frames y repite la anterior
+        for (i, (a, b)) in frames[..48000].iter().zip(&frames[48000..96000]).enumerate() {
+            assert!((a - b).abs() < 1e-5, "frame {}: {} frente a {}", i, a, b);
+        }
+        // Ningún salto mayor que el paso del seno: ni silencio ni cortes en la costura
+        let max_step = 0.5 * std::f32::consts::TAU * 440.0 / 48000.0;
+        for (i, pair) in frames.windows(2).enumerate() {
+            assert!((pair[1] - pair[0]).abs() <= max_step * 1.1, "salto en el frame {}", i + 1);
+        }
+        assert!(!clip.is_finished());
+    }
+
+    #[test]
+    fn stream_without_loop_finishes_after_the_file() {
+        let mut clip = StreamingClip::open("tone", Arc::from(TONE_WAV), 48000, 0.1, None).unwrap();
+        assert_eq!(read_all(&mut clip, 48000).len(), 48000);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !clip.is_finished() {
+            assert!(clip.read_frames(DECODE_BLOCK_FRAMES).is_empty());
+            assert!(Instant::now() < deadline);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
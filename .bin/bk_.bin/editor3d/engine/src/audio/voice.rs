@@ -0,0 +1,434 @@
+//! Chat de voz
+//!
+//! El micrófono se codifica en Opus en tramas de 20 ms a 48 kHz mono y
+//! cada trama sale numerada hacia la red (`NetworkingSystem::send_voice_packet`).
+//! Los paquetes recibidos entran en un `VoiceJitterBuffer` por hablante que
+//! acumula unos 60 ms antes de reproducir, absorbe el desorden de la red y
+//! marca como perdidos los paquetes que no llegan a tiempo; el decodificador
+//! Opus rellena esos huecos con su ocultación de pérdidas. Cada hablante
+//! tiene su propio decodificador porque el estado de Opus es por flujo.
+//!
+//! Las tramas decodificadas salen con la posición de la entidad del
+//! hablante para que `AudioSystem` las mezcle en la salida espacial.
+//!
+//! libopus es una biblioteca de C y solo se enlaza en nativo: en wasm32
+//! `VoiceChat::new` devuelve un error y el sistema de audio sigue sin voz.
+
+use std::collections::{BTreeMap, HashMap};
+use anyhow::Result;
+use glam::Vec3;
+use serde::{Serialize, Deserialize};
+use tracing::debug;
+
+use crate::ecs::EntityId;
+
+/// Frecuencia de muestreo de la voz
+pub const VOICE_SAMPLE_RATE: u32 = 48_000;
+/// Duración de una trama en segundos
+pub const VOICE_FRAME_DURATION: f32 = 0.02;
+/// Muestras por trama (mono)
+pub const VOICE_FRAME_SAMPLES: usize = 960;
+/// Latencia objetivo del jitter buffer en segundos
+pub const VOICE_TARGET_LATENCY: f32 = 0.06;
+/// Tamaño máximo de un paquete Opus
+const MAX_PACKET_BYTES: usize = 1275;
+/// Tramas máximas en espera por hablante (1 s); las más viejas se descartan
+const MAX_BUFFERED_FRAMES: usize = 50;
+/// Un hablante está activo si habló en este tiempo (s)
+const ACTIVE_SPEAKER_WINDOW: f32 = 0.5;
+/// Un hablante en silencio este tiempo (s) se olvida
+const SPEAKER_EXPIRY: f32 = 10.0;
+
+/// Estadísticas del chat de voz
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VoiceStats {
+    /// Hablantes con voz reciente
+    pub active_speakers: u32,
+    /// Porcentaje de paquetes perdidos o llegados tarde
+    pub packet_loss_pct: f32,
+}
+
+/// Trama de micrófono codificada para enviar
+#[derive(Debug, Clone)]
+pub struct EncodedVoiceFrame {
+    /// Número de secuencia
+    pub sequence: u32,
+    /// Paquete Opus
+    pub data: Vec<u8>,
+}
+
+/// Trama decodificada de un hablante
+#[derive(Debug, Clone)]
+pub struct DecodedVoiceFrame {
+    /// Hablante
+    pub speaker: String,
+    /// Posición mundo del hablante
+    pub position: Vec3,
+    /// Orden de la trama dentro de la actualización
+    pub slot: usize,
+    /// Muestras mono a 48 kHz
+    pub pcm: Vec<i16>,
+}
+
+/// Siguiente trama que sale del jitter buffer
+#[derive(Debug, Clone, PartialEq)]
+pub enum JitterFrame {
+    /// Paquete recibido
+    Packet(Vec<u8>),
+    /// Paquete perdido: hay que ocultarlo
+    Lost,
+    /// Sin datos (acumulando o sin voz)
+    Empty,
+}
+
+/// Buffer de jitter por números de secuencia
+#[derive(Debug, Clone)]
+pub struct VoiceJitterBuffer {
+    /// Paquetes en espera por secuencia
+    packets: BTreeMap<u32, Vec<u8>>,
+    /// Secuencia de la siguiente trama a reproducir
+    next_sequence: Option<u32>,
+    /// Tramas a acumular antes de reproducir
+    target_frames: usize,
+    /// Acumulando hasta la latencia objetivo
+    buffering: bool,
+    /// Paquetes reproducidos
+    played: u64,
+    /// Paquetes perdidos o llegados tarde
+    lost: u64,
+}
+
+impl VoiceJitterBuffer {
+    /// Nuevo buffer con una latencia objetivo en segundos
+    pub fn new(target_latency: f32) -> Self {
+        Self {
+            packets: BTreeMap::new(),
+            next_sequence: None,
+            target_frames: ((target_latency / VOICE_FRAME_DURATION).round() as usize).max(1),
+            buffering: true,
+            played: 0,
+            lost: 0,
+        }
+    }
+
+    /// Guarda un paquete. Los repetidos y los que llegan después de que su
+    /// trama se reprodujo se descartan
+    pub fn insert(&mut self, sequence: u32, data: Vec<u8>) -> bool {
+        if self.next_sequence.is_some_and(|next| sequence < next) || self.packets.contains_key(&sequence) {
+            return false;
+        }
+        self.packets.insert(sequence, data);
+
+        // Sin sitio se salta hasta el paquete más viejo que queda
+        while self.packets.len() > MAX_BUFFERED_FRAMES {
+            self.packets.pop_first();
+            if let Some((&first, _)) = self.packets.first_key_value() {
+                if let Some(next) = self.next_sequence {
+                    self.lost += u64::from(first.saturating_sub(next));
+                }
+                self.next_sequence = Some(first);
+            }
+        }
+        true
+    }
+
+    /// Saca la trama de los siguientes 20 ms
+    pub fn pop(&mut self) -> JitterFrame {
+        if self.buffering {
+            if self.packets.len() < self.target_frames {
+                return JitterFrame::Empty;
+            }
+            self.buffering = false;
+            // Lo que se saltó mientras no había voz cuenta como perdido
+            let first = *self.packets.keys().next().unwrap();
+            if let Some(next) = self.next_sequence {
+                self.lost += u64::from(first.saturating_sub(next));
+            }
+            self.next_sequence = Some(first);
+        }
+
+        let Some(next) = self.next_sequence else {
+            return JitterFrame::Empty;
+        };
+        if let Some(data) = self.packets.remove(&next) {
+            self.next_sequence = Some(next.wrapping_add(1));
+            self.played += 1;
+            return JitterFrame::Packet(data);
+        }
+        if self.packets.is_empty() {
+            // Se vació: volver a acumular sin avanzar
+            self.buffering = true;
+            return JitterFrame::Empty;
+        }
+        self.next_sequence = Some(next.wrapping_add(1));
+        self.lost += 1;
+        JitterFrame::Lost
+    }
+
+    /// Tramas en espera
+    pub fn buffered_frames(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Paquetes reproducidos y perdidos
+    pub fn counters(&self) -> (u64, u64) {
+        (self.played, self.lost)
+    }
+}
+
+/// Códec Opus de los flujos de voz
+#[cfg(not(target_arch = "wasm32"))]
+mod codec {
+    use super::VOICE_SAMPLE_RATE;
+    use anyhow::Result;
+
+    pub use opus::{Decoder, Encoder};
+
+    /// Codificador mono para voz con el bitrate en bits por segundo
+    pub fn encoder(bitrate: u32) -> Result<Encoder> {
+        let mut encoder = Encoder::new(VOICE_SAMPLE_RATE, opus::Channels::Mono, opus::Application::Voip)?;
+        encoder.set_bitrate(opus::Bitrate::Bits(bitrate.clamp(6_000, 510_000) as i32))?;
+        Ok(encoder)
+    }
+
+    /// Decodificador mono de un flujo
+    pub fn decoder() -> Result<Decoder> {
+        Ok(Decoder::new(VOICE_SAMPLE_RATE, opus::Channels::Mono)?)
+    }
+}
+
+/// Sin libopus en wasm32: los tipos no tienen valores y crearlos falla
+#[cfg(target_arch = "wasm32")]
+mod codec {
+    use anyhow::{anyhow, Result};
+
+    pub enum Encoder {}
+
+    impl Encoder {
+        pub fn encode(&mut self, _pcm: &[i16], _packet: &mut [u8]) -> Result<usize> {
+            match *self {}
+        }
+    }
+
+    pub enum Decoder {}
+
+    impl Decoder {
+        pub fn decode(&mut self, _packet: &[u8], _pcm: &mut [i16], _fec: bool) -> Result<usize> {
+            match *self {}
+        }
+    }
+
+    pub fn encoder(_bitrate: u32) -> Result<Encoder> {
+        Err(anyhow!("El chat de voz necesita libopus, que no existe en wasm32"))
+    }
+
+    pub fn decoder() -> Result<Decoder> {
+        Err(anyhow!("El chat de voz necesita libopus, que no existe en wasm32"))
+    }
+}
+
+/// Hablante remoto con su decodificador
+struct VoiceSpeaker {
+    /// Decodificador Opus del flujo
+    decoder: codec::Decoder,
+    /// Paquetes en espera
+    jitter_buffer: VoiceJitterBuffer,
+    /// Entidad en la que suena la voz
+    entity_id: Option<EntityId>,
+    /// Posición mundo de la entidad
+    position: Vec3,
+    /// Segundos desde el último paquete
+    silence: f32,
+}
+
+/// Chat de voz: codificación del micrófono y decodificación por hablante
+pub struct VoiceChat {
+    /// Codificador Opus del micrófono
+    encoder: codec::Encoder,
+    /// Muestras del micrófono pendientes de completar una trama
+    capture: Vec<i16>,
+    /// Secuencia de la siguiente trama codificada
+    sequence: u32,
+    /// Tramas codificadas pendientes de enviar
+    outgoing: Vec<EncodedVoiceFrame>,
+    /// Hablantes remotos
+    speakers: HashMap<String, VoiceSpeaker>,
+    /// Tiempo acumulado hasta la siguiente trama de reproducción
+    playback_clock: f32,
+}
+
+impl VoiceChat {
+    /// Nuevo chat de voz con el bitrate en bits por segundo
+    pub fn new(bitrate: u32) -> Result<Self> {
+        Ok(Self {
+            encoder: codec::encoder(bitrate)?,
+            capture: Vec::with_capacity(VOICE_FRAME_SAMPLES * 2),
+            sequence: 0,
+            outgoing: Vec::new(),
+            speakers: HashMap::new(),
+            playback_clock: 0.0,
+        })
+    }
+
+    /// Añade muestras del micrófono (mono, 48 kHz) y codifica cada trama
+    /// de 20 ms completa
+    pub fn push_microphone_frame(&mut self, pcm: &[i16]) -> Result<()> {
+        self.capture.extend_from_slice(pcm);
+        let mut packet = [0u8; MAX_PACKET_BYTES];
+        let mut consumed = 0;
+        while self.capture.len() - consumed >= VOICE_FRAME_SAMPLES {
+            let frame = &self.capture[consumed..consumed + VOICE_FRAME_SAMPLES];
+            let length = self.encoder.encode(frame, &mut packet)?;
+            self.outgoing.push(EncodedVoiceFrame { sequence: self.sequence, data: packet[..length].to_vec() });
+            self.sequence = self.sequence.wrapping_add(1);
+            consumed += VOICE_FRAME_SAMPLES;
+        }
+        self.capture.drain(..consumed);
+        Ok(())
+    }
+
+    /// Tramas codificadas desde la última llamada
+    pub fn drain_outgoing(&mut self) -> Vec<EncodedVoiceFrame> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /// Recibe un paquete de un hablante que suena en `entity_id`
+    pub fn receive_packet(&mut self, speaker: &str, entity_id: Option<EntityId>, sequence: u32, data: Vec<u8>) -> Result<()> {
+        if !self.speakers.contains_key(speaker) {
+            debug!("Nuevo hablante de voz: {}", speaker);
+            self.speakers.insert(speaker.to_string(), VoiceSpeaker {
+                decoder: codec::decoder()?,
+                jitter_buffer: VoiceJitterBuffer::new(VOICE_TARGET_LATENCY),
+                entity_id,
+                position: Vec3::ZERO,
+                silence: 0.0,
+            });
+        }
+        let entry = self.speakers.get_mut(speaker).unwrap();
+        entry.entity_id = entity_id.or(entry.entity_id);
+        entry.silence = 0.0;
+        entry.jitter_buffer.insert(sequence, data);
+        Ok(())
+    }
+
+    /// Entidades de los hablantes, para colocar sus voces
+    pub fn speaker_entities(&self) -> Vec<(String, EntityId)> {
+        self.speakers.iter()
+            .filter_map(|(id, speaker)| speaker.entity_id.map(|entity_id| (id.clone(), entity_id)))
+            .collect()
+    }
+
+    /// Algún hablante envió voz en la última ventana de actividad
+    pub fn has_active_speakers(&self) -> bool {
+        self.speakers.values().any(|speaker| speaker.silence < ACTIVE_SPEAKER_WINDOW)
+    }
+
+    /// Coloca la voz de un hablante
+    pub fn set_speaker_position(&mut self, speaker: &str, position: Vec3) {
+        if let Some(speaker) = self.speakers.get_mut(speaker) {
+            speaker.position = position;
+        }
+    }
+
+    /// Avanza la reproducción y decodifica una trama por hablante cada 20 ms
+    pub fn tick(&mut self, delta_time: f32) -> Result<Vec<DecodedVoiceFrame>> {
+        for speaker in self.speakers.values_mut() {
+            speaker.silence += delta_time;
+        }
+        self.speakers.retain(|_, speaker| speaker.silence < SPEAKER_EXPIRY || speaker.jitter_buffer.buffered_frames() > 0);
+
+        self.playback_clock += delta_time;
+        let mut frames = Vec::new();
+        let mut slot = 0;
+        while self.playback_clock >= VOICE_FRAME_DURATION {
+            self.playback_clock -= VOICE_FRAME_DURATION;
+            for (id, speaker) in self.speakers.iter_mut() {
+                let mut pcm = vec![0i16; VOICE_FRAME_SAMPLES];
+                let decoded = match speaker.jitter_buffer.pop() {
+                    JitterFrame::Packet(data) => speaker.decoder.decode(&data, &mut pcm, false)?,
+                    // Un paquete vacío pide al decodificador que oculte la pérdida
+                    JitterFrame::Lost => speaker.decoder.decode(&[], &mut pcm, false)?,
+                    JitterFrame::Empty => continue,
+                };
+                pcm.truncate(decoded);
+                frames.push(DecodedVoiceFrame { speaker: id.clone(), position: speaker.position, slot, pcm });
+            }
+            slot += 1;
+        }
+        Ok(frames)
+    }
+
+    /// Estadísticas de los hablantes
+    pub fn stats(&self) -> VoiceStats {
+        let (played, lost) = self.speakers.values()
+            .map(|speaker| speaker.jitter_buffer.counters())
+            .fold((0, 0), |(played, lost), (p, l)| (played + p, lost + l));
+        let total = played + lost;
+        VoiceStats {
+            active_speakers: self.speakers.values().filter(|speaker| speaker.silence < ACTIVE_SPEAKER_WINDOW).count() as u32,
+            packet_loss_pct: if total > 0 { lost as f32 / total as f32 * 100.0 } else { 0.0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tramas del tono de prueba (0,5 s)
+    const TONE_FRAMES: usize = 25;
+    /// Tramas iniciales que se ignoran mientras el códec converge
+    const WARMUP_FRAMES: usize = 5;
+    /// Retardo máximo del códec que se busca, en muestras
+    const MAX_CODEC_DELAY: usize = 1_000;
+
+    fn sine(samples: usize) -> Vec<i16> {
+        (0..samples)
+            .map(|i| {
+                let t = i as f32 / VOICE_SAMPLE_RATE as f32;
+                ((2.0 * std::f32::consts::PI * 440.0 * t).sin() * 0.5 * i16::MAX as f32) as i16
+            })
+            .collect()
+    }
+
+    /// Relación señal/ruido en dB de `decoded` frente a `reference`
+    /// retrasada `delay` muestras
+    fn snr_db(reference: &[i16], decoded: &[i16], delay: usize) -> f64 {
+        let start = WARMUP_FRAMES * VOICE_FRAME_SAMPLES;
+        let (signal, noise) = (start..decoded.len())
+            .filter(|&i| i >= delay && i - delay < reference.len())
+            .map(|i| (reference[i - delay] as f64, decoded[i] as f64))
+            .fold((0.0, 0.0), |(signal, noise), (expected, actual)| {
+                (signal + expected * expected, noise + (expected - actual).powi(2))
+            });
+        10.0 * (signal / noise.max(f64::MIN_POSITIVE)).log10()
+    }
+
+    #[test]
+    fn sine_survives_the_opus_loopback() {
+        let tone = sine(TONE_FRAMES * VOICE_FRAME_SAMPLES);
+        let mut sender = VoiceChat::new(64_000).unwrap();
+        let mut receiver = VoiceChat::new(64_000).unwrap();
+
+        sender.push_microphone_frame(&tone).unwrap();
+        let packets = sender.drain_outgoing();
+        assert_eq!(packets.len(), TONE_FRAMES);
+        for packet in packets {
+            receiver.receive_packet("peer", None, packet.sequence, packet.data).unwrap();
+        }
+
+        let mut decoded = Vec::new();
+        for _ in 0..TONE_FRAMES * 2 {
+            for frame in receiver.tick(VOICE_FRAME_DURATION).unwrap() {
+                decoded.extend(frame.pcm);
+            }
+        }
+        assert_eq!(decoded.len(), tone.len());
+
+        // El códec retrasa la señal unas pocas muestras
+        let best = (0..MAX_CODEC_DELAY)
+            .map(|delay| snr_db(&tone, &decoded, delay))
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert!(best > 30.0, "SNR de {:.1} dB", best);
+    }
+}
@@ -0,0 +1,304 @@
+//! # Flashbots
+//!
+//! Envío de transacciones a un relay de Flashbots en lugar del mempool
+//! público, para que nadie pueda adelantarlas ni envolverlas en un sándwich.
+//! Las transacciones van en un bundle dirigido a un bloque concreto y cada
+//! petición JSON-RPC se firma con la clave del wallet en la cabecera
+//! `X-Flashbots-Signature` (firma EIP-191 del keccak del cuerpo).
+//!
+//! Si el bundle no entra en su bloque, `BlockchainSystem` lo reenvía al
+//! siguiente; tras `MAX_MISSED_BLOCKS` bloques fallidos envía las
+//! transacciones por el mempool público.
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+
+use metaverso_engine::crypto::SignedTransaction;
+use metaverso_engine::crypto::wallet::{encode_hex, key_address, sign_message_with_key};
+
+/// Bloques fallidos antes de pasar al mempool público
+pub const MAX_MISSED_BLOCKS: u32 = 3;
+
+/// Hash de un bundle asignado por el relay (hex con prefijo 0x)
+pub type BundleHash = String;
+
+/// Transacciones firmadas que deben entrar juntas y en orden en un bloque
+#[derive(Debug, Clone)]
+pub struct FlashbotsBundle {
+    /// Transacciones firmadas
+    pub transactions: Vec<SignedTransaction>,
+    /// Bloque en el que deben entrar
+    pub target_block: u64,
+}
+
+impl FlashbotsBundle {
+    /// Parámetros de `eth_sendBundle`
+    fn params(&self) -> Value {
+        let txs: Vec<String> = self.transactions.iter()
+            .map(|tx| format!("0x{}", encode_hex(&tx.raw)))
+            .collect();
+        json!([{
+            "txs": txs,
+            "blockNumber": format!("0x{:x}", self.target_block),
+        }])
+    }
+}
+
+/// Estado de un bundle en el relay (`flashbots_getBundleStats`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleStats {
+    /// El relay simuló el bundle
+    #[serde(default)]
+    pub is_simulated: bool,
+    /// El relay lo entregó a los builders
+    #[serde(default)]
+    pub is_sent_to_miners: bool,
+    /// Prioridad alta en el relay
+    #[serde(default)]
+    pub is_high_priority: bool,
+    /// Momento de la simulación
+    #[serde(default)]
+    pub simulated_at: Option<String>,
+    /// Momento de la recepción
+    #[serde(default)]
+    pub submitted_at: Option<String>,
+}
+
+/// Cliente JSON-RPC de un relay de Flashbots
+pub struct FlashbotsClient {
+    /// URL del relay
+    relay_url: String,
+    /// Clave que firma las peticiones
+    auth_key: k256::SecretKey,
+    /// Cliente HTTP
+    http: reqwest::Client,
+}
+
+impl FlashbotsClient {
+    /// Cliente de `relay_url` que firma con `auth_key`
+    pub fn new(relay_url: &str, auth_key: k256::SecretKey) -> Self {
+        Self {
+            relay_url: relay_url.to_string(),
+            auth_key,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// URL del relay
+    pub fn relay_url(&self) -> &str {
+        &self.relay_url
+    }
+
+    /// Enviar un bundle al relay
+    pub async fn send_bundle(&self, bundle: &FlashbotsBundle) -> Result<BundleHash> {
+        if bundle.transactions.is_empty() {
+            return Err(anyhow!("Bundle sin transacciones"));
+        }
+        let result = self.call("eth_sendBundle", bundle.params()).await?;
+        result.get("bundleHash")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Respuesta de eth_sendBundle sin bundleHash"))
+    }
+
+    /// Estado de un bundle enviado para `target_block`
+    pub async fn bundle_stats(&self, bundle_hash: &str, target_block: u64) -> Result<BundleStats> {
+        let params = json!([{
+            "bundleHash": bundle_hash,
+            "blockNumber": format!("0x{:x}", target_block),
+        }]);
+        let result = self.call("flashbots_getBundleStats", params).await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Llamada JSON-RPC firmada
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let body = serde_json::to_vec(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        }))?;
+
+        // El relay espera la firma del hash en hex, no de sus bytes
+        let body_hash = format!("0x{}", encode_hex(&Keccak256::digest(&body)));
+        let signature = sign_message_with_key(&self.auth_key, body_hash.as_bytes());
+        let header = format!("{}:0x{}", key_address(&self.auth_key), encode_hex(&signature));
+
+        let response: Value = self.http.post(&self.relay_url)
+            .header("Content-Type", "application/json")
+            .header("X-Flashbots-Signature", header)
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if let Some(error) = response.get("error") {
+            return Err(anyhow!("Error del relay en {}: {}", method, error));
+        }
+        response.get("result")
+            .cloned()
+            .ok_or_else(|| anyhow!("Respuesta de {} sin resultado", method))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metaverso_engine::crypto::UnsignedTransaction;
+    use metaverso_engine::crypto::wallet::{recover_message_signer, sign_transaction_with_key};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// Petición recibida por el relay de prueba
+    struct RelayRequest {
+        /// Cabecera `X-Flashbots-Signature`
+        signature: String,
+        /// Cuerpo JSON-RPC tal cual llegó
+        body: Vec<u8>,
+    }
+
+    /// Relay mínimo: anota cada petición y responde `result`
+    async fn mock_relay(result: Value) -> (String, mpsc::UnboundedReceiver<RelayRequest>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, requests) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+                let header_end = loop {
+                    if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break end + 4;
+                    }
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    if read == 0 {
+                        return;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                };
+                let headers = String::from_utf8_lossy(&request[..header_end]).to_string();
+                let header = |name: &str| headers.lines()
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.trim().to_string())
+                    .unwrap_or_default();
+                let length: usize = header("content-length").parse().unwrap_or(0);
+                while request.len() < header_end + length {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    if read == 0 {
+                        return;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                let _ = sender.send(RelayRequest {
+                    signature: header("x-flashbots-signature"),
+                    body: request[header_end..header_end + length].to_vec(),
+                });
+
+                let response = json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (url, requests)
+    }
+
+    /// Clave fija de los tests
+    fn key() -> k256::SecretKey {
+        k256::SecretKey::from_slice(&[7u8; 32]).unwrap()
+    }
+
+    /// Transferencias firmadas con nonces consecutivos
+    fn transfers(count: u64) -> Vec<SignedTransaction> {
+        (0..count)
+            .map(|nonce| sign_transaction_with_key(&key(), &UnsignedTransaction {
+                nonce,
+                gas_price: 20_000_000_000,
+                gas_limit: 21000,
+                to: Some("0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB".to_string()),
+                value: 1_000 + nonce as u128,
+                data: Vec::new(),
+                chain_id: 1,
+            }).unwrap())
+            .collect()
+    }
+
+    fn decode_hex(value: &str) -> Vec<u8> {
+        let value = value.trim_start_matches("0x");
+        (0..value.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn relay_receives_a_signed_bundle_in_order() {
+        let (url, mut requests) = mock_relay(json!({ "bundleHash": "0xb0b" })).await;
+        let client = FlashbotsClient::new(&url, key());
+        let bundle = FlashbotsBundle { transactions: transfers(3), target_block: 0x1234 };
+
+        assert_eq!(client.send_bundle(&bundle).await.unwrap(), "0xb0b");
+
+        let request = requests.recv().await.unwrap();
+        // La firma es del keccak del cuerpo en hex y la recupera la dirección del wallet
+        let (address, signature) = request.signature.split_once(':').unwrap();
+        assert_eq!(address, key_address(&key()));
+        let signature: [u8; 65] = decode_hex(signature).try_into().unwrap();
+        let body_hash = format!("0x{}", encode_hex(&Keccak256::digest(&request.body)));
+        assert_eq!(recover_message_signer(body_hash.as_bytes(), &signature).unwrap(), address);
+
+        let body: Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["method"], "eth_sendBundle");
+        let params = &body["params"][0];
+        assert_eq!(params["blockNumber"], "0x1234");
+        let txs: Vec<String> = params["txs"].as_array().unwrap().iter()
+            .map(|tx| tx.as_str().unwrap().to_string())
+            .collect();
+        let expected: Vec<String> = bundle.transactions.iter()
+            .map(|tx| format!("0x{}", encode_hex(&tx.raw)))
+            .collect();
+        assert_eq!(txs, expected);
+    }
+
+    #[tokio::test]
+    async fn tampered_body_does_not_verify() {
+        let (url, mut requests) = mock_relay(json!({ "bundleHash": "0xb0b" })).await;
+        let client = FlashbotsClient::new(&url, key());
+        let mut transactions = transfers(2);
+        client.send_bundle(&FlashbotsBundle { transactions: transactions.clone(), target_block: 1 }).await.unwrap();
+        let request = requests.recv().await.unwrap();
+
+        // Otro orden es otro cuerpo: la firma recibida ya no corresponde
+        transactions.reverse();
+        let reordered = serde_json::to_vec(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendBundle",
+            "params": FlashbotsBundle { transactions, target_block: 1 }.params(),
+        })).unwrap();
+        assert_ne!(reordered, request.body);
+        let (address, signature) = request.signature.split_once(':').unwrap();
+        let signature: [u8; 65] = decode_hex(signature).try_into().unwrap();
+        let body_hash = format!("0x{}", encode_hex(&Keccak256::digest(&reordered)));
+        assert_ne!(recover_message_signer(body_hash.as_bytes(), &signature).unwrap(), address);
+    }
+
+    #[tokio::test]
+    async fn empty_bundles_and_relay_errors_are_rejected() {
+        let (url, _requests) = mock_relay(json!({})).await;
+        let client = FlashbotsClient::new(&url, key());
+        assert!(client.send_bundle(&FlashbotsBundle { transactions: Vec::new(), target_block: 1 }).await.is_err());
+        // Respuesta sin bundleHash
+        assert!(client.send_bundle(&FlashbotsBundle { transactions: transfers(1), target_block: 1 }).await.is_err());
+    }
+}
@@ -3,6 +3,8 @@
 //! Proporciona integración con múltiples blockchains,
 //! smart contracts, NFTs y transacciones descentralizadas.
 
+pub mod flashbots;
+
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
@@ -12,12 +14,16 @@ use anyhow::{Result, anyhow};
 use web3::{
     Web3, 
     transports::Http,
-    types::{Address, U256, Bytes, BlockNumber, TransactionReceipt, Log},
+    types::{Address, U256, H256, Bytes, BlockNumber, TransactionReceipt, Log},
     contract::{Contract, Options},
 };
 use secp256k1::{SecretKey, PublicKey, Secp256k1};
 use rand::rngs::OsRng;
 
+use metaverso_engine::crypto::UnsignedTransaction;
+use metaverso_engine::crypto::wallet::sign_transaction_with_key;
+use flashbots::{FlashbotsBundle, FlashbotsClient, BundleHash, MAX_MISSED_BLOCKS};
+
 /// Sistema Blockchain principal
 pub struct BlockchainSystem {
     /// Configuración del sistema
@@ -30,6 +36,10 @@ pub struct BlockchainSystem {
     wallets: Arc<RwLock<HashMap<String, Wallet>>>,
     /// Transacciones pendientes
     pending_transactions: Arc<RwLock<Vec<PendingTransaction>>>,
+    /// Cliente del relay de Flashbots
+    flashbots: Option<FlashbotsClient>,
+    /// Bundles enviados al relay pendientes de entrar en un bloque
+    bundle_submissions: Arc<RwLock<Vec<BundleSubmission>>>,
     /// Estado del sistema
     running: bool,
 }
@@ -47,6 +57,10 @@ pub struct BlockchainConfig {
     pub security_config: SecurityConfig,
     /// Configuración de transacciones
     pub transaction_config: TransactionConfig,
+    /// Relay de Flashbots; si se indica, las transacciones se envían en
+    /// bundles privados en lugar de al mempool público
+    #[serde(default)]
+    pub flashbots_relay_url: Option<String>,
 }
 
 /// Configuración de red
@@ -231,6 +245,19 @@ pub struct PendingTransaction {
     pub timestamp: u64,
 }
 
+/// Bundle enviado al relay de Flashbots
+#[derive(Debug, Clone)]
+pub struct BundleSubmission {
+    /// Bundle enviado
+    pub bundle: FlashbotsBundle,
+    /// Hash asignado por el relay
+    pub bundle_hash: BundleHash,
+    /// Red
+    pub network: String,
+    /// Bloques en los que el bundle no entró
+    pub missed_blocks: u32,
+}
+
 /// Estado de la transacción
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TransactionStatus {
@@ -251,6 +278,8 @@ impl BlockchainSystem {
             contracts: Arc::new(RwLock::new(HashMap::new())),
             wallets: Arc::new(RwLock::new(HashMap::new())),
             pending_transactions: Arc::new(RwLock::new(Vec::new())),
+            flashbots: None,
+            bundle_submissions: Arc::new(RwLock::new(Vec::new())),
             running: false,
         }
     }
@@ -339,6 +368,13 @@ impl BlockchainSystem {
         let mut wallets = self.wallets.write().unwrap();
         wallets.insert("default".to_string(), wallet);
 
+        // El relay identifica al remitente por la clave que firma las peticiones
+        if let Some(relay_url) = &self.config.flashbots_relay_url {
+            let auth_key = k256::SecretKey::from_slice(&secret_key.secret_bytes())?;
+            self.flashbots = Some(FlashbotsClient::new(relay_url, auth_key));
+            info!("Transacciones privadas por Flashbots: {}", relay_url);
+        }
+
         info!("Wallet por defecto creada: {:?}", address);
         Ok(())
     }
@@ -362,6 +398,9 @@ impl BlockchainSystem {
         // Actualizar wallets
         self.update_wallets().await?;
 
+        // Reenviar o liberar los bundles que no entraron
+        self.process_bundle_submissions().await?;
+
         // Procesar transacciones pendientes
         self.process_pending_transactions().await?;
 
@@ -463,6 +502,119 @@ impl BlockchainSystem {
         Ok(())
     }
 
+    /// Procesar bundles enviados al relay de Flashbots
+    async fn process_bundle_submissions(&mut self) -> Result<()> {
+        let Some(flashbots) = &self.flashbots else {
+            return Ok(());
+        };
+        let connections = self.connections.read().unwrap();
+        let mut submissions = self.bundle_submissions.write().unwrap();
+        let mut remaining = Vec::with_capacity(submissions.len());
+
+        for mut submission in submissions.drain(..) {
+            let Some(connection) = connections.get(&submission.network).filter(|c| c.state.connected) else {
+                remaining.push(submission);
+                continue;
+            };
+            // El bloque objetivo aún no se ha minado
+            let current_block = connection.state.last_block;
+            if current_block < submission.bundle.target_block {
+                remaining.push(submission);
+                continue;
+            }
+
+            // Las transacciones del bundle entran todas o ninguna
+            let first_hash = submission.bundle.transactions[0].hash.parse::<H256>()?;
+            if let Ok(Some(_)) = connection.web3.eth().transaction_receipt(first_hash).await {
+                info!("Bundle {} incluido en el bloque {}", submission.bundle_hash, submission.bundle.target_block);
+                continue;
+            }
+
+            match flashbots.bundle_stats(&submission.bundle_hash, submission.bundle.target_block).await {
+                Ok(stats) if !stats.is_simulated => {
+                    warn!("El relay no simuló el bundle {}", submission.bundle_hash);
+                }
+                Ok(stats) => debug!("Bundle {}: {:?}", submission.bundle_hash, stats),
+                Err(e) => warn!("Error consultando el bundle {}: {}", submission.bundle_hash, e),
+            }
+
+            submission.missed_blocks += 1;
+            if submission.missed_blocks >= MAX_MISSED_BLOCKS {
+                warn!(
+                    "Bundle {} fuera de {} bloques; envío por el mempool público",
+                    submission.bundle_hash, submission.missed_blocks
+                );
+                for transaction in &submission.bundle.transactions {
+                    if let Err(e) = connection.web3.eth().send_raw_transaction(Bytes(transaction.raw.clone())).await {
+                        error!("Error enviando la transacción {}: {}", transaction.hash, e);
+                    }
+                }
+                continue;
+            }
+
+            submission.bundle.target_block = current_block + 1;
+            match flashbots.send_bundle(&submission.bundle).await {
+                Ok(bundle_hash) => {
+                    debug!("Bundle reenviado al bloque {}", submission.bundle.target_block);
+                    submission.bundle_hash = bundle_hash;
+                }
+                Err(e) => warn!("Error reenviando el bundle {}: {}", submission.bundle_hash, e),
+            }
+            remaining.push(submission);
+        }
+
+        *submissions = remaining;
+        Ok(())
+    }
+
+    /// Enviar transacción por el relay de Flashbots. Si el relay no acepta
+    /// el bundle se envía por el mempool público
+    async fn send_private_transaction(
+        &self,
+        flashbots: &FlashbotsClient,
+        connection: &BlockchainConnection,
+        wallet: &Wallet,
+        to: Address,
+        value: U256,
+        data: Option<Bytes>,
+    ) -> Result<String> {
+        let transaction = UnsignedTransaction {
+            nonce: wallet.nonce,
+            gas_price: connection.config.gas_config.gas_price as u128,
+            gas_limit: 21000,
+            to: Some(format!("{:?}", to)),
+            value: value.as_u128(),
+            data: data.map(|data| data.0).unwrap_or_default(),
+            chain_id: connection.config.chain_id,
+        };
+        let signing_key = k256::SecretKey::from_slice(&wallet.private_key.secret_bytes())?;
+        let signed = sign_transaction_with_key(&signing_key, &transaction)?;
+        let hash = signed.hash.clone();
+
+        let bundle = FlashbotsBundle {
+            transactions: vec![signed],
+            target_block: connection.state.last_block + 1,
+        };
+        match flashbots.send_bundle(&bundle).await {
+            Ok(bundle_hash) => {
+                info!("Bundle {} enviado para el bloque {}", bundle_hash, bundle.target_block);
+                let mut submissions = self.bundle_submissions.write().unwrap();
+                submissions.push(BundleSubmission {
+                    bundle,
+                    bundle_hash,
+                    network: connection.network_id.clone(),
+                    missed_blocks: 0,
+                });
+            }
+            Err(e) => {
+                warn!("El relay {} rechazó el bundle: {}; envío público", flashbots.relay_url(), e);
+                let raw = Bytes(bundle.transactions[0].raw.clone());
+                connection.web3.eth().send_raw_transaction(raw).await?;
+            }
+        }
+        Ok(hash)
+    }
+
     /// Enviar transacción
     pub async fn send_transaction(&mut self, network: &str, to: Address, value: U256, data: Option<Bytes>) -> Result<String> {
         let connections = self.connections.read().unwrap();
@@ -473,6 +625,13 @@ impl BlockchainSystem {
         let wallet = wallets.get("default")
             .ok_or_else(|| anyhow!("Wallet no encontrada"))?;
 
+        // Con relay configurado la transacción no pasa por el mempool público
+        if let Some(flashbots) = &self.flashbots {
+            let hash = self.send_private_transaction(flashbots, connection, wallet, to, value, data).await?;
+            self.track_transaction(network, hash.clone());
+            return Ok(hash);
+        }
+
         // Crear transacción
         let transaction = web3::types::TransactionRequest::new()
             .to(to)
@@ -493,11 +652,17 @@ impl BlockchainSystem {
 
         // Enviar transacción
         let hash = connection.web3.eth().send_raw_transaction(signed.into()).await?;
+        self.track_transaction(network, format!("{:?}", hash));
 
-        // Agregar a transacciones pendientes
+        info!("Transacción enviada: {:?}", hash);
+        Ok(format!("{:?}", hash))
+    }
+
+    /// Agregar a transacciones pendientes
+    fn track_transaction(&self, network: &str, hash: String) {
         let pending_transaction = PendingTransaction {
             id: format!("tx_{}", hash),
-            hash: format!("{:?}", hash),
+            hash,
             network: network.to_string(),
             status: TransactionStatus::Pending,
             confirmations: 0,
@@ -509,9 +674,6 @@ impl BlockchainSystem {
 
         let mut pending_transactions = self.pending_transactions.write().unwrap();
         pending_transactions.push(pending_transaction);
-
-        info!("Transacción enviada: {:?}", hash);
-        Ok(format!("{:?}", hash))
     }
 
     /// Desplegar contrato
@@ -595,6 +757,8 @@ impl BlockchainSystem {
         self.contracts.write().unwrap().clear();
         self.wallets.write().unwrap().clear();
         self.pending_transactions.write().unwrap().clear();
+        self.bundle_submissions.write().unwrap().clear();
+        self.flashbots = None;
         
         info!("Sistema Blockchain limpiado");
         Ok(())
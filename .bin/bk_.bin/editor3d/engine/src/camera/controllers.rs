@@ -0,0 +1,484 @@
+//! # Controladores de cámara
+//!
+//! Comportamientos que mueven la cámara activa a partir de la entrada del
+//! usuario y del estado del ECS: órbita alrededor de un punto, primera
+//! persona pegada a la cabeza de un avatar y seguimiento amortiguado por un
+//! muelle. Cada controlador produce una `CameraPose` por frame; al cambiar de
+//! controlador, `CameraBlend` interpola durante un tiempo entre la pose
+//! saliente y la del controlador entrante.
+//!
+//! Los ángulos de la configuración (pitch, yaw, FOV) van en grados.
+
+use glam::{EulerRot, Mat4, Quat, Vec2, Vec3};
+use serde::{Serialize, Deserialize};
+
+use crate::ecs::{ComponentType, ECSSystem, EntityId, TransformComponent};
+
+/// Producto ω·t en el que un muelle críticamente amortiguado queda a menos
+/// del 2% del objetivo: (1 + ωt)·e^(-ωt) = 0.02
+const SPRING_SETTLE_FACTOR: f32 = 5.83;
+
+/// Entrada de cámara de un frame. La capa web la rellena con el ratón, la
+/// rueda y el teclado
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraInput {
+    /// Desplazamiento del ratón en píxeles (x a la derecha, y hacia abajo)
+    pub look_delta: Vec2,
+    /// Rueda del ratón (positivo acerca)
+    pub zoom_delta: f32,
+}
+
+/// Pose de la cámara
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraPose {
+    /// Posición
+    pub position: Vec3,
+    /// Orientación (la cámara mira hacia -Z)
+    pub rotation: Quat,
+    /// Campo de visión vertical en grados
+    pub fov: f32,
+}
+
+impl CameraPose {
+    /// Pose en `position` mirando a `target`
+    pub fn look_at(position: Vec3, target: Vec3, fov: f32) -> Self {
+        let direction = target - position;
+        let rotation = if direction.length_squared() > f32::EPSILON {
+            Quat::from_mat4(&Mat4::look_to_rh(position, direction.normalize(), Vec3::Y).inverse())
+        } else {
+            Quat::IDENTITY
+        };
+        Self { position, rotation, fov }
+    }
+
+    /// Interpolar hacia `other` (t en [0, 1])
+    pub fn lerp(&self, other: &CameraPose, t: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            fov: self.fov + (other.fov - self.fov) * t,
+        }
+    }
+}
+
+/// Configuración del controlador orbital
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrbitControllerConfig {
+    /// Punto alrededor del que se orbita
+    pub target: Vec3,
+    /// Distancia inicial al objetivo
+    pub distance: f32,
+    /// Límites de distancia [mínimo, máximo]
+    pub distance_limits: [f32; 2],
+    /// Yaw inicial
+    pub yaw: f32,
+    /// Pitch inicial (positivo mira desde arriba)
+    pub pitch: f32,
+    /// Límites de pitch [mínimo, máximo]
+    pub pitch_limits: [f32; 2],
+    /// Grados por píxel de ratón
+    pub rotation_speed: f32,
+    /// Fracción de distancia por unidad de rueda
+    pub zoom_speed: f32,
+    /// Campo de visión
+    pub fov: f32,
+}
+
+impl Default for OrbitControllerConfig {
+    fn default() -> Self {
+        Self {
+            target: Vec3::ZERO,
+            distance: 10.0,
+            distance_limits: [1.0, 100.0],
+            yaw: 0.0,
+            pitch: 20.0,
+            pitch_limits: [-80.0, 80.0],
+            rotation_speed: 0.25,
+            zoom_speed: 0.1,
+            fov: 60.0,
+        }
+    }
+}
+
+/// Controlador orbital
+#[derive(Debug, Clone)]
+pub struct OrbitController {
+    config: OrbitControllerConfig,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+impl OrbitController {
+    /// Crear controlador
+    pub fn new(config: OrbitControllerConfig) -> Self {
+        let mut controller = Self {
+            yaw: config.yaw,
+            pitch: config.pitch,
+            distance: config.distance,
+            config,
+        };
+        controller.clamp();
+        controller
+    }
+
+    /// Cambiar el punto de órbita
+    pub fn set_target(&mut self, target: Vec3) {
+        self.config.target = target;
+    }
+
+    /// Pitch actual en grados
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Yaw actual en grados
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    /// Distancia actual al objetivo
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    /// Aplicar la entrada y devolver la pose
+    pub fn update(&mut self, input: &CameraInput) -> CameraPose {
+        self.yaw -= input.look_delta.x * self.config.rotation_speed;
+        self.pitch += input.look_delta.y * self.config.rotation_speed;
+        self.distance *= 1.0 - input.zoom_delta * self.config.zoom_speed;
+        self.clamp();
+        self.pose()
+    }
+
+    /// Pose para el estado actual
+    pub fn pose(&self) -> CameraPose {
+        let rotation = Quat::from_euler(EulerRot::YXZ, self.yaw.to_radians(), -self.pitch.to_radians(), 0.0);
+        let position = self.config.target + rotation * Vec3::Z * self.distance;
+        CameraPose { position, rotation, fov: self.config.fov }
+    }
+
+    /// Mantener pitch y distancia dentro de sus límites
+    fn clamp(&mut self) {
+        let [min_pitch, max_pitch] = self.config.pitch_limits;
+        self.pitch = self.pitch.clamp(min_pitch.min(max_pitch), max_pitch.max(min_pitch));
+        let [min_distance, max_distance] = self.config.distance_limits;
+        self.distance = self.distance.clamp(min_distance.min(max_distance), max_distance.max(min_distance));
+        self.yaw = self.yaw.rem_euclid(360.0);
+    }
+}
+
+/// Configuración del controlador en primera persona
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirstPersonControllerConfig {
+    /// Entidad del avatar
+    pub avatar: EntityId,
+    /// Posición de la cabeza en el espacio local del avatar
+    pub head_offset: Vec3,
+    /// Límites de pitch [mínimo, máximo]
+    pub pitch_limits: [f32; 2],
+    /// Grados por píxel de ratón
+    pub sensitivity: f32,
+    /// Campo de visión
+    pub fov: f32,
+}
+
+/// Controlador en primera persona: la cámara va en la cabeza del avatar y el
+/// ratón gira la mirada respecto a la orientación del avatar
+#[derive(Debug, Clone)]
+pub struct FirstPersonController {
+    config: FirstPersonControllerConfig,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl FirstPersonController {
+    /// Crear controlador
+    pub fn new(config: FirstPersonControllerConfig) -> Self {
+        Self { config, yaw: 0.0, pitch: 0.0 }
+    }
+
+    /// Aplicar la entrada y devolver la pose. Sin transformación del avatar
+    /// la cabeza queda en el origen
+    pub fn update(&mut self, input: &CameraInput, avatar: Option<&TransformComponent>) -> CameraPose {
+        self.yaw = (self.yaw - input.look_delta.x * self.config.sensitivity).rem_euclid(360.0);
+        let [min_pitch, max_pitch] = self.config.pitch_limits;
+        self.pitch = (self.pitch - input.look_delta.y * self.config.sensitivity)
+            .clamp(min_pitch.min(max_pitch), max_pitch.max(min_pitch));
+
+        let (avatar_position, avatar_rotation) = avatar.map_or((Vec3::ZERO, Quat::IDENTITY), |t| (t.position, t.rotation));
+        // Solo el yaw del avatar orienta la mirada: inclinarse no la desvía
+        let (avatar_yaw, _, _) = avatar_rotation.to_euler(EulerRot::YXZ);
+        let body = Quat::from_rotation_y(avatar_yaw);
+        let rotation = body * Quat::from_euler(EulerRot::YXZ, self.yaw.to_radians(), self.pitch.to_radians(), 0.0);
+        CameraPose {
+            position: avatar_position + avatar_rotation * self.config.head_offset,
+            rotation,
+            fov: self.config.fov,
+        }
+    }
+
+    /// Entidad del avatar
+    pub fn avatar(&self) -> EntityId {
+        self.config.avatar
+    }
+}
+
+/// Configuración del controlador de seguimiento
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowControllerConfig {
+    /// Entidad seguida
+    pub target: EntityId,
+    /// Posición deseada de la cámara en el espacio local del objetivo
+    pub offset: Vec3,
+    /// Punto al que se mira, en el espacio local del objetivo
+    pub look_at_offset: Vec3,
+    /// Segundos en los que la cámara queda a menos del 2% de la posición deseada
+    pub settle_time: f32,
+    /// Campo de visión
+    pub fov: f32,
+}
+
+impl FollowControllerConfig {
+    /// Seguir a una entidad desde detrás y por encima
+    pub fn behind(target: EntityId) -> Self {
+        Self {
+            target,
+            offset: Vec3::new(0.0, 2.5, 6.0),
+            look_at_offset: Vec3::new(0.0, 1.5, 0.0),
+            settle_time: 0.5,
+            fov: 60.0,
+        }
+    }
+}
+
+/// Controlador de seguimiento: la cámara persigue una posición detrás del
+/// objetivo con un muelle críticamente amortiguado (sin oscilar)
+#[derive(Debug, Clone)]
+pub struct FollowController {
+    config: FollowControllerConfig,
+    /// Posición actual (None hasta el primer frame)
+    position: Option<Vec3>,
+    velocity: Vec3,
+}
+
+impl FollowController {
+    /// Crear controlador
+    pub fn new(config: FollowControllerConfig) -> Self {
+        Self { config, position: None, velocity: Vec3::ZERO }
+    }
+
+    /// Frecuencia angular del muelle para el tiempo de asentamiento configurado
+    pub fn angular_frequency(&self) -> f32 {
+        SPRING_SETTLE_FACTOR / self.config.settle_time.max(1e-3)
+    }
+
+    /// Empezar el seguimiento desde una pose (la de la cámara saliente)
+    pub fn start_from(&mut self, position: Vec3) {
+        self.position = Some(position);
+        self.velocity = Vec3::ZERO;
+    }
+
+    /// Avanzar el muelle y devolver la pose. Sin transformación del objetivo
+    /// se sigue al origen
+    pub fn update(&mut self, target: Option<&TransformComponent>, delta_time: f32) -> CameraPose {
+        let (target_position, target_rotation) = target.map_or((Vec3::ZERO, Quat::IDENTITY), |t| (t.position, t.rotation));
+        let desired = target_position + target_rotation * self.config.offset;
+        let look_at = target_position + target_rotation * self.config.look_at_offset;
+
+        let position = match self.position {
+            Some(position) => self.spring(position, desired, delta_time),
+            None => desired,
+        };
+        self.position = Some(position);
+        CameraPose::look_at(position, look_at, self.config.fov)
+    }
+
+    /// Paso exacto de un muelle críticamente amortiguado: estable con
+    /// cualquier delta time
+    fn spring(&mut self, position: Vec3, desired: Vec3, delta_time: f32) -> Vec3 {
+        let omega = self.angular_frequency();
+        let decay = (-omega * delta_time).exp();
+        let offset = position - desired;
+        let impulse = (self.velocity + offset * omega) * delta_time;
+        self.velocity = (self.velocity - impulse * omega) * decay;
+        desired + (offset + impulse) * decay
+    }
+
+    /// Entidad seguida
+    pub fn target(&self) -> EntityId {
+        self.config.target
+    }
+}
+
+/// Controlador de cámara
+#[derive(Debug, Clone)]
+pub enum CameraController {
+    /// Órbita alrededor de un punto
+    Orbit(OrbitController),
+    /// Cabeza de un avatar
+    FirstPerson(FirstPersonController),
+    /// Persecución amortiguada
+    Follow(FollowController),
+}
+
+impl CameraController {
+    /// Nombre del controlador
+    pub fn name(&self) -> &'static str {
+        match self {
+            CameraController::Orbit(_) => "orbit",
+            CameraController::FirstPerson(_) => "first_person",
+            CameraController::Follow(_) => "follow",
+        }
+    }
+
+    /// Pose del frame a partir de la entrada y del ECS
+    pub fn update(&mut self, input: &CameraInput, world: &ECSSystem, delta_time: f32) -> CameraPose {
+        let transform = |entity| world.get_component::<TransformComponent>(entity, ComponentType::Transform);
+        match self {
+            CameraController::Orbit(orbit) => orbit.update(input),
+            CameraController::FirstPerson(first_person) => {
+                let avatar = transform(first_person.avatar());
+                first_person.update(input, avatar.as_ref())
+            }
+            CameraController::Follow(follow) => {
+                let target = transform(follow.target());
+                follow.update(target.as_ref(), delta_time)
+            }
+        }
+    }
+}
+
+/// Transición entre la pose saliente y la del controlador entrante
+#[derive(Debug, Clone)]
+pub struct CameraBlend {
+    /// Pose de la cámara al empezar la transición
+    from: CameraPose,
+    /// Duración en segundos
+    duration: f32,
+    /// Tiempo transcurrido
+    elapsed: f32,
+}
+
+impl CameraBlend {
+    /// Crear transición desde `from`
+    pub fn new(from: CameraPose, duration: f32) -> Self {
+        Self { from, duration: duration.max(0.0), elapsed: 0.0 }
+    }
+
+    /// Avanzar y mezclar con la pose entrante. Con smoothstep el avance es
+    /// monótono y arranca y termina sin saltos de velocidad
+    pub fn advance(&mut self, incoming: &CameraPose, delta_time: f32) -> CameraPose {
+        self.elapsed += delta_time;
+        self.from.lerp(incoming, self.weight())
+    }
+
+    /// Peso de la pose entrante
+    pub fn weight(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        let t = (self.elapsed / self.duration).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    /// La transición terminó
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform_at(position: Vec3) -> TransformComponent {
+        TransformComponent {
+            position,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+            matrix: Mat4::from_translation(position),
+            parent: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn look(dy: f32) -> CameraInput {
+        CameraInput { look_delta: Vec2::new(0.0, dy), zoom_delta: 0.0 }
+    }
+
+    #[test]
+    fn pitch_stays_within_limits() {
+        let mut orbit = OrbitController::new(OrbitControllerConfig::default());
+        orbit.update(&look(10_000.0));
+        assert_eq!(orbit.pitch(), 80.0);
+        orbit.update(&look(-10_000.0));
+        assert_eq!(orbit.pitch(), -80.0);
+        // Desde el límite, la cámara sigue mirando al objetivo desde abajo
+        assert!(orbit.pose().position.y < 0.0);
+
+        let mut first_person = FirstPersonController::new(FirstPersonControllerConfig {
+            avatar: 1,
+            head_offset: Vec3::new(0.0, 1.7, 0.0),
+            pitch_limits: [-85.0, 85.0],
+            sensitivity: 0.2,
+            fov: 75.0,
+        });
+        for dy in [10_000.0, -10_000.0] {
+            let pose = first_person.update(&look(dy), None);
+            let (_, pitch, _) = pose.rotation.to_euler(EulerRot::YXZ);
+            assert!((pitch.to_degrees().abs() - 85.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn follow_spring_settles_without_overshoot() {
+        let mut follow = FollowController::new(FollowControllerConfig::behind(1));
+        let target = transform_at(Vec3::ZERO);
+        let desired = Vec3::new(0.0, 2.5, 6.0);
+        follow.start_from(Vec3::new(20.0, 2.5, 6.0));
+
+        let delta_time = 1.0 / 60.0;
+        let frames = (0.5 / delta_time).round() as usize;
+        let mut error = 20.0;
+        for frame in 1..=frames {
+            let pose = follow.update(Some(&target), delta_time);
+            let next_error = pose.position.distance(desired);
+            // Críticamente amortiguado: se acerca sin pasarse
+            assert!(next_error <= error, "el muelle se aleja en el frame {}", frame);
+            assert!(pose.position.x >= -1e-4, "el muelle oscila en el frame {}", frame);
+            if frame == frames / 2 {
+                assert!(next_error > 0.1 * 20.0);
+            }
+            error = next_error;
+        }
+        // En el tiempo de asentamiento queda a un 2% del desplazamiento inicial
+        assert!(error < 0.0205 * 20.0, "error {} tras el asentamiento", error);
+        let pose = follow.update(Some(&target), delta_time);
+        assert!(pose.position.distance(desired) < 0.02 * 20.0);
+    }
+
+    #[test]
+    fn blend_advances_monotonically() {
+        let from = CameraPose::look_at(Vec3::new(0.0, 5.0, 10.0), Vec3::ZERO, 60.0);
+        let to = CameraPose::look_at(Vec3::new(10.0, 2.0, 0.0), Vec3::ZERO, 90.0);
+        let mut blend = CameraBlend::new(from, 1.0);
+        assert_eq!(blend.weight(), 0.0);
+
+        let mut weight = 0.0;
+        let mut distance = from.position.distance(to.position);
+        let mut angle = from.rotation.angle_between(to.rotation);
+        while !blend.finished() {
+            let pose = blend.advance(&to, 0.05);
+            assert!(blend.weight() >= weight);
+            assert!(pose.position.distance(to.position) <= distance + 1e-5);
+            assert!(pose.rotation.angle_between(to.rotation) <= angle + 1e-4);
+            assert!(pose.fov >= from.fov && pose.fov <= to.fov);
+            weight = blend.weight();
+            distance = pose.position.distance(to.position);
+            angle = pose.rotation.angle_between(to.rotation);
+        }
+        assert_eq!(blend.weight(), 1.0);
+        assert!(distance < 1e-5);
+    }
+}
@@ -2,10 +2,336 @@
 //! 
 //! Sistema de gestión de cámaras 3D para el metaverso.
 //! Proporciona diferentes tipos de cámaras y controles.
+//! `CameraSystem` mueve la cámara activa del ECS con los controladores de
+//! `controllers`. Con el debug draw activo puede dibujar el frustum y los
+//! ejes de la pose del controlador. Las rutas cinemáticas de `path` toman
+//! la cámara mientras se reproducen. Con `CameraConfig::vr_mode` la pose
+//! coloca el espacio de tracking del casco y `xr` da una cámara por ojo.
 
+pub mod controllers;
+pub mod path;
+pub mod xr;
+
+use glam::{Mat4, Vec4};
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 use tracing::{info, debug};
 
+use crate::ecs::{CameraComponent, ComponentType, ECSSystem, EntityId};
+use crate::renderer::debug_draw::DebugDraw;
+use controllers::{CameraBlend, CameraController, CameraInput, CameraPose};
+use path::{CameraPath, CameraPlaybackHandle, PathPlayback};
+use xr::{VRPose, XrFrame};
+
+/// Distancia máxima a la que se dibuja el frustum de depuración
+const DEBUG_FRUSTUM_FAR: f32 = 50.0;
+
+/// Color del frustum de depuración
+const DEBUG_FRUSTUM_COLOR: Vec4 = Vec4::new(1.0, 0.85, 0.2, 1.0);
+
+/// Sistema de cámaras
+pub struct CameraSystem {
+    /// Configuración por defecto de las cámaras
+    config: CameraConfig,
+    /// Cámaras registradas
+    cameras: HashMap<String, Camera>,
+    /// Controlador de la cámara activa
+    controller: Option<CameraController>,
+    /// Transición en curso hacia el controlador actual
+    blend: Option<CameraBlend>,
+    /// Ruta cinemática en reproducción
+    path_playback: Option<PathPlayback>,
+    /// Entrada acumulada desde el último frame
+    input: CameraInput,
+    /// Última pose calculada
+    pose: Option<CameraPose>,
+    /// Transiciones completadas
+    completed_blends: u64,
+    /// Dibujar el frustum de la pose en el debug draw
+    debug_frustum: bool,
+    /// Configuración de VR
+    vr_config: xr::VRConfig,
+    /// Poses de VR del último frame
+    vr_pose: Option<VRPose>,
+    /// Estado del sistema
+    running: bool,
+}
+
+impl CameraSystem {
+    /// Crea el sistema de cámaras
+    pub fn new(config: &CameraConfig) -> Self {
+        info!("📷 Inicializando sistema de cámaras...");
+
+        Self {
+            config: config.clone(),
+            cameras: HashMap::new(),
+            controller: None,
+            blend: None,
+            path_playback: None,
+            input: CameraInput::default(),
+            pose: None,
+            completed_blends: 0,
+            debug_frustum: false,
+            vr_config: xr::VRConfig::default(),
+            vr_pose: None,
+            running: false,
+        }
+    }
+
+    /// Inicializa el sistema
+    pub async fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut camera = Camera::new("main", "Main Camera", CameraType::Perspective);
+        camera.config.fov = self.config.fov;
+        camera.config.aspect_ratio = self.config.aspect_ratio;
+        camera.config.near_plane = self.config.near_plane;
+        camera.config.far_plane = self.config.far_plane;
+        self.cameras.insert(camera.id.clone(), camera);
+        self.running = true;
+
+        info!("✅ Sistema de cámaras inicializado correctamente");
+        Ok(())
+    }
+
+    /// Actualiza las cámaras registradas
+    pub async fn update(&mut self, delta_time: f32) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.running {
+            return Ok(());
+        }
+
+        for camera in self.cameras.values_mut() {
+            camera.update(delta_time)?;
+        }
+
+        Ok(())
+    }
+
+    /// Avanza el controlador activo y la transición en curso con la entrada
+    /// acumulada. Devuelve la pose que debe tomar la cámara activa: la de la
+    /// ruta cinemática si hay una en reproducción
+    pub fn update_controller(&mut self, world: &ECSSystem, delta_time: f32) -> Option<CameraPose> {
+        if !self.running {
+            return None;
+        }
+        let input = std::mem::take(&mut self.input);
+
+        if let Some(playback) = &mut self.path_playback {
+            let (pose, finished) = playback.advance(delta_time);
+            // El controlador sigue a su objetivo sin entrada para que el
+            // relevo parta de una pose al día
+            if let Some(controller) = &mut self.controller {
+                controller.update(&CameraInput::default(), world, delta_time);
+            }
+            if finished {
+                let transition = playback.transition_duration();
+                self.path_playback = None;
+                self.blend = pose
+                    .filter(|_| transition > 0.0 && self.controller.is_some())
+                    .map(|pose| CameraBlend::new(pose, transition));
+            }
+            if let Some(pose) = pose {
+                self.pose = Some(pose);
+                return Some(pose);
+            }
+        }
+
+        let controller = self.controller.as_mut()?;
+        let incoming = controller.update(&input, world, delta_time);
+
+        let pose = match &mut self.blend {
+            Some(blend) => {
+                let pose = blend.advance(&incoming, delta_time);
+                if blend.finished() {
+                    self.blend = None;
+                    self.completed_blends += 1;
+                }
+                pose
+            }
+            None => incoming,
+        };
+        self.pose = Some(pose);
+        Some(pose)
+    }
+
+    /// Cambia el controlador de la cámara. Si ya había una pose, se mezcla
+    /// con la del nuevo controlador durante `blend_duration` segundos
+    pub fn set_controller(&mut self, mut controller: CameraController, blend_duration: f32) {
+        debug!("📷 Controlador de cámara: {}", controller.name());
+        if let (Some(pose), CameraController::Follow(follow)) = (&self.pose, &mut controller) {
+            follow.start_from(pose.position);
+        }
+        self.blend = self.pose
+            .filter(|_| blend_duration > 0.0)
+            .map(|pose| CameraBlend::new(pose, blend_duration));
+        self.controller = Some(controller);
+    }
+
+    /// Reproduce una ruta cinemática sobre la cámara activa, reemplazando
+    /// la que estuviera en curso. Al terminar, la cámara vuelve al
+    /// controlador mezclando durante `transition_duration_secs`
+    pub fn play_path(&mut self, path: &CameraPath) -> CameraPlaybackHandle {
+        debug!("🎬 Ruta de cámara: {} puntos, {:.1}s", path.control_points.len(), path.duration);
+        if let Some(previous) = self.path_playback.take() {
+            previous.stop();
+        }
+        let (playback, handle) = PathPlayback::start(path);
+        self.path_playback = Some(playback);
+        self.blend = None;
+        handle
+    }
+
+    /// Hay una ruta cinemática en reproducción
+    pub fn is_playing_path(&self) -> bool {
+        self.path_playback.is_some()
+    }
+
+    /// Quita el controlador: la cámara conserva su última pose
+    pub fn clear_controller(&mut self) {
+        self.controller = None;
+        self.blend = None;
+    }
+
+    /// Acumula la entrada del frame (la capa web puede enviarla varias veces)
+    pub fn push_input(&mut self, input: &CameraInput) {
+        self.input.look_delta += input.look_delta;
+        self.input.zoom_delta += input.zoom_delta;
+    }
+
+    /// Controlador actual
+    pub fn controller(&self) -> Option<&CameraController> {
+        self.controller.as_ref()
+    }
+
+    /// Última pose calculada
+    pub fn current_pose(&self) -> Option<CameraPose> {
+        self.pose
+    }
+
+    /// Hay una transición en curso
+    pub fn is_blending(&self) -> bool {
+        self.blend.is_some()
+    }
+
+    /// Dibujar en el debug draw el frustum de la pose del controlador
+    pub fn set_debug_draw(&mut self, frustum: bool) {
+        self.debug_frustum = frustum;
+    }
+
+    /// Emitir al debug draw el frustum (recortado a `DEBUG_FRUSTUM_FAR`) y
+    /// los ejes de la última pose
+    pub fn draw_debug(&self, draw: &mut DebugDraw) {
+        if !self.debug_frustum || !draw.is_enabled() {
+            return;
+        }
+        let Some(pose) = self.pose else {
+            return;
+        };
+        let near = self.config.near_plane.max(1e-3);
+        let far = self.config.far_plane.min(DEBUG_FRUSTUM_FAR).max(near * 2.0);
+        let projection = Mat4::perspective_rh(pose.fov.to_radians(), self.config.aspect_ratio.max(1e-3), near, far);
+        let transform = Mat4::from_rotation_translation(pose.rotation, pose.position);
+        draw.frustum(&(projection * transform.inverse()), DEBUG_FRUSTUM_COLOR);
+        draw.axes(&transform, near * 10.0);
+    }
+
+    /// Cambia la configuración de VR
+    pub fn set_vr_config(&mut self, config: xr::VRConfig) {
+        self.vr_config = config;
+    }
+
+    /// La cámara activa se dibuja en estéreo para un casco
+    pub fn is_vr_mode(&self) -> bool {
+        self.config.vr_mode
+    }
+
+    /// Cámaras de los dos ojos para un frame del runtime de XR. El espacio
+    /// de tracking se coloca en la última pose calculada (o en el origen).
+    /// Devuelve None fuera de `vr_mode`
+    pub fn update_vr(&mut self, frame: &XrFrame) -> Option<[CameraComponent; 2]> {
+        if !self.running || !self.config.vr_mode {
+            return None;
+        }
+        let rig = self.pose
+            .map_or(Mat4::IDENTITY, |pose| Mat4::from_rotation_translation(pose.rotation, pose.position));
+        let views = xr::adjust_views(&frame.views, &self.vr_config);
+        self.vr_pose = Some(xr::vr_pose(frame, rig));
+        Some(xr::eye_cameras(&views, rig, self.config.near_plane, self.config.far_plane))
+    }
+
+    /// Poses de la cabeza y los mandos del último frame de VR, en espacio
+    /// mundo
+    pub fn get_vr_pose(&self) -> Option<VRPose> {
+        self.vr_pose
+    }
+
+    /// Obtiene una cámara
+    pub fn get_camera(&self, id: &str) -> Option<&Camera> {
+        self.cameras.get(id)
+    }
+
+    /// Registra una cámara
+    pub fn add_camera(&mut self, camera: Camera) {
+        self.cameras.insert(camera.id.clone(), camera);
+    }
+
+    /// Limpia el sistema
+    pub async fn cleanup(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        info!("🧹 Limpiando sistema de cámaras...");
+
+        self.running = false;
+        self.cameras.clear();
+        self.controller = None;
+        self.blend = None;
+        if let Some(playback) = self.path_playback.take() {
+            playback.stop();
+        }
+        self.pose = None;
+        self.vr_pose = None;
+
+        info!("✅ Sistema de cámaras limpiado correctamente");
+        Ok(())
+    }
+
+    /// Obtiene el estado de salud del sistema
+    pub async fn health_check(&self) -> bool {
+        self.running
+    }
+
+    /// Obtiene estadísticas del sistema
+    pub fn get_stats(&self) -> CameraStats {
+        CameraStats {
+            camera_count: self.cameras.len(),
+            active_controller: self.controller.as_ref().map(|controller| controller.name().to_string()),
+            blending: self.blend.is_some(),
+            playing_path: self.path_playback.is_some(),
+            completed_blends: self.completed_blends,
+            vr_active: self.vr_pose.is_some(),
+        }
+    }
+}
+
+/// Cámara activa del ECS: la primera entidad con componente de cámara, la
+/// misma que usa el renderer
+pub fn active_camera_entity(world: &ECSSystem) -> Option<EntityId> {
+    world.get_entities_with_component(ComponentType::Camera).into_iter().next()
+}
+
+/// Estadísticas del sistema de cámaras
+#[derive(Debug, Clone)]
+pub struct CameraStats {
+    /// Cámaras registradas
+    pub camera_count: usize,
+    /// Controlador activo
+    pub active_controller: Option<String>,
+    /// Transición en curso
+    pub blending: bool,
+    /// Ruta cinemática en reproducción
+    pub playing_path: bool,
+    /// Transiciones completadas
+    pub completed_blends: u64,
+    /// Hay poses de VR del runtime
+    pub vr_active: bool,
+}
+
 /// Cámara principal del metaverso
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Camera {
@@ -67,6 +393,9 @@ pub struct CameraConfig {
     pub far_plane: f32,
     /// Configuración específica
     pub specific_config: CameraSpecificConfig,
+    /// Dibujar la cámara activa en estéreo para un casco de VR
+    #[serde(default)]
+    pub vr_mode: bool,
 }
 
 /// Configuración específica de cámara
@@ -447,6 +776,7 @@ impl Camera {
                     fov: 75.0,
                     aspect_ratio: 16.0 / 9.0,
                 }),
+                vr_mode: false,
             },
             CameraType::Orthographic => CameraConfig {
                 fov: 0.0,
@@ -457,6 +787,7 @@ impl Camera {
                     width: 10.0,
                     height: 10.0,
                 }),
+                vr_mode: false,
             },
             _ => CameraConfig {
                 fov: 75.0,
@@ -467,6 +798,7 @@ impl Camera {
                     fov: 75.0,
                     aspect_ratio: 16.0 / 9.0,
                 }),
+                vr_mode: false,
             },
         };
         
@@ -0,0 +1,282 @@
+//! # Rutas de cámara cinemáticas
+//!
+//! Recorridos de cámara definidos por puntos de control con marca de tiempo.
+//! La posición y el punto al que mira siguen cada uno una spline de
+//! Catmull-Rom que pasa por sus puntos; el FOV sigue una curva de Hermite
+//! con tangentes por diferencias centradas. Los extremos repiten el primer
+//! y el último punto, así que la ruta empieza y acaba exactamente en ellos.
+//!
+//! `CameraSystem::play_path` reproduce una ruta sobre la cámara activa y
+//! devuelve un `CameraPlaybackHandle` para pausarla, reanudarla o saltar a
+//! otro punto. Al terminar, la cámara vuelve al controlador con una
+//! transición de `transition_duration_secs`.
+
+use glam::Vec3;
+use serde::{Serialize, Deserialize};
+use std::sync::{Arc, Mutex};
+
+use super::controllers::CameraPose;
+
+/// Punto de control de una ruta
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraPathPoint {
+    /// Posición de la cámara
+    pub position: Vec3,
+    /// Punto al que mira
+    pub look_at: Vec3,
+    /// Campo de visión vertical en grados
+    pub fov: f32,
+    /// Segundos desde el inicio de la ruta
+    pub timestamp: f32,
+}
+
+/// Ruta de cámara cinemática
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraPath {
+    /// Puntos de control (se ordenan por `timestamp` al reproducir)
+    pub control_points: Vec<CameraPathPoint>,
+    /// Duración del recorrido en segundos
+    pub duration: f32,
+    /// Arrancar y frenar suavemente en lugar de a velocidad constante
+    pub ease_in_out: bool,
+    /// Segundos de transición de vuelta al controlador al terminar
+    #[serde(default = "default_transition_duration")]
+    pub transition_duration_secs: f32,
+}
+
+fn default_transition_duration() -> f32 {
+    1.0
+}
+
+impl CameraPath {
+    /// Ruta con los puntos repartidos uniformemente en `duration` segundos
+    /// (sustituye sus marcas de tiempo)
+    pub fn evenly_spaced(points: Vec<CameraPathPoint>, duration: f32) -> Self {
+        let last = points.len().saturating_sub(1).max(1) as f32;
+        let control_points = points.into_iter()
+            .enumerate()
+            .map(|(i, point)| CameraPathPoint { timestamp: duration * i as f32 / last, ..point })
+            .collect();
+        Self {
+            control_points,
+            duration,
+            ease_in_out: false,
+            transition_duration_secs: default_transition_duration(),
+        }
+    }
+
+    /// Pose en un punto del recorrido (0 = inicio, 1 = final). None sin
+    /// puntos de control
+    pub fn sample(&self, progress: f32) -> Option<CameraPose> {
+        let mut points = self.control_points.clone();
+        points.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+        let first = points.first()?;
+
+        let mut progress = progress.clamp(0.0, 1.0);
+        if self.ease_in_out {
+            progress = progress * progress * (3.0 - 2.0 * progress);
+        }
+        let time = progress * self.duration.max(0.0);
+
+        if points.len() == 1 {
+            return Some(CameraPose::look_at(first.position, first.look_at, first.fov));
+        }
+
+        // Segmento que contiene `time` y posición dentro de él
+        let segment = points.partition_point(|point| point.timestamp <= time).clamp(1, points.len() - 1) - 1;
+        let (a, b) = (&points[segment], &points[segment + 1]);
+        let span = b.timestamp - a.timestamp;
+        let u = if span > f32::EPSILON { ((time - a.timestamp) / span).clamp(0.0, 1.0) } else { 1.0 };
+
+        let before = &points[segment.saturating_sub(1)];
+        let after = &points[(segment + 2).min(points.len() - 1)];
+        let position = catmull_rom(before.position, a.position, b.position, after.position, u);
+        let look_at = catmull_rom(before.look_at, a.look_at, b.look_at, after.look_at, u);
+
+        // Tangentes del FOV por diferencias centradas, escaladas al segmento
+        let tangent = |previous: &CameraPathPoint, next: &CameraPathPoint| {
+            let dt = next.timestamp - previous.timestamp;
+            if dt > f32::EPSILON { (next.fov - previous.fov) / dt * span } else { 0.0 }
+        };
+        let fov = hermite(a.fov, tangent(before, b), b.fov, tangent(a, after), u);
+
+        Some(CameraPose::look_at(position, look_at, fov))
+    }
+}
+
+/// Spline de Catmull-Rom uniforme entre `p1` y `p2`
+pub fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * ((2.0 * p1)
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+/// Curva cúbica de Hermite entre `p0` y `p1` con tangentes `m0` y `m1`
+fn hermite(p0: f32, m0: f32, p1: f32, m1: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p0 + (t3 - 2.0 * t2 + t) * m0 + (-2.0 * t3 + 3.0 * t2) * p1 + (t3 - t2) * m1
+}
+
+/// Estado de una reproducción compartido con su handle
+#[derive(Debug, Default)]
+struct PlaybackControl {
+    paused: bool,
+    /// Salto pedido (progreso en [0, 1])
+    seek: Option<f32>,
+    /// Progreso del último frame
+    progress: f32,
+    finished: bool,
+}
+
+/// Control de la reproducción de una ruta
+#[derive(Debug, Clone)]
+pub struct CameraPlaybackHandle {
+    control: Arc<Mutex<PlaybackControl>>,
+}
+
+impl CameraPlaybackHandle {
+    /// Detener el avance de la ruta (la cámara se queda en la pose actual)
+    pub fn pause(&self) {
+        self.control.lock().unwrap().paused = true;
+    }
+
+    /// Reanudar el avance
+    pub fn resume(&self) {
+        self.control.lock().unwrap().paused = false;
+    }
+
+    /// Saltar a un punto del recorrido (0 = inicio, 1 = final). Se aplica en
+    /// el siguiente frame, también en pausa
+    pub fn seek(&self, t: f32) {
+        self.control.lock().unwrap().seek = Some(t.clamp(0.0, 1.0));
+    }
+
+    /// Progreso del último frame reproducido
+    pub fn progress(&self) -> f32 {
+        self.control.lock().unwrap().progress
+    }
+
+    /// La ruta está en pausa
+    pub fn is_paused(&self) -> bool {
+        self.control.lock().unwrap().paused
+    }
+
+    /// La ruta terminó o la reemplazó otra
+    pub fn is_finished(&self) -> bool {
+        self.control.lock().unwrap().finished
+    }
+}
+
+/// Ruta en reproducción
+#[derive(Debug)]
+pub struct PathPlayback {
+    path: CameraPath,
+    /// Segundos recorridos
+    elapsed: f32,
+    control: Arc<Mutex<PlaybackControl>>,
+}
+
+impl PathPlayback {
+    /// Empezar a reproducir una ruta
+    pub fn start(path: &CameraPath) -> (Self, CameraPlaybackHandle) {
+        let control = Arc::new(Mutex::new(PlaybackControl::default()));
+        let playback = Self { path: path.clone(), elapsed: 0.0, control: Arc::clone(&control) };
+        (playback, CameraPlaybackHandle { control })
+    }
+
+    /// Avanzar `delta_time` segundos (salvo en pausa) y devolver la pose del
+    /// frame y si la ruta ha terminado
+    pub fn advance(&mut self, delta_time: f32) -> (Option<CameraPose>, bool) {
+        let duration = self.path.duration.max(0.0);
+        let mut control = self.control.lock().unwrap();
+        if let Some(progress) = control.seek.take() {
+            self.elapsed = progress * duration;
+        } else if !control.paused {
+            self.elapsed += delta_time;
+        }
+        let progress = if duration > 0.0 { (self.elapsed / duration).min(1.0) } else { 1.0 };
+        control.progress = progress;
+        control.finished = progress >= 1.0 && !control.paused;
+        (self.path.sample(progress), control.finished)
+    }
+
+    /// Segundos de transición de vuelta al controlador
+    pub fn transition_duration(&self) -> f32 {
+        self.path.transition_duration_secs
+    }
+
+    /// Marcar la reproducción como terminada (al reemplazarla)
+    pub fn stop(&self) {
+        self.control.lock().unwrap().finished = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ruta de 3 puntos repartidos en 2 segundos
+    fn three_point_path() -> CameraPath {
+        let point = |position: Vec3, fov: f32| CameraPathPoint { position, look_at: Vec3::ZERO, fov, timestamp: 0.0 };
+        CameraPath::evenly_spaced(vec![
+            point(Vec3::new(0.0, 0.0, 0.0), 60.0),
+            point(Vec3::new(10.0, 5.0, 0.0), 90.0),
+            point(Vec3::new(20.0, 0.0, 10.0), 60.0),
+        ], 2.0)
+    }
+
+    fn assert_position(pose: Option<CameraPose>, expected: Vec3) {
+        let position = pose.expect("la ruta tiene puntos").position;
+        assert!(position.abs_diff_eq(expected, 1e-4), "{:?} != {:?}", position, expected);
+    }
+
+    #[test]
+    fn playing_a_3_point_path_passes_through_its_points() {
+        let (mut playback, handle) = PathPlayback::start(&three_point_path());
+
+        let (pose, finished) = playback.advance(0.0);
+        assert_position(pose, Vec3::new(0.0, 0.0, 0.0));
+        assert!(!finished);
+
+        let (pose, finished) = playback.advance(1.0);
+        assert_eq!(handle.progress(), 0.5);
+        assert_eq!(pose.unwrap().fov, 90.0);
+        assert_position(pose, Vec3::new(10.0, 5.0, 0.0));
+        assert!(!finished);
+
+        let (pose, finished) = playback.advance(1.0);
+        assert_position(pose, Vec3::new(20.0, 0.0, 10.0));
+        assert!(finished && handle.is_finished());
+    }
+
+    #[test]
+    fn positions_between_points_follow_catmull_rom() {
+        let (mut playback, handle) = PathPlayback::start(&three_point_path());
+
+        // catmull_rom(p0, p0, p1, p2, 0.5) = 0.5625·p1 - 0.0625·p2
+        handle.seek(0.25);
+        assert_position(playback.advance(0.0).0, Vec3::new(4.375, 2.8125, -0.625));
+
+        // catmull_rom(p0, p1, p2, p2, 0.5) = 0.5625·p1 + 0.5·p2
+        handle.seek(0.75);
+        assert_position(playback.advance(0.0).0, Vec3::new(15.625, 2.8125, 5.0));
+    }
+
+    #[test]
+    fn paused_playback_holds_its_pose() {
+        let (mut playback, handle) = PathPlayback::start(&three_point_path());
+        playback.advance(1.0);
+        handle.pause();
+
+        let (pose, finished) = playback.advance(5.0);
+        assert_position(pose, Vec3::new(10.0, 5.0, 0.0));
+        assert!(!finished);
+
+        handle.resume();
+        assert!(playback.advance(1.0).1);
+    }
+}
@@ -0,0 +1,188 @@
+//! # Cámaras estéreo para VR
+//!
+//! Con `CameraConfig::vr_mode` la cámara activa deja de ser un único punto
+//! de vista: en cada frame el runtime de XR (OpenXR) informa de la pose y el
+//! campo de visión de cada ojo y `CameraSystem::update_vr` construye dos
+//! `CameraComponent` con su matriz de vista y una proyección asimétrica.
+//!
+//! Las poses del runtime están en el espacio de tracking (el suelo de la
+//! habitación en `TrackingOrigin::Stage`, la cabeza al arrancar en
+//! `TrackingOrigin::Local`). Ese espacio se coloca en el mundo con la pose
+//! de la cámara activa, así que los controladores de cámara y las rutas
+//! siguen moviendo al jugador mientras la cabeza mira alrededor.
+
+use glam::{Mat4, Quat, Vec3, Vec4};
+use serde::{Serialize, Deserialize};
+
+use crate::ecs::{CameraComponent, CameraType};
+
+/// Espacio de referencia de las poses del runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TrackingOrigin {
+    /// Origen en la posición de la cabeza al iniciar la sesión (sentado)
+    Local,
+    /// Origen en el suelo del área de juego (de pie / room scale)
+    #[default]
+    Stage,
+}
+
+/// Configuración de VR del motor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VRConfig {
+    /// Distancia interpupilar en milímetros. 0 usa la que informa el runtime
+    #[serde(default)]
+    pub ipd_mm: f32,
+    /// Escala del campo de visión de cada ojo (1 = el del runtime)
+    #[serde(default = "default_fov_scale")]
+    pub fov_scale: f32,
+    /// Espacio de referencia del tracking
+    #[serde(default)]
+    pub tracking_origin: TrackingOrigin,
+}
+
+fn default_fov_scale() -> f32 {
+    1.0
+}
+
+impl Default for VRConfig {
+    fn default() -> Self {
+        Self {
+            ipd_mm: 0.0,
+            fov_scale: default_fov_scale(),
+            tracking_origin: TrackingOrigin::default(),
+        }
+    }
+}
+
+/// Campo de visión asimétrico de un ojo (ángulos en radianes respecto al eje
+/// de la vista; izquierda y abajo negativos), como `openxr::Fovf`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EyeFov {
+    pub angle_left: f32,
+    pub angle_right: f32,
+    pub angle_up: f32,
+    pub angle_down: f32,
+}
+
+/// Pose seguida por el runtime en el espacio de tracking
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackedPose {
+    /// Posición en metros
+    pub position: Vec3,
+    /// Orientación
+    pub orientation: Quat,
+}
+
+impl TrackedPose {
+    /// Pose en el origen del espacio de tracking
+    pub const IDENTITY: Self = Self { position: Vec3::ZERO, orientation: Quat::IDENTITY };
+
+    /// Matriz del espacio de la pose al de tracking
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.orientation, self.position)
+    }
+}
+
+/// Vista de un ojo, como `openxr::View`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EyeView {
+    /// Pose del ojo
+    pub pose: TrackedPose,
+    /// Campo de visión
+    pub fov: EyeFov,
+}
+
+/// Estado de un frame del runtime de XR
+#[derive(Debug, Clone)]
+pub struct XrFrame {
+    /// Instante previsto de presentación, en nanosegundos del runtime
+    pub predicted_display_time: i64,
+    /// El compositor mostrará el frame; si no, se cierra sin dibujar
+    pub should_render: bool,
+    /// Vistas del ojo izquierdo y derecho
+    pub views: [EyeView; 2],
+    /// Pose de la cabeza
+    pub head: TrackedPose,
+    /// Poses de los mandos izquierdo y derecho (None sin tracking)
+    pub controllers: [Option<TrackedPose>; 2],
+}
+
+/// Poses de VR del último frame, en espacio mundo
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VRPose {
+    /// Transformación de la cabeza
+    pub head: Mat4,
+    /// Transformación del mando izquierdo
+    pub left_controller: Option<Mat4>,
+    /// Transformación del mando derecho
+    pub right_controller: Option<Mat4>,
+}
+
+/// Proyección de un campo de visión asimétrico, con profundidad en [0, 1]
+/// como `Mat4::perspective_rh`
+pub fn asymmetric_projection(fov: &EyeFov, near: f32, far: f32) -> Mat4 {
+    let (left, right) = (fov.angle_left.tan(), fov.angle_right.tan());
+    let (up, down) = (fov.angle_up.tan(), fov.angle_down.tan());
+    let (width, height) = (right - left, up - down);
+    let depth = near - far;
+    Mat4::from_cols(
+        Vec4::new(2.0 / width, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, 2.0 / height, 0.0, 0.0),
+        Vec4::new((right + left) / width, (up + down) / height, far / depth, -1.0),
+        Vec4::new(0.0, 0.0, near * far / depth, 0.0),
+    )
+}
+
+/// Aplicar la configuración a las vistas del runtime: escala del FOV y, si
+/// hay una IPD fija, separar los ojos simétricamente respecto a su punto
+/// medio a lo largo de la línea que los une
+pub fn adjust_views(views: &[EyeView; 2], config: &VRConfig) -> [EyeView; 2] {
+    let mut views = *views;
+    let scale = config.fov_scale.max(0.01);
+    for view in &mut views {
+        let fov = &mut view.fov;
+        for angle in [&mut fov.angle_left, &mut fov.angle_right, &mut fov.angle_up, &mut fov.angle_down] {
+            *angle = (angle.tan() * scale).atan();
+        }
+    }
+
+    if config.ipd_mm > 0.0 {
+        let (left, right) = (views[0].pose.position, views[1].pose.position);
+        let center = (left + right) * 0.5;
+        let axis = (right - left).try_normalize()
+            .unwrap_or_else(|| views[0].pose.orientation * Vec3::X);
+        let half = axis * config.ipd_mm * 0.0005;
+        views[0].pose.position = center - half;
+        views[1].pose.position = center + half;
+    }
+    views
+}
+
+/// Cámaras de los dos ojos: `rig` lleva el espacio de tracking al mundo
+pub fn eye_cameras(views: &[EyeView; 2], rig: Mat4, near: f32, far: f32) -> [CameraComponent; 2] {
+    views.map(|view| {
+        let world = rig * view.pose.to_mat4();
+        let projection = asymmetric_projection(&view.fov, near, far);
+        let (left, right) = (view.fov.angle_left.tan(), view.fov.angle_right.tan());
+        let (up, down) = (view.fov.angle_up.tan(), view.fov.angle_down.tan());
+        CameraComponent {
+            camera_type: CameraType::Explicit,
+            fov: (view.fov.angle_up - view.fov.angle_down).to_degrees(),
+            aspect_ratio: (right - left) / (up - down),
+            near_plane: near,
+            far_plane: far,
+            projection,
+            view: world.inverse(),
+        }
+    })
+}
+
+/// Poses del frame llevadas al mundo con `rig`
+pub fn vr_pose(frame: &XrFrame, rig: Mat4) -> VRPose {
+    let [left, right] = frame.controllers.map(|pose| pose.map(|pose| rig * pose.to_mat4()));
+    VRPose {
+        head: rig * frame.head.to_mat4(),
+        left_controller: left,
+        right_controller: right,
+    }
+}
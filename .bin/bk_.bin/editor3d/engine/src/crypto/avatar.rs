@@ -0,0 +1,395 @@
+//! Avatares procedurales a partir del wallet
+//!
+//! `generate_avatar` deriva un avatar determinista de lo que un wallet tiene
+//! en cadena: el mismo wallet con los mismos NFTs da siempre el mismo avatar,
+//! sea cual sea el orden en que lleguen los NFTs.
+//!
+//! - Color de piel: hash Keccak-256 de la dirección y del balance de tokens
+//!   (número de NFTs), interpolado entre tonos de piel claros y oscuros.
+//! - Proporciones: longitud del nombre ENS (el NFT cuyo nombre acaba en
+//!   `.eth`). Los nombres cortos dan avatares bajos y anchos y los largos,
+//!   altos y estilizados; sin ENS las proporciones son las neutras.
+//! - Equipamiento: categoría de cada NFT (atributo `category` o `type`).
+//!   Cada categoría conocida ocupa un hueco y, si varios NFTs compiten por
+//!   el mismo, gana el más raro y después el de nombre menor.
+
+use glam::Vec3;
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Keccak256};
+
+use super::{NFTMetadata, RarityLevel};
+
+/// Longitud de nombre ENS con las proporciones más anchas
+const ENS_MIN_LENGTH: usize = 3;
+/// Longitud de nombre ENS con las proporciones más estilizadas
+const ENS_MAX_LENGTH: usize = 15;
+
+/// Tono de piel más claro (lineal)
+const SKIN_LIGHT: [f32; 3] = [0.93, 0.76, 0.64];
+/// Tono de piel más oscuro (lineal)
+const SKIN_DARK: [f32; 3] = [0.27, 0.16, 0.10];
+
+/// Hueco de equipamiento
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Head,
+    Torso,
+    Back,
+    LeftHand,
+    RightHand,
+    Feet,
+}
+
+impl EquipmentSlot {
+    /// Hueco de una categoría de NFT
+    pub fn from_category(category: &str) -> Option<Self> {
+        match category.trim().to_ascii_lowercase().as_str() {
+            "hat" | "helmet" | "headwear" | "mask" => Some(Self::Head),
+            "armor" | "armour" | "clothing" | "outfit" | "shirt" => Some(Self::Torso),
+            "wings" | "cape" | "backpack" => Some(Self::Back),
+            "shield" | "offhand" => Some(Self::LeftHand),
+            "weapon" | "sword" | "tool" | "staff" => Some(Self::RightHand),
+            "shoes" | "boots" | "footwear" => Some(Self::Feet),
+            _ => None,
+        }
+    }
+
+    /// Parte del cuerpo de la que cuelga el equipamiento
+    pub fn anchor(self) -> AvatarSlot {
+        match self {
+            Self::Head => AvatarSlot::Head,
+            Self::Torso | Self::Back | Self::Feet => AvatarSlot::Body,
+            Self::LeftHand => AvatarSlot::LeftArm,
+            Self::RightHand => AvatarSlot::RightArm,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Head => "head",
+            Self::Torso => "torso",
+            Self::Back => "back",
+            Self::LeftHand => "left_hand",
+            Self::RightHand => "right_hand",
+            Self::Feet => "feet",
+        }
+    }
+
+    /// Posición respecto a la parte de la que cuelga
+    fn offset(self, proportions: &AvatarProportions) -> Vec3 {
+        match self {
+            Self::Head => Vec3::new(0.0, 0.12, 0.0),
+            Self::Torso => Vec3::ZERO,
+            Self::Back => Vec3::new(0.0, 0.25 * proportions.height, -0.15 * proportions.width),
+            Self::LeftHand | Self::RightHand => Vec3::new(0.0, -0.6 * proportions.limb_length, 0.0),
+            Self::Feet => Vec3::new(0.0, -0.9 * proportions.height * proportions.limb_length, 0.0),
+        }
+    }
+}
+
+/// Parte de un avatar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AvatarSlot {
+    Body,
+    Head,
+    LeftArm,
+    RightArm,
+    LeftLeg,
+    RightLeg,
+    Equipment(EquipmentSlot),
+}
+
+impl AvatarSlot {
+    /// Parte de la que cuelga (None para el cuerpo, la raíz)
+    pub fn parent(self) -> Option<AvatarSlot> {
+        match self {
+            Self::Body => None,
+            Self::Equipment(slot) => Some(slot.anchor()),
+            _ => Some(Self::Body),
+        }
+    }
+}
+
+/// Proporciones del cuerpo (1 es el avatar neutro)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AvatarProportions {
+    /// Escala de la altura
+    pub height: f32,
+    /// Escala de la anchura de hombros y caderas
+    pub width: f32,
+    /// Escala de la longitud de brazos y piernas
+    pub limb_length: f32,
+}
+
+impl Default for AvatarProportions {
+    fn default() -> Self {
+        Self { height: 1.0, width: 1.0, limb_length: 1.0 }
+    }
+}
+
+/// Malla de una parte con su material y su transformación local
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AvatarPart {
+    /// Parte
+    pub slot: AvatarSlot,
+    /// Malla
+    pub mesh_id: String,
+    /// Material
+    pub material_id: String,
+    /// Posición respecto a la parte padre
+    pub offset: Vec3,
+    /// Escala
+    pub scale: Vec3,
+}
+
+/// Material propio de un avatar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AvatarMaterial {
+    /// ID del material
+    pub material_id: String,
+    /// Color base (lineal, con alpha)
+    pub base_color: [f32; 4],
+    /// Metallic
+    pub metallic: f32,
+    /// Roughness
+    pub roughness: f32,
+    /// Emisión
+    pub emissive: [f32; 3],
+}
+
+/// Avatar generado: mallas por parte y materiales que sobrescriben los de
+/// las mallas
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AvatarConfig {
+    /// Dirección del wallet (en minúsculas)
+    pub wallet_address: String,
+    /// Nombre ENS del que salen las proporciones
+    pub ens_name: Option<String>,
+    /// Proporciones del cuerpo
+    pub proportions: AvatarProportions,
+    /// Partes, con el cuerpo primero
+    pub parts: Vec<AvatarPart>,
+    /// Materiales de la piel y del equipamiento
+    pub material_overrides: Vec<AvatarMaterial>,
+}
+
+impl AvatarConfig {
+    /// Mallas de todas las partes
+    pub fn mesh_ids(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().map(|part| part.mesh_id.as_str())
+    }
+
+    /// Parte de un hueco
+    pub fn part(&self, slot: AvatarSlot) -> Option<&AvatarPart> {
+        self.parts.iter().find(|part| part.slot == slot)
+    }
+
+    /// Material propio por ID
+    pub fn material(&self, material_id: &str) -> Option<&AvatarMaterial> {
+        self.material_overrides.iter().find(|material| material.material_id == material_id)
+    }
+}
+
+/// Genera el avatar de un wallet a partir de los NFTs que tiene
+pub fn generate_avatar(wallet_address: &str, nfts: &[NFTMetadata]) -> AvatarConfig {
+    let address = wallet_address.trim().to_ascii_lowercase();
+    let ens_name = ens_name(nfts);
+    let proportions = ens_name.as_deref().map(ens_proportions).unwrap_or_default();
+
+    let skin_id = format!("avatar/{}/skin", address);
+    let skin = AvatarMaterial {
+        material_id: skin_id.clone(),
+        base_color: skin_color(&address, nfts.len() as u64),
+        metallic: 0.0,
+        roughness: 0.6,
+        emissive: [0.0; 3],
+    };
+
+    let AvatarProportions { height, width, limb_length } = proportions;
+    let body_part = |slot: AvatarSlot, mesh: &str, offset: Vec3, scale: Vec3| AvatarPart {
+        slot,
+        mesh_id: format!("avatar/{}", mesh),
+        material_id: skin_id.clone(),
+        offset,
+        scale,
+    };
+    // El origen del cuerpo está en las caderas
+    let mut parts = vec![
+        body_part(AvatarSlot::Body, "body", Vec3::ZERO, Vec3::new(width, height, width)),
+        body_part(AvatarSlot::Head, "head", Vec3::new(0.0, 0.65 * height, 0.0), Vec3::ONE),
+        body_part(AvatarSlot::LeftArm, "arm_left", Vec3::new(-0.22 * width, 0.5 * height, 0.0), Vec3::new(1.0, limb_length, 1.0)),
+        body_part(AvatarSlot::RightArm, "arm_right", Vec3::new(0.22 * width, 0.5 * height, 0.0), Vec3::new(1.0, limb_length, 1.0)),
+        body_part(AvatarSlot::LeftLeg, "leg_left", Vec3::new(-0.1 * width, 0.0, 0.0), Vec3::new(1.0, height * limb_length, 1.0)),
+        body_part(AvatarSlot::RightLeg, "leg_right", Vec3::new(0.1 * width, 0.0, 0.0), Vec3::new(1.0, height * limb_length, 1.0)),
+    ];
+    let mut material_overrides = vec![skin];
+
+    for (slot, nft) in equipment(nfts) {
+        let material_id = format!("avatar/{}/equipment/{}", address, slot.name());
+        parts.push(AvatarPart {
+            slot: AvatarSlot::Equipment(slot),
+            mesh_id: format!("avatar/equipment/{}", category(nft).unwrap_or_default().trim().to_ascii_lowercase()),
+            material_id: material_id.clone(),
+            offset: slot.offset(&proportions),
+            scale: Vec3::ONE,
+        });
+        material_overrides.push(equipment_material(material_id, nft));
+    }
+
+    AvatarConfig { wallet_address: address, ens_name, proportions, parts, material_overrides }
+}
+
+/// Nombre ENS entre los NFTs (el menor si hay varios)
+fn ens_name(nfts: &[NFTMetadata]) -> Option<String> {
+    nfts.iter()
+        .map(|nft| nft.name.trim().to_ascii_lowercase())
+        .filter(|name| name.len() > ".eth".len() && name.ends_with(".eth"))
+        .min()
+}
+
+/// Proporciones según la longitud de la primera etiqueta del nombre ENS
+fn ens_proportions(name: &str) -> AvatarProportions {
+    let label = name.split('.').next().unwrap_or_default().chars().count();
+    let t = (label.clamp(ENS_MIN_LENGTH, ENS_MAX_LENGTH) - ENS_MIN_LENGTH) as f32
+        / (ENS_MAX_LENGTH - ENS_MIN_LENGTH) as f32;
+    AvatarProportions {
+        height: 0.9 + 0.2 * t,
+        width: 1.12 - 0.24 * t,
+        limb_length: 0.95 + 0.1 * t,
+    }
+}
+
+/// Color de piel del hash de la dirección y el balance
+fn skin_color(address: &str, balance: u64) -> [f32; 4] {
+    let mut hasher = Keccak256::new();
+    hasher.update(address.as_bytes());
+    hasher.update(balance.to_be_bytes());
+    let hash = hasher.finalize();
+
+    let t = hash[0] as f32 / 255.0;
+    // Variación leve de matiz: más rojizo o más amarillento
+    let warmth = (hash[1] as f32 / 255.0 - 0.5) * 0.06;
+    let channel = |index: usize| SKIN_LIGHT[index] + (SKIN_DARK[index] - SKIN_LIGHT[index]) * t;
+    [
+        (channel(0) + warmth).clamp(0.0, 1.0),
+        channel(1),
+        (channel(2) - warmth).clamp(0.0, 1.0),
+        1.0,
+    ]
+}
+
+/// Categoría de un NFT
+fn category(nft: &NFTMetadata) -> Option<&str> {
+    ["category", "type"].iter().find_map(|key| {
+        nft.attributes.iter()
+            .find(|attribute| attribute.trait_type.eq_ignore_ascii_case(key))
+            .map(|attribute| attribute.value.as_str())
+    })
+}
+
+/// Rareza de un NFT (0 común, 4 legendario)
+fn rarity_rank(nft: &NFTMetadata) -> u8 {
+    nft.attributes.iter()
+        .filter_map(|attribute| attribute.rarity_config.as_ref())
+        .map(|rarity| match rarity.rarity_level {
+            RarityLevel::Common | RarityLevel::Custom(_) => 0,
+            RarityLevel::Uncommon => 1,
+            RarityLevel::Rare => 2,
+            RarityLevel::Epic => 3,
+            RarityLevel::Legendary => 4,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Un NFT por hueco: el más raro y, a igual rareza, el de nombre menor
+fn equipment(nfts: &[NFTMetadata]) -> Vec<(EquipmentSlot, &NFTMetadata)> {
+    let mut candidates: Vec<(EquipmentSlot, &NFTMetadata)> = nfts.iter()
+        .filter_map(|nft| Some((EquipmentSlot::from_category(category(nft)?)?, nft)))
+        .collect();
+    candidates.sort_by(|(slot_a, a), (slot_b, b)| {
+        slot_a.cmp(slot_b)
+            .then(rarity_rank(b).cmp(&rarity_rank(a)))
+            .then(a.name.cmp(&b.name))
+    });
+    candidates.dedup_by_key(|(slot, _)| *slot);
+    candidates
+}
+
+/// Material del equipamiento: color del hash del nombre del NFT; los más
+/// raros son más metálicos y los legendarios brillan
+fn equipment_material(material_id: String, nft: &NFTMetadata) -> AvatarMaterial {
+    let hash = Keccak256::digest(nft.name.as_bytes());
+    let rank = rarity_rank(nft) as f32 / 4.0;
+    let base_color = [hash[0] as f32 / 255.0, hash[1] as f32 / 255.0, hash[2] as f32 / 255.0, 1.0];
+    let glow = if rank >= 1.0 { 0.3 } else { 0.0 };
+    AvatarMaterial {
+        material_id,
+        base_color,
+        metallic: 0.8 * rank,
+        roughness: 0.7 - 0.45 * rank,
+        emissive: [base_color[0] * glow, base_color[1] * glow, base_color[2] * glow],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{NFTAttribute, RarityConfig};
+
+    const WALLET: &str = "0x52908400098527886E0F7030069857D2E4169EE7";
+
+    fn nft(name: &str, category: Option<&str>, rarity: RarityLevel) -> NFTMetadata {
+        NFTMetadata {
+            name: name.to_string(),
+            description: String::new(),
+            image: String::new(),
+            attributes: category.into_iter()
+                .map(|category| NFTAttribute {
+                    trait_type: "category".to_string(),
+                    value: category.to_string(),
+                    rarity_config: Some(RarityConfig { rarity_level: rarity.clone(), probability: 0.1 }),
+                })
+                .collect(),
+            animation_config: None,
+        }
+    }
+
+    /// ENS, un sombrero, dos espadas que compiten por la mano derecha y un
+    /// NFT sin categoría conocida
+    fn holdings() -> Vec<NFTMetadata> {
+        vec![
+            nft("islander.eth", None, RarityLevel::Common),
+            nft("Straw Hat", Some("hat"), RarityLevel::Rare),
+            nft("Wooden Sword", Some("sword"), RarityLevel::Common),
+            nft("Sunblade", Some("weapon"), RarityLevel::Legendary),
+            nft("Postcard", Some("collectible"), RarityLevel::Epic),
+        ]
+    }
+
+    #[test]
+    fn same_wallet_produces_the_same_avatar_across_calls() {
+        let nfts = holdings();
+        let avatar = generate_avatar(WALLET, &nfts);
+        for _ in 0..3 {
+            assert_eq!(generate_avatar(WALLET, &nfts), avatar);
+        }
+
+        // Ni el orden de los NFTs ni las mayúsculas de la dirección cuentan
+        let reversed: Vec<NFTMetadata> = nfts.iter().rev().cloned().collect();
+        assert_eq!(generate_avatar(WALLET, &reversed), avatar);
+        assert_eq!(generate_avatar(&format!("  {}  ", WALLET.to_ascii_lowercase()), &nfts), avatar);
+
+        assert_eq!(avatar.wallet_address, WALLET.to_ascii_lowercase());
+        assert_eq!(avatar.ens_name.as_deref(), Some("islander.eth"));
+        assert_eq!(avatar.part(AvatarSlot::Equipment(EquipmentSlot::Head)).unwrap().mesh_id, "avatar/equipment/hat");
+        assert_eq!(avatar.part(AvatarSlot::Equipment(EquipmentSlot::RightHand)).unwrap().mesh_id, "avatar/equipment/weapon");
+        assert_eq!(avatar.parts.len(), 8);
+        assert!(avatar.parts.iter().all(|part| avatar.material(&part.material_id).is_some()));
+
+        // Otro wallet tiene su propio avatar
+        let other = generate_avatar("0x0000000000000000000000000000000000000001", &nfts);
+        assert_ne!(other.wallet_address, avatar.wallet_address);
+        assert_ne!(other.material_overrides[0].material_id, avatar.material_overrides[0].material_id);
+    }
+}
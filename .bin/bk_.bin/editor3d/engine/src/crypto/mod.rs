@@ -3,10 +3,27 @@
 //! Sistema de gestión de criptografía y blockchain para el metaverso.
 //! Proporciona verificación de transacciones, NFTs y smart contracts.
 
+pub mod avatar;
+pub mod trie;
+pub mod wallet;
+pub mod zk;
+
 use serde::{Serialize, Deserialize};
 use tracing::{info, debug};
 use std::collections::HashMap;
 
+pub use avatar::{generate_avatar, AvatarConfig};
+pub use wallet::{EIP712Domain, UnsignedTransaction, SignedTransaction};
+pub use trie::{MerkleProof, MerkleTrie};
+pub use zk::{OwnershipProof, OwnershipTree};
+
+/// Identificador de wallet importado (su dirección con checksum)
+pub type WalletId = String;
+
+/// Trie del estado del mundo: ID de entidad en big endian a su estado
+/// serializado por `ECSSystem::serialize`
+pub type StateTrie = MerkleTrie<[u8; 8], Vec<u8>>;
+
 /// Sistema de crypto principal
 pub struct CryptoSystem {
     /// Configuración del sistema
@@ -17,6 +34,16 @@ pub struct CryptoSystem {
     nfts: HashMap<String, NFT>,
     /// Smart contracts
     contracts: HashMap<String, SmartContract>,
+    /// Wallets HD importados (solo en memoria)
+    hd_wallets: HashMap<WalletId, wallet::Wallet>,
+    /// Árbol de commitments de propietarios privados
+    ownership_tree: zk::OwnershipTree,
+    /// Parámetros Groth16 de las pruebas de propiedad
+    ownership_keys: Option<zk::OwnershipKeys>,
+    /// Commitments verificados por identificador de prueba
+    verified_commitments: HashMap<String, [u8; 32]>,
+    /// Trie del estado del mundo del último tick
+    state_trie: StateTrie,
     /// Estado del sistema
     running: bool,
 }
@@ -765,6 +792,11 @@ impl CryptoSystem {
             transactions: HashMap::new(),
             nfts: HashMap::new(),
             contracts: HashMap::new(),
+            hd_wallets: HashMap::new(),
+            ownership_tree: zk::OwnershipTree::new(),
+            ownership_keys: None,
+            verified_commitments: HashMap::new(),
+            state_trie: StateTrie::new(),
             running: false,
         }
     }
@@ -816,6 +848,10 @@ impl CryptoSystem {
         self.transactions.clear();
         self.nfts.clear();
         self.contracts.clear();
+        self.hd_wallets.clear();
+        self.ownership_tree = zk::OwnershipTree::new();
+        self.verified_commitments.clear();
+        self.state_trie = StateTrie::new();
         
         info!("✅ Sistema de crypto limpiado correctamente");
         Ok(())
@@ -923,6 +959,115 @@ impl CryptoSystem {
         self.contracts.get(id)
     }
 
+    /// Importa un wallet desde una frase mnemónica BIP-39
+    pub fn import_wallet(&mut self, phrase: &str) -> Result<WalletId, Box<dyn std::error::Error>> {
+        let wallet = wallet::Wallet::from_mnemonic(phrase, "", wallet::DEFAULT_DERIVATION_PATH)?;
+        let wallet_id = wallet.address();
+        self.hd_wallets.insert(wallet_id.clone(), wallet);
+        
+        debug!("🔑 Wallet importado: {}", wallet_id);
+        Ok(wallet_id)
+    }
+
+    /// Obtiene un wallet importado
+    pub fn get_wallet(&self, wallet_id: &str) -> Option<&wallet::Wallet> {
+        self.hd_wallets.get(wallet_id)
+    }
+
+    /// Firma una transacción con un wallet importado
+    pub fn sign_transaction(&self, wallet_id: &str, tx: &UnsignedTransaction) -> Result<SignedTransaction, Box<dyn std::error::Error>> {
+        let wallet = self.hd_wallets.get(wallet_id)
+            .ok_or_else(|| format!("Wallet no encontrado: {}", wallet_id))?;
+        let signed = wallet.sign_transaction(tx)?;
+        
+        debug!("✍️ Transacción firmada: {}", signed.hash);
+        Ok(signed)
+    }
+
+    /// Genera los parámetros de las pruebas de propiedad (o los carga si se pasan)
+    pub fn setup_ownership_keys(&mut self, params: Option<&[u8]>) -> Result<(), Box<dyn std::error::Error>> {
+        let keys = match params {
+            Some(bytes) => zk::OwnershipKeys::read(bytes)?,
+            None => zk::OwnershipKeys::setup()?,
+        };
+        self.ownership_keys = Some(keys);
+        Ok(())
+    }
+
+    /// Clave de verificación serializada de las pruebas de propiedad
+    pub fn ownership_verifying_key(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let keys = self.ownership_keys.as_ref().ok_or("Claves de propiedad no inicializadas")?;
+        Ok(keys.verifying_key()?)
+    }
+
+    /// Registra el commitment del propietario privado de un NFT
+    pub fn register_private_owner(&mut self, nft_id: &str, commitment: [u8; 32]) -> Result<(), Box<dyn std::error::Error>> {
+        let position = self.ownership_tree.insert(nft_id, commitment)?;
+        
+        debug!("🕶️ Propietario privado registrado: {} (hoja {})", nft_id, position);
+        Ok(())
+    }
+
+    /// Raíz actual del árbol de propiedad
+    pub fn ownership_root(&self) -> [u8; 32] {
+        self.ownership_tree.root()
+    }
+
+    /// Genera una prueba de propiedad sin revelar la dirección del propietario
+    pub fn generate_ownership_proof(&self, nft_id: &str, owner_secret: &[u8; 32]) -> Result<OwnershipProof, Box<dyn std::error::Error>> {
+        let keys = self.ownership_keys.as_ref().ok_or("Claves de propiedad no inicializadas")?;
+        Ok(keys.generate_ownership_proof(&self.ownership_tree, nft_id, owner_secret)?)
+    }
+
+    /// Verifica una prueba de propiedad. Devuelve el identificador seudónimo
+    /// con el que queda guardado el commitment verificado
+    pub fn verify_ownership_proof(&mut self, proof: &OwnershipProof, commitment: [u8; 32], merkle_root: [u8; 32]) -> Option<String> {
+        let verifier = self.ownership_keys.as_ref()?.verifier();
+        if !verifier.verify_ownership_proof(proof, commitment, merkle_root) {
+            debug!("❌ Prueba de propiedad inválida para {}", proof.nft_id);
+            return None;
+        }
+        let proof_id = proof.proof_id();
+        self.verified_commitments.insert(proof_id.clone(), commitment);
+        
+        debug!("✅ Propiedad verificada: {} ({})", proof.nft_id, proof_id);
+        Some(proof_id)
+    }
+
+    /// Commitment asociado a una prueba verificada
+    pub fn verified_commitment(&self, proof_id: &str) -> Option<[u8; 32]> {
+        self.verified_commitments.get(proof_id).copied()
+    }
+
+    /// Reconstruye el trie de estado con el estado de cada entidad y
+    /// devuelve su raíz
+    pub fn update_state_trie(&mut self, states: Vec<(u64, Vec<u8>)>) -> [u8; 32] {
+        let mut trie = StateTrie::new();
+        for (entity_id, state) in states {
+            trie.insert(entity_id.to_be_bytes(), &state);
+        }
+        self.state_trie = trie;
+        self.state_trie.root_hash()
+    }
+
+    /// Raíz del trie de estado
+    pub fn state_root(&self) -> [u8; 32] {
+        self.state_trie.root_hash()
+    }
+
+    /// Estado de una entidad con su prueba contra `state_root`
+    pub fn state_proof(&self, entity_id: u64) -> Option<(Vec<u8>, MerkleProof)> {
+        let key = entity_id.to_be_bytes();
+        let state = self.state_trie.get(&key)?;
+        Some((state, self.state_trie.generate_proof(&key)))
+    }
+
+    /// Verifica el estado de una entidad recibido de otro peer contra la
+    /// raíz que publicó
+    pub fn verify_state_proof(root: [u8; 32], entity_id: u64, state: &[u8], proof: &MerkleProof) -> bool {
+        StateTrie::verify_proof(root, &entity_id.to_be_bytes(), &state.to_vec(), proof)
+    }
+
     /// Obtiene el estado de salud del sistema
     pub async fn health_check(&self) -> bool {
         self.running
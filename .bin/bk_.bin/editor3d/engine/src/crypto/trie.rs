@@ -0,0 +1,471 @@
+//! # Merkle Patricia Trie
+//!
+//! Trie de Merkle-Patricia con el formato de Ethereum para probar el estado
+//! de una entidad a otro peer sin enviarle el mundo: el peer conoce la raíz
+//! publicada y recibe solo los nodos del camino hasta la hoja.
+//!
+//! Las claves se recorren por el Keccak-256 de la clave (trie "seguro"), así
+//! que todos los caminos tienen 64 nibbles y el trie queda equilibrado. Los
+//! valores se guardan serializados con bincode. Cada nodo se codifica en
+//! RLP (hoja y extensión como `[hex-prefix(camino), valor | hijo]`, rama
+//! como `[hijo × 16, valor]`) y se referencia por el Keccak-256 de su
+//! codificación, o en línea si ocupa menos de 32 bytes. La codificación de
+//! cada nodo se guarda al calcularla y solo se rehace en el camino de las
+//! inserciones.
+
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use sha3::{Digest, Keccak256};
+
+use super::wallet::{rlp_bytes, rlp_list};
+
+/// Prueba de inclusión: codificación RLP de los nodos referenciados por
+/// hash desde la raíz hasta la hoja
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub nodes: Vec<Vec<u8>>,
+}
+
+impl MerkleProof {
+    /// Bytes de la prueba
+    pub fn size(&self) -> usize {
+        self.nodes.iter().map(Vec::len).sum()
+    }
+}
+
+/// Nodo del trie
+#[derive(Debug, Default)]
+enum NodeKind {
+    #[default]
+    Empty,
+    Leaf { path: Vec<u8>, value: Vec<u8> },
+    Extension { path: Vec<u8>, child: Box<Node> },
+    Branch { children: Box<[Node; 16]>, value: Option<Vec<u8>> },
+}
+
+/// Nodo con su codificación RLP calculada
+#[derive(Debug, Default)]
+struct Node {
+    kind: NodeKind,
+    encoded: OnceLock<Vec<u8>>,
+}
+
+impl Node {
+    fn new(kind: NodeKind) -> Self {
+        Self { kind, encoded: OnceLock::new() }
+    }
+
+    fn leaf(path: &[u8], value: Vec<u8>) -> Self {
+        Self::new(NodeKind::Leaf { path: path.to_vec(), value })
+    }
+
+    /// Codificación RLP del nodo
+    fn encode(&self) -> &[u8] {
+        self.encoded.get_or_init(|| match &self.kind {
+            NodeKind::Empty => rlp_bytes(&[]),
+            NodeKind::Leaf { path, value } => rlp_list(&[rlp_bytes(&hex_prefix(path, true)), rlp_bytes(value)]),
+            NodeKind::Extension { path, child } => rlp_list(&[rlp_bytes(&hex_prefix(path, false)), child.reference()]),
+            NodeKind::Branch { children, value } => {
+                let mut items: Vec<Vec<u8>> = children.iter().map(Node::reference).collect();
+                items.push(rlp_bytes(value.as_deref().unwrap_or(&[])));
+                rlp_list(&items)
+            }
+        })
+    }
+
+    /// Referencia desde el padre: la codificación en línea si ocupa menos
+    /// de 32 bytes y su hash si no
+    fn reference(&self) -> Vec<u8> {
+        let encoded = self.encode();
+        if encoded.len() < 32 {
+            encoded.to_vec()
+        } else {
+            rlp_bytes(&Keccak256::digest(encoded))
+        }
+    }
+
+    /// Insertar un valor bajo un camino de nibbles
+    fn insert(self, path: &[u8], value: Vec<u8>) -> Node {
+        match self.kind {
+            NodeKind::Empty => Node::leaf(path, value),
+            NodeKind::Leaf { path: leaf_path, value: leaf_value } => {
+                if leaf_path == path {
+                    return Node::leaf(path, value);
+                }
+                let common = common_prefix(&leaf_path, path);
+                let mut children: Box<[Node; 16]> = Box::default();
+                let mut branch_value = None;
+                for (rest, value) in [(&leaf_path[common..], leaf_value), (&path[common..], value)] {
+                    match rest.split_first() {
+                        Some((nibble, rest)) => children[*nibble as usize] = Node::leaf(rest, value),
+                        None => branch_value = Some(value),
+                    }
+                }
+                wrap_extension(&path[..common], Node::new(NodeKind::Branch { children, value: branch_value }))
+            }
+            NodeKind::Extension { path: extension_path, child } => {
+                let common = common_prefix(&extension_path, path);
+                if common == extension_path.len() {
+                    let child = child.insert(&path[common..], value);
+                    return Node::new(NodeKind::Extension { path: extension_path, child: Box::new(child) });
+                }
+                let mut children: Box<[Node; 16]> = Box::default();
+                let mut branch_value = None;
+                let nibble = extension_path[common] as usize;
+                children[nibble] = wrap_extension(&extension_path[common + 1..], *child);
+                match path[common..].split_first() {
+                    Some((nibble, rest)) => children[*nibble as usize] = Node::leaf(rest, value),
+                    None => branch_value = Some(value),
+                }
+                wrap_extension(&path[..common], Node::new(NodeKind::Branch { children, value: branch_value }))
+            }
+            NodeKind::Branch { mut children, value: branch_value } => match path.split_first() {
+                Some((nibble, rest)) => {
+                    let child = std::mem::take(&mut children[*nibble as usize]);
+                    children[*nibble as usize] = child.insert(rest, value);
+                    Node::new(NodeKind::Branch { children, value: branch_value })
+                }
+                None => Node::new(NodeKind::Branch { children, value: Some(value) }),
+            },
+        }
+    }
+
+    /// Valor bajo un camino, con los nodos referenciados por hash del
+    /// recorrido si se pide la prueba
+    fn lookup(&self, path: &[u8], mut proof: Option<&mut Vec<Vec<u8>>>) -> Option<&[u8]> {
+        if let Some(proof) = proof.as_deref_mut() {
+            if self.encode().len() >= 32 || proof.is_empty() {
+                proof.push(self.encode().to_vec());
+            }
+        }
+        match &self.kind {
+            NodeKind::Empty => None,
+            NodeKind::Leaf { path: leaf_path, value } => (leaf_path.as_slice() == path).then_some(value.as_slice()),
+            NodeKind::Extension { path: extension_path, child } => {
+                let rest = path.strip_prefix(extension_path.as_slice())?;
+                child.lookup(rest, proof)
+            }
+            NodeKind::Branch { children, value } => match path.split_first() {
+                Some((nibble, rest)) => children[*nibble as usize].lookup(rest, proof),
+                None => value.as_deref(),
+            },
+        }
+    }
+}
+
+/// Poner una extensión delante de un nodo si el camino no está vacío. Dos
+/// extensiones seguidas se juntan y una hoja absorbe el camino
+fn wrap_extension(path: &[u8], node: Node) -> Node {
+    if path.is_empty() {
+        return node;
+    }
+    match node.kind {
+        NodeKind::Leaf { path: rest, value } => Node::leaf(&[path, &rest].concat(), value),
+        NodeKind::Extension { path: rest, child } => Node::new(NodeKind::Extension { path: [path, &rest].concat(), child }),
+        kind => Node::new(NodeKind::Extension { path: path.to_vec(), child: Box::new(Node::new(kind)) }),
+    }
+}
+
+/// Trie de Merkle-Patricia de valores serializables
+pub struct MerkleTrie<K, V> {
+    root: Node,
+    len: usize,
+    _marker: PhantomData<fn(K, V)>,
+}
+
+impl<K, V> Default for MerkleTrie<K, V> {
+    fn default() -> Self {
+        Self { root: Node::default(), len: 0, _marker: PhantomData }
+    }
+}
+
+impl<K: AsRef<[u8]>, V: Serialize + DeserializeOwned> MerkleTrie<K, V> {
+    /// Trie vacío
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Número de claves
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Sin claves
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Insertar o reemplazar el valor de una clave
+    pub fn insert(&mut self, key: K, value: &V) {
+        let path = key_path(key.as_ref());
+        let encoded = bincode::serialize(value).expect("valor serializable");
+        if self.root.lookup(&path, None).is_none() {
+            self.len += 1;
+        }
+        self.root = std::mem::take(&mut self.root).insert(&path, encoded);
+    }
+
+    /// Valor de una clave
+    pub fn get(&self, key: &K) -> Option<V> {
+        let encoded = self.root.lookup(&key_path(key.as_ref()), None)?;
+        bincode::deserialize(encoded).ok()
+    }
+
+    /// Raíz del trie (Keccak-256 de la codificación del nodo raíz)
+    pub fn root_hash(&self) -> [u8; 32] {
+        Keccak256::digest(self.root.encode()).into()
+    }
+
+    /// Prueba de la clave: los nodos del camino desde la raíz. Para una
+    /// clave ausente los nodos prueban su ausencia pero no verifican
+    /// ningún valor
+    pub fn generate_proof(&self, key: &K) -> MerkleProof {
+        let mut nodes = Vec::new();
+        self.root.lookup(&key_path(key.as_ref()), Some(&mut nodes));
+        MerkleProof { nodes }
+    }
+
+    /// Comprobar que `value` es el valor de `key` en el trie de raíz `root`
+    pub fn verify_proof(root: [u8; 32], key: &K, value: &V, proof: &MerkleProof) -> bool {
+        let Ok(expected) = bincode::serialize(value) else {
+            return false;
+        };
+        verify_path(root, &key_path(key.as_ref()), proof) == Some(expected)
+    }
+}
+
+/// Recorrer una prueba desde la raíz y devolver el valor al final del camino
+fn verify_path(root: [u8; 32], path: &[u8], proof: &MerkleProof) -> Option<Vec<u8>> {
+    let mut nodes = proof.nodes.iter();
+    let mut node = {
+        let encoded = nodes.next()?;
+        if <[u8; 32]>::from(Keccak256::digest(encoded)) != root {
+            return None;
+        }
+        rlp::decode(encoded)?
+    };
+    let mut path = path;
+    loop {
+        let items = node.list()?;
+        let child = match items.len() {
+            2 => {
+                let (node_path, leaf) = decode_hex_prefix(items[0].bytes()?)?;
+                path = path.strip_prefix(node_path.as_slice())?;
+                if leaf {
+                    return path.is_empty().then(|| items[1].bytes().map(<[u8]>::to_vec))?;
+                }
+                &items[1]
+            }
+            17 => match path.split_first() {
+                Some((nibble, rest)) => {
+                    path = rest;
+                    &items[*nibble as usize]
+                }
+                None => {
+                    let value = items[16].bytes()?;
+                    return (!value.is_empty()).then(|| value.to_vec());
+                }
+            },
+            _ => return None,
+        };
+
+        // Hijo en línea o por hash (el siguiente nodo de la prueba)
+        node = match child {
+            rlp::Item::List(_) => child.clone(),
+            rlp::Item::Bytes(hash) if hash.len() == 32 => {
+                let encoded = nodes.next()?;
+                if Keccak256::digest(encoded).as_slice() != *hash {
+                    return None;
+                }
+                rlp::decode(encoded)?
+            }
+            rlp::Item::Bytes(_) => return None,
+        };
+    }
+}
+
+/// Nibbles del Keccak-256 de una clave
+fn key_path(key: &[u8]) -> Vec<u8> {
+    Keccak256::digest(key).iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect()
+}
+
+/// Longitud del prefijo común de dos caminos
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Codificación hex-prefix de un camino de nibbles: el primer nibble marca
+/// si es hoja y si la longitud es impar
+fn hex_prefix(path: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 } + (path.len() % 2) as u8;
+    let mut encoded = Vec::with_capacity(path.len() / 2 + 1);
+    let rest = if path.len() % 2 == 1 {
+        encoded.push(flag << 4 | path[0]);
+        &path[1..]
+    } else {
+        encoded.push(flag << 4);
+        path
+    };
+    encoded.extend(rest.chunks_exact(2).map(|pair| pair[0] << 4 | pair[1]));
+    encoded
+}
+
+/// Camino y si es hoja de una codificación hex-prefix
+fn decode_hex_prefix(encoded: &[u8]) -> Option<(Vec<u8>, bool)> {
+    let (first, rest) = encoded.split_first()?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return None;
+    }
+    let mut path = Vec::with_capacity(rest.len() * 2 + 1);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(rest.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]));
+    Some((path, flag & 2 == 2))
+}
+
+/// Decodificación RLP mínima para verificar pruebas
+mod rlp {
+    /// Elemento RLP
+    #[derive(Debug, Clone)]
+    pub enum Item<'a> {
+        Bytes(&'a [u8]),
+        List(Vec<Item<'a>>),
+    }
+
+    impl<'a> Item<'a> {
+        pub fn bytes(&self) -> Option<&'a [u8]> {
+            match self {
+                Item::Bytes(bytes) => Some(bytes),
+                Item::List(_) => None,
+            }
+        }
+
+        pub fn list(&self) -> Option<&[Item<'a>]> {
+            match self {
+                Item::List(items) => Some(items),
+                Item::Bytes(_) => None,
+            }
+        }
+    }
+
+    /// Decodificar un elemento que ocupa todos los bytes
+    pub fn decode(data: &[u8]) -> Option<Item<'_>> {
+        let (item, used) = decode_item(data)?;
+        (used == data.len()).then_some(item)
+    }
+
+    /// Decodificar un elemento al principio de `data` y los bytes que ocupa
+    fn decode_item(data: &[u8]) -> Option<(Item<'_>, usize)> {
+        let prefix = *data.first()?;
+        let (offset, length, list) = match prefix {
+            0x00..=0x7f => return Some((Item::Bytes(&data[..1]), 1)),
+            0x80..=0xb7 => (1, (prefix - 0x80) as usize, false),
+            0xb8..=0xbf => long_length(data, (prefix - 0xb7) as usize).map(|(offset, length)| (offset, length, false))?,
+            0xc0..=0xf7 => (1, (prefix - 0xc0) as usize, true),
+            0xf8..=0xff => long_length(data, (prefix - 0xf7) as usize).map(|(offset, length)| (offset, length, true))?,
+        };
+        let payload = data.get(offset..offset.checked_add(length)?)?;
+        if !list {
+            return Some((Item::Bytes(payload), offset + length));
+        }
+        let mut items = Vec::new();
+        let mut position = 0;
+        while position < payload.len() {
+            let (item, used) = decode_item(&payload[position..])?;
+            items.push(item);
+            position += used;
+        }
+        Some((Item::List(items), offset + length))
+    }
+
+    /// Longitud larga en `bytes` bytes tras el prefijo
+    fn long_length(data: &[u8], bytes: usize) -> Option<(usize, usize)> {
+        let length_bytes = data.get(1..1 + bytes)?;
+        if bytes > std::mem::size_of::<usize>() {
+            return None;
+        }
+        let length = length_bytes.iter().fold(0usize, |length, byte| length << 8 | *byte as usize);
+        Some((1 + bytes, length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    type Trie = MerkleTrie<Vec<u8>, u64>;
+
+    /// Siguiente número del generador xorshift64*
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// 10000 claves aleatorias de 1 a 40 bytes con su valor. Alguna se
+    /// repite y se queda con el último valor
+    fn random_entries(seed: u64) -> Vec<(Vec<u8>, u64)> {
+        let mut state = seed;
+        (0..10_000)
+            .map(|_| {
+                let length = 1 + next_random(&mut state) % 40;
+                let key = (0..length).map(|_| (next_random(&mut state) % 4) as u8).collect();
+                (key, next_random(&mut state))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_proof_of_ten_thousand_random_keys_verifies() {
+        let entries = random_entries(0x9E37_79B9_7F4A_7C15);
+        let mut trie = Trie::new();
+        let mut expected = HashMap::new();
+        for (key, value) in &entries {
+            trie.insert(key.clone(), value);
+            expected.insert(key.clone(), *value);
+        }
+        assert_eq!(trie.len(), expected.len());
+        assert!(expected.len() < entries.len(), "las claves cortas deberían repetirse");
+
+        let root = trie.root_hash();
+        for (key, value) in &expected {
+            assert_eq!(trie.get(key), Some(*value));
+            let proof = trie.generate_proof(key);
+            assert!(Trie::verify_proof(root, key, value, &proof), "prueba de {:?}", key);
+            assert!(!Trie::verify_proof(root, key, &value.wrapping_add(1), &proof));
+        }
+
+        // La raíz no depende del orden de inserción (el del HashMap es otro)
+        let mut reordered = Trie::new();
+        for (key, value) in expected.iter() {
+            reordered.insert(key.clone(), value);
+        }
+        assert_eq!(reordered.root_hash(), root);
+    }
+
+    #[test]
+    fn proofs_do_not_verify_against_another_root_or_for_absent_keys() {
+        let entries = random_entries(7);
+        let mut trie = Trie::new();
+        for (key, value) in &entries[..1000] {
+            trie.insert(key.clone(), value);
+        }
+        let (key, _) = &entries[999];
+        let value = trie.get(key).unwrap();
+        let proof = trie.generate_proof(key);
+        let mut other_root = trie.root_hash();
+        other_root[0] ^= 1;
+        assert!(!Trie::verify_proof(other_root, key, &value, &proof));
+
+        // Las claves son de bytes 0-3: una con el byte 0xff no está
+        let absent = vec![0xff; 8];
+        assert_eq!(trie.get(&absent), None);
+        assert!(!Trie::verify_proof(trie.root_hash(), &absent, &value, &trie.generate_proof(&absent)));
+    }
+}
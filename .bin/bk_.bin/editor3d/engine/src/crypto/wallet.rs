@@ -0,0 +1,614 @@
+//! # Wallet HD
+//!
+//! Derivación de wallets a partir de frases mnemónicas BIP-39 y rutas BIP-32.
+//! La clave raíz solo vive en memoria y los bytes privados se ponen a cero al
+//! liberarse.
+
+use anyhow::{Result, anyhow};
+use k256::ecdsa::{SigningKey, VerifyingKey, RecoveryId, Signature};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Keccak256};
+use std::collections::{BTreeMap, BTreeSet};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Ruta de derivación por defecto de Ethereum (BIP-44)
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
+/// Bytes de una clave privada, puestos a cero al liberarse
+#[derive(Zeroize, ZeroizeOnDrop)]
+struct PrivateKeyBytes([u8; 32]);
+
+/// Wallet derivado de una frase mnemónica
+pub struct Wallet {
+    /// Clave raíz HD (solo en memoria)
+    root_key: PrivateKeyBytes,
+    /// Clave de firma de la ruta derivada
+    signing_key: k256::SecretKey,
+    /// Ruta de derivación
+    derivation_path: String,
+}
+
+/// Dominio EIP-712
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EIP712Domain {
+    /// Nombre de la aplicación
+    pub name: String,
+    /// Versión
+    pub version: String,
+    /// ID de cadena
+    pub chain_id: u64,
+    /// Contrato verificador (hex con prefijo 0x)
+    pub verifying_contract: String,
+}
+
+/// Campo de un tipo estructurado EIP-712
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EIP712Field {
+    /// Nombre del campo
+    pub name: String,
+    /// Tipo Solidity (`address`, `uint256`, `Person[]`...)
+    #[serde(rename = "type")]
+    pub field_type: String,
+}
+
+/// Tipos estructurados EIP-712 por nombre, sin `EIP712Domain`
+pub type EIP712Types = BTreeMap<String, Vec<EIP712Field>>;
+
+/// Transacción sin firmar (legacy con EIP-155)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsignedTransaction {
+    /// Nonce
+    pub nonce: u64,
+    /// Precio de gas
+    pub gas_price: u128,
+    /// Límite de gas
+    pub gas_limit: u64,
+    /// Destino (hex con prefijo 0x); `None` para despliegue de contratos
+    pub to: Option<String>,
+    /// Valor
+    pub value: u128,
+    /// Datos
+    pub data: Vec<u8>,
+    /// ID de cadena
+    pub chain_id: u64,
+}
+
+/// Transacción firmada
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    /// Hash de la transacción
+    pub hash: String,
+    /// Dirección del firmante
+    pub from: String,
+    /// Transacción codificada en RLP
+    pub raw: Vec<u8>,
+    /// Componente v
+    pub v: u64,
+    /// Componente r
+    pub r: [u8; 32],
+    /// Componente s
+    pub s: [u8; 32],
+}
+
+impl Wallet {
+    /// Derivar wallet desde una frase mnemónica BIP-39
+    pub fn from_mnemonic(phrase: &str, passphrase: &str, derivation_path: &str) -> Result<Wallet> {
+        let mnemonic = bip39::Mnemonic::parse_normalized(phrase)
+            .map_err(|e| anyhow!("Frase mnemónica inválida: {}", e))?;
+        // La semilla se pone a cero en cualquier salida, también con error
+        let seed = Zeroizing::new(mnemonic.to_seed_normalized(passphrase));
+
+        let root = tiny_bip32::ExtendedPrivKey::derive(&seed[..], "m")
+            .map_err(|e| anyhow!("Error derivando clave raíz: {:?}", e))?;
+        let child = tiny_bip32::ExtendedPrivKey::derive(&seed[..], derivation_path)
+            .map_err(|e| anyhow!("Ruta de derivación inválida {}: {:?}", derivation_path, e))?;
+
+        let root_key = PrivateKeyBytes(root.secret());
+        // Los bytes temporales se ponen a cero al salir de ámbito
+        let private_key = PrivateKeyBytes(child.secret());
+        let signing_key = k256::SecretKey::from_slice(&private_key.0)
+            .map_err(|e| anyhow!("Clave privada inválida: {}", e))?;
+
+        Ok(Wallet {
+            root_key,
+            signing_key,
+            derivation_path: derivation_path.to_string(),
+        })
+    }
+
+    /// Clave de firma
+    pub fn signing_key(&self) -> &k256::SecretKey {
+        &self.signing_key
+    }
+
+    /// Ruta de derivación
+    pub fn derivation_path(&self) -> &str {
+        &self.derivation_path
+    }
+
+    /// Huella de la clave raíz, para identificar el wallet sin exponerla
+    pub fn root_fingerprint(&self) -> [u8; 4] {
+        let hash = Keccak256::digest(self.root_key.0);
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    /// Dirección con checksum EIP-55
+    pub fn address(&self) -> String {
+        key_address(&self.signing_key)
+    }
+
+    /// Firmar datos tipados EIP-712 (`eth_signTypedData_v4`)
+    ///
+    /// `message` se serializa a JSON y se codifica con `hashStruct` según
+    /// `types`, que describe `primary_type` y los structs que referencia.
+    pub fn sign_typed_data(
+        &self,
+        domain: &EIP712Domain,
+        types: &EIP712Types,
+        primary_type: &str,
+        message: &impl Serialize,
+    ) -> Result<[u8; 65]> {
+        let message = serde_json::to_value(message)
+            .map_err(|e| anyhow!("Mensaje EIP-712 no serializable: {}", e))?;
+        let digest = typed_data_digest(domain, types, primary_type, &message)?;
+
+        let (signature, recovery_id) = self.sign_digest(&digest);
+        let mut output = [0u8; 65];
+        output[..64].copy_from_slice(&signature.to_bytes());
+        output[64] = 27 + recovery_id.to_byte();
+        Ok(output)
+    }
+
+    /// Firmar una transacción con EIP-155
+    pub fn sign_transaction(&self, tx: &UnsignedTransaction) -> Result<SignedTransaction> {
+        sign_transaction_with_key(&self.signing_key, tx)
+    }
+
+    /// Firmar un mensaje con EIP-191 (`personal_sign`)
+    pub fn sign_message(&self, message: &[u8]) -> [u8; 65] {
+        sign_message_with_key(&self.signing_key, message)
+    }
+
+    /// Clave estática de red derivada de la clave del wallet
+    pub fn derive_network_key(&self) -> [u8; 32] {
+        let mut secret = self.signing_key.to_bytes();
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(b"metaverso/network-static");
+        hasher.update(&secret);
+        secret.zeroize();
+        hasher.finalize().into()
+    }
+
+    /// Firmar un digest de 32 bytes
+    fn sign_digest(&self, digest: &[u8]) -> (Signature, RecoveryId) {
+        sign_digest(&self.signing_key, digest)
+    }
+}
+
+impl std::fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Nunca imprimir material privado
+        f.debug_struct("Wallet")
+            .field("address", &self.address())
+            .field("derivation_path", &self.derivation_path)
+            .finish()
+    }
+}
+
+/// Dirección con checksum EIP-55 de una clave privada
+pub fn key_address(key: &k256::SecretKey) -> String {
+    let public_key = key.public_key().to_encoded_point(false);
+    let hash = Keccak256::digest(&public_key.as_bytes()[1..]);
+    to_checksum_address(&hash[12..])
+}
+
+/// Firmar una transacción con EIP-155 con una clave privada
+pub fn sign_transaction_with_key(key: &k256::SecretKey, tx: &UnsignedTransaction) -> Result<SignedTransaction> {
+    let to = match &tx.to {
+        Some(to) => decode_hex(to)?,
+        None => Vec::new(),
+    };
+
+    let mut fields = vec![
+        rlp_uint(tx.nonce as u128),
+        rlp_uint(tx.gas_price),
+        rlp_uint(tx.gas_limit as u128),
+        rlp_bytes(&to),
+        rlp_uint(tx.value),
+        rlp_bytes(&tx.data),
+    ];
+
+    let mut signing_fields = fields.clone();
+    signing_fields.push(rlp_uint(tx.chain_id as u128));
+    signing_fields.push(rlp_uint(0));
+    signing_fields.push(rlp_uint(0));
+    let digest = Keccak256::digest(rlp_list(&signing_fields));
+
+    let (signature, recovery_id) = sign_digest(key, &digest);
+    let v = recovery_id.to_byte() as u64 + tx.chain_id * 2 + 35;
+    let mut r = [0u8; 32];
+    let mut s = [0u8; 32];
+    r.copy_from_slice(&signature.r().to_bytes());
+    s.copy_from_slice(&signature.s().to_bytes());
+
+    fields.push(rlp_uint(v as u128));
+    fields.push(rlp_bytes(trim_leading_zeros(&r)));
+    fields.push(rlp_bytes(trim_leading_zeros(&s)));
+    let raw = rlp_list(&fields);
+    let hash = format!("0x{}", encode_hex(&Keccak256::digest(&raw)));
+
+    Ok(SignedTransaction {
+        hash,
+        from: key_address(key),
+        raw,
+        v,
+        r,
+        s,
+    })
+}
+
+/// Firmar un mensaje con EIP-191 (`personal_sign`) con una clave privada
+pub fn sign_message_with_key(key: &k256::SecretKey, message: &[u8]) -> [u8; 65] {
+    let (signature, recovery_id) = sign_digest(key, &personal_message_digest(message));
+    let mut output = [0u8; 65];
+    output[..64].copy_from_slice(&signature.to_bytes());
+    output[64] = 27 + recovery_id.to_byte();
+    output
+}
+
+/// Firmar un digest de 32 bytes
+fn sign_digest(key: &k256::SecretKey, digest: &[u8]) -> (Signature, RecoveryId) {
+    SigningKey::from(key)
+        .sign_prehash_recoverable(digest)
+        .expect("un digest de 32 bytes siempre es firmable")
+}
+
+/// Recuperar la dirección que firmó un mensaje EIP-191
+pub fn recover_message_signer(message: &[u8], signature: &[u8; 65]) -> Result<String> {
+    let recovery_id = RecoveryId::from_byte(signature[64].wrapping_sub(27))
+        .ok_or_else(|| anyhow!("Identificador de recuperación inválido"))?;
+    let signature = Signature::from_slice(&signature[..64])
+        .map_err(|e| anyhow!("Firma inválida: {}", e))?;
+    let verifying_key = VerifyingKey::recover_from_prehash(&personal_message_digest(message), &signature, recovery_id)
+        .map_err(|e| anyhow!("No se pudo recuperar el firmante: {}", e))?;
+
+    let public_key = verifying_key.to_encoded_point(false);
+    let hash = Keccak256::digest(&public_key.as_bytes()[1..]);
+    Ok(to_checksum_address(&hash[12..]))
+}
+
+/// Digest EIP-191 de un mensaje
+fn personal_message_digest(message: &[u8]) -> [u8; 32] {
+    let mut payload = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    payload.extend_from_slice(message);
+    Keccak256::digest(&payload).into()
+}
+
+/// Separador de dominio EIP-712
+fn domain_separator(domain: &EIP712Domain) -> [u8; 32] {
+    let type_hash = Keccak256::digest(
+        b"EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)",
+    );
+    let mut contract = [0u8; 32];
+    if let Ok(bytes) = decode_hex(&domain.verifying_contract) {
+        if bytes.len() <= 32 {
+            contract[32 - bytes.len()..].copy_from_slice(&bytes);
+        }
+    }
+    let mut chain_id = [0u8; 32];
+    chain_id[24..].copy_from_slice(&domain.chain_id.to_be_bytes());
+
+    let mut encoded = Vec::with_capacity(160);
+    encoded.extend_from_slice(&type_hash);
+    encoded.extend_from_slice(&Keccak256::digest(domain.name.as_bytes()));
+    encoded.extend_from_slice(&Keccak256::digest(domain.version.as_bytes()));
+    encoded.extend_from_slice(&chain_id);
+    encoded.extend_from_slice(&contract);
+    Keccak256::digest(&encoded).into()
+}
+
+/// Digest EIP-712 a firmar: `keccak256(0x1901 ‖ domainSeparator ‖ hashStruct(message))`
+pub fn typed_data_digest(
+    domain: &EIP712Domain,
+    types: &EIP712Types,
+    primary_type: &str,
+    message: &serde_json::Value,
+) -> Result<[u8; 32]> {
+    let mut payload = Vec::with_capacity(66);
+    payload.extend_from_slice(&[0x19, 0x01]);
+    payload.extend_from_slice(&domain_separator(domain));
+    payload.extend_from_slice(&hash_struct(types, primary_type, message)?);
+    Ok(Keccak256::digest(&payload).into())
+}
+
+/// `hashStruct(s) = keccak256(typeHash ‖ encodeData(s))`
+pub fn hash_struct(types: &EIP712Types, type_name: &str, data: &serde_json::Value) -> Result<[u8; 32]> {
+    let fields = types.get(type_name)
+        .ok_or_else(|| anyhow!("Tipo EIP-712 desconocido: {}", type_name))?;
+    let object = data.as_object()
+        .ok_or_else(|| anyhow!("Se esperaba un objeto para {}", type_name))?;
+
+    let mut encoded = Vec::with_capacity(32 * (fields.len() + 1));
+    encoded.extend_from_slice(&Keccak256::digest(encode_type(types, type_name)?.as_bytes()));
+    for field in fields {
+        let value = object.get(&field.name)
+            .ok_or_else(|| anyhow!("Falta el campo {}.{}", type_name, field.name))?;
+        encoded.extend_from_slice(&encode_value(types, &field.field_type, value)?);
+    }
+    Ok(Keccak256::digest(&encoded).into())
+}
+
+/// `encodeType`: el tipo principal seguido de sus dependencias por orden alfabético
+pub fn encode_type(types: &EIP712Types, type_name: &str) -> Result<String> {
+    let mut dependencies = BTreeSet::new();
+    collect_dependencies(types, type_name, &mut dependencies);
+    if !dependencies.remove(type_name) {
+        return Err(anyhow!("Tipo EIP-712 desconocido: {}", type_name));
+    }
+
+    let mut encoded = String::new();
+    for name in std::iter::once(type_name).chain(dependencies.iter().map(String::as_str)) {
+        let fields: Vec<String> = types[name].iter()
+            .map(|field| format!("{} {}", field.field_type, field.name))
+            .collect();
+        encoded.push_str(&format!("{}({})", name, fields.join(",")));
+    }
+    Ok(encoded)
+}
+
+/// Structs alcanzables desde un tipo, incluido él mismo
+fn collect_dependencies(types: &EIP712Types, type_name: &str, found: &mut BTreeSet<String>) {
+    let base = type_name.split('[').next().unwrap_or(type_name);
+    let fields = match types.get(base) {
+        Some(fields) => fields,
+        None => return,
+    };
+    if !found.insert(base.to_string()) {
+        return;
+    }
+    for field in fields {
+        collect_dependencies(types, &field.field_type, found);
+    }
+}
+
+/// Codificar un valor en su palabra de 32 bytes de `encodeData`
+fn encode_value(types: &EIP712Types, field_type: &str, value: &serde_json::Value) -> Result<[u8; 32]> {
+    // Arrays: keccak256 de la concatenación de sus elementos codificados
+    if let Some(open) = field_type.rfind('[').filter(|_| field_type.ends_with(']')) {
+        let item_type = &field_type[..open];
+        let items = value.as_array()
+            .ok_or_else(|| anyhow!("Se esperaba un array para {}", field_type))?;
+        let mut encoded = Vec::with_capacity(32 * items.len());
+        for item in items {
+            encoded.extend_from_slice(&encode_value(types, item_type, item)?);
+        }
+        return Ok(Keccak256::digest(&encoded).into());
+    }
+
+    if types.contains_key(field_type) {
+        return hash_struct(types, field_type, value);
+    }
+
+    let mut word = [0u8; 32];
+    match field_type {
+        "string" => {
+            let text = value.as_str().ok_or_else(|| anyhow!("Se esperaba un string"))?;
+            word = Keccak256::digest(text.as_bytes()).into();
+        }
+        "bytes" => {
+            let hex = value.as_str().ok_or_else(|| anyhow!("Se esperaban bytes en hex"))?;
+            word = Keccak256::digest(decode_hex(hex)?).into();
+        }
+        "bool" => {
+            word[31] = value.as_bool().ok_or_else(|| anyhow!("Se esperaba un bool"))? as u8;
+        }
+        "address" => {
+            let hex = value.as_str().ok_or_else(|| anyhow!("Se esperaba una dirección"))?;
+            let bytes = decode_hex(hex)?;
+            if bytes.len() != 20 {
+                return Err(anyhow!("Dirección inválida: {}", hex));
+            }
+            word[12..].copy_from_slice(&bytes);
+        }
+        _ if field_type.starts_with("bytes") => {
+            let size: usize = field_type[5..].parse()
+                .map_err(|_| anyhow!("Tipo EIP-712 desconocido: {}", field_type))?;
+            let hex = value.as_str().ok_or_else(|| anyhow!("Se esperaban bytes en hex"))?;
+            let bytes = decode_hex(hex)?;
+            if size == 0 || size > 32 || bytes.len() > size {
+                return Err(anyhow!("Valor inválido para {}: {}", field_type, hex));
+            }
+            word[..bytes.len()].copy_from_slice(&bytes);
+        }
+        _ if field_type.starts_with("uint") => {
+            word = encode_uint(value)?;
+        }
+        _ if field_type.starts_with("int") => {
+            let number = match value {
+                serde_json::Value::Number(n) => n.as_i64().map(i128::from),
+                serde_json::Value::String(s) => s.parse::<i128>().ok(),
+                _ => None,
+            }.ok_or_else(|| anyhow!("Entero inválido para {}: {}", field_type, value))?;
+            // Complemento a dos extendido a 256 bits
+            if number < 0 {
+                word = [0xff; 32];
+            }
+            word[16..].copy_from_slice(&number.to_be_bytes());
+        }
+        _ => return Err(anyhow!("Tipo EIP-712 desconocido: {}", field_type)),
+    }
+    Ok(word)
+}
+
+/// Entero sin signo desde un número JSON, un decimal o un hex con prefijo 0x
+fn encode_uint(value: &serde_json::Value) -> Result<[u8; 32]> {
+    let mut word = [0u8; 32];
+    match value {
+        serde_json::Value::Number(n) => {
+            let number = n.as_u64().ok_or_else(|| anyhow!("Entero sin signo inválido: {}", n))?;
+            word[24..].copy_from_slice(&number.to_be_bytes());
+        }
+        serde_json::Value::String(s) if s.starts_with("0x") => {
+            let hex = if s.len() % 2 == 0 { s.clone() } else { format!("0x0{}", &s[2..]) };
+            let bytes = decode_hex(&hex)?;
+            let bytes = trim_leading_zeros(&bytes);
+            if bytes.len() > 32 {
+                return Err(anyhow!("Entero fuera de rango: {}", s));
+            }
+            word[32 - bytes.len()..].copy_from_slice(bytes);
+        }
+        serde_json::Value::String(s) => {
+            let number: u128 = s.parse().map_err(|_| anyhow!("Entero sin signo inválido: {}", s))?;
+            word[16..].copy_from_slice(&number.to_be_bytes());
+        }
+        _ => return Err(anyhow!("Entero sin signo inválido: {}", value)),
+    }
+    Ok(word)
+}
+
+/// Dirección con checksum EIP-55
+fn to_checksum_address(address: &[u8]) -> String {
+    let lower = encode_hex(address);
+    let hash = Keccak256::digest(lower.as_bytes());
+    let checksummed: String = lower.chars().enumerate().map(|(i, c)| {
+        let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+        if c.is_ascii_alphabetic() && nibble >= 8 { c.to_ascii_uppercase() } else { c }
+    }).collect();
+    format!("0x{}", checksummed)
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>> {
+    let value = value.trim_start_matches("0x");
+    if value.len() % 2 != 0 {
+        return Err(anyhow!("Hex con longitud impar: {}", value));
+    }
+    (0..value.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).map_err(|e| anyhow!("Hex inválido: {}", e)))
+        .collect()
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+fn rlp_length_prefix(length: usize, offset: u8) -> Vec<u8> {
+    if length < 56 {
+        vec![offset + length as u8]
+    } else {
+        let length_bytes = length.to_be_bytes();
+        let length_bytes = trim_leading_zeros(&length_bytes);
+        let mut prefix = vec![offset + 55 + length_bytes.len() as u8];
+        prefix.extend_from_slice(length_bytes);
+        prefix
+    }
+}
+
+pub(crate) fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut encoded = rlp_length_prefix(bytes.len(), 0x80);
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+fn rlp_uint(value: u128) -> Vec<u8> {
+    rlp_bytes(trim_leading_zeros(&value.to_be_bytes()))
+}
+
+pub(crate) fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload: Vec<u8> = items.concat();
+    let mut encoded = rlp_length_prefix(payload.len(), 0xc0);
+    encoded.extend_from_slice(&payload);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ejemplo "Mail" de la especificación EIP-712
+    fn mail_types() -> EIP712Types {
+        let field = |name: &str, field_type: &str| EIP712Field {
+            name: name.to_string(),
+            field_type: field_type.to_string(),
+        };
+        let mut types = EIP712Types::new();
+        types.insert("Person".to_string(), vec![field("name", "string"), field("wallet", "address")]);
+        types.insert("Mail".to_string(), vec![field("from", "Person"), field("to", "Person"), field("contents", "string")]);
+        types
+    }
+
+    fn mail_domain() -> EIP712Domain {
+        EIP712Domain {
+            name: "Ether Mail".to_string(),
+            version: "1".to_string(),
+            chain_id: 1,
+            verifying_contract: "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC".to_string(),
+        }
+    }
+
+    fn mail_message() -> serde_json::Value {
+        serde_json::json!({
+            "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+            "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+            "contents": "Hello, Bob!",
+        })
+    }
+
+    #[test]
+    fn typed_data_matches_the_mail_reference_vector() {
+        let types = mail_types();
+        assert_eq!(
+            encode_type(&types, "Mail").unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        assert_eq!(
+            encode_hex(&Keccak256::digest(encode_type(&types, "Mail").unwrap().as_bytes())),
+            "a0cedeb2dc280ba39b857546d74f5549c3a1d7bdc2dd96bf881f76108e23dac2"
+        );
+        assert_eq!(
+            encode_hex(&hash_struct(&types, "Mail", &mail_message()).unwrap()),
+            "c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e"
+        );
+        assert_eq!(
+            encode_hex(&domain_separator(&mail_domain())),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+        assert_eq!(
+            encode_hex(&typed_data_digest(&mail_domain(), &types, "Mail", &mail_message()).unwrap()),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+    }
+
+    #[test]
+    fn typed_data_signature_matches_the_mail_reference_vector() {
+        // Clave del ejemplo: keccak256("cow")
+        let key = k256::SecretKey::from_slice(&Keccak256::digest(b"cow")).unwrap();
+        assert_eq!(key_address(&key), "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826");
+
+        let digest = typed_data_digest(&mail_domain(), &mail_types(), "Mail", &mail_message()).unwrap();
+        let (signature, recovery_id) = sign_digest(&key, &digest);
+        assert_eq!(
+            encode_hex(&signature.r().to_bytes()),
+            "4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d"
+        );
+        assert_eq!(
+            encode_hex(&signature.s().to_bytes()),
+            "07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562"
+        );
+        assert_eq!(27 + recovery_id.to_byte(), 28);
+    }
+
+    #[test]
+    fn typed_data_rejects_missing_fields() {
+        let message = serde_json::json!({ "from": { "name": "Cow" }, "to": {}, "contents": "" });
+        assert!(typed_data_digest(&mail_domain(), &mail_types(), "Mail", &message).is_err());
+    }
+}
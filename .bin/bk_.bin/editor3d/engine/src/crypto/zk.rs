@@ -0,0 +1,584 @@
+//! # Pruebas de propiedad privadas
+//!
+//! Pruebas Groth16 (bellman, BLS12-381) de la afirmación "conozco un secreto
+//! `s` tal que `Poseidon(s) = commitment` y `commitment` está en el árbol
+//! Merkle de propiedad del NFT". El verificador aprende el commitment y la
+//! raíz, pero no la posición de la hoja ni el secreto.
+//!
+//! Poseidon usa ancho 3, S-box x^5, 8 rondas completas y 57 parciales. Las
+//! constantes de ronda se derivan con SHA-256 de una etiqueta fija y la
+//! matriz MDS es de Cauchy, así que los hashes no coinciden con otras
+//! instancias de Poseidon.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use anyhow::{Result, anyhow};
+use bellman::gadgets::boolean::AllocatedBit;
+use bellman::gadgets::num::AllocatedNum;
+use bellman::groth16::{self, Parameters, PreparedVerifyingKey, Proof, VerifyingKey};
+use bellman::{Circuit, ConstraintSystem, LinearCombination, SynthesisError};
+use bls12_381::{Bls12, Scalar};
+use ff::Field;
+use rand_core::OsRng;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+
+use super::wallet::encode_hex;
+
+/// Profundidad del árbol de propiedad (hasta 65536 NFTs privados)
+pub const OWNERSHIP_TREE_DEPTH: usize = 16;
+
+/// Ancho del estado de Poseidon
+const WIDTH: usize = 3;
+/// Rondas completas (la mitad al principio y la mitad al final)
+const FULL_ROUNDS: usize = 8;
+/// Rondas parciales
+const PARTIAL_ROUNDS: usize = 57;
+/// Etiqueta de derivación de las constantes de ronda
+const ROUND_CONSTANT_TAG: &[u8] = b"metaverso-poseidon-bls12-381-t3";
+
+/// Constantes de Poseidon
+struct PoseidonConstants {
+    round_constants: Vec<[Scalar; WIDTH]>,
+    mds: [[Scalar; WIDTH]; WIDTH],
+}
+
+/// Constantes compartidas, calculadas una vez
+fn poseidon_constants() -> &'static PoseidonConstants {
+    static CONSTANTS: OnceLock<PoseidonConstants> = OnceLock::new();
+    CONSTANTS.get_or_init(|| {
+        let round_constants = (0..FULL_ROUNDS + PARTIAL_ROUNDS)
+            .map(|round| {
+                let mut constants = [Scalar::ZERO; WIDTH];
+                for (i, constant) in constants.iter_mut().enumerate() {
+                    let label = [&(round as u32).to_le_bytes()[..], &(i as u32).to_le_bytes()[..]].concat();
+                    *constant = hash_to_scalar(ROUND_CONSTANT_TAG, &label);
+                }
+                constants
+            })
+            .collect();
+
+        // Cauchy: M[i][j] = 1 / (x_i + y_j) con x_i = i, y_j = WIDTH + j
+        let mut mds = [[Scalar::ZERO; WIDTH]; WIDTH];
+        for (i, row) in mds.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                *entry = Scalar::from((i + WIDTH + j) as u64).invert().unwrap();
+            }
+        }
+        PoseidonConstants { round_constants, mds }
+    })
+}
+
+/// Reducir SHA-256(tag || data || 0) || SHA-256(tag || data || 1) al campo
+fn hash_to_scalar(tag: &[u8], data: &[u8]) -> Scalar {
+    let mut wide = [0u8; 64];
+    for (half, chunk) in wide.chunks_mut(32).enumerate() {
+        let digest = Sha256::new()
+            .chain_update(tag)
+            .chain_update(data)
+            .chain_update([half as u8])
+            .finalize();
+        chunk.copy_from_slice(&digest);
+    }
+    Scalar::from_bytes_wide(&wide)
+}
+
+/// Ronda completa (S-box en todo el estado) o parcial (solo el primer elemento)
+fn is_full_round(round: usize) -> bool {
+    round < FULL_ROUNDS / 2 || round >= FULL_ROUNDS / 2 + PARTIAL_ROUNDS
+}
+
+/// Permutación de Poseidon
+fn poseidon_permute(state: &mut [Scalar; WIDTH]) {
+    let constants = poseidon_constants();
+    for (round, round_constants) in constants.round_constants.iter().enumerate() {
+        for (value, constant) in state.iter_mut().zip(round_constants) {
+            *value += constant;
+        }
+        let sbox_count = if is_full_round(round) { WIDTH } else { 1 };
+        for value in state.iter_mut().take(sbox_count) {
+            let square = value.square();
+            *value *= square.square();
+        }
+        let mut mixed = [Scalar::ZERO; WIDTH];
+        for (row, out) in constants.mds.iter().zip(mixed.iter_mut()) {
+            *out = row.iter().zip(state.iter()).map(|(m, v)| m * v).sum();
+        }
+        *state = mixed;
+    }
+}
+
+/// Poseidon de un elemento (la capacidad lleva el número de entradas)
+pub fn poseidon_hash1(value: Scalar) -> Scalar {
+    let mut state = [Scalar::from(1u64), value, Scalar::ZERO];
+    poseidon_permute(&mut state);
+    state[1]
+}
+
+/// Poseidon de dos elementos
+pub fn poseidon_hash2(left: Scalar, right: Scalar) -> Scalar {
+    let mut state = [Scalar::from(2u64), left, right];
+    poseidon_permute(&mut state);
+    state[1]
+}
+
+/// Secreto del propietario como elemento del campo
+fn secret_scalar(owner_secret: &[u8; 32]) -> Scalar {
+    let mut wide = [0u8; 64];
+    wide[..32].copy_from_slice(owner_secret);
+    Scalar::from_bytes_wide(&wide)
+}
+
+/// Etiqueta pública del NFT dentro del circuito
+fn nft_tag(nft_id: &str) -> Scalar {
+    hash_to_scalar(b"metaverso-nft-id", nft_id.as_bytes())
+}
+
+/// Leer un elemento del campo en little-endian
+fn scalar_from_bytes(bytes: &[u8; 32]) -> Option<Scalar> {
+    Option::from(Scalar::from_bytes(bytes))
+}
+
+/// Commitment del propietario: Poseidon(s)
+pub fn owner_commitment(owner_secret: &[u8; 32]) -> [u8; 32] {
+    poseidon_hash1(secret_scalar(owner_secret)).to_bytes()
+}
+
+/// Árbol Merkle de propiedad. Cada hoja es Poseidon(commitment, nft)
+#[derive(Debug, Clone)]
+pub struct OwnershipTree {
+    /// Hojas ocupadas en orden de inserción
+    leaves: Vec<Scalar>,
+    /// Posición de la hoja de cada NFT
+    positions: HashMap<String, usize>,
+    /// Hash de un subárbol vacío por nivel
+    empty: Vec<Scalar>,
+}
+
+impl Default for OwnershipTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OwnershipTree {
+    /// Crear árbol vacío
+    pub fn new() -> Self {
+        let mut empty = vec![Scalar::ZERO];
+        for level in 0..OWNERSHIP_TREE_DEPTH {
+            empty.push(poseidon_hash2(empty[level], empty[level]));
+        }
+        Self {
+            leaves: Vec::new(),
+            positions: HashMap::new(),
+            empty,
+        }
+    }
+
+    /// Asignar o reasignar el commitment propietario de un NFT
+    pub fn insert(&mut self, nft_id: &str, commitment: [u8; 32]) -> Result<usize> {
+        let commitment = scalar_from_bytes(&commitment)
+            .ok_or_else(|| anyhow!("Commitment fuera del campo"))?;
+        let leaf = poseidon_hash2(commitment, nft_tag(nft_id));
+        if let Some(&position) = self.positions.get(nft_id) {
+            self.leaves[position] = leaf;
+            return Ok(position);
+        }
+        if self.leaves.len() >= 1 << OWNERSHIP_TREE_DEPTH {
+            return Err(anyhow!("Árbol de propiedad lleno"));
+        }
+        self.leaves.push(leaf);
+        self.positions.insert(nft_id.to_string(), self.leaves.len() - 1);
+        Ok(self.leaves.len() - 1)
+    }
+
+    /// Número de NFTs en el árbol
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Árbol vacío
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Raíz del árbol
+    pub fn root(&self) -> [u8; 32] {
+        self.root_scalar().to_bytes()
+    }
+
+    fn root_scalar(&self) -> Scalar {
+        let mut level_nodes = self.leaves.clone();
+        for level in 0..OWNERSHIP_TREE_DEPTH {
+            level_nodes = self.parent_level(&level_nodes, level);
+        }
+        level_nodes.first().copied().unwrap_or(self.empty[OWNERSHIP_TREE_DEPTH])
+    }
+
+    /// Nodos del nivel superior (los ausentes son subárboles vacíos)
+    fn parent_level(&self, nodes: &[Scalar], level: usize) -> Vec<Scalar> {
+        nodes.chunks(2)
+            .map(|pair| poseidon_hash2(pair[0], pair.get(1).copied().unwrap_or(self.empty[level])))
+            .collect()
+    }
+
+    /// Camino de un NFT: (hermano, el nodo actual es hijo derecho) por nivel
+    fn path(&self, nft_id: &str) -> Option<Vec<(Scalar, bool)>> {
+        let mut position = *self.positions.get(nft_id)?;
+        let mut level_nodes = self.leaves.clone();
+        let mut path = Vec::with_capacity(OWNERSHIP_TREE_DEPTH);
+        for level in 0..OWNERSHIP_TREE_DEPTH {
+            let sibling = level_nodes.get(position ^ 1).copied().unwrap_or(self.empty[level]);
+            path.push((sibling, position & 1 == 1));
+            level_nodes = self.parent_level(&level_nodes, level);
+            position >>= 1;
+        }
+        Some(path)
+    }
+}
+
+/// Elemento del circuito: combinación lineal y su valor (None sin testigo)
+#[derive(Clone)]
+struct Elt {
+    lc: LinearCombination<Scalar>,
+    value: Option<Scalar>,
+}
+
+impl Elt {
+    fn from_num(num: &AllocatedNum<Scalar>) -> Self {
+        Self { lc: LinearCombination::zero() + num.get_variable(), value: num.get_value() }
+    }
+
+    fn constant<CS: ConstraintSystem<Scalar>>(value: Scalar) -> Self {
+        Self { lc: LinearCombination::zero() + (value, CS::one()), value: Some(value) }
+    }
+
+    fn add_constant<CS: ConstraintSystem<Scalar>>(self, constant: Scalar) -> Self {
+        Self { lc: self.lc + (constant, CS::one()), value: self.value.map(|v| v + constant) }
+    }
+
+    /// Combinación lineal de varios elementos
+    fn linear(elements: &[Elt], coefficients: &[Scalar]) -> Self {
+        let mut lc = LinearCombination::zero();
+        let mut value = Some(Scalar::ZERO);
+        for (element, coefficient) in elements.iter().zip(coefficients) {
+            lc = lc + (*coefficient, &element.lc);
+            value = value.zip(element.value).map(|(acc, v)| acc + v * coefficient);
+        }
+        Self { lc, value }
+    }
+
+    /// Asignar una variable igual a la combinación para que no crezca
+    fn compact<CS: ConstraintSystem<Scalar>>(self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let num = AllocatedNum::alloc(cs.namespace(|| "value"), || self.value.ok_or(SynthesisError::AssignmentMissing))?;
+        cs.enforce(|| "compact", |lc| lc + &self.lc, |lc| lc + CS::one(), |lc| lc + num.get_variable());
+        Ok(Self::from_num(&num))
+    }
+}
+
+/// S-box x^5 en el circuito
+fn sbox_gadget<CS: ConstraintSystem<Scalar>>(mut cs: CS, x: &Elt) -> Result<Elt, SynthesisError> {
+    let x2 = AllocatedNum::alloc(cs.namespace(|| "x2"), || {
+        x.value.map(|v| v.square()).ok_or(SynthesisError::AssignmentMissing)
+    })?;
+    cs.enforce(|| "x2 = x * x", |lc| lc + &x.lc, |lc| lc + &x.lc, |lc| lc + x2.get_variable());
+    let x4 = x2.square(cs.namespace(|| "x4"))?;
+    let x5 = AllocatedNum::alloc(cs.namespace(|| "x5"), || {
+        x4.get_value().zip(x.value).map(|(a, b)| a * b).ok_or(SynthesisError::AssignmentMissing)
+    })?;
+    cs.enforce(|| "x5 = x4 * x", |lc| lc + x4.get_variable(), |lc| lc + &x.lc, |lc| lc + x5.get_variable());
+    Ok(Elt::from_num(&x5))
+}
+
+/// Permutación de Poseidon en el circuito
+fn poseidon_gadget<CS: ConstraintSystem<Scalar>>(mut cs: CS, mut state: [Elt; WIDTH]) -> Result<[Elt; WIDTH], SynthesisError> {
+    let constants = poseidon_constants();
+    for (round, round_constants) in constants.round_constants.iter().enumerate() {
+        let mut cs = cs.namespace(|| format!("round {}", round));
+        for (element, constant) in state.iter_mut().zip(round_constants) {
+            *element = element.clone().add_constant::<CS>(*constant);
+        }
+        let sbox_count = if is_full_round(round) { WIDTH } else { 1 };
+        for (i, element) in state.iter_mut().enumerate().take(sbox_count) {
+            *element = sbox_gadget(cs.namespace(|| format!("sbox {}", i)), element)?;
+        }
+        let mut mixed = Vec::with_capacity(WIDTH);
+        for (i, row) in constants.mds.iter().enumerate() {
+            mixed.push(Elt::linear(&state, row).compact(cs.namespace(|| format!("mds {}", i)))?);
+        }
+        state = [mixed[0].clone(), mixed[1].clone(), mixed[2].clone()];
+    }
+    Ok(state)
+}
+
+fn hash1_gadget<CS: ConstraintSystem<Scalar>>(cs: CS, value: Elt) -> Result<Elt, SynthesisError> {
+    let state = [Elt::constant::<CS>(Scalar::from(1u64)), value, Elt::constant::<CS>(Scalar::ZERO)];
+    Ok(poseidon_gadget(cs, state)?[1].clone())
+}
+
+fn hash2_gadget<CS: ConstraintSystem<Scalar>>(cs: CS, left: Elt, right: Elt) -> Result<Elt, SynthesisError> {
+    let state = [Elt::constant::<CS>(Scalar::from(2u64)), left, right];
+    Ok(poseidon_gadget(cs, state)?[1].clone())
+}
+
+/// Circuito de propiedad. Entradas públicas: commitment, NFT y raíz
+#[derive(Clone)]
+struct OwnershipCircuit {
+    secret: Option<Scalar>,
+    commitment: Option<Scalar>,
+    nft_tag: Option<Scalar>,
+    merkle_root: Option<Scalar>,
+    /// (hermano, el nodo actual es hijo derecho) por nivel
+    path: Vec<(Option<Scalar>, Option<bool>)>,
+}
+
+impl OwnershipCircuit {
+    /// Circuito sin testigo para generar los parámetros
+    fn blank() -> Self {
+        Self {
+            secret: None,
+            commitment: None,
+            nft_tag: None,
+            merkle_root: None,
+            path: vec![(None, None); OWNERSHIP_TREE_DEPTH],
+        }
+    }
+}
+
+impl Circuit<Scalar> for OwnershipCircuit {
+    fn synthesize<CS: ConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let missing = || SynthesisError::AssignmentMissing;
+        let commitment = AllocatedNum::alloc_input(cs.namespace(|| "commitment"), || self.commitment.ok_or_else(missing))?;
+        let nft_tag = AllocatedNum::alloc_input(cs.namespace(|| "nft"), || self.nft_tag.ok_or_else(missing))?;
+        let merkle_root = AllocatedNum::alloc_input(cs.namespace(|| "merkle root"), || self.merkle_root.ok_or_else(missing))?;
+        let secret = AllocatedNum::alloc(cs.namespace(|| "secret"), || self.secret.ok_or_else(missing))?;
+
+        // Poseidon(s) = commitment
+        let hashed = hash1_gadget(cs.namespace(|| "commitment hash"), Elt::from_num(&secret))?;
+        cs.enforce(
+            || "commitment matches",
+            |lc| lc + &hashed.lc - commitment.get_variable(),
+            |lc| lc + CS::one(),
+            |lc| lc,
+        );
+
+        // La hoja Poseidon(commitment, nft) sube hasta la raíz
+        let mut current = hash2_gadget(cs.namespace(|| "leaf"), Elt::from_num(&commitment), Elt::from_num(&nft_tag))?;
+        for (level, (sibling, is_right)) in self.path.into_iter().enumerate() {
+            let mut cs = cs.namespace(|| format!("level {}", level));
+            let bit = AllocatedBit::alloc(cs.namespace(|| "is right"), is_right)?;
+            let sibling = AllocatedNum::alloc(cs.namespace(|| "sibling"), || sibling.ok_or_else(missing))?;
+
+            // left = current + bit * (sibling - current)
+            let left_value = match (is_right, current.value, sibling.get_value()) {
+                (Some(is_right), Some(current), Some(sibling)) => Some(if is_right { sibling } else { current }),
+                _ => None,
+            };
+            let left = AllocatedNum::alloc(cs.namespace(|| "left"), || left_value.ok_or_else(missing))?;
+            cs.enforce(
+                || "select left",
+                |lc| lc + bit.get_variable(),
+                |lc| lc + sibling.get_variable() - &current.lc,
+                |lc| lc + left.get_variable() - &current.lc,
+            );
+            let right = Elt {
+                lc: current.lc.clone() + sibling.get_variable() - left.get_variable(),
+                value: current.value
+                    .zip(sibling.get_value())
+                    .zip(left.get_value())
+                    .map(|((c, s), l)| c + s - l),
+            };
+            current = hash2_gadget(cs.namespace(|| "node"), Elt::from_num(&left), right)?;
+        }
+
+        cs.enforce(
+            || "root matches",
+            |lc| lc + &current.lc - merkle_root.get_variable(),
+            |lc| lc + CS::one(),
+            |lc| lc,
+        );
+        Ok(())
+    }
+}
+
+/// Prueba de propiedad de un NFT
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnershipProof {
+    /// NFT al que se refiere la prueba
+    pub nft_id: String,
+    /// Prueba Groth16 serializada
+    pub proof: Vec<u8>,
+}
+
+impl OwnershipProof {
+    /// Identificador seudónimo: no revela el propietario ni enlaza pruebas distintas
+    pub fn proof_id(&self) -> String {
+        let digest = Sha256::new()
+            .chain_update(b"metaverso-ownership-proof")
+            .chain_update(&self.proof)
+            .finalize();
+        format!("0x{}", encode_hex(&digest))
+    }
+}
+
+/// Parámetros Groth16 del circuito de propiedad
+pub struct OwnershipKeys {
+    params: Parameters<Bls12>,
+}
+
+impl OwnershipKeys {
+    /// Generar parámetros nuevos. Quien ejecuta el setup podría falsificar
+    /// pruebas, así que en producción se cargan los de una ceremonia
+    pub fn setup() -> Result<Self> {
+        let params = groth16::generate_random_parameters::<Bls12, _, _>(OwnershipCircuit::blank(), &mut OsRng)?;
+        Ok(Self { params })
+    }
+
+    /// Leer parámetros serializados
+    pub fn read(bytes: &[u8]) -> Result<Self> {
+        Ok(Self { params: Parameters::read(bytes, true)? })
+    }
+
+    /// Serializar parámetros
+    pub fn write(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.params.write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Clave de verificación serializada (para el frontend)
+    pub fn verifying_key(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.params.vk.write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Verificador de estas claves
+    pub fn verifier(&self) -> OwnershipVerifier {
+        OwnershipVerifier { key: groth16::prepare_verifying_key(&self.params.vk) }
+    }
+
+    /// Generar la prueba de que `owner_secret` es dueño de `nft_id` en `tree`
+    pub fn generate_ownership_proof(&self, tree: &OwnershipTree, nft_id: &str, owner_secret: &[u8; 32]) -> Result<OwnershipProof> {
+        let path = tree.path(nft_id).ok_or_else(|| anyhow!("NFT sin propietario privado: {}", nft_id))?;
+        let secret = secret_scalar(owner_secret);
+        let circuit = OwnershipCircuit {
+            secret: Some(secret),
+            commitment: Some(poseidon_hash1(secret)),
+            nft_tag: Some(nft_tag(nft_id)),
+            merkle_root: Some(tree.root_scalar()),
+            path: path.into_iter().map(|(sibling, is_right)| (Some(sibling), Some(is_right))).collect(),
+        };
+        let proof = groth16::create_random_proof(circuit, &self.params, &mut OsRng)?;
+
+        let mut bytes = Vec::new();
+        proof.write(&mut bytes)?;
+        Ok(OwnershipProof { nft_id: nft_id.to_string(), proof: bytes })
+    }
+}
+
+/// Verificador de pruebas de propiedad
+pub struct OwnershipVerifier {
+    key: PreparedVerifyingKey<Bls12>,
+}
+
+impl OwnershipVerifier {
+    /// Leer una clave de verificación serializada
+    pub fn read(bytes: &[u8]) -> Result<Self> {
+        let key = VerifyingKey::<Bls12>::read(bytes)?;
+        Ok(Self { key: groth16::prepare_verifying_key(&key) })
+    }
+
+    /// Verificar que la prueba demuestra la propiedad de `commitment` bajo `merkle_root`
+    pub fn verify_ownership_proof(&self, proof: &OwnershipProof, commitment: [u8; 32], merkle_root: [u8; 32]) -> bool {
+        let (Some(commitment), Some(merkle_root)) = (scalar_from_bytes(&commitment), scalar_from_bytes(&merkle_root)) else {
+            return false;
+        };
+        let Ok(groth16_proof) = Proof::<Bls12>::read(proof.proof.as_slice()) else {
+            return false;
+        };
+        let inputs = [commitment, nft_tag(&proof.nft_id), merkle_root];
+        groth16::verify_proof(&self.key, &groth16_proof, &inputs).is_ok()
+    }
+}
+
+/// Verificar una prueba de propiedad en el navegador
+#[wasm_bindgen(js_name = verifyOwnershipProof)]
+pub fn verify_ownership_proof_wasm(
+    verifying_key: &[u8],
+    nft_id: &str,
+    proof: &[u8],
+    commitment: &[u8],
+    merkle_root: &[u8],
+) -> Result<bool, JsValue> {
+    let verifier = OwnershipVerifier::read(verifying_key)
+        .map_err(|e| JsValue::from_str(&format!("Clave de verificación inválida: {}", e)))?;
+    let commitment: [u8; 32] = commitment.try_into()
+        .map_err(|_| JsValue::from_str("El commitment debe tener 32 bytes"))?;
+    let merkle_root: [u8; 32] = merkle_root.try_into()
+        .map_err(|_| JsValue::from_str("La raíz debe tener 32 bytes"))?;
+    let proof = OwnershipProof { nft_id: nft_id.to_string(), proof: proof.to_vec() };
+    Ok(verifier.verify_ownership_proof(&proof, commitment, merkle_root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parámetros compartidos: el setup es lo más caro de cada test
+    fn keys() -> &'static OwnershipKeys {
+        static KEYS: OnceLock<OwnershipKeys> = OnceLock::new();
+        KEYS.get_or_init(|| OwnershipKeys::setup().unwrap())
+    }
+
+    /// Árbol con tres NFTs; el secreto `[7; 32]` es dueño de "nft-2"
+    fn ownership_tree() -> (OwnershipTree, [u8; 32]) {
+        let secret = [7u8; 32];
+        let mut tree = OwnershipTree::new();
+        tree.insert("nft-1", owner_commitment(&[1u8; 32])).unwrap();
+        tree.insert("nft-2", owner_commitment(&secret)).unwrap();
+        tree.insert("nft-3", owner_commitment(&[3u8; 32])).unwrap();
+        (tree, secret)
+    }
+
+    #[test]
+    fn valid_proof_verifies() {
+        let (tree, secret) = ownership_tree();
+        let proof = keys().generate_ownership_proof(&tree, "nft-2", &secret).unwrap();
+
+        let verifier = keys().verifier();
+        assert!(verifier.verify_ownership_proof(&proof, owner_commitment(&secret), tree.root()));
+
+        // La clave serializada para el frontend verifica igual
+        let verifier = OwnershipVerifier::read(&keys().verifying_key().unwrap()).unwrap();
+        assert!(verifier.verify_ownership_proof(&proof, owner_commitment(&secret), tree.root()));
+    }
+
+    #[test]
+    fn invalid_proofs_are_rejected() {
+        let (tree, secret) = ownership_tree();
+        let verifier = keys().verifier();
+        let commitment = owner_commitment(&secret);
+        let proof = keys().generate_ownership_proof(&tree, "nft-2", &secret).unwrap();
+
+        // Entradas públicas que no corresponden a la prueba
+        assert!(!verifier.verify_ownership_proof(&proof, owner_commitment(&[1u8; 32]), tree.root()));
+        assert!(!verifier.verify_ownership_proof(&proof, commitment, OwnershipTree::new().root()));
+        let other_nft = OwnershipProof { nft_id: "nft-3".into(), ..proof.clone() };
+        assert!(!verifier.verify_ownership_proof(&other_nft, commitment, tree.root()));
+
+        // Bytes de prueba alterados o truncados
+        let mut tampered = proof.clone();
+        tampered.proof[10] ^= 0x01;
+        assert!(!verifier.verify_ownership_proof(&tampered, commitment, tree.root()));
+        let truncated = OwnershipProof { proof: proof.proof[..proof.proof.len() / 2].to_vec(), ..proof.clone() };
+        assert!(!verifier.verify_ownership_proof(&truncated, commitment, tree.root()));
+
+        // Un secreto ajeno no satisface el circuito: la prueba no verifica
+        let forged = keys().generate_ownership_proof(&tree, "nft-2", &[9u8; 32]).unwrap();
+        assert!(!verifier.verify_ownership_proof(&forged, commitment, tree.root()));
+        assert!(!verifier.verify_ownership_proof(&forged, owner_commitment(&[9u8; 32]), tree.root()));
+    }
+}
@@ -0,0 +1,306 @@
+//! # Arquetipos y listeners de cambios
+//!
+//! El arquetipo de una entidad es el conjunto de tipos de componente que
+//! tiene. Cuando `flush_commands` añade o quita componentes (o crea y
+//! destruye entidades) la entidad pasa de un arquetipo a otro y el ECS avisa
+//! a los `ArchetypeChangeListener` registrados, una vez por entidad y flush
+//! con el arquetipo de partida y el final, en el orden en que cambiaron.
+//!
+//! Los sistemas que recorren un subconjunto de entidades mantienen así sus
+//! listas al día sin recorrer el mundo cada frame. `QueryMembership` es el
+//! caso común: un conjunto ordenado de las entidades que tienen ciertos
+//! componentes, compartido entre el listener y el sistema.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
+
+use super::{ComponentType, EntityId};
+
+/// Bits reservados a los componentes del motor
+const BUILTIN_BITS: u32 = 13;
+
+/// Conjunto de tipos de componente como máscara de bits. Los componentes del
+/// motor tienen un bit propio; los `Custom` se reparten por hash en los bits
+/// restantes, así que dos tipos propios pueden compartir bit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ArchetypeId(u64);
+
+impl ArchetypeId {
+    /// Arquetipo sin componentes (entidades vacías o destruidas)
+    pub const EMPTY: Self = Self(0);
+
+    /// Arquetipo de una lista de componentes
+    pub fn from_components<'a>(components: impl IntoIterator<Item = &'a ComponentType>) -> Self {
+        components.into_iter().fold(Self::EMPTY, |id, component_type| id.with(component_type))
+    }
+
+    /// Arquetipo con un componente más
+    pub fn with(self, component_type: &ComponentType) -> Self {
+        Self(self.0 | component_bit(component_type))
+    }
+
+    /// Arquetipo con un componente menos
+    pub fn without(self, component_type: &ComponentType) -> Self {
+        Self(self.0 & !component_bit(component_type))
+    }
+
+    /// El arquetipo incluye el componente
+    pub fn contains(&self, component_type: &ComponentType) -> bool {
+        self.0 & component_bit(component_type) != 0
+    }
+
+    /// El arquetipo incluye todos los componentes de `other`
+    pub fn contains_all(&self, other: ArchetypeId) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Máscara de bits
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Bit de un tipo de componente
+fn component_bit(component_type: &ComponentType) -> u64 {
+    let index = match component_type {
+        ComponentType::Transform => 0,
+        ComponentType::Mesh => 1,
+        ComponentType::Material => 2,
+        ComponentType::Light => 3,
+        ComponentType::Camera => 4,
+        ComponentType::Physics => 5,
+        ComponentType::Audio => 6,
+        ComponentType::Animation => 7,
+        ComponentType::Script => 8,
+        ComponentType::Network => 9,
+        ComponentType::Decal => 10,
+        ComponentType::Label => 11,
+        ComponentType::AmbientZone => 12,
+        ComponentType::Custom(name) => {
+            // DefaultHasher::new usa siempre las mismas claves: el bit de un
+            // tipo no cambia entre ejecuciones
+            let mut hasher = DefaultHasher::new();
+            name.hash(&mut hasher);
+            BUILTIN_BITS + (hasher.finish() % u64::from(64 - BUILTIN_BITS)) as u32
+        }
+    };
+    1 << index
+}
+
+/// Recibe los cambios de arquetipo de las entidades
+pub trait ArchetypeChangeListener: Send + Sync {
+    /// La entidad pasó de `from` a `to` en el último flush. Crear una
+    /// entidad parte de `ArchetypeId::EMPTY` y destruirla termina en él
+    fn on_entity_moved(&mut self, entity: EntityId, from: ArchetypeId, to: ArchetypeId);
+}
+
+/// Cambio de arquetipo pendiente de notificar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchetypeMove {
+    /// Entidad
+    pub entity: EntityId,
+    /// Arquetipo antes del flush
+    pub from: ArchetypeId,
+    /// Arquetipo después del flush
+    pub to: ArchetypeId,
+}
+
+/// Cambios de un flush, uno por entidad. Los cambios sucesivos de la misma
+/// entidad se funden conservando el arquetipo de partida
+#[derive(Debug, Default)]
+pub struct ArchetypeMoves {
+    moves: Vec<ArchetypeMove>,
+    /// Posición de cada entidad en `moves`
+    index: HashMap<EntityId, usize>,
+}
+
+impl ArchetypeMoves {
+    /// Registrar un cambio de arquetipo
+    pub fn record(&mut self, entity: EntityId, from: ArchetypeId, to: ArchetypeId) {
+        if from == to {
+            return;
+        }
+        match self.index.get(&entity) {
+            Some(&i) => self.moves[i].to = to,
+            None => {
+                self.index.insert(entity, self.moves.len());
+                self.moves.push(ArchetypeMove { entity, from, to });
+            }
+        }
+    }
+
+    /// Cambios efectivos en el orden del primer cambio de cada entidad (los
+    /// que vuelven al arquetipo de partida se descartan)
+    pub fn into_moves(self) -> impl Iterator<Item = ArchetypeMove> {
+        self.moves.into_iter().filter(|pending| pending.from != pending.to)
+    }
+}
+
+/// Entidades que tienen unos componentes, en orden de ID. El sistema lee el
+/// conjunto con `entities` y su listener lo actualiza
+#[derive(Debug, Clone)]
+pub struct QueryMembership {
+    required: ArchetypeId,
+    entities: Arc<RwLock<BTreeSet<EntityId>>>,
+}
+
+impl QueryMembership {
+    /// Consulta de las entidades con todos los componentes de `required`
+    pub fn new(required: &[ComponentType]) -> Self {
+        Self {
+            required: ArchetypeId::from_components(required),
+            entities: Arc::new(RwLock::new(BTreeSet::new())),
+        }
+    }
+
+    /// Entidades que cumplen la consulta, ordenadas por ID
+    pub fn entities(&self) -> Vec<EntityId> {
+        self.entities.read().unwrap().iter().copied().collect()
+    }
+
+    /// Número de entidades
+    pub fn len(&self) -> usize {
+        self.entities.read().unwrap().len()
+    }
+
+    /// No hay entidades
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// El arquetipo cumple la consulta
+    pub fn matches(&self, archetype: ArchetypeId) -> bool {
+        archetype != ArchetypeId::EMPTY && archetype.contains_all(self.required)
+    }
+}
+
+impl ArchetypeChangeListener for QueryMembership {
+    fn on_entity_moved(&mut self, entity: EntityId, from: ArchetypeId, to: ArchetypeId) {
+        match (self.matches(from), self.matches(to)) {
+            (false, true) => {
+                self.entities.write().unwrap().insert(entity);
+            }
+            (true, false) => {
+                self.entities.write().unwrap().remove(&entity);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::{
+        BodyType, CollisionConfig, CollisionShape, ComponentConfig, ECSConfig, ECSSystem, EntityConfig,
+        OptimizationConfig, PhysicsComponent, SystemConfig, TransformComponent,
+    };
+    use glam::{Mat4, Quat, Vec3};
+    use std::sync::Mutex;
+
+    fn ecs_config() -> ECSConfig {
+        ECSConfig {
+            enabled: true,
+            entity_config: EntityConfig { max_entities: 100, entity_pool: false, id_reuse: false },
+            component_config: ComponentConfig {
+                max_components_per_entity: 16,
+                component_cache: false,
+                auto_serialization: false,
+            },
+            system_config: SystemConfig { parallel_execution: false, system_priority: true, hot_reloading: false },
+            optimization_config: OptimizationConfig { cache_friendly: true, memory_pooling: false, batch_processing: false },
+            max_parallel_systems: 1,
+            system_hot_reloading: false,
+            systems_directory: "systems".into(),
+        }
+    }
+
+    /// Listener que anota cada llamada
+    #[derive(Clone, Default)]
+    struct Recorder {
+        calls: Arc<Mutex<Vec<ArchetypeMove>>>,
+    }
+
+    impl Recorder {
+        fn take(&self) -> Vec<ArchetypeMove> {
+            std::mem::take(&mut *self.calls.lock().unwrap())
+        }
+    }
+
+    impl ArchetypeChangeListener for Recorder {
+        fn on_entity_moved(&mut self, entity: EntityId, from: ArchetypeId, to: ArchetypeId) {
+            self.calls.lock().unwrap().push(ArchetypeMove { entity, from, to });
+        }
+    }
+
+    fn transform() -> TransformComponent {
+        TransformComponent {
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+            matrix: Mat4::IDENTITY,
+            parent: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn physics() -> PhysicsComponent {
+        PhysicsComponent {
+            body_type: BodyType::Dynamic,
+            mass: 1.0,
+            velocity: Vec3::ZERO,
+            force: Vec3::ZERO,
+            collision: true,
+            collision_config: CollisionConfig {
+                shape: CollisionShape::Sphere(0.5),
+                filter: 1,
+                material: "default".to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn adding_physics_to_a_transform_entity_fires_one_listener_call() {
+        let mut world = ECSSystem::new(ecs_config());
+        world.initialize().await.unwrap();
+        let recorder = Recorder::default();
+        world.add_archetype_listener(Box::new(recorder.clone()));
+
+        // Crear la entidad y su transformación en el mismo flush es un único cambio
+        let entity = world.create_entity("body".to_string()).await.unwrap();
+        world.add_component(entity, Box::new(transform())).await.unwrap();
+        world.update(0.016).await.unwrap();
+        let transform_only = ArchetypeId::from_components(&[ComponentType::Transform]);
+        assert_eq!(recorder.take(), vec![ArchetypeMove { entity, from: ArchetypeId::EMPTY, to: transform_only }]);
+
+        world.add_component(entity, Box::new(physics())).await.unwrap();
+        world.update(0.016).await.unwrap();
+        assert_eq!(recorder.take(), vec![ArchetypeMove {
+            entity,
+            from: transform_only,
+            to: transform_only.with(&ComponentType::Physics),
+        }]);
+        assert_eq!(world.get_stats().archetype_changes_per_frame, 1);
+
+        // Un frame sin cambios no avisa a nadie
+        world.update(0.016).await.unwrap();
+        assert!(recorder.take().is_empty());
+        assert_eq!(world.get_stats().archetype_changes_per_frame, 0);
+    }
+
+    #[test]
+    fn query_membership_follows_the_archetype() {
+        let mut bodies = QueryMembership::new(&[ComponentType::Transform, ComponentType::Physics]);
+        let transform_only = ArchetypeId::from_components(&[ComponentType::Transform]);
+        let with_physics = transform_only.with(&ComponentType::Physics);
+
+        bodies.on_entity_moved(7, ArchetypeId::EMPTY, transform_only);
+        assert!(bodies.is_empty());
+        bodies.on_entity_moved(7, transform_only, with_physics);
+        assert_eq!(bodies.entities(), vec![7]);
+        bodies.on_entity_moved(7, with_physics, ArchetypeId::EMPTY);
+        assert!(bodies.is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! # Recarga de sistemas
+//!
+//! Recarga en caliente de sistemas del ECS compilados como bibliotecas
+//! dinámicas (`.so`, `.dylib`, `.dll`). Cada biblioteca del directorio
+//! vigilado exporta:
+//!
+//! ```ignore
+//! #[no_mangle]
+//! pub fn create_system() -> Box<dyn metaverso_engine::ecs::ECSSystem> {
+//!     Box::new(MySystem::new())
+//! }
+//! ```
+//!
+//! El símbolo usa el ABI de Rust: la biblioteca debe compilarse con el mismo
+//! compilador y la misma versión del motor que el ejecutable. Si ya hay un
+//! sistema con el nombre del creado se sustituye con
+//! `ECSSystem::reload_system`; si no, se añade.
+//!
+//! El loader de bibliotecas devuelve la misma instancia al abrir dos veces
+//! una ruta, así que cada versión se copia antes a un fichero con nombre
+//! propio. La biblioteca se mantiene abierta mientras viva su sistema.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+use super::ECSSystem;
+
+/// Intervalo mínimo entre dos exploraciones del directorio vigilado
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Símbolo que exporta cada biblioteca de sistema
+const CREATE_SYSTEM_SYMBOL: &[u8] = b"create_system";
+
+/// Firma de `create_system`
+type CreateSystem = fn() -> Box<dyn ECSSystem>;
+
+/// Versión de las copias de bibliotecas cargadas
+static LIBRARY_VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// Sistema creado por una biblioteca dinámica
+pub struct LoadedSystem {
+    /// Sistema creado por `create_system`
+    pub system: Box<dyn ECSSystem>,
+    /// Biblioteca que contiene el código del sistema
+    pub library: libloading::Library,
+}
+
+/// Cargar la biblioteca de `path` y crear su sistema
+pub fn load_system(path: &Path) -> Result<LoadedSystem> {
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("system");
+    let version = LIBRARY_VERSION.fetch_add(1, Ordering::Relaxed);
+    let directory = std::env::temp_dir().join("metaverso-systems");
+    std::fs::create_dir_all(&directory)?;
+    let copy = directory.join(format!(
+        "{}-{}-{}.{}",
+        stem,
+        std::process::id(),
+        version,
+        std::env::consts::DLL_EXTENSION,
+    ));
+    std::fs::copy(path, &copy)?;
+
+    // SAFETY: la biblioteca es un sistema del motor compilado con el mismo
+    // compilador; sus inicializadores no tienen efectos fuera del sistema
+    let library = unsafe { libloading::Library::new(&copy) };
+    // En Unix la copia abierta se puede borrar ya; en Windows queda en temp
+    let _ = std::fs::remove_file(&copy);
+    let library = library.map_err(|e| anyhow!("No se puede cargar {:?}: {}", path, e))?;
+
+    // SAFETY: `create_system` tiene la firma `CreateSystem` por contrato
+    let system = unsafe {
+        let create: libloading::Symbol<CreateSystem> = library
+            .get(CREATE_SYSTEM_SYMBOL)
+            .map_err(|e| anyhow!("{:?} no exporta create_system: {}", path, e))?;
+        create()
+    };
+    Ok(LoadedSystem { system, library })
+}
+
+/// Vigilancia de un directorio de bibliotecas de sistemas por fecha de
+/// modificación
+#[derive(Debug)]
+pub struct SystemWatcher {
+    /// Directorio vigilado
+    directory: PathBuf,
+    /// Fecha de modificación vista por fichero
+    modified: HashMap<PathBuf, SystemTime>,
+    /// Intervalo mínimo entre exploraciones
+    poll_interval: Duration,
+    /// Última exploración
+    last_poll: Option<Instant>,
+}
+
+impl SystemWatcher {
+    /// Vigilar `directory`. La primera exploración entrega todas las
+    /// bibliotecas que ya contiene
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            modified: HashMap::new(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            last_poll: None,
+        }
+    }
+
+    /// Cambiar el intervalo entre exploraciones
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Directorio vigilado
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Bibliotecas nuevas o modificadas desde la última exploración. No
+    /// explora si no ha pasado el intervalo
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        if self.last_poll.is_some_and(|last| now.duration_since(last) < self.poll_interval) {
+            return Vec::new();
+        }
+        self.last_poll = Some(now);
+
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("No se puede leer el directorio de sistemas {:?}: {}", self.directory, e);
+                return Vec::new();
+            }
+        };
+
+        let mut changed = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some(std::env::consts::DLL_EXTENSION) {
+                continue;
+            }
+            let Ok(modified) = entry.metadata().and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+            if self.modified.insert(path.clone(), modified) != Some(modified) {
+                changed.push(path);
+            }
+        }
+        changed.sort();
+        changed
+    }
+
+    /// Volver a entregar `path` en la próxima exploración (p. ej. si el
+    /// linker aún lo estaba escribiendo)
+    pub fn retry(&mut self, path: &Path) {
+        self.modified.remove(path);
+    }
+}
@@ -3,12 +3,23 @@
 //! Sistema ECS optimizado para el metaverso 3D descentralizado.
 //! Proporciona gestión eficiente de entidades, componentes y sistemas.
 
+pub mod archetype;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hot_reload;
+pub mod registry;
+pub mod snapshot;
+
 use std::collections::HashMap;
 use std::any::{Any, TypeId};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
-use tracing::{info, error, debug};
+use tracing::{info, error, debug, warn};
+
+/// Derive que implementa `Component` (ver `engine_macros`)
+pub use engine_macros::Component;
+
+use archetype::{ArchetypeChangeListener, ArchetypeId, ArchetypeMoves, QueryMembership};
 
 /// ID único de entidad
 pub type EntityId = u64;
@@ -76,6 +87,10 @@ pub enum EventType {
     ComponentRemoved(EntityId, TypeId),
     SystemStarted(String),
     SystemStopped(String),
+    /// Una entidad entró en una zona de trigger de física
+    TriggerEntered(crate::physics::triggers::TriggerZoneId, EntityId),
+    /// Una entidad salió de una zona de trigger de física
+    TriggerExited(crate::physics::triggers::TriggerZoneId, EntityId),
     Custom(String),
 }
 
@@ -316,6 +331,11 @@ impl World {
     pub fn get_entity(&self, id: EntityId) -> Option<&Entity> {
         self.entities.get(&id)
     }
+
+    /// Sistema de eventos compartido (suscripciones y eventos de juego)
+    pub fn events(&self) -> Arc<RwLock<EventSystem>> {
+        self.events.clone()
+    }
 }
 
 impl EventSystem {
@@ -756,10 +776,33 @@ pub struct ECSSystem {
     components: Arc<RwLock<HashMap<ComponentType, HashMap<EntityId, Box<dyn Component>>>>>,
     /// Sistemas del ECS
     systems: Vec<Box<dyn ECSSystem>>,
+    /// Niveles del DAG de dependencias (índices en `systems`), None si hay que recalcularlo
+    schedule: Option<Vec<Vec<usize>>>,
+    /// Pool de hilos para sistemas independientes (None = ejecución secuencial)
+    job_system: Option<crate::utils::JobSystem>,
     /// Cola de comandos
     command_queue: VecDeque<ECSCommand>,
+    /// Listeners de cambios de arquetipo
+    archetype_listeners: Vec<Box<dyn ArchetypeChangeListener>>,
     /// Estadísticas del sistema
     stats: ECSStats,
+    /// Mundo raíz de la partición en islas (solo lectura desde la isla)
+    root_world: Option<crate::scene::partition::SharedRootWorld>,
+    /// Runtime de scripts Rhai para el `ScriptSystem`
+    script_runtime: Option<crate::wasm::SharedScriptRuntime>,
+    /// Delta del frame en curso, en segundos
+    delta_time: f32,
+    /// Extremo de envío de las sustituciones de sistemas
+    reload_sender: SystemReloader,
+    /// Sustituciones pendientes hasta el final del frame en curso
+    reload_receiver: std::sync::Mutex<std::sync::mpsc::Receiver<(String, Box<dyn ECSSystem>)>>,
+    /// Vigilancia de las bibliotecas de sistemas
+    #[cfg(not(target_arch = "wasm32"))]
+    system_watcher: Option<hot_reload::SystemWatcher>,
+    /// Bibliotecas de los sistemas cargados en caliente, por nombre de
+    /// sistema. Se declaran tras `systems` para liberarse después
+    #[cfg(not(target_arch = "wasm32"))]
+    system_libraries: HashMap<String, libloading::Library>,
     /// Estado del sistema
     running: bool,
 }
@@ -777,8 +820,31 @@ pub struct ECSConfig {
     pub system_config: SystemConfig,
     /// Configuración de optimización
     pub optimization_config: OptimizationConfig,
+    /// Máximo de sistemas ejecutándose en paralelo
+    #[serde(default = "default_max_parallel_systems")]
+    pub max_parallel_systems: usize,
+    /// Recargar los sistemas compilados como bibliotecas dinámicas al
+    /// reconstruirlas (ver `hot_reload`)
+    #[serde(default)]
+    pub system_hot_reloading: bool,
+    /// Directorio vigilado con `system_hot_reloading`
+    #[serde(default = "default_systems_directory")]
+    pub systems_directory: std::path::PathBuf,
+}
+
+/// Paralelismo por defecto: un sistema por núcleo
+fn default_max_parallel_systems() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+fn default_systems_directory() -> std::path::PathBuf {
+    std::path::PathBuf::from("systems")
 }
 
+/// Canal para pedir la sustitución de un sistema (nombre, sistema nuevo)
+/// desde otro hilo; se aplica entre dos frames
+pub type SystemReloader = std::sync::mpsc::Sender<(String, Box<dyn ECSSystem>)>;
+
 /// Configuración de entidades
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EntityConfig {
@@ -854,6 +920,14 @@ pub struct EntityState {
     pub locked: bool,
 }
 
+/// Siguiente ID de entidad, compartido por todos los mundos
+static ENTITY_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
+/// Generar un ID de entidad fuera de un mundo (p. ej. desde un script)
+pub(crate) fn next_entity_id() -> EntityId {
+    ENTITY_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
 /// Tipo de componente
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ComponentType {
@@ -868,6 +942,10 @@ pub enum ComponentType {
     Script,
     Network,
     Custom(String),
+    // Después de Custom para no cambiar el índice serializado de las anteriores
+    Decal,
+    Label,
+    AmbientZone,
 }
 
 /// Trait para componentes
@@ -882,8 +960,14 @@ pub trait Component: Send + Sync {
     fn deserialize(data: &[u8]) -> Result<Box<dyn Component>>;
 }
 
+/// Tipo de componente sin instancia (lo implementa `#[derive(Component)]`)
+pub trait ComponentTypeOf {
+    /// Tipo de componente
+    fn static_type() -> ComponentType;
+}
+
 /// Componente de transformación
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
 pub struct TransformComponent {
     /// Posición
     pub position: Vec3,
@@ -899,27 +983,8 @@ pub struct TransformComponent {
     pub children: Vec<EntityId>,
 }
 
-impl Component for TransformComponent {
-    fn get_type(&self) -> ComponentType {
-        ComponentType::Transform
-    }
-
-    fn clone_box(&self) -> Box<dyn Component> {
-        Box::new(self.clone())
-    }
-
-    fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
-    }
-
-    fn deserialize(data: &[u8]) -> Result<Box<dyn Component>> {
-        let component: TransformComponent = bincode::deserialize(data)?;
-        Ok(Box::new(component))
-    }
-}
-
 /// Componente de malla
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
 pub struct MeshComponent {
     /// ID de la malla
     pub mesh_id: String,
@@ -933,31 +998,118 @@ pub struct MeshComponent {
     pub indices: Vec<u32>,
     /// Material
     pub material_id: Option<String>,
-    /// LOD
+    /// Nivel de LOD mínimo
     pub lod_level: u32,
+    /// Index buffers reducidos por nivel de LOD (el nivel 1 es el primero)
+    #[serde(default)]
+    pub lod_indices: Vec<Vec<u32>>,
+    /// Articulaciones que influyen en cada vértice (índices en la paleta del skin)
+    #[serde(default)]
+    pub joint_indices: Vec<[u16; 4]>,
+    /// Peso de cada articulación por vértice
+    #[serde(default)]
+    pub joint_weights: Vec<[f32; 4]>,
+    /// Capas de render de la malla: los decals solo se proyectan sobre las
+    /// mallas que comparten alguna capa con su máscara
+    #[serde(default = "default_render_layers")]
+    pub render_layers: u32,
+}
+
+/// Capa de render por defecto de las mallas
+pub const RENDER_LAYER_DEFAULT: u32 = 1 << 0;
+/// Capa de render de los avatares
+pub const RENDER_LAYER_AVATAR: u32 = 1 << 1;
+/// Capa de render del terreno
+pub const RENDER_LAYER_TERRAIN: u32 = 1 << 2;
+
+fn default_render_layers() -> u32 {
+    RENDER_LAYER_DEFAULT
+}
+
+/// Componente de decal: proyector de caja que pinta una textura sobre las
+/// superficies que atraviesa. La caja es el cubo unidad centrado en la
+/// entidad escalado por `size` y proyecta a lo largo de -Z local; la textura
+/// cubre el plano XY
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
+pub struct DecalComponent {
+    /// Textura proyectada
+    pub texture_id: String,
+    /// Tamaño de la caja (X e Y cubren la textura, Z es la profundidad de proyección)
+    pub size: Vec3,
+    /// Color que multiplica a la textura (alpha es la opacidad)
+    pub color: Vec4,
+    /// Modo de mezcla con la superficie
+    pub blend_mode: DecalBlendMode,
+    /// Distancia a la cámara a la que el decal desaparece (0 sin desvanecer)
+    pub fade_distance: f32,
+    /// Capas de render que recibe el decal
+    pub layer_mask: u32,
+    /// Prioridad: con más decals que el presupuesto se descartan los de menor prioridad
+    pub priority: i32,
+}
+
+/// Modo de mezcla de un decal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DecalBlendMode {
+    /// Sustituye el albedo según el alpha (carteles, pintadas)
+    #[default]
+    Alpha,
+    /// Multiplica el albedo (suciedad, impactos)
+    Multiply,
+    /// Suma luz emisiva (límites de parcela, marcas luminosas)
+    Additive,
+}
+
+/// Componente de etiqueta: texto en mundo sobre la entidad (nombres de
+/// avatares, carteles). Se ancla en el centro de la parte superior de la caja
+/// de la entidad, o en su posición si no tiene malla
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
+pub struct LabelComponent {
+    /// Texto (`\n` separa líneas)
+    pub text: String,
+    /// Estilo del texto
+    pub style: TextStyle,
+    /// Desplazamiento en mundo desde el anclaje
+    #[serde(default)]
+    pub offset: Vec3,
+}
+
+/// Estilo de un texto
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextStyle {
+    /// Fuente cargada en el renderer
+    pub font_id: String,
+    /// Altura de letra: píxeles, o metros con `scale_with_distance`
+    pub size: f32,
+    /// Color del relleno
+    pub color: Vec4,
+    /// Color del contorno
+    pub outline_color: Vec4,
+    /// Grosor del contorno como fracción de la altura de letra (0 sin contorno)
+    pub outline_width: f32,
+    /// Las etiquetas en mundo encogen con la distancia como la geometría
+    pub scale_with_distance: bool,
+    /// Distancia a la cámara a partir de la cual no se dibuja (0 sin límite)
+    pub max_distance: f32,
 }
 
-impl Component for MeshComponent {
-    fn get_type(&self) -> ComponentType {
-        ComponentType::Mesh
-    }
-
-    fn clone_box(&self) -> Box<dyn Component> {
-        Box::new(self.clone())
-    }
-
-    fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
-    }
-
-    fn deserialize(data: &[u8]) -> Result<Box<dyn Component>> {
-        let component: MeshComponent = bincode::deserialize(data)?;
-        Ok(Box::new(component))
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            font_id: "default".to_string(),
+            size: 16.0,
+            color: Vec4::ONE,
+            outline_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            outline_width: 0.0,
+            scale_with_distance: false,
+            max_distance: 0.0,
+        }
     }
 }
 
 /// Componente de material
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
 pub struct MaterialComponent {
     /// ID del material
     pub material_id: String,
@@ -979,27 +1131,8 @@ pub enum MaterialType {
     Custom(String),
 }
 
-impl Component for MaterialComponent {
-    fn get_type(&self) -> ComponentType {
-        ComponentType::Material
-    }
-
-    fn clone_box(&self) -> Box<dyn Component> {
-        Box::new(self.clone())
-    }
-
-    fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
-    }
-
-    fn deserialize(data: &[u8]) -> Result<Box<dyn Component>> {
-        let component: MaterialComponent = bincode::deserialize(data)?;
-        Ok(Box::new(component))
-    }
-}
-
 /// Componente de luz
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
 pub struct LightComponent {
     /// Tipo de luz
     pub light_type: LightType,
@@ -1037,27 +1170,8 @@ pub struct ShadowConfig {
     pub soft_shadows: bool,
 }
 
-impl Component for LightComponent {
-    fn get_type(&self) -> ComponentType {
-        ComponentType::Light
-    }
-
-    fn clone_box(&self) -> Box<dyn Component> {
-        Box::new(self.clone())
-    }
-
-    fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
-    }
-
-    fn deserialize(data: &[u8]) -> Result<Box<dyn Component>> {
-        let component: LightComponent = bincode::deserialize(data)?;
-        Ok(Box::new(component))
-    }
-}
-
 /// Componente de cámara
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
 pub struct CameraComponent {
     /// Tipo de cámara
     pub camera_type: CameraType,
@@ -1080,29 +1194,12 @@ pub struct CameraComponent {
 pub enum CameraType {
     Perspective,
     Orthographic,
-}
-
-impl Component for CameraComponent {
-    fn get_type(&self) -> ComponentType {
-        ComponentType::Camera
-    }
-
-    fn clone_box(&self) -> Box<dyn Component> {
-        Box::new(self.clone())
-    }
-
-    fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
-    }
-
-    fn deserialize(data: &[u8]) -> Result<Box<dyn Component>> {
-        let component: CameraComponent = bincode::deserialize(data)?;
-        Ok(Box::new(component))
-    }
+    /// Proyección dada en `projection` (p. ej. la asimétrica de cada ojo en VR)
+    Explicit,
 }
 
 /// Componente de física
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
 pub struct PhysicsComponent {
     /// Tipo de cuerpo
     pub body_type: BodyType,
@@ -1146,27 +1243,8 @@ pub enum CollisionShape {
     Mesh(Vec<Vec3>),
 }
 
-impl Component for PhysicsComponent {
-    fn get_type(&self) -> ComponentType {
-        ComponentType::Physics
-    }
-
-    fn clone_box(&self) -> Box<dyn Component> {
-        Box::new(self.clone())
-    }
-
-    fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
-    }
-
-    fn deserialize(data: &[u8]) -> Result<Box<dyn Component>> {
-        let component: PhysicsComponent = bincode::deserialize(data)?;
-        Ok(Box::new(component))
-    }
-}
-
 /// Componente de audio
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
 pub struct AudioComponent {
     /// ID del audio
     pub audio_id: String,
@@ -1182,6 +1260,45 @@ pub struct AudioComponent {
     pub spatial: bool,
     /// Configuración espacial
     pub spatial_config: SpatialAudioConfig,
+    /// Efectos aplicados en orden antes de espacializar
+    #[serde(default)]
+    pub effect_chain: Vec<crate::audio::dsp::AudioEffect>,
+    /// Cambiar el pitch con la velocidad relativa al oyente
+    #[serde(default = "default_audio_doppler")]
+    pub doppler: bool,
+}
+
+fn default_audio_doppler() -> bool {
+    true
+}
+
+/// Zona de sonido ambiental: capas en bucle que suenan a pleno con el
+/// oyente dentro de la caja centrada en la entidad y se funden a lo largo de
+/// `blend_distance` fuera de ella
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
+pub struct AmbientZoneComponent {
+    /// Semiejes de la caja alrededor de la posición de la entidad
+    pub half_extents: Vec3,
+    /// Distancia fuera de la caja a lo largo de la que se funde
+    pub blend_distance: f32,
+    /// Prioridad frente a otras zonas cuando se llega al límite de voces
+    #[serde(default)]
+    pub priority: u32,
+    /// Capas del ambiente
+    pub layers: Vec<AmbientLayer>,
+}
+
+/// Capa de una zona ambiental
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmbientLayer {
+    /// Clip en bucle
+    pub clip_id: String,
+    /// Volumen de la capa
+    pub volume: f32,
+    /// Ganancia según la hora del día: pares (hora normalizada en [0, 1),
+    /// ganancia) interpolados de forma cíclica. Vacía suena todo el día
+    #[serde(default)]
+    pub day_curve: Vec<(f32, f32)>,
 }
 
 /// Tipo de audio
@@ -1202,29 +1319,17 @@ pub struct SpatialAudioConfig {
     pub max_distance: f32,
     /// Rolloff
     pub rolloff: f32,
-}
-
-impl Component for AudioComponent {
-    fn get_type(&self) -> ComponentType {
-        ComponentType::Audio
-    }
-
-    fn clone_box(&self) -> Box<dyn Component> {
-        Box::new(self.clone())
-    }
-
-    fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
-    }
-
-    fn deserialize(data: &[u8]) -> Result<Box<dyn Component>> {
-        let component: AudioComponent = bincode::deserialize(data)?;
-        Ok(Box::new(component))
-    }
+    /// Distancia bajo la cual se aplica HRTF y reverb completos
+    pub near_distance: f32,
+    /// Distancia a partir de la cual la fuente deja de procesarse
+    pub far_distance: f32,
+    /// Ganancia a `max_distance` y más allá
+    #[serde(default)]
+    pub min_gain: f32,
 }
 
 /// Componente de animación
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
 pub struct AnimationComponent {
     /// ID de la animación
     pub animation_id: String,
@@ -1308,27 +1413,8 @@ pub enum EasingType {
     Custom,
 }
 
-impl Component for AnimationComponent {
-    fn get_type(&self) -> ComponentType {
-        ComponentType::Animation
-    }
-
-    fn clone_box(&self) -> Box<dyn Component> {
-        Box::new(self.clone())
-    }
-
-    fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
-    }
-
-    fn deserialize(data: &[u8]) -> Result<Box<dyn Component>> {
-        let component: AnimationComponent = bincode::deserialize(data)?;
-        Ok(Box::new(component))
-    }
-}
-
 /// Componente de script
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
 pub struct ScriptComponent {
     /// ID del script
     pub script_id: String,
@@ -1338,6 +1424,19 @@ pub struct ScriptComponent {
     pub code: String,
     /// Estado del script
     pub state: ScriptState,
+    /// Llamadas pendientes a funciones del script (p. ej. eventos de
+    /// animación), en orden
+    #[serde(default)]
+    pub pending_calls: Vec<ScriptCall>,
+}
+
+/// Llamada a una función de un script
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptCall {
+    /// Nombre de la función
+    pub function_name: String,
+    /// Parámetros
+    pub parameters: Vec<serde_json::Value>,
 }
 
 /// Tipo de script
@@ -1347,6 +1446,8 @@ pub enum ScriptType {
     TypeScript,
     Rust,
     WASM,
+    /// Script Rhai ejecutado por el runtime de scripts del sistema WASM
+    Rhai,
 }
 
 /// Estado del script
@@ -1360,27 +1461,8 @@ pub struct ScriptState {
     pub error: Option<String>,
 }
 
-impl Component for ScriptComponent {
-    fn get_type(&self) -> ComponentType {
-        ComponentType::Script
-    }
-
-    fn clone_box(&self) -> Box<dyn Component> {
-        Box::new(self.clone())
-    }
-
-    fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
-    }
-
-    fn deserialize(data: &[u8]) -> Result<Box<dyn Component>> {
-        let component: ScriptComponent = bincode::deserialize(data)?;
-        Ok(Box::new(component))
-    }
-}
-
 /// Componente de red
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
 pub struct NetworkComponent {
     /// ID de red
     pub network_id: String,
@@ -1423,25 +1505,6 @@ pub struct NetworkConfig {
     pub protocol: String,
 }
 
-impl Component for NetworkComponent {
-    fn get_type(&self) -> ComponentType {
-        ComponentType::Network
-    }
-
-    fn clone_box(&self) -> Box<dyn Component> {
-        Box::new(self.clone())
-    }
-
-    fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
-    }
-
-    fn deserialize(data: &[u8]) -> Result<Box<dyn Component>> {
-        let component: NetworkComponent = bincode::deserialize(data)?;
-        Ok(Box::new(component))
-    }
-}
-
 /// Comando del ECS
 #[derive(Debug, Clone)]
 pub enum ECSCommand {
@@ -1451,16 +1514,99 @@ pub enum ECSCommand {
     RemoveComponent(EntityId, ComponentType),
     UpdateComponent(EntityId, ComponentType, Box<dyn Component>),
     SetEntityState(EntityId, EntityState),
+    SetNetworkOwner(EntityId, Option<String>, u64),
+    ApplyRootMotion(EntityId, Vec3, Quat),
+    SetCameraPose(EntityId, Vec3, Quat, f32),
+    /// Sin efecto (un comando anulado al fusionar ediciones concurrentes)
+    Noop,
+}
+
+/// Buffer de comandos de un sistema. Durante `execute` los sistemas solo
+/// leen el mundo y encolan aquí sus cambios; `ECSSystem::flush_commands`
+/// los aplica al final del frame bajo un único bloqueo de escritura
+#[derive(Debug, Default)]
+pub struct CommandBuffer {
+    commands: Vec<ECSCommand>,
+}
+
+impl CommandBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encolar un comando arbitrario
+    pub fn push(&mut self, command: ECSCommand) {
+        self.commands.push(command);
+    }
+
+    /// Crear una entidad ya construida (con ID de `reserve_entity_id`)
+    pub fn spawn_entity(&mut self, entity: Entity) {
+        self.push(ECSCommand::CreateEntity(entity));
+    }
+
+    /// Destruir entidad
+    pub fn destroy_entity(&mut self, entity_id: EntityId) {
+        self.push(ECSCommand::DestroyEntity(entity_id));
+    }
+
+    /// Agregar componente
+    pub fn add_component(&mut self, entity_id: EntityId, component: Box<dyn Component>) {
+        self.push(ECSCommand::AddComponent(entity_id, component));
+    }
+
+    /// Remover componente
+    pub fn remove_component(&mut self, entity_id: EntityId, component_type: ComponentType) {
+        self.push(ECSCommand::RemoveComponent(entity_id, component_type));
+    }
+
+    /// Reemplazar un componente existente
+    pub fn update_component(&mut self, entity_id: EntityId, component: Box<dyn Component>) {
+        let component_type = component.get_type();
+        self.push(ECSCommand::UpdateComponent(entity_id, component_type, component));
+    }
+
+    /// Cambiar el estado de una entidad
+    pub fn set_entity_state(&mut self, entity_id: EntityId, state: EntityState) {
+        self.push(ECSCommand::SetEntityState(entity_id, state));
+    }
+
+    /// Número de comandos encolados
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Vaciar el buffer devolviendo sus comandos en orden de encolado
+    pub fn drain(&mut self) -> std::vec::Drain<'_, ECSCommand> {
+        self.commands.drain(..)
+    }
 }
 
 /// Sistema del ECS
 pub trait ECSSystem: Send + Sync {
-    /// Ejecutar sistema
-    fn execute(&self, world: &ECSSystem) -> Result<()>;
+    /// Ejecutar sistema. El mundo es de solo lectura; los cambios se
+    /// encolan en `commands` y se aplican al final del frame
+    fn execute(&self, world: &ECSSystem, commands: &mut CommandBuffer) -> Result<()>;
     /// Obtener prioridad
     fn get_priority(&self) -> u32;
     /// Obtener nombre
     fn get_name(&self) -> &str;
+    /// Nombres de los sistemas que deben ejecutarse antes que este
+    fn dependencies(&self) -> Vec<String> {
+        Vec::new()
+    }
+    /// Preparar el sistema antes de su primer frame (p. ej. al recargarlo)
+    fn initialize(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Liberar los recursos del sistema antes de retirarlo (p. ej. al
+    /// sustituirlo por una versión recargada)
+    fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Estadísticas del ECS
@@ -1478,19 +1624,26 @@ pub struct ECSStats {
     pub memory_usage: usize,
     /// Comandos por frame
     pub commands_per_frame: usize,
+    /// Entidades que cambiaron de arquetipo en el frame
+    #[serde(default)]
+    pub archetype_changes_per_frame: u32,
 }
 
 impl ECSSystem {
     /// Crear nuevo sistema ECS
     pub fn new(config: ECSConfig) -> Self {
         info!("Inicializando sistema ECS");
+        let (reload_sender, reload_receiver) = std::sync::mpsc::channel();
         
         Self {
             config,
             entities: Arc::new(RwLock::new(HashMap::new())),
             components: Arc::new(RwLock::new(HashMap::new())),
             systems: Vec::new(),
+            schedule: None,
+            job_system: None,
             command_queue: VecDeque::new(),
+            archetype_listeners: Vec::new(),
             stats: ECSStats {
                 entity_count: 0,
                 component_count: 0,
@@ -1498,11 +1651,47 @@ impl ECSSystem {
                 execution_time: 0.0,
                 memory_usage: 0,
                 commands_per_frame: 0,
+                archetype_changes_per_frame: 0,
             },
+            root_world: None,
+            script_runtime: None,
+            delta_time: 0.0,
+            reload_sender,
+            reload_receiver: std::sync::Mutex::new(reload_receiver),
+            #[cfg(not(target_arch = "wasm32"))]
+            system_watcher: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            system_libraries: HashMap::new(),
             running: false,
         }
     }
 
+    /// Enlazar el mundo raíz compartido entre islas
+    pub fn set_root_world(&mut self, root: Option<crate::scene::partition::SharedRootWorld>) {
+        self.root_world = root;
+    }
+
+    /// Singletons del mundo raíz (hora del servidor, iluminación global).
+    /// None si el mundo no es una isla de una partición
+    pub fn root_world(&self) -> Option<std::sync::RwLockReadGuard<'_, crate::scene::partition::RootWorld>> {
+        self.root_world.as_ref().map(|root| root.read().unwrap())
+    }
+
+    /// Enlazar el runtime de scripts Rhai del sistema WASM
+    pub fn set_script_runtime(&mut self, runtime: Option<crate::wasm::SharedScriptRuntime>) {
+        self.script_runtime = runtime;
+    }
+
+    /// Runtime de scripts Rhai, si hay uno enlazado
+    pub fn script_runtime(&self) -> Option<crate::wasm::SharedScriptRuntime> {
+        self.script_runtime.clone()
+    }
+
+    /// Delta del frame en curso, en segundos
+    pub fn delta_time(&self) -> f32 {
+        self.delta_time
+    }
+
     /// Inicializar sistema
     pub async fn initialize(&mut self) -> Result<()> {
         info!("Inicializando sistema ECS");
@@ -1514,6 +1703,19 @@ impl ECSSystem {
 
         // Inicializar sistemas por defecto
         self.initialize_default_systems().await?;
+
+        // El hilo que ejecuta los sistemas también toma jobs mientras espera
+        let max_parallel = self.config.max_parallel_systems;
+        if self.config.system_config.parallel_execution && max_parallel > 1 {
+            self.job_system = Some(crate::utils::JobSystem::new(max_parallel - 1));
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.config.system_hot_reloading {
+            let watcher = hot_reload::SystemWatcher::new(self.config.systems_directory.clone());
+            info!("Vigilando sistemas en {:?}", watcher.directory());
+            self.system_watcher = Some(watcher);
+        }
         
         self.running = true;
         info!("Sistema ECS inicializado correctamente");
@@ -1527,10 +1729,14 @@ impl ECSSystem {
         self.add_system(Box::new(TransformSystem::new()));
         
         // Sistema de renderizado
-        self.add_system(Box::new(RenderSystem::new()));
+        let render_system = RenderSystem::new();
+        self.add_archetype_listener(Box::new(render_system.batch_listener()));
+        self.add_system(Box::new(render_system));
         
         // Sistema de física
-        self.add_system(Box::new(PhysicsSystem::new()));
+        let physics_system = PhysicsSystem::new();
+        self.add_archetype_listener(Box::new(physics_system.body_listener()));
+        self.add_system(Box::new(physics_system));
         
         // Sistema de animación
         self.add_system(Box::new(AnimationSystem::new()));
@@ -1555,66 +1761,298 @@ impl ECSSystem {
         }
 
         let start_time = std::time::Instant::now();
+        self.delta_time = delta_time;
+
+        // Comandos de la API encolados desde el frame anterior
+        self.stats.archetype_changes_per_frame = 0;
+        let mut commands = self.flush_commands();
 
-        // Procesar comandos
-        self.process_commands().await?;
+        // Ningún sistema se está ejecutando: es el momento de sustituirlos
+        self.apply_system_reloads();
 
-        // Ejecutar sistemas
+        // Ejecutar sistemas; sus cambios quedan en buffers de comandos
         self.execute_systems().await?;
 
+        // Aplicar los cambios de los sistemas de una vez al final del frame
+        commands += self.flush_commands();
+        self.stats.commands_per_frame = commands;
+
         // Actualizar estadísticas
         self.update_stats(start_time.elapsed().as_secs_f32());
 
         Ok(())
     }
 
-    /// Procesar comandos
-    async fn process_commands(&mut self) -> Result<()> {
-        let mut commands = std::mem::take(&mut self.command_queue);
-        
-        for command in commands.drain(..) {
-            match command {
-                ECSCommand::CreateEntity(entity) => {
-                    self.create_entity_internal(entity).await?;
+    /// Aplicar los comandos encolados, tanto los de la API pública como los
+    /// de los buffers de los sistemas, bajo un único bloqueo de escritura de
+    /// entidades y componentes. Después se avisa a los listeners de las
+    /// entidades que cambiaron de arquetipo. Devuelve el número de comandos
+    /// aplicados
+    pub fn flush_commands(&mut self) -> usize {
+        if self.command_queue.is_empty() {
+            return 0;
+        }
+
+        let commands = std::mem::take(&mut self.command_queue);
+        let count = commands.len();
+        let mut moves = ArchetypeMoves::default();
+
+        {
+            let mut entities = self.entities.write().unwrap();
+            let mut components = self.components.write().unwrap();
+            for command in commands {
+                Self::apply_command(&mut entities, &mut components, &mut moves, command);
+            }
+
+            self.stats.entity_count = entities.len();
+            self.stats.component_count = components.values().map(|m| m.len()).sum();
+        }
+
+        for change in moves.into_moves() {
+            for listener in &mut self.archetype_listeners {
+                listener.on_entity_moved(change.entity, change.from, change.to);
+            }
+            self.stats.archetype_changes_per_frame += 1;
+        }
+        count
+    }
+
+    /// Registrar un listener de cambios de arquetipo. Las entidades que ya
+    /// existen se le notifican como recién creadas
+    pub fn add_archetype_listener(&mut self, mut listener: Box<dyn ArchetypeChangeListener>) {
+        let entities = self.entities.read().unwrap();
+        let mut existing: Vec<&Entity> = entities.values().collect();
+        existing.sort_by_key(|entity| entity.id);
+        for entity in existing {
+            let archetype = ArchetypeId::from_components(&entity.components);
+            if archetype != ArchetypeId::EMPTY {
+                listener.on_entity_moved(entity.id, ArchetypeId::EMPTY, archetype);
+            }
+        }
+        drop(entities);
+        self.archetype_listeners.push(listener);
+    }
+
+    /// Aplicar un comando sobre los mapas ya bloqueados, anotando en `moves`
+    /// los cambios de arquetipo
+    fn apply_command(
+        entities: &mut HashMap<EntityId, Entity>,
+        components: &mut HashMap<ComponentType, HashMap<EntityId, Box<dyn Component>>>,
+        moves: &mut ArchetypeMoves,
+        command: ECSCommand,
+    ) {
+        match command {
+            ECSCommand::Noop => {}
+            ECSCommand::CreateEntity(entity) => {
+                let archetype = ArchetypeId::from_components(&entity.components);
+                moves.record(entity.id, ArchetypeId::EMPTY, archetype);
+                entities.insert(entity.id, entity);
+            }
+            ECSCommand::DestroyEntity(entity_id) => {
+                for component_map in components.values_mut() {
+                    component_map.remove(&entity_id);
                 }
-                ECSCommand::DestroyEntity(entity_id) => {
-                    self.destroy_entity_internal(entity_id).await?;
+                if let Some(entity) = entities.remove(&entity_id) {
+                    moves.record(entity_id, ArchetypeId::from_components(&entity.components), ArchetypeId::EMPTY);
                 }
-                ECSCommand::AddComponent(entity_id, component) => {
-                    self.add_component_internal(entity_id, component).await?;
+            }
+            ECSCommand::AddComponent(entity_id, component) => {
+                let component_type = component.get_type();
+                components
+                    .entry(component_type.clone())
+                    .or_insert_with(HashMap::new)
+                    .insert(entity_id, component);
+                if let Some(entity) = entities.get_mut(&entity_id) {
+                    if !entity.components.contains(&component_type) {
+                        let from = ArchetypeId::from_components(&entity.components);
+                        entity.components.push(component_type);
+                        moves.record(entity_id, from, ArchetypeId::from_components(&entity.components));
+                    }
                 }
-                ECSCommand::RemoveComponent(entity_id, component_type) => {
-                    self.remove_component_internal(entity_id, component_type).await?;
+            }
+            ECSCommand::RemoveComponent(entity_id, component_type) => {
+                if let Some(component_map) = components.get_mut(&component_type) {
+                    component_map.remove(&entity_id);
                 }
-                ECSCommand::UpdateComponent(entity_id, component_type, component) => {
-                    self.update_component_internal(entity_id, component_type, component).await?;
+                if let Some(entity) = entities.get_mut(&entity_id) {
+                    if entity.components.contains(&component_type) {
+                        let from = ArchetypeId::from_components(&entity.components);
+                        entity.components.retain(|c| *c != component_type);
+                        moves.record(entity_id, from, ArchetypeId::from_components(&entity.components));
+                    }
                 }
-                ECSCommand::SetEntityState(entity_id, state) => {
-                    self.set_entity_state_internal(entity_id, state).await?;
+            }
+            ECSCommand::UpdateComponent(entity_id, component_type, component) => {
+                // Si la entidad no tenía el componente no se añade
+                if let Some(existing) = components.get_mut(&component_type).and_then(|m| m.get_mut(&entity_id)) {
+                    *existing = component;
                 }
             }
-        }
+            ECSCommand::SetEntityState(entity_id, state) => {
+                if let Some(entity) = entities.get_mut(&entity_id) {
+                    entity.state = state;
+                }
+            }
+            ECSCommand::SetNetworkOwner(entity_id, owner, version) => {
+                if let Some(entity) = entities.get_mut(&entity_id) {
+                    // Versiones antiguas llegan tarde y no deben pisar a las nuevas
+                    let current_version = entity.metadata.get("ownership_version")
+                        .and_then(|v| v.parse::<u64>().ok())
+                        .unwrap_or(0);
+                    if version < current_version {
+                        return;
+                    }
 
-        self.stats.commands_per_frame = commands.len();
-        Ok(())
+                    match owner {
+                        Some(owner) => {
+                            entity.metadata.insert("network_owner".to_string(), owner);
+                        }
+                        None => {
+                            entity.metadata.remove("network_owner");
+                        }
+                    }
+                    entity.metadata.insert("ownership_version".to_string(), version.to_string());
+                }
+            }
+            ECSCommand::ApplyRootMotion(entity_id, translation, rotation) => {
+                let transform = components.get_mut(&ComponentType::Transform)
+                    .and_then(|m| m.get_mut(&entity_id))
+                    .and_then(|c| c.as_any_mut().downcast_mut::<TransformComponent>());
+                if let Some(transform) = transform {
+                    // El desplazamiento está en espacio local: solo se respeta la orientación horizontal
+                    let (yaw, _, _) = transform.rotation.to_euler(glam::EulerRot::YXZ);
+                    transform.position += Quat::from_rotation_y(yaw) * translation;
+                    transform.rotation = (rotation * transform.rotation).normalize();
+                    transform.matrix = Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position);
+                }
+            }
+            ECSCommand::SetCameraPose(entity_id, position, rotation, fov) => {
+                let transform = components.get_mut(&ComponentType::Transform)
+                    .and_then(|m| m.get_mut(&entity_id))
+                    .and_then(|c| c.as_any_mut().downcast_mut::<TransformComponent>());
+                if let Some(transform) = transform {
+                    transform.position = position;
+                    transform.rotation = rotation;
+                    transform.matrix = Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position);
+                }
+                let camera = components.get_mut(&ComponentType::Camera)
+                    .and_then(|m| m.get_mut(&entity_id))
+                    .and_then(|c| c.as_any_mut().downcast_mut::<CameraComponent>());
+                if let Some(camera) = camera {
+                    camera.fov = fov;
+                }
+            }
+        }
     }
 
-    /// Ejecutar sistemas
+    /// Ejecutar sistemas nivel a nivel del DAG de dependencias. Los sistemas
+    /// de un mismo nivel son independientes y se reparten en el pool de jobs.
     async fn execute_systems(&mut self) -> Result<()> {
-        // Ordenar sistemas por prioridad
-        let mut systems = self.systems.clone();
-        systems.sort_by_key(|system| system.get_priority());
-
-        // Ejecutar sistemas
-        for system in systems {
-            if let Err(e) = system.execute(self) {
-                error!("Error ejecutando sistema {}: {}", system.get_name(), e);
+        if self.schedule.is_none() {
+            self.schedule = Some(self.build_schedule());
+        }
+
+        let world: &ECSSystem = self;
+        let Some(schedule) = &world.schedule else {
+            return Ok(());
+        };
+
+        // Un buffer por sistema, así los jobs de un nivel no comparten estado
+        let mut buffers: Vec<CommandBuffer> = Vec::with_capacity(world.systems.len());
+        for level in schedule {
+            let mut level_buffers: Vec<CommandBuffer> = level.iter().map(|_| CommandBuffer::new()).collect();
+            match &world.job_system {
+                Some(jobs) if level.len() > 1 => {
+                    let batch = level.iter()
+                        .zip(level_buffers.iter_mut())
+                        .map(|(&index, buffer)| {
+                            let system = world.systems[index].as_ref();
+                            Box::new(move || Self::run_system(system, world, buffer)) as Box<dyn FnOnce() + Send + '_>
+                        })
+                        .collect();
+                    // Espera a que termine todo el nivel antes de pasar al siguiente
+                    jobs.execute_batch(batch);
+                }
+                _ => {
+                    for (&index, buffer) in level.iter().zip(level_buffers.iter_mut()) {
+                        Self::run_system(world.systems[index].as_ref(), world, buffer);
+                    }
+                }
             }
+            buffers.extend(level_buffers);
+        }
+
+        // Orden determinista: por nivel y, dentro de cada nivel, por prioridad
+        for mut buffer in buffers {
+            self.command_queue.extend(buffer.drain());
         }
 
         Ok(())
     }
 
+    /// Ejecutar un sistema registrando su error
+    fn run_system(system: &dyn ECSSystem, world: &ECSSystem, commands: &mut CommandBuffer) {
+        crate::profile_scope!(system.get_name());
+        if let Err(e) = system.execute(world, commands) {
+            error!("Error ejecutando sistema {}: {}", system.get_name(), e);
+        }
+    }
+
+    /// Ordenar topológicamente los sistemas por niveles según `dependencies()`.
+    /// Dentro de cada nivel se respeta la prioridad.
+    fn build_schedule(&self) -> Vec<Vec<usize>> {
+        let count = self.systems.len();
+        let index_by_name: HashMap<&str, usize> = self.systems.iter()
+            .enumerate()
+            .map(|(index, system)| (system.get_name(), index))
+            .collect();
+
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); count];
+        let mut in_degree = vec![0usize; count];
+        for (index, system) in self.systems.iter().enumerate() {
+            for dependency in system.dependencies() {
+                match index_by_name.get(dependency.as_str()) {
+                    Some(&other) if other != index => {
+                        dependents[other].push(index);
+                        in_degree[index] += 1;
+                    }
+                    _ => warn!("Dependencia desconocida '{}' del sistema {}", dependency, system.get_name()),
+                }
+            }
+        }
+
+        let by_priority = |level: &mut Vec<usize>| level.sort_by_key(|&index| self.systems[index].get_priority());
+
+        let mut levels = Vec::new();
+        let mut level: Vec<usize> = (0..count).filter(|&index| in_degree[index] == 0).collect();
+        let mut scheduled = 0;
+        while !level.is_empty() {
+            by_priority(&mut level);
+            let mut next = Vec::new();
+            for &index in &level {
+                for &dependent in &dependents[index] {
+                    in_degree[dependent] -= 1;
+                    if in_degree[dependent] == 0 {
+                        next.push(dependent);
+                    }
+                }
+            }
+            scheduled += level.len();
+            levels.push(level);
+            level = next;
+        }
+
+        // Los sistemas en un ciclo se ejecutan uno a uno al final
+        if scheduled < count {
+            let mut remaining: Vec<usize> = (0..count).filter(|&index| in_degree[index] > 0).collect();
+            warn!("Ciclo de dependencias entre {} sistemas; se ejecutarán en serie", remaining.len());
+            by_priority(&mut remaining);
+            levels.extend(remaining.into_iter().map(|index| vec![index]));
+        }
+
+        levels
+    }
+
     /// Crear entidad
     pub async fn create_entity(&mut self, name: String) -> Result<EntityId> {
         let entity_id = self.generate_entity_id();
@@ -1635,33 +2073,34 @@ impl ECSSystem {
         Ok(entity_id)
     }
 
-    /// Crear entidad interna
-    async fn create_entity_internal(&mut self, entity: Entity) -> Result<()> {
-        let mut entities = self.entities.write().unwrap();
-        entities.insert(entity.id, entity);
-        self.stats.entity_count = entities.len();
-        Ok(())
+    /// Reservar un ID de entidad sin crearla, para enlazar jerarquías antes
+    /// de que existan sus entidades
+    pub fn reserve_entity_id(&self) -> EntityId {
+        self.generate_entity_id()
     }
 
-    /// Destruir entidad
-    pub async fn destroy_entity(&mut self, entity_id: EntityId) -> Result<()> {
-        self.command_queue.push_back(ECSCommand::DestroyEntity(entity_id));
-        Ok(())
+    /// Que los IDs generados a partir de ahora sean mayores que `entity_id`
+    /// (p. ej. al cargar entidades con IDs guardados de otra ejecución)
+    pub fn advance_entity_ids(&self, entity_id: EntityId) {
+        ENTITY_COUNTER.fetch_max(entity_id.saturating_add(1), std::sync::atomic::Ordering::Relaxed);
     }
 
-    /// Destruir entidad interna
-    async fn destroy_entity_internal(&mut self, entity_id: EntityId) -> Result<()> {
-        // Remover componentes
-        let mut components = self.components.write().unwrap();
-        for component_map in components.values_mut() {
-            component_map.remove(&entity_id);
-        }
+    /// Crear una entidad ya construida (con ID de `reserve_entity_id`)
+    pub async fn spawn_entity(&mut self, entity: Entity) -> Result<()> {
+        self.command_queue.push_back(ECSCommand::CreateEntity(entity));
+        Ok(())
+    }
 
-        // Remover entidad
-        let mut entities = self.entities.write().unwrap();
-        entities.remove(&entity_id);
-        self.stats.entity_count = entities.len();
+    /// Encolar un comando ya construido (p. ej. recibido de una sesión de
+    /// edición colaborativa)
+    pub async fn queue_command(&mut self, command: ECSCommand) -> Result<()> {
+        self.command_queue.push_back(command);
+        Ok(())
+    }
 
+    /// Destruir entidad
+    pub async fn destroy_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        self.command_queue.push_back(ECSCommand::DestroyEntity(entity_id));
         Ok(())
     }
 
@@ -1671,48 +2110,35 @@ impl ECSSystem {
         Ok(())
     }
 
-    /// Agregar componente interno
-    async fn add_component_internal(&mut self, entity_id: EntityId, component: Box<dyn Component>) -> Result<()> {
-        let component_type = component.get_type();
-        
-        // Agregar a componentes
-        let mut components = self.components.write().unwrap();
-        components
-            .entry(component_type.clone())
-            .or_insert_with(HashMap::new)
-            .insert(entity_id, component);
-
-        // Actualizar entidad
-        let mut entities = self.entities.write().unwrap();
-        if let Some(entity) = entities.get_mut(&entity_id) {
-            entity.components.push(component_type);
-        }
-
-        self.stats.component_count = components.values().map(|m| m.len()).sum();
-        Ok(())
-    }
-
     /// Remover componente
     pub async fn remove_component(&mut self, entity_id: EntityId, component_type: ComponentType) -> Result<()> {
         self.command_queue.push_back(ECSCommand::RemoveComponent(entity_id, component_type));
         Ok(())
     }
 
-    /// Remover componente interno
-    async fn remove_component_internal(&mut self, entity_id: EntityId, component_type: ComponentType) -> Result<()> {
-        // Remover de componentes
-        let mut components = self.components.write().unwrap();
-        if let Some(component_map) = components.get_mut(&component_type) {
-            component_map.remove(&entity_id);
-        }
+    /// Reemplazar un componente existente
+    pub async fn update_component(&mut self, entity_id: EntityId, component: Box<dyn Component>) -> Result<()> {
+        let component_type = component.get_type();
+        self.command_queue.push_back(ECSCommand::UpdateComponent(entity_id, component_type, component));
+        Ok(())
+    }
 
-        // Actualizar entidad
-        let mut entities = self.entities.write().unwrap();
-        if let Some(entity) = entities.get_mut(&entity_id) {
-            entity.components.retain(|c| *c != component_type);
-        }
+    /// Registrar cambio de propiedad de red de una entidad
+    pub async fn set_network_owner(&mut self, entity_id: EntityId, owner: Option<String>, version: u64) -> Result<()> {
+        self.command_queue.push_back(ECSCommand::SetNetworkOwner(entity_id, owner, version));
+        Ok(())
+    }
+
+    /// Aplicar root motion a una entidad: traslación en su espacio local y
+    /// giro alrededor de Y
+    pub async fn apply_root_motion(&mut self, entity_id: EntityId, translation: Vec3, rotation: Quat) -> Result<()> {
+        self.command_queue.push_back(ECSCommand::ApplyRootMotion(entity_id, translation, rotation));
+        Ok(())
+    }
 
-        self.stats.component_count = components.values().map(|m| m.len()).sum();
+    /// Colocar una cámara: posición y orientación de su transformación y FOV
+    pub async fn set_camera_pose(&mut self, entity_id: EntityId, position: Vec3, rotation: Quat, fov: f32) -> Result<()> {
+        self.command_queue.push_back(ECSCommand::SetCameraPose(entity_id, position, rotation, fov));
         Ok(())
     }
 
@@ -1742,17 +2168,130 @@ impl ECSSystem {
             .unwrap_or_default()
     }
 
+    /// Capturar entidades con sus componentes. Las entidades que no existen
+    /// se omiten
+    pub fn snapshot_entities(&self, entity_ids: &[EntityId]) -> Result<snapshot::WorldSnapshot> {
+        let entities = self.entities.read().unwrap();
+        let components = self.components.read().unwrap();
+        let mut world = snapshot::WorldSnapshot::default();
+        for entity in entity_ids.iter().filter_map(|id| entities.get(id)) {
+            let mut captured = Vec::new();
+            for component_type in &entity.components {
+                if let Some(component) = components.get(component_type).and_then(|m| m.get(&entity.id)) {
+                    captured.push(snapshot::ComponentSnapshot::capture(component.as_ref())?);
+                }
+            }
+            world.entities.push(snapshot::EntitySnapshot {
+                id: entity.id,
+                name: entity.name.clone(),
+                state: entity.state.clone(),
+                metadata: entity.metadata.clone(),
+                components: captured,
+            });
+        }
+        Ok(world)
+    }
+
+    /// Estado serializado de cada entidad, en orden de ID: nombre, estado,
+    /// metadatos ordenados y componentes. Los mismos datos dan los mismos
+    /// bytes en todos los peers, así que sirven de hojas del trie de estado
+    pub fn serialize(&self) -> Result<Vec<(EntityId, Vec<u8>)>> {
+        let mut entity_ids: Vec<EntityId> = self.entities.read().unwrap().keys().copied().collect();
+        entity_ids.sort_unstable();
+        self.snapshot_entities(&entity_ids)?.entities.into_iter()
+            .map(|entity| {
+                let mut metadata: Vec<(String, String)> = entity.metadata.into_iter().collect();
+                metadata.sort();
+                let state = bincode::serialize(&(entity.name, entity.state, metadata, entity.components))?;
+                Ok((entity.id, state))
+            })
+            .collect()
+    }
+
     /// Agregar sistema
     pub fn add_system(&mut self, system: Box<dyn ECSSystem>) {
         self.systems.push(system);
+        self.schedule = None;
         self.stats.system_count = self.systems.len();
     }
 
+    /// Sustituir el sistema `name` por `new_system`. Se llama entre frames
+    /// (`&mut self` garantiza que ningún sistema se está ejecutando); desde
+    /// otro hilo se usa `system_reloader`. Si `initialize` falla se conserva
+    /// el sistema anterior; si no, el anterior se cierra con `shutdown` antes
+    /// de la sustitución
+    pub fn reload_system(&mut self, name: &str, mut new_system: Box<dyn ECSSystem>) -> Result<()> {
+        let index = self.systems.iter()
+            .position(|system| system.get_name() == name)
+            .ok_or_else(|| anyhow::anyhow!("No existe el sistema {}", name))?;
+        new_system.initialize()?;
+        if let Err(e) = self.systems[index].shutdown() {
+            warn!("Error cerrando el sistema {} antes de recargarlo: {}", name, e);
+        }
+        drop(std::mem::replace(&mut self.systems[index], new_system));
+        // Las dependencias y la prioridad pueden haber cambiado
+        self.schedule = None;
+        info!("Sistema {} recargado", name);
+        Ok(())
+    }
+
+    /// Canal para sustituir sistemas desde otro hilo al final del frame en curso
+    pub fn system_reloader(&self) -> SystemReloader {
+        self.reload_sender.clone()
+    }
+
+    /// Aplicar las sustituciones pedidas por canal y las bibliotecas de
+    /// sistemas reconstruidas
+    fn apply_system_reloads(&mut self) {
+        let pending: Vec<_> = self.reload_receiver.get_mut().unwrap().try_iter().collect();
+        for (name, system) in pending {
+            if let Err(e) = self.reload_system(&name, system) {
+                error!("Error recargando el sistema {}: {}", name, e);
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.poll_system_libraries();
+    }
+
+    /// Cargar las bibliotecas nuevas o modificadas del directorio de sistemas
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_system_libraries(&mut self) {
+        let paths = match &mut self.system_watcher {
+            Some(watcher) => watcher.poll(),
+            None => return,
+        };
+
+        for path in paths {
+            let hot_reload::LoadedSystem { system, library } = match hot_reload::load_system(&path) {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    warn!("{}", e);
+                    if let Some(watcher) = &mut self.system_watcher {
+                        watcher.retry(&path);
+                    }
+                    continue;
+                }
+            };
+
+            let name = system.get_name().to_string();
+            let result = if self.systems.iter().any(|existing| existing.get_name() == name) {
+                self.reload_system(&name, system)
+            } else {
+                let mut system = system;
+                system.initialize().map(|_| self.add_system(system))
+            };
+            match result {
+                // La biblioteca anterior se libera después de su sistema
+                Ok(()) => drop(self.system_libraries.insert(name, library)),
+                Err(e) => error!("Error cargando el sistema {} de {:?}: {}", name, path, e),
+            }
+        }
+    }
+
     /// Generar ID de entidad
     fn generate_entity_id(&self) -> EntityId {
-        use std::sync::atomic::{AtomicU64, Ordering};
-        static ENTITY_COUNTER: AtomicU64 = AtomicU64::new(1);
-        ENTITY_COUNTER.fetch_add(1, Ordering::Relaxed)
+        next_entity_id()
     }
 
     /// Actualizar estadísticas
@@ -1767,6 +2306,47 @@ impl ECSSystem {
         self.stats.clone()
     }
 
+    /// Máximo de sistemas ejecutándose en paralelo
+    pub fn max_parallel_systems(&self) -> usize {
+        self.config.max_parallel_systems
+    }
+
+    /// Cambiar el máximo de sistemas en paralelo; el pool de jobs se recrea
+    /// con un hilo menos, ya que el hilo de los sistemas también toma jobs
+    pub fn set_max_parallel_systems(&mut self, max_parallel: usize) {
+        let max_parallel = max_parallel.max(1);
+        if max_parallel == self.config.max_parallel_systems {
+            return;
+        }
+        self.config.max_parallel_systems = max_parallel;
+        if !self.running {
+            return;
+        }
+        self.job_system = if self.config.system_config.parallel_execution && max_parallel > 1 {
+            Some(crate::utils::JobSystem::new(max_parallel - 1))
+        } else {
+            None
+        };
+        info!("Sistemas en paralelo: {}", max_parallel);
+    }
+
+    /// Liberar la capacidad sobrante de los pools de componentes, de las
+    /// entidades y de la cola de comandos. Devuelve los pools vacíos quitados
+    pub fn shrink_component_pools(&mut self) -> usize {
+        let mut components = self.components.write().unwrap();
+        let before = components.len();
+        components.retain(|_, pool| !pool.is_empty());
+        let removed = before - components.len();
+        for pool in components.values_mut() {
+            pool.shrink_to_fit();
+        }
+        components.shrink_to_fit();
+        drop(components);
+        self.entities.write().unwrap().shrink_to_fit();
+        self.command_queue.shrink_to_fit();
+        removed
+    }
+
     /// Limpiar sistema
     pub async fn cleanup(&mut self) -> Result<()> {
         info!("Limpiando sistema ECS");
@@ -1775,7 +2355,15 @@ impl ECSSystem {
         self.entities.write().unwrap().clear();
         self.components.write().unwrap().clear();
         self.systems.clear();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.system_libraries.clear();
+            self.system_watcher = None;
+        }
+        self.schedule = None;
+        self.job_system = None;
         self.command_queue.clear();
+        self.archetype_listeners.clear();
         
         info!("Sistema ECS limpiado");
         Ok(())
@@ -1796,18 +2384,22 @@ impl TransformSystem {
 }
 
 impl ECSSystem for TransformSystem {
-    fn execute(&self, world: &ECSSystem) -> Result<()> {
+    fn execute(&self, world: &ECSSystem, commands: &mut CommandBuffer) -> Result<()> {
         // Actualizar transformaciones
         let entities = world.get_entities_with_component(ComponentType::Transform);
         
         for entity_id in entities {
-            if let Some(transform) = world.get_component::<TransformComponent>(entity_id, ComponentType::Transform) {
+            if let Some(mut transform) = world.get_component::<TransformComponent>(entity_id, ComponentType::Transform) {
                 // Actualizar matriz de transformación
                 let matrix = Mat4::from_translation(transform.position)
                     * Mat4::from_quat(transform.rotation)
                     * Mat4::from_scale(transform.scale);
                 
-                // Aquí se actualizaría la matriz en el componente
+                // Solo se encolan las matrices que han cambiado
+                if transform.matrix != matrix {
+                    transform.matrix = matrix;
+                    commands.update_component(entity_id, Box::new(transform));
+                }
             }
         }
         
@@ -1823,23 +2415,92 @@ impl ECSSystem for TransformSystem {
     }
 }
 
+/// Lote de material de una entidad con malla
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialBatch {
+    /// Con componente de material
+    Material,
+    /// Sin material: se dibuja con el material por defecto
+    Default,
+}
+
+/// Entidades con malla por lote de material, en orden de ID
+#[derive(Debug, Default)]
+pub struct RenderBatches {
+    /// Mallas con componente de material
+    pub material: std::collections::BTreeSet<EntityId>,
+    /// Mallas con el material por defecto
+    pub default_material: std::collections::BTreeSet<EntityId>,
+}
+
+impl RenderBatches {
+    /// Lote de un arquetipo (None sin malla)
+    pub fn batch_of(archetype: ArchetypeId) -> Option<MaterialBatch> {
+        if !archetype.contains(&ComponentType::Mesh) {
+            return None;
+        }
+        Some(if archetype.contains(&ComponentType::Material) {
+            MaterialBatch::Material
+        } else {
+            MaterialBatch::Default
+        })
+    }
+
+    fn set_mut(&mut self, batch: MaterialBatch) -> &mut std::collections::BTreeSet<EntityId> {
+        match batch {
+            MaterialBatch::Material => &mut self.material,
+            MaterialBatch::Default => &mut self.default_material,
+        }
+    }
+}
+
+/// Listener que reasigna el lote de material al añadir o quitar la malla o
+/// el material
+#[derive(Debug, Clone)]
+pub struct RenderBatchListener {
+    batches: Arc<std::sync::RwLock<RenderBatches>>,
+}
+
+impl ArchetypeChangeListener for RenderBatchListener {
+    fn on_entity_moved(&mut self, entity: EntityId, from: ArchetypeId, to: ArchetypeId) {
+        let (from, to) = (RenderBatches::batch_of(from), RenderBatches::batch_of(to));
+        if from == to {
+            return;
+        }
+        let mut batches = self.batches.write().unwrap();
+        if let Some(from) = from {
+            batches.set_mut(from).remove(&entity);
+        }
+        if let Some(to) = to {
+            batches.set_mut(to).insert(entity);
+        }
+    }
+}
+
 /// Sistema de renderizado
 pub struct RenderSystem {
     priority: u32,
+    /// Entidades con malla por lote, al día con los cambios de arquetipo
+    batches: Arc<std::sync::RwLock<RenderBatches>>,
 }
 
 impl RenderSystem {
     pub fn new() -> Self {
-        Self { priority: 200 }
+        Self { priority: 200, batches: Arc::default() }
+    }
+
+    /// Listener que mantiene los lotes del sistema
+    pub fn batch_listener(&self) -> RenderBatchListener {
+        RenderBatchListener { batches: self.batches.clone() }
     }
 }
 
 impl ECSSystem for RenderSystem {
-    fn execute(&self, world: &ECSSystem) -> Result<()> {
-        // Renderizar entidades con malla
-        let entities = world.get_entities_with_component(ComponentType::Mesh);
+    fn execute(&self, world: &ECSSystem, _commands: &mut CommandBuffer) -> Result<()> {
+        // Renderizar entidades con malla, lote a lote
+        let batches = self.batches.read().unwrap();
         
-        for entity_id in entities {
+        for entity_id in batches.material.iter().chain(&batches.default_material).copied() {
             if let Some(mesh) = world.get_component::<MeshComponent>(entity_id, ComponentType::Mesh) {
                 if let Some(transform) = world.get_component::<TransformComponent>(entity_id, ComponentType::Transform) {
                     // Renderizar malla con transformación
@@ -1862,18 +2523,25 @@ impl ECSSystem for RenderSystem {
 /// Sistema de física
 pub struct PhysicsSystem {
     priority: u32,
+    /// Entidades con cuerpo físico, al día con los cambios de arquetipo
+    bodies: QueryMembership,
 }
 
 impl PhysicsSystem {
     pub fn new() -> Self {
-        Self { priority: 150 }
+        Self { priority: 150, bodies: QueryMembership::new(&[ComponentType::Physics]) }
+    }
+
+    /// Listener que mantiene las entidades con cuerpo del sistema
+    pub fn body_listener(&self) -> QueryMembership {
+        self.bodies.clone()
     }
 }
 
 impl ECSSystem for PhysicsSystem {
-    fn execute(&self, world: &ECSSystem) -> Result<()> {
-        // Simular física
-        let entities = world.get_entities_with_component(ComponentType::Physics);
+    fn execute(&self, world: &ECSSystem, _commands: &mut CommandBuffer) -> Result<()> {
+        // Simular física en orden de entidad
+        let entities = self.bodies.entities();
         
         for entity_id in entities {
             if let Some(physics) = world.get_component::<PhysicsComponent>(entity_id, ComponentType::Physics) {
@@ -1905,7 +2573,7 @@ impl AnimationSystem {
 }
 
 impl ECSSystem for AnimationSystem {
-    fn execute(&self, world: &ECSSystem) -> Result<()> {
+    fn execute(&self, world: &ECSSystem, _commands: &mut CommandBuffer) -> Result<()> {
         // Actualizar animaciones
         let entities = world.get_entities_with_component(ComponentType::Animation);
         
@@ -1939,8 +2607,10 @@ impl AudioSystem {
 }
 
 impl ECSSystem for AudioSystem {
-    fn execute(&self, world: &ECSSystem) -> Result<()> {
-        // Procesar audio
+    fn execute(&self, world: &ECSSystem, _commands: &mut CommandBuffer) -> Result<()> {
+        // Las voces de las entidades las crea `audio::AudioSystem::sync_audio_components`
+        // y el mezclador elige su banda de LOD (HRTF, mono o descartada)
+        // con la distancia al oyente en cada bloque
         let entities = world.get_entities_with_component(ComponentType::Audio);
         
         for entity_id in entities {
@@ -1973,7 +2643,7 @@ impl NetworkSystem {
 }
 
 impl ECSSystem for NetworkSystem {
-    fn execute(&self, world: &ECSSystem) -> Result<()> {
+    fn execute(&self, world: &ECSSystem, _commands: &mut CommandBuffer) -> Result<()> {
         // Procesar red
         let entities = world.get_entities_with_component(ComponentType::Network);
         
@@ -2007,16 +2677,12 @@ impl ScriptSystem {
 }
 
 impl ECSSystem for ScriptSystem {
-    fn execute(&self, world: &ECSSystem) -> Result<()> {
-        // Ejecutar scripts
-        let entities = world.get_entities_with_component(ComponentType::Script);
-        
-        for entity_id in entities {
-            if let Some(script) = world.get_component::<ScriptComponent>(entity_id, ComponentType::Script) {
-                // Ejecutar script
-            }
+    fn execute(&self, world: &ECSSystem, commands: &mut CommandBuffer) -> Result<()> {
+        // Los scripts Rhai los ejecuta el runtime del sistema WASM
+        if let Some(runtime) = world.script_runtime() {
+            runtime.lock().unwrap().run_frame(world, commands, world.delta_time());
         }
-        
+
         Ok(())
     }
 
@@ -2032,12 +2698,17 @@ impl ECSSystem for ScriptSystem {
 // Extensión para Component trait
 pub trait ComponentExt: Component {
     fn as_any(&self) -> &dyn std::any::Any;
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
 }
 
 impl<T: Component + 'static> ComponentExt for T {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
 }
 
 // Implementación de Clone para Box<dyn Component>
@@ -0,0 +1,73 @@
+//! # Registro de deserializadores de componentes
+//!
+//! Tabla global de nombre de tipo a función de deserialización. Los
+//! componentes del motor se resuelven por su `ComponentType`; los
+//! componentes propios (`ComponentType::Custom`) necesitan registrarse con
+//! `register_component!` antes de restaurar instantáneas que los contengan:
+//!
+//! ```ignore
+//! #[derive(Clone, Serialize, Deserialize, Component)]
+//! struct Inventory { items: Vec<String> }
+//!
+//! register_component!(Inventory);
+//! ```
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use super::{Component, ComponentType};
+
+/// Función que reconstruye un componente desde sus bytes
+pub type ComponentDeserializer = fn(&[u8]) -> Result<Box<dyn Component>>;
+
+/// Deserializadores por nombre de tipo
+static DESERIALIZER_REGISTRY: OnceLock<RwLock<HashMap<String, ComponentDeserializer>>> = OnceLock::new();
+
+fn registry() -> &'static RwLock<HashMap<String, ComponentDeserializer>> {
+    DESERIALIZER_REGISTRY.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Nombre de registro de un tipo: la variante o el nombre de `Custom`
+pub fn registry_key(component_type: &ComponentType) -> String {
+    match component_type {
+        ComponentType::Custom(name) => name.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Registrar un deserializador. Uno anterior con el mismo nombre se reemplaza
+pub fn register_deserializer(component_type: &ComponentType, deserializer: ComponentDeserializer) {
+    registry()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(registry_key(component_type), deserializer);
+}
+
+/// Deserializador registrado para un tipo
+pub fn deserializer(component_type: &ComponentType) -> Option<ComponentDeserializer> {
+    registry()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&registry_key(component_type))
+        .copied()
+}
+
+/// Reconstruir un componente con el deserializador registrado
+pub fn deserialize_component(component_type: &ComponentType, data: &[u8]) -> Result<Box<dyn Component>> {
+    let deserializer = deserializer(component_type)
+        .ok_or_else(|| anyhow!("Componente '{}' sin deserializador registrado", registry_key(component_type)))?;
+    deserializer(data)
+}
+
+/// Registrar el deserializador de un tipo que implementa `Component` y
+/// `ComponentTypeOf` (ambos los genera `#[derive(Component)]`)
+#[macro_export]
+macro_rules! register_component {
+    ($component:ty) => {
+        $crate::ecs::registry::register_deserializer(
+            &<$component as $crate::ecs::ComponentTypeOf>::static_type(),
+            <$component as $crate::ecs::Component>::deserialize,
+        )
+    };
+}
@@ -0,0 +1,126 @@
+//! # Instantáneas del mundo
+//!
+//! Formato binario (bincode) de un conjunto de entidades con sus componentes.
+//! Cada componente se guarda con su tipo y los bytes de `Component::serialize`,
+//! de modo que restaurarlo es llamar al `deserialize` del tipo concreto. Los
+//! componentes `Custom` se restauran con el deserializador registrado con
+//! `register_component!`.
+
+use anyhow::{anyhow, Result};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use super::registry;
+use super::{
+    AmbientZoneComponent, AnimationComponent, AudioComponent, CameraComponent, Component, ComponentType, DecalComponent, Entity,
+    EntityId, EntityState, LabelComponent, LightComponent, MaterialComponent, MeshComponent, NetworkComponent,
+    PhysicsComponent, ScriptComponent, TransformComponent,
+};
+
+/// Versión del formato de instantánea
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Componente serializado
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentSnapshot {
+    /// Tipo del componente
+    pub component_type: ComponentType,
+    /// Bytes de `Component::serialize`
+    pub data: Vec<u8>,
+}
+
+impl ComponentSnapshot {
+    /// Serializar un componente
+    pub fn capture(component: &dyn Component) -> Result<Self> {
+        Ok(Self {
+            component_type: component.get_type(),
+            data: component.serialize()?,
+        })
+    }
+
+    /// Reconstruir el componente
+    pub fn restore(&self) -> Result<Box<dyn Component>> {
+        match &self.component_type {
+            ComponentType::Transform => TransformComponent::deserialize(&self.data),
+            ComponentType::Mesh => MeshComponent::deserialize(&self.data),
+            ComponentType::Material => MaterialComponent::deserialize(&self.data),
+            ComponentType::Light => LightComponent::deserialize(&self.data),
+            ComponentType::Camera => CameraComponent::deserialize(&self.data),
+            ComponentType::Physics => PhysicsComponent::deserialize(&self.data),
+            ComponentType::Audio => AudioComponent::deserialize(&self.data),
+            ComponentType::Animation => AnimationComponent::deserialize(&self.data),
+            ComponentType::Script => ScriptComponent::deserialize(&self.data),
+            ComponentType::Network => NetworkComponent::deserialize(&self.data),
+            ComponentType::Decal => DecalComponent::deserialize(&self.data),
+            ComponentType::Label => LabelComponent::deserialize(&self.data),
+            ComponentType::AmbientZone => AmbientZoneComponent::deserialize(&self.data),
+            ComponentType::Custom(_) => registry::deserialize_component(&self.component_type, &self.data),
+        }
+    }
+}
+
+/// Entidad serializada
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntitySnapshot {
+    /// ID de la entidad al capturarla
+    pub id: EntityId,
+    /// Nombre de la entidad
+    pub name: String,
+    /// Estado de la entidad
+    pub state: EntityState,
+    /// Metadatos de la entidad
+    pub metadata: HashMap<String, String>,
+    /// Componentes de la entidad
+    pub components: Vec<ComponentSnapshot>,
+}
+
+impl EntitySnapshot {
+    /// Entidad sin componentes con otro ID (los componentes se añaden aparte)
+    pub fn to_entity(&self, id: EntityId) -> Entity {
+        Entity {
+            id,
+            name: self.name.clone(),
+            components: Vec::new(),
+            state: self.state.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+/// Instantánea de un conjunto de entidades
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    /// Versión del formato
+    pub version: u32,
+    /// Entidades capturadas
+    pub entities: Vec<EntitySnapshot>,
+}
+
+impl Default for WorldSnapshot {
+    fn default() -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            entities: Vec::new(),
+        }
+    }
+}
+
+impl WorldSnapshot {
+    /// Serializar la instantánea
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Leer una instantánea comprobando la versión
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let snapshot: WorldSnapshot = bincode::deserialize(data)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(anyhow!(
+                "Versión de instantánea {} no soportada (se esperaba {})",
+                snapshot.version,
+                SNAPSHOT_VERSION
+            ));
+        }
+        Ok(snapshot)
+    }
+}
@@ -3,12 +3,16 @@
 //! Motor 3D descentralizado de alto rendimiento para el metaverso.
 //! Proporciona renderizado, física, networking, y más en un solo sistema integrado.
 
+// Las macros derive generan rutas `::metaverso_engine::...`, también dentro del propio crate
+extern crate self as metaverso_engine;
+
 pub mod ecs;
 pub mod physics;
 pub mod networking;
 pub mod wasm;
 pub mod renderer;
 pub mod scene;
+pub mod terrain;
 pub mod camera;
 pub mod lighting;
 pub mod materials;
@@ -16,6 +20,15 @@ pub mod animations;
 pub mod audio;
 pub mod crypto;
 pub mod utils;
+pub mod profiling;
+
+/// Dependencias usadas por el código que genera `engine_macros`
+#[doc(hidden)]
+pub mod __private {
+    pub use anyhow;
+    pub use bincode;
+    pub use serde;
+}
 
 use serde::{Serialize, Deserialize};
 use tracing::{info, debug, error};
@@ -37,6 +50,8 @@ pub struct Engine3D {
     renderer_system: renderer::RendererSystem,
     /// Sistema de escenas
     scene_system: scene::SceneSystem,
+    /// Sistema de terreno
+    terrain_system: terrain::TerrainSystem,
     /// Sistema de cámaras
     camera_system: camera::CameraSystem,
     /// Sistema de iluminación
@@ -51,6 +66,8 @@ pub struct Engine3D {
     crypto_system: crypto::CryptoSystem,
     /// Sistema de utilidades
     utils_system: utils::UtilsSystem,
+    /// Runtime de XR del casco (None sin VR)
+    xr_runtime: Option<Box<dyn renderer::xr::XrRuntime>>,
     /// Estado del motor
     running: bool,
 }
@@ -74,8 +91,14 @@ pub struct EngineConfig {
     pub renderer_config: renderer::RendererConfig,
     /// Configuración de escenas
     pub scene_config: scene::SceneConfig,
+    /// Configuración del terreno
+    #[serde(default)]
+    pub terrain_config: terrain::TerrainConfig,
     /// Configuración de cámaras
     pub camera_config: camera::CameraConfig,
+    /// Configuración de VR (con `camera_config.vr_mode`)
+    #[serde(default)]
+    pub vr_config: camera::xr::VRConfig,
     /// Configuración de iluminación
     pub lighting_config: lighting::LightingConfig,
     /// Configuración de materiales
@@ -127,6 +150,18 @@ pub struct GraphicsConfig {
     pub antialiasing: AntialiasingConfig,
     /// Configuración de calidad
     pub quality_config: QualityConfig,
+    /// Variable rate shading (None desactivado)
+    #[serde(default)]
+    pub variable_rate_shading: Option<renderer::vrs::VRSConfig>,
+    /// Mezcla de articulaciones de las mallas con skin
+    #[serde(default)]
+    pub skinning_mode: animations::skinning::SkinningMode,
+    /// Resolución dinámica según el tiempo de GPU (None a resolución completa)
+    #[serde(default)]
+    pub dynamic_resolution: Option<renderer::dynamic_resolution::DynamicResolutionConfig>,
+    /// Bloom físico sobre el render HDR (None deja el de la configuración de efectos)
+    #[serde(default)]
+    pub bloom: Option<renderer::bloom::BloomConfig>,
 }
 
 /// Configuración de antialiasing
@@ -238,6 +273,7 @@ impl Engine3D {
             wasm_system: wasm::WASMSystem::new(&config.wasm_config),
             renderer_system: renderer::RendererSystem::new(&config.renderer_config),
             scene_system: scene::SceneSystem::new(&config.scene_config),
+            terrain_system: terrain::TerrainSystem::new(&config.terrain_config),
             camera_system: camera::CameraSystem::new(&config.camera_config),
             lighting_system: lighting::LightingSystem::new(&config.lighting_config),
             material_system: materials::MaterialSystem::new(&config.material_config),
@@ -245,6 +281,7 @@ impl Engine3D {
             audio_system: audio::AudioSystem::new(&config.audio_config),
             crypto_system: crypto::CryptoSystem::new(&config.crypto_config),
             utils_system: utils::UtilsSystem::new(&config.utils_config),
+            xr_runtime: None,
             running: false,
         }
     }
@@ -260,13 +297,27 @@ impl Engine3D {
         self.animation_system.initialize().await?;
         self.material_system.initialize().await?;
         self.lighting_system.initialize().await?;
+        self.camera_system.set_vr_config(self.config.vr_config.clone());
         self.camera_system.initialize().await?;
         self.scene_system.initialize().await?;
+        self.terrain_system.initialize().await?;
+        self.renderer_system.set_variable_rate_shading(self.config.graphics_config.variable_rate_shading.clone());
+        self.renderer_system.set_dynamic_resolution(self.config.graphics_config.dynamic_resolution.clone());
+        if let Some(bloom) = &self.config.graphics_config.bloom {
+            self.renderer_system.set_bloom(Some(bloom.clone()));
+        }
+        self.animation_system.set_skinning_mode(self.config.graphics_config.skinning_mode);
         self.renderer_system.initialize().await?;
         self.wasm_system.initialize().await?;
+        self.ecs_system.set_script_runtime(Some(self.wasm_system.script_runtime()));
         self.networking_system.initialize().await?;
         self.physics_system.initialize().await?;
         self.ecs_system.initialize().await?;
+
+        // La autoridad de física usa la identidad del peer de red
+        if let Some(replication) = self.networking_system.get_replication() {
+            self.physics_system.set_local_peer(replication.local_peer().to_string());
+        }
         
         self.running = true;
         
@@ -280,20 +331,191 @@ impl Engine3D {
             return Ok(());
         }
         
+        crate::profile_function!();
+
         // Actualizar sistemas en orden de dependencia
-        self.utils_system.update().await?;
-        self.crypto_system.update().await?;
-        self.audio_system.update(delta_time).await?;
-        self.animation_system.update(delta_time).await?;
-        self.material_system.update(delta_time).await?;
-        self.lighting_system.update(delta_time).await?;
-        self.camera_system.update(delta_time).await?;
-        self.scene_system.update(delta_time).await?;
-        self.renderer_system.update(delta_time).await?;
-        self.wasm_system.update(delta_time).await?;
-        self.networking_system.update(delta_time).await?;
-        self.physics_system.update(delta_time).await?;
-        self.ecs_system.update(delta_time).await?;
+        {
+            crate::profile_scope!("utils");
+            self.utils_system.update().await?;
+        }
+        {
+            crate::profile_scope!("crypto");
+            self.crypto_system.update().await?;
+        }
+        {
+            crate::profile_scope!("audio");
+            self.audio_system.sync_audio_components(&self.ecs_system, delta_time);
+            for (voice_id, source, listener) in self.audio_system.occlusion_queries() {
+                let (occluders, thickness) = self.physics_system.measure_occlusion(source, listener);
+                self.audio_system.set_voice_occlusion(voice_id, occluders, thickness);
+            }
+            self.audio_system.update(delta_time).await?;
+        }
+        {
+            crate::profile_scope!("animation");
+            self.animation_system.update(delta_time).await?;
+            self.dispatch_animation_events().await?;
+            self.apply_cinematic_samples().await?;
+        }
+        {
+            crate::profile_scope!("materials");
+            self.material_system.sync_components(&self.ecs_system);
+            self.material_system.update(&mut self.renderer_system).await?;
+        }
+        {
+            crate::profile_scope!("lighting");
+            self.lighting_system.update(delta_time).await?;
+            self.lighting_system.sync(&mut self.renderer_system)?;
+        }
+        {
+            crate::profile_scope!("camera");
+            self.camera_system.update(delta_time).await?;
+        }
+        {
+            crate::profile_scope!("scene");
+            self.scene_system.update(delta_time).await?;
+            self.scene_system.stream_entities(&mut self.ecs_system).await?;
+            self.scene_system.update_transition(&mut self.ecs_system, delta_time).await?;
+        }
+        // Trozos de terreno alrededor de la cámara del último frame
+        {
+            crate::profile_scope!("terrain");
+            self.terrain_system.update_streaming(self.renderer_system.camera_position());
+            self.terrain_system.sync(&mut self.renderer_system, &mut self.physics_system).await?;
+        }
+        {
+            crate::profile_scope!("rendering");
+            self.renderer_system.update(delta_time).await?;
+        }
+        {
+            crate::profile_scope!("wasm");
+            self.wasm_system.update(delta_time).await?;
+        }
+        {
+            crate::profile_scope!("networking");
+            self.networking_system.update(delta_time).await?;
+        }
+
+        // Intercambiar la voz con la red y colocar a los hablantes en sus entidades
+        {
+            crate::profile_scope!("voice");
+            for frame in self.audio_system.drain_voice_packets() {
+                self.networking_system.send_voice_packet(frame.data, frame.sequence).await?;
+            }
+            for (peer, packet) in self.networking_system.drain_voice_packets() {
+                self.audio_system.receive_voice_packet(&peer.to_string(), packet.entity_id, packet.sequence, packet.data)?;
+            }
+            for (speaker, entity_id) in self.audio_system.voice_speaker_entities() {
+                let position = animations::skinning::entity_world_matrix(&self.ecs_system, entity_id).w_axis.truncate();
+                self.audio_system.set_voice_speaker_position(&speaker, position);
+            }
+        }
+
+        // Trasladar el root motion de las animaciones a los personajes o, si
+        // no tienen, directamente a las entidades
+        for (entity_id, motion) in self.animation_system.get_root_motion_deltas() {
+            if !self.physics_system.move_character(*entity_id, motion.translation, motion.rotation) {
+                self.ecs_system.apply_root_motion(*entity_id, motion.translation, motion.rotation).await?;
+            }
+        }
+
+        // Intercambiar autoridad de física con la red
+        {
+            crate::profile_scope!("physics");
+            for message in self.networking_system.drain_physics_authority() {
+                self.physics_system.handle_authority_message(message);
+            }
+            self.physics_system.update(delta_time).await?;
+            let authority_messages = self.physics_system.drain_authority_messages();
+            self.networking_system.send_physics_authority(authority_messages).await?;
+        }
+
+        // Copiar a las entidades la pose de sus personajes tras el paso
+        for (entity_id, position, rotation) in self.physics_system.drain_character_moves() {
+            let transform = self.ecs_system.get_component::<ecs::TransformComponent>(entity_id, ecs::ComponentType::Transform);
+            if let Some(mut transform) = transform {
+                transform.position = position;
+                transform.rotation = rotation;
+                transform.matrix = glam::Mat4::from_scale_rotation_translation(transform.scale, rotation, position);
+                self.ecs_system.update_component(entity_id, Box::new(transform)).await?;
+            }
+        }
+
+        // Copiar a las mallas de las entidades los vértices de sus telas; los
+        // vértices fijados siguen a la entidad en el siguiente paso
+        for (entity_id, cloth_id) in self.physics_system.entity_cloths() {
+            let transform = self.ecs_system.get_component::<ecs::TransformComponent>(entity_id, ecs::ComponentType::Transform);
+            if let (Some(transform), Some(cloth)) = (transform, self.physics_system.get_cloth_mut(cloth_id)) {
+                cloth.set_transform(transform.matrix);
+            }
+            let mesh = self.ecs_system.get_component::<ecs::MeshComponent>(entity_id, ecs::ComponentType::Mesh);
+            if let (Some(mut mesh), Some(cloth)) = (mesh, self.physics_system.get_cloth(cloth_id)) {
+                cloth.write_mesh(&mut mesh);
+                self.ecs_system.update_component(entity_id, Box::new(mesh)).await?;
+            }
+        }
+
+        // Aplicar las ediciones colaborativas en orden de revisión
+        for command in self.networking_system.drain_collab_commands() {
+            self.ecs_system.queue_command(command).await?;
+        }
+
+        // Propagar cambios de propiedad de red al ECS
+        for event in self.networking_system.drain_ownership_events() {
+            self.ecs_system
+                .set_network_owner(event.entity_id, event.new_owner.map(|p| p.to_string()), event.version)
+                .await?;
+        }
+
+        // Colocar la cámara activa con su controlador
+        {
+            crate::profile_scope!("camera_controller");
+            if let Some(pose) = self.camera_system.update_controller(&self.ecs_system, delta_time) {
+                if let Some(camera_id) = camera::active_camera_entity(&self.ecs_system) {
+                    self.ecs_system.set_camera_pose(camera_id, pose.position, pose.rotation, pose.fov).await?;
+                }
+            }
+        }
+
+        {
+            crate::profile_scope!("ecs");
+            self.ecs_system.update(delta_time).await?;
+        }
+
+        // Publicar la raíz del estado del mundo: los peers verifican
+        // entidades sueltas con pruebas contra ella
+        {
+            crate::profile_scope!("state_trie");
+            let states = self.ecs_system.serialize()?;
+            let root = self.crypto_system.update_state_trie(states);
+            self.networking_system.publish_state_root(root).await?;
+        }
+
+        // Paletas de huesos con las transformaciones ya actualizadas del frame
+        {
+            crate::profile_scope!("skinning");
+            self.animation_system.apply_procedural_modifiers(&self.ecs_system, delta_time);
+            // Pies plantados sobre el terreno antes de resolver el IK
+            for probe in self.animation_system.foot_probes(&self.ecs_system) {
+                match self.physics_system.cast_ray(probe.origin, -glam::Vec3::Y, probe.max_distance, Some(probe.entity_id)) {
+                    Some(hit) => self.animation_system.set_ik_target(probe.entity_id, &probe.constraint_id, probe.goal(hit.point)),
+                    None => {
+                        self.animation_system.clear_ik_target(probe.entity_id, &probe.constraint_id);
+                    }
+                }
+            }
+            self.animation_system.apply_ik_constraints(&self.ecs_system);
+            self.animation_system.update_skin_palettes(&self.ecs_system);
+            self.renderer_system.set_skin_palettes(self.animation_system.skin_palettes());
+            self.renderer_system.set_morph_weights(self.animation_system.morph_weights());
+        }
+
+        // Emisores que siguen a entidades y partículas vivas del frame
+        {
+            crate::profile_scope!("particles");
+            self.animation_system.update_particle_emitters(&self.ecs_system);
+            self.renderer_system.set_particle_batches(self.animation_system.particle_batches());
+        }
         
         Ok(())
     }
@@ -305,11 +527,298 @@ impl Engine3D {
         }
         
         // Renderizar frame
+        self.queue_frame();
         self.renderer_system.render().await?;
+
+        // En VR se dibuja además un ojo por vista del casco
+        if self.camera_system.is_vr_mode() {
+            if let Some(mut runtime) = self.xr_runtime.take() {
+                let result = self.render_xr(runtime.as_mut());
+                self.xr_runtime = Some(runtime);
+                result?;
+            }
+        }
         
         Ok(())
     }
 
+    /// Atender los eventos de animación que no resuelve el propio sistema:
+    /// los sonidos suenan en la posición de la entidad y los callbacks sin
+    /// registrar pasan al script de la entidad
+    async fn dispatch_animation_events(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        for fired in self.animation_system.drain_animation_events() {
+            match &fired.event.data {
+                animations::AnimationEventData::Sound(sound) => {
+                    let position = fired.entity_id
+                        .map(|entity_id| animations::skinning::entity_world_matrix(&self.ecs_system, entity_id).w_axis.truncate())
+                        .unwrap_or(glam::Vec3::ZERO);
+                    if let Err(e) = self.audio_system.play_audio(&sound.sound_id, position, sound.volume, sound.pitch).await {
+                        debug!("Evento de sonido sin fuente: {}", e);
+                    }
+                }
+                animations::AnimationEventData::Callback(callback) => {
+                    let Some(entity_id) = fired.entity_id else {
+                        continue;
+                    };
+                    let script = self.ecs_system.get_component::<ecs::ScriptComponent>(entity_id, ecs::ComponentType::Script);
+                    if let Some(mut script) = script {
+                        script.pending_calls.push(ecs::ScriptCall {
+                            function_name: callback.function_name.clone(),
+                            parameters: callback.parameters.clone(),
+                        });
+                        self.ecs_system.update_component(entity_id, Box::new(script)).await?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Copiar las muestras de las cinemáticas a las cámaras y luces
+    async fn apply_cinematic_samples(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        for (&entity_id, sample) in self.animation_system.camera_samples() {
+            if let Some(mut camera) = self.ecs_system.get_component::<ecs::CameraComponent>(entity_id, ecs::ComponentType::Camera) {
+                camera.near_plane = sample.near_plane;
+                camera.far_plane = sample.far_plane;
+                self.ecs_system.update_component(entity_id, Box::new(camera)).await?;
+            }
+            self.ecs_system.set_camera_pose(entity_id, sample.position, sample.rotation, sample.fov).await?;
+        }
+        for (&entity_id, sample) in self.animation_system.light_samples() {
+            if let Some(mut light) = self.ecs_system.get_component::<ecs::LightComponent>(entity_id, ecs::ComponentType::Light) {
+                light.color = sample.color;
+                light.intensity = sample.intensity;
+                light.range = sample.range;
+                self.ecs_system.update_component(entity_id, Box::new(light)).await?;
+            }
+            if let Some(mut transform) = self.ecs_system.get_component::<ecs::TransformComponent>(entity_id, ecs::ComponentType::Transform) {
+                transform.position = sample.position;
+                transform.rotation = sample.rotation;
+                transform.matrix = glam::Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position);
+                self.ecs_system.update_component(entity_id, Box::new(transform)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Frame del runtime de XR: las cámaras de los ojos salen de la pose de
+    /// la cabeza del frame y el frame se cierra aunque no se dibuje
+    fn render_xr(&mut self, runtime: &mut dyn renderer::xr::XrRuntime) -> Result<(), Box<dyn std::error::Error>> {
+        let frame = runtime.begin_frame()?;
+        let cameras = self.camera_system.update_vr(&frame).filter(|_| frame.should_render);
+        let rendered = match cameras {
+            Some(cameras) => self.renderer_system.render_xr_frame(&self.ecs_system, &cameras, runtime),
+            None => Ok(()),
+        };
+        runtime.end_frame(&frame)?;
+        Ok(rendered?)
+    }
+
+    /// Establecer el runtime de XR (antes de `initialize`). Si el runtime
+    /// abrió su propio dispositivo gráfico, el backend wgpu se crea sobre él
+    pub fn set_xr_runtime(
+        &mut self,
+        runtime: Box<dyn renderer::xr::XrRuntime>,
+        graphics: Option<renderer::xr::XrGraphics>,
+    ) {
+        info!("🥽 Runtime de XR: {}", runtime.name());
+        if let Some(graphics) = graphics {
+            self.renderer_system.set_xr_graphics(graphics);
+        }
+        self.xr_runtime = Some(runtime);
+    }
+
+    /// Poses de la cabeza y los mandos del último frame de VR
+    pub fn get_vr_pose(&self) -> Option<camera::xr::VRPose> {
+        self.camera_system.get_vr_pose()
+    }
+
+    /// Renderiza un frame y devuelve la imagen final en RGBA8
+    pub async fn capture_frame(&mut self) -> Result<renderer::backend::ImageData, Box<dyn std::error::Error>> {
+        self.queue_frame();
+        Ok(self.renderer_system.capture_frame().await?)
+    }
+
+    /// Renderiza la escena desde la cámara de `camera` en un destino offscreen
+    /// de `width` x `height` (p. ej. miniaturas de objetos)
+    pub fn render_to_texture(
+        &mut self,
+        camera: ecs::EntityId,
+        width: u32,
+        height: u32,
+    ) -> Result<renderer::RenderTextureHandle, Box<dyn std::error::Error>> {
+        Ok(self.renderer_system.render_to_texture(&self.ecs_system, camera, width, height)?)
+    }
+
+    /// Lee la imagen RGBA8 de un destino offscreen
+    pub async fn read_render_texture(
+        &mut self,
+        handle: renderer::RenderTextureHandle,
+    ) -> Result<renderer::backend::ImageData, Box<dyn std::error::Error>> {
+        Ok(self.renderer_system.read_render_texture(handle).await?)
+    }
+
+    /// Libera un destino offscreen
+    pub fn release_render_texture(&mut self, handle: renderer::RenderTextureHandle) {
+        self.renderer_system.release_render_texture(handle);
+    }
+
+    /// Encola la escena, el terreno y el debug draw del frame, y decide qué
+    /// entidades dibuja cada cascada de sombras
+    fn queue_frame(&mut self) {
+        self.renderer_system.submit_scene(&self.ecs_system);
+        self.lighting_system.prepare_shadow_casters(&self.ecs_system, &mut self.renderer_system);
+        self.terrain_system.queue_draws(&mut self.renderer_system);
+        let debug_draw = self.renderer_system.debug_draw_mut();
+        if debug_draw.is_enabled() {
+            self.physics_system.draw_debug(debug_draw);
+            self.camera_system.draw_debug(debug_draw);
+        }
+    }
+
+    /// Carga un modelo glTF/GLB y crea sus entidades; devuelve las entidades raíz
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn load_model(&mut self, path: &str) -> Result<Vec<ecs::EntityId>, Box<dyn std::error::Error>> {
+        let model = renderer::gltf_loader::load_gltf(std::path::Path::new(path))?;
+        self.spawn_model(model).await
+    }
+
+    /// Carga un modelo glTF/GLB desde bytes (WASM, sin sistema de archivos)
+    pub async fn load_model_from_bytes(&mut self, name: &str, bytes: &[u8]) -> Result<Vec<ecs::EntityId>, Box<dyn std::error::Error>> {
+        let model = renderer::gltf_loader::load_gltf_slice(name, bytes)?;
+        self.spawn_model(model).await
+    }
+
+    /// Pide la carga asíncrona de un modelo o textura (disco o HTTP) sin
+    /// bloquear el frame; el resultado se recoge con `spawn_loaded_asset`
+    pub fn enqueue_load(&mut self, path: &str, priority: u8) -> renderer::assets::AssetHandle {
+        self.renderer_system.enqueue_load(path, priority)
+    }
+
+    /// Registra un asset ya cargado y, si es un modelo, crea sus entidades.
+    /// Devuelve `None` mientras la carga sigue en curso
+    pub async fn spawn_loaded_asset(&mut self, handle: renderer::assets::AssetHandle) -> Result<Option<Vec<ecs::EntityId>>, Box<dyn std::error::Error>> {
+        use renderer::assets::{AssetState, LoadedAsset};
+
+        let asset = match self.renderer_system.get_asset(handle) {
+            AssetState::Loading => return Ok(None),
+            AssetState::Ready(asset) => asset,
+            AssetState::Failed(e) => return Err(e.into()),
+            AssetState::Cancelled => return Err("Carga cancelada".into()),
+        };
+        self.renderer_system.release_asset(handle);
+        match asset.as_ref() {
+            LoadedAsset::Model(model) => self.spawn_model(model.clone()).await.map(Some),
+            LoadedAsset::Texture(texture) => {
+                self.renderer_system.load_texture(texture.clone()).await?;
+                Ok(Some(Vec::new()))
+            }
+        }
+    }
+
+    /// Registra los recursos del modelo y crea una entidad por nodo
+    async fn spawn_model(&mut self, model: renderer::gltf_loader::ModelData) -> Result<Vec<ecs::EntityId>, Box<dyn std::error::Error>> {
+        for texture in model.textures.iter().cloned() {
+            self.renderer_system.load_texture(texture).await?;
+        }
+        for material in model.materials.iter().cloned() {
+            self.renderer_system.create_material(material).await?;
+        }
+        let meshes: HashMap<String, renderer::Mesh> = model.meshes.iter()
+            .map(|mesh| (mesh.id.clone(), mesh.clone()))
+            .collect();
+        for mesh in model.meshes.iter().cloned() {
+            self.renderer_system.create_mesh(mesh).await?;
+        }
+
+        // Los niveles de LOD se simplifican una vez por mesh al importar
+        let lod_factors = self.renderer_system.lod_reduction_factors();
+        let lod_indices: HashMap<String, Vec<Vec<u32>>> = model.meshes.iter()
+            .filter(|_| !lod_factors.is_empty())
+            .map(|mesh| {
+                let positions: Vec<glam::Vec3> = mesh.geometry.vertices.iter().map(|v| v.position).collect();
+                let levels = lod_factors.iter()
+                    .map(|&factor| renderer::lod::simplify(&positions, &mesh.geometry.indices, factor))
+                    .collect();
+                (mesh.id.clone(), levels)
+            })
+            .collect();
+
+        // Crear primero las entidades para poder enlazar padres e hijos
+        let mut entities = Vec::with_capacity(model.nodes.len());
+        for node in &model.nodes {
+            entities.push(self.ecs_system.create_entity(format!("{}/{}", model.name, node.name)).await?);
+        }
+
+        for (index, node) in model.nodes.iter().enumerate() {
+            let entity_id = entities[index];
+            let mut children: Vec<ecs::EntityId> = node.children.iter().map(|&child| entities[child]).collect();
+
+            // Cada primitiva adicional cuelga del nodo como entidad hija
+            let mut mesh_entities = vec![(entity_id, node.meshes.first())];
+            for (i, mesh_id) in node.meshes.iter().enumerate().skip(1) {
+                let child = self.ecs_system.create_entity(format!("{}/{}#{}", model.name, node.name, i)).await?;
+                self.ecs_system.add_component(child, Box::new(ecs::TransformComponent {
+                    position: glam::Vec3::ZERO,
+                    rotation: glam::Quat::IDENTITY,
+                    scale: glam::Vec3::ONE,
+                    matrix: glam::Mat4::IDENTITY,
+                    parent: Some(entity_id),
+                    children: Vec::new(),
+                })).await?;
+                children.push(child);
+                mesh_entities.push((child, Some(mesh_id)));
+            }
+
+            self.ecs_system.add_component(entity_id, Box::new(ecs::TransformComponent {
+                position: node.translation,
+                rotation: node.rotation,
+                scale: node.scale,
+                matrix: model.local_matrix(index),
+                parent: node.parent.map(|parent| entities[parent]),
+                children,
+            })).await?;
+
+            for (target, mesh_id) in mesh_entities {
+                let Some(mesh) = mesh_id.and_then(|id| meshes.get(id)) else {
+                    continue;
+                };
+                let vertices = &mesh.geometry.vertices;
+                self.ecs_system.add_component(target, Box::new(ecs::MeshComponent {
+                    mesh_id: mesh.id.clone(),
+                    vertices: vertices.iter().map(|v| v.position).collect(),
+                    normals: vertices.iter().map(|v| v.normal).collect(),
+                    uvs: vertices.iter().map(|v| v.uv).collect(),
+                    indices: mesh.geometry.indices.clone(),
+                    material_id: mesh.material.clone(),
+                    lod_level: 0,
+                    lod_indices: lod_indices.get(&mesh.id).cloned().unwrap_or_default(),
+                    joint_indices: mesh.geometry.joints.clone(),
+                    joint_weights: mesh.geometry.weights.clone(),
+                    render_layers: ecs::RENDER_LAYER_DEFAULT,
+                })).await?;
+            }
+        }
+
+        // Skins para el sistema de animaciones
+        for (index, skin) in model.skins.iter().enumerate() {
+            let entity_id = model.nodes.iter()
+                .position(|node| node.skin == Some(index))
+                .map(|node| entities[node]);
+            self.animation_system.register_skin(animations::Skin {
+                id: format!("{}#skin{}", model.name, index),
+                entity_id,
+                joints: skin.joints.iter().map(|&joint| entities[joint]).collect(),
+                inverse_bind_matrices: skin.inverse_bind_matrices.clone(),
+                skeleton_root: skin.skeleton.map(|node| entities[node]),
+            });
+        }
+
+        info!("📦 Modelo {} cargado: {} nodos, {} meshes", model.name, model.nodes.len(), model.meshes.len());
+        Ok(model.roots.iter().map(|&root| entities[root]).collect())
+    }
+
     /// Limpia el motor
     pub async fn cleanup(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         info!("🧹 Limpiando motor 3D...");
@@ -322,6 +831,8 @@ impl Engine3D {
         self.networking_system.cleanup().await?;
         self.wasm_system.cleanup().await?;
         self.renderer_system.cleanup().await?;
+        self.xr_runtime = None;
+        self.terrain_system.cleanup().await?;
         self.scene_system.cleanup().await?;
         self.camera_system.cleanup().await?;
         self.lighting_system.cleanup().await?;
@@ -344,6 +855,7 @@ impl Engine3D {
         self.wasm_system.health_check().await &&
         self.renderer_system.health_check().await &&
         self.scene_system.health_check().await &&
+        self.terrain_system.health_check().await &&
         self.camera_system.health_check().await &&
         self.lighting_system.health_check().await &&
         self.material_system.health_check().await &&
@@ -362,6 +874,7 @@ impl Engine3D {
             wasm_stats: self.wasm_system.get_stats(),
             renderer_stats: self.renderer_system.get_stats(),
             scene_stats: self.scene_system.get_stats(),
+            terrain_stats: self.terrain_system.get_stats(),
             camera_stats: self.camera_system.get_stats(),
             lighting_stats: self.lighting_system.get_stats(),
             material_stats: self.material_system.get_stats(),
@@ -382,6 +895,49 @@ impl Engine3D {
         &self.physics_system
     }
 
+    /// Registra una zona de trigger de física
+    pub fn add_trigger_zone(&mut self, zone: physics::triggers::TriggerZone) -> anyhow::Result<physics::triggers::TriggerZoneId> {
+        self.physics_system.add_trigger_zone(zone)
+    }
+
+    /// Quita una zona de trigger de física
+    pub fn remove_trigger_zone(&mut self, id: physics::triggers::TriggerZoneId) -> Option<physics::triggers::TriggerZone> {
+        self.physics_system.remove_trigger_zone(id)
+    }
+
+    /// Mueve una entidad con un controlador de personaje sobre su cuerpo
+    /// cinemático; el root motion de sus animaciones pasa por él
+    pub fn add_character_controller(
+        &mut self,
+        entity_id: ecs::EntityId,
+        body_id: &str,
+        config: physics::character::CharacterControllerConfig,
+    ) -> anyhow::Result<()> {
+        self.physics_system.add_character_controller(entity_id, body_id, config)
+    }
+
+    /// Crea la masa de agua SPH de la física
+    pub fn set_fluid(&mut self, fluid: physics::fluid::SphFluid) {
+        self.physics_system.set_fluid(fluid)
+    }
+
+    /// Convierte en tela la malla de una entidad, colocada con su
+    /// transformación. La malla de la entidad sigue a la simulación
+    pub fn create_cloth(&mut self, entity_id: ecs::EntityId, mut config: physics::cloth::ClothConfig) -> anyhow::Result<physics::cloth::ClothId> {
+        let mesh = self.ecs_system.get_component::<ecs::MeshComponent>(entity_id, ecs::ComponentType::Mesh)
+            .ok_or_else(|| anyhow::anyhow!("La entidad {} no tiene malla", entity_id))?;
+        if let Some(transform) = self.ecs_system.get_component::<ecs::TransformComponent>(entity_id, ecs::ComponentType::Transform) {
+            config.transform = transform.matrix;
+        }
+        config.entity_id = Some(entity_id);
+        Ok(self.physics_system.create_cloth(&mesh, config))
+    }
+
+    /// Malla de la superficie del agua SPH, si hay
+    pub fn get_fluid_surface_mesh(&self) -> Option<ecs::MeshComponent> {
+        self.physics_system.get_fluid().map(|fluid| fluid.get_surface_mesh())
+    }
+
     /// Obtiene el sistema de networking
     pub fn get_networking_system(&self) -> &networking::NetworkingSystem {
         &self.networking_system
@@ -397,11 +953,89 @@ impl Engine3D {
         &self.renderer_system
     }
 
+    /// Carga una fuente TrueType/OpenType para las etiquetas y la UI
+    pub fn load_font(&mut self, font_id: &str, bytes: &[u8], size_px: f32) -> anyhow::Result<()> {
+        self.renderer_system.load_font_from_bytes(font_id, bytes, size_px)
+    }
+
+    /// Dibuja una etiqueta sobre una entidad en el próximo frame
+    pub fn draw_text(&mut self, entity: ecs::EntityId, text: &str, style: &ecs::TextStyle) {
+        self.renderer_system.draw_text(entity, text, style)
+    }
+
+    /// Capa de debug draw del próximo frame (desactivada por defecto)
+    pub fn debug_draw(&mut self) -> &mut renderer::debug_draw::DebugDraw {
+        self.renderer_system.debug_draw_mut()
+    }
+
+    /// Elige qué dibuja la física en el debug draw: colliders y contactos
+    pub fn set_physics_debug_draw(&mut self, colliders: bool, contacts: bool) {
+        self.physics_system.set_debug_draw(colliders, contacts);
+    }
+
+    /// Dibuja en el debug draw el frustum de la cámara del controlador
+    pub fn set_camera_debug_draw(&mut self, frustum: bool) {
+        self.camera_system.set_debug_draw(frustum);
+    }
+
+    /// Se une a una sesión de edición colaborativa de la escena ordenada por
+    /// `leader` (este peer si es None)
+    pub fn start_collab_session(&mut self, session_id: &str, leader: Option<libp2p::PeerId>) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(self.networking_system.start_collab_session(session_id, leader)?)
+    }
+
+    /// Sale de la sesión de edición colaborativa
+    pub fn leave_collab_session(&mut self) {
+        self.networking_system.leave_collab_session();
+    }
+
+    /// Envía un comando del ECS a la sesión; se aplica cuando el líder lo ordena
+    pub fn submit_edit(&mut self, command: ecs::ECSCommand) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(self.networking_system.submit_edit(command)?)
+    }
+
     /// Obtiene el sistema de escenas
     pub fn get_scene_system(&self) -> &scene::SceneSystem {
         &self.scene_system
     }
 
+    /// Registra una definición de escena
+    pub fn register_scene(&mut self, definition: scene::definition::SceneDefinition) {
+        self.scene_system.register_scene(definition);
+    }
+
+    /// Carga una escena registrada; sus entidades aparecen en los próximos frames
+    pub async fn load_scene(&mut self, name: &str, mode: scene::LoadMode) -> anyhow::Result<()> {
+        self.scene_system.load_scene(name, mode, &mut self.ecs_system).await
+    }
+
+    /// Descarga una escena y destruye sus entidades
+    pub async fn unload_scene(&mut self, name: &str) -> anyhow::Result<()> {
+        self.scene_system.unload_scene(name, &mut self.ecs_system).await
+    }
+
+    /// Lleva el avatar a un punto de aparición de una escena
+    pub fn transition_to_spawn_point(&mut self, avatar: ecs::EntityId, scene: &str, spawn_point: &str, kind: scene::transition::TransitionKind) -> anyhow::Result<()> {
+        self.scene_system.start_transition(avatar, scene, spawn_point, kind)
+    }
+
+    /// Parte el mundo en islas con su propio ECS, actualizadas en paralelo
+    pub async fn enable_world_partition(&mut self, island_count: u32, ecs_config: &ecs::ECSConfig) -> anyhow::Result<()> {
+        self.scene_system.enable_partition(island_count, ecs_config).await
+    }
+
+    /// Registra un portal entre dos islas
+    pub fn add_portal(&mut self, portal: scene::partition::Portal) -> anyhow::Result<()> {
+        self.scene_system.partition_mut()
+            .ok_or_else(|| anyhow::anyhow!("El mundo no está partido en islas"))?
+            .add_portal(portal)
+    }
+
+    /// Obtiene el sistema de terreno
+    pub fn get_terrain_system(&self) -> &terrain::TerrainSystem {
+        &self.terrain_system
+    }
+
     /// Obtiene el sistema de cámaras
     pub fn get_camera_system(&self) -> &camera::CameraSystem {
         &self.camera_system
@@ -412,6 +1046,11 @@ impl Engine3D {
         &self.lighting_system
     }
 
+    /// Cambiar el entorno HDR (el actual se mantiene hasta que el nuevo está listo)
+    pub fn set_environment(&mut self, path: Option<lighting::EnvironmentPath>) -> anyhow::Result<()> {
+        self.lighting_system.set_environment(path)
+    }
+
     /// Obtiene el sistema de materiales
     pub fn get_material_system(&self) -> &materials::MaterialSystem {
         &self.material_system
@@ -422,6 +1061,98 @@ impl Engine3D {
         &self.animation_system
     }
 
+    /// Registra una cinemática de cámara y luces
+    pub fn create_cinematic(&mut self, cinematic: animations::cinematic::Cinematic) {
+        self.animation_system.create_cinematic(cinematic);
+    }
+
+    /// Reproduce una cinemática
+    pub fn play_cinematic(&mut self, id: &str) -> bool {
+        self.animation_system.play_cinematic(id)
+    }
+
+    /// Pausa una cinemática
+    pub fn pause_cinematic(&mut self, id: &str) -> bool {
+        self.animation_system.pause_cinematic(id)
+    }
+
+    /// Lleva una cinemática a `time`
+    pub fn seek_cinematic(&mut self, id: &str, time: f32) -> bool {
+        self.animation_system.seek_cinematic(id, time)
+    }
+
+    /// Cambia la velocidad de una cinemática
+    pub fn set_cinematic_speed(&mut self, id: &str, speed: f32) -> bool {
+        self.animation_system.set_cinematic_speed(id, speed)
+    }
+
+    /// Cinemáticas terminadas desde la última llamada
+    pub fn drain_finished_cinematics(&mut self) -> Vec<animations::cinematic::CinematicFinished> {
+        self.animation_system.drain_finished_cinematics()
+    }
+
+    /// Registra el callback de Rust de los eventos de animación `Callback`
+    /// con ese nombre de función
+    pub fn register_animation_callback(&mut self, function_name: &str, callback: animations::events::AnimationCallback) {
+        self.animation_system.register_event_callback(function_name, callback);
+    }
+
+    /// Añade muestras del micrófono (mono, 48 kHz) al chat de voz
+    pub fn push_microphone_frame(&mut self, pcm: &[i16]) -> anyhow::Result<()> {
+        self.audio_system.push_microphone_frame(pcm)
+    }
+
+    /// Entidad (el avatar local) en la que los demás oyen la voz local
+    pub fn set_voice_entity(&mut self, entity_id: Option<ecs::EntityId>) {
+        self.networking_system.set_voice_entity(entity_id);
+    }
+
+    /// Añade un modificador procedural (ruido, muelle, look-at) a la pose de
+    /// una entidad animada
+    pub fn add_procedural_modifier(&mut self, entity_id: ecs::EntityId, modifier: animations::procedural::ProceduralModifier) {
+        self.animation_system.add_procedural_modifier(entity_id, modifier);
+    }
+
+    /// Fija el objetivo en espacio mundo de un constraint IK de dos huesos
+    pub fn set_ik_target(&mut self, entity_id: ecs::EntityId, constraint_id: &str, goal: animations::ik::IkGoal) {
+        self.animation_system.set_ik_target(entity_id, constraint_id, goal);
+    }
+
+    /// Planta los pies de una entidad sobre el terreno con los constraints
+    /// IK de sus piernas
+    pub fn set_foot_placement(&mut self, entity_id: ecs::EntityId, config: animations::ik::FootPlacementConfig) {
+        self.animation_system.set_foot_placement(entity_id, config);
+    }
+
+    /// Cambia el controlador de la cámara activa, mezclando durante
+    /// `blend_duration` segundos desde la pose actual
+    pub fn set_camera_controller(&mut self, controller: camera::controllers::CameraController, blend_duration: f32) {
+        self.camera_system.set_controller(controller, blend_duration);
+    }
+
+    /// Reproduce una ruta cinemática sobre la cámara activa
+    pub fn play_camera_path(&mut self, path: &camera::path::CameraPath) -> camera::path::CameraPlaybackHandle {
+        self.camera_system.play_path(path)
+    }
+
+    /// Envía la entrada de cámara del frame (ratón y rueda)
+    pub fn push_camera_input(&mut self, input: &camera::controllers::CameraInput) {
+        self.camera_system.push_input(input);
+    }
+
+    /// Crea el avatar de un wallet a partir de sus NFTs (generado una vez
+    /// por dirección) y devuelve la entidad raíz
+    pub async fn spawn_wallet_avatar(&mut self, wallet_address: &str, nfts: &[crypto::NFTMetadata]) -> anyhow::Result<ecs::EntityId> {
+        let config = self.scene_system.avatar_for_wallet(wallet_address, nfts).clone();
+        renderer::avatar::assemble_avatar(&config, &mut self.material_system, &mut self.ecs_system).await
+    }
+
+    /// Hora del día normalizada (0 medianoche, 0.5 mediodía) para el
+    /// ambiente sonoro de las zonas
+    pub fn set_day_time(&mut self, day_time: f32) {
+        self.audio_system.set_day_time(day_time);
+    }
+
     /// Obtiene el sistema de audio
     pub fn get_audio_system(&self) -> &audio::AudioSystem {
         &self.audio_system
@@ -438,6 +1169,59 @@ impl Engine3D {
     }
 }
 
+/// Las optimizaciones automáticas del profiling actúan sobre el renderer, el
+/// ECS y el networking del motor (`ProfilingSystem::apply_optimizations`)
+impl profiling::OptimizationHooks for Engine3D {
+    fn lod_distance_bias(&self) -> f32 {
+        self.renderer_system.lod_distance_bias()
+    }
+
+    fn set_lod_distance_bias(&mut self, bias: f32) {
+        self.renderer_system.set_lod_distance_bias(bias);
+    }
+
+    fn distance_culling(&self) -> profiling::DistanceCulling {
+        let (enabled, max_distance) = self.renderer_system.distance_culling();
+        profiling::DistanceCulling { enabled, max_distance }
+    }
+
+    fn set_distance_culling(&mut self, culling: profiling::DistanceCulling) {
+        self.renderer_system.set_distance_culling(culling.enabled, culling.max_distance);
+    }
+
+    fn worker_threads(&self) -> usize {
+        self.ecs_system.max_parallel_systems()
+    }
+
+    fn set_worker_threads(&mut self, threads: usize) {
+        self.ecs_system.set_max_parallel_systems(threads);
+    }
+
+    fn reclaim_memory(&mut self) -> usize {
+        self.ecs_system.shrink_component_pools() + self.renderer_system.evict_asset_cache()
+    }
+
+    fn gpu_quality(&self) -> profiling::GpuQuality {
+        profiling::GpuQuality {
+            shadow_resolution: self.renderer_system.shadow_resolution(),
+            msaa_samples: self.renderer_system.msaa_samples(),
+        }
+    }
+
+    fn set_gpu_quality(&mut self, quality: profiling::GpuQuality) {
+        self.renderer_system.set_shadow_resolution(quality.shadow_resolution);
+        self.renderer_system.set_msaa_samples(quality.msaa_samples);
+    }
+
+    fn replication_tick_rate(&self) -> f32 {
+        self.networking_system.replication_tick_rate()
+    }
+
+    fn set_replication_tick_rate(&mut self, rate: f32) {
+        self.networking_system.set_replication_tick_rate(rate);
+    }
+}
+
 /// Estadísticas del motor
 #[derive(Debug, Clone)]
 pub struct EngineStats {
@@ -453,6 +1237,8 @@ pub struct EngineStats {
     pub renderer_stats: renderer::RendererStats,
     /// Estadísticas de escenas
     pub scene_stats: scene::SceneStats,
+    /// Estadísticas del terreno
+    pub terrain_stats: terrain::TerrainStats,
     /// Estadísticas de cámaras
     pub camera_stats: camera::CameraStats,
     /// Estadísticas de iluminación
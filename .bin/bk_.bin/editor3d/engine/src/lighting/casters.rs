@@ -0,0 +1,114 @@
+//! # Casters de sombra
+//!
+//! Selección por cascada de las entidades que pueden proyectar sombra dentro
+//! de la vista. Una entidad entra en el shadow map de una cascada si su caja
+//! intersecta el frustum ortográfico de la cascada y si la caja extruida en
+//! la dirección de la luz (el volumen de su sombra) intersecta el frustum de
+//! la cámara. Las dos pruebas son conservadoras: pueden dejar pasar una
+//! entidad cuya sombra no se ve, nunca descartan una que sí.
+//!
+//! Las cajas en mundo se guardan en `AabbCache`; la caja local de cada mesh
+//! sale de sus vértices una vez y solo se recalcula si cambian.
+
+use glam::{Mat4, Vec3};
+use std::collections::HashMap;
+
+use crate::ecs::{ComponentType, ECSSystem, EntityId, MeshComponent, TransformComponent};
+use crate::renderer::culling::{Aabb, Frustum, MeshBoundsCache};
+use crate::renderer::shadows::DEFAULT_SHADOW_DISTANCE;
+
+/// Cajas envolventes en mundo de las entidades con malla
+#[derive(Debug, Clone, Default)]
+pub struct AabbCache {
+    /// Cajas locales por mesh
+    meshes: MeshBoundsCache,
+    /// Caja en mundo por entidad
+    entities: HashMap<EntityId, Aabb>,
+}
+
+impl AabbCache {
+    /// Caché vacía
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Guardar la caja de una entidad a partir de su malla y su modelo
+    pub fn update_mesh(&mut self, entity_id: EntityId, mesh: &MeshComponent, model: &Mat4) {
+        let local = self.meshes.get(&mesh.mesh_id, &mesh.vertices);
+        self.entities.insert(entity_id, local.transformed(model));
+    }
+
+    /// Guardar directamente la caja en mundo de una entidad
+    pub fn insert(&mut self, entity_id: EntityId, aabb: Aabb) {
+        self.entities.insert(entity_id, aabb);
+    }
+
+    /// Quitar una entidad
+    pub fn remove(&mut self, entity_id: EntityId) {
+        self.entities.remove(&entity_id);
+    }
+
+    /// Caja en mundo de una entidad
+    pub fn get(&self, entity_id: EntityId) -> Option<Aabb> {
+        self.entities.get(&entity_id).copied()
+    }
+
+    /// Entidades con caja
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Sin entidades
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Sincronizar con las entidades del mundo que tienen malla y transformación
+    pub fn sync(&mut self, world: &ECSSystem) {
+        let mut seen = Vec::new();
+        for entity_id in world.get_entities_with_component(ComponentType::Mesh) {
+            let Some(mesh) = world.get_component::<MeshComponent>(entity_id, ComponentType::Mesh) else {
+                continue;
+            };
+            let Some(transform) = world.get_component::<TransformComponent>(entity_id, ComponentType::Transform) else {
+                continue;
+            };
+            let model = Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position);
+            self.update_mesh(entity_id, &mesh, &model);
+            seen.push(entity_id);
+        }
+        seen.sort_unstable();
+        self.entities.retain(|entity_id, _| seen.binary_search(entity_id).is_ok());
+    }
+
+    /// Cajas en mundo de todas las entidades
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &Aabb)> {
+        self.entities.iter().map(|(entity_id, aabb)| (*entity_id, aabb))
+    }
+}
+
+/// Caja que cubre una caja y su sombra hasta `distance` en la dirección de la luz
+pub fn shadow_volume(aabb: &Aabb, light_direction: Vec3, distance: f32) -> Aabb {
+    let offset = light_direction.try_normalize().unwrap_or(Vec3::NEG_Y) * distance;
+    Aabb::new(aabb.min.min(aabb.min + offset), aabb.max.max(aabb.max + offset))
+}
+
+/// Entidades de la caché que proyectan sombra en la cascada con la matriz
+/// vista-proyección de luz dada y cuya sombra puede caer en la vista
+pub fn shadow_caster_set(
+    cache: &AabbCache,
+    camera_frustum: &Frustum,
+    light_direction: Vec3,
+    cascade_view_projection: &Mat4,
+) -> Vec<EntityId> {
+    let light_frustum = Frustum::from_view_projection(cascade_view_projection);
+    let mut casters: Vec<EntityId> = cache.iter()
+        .filter(|(_, aabb)| light_frustum.intersects(aabb))
+        .filter(|(_, aabb)| {
+            camera_frustum.intersects(&shadow_volume(aabb, light_direction, DEFAULT_SHADOW_DISTANCE))
+        })
+        .map(|(entity_id, _)| entity_id)
+        .collect();
+    casters.sort_unstable();
+    casters
+}
@@ -2,16 +2,139 @@
 //! 
 //! Sistema de gestión de iluminación 3D para el metaverso.
 //! Proporciona diferentes tipos de luces y efectos de iluminación.
-
+//!
+//! El entorno HDR (iluminación de imagen y skybox) se carga y precalcula en
+//! segundo plano. Mientras tanto se sigue dibujando el entorno anterior;
+//! cuando los mapas nuevos están listos, `sync` los sube al renderer, cambia
+//! el entorno del frame y libera el anterior en el mismo paso, de modo que el
+//! cambio no deja ningún frame sin entorno.
+//!
+//! Las luces puntuales y los focos se dibujan con clustered shading: la
+//! rejilla de clusters y las luces que caben en cada uno se configuran en
+//! `LightingConfig::clustering`.
+//!
+//! Cada frame `prepare_shadow_casters` decide qué entidades entran en cada
+//! cascada de sombras (ver `casters`); las demás no se dibujan en ese shadow
+//! map.
+
+pub mod casters;
+
+use glam::{Mat4, Vec3};
 use serde::{Serialize, Deserialize};
-use tracing::{info, debug};
+use tokio::task::JoinHandle;
+use tracing::{info, debug, warn};
+
+use crate::ecs::{ECSSystem, EntityId};
+use crate::renderer::RendererSystem;
+use crate::renderer::culling::Frustum;
+use crate::renderer::backend::EnvironmentFrame;
+use crate::renderer::clustering::LightClusterConfig;
+use crate::renderer::environment::{EnvironmentMaps, EnvironmentSettings, EnvironmentSource, HdrImage};
+
+/// Configuración del sistema de iluminación
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightingConfig {
+    /// Habilitado
+    pub enabled: bool,
+    /// Luz ambiente
+    pub ambient_lighting: bool,
+    /// Luces direccionales
+    pub directional_lighting: bool,
+    /// Luces puntuales
+    pub point_lighting: bool,
+    /// Luces focales
+    pub spot_lighting: bool,
+    /// Entorno HDR
+    #[serde(default)]
+    pub environment: EnvironmentConfig,
+    /// Rejilla de clusters de las luces puntuales y los focos
+    #[serde(default)]
+    pub clustering: LightClusterConfig,
+}
+
+impl Default for LightingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ambient_lighting: true,
+            directional_lighting: true,
+            point_lighting: true,
+            spot_lighting: true,
+            environment: EnvironmentConfig::default(),
+            clustering: LightClusterConfig::default(),
+        }
+    }
+}
+
+/// Configuración del entorno HDR
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvironmentConfig {
+    /// Imagen del entorno (None sin IBL ni skybox)
+    pub source: Option<EnvironmentPath>,
+    /// Multiplicador de la luz del entorno y del skybox
+    pub intensity: f32,
+    /// Giro del entorno alrededor de Y en radianes
+    pub rotation: f32,
+    /// Dibujar el entorno como fondo
+    pub skybox: bool,
+    /// Resolución del precálculo
+    pub settings: EnvironmentSettings,
+}
+
+impl Default for EnvironmentConfig {
+    fn default() -> Self {
+        Self {
+            source: None,
+            intensity: 1.0,
+            rotation: 0.0,
+            skybox: true,
+            settings: EnvironmentSettings::default(),
+        }
+    }
+}
+
+/// Ficheros Radiance `.hdr` de un entorno
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EnvironmentPath {
+    /// Panorama equirectangular
+    Equirectangular(String),
+    /// Seis caras en orden +X, -X, +Y, -Y, +Z, -Z
+    Cubemap([String; 6]),
+}
+
+/// Entorno activo y carga en curso
+#[derive(Default)]
+struct EnvironmentState {
+    /// ID del entorno que se está dibujando
+    active: Option<String>,
+    /// Mapas precalculados a la espera de subirse al renderer
+    ready: Option<EnvironmentMaps>,
+    /// Carga y precálculo en segundo plano
+    loading: Option<JoinHandle<anyhow::Result<EnvironmentMaps>>>,
+    /// Quitar el entorno activo en el próximo `sync`
+    clear_active: bool,
+    /// Entornos cargados (cada carga tiene un ID nuevo para que el anterior
+    /// siga en la GPU hasta el cambio)
+    generation: u64,
+}
 
 /// Sistema de iluminación principal
 pub struct LightingSystem {
+    /// Configuración
+    config: LightingConfig,
     /// Luces de la escena
     lights: Vec<Light>,
     /// Configuración global de iluminación
     global_config: GlobalLightingConfig,
+    /// Entorno HDR
+    environment: EnvironmentState,
+    /// Cajas en mundo de las entidades con malla
+    aabb_cache: casters::AabbCache,
+    /// Matriz vista-proyección de la luz por cascada del frame
+    shadow_cascades: Vec<Mat4>,
+    /// Dibujos en shadow maps descartados en el último frame
+    shadow_casters_culled: u32,
     /// Estado del sistema
     state: LightingState,
 }
@@ -957,10 +1080,11 @@ pub struct LightingState {
 
 impl LightingSystem {
     /// Crea un nuevo sistema de iluminación
-    pub fn new() -> Self {
+    pub fn new(config: &LightingConfig) -> Self {
         info!("💡 Inicializando sistema de iluminación...");
         
         Self {
+            config: config.clone(),
             lights: Vec::new(),
             global_config: GlobalLightingConfig {
                 ambient_config: GlobalAmbientConfig {
@@ -1022,8 +1146,12 @@ impl LightingSystem {
                     },
                 },
             },
+            environment: EnvironmentState::default(),
+            aabb_cache: casters::AabbCache::new(),
+            shadow_cascades: Vec::new(),
+            shadow_casters_culled: 0,
             state: LightingState {
-                active: true,
+                active: config.enabled,
                 paused: false,
                 time: 0.0,
                 delta_time: 0.0,
@@ -1031,6 +1159,186 @@ impl LightingSystem {
         }
     }
 
+    /// Inicializar: empieza a cargar el entorno configurado
+    pub async fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        info!("Inicializando sistema de iluminación");
+        if let Some(path) = self.config.environment.source.clone() {
+            self.set_environment(Some(path))?;
+        }
+        Ok(())
+    }
+
+    /// Cambiar el entorno HDR. El actual se sigue dibujando hasta que el
+    /// nuevo está precalculado y subido; con None se quita en el próximo `sync`
+    pub fn set_environment(&mut self, path: Option<EnvironmentPath>) -> anyhow::Result<()> {
+        self.config.environment.source = path.clone();
+        match path {
+            Some(path) => {
+                info!("🌅 Cargando entorno: {:?}", path);
+                self.start_environment_load(async move { load_environment_source(&path).await })
+            }
+            None => {
+                self.cancel_environment_load();
+                self.environment.clear_active = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Cambiar el entorno HDR por una imagen ya en memoria
+    pub fn set_environment_source(&mut self, source: EnvironmentSource) -> anyhow::Result<()> {
+        source.validate()?;
+        self.start_environment_load(async move { Ok(source) })
+    }
+
+    /// Lanzar la lectura y el precálculo de un entorno, descartando una
+    /// carga anterior sin terminar
+    fn start_environment_load(
+        &mut self,
+        source: impl std::future::Future<Output = anyhow::Result<EnvironmentSource>> + Send + 'static,
+    ) -> anyhow::Result<()> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|_| anyhow::anyhow!("La carga de entornos necesita un runtime de tokio"))?;
+        self.cancel_environment_load();
+        self.environment.clear_active = false;
+        self.environment.generation += 1;
+        let id = format!("environment-{}", self.environment.generation);
+        let settings = self.config.environment.settings.clone();
+        self.environment.loading = Some(runtime.spawn(async move {
+            let source = source.await?;
+            let maps = tokio::task::spawn_blocking(move || EnvironmentMaps::precompute(id, &source, &settings)).await?;
+            Ok(maps)
+        }));
+        Ok(())
+    }
+
+    /// Descartar la carga en curso y los mapas aún no subidos
+    fn cancel_environment_load(&mut self) {
+        if let Some(loading) = self.environment.loading.take() {
+            loading.abort();
+        }
+        self.environment.ready = None;
+    }
+
+    /// Cambiar la intensidad del entorno
+    pub fn set_environment_intensity(&mut self, intensity: f32) {
+        self.config.environment.intensity = intensity.max(0.0);
+    }
+
+    /// Cambiar el giro del entorno alrededor de Y, en radianes
+    pub fn set_environment_rotation(&mut self, rotation: f32) {
+        self.config.environment.rotation = rotation;
+    }
+
+    /// Mostrar u ocultar el skybox
+    pub fn set_skybox_enabled(&mut self, enabled: bool) {
+        self.config.environment.skybox = enabled;
+    }
+
+    /// Configuración del entorno
+    pub fn environment_config(&self) -> &EnvironmentConfig {
+        &self.config.environment
+    }
+
+    /// Entorno del frame, si hay uno activo
+    pub fn environment_frame(&self) -> Option<EnvironmentFrame> {
+        let environment = &self.config.environment;
+        self.environment.active.as_ref().map(|id| EnvironmentFrame {
+            id: id.clone(),
+            intensity: environment.intensity,
+            rotation: environment.rotation,
+            skybox: environment.skybox,
+        })
+    }
+
+    /// Recoger la carga de entorno si ha terminado. Si falla se conserva el
+    /// entorno anterior
+    async fn poll_environment_load(&mut self) {
+        if !self.environment.loading.as_ref().is_some_and(|loading| loading.is_finished()) {
+            return;
+        }
+        let Some(loading) = self.environment.loading.take() else {
+            return;
+        };
+        match loading.await {
+            Ok(Ok(maps)) => {
+                debug!("Entorno {} precalculado ({} niveles de especular)", maps.id, maps.specular.len());
+                self.environment.ready = Some(maps);
+            }
+            Ok(Err(error)) => warn!("No se pudo cargar el entorno: {}", error),
+            Err(error) => warn!("La carga del entorno terminó con error: {}", error),
+        }
+    }
+
+    /// Subir el entorno listo, cambiarlo por el activo y pasar el entorno
+    /// del frame y la configuración de las luces locales al renderer
+    pub fn sync(&mut self, renderer: &mut RendererSystem) -> anyhow::Result<()> {
+        if std::mem::take(&mut self.environment.clear_active) {
+            if let Some(previous) = self.environment.active.take() {
+                renderer.remove_environment(&previous);
+            }
+        }
+        if let Some(maps) = self.environment.ready.take() {
+            if renderer.upload_environment(&maps)? {
+                info!("🌅 Entorno {} activo", maps.id);
+                if let Some(previous) = self.environment.active.replace(maps.id) {
+                    renderer.remove_environment(&previous);
+                }
+            } else {
+                // Sin backend todavía: se reintenta en el próximo frame
+                self.environment.ready = Some(maps);
+            }
+        }
+        renderer.set_environment(self.environment_frame());
+        let local_lights = self.config.enabled && (self.config.point_lighting || self.config.spot_lighting);
+        renderer.set_light_clustering(local_lights.then(|| self.config.clustering.clone()));
+        renderer.set_local_light_types(self.config.point_lighting, self.config.spot_lighting);
+        Ok(())
+    }
+
+    /// Calcular las cascadas del frame y pasar al renderer las entidades que
+    /// cada una debe dibujar. Se llama después de `submit_scene`, con la
+    /// cámara y la luz del frame ya fijadas
+    pub fn prepare_shadow_casters(&mut self, world: &ECSSystem, renderer: &mut RendererSystem) {
+        self.aabb_cache.sync(world);
+        let Some((shadows, light_direction)) = renderer.compute_shadow_cascades() else {
+            self.shadow_cascades.clear();
+            self.shadow_casters_culled = 0;
+            renderer.set_shadow_casters(None);
+            return;
+        };
+        self.shadow_cascades = shadows.cascades;
+
+        let camera_frustum = Frustum::from_view_projection(&renderer.view_projection());
+        let queued = renderer.queued_entities();
+        let mut culled = 0;
+        let sets: Vec<Vec<EntityId>> = (0..self.shadow_cascades.len())
+            .map(|cascade| {
+                let set = self.compute_shadow_caster_set(&camera_frustum, light_direction, cascade);
+                culled += queued.iter().filter(|entity_id| set.binary_search(entity_id).is_err()).count();
+                set
+            })
+            .collect();
+        self.shadow_casters_culled = culled as u32;
+        debug!("🌑 {} dibujos de sombra descartados", culled);
+        renderer.set_shadow_casters(Some(sets));
+    }
+
+    /// Entidades (ordenadas) cuya caja intersecta el frustum de luz de la
+    /// cascada y cuya sombra puede caer dentro del frustum de la cámara. Vacío
+    /// si la cascada no existe en el frame
+    pub fn compute_shadow_caster_set(&self, camera_frustum: &Frustum, light_dir: Vec3, cascade: usize) -> Vec<EntityId> {
+        match self.shadow_cascades.get(cascade) {
+            Some(view_projection) => casters::shadow_caster_set(&self.aabb_cache, camera_frustum, light_dir, view_projection),
+            None => Vec::new(),
+        }
+    }
+
+    /// Cajas en mundo de las entidades con malla
+    pub fn aabb_cache(&self) -> &casters::AabbCache {
+        &self.aabb_cache
+    }
+
     /// Agrega una luz al sistema
     pub fn add_light(&mut self, light: Light) -> Result<(), Box<dyn std::error::Error>> {
         info!("➕ Agregando luz: {} ({})", light.name, light.id);
@@ -1058,10 +1366,11 @@ impl LightingSystem {
     }
 
     /// Actualiza el sistema de iluminación
-    pub fn update(&mut self, delta_time: f32) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn update(&mut self, delta_time: f32) -> Result<(), Box<dyn std::error::Error>> {
         if !self.state.active || self.state.paused {
             return Ok(());
         }
+        self.poll_environment_load().await;
         
         // Actualizar tiempo del sistema
         self.state.delta_time = delta_time;
@@ -1184,8 +1493,47 @@ impl LightingSystem {
             light_count: self.lights.len(),
             active_lights: self.lights.iter().filter(|light| light.state.active).count(),
             system_time: self.state.time,
+            environment_active: self.environment.active.is_some(),
+            environment_loading: self.environment.loading.is_some() || self.environment.ready.is_some(),
+            shadow_casters_culled: self.shadow_casters_culled,
         }
     }
+
+    /// Verificar salud del sistema
+    pub async fn health_check(&self) -> bool {
+        true
+    }
+
+    /// Limpiar sistema
+    pub async fn cleanup(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        info!("Limpiando sistema de iluminación");
+        self.cancel_environment_load();
+        self.environment.active = None;
+        self.lights.clear();
+        self.aabb_cache = casters::AabbCache::new();
+        self.shadow_cascades.clear();
+        Ok(())
+    }
+}
+
+/// Leer la imagen de un entorno
+async fn load_environment_source(path: &EnvironmentPath) -> anyhow::Result<EnvironmentSource> {
+    let source = match path {
+        EnvironmentPath::Equirectangular(path) => {
+            EnvironmentSource::Equirectangular(HdrImage::from_radiance(&tokio::fs::read(path).await?)?)
+        }
+        EnvironmentPath::Cubemap(paths) => {
+            let mut faces = Vec::with_capacity(paths.len());
+            for path in paths {
+                faces.push(HdrImage::from_radiance(&tokio::fs::read(path).await?)?);
+            }
+            let faces: Box<[HdrImage; 6]> = faces.into_boxed_slice().try_into()
+                .map_err(|_| anyhow::anyhow!("Un cubemap necesita seis caras"))?;
+            EnvironmentSource::Cubemap(faces)
+        }
+    };
+    source.validate()?;
+    Ok(source)
 }
 
 /// Estadísticas del sistema de iluminación
@@ -1197,4 +1545,11 @@ pub struct LightingStats {
     pub active_lights: usize,
     /// Tiempo del sistema
     pub system_time: f32,
-} 
\ No newline at end of file
+    /// Hay un entorno HDR activo
+    pub environment_active: bool,
+    /// Hay un entorno cargándose o a la espera de subirse
+    pub environment_loading: bool,
+    /// Entidades visibles que no se dibujaron en un shadow map por no
+    /// proyectar sombra en él (sumadas sobre las cascadas)
+    pub shadow_casters_culled: u32,
+}
@@ -28,6 +28,10 @@ use blockchain::BlockchainSystem;
 use profiling::ProfilingSystem;
 use utils::UtilsSystem;
 
+/// Allocator global con telemetría de memoria para el sistema de profiling
+#[global_allocator]
+static A: profiling::allocator::TracingAllocator = profiling::allocator::TracingAllocator::new();
+
 /// Motor 3D principal
 pub struct Engine3D {
     /// Sistema ECS
@@ -149,8 +153,8 @@ impl Engine3D {
             return Ok(());
         }
 
-        // Iniciar profiling del frame
-        self.profiling.start_profiler("frame").unwrap();
+        // Scope raíz del frame: los scopes de los sistemas cuelgan de él
+        crate::profile_scope!("frame");
 
         // Actualizar sistema de utilidades
         let mut utils = Arc::get_mut(&mut self.utils).unwrap();
@@ -188,8 +192,13 @@ impl Engine3D {
         let mut rendering = Arc::get_mut(&mut self.rendering).unwrap();
         rendering.render().await?;
 
-        // Detener profiling del frame
-        self.profiling.stop_profiler("frame").unwrap();
+        // Objetos renderizados tras el culling
+        let render_stats = rendering.get_stats();
+        self.profiling.record_render_stats(
+            render_stats.rendered_objects,
+            render_stats.triangles,
+            render_stats.draw_calls,
+        );
 
         Ok(())
     }
@@ -470,6 +479,7 @@ async fn main() -> Result<()> {
             hot_reloading: true,
             sandboxing: true,
             memory_limit: 1024 * 1024 * 1024, // 1GB
+            scripting_max_ops_per_frame: 100_000,
         },
         blockchain_config: blockchain::BlockchainConfig {
             enabled: true,
@@ -515,6 +525,7 @@ async fn main() -> Result<()> {
                     nonce_pool: true,
                 },
             },
+            flashbots_relay_url: None,
         },
         profiling_config: profiling::ProfilingConfig {
             enabled: true,
@@ -527,6 +538,7 @@ async fn main() -> Result<()> {
                 network_metrics: true,
                 io_metrics: true,
                 data_retention: std::time::Duration::from_secs(3600), // 1 hora
+                allocation_sample_threshold: 1024 * 1024, // 1MB
             },
             optimization_config: profiling::OptimizationConfig {
                 auto_optimizations: true,
@@ -557,6 +569,8 @@ async fn main() -> Result<()> {
                     work_stealing: true,
                     load_balancing: true,
                 },
+                evaluation_window: std::time::Duration::from_secs(5),
+                min_improvement: 0.05,
             },
             reporting_config: profiling::ReportingConfig {
                 auto_reports: true,
@@ -566,6 +580,7 @@ async fn main() -> Result<()> {
                     csv_format: true,
                     html_format: true,
                     export_directory: "./reports".to_string(),
+                    max_export_bytes: 64 * 1024 * 1024,
                 },
                 alert_config: profiling::AlertConfig {
                     performance_alerts: true,
@@ -574,7 +589,9 @@ async fn main() -> Result<()> {
                         console_notifications: true,
                         file_notifications: true,
                         network_notifications: false,
+                        alert_file: "./reports/alerts.jsonl".to_string(),
                     },
+                    rules: Vec::new(),
                 },
             },
         },
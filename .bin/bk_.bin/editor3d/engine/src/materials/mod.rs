@@ -2,10 +2,17 @@
 //! 
 //! Sistema de gestión de materiales PBR y avanzados para el metaverso.
 //! Proporciona materiales físicamente basados y efectos visuales avanzados.
+//! Los `MaterialComponent` del ECS se siguen con ticks de cambio y solo los
+//! materiales modificados se vuelven a subir al backend de renderizado.
 
 use serde::{Serialize, Deserialize};
 use tracing::{info, debug};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use glam::{Vec3, Vec4};
+
+use crate::ecs::{self, ComponentType, ECSSystem, MaterialComponent};
+use crate::renderer::RendererSystem;
+use crate::renderer::backend::{MaterialDesc, MaterialKind, MaterialParams, MaterialTextureSlots};
 
 /// Sistema de materiales principal
 pub struct MaterialSystem {
@@ -15,10 +22,34 @@ pub struct MaterialSystem {
     shaders: HashMap<String, Shader>,
     /// Texturas cargadas
     textures: HashMap<String, Texture>,
+    /// Materiales de componentes del ECS
+    component_materials: HashMap<String, TrackedMaterial>,
+    /// Materiales de componentes eliminados pendientes de liberar en la GPU
+    removed_materials: Vec<String>,
+    /// Tick de cambio actual
+    change_tick: u64,
     /// Estado del sistema
     running: bool,
 }
 
+/// Material de un componente con sus ticks de cambio
+#[derive(Debug, Clone)]
+struct TrackedMaterial {
+    /// Último descriptor visto
+    desc: MaterialDesc,
+    /// Tick en que cambió por última vez
+    changed_tick: u64,
+    /// Tick de la última versión subida (0 si nunca se subió)
+    uploaded_tick: u64,
+}
+
+impl TrackedMaterial {
+    /// Pendiente de subir
+    fn is_dirty(&self) -> bool {
+        self.changed_tick > self.uploaded_tick
+    }
+}
+
 /// Material principal
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Material {
@@ -562,6 +593,9 @@ impl MaterialSystem {
             materials: HashMap::new(),
             shaders: HashMap::new(),
             textures: HashMap::new(),
+            component_materials: HashMap::new(),
+            removed_materials: Vec::new(),
+            change_tick: 0,
             running: false,
         }
     }
@@ -585,8 +619,8 @@ impl MaterialSystem {
         Ok(())
     }
 
-    /// Actualiza el sistema
-    pub async fn update(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Actualiza el sistema y sube al renderer los materiales modificados
+    pub async fn update(&mut self, renderer: &mut RendererSystem) -> Result<(), Box<dyn std::error::Error>> {
         if !self.running {
             return Ok(());
         }
@@ -597,10 +631,74 @@ impl MaterialSystem {
                 self.update_material(material).await?;
             }
         }
+
+        for material_id in self.removed_materials.drain(..) {
+            renderer.remove_material(&material_id);
+        }
+        // Sin backend los materiales siguen pendientes hasta el siguiente frame
+        for tracked in self.component_materials.values_mut().filter(|tracked| tracked.is_dirty()) {
+            if renderer.upload_material(&tracked.desc)? {
+                debug!("Material {} subido (tick {})", tracked.desc.id, tracked.changed_tick);
+                tracked.uploaded_tick = tracked.changed_tick;
+            }
+        }
         
         Ok(())
     }
 
+    /// Sincronizar los `MaterialComponent` del ECS. El ECS no lleva ticks por
+    /// componente, así que un material cambia en el tick actual cuando su
+    /// descriptor difiere del último visto. Devuelve los materiales cambiados
+    pub fn sync_components(&mut self, world: &ECSSystem) -> usize {
+        self.change_tick += 1;
+        let tick = self.change_tick;
+
+        let mut seen = HashSet::new();
+        let mut changed = 0;
+        for entity_id in world.get_entities_with_component(ComponentType::Material) {
+            let Some(component) = world.get_component::<MaterialComponent>(entity_id, ComponentType::Material) else {
+                continue;
+            };
+            // Entidades que comparten material: cuenta la primera
+            if !seen.insert(component.material_id.clone()) {
+                continue;
+            }
+
+            let desc = material_desc_from_component(&component);
+            match self.component_materials.get_mut(&desc.id) {
+                Some(tracked) if tracked.desc == desc => {}
+                Some(tracked) => {
+                    tracked.desc = desc;
+                    tracked.changed_tick = tick;
+                    changed += 1;
+                }
+                None => {
+                    self.component_materials.insert(desc.id.clone(), TrackedMaterial {
+                        desc,
+                        changed_tick: tick,
+                        uploaded_tick: 0,
+                    });
+                    changed += 1;
+                }
+            }
+        }
+
+        let removed: Vec<String> = self.component_materials.keys()
+            .filter(|id| !seen.contains(*id))
+            .cloned()
+            .collect();
+        for material_id in removed {
+            self.component_materials.remove(&material_id);
+            self.removed_materials.push(material_id);
+        }
+        changed
+    }
+
+    /// Tick de cambio actual
+    pub fn change_tick(&self) -> u64 {
+        self.change_tick
+    }
+
     /// Limpia el sistema
     pub async fn cleanup(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         info!("🧹 Limpiando sistema de materiales...");
@@ -609,6 +707,8 @@ impl MaterialSystem {
         self.materials.clear();
         self.shaders.clear();
         self.textures.clear();
+        self.component_materials.clear();
+        self.removed_materials.clear();
         
         info!("✅ Sistema de materiales limpiado correctamente");
         Ok(())
@@ -869,6 +969,8 @@ impl MaterialSystem {
             texture_count: self.textures.len(),
             active_materials: self.materials.values().filter(|m| m.state.active).count(),
             compiled_materials: self.materials.values().filter(|m| m.state.compiled).count(),
+            component_materials: self.component_materials.len(),
+            pending_uploads: self.component_materials.values().filter(|m| m.is_dirty()).count(),
         }
     }
 }
@@ -886,4 +988,46 @@ pub struct MaterialStats {
     pub active_materials: usize,
     /// Número de materiales compilados
     pub compiled_materials: usize,
+    /// Materiales de componentes del ECS
+    pub component_materials: usize,
+    /// Materiales de componentes pendientes de subir
+    pub pending_uploads: usize,
+}
+
+/// Material de un componente del ECS en el formato del backend. Propiedades
+/// reconocidas: `base_color_r/g/b/a`, `emissive_r/g/b`, `metallic`,
+/// `roughness`, `normal_scale`, `occlusion_strength`, `alpha_cutoff` y
+/// `double_sided` (distinto de 0). Las texturas usan los slots `base_color`,
+/// `metallic_roughness`, `normal`, `emissive` y `occlusion`
+pub fn material_desc_from_component(component: &MaterialComponent) -> MaterialDesc {
+    let defaults = MaterialParams::default();
+    let property = |name: &str, default: f32| component.properties.get(name).copied().unwrap_or(default);
+
+    MaterialDesc {
+        id: component.material_id.clone(),
+        kind: match component.material_type {
+            ecs::MaterialType::Unlit => MaterialKind::Unlit,
+            _ => MaterialKind::Pbr,
+        },
+        params: MaterialParams {
+            base_color: Vec4::new(
+                property("base_color_r", defaults.base_color.x),
+                property("base_color_g", defaults.base_color.y),
+                property("base_color_b", defaults.base_color.z),
+                property("base_color_a", defaults.base_color.w),
+            ),
+            emissive: Vec3::new(
+                property("emissive_r", defaults.emissive.x),
+                property("emissive_g", defaults.emissive.y),
+                property("emissive_b", defaults.emissive.z),
+            ),
+            metallic: property("metallic", defaults.metallic),
+            roughness: property("roughness", defaults.roughness),
+            normal_scale: property("normal_scale", defaults.normal_scale),
+            occlusion_strength: property("occlusion_strength", defaults.occlusion_strength),
+            alpha_cutoff: property("alpha_cutoff", defaults.alpha_cutoff),
+            double_sided: property("double_sided", 0.0) != 0.0,
+        },
+        textures: MaterialTextureSlots::from_map(&component.textures),
+    }
 } 
\ No newline at end of file
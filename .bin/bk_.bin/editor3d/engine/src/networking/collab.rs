@@ -0,0 +1,433 @@
+//! # Edición colaborativa de escenas
+//!
+//! Varios peers editan la misma escena enviando `ECSCommand`s a un líder
+//! elegido al crear la sesión. Cada comando lleva la sesión, su autor y la
+//! última revisión que el autor había aplicado. El líder lo transforma con
+//! `ot::transform` contra los comandos aplicados desde esa revisión (los ya
+//! aplicados ganan los empates), lo aplica con la revisión siguiente y lo
+//! difunde. Todos los peers, el líder incluido, aplican los comandos en orden
+//! de revisión, así que el mundo converge al mismo estado aunque dos autores
+//! editen la misma entidad a la vez.
+//!
+//! Los comandos locales no se aplican hasta que vuelven del líder.
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use tracing::debug;
+
+use super::ot::{self, WireCommand};
+use crate::ecs::{ECSCommand, EntityId};
+
+/// Comandos aplicados que el líder conserva para transformar los que
+/// llegan con una revisión antigua
+pub const MAX_HISTORY: usize = 1024;
+
+/// Comando de edición de un autor
+#[derive(Debug, Clone)]
+pub struct EditCommand {
+    /// Sesión de edición
+    pub session_id: String,
+    /// Autor del comando
+    pub author_id: String,
+    /// Última revisión aplicada por el autor al crear el comando, o la
+    /// revisión asignada por el líder en los comandos aplicados
+    pub revision: u64,
+    /// Comando del ECS
+    pub command: ECSCommand,
+}
+
+/// Mensaje de la sesión en la red
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CollabMessage {
+    /// Comando de un autor para el líder
+    Submit {
+        session_id: String,
+        author_id: String,
+        revision: u64,
+        command: WireCommand,
+    },
+    /// Comando transformado y aplicado por el líder
+    Applied {
+        session_id: String,
+        author_id: String,
+        revision: u64,
+        command: WireCommand,
+    },
+}
+
+/// Estadísticas de la sesión
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionStats {
+    /// Revisiones aplicadas
+    pub applied_revisions: u64,
+    /// Comandos enviados por el peer local
+    pub submitted_commands: u64,
+    /// Comandos anulados por un conflicto
+    pub cancelled_commands: u64,
+    /// Comandos descartados por llegar con una revisión fuera del historial
+    pub rejected_commands: u64,
+}
+
+/// Sesión de edición colaborativa
+pub struct CollaborativeEditSession {
+    /// ID de la sesión
+    session_id: String,
+    /// Autor local
+    local_author: String,
+    /// Autor que ordena los comandos
+    leader: String,
+    /// Última revisión aplicada
+    revision: u64,
+    /// Comandos aplicados recientes (solo en el líder) con su autor
+    history: VecDeque<(String, ECSCommand)>,
+    /// IDs de entidades reasignados por autor (solo en el líder)
+    remapped: HashMap<(String, EntityId), EntityId>,
+    /// Comandos del líder que llegaron antes que los anteriores
+    out_of_order: BTreeMap<u64, EditCommand>,
+    /// Comandos listos para el ECS en orden de revisión
+    ready: Vec<ECSCommand>,
+    /// Mensajes salientes
+    outbox: Vec<CollabMessage>,
+    /// Estadísticas
+    stats: SessionStats,
+}
+
+impl CollaborativeEditSession {
+    /// Crear la sesión `session_id` para `local_author` ordenada por `leader`
+    pub fn new(session_id: &str, local_author: &str, leader: &str) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            local_author: local_author.to_string(),
+            leader: leader.to_string(),
+            revision: 0,
+            history: VecDeque::new(),
+            remapped: HashMap::new(),
+            out_of_order: BTreeMap::new(),
+            ready: Vec::new(),
+            outbox: Vec::new(),
+            stats: SessionStats::default(),
+        }
+    }
+
+    /// ID de la sesión
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Verificar si el peer local ordena la sesión
+    pub fn is_leader(&self) -> bool {
+        self.local_author == self.leader
+    }
+
+    /// Última revisión aplicada
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Enviar un comando local. El líder lo aplica al momento; el resto lo
+    /// manda al líder y lo aplica cuando vuelve
+    pub fn submit(&mut self, command: ECSCommand) -> Result<()> {
+        self.stats.submitted_commands += 1;
+        let edit = EditCommand {
+            session_id: self.session_id.clone(),
+            author_id: self.local_author.clone(),
+            revision: self.revision,
+            command,
+        };
+        if self.is_leader() {
+            return self.integrate(edit);
+        }
+        self.outbox.push(CollabMessage::Submit {
+            session_id: edit.session_id,
+            author_id: edit.author_id,
+            revision: edit.revision,
+            command: WireCommand::capture(&edit.command)?,
+        });
+        Ok(())
+    }
+
+    /// Procesar un mensaje de otro peer
+    pub fn handle_message(&mut self, message: CollabMessage) -> Result<()> {
+        match message {
+            CollabMessage::Submit { session_id, author_id, revision, command } => {
+                if session_id != self.session_id || !self.is_leader() {
+                    return Ok(());
+                }
+                self.integrate(EditCommand { session_id, author_id, revision, command: command.restore()? })
+            }
+            CollabMessage::Applied { session_id, author_id, revision, command } => {
+                // El líder ya aplicó sus propios comandos
+                if session_id != self.session_id || self.is_leader() || revision <= self.revision {
+                    return Ok(());
+                }
+                let edit = EditCommand { session_id, author_id, revision, command: command.restore()? };
+                self.out_of_order.insert(revision, edit);
+                while let Some(edit) = self.out_of_order.remove(&(self.revision + 1)) {
+                    self.apply(edit.command);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Transformar un comando contra los aplicados desde su revisión,
+    /// aplicarlo y difundirlo (solo en el líder)
+    fn integrate(&mut self, edit: EditCommand) -> Result<()> {
+        let oldest = self.revision - self.history.len() as u64;
+        if edit.revision < oldest || edit.revision > self.revision {
+            self.stats.rejected_commands += 1;
+            return Err(anyhow!(
+                "Revisión {} de {} fuera del historial ({}..={})",
+                edit.revision, edit.author_id, oldest, self.revision,
+            ));
+        }
+
+        // Los comandos posteriores del autor siguen a las entidades reasignadas
+        let original_entity = ot::command_entity(&edit.command);
+        let mut command = match original_entity.and_then(|entity_id| self.remapped.get(&(edit.author_id.clone(), entity_id))) {
+            Some(&entity_id) => ot::with_entity(&edit.command, entity_id),
+            None => edit.command,
+        };
+
+        let concurrent = (edit.revision - oldest) as usize;
+        for (author, applied) in self.history.iter().skip(concurrent) {
+            // Los comandos del mismo autor ya estaban ordenados al crearlo
+            if *author == edit.author_id {
+                continue;
+            }
+            command = ot::transform(applied, &command).1;
+        }
+
+        if let (Some(original), ECSCommand::CreateEntity(entity)) = (original_entity, &command) {
+            if entity.id != original {
+                debug!("Entidad {} de {} reasignada a {}", original, edit.author_id, entity.id);
+                self.remapped.insert((edit.author_id.clone(), original), entity.id);
+            }
+        }
+        if matches!(command, ECSCommand::Noop) {
+            self.stats.cancelled_commands += 1;
+        }
+
+        self.outbox.push(CollabMessage::Applied {
+            session_id: self.session_id.clone(),
+            author_id: edit.author_id.clone(),
+            revision: self.revision + 1,
+            command: WireCommand::capture(&command)?,
+        });
+        self.history.push_back((edit.author_id, command.clone()));
+        if self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.apply(command);
+        Ok(())
+    }
+
+    /// Avanzar una revisión con un comando
+    fn apply(&mut self, command: ECSCommand) {
+        self.revision += 1;
+        self.stats.applied_revisions += 1;
+        if matches!(command, ECSCommand::Noop) {
+            return;
+        }
+        self.ready.push(command);
+    }
+
+    /// Extraer los comandos aplicados para el ECS, en orden de revisión
+    pub fn drain_commands(&mut self) -> Vec<ECSCommand> {
+        std::mem::take(&mut self.ready)
+    }
+
+    /// Extraer los mensajes salientes
+    pub fn drain_outbox(&mut self) -> Vec<CollabMessage> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Comandos de otros autores pendientes de una revisión anterior
+    pub fn waiting_revisions(&self) -> usize {
+        self.out_of_order.len()
+    }
+
+    /// Obtener estadísticas
+    pub fn get_stats(&self) -> SessionStats {
+        self.stats.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::{
+        ComponentConfig, ComponentType, ECSConfig, ECSSystem, Entity, EntityConfig, EntityState,
+        OptimizationConfig, SystemConfig, TransformComponent,
+    };
+    use glam::{Mat4, Quat, Vec3};
+
+    /// Entidad que mueven los dos peers
+    const BOX: EntityId = 4242;
+
+    fn ecs_config() -> ECSConfig {
+        ECSConfig {
+            enabled: true,
+            entity_config: EntityConfig { max_entities: 100, entity_pool: false, id_reuse: false },
+            component_config: ComponentConfig {
+                max_components_per_entity: 16,
+                component_cache: false,
+                auto_serialization: false,
+            },
+            system_config: SystemConfig { parallel_execution: false, system_priority: true, hot_reloading: false },
+            optimization_config: OptimizationConfig { cache_friendly: true, memory_pooling: false, batch_processing: false },
+            max_parallel_systems: 1,
+            system_hot_reloading: false,
+            systems_directory: "systems".into(),
+        }
+    }
+
+    fn transform_at(position: Vec3) -> TransformComponent {
+        TransformComponent {
+            position,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+            matrix: Mat4::from_translation(position),
+            parent: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Peer con su sesión y su mundo
+    struct Peer {
+        session: CollaborativeEditSession,
+        world: ECSSystem,
+    }
+
+    impl Peer {
+        fn new(author: &str) -> Self {
+            Self {
+                session: CollaborativeEditSession::new("escena", author, "alice"),
+                world: ECSSystem::new(ecs_config()),
+            }
+        }
+
+        /// Aplicar al mundo los comandos que ya tienen revisión
+        async fn sync(&mut self) {
+            for command in self.session.drain_commands() {
+                self.world.queue_command(command).await.unwrap();
+            }
+            self.world.flush_commands();
+        }
+
+        fn position(&self) -> Vec3 {
+            self.world.get_component::<TransformComponent>(BOX, ComponentType::Transform).unwrap().position
+        }
+    }
+
+    /// Entregar los mensajes pendientes de `from` a `to`, opcionalmente en
+    /// orden inverso de llegada
+    fn deliver(from: &mut Peer, to: &mut Peer, reversed: bool) {
+        let mut messages = from.session.drain_outbox();
+        if reversed {
+            messages.reverse();
+        }
+        for message in messages {
+            to.session.handle_message(message).unwrap();
+        }
+    }
+
+    /// Líder y peer con la caja ya creada en el origen en los dos mundos
+    async fn peers() -> (Peer, Peer) {
+        let (mut alice, mut bob) = (Peer::new("alice"), Peer::new("bob"));
+        alice.session.submit(ECSCommand::CreateEntity(Entity {
+            id: BOX,
+            name: "caja".to_string(),
+            components: Vec::new(),
+            state: EntityState { active: true, visible: true, selected: false, locked: false },
+            metadata: HashMap::new(),
+        })).unwrap();
+        alice.session.submit(ECSCommand::AddComponent(BOX, Box::new(transform_at(Vec3::ZERO)))).unwrap();
+        deliver(&mut alice, &mut bob, false);
+        alice.sync().await;
+        bob.sync().await;
+        assert_eq!(bob.position(), Vec3::ZERO);
+        (alice, bob)
+    }
+
+    /// Los dos mueven la caja: alice con root motion y un giro, bob con
+    /// otro root motion y después colocándola. Devuelve la posición final
+    /// en cada peer
+    async fn concurrent_moves(order: u32) -> (Vec3, Vec3) {
+        let (mut alice, mut bob) = peers().await;
+        let alice_move = ECSCommand::ApplyRootMotion(BOX, Vec3::X, Quat::from_rotation_y(std::f32::consts::FRAC_PI_2));
+        let bob_moves = [
+            ECSCommand::ApplyRootMotion(BOX, Vec3::NEG_Z * 2.0, Quat::IDENTITY),
+            ECSCommand::UpdateComponent(BOX, ComponentType::Transform, Box::new(transform_at(Vec3::new(0.0, 3.0, 0.0)))),
+        ];
+        match order {
+            // El líder aplica lo suyo antes de recibir lo de bob
+            0 => {
+                alice.session.submit(alice_move).unwrap();
+                for command in bob_moves {
+                    bob.session.submit(command).unwrap();
+                }
+                deliver(&mut bob, &mut alice, false);
+                deliver(&mut alice, &mut bob, false);
+            }
+            // Lo mismo, pero a bob le llegan las revisiones desordenadas
+            1 => {
+                alice.session.submit(alice_move).unwrap();
+                for command in bob_moves {
+                    bob.session.submit(command).unwrap();
+                }
+                deliver(&mut bob, &mut alice, false);
+                deliver(&mut alice, &mut bob, true);
+            }
+            // Lo de bob llega al líder antes de que alice mueva la caja
+            _ => {
+                for command in bob_moves {
+                    bob.session.submit(command).unwrap();
+                }
+                deliver(&mut bob, &mut alice, false);
+                alice.session.submit(alice_move).unwrap();
+                deliver(&mut alice, &mut bob, true);
+            }
+        }
+        alice.sync().await;
+        bob.sync().await;
+        assert_eq!(alice.session.revision(), bob.session.revision());
+        assert_eq!(bob.session.waiting_revisions(), 0);
+        (alice.position(), bob.position())
+    }
+
+    #[tokio::test]
+    async fn two_peers_moving_the_same_entity_converge() {
+        for order in 0..3 {
+            let (alice, bob) = concurrent_moves(order).await;
+            assert!(alice.abs_diff_eq(bob, 1e-5), "orden {}: {} frente a {}", order, alice, bob);
+        }
+
+        // Concurrentes: la colocación de bob se fusiona tras el root motion de
+        // alice y lo sustituye, así que la caja acaba donde bob la puso
+        let (alice, _) = concurrent_moves(0).await;
+        assert!(alice.abs_diff_eq(Vec3::new(0.0, 3.0, 0.0), 1e-5), "{}", alice);
+
+        // Con lo de bob ya aplicado, el root motion de alice parte de su
+        // colocación: un metro en X con la caja sin girar
+        let (alice, _) = concurrent_moves(2).await;
+        assert!(alice.abs_diff_eq(Vec3::new(1.0, 3.0, 0.0), 1e-5), "{}", alice);
+    }
+
+    #[tokio::test]
+    async fn concurrent_writes_of_the_same_component_keep_the_applied_one() {
+        let (mut alice, mut bob) = peers().await;
+        alice.session.submit(ECSCommand::UpdateComponent(BOX, ComponentType::Transform, Box::new(transform_at(Vec3::X)))).unwrap();
+        bob.session.submit(ECSCommand::UpdateComponent(BOX, ComponentType::Transform, Box::new(transform_at(Vec3::Y)))).unwrap();
+        deliver(&mut bob, &mut alice, false);
+        deliver(&mut alice, &mut bob, false);
+        alice.sync().await;
+        bob.sync().await;
+
+        assert_eq!(alice.position(), Vec3::X);
+        assert_eq!(bob.position(), Vec3::X);
+        assert_eq!(alice.session.get_stats().cancelled_commands, 1);
+        assert_eq!(alice.session.get_stats().applied_revisions, bob.session.get_stats().applied_revisions);
+    }
+}
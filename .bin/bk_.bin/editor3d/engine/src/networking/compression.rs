@@ -0,0 +1,136 @@
+//! # Compresión de paquetes
+//!
+//! Las actualizaciones de estado grandes (una explosión que mueve cientos de
+//! objetos) se comprimen antes de salir al socket y se descomprimen al
+//! recibirlas, antes de despachar el mensaje.
+//!
+//! Todo paquete saliente empieza con una cabecera de 4 bytes: el prefijo
+//! `b"WVC"` y el modo (0 sin comprimir, 1 LZ4, 2 Zstd), también los que no se
+//! comprimen, así que un payload que por azar empiece por el prefijo nunca se
+//! confunde con uno comprimido. Los paquetes sin el prefijo son los de peers
+//! anteriores a la compresión y se entregan tal cual.
+//!
+//! Zstd enlaza la biblioteca de C y solo existe en nativo; en wasm32 el modo
+//! no está disponible y un paquete Zstd recibido es un error.
+//!
+//! Por debajo de `COMPRESSION_THRESHOLD` bytes no se comprime: la ganancia de
+//! LZ4 en mensajes de posición o chat no compensa el coste por paquete. El
+//! umbral y la ganancia se miden con
+//! `cargo bench --bench packet_compression`, que comprime una actualización
+//! de estado de 50 KB con cada modo. Si el resultado comprimido no es menor
+//! que el original, el paquete sale con el modo 0.
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+
+/// Prefijo de la cabecera de compresión
+pub const HEADER_MAGIC: [u8; 3] = *b"WVC";
+/// Tamaño de la cabecera de compresión
+pub const HEADER_LEN: usize = 4;
+/// Tamaño mínimo de payload que se comprime
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+/// Tamaño máximo de un paquete descomprimido (protege de bombas de compresión)
+pub const MAX_DECOMPRESSED_SIZE: usize = 16 * 1024 * 1024;
+
+const MODE_NONE: u8 = 0;
+const MODE_LZ4: u8 = 1;
+#[cfg(not(target_arch = "wasm32"))]
+const MODE_ZSTD: u8 = 2;
+
+/// Algoritmo de compresión de los paquetes salientes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CompressionMode {
+    /// Sin compresión
+    #[default]
+    None,
+    /// LZ4: rápido, ratio moderado
+    Lz4,
+    /// Zstd con nivel (1 a 22): más lento, mejor ratio (solo nativo)
+    #[cfg(not(target_arch = "wasm32"))]
+    Zstd(i32),
+}
+
+/// Preparar un payload para el socket con el modo dado
+pub fn compress_packet(mode: CompressionMode, payload: &[u8]) -> Result<Vec<u8>> {
+    let compressed = match mode {
+        _ if payload.len() < COMPRESSION_THRESHOLD => None,
+        CompressionMode::None => None,
+        CompressionMode::Lz4 => Some((MODE_LZ4, lz4_flex::compress_prepend_size(payload))),
+        #[cfg(not(target_arch = "wasm32"))]
+        CompressionMode::Zstd(level) => Some((
+            MODE_ZSTD,
+            zstd::bulk::compress(payload, level)
+                .map_err(|e| anyhow!("Error comprimiendo con Zstd: {}", e))?,
+        )),
+    };
+
+    match compressed {
+        Some((mode, body)) if body.len() < payload.len() => Ok(with_header(mode, &body)),
+        _ => Ok(with_header(MODE_NONE, payload)),
+    }
+}
+
+/// Recuperar el payload de un paquete recibido; sin cabecera es de un peer
+/// anterior a la compresión
+pub fn decompress_packet(packet: &[u8]) -> Result<Vec<u8>> {
+    if packet.len() < HEADER_LEN || !packet.starts_with(&HEADER_MAGIC) {
+        return Ok(packet.to_vec());
+    }
+
+    let body = &packet[HEADER_LEN..];
+    match packet[HEADER_MAGIC.len()] {
+        MODE_NONE => Ok(body.to_vec()),
+        MODE_LZ4 => {
+            let size = body.get(..4)
+                .map(|prefix| u32::from_le_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize)
+                .ok_or_else(|| anyhow!("Paquete LZ4 truncado"))?;
+            if size > MAX_DECOMPRESSED_SIZE {
+                return Err(anyhow!("Paquete LZ4 de {} bytes supera el máximo", size));
+            }
+            lz4_flex::decompress_size_prepended(body)
+                .map_err(|e| anyhow!("Error descomprimiendo LZ4: {}", e))
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        MODE_ZSTD => zstd::bulk::decompress(body, MAX_DECOMPRESSED_SIZE)
+            .map_err(|e| anyhow!("Error descomprimiendo Zstd: {}", e)),
+        mode => Err(anyhow!("Modo de compresión desconocido: {}", mode)),
+    }
+}
+
+fn with_header(mode: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(HEADER_LEN + body.len());
+    packet.extend_from_slice(&HEADER_MAGIC);
+    packet.push(mode);
+    packet.extend_from_slice(body);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_packet_carries_the_header() {
+        for payload in [b"hola".to_vec(), vec![7; 4096]] {
+            for mode in [CompressionMode::None, CompressionMode::Lz4, CompressionMode::Zstd(3)] {
+                let packet = compress_packet(mode, &payload).unwrap();
+                assert!(packet.starts_with(&HEADER_MAGIC));
+                assert_eq!(decompress_packet(&packet).unwrap(), payload);
+            }
+        }
+    }
+
+    #[test]
+    fn payload_starting_with_the_magic_round_trips() {
+        // Un "modo LZ4" con un tamaño absurdo si se leyera como cabecera
+        let payload = [&HEADER_MAGIC[..], &[MODE_LZ4, 0xff, 0xff, 0xff, 0xff]].concat();
+        let packet = compress_packet(CompressionMode::Lz4, &payload).unwrap();
+        assert_eq!(packet[HEADER_MAGIC.len()], MODE_NONE);
+        assert_eq!(decompress_packet(&packet).unwrap(), payload);
+    }
+
+    #[test]
+    fn legacy_packets_without_header_are_delivered_as_is() {
+        assert_eq!(decompress_packet(b"{\"type\":\"chat\"}").unwrap(), b"{\"type\":\"chat\"}");
+    }
+}
@@ -0,0 +1,572 @@
+//! # DHT Kademlia
+//!
+//! Descubrimiento de peers y almacén clave-valor distribuido sin servidor
+//! central. Cada nodo tiene un `NodeId` de 256 bits (el SHA-256 de su
+//! `PeerId`) y la distancia entre nodos es el XOR de sus IDs. La tabla de
+//! rutas guarda hasta `K` contactos `(NodeId, Multiaddr)` por bucket, uno por
+//! cada bit de distancia.
+//!
+//! Las búsquedas son iterativas: en cada ronda se pregunta en paralelo a
+//! `ALPHA` de los `K` candidatos más cercanos al objetivo aún no consultados
+//! y las respuestas aportan candidatos más cercanos. La búsqueda termina
+//! cuando los `K` más cercanos han respondido (o fallado), o al encontrar el
+//! valor en `FIND_VALUE`. Al arrancar el nodo se busca a sí mismo
+//! (`FIND_NODE(self)`) para llenar la tabla y darse a conocer.
+//!
+//! El módulo no conoce el transporte: `handle_message` recibe los mensajes
+//! de los peers y `drain_outbox` devuelve los que hay que enviar, que
+//! `NetworkingSystem` manda por el canal punto a punto.
+
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use libp2p::core::PeerId;
+use libp2p::multiaddr::{Multiaddr, Protocol};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+/// Contactos por bucket
+pub const K: usize = 20;
+/// Peticiones en paralelo por ronda de búsqueda
+pub const ALPHA: usize = 3;
+/// Bits de un `NodeId`
+const ID_BITS: usize = 256;
+/// Tiempo máximo de respuesta a una petición
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Clave del almacén
+pub type DhtKey = [u8; 32];
+
+/// Identificador de nodo en el espacio de claves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NodeId(pub [u8; 32]);
+
+impl NodeId {
+    /// ID de un peer: SHA-256 de su `PeerId`
+    pub fn from_peer(peer: &PeerId) -> Self {
+        Self(Sha256::digest(peer.to_bytes()).into())
+    }
+
+    /// Distancia XOR
+    pub fn distance(&self, other: &NodeId) -> Distance {
+        let mut distance = [0u8; 32];
+        for (i, byte) in distance.iter_mut().enumerate() {
+            *byte = self.0[i] ^ other.0[i];
+        }
+        Distance(distance)
+    }
+}
+
+/// Distancia XOR entre dos IDs; se ordena como entero big-endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Distance(pub [u8; 32]);
+
+impl Distance {
+    /// Bucket de la distancia: posición del bit más alto a 1 (None a
+    /// distancia 0)
+    pub fn bucket_index(&self) -> Option<usize> {
+        let zeros = self.0.iter()
+            .position(|byte| *byte != 0)
+            .map_or(ID_BITS, |i| i * 8 + self.0[i].leading_zeros() as usize);
+        (zeros < ID_BITS).then(|| ID_BITS - 1 - zeros)
+    }
+}
+
+/// Peer alcanzable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhtContact {
+    /// Peer
+    pub peer: PeerId,
+    /// Dirección
+    pub address: Multiaddr,
+}
+
+impl DhtContact {
+    /// Contacto de una dirección con `/p2p/<peer>` (nodos bootstrap)
+    pub fn from_multiaddr(address: &Multiaddr) -> Option<Self> {
+        let peer = address.iter().find_map(|protocol| match protocol {
+            Protocol::P2p(peer) => Some(peer),
+            _ => None,
+        })?;
+        Some(Self { peer, address: address.clone() })
+    }
+
+    /// ID del contacto
+    pub fn node_id(&self) -> NodeId {
+        NodeId::from_peer(&self.peer)
+    }
+}
+
+/// Tabla de rutas: un bucket por bit de distancia, con los contactos más
+/// recientes al final
+#[derive(Debug, Clone)]
+pub struct RoutingTable {
+    local: NodeId,
+    bucket_size: usize,
+    buckets: Vec<Vec<(NodeId, DhtContact)>>,
+}
+
+impl RoutingTable {
+    /// Tabla vacía alrededor de `local`
+    pub fn new(local: NodeId, bucket_size: usize) -> Self {
+        Self {
+            local,
+            bucket_size: bucket_size.max(1),
+            buckets: vec![Vec::new(); ID_BITS],
+        }
+    }
+
+    /// Añadir o refrescar un contacto. Con el bucket lleno se conservan los
+    /// contactos antiguos, que han demostrado seguir vivos; devuelve si el
+    /// contacto es nuevo
+    pub fn insert(&mut self, contact: DhtContact) -> bool {
+        let node_id = contact.node_id();
+        let Some(index) = self.local.distance(&node_id).bucket_index() else {
+            return false;
+        };
+        let bucket = &mut self.buckets[index];
+        if let Some(position) = bucket.iter().position(|(id, _)| *id == node_id) {
+            bucket.remove(position);
+            bucket.push((node_id, contact));
+            return false;
+        }
+        if bucket.len() >= self.bucket_size {
+            return false;
+        }
+        bucket.push((node_id, contact));
+        true
+    }
+
+    /// Quitar un peer que no responde
+    pub fn remove(&mut self, peer: &PeerId) -> bool {
+        let node_id = NodeId::from_peer(peer);
+        let Some(index) = self.local.distance(&node_id).bucket_index() else {
+            return false;
+        };
+        let bucket = &mut self.buckets[index];
+        let before = bucket.len();
+        bucket.retain(|(id, _)| *id != node_id);
+        bucket.len() != before
+    }
+
+    /// El peer está en la tabla
+    pub fn contains(&self, peer: &PeerId) -> bool {
+        let node_id = NodeId::from_peer(peer);
+        self.local.distance(&node_id).bucket_index()
+            .is_some_and(|index| self.buckets[index].iter().any(|(id, _)| *id == node_id))
+    }
+
+    /// Los `count` contactos más cercanos a `target`
+    pub fn closest(&self, target: &NodeId, count: usize) -> Vec<DhtContact> {
+        let mut contacts: Vec<(Distance, &DhtContact)> = self.buckets.iter()
+            .flatten()
+            .map(|(id, contact)| (target.distance(id), contact))
+            .collect();
+        contacts.sort_by(|a, b| a.0.cmp(&b.0));
+        contacts.into_iter().take(count).map(|(_, contact)| contact.clone()).collect()
+    }
+
+    /// Todos los contactos
+    pub fn contacts(&self) -> impl Iterator<Item = &DhtContact> {
+        self.buckets.iter().flatten().map(|(_, contact)| contact)
+    }
+
+    /// Número de contactos
+    pub fn len(&self) -> usize {
+        self.buckets.iter().map(Vec::len).sum()
+    }
+
+    /// Tabla vacía
+    pub fn is_empty(&self) -> bool {
+        self.buckets.iter().all(Vec::is_empty)
+    }
+}
+
+/// Mensaje entre nodos de la DHT
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DhtMessage {
+    /// Contacto del remitente, que entra en la tabla de quien lo recibe
+    pub sender: DhtContact,
+    /// Contenido
+    pub body: DhtBody,
+}
+
+/// Contenido de un mensaje de la DHT. Las respuestas repiten el `query` de
+/// la petición
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DhtBody {
+    /// Pedir los contactos más cercanos a un ID
+    FindNode { query: u64, target: NodeId },
+    /// Pedir un valor (o los contactos más cercanos a su clave)
+    FindValue { query: u64, key: DhtKey },
+    /// Contactos más cercanos
+    Nodes { query: u64, contacts: Vec<DhtContact> },
+    /// Valor encontrado
+    Value { query: u64, value: Vec<u8> },
+    /// Guardar un valor
+    Store { key: DhtKey, value: Vec<u8> },
+}
+
+/// Qué persigue una búsqueda
+#[derive(Debug, Clone)]
+enum LookupGoal {
+    /// Llenar la tabla con los nodos cercanos al objetivo
+    Node,
+    /// Encontrar un valor
+    Value(DhtKey),
+    /// Guardar un valor en los nodos más cercanos a su clave
+    Store(DhtKey, Vec<u8>),
+}
+
+/// Estado de un candidato de una búsqueda
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CandidateState {
+    Pending,
+    Waiting(Instant),
+    Responded,
+    Failed,
+}
+
+/// Búsqueda iterativa en curso
+#[derive(Debug)]
+struct Lookup {
+    target: NodeId,
+    goal: LookupGoal,
+    candidates: BTreeMap<Distance, (DhtContact, CandidateState)>,
+    rounds: u32,
+    found: bool,
+}
+
+impl Lookup {
+    /// Añadir candidatos (los ya conocidos no cambian)
+    fn add_candidates(&mut self, local: &NodeId, contacts: impl IntoIterator<Item = DhtContact>) {
+        for contact in contacts {
+            let node_id = contact.node_id();
+            if node_id == *local {
+                continue;
+            }
+            self.candidates.entry(self.target.distance(&node_id))
+                .or_insert((contact, CandidateState::Pending));
+        }
+    }
+
+    /// Los `K` candidatos más cercanos que no han fallado
+    fn closest_live(&self) -> impl Iterator<Item = &(DhtContact, CandidateState)> {
+        self.candidates.values()
+            .filter(|(_, state)| *state != CandidateState::Failed)
+            .take(K)
+    }
+
+    /// Hay peticiones sin respuesta
+    fn waiting(&self) -> bool {
+        self.candidates.values().any(|(_, state)| matches!(state, CandidateState::Waiting(_)))
+    }
+
+    /// Candidato con una petición en vuelo
+    fn candidate_mut(&mut self, peer: &PeerId) -> Option<&mut CandidateState> {
+        let distance = self.target.distance(&NodeId::from_peer(peer));
+        self.candidates.get_mut(&distance)
+            .filter(|(_, state)| matches!(state, CandidateState::Waiting(_)))
+            .map(|(_, state)| state)
+    }
+}
+
+/// Valor guardado
+#[derive(Debug, Clone)]
+struct StoredValue {
+    value: Vec<u8>,
+    expires: Instant,
+}
+
+/// Estadísticas de la DHT
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DhtStats {
+    /// Contactos en la tabla de rutas
+    pub routing_table_size: usize,
+    /// Valores guardados
+    pub stored_values: usize,
+    /// Búsquedas en curso
+    pub active_lookups: usize,
+    /// Búsquedas terminadas
+    pub completed_lookups: u64,
+    /// Rondas de la última búsqueda terminada
+    pub last_lookup_rounds: u32,
+}
+
+/// Nodo de la DHT
+#[derive(Debug)]
+pub struct Dht {
+    local: DhtContact,
+    local_id: NodeId,
+    routing: RoutingTable,
+    values: HashMap<DhtKey, StoredValue>,
+    lookups: HashMap<u64, Lookup>,
+    next_query: u64,
+    outbox: Vec<(DhtContact, DhtMessage)>,
+    ttl: Duration,
+    request_timeout: Duration,
+    stats: DhtStats,
+}
+
+impl Dht {
+    /// Nodo con `bucket_size` contactos por bucket que guarda los valores
+    /// durante `ttl`
+    pub fn new(local: DhtContact, bucket_size: usize, ttl: Duration) -> Self {
+        let local_id = local.node_id();
+        Self {
+            local,
+            local_id,
+            routing: RoutingTable::new(local_id, bucket_size),
+            values: HashMap::new(),
+            lookups: HashMap::new(),
+            next_query: 0,
+            outbox: Vec::new(),
+            ttl,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            stats: DhtStats::default(),
+        }
+    }
+
+    /// ID del nodo local
+    pub fn local_id(&self) -> NodeId {
+        self.local_id
+    }
+
+    /// Dirección que se anuncia a los demás nodos
+    pub fn set_local_address(&mut self, address: Multiaddr) {
+        self.local.address = address;
+    }
+
+    /// Tiempo máximo de respuesta a una petición
+    pub fn set_request_timeout(&mut self, timeout: Duration) {
+        self.request_timeout = timeout;
+    }
+
+    /// Tabla de rutas
+    pub fn routing_table(&self) -> &RoutingTable {
+        &self.routing
+    }
+
+    /// Añadir un contacto conocido (bootstrap o mDNS); devuelve si es nuevo
+    pub fn add_contact(&mut self, contact: DhtContact) -> bool {
+        self.routing.insert(contact)
+    }
+
+    /// Buscarse a sí mismo para llenar la tabla desde los contactos
+    /// conocidos. Sin contactos no hace nada
+    pub fn bootstrap(&mut self, now: Instant) {
+        if self.routing.is_empty() {
+            debug!("DHT sin contactos: bootstrap aplazado");
+            return;
+        }
+        let target = self.local_id;
+        self.start_lookup(target, LookupGoal::Node, now);
+    }
+
+    /// Guardar un valor localmente y en los `K` nodos más cercanos a su clave
+    pub fn put_value(&mut self, key: DhtKey, value: Vec<u8>, now: Instant) {
+        self.values.insert(key, StoredValue { value: value.clone(), expires: now + self.ttl });
+        self.start_lookup(NodeId(key), LookupGoal::Store(key, value), now);
+    }
+
+    /// Valor de una clave si ya se conoce. Si no, empieza a buscarlo en la
+    /// red y lo devolverá una llamada posterior cuando llegue
+    pub fn get_value(&mut self, key: DhtKey, now: Instant) -> Option<Vec<u8>> {
+        if let Some(stored) = self.values.get(&key).filter(|stored| stored.expires > now) {
+            return Some(stored.value.clone());
+        }
+        let searching = self.lookups.values().any(|lookup| matches!(lookup.goal, LookupGoal::Value(k) if k == key));
+        if !searching {
+            self.start_lookup(NodeId(key), LookupGoal::Value(key), now);
+        }
+        None
+    }
+
+    /// Procesar un mensaje de `from`. Quien llama garantiza que `from` es
+    /// el peer autenticado de la conexión
+    pub fn handle_message(&mut self, from: PeerId, message: DhtMessage, now: Instant) {
+        if message.sender.peer != from {
+            debug!("Mensaje DHT de {} con remitente falso", from);
+            return;
+        }
+        let sender = message.sender;
+        self.routing.insert(sender.clone());
+
+        match message.body {
+            DhtBody::FindNode { query, target } => {
+                let contacts = self.closest_except(&target, &from);
+                self.send(sender, DhtBody::Nodes { query, contacts });
+            }
+            DhtBody::FindValue { query, key } => {
+                let body = match self.values.get(&key).filter(|stored| stored.expires > now) {
+                    Some(stored) => DhtBody::Value { query, value: stored.value.clone() },
+                    None => DhtBody::Nodes { query, contacts: self.closest_except(&NodeId(key), &from) },
+                };
+                self.send(sender, body);
+            }
+            DhtBody::Store { key, value } => {
+                self.values.insert(key, StoredValue { value, expires: now + self.ttl });
+            }
+            DhtBody::Nodes { query, contacts } => {
+                let local_id = self.local_id;
+                if let Some(lookup) = self.lookups.get_mut(&query) {
+                    if let Some(state) = lookup.candidate_mut(&from) {
+                        *state = CandidateState::Responded;
+                        lookup.add_candidates(&local_id, contacts);
+                    }
+                }
+                self.advance_lookup(query, now);
+            }
+            DhtBody::Value { query, value } => {
+                if let Some(lookup) = self.lookups.get_mut(&query) {
+                    if let LookupGoal::Value(key) = lookup.goal {
+                        if let Some(state) = lookup.candidate_mut(&from) {
+                            *state = CandidateState::Responded;
+                            lookup.found = true;
+                            self.values.insert(key, StoredValue { value, expires: now + self.ttl });
+                        }
+                    }
+                }
+                self.advance_lookup(query, now);
+            }
+        }
+    }
+
+    /// Vencer peticiones sin respuesta, avanzar las búsquedas y caducar
+    /// valores
+    pub fn tick(&mut self, now: Instant) {
+        let timeout = self.request_timeout;
+        let mut failed = Vec::new();
+        for lookup in self.lookups.values_mut() {
+            for (contact, state) in lookup.candidates.values_mut() {
+                if matches!(state, CandidateState::Waiting(sent) if now.duration_since(*sent) >= timeout) {
+                    *state = CandidateState::Failed;
+                    failed.push(contact.peer);
+                }
+            }
+        }
+        for peer in failed {
+            if self.routing.remove(&peer) {
+                debug!("Peer {} sin respuesta: fuera de la tabla de la DHT", peer);
+            }
+        }
+
+        let queries: Vec<u64> = self.lookups.keys().copied().collect();
+        for query in queries {
+            self.advance_lookup(query, now);
+        }
+        self.values.retain(|_, stored| stored.expires > now);
+        self.update_stats();
+    }
+
+    /// Mensajes pendientes de enviar
+    pub fn drain_outbox(&mut self) -> Vec<(DhtContact, DhtMessage)> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Estadísticas
+    pub fn stats(&self) -> DhtStats {
+        self.stats.clone()
+    }
+
+    /// Empezar una búsqueda desde los contactos más cercanos de la tabla
+    fn start_lookup(&mut self, target: NodeId, goal: LookupGoal, now: Instant) {
+        let query = self.next_query;
+        self.next_query += 1;
+        let mut lookup = Lookup {
+            target,
+            goal,
+            candidates: BTreeMap::new(),
+            rounds: 0,
+            found: false,
+        };
+        lookup.add_candidates(&self.local_id, self.routing.closest(&target, K));
+        self.lookups.insert(query, lookup);
+        self.advance_lookup(query, now);
+    }
+
+    /// Cuando la ronda en curso ha terminado, lanzar la siguiente o cerrar
+    /// la búsqueda
+    fn advance_lookup(&mut self, query: u64, now: Instant) {
+        let Some(lookup) = self.lookups.get_mut(&query) else {
+            return;
+        };
+        if lookup.waiting() && !lookup.found {
+            return;
+        }
+
+        let next: Vec<DhtContact> = if lookup.found {
+            Vec::new()
+        } else {
+            lookup.closest_live()
+                .filter(|(_, state)| *state == CandidateState::Pending)
+                .take(ALPHA)
+                .map(|(contact, _)| contact.clone())
+                .collect()
+        };
+
+        if next.is_empty() {
+            let lookup = self.lookups.remove(&query).expect("búsqueda en curso");
+            self.finish_lookup(lookup);
+            return;
+        }
+
+        lookup.rounds += 1;
+        let request = match lookup.goal {
+            LookupGoal::Value(key) => DhtBody::FindValue { query, key },
+            _ => DhtBody::FindNode { query, target: lookup.target },
+        };
+        for contact in &next {
+            let distance = lookup.target.distance(&contact.node_id());
+            if let Some((_, state)) = lookup.candidates.get_mut(&distance) {
+                *state = CandidateState::Waiting(now);
+            }
+        }
+        for contact in next {
+            self.send(contact, request.clone());
+        }
+    }
+
+    /// Cerrar una búsqueda: los `Store` se envían a los nodos más cercanos
+    /// que respondieron
+    fn finish_lookup(&mut self, lookup: Lookup) {
+        self.stats.completed_lookups += 1;
+        self.stats.last_lookup_rounds = lookup.rounds;
+        debug!("Búsqueda DHT terminada en {} rondas", lookup.rounds);
+
+        if let LookupGoal::Store(key, value) = &lookup.goal {
+            let holders: Vec<DhtContact> = lookup.candidates.values()
+                .filter(|(_, state)| *state == CandidateState::Responded)
+                .take(K)
+                .map(|(contact, _)| contact.clone())
+                .collect();
+            for contact in holders {
+                self.send(contact, DhtBody::Store { key: *key, value: value.clone() });
+            }
+        }
+        if matches!(lookup.goal, LookupGoal::Node) && lookup.target == self.local_id {
+            info!("Bootstrap de la DHT completado: {} contactos", self.routing.len());
+        }
+    }
+
+    /// Contactos más cercanos a `target` sin contar a quien pregunta
+    fn closest_except(&self, target: &NodeId, peer: &PeerId) -> Vec<DhtContact> {
+        let mut contacts = self.routing.closest(target, K + 1);
+        contacts.retain(|contact| contact.peer != *peer);
+        contacts.truncate(K);
+        contacts
+    }
+
+    /// Encolar un mensaje
+    fn send(&mut self, to: DhtContact, body: DhtBody) {
+        let message = DhtMessage { sender: self.local.clone(), body };
+        self.outbox.push((to, message));
+    }
+
+    /// Actualizar estadísticas
+    fn update_stats(&mut self) {
+        self.stats.routing_table_size = self.routing.len();
+        self.stats.stored_values = self.values.len();
+        self.stats.active_lookups = self.lookups.len();
+    }
+}
@@ -2,6 +2,17 @@
 //! 
 //! Proporciona comunicación peer-to-peer sin servidor central,
 //! descubrimiento automático de nodos y sincronización de estado en tiempo real.
+//! Los peers se descubren con la DHT Kademlia de `dht` a partir de cualquier
+//! nodo bootstrap conocido, o por mDNS en la red local si no hay ninguno.
+
+pub mod replication;
+pub mod nat;
+pub mod transfer;
+pub mod security;
+pub mod ot;
+pub mod collab;
+pub mod dht;
+pub mod compression;
 
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
@@ -17,7 +28,8 @@ use libp2p::{
 };
 use libp2p::ping::{Ping, PingEvent};
 use libp2p::identify::{Identify, IdentifyEvent};
-use libp2p::kad::{Kademlia, KademliaEvent, QueryResult};
+use libp2p::mdns;
+use libp2p::swarm::behaviour::toggle::Toggle;
 use libp2p::gossipsub::{Gossipsub, GossipsubEvent, MessageId, ValidationMode};
 use libp2p::request_response::{RequestResponse, RequestResponseEvent, RequestResponseCodec};
 use std::io;
@@ -35,6 +47,32 @@ pub struct NetworkingSystem {
     pending_messages: Arc<RwLock<Vec<NetworkMessage>>>,
     /// Estado del sistema
     state: Arc<RwLock<NetworkState>>,
+    /// Replicación de entidades con propiedad
+    replication: Option<replication::ReplicationManager>,
+    /// Mensajes de autoridad de física recibidos
+    physics_inbox: Vec<crate::physics::authority::AuthorityMessage>,
+    /// Reintentos de promoción de conexiones por relay
+    upgrade_scheduler: nat::UpgradeScheduler,
+    /// Transferencias de assets por trozos
+    transfers: transfer::TransferManager,
+    /// Cifrado y autenticación por peer
+    security: Option<security::PeerSecurity>,
+    /// Mensajes en espera de una sesión segura
+    secure_backlog: HashMap<PeerId, Vec<NetworkMessage>>,
+    /// Sesión de edición colaborativa de la escena
+    collab: Option<collab::CollaborativeEditSession>,
+    /// Nodo de la DHT de descubrimiento y registros
+    dht: Option<dht::Dht>,
+    /// Entidad en la que suena la voz local para los demás
+    voice_entity: Option<crate::ecs::EntityId>,
+    /// Paquetes de voz recibidos
+    voice_inbox: Vec<(PeerId, VoicePacket)>,
+    /// Última raíz de estado publicada por cada peer
+    state_roots: HashMap<PeerId, StateRoot>,
+    /// Tick de la siguiente raíz de estado local
+    state_tick: u64,
+    /// Segundos desde el último envío de replicación
+    replication_elapsed: f32,
     /// Estadísticas del sistema
     stats: NetworkingStats,
     /// Estado del sistema
@@ -54,6 +92,22 @@ pub struct NetworkingConfig {
     pub message_config: MessageConfig,
     /// Configuración de seguridad
     pub security_config: SecurityConfig,
+    /// Direcciones de relays de circuito
+    pub relays: Vec<String>,
+    /// Intervalo (s) entre intentos de promover conexiones por relay a directas
+    pub relay_upgrade_interval: u64,
+    /// Configuración de transferencia de assets
+    #[serde(default)]
+    pub transfer_config: transfer::TransferConfig,
+    /// Permitir sesiones de edición colaborativa de la escena
+    #[serde(default)]
+    pub collab_enabled: bool,
+    /// Compresión de los paquetes salientes grandes
+    #[serde(default)]
+    pub packet_compression: compression::CompressionMode,
+    /// Envíos de replicación por segundo (0 = en cada update)
+    #[serde(default)]
+    pub replication_tick_rate: f32,
 }
 
 /// Tipo de red
@@ -160,9 +214,36 @@ pub enum MessageType {
     Animation,
     Chat,
     State,
+    Replication,
+    PhysicsAuthority,
+    AssetTransfer,
+    Secure,
+    Collab,
+    Dht,
+    Voice,
     Custom(String),
 }
 
+/// Raíz del trie de estado del mundo de un peer en un tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateRoot {
+    /// Tick del peer
+    pub tick: u64,
+    /// Raíz (Keccak-256)
+    pub root: [u8; 32],
+}
+
+/// Trama de voz Opus numerada, con la entidad del hablante
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoicePacket {
+    /// Entidad en la que suena la voz
+    pub entity_id: Option<crate::ecs::EntityId>,
+    /// Número de secuencia
+    pub sequence: u32,
+    /// Paquete Opus
+    pub data: Vec<u8>,
+}
+
 /// Configuración de buffer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BufferConfig {
@@ -231,6 +312,10 @@ pub struct PeerInfo {
     pub latency: f32,
     /// Último ping
     pub last_ping: u64,
+    /// Ruta de conexión establecida
+    pub connection_path: Option<nat::ConnectionPath>,
+    /// RTT (ms) medido al establecer la ruta
+    pub path_rtt: f32,
     /// Metadatos
     pub metadata: HashMap<String, String>,
 }
@@ -317,6 +402,17 @@ pub struct NetworkingStats {
     pub connection_time: f32,
     /// Memoria utilizada
     pub memory_usage: usize,
+    /// Conexiones directas
+    pub direct_connections: usize,
+    /// Conexiones por hole punching
+    pub hole_punched_connections: usize,
+    /// Conexiones por relay
+    pub relayed_connections: usize,
+    /// Mensajes seguros rechazados (manipulados, repetidos o con handshake fallido)
+    pub rejected_secure_messages: u64,
+    /// Contactos en la tabla de rutas de la DHT
+    #[serde(default)]
+    pub dht_peers: usize,
 }
 
 /// Comportamiento de red del metaverso
@@ -326,8 +422,8 @@ pub struct MetaversoBehaviour {
     pub ping: Ping,
     /// Identificación
     pub identify: Identify,
-    /// Kademlia DHT
-    pub kademlia: Kademlia<libp2p::kad::store::MemoryStore>,
+    /// Descubrimiento mDNS en la red local (solo sin nodos bootstrap)
+    pub mdns: Toggle<mdns::tokio::Behaviour>,
     /// Gossipsub
     pub gossipsub: Gossipsub,
     /// Request-Response
@@ -393,6 +489,8 @@ impl NetworkingSystem {
     pub fn new(config: NetworkingConfig) -> Self {
         info!("Inicializando sistema de networking");
         
+        let upgrade_interval = std::time::Duration::from_secs(config.relay_upgrade_interval);
+        let transfers = transfer::TransferManager::new(config.transfer_config.clone());
         Self {
             config,
             swarm: None,
@@ -410,6 +508,19 @@ impl NetworkingSystem {
                     packets_received: 0,
                 },
             })),
+            replication: None,
+            physics_inbox: Vec::new(),
+            upgrade_scheduler: nat::UpgradeScheduler::new(upgrade_interval),
+            transfers,
+            security: None,
+            secure_backlog: HashMap::new(),
+            collab: None,
+            dht: None,
+            voice_entity: None,
+            voice_inbox: Vec::new(),
+            state_roots: HashMap::new(),
+            state_tick: 0,
+            replication_elapsed: 0.0,
             stats: NetworkingStats {
                 peer_count: 0,
                 messages_sent: 0,
@@ -418,6 +529,11 @@ impl NetworkingSystem {
                 packet_loss: 0.0,
                 connection_time: 0.0,
                 memory_usage: 0,
+                direct_connections: 0,
+                hole_punched_connections: 0,
+                relayed_connections: 0,
+                rejected_secure_messages: 0,
+                dht_peers: 0,
             },
             running: false,
         }
@@ -441,6 +557,14 @@ impl NetworkingSystem {
         // Conectar a nodos bootstrap
         self.connect_bootstrap_nodes().await?;
 
+        // Llenar la tabla de la DHT buscando el propio nodo
+        if let Some(dht) = &mut self.dht {
+            if self.config.p2p_config.discovery_config.bootstrap_nodes.is_empty() {
+                info!("Sin nodos bootstrap: descubrimiento por mDNS");
+            }
+            dht.bootstrap(std::time::Instant::now());
+        }
+
         self.running = true;
         info!("Sistema de networking inicializado correctamente");
         
@@ -471,7 +595,11 @@ impl NetworkingSystem {
                 "/metaverso/1.0.0".to_string(),
                 peer_id,
             )),
-            kademlia: Kademlia::new(peer_id, libp2p::kad::store::MemoryStore::new(peer_id)),
+            mdns: Toggle::from(if self.uses_mdns() {
+                Some(mdns::tokio::Behaviour::new(mdns::Config::default(), peer_id)?)
+            } else {
+                None
+            }),
             gossipsub: Gossipsub::new(
                 MessageAuthenticity::Signed(peer_id),
                 GossipsubConfig::default(),
@@ -490,6 +618,17 @@ impl NetworkingSystem {
         swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
 
         self.swarm = Some(swarm);
+        self.replication = Some(replication::ReplicationManager::new(peer_id));
+        let dht_config = &self.config.p2p_config.discovery_config.dht_config;
+        let bucket_size = if dht_config.bucket_size == 0 { dht::K } else { dht_config.bucket_size };
+        self.dht = Some(dht::Dht::new(
+            dht::DhtContact { peer: peer_id, address: Multiaddr::empty() },
+            bucket_size,
+            std::time::Duration::from_secs(dht_config.ttl),
+        ));
+        if self.config.security_config.encryption && self.security.is_none() {
+            self.security = Some(security::PeerSecurity::generate()?);
+        }
         info!("Swarm creado con peer ID: {}", peer_id);
         
         Ok(())
@@ -500,18 +639,21 @@ impl NetworkingSystem {
         if let Some(swarm) = &mut self.swarm {
             match self.config.p2p_config.discovery_config.discovery_method {
                 DiscoveryMethod::DHT => {
-                    // Configurar DHT
-                    if self.config.p2p_config.discovery_config.dht_config.enabled {
-                        // Agregar nodos bootstrap al DHT
+                    // Agregar nodos bootstrap a la DHT
+                    if let Some(dht) = &mut self.dht {
                         for node in &self.config.p2p_config.discovery_config.bootstrap_nodes {
-                            if let Ok(addr) = node.parse::<Multiaddr>() {
-                                swarm.behaviour_mut().kademlia.add_address(&addr);
+                            match node.parse::<Multiaddr>().ok().and_then(|addr| dht::DhtContact::from_multiaddr(&addr)) {
+                                Some(contact) => {
+                                    swarm.behaviour_mut().request_response.add_address(&contact.peer, contact.address.clone());
+                                    dht.add_contact(contact);
+                                }
+                                None => warn!("Nodo bootstrap sin /p2p/<peer>: {}", node),
                             }
                         }
                     }
                 }
                 DiscoveryMethod::MDNS => {
-                    // Configurar mDNS (no implementado en este ejemplo)
+                    // mDNS se activa al crear el swarm (`uses_mdns`)
                 }
                 DiscoveryMethod::Bootstrap => {
                     // Configurar nodos bootstrap
@@ -526,6 +668,17 @@ impl NetworkingSystem {
         Ok(())
     }
 
+    /// Usar mDNS: con el método `MDNS` o, en la DHT, cuando no hay nodos
+    /// bootstrap de los que partir
+    fn uses_mdns(&self) -> bool {
+        let discovery = &self.config.p2p_config.discovery_config;
+        discovery.enabled && match discovery.discovery_method {
+            DiscoveryMethod::MDNS => true,
+            DiscoveryMethod::DHT => discovery.dht_config.enabled && discovery.bootstrap_nodes.is_empty(),
+            _ => false,
+        }
+    }
+
     /// Conectar a nodos bootstrap
     async fn connect_bootstrap_nodes(&mut self) -> Result<()> {
         if let Some(swarm) = &mut self.swarm {
@@ -556,6 +709,29 @@ impl NetworkingSystem {
         // Procesar mensajes pendientes
         self.process_pending_messages().await?;
 
+        // Resolver propiedad y enviar replicación a `replication_tick_rate`
+        self.replication_elapsed += delta_time;
+        let tick_rate = self.config.replication_tick_rate;
+        if tick_rate <= 0.0 || self.replication_elapsed >= 1.0 / tick_rate {
+            self.replication_elapsed = 0.0;
+            self.flush_replication().await?;
+        }
+
+        // Enviar comandos de la sesión de edición colaborativa
+        self.flush_collab().await?;
+
+        // Intentar promover conexiones por relay a directas
+        self.retry_relay_upgrades().await;
+
+        // Establecer sesiones seguras con peers nuevos
+        self.establish_secure_sessions().await?;
+
+        // Reanudar y enviar transferencias de assets
+        self.flush_transfers().await?;
+
+        // Avanzar las búsquedas de la DHT y enviar sus mensajes
+        self.flush_dht().await?;
+
         // Actualizar estado de peers
         self.update_peer_states().await?;
 
@@ -574,6 +750,10 @@ impl NetworkingSystem {
                 match event {
                     SwarmEvent::NewListenAddr { address, .. } => {
                         info!("Escuchando en: {}", address);
+                        if let Some(dht) = &mut self.dht {
+                            let peer_id = *swarm.local_peer_id();
+                            dht.set_local_address(address.with(libp2p::multiaddr::Protocol::P2p(peer_id)));
+                        }
                     }
                     SwarmEvent::Behaviour(MetaversoBehaviourEvent::Ping(PingEvent {
                         peer,
@@ -587,11 +767,18 @@ impl NetworkingSystem {
                     })) => {
                         self.handle_peer_identified(peer_id, info).await;
                     }
-                    SwarmEvent::Behaviour(MetaversoBehaviourEvent::Kademlia(KademliaEvent::OutboundQueryCompleted {
-                        result: QueryResult::Bootstrap(Ok(_)),
-                        ..
-                    })) => {
-                        info!("Bootstrap completado");
+                    SwarmEvent::Behaviour(MetaversoBehaviourEvent::Mdns(mdns::Event::Discovered(discovered))) => {
+                        // Los peers locales sirven de bootstrap a la DHT
+                        if let Some(dht) = &mut self.dht {
+                            let mut new_contacts = false;
+                            for (peer, address) in discovered {
+                                swarm.behaviour_mut().request_response.add_address(&peer, address.clone());
+                                new_contacts |= dht.add_contact(dht::DhtContact { peer, address });
+                            }
+                            if new_contacts {
+                                dht.bootstrap(std::time::Instant::now());
+                            }
+                        }
                     }
                     SwarmEvent::Behaviour(MetaversoBehaviourEvent::Gossipsub(GossipsubEvent::Message {
                         propagation_source,
@@ -629,12 +816,17 @@ impl NetworkingSystem {
     /// Manejar peer identificado
     async fn handle_peer_identified(&mut self, peer_id: PeerId, info: libp2p::identify::Info) {
         let mut peers = self.peers.write().unwrap();
+        let (connection_path, path_rtt) = peers.get(&peer_id)
+            .map(|existing| (existing.connection_path, existing.path_rtt))
+            .unwrap_or((None, 0.0));
         let peer_info = PeerInfo {
             peer_id,
             address: info.listen_addrs[0].clone(),
             connection_state: ConnectionState::Connected,
             latency: 0.0,
             last_ping: 0,
+            connection_path,
+            path_rtt,
             metadata: HashMap::new(),
         };
         peers.insert(peer_id, peer_info);
@@ -644,6 +836,78 @@ impl NetworkingSystem {
         state.connected = true;
     }
 
+    /// Conectar con un peer probando directo, hole punching y relays en orden
+    pub async fn connect_peer(&mut self, peer_id: PeerId, address: Multiaddr) -> Result<nat::ConnectionPath> {
+        let candidates = nat::plan_paths(peer_id, &address, &self.config.relays);
+        let outcome = match &mut self.swarm {
+            Some(swarm) => {
+                let mut dialer = nat::SwarmDialer { swarm };
+                nat::connect_with_fallback(&mut dialer, peer_id, &candidates).await?
+            }
+            None => return Err(anyhow!("Swarm no inicializado")),
+        };
+
+        self.record_connection_path(peer_id, address, &outcome);
+        Ok(outcome.path)
+    }
+
+    /// Registrar la ruta de conexión en la tabla de peers
+    fn record_connection_path(&mut self, peer_id: PeerId, address: Multiaddr, outcome: &nat::PathOutcome) {
+        let mut peers = self.peers.write().unwrap();
+        let peer_info = peers.entry(peer_id).or_insert_with(|| PeerInfo {
+            peer_id,
+            address,
+            connection_state: ConnectionState::Connecting,
+            latency: 0.0,
+            last_ping: 0,
+            connection_path: None,
+            path_rtt: 0.0,
+            metadata: HashMap::new(),
+        });
+        peer_info.connection_path = Some(outcome.path);
+        peer_info.path_rtt = outcome.rtt.as_secs_f32() * 1000.0; // Convertir a ms
+
+        if outcome.path == nat::ConnectionPath::Relayed {
+            self.upgrade_scheduler.schedule(peer_id, std::time::Instant::now());
+        } else {
+            self.upgrade_scheduler.cancel(&peer_id);
+        }
+    }
+
+    /// Reintentar conexión directa para peers conectados por relay
+    async fn retry_relay_upgrades(&mut self) {
+        let due = self.upgrade_scheduler.due(std::time::Instant::now());
+        for peer_id in due {
+            let address = match self.peers.read().unwrap().get(&peer_id) {
+                Some(peer_info) if peer_info.connection_path == Some(nat::ConnectionPath::Relayed) => {
+                    peer_info.address.clone()
+                }
+                _ => {
+                    self.upgrade_scheduler.cancel(&peer_id);
+                    continue;
+                }
+            };
+
+            // Solo rutas directas: el relay ya está establecido
+            let candidates = nat::plan_paths(peer_id, &address, &[]);
+            let outcome = match &mut self.swarm {
+                Some(swarm) => {
+                    let mut dialer = nat::SwarmDialer { swarm };
+                    nat::connect_with_fallback(&mut dialer, peer_id, &candidates).await
+                }
+                None => return,
+            };
+
+            match outcome {
+                Ok(outcome) => {
+                    info!("Conexión con {} promovida de relay a {:?}", peer_id, outcome.path);
+                    self.record_connection_path(peer_id, address, &outcome);
+                }
+                Err(e) => debug!("Peer {} sigue por relay: {}", peer_id, e),
+            }
+        }
+    }
+
     /// Manejar mensaje gossipsub
     async fn handle_gossipsub_message(
         &mut self,
@@ -651,12 +915,38 @@ impl NetworkingSystem {
         message_id: MessageId,
         message: libp2p::gossipsub::Message,
     ) {
+        let replication_topic = libp2p::gossipsub::IdentTopic::new("metaverso-replication").hash();
+        let physics_topic = libp2p::gossipsub::IdentTopic::new("metaverso-physics").hash();
+        let collab_topic = libp2p::gossipsub::IdentTopic::new("metaverso-collab").hash();
+        let voice_topic = libp2p::gossipsub::IdentTopic::new("metaverso-voice").hash();
+        let state_topic = libp2p::gossipsub::IdentTopic::new("metaverso-state").hash();
+        let message_type = if message.topic == replication_topic {
+            MessageType::Replication
+        } else if message.topic == physics_topic {
+            MessageType::PhysicsAuthority
+        } else if message.topic == collab_topic {
+            MessageType::Collab
+        } else if message.topic == voice_topic {
+            MessageType::Voice
+        } else if message.topic == state_topic {
+            MessageType::State
+        } else {
+            MessageType::Custom("gossipsub".to_string())
+        };
+        let data = match compression::decompress_packet(&message.data) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Paquete de {} descartado: {}", source, e);
+                return;
+            }
+        };
+
         let network_message = NetworkMessage {
             id: message_id.to_string(),
-            message_type: MessageType::Custom("gossipsub".to_string()),
+            message_type,
             sender: source,
             recipient: None,
-            data: message.data,
+            data,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
@@ -676,6 +966,44 @@ impl NetworkingSystem {
     ) {
         match message {
             libp2p::request_response::Message::Request { request_id, request, .. } => {
+                let request = match compression::decompress_packet(&request) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        warn!("Petición de {} descartada: {}", peer, e);
+                        return;
+                    }
+                };
+                // Los mensajes de transferencia viajan como NetworkMessage completo
+                if let Ok(network_message) = bincode::deserialize::<NetworkMessage>(&request) {
+                    match network_message.message_type {
+                        MessageType::Secure => {
+                            self.pending_messages.write().unwrap().push(network_message);
+                            return;
+                        }
+                        MessageType::Dht => {
+                            // El remitente de la DHT entra en la tabla de rutas: tiene que
+                            // ser el peer de la conexión
+                            if network_message.sender == peer {
+                                self.pending_messages.write().unwrap().push(network_message);
+                            } else {
+                                warn!("Mensaje DHT de {} con remitente falso", peer);
+                            }
+                            return;
+                        }
+                        MessageType::AssetTransfer => {
+                            // Con cifrado activo no se aceptan mensajes punto a punto en claro
+                            if self.security.is_some() {
+                                warn!("Mensaje en claro de {} descartado", peer);
+                                self.stats.rejected_secure_messages += 1;
+                            } else {
+                                self.pending_messages.write().unwrap().push(network_message);
+                            }
+                            return;
+                        }
+                        _ => {}
+                    }
+                }
+
                 // Procesar request
                 let response = self.process_request(request).await;
                 // Enviar response (implementar)
@@ -729,6 +1057,43 @@ impl NetworkingSystem {
                 // Procesar actualización de estado
                 self.handle_state_update(message).await?;
             }
+            MessageType::Replication => {
+                // Procesar mensaje de replicación
+                self.handle_replication_message(message).await?;
+            }
+            MessageType::PhysicsAuthority => {
+                // Encolar mensaje de autoridad de física
+                let authority_message = bincode::deserialize(&message.data)?;
+                self.physics_inbox.push(authority_message);
+            }
+            MessageType::AssetTransfer => {
+                // Procesar mensaje de transferencia de assets
+                let transfer_message = bincode::deserialize(&message.data)?;
+                self.transfers.handle_message(message.sender, transfer_message);
+            }
+            MessageType::Secure => {
+                // Procesar trama segura
+                self.handle_secure_frame(message).await?;
+            }
+            MessageType::Collab => {
+                // Procesar comando de edición colaborativa
+                if let Some(session) = &mut self.collab {
+                    let collab_message = bincode::deserialize(&message.data)?;
+                    session.handle_message(collab_message)?;
+                }
+            }
+            MessageType::Dht => {
+                // Procesar mensaje de la DHT
+                if let Some(dht) = &mut self.dht {
+                    let dht_message = bincode::deserialize(&message.data)?;
+                    dht.handle_message(message.sender, dht_message, std::time::Instant::now());
+                }
+            }
+            MessageType::Voice => {
+                // Encolar paquete de voz
+                let voice_packet = bincode::deserialize(&message.data)?;
+                self.voice_inbox.push((message.sender, voice_packet));
+            }
             MessageType::Custom(_) => {
                 // Procesar mensaje personalizado
                 self.handle_custom_message(message).await?;
@@ -762,11 +1127,299 @@ impl NetworkingSystem {
 
     /// Manejar actualización de estado
     async fn handle_state_update(&mut self, message: NetworkMessage) -> Result<()> {
-        // Implementar sincronización de estado
         debug!("Procesando actualización de estado de {}", message.sender);
+        let state_root: StateRoot = bincode::deserialize(&message.data)?;
+        // Las raíces atrasadas (gossip desordenado) no reemplazan a la última
+        let newer = self.state_roots.get(&message.sender).map_or(true, |current| state_root.tick > current.tick);
+        if newer {
+            self.state_roots.insert(message.sender, state_root);
+        }
+        Ok(())
+    }
+
+    /// Manejar mensaje de replicación
+    async fn handle_replication_message(&mut self, message: NetworkMessage) -> Result<()> {
+        if let Some(replication) = &mut self.replication {
+            let replication_message: replication::ReplicationMessage = bincode::deserialize(&message.data)?;
+            replication.handle_message(replication_message)?;
+        }
+        Ok(())
+    }
+
+    /// Enviar los mensajes salientes de la sesión de edición colaborativa
+    async fn flush_collab(&mut self) -> Result<()> {
+        let (local_peer, outgoing) = match (&self.replication, &mut self.collab) {
+            (Some(replication), Some(session)) => (replication.local_peer(), session.drain_outbox()),
+            _ => return Ok(()),
+        };
+
+        for collab_message in outgoing {
+            let message = NetworkMessage {
+                id: format!("collab-{}", self.stats.messages_sent),
+                message_type: MessageType::Collab,
+                sender: local_peer,
+                recipient: None,
+                data: bincode::serialize(&collab_message)?,
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                priority: MessagePriority::High,
+            };
+            self.send_message(message).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Resolver transferencias de propiedad y enviar mensajes de replicación
+    async fn flush_replication(&mut self) -> Result<()> {
+        let (local_peer, outgoing) = match &mut self.replication {
+            Some(replication) => {
+                replication.resolve_transfers();
+                (replication.local_peer(), replication.drain_outbox())
+            }
+            None => return Ok(()),
+        };
+
+        for replication_message in outgoing {
+            let message = NetworkMessage {
+                id: format!("replication-{}", self.stats.messages_sent),
+                message_type: MessageType::Replication,
+                sender: local_peer,
+                recipient: None,
+                data: bincode::serialize(&replication_message)?,
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                priority: MessagePriority::High,
+            };
+            self.send_message(message).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Procesar trama segura: handshake o mensaje cifrado
+    async fn handle_secure_frame(&mut self, message: NetworkMessage) -> Result<()> {
+        let security = match &mut self.security {
+            Some(security) => security,
+            None => return Ok(()),
+        };
+        let peer = message.sender;
+        let frame: security::SecureFrame = bincode::deserialize(&message.data)?;
+
+        match frame {
+            security::SecureFrame::Handshake { stage, payload } => {
+                let reply = match security.handle_handshake(peer, stage, &payload) {
+                    Ok(reply) => reply,
+                    Err(e) => {
+                        warn!("Handshake con {} fallido: {}", peer, e);
+                        self.stats.rejected_secure_messages += 1;
+                        self.secure_backlog.remove(&peer);
+                        return Ok(());
+                    }
+                };
+                let established = security.is_established(&peer);
+
+                if let Some(reply) = reply {
+                    self.send_secure_frame(peer, reply).await?;
+                }
+
+                // Enviar lo que esperaba a la sesión
+                if established {
+                    for pending in self.secure_backlog.remove(&peer).unwrap_or_default() {
+                        self.send_message(pending).await?;
+                    }
+                }
+            }
+            security::SecureFrame::Sealed { channel, nonce, ciphertext } => {
+                let plaintext = match security.open(&peer, channel, nonce, &ciphertext) {
+                    Ok(plaintext) => plaintext,
+                    Err(e) => {
+                        warn!("Mensaje seguro de {} rechazado: {}", peer, e);
+                        self.stats.rejected_secure_messages += 1;
+                        return Ok(());
+                    }
+                };
+
+                let inner: NetworkMessage = bincode::deserialize(&plaintext)?;
+                if inner.sender != peer {
+                    warn!("Remitente interno {} no coincide con la sesión de {}", inner.sender, peer);
+                    self.stats.rejected_secure_messages += 1;
+                    return Ok(());
+                }
+                self.pending_messages.write().unwrap().push(inner);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Iniciar handshakes con peers conectados sin sesión segura
+    async fn establish_secure_sessions(&mut self) -> Result<()> {
+        let connected = self.connected_peers();
+        let security = match &mut self.security {
+            Some(security) => security,
+            None => return Ok(()),
+        };
+
+        let mut frames = Vec::new();
+        for peer in connected {
+            if !security.is_established(&peer) && !security.is_pending(&peer) {
+                frames.push((peer, security.initiate(peer)?));
+            }
+        }
+
+        for (peer, frame) in frames {
+            self.send_secure_frame(peer, frame).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Enviar una trama segura a un peer
+    async fn send_secure_frame(&mut self, peer: PeerId, frame: security::SecureFrame) -> Result<()> {
+        let sender = match self.swarm.as_ref() {
+            Some(swarm) => *swarm.local_peer_id(),
+            None => return Ok(()),
+        };
+
+        let message = NetworkMessage {
+            id: format!("secure-{}", self.stats.messages_sent),
+            message_type: MessageType::Secure,
+            sender,
+            recipient: Some(peer),
+            data: bincode::serialize(&frame)?,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            priority: MessagePriority::High,
+        };
+        self.send_message(message).await
+    }
+
+    /// Cifrar un mensaje punto a punto; `None` si queda en espera del handshake
+    fn seal_outgoing(&mut self, message: NetworkMessage) -> Result<Option<NetworkMessage>> {
+        let security = match &mut self.security {
+            Some(security) => security,
+            None => return Ok(Some(message)),
+        };
+        let recipient = match message.recipient {
+            // La DHT descubre peers antes de que haya sesión segura con ellos
+            Some(recipient) if !matches!(message.message_type, MessageType::Secure | MessageType::Dht) => recipient,
+            _ => return Ok(Some(message)),
+        };
+
+        if !security.is_established(&recipient) {
+            self.secure_backlog.entry(recipient).or_default().push(message);
+            return Ok(None);
+        }
+
+        let channel = match message.message_type {
+            MessageType::Replication | MessageType::PhysicsAuthority | MessageType::State | MessageType::Collab => {
+                security::SecureChannel::Control
+            }
+            _ => security::SecureChannel::Data,
+        };
+        let frame = security.seal(&recipient, channel, &bincode::serialize(&message)?)?;
+
+        Ok(Some(NetworkMessage {
+            id: message.id,
+            message_type: MessageType::Secure,
+            sender: message.sender,
+            recipient: Some(recipient),
+            data: bincode::serialize(&frame)?,
+            timestamp: message.timestamp,
+            priority: message.priority,
+        }))
+    }
+
+    /// Vincular la identidad de red a un wallet; reinicia las sesiones seguras
+    pub fn bind_wallet_identity(&mut self, wallet: &crate::crypto::wallet::Wallet) {
+        self.security = Some(security::PeerSecurity::from_wallet(wallet));
+        self.secure_backlog.clear();
+    }
+
+    /// Dirección de wallet verificada de un peer
+    pub fn get_peer_wallet(&self, peer: &PeerId) -> Option<String> {
+        self.security.as_ref()
+            .and_then(|security| security.remote_wallet(peer))
+            .map(|address| address.to_string())
+    }
+
+    /// Reanudar transferencias detenidas y enviar mensajes de transferencia
+    async fn flush_transfers(&mut self) -> Result<()> {
+        let sender = match self.swarm.as_ref() {
+            Some(swarm) => *swarm.local_peer_id(),
+            None => return Ok(()),
+        };
+
+        let connected = self.connected_peers();
+        self.transfers.resume_stalled(&connected, std::time::Instant::now());
+
+        for (peer, transfer_message) in self.transfers.drain_outbox() {
+            let message = NetworkMessage {
+                id: format!("transfer-{}", self.stats.messages_sent),
+                message_type: MessageType::AssetTransfer,
+                sender,
+                recipient: Some(peer),
+                data: bincode::serialize(&transfer_message)?,
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                priority: MessagePriority::Low,
+            };
+            self.send_message(message).await?;
+        }
+
         Ok(())
     }
 
+    /// Avanzar la DHT y enviar sus mensajes a la dirección de cada contacto
+    async fn flush_dht(&mut self) -> Result<()> {
+        let (Some(dht), Some(swarm)) = (&mut self.dht, &mut self.swarm) else {
+            return Ok(());
+        };
+        let sender = *swarm.local_peer_id();
+        dht.tick(std::time::Instant::now());
+        self.stats.dht_peers = dht.routing_table().len();
+        let outbox = dht.drain_outbox();
+
+        for (contact, dht_message) in outbox {
+            if let Some(swarm) = &mut self.swarm {
+                swarm.behaviour_mut().request_response.add_address(&contact.peer, contact.address.clone());
+            }
+            let message = NetworkMessage {
+                id: format!("dht-{}", self.stats.messages_sent),
+                message_type: MessageType::Dht,
+                sender,
+                recipient: Some(contact.peer),
+                data: bincode::serialize(&dht_message)?,
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                priority: MessagePriority::Normal,
+            };
+            self.send_message(message).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Peers conectados
+    fn connected_peers(&self) -> Vec<PeerId> {
+        self.peers.read().unwrap()
+            .values()
+            .filter(|peer_info| matches!(peer_info.connection_state, ConnectionState::Connected))
+            .map(|peer_info| peer_info.peer_id)
+            .collect()
+    }
+
     /// Manejar mensaje personalizado
     async fn handle_custom_message(&mut self, message: NetworkMessage) -> Result<()> {
         // Implementar procesamiento de mensajes personalizados
@@ -779,6 +1432,7 @@ impl NetworkingSystem {
         let mut peers = self.peers.write().unwrap();
         let mut total_latency = 0.0;
         let mut active_peers = 0;
+        let mut disconnected = Vec::new();
 
         for peer_info in peers.values_mut() {
             // Verificar si el peer sigue activo
@@ -788,6 +1442,9 @@ impl NetworkingSystem {
                 .as_secs();
 
             if now - peer_info.last_ping > 30 {
+                if !matches!(peer_info.connection_state, ConnectionState::Disconnected) {
+                    disconnected.push(peer_info.peer_id);
+                }
                 peer_info.connection_state = ConnectionState::Disconnected;
             } else {
                 total_latency += peer_info.latency;
@@ -802,27 +1459,70 @@ impl NetworkingSystem {
             state.average_latency = total_latency / active_peers as f32;
         }
 
+        // Las descargas servidas por peers desconectados quedan a la espera de reanudarse
+        for peer_id in disconnected {
+            self.transfers.handle_peer_disconnected(&peer_id);
+            if let Some(security) = &mut self.security {
+                security.remove_peer(&peer_id);
+            }
+            self.secure_backlog.remove(&peer_id);
+        }
+
         Ok(())
     }
 
     /// Enviar mensaje
     pub async fn send_message(&mut self, message: NetworkMessage) -> Result<()> {
+        let message = match self.seal_outgoing(message)? {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+
         if let Some(swarm) = &mut self.swarm {
+            let mode = self.config.packet_compression;
+            let packet = |data: &[u8]| compression::compress_packet(mode, data);
             match message.message_type {
                 MessageType::Position | MessageType::Animation | MessageType::State => {
                     // Usar gossipsub para mensajes de estado
                     let topic = libp2p::gossipsub::IdentTopic::new("metaverso-state");
-                    swarm.behaviour_mut().gossipsub.publish(topic, message.data)?;
+                    swarm.behaviour_mut().gossipsub.publish(topic, packet(&message.data)?)?;
+                }
+                MessageType::Replication => {
+                    // Usar gossipsub para replicación
+                    let topic = libp2p::gossipsub::IdentTopic::new("metaverso-replication");
+                    swarm.behaviour_mut().gossipsub.publish(topic, packet(&message.data)?)?;
+                }
+                MessageType::PhysicsAuthority => {
+                    // Usar gossipsub para autoridad de física
+                    let topic = libp2p::gossipsub::IdentTopic::new("metaverso-physics");
+                    swarm.behaviour_mut().gossipsub.publish(topic, packet(&message.data)?)?;
+                }
+                MessageType::Collab => {
+                    // Usar gossipsub para la edición colaborativa
+                    let topic = libp2p::gossipsub::IdentTopic::new("metaverso-collab");
+                    swarm.behaviour_mut().gossipsub.publish(topic, packet(&message.data)?)?;
                 }
                 MessageType::Chat => {
                     // Usar gossipsub para chat
                     let topic = libp2p::gossipsub::IdentTopic::new("metaverso-chat");
-                    swarm.behaviour_mut().gossipsub.publish(topic, message.data)?;
+                    swarm.behaviour_mut().gossipsub.publish(topic, packet(&message.data)?)?;
+                }
+                MessageType::Voice => {
+                    // Usar gossipsub para la voz; el jitter buffer absorbe pérdidas y desorden
+                    let topic = libp2p::gossipsub::IdentTopic::new("metaverso-voice");
+                    swarm.behaviour_mut().gossipsub.publish(topic, packet(&message.data)?)?;
+                }
+                MessageType::AssetTransfer | MessageType::Secure | MessageType::Dht => {
+                    // Usar request-response (fiable, punto a punto) para trozos de assets, tramas seguras y la DHT
+                    if let Some(recipient) = message.recipient {
+                        let data = packet(&bincode::serialize(&message)?)?;
+                        swarm.behaviour_mut().request_response.send_request(&recipient, data);
+                    }
                 }
                 MessageType::Custom(_) => {
                     // Usar request-response para mensajes personalizados
                     if let Some(recipient) = message.recipient {
-                        swarm.behaviour_mut().request_response.send_request(&recipient, message.data);
+                        swarm.behaviour_mut().request_response.send_request(&recipient, packet(&message.data)?);
                     }
                 }
             }
@@ -839,6 +1539,206 @@ impl NetworkingSystem {
         peers.values().cloned().collect()
     }
 
+    /// Envíos de replicación por segundo (0 = en cada update)
+    pub fn replication_tick_rate(&self) -> f32 {
+        self.config.replication_tick_rate
+    }
+
+    /// Cambiar los envíos de replicación por segundo
+    pub fn set_replication_tick_rate(&mut self, tick_rate: f32) {
+        self.config.replication_tick_rate = tick_rate.max(0.0);
+    }
+
+    /// Obtener gestor de replicación
+    pub fn get_replication(&self) -> Option<&replication::ReplicationManager> {
+        self.replication.as_ref()
+    }
+
+    /// Obtener gestor de replicación mutable
+    pub fn get_replication_mut(&mut self) -> Option<&mut replication::ReplicationManager> {
+        self.replication.as_mut()
+    }
+
+    /// Extraer eventos de cambio de propiedad
+    pub fn drain_ownership_events(&mut self) -> Vec<replication::OwnershipChanged> {
+        self.replication
+            .as_mut()
+            .map(|replication| replication.drain_events())
+            .unwrap_or_default()
+    }
+
+    /// Enviar mensajes de autoridad de física
+    pub async fn send_physics_authority(&mut self, messages: Vec<crate::physics::authority::AuthorityMessage>) -> Result<()> {
+        let sender = match &self.replication {
+            Some(replication) => replication.local_peer(),
+            None => return Ok(()),
+        };
+
+        for authority_message in messages {
+            let message = NetworkMessage {
+                id: format!("physics-{}", self.stats.messages_sent),
+                message_type: MessageType::PhysicsAuthority,
+                sender,
+                recipient: None,
+                data: bincode::serialize(&authority_message)?,
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                priority: MessagePriority::High,
+            };
+            self.send_message(message).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Entidad en la que los demás oyen la voz local (el avatar)
+    pub fn set_voice_entity(&mut self, entity_id: Option<crate::ecs::EntityId>) {
+        self.voice_entity = entity_id;
+    }
+
+    /// Enviar una trama de voz codificada a todos los peers
+    pub async fn send_voice_packet(&mut self, data: Vec<u8>, sequence: u32) -> Result<()> {
+        let sender = match &self.swarm {
+            Some(swarm) => *swarm.local_peer_id(),
+            None => return Ok(()),
+        };
+        let voice_packet = VoicePacket { entity_id: self.voice_entity, sequence, data };
+        let message = NetworkMessage {
+            id: format!("voice-{}", sequence),
+            message_type: MessageType::Voice,
+            sender,
+            recipient: None,
+            data: bincode::serialize(&voice_packet)?,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            priority: MessagePriority::High,
+        };
+        self.send_message(message).await
+    }
+
+    /// Publicar la raíz del trie de estado del mundo del tick actual
+    pub async fn publish_state_root(&mut self, root: [u8; 32]) -> Result<()> {
+        let sender = match &self.swarm {
+            Some(swarm) => *swarm.local_peer_id(),
+            None => return Ok(()),
+        };
+        let state_root = StateRoot { tick: self.state_tick, root };
+        self.state_tick += 1;
+        let message = NetworkMessage {
+            id: format!("state-{}", state_root.tick),
+            message_type: MessageType::State,
+            sender,
+            recipient: None,
+            data: bincode::serialize(&state_root)?,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            priority: MessagePriority::Normal,
+        };
+        self.send_message(message).await
+    }
+
+    /// Última raíz de estado publicada por un peer, contra la que se
+    /// verifican sus pruebas de entidades
+    pub fn peer_state_root(&self, peer: &PeerId) -> Option<StateRoot> {
+        self.state_roots.get(peer).copied()
+    }
+
+    /// Extraer los paquetes de voz recibidos con su remitente
+    pub fn drain_voice_packets(&mut self) -> Vec<(PeerId, VoicePacket)> {
+        std::mem::take(&mut self.voice_inbox)
+    }
+
+    /// Ofrecer un asset a otros peers; devuelve su hash de contenido
+    pub fn provide_asset(&mut self, data: Vec<u8>) -> String {
+        self.transfers.provide(data)
+    }
+
+    /// Solicitar un asset por hash; el stream publica el progreso de la descarga
+    pub fn request_asset(&mut self, hash: &str) -> impl futures::Stream<Item = transfer::TransferProgress> {
+        let connected = self.connected_peers();
+        self.transfers.request(hash, &connected)
+    }
+
+    /// Obtener un asset descargado y verificado
+    pub fn get_asset(&self, hash: &str) -> Option<Arc<Vec<u8>>> {
+        self.transfers.get_completed(hash)
+    }
+
+    /// Unirse a una sesión de edición colaborativa ordenada por `leader`
+    /// (el peer local si es None)
+    pub fn start_collab_session(&mut self, session_id: &str, leader: Option<PeerId>) -> Result<()> {
+        if !self.config.collab_enabled {
+            return Err(anyhow!("Edición colaborativa deshabilitada"));
+        }
+        let local_peer = self.replication
+            .as_ref()
+            .map(|replication| replication.local_peer())
+            .ok_or_else(|| anyhow!("Networking sin iniciar"))?;
+        let leader = leader.unwrap_or(local_peer);
+        info!("Sesión de edición {} con líder {}", session_id, leader);
+        self.collab = Some(collab::CollaborativeEditSession::new(
+            session_id,
+            &local_peer.to_string(),
+            &leader.to_string(),
+        ));
+        Ok(())
+    }
+
+    /// Salir de la sesión de edición colaborativa
+    pub fn leave_collab_session(&mut self) {
+        self.collab = None;
+    }
+
+    /// Enviar un comando a la sesión de edición colaborativa
+    pub fn submit_edit(&mut self, command: crate::ecs::ECSCommand) -> Result<()> {
+        match &mut self.collab {
+            Some(session) => session.submit(command),
+            None => Err(anyhow!("Sin sesión de edición colaborativa")),
+        }
+    }
+
+    /// Extraer los comandos de la sesión listos para el ECS, en orden de revisión
+    pub fn drain_collab_commands(&mut self) -> Vec<crate::ecs::ECSCommand> {
+        self.collab
+            .as_mut()
+            .map(|session| session.drain_commands())
+            .unwrap_or_default()
+    }
+
+    /// Obtener estadísticas de la sesión de edición colaborativa
+    pub fn collab_stats(&self) -> Option<collab::SessionStats> {
+        self.collab.as_ref().map(|session| session.get_stats())
+    }
+
+    /// Extraer mensajes de autoridad de física recibidos
+    pub fn drain_physics_authority(&mut self) -> Vec<crate::physics::authority::AuthorityMessage> {
+        std::mem::take(&mut self.physics_inbox)
+    }
+
+    /// Publicar un valor en la DHT (p. ej. el registro de un mundo)
+    pub fn put_value(&mut self, key: [u8; 32], value: Vec<u8>) -> Result<()> {
+        let dht = self.dht.as_mut().ok_or_else(|| anyhow!("DHT no inicializada"))?;
+        dht.put_value(key, value, std::time::Instant::now());
+        Ok(())
+    }
+
+    /// Valor de una clave de la DHT. Si aún no se conoce se busca en la red
+    /// y estará disponible en una llamada posterior
+    pub fn get_value(&mut self, key: [u8; 32]) -> Option<Vec<u8>> {
+        self.dht.as_mut()?.get_value(key, std::time::Instant::now())
+    }
+
+    /// Estadísticas de la DHT
+    pub fn dht_stats(&self) -> Option<dht::DhtStats> {
+        self.dht.as_ref().map(|dht| dht.stats())
+    }
+
     /// Obtener estado de red
     pub fn get_network_state(&self) -> NetworkState {
         let state = self.state.read().unwrap();
@@ -857,6 +1757,15 @@ impl NetworkingSystem {
             self.stats.average_latency = total_latency / peers.len() as f32;
         }
 
+        // Contar conexiones por ruta
+        let count_path = |path: nat::ConnectionPath| {
+            peers.values().filter(|p| p.connection_path == Some(path)).count()
+        };
+        self.stats.direct_connections = count_path(nat::ConnectionPath::Direct);
+        self.stats.hole_punched_connections = count_path(nat::ConnectionPath::HolePunched);
+        self.stats.relayed_connections = count_path(nat::ConnectionPath::Relayed);
+        drop(peers);
+
         // Calcular uso de memoria
         self.stats.memory_usage = std::mem::size_of_val(self);
     }
@@ -872,6 +1781,19 @@ impl NetworkingSystem {
         
         self.running = false;
         self.swarm = None;
+        self.replication = None;
+        self.collab = None;
+        self.dht = None;
+        self.voice_inbox.clear();
+        self.state_roots.clear();
+        self.physics_inbox.clear();
+        if let Some(security) = &mut self.security {
+            security.clear_sessions();
+        }
+        self.secure_backlog.clear();
+        self.upgrade_scheduler = nat::UpgradeScheduler::new(
+            std::time::Duration::from_secs(self.config.relay_upgrade_interval),
+        );
         self.peers.write().unwrap().clear();
         self.pending_messages.write().unwrap().clear();
         
@@ -0,0 +1,262 @@
+//! # Travesía de NAT
+//!
+//! Selección de ruta de conexión para peers detrás de NAT: primero conexión
+//! directa, después hole punching y, como último recurso, un relay de circuito.
+//! Las conexiones por relay se reintentan periódicamente para promoverlas a
+//! conexión directa.
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use libp2p::{core::PeerId, Multiaddr};
+use anyhow::{Result, anyhow};
+use tracing::{debug, info, warn};
+
+/// Ruta de conexión con un peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ConnectionPath {
+    /// Conexión directa
+    Direct,
+    /// Conexión directa tras hole punching coordinado
+    HolePunched,
+    /// Conexión a través de un relay de circuito
+    Relayed,
+}
+
+/// Candidato de conexión
+#[derive(Debug, Clone)]
+pub struct PathCandidate {
+    /// Ruta
+    pub path: ConnectionPath,
+    /// Dirección a marcar
+    pub address: Multiaddr,
+}
+
+/// Resultado de una conexión establecida
+#[derive(Debug, Clone)]
+pub struct PathOutcome {
+    /// Ruta que tuvo éxito
+    pub path: ConnectionPath,
+    /// Dirección usada
+    pub address: Multiaddr,
+    /// Tiempo de ida y vuelta
+    pub rtt: Duration,
+}
+
+/// Marcador de conexiones por ruta
+#[async_trait::async_trait]
+pub trait PathDialer: Send {
+    /// Intentar conectar por una ruta concreta y devolver el RTT medido
+    async fn dial(&mut self, peer: PeerId, candidate: &PathCandidate) -> Result<Duration>;
+}
+
+/// Construir los candidatos en orden: directo, hole punching, relays
+pub fn plan_paths(peer: PeerId, address: &Multiaddr, relays: &[String]) -> Vec<PathCandidate> {
+    let mut candidates = vec![
+        PathCandidate { path: ConnectionPath::Direct, address: address.clone() },
+        PathCandidate { path: ConnectionPath::HolePunched, address: address.clone() },
+    ];
+
+    for relay in relays {
+        match format!("{}/p2p-circuit/p2p/{}", relay, peer).parse::<Multiaddr>() {
+            Ok(address) => candidates.push(PathCandidate { path: ConnectionPath::Relayed, address }),
+            Err(e) => warn!("Dirección de relay inválida {}: {}", relay, e),
+        }
+    }
+
+    candidates
+}
+
+/// Conectar probando los candidatos en orden hasta que uno tenga éxito
+pub async fn connect_with_fallback<D: PathDialer>(
+    dialer: &mut D,
+    peer: PeerId,
+    candidates: &[PathCandidate],
+) -> Result<PathOutcome> {
+    for candidate in candidates {
+        match dialer.dial(peer, candidate).await {
+            Ok(rtt) => {
+                info!("Conectado a {} por {:?} (rtt {:?})", peer, candidate.path, rtt);
+                return Ok(PathOutcome {
+                    path: candidate.path,
+                    address: candidate.address.clone(),
+                    rtt,
+                });
+            }
+            Err(e) => {
+                debug!("Fallo conectando a {} por {:?}: {}", peer, candidate.path, e);
+            }
+        }
+    }
+
+    Err(anyhow!("No se pudo conectar con {} por ninguna ruta", peer))
+}
+
+/// Planificador de reintentos para promover conexiones por relay a directas
+#[derive(Debug, Clone)]
+pub struct UpgradeScheduler {
+    /// Intervalo entre reintentos
+    interval: Duration,
+    /// Próximo reintento por peer
+    next_retry: HashMap<PeerId, Instant>,
+}
+
+impl UpgradeScheduler {
+    /// Crear planificador
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next_retry: HashMap::new(),
+        }
+    }
+
+    /// Programar reintentos para un peer conectado por relay
+    pub fn schedule(&mut self, peer: PeerId, now: Instant) {
+        self.next_retry.insert(peer, now + self.interval);
+    }
+
+    /// Dejar de reintentar para un peer
+    pub fn cancel(&mut self, peer: &PeerId) {
+        self.next_retry.remove(peer);
+    }
+
+    /// Peers cuyo reintento vence en `now`; se reprograman automáticamente
+    pub fn due(&mut self, now: Instant) -> Vec<PeerId> {
+        let due: Vec<PeerId> = self.next_retry.iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(peer, _)| *peer)
+            .collect();
+
+        for peer in &due {
+            self.next_retry.insert(*peer, now + self.interval);
+        }
+
+        due
+    }
+
+    /// Número de peers programados
+    pub fn pending(&self) -> usize {
+        self.next_retry.len()
+    }
+}
+
+/// Marcador sobre el swarm de libp2p
+pub struct SwarmDialer<'a> {
+    /// Swarm
+    pub swarm: &'a mut libp2p::swarm::Swarm<super::MetaversoBehaviour>,
+}
+
+#[async_trait::async_trait]
+impl<'a> PathDialer for SwarmDialer<'a> {
+    async fn dial(&mut self, peer: PeerId, candidate: &PathCandidate) -> Result<Duration> {
+        let start = Instant::now();
+        match candidate.path {
+            ConnectionPath::Direct | ConnectionPath::Relayed => {
+                self.swarm.dial(candidate.address.clone())?;
+            }
+            ConnectionPath::HolePunched => {
+                // El hole punching requiere una conexión previa por relay que
+                // coordine la apertura simultánea (DCUtR)
+                if !self.swarm.is_connected(&peer) {
+                    return Err(anyhow!("Hole punching sin conexión de coordinación"));
+                }
+                self.swarm.dial(candidate.address.clone())?;
+            }
+        }
+        Ok(start.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transporte simulado: solo conectan las rutas alcanzables y se anota
+    /// el orden de los intentos
+    struct SimulatedDialer {
+        reachable: Vec<ConnectionPath>,
+        attempts: Vec<ConnectionPath>,
+    }
+
+    #[async_trait::async_trait]
+    impl PathDialer for SimulatedDialer {
+        async fn dial(&mut self, _peer: PeerId, candidate: &PathCandidate) -> Result<Duration> {
+            self.attempts.push(candidate.path);
+            if self.reachable.contains(&candidate.path) {
+                Ok(Duration::from_millis(20))
+            } else {
+                Err(anyhow!("Ruta {:?} inalcanzable", candidate.path))
+            }
+        }
+    }
+
+    fn relay() -> String {
+        format!("/ip4/10.0.0.1/tcp/4001/p2p/{}", PeerId::random())
+    }
+
+    #[test]
+    fn paths_are_planned_direct_then_hole_punch_then_relays() {
+        let peer = PeerId::random();
+        let address: Multiaddr = "/ip4/192.168.1.20/udp/4001/quic-v1".parse().unwrap();
+        let relays = [relay(), "no es una dirección".to_string(), relay()];
+
+        let candidates = plan_paths(peer, &address, &relays);
+        let paths: Vec<_> = candidates.iter().map(|candidate| candidate.path).collect();
+        assert_eq!(paths, [
+            ConnectionPath::Direct,
+            ConnectionPath::HolePunched,
+            ConnectionPath::Relayed,
+            ConnectionPath::Relayed,
+        ]);
+        assert_eq!(candidates[0].address, address);
+        assert!(candidates[2].address.to_string().starts_with(&relays[0]));
+        assert!(candidates[3].address.to_string().ends_with(&format!("/p2p-circuit/p2p/{}", peer)));
+    }
+
+    #[tokio::test]
+    async fn fallback_stops_at_the_first_reachable_path() {
+        let peer = PeerId::random();
+        let address: Multiaddr = "/ip4/192.168.1.20/udp/4001/quic-v1".parse().unwrap();
+        let candidates = plan_paths(peer, &address, &[relay()]);
+
+        let mut dialer = SimulatedDialer { reachable: vec![ConnectionPath::Relayed], attempts: Vec::new() };
+        let outcome = connect_with_fallback(&mut dialer, peer, &candidates).await.unwrap();
+        assert_eq!(outcome.path, ConnectionPath::Relayed);
+        assert_eq!(outcome.rtt, Duration::from_millis(20));
+        assert_eq!(dialer.attempts, [ConnectionPath::Direct, ConnectionPath::HolePunched, ConnectionPath::Relayed]);
+
+        let mut dialer = SimulatedDialer {
+            reachable: vec![ConnectionPath::Direct, ConnectionPath::Relayed],
+            attempts: Vec::new(),
+        };
+        let outcome = connect_with_fallback(&mut dialer, peer, &candidates).await.unwrap();
+        assert_eq!(outcome.path, ConnectionPath::Direct);
+        assert_eq!(dialer.attempts, [ConnectionPath::Direct]);
+
+        let mut dialer = SimulatedDialer { reachable: Vec::new(), attempts: Vec::new() };
+        assert!(connect_with_fallback(&mut dialer, peer, &candidates).await.is_err());
+        assert_eq!(dialer.attempts.len(), candidates.len());
+    }
+
+    #[test]
+    fn relayed_peers_are_retried_every_interval_until_cancelled() {
+        let interval = Duration::from_secs(30);
+        let mut scheduler = UpgradeScheduler::new(interval);
+        let (relayed, other) = (PeerId::random(), PeerId::random());
+        let start = Instant::now();
+        scheduler.schedule(relayed, start);
+        scheduler.schedule(other, start + Duration::from_secs(10));
+        assert_eq!(scheduler.pending(), 2);
+
+        assert!(scheduler.due(start + interval - Duration::from_millis(1)).is_empty());
+        assert_eq!(scheduler.due(start + interval), [relayed]);
+        // Reprogramado un intervalo después del vencimiento
+        assert!(scheduler.due(start + interval + Duration::from_secs(5)).is_empty());
+        assert_eq!(scheduler.due(start + interval + Duration::from_secs(10)), [other]);
+        assert_eq!(scheduler.due(start + interval * 2), [relayed]);
+
+        scheduler.cancel(&relayed);
+        assert_eq!(scheduler.pending(), 1);
+        assert_eq!(scheduler.due(start + interval * 10), [other]);
+    }
+}
@@ -0,0 +1,175 @@
+//! # Transformación operacional de comandos del ECS
+//!
+//! Reglas para fusionar dos `ECSCommand` concurrentes: `transform(a, b)`
+//! devuelve `(a', b')` de modo que aplicar `a` y después `b'` deja el mundo
+//! igual que aplicar `b` y después `a'`. En los empates gana `a`, así que
+//! quien llama decide la prioridad con el orden de los argumentos (el líder
+//! de una sesión pasa primero el comando que ya aplicó).
+//!
+//! - Dos `CreateEntity` con el mismo ID: `b` recibe otro ID derivado de ambos.
+//! - `DestroyEntity` anula cualquier otro comando sobre la misma entidad.
+//! - Dos escrituras del mismo componente (añadir, actualizar, quitar o la
+//!   pose de cámara sobre `Transform`) se resuelven a favor de `a`; sobre
+//!   componentes distintos se conservan las dos, así que dos `AddComponent`
+//!   sobre la misma entidad se fusionan.
+//! - El root motion se acumula y se conserva con todo salvo la destrucción
+//!   (con giro, el orden de dos movimientos cambia la posición final).
+
+use anyhow::Result;
+use glam::{Quat, Vec3};
+use serde::{Serialize, Deserialize};
+
+use crate::ecs::snapshot::ComponentSnapshot;
+use crate::ecs::{ComponentType, ECSCommand, Entity, EntityId, EntityState};
+
+/// Bit alto de los IDs reasignados, fuera del rango del contador local
+const REMAPPED_ID_BIT: EntityId = 1 << 63;
+
+/// Parte del estado de una entidad que escribe un comando
+#[derive(Debug, Clone, PartialEq)]
+enum Slot {
+    /// La entidad entera (crear o destruir)
+    Entity,
+    /// Un componente
+    Component(ComponentType),
+    /// El estado de la entidad
+    State,
+    /// El propietario de red (ordenado por su propia versión)
+    Owner,
+    /// Desplazamiento aditivo del root motion
+    Motion,
+}
+
+/// Entidad y parte que escribe un comando (None para `Noop`)
+fn target(command: &ECSCommand) -> Option<(EntityId, Slot)> {
+    match command {
+        ECSCommand::Noop => None,
+        ECSCommand::CreateEntity(entity) => Some((entity.id, Slot::Entity)),
+        ECSCommand::DestroyEntity(entity_id) => Some((*entity_id, Slot::Entity)),
+        ECSCommand::AddComponent(entity_id, component) => Some((*entity_id, Slot::Component(component.get_type()))),
+        ECSCommand::RemoveComponent(entity_id, component_type)
+        | ECSCommand::UpdateComponent(entity_id, component_type, _) => Some((*entity_id, Slot::Component(component_type.clone()))),
+        ECSCommand::SetEntityState(entity_id, _) => Some((*entity_id, Slot::State)),
+        ECSCommand::SetNetworkOwner(entity_id, _, _) => Some((*entity_id, Slot::Owner)),
+        ECSCommand::ApplyRootMotion(entity_id, _, _) => Some((*entity_id, Slot::Motion)),
+        ECSCommand::SetCameraPose(entity_id, _, _, _) => Some((*entity_id, Slot::Component(ComponentType::Transform))),
+    }
+}
+
+/// Entidad sobre la que actúa un comando
+pub fn command_entity(command: &ECSCommand) -> Option<EntityId> {
+    target(command).map(|(entity_id, _)| entity_id)
+}
+
+/// El mismo comando sobre otra entidad
+pub fn with_entity(command: &ECSCommand, entity_id: EntityId) -> ECSCommand {
+    match command.clone() {
+        ECSCommand::Noop => ECSCommand::Noop,
+        ECSCommand::CreateEntity(mut entity) => {
+            entity.id = entity_id;
+            ECSCommand::CreateEntity(entity)
+        }
+        ECSCommand::DestroyEntity(_) => ECSCommand::DestroyEntity(entity_id),
+        ECSCommand::AddComponent(_, component) => ECSCommand::AddComponent(entity_id, component),
+        ECSCommand::RemoveComponent(_, component_type) => ECSCommand::RemoveComponent(entity_id, component_type),
+        ECSCommand::UpdateComponent(_, component_type, component) => ECSCommand::UpdateComponent(entity_id, component_type, component),
+        ECSCommand::SetEntityState(_, state) => ECSCommand::SetEntityState(entity_id, state),
+        ECSCommand::SetNetworkOwner(_, owner, version) => ECSCommand::SetNetworkOwner(entity_id, owner, version),
+        ECSCommand::ApplyRootMotion(_, translation, rotation) => ECSCommand::ApplyRootMotion(entity_id, translation, rotation),
+        ECSCommand::SetCameraPose(_, position, rotation, fov) => ECSCommand::SetCameraPose(entity_id, position, rotation, fov),
+    }
+}
+
+/// ID determinista para una entidad creada a la vez que otra con el mismo ID
+pub fn remap_entity_id(entity_id: EntityId, salt: u64) -> EntityId {
+    // splitmix64 de ambos valores
+    let mut z = entity_id ^ salt.rotate_left(32) ^ 0x9e37_79b9_7f4a_7c15;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)) | REMAPPED_ID_BIT
+}
+
+/// Transformar dos comandos concurrentes. `a'` se aplica tras `b` y `b'`
+/// tras `a`; en los empates gana `a`
+pub fn transform(a: &ECSCommand, b: &ECSCommand) -> (ECSCommand, ECSCommand) {
+    let (Some((entity_a, slot_a)), Some((entity_b, slot_b))) = (target(a), target(b)) else {
+        return (a.clone(), b.clone());
+    };
+    if entity_a != entity_b {
+        return (a.clone(), b.clone());
+    }
+
+    match (a, b) {
+        // Dos entidades nuevas con el mismo ID: `b` pasa a otro
+        (ECSCommand::CreateEntity(_), ECSCommand::CreateEntity(_)) => {
+            (a.clone(), with_entity(b, remap_entity_id(entity_b, entity_a.wrapping_add(1))))
+        }
+        // Ambos destruyen: el segundo ya no tiene nada que hacer
+        (ECSCommand::DestroyEntity(_), ECSCommand::DestroyEntity(_)) => (ECSCommand::Noop, ECSCommand::Noop),
+        // La destrucción gana a cualquier otro cambio de la entidad
+        (ECSCommand::DestroyEntity(_), _) => (a.clone(), ECSCommand::Noop),
+        (_, ECSCommand::DestroyEntity(_)) => (ECSCommand::Noop, b.clone()),
+        _ => match (&slot_a, &slot_b) {
+            (Slot::Motion, _) | (_, Slot::Motion) | (Slot::Owner, Slot::Owner) => (a.clone(), b.clone()),
+            // Misma parte de la entidad: se queda la escritura de `a`
+            _ if slot_a == slot_b => (a.clone(), ECSCommand::Noop),
+            _ => (a.clone(), b.clone()),
+        },
+    }
+}
+
+/// Comando serializable para la red: los componentes viajan como
+/// `ComponentSnapshot`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WireCommand {
+    Noop,
+    CreateEntity(Entity),
+    DestroyEntity(EntityId),
+    AddComponent(EntityId, ComponentSnapshot),
+    RemoveComponent(EntityId, ComponentType),
+    UpdateComponent(EntityId, ComponentType, ComponentSnapshot),
+    SetEntityState(EntityId, EntityState),
+    SetNetworkOwner(EntityId, Option<String>, u64),
+    ApplyRootMotion(EntityId, Vec3, Quat),
+    SetCameraPose(EntityId, Vec3, Quat, f32),
+}
+
+impl WireCommand {
+    /// Serializar un comando
+    pub fn capture(command: &ECSCommand) -> Result<Self> {
+        Ok(match command {
+            ECSCommand::Noop => Self::Noop,
+            ECSCommand::CreateEntity(entity) => Self::CreateEntity(entity.clone()),
+            ECSCommand::DestroyEntity(entity_id) => Self::DestroyEntity(*entity_id),
+            ECSCommand::AddComponent(entity_id, component) => {
+                Self::AddComponent(*entity_id, ComponentSnapshot::capture(component.as_ref())?)
+            }
+            ECSCommand::RemoveComponent(entity_id, component_type) => Self::RemoveComponent(*entity_id, component_type.clone()),
+            ECSCommand::UpdateComponent(entity_id, component_type, component) => {
+                Self::UpdateComponent(*entity_id, component_type.clone(), ComponentSnapshot::capture(component.as_ref())?)
+            }
+            ECSCommand::SetEntityState(entity_id, state) => Self::SetEntityState(*entity_id, state.clone()),
+            ECSCommand::SetNetworkOwner(entity_id, owner, version) => Self::SetNetworkOwner(*entity_id, owner.clone(), *version),
+            ECSCommand::ApplyRootMotion(entity_id, translation, rotation) => Self::ApplyRootMotion(*entity_id, *translation, *rotation),
+            ECSCommand::SetCameraPose(entity_id, position, rotation, fov) => Self::SetCameraPose(*entity_id, *position, *rotation, *fov),
+        })
+    }
+
+    /// Reconstruir el comando
+    pub fn restore(&self) -> Result<ECSCommand> {
+        Ok(match self {
+            Self::Noop => ECSCommand::Noop,
+            Self::CreateEntity(entity) => ECSCommand::CreateEntity(entity.clone()),
+            Self::DestroyEntity(entity_id) => ECSCommand::DestroyEntity(*entity_id),
+            Self::AddComponent(entity_id, component) => ECSCommand::AddComponent(*entity_id, component.restore()?),
+            Self::RemoveComponent(entity_id, component_type) => ECSCommand::RemoveComponent(*entity_id, component_type.clone()),
+            Self::UpdateComponent(entity_id, component_type, component) => {
+                ECSCommand::UpdateComponent(*entity_id, component_type.clone(), component.restore()?)
+            }
+            Self::SetEntityState(entity_id, state) => ECSCommand::SetEntityState(*entity_id, state.clone()),
+            Self::SetNetworkOwner(entity_id, owner, version) => ECSCommand::SetNetworkOwner(*entity_id, owner.clone(), *version),
+            Self::ApplyRootMotion(entity_id, translation, rotation) => ECSCommand::ApplyRootMotion(*entity_id, *translation, *rotation),
+            Self::SetCameraPose(entity_id, position, rotation, fov) => ECSCommand::SetCameraPose(*entity_id, *position, *rotation, *fov),
+        })
+    }
+}
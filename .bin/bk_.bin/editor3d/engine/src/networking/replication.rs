@@ -0,0 +1,542 @@
+//! # Replicación con Propiedad
+//!
+//! Semántica de propiedad para entidades replicadas entre peers.
+//! Cada entidad tiene un peer propietario: sólo él escribe su estado, el resto
+//! envía solicitudes. La propiedad se transfiere con un handshake de tres pasos
+//! (solicitud, concesión, confirmación) y un contador de versión que invalida
+//! los paquetes tardíos del propietario anterior.
+//!
+//! Las entidades sin propietario se reclaman durante una ventana de ticks:
+//! cada solicitud queda abierta hasta que las de los demás peers han podido
+//! llegar, y sólo entonces se elige al ID de peer menor.
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use libp2p::core::PeerId;
+use anyhow::{Result, anyhow};
+use tracing::{debug, info};
+
+use crate::ecs::EntityId;
+
+/// Ticks que permanece abierta la reclamación de una entidad sin propietario
+pub const DEFAULT_CLAIM_WINDOW_TICKS: u32 = 2;
+
+/// Gestor de replicación
+pub struct ReplicationManager {
+    /// Peer local
+    local_peer: PeerId,
+    /// Entidades replicadas
+    entities: HashMap<EntityId, ReplicatedEntity>,
+    /// Solicitudes de propiedad pendientes de resolver
+    transfer_requests: HashMap<EntityId, PendingClaims>,
+    /// Ticks de espera antes de resolver reclamaciones sin propietario
+    claim_window_ticks: u32,
+    /// Acciones solicitadas por otros peers sobre entidades propias
+    pending_actions: Vec<ActionRequest>,
+    /// Mensajes salientes
+    outbox: Vec<ReplicationMessage>,
+    /// Eventos de cambio de propiedad
+    events: Vec<OwnershipChanged>,
+    /// Estadísticas
+    stats: ReplicationStats,
+}
+
+/// Entidad replicada
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicatedEntity {
+    /// ID de la entidad
+    pub entity_id: EntityId,
+    /// Propietario actual
+    pub owner: Option<PeerId>,
+    /// Versión de propiedad
+    pub version: u64,
+    /// Estado de transferencia
+    pub transfer_state: TransferState,
+    /// Último estado replicado
+    pub data: Vec<u8>,
+}
+
+/// Solicitudes acumuladas sobre una entidad
+#[derive(Debug, Clone, Default)]
+struct PendingClaims {
+    /// Peers candidatos
+    candidates: Vec<PeerId>,
+    /// Ticks restantes antes de resolver
+    ticks_remaining: u32,
+}
+
+/// Estado de transferencia de propiedad
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferState {
+    /// Sin transferencia en curso
+    Stable,
+    /// El peer local solicitó la propiedad sobre esta versión
+    Requested { version: u64 },
+    /// El peer local concedió la propiedad y espera confirmación
+    Granted { to: PeerId, version: u64 },
+}
+
+/// Mensaje del protocolo de replicación
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReplicationMessage {
+    /// Escritura de estado del propietario
+    Write { entity_id: EntityId, sender: PeerId, version: u64, data: Vec<u8> },
+    /// Acción solicitada por un no propietario (p. ej. "recoger objeto")
+    Action { entity_id: EntityId, sender: PeerId, action: String, payload: Vec<u8> },
+    /// Paso 1: solicitud de propiedad sobre la versión conocida
+    TransferRequest { entity_id: EntityId, sender: PeerId, version: u64 },
+    /// Paso 2: concesión del propietario con la nueva versión
+    TransferGrant { entity_id: EntityId, sender: PeerId, new_owner: PeerId, version: u64 },
+    /// Paso 3: confirmación del nuevo propietario
+    TransferAck { entity_id: EntityId, sender: PeerId, version: u64 },
+}
+
+/// Acción solicitada por otro peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionRequest {
+    /// ID de la entidad
+    pub entity_id: EntityId,
+    /// Peer solicitante
+    pub sender: PeerId,
+    /// Nombre de la acción
+    pub action: String,
+    /// Datos de la acción
+    pub payload: Vec<u8>,
+}
+
+/// Evento de cambio de propiedad
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnershipChanged {
+    /// ID de la entidad
+    pub entity_id: EntityId,
+    /// Propietario anterior
+    pub previous_owner: Option<PeerId>,
+    /// Nuevo propietario
+    pub new_owner: Option<PeerId>,
+    /// Versión de propiedad
+    pub version: u64,
+}
+
+/// Estadísticas de replicación
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplicationStats {
+    /// Entidades replicadas
+    pub replicated_entities: usize,
+    /// Entidades propias
+    pub owned_entities: usize,
+    /// Escrituras rechazadas
+    pub rejected_writes: u64,
+    /// Transferencias completadas
+    pub transfers_completed: u64,
+}
+
+impl ReplicationManager {
+    /// Crear nuevo gestor de replicación
+    pub fn new(local_peer: PeerId) -> Self {
+        Self {
+            local_peer,
+            entities: HashMap::new(),
+            transfer_requests: HashMap::new(),
+            claim_window_ticks: DEFAULT_CLAIM_WINDOW_TICKS,
+            pending_actions: Vec::new(),
+            outbox: Vec::new(),
+            events: Vec::new(),
+            stats: ReplicationStats::default(),
+        }
+    }
+
+    /// Peer local
+    pub fn local_peer(&self) -> PeerId {
+        self.local_peer
+    }
+
+    /// Ticks de espera de las reclamaciones sin propietario
+    pub fn claim_window_ticks(&self) -> u32 {
+        self.claim_window_ticks
+    }
+
+    /// Establecer los ticks de espera de las reclamaciones sin propietario.
+    /// Debe cubrir al menos un viaje de ida y vuelta entre peers.
+    pub fn set_claim_window_ticks(&mut self, ticks: u32) {
+        self.claim_window_ticks = ticks.max(1);
+    }
+
+    /// Registrar entidad replicada
+    pub fn register_entity(&mut self, entity_id: EntityId, owner: Option<PeerId>) {
+        self.entities.entry(entity_id).or_insert(ReplicatedEntity {
+            entity_id,
+            owner,
+            version: 0,
+            transfer_state: TransferState::Stable,
+            data: Vec::new(),
+        });
+        self.update_stats();
+    }
+
+    /// Eliminar entidad replicada
+    pub fn unregister_entity(&mut self, entity_id: EntityId) {
+        self.entities.remove(&entity_id);
+        self.transfer_requests.remove(&entity_id);
+        self.update_stats();
+    }
+
+    /// Obtener entidad replicada
+    pub fn get_entity(&self, entity_id: EntityId) -> Option<&ReplicatedEntity> {
+        self.entities.get(&entity_id)
+    }
+
+    /// Obtener propietario de una entidad
+    pub fn owner(&self, entity_id: EntityId) -> Option<PeerId> {
+        self.entities.get(&entity_id).and_then(|e| e.owner)
+    }
+
+    /// Verificar si el peer local es propietario
+    pub fn is_owner(&self, entity_id: EntityId) -> bool {
+        self.owner(entity_id) == Some(self.local_peer)
+    }
+
+    /// Escribir estado de una entidad propia
+    pub fn write(&mut self, entity_id: EntityId, data: Vec<u8>) -> Result<()> {
+        let local_peer = self.local_peer;
+        let entity = self.entities.get_mut(&entity_id)
+            .ok_or_else(|| anyhow!("Entidad {} no replicada", entity_id))?;
+
+        if entity.owner != Some(local_peer) {
+            return Err(anyhow!("El peer local no es propietario de la entidad {}", entity_id));
+        }
+
+        entity.data = data.clone();
+        self.outbox.push(ReplicationMessage::Write {
+            entity_id,
+            sender: local_peer,
+            version: entity.version,
+            data,
+        });
+
+        Ok(())
+    }
+
+    /// Solicitar una acción al propietario de la entidad
+    pub fn request_action(&mut self, entity_id: EntityId, action: &str, payload: Vec<u8>) -> Result<()> {
+        if !self.entities.contains_key(&entity_id) {
+            return Err(anyhow!("Entidad {} no replicada", entity_id));
+        }
+
+        let request = ActionRequest {
+            entity_id,
+            sender: self.local_peer,
+            action: action.to_string(),
+            payload,
+        };
+
+        if self.is_owner(entity_id) {
+            self.pending_actions.push(request);
+        } else {
+            self.outbox.push(ReplicationMessage::Action {
+                entity_id,
+                sender: request.sender,
+                action: request.action,
+                payload: request.payload,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Solicitar la propiedad de una entidad
+    pub fn request_ownership(&mut self, entity_id: EntityId) -> Result<()> {
+        let local_peer = self.local_peer;
+        let entity = self.entities.get_mut(&entity_id)
+            .ok_or_else(|| anyhow!("Entidad {} no replicada", entity_id))?;
+
+        if entity.owner == Some(local_peer) {
+            return Ok(());
+        }
+
+        let version = entity.version;
+        entity.transfer_state = TransferState::Requested { version };
+
+        // Sin propietario todos los peers arbitran, incluido el local
+        if entity.owner.is_none() {
+            self.add_claim(entity_id, local_peer, false);
+        }
+
+        self.outbox.push(ReplicationMessage::TransferRequest {
+            entity_id,
+            sender: local_peer,
+            version,
+        });
+
+        Ok(())
+    }
+
+    /// Procesar mensaje recibido
+    pub fn handle_message(&mut self, message: ReplicationMessage) -> Result<()> {
+        match message {
+            ReplicationMessage::Write { entity_id, sender, version, data } => {
+                let entity = self.entities.get_mut(&entity_id)
+                    .ok_or_else(|| anyhow!("Entidad {} no replicada", entity_id))?;
+
+                if entity.owner != Some(sender) || entity.version != version {
+                    debug!("Escritura descartada de {} sobre entidad {} (versión {})", sender, entity_id, version);
+                    self.stats.rejected_writes += 1;
+                    return Ok(());
+                }
+
+                entity.data = data;
+            }
+            ReplicationMessage::Action { entity_id, sender, action, payload } => {
+                if self.is_owner(entity_id) {
+                    self.pending_actions.push(ActionRequest { entity_id, sender, action, payload });
+                }
+            }
+            ReplicationMessage::TransferRequest { entity_id, sender, version } => {
+                let local_peer = self.local_peer;
+                let entity = self.entities.get(&entity_id)
+                    .ok_or_else(|| anyhow!("Entidad {} no replicada", entity_id))?;
+
+                // Las solicitudes sobre versiones antiguas llegan tarde y se ignoran
+                if entity.version != version {
+                    return Ok(());
+                }
+
+                // El propietario arbitra; sin propietario arbitran todos
+                if entity.owner == Some(local_peer) {
+                    self.add_claim(entity_id, sender, true);
+                } else if entity.owner.is_none() {
+                    self.add_claim(entity_id, sender, false);
+                }
+            }
+            ReplicationMessage::TransferGrant { entity_id, sender, new_owner, version } => {
+                let local_peer = self.local_peer;
+                let entity = self.entities.get_mut(&entity_id)
+                    .ok_or_else(|| anyhow!("Entidad {} no replicada", entity_id))?;
+
+                if entity.owner != Some(sender) || version != entity.version + 1 {
+                    debug!("Concesión descartada de {} sobre entidad {}", sender, entity_id);
+                    return Ok(());
+                }
+
+                let previous_owner = entity.owner;
+                entity.owner = Some(new_owner);
+                entity.version = version;
+                entity.transfer_state = TransferState::Stable;
+
+                if new_owner == local_peer {
+                    self.outbox.push(ReplicationMessage::TransferAck {
+                        entity_id,
+                        sender: local_peer,
+                        version,
+                    });
+                }
+
+                self.transfer_requests.remove(&entity_id);
+                self.events.push(OwnershipChanged {
+                    entity_id,
+                    previous_owner,
+                    new_owner: Some(new_owner),
+                    version,
+                });
+                self.update_stats();
+            }
+            ReplicationMessage::TransferAck { entity_id, sender, version } => {
+                if let Some(entity) = self.entities.get_mut(&entity_id) {
+                    if entity.transfer_state == (TransferState::Granted { to: sender, version }) {
+                        entity.transfer_state = TransferState::Stable;
+                        self.stats.transfers_completed += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Añadir un candidato a las solicitudes de una entidad. Las solicitudes
+    /// al propietario se resuelven en el siguiente tick; las reclamaciones sin
+    /// propietario esperan la ventana completa.
+    fn add_claim(&mut self, entity_id: EntityId, peer: PeerId, arbitrated_by_owner: bool) {
+        let window = if arbitrated_by_owner { 0 } else { self.claim_window_ticks };
+        let claims = self.transfer_requests.entry(entity_id).or_insert_with(|| PendingClaims {
+            candidates: Vec::new(),
+            ticks_remaining: window,
+        });
+        claims.candidates.push(peer);
+    }
+
+    /// Resolver las solicitudes de propiedad cuya ventana ha vencido.
+    /// Ante solicitudes simultáneas gana el ID de peer menor, de modo que
+    /// todos los peers llegan al mismo resultado.
+    pub fn resolve_transfers(&mut self) {
+        let local_peer = self.local_peer;
+        let mut requests = std::mem::take(&mut self.transfer_requests);
+
+        // Las reclamaciones abiertas esperan a las solicitudes de otros peers
+        requests.retain(|entity_id, claims| {
+            if claims.ticks_remaining == 0 {
+                return true;
+            }
+            claims.ticks_remaining -= 1;
+            self.transfer_requests.insert(*entity_id, claims.clone());
+            false
+        });
+
+        for (entity_id, claims) in requests {
+            let mut candidates = claims.candidates;
+            let entity = match self.entities.get_mut(&entity_id) {
+                Some(entity) => entity,
+                None => continue,
+            };
+
+            candidates.sort();
+            candidates.dedup();
+            let winner = match candidates.first() {
+                Some(winner) => *winner,
+                None => continue,
+            };
+
+            let previous_owner = entity.owner;
+            let version = entity.version + 1;
+
+            match previous_owner {
+                Some(owner) if owner == local_peer => {
+                    if entity.transfer_state != TransferState::Stable {
+                        // Transferencia en curso; las solicitudes quedan obsoletas
+                        continue;
+                    }
+                    entity.transfer_state = TransferState::Granted { to: winner, version };
+                    self.outbox.push(ReplicationMessage::TransferGrant {
+                        entity_id,
+                        sender: local_peer,
+                        new_owner: winner,
+                        version,
+                    });
+                }
+                Some(_) => continue,
+                None => {
+                    entity.transfer_state = TransferState::Stable;
+                }
+            }
+
+            entity.owner = Some(winner);
+            entity.version = version;
+
+            info!("Propiedad de entidad {} transferida a {} (versión {})", entity_id, winner, version);
+            self.events.push(OwnershipChanged {
+                entity_id,
+                previous_owner,
+                new_owner: Some(winner),
+                version,
+            });
+        }
+
+        self.update_stats();
+    }
+
+    /// Extraer mensajes salientes
+    pub fn drain_outbox(&mut self) -> Vec<ReplicationMessage> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Extraer acciones pendientes
+    pub fn drain_actions(&mut self) -> Vec<ActionRequest> {
+        std::mem::take(&mut self.pending_actions)
+    }
+
+    /// Extraer eventos de cambio de propiedad
+    pub fn drain_events(&mut self) -> Vec<OwnershipChanged> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Actualizar estadísticas
+    fn update_stats(&mut self) {
+        self.stats.replicated_entities = self.entities.len();
+        self.stats.owned_entities = self.entities.values()
+            .filter(|e| e.owner == Some(self.local_peer))
+            .count();
+    }
+
+    /// Obtener estadísticas
+    pub fn get_stats(&self) -> ReplicationStats {
+        self.stats.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Un tick de red: cada peer resuelve y envía, y el otro recibe
+    fn tick(a: &mut ReplicationManager, b: &mut ReplicationManager) {
+        a.resolve_transfers();
+        b.resolve_transfers();
+        let from_a = a.drain_outbox();
+        let from_b = b.drain_outbox();
+        for message in from_a {
+            b.handle_message(message).unwrap();
+        }
+        for message in from_b {
+            a.handle_message(message).unwrap();
+        }
+    }
+
+    fn peers() -> (ReplicationManager, ReplicationManager) {
+        let mut a = ReplicationManager::new(PeerId::random());
+        let mut b = ReplicationManager::new(PeerId::random());
+        a.register_entity(1, None);
+        b.register_entity(1, None);
+        (a, b)
+    }
+
+    #[test]
+    fn simultaneous_requests_elect_a_single_owner() {
+        let (mut a, mut b) = peers();
+        a.request_ownership(1).unwrap();
+        b.request_ownership(1).unwrap();
+
+        // La ventana sigue abierta tras el primer envío
+        tick(&mut a, &mut b);
+        assert_eq!(a.owner(1), None);
+        assert_eq!(b.owner(1), None);
+
+        for _ in 0..DEFAULT_CLAIM_WINDOW_TICKS {
+            tick(&mut a, &mut b);
+        }
+
+        let expected = a.local_peer().min(b.local_peer());
+        assert_eq!(a.owner(1), Some(expected));
+        assert_eq!(b.owner(1), Some(expected));
+        assert_eq!(a.get_entity(1).unwrap().version, 1);
+        assert_eq!(b.get_entity(1).unwrap().version, 1);
+        assert_ne!(a.is_owner(1), b.is_owner(1));
+    }
+
+    #[test]
+    fn writes_from_the_loser_are_discarded() {
+        let (mut a, mut b) = peers();
+        a.request_ownership(1).unwrap();
+        b.request_ownership(1).unwrap();
+        for _ in 0..=DEFAULT_CLAIM_WINDOW_TICKS {
+            tick(&mut a, &mut b);
+        }
+
+        let (winner, loser) = if a.is_owner(1) { (&mut a, &mut b) } else { (&mut b, &mut a) };
+        assert!(loser.write(1, vec![1]).is_err());
+
+        // Paquete tardío del perdedor con la versión anterior
+        winner.handle_message(ReplicationMessage::Write {
+            entity_id: 1,
+            sender: loser.local_peer(),
+            version: 0,
+            data: vec![2],
+        }).unwrap();
+        assert!(winner.get_entity(1).unwrap().data.is_empty());
+        assert_eq!(winner.get_stats().rejected_writes, 1);
+
+        winner.write(1, vec![3]).unwrap();
+        for message in winner.drain_outbox() {
+            loser.handle_message(message).unwrap();
+        }
+        assert_eq!(loser.get_entity(1).unwrap().data, vec![3]);
+    }
+}
@@ -0,0 +1,488 @@
+//! # Seguridad por Peer
+//!
+//! Handshake Noise XX por peer que produce claves de sesión propias y cifrado
+//! ChaCha20-Poly1305 de los mensajes punto a punto. Cada trama lleva un nonce
+//! explícito y el receptor mantiene una ventana deslizante para descartar
+//! repeticiones, ya que el canal no garantiza orden. La clave estática puede
+//! derivarse del wallet y vincularse a su dirección con una firma EIP-191.
+//!
+//! Los temas gossipsub son difusión y no usan claves por peer; quedan cubiertos
+//! por el transporte noise de libp2p y la firma de mensajes de gossipsub.
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use libp2p::core::PeerId;
+use anyhow::{Result, anyhow};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, KeyInit};
+use chacha20poly1305::aead::{Aead, Payload};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+use zeroize::Zeroize;
+
+use crate::crypto::wallet;
+
+/// Patrón Noise usado en el handshake
+const NOISE_PATTERN: &str = "Noise_XX_25519_ChaChaPoly_SHA256";
+/// Tamaño máximo de un mensaje de handshake
+const MAX_HANDSHAKE_LEN: usize = 1024;
+/// Tamaño de la ventana de repetición en nonces
+const REPLAY_WINDOW: u64 = 64;
+
+/// Canal de una trama cifrada, usado como dato asociado
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SecureChannel {
+    /// Mensajes de control (handshakes de aplicación, propiedad, autoridad)
+    Control,
+    /// Datos de juego y assets
+    Data,
+}
+
+/// Trama del protocolo seguro
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SecureFrame {
+    /// Mensaje de handshake Noise (etapas 1 a 3)
+    Handshake { stage: u8, payload: Vec<u8> },
+    /// Mensaje cifrado
+    Sealed { channel: SecureChannel, nonce: u64, ciphertext: Vec<u8> },
+}
+
+/// Vinculación de la clave de red a una dirección de wallet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletBinding {
+    /// Dirección del wallet
+    pub address: String,
+    /// Firma EIP-191 sobre la clave estática pública de red
+    pub signature: Vec<u8>,
+}
+
+/// Ventana deslizante de nonces recibidos
+#[derive(Debug, Clone, Default)]
+struct ReplayWindow {
+    /// Nonce más alto aceptado
+    highest: u64,
+    /// Bits de los nonces aceptados por debajo del más alto
+    bitmap: u64,
+    /// Se recibió algún nonce
+    initialized: bool,
+}
+
+impl ReplayWindow {
+    /// Comprobar si un nonce es nuevo y dentro de la ventana
+    fn check(&self, nonce: u64) -> bool {
+        if !self.initialized || nonce > self.highest {
+            return true;
+        }
+        let offset = self.highest - nonce;
+        offset < REPLAY_WINDOW && self.bitmap & (1 << offset) == 0
+    }
+
+    /// Registrar un nonce ya autenticado
+    fn accept(&mut self, nonce: u64) {
+        if !self.initialized {
+            self.initialized = true;
+            self.highest = nonce;
+            self.bitmap = 1;
+        } else if nonce > self.highest {
+            let shift = nonce - self.highest;
+            self.bitmap = if shift >= REPLAY_WINDOW { 0 } else { self.bitmap << shift };
+            self.bitmap |= 1;
+            self.highest = nonce;
+        } else {
+            self.bitmap |= 1 << (self.highest - nonce);
+        }
+    }
+}
+
+/// Sesión establecida con un peer
+struct PeerSession {
+    /// Cifrador de envío
+    send: ChaCha20Poly1305,
+    /// Cifrador de recepción
+    recv: ChaCha20Poly1305,
+    /// Siguiente nonce de envío
+    next_nonce: u64,
+    /// Ventana de repetición
+    replay: ReplayWindow,
+    /// Clave estática del peer
+    remote_static: [u8; 32],
+    /// Dirección de wallet verificada del peer
+    remote_wallet: Option<String>,
+}
+
+/// Handshake en curso con un peer
+struct PendingHandshake {
+    /// Estado Noise
+    state: snow::HandshakeState,
+    /// Somos el iniciador
+    initiator: bool,
+    /// Siguiente etapa esperada
+    next_stage: u8,
+}
+
+/// Gestor de seguridad por peer
+pub struct PeerSecurity {
+    /// Clave estática privada
+    static_secret: [u8; 32],
+    /// Clave estática pública
+    static_public: [u8; 32],
+    /// Vinculación a wallet del peer local
+    binding: Option<WalletBinding>,
+    /// Claves estáticas esperadas por peer
+    pinned: HashMap<PeerId, [u8; 32]>,
+    /// Handshakes en curso
+    pending: HashMap<PeerId, PendingHandshake>,
+    /// Sesiones establecidas
+    sessions: HashMap<PeerId, PeerSession>,
+}
+
+impl PeerSecurity {
+    /// Crear gestor con una clave estática
+    pub fn new(static_secret: [u8; 32]) -> Self {
+        let static_public = x25519_dalek::x25519(static_secret, x25519_dalek::X25519_BASEPOINT_BYTES);
+        Self {
+            static_secret,
+            static_public,
+            binding: None,
+            pinned: HashMap::new(),
+            pending: HashMap::new(),
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// Crear gestor con una clave estática aleatoria
+    pub fn generate() -> Result<Self> {
+        let mut keypair = snow::Builder::new(NOISE_PATTERN.parse()?).generate_keypair()?;
+        let secret: [u8; 32] = keypair.private.as_slice().try_into()
+            .map_err(|_| anyhow!("Clave estática generada con longitud inválida"))?;
+        keypair.private.zeroize();
+        Ok(Self::new(secret))
+    }
+
+    /// Crear gestor con la clave de red derivada de un wallet, vinculada a su dirección
+    pub fn from_wallet(wallet: &wallet::Wallet) -> Self {
+        let mut security = Self::new(wallet.derive_network_key());
+        let signature = wallet.sign_message(&security.static_public);
+        security.binding = Some(WalletBinding {
+            address: wallet.address(),
+            signature: signature.to_vec(),
+        });
+        security
+    }
+
+    /// Clave estática pública local
+    pub fn static_public(&self) -> [u8; 32] {
+        self.static_public
+    }
+
+    /// Fijar la clave estática esperada de un peer
+    pub fn pin_peer(&mut self, peer: PeerId, static_public: [u8; 32]) {
+        self.pinned.insert(peer, static_public);
+    }
+
+    /// Verificar si hay sesión con un peer
+    pub fn is_established(&self, peer: &PeerId) -> bool {
+        self.sessions.contains_key(peer)
+    }
+
+    /// Verificar si hay un handshake en curso con un peer
+    pub fn is_pending(&self, peer: &PeerId) -> bool {
+        self.pending.contains_key(peer)
+    }
+
+    /// Dirección de wallet verificada de un peer
+    pub fn remote_wallet(&self, peer: &PeerId) -> Option<&str> {
+        self.sessions.get(peer).and_then(|session| session.remote_wallet.as_deref())
+    }
+
+    /// Clave estática de un peer con sesión
+    pub fn remote_static(&self, peer: &PeerId) -> Option<[u8; 32]> {
+        self.sessions.get(peer).map(|session| session.remote_static)
+    }
+
+    /// Olvidar todas las sesiones y handshakes
+    pub fn clear_sessions(&mut self) {
+        self.pending.clear();
+        self.sessions.clear();
+    }
+
+    /// Olvidar la sesión con un peer
+    pub fn remove_peer(&mut self, peer: &PeerId) {
+        self.pending.remove(peer);
+        self.sessions.remove(peer);
+    }
+
+    /// Iniciar handshake con un peer
+    pub fn initiate(&mut self, peer: PeerId) -> Result<SecureFrame> {
+        let mut state = self.builder()?.build_initiator()?;
+        let mut buffer = [0u8; MAX_HANDSHAKE_LEN];
+        let len = state.write_message(&[], &mut buffer)?;
+
+        self.sessions.remove(&peer);
+        self.pending.insert(peer, PendingHandshake { state, initiator: true, next_stage: 2 });
+        debug!("Handshake iniciado con {}", peer);
+        Ok(SecureFrame::Handshake { stage: 1, payload: buffer[..len].to_vec() })
+    }
+
+    /// Procesar mensaje de handshake; devuelve la respuesta si corresponde
+    pub fn handle_handshake(&mut self, peer: PeerId, stage: u8, payload: &[u8]) -> Result<Option<SecureFrame>> {
+        let result = self.advance_handshake(peer, stage, payload);
+        if result.is_err() {
+            // Un handshake fallido no deja estado a medias
+            self.pending.remove(&peer);
+        }
+        result
+    }
+
+    fn advance_handshake(&mut self, peer: PeerId, stage: u8, payload: &[u8]) -> Result<Option<SecureFrame>> {
+        let mut buffer = [0u8; MAX_HANDSHAKE_LEN];
+
+        if stage == 1 {
+            // Nuevo handshake como respondedor; reemplaza cualquier sesión previa
+            let mut state = self.builder()?.build_responder()?;
+            state.read_message(payload, &mut buffer)?;
+            let binding = self.encode_binding()?;
+            let len = state.write_message(&binding, &mut buffer)?;
+            self.sessions.remove(&peer);
+            self.pending.insert(peer, PendingHandshake { state, initiator: false, next_stage: 3 });
+            return Ok(Some(SecureFrame::Handshake { stage: 2, payload: buffer[..len].to_vec() }));
+        }
+
+        let mut pending = self.pending.remove(&peer)
+            .ok_or_else(|| anyhow!("Handshake inesperado de {}", peer))?;
+        if pending.next_stage != stage {
+            return Err(anyhow!("Etapa de handshake {} inesperada de {}", stage, peer));
+        }
+
+        let len = pending.state.read_message(payload, &mut buffer)?;
+        let remote_binding = &buffer[..len];
+        let remote_static = self.verify_remote_static(peer, &pending.state)?;
+        let remote_wallet = self.verify_binding(remote_binding, &remote_static)?;
+
+        let reply = if pending.initiator {
+            // Etapa 2 recibida: enviar etapa 3 con nuestra vinculación
+            let binding = self.encode_binding()?;
+            let len = pending.state.write_message(&binding, &mut buffer)?;
+            Some(SecureFrame::Handshake { stage: 3, payload: buffer[..len].to_vec() })
+        } else {
+            None
+        };
+
+        if !pending.state.is_handshake_finished() {
+            return Err(anyhow!("Handshake con {} incompleto", peer));
+        }
+
+        let (initiator_key, responder_key) = pending.state.dangerously_get_raw_split();
+        let (send_key, recv_key) = if pending.initiator {
+            (initiator_key, responder_key)
+        } else {
+            (responder_key, initiator_key)
+        };
+
+        self.sessions.insert(peer, PeerSession {
+            send: ChaCha20Poly1305::new(Key::from_slice(&send_key)),
+            recv: ChaCha20Poly1305::new(Key::from_slice(&recv_key)),
+            next_nonce: 0,
+            replay: ReplayWindow::default(),
+            remote_static,
+            remote_wallet,
+        });
+        info!("Sesión segura establecida con {}", peer);
+
+        Ok(reply)
+    }
+
+    /// Cifrar un mensaje para un peer
+    pub fn seal(&mut self, peer: &PeerId, channel: SecureChannel, plaintext: &[u8]) -> Result<SecureFrame> {
+        let session = self.sessions.get_mut(peer)
+            .ok_or_else(|| anyhow!("Sin sesión segura con {}", peer))?;
+
+        let nonce = session.next_nonce;
+        session.next_nonce = session.next_nonce.checked_add(1)
+            .ok_or_else(|| anyhow!("Nonces agotados con {}; se requiere nuevo handshake", peer))?;
+
+        let ciphertext = session.send
+            .encrypt(&nonce_bytes(nonce), Payload { msg: plaintext, aad: &channel_aad(channel) })
+            .map_err(|_| anyhow!("Error cifrando mensaje para {}", peer))?;
+
+        Ok(SecureFrame::Sealed { channel, nonce, ciphertext })
+    }
+
+    /// Descifrar un mensaje de un peer, descartando manipulaciones y repeticiones
+    pub fn open(&mut self, peer: &PeerId, channel: SecureChannel, nonce: u64, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let session = self.sessions.get_mut(peer)
+            .ok_or_else(|| anyhow!("Sin sesión segura con {}", peer))?;
+
+        if !session.replay.check(nonce) {
+            return Err(anyhow!("Paquete repetido o demasiado antiguo de {} (nonce {})", peer, nonce));
+        }
+
+        let plaintext = session.recv
+            .decrypt(&nonce_bytes(nonce), Payload { msg: ciphertext, aad: &channel_aad(channel) })
+            .map_err(|_| anyhow!("Autenticación fallida del mensaje de {}", peer))?;
+
+        // La ventana solo avanza con tramas auténticas
+        session.replay.accept(nonce);
+        Ok(plaintext)
+    }
+
+    /// Constructor Noise con la clave estática local
+    fn builder(&self) -> Result<snow::Builder<'_>> {
+        Ok(snow::Builder::new(NOISE_PATTERN.parse()?).local_private_key(&self.static_secret))
+    }
+
+    /// Codificar la vinculación local para el payload del handshake
+    fn encode_binding(&self) -> Result<Vec<u8>> {
+        match &self.binding {
+            Some(binding) => Ok(bincode::serialize(binding)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Comprobar la clave estática remota contra la fijada
+    fn verify_remote_static(&self, peer: PeerId, state: &snow::HandshakeState) -> Result<[u8; 32]> {
+        let remote: [u8; 32] = state.get_remote_static()
+            .ok_or_else(|| anyhow!("El peer {} no envió clave estática", peer))?
+            .try_into()
+            .map_err(|_| anyhow!("Clave estática inválida de {}", peer))?;
+
+        if let Some(expected) = self.pinned.get(&peer) {
+            if *expected != remote {
+                warn!("Clave estática de {} no coincide con la fijada", peer);
+                return Err(anyhow!("Clave estática de {} no coincide", peer));
+            }
+        }
+        Ok(remote)
+    }
+
+    /// Verificar la vinculación a wallet enviada por el peer
+    fn verify_binding(&self, payload: &[u8], remote_static: &[u8; 32]) -> Result<Option<String>> {
+        if payload.is_empty() {
+            return Ok(None);
+        }
+
+        let binding: WalletBinding = bincode::deserialize(payload)?;
+        let signature: [u8; 65] = binding.signature.as_slice().try_into()
+            .map_err(|_| anyhow!("Firma de vinculación con longitud inválida"))?;
+        let signer = wallet::recover_message_signer(remote_static, &signature)?;
+        if !signer.eq_ignore_ascii_case(&binding.address) {
+            return Err(anyhow!("La vinculación a {} no está firmada por ese wallet", binding.address));
+        }
+        Ok(Some(signer))
+    }
+}
+
+impl Drop for PeerSecurity {
+    fn drop(&mut self) {
+        self.static_secret.zeroize();
+    }
+}
+
+/// Nonce de 96 bits con el contador en los últimos 8 bytes
+fn nonce_bytes(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_le_bytes());
+    *Nonce::from_slice(&nonce)
+}
+
+/// Dato asociado que separa los canales de control y datos
+fn channel_aad(channel: SecureChannel) -> [u8; 32] {
+    let label: &[u8] = match channel {
+        SecureChannel::Control => b"metaverso/secure/control",
+        SecureChannel::Data => b"metaverso/secure/data",
+    };
+    Sha256::digest(label).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Entregar una trama de handshake y devolver la respuesta
+    fn deliver(to: &mut PeerSecurity, from: PeerId, frame: SecureFrame) -> Result<Option<SecureFrame>> {
+        match frame {
+            SecureFrame::Handshake { stage, payload } => to.handle_handshake(from, stage, &payload),
+            SecureFrame::Sealed { .. } => Err(anyhow!("Se esperaba un handshake")),
+        }
+    }
+
+    /// Handshake XX completo de `a` (iniciador) con `b`
+    fn handshake(a: &mut PeerSecurity, a_id: PeerId, b: &mut PeerSecurity, b_id: PeerId) -> Result<()> {
+        let first = a.initiate(b_id)?;
+        let second = deliver(b, a_id, first)?.ok_or_else(|| anyhow!("Sin etapa 2"))?;
+        let third = deliver(a, b_id, second)?.ok_or_else(|| anyhow!("Sin etapa 3"))?;
+        assert!(deliver(b, a_id, third)?.is_none());
+        Ok(())
+    }
+
+    fn sealed(frame: SecureFrame) -> (SecureChannel, u64, Vec<u8>) {
+        match frame {
+            SecureFrame::Sealed { channel, nonce, ciphertext } => (channel, nonce, ciphertext),
+            SecureFrame::Handshake { .. } => panic!("Se esperaba una trama cifrada"),
+        }
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let (a_id, b_id) = (PeerId::random(), PeerId::random());
+        let mut a = PeerSecurity::new([1; 32]);
+        let mut b = PeerSecurity::new([2; 32]);
+        handshake(&mut a, a_id, &mut b, b_id).unwrap();
+
+        let (channel, nonce, ciphertext) = sealed(a.seal(&b_id, SecureChannel::Data, b"posicion").unwrap());
+        for index in [0, ciphertext.len() - 1] {
+            let mut tampered = ciphertext.clone();
+            tampered[index] ^= 1;
+            assert!(b.open(&a_id, channel, nonce, &tampered).is_err(), "byte {} alterado", index);
+        }
+        // El canal entra como dato asociado
+        assert!(b.open(&a_id, SecureChannel::Control, nonce, &ciphertext).is_err());
+
+        // Las tramas rechazadas no consumen el nonce
+        assert_eq!(b.open(&a_id, channel, nonce, &ciphertext).unwrap(), b"posicion");
+    }
+
+    #[test]
+    fn replayed_nonce_is_dropped() {
+        let (a_id, b_id) = (PeerId::random(), PeerId::random());
+        let mut a = PeerSecurity::new([1; 32]);
+        let mut b = PeerSecurity::new([2; 32]);
+        handshake(&mut a, a_id, &mut b, b_id).unwrap();
+
+        let frames: Vec<_> = (0..3)
+            .map(|i| sealed(a.seal(&b_id, SecureChannel::Data, &[i]).unwrap()))
+            .collect();
+        // Fuera de orden dentro de la ventana se aceptan una sola vez
+        for &index in &[2, 0, 1] {
+            let (channel, nonce, ciphertext) = &frames[index];
+            assert_eq!(b.open(&a_id, *channel, *nonce, ciphertext).unwrap(), [index as u8]);
+        }
+        for (channel, nonce, ciphertext) in &frames {
+            assert!(b.open(&a_id, *channel, *nonce, ciphertext).is_err(), "nonce {} repetido", nonce);
+        }
+
+        let mut window = ReplayWindow::default();
+        window.accept(100);
+        assert!(!window.check(100));
+        assert!(window.check(99));
+        assert!(!window.check(100 - REPLAY_WINDOW));
+    }
+
+    #[test]
+    fn mismatched_static_key_fails_the_handshake_cleanly() {
+        let (a_id, b_id) = (PeerId::random(), PeerId::random());
+        let mut a = PeerSecurity::new([1; 32]);
+        let mut b = PeerSecurity::new([2; 32]);
+        a.pin_peer(b_id, PeerSecurity::new([3; 32]).static_public());
+
+        assert!(handshake(&mut a, a_id, &mut b, b_id).is_err());
+        assert!(!a.is_established(&b_id));
+        assert!(!a.is_pending(&b_id));
+        assert!(a.seal(&b_id, SecureChannel::Data, b"x").is_err());
+
+        // Con la clave correcta fijada el handshake se repite sin restos
+        a.pin_peer(b_id, b.static_public());
+        handshake(&mut a, a_id, &mut b, b_id).unwrap();
+        assert_eq!(a.remote_static(&b_id), Some(b.static_public()));
+        assert_eq!(b.remote_static(&a_id), Some(a.static_public()));
+    }
+}
@@ -0,0 +1,615 @@
+//! # Transferencia de Assets
+//!
+//! Transferencia por trozos de contenidos grandes (modelos GLB, texturas) sobre
+//! el canal fiable punto a punto. Cada asset se identifica por su hash SHA-256:
+//! el proveedor envía un manifiesto y una ventana de trozos, el receptor confirma
+//! rangos recibidos y, tras una desconexión o pérdida, reanuda la transferencia
+//! pidiendo solo los trozos que le faltan.
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use libp2p::core::PeerId;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+/// Configuración de transferencia
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferConfig {
+    /// Tamaño de trozo en bytes
+    pub chunk_size: u32,
+    /// Trozos enviados sin confirmar por transferencia
+    pub window: u32,
+    /// Trozos recibidos entre confirmaciones
+    pub ack_interval: u32,
+    /// Tiempo (ms) sin recibir trozos antes de reanudar
+    pub stall_timeout: u64,
+}
+
+impl Default for TransferConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 64 * 1024,
+            window: 16,
+            ack_interval: 8,
+            stall_timeout: 2000,
+        }
+    }
+}
+
+/// Manifiesto de un asset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetManifest {
+    /// Hash SHA-256 (hex)
+    pub hash: String,
+    /// Tamaño total
+    pub total_size: u64,
+    /// Tamaño de trozo
+    pub chunk_size: u32,
+}
+
+impl AssetManifest {
+    /// Número de trozos
+    pub fn chunk_count(&self) -> u32 {
+        ((self.total_size + self.chunk_size as u64 - 1) / self.chunk_size as u64) as u32
+    }
+}
+
+/// Rango de trozos `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRange {
+    /// Primer trozo
+    pub start: u32,
+    /// Trozo siguiente al último
+    pub end: u32,
+}
+
+/// Mensaje del protocolo de transferencia
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TransferMessage {
+    /// Solicitud de un asset indicando los trozos ya recibidos
+    Request { hash: String, have: Vec<ChunkRange> },
+    /// Manifiesto del asset
+    Manifest(AssetManifest),
+    /// Trozo de datos
+    Chunk { hash: String, index: u32, data: Vec<u8> },
+    /// Confirmación de rangos recibidos
+    Ack { hash: String, ranges: Vec<ChunkRange> },
+    /// El peer no tiene el asset
+    NotFound { hash: String },
+}
+
+/// Progreso de una transferencia
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferProgress {
+    /// Hash del asset
+    pub hash: String,
+    /// Bytes recibidos
+    pub received_bytes: u64,
+    /// Bytes totales (0 hasta recibir el manifiesto)
+    pub total_bytes: u64,
+    /// Estado
+    pub status: TransferStatus,
+}
+
+/// Estado de una transferencia
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferStatus {
+    /// Buscando proveedor
+    Requested,
+    /// Recibiendo trozos
+    Receiving,
+    /// Sin proveedor; se reanudará al reconectar
+    Stalled,
+    /// Completada y verificada
+    Completed,
+    /// El hash no coincide; se reinicia la descarga
+    Corrupted,
+}
+
+/// Transferencia saliente hacia un peer
+struct OutgoingTransfer {
+    /// Trozos confirmados
+    acked: Vec<bool>,
+    /// Trozos enviados sin confirmar
+    in_flight: Vec<u32>,
+}
+
+/// Transferencia entrante
+struct IncomingTransfer {
+    /// Manifiesto
+    manifest: Option<AssetManifest>,
+    /// Proveedor actual
+    source: Option<PeerId>,
+    /// Trozos recibidos
+    chunks: Vec<Option<Vec<u8>>>,
+    /// Bytes recibidos
+    received_bytes: u64,
+    /// Trozos recibidos desde la última confirmación
+    since_ack: u32,
+    /// Última actividad
+    last_activity: Instant,
+    /// Suscriptores de progreso
+    subscribers: Vec<UnboundedSender<TransferProgress>>,
+}
+
+impl IncomingTransfer {
+    /// Rangos de trozos ya recibidos
+    fn have_ranges(&self) -> Vec<ChunkRange> {
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            match (chunk.is_some(), start) {
+                (true, None) => start = Some(index as u32),
+                (false, Some(s)) => {
+                    ranges.push(ChunkRange { start: s, end: index as u32 });
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(s) = start {
+            ranges.push(ChunkRange { start: s, end: self.chunks.len() as u32 });
+        }
+        ranges
+    }
+
+    /// Notificar progreso a los suscriptores
+    fn notify(&mut self, hash: &str, status: TransferStatus) {
+        let progress = TransferProgress {
+            hash: hash.to_string(),
+            received_bytes: self.received_bytes,
+            total_bytes: self.manifest.as_ref().map(|m| m.total_size).unwrap_or(0),
+            status,
+        };
+        self.subscribers.retain(|subscriber| subscriber.unbounded_send(progress.clone()).is_ok());
+    }
+}
+
+/// Gestor de transferencias de assets
+pub struct TransferManager {
+    /// Configuración
+    config: TransferConfig,
+    /// Assets ofrecidos por el peer local
+    provided: HashMap<String, Arc<Vec<u8>>>,
+    /// Transferencias salientes por peer y hash
+    outgoing: HashMap<(PeerId, String), OutgoingTransfer>,
+    /// Transferencias entrantes por hash
+    incoming: HashMap<String, IncomingTransfer>,
+    /// Assets descargados y verificados
+    completed: HashMap<String, Arc<Vec<u8>>>,
+    /// Mensajes salientes
+    outbox: Vec<(PeerId, TransferMessage)>,
+}
+
+/// Hash SHA-256 en hex de un contenido
+pub fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl TransferManager {
+    /// Crear gestor de transferencias
+    pub fn new(config: TransferConfig) -> Self {
+        Self {
+            config,
+            provided: HashMap::new(),
+            outgoing: HashMap::new(),
+            incoming: HashMap::new(),
+            completed: HashMap::new(),
+            outbox: Vec::new(),
+        }
+    }
+
+    /// Ofrecer un asset a otros peers; devuelve su hash
+    pub fn provide(&mut self, data: Vec<u8>) -> String {
+        let hash = content_hash(&data);
+        self.provided.insert(hash.clone(), Arc::new(data));
+        debug!("Asset ofrecido: {}", hash);
+        hash
+    }
+
+    /// Dejar de ofrecer un asset
+    pub fn unprovide(&mut self, hash: &str) {
+        self.provided.remove(hash);
+        self.outgoing.retain(|(_, h), _| h != hash);
+    }
+
+    /// Verificar si el peer local ofrece un asset
+    pub fn is_provided(&self, hash: &str) -> bool {
+        self.provided.contains_key(hash)
+    }
+
+    /// Solicitar un asset a los peers candidatos; el progreso se publica en el stream
+    pub fn request(&mut self, hash: &str, candidates: &[PeerId]) -> UnboundedReceiver<TransferProgress> {
+        let (sender, receiver) = unbounded();
+
+        if let Some(data) = self.completed.get(hash) {
+            let size = data.len() as u64;
+            let _ = sender.unbounded_send(TransferProgress {
+                hash: hash.to_string(),
+                received_bytes: size,
+                total_bytes: size,
+                status: TransferStatus::Completed,
+            });
+            return receiver;
+        }
+
+        let transfer = self.incoming.entry(hash.to_string()).or_insert_with(|| IncomingTransfer {
+            manifest: None,
+            source: None,
+            chunks: Vec::new(),
+            received_bytes: 0,
+            since_ack: 0,
+            last_activity: Instant::now(),
+            subscribers: Vec::new(),
+        });
+        transfer.subscribers.push(sender);
+
+        if transfer.source.is_none() {
+            let have = transfer.have_ranges();
+            for peer in candidates {
+                self.outbox.push((*peer, TransferMessage::Request { hash: hash.to_string(), have: have.clone() }));
+            }
+            transfer.last_activity = Instant::now();
+            transfer.notify(hash, TransferStatus::Requested);
+        }
+
+        receiver
+    }
+
+    /// Procesar mensaje recibido
+    pub fn handle_message(&mut self, peer: PeerId, message: TransferMessage) {
+        match message {
+            TransferMessage::Request { hash, have } => self.handle_request(peer, hash, have),
+            TransferMessage::Ack { hash, ranges } => self.handle_ack(peer, hash, ranges),
+            TransferMessage::Manifest(manifest) => self.handle_manifest(peer, manifest),
+            TransferMessage::Chunk { hash, index, data } => self.handle_chunk(peer, hash, index, data),
+            TransferMessage::NotFound { hash } => {
+                debug!("Peer {} no tiene el asset {}", peer, hash);
+            }
+        }
+    }
+
+    /// Atender solicitud de un asset propio
+    fn handle_request(&mut self, peer: PeerId, hash: String, have: Vec<ChunkRange>) {
+        let data = match self.provided.get(&hash) {
+            Some(data) => data.clone(),
+            None => {
+                self.outbox.push((peer, TransferMessage::NotFound { hash }));
+                return;
+            }
+        };
+
+        let manifest = AssetManifest {
+            hash: hash.clone(),
+            total_size: data.len() as u64,
+            chunk_size: self.config.chunk_size,
+        };
+        let mut acked = vec![false; manifest.chunk_count() as usize];
+        mark_ranges(&mut acked, &have);
+
+        self.outbox.push((peer, TransferMessage::Manifest(manifest)));
+        self.outgoing.insert((peer, hash.clone()), OutgoingTransfer { acked, in_flight: Vec::new() });
+        self.fill_window(peer, &hash);
+    }
+
+    /// Procesar confirmación de rangos
+    fn handle_ack(&mut self, peer: PeerId, hash: String, ranges: Vec<ChunkRange>) {
+        let finished = match self.outgoing.get_mut(&(peer, hash.clone())) {
+            Some(transfer) => {
+                mark_ranges(&mut transfer.acked, &ranges);
+                let acked = &transfer.acked;
+                transfer.in_flight.retain(|index| !acked[*index as usize]);
+                transfer.acked.iter().all(|a| *a)
+            }
+            None => return,
+        };
+
+        if finished {
+            self.outgoing.remove(&(peer, hash));
+        } else {
+            self.fill_window(peer, &hash);
+        }
+    }
+
+    /// Enviar trozos hasta llenar la ventana
+    fn fill_window(&mut self, peer: PeerId, hash: &str) {
+        let data = match self.provided.get(hash) {
+            Some(data) => data.clone(),
+            None => return,
+        };
+        let transfer = match self.outgoing.get_mut(&(peer, hash.to_string())) {
+            Some(transfer) => transfer,
+            None => return,
+        };
+
+        let chunk_size = self.config.chunk_size as usize;
+        for index in 0..transfer.acked.len() as u32 {
+            if transfer.in_flight.len() as u32 >= self.config.window {
+                break;
+            }
+            if transfer.acked[index as usize] || transfer.in_flight.contains(&index) {
+                continue;
+            }
+
+            let start = index as usize * chunk_size;
+            let end = (start + chunk_size).min(data.len());
+            transfer.in_flight.push(index);
+            self.outbox.push((peer, TransferMessage::Chunk {
+                hash: hash.to_string(),
+                index,
+                data: data[start..end].to_vec(),
+            }));
+        }
+    }
+
+    /// Procesar manifiesto
+    fn handle_manifest(&mut self, peer: PeerId, manifest: AssetManifest) {
+        let transfer = match self.incoming.get_mut(&manifest.hash) {
+            Some(transfer) => transfer,
+            None => return,
+        };
+
+        // Solo se acepta un proveedor a la vez
+        if transfer.source.map_or(false, |source| source != peer) {
+            return;
+        }
+
+        match &transfer.manifest {
+            Some(existing) if *existing != manifest => {
+                warn!("Manifiesto incompatible para {}; se reinicia la descarga", manifest.hash);
+                transfer.chunks = vec![None; manifest.chunk_count() as usize];
+                transfer.received_bytes = 0;
+            }
+            Some(_) => {}
+            None => transfer.chunks = vec![None; manifest.chunk_count() as usize],
+        }
+
+        transfer.source = Some(peer);
+        transfer.last_activity = Instant::now();
+        let hash = manifest.hash.clone();
+        transfer.manifest = Some(manifest);
+        transfer.notify(&hash, TransferStatus::Receiving);
+    }
+
+    /// Procesar trozo recibido
+    fn handle_chunk(&mut self, peer: PeerId, hash: String, index: u32, data: Vec<u8>) {
+        let transfer = match self.incoming.get_mut(&hash) {
+            Some(transfer) => transfer,
+            None => return,
+        };
+        if transfer.source != Some(peer) {
+            return;
+        }
+        let slot = match transfer.chunks.get_mut(index as usize) {
+            Some(slot) => slot,
+            None => return,
+        };
+        if slot.is_some() {
+            return;
+        }
+
+        transfer.received_bytes += data.len() as u64;
+        *slot = Some(data);
+        transfer.since_ack += 1;
+        transfer.last_activity = Instant::now();
+
+        let complete = transfer.chunks.iter().all(|chunk| chunk.is_some());
+        if complete || transfer.since_ack >= self.config.ack_interval {
+            transfer.since_ack = 0;
+            self.outbox.push((peer, TransferMessage::Ack { hash: hash.clone(), ranges: transfer.have_ranges() }));
+        }
+
+        if complete {
+            self.finish(&hash);
+        } else {
+            transfer.notify(&hash, TransferStatus::Receiving);
+        }
+    }
+
+    /// Ensamblar y verificar un asset completo
+    fn finish(&mut self, hash: &str) {
+        let mut transfer = match self.incoming.remove(hash) {
+            Some(transfer) => transfer,
+            None => return,
+        };
+
+        let data: Vec<u8> = transfer.chunks.iter().flatten().flatten().copied().collect();
+        if content_hash(&data) == hash {
+            info!("Asset {} recibido ({} bytes)", hash, data.len());
+            transfer.notify(hash, TransferStatus::Completed);
+            self.completed.insert(hash.to_string(), Arc::new(data));
+        } else {
+            warn!("Hash incorrecto para el asset {}; se descarta", hash);
+            transfer.notify(hash, TransferStatus::Corrupted);
+            transfer.chunks.iter_mut().for_each(|chunk| *chunk = None);
+            transfer.received_bytes = 0;
+            transfer.source = None;
+            self.incoming.insert(hash.to_string(), transfer);
+        }
+    }
+
+    /// Marcar como detenidas las transferencias servidas por un peer desconectado
+    pub fn handle_peer_disconnected(&mut self, peer: &PeerId) {
+        self.outgoing.retain(|(p, _), _| p != peer);
+        for (hash, transfer) in self.incoming.iter_mut() {
+            if transfer.source.as_ref() == Some(peer) {
+                transfer.source = None;
+                transfer.notify(hash, TransferStatus::Stalled);
+            }
+        }
+    }
+
+    /// Reanudar transferencias detenidas o sin actividad pidiendo los trozos que faltan
+    pub fn resume_stalled(&mut self, candidates: &[PeerId], now: Instant) {
+        let timeout = Duration::from_millis(self.config.stall_timeout);
+        for (hash, transfer) in self.incoming.iter_mut() {
+            if now.duration_since(transfer.last_activity) < timeout {
+                continue;
+            }
+
+            // Si el proveedor sigue conectado se le vuelve a pedir; si no, a todos
+            let targets: Vec<PeerId> = match transfer.source {
+                Some(source) if candidates.contains(&source) => vec![source],
+                _ => {
+                    transfer.source = None;
+                    candidates.to_vec()
+                }
+            };
+            if targets.is_empty() {
+                continue;
+            }
+
+            let have = transfer.have_ranges();
+            for peer in targets {
+                self.outbox.push((peer, TransferMessage::Request { hash: hash.clone(), have: have.clone() }));
+            }
+            transfer.last_activity = now;
+            debug!("Reanudando transferencia de {} ({} bytes recibidos)", hash, transfer.received_bytes);
+        }
+    }
+
+    /// Obtener un asset descargado
+    pub fn get_completed(&self, hash: &str) -> Option<Arc<Vec<u8>>> {
+        self.completed.get(hash).cloned()
+    }
+
+    /// Extraer mensajes salientes
+    pub fn drain_outbox(&mut self) -> Vec<(PeerId, TransferMessage)> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Número de transferencias entrantes en curso
+    pub fn active_downloads(&self) -> usize {
+        self.incoming.len()
+    }
+
+    /// Número de transferencias salientes en curso
+    pub fn active_uploads(&self) -> usize {
+        self.outgoing.len()
+    }
+}
+
+/// Marcar los trozos cubiertos por los rangos
+fn mark_ranges(flags: &mut [bool], ranges: &[ChunkRange]) {
+    for range in ranges {
+        let end = (range.end as usize).min(flags.len());
+        for flag in flags.iter_mut().take(end).skip(range.start as usize) {
+            *flag = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{FutureExt, StreamExt};
+
+    /// Transporte con pérdidas: descarta uno de cada `drop_every` mensajes
+    struct LossyTransport {
+        drop_every: usize,
+        sent: usize,
+    }
+
+    impl LossyTransport {
+        fn delivers(&mut self) -> bool {
+            self.sent += 1;
+            self.sent % self.drop_every != 0
+        }
+    }
+
+    /// Asset de prueba de `size` bytes con contenido no periódico
+    fn blob(size: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_u32;
+        (0..size)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lossy_transfer_resumes_after_interruption_with_matching_hash() {
+        const SIZE: usize = 5 * 1024 * 1024;
+        let data = blob(SIZE);
+        let (provider_id, receiver_id) = (PeerId::random(), PeerId::random());
+        let mut provider = TransferManager::new(TransferConfig::default());
+        let mut receiver = TransferManager::new(TransferConfig::default());
+        let hash = provider.provide(data.clone());
+
+        let mut transport = LossyTransport { drop_every: 7, sent: 0 };
+        let mut progress = receiver.request(&hash, &[provider_id]);
+        let mut clock = Instant::now();
+        let (mut received_bytes, mut stalled) = (0, false);
+        let mut resumed_with: Option<Vec<ChunkRange>> = None;
+        let mut resent_chunks = Vec::new();
+
+        for _ in 0..10_000 {
+            if receiver.get_completed(&hash).is_some() {
+                break;
+            }
+
+            let mut idle = true;
+            for (_, message) in receiver.drain_outbox() {
+                idle = false;
+                match &message {
+                    TransferMessage::Request { have, .. } if stalled && resumed_with.is_none() => {
+                        resumed_with = Some(have.clone());
+                    }
+                    _ => {}
+                }
+                if transport.delivers() {
+                    provider.handle_message(receiver_id, message);
+                }
+            }
+            for (_, message) in provider.drain_outbox() {
+                idle = false;
+                match &message {
+                    TransferMessage::Chunk { index, .. } if resumed_with.is_some() => resent_chunks.push(*index),
+                    _ => {}
+                }
+                if transport.delivers() {
+                    receiver.handle_message(provider_id, message);
+                }
+            }
+            while let Some(Some(update)) = progress.next().now_or_never() {
+                received_bytes = update.received_bytes;
+                stalled |= update.status == TransferStatus::Stalled;
+            }
+
+            // Corte al 60%: lo que estaba en vuelo se pierde y ambos lados
+            // ven la desconexión
+            if !stalled && received_bytes >= SIZE as u64 * 6 / 10 {
+                receiver.drain_outbox();
+                provider.drain_outbox();
+                receiver.handle_peer_disconnected(&provider_id);
+                provider.handle_peer_disconnected(&receiver_id);
+                assert_eq!(provider.active_uploads(), 0);
+                continue;
+            }
+
+            // Las pérdidas paran la ventana; el receptor reanuda tras el timeout
+            if idle {
+                clock += Duration::from_millis(TransferConfig::default().stall_timeout + 1);
+                receiver.resume_stalled(&[provider_id], clock);
+            }
+        }
+
+        assert!(stalled, "la transferencia no llegó a interrumpirse");
+        let completed = receiver.get_completed(&hash).expect("la transferencia no terminó");
+        assert_eq!(content_hash(&completed), content_hash(&data));
+        assert_eq!(completed.len(), SIZE);
+
+        // Al reanudar solo se piden los trozos que faltaban
+        let have = resumed_with.expect("no se reanudó la transferencia");
+        let chunk_size = TransferConfig::default().chunk_size as u64;
+        let kept: u32 = have.iter().map(|range| range.end - range.start).sum();
+        assert!(kept as u64 * chunk_size >= SIZE as u64 / 2);
+        assert!(resent_chunks.iter().all(|index| !have.iter().any(|range| (range.start..range.end).contains(index))));
+    }
+}
@@ -0,0 +1,425 @@
+//! # Autoridad de Física en Red
+//!
+//! Traspaso de autoridad de simulación para objetos interactivos.
+//! Un peer puede reclamar los cuerpos dinámicos cercanos a su avatar para
+//! simularlos localmente y replicar su estado; tras un tiempo inactivo la
+//! autoridad vuelve al servidor. Las reclamaciones en conflicto también
+//! devuelven la autoridad al servidor.
+
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use tracing::{debug, info};
+use glam::{Vec3, Quat};
+
+/// Identificador de peer usado por el servidor de autoridad
+pub const SERVER_PEER: &str = "server";
+
+/// Autoridad de simulación de un cuerpo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyAuthority {
+    /// Simulado por el servidor
+    Server,
+    /// Simulado por el peer local
+    Local,
+    /// Simulado por otro peer
+    Remote(String),
+}
+
+impl Default for BodyAuthority {
+    fn default() -> Self {
+        BodyAuthority::Server
+    }
+}
+
+/// Configuración de autoridad
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorityConfig {
+    /// Habilitado
+    pub enabled: bool,
+    /// Radio alrededor del avatar para reclamar cuerpos
+    pub claim_radius: f32,
+    /// Tiempo inactivo (s) antes de devolver la autoridad al servidor
+    pub idle_timeout: f32,
+    /// Velocidad lineal bajo la cual un cuerpo se considera inactivo
+    pub idle_velocity_threshold: f32,
+    /// El peer local actúa como servidor de autoridad
+    pub is_server: bool,
+}
+
+impl Default for AuthorityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            claim_radius: 3.0,
+            idle_timeout: 2.0,
+            idle_velocity_threshold: 0.05,
+            is_server: false,
+        }
+    }
+}
+
+/// Estado replicado de un cuerpo
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BodySnapshot {
+    /// Posición
+    pub position: Vec3,
+    /// Rotación
+    pub rotation: Quat,
+    /// Velocidad lineal
+    pub linear_velocity: Vec3,
+    /// Velocidad angular
+    pub angular_velocity: Vec3,
+}
+
+/// Mensaje del protocolo de autoridad
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AuthorityMessage {
+    /// Reclamación de autoridad sobre la versión siguiente
+    Claim { body_id: String, peer: String, version: u64 },
+    /// Estado simulado por la autoridad actual
+    State { body_id: String, peer: String, version: u64, snapshot: BodySnapshot },
+    /// Devolución de la autoridad al servidor
+    Release { body_id: String, peer: String, version: u64 },
+}
+
+/// Estado de autoridad de un cuerpo
+#[derive(Debug, Clone)]
+pub struct AuthorityEntry {
+    /// Autoridad actual
+    pub authority: BodyAuthority,
+    /// Versión de autoridad
+    pub version: u64,
+    /// Tiempo inactivo acumulado
+    pub idle_time: f32,
+    /// Último estado recibido de la autoridad remota
+    pub remote_snapshot: Option<BodySnapshot>,
+}
+
+/// Gestor de autoridad de física
+pub struct AuthorityManager {
+    /// Configuración
+    config: AuthorityConfig,
+    /// Peer local
+    local_peer: String,
+    /// Autoridad por cuerpo
+    entries: HashMap<String, AuthorityEntry>,
+    /// Mensajes salientes
+    outbox: Vec<AuthorityMessage>,
+}
+
+impl AuthorityManager {
+    /// Crear nuevo gestor de autoridad
+    pub fn new(config: AuthorityConfig) -> Self {
+        let local_peer = if config.is_server { SERVER_PEER.to_string() } else { String::new() };
+        Self {
+            config,
+            local_peer,
+            entries: HashMap::new(),
+            outbox: Vec::new(),
+        }
+    }
+
+    /// Establecer peer local
+    pub fn set_local_peer(&mut self, peer: String) {
+        if !self.config.is_server {
+            self.local_peer = peer;
+        }
+    }
+
+    /// Registrar cuerpo bajo autoridad del servidor
+    pub fn register_body(&mut self, body_id: &str) {
+        self.entries.entry(body_id.to_string()).or_insert(AuthorityEntry {
+            authority: BodyAuthority::Server,
+            version: 0,
+            idle_time: 0.0,
+            remote_snapshot: None,
+        });
+    }
+
+    /// Eliminar cuerpo
+    pub fn unregister_body(&mut self, body_id: &str) {
+        self.entries.remove(body_id);
+    }
+
+    /// Obtener autoridad de un cuerpo
+    pub fn authority(&self, body_id: &str) -> BodyAuthority {
+        self.entries.get(body_id)
+            .map(|entry| entry.authority.clone())
+            .unwrap_or_default()
+    }
+
+    /// Verificar si el peer local simula el cuerpo
+    pub fn simulates_locally(&self, body_id: &str) -> bool {
+        match self.authority(body_id) {
+            BodyAuthority::Local => true,
+            BodyAuthority::Server => self.config.is_server,
+            BodyAuthority::Remote(_) => false,
+        }
+    }
+
+    /// Último estado recibido para un cuerpo no autoritativo
+    pub fn remote_snapshot(&self, body_id: &str) -> Option<BodySnapshot> {
+        self.entries.get(body_id).and_then(|entry| entry.remote_snapshot)
+    }
+
+    /// Reclamar un cuerpo si está bajo el radio del avatar y en manos del servidor
+    pub fn try_claim(&mut self, body_id: &str, avatar_position: Vec3, body_position: Vec3) -> bool {
+        if !self.config.enabled || self.config.is_server {
+            return false;
+        }
+        if avatar_position.distance(body_position) > self.config.claim_radius {
+            return false;
+        }
+
+        let entry = match self.entries.get_mut(body_id) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        match entry.authority {
+            BodyAuthority::Local => {
+                // Seguir interactuando reinicia el temporizador
+                entry.idle_time = 0.0;
+                true
+            }
+            BodyAuthority::Remote(_) => false,
+            BodyAuthority::Server => {
+                entry.version += 1;
+                entry.authority = BodyAuthority::Local;
+                entry.idle_time = 0.0;
+                entry.remote_snapshot = None;
+                self.outbox.push(AuthorityMessage::Claim {
+                    body_id: body_id.to_string(),
+                    peer: self.local_peer.clone(),
+                    version: entry.version,
+                });
+                debug!("Cuerpo {} reclamado (versión {})", body_id, entry.version);
+                true
+            }
+        }
+    }
+
+    /// Procesar mensaje recibido
+    pub fn handle_message(&mut self, message: AuthorityMessage) {
+        match message {
+            AuthorityMessage::Claim { body_id, peer, version } => {
+                if peer == self.local_peer {
+                    return;
+                }
+                let entry = match self.entries.get_mut(&body_id) {
+                    Some(entry) => entry,
+                    None => return,
+                };
+
+                if version < entry.version {
+                    return;
+                }
+
+                let conflict = version == entry.version && entry.authority != BodyAuthority::Server;
+                if conflict {
+                    // Dos reclamaciones sobre la misma versión: vuelve al servidor
+                    info!("Reclamaciones en conflicto sobre {}; autoridad devuelta al servidor", body_id);
+                    entry.authority = BodyAuthority::Server;
+                    entry.version = version + 1;
+                } else {
+                    entry.authority = BodyAuthority::Remote(peer);
+                    entry.version = version;
+                }
+                entry.idle_time = 0.0;
+                entry.remote_snapshot = None;
+            }
+            AuthorityMessage::State { body_id, peer, version, snapshot } => {
+                let entry = match self.entries.get_mut(&body_id) {
+                    Some(entry) => entry,
+                    None => return,
+                };
+
+                let from_authority = match &entry.authority {
+                    BodyAuthority::Remote(owner) => *owner == peer,
+                    BodyAuthority::Server => peer == SERVER_PEER && !self.config.is_server,
+                    BodyAuthority::Local => false,
+                };
+
+                // Los paquetes tardíos de una autoridad anterior se descartan
+                if from_authority && version == entry.version {
+                    entry.remote_snapshot = Some(snapshot);
+                }
+            }
+            AuthorityMessage::Release { body_id, peer, version } => {
+                if let Some(entry) = self.entries.get_mut(&body_id) {
+                    if entry.authority == BodyAuthority::Remote(peer) && version == entry.version {
+                        entry.authority = BodyAuthority::Server;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Avanzar temporizadores de inactividad y publicar estados locales
+    pub fn update(&mut self, delta_time: f32, snapshots: &HashMap<String, BodySnapshot>) {
+        let mut released = Vec::new();
+
+        for (body_id, entry) in self.entries.iter_mut() {
+            let simulated_here = match entry.authority {
+                BodyAuthority::Local => true,
+                BodyAuthority::Server => self.config.is_server,
+                BodyAuthority::Remote(_) => false,
+            };
+            if !simulated_here {
+                continue;
+            }
+
+            let snapshot = match snapshots.get(body_id) {
+                Some(snapshot) => *snapshot,
+                None => continue,
+            };
+
+            self.outbox.push(AuthorityMessage::State {
+                body_id: body_id.clone(),
+                peer: self.local_peer.clone(),
+                version: entry.version,
+                snapshot,
+            });
+
+            if entry.authority == BodyAuthority::Local {
+                if snapshot.linear_velocity.length() < self.config.idle_velocity_threshold {
+                    entry.idle_time += delta_time;
+                } else {
+                    entry.idle_time = 0.0;
+                }
+
+                if entry.idle_time >= self.config.idle_timeout {
+                    released.push(body_id.clone());
+                }
+            }
+        }
+
+        for body_id in released {
+            if let Some(entry) = self.entries.get_mut(&body_id) {
+                entry.authority = BodyAuthority::Server;
+                entry.idle_time = 0.0;
+                self.outbox.push(AuthorityMessage::Release {
+                    body_id: body_id.clone(),
+                    peer: self.local_peer.clone(),
+                    version: entry.version,
+                });
+                debug!("Autoridad de {} devuelta al servidor por inactividad", body_id);
+            }
+        }
+    }
+
+    /// Extraer mensajes salientes
+    pub fn drain_outbox(&mut self) -> Vec<AuthorityMessage> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Número de cuerpos simulados localmente
+    pub fn local_body_count(&self) -> usize {
+        self.entries.values()
+            .filter(|entry| entry.authority == BodyAuthority::Local)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Paso de simulación de los tests
+    const DT: f32 = 1.0 / 60.0;
+    const BODY: &str = "caja";
+
+    /// Réplica de un peer: su gestor y su copia del cuerpo
+    struct Replica {
+        manager: AuthorityManager,
+        position: Vec3,
+        velocity: Vec3,
+    }
+
+    impl Replica {
+        fn new(peer: Option<&str>) -> Self {
+            let mut manager = AuthorityManager::new(AuthorityConfig {
+                is_server: peer.is_none(),
+                ..AuthorityConfig::default()
+            });
+            if let Some(peer) = peer {
+                manager.set_local_peer(peer.to_string());
+            }
+            manager.register_body(BODY);
+            Self { manager, position: Vec3::ZERO, velocity: Vec3::ZERO }
+        }
+
+        /// Simular el cuerpo si es la autoridad o copiar el último estado recibido
+        fn step(&mut self) {
+            if self.manager.simulates_locally(BODY) {
+                self.position += self.velocity * DT;
+                self.velocity *= 1.0 - 3.0 * DT;
+                if self.velocity.length() < 0.01 {
+                    self.velocity = Vec3::ZERO;
+                }
+            } else if let Some(snapshot) = self.manager.remote_snapshot(BODY) {
+                self.position = snapshot.position;
+                self.velocity = snapshot.linear_velocity;
+            }
+            let snapshot = BodySnapshot {
+                position: self.position,
+                rotation: Quat::IDENTITY,
+                linear_velocity: self.velocity,
+                angular_velocity: Vec3::ZERO,
+            };
+            self.manager.update(DT, &HashMap::from([(BODY.to_string(), snapshot)]));
+        }
+    }
+
+    /// Un tick en todas las réplicas y entrega de los mensajes a las demás
+    fn tick(replicas: &mut [Replica]) {
+        for replica in replicas.iter_mut() {
+            replica.step();
+        }
+        let outboxes: Vec<_> = replicas.iter_mut().map(|replica| replica.manager.drain_outbox()).collect();
+        for (from, messages) in outboxes.into_iter().enumerate() {
+            for message in messages {
+                for (to, replica) in replicas.iter_mut().enumerate() {
+                    if to != from {
+                        replica.manager.handle_message(message.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Empujar el cuerpo desde la réplica `pusher` y esperar a que la
+    /// autoridad vuelva al servidor
+    fn push(replicas: &mut [Replica], pusher: usize, velocity: Vec3) {
+        let position = replicas[pusher].position;
+        assert!(replicas[pusher].manager.try_claim(BODY, position, position));
+        replicas[pusher].velocity = velocity;
+        for _ in 0..600 {
+            tick(replicas);
+            if replicas.iter().all(|replica| replica.manager.authority(BODY) == BodyAuthority::Server) {
+                return;
+            }
+        }
+        panic!("La autoridad de {} no volvió al servidor", BODY);
+    }
+
+    #[test]
+    fn alternating_pushes_converge_on_every_peer() {
+        let mut replicas = [Replica::new(Some("peer-a")), Replica::new(Some("peer-b")), Replica::new(None)];
+
+        push(&mut replicas, 0, Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(replicas[1].manager.authority(BODY), BodyAuthority::Server);
+        push(&mut replicas, 1, Vec3::new(0.0, 0.0, -1.5));
+        push(&mut replicas, 0, Vec3::new(-1.0, 0.0, 0.0));
+        for _ in 0..10 {
+            tick(&mut replicas);
+        }
+
+        let rest = replicas[2].position;
+        assert!(rest.x > 0.1 && rest.z < -0.1, "posición final {:?}", rest);
+        for replica in &replicas {
+            assert!(replica.position.distance(rest) < 1e-5, "{:?} frente a {:?}", replica.position, rest);
+            assert_eq!(replica.velocity, Vec3::ZERO);
+        }
+    }
+}
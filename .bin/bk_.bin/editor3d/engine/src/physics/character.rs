@@ -0,0 +1,257 @@
+//! # Controlador de personaje
+//!
+//! Un personaje es un cuerpo cinemático que se mueve con desplazamientos
+//! pedidos (el root motion de sus animaciones) en lugar de con fuerzas. Antes
+//! de cada paso el `KinematicCharacterController` de Rapier recorta el
+//! desplazamiento contra los colliders: desliza por las paredes, sube
+//! escalones y pendientes suaves y se pega al suelo al bajarlas. La gravedad
+//! se acumula mientras el personaje no toca el suelo.
+//!
+//! Tras el paso, `PhysicsSystem::drain_character_moves` devuelve la pose
+//! resultante de cada personaje para copiarla a su `Transform`.
+
+use glam::{Quat, Vec3};
+use rapier3d::control::{CharacterAutostep, CharacterLength, KinematicCharacterController};
+use rapier3d::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::ecs::EntityId;
+
+/// Configuración de un controlador de personaje
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CharacterControllerConfig {
+    /// Separación que se mantiene con los colliders, en metros
+    #[serde(default = "default_offset")]
+    pub offset: f32,
+    /// Pendiente máxima que puede subir, en grados
+    #[serde(default = "default_max_slope")]
+    pub max_slope_degrees: f32,
+    /// Altura máxima de los escalones que sube (0 desactiva el autostep)
+    #[serde(default = "default_step_height")]
+    pub step_height: f32,
+    /// Distancia a la que se pega al suelo al bajar (0 lo desactiva)
+    #[serde(default = "default_snap_to_ground")]
+    pub snap_to_ground: f32,
+    /// Aplicar la gravedad de la simulación cuando no toca el suelo
+    #[serde(default = "default_gravity")]
+    pub gravity: bool,
+}
+
+fn default_offset() -> f32 {
+    0.01
+}
+
+fn default_max_slope() -> f32 {
+    45.0
+}
+
+fn default_step_height() -> f32 {
+    0.3
+}
+
+fn default_snap_to_ground() -> f32 {
+    0.2
+}
+
+fn default_gravity() -> bool {
+    true
+}
+
+impl Default for CharacterControllerConfig {
+    fn default() -> Self {
+        Self {
+            offset: default_offset(),
+            max_slope_degrees: default_max_slope(),
+            step_height: default_step_height(),
+            snap_to_ground: default_snap_to_ground(),
+            gravity: default_gravity(),
+        }
+    }
+}
+
+impl CharacterControllerConfig {
+    /// Controlador de Rapier con esta configuración
+    pub fn to_rapier(&self) -> KinematicCharacterController {
+        let max_slope = self.max_slope_degrees.clamp(0.0, 89.0).to_radians();
+        KinematicCharacterController {
+            offset: CharacterLength::Absolute(self.offset.max(0.0)),
+            slide: true,
+            autostep: (self.step_height > 0.0).then(|| CharacterAutostep {
+                max_height: CharacterLength::Absolute(self.step_height),
+                min_width: CharacterLength::Absolute(self.step_height * 0.5),
+                include_dynamic_bodies: false,
+            }),
+            max_slope_climb_angle: max_slope,
+            min_slope_slide_angle: max_slope,
+            snap_to_ground: (self.snap_to_ground > 0.0).then(|| CharacterLength::Absolute(self.snap_to_ground)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Personaje controlado por desplazamientos
+#[derive(Debug, Clone)]
+pub struct CharacterController {
+    /// Entidad del ECS que mueve
+    pub entity: EntityId,
+    /// Cuerpo cinemático del personaje
+    pub body: RigidBodyHandle,
+    /// Configuración
+    pub config: CharacterControllerConfig,
+    /// Desplazamiento pedido en este frame, en espacio mundo
+    pub pending_translation: Vec3,
+    /// Giro pedido en este frame
+    pub pending_rotation: Quat,
+    /// Velocidad vertical por la gravedad
+    pub vertical_velocity: f32,
+    /// Tocaba el suelo al terminar el último movimiento
+    pub grounded: bool,
+    /// Se movió en el último paso y su pose está pendiente de copiar al ECS
+    pub moved: bool,
+}
+
+impl CharacterController {
+    /// Nuevo controlador sobre un cuerpo
+    pub fn new(entity: EntityId, body: RigidBodyHandle, config: CharacterControllerConfig) -> Self {
+        Self {
+            entity,
+            body,
+            config,
+            pending_translation: Vec3::ZERO,
+            pending_rotation: Quat::IDENTITY,
+            vertical_velocity: 0.0,
+            grounded: false,
+            moved: false,
+        }
+    }
+
+    /// Acumular un movimiento en espacio mundo
+    pub fn push_move(&mut self, translation: Vec3, rotation: Quat) {
+        self.pending_translation += translation;
+        self.pending_rotation = (rotation * self.pending_rotation).normalize();
+    }
+
+    /// Vaciar el movimiento pedido
+    pub fn take_move(&mut self) -> (Vec3, Quat) {
+        let pending = (self.pending_translation, self.pending_rotation);
+        self.pending_translation = Vec3::ZERO;
+        self.pending_rotation = Quat::IDENTITY;
+        pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::sampling::{remove_root_motion, ClipSampler, RotationInterpolation, SkeletonPose};
+    use crate::animations::{
+        Bone, ClipConfig, EasingConfig, EasingType, FalloffConfig, FalloffType, InfluenceConfig,
+        InterpolationType, KeyframeInterpolation, SkeletalData, Transform, TransformKeyframe,
+    };
+
+    /// Segundos por frame: 4 frames por vuelta del clip
+    const FRAME: f32 = 0.25;
+
+    fn transform_at(position: [f32; 3]) -> Transform {
+        Transform { position, rotation: [0.0, 0.0, 0.0, 1.0], scale: [1.0; 3] }
+    }
+
+    /// Clip de 1 s en bucle cuya raíz avanza 2 m en +Z
+    fn stride() -> (SkeletalData, ClipConfig) {
+        let root = Bone {
+            id: "root".to_string(),
+            name: "Root".to_string(),
+            parent_id: None,
+            local_transform: transform_at([0.0; 3]),
+            world_transform: transform_at([0.0; 3]),
+            influence_config: InfluenceConfig {
+                influence_radius: 1.0,
+                influence_weight: 1.0,
+                falloff_config: FalloffConfig { falloff_type: FalloffType::Linear, falloff_exponent: 1.0 },
+            },
+        };
+        let keyframes = [(0.0, 0.0), (1.0, 2.0)]
+            .into_iter()
+            .map(|(time, z)| TransformKeyframe {
+                time,
+                bone_id: "root".to_string(),
+                transform: transform_at([0.0, 0.9, z]),
+                interpolation: KeyframeInterpolation {
+                    interpolation_type: InterpolationType::Linear,
+                    tangents: None,
+                    easing: EasingConfig { easing_type: EasingType::None, parameters: [0.0; 4] },
+                },
+            })
+            .collect();
+        let skeletal = SkeletalData {
+            bones: vec![root],
+            keyframes,
+            constraints: vec![],
+            root_bone_id: Some("root".to_string()),
+            compressed: None,
+        };
+        let config = ClipConfig {
+            duration: 1.0,
+            fps: 30.0,
+            looped: true,
+            compression: None,
+            optimization: None,
+            rotation_interpolation: RotationInterpolation::Slerp,
+        };
+        (skeletal, config)
+    }
+
+    /// Reproducir `frames` frames del clip moviendo la entidad con el
+    /// controlador (sin obstáculos el movimiento pedido es el aplicado).
+    /// Devuelve la posición final y el desfase horizontal entre la raíz y la
+    /// entidad en cada frame
+    fn play(frames: usize) -> (Vec3, Vec<Vec3>) {
+        let (skeletal, config) = stride();
+        let sampler = ClipSampler::new(&skeletal);
+        let reference = sampler.sample_bone(&skeletal, &config, 0, 0.0);
+        let mut character = CharacterController::new(1, RigidBodyHandle::invalid(), CharacterControllerConfig::default());
+        let (mut position, mut time, mut pose) = (Vec3::ZERO, 0.0, SkeletonPose::default());
+        let mut offsets = Vec::new();
+
+        for _ in 0..frames {
+            let motion = sampler.root_motion(&skeletal, &config, 0, time, FRAME);
+            time += FRAME;
+            character.push_move(motion.translation, motion.rotation);
+            let (translation, _) = character.take_move();
+            position += translation;
+
+            sampler.sample(&skeletal, &config, time, &mut pose);
+            remove_root_motion(&mut pose, &reference, 0);
+            let root = position + pose.bones[0].translation;
+            offsets.push(Vec3::new(root.x - position.x, 0.0, root.z - position.z));
+        }
+        (position, offsets)
+    }
+
+    #[test]
+    fn root_motion_moves_2m_per_loop() {
+        let (one_loop, _) = play(4);
+        assert!((one_loop - Vec3::new(0.0, 0.0, 2.0)).length() < 1e-4, "{:?}", one_loop);
+        let (two_loops, _) = play(8);
+        assert!((two_loops - Vec3::new(0.0, 0.0, 4.0)).length() < 1e-4, "{:?}", two_loops);
+    }
+
+    #[test]
+    fn root_bone_stays_over_the_entity() {
+        let (_, offsets) = play(8);
+        for (frame, offset) in offsets.iter().enumerate() {
+            assert!(offset.length() < 1e-5, "frame {}: la raíz se desliza {:?}", frame, offset);
+        }
+    }
+
+    #[test]
+    fn moves_accumulate_until_taken() {
+        let mut character = CharacterController::new(1, RigidBodyHandle::invalid(), CharacterControllerConfig::default());
+        character.push_move(Vec3::Z, Quat::from_rotation_y(0.25));
+        character.push_move(Vec3::X, Quat::from_rotation_y(0.5));
+        let (translation, rotation) = character.take_move();
+        assert_eq!(translation, Vec3::new(1.0, 0.0, 1.0));
+        assert!(rotation.abs_diff_eq(Quat::from_rotation_y(0.75), 1e-6));
+        assert_eq!(character.take_move(), (Vec3::ZERO, Quat::IDENTITY));
+    }
+}
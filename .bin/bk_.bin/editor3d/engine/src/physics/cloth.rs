@@ -0,0 +1,508 @@
+//! # Telas
+//!
+//! Simulación de telas con dinámica basada en posiciones (Müller et al.
+//! 2007). Cada vértice de la malla es una partícula que se integra con
+//! Verlet bajo la gravedad y el viento; después, en `solver_iterations`
+//! pasadas de Gauss-Seidel, se proyectan las restricciones de distancia (las
+//! aristas de los triángulos y la diagonal cruzada de cada par de triángulos
+//! vecinos) y las de flexión, que conservan el ángulo diedro en reposo de
+//! cada arista compartida. Por último las partículas se sacan de los
+//! colliders del mundo y de los campos de distancia del terreno.
+//!
+//! Las partículas fijadas (`pinned_vertices`) tienen masa inversa 0 y siguen
+//! a la transformación de la tela, de modo que una capa puede ir sujeta a un
+//! personaje.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use serde::{Serialize, Deserialize};
+use glam::{Mat4, Vec3};
+use rapier3d::prelude::*;
+
+use super::sdf::{self, SDFShape};
+use crate::ecs::{EntityId, MeshComponent};
+
+/// ID de una tela
+pub type ClothId = u64;
+
+/// Parámetros de una tela
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClothConfig {
+    /// Masa de cada partícula (kg)
+    pub particle_mass: f32,
+    /// Rigidez de las aristas en [0, 1]
+    pub stretch_stiffness: f32,
+    /// Rigidez de las diagonales cruzadas en [0, 1]
+    pub shear_stiffness: f32,
+    /// Rigidez de flexión en [0, 1]
+    pub bend_stiffness: f32,
+    /// Pasadas del solver por subpaso
+    #[serde(default = "default_solver_iterations")]
+    pub solver_iterations: u32,
+    /// Subpasos por paso de física
+    #[serde(default = "default_substeps")]
+    pub substeps: u32,
+    /// Amortiguación de la velocidad por segundo en [0, 1]
+    #[serde(default = "default_damping")]
+    pub damping: f32,
+    /// Coeficiente de arrastre aerodinámico
+    #[serde(default = "default_drag")]
+    pub drag: f32,
+    /// Velocidad del viento (m/s)
+    #[serde(default)]
+    pub wind: Vec3,
+    /// Grosor: distancia mínima de las partículas a los colliders
+    #[serde(default = "default_thickness")]
+    pub thickness: f32,
+    /// Fricción con los colliders en [0, 1]
+    #[serde(default = "default_friction")]
+    pub friction: f32,
+    /// Vértices sujetos a la transformación de la tela
+    #[serde(default)]
+    pub pinned_vertices: Vec<u32>,
+    /// Transformación de la malla al mundo
+    #[serde(default = "default_transform")]
+    pub transform: Mat4,
+    /// Entidad cuyo `MeshComponent` sigue a la tela
+    #[serde(default)]
+    pub entity_id: Option<EntityId>,
+}
+
+fn default_solver_iterations() -> u32 {
+    8
+}
+
+fn default_substeps() -> u32 {
+    2
+}
+
+fn default_damping() -> f32 {
+    0.1
+}
+
+fn default_drag() -> f32 {
+    0.5
+}
+
+fn default_thickness() -> f32 {
+    0.02
+}
+
+fn default_friction() -> f32 {
+    0.3
+}
+
+fn default_transform() -> Mat4 {
+    Mat4::IDENTITY
+}
+
+impl Default for ClothConfig {
+    fn default() -> Self {
+        Self {
+            particle_mass: 0.01,
+            stretch_stiffness: 1.0,
+            shear_stiffness: 0.5,
+            bend_stiffness: 0.1,
+            solver_iterations: default_solver_iterations(),
+            substeps: default_substeps(),
+            damping: default_damping(),
+            drag: default_drag(),
+            wind: Vec3::ZERO,
+            thickness: default_thickness(),
+            friction: default_friction(),
+            pinned_vertices: Vec::new(),
+            transform: default_transform(),
+            entity_id: None,
+        }
+    }
+}
+
+/// Partícula de la tela
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ClothParticle {
+    /// Posición en el mundo
+    pub position: Vec3,
+    /// Posición del subpaso anterior (Verlet)
+    pub prev_position: Vec3,
+    /// Masa inversa (0 si está fijada)
+    pub inverse_mass: f32,
+}
+
+/// Restricción de distancia entre dos partículas
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DistanceConstraint {
+    pub a: usize,
+    pub b: usize,
+    /// Longitud en reposo
+    pub rest_length: f32,
+    /// Rigidez en [0, 1]
+    pub stiffness: f32,
+}
+
+/// Restricción de flexión sobre la arista compartida `edge` de dos
+/// triángulos, con `wings` los vértices opuestos
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BendConstraint {
+    pub edge: [usize; 2],
+    pub wings: [usize; 2],
+    /// Ángulo diedro en reposo (0 con los triángulos en el mismo plano)
+    pub rest_angle: f32,
+}
+
+/// Tela simulada
+#[derive(Debug, Clone)]
+pub struct ClothSimulator {
+    /// Partículas, una por vértice de la malla
+    pub particles: Vec<ClothParticle>,
+    /// Restricciones de distancia
+    pub distance_constraints: Vec<DistanceConstraint>,
+    /// Restricciones de flexión
+    pub bend_constraints: Vec<BendConstraint>,
+    /// Parámetros
+    pub config: ClothConfig,
+    /// Triángulos de la malla (para el viento y las normales)
+    triangles: Vec<[usize; 3]>,
+    /// Posición en la malla de las partículas fijadas
+    pinned: Vec<(usize, Vec3)>,
+    /// Vértices en espacio de la malla tras el último paso
+    local_positions: Vec<Vec3>,
+    /// Duración del último subpaso (0 antes del primero)
+    last_dt: f32,
+}
+
+impl ClothSimulator {
+    /// Crear la tela de una malla: una partícula por vértice, una restricción
+    /// de distancia por arista y, por cada arista con dos triángulos, la
+    /// diagonal entre sus vértices opuestos y una de flexión
+    pub fn from_mesh(mesh: &MeshComponent, config: ClothConfig) -> Self {
+        let inverse_mass = if config.particle_mass > 0.0 { 1.0 / config.particle_mass } else { 0.0 };
+        let mut particles: Vec<ClothParticle> = mesh.vertices.iter()
+            .map(|vertex| {
+                let position = config.transform.transform_point3(*vertex);
+                ClothParticle { position, prev_position: position, inverse_mass }
+            })
+            .collect();
+        let pinned: Vec<(usize, Vec3)> = config.pinned_vertices.iter()
+            .map(|&vertex| vertex as usize)
+            .filter(|&vertex| vertex < particles.len())
+            .map(|vertex| (vertex, mesh.vertices[vertex]))
+            .collect();
+        for &(vertex, _) in &pinned {
+            particles[vertex].inverse_mass = 0.0;
+        }
+
+        let triangles: Vec<[usize; 3]> = mesh.indices.chunks_exact(3)
+            .map(|triangle| [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize])
+            .filter(|triangle| triangle.iter().all(|&vertex| vertex < particles.len()))
+            .filter(|[a, b, c]| a != b && b != c && a != c)
+            .collect();
+
+        // Vértices opuestos de cada arista
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for &[a, b, c] in &triangles {
+            for (u, v, opposite) in [(a, b, c), (b, c, a), (c, a, b)] {
+                edges.entry((u.min(v), u.max(v))).or_default().push(opposite);
+            }
+        }
+
+        let rest_length = |a: usize, b: usize| particles[a].position.distance(particles[b].position);
+        let mut distance_constraints = Vec::new();
+        let mut bend_constraints = Vec::new();
+        let mut edge_list: Vec<_> = edges.into_iter().collect();
+        edge_list.sort_unstable_by_key(|(edge, _)| *edge);
+        for ((a, b), wings) in edge_list {
+            distance_constraints.push(DistanceConstraint {
+                a,
+                b,
+                rest_length: rest_length(a, b),
+                stiffness: config.stretch_stiffness,
+            });
+            if let [c, d] = wings[..] {
+                if c != d {
+                    distance_constraints.push(DistanceConstraint {
+                        a: c,
+                        b: d,
+                        rest_length: rest_length(c, d),
+                        stiffness: config.shear_stiffness,
+                    });
+                    let positions = [particles[a].position, particles[b].position, particles[c].position, particles[d].position];
+                    if let Some((rest_angle, _)) = dihedral(positions) {
+                        bend_constraints.push(BendConstraint { edge: [a, b], wings: [c, d], rest_angle });
+                    }
+                }
+            }
+        }
+
+        Self {
+            particles,
+            distance_constraints,
+            bend_constraints,
+            local_positions: mesh.vertices.clone(),
+            config,
+            triangles,
+            pinned,
+            last_dt: 0.0,
+        }
+    }
+
+    /// Número de partículas
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// No hay partículas
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Mover la tela: las partículas fijadas siguen a la nueva transformación
+    /// en el siguiente paso y el resto las sigue por las restricciones
+    pub fn set_transform(&mut self, transform: Mat4) {
+        self.config.transform = transform;
+    }
+
+    /// Posiciones de los vértices en espacio de la malla tras el último paso
+    pub fn positions(&self) -> &[Vec3] {
+        &self.local_positions
+    }
+
+    /// Copiar a una malla los vértices y las normales recalculadas
+    pub fn write_mesh(&self, mesh: &mut MeshComponent) {
+        mesh.vertices.clear();
+        mesh.vertices.extend_from_slice(&self.local_positions);
+        let mut normals = vec![Vec3::ZERO; self.local_positions.len()];
+        for &[a, b, c] in &self.triangles {
+            let (pa, pb, pc) = (self.local_positions[a], self.local_positions[b], self.local_positions[c]);
+            // Sin normalizar: pesa por el área del triángulo
+            let normal = (pb - pa).cross(pc - pa);
+            normals[a] += normal;
+            normals[b] += normal;
+            normals[c] += normal;
+        }
+        mesh.normals = normals.into_iter().map(|normal| normal.try_normalize().unwrap_or(Vec3::Y)).collect();
+    }
+
+    /// Avanzar la simulación `dt` segundos en `config.substeps` subpasos.
+    /// `boundaries` son los colliders en posición de mundo y `fields` los
+    /// campos de distancia con la posición de su collider
+    pub fn step(&mut self, dt: f32, gravity: Vec3, boundaries: &[(Isometry<Real>, SharedShape)], fields: &[(Isometry<Real>, &SDFShape)]) {
+        if self.particles.is_empty() || dt <= 0.0 {
+            return;
+        }
+        let bounds: Vec<(Vec3, Vec3)> = boundaries.iter()
+            .map(|(position, shape)| {
+                let aabb = shape.compute_aabb(position);
+                (
+                    Vec3::new(aabb.mins.x, aabb.mins.y, aabb.mins.z),
+                    Vec3::new(aabb.maxs.x, aabb.maxs.y, aabb.maxs.z),
+                )
+            })
+            .collect();
+
+        let substeps = self.config.substeps.max(1);
+        let sub_dt = dt / substeps as f32;
+        let iterations = self.config.solver_iterations.max(1);
+        for _ in 0..substeps {
+            self.move_pinned();
+            let forces = self.compute_forces(gravity);
+            self.integrate(sub_dt, &forces);
+            for _ in 0..iterations {
+                self.project_distances(iterations);
+                self.project_bends(iterations);
+            }
+            self.resolve_collisions(boundaries, &bounds, fields);
+        }
+
+        let inverse = self.config.transform.inverse();
+        self.local_positions.clear();
+        self.local_positions.extend(self.particles.iter().map(|particle| inverse.transform_point3(particle.position)));
+    }
+
+    /// Llevar las partículas fijadas a su sitio en la transformación actual
+    fn move_pinned(&mut self) {
+        for &(vertex, local) in &self.pinned {
+            let particle = &mut self.particles[vertex];
+            particle.prev_position = particle.position;
+            particle.position = self.config.transform.transform_point3(local);
+        }
+    }
+
+    /// Aceleración de cada partícula: gravedad y arrastre del viento sobre
+    /// cada triángulo, repartido entre sus vértices
+    fn compute_forces(&self, gravity: Vec3) -> Vec<Vec3> {
+        let mut accelerations = vec![gravity; self.particles.len()];
+        if self.config.drag <= 0.0 || self.last_dt <= 0.0 {
+            return accelerations;
+        }
+        let velocity = |index: usize| {
+            let particle = &self.particles[index];
+            (particle.position - particle.prev_position) / self.last_dt
+        };
+        for &[a, b, c] in &self.triangles {
+            let (pa, pb, pc) = (self.particles[a].position, self.particles[b].position, self.particles[c].position);
+            let cross = (pb - pa).cross(pc - pa);
+            let area = cross.length() * 0.5;
+            let Some(normal) = cross.try_normalize() else {
+                continue;
+            };
+            let relative = self.config.wind - (velocity(a) + velocity(b) + velocity(c)) / 3.0;
+            // Solo empuja la componente del viento perpendicular al triángulo
+            let force = normal * (self.config.drag * area * relative.dot(normal)) / 3.0;
+            for vertex in [a, b, c] {
+                accelerations[vertex] += force * self.particles[vertex].inverse_mass;
+            }
+        }
+        accelerations
+    }
+
+    /// Verlet con amortiguación y corrección por cambio de paso
+    fn integrate(&mut self, dt: f32, accelerations: &[Vec3]) {
+        let retain = (1.0 - self.config.damping.clamp(0.0, 1.0)).powf(dt);
+        let scale = if self.last_dt > 0.0 { dt / self.last_dt } else { 0.0 };
+        for (particle, acceleration) in self.particles.iter_mut().zip(accelerations) {
+            if particle.inverse_mass == 0.0 {
+                continue;
+            }
+            let displacement = (particle.position - particle.prev_position) * scale * retain;
+            let next = particle.position + displacement + *acceleration * dt * dt;
+            particle.prev_position = particle.position;
+            particle.position = next;
+        }
+        self.last_dt = dt;
+    }
+
+    /// Proyectar las restricciones de distancia. La rigidez se reparte entre
+    /// las pasadas para que no dependa de su número
+    fn project_distances(&mut self, iterations: u32) {
+        for constraint in &self.distance_constraints {
+            let (pa, pb) = (self.particles[constraint.a], self.particles[constraint.b]);
+            let weight = pa.inverse_mass + pb.inverse_mass;
+            if weight == 0.0 {
+                continue;
+            }
+            let offset = pa.position - pb.position;
+            let length = offset.length();
+            if length <= 1e-6 {
+                continue;
+            }
+            let stiffness = iteration_stiffness(constraint.stiffness, iterations);
+            let correction = offset / length * ((length - constraint.rest_length) / weight * stiffness);
+            self.particles[constraint.a].position -= correction * pa.inverse_mass;
+            self.particles[constraint.b].position += correction * pb.inverse_mass;
+        }
+    }
+
+    /// Proyectar las restricciones de flexión sobre el ángulo diedro
+    fn project_bends(&mut self, iterations: u32) {
+        let stiffness = iteration_stiffness(self.config.bend_stiffness, iterations);
+        if stiffness <= 0.0 {
+            return;
+        }
+        for constraint in &self.bend_constraints {
+            let indices = [constraint.edge[0], constraint.edge[1], constraint.wings[0], constraint.wings[1]];
+            let positions = indices.map(|index| self.particles[index].position);
+            let Some((angle, gradients)) = dihedral(positions) else {
+                continue;
+            };
+            let weights = indices.map(|index| self.particles[index].inverse_mass);
+            let denominator: f32 = (0..4).map(|i| weights[i] * gradients[i].length_squared()).sum();
+            if denominator <= 1e-9 {
+                continue;
+            }
+            let error = wrap_angle(angle - constraint.rest_angle);
+            let lambda = -error / denominator * stiffness;
+            for i in 0..4 {
+                self.particles[indices[i]].position += gradients[i] * (lambda * weights[i]);
+            }
+        }
+    }
+
+    /// Sacar las partículas de los colliders y de los campos de distancia.
+    /// La velocidad hacia la superficie se anula y la tangencial se frena
+    /// con la fricción
+    fn resolve_collisions(&mut self, boundaries: &[(Isometry<Real>, SharedShape)], bounds: &[(Vec3, Vec3)], fields: &[(Isometry<Real>, &SDFShape)]) {
+        let thickness = self.config.thickness;
+        let friction = self.config.friction.clamp(0.0, 1.0);
+        for particle in &mut self.particles {
+            if particle.inverse_mass == 0.0 {
+                continue;
+            }
+            for ((position, shape), (min, max)) in boundaries.iter().zip(bounds) {
+                if particle.position.cmplt(*min - thickness).any() || particle.position.cmpgt(*max + thickness).any() {
+                    continue;
+                }
+                let point = Point::new(particle.position.x, particle.position.y, particle.position.z);
+                let projection = shape.project_point(position, &point, false);
+                let surface = Vec3::new(projection.point.x, projection.point.y, projection.point.z);
+                let offset = particle.position - surface;
+                let distance = offset.length();
+                let normal = if projection.is_inside {
+                    -offset / distance.max(1e-6)
+                } else if distance < thickness && distance > 1e-6 {
+                    offset / distance
+                } else {
+                    continue;
+                };
+                push_out(particle, surface + normal * thickness, normal, friction);
+            }
+
+            for (position, field) in fields {
+                let local = position.inverse_transform_point(&Point::new(particle.position.x, particle.position.y, particle.position.z));
+                let (distance, normal) = sdf::evaluate(field, Vec3::new(local.x, local.y, local.z));
+                if distance >= thickness {
+                    continue;
+                }
+                let normal = position.rotation * Vector::new(normal.x, normal.y, normal.z);
+                let normal = Vec3::new(normal.x, normal.y, normal.z);
+                push_out(particle, particle.position + normal * (thickness - distance), normal, friction);
+            }
+        }
+    }
+}
+
+/// Rigidez por pasada que, aplicada `iterations` veces, equivale a `stiffness`
+fn iteration_stiffness(stiffness: f32, iterations: u32) -> f32 {
+    1.0 - (1.0 - stiffness.clamp(0.0, 1.0)).powf(1.0 / iterations.max(1) as f32)
+}
+
+/// Colocar una partícula en `target` fuera de una superficie de normal
+/// `normal`, sin velocidad hacia ella y con la tangencial frenada
+fn push_out(particle: &mut ClothParticle, target: Vec3, normal: Vec3, friction: f32) {
+    let displacement = particle.position - particle.prev_position;
+    let approach = displacement.dot(normal);
+    let normal_part = if approach < 0.0 { normal * approach } else { Vec3::ZERO };
+    let tangent = displacement - normal * approach;
+    particle.position = target;
+    particle.prev_position = target - (displacement - normal_part - tangent * friction);
+}
+
+/// Ángulo en (-π, π]
+fn wrap_angle(angle: f32) -> f32 {
+    let wrapped = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    if wrapped <= -PI { wrapped + 2.0 * PI } else { wrapped }
+}
+
+/// Ángulo diedro con signo sobre la arista `p[0]`-`p[1]` entre los
+/// triángulos de `p[2]` y `p[3]` (0 en el mismo plano) y su gradiente
+/// respecto a los cuatro puntos (Bridson et al. 2003). None si algún
+/// triángulo es degenerado
+fn dihedral(p: [Vec3; 4]) -> Option<(f32, [Vec3; 4])> {
+    let [x3, x4, x1, x2] = p;
+    let edge = x4 - x3;
+    let edge_length = edge.length();
+    let n1 = (x1 - x3).cross(x1 - x4);
+    let n2 = (x2 - x4).cross(x2 - x3);
+    let (n1_squared, n2_squared) = (n1.length_squared(), n2.length_squared());
+    if edge_length <= 1e-6 || n1_squared <= 1e-12 || n2_squared <= 1e-12 {
+        return None;
+    }
+    let (unit1, unit2, unit_edge) = (n1 / n1_squared.sqrt(), n2 / n2_squared.sqrt(), edge / edge_length);
+    let angle = unit2.cross(unit1).dot(unit_edge).atan2(unit1.dot(unit2));
+
+    let (n1, n2) = (n1 / n1_squared, n2 / n2_squared);
+    let u1 = n1 * edge_length;
+    let u2 = n2 * edge_length;
+    let u3 = n1 * ((x1 - x4).dot(unit_edge)) + n2 * ((x2 - x4).dot(unit_edge));
+    let u4 = -n1 * ((x1 - x3).dot(unit_edge)) - n2 * ((x2 - x3).dot(unit_edge));
+    Some((angle, [u3, u4, u1, u2]))
+}
@@ -0,0 +1,225 @@
+//! # Descomposición Convexa
+//!
+//! Convierte mallas arbitrarias en conjuntos de piezas convexas mediante
+//! V-HACD (la implementación incluida en parry, el backend de colisiones de
+//! Rapier) para construir colliders compuestos.
+
+use glam::Vec3;
+use rapier3d::parry::transformation::vhacd::{VHACD, VHACDParameters};
+use rapier3d::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+/// Número máximo de piezas convexas por defecto
+pub const DEFAULT_MAX_CONVEX_HULLS: u32 = 16;
+
+/// Capacidad por defecto de la caché de descomposiciones
+pub const DEFAULT_DECOMPOSITION_CACHE_SIZE: usize = 64;
+
+/// Descomponer una malla en piezas convexas
+pub fn compute_convex_decomposition(vertices: &[Vec3], indices: &[u32], max_convex_hulls: u32) -> Vec<Vec<Vec3>> {
+    if vertices.len() < 4 || indices.len() < 3 {
+        return Vec::new();
+    }
+
+    let points: Vec<Point<Real>> = vertices.iter()
+        .map(|v| Point::new(v.x, v.y, v.z))
+        .collect();
+    let triangles: Vec<[u32; 3]> = indices.chunks_exact(3)
+        .filter(|t| t.iter().all(|&i| (i as usize) < vertices.len()))
+        .map(|t| [t[0], t[1], t[2]])
+        .collect();
+
+    let params = VHACDParameters {
+        max_convex_hulls: max_convex_hulls.max(1),
+        ..Default::default()
+    };
+    let decomposition = VHACD::decompose(&params, &points, &triangles, false);
+
+    decomposition.compute_convex_hulls(params.convex_hull_downsampling)
+        .into_iter()
+        .map(|(hull, _)| hull.iter().map(|p| Vec3::new(p.x, p.y, p.z)).collect::<Vec<_>>())
+        .filter(|hull| hull.len() >= 4)
+        .collect()
+}
+
+/// Índices de una sopa de triángulos (cada tres vértices forman un triángulo)
+pub fn triangle_soup_indices(vertex_count: usize) -> Vec<u32> {
+    (0..(vertex_count - vertex_count % 3) as u32).collect()
+}
+
+/// Construir un collider compuesto a partir de piezas convexas
+pub fn compound_collider(hulls: &[Vec<Vec3>]) -> Option<ColliderBuilder> {
+    let shapes: Vec<(Isometry<Real>, SharedShape)> = hulls.iter()
+        .filter_map(|hull| {
+            let points: Vec<Point<Real>> = hull.iter().map(|v| Point::new(v.x, v.y, v.z)).collect();
+            SharedShape::convex_hull(&points)
+        })
+        .map(|shape| (Isometry::identity(), shape))
+        .collect();
+
+    if shapes.is_empty() {
+        None
+    } else {
+        Some(ColliderBuilder::compound(shapes))
+    }
+}
+
+/// Caché LRU de descomposiciones convexas
+#[derive(Debug)]
+pub struct DecompositionCache {
+    /// Capacidad máxima
+    capacity: usize,
+    /// Descomposiciones por clave
+    entries: HashMap<u64, Vec<Vec<Vec3>>>,
+    /// Orden de uso (el más reciente al final)
+    order: VecDeque<u64>,
+}
+
+impl DecompositionCache {
+    /// Crear caché
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Clave de una malla y sus parámetros
+    pub fn key(vertices: &[Vec3], indices: &[u32], max_convex_hulls: u32) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for v in vertices {
+            v.x.to_bits().hash(&mut hasher);
+            v.y.to_bits().hash(&mut hasher);
+            v.z.to_bits().hash(&mut hasher);
+        }
+        indices.hash(&mut hasher);
+        max_convex_hulls.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Obtener la descomposición de una malla, calculándola si no está en caché
+    pub fn get_or_compute(&mut self, vertices: &[Vec3], indices: &[u32], max_convex_hulls: u32) -> Vec<Vec<Vec3>> {
+        let key = Self::key(vertices, indices, max_convex_hulls);
+        if let Some(hulls) = self.entries.get(&key) {
+            let hulls = hulls.clone();
+            self.touch(key);
+            return hulls;
+        }
+
+        let hulls = compute_convex_decomposition(vertices, indices, max_convex_hulls);
+        self.insert(key, hulls.clone());
+        hulls
+    }
+
+    /// Insertar una descomposición, desalojando la menos usada si está llena
+    fn insert(&mut self, key: u64, hulls: Vec<Vec<Vec3>>) {
+        if self.entries.len() >= self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+        self.entries.insert(key, hulls);
+        self.order.push_back(key);
+    }
+
+    /// Marcar una entrada como usada recientemente
+    fn touch(&mut self, key: u64) {
+        if let Some(position) = self.order.iter().position(|k| *k == key) {
+            self.order.remove(position);
+        }
+        self.order.push_back(key);
+    }
+
+    /// Número de entradas
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Verificar si está vacía
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Vaciar caché
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rapier3d::parry::query;
+
+    /// Prisma en L de 1 m de fondo: brazo inferior x∈[0,3], y∈[0,1] y
+    /// brazo vertical x∈[0,1], y∈[0,3]; el hueco cóncavo queda en x,y∈[1,3]
+    fn l_prism() -> (Vec<Vec3>, Vec<u32>) {
+        let profile = [(0.0, 0.0), (3.0, 0.0), (3.0, 1.0), (1.0, 1.0), (1.0, 3.0), (0.0, 3.0)];
+        let n = profile.len() as u32;
+        let vertices: Vec<Vec3> = [0.0, 1.0].iter()
+            .flat_map(|&z| profile.iter().map(move |&(x, y)| Vec3::new(x, y, z)))
+            .collect();
+
+        // Tapas en abanico desde el origen (el perfil es estrellado respecto a él)
+        let mut indices = Vec::new();
+        for i in 1..n - 1 {
+            indices.extend_from_slice(&[n, n + i, n + i + 1]);
+            indices.extend_from_slice(&[0, i + 1, i]);
+        }
+        for i in 0..n {
+            let j = (i + 1) % n;
+            indices.extend_from_slice(&[i, j, n + j, i, n + j, n + i]);
+        }
+        (vertices, indices)
+    }
+
+    /// Distancia que recorre hacia abajo una esfera soltada en `(x, 5, 0.5)`
+    /// hasta tocar el collider
+    fn drop_distance(collider: &Collider, x: f32, radius: f32) -> f32 {
+        let ball = Ball::new(radius);
+        let start = Isometry::translation(x, 5.0, 0.5);
+        query::time_of_impact(
+            &start, &vector![0.0, -1.0, 0.0], &ball,
+            collider.position(), &vector![0.0, 0.0, 0.0], collider.shape(),
+            10.0, true,
+        )
+        .unwrap()
+        .expect("la esfera atraviesa el collider")
+        .toi
+    }
+
+    #[test]
+    fn compound_l_shape_blocks_a_sphere_in_the_concave_region() {
+        let (vertices, indices) = l_prism();
+        let hulls = compute_convex_decomposition(&vertices, &indices, DEFAULT_MAX_CONVEX_HULLS);
+        assert!(hulls.len() >= 2, "una L no es convexa: {} piezas", hulls.len());
+        let compound = compound_collider(&hulls).unwrap().build();
+
+        // Un único hull llenaría el hueco hasta la diagonal x + y = 4
+        let single = compound_collider(&[vertices.clone()]).unwrap().build();
+
+        // Soltada sobre el hueco, la esfera se apoya en el brazo inferior
+        // (centro en y = 1 + r) y no en la diagonal del hull envolvente
+        let radius = 0.25;
+        let rest = 5.0 - drop_distance(&compound, 2.5, radius);
+        assert!((rest - (1.0 + radius)).abs() < 0.15, "reposo en y = {}", rest);
+        assert!(5.0 - drop_distance(&single, 2.5, radius) > 1.75);
+
+        // Sobre el brazo vertical se detiene en su tope
+        let rest = 5.0 - drop_distance(&compound, 0.5, radius);
+        assert!((rest - (3.0 + radius)).abs() < 0.15, "reposo en y = {}", rest);
+
+        // Dentro del hueco no hay colisión; dentro de los brazos sí
+        let ball = Ball::new(radius);
+        let touches = |x: f32, y: f32| {
+            query::intersection_test(&Isometry::translation(x, y, 0.5), &ball, compound.position(), compound.shape()).unwrap()
+        };
+        assert!(!touches(2.0, 2.0));
+        assert!(touches(2.0, 0.5));
+        assert!(touches(0.5, 2.0));
+    }
+}
@@ -0,0 +1,540 @@
+//! # Fluidos SPH
+//!
+//! Masas de agua simuladas con Smoothed Particle Hydrodynamics (Müller et
+//! al. 2003). En cada subpaso se buscan los vecinos con una rejilla hash de
+//! celda `h` (el radio de suavizado), se calculan densidad y presión con el
+//! kernel poly6, las fuerzas de presión con el gradiente del kernel spiky,
+//! la viscosidad con el laplaciano del kernel de viscosidad y la tensión
+//! superficial con el campo de color. Las posiciones se integran con Verlet
+//! y se sacan de los colliders estáticos que atraviesan. La superficie se
+//! extrae del campo de densidad con marching cubes, partiendo cada cubo en
+//! seis tetraedros.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use serde::{Serialize, Deserialize};
+use glam::Vec3;
+use rapier3d::prelude::*;
+
+use crate::ecs::{MeshComponent, RENDER_LAYER_DEFAULT};
+
+/// ID del mesh de la superficie del fluido
+pub const FLUID_SURFACE_MESH_ID: &str = "sph_fluid_surface";
+
+/// Radio de suavizado en radios de partícula
+const SMOOTHING_SCALE: f32 = 4.0;
+
+/// Muestras como máximo por eje al extraer la superficie
+const MAX_SURFACE_RESOLUTION: usize = 128;
+
+/// Gradiente del campo de color (por 1/h) a partir del cual una partícula
+/// está en la superficie y recibe tensión superficial
+const SURFACE_THRESHOLD: f32 = 0.1;
+
+/// Esquinas de un cubo de la rejilla de muestreo
+const CUBE_CORNERS: [[usize; 3]; 8] = [
+    [0, 0, 0], [1, 0, 0], [0, 1, 0], [1, 1, 0],
+    [0, 0, 1], [1, 0, 1], [0, 1, 1], [1, 1, 1],
+];
+
+/// Tetraedros del cubo alrededor de la diagonal 0-7. Las caras compartidas
+/// se parten igual en cubos vecinos y la superficie queda cerrada
+const CUBE_TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7], [0, 1, 5, 7], [0, 2, 3, 7],
+    [0, 2, 6, 7], [0, 4, 5, 7], [0, 4, 6, 7],
+];
+
+/// Parámetros del fluido
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SphConfig {
+    /// Radio de partícula (la separación en reposo es el doble)
+    pub particle_radius: f32,
+    /// Densidad en reposo (kg/m³)
+    pub rest_density: f32,
+    /// Rigidez de la ecuación de estado: presión = rigidez * (densidad - reposo)
+    pub stiffness: f32,
+    /// Viscosidad
+    pub viscosity: f32,
+    /// Coeficiente de tensión superficial
+    pub surface_tension: f32,
+    /// Subpasos por paso de física (la presión es rígida y necesita pasos cortos)
+    #[serde(default = "default_substeps")]
+    pub substeps: u32,
+}
+
+fn default_substeps() -> u32 {
+    4
+}
+
+impl Default for SphConfig {
+    fn default() -> Self {
+        Self {
+            particle_radius: 0.05,
+            rest_density: 1000.0,
+            stiffness: 200.0,
+            viscosity: 3.5,
+            surface_tension: 0.0728,
+            substeps: default_substeps(),
+        }
+    }
+}
+
+/// Partícula del fluido
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SphParticle {
+    /// Posición
+    pub position: Vec3,
+    /// Posición del subpaso anterior (Verlet)
+    pub previous_position: Vec3,
+    /// Velocidad derivada del último subpaso
+    pub velocity: Vec3,
+    /// Densidad
+    pub density: f32,
+    /// Presión
+    pub pressure: f32,
+}
+
+/// Fluido SPH
+#[derive(Debug, Clone)]
+pub struct SphFluid {
+    /// Partículas
+    pub particles: Vec<SphParticle>,
+    /// Parámetros
+    pub config: SphConfig,
+    /// Partículas por celda de la rejilla hash
+    grid: HashMap<[i32; 3], Vec<usize>>,
+    /// Vecinos de cada partícula (sin ella misma)
+    neighbours: Vec<Vec<usize>>,
+    /// Duración del último subpaso (0 antes del primero)
+    last_dt: f32,
+}
+
+impl SphFluid {
+    /// Crear fluido vacío
+    pub fn new(config: SphConfig) -> Self {
+        Self {
+            particles: Vec::new(),
+            config,
+            grid: HashMap::new(),
+            neighbours: Vec::new(),
+            last_dt: 0.0,
+        }
+    }
+
+    /// Radio de suavizado
+    pub fn smoothing_radius(&self) -> f32 {
+        self.config.particle_radius * SMOOTHING_SCALE
+    }
+
+    /// Masa de cada partícula: la de su celda en reposo
+    pub fn particle_mass(&self) -> f32 {
+        self.config.rest_density * (2.0 * self.config.particle_radius).powi(3)
+    }
+
+    /// Añadir una partícula
+    pub fn add_particle(&mut self, position: Vec3, velocity: Vec3) {
+        self.particles.push(SphParticle {
+            position,
+            previous_position: position - velocity * self.last_dt,
+            velocity,
+            density: self.config.rest_density,
+            pressure: 0.0,
+        });
+    }
+
+    /// Llenar una caja con partículas en reposo a la separación de reposo.
+    /// Devuelve las partículas añadidas
+    pub fn spawn_block(&mut self, min: Vec3, max: Vec3) -> usize {
+        let spacing = 2.0 * self.config.particle_radius;
+        if spacing <= 0.0 {
+            return 0;
+        }
+        let counts = ((max - min) / spacing).floor().max(Vec3::ZERO).as_uvec3() + 1;
+        for z in 0..counts.z {
+            for y in 0..counts.y {
+                for x in 0..counts.x {
+                    let offset = Vec3::new(x as f32, y as f32, z as f32) * spacing;
+                    self.add_particle(min + offset, Vec3::ZERO);
+                }
+            }
+        }
+        (counts.x * counts.y * counts.z) as usize
+    }
+
+    /// Número de partículas
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// No hay partículas
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Quitar todas las partículas
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.grid.clear();
+        self.neighbours.clear();
+        self.last_dt = 0.0;
+    }
+
+    /// Avanzar la simulación `dt` segundos en `config.substeps` subpasos.
+    /// `boundaries` son los colliders estáticos en posición de mundo
+    pub fn step(&mut self, dt: f32, gravity: Vec3, boundaries: &[(Isometry<Real>, SharedShape)]) {
+        if self.particles.is_empty() || dt <= 0.0 {
+            return;
+        }
+        let bounds: Vec<(Vec3, Vec3)> = boundaries.iter()
+            .map(|(position, shape)| {
+                let aabb = shape.compute_aabb(position);
+                (
+                    Vec3::new(aabb.mins.x, aabb.mins.y, aabb.mins.z),
+                    Vec3::new(aabb.maxs.x, aabb.maxs.y, aabb.maxs.z),
+                )
+            })
+            .collect();
+
+        let substeps = self.config.substeps.max(1);
+        let sub_dt = dt / substeps as f32;
+        for _ in 0..substeps {
+            self.build_neighbours();
+            self.compute_density_pressure();
+            let accelerations = self.compute_accelerations(gravity);
+            self.integrate(sub_dt, &accelerations);
+            self.resolve_boundaries(boundaries, &bounds);
+        }
+    }
+
+    /// Celda de la rejilla hash de un punto
+    fn cell(&self, position: Vec3) -> [i32; 3] {
+        let cell = (position / self.smoothing_radius()).floor();
+        [cell.x as i32, cell.y as i32, cell.z as i32]
+    }
+
+    /// Rellenar la rejilla y la lista de vecinos a menos de `h`
+    fn build_neighbours(&mut self) {
+        for cell in self.grid.values_mut() {
+            cell.clear();
+        }
+        for (index, particle) in self.particles.iter().enumerate() {
+            let cell = self.cell(particle.position);
+            self.grid.entry(cell).or_default().push(index);
+        }
+        self.grid.retain(|_, cell| !cell.is_empty());
+
+        let h2 = self.smoothing_radius().powi(2);
+        self.neighbours.resize_with(self.particles.len(), Vec::new);
+        for (index, particle) in self.particles.iter().enumerate() {
+            let mut neighbours = std::mem::take(&mut self.neighbours[index]);
+            neighbours.clear();
+            let [cx, cy, cz] = self.cell(particle.position);
+            for dz in -1..=1 {
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let Some(cell) = self.grid.get(&[cx + dx, cy + dy, cz + dz]) else {
+                            continue;
+                        };
+                        neighbours.extend(cell.iter().copied().filter(|&other| {
+                            other != index && self.particles[other].position.distance_squared(particle.position) < h2
+                        }));
+                    }
+                }
+            }
+            self.neighbours[index] = neighbours;
+        }
+    }
+
+    /// Densidad con el kernel poly6 (incluida la propia partícula) y presión
+    /// por la ecuación de estado. La presión negativa se descarta para que
+    /// las partículas de la superficie no se agrupen
+    fn compute_density_pressure(&mut self) {
+        let h = self.smoothing_radius();
+        let h2 = h * h;
+        let mass = self.particle_mass();
+        let poly6 = 315.0 / (64.0 * PI * h.powi(9));
+        for index in 0..self.particles.len() {
+            let position = self.particles[index].position;
+            let density = mass * poly6 * h2.powi(3)
+                + self.neighbours[index].iter()
+                    .map(|&other| {
+                        let r2 = self.particles[other].position.distance_squared(position);
+                        mass * poly6 * (h2 - r2).powi(3)
+                    })
+                    .sum::<f32>();
+            let particle = &mut self.particles[index];
+            particle.density = density;
+            particle.pressure = (self.config.stiffness * (density - self.config.rest_density)).max(0.0);
+        }
+    }
+
+    /// Aceleración de cada partícula: presión (gradiente spiky), viscosidad
+    /// (laplaciano del kernel de viscosidad), tensión superficial (campo de
+    /// color con el poly6) y gravedad
+    fn compute_accelerations(&self, gravity: Vec3) -> Vec<Vec3> {
+        let h = self.smoothing_radius();
+        let h2 = h * h;
+        let mass = self.particle_mass();
+        let spiky_gradient = -45.0 / (PI * h.powi(6));
+        let viscosity_laplacian = 45.0 / (PI * h.powi(6));
+        let poly6_gradient = -945.0 / (32.0 * PI * h.powi(9));
+
+        self.particles.iter().enumerate()
+            .map(|(index, particle)| {
+                let mut pressure = Vec3::ZERO;
+                let mut viscosity = Vec3::ZERO;
+                let mut color_gradient = Vec3::ZERO;
+                let mut color_laplacian = 0.0;
+                for &other in &self.neighbours[index] {
+                    let neighbour = &self.particles[other];
+                    let offset = particle.position - neighbour.position;
+                    let r = offset.length();
+                    let volume = mass / neighbour.density.max(1e-6);
+                    if r > 1e-6 {
+                        pressure -= offset / r * volume * (particle.pressure + neighbour.pressure) * 0.5
+                            * spiky_gradient * (h - r).powi(2);
+                    }
+                    viscosity += (neighbour.velocity - particle.velocity) * volume * viscosity_laplacian * (h - r);
+                    let w = h2 - r * r;
+                    color_gradient += offset * volume * poly6_gradient * w * w;
+                    color_laplacian += volume * poly6_gradient * w * (3.0 * h2 - 7.0 * r * r);
+                }
+
+                let mut force = pressure + viscosity * self.config.viscosity;
+                let normal_length = color_gradient.length();
+                if normal_length > SURFACE_THRESHOLD / h {
+                    force -= color_gradient / normal_length * color_laplacian * self.config.surface_tension;
+                }
+                force / particle.density.max(1e-6) + gravity
+            })
+            .collect()
+    }
+
+    /// Verlet con corrección por cambio de paso. En el primer subpaso se
+    /// parte de la velocidad de cada partícula
+    fn integrate(&mut self, dt: f32, accelerations: &[Vec3]) {
+        let last_dt = self.last_dt;
+        for (particle, acceleration) in self.particles.iter_mut().zip(accelerations) {
+            let displacement = if last_dt > 0.0 {
+                (particle.position - particle.previous_position) * (dt / last_dt)
+            } else {
+                particle.velocity * dt
+            };
+            let next = particle.position + displacement + *acceleration * dt * dt;
+            particle.previous_position = particle.position;
+            particle.position = next;
+            particle.velocity = (next - particle.previous_position) / dt;
+        }
+        self.last_dt = dt;
+    }
+
+    /// Sacar las partículas de los colliders y anular la velocidad que
+    /// llevan hacia ellos (sin rebote; la tangencial se conserva)
+    fn resolve_boundaries(&mut self, boundaries: &[(Isometry<Real>, SharedShape)], bounds: &[(Vec3, Vec3)]) {
+        let radius = self.config.particle_radius;
+        let dt = self.last_dt;
+        for particle in &mut self.particles {
+            for ((position, shape), (min, max)) in boundaries.iter().zip(bounds) {
+                if particle.position.cmplt(*min - radius).any() || particle.position.cmpgt(*max + radius).any() {
+                    continue;
+                }
+                let point = Point::new(particle.position.x, particle.position.y, particle.position.z);
+                let projection = shape.project_point(position, &point, false);
+                let surface = Vec3::new(projection.point.x, projection.point.y, projection.point.z);
+                let offset = particle.position - surface;
+                let distance = offset.length();
+                let normal = if projection.is_inside {
+                    -offset / distance.max(1e-6)
+                } else if distance < radius && distance > 1e-6 {
+                    offset / distance
+                } else {
+                    continue;
+                };
+
+                particle.position = surface + normal * radius;
+                let approach = particle.velocity.dot(normal);
+                if approach < 0.0 {
+                    particle.velocity -= normal * approach;
+                }
+                particle.previous_position = particle.position - particle.velocity * dt;
+            }
+        }
+    }
+
+    /// Malla de la superficie del fluido en espacio mundo: isosuperficie de
+    /// la mitad de la densidad en reposo, con normales del gradiente del campo
+    pub fn get_surface_mesh(&self) -> MeshComponent {
+        let mut mesh = MeshComponent {
+            mesh_id: FLUID_SURFACE_MESH_ID.to_string(),
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+            material_id: None,
+            lod_level: 0,
+            lod_indices: Vec::new(),
+            joint_indices: Vec::new(),
+            joint_weights: Vec::new(),
+            render_layers: RENDER_LAYER_DEFAULT,
+        };
+        if self.particles.is_empty() {
+            return mesh;
+        }
+
+        let h = self.smoothing_radius();
+        let mut grid: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+        let (mut min, mut max) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+        for (index, particle) in self.particles.iter().enumerate() {
+            grid.entry(self.cell(particle.position)).or_default().push(index);
+            min = min.min(particle.position);
+            max = max.max(particle.position);
+        }
+        let (min, max) = (min - Vec3::splat(h), max + Vec3::splat(h));
+        let extent = (max - min).max_element();
+        let spacing = self.config.particle_radius.max(extent / MAX_SURFACE_RESOLUTION as f32);
+        let samples = ((max - min) / spacing).ceil().as_uvec3() + 1;
+        let (nx, ny, nz) = (samples.x as usize, samples.y as usize, samples.z as usize);
+
+        let mass = self.particle_mass();
+        let poly6 = 315.0 / (64.0 * PI * h.powi(9));
+        let density_at = |point: Vec3| -> f32 {
+            let [cx, cy, cz] = self.cell(point);
+            let mut density = 0.0;
+            for dz in -1..=1 {
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        for &index in grid.get(&[cx + dx, cy + dy, cz + dz]).into_iter().flatten() {
+                            let r2 = self.particles[index].position.distance_squared(point);
+                            if r2 < h * h {
+                                density += mass * poly6 * (h * h - r2).powi(3);
+                            }
+                        }
+                    }
+                }
+            }
+            density
+        };
+        // La densidad baja hacia fuera: la normal es el gradiente cambiado de signo
+        let normal_at = |point: Vec3| -> Vec3 {
+            let e = spacing * 0.5;
+            let gradient = Vec3::new(
+                density_at(point + Vec3::X * e) - density_at(point - Vec3::X * e),
+                density_at(point + Vec3::Y * e) - density_at(point - Vec3::Y * e),
+                density_at(point + Vec3::Z * e) - density_at(point - Vec3::Z * e),
+            );
+            (-gradient).normalize_or_zero()
+        };
+
+        let point_at = |x: usize, y: usize, z: usize| min + Vec3::new(x as f32, y as f32, z as f32) * spacing;
+        let mut field = vec![0.0; nx * ny * nz];
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    field[(z * ny + y) * nx + x] = density_at(point_at(x, y, z));
+                }
+            }
+        }
+
+        let iso = self.config.rest_density * 0.5;
+        let mut triangles = Vec::new();
+        for z in 0..nz - 1 {
+            for y in 0..ny - 1 {
+                for x in 0..nx - 1 {
+                    let corners = CUBE_CORNERS.map(|[dx, dy, dz]| {
+                        let (cx, cy, cz) = (x + dx, y + dy, z + dz);
+                        (point_at(cx, cy, cz), field[(cz * ny + cy) * nx + cx])
+                    });
+                    for tetrahedron in CUBE_TETRAHEDRA {
+                        polygonise_tetrahedron(tetrahedron.map(|corner| corners[corner]), iso, &mut triangles);
+                    }
+                }
+            }
+        }
+
+        for triangle in triangles {
+            let normals = triangle.map(normal_at);
+            // El orden de los vértices sigue a la normal del campo
+            let face = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]);
+            let order = if face.dot(normals[0] + normals[1] + normals[2]) < 0.0 { [0, 2, 1] } else { [0, 1, 2] };
+            for corner in order {
+                let vertex = triangle[corner];
+                mesh.indices.push(mesh.vertices.len() as u32);
+                mesh.vertices.push(vertex);
+                mesh.normals.push(normals[corner]);
+                mesh.uvs.push(Vec3::new(vertex.x, vertex.z, 0.0));
+            }
+        }
+        mesh
+    }
+}
+
+/// Triángulos de la isosuperficie dentro de un tetraedro: uno si un vértice
+/// queda a un lado y los otros tres al otro, dos (un quad) si quedan dos y dos
+fn polygonise_tetrahedron(corners: [(Vec3, f32); 4], iso: f32, triangles: &mut Vec<[Vec3; 3]>) {
+    let (inside, outside): (Vec<usize>, Vec<usize>) = (0..4).partition(|&i| corners[i].1 >= iso);
+    let edge = |a: usize, b: usize| {
+        let ((pa, va), (pb, vb)) = (corners[a], corners[b]);
+        let t = if (vb - va).abs() > f32::EPSILON { ((iso - va) / (vb - va)).clamp(0.0, 1.0) } else { 0.5 };
+        pa.lerp(pb, t)
+    };
+    match (inside.as_slice(), outside.as_slice()) {
+        ([single], [a, b, c]) | ([a, b, c], [single]) => {
+            triangles.push([edge(*single, *a), edge(*single, *b), edge(*single, *c)]);
+        }
+        ([i0, i1], [o0, o1]) => {
+            let quad = [edge(*i0, *o0), edge(*i0, *o1), edge(*i1, *o1), edge(*i1, *o0)];
+            triangles.push([quad[0], quad[1], quad[2]]);
+            triangles.push([quad[0], quad[2], quad[3]]);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Caja abierta por arriba con el interior en x, z en [0, 0.6] y el
+    /// suelo en y = 0
+    fn open_box() -> Vec<(Isometry<Real>, SharedShape)> {
+        vec![
+            (Isometry::translation(0.3, -0.1, 0.3), SharedShape::cuboid(0.5, 0.1, 0.5)),
+            (Isometry::translation(-0.1, 1.0, 0.3), SharedShape::cuboid(0.1, 1.0, 0.5)),
+            (Isometry::translation(0.7, 1.0, 0.3), SharedShape::cuboid(0.1, 1.0, 0.5)),
+            (Isometry::translation(0.3, 1.0, -0.1), SharedShape::cuboid(0.5, 1.0, 0.1)),
+            (Isometry::translation(0.3, 1.0, 0.7), SharedShape::cuboid(0.5, 1.0, 0.1)),
+        ]
+    }
+
+    #[test]
+    fn dropped_particles_settle_at_the_bottom_of_a_box() {
+        let mut fluid = SphFluid::new(SphConfig::default());
+        // Bloque de 5x4x5 a un metro del suelo
+        let spawned = fluid.spawn_block(Vec3::new(0.1, 1.0, 0.1), Vec3::new(0.5, 1.3, 0.5));
+        assert_eq!(spawned, 100);
+
+        let boundaries = open_box();
+        let gravity = Vec3::new(0.0, -9.81, 0.0);
+        for _ in 0..240 {
+            fluid.step(1.0 / 60.0, gravity, &boundaries);
+        }
+
+        let radius = fluid.config.particle_radius;
+        for particle in &fluid.particles {
+            let position = particle.position;
+            assert!(position.is_finite(), "partícula no finita: {:?}", position);
+            // Dentro de la caja y por debajo de donde se soltaron
+            assert!(position.x >= -1e-3 && position.x <= 0.6 + 1e-3, "fuera de la caja: {:?}", position);
+            assert!(position.z >= -1e-3 && position.z <= 0.6 + 1e-3, "fuera de la caja: {:?}", position);
+            assert!(position.y >= radius - 1e-3, "atraviesa el suelo: {:?}", position);
+            assert!(position.y < 0.6, "no ha caído: {:?}", position);
+        }
+        // 100 partículas a 0.1 m de separación llenan unas tres capas de
+        // 6x6: el centro de masas queda cerca del fondo y en reposo
+        let count = fluid.len() as f32;
+        let mean_height = fluid.particles.iter().map(|particle| particle.position.y).sum::<f32>() / count;
+        let mean_speed = fluid.particles.iter().map(|particle| particle.velocity.length()).sum::<f32>() / count;
+        assert!(mean_height < 0.25, "altura media {}", mean_height);
+        assert!(mean_speed < 0.2, "velocidad media {}", mean_speed);
+    }
+}
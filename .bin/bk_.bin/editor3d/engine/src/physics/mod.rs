@@ -4,6 +4,14 @@
 //! Integra Rapier3D para simulación de física y soporte para física distribuida.
 
 pub mod distributed;
+pub mod authority;
+pub mod collision;
+pub mod triggers;
+pub mod fluid;
+pub mod cloth;
+pub mod sdf;
+pub mod terrain;
+pub mod character;
 
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -16,6 +24,20 @@ use anyhow::{Result, anyhow};
 use glam::{Vec3, Vec4, Mat4, Quat};
 use tokio::sync::mpsc;
 
+use crate::renderer::culling::Aabb;
+use crate::renderer::debug_draw::DebugDraw;
+
+/// Colores del debug draw: colliders estáticos, dinámicos, dormidos y
+/// sensores, y normales de contacto
+const DEBUG_STATIC_COLOR: Vec4 = Vec4::new(0.6, 0.6, 0.6, 1.0);
+const DEBUG_DYNAMIC_COLOR: Vec4 = Vec4::new(0.2, 1.0, 0.4, 1.0);
+const DEBUG_SLEEPING_COLOR: Vec4 = Vec4::new(0.3, 0.5, 1.0, 1.0);
+const DEBUG_SENSOR_COLOR: Vec4 = Vec4::new(1.0, 0.9, 0.2, 1.0);
+const DEBUG_CONTACT_COLOR: Vec4 = Vec4::new(1.0, 0.2, 0.2, 1.0);
+
+/// Longitud de las normales de contacto dibujadas
+const DEBUG_NORMAL_LENGTH: f32 = 0.25;
+
 /// Configuración del sistema de física
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PhysicsConfig {
@@ -29,6 +51,8 @@ pub struct PhysicsConfig {
     pub optimization_config: OptimizationConfig,
     /// Configuración de red
     pub network_config: NetworkConfig,
+    /// Configuración de autoridad en red
+    pub authority_config: authority::AuthorityConfig,
 }
 
 /// Sistema de física principal
@@ -43,8 +67,34 @@ pub struct PhysicsSystem {
     collisions: Arc<RwLock<Vec<Collision>>>,
     /// Fuerzas aplicadas
     forces: Arc<RwLock<Vec<AppliedForce>>>,
+    /// Autoridad de simulación en red
+    authority: authority::AuthorityManager,
+    /// Caché de descomposiciones convexas de mallas
+    decomposition_cache: collision::DecompositionCache,
+    /// Colliders estáticos sin cuerpo (terreno), por ID
+    static_colliders: HashMap<String, ColliderHandle>,
+    /// Campos de distancia de los colliders estáticos SDF
+    sdf_colliders: HashMap<ColliderHandle, sdf::SDFShape>,
+    /// Contactos con colliders SDF del último paso
+    sdf_contacts: Vec<Collision>,
+    /// Zonas de trigger
+    triggers: triggers::TriggerManager,
+    /// Masa de agua SPH
+    fluid: Option<fluid::SphFluid>,
+    /// Telas simuladas
+    cloths: HashMap<cloth::ClothId, cloth::ClothSimulator>,
+    /// Siguiente ID de tela
+    next_cloth_id: cloth::ClothId,
+    /// Controladores de personaje por entidad
+    character_controllers: HashMap<crate::ecs::EntityId, character::CharacterController>,
+    /// Sistema de eventos del ECS al que se envían los eventos de trigger
+    event_system: Option<Arc<RwLock<crate::ecs::EventSystem>>>,
     /// Estadísticas del sistema
     stats: PhysicsStats,
+    /// Dibujar los colliders en el debug draw
+    debug_colliders: bool,
+    /// Dibujar los puntos y normales de contacto en el debug draw
+    debug_contacts: bool,
     /// Estado del sistema
     running: bool,
 }
@@ -65,12 +115,25 @@ pub struct PhysicsWorld {
     pub broad_phase: BroadPhase,
     /// Narrow phase
     pub narrow_phase: NarrowPhase,
+    /// Consultas de escena (movimiento de los personajes)
+    pub query_pipeline: QueryPipeline,
     /// Physics hooks
     pub hooks: PhysicsHooks,
     /// Event handler
     pub events: EventHandler,
 }
 
+/// Impacto de un rayo
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// Punto de impacto
+    pub point: Vec3,
+    /// Normal de la superficie
+    pub normal: Vec3,
+    /// Distancia desde el origen
+    pub distance: f32,
+}
+
 /// Cuerpo de física
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PhysicsBody {
@@ -86,6 +149,12 @@ pub struct PhysicsBody {
     pub state: BodyState,
     /// Propiedades
     pub properties: BodyProperties,
+    /// Autoridad de simulación
+    #[serde(default)]
+    pub authority: authority::BodyAuthority,
+    /// Entidad del ECS asociada (necesaria para los eventos de trigger)
+    #[serde(default)]
+    pub entity: Option<crate::ecs::EntityId>,
 }
 
 /// Tipo de cuerpo
@@ -134,9 +203,49 @@ pub enum CollisionShape {
     Cylinder(f32, f32),
     Cone(f32, f32),
     Mesh(Vec<Vec3>),
+    /// Campo de alturas de `resolution` x `resolution` muestras (fila a
+    /// fila, de -Z a +Z) que cubre un cuadrado de `size` metros centrado en
+    /// la posición del collider
+    Heightfield { heights: Vec<f32>, resolution: usize, size: f32 },
+    /// Campo de distancia con signo (ver `sdf::SDFShape`). Solo para
+    /// colliders estáticos; choca con las esferas de los cuerpos dinámicos
+    SDF {
+        data: Vec<f32>,
+        grid_size: [u32; 3],
+        cell_size: f32,
+        /// Posición de la muestra (0, 0, 0) respecto al collider
+        #[serde(default)]
+        origin: Vec3,
+    },
     Custom(String),
 }
 
+impl CollisionShape {
+    /// Campo de distancia de una forma SDF
+    pub fn as_sdf(&self) -> Option<sdf::SDFShape> {
+        match self {
+            CollisionShape::SDF { data, grid_size, cell_size, origin } => Some(sdf::SDFShape {
+                data: data.clone(),
+                grid_size: *grid_size,
+                cell_size: *cell_size,
+                origin: *origin,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl From<sdf::SDFShape> for CollisionShape {
+    fn from(shape: sdf::SDFShape) -> Self {
+        CollisionShape::SDF {
+            data: shape.data,
+            grid_size: shape.grid_size,
+            cell_size: shape.cell_size,
+            origin: shape.origin,
+        }
+    }
+}
+
 /// Filtro de colisión
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CollisionFilter {
@@ -279,6 +388,15 @@ pub struct PhysicsStats {
     pub active_islands: usize,
     /// Cuerpos dormidos
     pub sleeping_bodies: usize,
+    /// Cuerpos simulados localmente por autoridad reclamada
+    pub locally_authoritative_bodies: usize,
+    /// Zonas de trigger registradas
+    pub trigger_zone_count: usize,
+    /// Partículas de fluido simuladas
+    pub fluid_particle_count: usize,
+    /// Partículas de tela simuladas
+    #[serde(default)]
+    pub cloth_particle_count: usize,
 }
 
 impl PhysicsSystem {
@@ -287,11 +405,22 @@ impl PhysicsSystem {
         info!("Inicializando sistema de física");
         
         Self {
+            authority: authority::AuthorityManager::new(config.authority_config.clone()),
             config,
             world: None,
             bodies: Arc::new(RwLock::new(HashMap::new())),
             collisions: Arc::new(RwLock::new(Vec::new())),
             forces: Arc::new(RwLock::new(Vec::new())),
+            decomposition_cache: collision::DecompositionCache::new(collision::DEFAULT_DECOMPOSITION_CACHE_SIZE),
+            static_colliders: HashMap::new(),
+            sdf_colliders: HashMap::new(),
+            sdf_contacts: Vec::new(),
+            triggers: triggers::TriggerManager::new(),
+            fluid: None,
+            cloths: HashMap::new(),
+            next_cloth_id: 1,
+            character_controllers: HashMap::new(),
+            event_system: None,
             stats: PhysicsStats {
                 body_count: 0,
                 collision_count: 0,
@@ -300,7 +429,13 @@ impl PhysicsSystem {
                 memory_usage: 0,
                 active_islands: 0,
                 sleeping_bodies: 0,
+                locally_authoritative_bodies: 0,
+                trigger_zone_count: 0,
+                fluid_particle_count: 0,
+                cloth_particle_count: 0,
             },
+            debug_colliders: false,
+            debug_contacts: false,
             running: false,
         }
     }
@@ -345,6 +480,9 @@ impl PhysicsSystem {
         // Crear narrow phase
         let narrow_phase = NarrowPhase::new();
 
+        // Crear pipeline de consultas
+        let query_pipeline = QueryPipeline::new();
+
         // Crear hooks
         let hooks = PhysicsHooks::new();
 
@@ -360,6 +498,7 @@ impl PhysicsSystem {
             islands,
             broad_phase,
             narrow_phase,
+            query_pipeline,
             hooks,
             events,
         });
@@ -392,12 +531,30 @@ impl PhysicsSystem {
 
         let start_time = std::time::Instant::now();
 
+        // Seguir cinemáticamente los cuerpos sin autoridad local
+        self.apply_authority_modes().await?;
+
+        // Mover los personajes antes del paso
+        self.move_characters(delta_time);
+
         // Simular física
         self.simulate_physics(delta_time).await?;
 
+        // Fluido SPH contra los colliders estáticos
+        self.update_fluid(delta_time);
+
+        // Telas contra los colliders y el terreno
+        self.update_cloths(delta_time);
+
+        // Publicar estados autoritativos y liberar cuerpos inactivos
+        self.update_authority(delta_time).await?;
+
         // Procesar colisiones
         self.process_collisions().await?;
 
+        // Entradas y salidas de zonas de trigger
+        self.update_triggers().await?;
+
         // Aplicar fuerzas
         self.apply_forces().await?;
 
@@ -436,6 +593,10 @@ impl PhysicsSystem {
                 &physics_hooks,
                 &event_handler,
             );
+            world.query_pipeline.update(&world.islands, &world.rigid_bodies, &world.colliders);
+
+            // Contactos de las esferas con los colliders SDF
+            self.resolve_sdf_contacts();
 
             // Actualizar estados de cuerpos
             self.update_body_states().await?;
@@ -444,6 +605,200 @@ impl PhysicsSystem {
         Ok(())
     }
 
+    /// Separar las esferas de los cuerpos dinámicos de los colliders SDF que
+    /// atraviesan (ver `sdf::resolve_sphere`)
+    fn resolve_sdf_contacts(&mut self) {
+        self.sdf_contacts.clear();
+        let Some(world) = &mut self.world else {
+            return;
+        };
+        if self.sdf_colliders.is_empty() {
+            return;
+        }
+
+        let surfaces: Vec<(&str, ColliderHandle)> = self.static_colliders.iter()
+            .filter(|(_, handle)| self.sdf_colliders.contains_key(*handle))
+            .map(|(id, handle)| (id.as_str(), *handle))
+            .collect();
+        let body_handles: Vec<RigidBodyHandle> = world.rigid_bodies.iter()
+            .filter(|(_, body)| body.is_dynamic() && !body.is_sleeping())
+            .map(|(handle, _)| handle)
+            .collect();
+
+        for body_handle in body_handles {
+            let Some(body) = world.rigid_bodies.get(body_handle) else {
+                continue;
+            };
+            let balls: Vec<(Vec3, f32, InteractionGroups, f32, f32)> = body.colliders().iter()
+                .filter_map(|handle| world.colliders.get(*handle))
+                .filter(|collider| !collider.is_sensor())
+                .filter_map(|collider| {
+                    let ball = collider.shape().as_ball()?;
+                    let center = collider.position().translation;
+                    Some((
+                        Vec3::new(center.x, center.y, center.z),
+                        ball.radius,
+                        collider.collision_groups(),
+                        collider.friction(),
+                        collider.restitution(),
+                    ))
+                })
+                .collect();
+
+            for (center, radius, groups, friction, restitution) in balls {
+                for (id, surface_handle) in &surfaces {
+                    let (Some(surface), Some(field)) = (world.colliders.get(*surface_handle), self.sdf_colliders.get(surface_handle)) else {
+                        continue;
+                    };
+                    if !groups.test(surface.collision_groups()) {
+                        continue;
+                    }
+                    let local = surface.position().inverse_transform_point(&Point::new(center.x, center.y, center.z));
+                    let rotation = surface.position().rotation;
+                    let Some(body) = world.rigid_bodies.get_mut(body_handle) else {
+                        continue;
+                    };
+                    let velocity = rotation.inverse() * *body.linvel();
+                    let (friction, restitution) = ((friction + surface.friction()) * 0.5, (restitution + surface.restitution()) * 0.5);
+                    let Some(contact) = sdf::resolve_sphere(
+                        field,
+                        Vec3::new(local.x, local.y, local.z),
+                        radius,
+                        Vec3::new(velocity.x, velocity.y, velocity.z),
+                        friction,
+                        restitution,
+                    ) else {
+                        continue;
+                    };
+
+                    let normal = rotation * Vector3::new(contact.normal.x, contact.normal.y, contact.normal.z);
+                    let translation = *body.translation() + normal * contact.penetration;
+                    body.set_translation(translation, true);
+
+                    let new_velocity = rotation * Vector3::new(contact.velocity.x, contact.velocity.y, contact.velocity.z);
+                    let change = new_velocity - *body.linvel();
+                    body.set_linvel(new_velocity, true);
+                    let impulse = Vec3::new(change.x, change.y, change.z) * body.mass();
+                    let (normal, penetration, distance) = (Vec3::new(normal.x, normal.y, normal.z), contact.penetration, contact.distance);
+
+                    self.sdf_contacts.push(Collision {
+                        id: format!("collision_{}_{}", body_handle.0, id),
+                        body1: format!("body_{}", body_handle.0),
+                        body2: id.to_string(),
+                        contact_point: center - normal * distance,
+                        normal,
+                        penetration,
+                        impulse,
+                        time: 0.0,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Recortar el movimiento pedido de cada personaje contra los colliders
+    /// y fijarlo como siguiente pose de su cuerpo cinemático
+    fn move_characters(&mut self, delta_time: f32) {
+        let Some(world) = &mut self.world else {
+            return;
+        };
+        let gravity = self.config.simulation_config.gravity.y;
+
+        for character in self.character_controllers.values_mut() {
+            let (translation, rotation) = character.take_move();
+            let Some(body) = world.rigid_bodies.get(character.body) else {
+                continue;
+            };
+            let Some(collider) = body.colliders().first().and_then(|handle| world.colliders.get(*handle)) else {
+                continue;
+            };
+
+            // La gravedad empuja hacia abajo cada frame para detectar el suelo
+            if character.config.gravity {
+                character.vertical_velocity += gravity * delta_time;
+            }
+            let desired = translation + Vec3::Y * character.vertical_velocity * delta_time;
+
+            let movement = character.config.to_rapier().move_shape(
+                delta_time,
+                &world.rigid_bodies,
+                &world.colliders,
+                &world.query_pipeline,
+                collider.shape(),
+                collider.position(),
+                Vector3::new(desired.x, desired.y, desired.z),
+                QueryFilter::default().exclude_rigid_body(character.body),
+                |_| {},
+            );
+            character.grounded = movement.grounded;
+            if character.grounded {
+                character.vertical_velocity = 0.0;
+            }
+
+            let current = body.rotation();
+            let current = Quat::from_xyzw(current.i, current.j, current.k, current.w);
+            let next_rotation = (rotation * current).normalize();
+            let next_rotation = nalgebra::UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(
+                next_rotation.w,
+                next_rotation.x,
+                next_rotation.y,
+                next_rotation.z,
+            ));
+            let next_translation = body.translation() + movement.translation;
+            if let Some(body) = world.rigid_bodies.get_mut(character.body) {
+                body.set_next_kinematic_position(Isometry3::from_parts(next_translation.into(), next_rotation));
+                character.moved = true;
+            }
+        }
+    }
+
+    /// Avanzar el fluido SPH. Choca con los colliders estáticos y con los de
+    /// los cuerpos fijos
+    fn update_fluid(&mut self, delta_time: f32) {
+        let (Some(fluid), Some(world)) = (&mut self.fluid, &self.world) else {
+            return;
+        };
+
+        let fixed = world.rigid_bodies.iter()
+            .filter(|(_, body)| body.is_fixed())
+            .flat_map(|(_, body)| body.colliders().iter().copied());
+        let boundaries: Vec<(Isometry<Real>, SharedShape)> = self.static_colliders.values().copied()
+            .chain(fixed)
+            .filter_map(|handle| world.colliders.get(handle))
+            .filter(|collider| !collider.is_sensor())
+            .map(|collider| (*collider.position(), collider.shared_shape().clone()))
+            .collect();
+
+        let gravity = self.config.simulation_config.gravity;
+        fluid.step(delta_time, Vec3::new(gravity.x, gravity.y, gravity.z), &boundaries);
+        self.stats.fluid_particle_count = fluid.len();
+    }
+
+    /// Avanzar las telas. Chocan con todos los colliders sólidos (sin
+    /// moverlos) y con los campos de distancia del terreno
+    fn update_cloths(&mut self, delta_time: f32) {
+        let Some(world) = &self.world else {
+            return;
+        };
+        if self.cloths.is_empty() {
+            return;
+        }
+
+        let boundaries: Vec<(Isometry<Real>, SharedShape)> = world.colliders.iter()
+            .filter(|(_, collider)| !collider.is_sensor())
+            .map(|(_, collider)| (*collider.position(), collider.shared_shape().clone()))
+            .collect();
+        let fields: Vec<(Isometry<Real>, &sdf::SDFShape)> = self.sdf_colliders.iter()
+            .filter_map(|(handle, field)| Some((*world.colliders.get(*handle)?.position(), field)))
+            .collect();
+
+        let gravity = self.config.simulation_config.gravity;
+        let gravity = Vec3::new(gravity.x, gravity.y, gravity.z);
+        for cloth in self.cloths.values_mut() {
+            cloth.step(delta_time, gravity, &boundaries, &fields);
+        }
+    }
+
     /// Actualizar estados de cuerpos
     async fn update_body_states(&mut self) -> Result<()> {
         if let Some(world) = &self.world {
@@ -468,6 +823,112 @@ impl PhysicsSystem {
         Ok(())
     }
 
+    /// Aplicar modo dinámico o de seguimiento cinemático según la autoridad
+    async fn apply_authority_modes(&mut self) -> Result<()> {
+        if !self.config.authority_config.enabled {
+            return Ok(());
+        }
+
+        if let Some(world) = &mut self.world {
+            let mut bodies = self.bodies.write().unwrap();
+
+            for (handle, body) in bodies.iter_mut() {
+                if !matches!(body.body_type, BodyType::Dynamic) {
+                    continue;
+                }
+
+                body.authority = self.authority.authority(&body.id);
+                let simulated_here = self.authority.simulates_locally(&body.id);
+
+                if let Some(rigid_body) = world.rigid_bodies.get_mut(*handle) {
+                    if simulated_here {
+                        if rigid_body.body_type() != RigidBodyType::Dynamic {
+                            rigid_body.set_body_type(RigidBodyType::Dynamic, true);
+                        }
+                    } else {
+                        // Copia no autoritativa: sigue la última pose replicada
+                        if rigid_body.body_type() != RigidBodyType::KinematicPositionBased {
+                            rigid_body.set_body_type(RigidBodyType::KinematicPositionBased, true);
+                        }
+                        if let Some(snapshot) = self.authority.remote_snapshot(&body.id) {
+                            let translation = Vector3::new(snapshot.position.x, snapshot.position.y, snapshot.position.z);
+                            let rotation = nalgebra::UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(
+                                snapshot.rotation.w,
+                                snapshot.rotation.x,
+                                snapshot.rotation.y,
+                                snapshot.rotation.z,
+                            ));
+                            rigid_body.set_next_kinematic_position(Isometry3::from_parts(translation.into(), rotation));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Actualizar autoridad tras el paso de simulación
+    async fn update_authority(&mut self, delta_time: f32) -> Result<()> {
+        if !self.config.authority_config.enabled {
+            return Ok(());
+        }
+
+        let snapshots: HashMap<String, authority::BodySnapshot> = {
+            let bodies = self.bodies.read().unwrap();
+            bodies.values()
+                .filter(|body| matches!(body.body_type, BodyType::Dynamic))
+                .map(|body| (body.id.clone(), authority::BodySnapshot {
+                    position: body.state.position,
+                    rotation: body.state.rotation,
+                    linear_velocity: body.state.linear_velocity,
+                    angular_velocity: body.state.angular_velocity,
+                }))
+                .collect()
+        };
+
+        self.authority.update(delta_time, &snapshots);
+        self.stats.locally_authoritative_bodies = self.authority.local_body_count();
+
+        Ok(())
+    }
+
+    /// Establecer identificador del peer local para la autoridad en red
+    pub fn set_local_peer(&mut self, peer: String) {
+        self.authority.set_local_peer(peer);
+    }
+
+    /// Reclamar la simulación de los cuerpos dinámicos cercanos al avatar
+    pub fn claim_nearby_bodies(&mut self, avatar_position: Vec3) -> Vec<String> {
+        let candidates: Vec<(String, Vec3)> = {
+            let bodies = self.bodies.read().unwrap();
+            bodies.values()
+                .filter(|body| matches!(body.body_type, BodyType::Dynamic))
+                .map(|body| (body.id.clone(), body.state.position))
+                .collect()
+        };
+
+        candidates.into_iter()
+            .filter(|(body_id, position)| self.authority.try_claim(body_id, avatar_position, *position))
+            .map(|(body_id, _)| body_id)
+            .collect()
+    }
+
+    /// Obtener autoridad de un cuerpo
+    pub fn get_body_authority(&self, body_id: &str) -> authority::BodyAuthority {
+        self.authority.authority(body_id)
+    }
+
+    /// Procesar mensaje de autoridad recibido de la red
+    pub fn handle_authority_message(&mut self, message: authority::AuthorityMessage) {
+        self.authority.handle_message(message);
+    }
+
+    /// Extraer mensajes de autoridad para enviar por la red
+    pub fn drain_authority_messages(&mut self) -> Vec<authority::AuthorityMessage> {
+        self.authority.drain_outbox()
+    }
+
     /// Procesar colisiones
     async fn process_collisions(&mut self) -> Result<()> {
         if let Some(world) = &self.world {
@@ -492,12 +953,153 @@ impl PhysicsSystem {
                 }
             }
 
+            collisions.extend(self.sdf_contacts.iter().cloned());
             self.stats.collision_count = collisions.len();
         }
 
         Ok(())
     }
 
+    /// Probar las zonas de trigger contra los cuerpos dinámicos con entidad
+    /// y enviar los eventos de entrada y salida al ECS
+    async fn update_triggers(&mut self) -> Result<()> {
+        if self.triggers.is_empty() {
+            return Ok(());
+        }
+        let Some(world) = &self.world else {
+            return Ok(());
+        };
+
+        let candidates: Vec<triggers::TriggerCandidate> = {
+            let bodies = self.bodies.read().unwrap();
+            bodies.iter()
+                .filter(|(_, body)| matches!(body.body_type, BodyType::Dynamic))
+                .filter_map(|(handle, body)| {
+                    let entity = body.entity?;
+                    let rigid_body = world.rigid_bodies.get(*handle)?;
+                    let colliders = rigid_body.colliders().iter()
+                        .filter_map(|collider| world.colliders.get(*collider))
+                        .map(|collider| (*collider.position(), collider.shared_shape().clone()))
+                        .collect();
+                    Some(triggers::TriggerCandidate {
+                        entity,
+                        groups: body.config.collision_config.filter.groups,
+                        colliders,
+                    })
+                })
+                .collect()
+        };
+        self.triggers.update(&candidates);
+
+        if let Some(event_system) = &self.event_system {
+            let events: Vec<_> = self.triggers.drain_events().iter()
+                .map(|event| self.triggers.to_ecs_event(event))
+                .collect();
+            let mut event_system = event_system.write().await;
+            for event in events {
+                event_system.emit(event).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Conectar el sistema de eventos del ECS que recibe `TriggerEntered` y
+    /// `TriggerExited`. Sin él los eventos se acumulan hasta `drain_trigger_events`
+    pub fn set_event_system(&mut self, events: Arc<RwLock<crate::ecs::EventSystem>>) {
+        self.event_system = Some(events);
+    }
+
+    /// Registrar una zona de trigger
+    pub fn add_trigger_zone(&mut self, zone: triggers::TriggerZone) -> Result<triggers::TriggerZoneId> {
+        let collider = self.create_collider(&CollisionConfig {
+            shape: zone.shape.clone(),
+            filter: CollisionFilter { groups: u32::MAX, masks: zone.filter_mask, exceptions: Vec::new() },
+            material: CollisionMaterial { friction: 0.0, restitution: 0.0, density: 0.0 },
+        })?;
+        let id = self.triggers.add(zone, collider.shared_shape().clone());
+        self.stats.trigger_zone_count = self.triggers.len();
+        Ok(id)
+    }
+
+    /// Quitar una zona de trigger. No genera eventos de salida
+    pub fn remove_trigger_zone(&mut self, id: triggers::TriggerZoneId) -> Option<triggers::TriggerZone> {
+        let zone = self.triggers.remove(id);
+        self.stats.trigger_zone_count = self.triggers.len();
+        zone
+    }
+
+    /// Entidades dentro de una zona en el último paso
+    pub fn get_entities_in_zone(&self, id: triggers::TriggerZoneId) -> Vec<crate::ecs::EntityId> {
+        self.triggers.entities_in(id)
+    }
+
+    /// Extraer los eventos de trigger no enviados al ECS
+    pub fn drain_trigger_events(&mut self) -> Vec<triggers::TriggerEvent> {
+        self.triggers.drain_events()
+    }
+
+    /// Crear la masa de agua SPH (reemplaza la anterior)
+    pub fn set_fluid(&mut self, fluid: fluid::SphFluid) {
+        self.stats.fluid_particle_count = fluid.len();
+        self.fluid = Some(fluid);
+    }
+
+    /// Quitar la masa de agua
+    pub fn remove_fluid(&mut self) -> Option<fluid::SphFluid> {
+        self.stats.fluid_particle_count = 0;
+        self.fluid.take()
+    }
+
+    /// Masa de agua SPH
+    pub fn get_fluid(&self) -> Option<&fluid::SphFluid> {
+        self.fluid.as_ref()
+    }
+
+    /// Masa de agua SPH (mutable, para añadir partículas)
+    pub fn get_fluid_mut(&mut self) -> Option<&mut fluid::SphFluid> {
+        self.fluid.as_mut()
+    }
+
+    /// Crear una tela a partir de una malla: restricciones de distancia en
+    /// las aristas y las diagonales y de flexión entre triángulos vecinos
+    pub fn create_cloth(&mut self, mesh: &crate::ecs::MeshComponent, config: cloth::ClothConfig) -> cloth::ClothId {
+        let id = self.next_cloth_id;
+        self.next_cloth_id += 1;
+        let simulator = cloth::ClothSimulator::from_mesh(mesh, config);
+        self.stats.cloth_particle_count += simulator.len();
+        self.cloths.insert(id, simulator);
+        id
+    }
+
+    /// Quitar una tela
+    pub fn remove_cloth(&mut self, id: cloth::ClothId) -> Option<cloth::ClothSimulator> {
+        let simulator = self.cloths.remove(&id)?;
+        self.stats.cloth_particle_count -= simulator.len();
+        Some(simulator)
+    }
+
+    /// Vértices de una tela en espacio de su malla tras el último paso
+    pub fn get_cloth_positions(&self, id: cloth::ClothId) -> &[Vec3] {
+        self.cloths.get(&id).map(|cloth| cloth.positions()).unwrap_or(&[])
+    }
+
+    /// Tela simulada
+    pub fn get_cloth(&self, id: cloth::ClothId) -> Option<&cloth::ClothSimulator> {
+        self.cloths.get(&id)
+    }
+
+    /// Tela simulada (mutable, p. ej. para cambiar el viento)
+    pub fn get_cloth_mut(&mut self, id: cloth::ClothId) -> Option<&mut cloth::ClothSimulator> {
+        self.cloths.get_mut(&id)
+    }
+
+    /// Telas que siguen a una entidad
+    pub fn entity_cloths(&self) -> Vec<(crate::ecs::EntityId, cloth::ClothId)> {
+        self.cloths.iter()
+            .filter_map(|(id, cloth)| Some((cloth.config.entity_id?, *id)))
+            .collect()
+    }
+
     /// Aplicar fuerzas
     async fn apply_forces(&mut self) -> Result<()> {
         if let Some(world) = &mut self.world {
@@ -530,6 +1132,17 @@ impl PhysicsSystem {
 
     /// Crear cuerpo
     pub async fn create_body(&mut self, body: PhysicsBody) -> Result<RigidBodyHandle> {
+        if self.world.is_none() {
+            return Err(anyhow!("Mundo de física no inicializado"));
+        }
+
+        if matches!(body.config.collision_config.shape, CollisionShape::SDF { .. }) {
+            return Err(anyhow!("Las formas SDF solo pueden usarse en colliders estáticos"));
+        }
+
+        // Crear collider (las mallas se descomponen en piezas convexas)
+        let collider = self.create_collider(&body.config.collision_config)?;
+
         if let Some(world) = &mut self.world {
             // Crear configuración de cuerpo rígido
             let rigid_body = match body.body_type {
@@ -542,9 +1155,6 @@ impl PhysicsSystem {
             .rotation(body.config.initial_rotation.into())
             .build();
 
-            // Crear collider
-            let collider = self.create_collider(&body.config.collision_config)?;
-
             // Insertar en el mundo
             let handle = world.rigid_bodies.insert(rigid_body);
             world.colliders.insert_with_parent(
@@ -553,6 +1163,11 @@ impl PhysicsSystem {
                 &mut world.rigid_bodies,
             );
 
+            // Registrar autoridad de red
+            if matches!(body.body_type, BodyType::Dynamic) {
+                self.authority.register_body(&body.id);
+            }
+
             // Agregar a la lista de cuerpos
             let mut bodies = self.bodies.write().unwrap();
             bodies.insert(handle, body);
@@ -565,7 +1180,7 @@ impl PhysicsSystem {
     }
 
     /// Crear collider
-    fn create_collider(&self, config: &CollisionConfig) -> Result<Collider> {
+    fn create_collider(&mut self, config: &CollisionConfig) -> Result<Collider> {
         let collider = match &config.shape {
             CollisionShape::Box(size) => ColliderBuilder::cuboid(size.x / 2.0, size.y / 2.0, size.z / 2.0),
             CollisionShape::Sphere(radius) => ColliderBuilder::ball(*radius),
@@ -573,11 +1188,36 @@ impl PhysicsSystem {
             CollisionShape::Cylinder(radius, height) => ColliderBuilder::cylinder(height / 2.0, *radius),
             CollisionShape::Cone(radius, height) => ColliderBuilder::cone_y(height / 2.0, *radius),
             CollisionShape::Mesh(vertices) => {
-                // Crear mesh de colisión
-                let points: Vec<Point<f32>> = vertices.iter()
-                    .map(|v| Point::new(v.x, v.y, v.z))
-                    .collect();
-                ColliderBuilder::trimesh(points, vec![])
+                // La malla es una sopa de triángulos: collider compuesto de piezas convexas
+                let indices = collision::triangle_soup_indices(vertices.len());
+                let hulls = self.decomposition_cache.get_or_compute(
+                    vertices,
+                    &indices,
+                    collision::DEFAULT_MAX_CONVEX_HULLS,
+                );
+                collision::compound_collider(&hulls)
+                    .ok_or_else(|| anyhow!("No se pudo descomponer la malla de colisión"))?
+            }
+            CollisionShape::Heightfield { heights, resolution, size } => {
+                if *resolution < 2 || heights.len() != resolution * resolution {
+                    return Err(anyhow!("Campo de alturas inválido: {} muestras para {}x{}", heights.len(), resolution, resolution));
+                }
+                // Las filas del campo de Rapier avanzan en Z y las columnas en X
+                let matrix = nalgebra::DMatrix::from_fn(*resolution, *resolution, |row, column| heights[row * resolution + column]);
+                ColliderBuilder::heightfield(matrix, Vector3::new(*size, 1.0, *size))
+            }
+            CollisionShape::SDF { data, grid_size, cell_size, origin } => {
+                // Rapier solo ve la caja de la rejilla como sensor; el
+                // contacto lo resuelve `resolve_sdf_contacts`
+                let field = sdf::SDFShape { data: data.clone(), grid_size: *grid_size, cell_size: *cell_size, origin: *origin };
+                field.validate().map_err(|e| anyhow!(e))?;
+                let (min, max) = field.bounds();
+                let (center, half) = ((min + max) * 0.5, (max - min) * 0.5);
+                ColliderBuilder::compound(vec![(
+                    Isometry::translation(center.x, center.y, center.z),
+                    SharedShape::cuboid(half.x, half.y, half.z),
+                )])
+                .sensor(true)
             }
             CollisionShape::Custom(_) => ColliderBuilder::ball(1.0), // Default
         }
@@ -593,6 +1233,121 @@ impl PhysicsSystem {
         Ok(collider)
     }
 
+    /// Registrar un collider estático sin cuerpo (p. ej. un trozo de
+    /// terreno). Entra en la broad phase en el siguiente paso. Un ID ya
+    /// registrado se reemplaza
+    pub fn add_static_collider(&mut self, id: &str, config: &CollisionConfig, position: Vec3) -> Result<ColliderHandle> {
+        if self.world.is_none() {
+            return Err(anyhow!("Mundo de física no inicializado"));
+        }
+        self.remove_static_collider(id);
+
+        let mut collider = self.create_collider(config)?;
+        collider.set_translation(position.into());
+        let world = self.world.as_mut().ok_or_else(|| anyhow!("Mundo de física no inicializado"))?;
+        let handle = world.colliders.insert(collider);
+        self.static_colliders.insert(id.to_string(), handle);
+        if let Some(field) = config.shape.as_sdf() {
+            self.sdf_colliders.insert(handle, field);
+        }
+        Ok(handle)
+    }
+
+    /// Quitar un collider estático. Devuelve false si no existía
+    pub fn remove_static_collider(&mut self, id: &str) -> bool {
+        let Some(handle) = self.static_colliders.remove(id) else {
+            return false;
+        };
+        self.sdf_colliders.remove(&handle);
+        if let Some(world) = &mut self.world {
+            world.colliders.remove(handle, &mut world.islands, &mut world.rigid_bodies, false);
+        }
+        true
+    }
+
+    /// Número de colliders estáticos registrados
+    pub fn static_collider_count(&self) -> usize {
+        self.static_colliders.len()
+    }
+
+    /// Lanzar un rayo contra los colliders sólidos (sin sensores) con las
+    /// consultas del último paso. Con `exclude` se ignora el cuerpo del
+    /// personaje de esa entidad, p. ej. al buscar el suelo bajo sus pies
+    pub fn cast_ray(&self, origin: Vec3, direction: Vec3, max_distance: f32, exclude: Option<crate::ecs::EntityId>) -> Option<RayHit> {
+        let world = self.world.as_ref()?;
+        let direction = direction.try_normalize()?;
+        let mut filter = QueryFilter::default().exclude_sensors();
+        if let Some(character) = exclude.and_then(|entity| self.character_controllers.get(&entity)) {
+            filter = filter.exclude_rigid_body(character.body);
+        }
+        let ray = Ray::new(point![origin.x, origin.y, origin.z], vector![direction.x, direction.y, direction.z]);
+        let (_, intersection) = world.query_pipeline.cast_ray_and_get_normal(
+            &world.rigid_bodies,
+            &world.colliders,
+            &ray,
+            max_distance,
+            true,
+            filter,
+        )?;
+        Some(RayHit {
+            point: origin + direction * intersection.toi,
+            normal: Vec3::new(intersection.normal.x, intersection.normal.y, intersection.normal.z),
+            distance: intersection.toi,
+        })
+    }
+
+    /// Obstáculos sólidos entre dos puntos para la oclusión del audio:
+    /// cuántos colliders corta el segmento y cuántos metros recorre dentro
+    /// de ellos. Se ignoran los colliders que contienen alguno de los
+    /// extremos (el objeto que suena o el cuerpo del oyente)
+    pub fn measure_occlusion(&self, from: Vec3, to: Vec3) -> (u32, f32) {
+        let Some(world) = &self.world else {
+            return (0, 0.0);
+        };
+        let length = from.distance(to);
+        let Some(direction) = (to - from).try_normalize() else {
+            return (0, 0.0);
+        };
+        let (start, end) = (point![from.x, from.y, from.z], point![to.x, to.y, to.z]);
+        let ray = Ray::new(start, vector![direction.x, direction.y, direction.z]);
+        let reverse = Ray::new(end, vector![-direction.x, -direction.y, -direction.z]);
+
+        let mut hits = Vec::new();
+        world.query_pipeline.intersections_with_ray(
+            &world.rigid_bodies,
+            &world.colliders,
+            &ray,
+            length,
+            true,
+            QueryFilter::default().exclude_sensors(),
+            |handle, _| {
+                hits.push(handle);
+                true
+            },
+        );
+
+        let (mut occluders, mut thickness) = (0, 0.0);
+        for handle in hits {
+            let Some(collider) = world.colliders.get(handle) else {
+                continue;
+            };
+            let (shape, position) = (collider.shape(), collider.position());
+            if shape.contains_point(position, &start) || shape.contains_point(position, &end) {
+                continue;
+            }
+            // Entrada por el rayo de ida y salida por el de vuelta
+            let (Some(entry), Some(exit)) = (
+                shape.cast_ray(position, &ray, length, true),
+                shape.cast_ray(position, &reverse, length, true),
+            ) else {
+                continue;
+            };
+            occluders += 1;
+            thickness += (length - exit - entry).max(0.0);
+        }
+        (occluders, thickness)
+    }
+
     /// Obtener handle de cuerpo
     fn get_body_handle(&self, body_id: &str) -> Option<RigidBodyHandle> {
         let bodies = self.bodies.read().unwrap();
@@ -616,6 +1371,86 @@ impl PhysicsSystem {
         Ok(())
     }
 
+    /// Elegir qué emite `draw_debug`: colliders y contactos
+    pub fn set_debug_draw(&mut self, colliders: bool, contacts: bool) {
+        self.debug_colliders = colliders;
+        self.debug_contacts = contacts;
+    }
+
+    /// Emitir al debug draw los colliders (color según el estado del cuerpo)
+    /// y los puntos de contacto con su normal
+    pub fn draw_debug(&self, draw: &mut DebugDraw) {
+        if !(self.debug_colliders || self.debug_contacts) || !draw.is_enabled() {
+            return;
+        }
+        let Some(world) = &self.world else {
+            return;
+        };
+
+        if self.debug_colliders {
+            for (_, collider) in world.colliders.iter() {
+                let body = collider.parent().and_then(|handle| world.rigid_bodies.get(handle));
+                let color = if collider.is_sensor() {
+                    DEBUG_SENSOR_COLOR
+                } else {
+                    match body {
+                        Some(body) if body.is_sleeping() => DEBUG_SLEEPING_COLOR,
+                        Some(body) if body.is_dynamic() => DEBUG_DYNAMIC_COLOR,
+                        _ => DEBUG_STATIC_COLOR,
+                    }
+                };
+                let position = collider.position();
+                let rotation = position.rotation;
+                let transform = Mat4::from_rotation_translation(
+                    Quat::from_xyzw(rotation.i, rotation.j, rotation.k, rotation.w),
+                    Vec3::new(position.translation.x, position.translation.y, position.translation.z),
+                );
+                match collider.shape().as_typed_shape() {
+                    TypedShape::Ball(ball) => {
+                        draw.sphere(transform.transform_point3(Vec3::ZERO), ball.radius, color);
+                    }
+                    TypedShape::Cuboid(cuboid) => {
+                        let half = cuboid.half_extents;
+                        draw.oriented_box(&transform, Vec3::new(half.x, half.y, half.z), color);
+                    }
+                    TypedShape::Capsule(capsule) => {
+                        let (a, b) = (capsule.segment.a, capsule.segment.b);
+                        draw.capsule(
+                            transform.transform_point3(Vec3::new(a.x, a.y, a.z)),
+                            transform.transform_point3(Vec3::new(b.x, b.y, b.z)),
+                            capsule.radius,
+                            color,
+                        );
+                    }
+                    // Mallas, heightfields y compuestos: su caja en mundo
+                    _ => {
+                        let aabb = collider.compute_aabb();
+                        draw.aabb(
+                            &Aabb::new(
+                                Vec3::new(aabb.mins.x, aabb.mins.y, aabb.mins.z),
+                                Vec3::new(aabb.maxs.x, aabb.maxs.y, aabb.maxs.z),
+                            ),
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+
+        if self.debug_contacts {
+            for pair in world.narrow_phase.contact_pairs().filter(|pair| pair.has_any_active_contact) {
+                for manifold in &pair.manifolds {
+                    let normal = manifold.data.normal;
+                    let normal = Vec3::new(normal.x, normal.y, normal.z);
+                    for contact in &manifold.data.solver_contacts {
+                        let point = Vec3::new(contact.point.x, contact.point.y, contact.point.z);
+                        draw.line(point, point + normal * DEBUG_NORMAL_LENGTH, DEBUG_CONTACT_COLOR);
+                    }
+                }
+            }
+        }
+    }
+
     /// Obtener colisiones
     pub fn get_collisions(&self) -> Vec<Collision> {
         let collisions = self.collisions.read().unwrap();
@@ -640,6 +1475,69 @@ impl PhysicsSystem {
         self.stats.memory_usage = std::mem::size_of_val(self);
     }
 
+    /// Controlar con un personaje la entidad de un cuerpo cinemático
+    pub fn add_character_controller(
+        &mut self,
+        entity: crate::ecs::EntityId,
+        body_id: &str,
+        config: character::CharacterControllerConfig,
+    ) -> Result<()> {
+        let body = self.get_body(body_id).ok_or_else(|| anyhow!("Cuerpo no encontrado: {}", body_id))?;
+        if !matches!(body.body_type, BodyType::Kinematic) {
+            return Err(anyhow!("El controlador de personaje necesita un cuerpo cinemático: {}", body_id));
+        }
+        let handle = self.get_body_handle(body_id).ok_or_else(|| anyhow!("Cuerpo no encontrado: {}", body_id))?;
+        self.character_controllers.insert(entity, character::CharacterController::new(entity, handle, config));
+        Ok(())
+    }
+
+    /// Quitar el controlador de personaje de una entidad
+    pub fn remove_character_controller(&mut self, entity: crate::ecs::EntityId) -> Option<character::CharacterController> {
+        self.character_controllers.remove(&entity)
+    }
+
+    /// La entidad tiene controlador de personaje
+    pub fn has_character_controller(&self, entity: crate::ecs::EntityId) -> bool {
+        self.character_controllers.contains_key(&entity)
+    }
+
+    /// Pedir un movimiento al personaje de una entidad: traslación en su
+    /// espacio local (se orienta con el giro horizontal del cuerpo) y giro.
+    /// Se aplica en el siguiente paso; false si la entidad no tiene personaje
+    pub fn move_character(&mut self, entity: crate::ecs::EntityId, translation: Vec3, rotation: Quat) -> bool {
+        let Some(character) = self.character_controllers.get_mut(&entity) else {
+            return false;
+        };
+        let yaw = self.world.as_ref()
+            .and_then(|world| world.rigid_bodies.get(character.body))
+            .map(|body| {
+                let rotation = body.rotation();
+                Quat::from_xyzw(rotation.i, rotation.j, rotation.k, rotation.w).to_euler(glam::EulerRot::YXZ).0
+            })
+            .unwrap_or(0.0);
+        character.push_move(Quat::from_rotation_y(yaw) * translation, rotation);
+        true
+    }
+
+    /// Poses de los personajes que se movieron en el último paso
+    pub fn drain_character_moves(&mut self) -> Vec<(crate::ecs::EntityId, Vec3, Quat)> {
+        let Some(world) = &self.world else {
+            return Vec::new();
+        };
+        self.character_controllers.values_mut()
+            .filter(|character| std::mem::take(&mut character.moved))
+            .filter_map(|character| {
+                let body = world.rigid_bodies.get(character.body)?;
+                let (position, rotation) = (body.translation(), body.rotation());
+                Some((
+                    character.entity,
+                    Vec3::new(position.x, position.y, position.z),
+                    Quat::from_xyzw(rotation.i, rotation.j, rotation.k, rotation.w),
+                ))
+            })
+            .collect()
+    }
+
     /// Obtener estadísticas
     pub fn get_stats(&self) -> PhysicsStats {
         self.stats.clone()
@@ -654,6 +1552,16 @@ impl PhysicsSystem {
         self.bodies.write().unwrap().clear();
         self.collisions.write().unwrap().clear();
         self.forces.write().unwrap().clear();
+        self.static_colliders.clear();
+        self.sdf_colliders.clear();
+        self.sdf_contacts.clear();
+        self.triggers.clear();
+        self.fluid = None;
+        self.stats.fluid_particle_count = 0;
+        self.cloths.clear();
+        self.stats.cloth_particle_count = 0;
+        self.character_controllers.clear();
+        self.event_system = None;
         
         info!("Sistema de física limpiado");
         Ok(())
@@ -0,0 +1,187 @@
+//! # Campos de distancia con signo
+//!
+//! Colisión contra superficies suaves (terreno esculpido, cuevas) descritas
+//! por un campo de distancia con signo muestreado en una rejilla regular.
+//! Rapier no conoce estas formas: el collider que se registra en el mundo es
+//! un sensor con la caja de la rejilla y el contacto con las esferas
+//! dinámicas se resuelve de forma analítica tras cada paso de simulación.
+//!
+//! La distancia es positiva fuera del sólido y negativa dentro. Entre
+//! muestras se interpola trilinealmente y la normal es el gradiente del campo
+//! por diferencias centradas, de modo que varía de forma continua y las
+//! esferas ruedan sin los saltos de las aristas de un heightfield.
+
+use glam::{UVec3, Vec3};
+use serde::{Serialize, Deserialize};
+
+/// Campo de distancia con signo en una rejilla regular
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SDFShape {
+    /// Distancias, con X variando más rápido, luego Y y luego Z
+    pub data: Vec<f32>,
+    /// Muestras por eje
+    pub grid_size: [u32; 3],
+    /// Separación entre muestras en metros
+    pub cell_size: f32,
+    /// Posición de la muestra (0, 0, 0) respecto al collider
+    #[serde(default)]
+    pub origin: Vec3,
+}
+
+impl SDFShape {
+    /// Comprobar que la rejilla tiene al menos 2 muestras por eje y tantos
+    /// datos como muestras
+    pub fn validate(&self) -> Result<(), String> {
+        if self.grid_size.iter().any(|&size| size < 2) {
+            return Err(format!("Rejilla SDF inválida: {:?}", self.grid_size));
+        }
+        let samples = self.grid_size.iter().map(|&size| size as usize).product::<usize>();
+        if self.data.len() != samples {
+            return Err(format!("Campo SDF inválido: {} muestras para {:?}", self.data.len(), self.grid_size));
+        }
+        if !self.cell_size.is_finite() || self.cell_size <= 0.0 {
+            return Err(format!("Tamaño de celda SDF inválido: {}", self.cell_size));
+        }
+        Ok(())
+    }
+
+    /// Tamaño de la rejilla en metros
+    pub fn extent(&self) -> Vec3 {
+        (UVec3::from(self.grid_size) - UVec3::ONE).as_vec3() * self.cell_size
+    }
+
+    /// Esquinas de la rejilla respecto al collider
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        (self.origin, self.origin + self.extent())
+    }
+
+    /// Distancia guardada en una muestra (índices ya limitados a la rejilla)
+    fn at(&self, x: u32, y: u32, z: u32) -> f32 {
+        let [size_x, size_y, _] = self.grid_size;
+        self.data[((z * size_y + y) * size_x + x) as usize]
+    }
+
+    /// Distancia interpolada trilinealmente. Fuera de la rejilla se suma la
+    /// distancia al borde, que nunca subestima la distancia real
+    fn distance(&self, point: Vec3) -> f32 {
+        let (min, max) = self.bounds();
+        let clamped = point.clamp(min, max);
+        let outside = point.distance(clamped);
+
+        let max_cell = UVec3::from(self.grid_size) - UVec3::splat(2);
+        let cell_position = (clamped - self.origin) / self.cell_size;
+        let cell = cell_position.floor().as_uvec3().min(max_cell);
+        let t = cell_position - cell.as_vec3();
+
+        let sample = |dx: u32, dy: u32, dz: u32| self.at(cell.x + dx, cell.y + dy, cell.z + dz);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let x00 = lerp(sample(0, 0, 0), sample(1, 0, 0), t.x);
+        let x10 = lerp(sample(0, 1, 0), sample(1, 1, 0), t.x);
+        let x01 = lerp(sample(0, 0, 1), sample(1, 0, 1), t.x);
+        let x11 = lerp(sample(0, 1, 1), sample(1, 1, 1), t.x);
+        let inside = lerp(lerp(x00, x10, t.y), lerp(x01, x11, t.y), t.z);
+
+        inside + outside
+    }
+}
+
+/// Distancia con signo y normal de la superficie en un punto del espacio del
+/// collider. La normal apunta hacia fuera del sólido; donde el gradiente se
+/// anula se devuelve +Y
+pub fn evaluate(sdf: &SDFShape, point: Vec3) -> (f32, Vec3) {
+    let distance = sdf.distance(point);
+
+    // Gradiente por diferencias centradas a media celda
+    let h = sdf.cell_size * 0.5;
+    let gradient = Vec3::new(
+        sdf.distance(point + Vec3::X * h) - sdf.distance(point - Vec3::X * h),
+        sdf.distance(point + Vec3::Y * h) - sdf.distance(point - Vec3::Y * h),
+        sdf.distance(point + Vec3::Z * h) - sdf.distance(point - Vec3::Z * h),
+    );
+    (distance, gradient.try_normalize().unwrap_or(Vec3::Y))
+}
+
+/// Contacto de una esfera con un SDF, en el espacio del collider
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SphereContact {
+    /// Normal de la superficie hacia fuera del sólido
+    pub normal: Vec3,
+    /// Distancia con signo del centro a la superficie
+    pub distance: f32,
+    /// Profundidad con que la esfera atraviesa la superficie
+    pub penetration: f32,
+    /// Velocidad de la esfera tras el contacto
+    pub velocity: Vec3,
+}
+
+/// Resolver una esfera contra el campo. None si no lo toca. La esfera debe
+/// desplazarse `normal * penetration` para quedar sobre la superficie; la
+/// velocidad normal se refleja con la restitución y la tangencial se frena
+/// por fricción de Coulomb con el mismo impulso
+pub fn resolve_sphere(sdf: &SDFShape, center: Vec3, radius: f32, velocity: Vec3, friction: f32, restitution: f32) -> Option<SphereContact> {
+    let (distance, normal) = evaluate(sdf, center);
+    let penetration = radius - distance;
+    if penetration <= 0.0 {
+        return None;
+    }
+
+    let normal_speed = velocity.dot(normal);
+    let velocity = if normal_speed < 0.0 {
+        let tangent = velocity - normal * normal_speed;
+        let normal_change = -(1.0 + restitution) * normal_speed;
+        let tangent_change = (friction * normal_change).min(tangent.length());
+        let tangent_direction = tangent.try_normalize().unwrap_or(Vec3::ZERO);
+        velocity + normal * normal_change - tangent_direction * tangent_change
+    } else {
+        velocity
+    };
+
+    Some(SphereContact { normal, distance, penetration, velocity })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::terrain::bake_terrain_sdf;
+
+    const GRAVITY: Vec3 = Vec3::new(0.0, -9.81, 0.0);
+    const TIME_STEP: f32 = 1.0 / 60.0;
+    const RADIUS: f32 = 0.5;
+
+    /// Un paso de simulación de la esfera: gravedad, integración y contacto
+    fn step(field: &SDFShape, center: &mut Vec3, velocity: &mut Vec3) {
+        *velocity += GRAVITY * TIME_STEP;
+        *center += *velocity * TIME_STEP;
+        if let Some(contact) = resolve_sphere(field, *center, RADIUS, *velocity, 0.5, 0.0) {
+            *center += contact.normal * contact.penetration;
+            *velocity = contact.velocity;
+        }
+    }
+
+    #[test]
+    fn sphere_on_flat_terrain_reaches_equilibrium_within_10_steps() {
+        let field = bake_terrain_sdf(&[0.0; 64], 8, 8, 0.5);
+        let (mut center, mut velocity) = (Vec3::new(0.0, RADIUS + 0.02, 0.0), Vec3::ZERO);
+
+        let mut positions = Vec::new();
+        for _ in 0..10 {
+            step(&field, &mut center, &mut velocity);
+            positions.push(center);
+        }
+
+        assert!((center.y - RADIUS).abs() < 1e-3, "la esfera quedó en y = {}", center.y);
+        assert!(velocity.length() < 1e-3, "la esfera sigue moviéndose a {:?}", velocity);
+        // Los últimos pasos ya no la mueven
+        assert!(positions[positions.len() - 2].distance(center) < 1e-4);
+    }
+
+    #[test]
+    fn sphere_above_the_surface_has_no_contact() {
+        let field = bake_terrain_sdf(&[0.0; 64], 8, 8, 0.5);
+        assert!(resolve_sphere(&field, Vec3::new(0.0, 1.0, 0.0), RADIUS, GRAVITY, 0.5, 0.0).is_none());
+
+        let contact = resolve_sphere(&field, Vec3::new(0.0, 0.4, 0.0), RADIUS, Vec3::ZERO, 0.5, 0.0).unwrap();
+        assert!(contact.normal.abs_diff_eq(Vec3::Y, 1e-4));
+        assert!((contact.penetration - 0.1).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,70 @@
+//! # SDF de terreno
+//!
+//! Horneado de un mapa de alturas a un campo de distancia con signo para la
+//! colisión suave de `CollisionShape::SDF`.
+
+use glam::Vec3;
+
+use super::sdf::SDFShape;
+
+/// Celdas de margen por encima del punto más alto y por debajo del más bajo
+pub const SDF_TERRAIN_MARGIN_CELLS: u32 = 4;
+
+/// Hornear un mapa de alturas de `width` x `height` muestras (fila a fila,
+/// de -Z a +Z, con `cell_size` metros entre muestras) a un SDF. Como el
+/// `Heightfield`, la rejilla queda centrada en X y Z sobre la posición del
+/// collider; en Y cubre las alturas del mapa más un margen.
+///
+/// La distancia de cada muestra es su altura sobre el terreno corregida por
+/// la pendiente local, `(y - h) / sqrt(1 + |∇h|²)`: la distancia al plano
+/// tangente, exacta cerca de la superficie que es donde se resuelven los
+/// contactos
+pub fn bake_terrain_sdf(heightmap: &[f32], width: usize, height: usize, cell_size: f32) -> SDFShape {
+    let (width, height) = (width.max(2), height.max(2));
+    let height_at = |x: usize, z: usize| {
+        let (x, z) = (x.min(width - 1), z.min(height - 1));
+        heightmap.get(z * width + x).copied().unwrap_or(0.0)
+    };
+
+    let (min_height, max_height) = (0..height)
+        .flat_map(|z| (0..width).map(move |x| (x, z)))
+        .map(|(x, z)| height_at(x, z))
+        .fold((f32::MAX, f32::MIN), |(min, max), h| (min.min(h), max.max(h)));
+    let margin = SDF_TERRAIN_MARGIN_CELLS as f32 * cell_size;
+    let bottom = min_height - margin;
+    let levels = ((max_height + margin - bottom) / cell_size).ceil() as u32 + 1;
+
+    // Factor de pendiente por columna con diferencias centradas
+    let slope_scale: Vec<f32> = (0..height)
+        .flat_map(|z| (0..width).map(move |x| (x, z)))
+        .map(|(x, z)| {
+            let dx = (height_at(x + 1, z) - height_at(x.saturating_sub(1), z))
+                / ((x + 1).min(width - 1) - x.saturating_sub(1)) as f32;
+            let dz = (height_at(x, z + 1) - height_at(x, z.saturating_sub(1)))
+                / ((z + 1).min(height - 1) - z.saturating_sub(1)) as f32;
+            let (dx, dz) = (dx / cell_size, dz / cell_size);
+            1.0 / (1.0 + dx * dx + dz * dz).sqrt()
+        })
+        .collect();
+
+    let mut data = Vec::with_capacity(width * height * levels as usize);
+    for z in 0..height {
+        for level in 0..levels {
+            let y = bottom + level as f32 * cell_size;
+            for x in 0..width {
+                data.push((y - height_at(x, z)) * slope_scale[z * width + x]);
+            }
+        }
+    }
+
+    SDFShape {
+        data,
+        grid_size: [width as u32, levels, height as u32],
+        cell_size,
+        origin: Vec3::new(
+            -((width - 1) as f32) * cell_size * 0.5,
+            bottom,
+            -((height - 1) as f32) * cell_size * 0.5,
+        ),
+    }
+}
@@ -0,0 +1,268 @@
+//! # Zonas de Trigger
+//!
+//! Volúmenes que detectan la entrada y salida de cuerpos dinámicos. Cada
+//! paso de física se prueba el solapamiento de cada zona con los colliders
+//! de los cuerpos cuyo grupo de colisión coincide con la máscara de la zona,
+//! y se compara con el conjunto del paso anterior: las entidades nuevas
+//! generan `TriggerEntered` y las que faltan `TriggerExited`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use serde::{Serialize, Deserialize};
+use tracing::debug;
+use glam::{Vec3, Quat};
+use nalgebra::{Quaternion, Translation3, UnitQuaternion, Vector3};
+use rapier3d::prelude::*;
+
+use super::CollisionShape;
+use crate::ecs::{Event, EventType, EntityId};
+
+/// Identificador de zona de trigger
+pub type TriggerZoneId = u64;
+
+/// Posición y orientación de una zona
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Transform {
+    /// Posición
+    pub position: Vec3,
+    /// Rotación
+    pub rotation: Quat,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+        }
+    }
+}
+
+impl Transform {
+    /// Isometría de Rapier equivalente
+    pub fn isometry(&self) -> Isometry<Real> {
+        let rotation = UnitQuaternion::from_quaternion(Quaternion::new(
+            self.rotation.w,
+            self.rotation.x,
+            self.rotation.y,
+            self.rotation.z,
+        ));
+        Isometry::from_parts(
+            Translation3::from(Vector3::new(self.position.x, self.position.y, self.position.z)),
+            rotation,
+        )
+    }
+}
+
+/// Zona de trigger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerZone {
+    /// Nombre de la zona (se envía como dato del evento)
+    pub id: String,
+    /// Forma del volumen
+    pub shape: CollisionShape,
+    /// Posición y orientación del volumen
+    pub transform: Transform,
+    /// Grupos de colisión que detecta la zona
+    pub filter_mask: u32,
+}
+
+/// Evento de trigger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TriggerEvent {
+    /// La entidad entró en la zona
+    Entered { zone: TriggerZoneId, entity: EntityId },
+    /// La entidad salió de la zona
+    Exited { zone: TriggerZoneId, entity: EntityId },
+}
+
+impl TriggerEvent {
+    /// Tipo de evento del ECS
+    pub fn event_type(&self) -> EventType {
+        match *self {
+            TriggerEvent::Entered { zone, entity } => EventType::TriggerEntered(zone, entity),
+            TriggerEvent::Exited { zone, entity } => EventType::TriggerExited(zone, entity),
+        }
+    }
+}
+
+/// Cuerpo candidato para las zonas en un paso
+pub struct TriggerCandidate {
+    /// Entidad del cuerpo
+    pub entity: EntityId,
+    /// Grupos de colisión del cuerpo
+    pub groups: u32,
+    /// Colliders del cuerpo en posición de mundo
+    pub colliders: Vec<(Isometry<Real>, SharedShape)>,
+}
+
+/// Zona registrada con su forma ya construida
+struct ZoneState {
+    zone: TriggerZone,
+    shape: SharedShape,
+    inside: BTreeSet<EntityId>,
+}
+
+/// Gestor de zonas de trigger
+#[derive(Default)]
+pub struct TriggerManager {
+    /// Zonas por ID (ordenadas para que los eventos sean deterministas)
+    zones: BTreeMap<TriggerZoneId, ZoneState>,
+    /// Siguiente ID
+    next_id: TriggerZoneId,
+    /// Eventos pendientes
+    events: Vec<TriggerEvent>,
+}
+
+impl TriggerManager {
+    /// Crear gestor vacío
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registrar una zona con la forma construida por el sistema de física
+    pub fn add(&mut self, zone: TriggerZone, shape: SharedShape) -> TriggerZoneId {
+        self.next_id += 1;
+        let id = self.next_id;
+        debug!("Zona de trigger '{}' registrada ({})", zone.id, id);
+        self.zones.insert(id, ZoneState { zone, shape, inside: BTreeSet::new() });
+        id
+    }
+
+    /// Quitar una zona. No genera eventos de salida
+    pub fn remove(&mut self, id: TriggerZoneId) -> Option<TriggerZone> {
+        self.zones.remove(&id).map(|state| state.zone)
+    }
+
+    /// Zona registrada
+    pub fn get(&self, id: TriggerZoneId) -> Option<&TriggerZone> {
+        self.zones.get(&id).map(|state| &state.zone)
+    }
+
+    /// Entidades dentro de una zona en el último paso
+    pub fn entities_in(&self, id: TriggerZoneId) -> Vec<EntityId> {
+        self.zones.get(&id)
+            .map(|state| state.inside.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Número de zonas
+    pub fn len(&self) -> usize {
+        self.zones.len()
+    }
+
+    /// No hay zonas
+    pub fn is_empty(&self) -> bool {
+        self.zones.is_empty()
+    }
+
+    /// Recalcular los solapamientos y acumular los eventos de entrada y salida
+    pub fn update(&mut self, candidates: &[TriggerCandidate]) {
+        for (zone_id, state) in self.zones.iter_mut() {
+            let zone_position = state.zone.transform.isometry();
+            let inside: BTreeSet<EntityId> = candidates.iter()
+                .filter(|candidate| candidate.groups & state.zone.filter_mask != 0)
+                .filter(|candidate| {
+                    candidate.colliders.iter().any(|(position, shape)| {
+                        // Pares de formas sin prueba en parry (p. ej. dos campos de alturas) no solapan
+                        query::intersection_test(&zone_position, state.shape.as_ref(), position, shape.as_ref())
+                            .unwrap_or(false)
+                    })
+                })
+                .map(|candidate| candidate.entity)
+                .collect();
+
+            for entity in inside.difference(&state.inside) {
+                self.events.push(TriggerEvent::Entered { zone: *zone_id, entity: *entity });
+            }
+            for entity in state.inside.difference(&inside) {
+                self.events.push(TriggerEvent::Exited { zone: *zone_id, entity: *entity });
+            }
+            state.inside = inside;
+        }
+    }
+
+    /// Extraer los eventos pendientes
+    pub fn drain_events(&mut self) -> Vec<TriggerEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Evento del ECS para un evento de trigger, con el nombre de la zona como dato
+    pub fn to_ecs_event(&self, event: &TriggerEvent) -> Event {
+        let zone = match event {
+            TriggerEvent::Entered { zone, .. } | TriggerEvent::Exited { zone, .. } => *zone,
+        };
+        Event {
+            event_type: event.event_type(),
+            data: self.get(zone).map(|zone| Box::new(zone.id.clone()) as Box<dyn std::any::Any + Send + Sync>),
+            timestamp: std::time::Instant::now(),
+        }
+    }
+
+    /// Quitar todas las zonas y eventos
+    pub fn clear(&mut self) {
+        self.zones.clear();
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Caja de 2 m de lado en el origen que detecta el grupo 1
+    fn box_zone(manager: &mut TriggerManager) -> TriggerZoneId {
+        manager.add(TriggerZone {
+            id: "puerta".to_string(),
+            shape: CollisionShape::Box(Vec3::ONE),
+            transform: Transform::default(),
+            filter_mask: 0b01,
+        }, SharedShape::cuboid(1.0, 1.0, 1.0))
+    }
+
+    /// Esfera de 0.5 m de radio en `x` sobre el eje X
+    fn sphere_at(entity: EntityId, groups: u32, x: f32) -> TriggerCandidate {
+        TriggerCandidate {
+            entity,
+            groups,
+            colliders: vec![(Isometry::translation(x, 0.0, 0.0), SharedShape::ball(0.5))],
+        }
+    }
+
+    #[test]
+    fn sphere_through_box_enters_and_exits_once() {
+        let mut manager = TriggerManager::new();
+        let zone = box_zone(&mut manager);
+
+        let mut events = Vec::new();
+        let mut inside_steps = 0;
+        for step in 0..=100 {
+            let x = -5.0 + step as f32 * 0.1;
+            manager.update(&[sphere_at(7, 0b01, x)]);
+            events.extend(manager.drain_events());
+            if manager.entities_in(zone) == [7] {
+                inside_steps += 1;
+            }
+        }
+
+        assert_eq!(events, [
+            TriggerEvent::Entered { zone, entity: 7 },
+            TriggerEvent::Exited { zone, entity: 7 },
+        ]);
+        // Solapa mientras |x| <= 1.5: unos 30 pasos de 10 cm
+        assert!((29..=31).contains(&inside_steps), "{} pasos dentro", inside_steps);
+        assert!(manager.entities_in(zone).is_empty());
+
+        let event = manager.to_ecs_event(&events[0]);
+        assert_eq!(event.event_type, EventType::TriggerEntered(zone, 7));
+        assert_eq!(event.data.unwrap().downcast_ref::<String>().map(String::as_str), Some("puerta"));
+    }
+
+    #[test]
+    fn bodies_outside_the_mask_are_ignored() {
+        let mut manager = TriggerManager::new();
+        let zone = box_zone(&mut manager);
+        manager.update(&[sphere_at(3, 0b10, 0.0), sphere_at(4, 0b11, 0.0)]);
+        assert_eq!(manager.drain_events(), [TriggerEvent::Entered { zone, entity: 4 }]);
+        assert_eq!(manager.entities_in(zone), [4]);
+    }
+}
@@ -0,0 +1,380 @@
+//! # Alertas de rendimiento
+//!
+//! Reglas de umbral sobre las métricas del sistema evaluadas en cada
+//! `ProfilingSystem::update`. Una regla dispara cuando su métrica cruza el
+//! umbral durante `sustained` seguido, así que un frame malo aislado no
+//! genera alertas; y no se vuelve a disparar hasta que la métrica se recupera
+//! más allá del umbral más la histéresis, lo que también emite una alerta de
+//! recuperación.
+//!
+//! Cada alerta lleva las métricas del momento y se entrega a los destinos de
+//! `NotificationConfig`: la consola con `tracing`, una línea JSON añadida al
+//! fichero de alertas y los canales abiertos con `subscribe`.
+
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use super::{NotificationConfig, SystemMetrics};
+
+/// Métrica vigilada por una regla
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertMetric {
+    /// FPS medios
+    Fps,
+    /// Tiempo de frame medio en ms
+    FrameTime,
+    /// Uso de CPU en porcentaje
+    CpuUsage,
+    /// Uso de memoria en porcentaje
+    MemoryUsage,
+    /// Uso de GPU en porcentaje
+    GpuUsage,
+    /// Tiempo de GPU del frame en ms
+    GpuFrameTime,
+    /// Latencia de red en ms
+    NetworkLatency,
+}
+
+impl AlertMetric {
+    /// Valor de la métrica
+    pub fn value(&self, metrics: &SystemMetrics) -> f32 {
+        match self {
+            AlertMetric::Fps => metrics.performance.average_fps,
+            AlertMetric::FrameTime => metrics.performance.average_frame_time,
+            AlertMetric::CpuUsage => metrics.cpu.usage,
+            AlertMetric::MemoryUsage => metrics.memory.usage_percentage,
+            AlertMetric::GpuUsage => metrics.gpu.usage,
+            AlertMetric::GpuFrameTime => metrics.gpu.frame_time,
+            AlertMetric::NetworkLatency => metrics.network.latency,
+        }
+    }
+}
+
+/// Sentido en que una métrica incumple el umbral
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertComparator {
+    /// Incumple por debajo (FPS)
+    Below,
+    /// Incumple por encima (tiempos, usos)
+    Above,
+}
+
+/// Regla de alerta
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    /// Nombre de la regla
+    pub name: String,
+    /// Métrica vigilada
+    pub metric: AlertMetric,
+    /// Sentido del incumplimiento
+    pub comparator: AlertComparator,
+    /// Umbral
+    pub threshold: f32,
+    /// Margen más allá del umbral que debe recuperar la métrica para
+    /// cerrar la alerta
+    #[serde(default)]
+    pub hysteresis: f32,
+    /// Tiempo seguido incumpliendo antes de disparar
+    #[serde(default)]
+    pub sustained: Duration,
+}
+
+impl AlertRule {
+    /// La métrica incumple el umbral
+    fn breached(&self, value: f32) -> bool {
+        match self.comparator {
+            AlertComparator::Below => value < self.threshold,
+            AlertComparator::Above => value > self.threshold,
+        }
+    }
+
+    /// La métrica ha vuelto más allá del umbral y la histéresis
+    fn recovered(&self, value: f32) -> bool {
+        match self.comparator {
+            AlertComparator::Below => value >= self.threshold + self.hysteresis,
+            AlertComparator::Above => value <= self.threshold - self.hysteresis,
+        }
+    }
+}
+
+/// Regla por defecto: FPS por debajo del umbral de `AlertConfig` durante 2 s
+pub fn default_rules(fps_threshold: f32) -> Vec<AlertRule> {
+    vec![AlertRule {
+        name: "fps_bajo".to_string(),
+        metric: AlertMetric::Fps,
+        comparator: AlertComparator::Below,
+        threshold: fps_threshold,
+        hysteresis: 5.0,
+        sustained: Duration::from_secs(2),
+    }]
+}
+
+/// Tipo de alerta
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertKind {
+    /// La regla empieza a incumplirse
+    Fired,
+    /// La métrica se ha recuperado
+    Cleared,
+}
+
+/// Alerta emitida
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    /// Regla que la emite
+    pub rule: String,
+    /// Tipo
+    pub kind: AlertKind,
+    /// Métrica
+    pub metric: AlertMetric,
+    /// Valor de la métrica
+    pub value: f32,
+    /// Umbral de la regla
+    pub threshold: f32,
+    /// Segundos incumpliendo (al disparar) o en alerta (al recuperarse)
+    pub duration: f32,
+    /// Timestamp en segundos
+    pub timestamp: u64,
+    /// Métricas del momento
+    pub metrics: SystemMetrics,
+}
+
+/// Estado de una regla
+#[derive(Debug, Clone, Default)]
+struct RuleState {
+    /// Instante desde el que incumple
+    breach_since: Option<f32>,
+    /// Instante en que disparó (None sin alerta activa)
+    fired_at: Option<f32>,
+}
+
+/// Evaluador de reglas y entrega de alertas
+pub struct AlertEngine {
+    /// Reglas
+    rules: Vec<AlertRule>,
+    /// Estado por regla
+    states: Vec<RuleState>,
+    /// Destinos
+    notifications: NotificationConfig,
+    /// Canales suscritos
+    subscribers: Vec<mpsc::UnboundedSender<Alert>>,
+    /// Tiempo acumulado de las actualizaciones
+    time: f32,
+}
+
+impl AlertEngine {
+    /// Evaluador con reglas y destinos
+    pub fn new(rules: Vec<AlertRule>, notifications: NotificationConfig) -> Self {
+        let states = vec![RuleState::default(); rules.len()];
+        Self {
+            rules,
+            states,
+            notifications,
+            subscribers: Vec::new(),
+            time: 0.0,
+        }
+    }
+
+    /// Reglas configuradas
+    pub fn rules(&self) -> &[AlertRule] {
+        &self.rules
+    }
+
+    /// Nombres de las reglas con alerta activa
+    pub fn active(&self) -> Vec<String> {
+        self.rules.iter()
+            .zip(&self.states)
+            .filter(|(_, state)| state.fired_at.is_some())
+            .map(|(rule, _)| rule.name.clone())
+            .collect()
+    }
+
+    /// Canal que recibe todas las alertas a partir de ahora
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<Alert> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Evaluar las reglas tras `delta_time` segundos y entregar las alertas
+    /// que resulten
+    pub fn evaluate(&mut self, delta_time: f32, metrics: &SystemMetrics) -> Vec<Alert> {
+        self.time += delta_time.max(0.0);
+        let now = self.time;
+
+        let mut alerts = Vec::new();
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            let value = rule.metric.value(metrics);
+            let (kind, duration) = match state.fired_at {
+                None if rule.breached(value) => {
+                    let since = *state.breach_since.get_or_insert(now);
+                    if now - since < rule.sustained.as_secs_f32() {
+                        continue;
+                    }
+                    state.fired_at = Some(now);
+                    (AlertKind::Fired, now - since)
+                }
+                None => {
+                    state.breach_since = None;
+                    continue;
+                }
+                Some(fired_at) if rule.recovered(value) => {
+                    state.fired_at = None;
+                    state.breach_since = None;
+                    (AlertKind::Cleared, now - fired_at)
+                }
+                Some(_) => continue,
+            };
+            alerts.push(Alert {
+                rule: rule.name.clone(),
+                kind,
+                metric: rule.metric,
+                value,
+                threshold: rule.threshold,
+                duration,
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_secs()),
+                metrics: metrics.clone(),
+            });
+        }
+
+        for alert in &alerts {
+            self.deliver(alert);
+        }
+        alerts
+    }
+
+    /// Entregar una alerta a los destinos configurados
+    fn deliver(&mut self, alert: &Alert) {
+        if self.notifications.console_notifications {
+            match alert.kind {
+                AlertKind::Fired => warn!(
+                    "⚠️ Alerta {}: {:?} = {:.2} (umbral {:.2}) durante {:.1}s",
+                    alert.rule, alert.metric, alert.value, alert.threshold, alert.duration
+                ),
+                AlertKind::Cleared => info!(
+                    "✅ Alerta {} recuperada: {:?} = {:.2} tras {:.1}s",
+                    alert.rule, alert.metric, alert.value, alert.duration
+                ),
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.notifications.file_notifications {
+            if let Err(error) = append_alert(&self.notifications.alert_file, alert) {
+                warn!("No se pudo escribir la alerta en {}: {}", self.notifications.alert_file, error);
+            }
+        }
+
+        self.subscribers.retain(|subscriber| subscriber.send(alert.clone()).is_ok());
+    }
+}
+
+/// Añadir una alerta como línea JSON al fichero de alertas
+#[cfg(not(target_arch = "wasm32"))]
+fn append_alert(path: &str, alert: &Alert) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let path = std::path::Path::new(path);
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut line = serde_json::to_vec(alert)?;
+    line.push(b'\n');
+    std::fs::OpenOptions::new().create(true).append(true).open(path)?.write_all(&line)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Paso de las actualizaciones en segundos
+    const STEP: f32 = 0.1;
+    /// Umbral de FPS de las reglas por defecto
+    const FPS_THRESHOLD: f32 = 30.0;
+
+    fn notifications(alert_file: Option<&std::path::Path>) -> NotificationConfig {
+        NotificationConfig {
+            console_notifications: false,
+            file_notifications: alert_file.is_some(),
+            network_notifications: false,
+            alert_file: alert_file.map_or_else(String::new, |path| path.display().to_string()),
+        }
+    }
+
+    fn metrics(fps: f32) -> SystemMetrics {
+        let mut metrics = SystemMetrics::default();
+        metrics.performance.average_fps = fps;
+        metrics.performance.average_frame_time = 1000.0 / fps;
+        metrics
+    }
+
+    /// Evaluar `seconds` segundos a unos FPS fijos y devolver las alertas
+    fn run(engine: &mut AlertEngine, fps: f32, seconds: f32) -> Vec<Alert> {
+        let steps = (seconds / STEP).round() as usize;
+        (0..steps).flat_map(|_| engine.evaluate(STEP, &metrics(fps))).collect()
+    }
+
+    #[test]
+    fn sustained_fps_drop_fires_once_and_clears_past_the_hysteresis() {
+        let mut engine = AlertEngine::new(default_rules(FPS_THRESHOLD), notifications(None));
+        let mut received = engine.subscribe();
+        let rule = engine.rules()[0].clone();
+
+        // Un frame malo aislado no dispara
+        assert!(run(&mut engine, 60.0, 1.0).is_empty());
+        assert!(engine.evaluate(STEP, &metrics(12.0)).is_empty());
+        assert!(run(&mut engine, 60.0, 1.0).is_empty());
+
+        // Por debajo de 30 el tiempo configurado: una sola alerta
+        let fired = run(&mut engine, 25.0, rule.sustained.as_secs_f32() + 3.0);
+        assert_eq!(fired.len(), 1, "{:?}", fired);
+        assert_eq!(fired[0].kind, AlertKind::Fired);
+        assert_eq!((fired[0].rule.as_str(), fired[0].metric), ("fps_bajo", AlertMetric::Fps));
+        assert_eq!(fired[0].value, 25.0);
+        assert!(fired[0].duration >= rule.sustained.as_secs_f32() - STEP);
+        assert_eq!(fired[0].metrics.performance.average_fps, 25.0, "la alerta lleva las métricas");
+        assert_eq!(engine.active(), vec!["fps_bajo".to_string()]);
+
+        // Por encima del umbral pero dentro de la histéresis sigue activa
+        assert!(run(&mut engine, FPS_THRESHOLD + rule.hysteresis * 0.5, 2.0).is_empty());
+        assert_eq!(engine.active().len(), 1);
+
+        let cleared = run(&mut engine, FPS_THRESHOLD + rule.hysteresis + 1.0, 1.0);
+        assert_eq!(cleared.len(), 1, "{:?}", cleared);
+        assert_eq!(cleared[0].kind, AlertKind::Cleared);
+        assert!(engine.active().is_empty());
+
+        // Los suscriptores reciben las mismas alertas
+        let kinds: Vec<AlertKind> = std::iter::from_fn(|| received.try_recv().ok()).map(|alert| alert.kind).collect();
+        assert_eq!(kinds, vec![AlertKind::Fired, AlertKind::Cleared]);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn file_sink_appends_each_alert_as_a_json_line() {
+        let directory = std::env::temp_dir().join(format!("metaverso-alerts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let path = directory.join("alerts.jsonl");
+        let mut engine = AlertEngine::new(default_rules(FPS_THRESHOLD), notifications(Some(&path)));
+
+        let mut alerts = run(&mut engine, 20.0, 3.0);
+        alerts.extend(run(&mut engine, 50.0, 1.0));
+        assert_eq!(alerts.len(), 2);
+
+        let contents = std::fs::read_to_string(&path).expect("el destino de fichero crea el fichero");
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), alerts.len());
+        for (line, alert) in lines.iter().zip(&alerts) {
+            assert_eq!(*line, serde_json::to_string(alert).unwrap());
+            let stored: Alert = serde_json::from_str(line).unwrap();
+            assert_eq!((stored.kind, stored.value), (alert.kind, alert.value));
+            assert_eq!(stored.metrics.performance.average_fps, alert.value);
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
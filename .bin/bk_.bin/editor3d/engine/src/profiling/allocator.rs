@@ -0,0 +1,193 @@
+//! # Allocator de Telemetría
+//!
+//! Allocator global que envuelve al del sistema y contabiliza memoria viva,
+//! pico y número de asignaciones. Opcionalmente muestrea el punto de llamada de
+//! las asignaciones que superan un umbral de bytes.
+
+use serde::{Serialize, Deserialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Bytes asignados actualmente
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+/// Pico de bytes asignados
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+/// Número de asignaciones
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+/// Número de liberaciones
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+/// Umbral de muestreo de call-stacks (0 = deshabilitado)
+static SAMPLE_THRESHOLD: AtomicUsize = AtomicUsize::new(0);
+/// Puntos de asignación muestreados
+static SITES: Mutex<Option<HashMap<(String, u32), AllocationSite>>> = Mutex::new(None);
+
+thread_local! {
+    /// Evita reentrar en el muestreo cuando éste asigna memoria
+    static SAMPLING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Allocator global con telemetría
+pub struct TracingAllocator;
+
+/// Punto de asignación muestreado
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllocationSite {
+    /// Archivo
+    pub file: String,
+    /// Línea
+    pub line: u32,
+    /// Bytes asignados en total
+    pub size: u64,
+    /// Número de asignaciones
+    pub count: u64,
+}
+
+/// Contadores del allocator
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct AllocationSnapshot {
+    /// Bytes asignados actualmente
+    pub allocated: u64,
+    /// Pico de bytes asignados
+    pub peak: u64,
+    /// Número de asignaciones
+    pub allocations: u64,
+    /// Número de liberaciones
+    pub deallocations: u64,
+}
+
+impl TracingAllocator {
+    /// Crear allocator
+    pub const fn new() -> Self {
+        TracingAllocator
+    }
+}
+
+unsafe impl GlobalAlloc for TracingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record_alloc(ptr, layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record_alloc(ptr, layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        record_dealloc(ptr, layout.size());
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record_dealloc(ptr, layout.size());
+            record_alloc(new_ptr, new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Registrar asignación
+fn record_alloc(ptr: *mut u8, size: usize) {
+    let allocated = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(allocated, Ordering::Relaxed);
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+
+    #[cfg(feature = "tracy")]
+    unsafe {
+        tracy_client_sys::___tracy_emit_memory_alloc(ptr as *const std::ffi::c_void, size, 0);
+    }
+    #[cfg(not(feature = "tracy"))]
+    let _ = ptr;
+
+    let threshold = SAMPLE_THRESHOLD.load(Ordering::Relaxed);
+    if threshold > 0 && size >= threshold {
+        sample_call_site(size);
+    }
+}
+
+/// Registrar liberación
+fn record_dealloc(ptr: *mut u8, size: usize) {
+    ALLOCATED.fetch_sub(size, Ordering::Relaxed);
+    DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+
+    #[cfg(feature = "tracy")]
+    unsafe {
+        tracy_client_sys::___tracy_emit_memory_free(ptr as *const std::ffi::c_void, 0);
+    }
+    #[cfg(not(feature = "tracy"))]
+    let _ = ptr;
+}
+
+/// Muestrear el punto de llamada de una asignación grande
+fn sample_call_site(size: usize) {
+    let reentrant = SAMPLING.try_with(|sampling| sampling.replace(true)).unwrap_or(true);
+    if reentrant {
+        return;
+    }
+
+    let mut site: Option<(String, u32)> = None;
+    backtrace::trace(|frame| {
+        backtrace::resolve_frame(frame, |symbol| {
+            if site.is_some() {
+                return;
+            }
+            if let (Some(file), Some(line)) = (symbol.filename(), symbol.lineno()) {
+                let file = file.to_string_lossy();
+                // Saltar los marcos del propio allocator y de la librería estándar
+                if !file.contains("/rustc/") && !file.ends_with("allocator.rs") {
+                    site = Some((file.into_owned(), line));
+                }
+            }
+        });
+        site.is_none()
+    });
+
+    if let Some((file, line)) = site {
+        if let Ok(mut sites) = SITES.lock() {
+            let entry = sites.get_or_insert_with(HashMap::new)
+                .entry((file.clone(), line))
+                .or_insert(AllocationSite { file, line, size: 0, count: 0 });
+            entry.size += size as u64;
+            entry.count += 1;
+        }
+    }
+
+    let _ = SAMPLING.try_with(|sampling| sampling.set(false));
+}
+
+/// Leer contadores actuales
+pub fn snapshot() -> AllocationSnapshot {
+    AllocationSnapshot {
+        allocated: ALLOCATED.load(Ordering::Relaxed) as u64,
+        peak: PEAK.load(Ordering::Relaxed) as u64,
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+    }
+}
+
+/// Configurar el umbral de muestreo de call-stacks (0 lo deshabilita)
+pub fn set_sample_threshold(bytes: usize) {
+    SAMPLE_THRESHOLD.store(bytes, Ordering::Relaxed);
+}
+
+/// Puntos de asignación con más bytes acumulados
+pub fn hotspots(top_n: usize) -> Vec<AllocationSite> {
+    let mut sites: Vec<AllocationSite> = match SITES.lock() {
+        Ok(sites) => sites.as_ref().map(|s| s.values().cloned().collect()).unwrap_or_default(),
+        Err(_) => return Vec::new(),
+    };
+    sites.sort_by(|a, b| b.size.cmp(&a.size));
+    sites.truncate(top_n);
+    sites
+}
@@ -0,0 +1,508 @@
+//! # Exportación de Profiling
+//!
+//! Serializa los scopes y el historial de métricas en dos formatos:
+//!
+//! - JSON de Chrome Trace Event, que abren `chrome://tracing` y Perfetto: un
+//!   par de eventos `B`/`E` por scope en su hilo, metadatos con el nombre de
+//!   cada hilo y contadores `C` de FPS, tiempo de frame, CPU y memoria por
+//!   cada snapshot del historial.
+//! - CSV para hojas de cálculo: una fila por snapshot con las métricas del
+//!   sistema y una fila por nodo del árbol de cada frame.
+//!
+//! En nativo los ficheros se escriben en `export_directory` con el instante
+//! en el nombre (`trace-<ms>.json`) y, cuando los de un mismo tipo superan
+//! `max_export_bytes`, se borran los más antiguos. En wasm no hay sistema de
+//! ficheros y se devuelven los bytes para que los descargue la página.
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use super::scope;
+use super::timeline::{FrameNode, TraceSpan};
+use super::MetricSnapshot;
+
+/// Tipo de evento de Chrome Trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TracePhase {
+    /// Apertura de un scope
+    #[serde(rename = "B")]
+    Begin,
+    /// Cierre de un scope
+    #[serde(rename = "E")]
+    End,
+    /// Muestra de contador
+    #[serde(rename = "C")]
+    Counter,
+    /// Metadatos (nombre de proceso o hilo)
+    #[serde(rename = "M")]
+    Metadata,
+}
+
+/// Evento de Chrome Trace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TraceEvent {
+    /// Nombre del scope, contador o metadato
+    pub name: String,
+    /// Categoría
+    pub cat: String,
+    /// Tipo de evento
+    pub ph: TracePhase,
+    /// Instante en microsegundos
+    pub ts: f64,
+    /// ID de proceso
+    pub pid: u32,
+    /// ID de hilo
+    pub tid: u32,
+    /// Valores de contadores y metadatos
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, serde_json::Value>,
+}
+
+/// Documento de Chrome Trace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChromeTrace {
+    /// Eventos
+    #[serde(rename = "traceEvents")]
+    pub trace_events: Vec<TraceEvent>,
+    /// Unidad de tiempo del visor
+    #[serde(rename = "displayTimeUnit")]
+    pub display_time_unit: String,
+}
+
+/// Resultado de una exportación
+#[derive(Debug, Clone)]
+pub enum ExportOutput {
+    /// Fichero escrito en el directorio de exportación
+    File(std::path::PathBuf),
+    /// Bytes serializados (wasm)
+    Bytes(Vec<u8>),
+}
+
+/// Categoría de los eventos de scopes
+const SPAN_CATEGORY: &str = "scope";
+/// Categoría de los contadores
+const COUNTER_CATEGORY: &str = "metrics";
+
+/// ID del proceso en la traza
+fn process_id() -> u32 {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::process::id()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        1
+    }
+}
+
+/// Construir la traza con los scopes y el historial
+pub fn chrome_trace(spans: &[TraceSpan], history: &[MetricSnapshot]) -> ChromeTrace {
+    let pid = process_id();
+    let mut events = Vec::with_capacity(spans.len() * 2 + history.len() * 4);
+
+    let mut spans = spans.to_vec();
+    spans.sort_by(|a, b| {
+        a.thread.cmp(&b.thread)
+            .then(a.start_ns.cmp(&b.start_ns))
+            .then(b.end_ns.cmp(&a.end_ns))
+    });
+
+    let mut threads: Vec<u32> = spans.iter().map(|span| span.thread).collect();
+    threads.dedup();
+    for thread in threads {
+        events.push(TraceEvent {
+            name: "thread_name".to_string(),
+            cat: "__metadata".to_string(),
+            ph: TracePhase::Metadata,
+            ts: 0.0,
+            pid,
+            tid: thread,
+            args: BTreeMap::from([("name".to_string(), scope::thread_name(thread).into())]),
+        });
+    }
+
+    // Los B/E de cada hilo deben anidar: se cierran los scopes abiertos que
+    // terminan antes del siguiente, y un scope que sobresale de su padre (uno
+    // que cruzó un `.await`) se recorta al final del padre
+    let span_event = |name: &str, ph, ns: u64, thread| TraceEvent {
+        name: name.to_string(),
+        cat: SPAN_CATEGORY.to_string(),
+        ph,
+        ts: ns as f64 / 1000.0,
+        pid,
+        tid: thread,
+        args: BTreeMap::new(),
+    };
+    let mut stack: Vec<(&'static str, u64)> = Vec::new();
+    let mut current_thread = None;
+    for span in &spans {
+        if current_thread != Some(span.thread) {
+            if let Some(thread) = current_thread {
+                while let Some((name, end_ns)) = stack.pop() {
+                    events.push(span_event(name, TracePhase::End, end_ns, thread));
+                }
+            }
+            current_thread = Some(span.thread);
+        }
+        while let Some(&(name, end_ns)) = stack.last() {
+            if end_ns > span.start_ns {
+                break;
+            }
+            stack.pop();
+            events.push(span_event(name, TracePhase::End, end_ns, span.thread));
+        }
+        let end_ns = stack.last().map_or(span.end_ns, |(_, parent_end)| span.end_ns.min(*parent_end));
+        events.push(span_event(span.name, TracePhase::Begin, span.start_ns, span.thread));
+        stack.push((span.name, end_ns));
+    }
+    if let Some(thread) = current_thread {
+        while let Some((name, end_ns)) = stack.pop() {
+            events.push(span_event(name, TracePhase::End, end_ns, thread));
+        }
+    }
+
+    for snapshot in history {
+        let metrics = &snapshot.system_metrics;
+        let counters: [(&str, &str, serde_json::Value); 4] = [
+            ("FPS", "fps", metrics.performance.average_fps.into()),
+            ("Frame", "ms", metrics.performance.average_frame_time.into()),
+            ("CPU", "percent", metrics.cpu.usage.into()),
+            ("Memoria", "bytes", metrics.memory.used.into()),
+        ];
+        for (name, series, value) in counters {
+            events.push(TraceEvent {
+                name: name.to_string(),
+                cat: COUNTER_CATEGORY.to_string(),
+                ph: TracePhase::Counter,
+                ts: snapshot.timeline_ns as f64 / 1000.0,
+                pid,
+                tid: 0,
+                args: BTreeMap::from([(series.to_string(), value)]),
+            });
+        }
+    }
+
+    ChromeTrace {
+        trace_events: events,
+        display_time_unit: "ms".to_string(),
+    }
+}
+
+/// CSV con una fila por snapshot del historial
+pub fn metrics_csv(history: &[MetricSnapshot]) -> String {
+    let mut csv = String::from(
+        "timestamp,timeline_ms,average_fps,frame_time_ms,cpu_usage,memory_used,memory_usage_percentage,gpu_usage,gpu_frame_time_ms,rendered_objects,draw_calls\n",
+    );
+    for snapshot in history {
+        let metrics = &snapshot.system_metrics;
+        let _ = writeln!(
+            csv,
+            "{},{:.3},{:.2},{:.3},{:.2},{},{:.2},{:.2},{:.3},{},{}",
+            snapshot.timestamp,
+            snapshot.timeline_ns as f64 / 1_000_000.0,
+            metrics.performance.average_fps,
+            metrics.performance.average_frame_time,
+            metrics.cpu.usage,
+            metrics.memory.used,
+            metrics.memory.usage_percentage,
+            metrics.gpu.usage,
+            metrics.gpu.frame_time,
+            metrics.performance.rendered_objects,
+            metrics.performance.draw_calls,
+        );
+    }
+    csv
+}
+
+/// CSV con una fila por nodo del árbol de cada snapshot; la ruta une los
+/// nombres con `/`
+pub fn frames_csv(history: &[MetricSnapshot]) -> String {
+    fn write_node(csv: &mut String, frame: u64, parent: &str, node: &FrameNode) {
+        let path = if parent.is_empty() {
+            node.name.clone()
+        } else {
+            format!("{}/{}", parent, node.name)
+        };
+        let _ = writeln!(
+            csv,
+            "{},{},{},{:.4},{:.4},{}",
+            frame,
+            csv_field(&path),
+            csv_field(&node.thread),
+            node.inclusive_ms,
+            node.exclusive_ms,
+            node.calls,
+        );
+        for child in &node.children {
+            write_node(csv, frame, &path, child);
+        }
+    }
+
+    let mut csv = String::from("frame,path,thread,inclusive_ms,exclusive_ms,calls\n");
+    for snapshot in history {
+        for root in &snapshot.frame_tree.roots {
+            write_node(&mut csv, snapshot.frame_tree.frame, "", root);
+        }
+    }
+    csv
+}
+
+/// Campo CSV entre comillas si lleva separadores
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Página HTML con el reporte de texto
+pub fn report_html(report: &str) -> String {
+    let escaped = report
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"es\">\n<head><meta charset=\"utf-8\"><title>Reporte de rendimiento</title></head>\n<body><pre>{}</pre></body>\n</html>\n",
+        escaped
+    )
+}
+
+/// Escribir un export con nombre `<prefix>-<ms>.<extension>` y borrar los
+/// más antiguos del mismo tipo mientras superen `max_bytes`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_export(
+    directory: &str,
+    prefix: &str,
+    extension: &str,
+    bytes: Vec<u8>,
+    max_bytes: u64,
+) -> Result<ExportOutput> {
+    use std::fs;
+
+    let directory = std::path::Path::new(directory);
+    fs::create_dir_all(directory)?;
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_millis();
+    let path = directory.join(format!("{}-{}.{}", prefix, millis, extension));
+    fs::write(&path, &bytes)?;
+
+    // Los nombres llevan el instante, así que el orden alfabético es el de creación
+    let mut files: Vec<(String, u64)> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let stamp = name.strip_prefix(prefix)?.strip_prefix('-')?.strip_suffix(extension)?.strip_suffix('.')?;
+            stamp.parse::<u128>().ok()?;
+            Some((name, entry.metadata().ok()?.len()))
+        })
+        .collect();
+    files.sort_by(|a, b| a.0.len().cmp(&b.0.len()).then_with(|| a.0.cmp(&b.0)));
+
+    let mut total: u64 = files.iter().map(|(_, size)| size).sum();
+    for (name, size) in &files[..files.len().saturating_sub(1)] {
+        if total <= max_bytes {
+            break;
+        }
+        fs::remove_file(directory.join(name))?;
+        total -= size;
+    }
+
+    Ok(ExportOutput::File(path))
+}
+
+/// En wasm se devuelven los bytes sin escribir nada
+#[cfg(target_arch = "wasm32")]
+pub fn write_export(
+    _directory: &str,
+    _prefix: &str,
+    _extension: &str,
+    bytes: Vec<u8>,
+    _max_bytes: u64,
+) -> Result<ExportOutput> {
+    Ok(ExportOutput::Bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiling::SystemMetrics;
+    use std::collections::HashMap;
+
+    /// Modelo estricto del formato de Chrome Trace: cada tipo de evento con
+    /// solo los campos que admite, y nada más
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct StrictTrace {
+        #[serde(rename = "traceEvents")]
+        trace_events: Vec<StrictEvent>,
+        #[serde(rename = "displayTimeUnit")]
+        display_time_unit: StrictTimeUnit,
+    }
+
+    #[derive(Debug, Deserialize)]
+    enum StrictTimeUnit {
+        #[serde(rename = "ms")]
+        Milliseconds,
+        #[serde(rename = "ns")]
+        Nanoseconds,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(tag = "ph", deny_unknown_fields)]
+    enum StrictEvent {
+        #[serde(rename = "B")]
+        Begin { name: String, cat: String, ts: f64, pid: u32, tid: u32 },
+        #[serde(rename = "E")]
+        End { name: String, cat: String, ts: f64, pid: u32, tid: u32 },
+        #[serde(rename = "C")]
+        Counter { name: String, cat: String, ts: f64, pid: u32, tid: u32, args: BTreeMap<String, f64> },
+        #[serde(rename = "M")]
+        Metadata { name: String, cat: String, ts: f64, pid: u32, tid: u32, args: StrictMetadataArgs },
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct StrictMetadataArgs {
+        name: String,
+    }
+
+    fn span(name: &'static str, thread: u32, start_us: u64, end_us: u64) -> TraceSpan {
+        TraceSpan { name, thread, start_ns: start_us * 1000, end_ns: end_us * 1000 }
+    }
+
+    /// Snapshot del historial con métricas distintas en cada índice
+    fn snapshot(index: u64) -> MetricSnapshot {
+        let mut system_metrics = SystemMetrics::default();
+        system_metrics.performance.average_fps = 60.0 - index as f32 * 1.5;
+        system_metrics.performance.average_frame_time = 1000.0 / system_metrics.performance.average_fps;
+        system_metrics.cpu.usage = 20.0 + index as f32;
+        system_metrics.memory.used = 512 * 1024 * 1024 + index * 4096;
+        MetricSnapshot {
+            system_metrics,
+            profiler_metrics: HashMap::new(),
+            frame_tree: Default::default(),
+            timeline_ns: (index + 1) * 16_000_000,
+            timestamp: 1_700_000_000 + index,
+        }
+    }
+
+    /// Scopes de dos hilos: anidados, hermanos y uno que sobresale de su
+    /// padre por cruzar un `.await`
+    fn spans() -> Vec<TraceSpan> {
+        vec![
+            span("frame", 0, 0, 16_000),
+            span("physics", 0, 1_000, 5_000),
+            span("physics.solve", 0, 2_000, 4_000),
+            span("render", 0, 6_000, 15_000),
+            span("render.upload", 0, 14_000, 18_000),
+            span("frame", 0, 16_000, 32_000),
+            span("assets.load", 1, 500, 3_000),
+            span("assets.decode", 1, 1_000, 2_000),
+            span("assets.load", 1, 3_000, 9_000),
+        ]
+    }
+
+    fn load(spans: &[TraceSpan], history: &[MetricSnapshot]) -> StrictTrace {
+        let json = serde_json::to_vec(&chrome_trace(spans, history)).unwrap();
+        serde_json::from_slice(&json).expect("la traza cumple el formato estricto")
+    }
+
+    #[test]
+    fn trace_json_loads_with_the_strict_model() {
+        let history: Vec<MetricSnapshot> = (0..4).map(snapshot).collect();
+        let trace = load(&spans(), &history);
+        assert!(matches!(trace.display_time_unit, StrictTimeUnit::Milliseconds));
+
+        let pid = process_id();
+        let mut thread_names = Vec::new();
+        for event in &trace.trace_events {
+            match event {
+                StrictEvent::Begin { pid: event_pid, cat, .. } | StrictEvent::End { pid: event_pid, cat, .. } => {
+                    assert_eq!(*event_pid, pid);
+                    assert_eq!(cat, SPAN_CATEGORY);
+                }
+                StrictEvent::Counter { pid: event_pid, tid, cat, args, .. } => {
+                    assert_eq!((*event_pid, *tid), (pid, 0));
+                    assert_eq!(cat, COUNTER_CATEGORY);
+                    assert_eq!(args.len(), 1, "un valor por contador");
+                }
+                StrictEvent::Metadata { name, cat, ts, pid: event_pid, tid, args } => {
+                    assert_eq!((name.as_str(), cat.as_str()), ("thread_name", "__metadata"));
+                    assert_eq!((*ts, *event_pid), (0.0, pid));
+                    assert!(!args.name.is_empty());
+                    thread_names.push(*tid);
+                }
+            }
+        }
+        assert_eq!(thread_names, vec![0, 1], "un nombre por hilo con scopes");
+    }
+
+    #[test]
+    fn span_begin_and_end_events_balance_per_thread() {
+        let spans = spans();
+        let trace = load(&spans, &[]);
+
+        let mut stacks: BTreeMap<u32, Vec<(String, f64)>> = BTreeMap::new();
+        let mut begins = 0;
+        for event in &trace.trace_events {
+            match event {
+                StrictEvent::Begin { name, ts, tid, .. } => {
+                    let stack = stacks.entry(*tid).or_default();
+                    if let Some((_, parent_ts)) = stack.last() {
+                        assert!(ts >= parent_ts, "{} empieza antes que su padre", name);
+                    }
+                    stack.push((name.clone(), *ts));
+                    begins += 1;
+                }
+                StrictEvent::End { name, ts, tid, .. } => {
+                    let (open, begin_ts) = stacks.get_mut(tid)
+                        .and_then(|stack| stack.pop())
+                        .unwrap_or_else(|| panic!("E de {} sin B abierto en el hilo {}", name, tid));
+                    assert_eq!(&open, name, "los scopes del hilo {} no anidan", tid);
+                    assert!(*ts >= begin_ts, "{} termina antes de empezar", name);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(begins, spans.len(), "un B por scope");
+        for (thread, stack) in stacks {
+            assert!(stack.is_empty(), "scopes sin cerrar en el hilo {}: {:?}", thread, stack);
+        }
+    }
+
+    #[test]
+    fn counter_samples_match_the_metric_history() {
+        let history: Vec<MetricSnapshot> = (0..8).map(snapshot).collect();
+        let trace = load(&[], &history);
+
+        let mut samples: BTreeMap<String, Vec<(f64, f64)>> = BTreeMap::new();
+        for event in trace.trace_events {
+            if let StrictEvent::Counter { name, ts, args, .. } = event {
+                let value = *args.values().next().unwrap();
+                samples.entry(name).or_default().push((ts, value));
+            }
+        }
+        assert_eq!(samples.len(), 4, "FPS, tiempo de frame, CPU y memoria");
+
+        let expected: [(&str, fn(&SystemMetrics) -> f64); 4] = [
+            ("FPS", |metrics| metrics.performance.average_fps as f64),
+            ("Frame", |metrics| metrics.performance.average_frame_time as f64),
+            ("CPU", |metrics| metrics.cpu.usage as f64),
+            ("Memoria", |metrics| metrics.memory.used as f64),
+        ];
+        for (name, metric) in expected {
+            let series = &samples[name];
+            assert_eq!(series.len(), history.len(), "muestras de {}", name);
+            for ((ts, value), snapshot) in series.iter().zip(&history) {
+                assert_eq!(*ts, snapshot.timeline_ns as f64 / 1000.0, "instante de {}", name);
+                assert_eq!(*value, metric(&snapshot.system_metrics), "valor de {}", name);
+            }
+        }
+    }
+}
@@ -1,16 +1,43 @@
 //! Sistema de Profiling para el motor 3D
 //! 
 //! Proporciona análisis de rendimiento, métricas detalladas,
-//! optimizaciones automáticas y debugging avanzado.
+//! optimizaciones automáticas y debugging avanzado. CPU y memoria se leen
+//! del sistema (`system_info`); el tiempo de GPU y el adaptador los
+//! registra el renderer con `record_gpu_frame` y `set_gpu_adapter`. Los
+//! scopes de `profile_scope!` forman cada frame un árbol de tiempos
+//! (`get_frame_tree`) para la vista de línea de tiempo del editor. Los
+//! reportes se exportan como traza de Chrome y CSV (`export`). Las reglas
+//! de `AlertConfig` se evalúan en cada actualización (`alerts`). Las
+//! optimizaciones automáticas actúan sobre el motor con `apply_optimizations`
+//! y se deshacen si no mejoran lo suficiente (`optimizer`).
+
+pub mod alerts;
+pub mod allocator;
+pub mod export;
+pub mod optimizer;
+pub mod scope;
+pub mod system_info;
+pub mod timeline;
 
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, RwLock};
 use std::time::{Instant, Duration};
 use tokio::sync::mpsc;
 use tracing::{info, debug, error, warn};
 use anyhow::{Result, anyhow};
 
+pub use alerts::{Alert, AlertRule};
+pub use export::ExportOutput;
+pub use optimizer::{DistanceCulling, GpuQuality, OptimizationHooks, OptimizationSetting};
+pub use timeline::{FrameNode, FrameTree};
+
+/// Peso del frame actual en las medias de FPS y tiempo de frame
+const FRAME_AVERAGE_FACTOR: f32 = 0.1;
+
+/// Scopes como máximo retenidos para exportar la traza
+const MAX_TRACE_SPANS: usize = 200_000;
+
 /// Sistema de Profiling principal
 pub struct ProfilingSystem {
     /// Configuración del sistema
@@ -23,6 +50,20 @@ pub struct ProfilingSystem {
     history: Arc<RwLock<Vec<MetricSnapshot>>>,
     /// Optimizaciones automáticas
     auto_optimizations: Arc<RwLock<Vec<AutoOptimization>>>,
+    /// Contadores del allocator en el frame anterior
+    last_allocation: allocator::AllocationSnapshot,
+    /// Lector de CPU y memoria del sistema
+    sampler: system_info::SystemSampler,
+    /// Constructor del árbol de scopes por frame
+    timeline: timeline::TimelineBuilder,
+    /// Árbol del último frame completo
+    frame_tree: FrameTree,
+    /// Scopes recientes para la traza de Chrome
+    trace_spans: VecDeque<timeline::TraceSpan>,
+    /// Instante del último reporte
+    last_report: Option<Instant>,
+    /// Reglas de alerta y sus destinos
+    alerts: alerts::AlertEngine,
     /// Estado del sistema
     running: bool,
 }
@@ -59,6 +100,9 @@ pub struct MetricsConfig {
     pub io_metrics: bool,
     /// Retención de datos
     pub data_retention: Duration,
+    /// Bytes a partir de los cuales se muestrea el call-stack de una asignación (0 = deshabilitado)
+    #[serde(default)]
+    pub allocation_sample_threshold: usize,
 }
 
 /// Configuración de optimizaciones
@@ -74,6 +118,20 @@ pub struct OptimizationConfig {
     pub culling_config: CullingConfig,
     /// Configuración de threading
     pub threading_config: ThreadingConfig,
+    /// Tiempo tras aplicar una optimización hasta comparar las métricas
+    #[serde(default = "default_evaluation_window")]
+    pub evaluation_window: Duration,
+    /// Mejora relativa mínima para conservar una optimización (0.05 = 5%)
+    #[serde(default = "default_min_improvement")]
+    pub min_improvement: f32,
+}
+
+fn default_evaluation_window() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_min_improvement() -> f32 {
+    0.05
 }
 
 /// Configuración de LOD
@@ -161,6 +219,13 @@ pub struct ExportConfig {
     pub html_format: bool,
     /// Directorio de exportación
     pub export_directory: String,
+    /// Bytes máximos por tipo de export; se borran los más antiguos al superarlos
+    #[serde(default = "default_max_export_bytes")]
+    pub max_export_bytes: u64,
+}
+
+fn default_max_export_bytes() -> u64 {
+    64 * 1024 * 1024
 }
 
 /// Configuración de alertas
@@ -168,10 +233,13 @@ pub struct ExportConfig {
 pub struct AlertConfig {
     /// Alertas de rendimiento
     pub performance_alerts: bool,
-    /// Umbral de alerta
+    /// Umbral de alerta (FPS de la regla por defecto)
     pub alert_threshold: f32,
     /// Configuración de notificaciones
     pub notification_config: NotificationConfig,
+    /// Reglas de alerta (vacío: FPS por debajo de `alert_threshold`)
+    #[serde(default)]
+    pub rules: Vec<AlertRule>,
 }
 
 /// Configuración de notificaciones
@@ -183,6 +251,13 @@ pub struct NotificationConfig {
     pub file_notifications: bool,
     /// Notificaciones de red
     pub network_notifications: bool,
+    /// Fichero al que se añaden las alertas, una por línea en JSON
+    #[serde(default = "default_alert_file")]
+    pub alert_file: String,
+}
+
+fn default_alert_file() -> String {
+    "./reports/alerts.jsonl".to_string()
 }
 
 /// Métricas del sistema
@@ -221,6 +296,9 @@ pub struct CPUMetrics {
     pub system_time: f32,
     /// Tiempo de idle
     pub idle_time: f32,
+    /// Uso de cada core
+    #[serde(default)]
+    pub per_core_usage: Vec<f32>,
 }
 
 /// Métricas de memoria
@@ -240,6 +318,21 @@ pub struct MemoryMetrics {
     pub virtual_memory: u64,
     /// Memoria swap
     pub swap_memory: u64,
+    /// Pico de memoria asignada
+    #[serde(default)]
+    pub peak: u64,
+    /// Bytes netos asignados en el último frame
+    #[serde(default)]
+    pub frame_allocated_bytes: i64,
+    /// Asignaciones en el último frame
+    #[serde(default)]
+    pub frame_allocations: u64,
+    /// Swap utilizado
+    #[serde(default)]
+    pub swap_used: u64,
+    /// Bytes vivos asignados por el proceso (allocator de telemetría)
+    #[serde(default)]
+    pub allocated: u64,
 }
 
 /// Métricas de GPU
@@ -261,6 +354,12 @@ pub struct GPUMetrics {
     pub fps: f32,
     /// Frame time
     pub frame_time: f32,
+    /// Nombre del adaptador gráfico
+    #[serde(default)]
+    pub adapter_name: String,
+    /// API gráfica del adaptador
+    #[serde(default)]
+    pub adapter_backend: String,
 }
 
 /// Métricas de red
@@ -338,6 +437,42 @@ pub struct Profiler {
     pub metrics: ProfilerMetrics,
 }
 
+impl Profiler {
+    /// Profiler de un scope medido con `profile_scope!`
+    fn for_scope(name: &str, config: &ProfilingConfig) -> Self {
+        Self {
+            id: name.to_string(),
+            name: name.to_string(),
+            config: ProfilerConfig {
+                enabled: true,
+                detailed_mode: config.detailed_mode,
+                sampling_interval: config.sampling_interval,
+                filter_config: FilterConfig {
+                    time_filter: false,
+                    frequency_filter: false,
+                    min_threshold: 0.0,
+                    max_threshold: f32::MAX,
+                },
+            },
+            state: ProfilerState {
+                active: true,
+                running: false,
+                error: None,
+                start_time: 0,
+                end_time: None,
+            },
+            metrics: ProfilerMetrics {
+                total_time: 0.0,
+                average_time: 0.0,
+                min_time: 0.0,
+                max_time: 0.0,
+                call_count: 0,
+                call_frequency: 0.0,
+            },
+        }
+    }
+}
+
 /// Configuración del profiler
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProfilerConfig {
@@ -403,6 +538,12 @@ pub struct MetricSnapshot {
     pub system_metrics: SystemMetrics,
     /// Métricas de profilers
     pub profiler_metrics: HashMap<String, ProfilerMetrics>,
+    /// Árbol de scopes del frame
+    #[serde(default)]
+    pub frame_tree: FrameTree,
+    /// Instante en la línea de tiempo de los scopes, en ns
+    #[serde(default)]
+    pub timeline_ns: u64,
     /// Timestamp
     pub timestamp: u64,
 }
@@ -444,6 +585,18 @@ pub struct OptimizationState {
     pub error: Option<String>,
     /// Tiempo de aplicación
     pub application_time: u64,
+    /// Ajuste anterior, para deshacerla
+    #[serde(default)]
+    pub previous: Option<OptimizationSetting>,
+    /// Segundos desde que se aplicó
+    #[serde(default)]
+    pub elapsed: f32,
+    /// Ya se compararon las métricas de antes y después
+    #[serde(default)]
+    pub evaluated: bool,
+    /// Se deshizo por no mejorar lo suficiente
+    #[serde(default)]
+    pub rolled_back: bool,
 }
 
 /// Resultado de la optimización
@@ -467,6 +620,14 @@ impl ProfilingSystem {
     /// Crear nuevo sistema de profiling
     pub fn new(config: ProfilingConfig) -> Self {
         info!("Inicializando sistema de Profiling");
+
+        let alert_config = &config.reporting_config.alert_config;
+        let rules = if alert_config.rules.is_empty() {
+            alerts::default_rules(alert_config.alert_threshold)
+        } else {
+            alert_config.rules.clone()
+        };
+        let alerts = alerts::AlertEngine::new(rules, alert_config.notification_config.clone());
         
         Self {
             config,
@@ -474,6 +635,13 @@ impl ProfilingSystem {
             profilers: Arc::new(RwLock::new(HashMap::new())),
             history: Arc::new(RwLock::new(Vec::new())),
             auto_optimizations: Arc::new(RwLock::new(Vec::new())),
+            last_allocation: allocator::AllocationSnapshot::default(),
+            sampler: system_info::SystemSampler::new(),
+            timeline: timeline::TimelineBuilder::new(),
+            frame_tree: FrameTree::default(),
+            trace_spans: VecDeque::new(),
+            last_report: None,
+            alerts,
             running: false,
         }
     }
@@ -518,11 +686,14 @@ impl ProfilingSystem {
                 user_time: 0.0,
                 system_time: 0.0,
                 idle_time: 0.0,
+                per_core_usage: Vec::new(),
             };
         }
 
         // Inicializar métricas de memoria
         if self.config.metrics_config.memory_metrics {
+            allocator::set_sample_threshold(self.config.metrics_config.allocation_sample_threshold);
+            self.last_allocation = allocator::snapshot();
             metrics.memory = MemoryMetrics {
                 total: 0,
                 used: 0,
@@ -531,6 +702,11 @@ impl ProfilingSystem {
                 usage_percentage: 0.0,
                 virtual_memory: 0,
                 swap_memory: 0,
+                peak: 0,
+                frame_allocated_bytes: 0,
+                frame_allocations: 0,
+                swap_used: 0,
+                allocated: 0,
             };
         }
 
@@ -545,6 +721,8 @@ impl ProfilingSystem {
                 power: 0.0,
                 fps: 0.0,
                 frame_time: 0.0,
+                adapter_name: String::new(),
+                adapter_backend: String::new(),
             };
         }
 
@@ -704,47 +882,39 @@ impl ProfilingSystem {
     async fn setup_auto_optimizations(&mut self) -> Result<()> {
         let mut optimizations = self.auto_optimizations.write().unwrap();
 
-        // Optimización de LOD
-        optimizations.push(AutoOptimization {
-            id: "lod_optimization".to_string(),
-            optimization_type: OptimizationType::LOD,
-            config: self.config.optimization_config.clone(),
-            state: OptimizationState {
-                active: true,
-                applied: false,
-                error: None,
-                application_time: 0,
-            },
-            result: OptimizationResult {
-                performance_improvement: 0.0,
-                memory_reduction: 0.0,
-                cpu_reduction: 0.0,
-                gpu_reduction: 0.0,
-                metrics_before: SystemMetrics::default(),
-                metrics_after: SystemMetrics::default(),
-            },
-        });
-
-        // Optimización de culling
-        optimizations.push(AutoOptimization {
-            id: "culling_optimization".to_string(),
-            optimization_type: OptimizationType::Culling,
-            config: self.config.optimization_config.clone(),
-            state: OptimizationState {
-                active: true,
-                applied: false,
-                error: None,
-                application_time: 0,
-            },
-            result: OptimizationResult {
-                performance_improvement: 0.0,
-                memory_reduction: 0.0,
-                cpu_reduction: 0.0,
-                gpu_reduction: 0.0,
-                metrics_before: SystemMetrics::default(),
-                metrics_after: SystemMetrics::default(),
-            },
-        });
+        let types = [
+            ("lod_optimization", OptimizationType::LOD),
+            ("culling_optimization", OptimizationType::Culling),
+            ("threading_optimization", OptimizationType::Threading),
+            ("memory_optimization", OptimizationType::Memory),
+            ("gpu_optimization", OptimizationType::GPU),
+            ("network_optimization", OptimizationType::Network),
+        ];
+        for (id, optimization_type) in types {
+            optimizations.push(AutoOptimization {
+                id: id.to_string(),
+                optimization_type,
+                config: self.config.optimization_config.clone(),
+                state: OptimizationState {
+                    active: true,
+                    applied: false,
+                    error: None,
+                    application_time: 0,
+                    previous: None,
+                    elapsed: 0.0,
+                    evaluated: false,
+                    rolled_back: false,
+                },
+                result: OptimizationResult {
+                    performance_improvement: 0.0,
+                    memory_reduction: 0.0,
+                    cpu_reduction: 0.0,
+                    gpu_reduction: 0.0,
+                    metrics_before: SystemMetrics::default(),
+                    metrics_after: SystemMetrics::default(),
+                },
+            });
+        }
 
         info!("Optimizaciones automáticas configuradas");
         Ok(())
@@ -757,72 +927,105 @@ impl ProfilingSystem {
         }
 
         // Actualizar métricas del sistema
-        self.update_system_metrics().await?;
+        self.update_system_metrics(delta_time).await?;
+
+        // Evaluar las reglas de alerta
+        if self.config.reporting_config.alert_config.performance_alerts {
+            let metrics = self.metrics.read().unwrap().clone();
+            self.alerts.evaluate(delta_time, &metrics);
+        }
 
         // Actualizar profilers
         self.update_profilers(delta_time).await?;
 
-        // Aplicar optimizaciones automáticas
-        if self.config.optimization_config.auto_optimizations {
-            self.apply_auto_optimizations().await?;
+        // Construir el árbol de scopes del último frame completo
+        self.frame_tree = self.timeline.build_frame();
+        self.trace_spans.extend(self.timeline.drain_frame_spans());
+        let excess = self.trace_spans.len().saturating_sub(MAX_TRACE_SPANS);
+        self.trace_spans.drain(..excess);
+
+        // Tiempo desde que se aplicó cada optimización; se evalúan en `apply_optimizations`
+        for optimization in self.auto_optimizations.write().unwrap().iter_mut() {
+            if optimization.state.applied && !optimization.state.evaluated {
+                optimization.state.elapsed += delta_time.max(0.0);
+            }
         }
 
         // Generar snapshot
         self.generate_snapshot().await?;
 
-        // Generar reportes
+        // Generar reportes cada `report_interval`
         if self.config.reporting_config.auto_reports {
-            self.generate_reports().await?;
+            let due = self.last_report
+                .map_or(true, |last| last.elapsed() >= self.config.reporting_config.report_interval);
+            if due {
+                self.last_report = Some(Instant::now());
+                self.generate_reports().await?;
+            }
         }
 
         Ok(())
     }
 
     /// Actualizar métricas del sistema
-    async fn update_system_metrics(&mut self) -> Result<()> {
+    async fn update_system_metrics(&mut self, delta_time: f32) -> Result<()> {
         let mut metrics = self.metrics.write().unwrap();
 
         // Actualizar métricas de CPU
         if self.config.metrics_config.cpu_metrics {
-            // Simular métricas de CPU (en implementación real usaría sysinfo)
-            metrics.cpu.usage = (std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() % 100) as f32;
+            if let Some(cpu) = self.sampler.sample_cpu() {
+                metrics.cpu.usage = cpu.usage;
+                metrics.cpu.cores = cpu.cores;
+                metrics.cpu.frequency = cpu.frequency_mhz;
+                metrics.cpu.per_core_usage = cpu.per_core;
+            }
         }
 
         // Actualizar métricas de memoria
         if self.config.metrics_config.memory_metrics {
-            // Simular métricas de memoria
-            metrics.memory.total = 16 * 1024 * 1024 * 1024; // 16GB
-            metrics.memory.used = (metrics.memory.total as f32 * 0.6) as u64;
-            metrics.memory.free = metrics.memory.total - metrics.memory.used;
-            metrics.memory.available = metrics.memory.free;
-            metrics.memory.usage_percentage = (metrics.memory.used as f32 / metrics.memory.total as f32) * 100.0;
+            let allocation = allocator::snapshot();
+            metrics.memory.allocated = allocation.allocated;
+            metrics.memory.peak = allocation.peak;
+            metrics.memory.frame_allocated_bytes = allocation.allocated as i64 - self.last_allocation.allocated as i64;
+            metrics.memory.frame_allocations = allocation.allocations - self.last_allocation.allocations;
+            self.last_allocation = allocation;
+
+            if let Some(memory) = self.sampler.sample_memory() {
+                metrics.memory.total = memory.total;
+                metrics.memory.used = memory.used;
+                metrics.memory.free = memory.free;
+                metrics.memory.available = memory.available;
+                metrics.memory.virtual_memory = memory.total + memory.swap_total;
+                metrics.memory.swap_memory = memory.swap_total;
+                metrics.memory.swap_used = memory.swap_used;
+                metrics.memory.usage_percentage = if memory.total > 0 {
+                    memory.used as f32 / memory.total as f32 * 100.0
+                } else {
+                    0.0
+                };
+            }
         }
 
-        // Actualizar métricas de GPU
-        if self.config.metrics_config.gpu_metrics {
-            // Simular métricas de GPU
-            metrics.gpu.usage = (std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() % 100) as f32;
-            metrics.gpu.fps = 60.0 + (std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() % 20) as f32;
-            metrics.gpu.frame_time = 1000.0 / metrics.gpu.fps;
+        // Tiempo de frame del bucle; el de GPU llega con `record_gpu_frame`
+        if delta_time > 0.0 {
+            let frame_time = delta_time * 1000.0;
+            let fps = 1.0 / delta_time;
+            metrics.gpu.fps = fps;
+
+            let performance = &mut metrics.performance;
+            let first = performance.average_fps <= 0.0;
+            performance.average_fps = if first { fps } else { performance.average_fps + (fps - performance.average_fps) * FRAME_AVERAGE_FACTOR };
+            performance.min_fps = if first { fps } else { performance.min_fps.min(fps) };
+            performance.max_fps = performance.max_fps.max(fps);
+            performance.average_frame_time = if first {
+                frame_time
+            } else {
+                performance.average_frame_time + (frame_time - performance.average_frame_time) * FRAME_AVERAGE_FACTOR
+            };
+            performance.min_frame_time = if first { frame_time } else { performance.min_frame_time.min(frame_time) };
+            performance.max_frame_time = performance.max_frame_time.max(frame_time);
         }
 
-        // Actualizar métricas de rendimiento
-        metrics.performance.average_fps = metrics.gpu.fps;
-        metrics.performance.min_fps = metrics.gpu.fps.min(metrics.performance.min_fps);
-        metrics.performance.max_fps = metrics.gpu.fps.max(metrics.performance.max_fps);
-        metrics.performance.average_frame_time = metrics.gpu.frame_time;
-        metrics.performance.min_frame_time = metrics.gpu.frame_time.min(metrics.performance.min_frame_time);
-        metrics.performance.max_frame_time = metrics.gpu.frame_time.max(metrics.performance.max_frame_time);
-
         metrics.timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -847,37 +1050,11 @@ impl ProfilingSystem {
             }
         }
 
-        Ok(())
-    }
-
-    /// Aplicar optimizaciones automáticas
-    async fn apply_auto_optimizations(&mut self) -> Result<()> {
-        let metrics = self.metrics.read().unwrap();
-        let mut optimizations = self.auto_optimizations.write().unwrap();
-
-        for optimization in optimizations.iter_mut() {
-            if optimization.state.active && !optimization.state.applied {
-                // Verificar si se necesita optimización
-                let needs_optimization = match optimization.optimization_type {
-                    OptimizationType::LOD => metrics.performance.average_fps < 30.0,
-                    OptimizationType::Culling => metrics.performance.rendered_objects > 10000,
-                    OptimizationType::Threading => metrics.cpu.usage > 80.0,
-                    OptimizationType::Memory => metrics.memory.usage_percentage > 90.0,
-                    OptimizationType::GPU => metrics.gpu.usage > 90.0,
-                    OptimizationType::Network => metrics.network.latency > 100.0,
-                };
-
-                if needs_optimization {
-                    // Aplicar optimización
-                    optimization.state.applied = true;
-                    optimization.state.application_time = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs();
-
-                    info!("Optimización aplicada: {:?}", optimization.optimization_type);
-                }
-            }
+        // Volcar los tiempos medidos con `profile_scope!`
+        for (name, metrics) in scope::snapshot() {
+            profilers.entry(name.to_string())
+                .or_insert_with(|| Profiler::for_scope(name, &self.config))
+                .metrics = metrics;
         }
 
         Ok(())
@@ -897,6 +1074,8 @@ impl ProfilingSystem {
         let snapshot = MetricSnapshot {
             system_metrics: metrics.clone(),
             profiler_metrics,
+            frame_tree: self.frame_tree.clone(),
+            timeline_ns: scope::timeline_now_ns(),
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
@@ -925,11 +1104,11 @@ impl ProfilingSystem {
 
         // Exportar reporte si está configurado
         if self.config.reporting_config.export_config.json_format {
-            self.export_report_json(&report).await?;
+            self.export_report_json().await?;
         }
 
         if self.config.reporting_config.export_config.csv_format {
-            self.export_report_csv(&report).await?;
+            self.export_report_csv().await?;
         }
 
         if self.config.reporting_config.export_config.html_format {
@@ -965,26 +1144,55 @@ impl ProfilingSystem {
     }
 
     /// Exportar reporte JSON
-    async fn export_report_json(&self, report: &str) -> Result<()> {
-        // Implementar exportación JSON
-        debug!("Exportando reporte JSON");
+    async fn export_report_json(&self) -> Result<()> {
+        let output = self.export_chrome_trace()?;
+        debug!("Traza de Chrome exportada: {}", output_label(&output));
         Ok(())
     }
 
     /// Exportar reporte CSV
-    async fn export_report_csv(&self, report: &str) -> Result<()> {
-        // Implementar exportación CSV
-        debug!("Exportando reporte CSV");
+    async fn export_report_csv(&self) -> Result<()> {
+        for output in self.export_csv()? {
+            debug!("CSV exportado: {}", output_label(&output));
+        }
         Ok(())
     }
 
     /// Exportar reporte HTML
     async fn export_report_html(&self, report: &str) -> Result<()> {
-        // Implementar exportación HTML
-        debug!("Exportando reporte HTML");
+        let output = self.write_export("report", "html", export::report_html(report).into_bytes())?;
+        debug!("Reporte HTML exportado: {}", output_label(&output));
         Ok(())
     }
 
+    /// Exportar los scopes recientes y el historial de métricas como traza de
+    /// Chrome (`chrome://tracing`, Perfetto). En wasm devuelve los bytes
+    pub fn export_chrome_trace(&self) -> Result<ExportOutput> {
+        let spans: Vec<timeline::TraceSpan> = self.trace_spans.iter().copied().collect();
+        let trace = export::chrome_trace(&spans, &self.history.read().unwrap());
+        let bytes = serde_json::to_vec(&trace)
+            .map_err(|e| anyhow!("Error serializando la traza: {}", e))?;
+        self.write_export("trace", "json", bytes)
+    }
+
+    /// Exportar el historial de métricas y los árboles de frame como CSV. En
+    /// wasm devuelve los bytes
+    pub fn export_csv(&self) -> Result<Vec<ExportOutput>> {
+        let history = self.history.read().unwrap();
+        let metrics = export::metrics_csv(&history);
+        let frames = export::frames_csv(&history);
+        drop(history);
+        Ok(vec![
+            self.write_export("metrics", "csv", metrics.into_bytes())?,
+            self.write_export("frames", "csv", frames.into_bytes())?,
+        ])
+    }
+
+    fn write_export(&self, prefix: &str, extension: &str, bytes: Vec<u8>) -> Result<ExportOutput> {
+        let config = &self.config.reporting_config.export_config;
+        export::write_export(&config.export_directory, prefix, extension, bytes, config.max_export_bytes)
+    }
+
     /// Iniciar profiler
     pub fn start_profiler(&mut self, id: &str) -> Result<()> {
         let mut profilers = self.profilers.write().unwrap();
@@ -1024,6 +1232,43 @@ impl ProfilingSystem {
         Ok(())
     }
 
+    /// Registrar las estadísticas del último frame renderizado (tras el culling)
+    pub fn record_render_stats(&self, rendered_objects: u32, rendered_triangles: u32, draw_calls: u32) {
+        let mut metrics = self.metrics.write().unwrap();
+        metrics.performance.rendered_objects = rendered_objects;
+        metrics.performance.rendered_triangles = rendered_triangles;
+        metrics.performance.draw_calls = draw_calls;
+    }
+
+    /// Registrar el tiempo de GPU del último frame (de las timestamp
+    /// queries; 0 si el backend no las tiene) y la memoria de GPU usada si
+    /// el backend la conoce
+    pub fn record_gpu_frame(&self, gpu_time_ms: f32, memory_used: Option<u64>) {
+        if !self.config.metrics_config.gpu_metrics {
+            return;
+        }
+        let mut metrics = self.metrics.write().unwrap();
+        metrics.gpu.frame_time = gpu_time_ms;
+        if let Some(memory_used) = memory_used {
+            metrics.gpu.memory_used = memory_used;
+        }
+        // Fracción del frame que la GPU está ocupada
+        let frame_time = metrics.performance.average_frame_time;
+        if gpu_time_ms > 0.0 && frame_time > 0.0 {
+            metrics.gpu.usage = (gpu_time_ms / frame_time * 100.0).min(100.0);
+        }
+    }
+
+    /// Registrar el adaptador gráfico en uso
+    pub fn set_gpu_adapter(&self, info: &wgpu::AdapterInfo) {
+        if !self.config.metrics_config.gpu_metrics {
+            return;
+        }
+        let mut metrics = self.metrics.write().unwrap();
+        metrics.gpu.adapter_name = info.name.clone();
+        metrics.gpu.adapter_backend = format!("{:?}", info.backend);
+    }
+
     /// Obtener métricas del sistema
     pub fn get_system_metrics(&self) -> SystemMetrics {
         let metrics = self.metrics.read().unwrap();
@@ -1036,6 +1281,98 @@ impl ProfilingSystem {
         profilers.get(id).map(|p| p.metrics.clone())
     }
 
+    /// Obtener los puntos de asignación con más bytes muestreados
+    pub fn get_allocation_hotspots(&self, top_n: usize) -> Vec<allocator::AllocationSite> {
+        allocator::hotspots(top_n)
+    }
+
+    /// Aplicar sobre los sistemas del motor las optimizaciones que pidan las
+    /// métricas actuales y evaluar las ya aplicadas. Pasado
+    /// `evaluation_window` desde que se aplicó, una optimización cuya mejora
+    /// no llegue a `min_improvement` se deshace y no se vuelve a intentar.
+    /// Se llama tras `update` con los sistemas que se quieren optimizar
+    pub fn apply_optimizations(&mut self, hooks: &mut dyn OptimizationHooks) {
+        if !self.running || !self.config.optimization_config.auto_optimizations {
+            return;
+        }
+
+        let metrics = self.metrics.read().unwrap().clone();
+        let mut optimizations = self.auto_optimizations.write().unwrap();
+
+        for optimization in optimizations.iter_mut().filter(|optimization| optimization.state.active) {
+            let state = &mut optimization.state;
+            if !state.applied {
+                if !optimizer::needs_optimization(&optimization.optimization_type, &metrics) {
+                    continue;
+                }
+                state.previous = Some(optimizer::apply(&optimization.optimization_type, &optimization.config, hooks));
+                state.applied = true;
+                state.elapsed = 0.0;
+                state.application_time = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                optimization.result.metrics_before = metrics.clone();
+                info!("Optimización aplicada: {:?}", optimization.optimization_type);
+                continue;
+            }
+
+            if state.evaluated || state.elapsed < optimization.config.evaluation_window.as_secs_f32() {
+                continue;
+            }
+            state.evaluated = true;
+
+            let result = &mut optimization.result;
+            let before = &result.metrics_before;
+            result.performance_improvement = optimizer::improvement(&optimization.optimization_type, before, &metrics);
+            result.memory_reduction = optimizer::relative_reduction(before.memory.used as f32, metrics.memory.used as f32);
+            result.cpu_reduction = optimizer::relative_reduction(before.cpu.usage, metrics.cpu.usage);
+            result.gpu_reduction = optimizer::relative_reduction(before.gpu.usage, metrics.gpu.usage);
+            result.metrics_after = metrics.clone();
+
+            if result.performance_improvement >= optimization.config.min_improvement {
+                info!(
+                    "Optimización {:?} conservada: mejora {:.1}%",
+                    optimization.optimization_type,
+                    result.performance_improvement * 100.0
+                );
+                continue;
+            }
+            if let Some(previous) = state.previous.take() {
+                previous.restore(hooks);
+            }
+            state.rolled_back = true;
+            state.active = false;
+            warn!(
+                "Optimización {:?} deshecha: mejora {:.1}% por debajo del {:.1}%",
+                optimization.optimization_type,
+                result.performance_improvement * 100.0,
+                optimization.config.min_improvement * 100.0
+            );
+        }
+    }
+
+    /// Estado y resultado de las optimizaciones automáticas
+    pub fn get_optimizations(&self) -> Vec<AutoOptimization> {
+        self.auto_optimizations.read().unwrap().clone()
+    }
+
+    /// Árbol de scopes del último frame completo, con tiempos inclusivos y
+    /// exclusivos por nodo
+    pub fn get_frame_tree(&self) -> FrameTree {
+        self.frame_tree.clone()
+    }
+
+    /// Canal que recibe las alertas de rendimiento (disparos y recuperaciones)
+    pub fn subscribe_alerts(&mut self) -> mpsc::UnboundedReceiver<Alert> {
+        self.alerts.subscribe()
+    }
+
+    /// Reglas con alerta activa
+    pub fn active_alerts(&self) -> Vec<String> {
+        self.alerts.active()
+    }
+
     /// Obtener historial
     pub fn get_history(&self) -> Vec<MetricSnapshot> {
         let history = self.history.read().unwrap();
@@ -1050,6 +1387,9 @@ impl ProfilingSystem {
         self.metrics.write().unwrap().clone_from(&SystemMetrics::default());
         self.profilers.write().unwrap().clear();
         self.history.write().unwrap().clear();
+        self.timeline.clear();
+        self.frame_tree = FrameTree::default();
+        self.trace_spans.clear();
         self.auto_optimizations.write().unwrap().clear();
         
         info!("Sistema de Profiling limpiado");
@@ -1068,6 +1408,7 @@ impl Default for SystemMetrics {
                 user_time: 0.0,
                 system_time: 0.0,
                 idle_time: 0.0,
+                per_core_usage: Vec::new(),
             },
             memory: MemoryMetrics {
                 total: 0,
@@ -1077,6 +1418,11 @@ impl Default for SystemMetrics {
                 usage_percentage: 0.0,
                 virtual_memory: 0,
                 swap_memory: 0,
+                peak: 0,
+                frame_allocated_bytes: 0,
+                frame_allocations: 0,
+                swap_used: 0,
+                allocated: 0,
             },
             gpu: GPUMetrics {
                 usage: 0.0,
@@ -1087,6 +1433,8 @@ impl Default for SystemMetrics {
                 power: 0.0,
                 fps: 0.0,
                 frame_time: 0.0,
+                adapter_name: String::new(),
+                adapter_backend: String::new(),
             },
             network: NetworkMetrics {
                 bandwidth_in: 0.0,
@@ -1120,4 +1468,12 @@ impl Default for SystemMetrics {
             timestamp: 0,
         }
     }
-} 
\ No newline at end of file
+} 
+
+/// Descripción de un export para los logs
+fn output_label(output: &ExportOutput) -> String {
+    match output {
+        ExportOutput::File(path) => path.display().to_string(),
+        ExportOutput::Bytes(bytes) => format!("{} bytes", bytes.len()),
+    }
+}
@@ -0,0 +1,401 @@
+//! # Acciones de las optimizaciones automáticas
+//!
+//! El sistema de profiling decide cuándo se aplica cada `OptimizationType`;
+//! el cambio lo hacen los sistemas del motor a través de `OptimizationHooks`,
+//! que implementa quien los posee (`Engine3D`):
+//!
+//! - LOD: acerca las distancias de LOD del renderer.
+//! - Culling: activa el distance culling y reduce su radio.
+//! - Threading: quita un hilo al scheduler del ECS.
+//! - Memory: encoge los pools de componentes y desaloja la caché de assets.
+//! - GPU: baja un paso la resolución de sombras y el MSAA.
+//! - Network: reduce la frecuencia de ticks de replicación.
+//!
+//! Cada acción devuelve el ajuste anterior (`OptimizationSetting`) para
+//! poder deshacerla si no mejora la métrica que la disparó.
+
+use serde::{Serialize, Deserialize};
+
+use super::{OptimizationConfig, OptimizationType, SystemMetrics};
+
+/// Factor del radio de distance culling en cada aplicación
+const CULLING_RADIUS_FACTOR: f32 = 0.75;
+/// Radio de partida cuando el distance culling no tenía límite
+const DEFAULT_CULLING_DISTANCE: f32 = 500.0;
+/// Radio mínimo de distance culling
+const MIN_CULLING_DISTANCE: f32 = 50.0;
+/// Multiplicador mínimo de las distancias de LOD
+const MIN_LOD_DISTANCE_BIAS: f32 = 0.1;
+/// Resolución mínima de los shadow maps
+const MIN_SHADOW_RESOLUTION: u32 = 512;
+/// Tick de replicación de partida cuando se replicaba en cada update
+const DEFAULT_REPLICATION_TICK_RATE: f32 = 30.0;
+/// Tick de replicación mínimo
+const MIN_REPLICATION_TICK_RATE: f32 = 5.0;
+
+/// Distance culling del renderer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DistanceCulling {
+    /// Habilitado
+    pub enabled: bool,
+    /// Distancia máxima de dibujo (0 = sin límite)
+    pub max_distance: f32,
+}
+
+/// Ajustes de calidad de GPU que puede bajar la optimización
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GpuQuality {
+    /// Resolución de cada cascada de sombras
+    pub shadow_resolution: u32,
+    /// Muestras MSAA (1 = sin MSAA)
+    pub msaa_samples: u32,
+}
+
+impl GpuQuality {
+    /// Un paso por debajo: mitad de resolución de sombras y mitad de muestras
+    pub fn step_down(&self) -> Self {
+        Self {
+            shadow_resolution: (self.shadow_resolution / 2).max(MIN_SHADOW_RESOLUTION).min(self.shadow_resolution),
+            msaa_samples: (self.msaa_samples / 2).max(1),
+        }
+    }
+}
+
+/// Puntos de los sistemas del motor sobre los que actúan las optimizaciones
+pub trait OptimizationHooks {
+    /// Multiplicador global de las distancias de LOD
+    fn lod_distance_bias(&self) -> f32;
+    /// Cambiar el multiplicador de las distancias de LOD
+    fn set_lod_distance_bias(&mut self, bias: f32);
+    /// Distance culling actual
+    fn distance_culling(&self) -> DistanceCulling;
+    /// Cambiar el distance culling
+    fn set_distance_culling(&mut self, culling: DistanceCulling);
+    /// Hilos del scheduler del ECS
+    fn worker_threads(&self) -> usize;
+    /// Cambiar los hilos del scheduler del ECS
+    fn set_worker_threads(&mut self, threads: usize);
+    /// Encoger los pools de componentes y desalojar la caché de assets.
+    /// Devuelve las entradas liberadas
+    fn reclaim_memory(&mut self) -> usize;
+    /// Calidad de GPU actual
+    fn gpu_quality(&self) -> GpuQuality;
+    /// Cambiar la calidad de GPU
+    fn set_gpu_quality(&mut self, quality: GpuQuality);
+    /// Ticks de replicación por segundo (0 = en cada update)
+    fn replication_tick_rate(&self) -> f32;
+    /// Cambiar los ticks de replicación por segundo
+    fn set_replication_tick_rate(&mut self, rate: f32);
+}
+
+/// Ajuste anterior a una optimización
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OptimizationSetting {
+    /// Multiplicador de LOD
+    LodDistanceBias(f32),
+    /// Distance culling
+    DistanceCulling(DistanceCulling),
+    /// Hilos del ECS
+    WorkerThreads(usize),
+    /// La memoria liberada no se puede devolver
+    Irreversible,
+    /// Calidad de GPU
+    GpuQuality(GpuQuality),
+    /// Ticks de replicación
+    ReplicationTickRate(f32),
+}
+
+impl OptimizationSetting {
+    /// Restaurar el ajuste
+    pub fn restore(&self, hooks: &mut dyn OptimizationHooks) {
+        match *self {
+            OptimizationSetting::LodDistanceBias(bias) => hooks.set_lod_distance_bias(bias),
+            OptimizationSetting::DistanceCulling(culling) => hooks.set_distance_culling(culling),
+            OptimizationSetting::WorkerThreads(threads) => hooks.set_worker_threads(threads),
+            OptimizationSetting::Irreversible => {}
+            OptimizationSetting::GpuQuality(quality) => hooks.set_gpu_quality(quality),
+            OptimizationSetting::ReplicationTickRate(rate) => hooks.set_replication_tick_rate(rate),
+        }
+    }
+}
+
+/// La optimización hace falta con estas métricas
+pub fn needs_optimization(optimization_type: &OptimizationType, metrics: &SystemMetrics) -> bool {
+    match optimization_type {
+        OptimizationType::LOD => metrics.performance.average_fps < 30.0,
+        OptimizationType::Culling => metrics.performance.rendered_objects > 10000,
+        OptimizationType::Threading => metrics.cpu.usage > 80.0,
+        OptimizationType::Memory => metrics.memory.usage_percentage > 90.0,
+        OptimizationType::GPU => metrics.gpu.usage > 90.0,
+        OptimizationType::Network => metrics.network.latency > 100.0,
+    }
+}
+
+/// Aplicar la acción de una optimización y devolver el ajuste anterior
+pub fn apply(
+    optimization_type: &OptimizationType,
+    config: &OptimizationConfig,
+    hooks: &mut dyn OptimizationHooks,
+) -> OptimizationSetting {
+    match optimization_type {
+        OptimizationType::LOD => {
+            let bias = hooks.lod_distance_bias();
+            let factor = if config.lod_config.enabled && config.lod_config.reduction_factor > 0.0 {
+                config.lod_config.reduction_factor.min(1.0)
+            } else {
+                1.0
+            };
+            hooks.set_lod_distance_bias((bias * factor).max(MIN_LOD_DISTANCE_BIAS));
+            OptimizationSetting::LodDistanceBias(bias)
+        }
+        OptimizationType::Culling => {
+            let culling = hooks.distance_culling();
+            let radius = if culling.enabled && culling.max_distance > 0.0 {
+                culling.max_distance * CULLING_RADIUS_FACTOR
+            } else {
+                DEFAULT_CULLING_DISTANCE
+            };
+            hooks.set_distance_culling(DistanceCulling {
+                enabled: true,
+                max_distance: radius.max(MIN_CULLING_DISTANCE),
+            });
+            OptimizationSetting::DistanceCulling(culling)
+        }
+        OptimizationType::Threading => {
+            // Con la CPU saturada el ECS no debe ocupar los núcleos del
+            // renderer y del sistema: un hilo menos que el límite configurado
+            let threads = hooks.worker_threads();
+            let limit = (config.threading_config.thread_count as usize).max(1);
+            hooks.set_worker_threads(threads.min(limit).saturating_sub(1).max(1));
+            OptimizationSetting::WorkerThreads(threads)
+        }
+        OptimizationType::Memory => {
+            hooks.reclaim_memory();
+            OptimizationSetting::Irreversible
+        }
+        OptimizationType::GPU => {
+            let quality = hooks.gpu_quality();
+            hooks.set_gpu_quality(quality.step_down());
+            OptimizationSetting::GpuQuality(quality)
+        }
+        OptimizationType::Network => {
+            let rate = hooks.replication_tick_rate();
+            let lowered = if rate > 0.0 { rate * 0.5 } else { DEFAULT_REPLICATION_TICK_RATE };
+            hooks.set_replication_tick_rate(lowered.max(MIN_REPLICATION_TICK_RATE));
+            OptimizationSetting::ReplicationTickRate(rate)
+        }
+    }
+}
+
+/// Mejora relativa de la métrica que dispara cada optimización (positiva si mejora)
+pub fn improvement(optimization_type: &OptimizationType, before: &SystemMetrics, after: &SystemMetrics) -> f32 {
+    match optimization_type {
+        OptimizationType::LOD => relative_gain(before.performance.average_fps, after.performance.average_fps),
+        OptimizationType::Culling => relative_reduction(
+            before.performance.rendered_objects as f32,
+            after.performance.rendered_objects as f32,
+        ),
+        OptimizationType::Threading => relative_reduction(before.cpu.usage, after.cpu.usage),
+        OptimizationType::Memory => relative_reduction(before.memory.used as f32, after.memory.used as f32),
+        OptimizationType::GPU => relative_reduction(before.gpu.usage, after.gpu.usage),
+        OptimizationType::Network => relative_reduction(before.network.latency, after.network.latency),
+    }
+}
+
+/// Aumento relativo de una métrica en la que más es mejor
+pub fn relative_gain(before: f32, after: f32) -> f32 {
+    if before > 0.0 { (after - before) / before } else { 0.0 }
+}
+
+/// Reducción relativa de una métrica en la que menos es mejor
+pub fn relative_reduction(before: f32, after: f32) -> f32 {
+    if before > 0.0 { (before - after) / before } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiling::{CullingConfig, LODConfig, OctreeConfig, SubdivisionConfig, ThreadingConfig};
+    use std::time::Duration;
+
+    const ALL_TYPES: [OptimizationType; 6] = [
+        OptimizationType::LOD,
+        OptimizationType::Culling,
+        OptimizationType::Threading,
+        OptimizationType::Memory,
+        OptimizationType::GPU,
+        OptimizationType::Network,
+    ];
+
+    /// Sistemas del motor simulados que registran cada llamada de cambio
+    #[derive(Debug, Clone, PartialEq)]
+    struct MockHooks {
+        lod_distance_bias: f32,
+        distance_culling: DistanceCulling,
+        worker_threads: usize,
+        gpu_quality: GpuQuality,
+        replication_tick_rate: f32,
+        calls: Vec<&'static str>,
+    }
+
+    impl MockHooks {
+        fn new() -> Self {
+            Self {
+                lod_distance_bias: 1.0,
+                distance_culling: DistanceCulling { enabled: false, max_distance: 0.0 },
+                worker_threads: 8,
+                gpu_quality: GpuQuality { shadow_resolution: 2048, msaa_samples: 4 },
+                replication_tick_rate: 20.0,
+                calls: Vec::new(),
+            }
+        }
+
+        /// Ajustes sin el registro de llamadas
+        fn settings(&self) -> Self {
+            Self { calls: Vec::new(), ..self.clone() }
+        }
+    }
+
+    impl OptimizationHooks for MockHooks {
+        fn lod_distance_bias(&self) -> f32 {
+            self.lod_distance_bias
+        }
+
+        fn set_lod_distance_bias(&mut self, bias: f32) {
+            self.calls.push("set_lod_distance_bias");
+            self.lod_distance_bias = bias;
+        }
+
+        fn distance_culling(&self) -> DistanceCulling {
+            self.distance_culling
+        }
+
+        fn set_distance_culling(&mut self, culling: DistanceCulling) {
+            self.calls.push("set_distance_culling");
+            self.distance_culling = culling;
+        }
+
+        fn worker_threads(&self) -> usize {
+            self.worker_threads
+        }
+
+        fn set_worker_threads(&mut self, threads: usize) {
+            self.calls.push("set_worker_threads");
+            self.worker_threads = threads;
+        }
+
+        fn reclaim_memory(&mut self) -> usize {
+            self.calls.push("reclaim_memory");
+            16
+        }
+
+        fn gpu_quality(&self) -> GpuQuality {
+            self.gpu_quality
+        }
+
+        fn set_gpu_quality(&mut self, quality: GpuQuality) {
+            self.calls.push("set_gpu_quality");
+            self.gpu_quality = quality;
+        }
+
+        fn replication_tick_rate(&self) -> f32 {
+            self.replication_tick_rate
+        }
+
+        fn set_replication_tick_rate(&mut self, rate: f32) {
+            self.calls.push("set_replication_tick_rate");
+            self.replication_tick_rate = rate;
+        }
+    }
+
+    fn config() -> OptimizationConfig {
+        OptimizationConfig {
+            auto_optimizations: true,
+            performance_threshold: 30.0,
+            lod_config: LODConfig { enabled: true, base_distance: 100.0, reduction_factor: 0.5, max_levels: 4 },
+            culling_config: CullingConfig {
+                frustum_culling: true,
+                occlusion_culling: false,
+                distance_culling: true,
+                octree_config: OctreeConfig {
+                    max_depth: 8,
+                    min_node_size: 1.0,
+                    subdivision_config: SubdivisionConfig { enabled: true, object_threshold: 16, density_factor: 1.0 },
+                },
+            },
+            threading_config: ThreadingConfig {
+                auto_threading: true,
+                thread_count: 6,
+                work_stealing: true,
+                load_balancing: true,
+            },
+            evaluation_window: Duration::from_secs(5),
+            min_improvement: 0.05,
+        }
+    }
+
+    #[test]
+    fn each_optimization_invokes_its_engine_hook() {
+        let expected: [(&str, fn(&MockHooks) -> bool); 6] = [
+            ("set_lod_distance_bias", |hooks| hooks.lod_distance_bias == 0.5),
+            ("set_distance_culling", |hooks| {
+                hooks.distance_culling == DistanceCulling { enabled: true, max_distance: DEFAULT_CULLING_DISTANCE }
+            }),
+            // Un hilo menos que el límite de la configuración
+            ("set_worker_threads", |hooks| hooks.worker_threads == 5),
+            ("reclaim_memory", |_| true),
+            ("set_gpu_quality", |hooks| {
+                hooks.gpu_quality == GpuQuality { shadow_resolution: 1024, msaa_samples: 2 }
+            }),
+            ("set_replication_tick_rate", |hooks| hooks.replication_tick_rate == 10.0),
+        ];
+
+        for (optimization_type, (call, applied)) in ALL_TYPES.iter().zip(expected) {
+            let mut hooks = MockHooks::new();
+            apply(optimization_type, &config(), &mut hooks);
+            assert_eq!(hooks.calls, vec![call], "{:?}", optimization_type);
+            assert!(applied(&hooks), "{:?} no cambió el ajuste: {:?}", optimization_type, hooks);
+        }
+    }
+
+    #[test]
+    fn repeated_optimizations_stop_at_their_limits() {
+        let mut hooks = MockHooks::new();
+        for _ in 0..20 {
+            for optimization_type in &ALL_TYPES {
+                apply(optimization_type, &config(), &mut hooks);
+            }
+        }
+        assert_eq!(hooks.lod_distance_bias, MIN_LOD_DISTANCE_BIAS);
+        assert_eq!(hooks.distance_culling.max_distance, MIN_CULLING_DISTANCE);
+        assert_eq!(hooks.worker_threads, 1);
+        assert_eq!(hooks.gpu_quality, GpuQuality { shadow_resolution: MIN_SHADOW_RESOLUTION, msaa_samples: 1 });
+        assert_eq!(hooks.replication_tick_rate, MIN_REPLICATION_TICK_RATE);
+    }
+
+    #[test]
+    fn rollback_restores_the_original_settings() {
+        let mut hooks = MockHooks::new();
+        let original = hooks.settings();
+
+        let previous: Vec<OptimizationSetting> = ALL_TYPES.iter()
+            .map(|optimization_type| apply(optimization_type, &config(), &mut hooks))
+            .collect();
+        assert_ne!(hooks.settings(), original);
+        assert_eq!(previous[3], OptimizationSetting::Irreversible);
+
+        // Sin mejora suficiente tras la ventana se deshace cada una, en
+        // orden inverso como una pila de cambios
+        let before = SystemMetrics::default();
+        for (optimization_type, setting) in ALL_TYPES.iter().zip(&previous).rev() {
+            assert!(improvement(optimization_type, &before, &before) < config().min_improvement);
+            setting.restore(&mut hooks);
+        }
+        assert_eq!(hooks.settings(), original);
+
+        // Deshacer la memoria liberada no llama a ningún hook
+        let calls = hooks.calls.len();
+        OptimizationSetting::Irreversible.restore(&mut hooks);
+        assert_eq!(hooks.calls.len(), calls);
+    }
+}
@@ -0,0 +1,292 @@
+//! # Scopes de Profiling
+//!
+//! Medición de tiempos por scope con guardas RAII. `profile_scope!` y
+//! `profile_function!` crean una `ProfilingGuard` que acumula el tiempo del
+//! scope en contadores atómicos; sin la feature `profiling` los macros no
+//! generan código.
+//!
+//! Cada guarda además deja un evento de inicio y otro de fin en el buffer
+//! del hilo en que se ejecuta. `ProfilingSystem` recoge los buffers de todos
+//! los hilos una vez por frame y `timeline` reconstruye con ellos el árbol
+//! de scopes anidados. Los eventos llevan el hilo en que se abrió el scope,
+//! así que un scope que cruza un `.await` y termina en otro hilo del runtime
+//! sigue colgando de sus padres.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Instant;
+
+use super::ProfilerMetrics;
+
+/// Abre un scope de profiling hasta el final del bloque actual
+#[cfg(feature = "profiling")]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_guard = $crate::profiling::scope::ProfilingGuard::new($name);
+    };
+}
+
+/// Abre un scope de profiling hasta el final del bloque actual
+#[cfg(not(feature = "profiling"))]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _ = ();
+    };
+}
+
+/// Abre un scope de profiling con el nombre de la función actual
+#[macro_export]
+macro_rules! profile_function {
+    () => {
+        $crate::profile_scope!({
+            fn f() {}
+            fn type_name_of<T>(_: T) -> &'static str {
+                ::std::any::type_name::<T>()
+            }
+            type_name_of(f)
+                .trim_end_matches("::f")
+                .trim_end_matches("::{{closure}}")
+        });
+    };
+}
+
+/// Contadores acumulados de un scope
+#[derive(Debug)]
+pub struct ScopeStats {
+    /// Tiempo total en nanosegundos
+    total_ns: AtomicU64,
+    /// Llamadas
+    calls: AtomicU64,
+    /// Tiempo mínimo en nanosegundos
+    min_ns: AtomicU64,
+    /// Tiempo máximo en nanosegundos
+    max_ns: AtomicU64,
+}
+
+impl ScopeStats {
+    fn new() -> Self {
+        Self {
+            total_ns: AtomicU64::new(0),
+            calls: AtomicU64::new(0),
+            min_ns: AtomicU64::new(u64::MAX),
+            max_ns: AtomicU64::new(0),
+        }
+    }
+
+    /// Registrar una ejecución del scope
+    fn record(&self, elapsed_ns: u64) {
+        self.total_ns.fetch_add(elapsed_ns, Ordering::Relaxed);
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.min_ns.fetch_min(elapsed_ns, Ordering::Relaxed);
+        self.max_ns.fetch_max(elapsed_ns, Ordering::Relaxed);
+    }
+
+    /// Métricas en el formato de los profilers (segundos)
+    pub fn metrics(&self) -> ProfilerMetrics {
+        let calls = self.calls.load(Ordering::Relaxed);
+        let total_time = self.total_ns.load(Ordering::Relaxed) as f32 / 1e9;
+        let min_ns = self.min_ns.load(Ordering::Relaxed);
+        ProfilerMetrics {
+            total_time,
+            average_time: if calls > 0 { total_time / calls as f32 } else { 0.0 },
+            min_time: if calls > 0 { min_ns as f32 / 1e9 } else { 0.0 },
+            max_time: self.max_ns.load(Ordering::Relaxed) as f32 / 1e9,
+            call_count: calls,
+            call_frequency: if total_time > 0.0 { calls as f32 / total_time } else { 0.0 },
+        }
+    }
+}
+
+/// Registro global de scopes; los contadores viven lo que dura el proceso
+fn registry() -> &'static RwLock<HashMap<&'static str, &'static ScopeStats>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<&'static str, &'static ScopeStats>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+thread_local! {
+    /// Caché por hilo para no tomar el lock del registro en cada scope
+    static SCOPE_CACHE: RefCell<HashMap<&'static str, &'static ScopeStats>> = RefCell::new(HashMap::new());
+}
+
+/// Contadores de un scope por nombre, con el nombre con vida estática.
+/// Solo la primera vez que un hilo ve un nombre se consulta el registro global
+fn scope_stats(name: &str) -> (&'static str, &'static ScopeStats) {
+    SCOPE_CACHE.with(|cache| {
+        if let Some((key, stats)) = cache.borrow().get_key_value(name) {
+            return (*key, *stats);
+        }
+        let mut registry = registry().write().unwrap();
+        let (key, stats) = match registry.get_key_value(name) {
+            Some((key, stats)) => (*key, *stats),
+            None => {
+                let key: &'static str = Box::leak(name.to_owned().into_boxed_str());
+                let stats: &'static ScopeStats = Box::leak(Box::new(ScopeStats::new()));
+                registry.insert(key, stats);
+                (key, stats)
+            }
+        };
+        cache.borrow_mut().insert(key, stats);
+        (key, stats)
+    })
+}
+
+/// Eventos como máximo en el buffer de un hilo entre dos recogidas; los que
+/// no caben se descartan
+const MAX_BUFFERED_EVENTS: usize = 1 << 20;
+
+/// Evento de la línea de tiempo
+#[derive(Debug, Clone, Copy)]
+pub enum TimelineEvent {
+    /// Apertura de un scope
+    Begin { span: u64, name: &'static str, thread: u32, at_ns: u64 },
+    /// Cierre de un scope
+    End { span: u64, at_ns: u64 },
+}
+
+/// Buffer de eventos de un hilo
+type EventBuffer = Arc<Mutex<Vec<TimelineEvent>>>;
+
+/// Buffers de todos los hilos que han abierto scopes, con su índice y nombre
+fn thread_buffers() -> &'static Mutex<Vec<(u32, String, EventBuffer)>> {
+    static BUFFERS: OnceLock<Mutex<Vec<(u32, String, EventBuffer)>>> = OnceLock::new();
+    BUFFERS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+thread_local! {
+    /// Índice y buffer de eventos del hilo
+    static THREAD_BUFFER: (u32, EventBuffer) = {
+        static NEXT_THREAD: AtomicU32 = AtomicU32::new(0);
+        let index = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+        let name = std::thread::current().name().map_or_else(|| format!("hilo-{}", index), str::to_owned);
+        let buffer = EventBuffer::default();
+        thread_buffers().lock().unwrap().push((index, name, buffer.clone()));
+        (index, buffer)
+    };
+}
+
+/// Origen de los tiempos de la línea de tiempo
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// Nanosegundos desde el origen de la línea de tiempo
+pub fn timeline_now_ns() -> u64 {
+    epoch().elapsed().as_nanos() as u64
+}
+
+/// Guardar un evento en el buffer del hilo actual (se pierde si el hilo ya
+/// está destruyendo sus thread-locals)
+fn push_event(event: impl FnOnce(u32) -> TimelineEvent) {
+    let _ = THREAD_BUFFER.try_with(|(thread, buffer)| {
+        let mut events = buffer.lock().unwrap();
+        if events.len() < MAX_BUFFERED_EVENTS {
+            events.push(event(*thread));
+        }
+    });
+}
+
+/// Sacar los eventos de todos los hilos
+pub fn drain_timeline() -> Vec<TimelineEvent> {
+    let buffers = thread_buffers().lock().unwrap();
+    let mut events = Vec::new();
+    for (_, _, buffer) in buffers.iter() {
+        events.append(&mut buffer.lock().unwrap());
+    }
+    events
+}
+
+/// Nombre de un hilo por su índice
+pub fn thread_name(thread: u32) -> String {
+    thread_buffers().lock().unwrap()
+        .iter()
+        .find(|(index, _, _)| *index == thread)
+        .map_or_else(|| format!("hilo-{}", thread), |(_, name, _)| name.clone())
+}
+
+/// Guarda RAII que mide un scope hasta que se destruye
+#[must_use = "el scope se mide mientras la guarda está viva"]
+pub struct ProfilingGuard {
+    stats: &'static ScopeStats,
+    span: u64,
+    start: Instant,
+}
+
+impl ProfilingGuard {
+    /// Comenzar a medir un scope
+    #[inline]
+    pub fn new(name: &str) -> Self {
+        static NEXT_SPAN: AtomicU64 = AtomicU64::new(0);
+        let (name, stats) = scope_stats(name);
+        let span = NEXT_SPAN.fetch_add(1, Ordering::Relaxed);
+        push_event(|thread| TimelineEvent::Begin { span, name, thread, at_ns: timeline_now_ns() });
+        Self {
+            stats,
+            span,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for ProfilingGuard {
+    #[inline]
+    fn drop(&mut self) {
+        self.stats.record(self.start.elapsed().as_nanos() as u64);
+        let span = self.span;
+        push_event(|_| TimelineEvent::End { span, at_ns: timeline_now_ns() });
+    }
+}
+
+/// Métricas de todos los scopes registrados
+pub fn snapshot() -> Vec<(&'static str, ProfilerMetrics)> {
+    registry().read().unwrap()
+        .iter()
+        .map(|(name, stats)| (*name, stats.metrics()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Métricas acumuladas de un scope, si se ha registrado
+    fn scope_metrics(name: &str) -> Option<ProfilerMetrics> {
+        snapshot().into_iter()
+            .find(|(scope, _)| *scope == name)
+            .map(|(_, metrics)| metrics)
+    }
+
+    #[test]
+    fn profile_scope_records_into_the_scope_stats() {
+        for _ in 0..3 {
+            crate::profile_scope!("scope.test.profile_scope");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let metrics = scope_metrics("scope.test.profile_scope");
+        #[cfg(feature = "profiling")]
+        {
+            let metrics = metrics.expect("el scope se registró");
+            assert_eq!(metrics.call_count, 3);
+            assert!(metrics.min_time >= 0.001);
+            assert!(metrics.total_time >= 0.003);
+            assert!(metrics.min_time <= metrics.average_time && metrics.average_time <= metrics.max_time);
+        }
+        // Sin la feature el macro no deja rastro
+        #[cfg(not(feature = "profiling"))]
+        assert!(metrics.is_none());
+    }
+
+    #[test]
+    fn guard_records_on_drop() {
+        let guard = ProfilingGuard::new("scope.test.guard");
+        assert!(scope_metrics("scope.test.guard").is_some_and(|metrics| metrics.call_count == 0));
+        drop(guard);
+        assert_eq!(scope_metrics("scope.test.guard").unwrap().call_count, 1);
+    }
+}
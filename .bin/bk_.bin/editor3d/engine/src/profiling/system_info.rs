@@ -0,0 +1,202 @@
+//! # Métricas del Sistema
+//!
+//! Lecturas reales de CPU y memoria para `ProfilingSystem`. En nativo salen
+//! de `sysinfo`: uso global y por core, frecuencia, memoria total, usada y
+//! disponible, y swap. El uso de CPU es la diferencia entre dos lecturas, así
+//! que se refresca como mucho cada `sysinfo::MINIMUM_CPU_UPDATE_INTERVAL` y
+//! entre medias se devuelve la última muestra.
+//!
+//! En wasm no hay acceso al sistema: la memoria es el heap de JavaScript de
+//! `performance.memory` (solo en navegadores basados en Chromium) y la CPU
+//! no tiene muestras; del frame solo queda su tiempo.
+
+/// Muestra de CPU
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuSample {
+    /// Uso global en porcentaje
+    pub usage: f32,
+    /// Uso de cada core en porcentaje
+    pub per_core: Vec<f32>,
+    /// Frecuencia media en MHz
+    pub frequency_mhz: f32,
+    /// Número de cores lógicos
+    pub cores: u32,
+}
+
+/// Muestra de memoria en bytes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemorySample {
+    /// Memoria total
+    pub total: u64,
+    /// Memoria usada
+    pub used: u64,
+    /// Memoria libre
+    pub free: u64,
+    /// Memoria disponible para nuevas asignaciones
+    pub available: u64,
+    /// Swap total
+    pub swap_total: u64,
+    /// Swap usado
+    pub swap_used: u64,
+}
+
+/// Lector de métricas del sistema
+pub struct SystemSampler {
+    #[cfg(not(target_arch = "wasm32"))]
+    system: sysinfo::System,
+    #[cfg(not(target_arch = "wasm32"))]
+    last_cpu_refresh: Option<std::time::Instant>,
+    /// Última muestra de CPU
+    cpu: Option<CpuSample>,
+}
+
+impl Default for SystemSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemSampler {
+    /// Lector sin lecturas previas
+    pub fn new() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            system: sysinfo::System::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            last_cpu_refresh: None,
+            cpu: None,
+        }
+    }
+
+    /// Muestra de CPU (None en wasm). La primera lectura solo fija la
+    /// referencia y devuelve uso 0
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn sample_cpu(&mut self) -> Option<CpuSample> {
+        let now = std::time::Instant::now();
+        let due = self.last_cpu_refresh
+            .map_or(true, |last| now.duration_since(last) >= sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        if due {
+            self.system.refresh_cpu();
+            self.last_cpu_refresh = Some(now);
+
+            let cpus = self.system.cpus();
+            let frequency = cpus.iter().map(|cpu| cpu.frequency() as f32).sum::<f32>() / cpus.len().max(1) as f32;
+            self.cpu = Some(CpuSample {
+                usage: self.system.global_cpu_info().cpu_usage(),
+                per_core: cpus.iter().map(|cpu| cpu.cpu_usage()).collect(),
+                frequency_mhz: frequency,
+                cores: cpus.len() as u32,
+            });
+        }
+        self.cpu.clone()
+    }
+
+    /// Muestra de CPU (None en wasm)
+    #[cfg(target_arch = "wasm32")]
+    pub fn sample_cpu(&mut self) -> Option<CpuSample> {
+        self.cpu.clone()
+    }
+
+    /// Muestra de memoria del sistema
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn sample_memory(&mut self) -> Option<MemorySample> {
+        self.system.refresh_memory();
+        Some(MemorySample {
+            total: self.system.total_memory(),
+            used: self.system.used_memory(),
+            free: self.system.free_memory(),
+            available: self.system.available_memory(),
+            swap_total: self.system.total_swap(),
+            swap_used: self.system.used_swap(),
+        })
+    }
+
+    /// Muestra del heap de JavaScript de `performance.memory` (None si el
+    /// navegador no lo expone)
+    #[cfg(target_arch = "wasm32")]
+    pub fn sample_memory(&mut self) -> Option<MemorySample> {
+        use wasm_bindgen::JsValue;
+
+        let performance = web_sys::window()?.performance()?;
+        let memory = js_sys::Reflect::get(&performance, &JsValue::from_str("memory")).ok()?;
+        if memory.is_undefined() || memory.is_null() {
+            return None;
+        }
+        let read = |key: &str| {
+            js_sys::Reflect::get(&memory, &JsValue::from_str(key)).ok()
+                .and_then(|value| value.as_f64())
+                .map_or(0, |value| value as u64)
+        };
+        let total = read("jsHeapSizeLimit");
+        let used = read("usedJSHeapSize");
+        Some(MemorySample {
+            total,
+            used,
+            free: total.saturating_sub(used),
+            available: total.saturating_sub(used),
+            swap_total: 0,
+            swap_used: 0,
+        })
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// Ventana de cada medida de CPU
+    const WINDOW: Duration = Duration::from_millis(500);
+
+    /// Uso sumado de todos los cores (100 por core ocupado)
+    fn total_usage(sample: &CpuSample) -> f32 {
+        sample.per_core.iter().sum()
+    }
+
+    #[test]
+    fn busy_thread_raises_cpu_usage() {
+        let mut sampler = SystemSampler::new();
+        let first = sampler.sample_cpu().unwrap();
+        assert!(first.cores > 0);
+        assert_eq!(first.per_core.len(), first.cores as usize);
+
+        std::thread::sleep(WINDOW);
+        let idle = sampler.sample_cpu().unwrap();
+
+        let busy_thread = std::thread::spawn(|| {
+            let start = Instant::now();
+            let mut x = 1u64;
+            while start.elapsed() < WINDOW {
+                x = std::hint::black_box(x.wrapping_mul(6364136223846793005).wrapping_add(1));
+            }
+            x
+        });
+        busy_thread.join().unwrap();
+        let busy = sampler.sample_cpu().unwrap();
+
+        // El hilo ocupa un core entero durante la ventana; se pide al menos medio
+        assert!(total_usage(&busy) >= total_usage(&idle) + 50.0,
+            "en reposo {:.1}, con el hilo ocupado {:.1}", total_usage(&idle), total_usage(&busy));
+    }
+
+    #[test]
+    fn allocating_200_mb_raises_used_memory_by_about_that_much() {
+        const SIZE: usize = 200 * 1024 * 1024;
+        let mut sampler = SystemSampler::new();
+        let before = sampler.sample_memory().unwrap();
+        assert!(before.total > 0 && before.used <= before.total);
+
+        // Escribir cada página para que el sistema la asigne de verdad
+        let mut block = vec![0u8; SIZE];
+        for page in block.chunks_mut(4096) {
+            page[0] = 1;
+        }
+        let after = sampler.sample_memory().unwrap();
+        std::hint::black_box(&block);
+        drop(block);
+
+        let rise = after.used as f64 - before.used as f64;
+        assert!(rise > 0.75 * SIZE as f64 && rise < 1.5 * SIZE as f64,
+            "la memoria usada subió {:.0} MB", rise / (1024.0 * 1024.0));
+    }
+}
@@ -0,0 +1,388 @@
+//! # Línea de tiempo por frame
+//!
+//! Reconstruye el árbol de scopes de un frame a partir de los eventos de
+//! apertura y cierre que dejan las guardas de `profile_scope!`. El anidamiento
+//! sale de los intervalos: en un mismo hilo, un scope es hijo del scope más
+//! interno que lo contiene. Los hermanos con el mismo nombre se fusionan y
+//! acumulan tiempo y llamadas.
+//!
+//! Un scope cerrado solo entra en el árbol cuando ya no queda abierto ningún
+//! scope anterior de su hilo; así los hijos del scope raíz del frame esperan a
+//! que la raíz se cierre aunque `ProfilingSystem::update` se llame dentro del
+//! frame, y el árbol es siempre el del último frame completo.
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use super::scope::{self, TimelineEvent};
+
+/// Scopes abiertos durante más tiempo que esto se dan por perdidos (una
+/// guarda olvidada con `mem::forget`) y dejan de retener a los demás
+const STALE_OPEN_SPAN_NS: u64 = 10_000_000_000;
+
+/// Nodo del árbol de un frame
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameNode {
+    /// Nombre del scope
+    pub name: String,
+    /// Hilo en que se abrió
+    pub thread: String,
+    /// Tiempo total incluyendo los hijos en ms
+    pub inclusive_ms: f32,
+    /// Tiempo propio sin los hijos en ms
+    pub exclusive_ms: f32,
+    /// Llamadas en el frame
+    pub calls: u32,
+    /// Scopes anidados, por orden de inicio
+    pub children: Vec<FrameNode>,
+}
+
+/// Árbol de scopes de un frame
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameTree {
+    /// Número de frame
+    pub frame: u64,
+    /// Scopes raíz de todos los hilos, por orden de inicio
+    pub roots: Vec<FrameNode>,
+}
+
+impl FrameTree {
+    /// Tiempo del frame: la raíz más larga en ms
+    pub fn duration_ms(&self) -> f32 {
+        self.roots.iter().map(|root| root.inclusive_ms).fold(0.0, f32::max)
+    }
+
+    /// Buscar un nodo por su ruta de nombres ("frame/physics/physics.solve")
+    pub fn find(&self, path: &str) -> Option<&FrameNode> {
+        let mut names = path.split('/');
+        let first = names.next()?;
+        let mut node = self.roots.iter().find(|root| root.name == first)?;
+        for name in names {
+            node = node.children.iter().find(|child| child.name == name)?;
+        }
+        Some(node)
+    }
+}
+
+/// Scope cerrado con su intervalo en la línea de tiempo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceSpan {
+    /// Nombre del scope
+    pub name: &'static str,
+    /// Índice del hilo en que se abrió
+    pub thread: u32,
+    /// Inicio en ns desde el origen de la línea de tiempo
+    pub start_ns: u64,
+    /// Fin en ns desde el origen de la línea de tiempo
+    pub end_ns: u64,
+}
+
+/// Nodo en construcción
+struct BuildNode {
+    name: &'static str,
+    thread: u32,
+    inclusive_ns: u64,
+    calls: u32,
+    children: Vec<usize>,
+}
+
+/// Acumula eventos entre frames y construye el árbol de cada frame
+#[derive(Default)]
+pub struct TimelineBuilder {
+    /// Scopes abiertos por ID
+    open: HashMap<u64, TraceSpan>,
+    /// Cierres cuyo evento de apertura aún no ha llegado
+    early_ends: HashMap<u64, u64>,
+    /// Scopes cerrados que esperan a su raíz
+    closed: Vec<TraceSpan>,
+    /// Scopes que entraron en el último árbol
+    frame_spans: Vec<TraceSpan>,
+    /// Frames construidos
+    frame: u64,
+}
+
+impl TimelineBuilder {
+    /// Constructor vacío
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Recoger los eventos de todos los hilos y construir el árbol del frame
+    pub fn build_frame(&mut self) -> FrameTree {
+        self.ingest(scope::drain_timeline());
+        self.frame += 1;
+
+        let now_ns = scope::timeline_now_ns();
+        self.open.retain(|_, span| now_ns.saturating_sub(span.start_ns) < STALE_OPEN_SPAN_NS);
+
+        // Primer inicio abierto de cada hilo: lo que empieza después aún puede ser su hijo
+        let mut blocked_from: HashMap<u32, u64> = HashMap::new();
+        for span in self.open.values() {
+            let start = blocked_from.entry(span.thread).or_insert(span.start_ns);
+            *start = (*start).min(span.start_ns);
+        }
+        let (ready, waiting): (Vec<TraceSpan>, Vec<TraceSpan>) = self.closed.drain(..)
+            .partition(|span| blocked_from.get(&span.thread).map_or(true, |start| span.start_ns < *start));
+        self.closed = waiting;
+        self.frame_spans = ready.clone();
+
+        FrameTree {
+            frame: self.frame,
+            roots: build_tree(ready),
+        }
+    }
+
+    /// Sacar los scopes que entraron en el último árbol, para exportarlos
+    pub fn drain_frame_spans(&mut self) -> Vec<TraceSpan> {
+        std::mem::take(&mut self.frame_spans)
+    }
+
+    /// Descartar todos los eventos pendientes
+    pub fn clear(&mut self) {
+        scope::drain_timeline();
+        self.open.clear();
+        self.early_ends.clear();
+        self.closed.clear();
+        self.frame_spans.clear();
+    }
+
+    fn ingest(&mut self, events: Vec<TimelineEvent>) {
+        // Un scope que migra de hilo deja su cierre en otro buffer, que puede
+        // recogerse antes que el de su apertura
+        for event in &events {
+            if let TimelineEvent::Begin { span, name, thread, at_ns } = *event {
+                self.open.insert(span, TraceSpan { name, thread, start_ns: at_ns, end_ns: at_ns });
+            }
+        }
+        for event in &events {
+            if let TimelineEvent::End { span, at_ns } = *event {
+                self.early_ends.insert(span, at_ns);
+            }
+        }
+        let open = &mut self.open;
+        let closed = &mut self.closed;
+        self.early_ends.retain(|id, end_ns| match open.remove(id) {
+            Some(mut span) => {
+                span.end_ns = (*end_ns).max(span.start_ns);
+                closed.push(span);
+                false
+            }
+            None => true,
+        });
+    }
+}
+
+/// Anidar los scopes por contención dentro de cada hilo
+fn build_tree(mut spans: Vec<TraceSpan>) -> Vec<FrameNode> {
+    spans.sort_by(|a, b| {
+        a.thread.cmp(&b.thread)
+            .then(a.start_ns.cmp(&b.start_ns))
+            .then(b.end_ns.cmp(&a.end_ns))
+    });
+
+    let mut nodes: Vec<BuildNode> = Vec::new();
+    let mut roots: Vec<(u64, usize)> = Vec::new();
+    // Scopes que contienen al actual: (fin, nodo)
+    let mut stack: Vec<(u64, usize)> = Vec::new();
+    let mut current_thread = None;
+
+    for span in spans {
+        if current_thread != Some(span.thread) {
+            stack.clear();
+            current_thread = Some(span.thread);
+        }
+        while stack.last().map_or(false, |(end_ns, _)| *end_ns < span.end_ns) {
+            stack.pop();
+        }
+
+        let siblings = match stack.last() {
+            Some(&(_, parent)) => nodes[parent].children.clone(),
+            None => roots.iter()
+                .filter(|(_, index)| nodes[*index].thread == span.thread)
+                .map(|(_, index)| *index)
+                .collect(),
+        };
+        let index = match siblings.into_iter().find(|index| nodes[*index].name == span.name) {
+            Some(index) => index,
+            None => {
+                nodes.push(BuildNode {
+                    name: span.name,
+                    thread: span.thread,
+                    inclusive_ns: 0,
+                    calls: 0,
+                    children: Vec::new(),
+                });
+                let index = nodes.len() - 1;
+                match stack.last() {
+                    Some(&(_, parent)) => nodes[parent].children.push(index),
+                    None => roots.push((span.start_ns, index)),
+                }
+                index
+            }
+        };
+        nodes[index].inclusive_ns += span.end_ns - span.start_ns;
+        nodes[index].calls += 1;
+        stack.push((span.end_ns, index));
+    }
+
+    roots.sort_by_key(|(start_ns, _)| *start_ns);
+    let mut thread_names = HashMap::new();
+    roots.into_iter()
+        .map(|(_, index)| finish_node(&nodes, index, &mut thread_names))
+        .collect()
+}
+
+fn finish_node(nodes: &[BuildNode], index: usize, thread_names: &mut HashMap<u32, String>) -> FrameNode {
+    let node = &nodes[index];
+    let children: Vec<FrameNode> = node.children.iter()
+        .map(|child| finish_node(nodes, *child, thread_names))
+        .collect();
+    let children_ns: u64 = node.children.iter().map(|child| nodes[*child].inclusive_ns).sum();
+    FrameNode {
+        name: node.name.to_string(),
+        thread: thread_names.entry(node.thread)
+            .or_insert_with(|| scope::thread_name(node.thread))
+            .clone(),
+        inclusive_ms: node.inclusive_ns as f32 / 1_000_000.0,
+        exclusive_ms: node.inclusive_ns.saturating_sub(children_ns) as f32 / 1_000_000.0,
+        calls: node.calls,
+        children,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiling::scope::ProfilingGuard;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    /// `build_frame` recoge los eventos de todos los hilos, así que los
+    /// tests que lo usan no pueden solaparse
+    static TIMELINE_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Scopes por frame del presupuesto de overhead
+    const SCOPES_PER_FRAME: usize = 10_000;
+    /// Tiempo máximo para abrir y cerrar `SCOPES_PER_FRAME` scopes y
+    /// construir su árbol (holgado para builds de depuración)
+    const OVERHEAD_BUDGET: Duration = Duration::from_millis(250);
+    /// Tolerancia al sumar tiempos en ms con f32
+    const EPSILON_MS: f32 = 1e-3;
+
+    fn span(name: &'static str, start_ms: u64, end_ms: u64) -> TraceSpan {
+        TraceSpan {
+            name,
+            thread: 0,
+            start_ns: start_ms * 1_000_000,
+            end_ns: end_ms * 1_000_000,
+        }
+    }
+
+    /// Comprobar en todo el subárbol que los hijos caben en el padre y que
+    /// el tiempo propio es el inclusivo menos el de los hijos
+    fn assert_consistent(node: &FrameNode) {
+        let children_ms: f32 = node.children.iter().map(|child| child.inclusive_ms).sum();
+        assert!(
+            children_ms <= node.inclusive_ms + EPSILON_MS,
+            "{}: hijos {} ms > padre {} ms", node.name, children_ms, node.inclusive_ms
+        );
+        assert!(
+            (node.exclusive_ms + children_ms - node.inclusive_ms).abs() <= EPSILON_MS,
+            "{}: propio {} ms + hijos {} ms != {} ms", node.name, node.exclusive_ms, children_ms, node.inclusive_ms
+        );
+        for child in &node.children {
+            assert!(child.inclusive_ms <= node.inclusive_ms + EPSILON_MS);
+            assert_consistent(child);
+        }
+    }
+
+    /// Suma de los tiempos propios de un subárbol
+    fn exclusive_sum(node: &FrameNode) -> f32 {
+        node.exclusive_ms + node.children.iter().map(exclusive_sum).sum::<f32>()
+    }
+
+    #[test]
+    fn nested_spans_split_time_between_parent_and_children() {
+        let roots = build_tree(vec![
+            span("frame", 0, 100),
+            span("physics", 10, 40),
+            span("physics.solve", 12, 30),
+            span("physics.solve", 32, 38),
+            span("render", 50, 90),
+            span("render.shadows", 55, 70),
+        ]);
+        assert_eq!(roots.len(), 1);
+        let frame = &roots[0];
+        assert_consistent(frame);
+
+        assert_eq!(frame.inclusive_ms, 100.0);
+        assert_eq!(frame.exclusive_ms, 30.0);
+        let tree = FrameTree { frame: 1, roots: roots.clone() };
+        let solve = tree.find("frame/physics/physics.solve").unwrap();
+        assert_eq!((solve.inclusive_ms, solve.calls), (24.0, 2));
+        assert_eq!(tree.find("frame/physics").unwrap().exclusive_ms, 6.0);
+        assert_eq!(tree.find("frame/render").unwrap().exclusive_ms, 25.0);
+        assert!((exclusive_sum(frame) - frame.inclusive_ms).abs() <= EPSILON_MS);
+    }
+
+    #[test]
+    fn guard_tree_keeps_children_within_their_parent() {
+        let _lock = TIMELINE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut builder = TimelineBuilder::new();
+        builder.clear();
+
+        {
+            let _frame = ProfilingGuard::new("timeline.test.frame");
+            for _ in 0..3 {
+                let _update = ProfilingGuard::new("timeline.test.update");
+                {
+                    let _inner = ProfilingGuard::new("timeline.test.inner");
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        let tree = builder.build_frame();
+        let frame = tree.find("timeline.test.frame").expect("el frame está en el árbol");
+        assert_consistent(frame);
+        assert!((exclusive_sum(frame) - frame.inclusive_ms).abs() <= EPSILON_MS);
+
+        let update = tree.find("timeline.test.frame/timeline.test.update").unwrap();
+        let inner = tree.find("timeline.test.frame/timeline.test.update/timeline.test.inner").unwrap();
+        assert_eq!((update.calls, inner.calls), (3, 3));
+        assert!(inner.inclusive_ms >= 3.0);
+        assert!(update.exclusive_ms >= 3.0);
+    }
+
+    #[test]
+    fn ten_thousand_scopes_fit_the_frame_budget() {
+        let _lock = TIMELINE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut builder = TimelineBuilder::new();
+        builder.clear();
+
+        // 100 sistemas con 99 scopes cada uno: SCOPES_PER_FRAME bajo la raíz
+        let systems = 100;
+        let scopes_per_system = SCOPES_PER_FRAME / systems - 1;
+        let started = Instant::now();
+        {
+            let _frame = ProfilingGuard::new("timeline.bench.frame");
+            for _ in 0..systems {
+                let _system = ProfilingGuard::new("timeline.bench.system");
+                for _ in 0..scopes_per_system {
+                    let _scope = ProfilingGuard::new("timeline.bench.scope");
+                }
+            }
+        }
+        let tree = builder.build_frame();
+        let elapsed = started.elapsed();
+
+        let system = tree.find("timeline.bench.frame/timeline.bench.system").unwrap();
+        let scope = tree.find("timeline.bench.frame/timeline.bench.system/timeline.bench.scope").unwrap();
+        assert_eq!(system.calls as usize + scope.calls as usize, SCOPES_PER_FRAME);
+        assert!(
+            elapsed <= OVERHEAD_BUDGET,
+            "{} scopes tardaron {:?} (presupuesto {:?})", SCOPES_PER_FRAME, elapsed, OVERHEAD_BUDGET
+        );
+    }
+}
@@ -0,0 +1,568 @@
+//! # Carga asíncrona de assets
+//!
+//! Cola de prioridad de cargas que se reparten en tareas de Tokio, como mucho
+//! `max_concurrent_loads` a la vez. Cada carga lee el archivo del disco o lo
+//! descarga por HTTP y lo decodifica (glTF/GLB, OBJ o texturas Basis) fuera
+//! del hilo del runtime. Las cargas pendientes se pueden cancelar; las que ya
+//! están en curso se abortan con su `CancellationToken`, lo que descarta la
+//! petición HTTP a medio descargar.
+
+use anyhow::{Result, anyhow};
+use glam::{Quat, Vec3, Vec4};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use super::gltf_loader::{self, ModelData, ModelNode};
+use super::{
+    BoundingBox, BoundingSphere, Geometry, Mesh, Texture, TextureConfig, TextureFilter, TextureFormat, TextureType,
+    TextureWrap, Vertex,
+};
+
+/// Identificador de una carga pedida con `enqueue_load`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetHandle(u64);
+
+/// Asset ya decodificado
+#[derive(Debug, Clone)]
+pub enum LoadedAsset {
+    /// Modelo glTF/GLB u OBJ
+    Model(ModelData),
+    /// Textura (Basis transcodificada a RGBA8)
+    Texture(Texture),
+}
+
+/// Estado de una carga
+#[derive(Debug, Clone)]
+pub enum AssetState {
+    /// En cola o en curso
+    Loading,
+    /// Cargado
+    Ready(Arc<LoadedAsset>),
+    /// Falló la lectura o la decodificación
+    Failed(String),
+    /// Handle desconocido o cancelado
+    Cancelled,
+}
+
+/// Entrada de la cola de cargas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrioritizedAsset {
+    /// Prioridad (mayor primero)
+    pub priority: u8,
+    /// Orden de llegada, para desempatar en FIFO
+    pub sequence: u64,
+    /// Ruta o URL del asset
+    pub path: String,
+}
+
+impl Ord for PrioritizedAsset {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for PrioritizedAsset {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Carga en curso
+struct InFlight {
+    task: JoinHandle<()>,
+    cancel: CancellationToken,
+}
+
+/// Estado compartido entre el cargador y sus tareas
+struct LoaderShared {
+    /// Cargas pendientes
+    queue: Mutex<BinaryHeap<PrioritizedAsset>>,
+    /// Cargas en curso por ruta
+    in_flight: Mutex<HashMap<String, InFlight>>,
+    /// Assets cargados por ruta
+    loaded: RwLock<HashMap<String, Arc<LoadedAsset>>>,
+    /// Errores por ruta
+    failed: RwLock<HashMap<String, String>>,
+    /// Máximo de cargas simultáneas
+    max_concurrent: usize,
+}
+
+/// Cargador asíncrono de assets
+pub struct AssetLoader {
+    shared: Arc<LoaderShared>,
+    /// Ruta de cada handle vivo; varios handles pueden compartir ruta
+    handles: HashMap<AssetHandle, String>,
+    next_sequence: AtomicU64,
+}
+
+impl AssetLoader {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            shared: Arc::new(LoaderShared {
+                queue: Mutex::new(BinaryHeap::new()),
+                in_flight: Mutex::new(HashMap::new()),
+                loaded: RwLock::new(HashMap::new()),
+                failed: RwLock::new(HashMap::new()),
+                max_concurrent: max_concurrent.max(1),
+            }),
+            handles: HashMap::new(),
+            next_sequence: AtomicU64::new(1),
+        }
+    }
+
+    /// Pedir la carga de un asset. Una ruta ya cargada, en cola o en curso
+    /// no se vuelve a cargar; el handle nuevo comparte el resultado
+    pub fn enqueue_load(&mut self, path: &str, priority: u8) -> AssetHandle {
+        let sequence = self.next_sequence.fetch_add(1, AtomicOrdering::Relaxed);
+        let handle = AssetHandle(sequence);
+        let known = self.shared.loaded.read().unwrap().contains_key(path)
+            || self.shared.in_flight.lock().unwrap().contains_key(path)
+            || self.shared.queue.lock().unwrap().iter().any(|entry| entry.path == path);
+        self.handles.insert(handle, path.to_string());
+
+        if !known {
+            // Un reintento tras un fallo empieza de cero
+            self.shared.failed.write().unwrap().remove(path);
+            self.shared.queue.lock().unwrap().push(PrioritizedAsset {
+                priority,
+                sequence,
+                path: path.to_string(),
+            });
+            LoaderShared::pump(&self.shared);
+        }
+        handle
+    }
+
+    /// Cancelar una carga. Si ningún otro handle espera la misma ruta se saca
+    /// de la cola o se aborta la tarea en curso
+    pub fn cancel_load(&mut self, handle: AssetHandle) {
+        let Some(path) = self.handles.remove(&handle) else {
+            return;
+        };
+        if self.handles.values().any(|p| *p == path) {
+            return;
+        }
+
+        self.shared.queue.lock().unwrap().retain(|entry| entry.path != path);
+        if let Some(in_flight) = self.shared.in_flight.lock().unwrap().remove(&path) {
+            debug!("Cancelando carga en curso de {}", path);
+            in_flight.cancel.cancel();
+        }
+        LoaderShared::pump(&self.shared);
+    }
+
+    /// Estado de una carga
+    pub fn get_asset(&self, handle: AssetHandle) -> AssetState {
+        let Some(path) = self.handles.get(&handle) else {
+            return AssetState::Cancelled;
+        };
+        if let Some(asset) = self.shared.loaded.read().unwrap().get(path) {
+            return AssetState::Ready(asset.clone());
+        }
+        if let Some(error) = self.shared.failed.read().unwrap().get(path) {
+            return AssetState::Failed(error.clone());
+        }
+        AssetState::Loading
+    }
+
+    /// Olvidar un handle sin cancelar la carga; el asset sigue en caché
+    pub fn release(&mut self, handle: AssetHandle) {
+        self.handles.remove(&handle);
+    }
+
+    /// Reintentar lanzar cargas pendientes (las que se pidieron fuera del
+    /// runtime de Tokio se quedan en cola hasta la primera llamada dentro)
+    pub fn update(&self) {
+        LoaderShared::pump(&self.shared);
+    }
+
+    /// Número de cargas en cola sin empezar
+    pub fn pending_count(&self) -> usize {
+        self.shared.queue.lock().unwrap().len()
+    }
+
+    /// Número de cargas en curso
+    pub fn in_flight_count(&self) -> usize {
+        self.shared.in_flight.lock().unwrap().len()
+    }
+
+    /// Quitar de la caché los assets cargados o fallidos cuya ruta ya no
+    /// tiene handles. Devuelve cuántos se quitaron
+    pub fn evict_unreferenced(&mut self) -> usize {
+        let referenced = |path: &String| self.handles.values().any(|p| p == path);
+        let mut loaded = self.shared.loaded.write().unwrap();
+        let before = loaded.len();
+        loaded.retain(|path, _| referenced(path));
+        let evicted = before - loaded.len();
+        drop(loaded);
+        self.shared.failed.write().unwrap().retain(|path, _| referenced(path));
+        if evicted > 0 {
+            debug!("{} assets desalojados de la caché", evicted);
+        }
+        evicted
+    }
+
+    /// Cancelar todo y vaciar la caché
+    pub fn clear(&mut self) {
+        self.shared.queue.lock().unwrap().clear();
+        for (_, in_flight) in self.shared.in_flight.lock().unwrap().drain() {
+            in_flight.cancel.cancel();
+            in_flight.task.abort();
+        }
+        self.shared.loaded.write().unwrap().clear();
+        self.shared.failed.write().unwrap().clear();
+        self.handles.clear();
+    }
+}
+
+impl LoaderShared {
+    /// Lanzar cargas de la cola mientras haya hueco
+    fn pump(shared: &Arc<Self>) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        // El bloqueo de `in_flight` se mantiene mientras se registra la tarea
+        // para que no pueda terminar antes de estar registrada
+        let mut in_flight = shared.in_flight.lock().unwrap();
+        let mut queue = shared.queue.lock().unwrap();
+        while in_flight.len() < shared.max_concurrent {
+            let Some(entry) = queue.pop() else {
+                break;
+            };
+            if in_flight.contains_key(&entry.path) {
+                continue;
+            }
+
+            let cancel = CancellationToken::new();
+            let task = runtime.spawn(Self::run(shared.clone(), entry.path.clone(), cancel.clone()));
+            in_flight.insert(entry.path, InFlight { task, cancel });
+        }
+    }
+
+    /// Tarea de una carga
+    async fn run(shared: Arc<Self>, path: String, cancel: CancellationToken) {
+        let result = tokio::select! {
+            _ = cancel.cancelled() => None,
+            result = load_asset(&path) => Some(result),
+        };
+
+        // Una cancelación que llega durante la decodificación descarta el resultado
+        if !cancel.is_cancelled() {
+            match result {
+                Some(Ok(asset)) => {
+                    debug!("Asset cargado: {}", path);
+                    shared.loaded.write().unwrap().insert(path.clone(), Arc::new(asset));
+                }
+                Some(Err(e)) => {
+                    warn!("Error cargando asset {}: {}", path, e);
+                    shared.failed.write().unwrap().insert(path.clone(), e.to_string());
+                }
+                None => {}
+            }
+            shared.in_flight.lock().unwrap().remove(&path);
+        }
+
+        Self::pump(&shared);
+    }
+}
+
+/// Leer y decodificar un asset
+async fn load_asset(path: &str) -> Result<LoadedAsset> {
+    let bytes = fetch(path).await?;
+    let path = path.to_string();
+    tokio::task::spawn_blocking(move || decode(&path, &bytes))
+        .await
+        .map_err(|e| anyhow!("Tarea de decodificación abortada: {}", e))?
+}
+
+/// Descargar por HTTP o leer del disco
+async fn fetch(path: &str) -> Result<Vec<u8>> {
+    if path.starts_with("http://") || path.starts_with("https://") {
+        let response = reqwest::get(path).await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    } else {
+        Ok(tokio::fs::read(path).await?)
+    }
+}
+
+/// Decodificar según la extensión
+fn decode(path: &str, bytes: &[u8]) -> Result<LoadedAsset> {
+    // Las URLs pueden llevar query: la extensión es la del último segmento
+    let file = path.split(['?', '#']).next().unwrap_or(path);
+    let name = Path::new(file).file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "asset".to_string());
+    let extension = Path::new(file).extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "glb" | "gltf" => Ok(LoadedAsset::Model(gltf_loader::load_gltf_slice(&name, bytes)?)),
+        "obj" => Ok(LoadedAsset::Model(load_obj(&name, bytes)?)),
+        "basis" => Ok(LoadedAsset::Texture(load_basis(&name, bytes)?)),
+        other => Err(anyhow!("Formato de asset no soportado: {:?} ({})", other, path)),
+    }
+}
+
+/// Importar un OBJ: un mesh por objeto colgando de un único nodo raíz. Los
+/// materiales `.mtl` no se resuelven
+fn load_obj(name: &str, bytes: &[u8]) -> Result<ModelData> {
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    };
+    let (models, _) = tobj::load_obj_buf(&mut std::io::Cursor::new(bytes), &options, |_| Ok(Default::default()))
+        .map_err(|e| anyhow!("Error importando OBJ {}: {}", name, e))?;
+
+    let mut model = ModelData {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    for (index, object) in models.iter().enumerate() {
+        let obj = &object.mesh;
+        let vertex_count = obj.positions.len() / 3;
+        let vertices: Vec<Vertex> = (0..vertex_count)
+            .map(|i| Vertex {
+                position: Vec3::from_slice(&obj.positions[i * 3..i * 3 + 3]),
+                normal: obj.normals.get(i * 3..i * 3 + 3).map(Vec3::from_slice).unwrap_or(Vec3::Y),
+                tangent: Vec3::ZERO,
+                uv: obj.texcoords.get(i * 2..i * 2 + 2)
+                    // OBJ tiene el origen de UV abajo
+                    .map(|uv| Vec3::new(uv[0], 1.0 - uv[1], 0.0))
+                    .unwrap_or(Vec3::ZERO),
+                color: obj.vertex_color.get(i * 3..i * 3 + 3)
+                    .map(|c| Vec4::new(c[0], c[1], c[2], 1.0))
+                    .unwrap_or(Vec4::ONE),
+            })
+            .collect();
+
+        let (min, max) = vertices.iter().fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)), |(min, max), v| {
+            (min.min(v.position), max.max(v.position))
+        });
+        let (min, max) = if vertices.is_empty() { (Vec3::ZERO, Vec3::ZERO) } else { (min, max) };
+        let center = (min + max) * 0.5;
+
+        model.meshes.push(Mesh {
+            id: format!("{}#mesh{}", name, index),
+            name: if object.name.is_empty() { format!("mesh{}", index) } else { object.name.clone() },
+            geometry: Geometry {
+                vertices,
+                indices: obj.indices.clone(),
+                bounding_box: BoundingBox { min, max },
+                bounding_sphere: BoundingSphere { center, radius: (max - center).length() },
+                joints: Vec::new(),
+                weights: Vec::new(),
+                morph_targets: Vec::new(),
+            },
+            material: None,
+            lod: Vec::new(),
+        });
+    }
+
+    model.nodes.push(ModelNode {
+        name: name.to_string(),
+        parent: None,
+        children: Vec::new(),
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+        meshes: model.meshes.iter().map(|mesh| mesh.id.clone()).collect(),
+        skin: None,
+    });
+    model.roots.push(0);
+    Ok(model)
+}
+
+/// Transcodificar el primer nivel de una textura Basis a RGBA8
+#[cfg(not(target_arch = "wasm32"))]
+fn load_basis(name: &str, bytes: &[u8]) -> Result<Texture> {
+    use basis_universal::{TranscodeParameters, Transcoder, TranscoderTextureFormat};
+
+    basis_universal::transcoder_init();
+    let mut transcoder = Transcoder::new();
+    transcoder.prepare_transcoding(bytes)
+        .map_err(|_| anyhow!("Textura Basis no válida: {}", name))?;
+    let description = transcoder.image_level_description(bytes, 0, 0)
+        .ok_or_else(|| anyhow!("Textura Basis sin imágenes: {}", name))?;
+    let pixels = transcoder
+        .transcode_image_level(bytes, TranscoderTextureFormat::RGBA32, TranscodeParameters {
+            image_index: 0,
+            level_index: 0,
+            ..Default::default()
+        })
+        .map_err(|e| anyhow!("Error transcodificando {}: {:?}", name, e))?;
+    transcoder.end_transcoding();
+
+    Ok(Texture {
+        id: name.to_string(),
+        name: name.to_string(),
+        texture_type: TextureType::Diffuse,
+        config: TextureConfig {
+            width: description.original_width,
+            height: description.original_height,
+            format: TextureFormat::RGBA8,
+            filter: TextureFilter::LinearMipmapLinear,
+            wrap: TextureWrap::Repeat,
+            mipmaps: true,
+        },
+        data: Some(pixels),
+    })
+}
+
+/// El transcodificador Basis es C++ y solo se enlaza en nativo
+#[cfg(target_arch = "wasm32")]
+fn load_basis(name: &str, _bytes: &[u8]) -> Result<Texture> {
+    Err(anyhow!("Texturas Basis no disponibles en wasm32: {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::{mpsc, Semaphore};
+
+    /// Triángulo en OBJ que sirve el servidor de prueba
+    const TRIANGLE_OBJ: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+    /// Tiempo máximo de espera de una carga
+    const LOAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Servidor HTTP mínimo: anota la ruta de cada petición en orden de
+    /// llegada y responde el triángulo cuando el test suelta un permiso
+    struct TestServer {
+        address: std::net::SocketAddr,
+        requests: mpsc::UnboundedReceiver<String>,
+        release: Arc<Semaphore>,
+    }
+
+    impl TestServer {
+        async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let (sender, requests) = mpsc::unbounded_channel();
+            let release = Arc::new(Semaphore::new(0));
+            let permits = release.clone();
+            tokio::spawn(async move {
+                while let Ok((mut socket, _)) = listener.accept().await {
+                    let sender = sender.clone();
+                    let permits = permits.clone();
+                    tokio::spawn(async move {
+                        let mut request = Vec::new();
+                        let mut buffer = [0u8; 1024];
+                        while !request.ends_with(b"\r\n\r\n") {
+                            let read = socket.read(&mut buffer).await.unwrap();
+                            if read == 0 {
+                                return;
+                            }
+                            request.extend_from_slice(&buffer[..read]);
+                        }
+                        let line = String::from_utf8_lossy(&request).lines().next().unwrap_or_default().to_string();
+                        let path = line.split_whitespace().nth(1).unwrap_or_default().to_string();
+                        let _ = sender.send(path);
+
+                        permits.acquire().await.unwrap().forget();
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            TRIANGLE_OBJ.len(),
+                            TRIANGLE_OBJ
+                        );
+                        let _ = socket.write_all(response.as_bytes()).await;
+                    });
+                }
+            });
+            Self { address, requests, release }
+        }
+
+        fn url(&self, name: &str) -> String {
+            format!("http://{}/{}.obj", self.address, name)
+        }
+
+        /// Ruta de la siguiente petición que llega
+        async fn next_request(&mut self) -> String {
+            tokio::time::timeout(LOAD_TIMEOUT, self.requests.recv()).await
+                .expect("no llegó ninguna petición")
+                .unwrap()
+        }
+    }
+
+    async fn wait_ready(loader: &AssetLoader, handle: AssetHandle) -> Arc<LoadedAsset> {
+        tokio::time::timeout(LOAD_TIMEOUT, async {
+            loop {
+                match loader.get_asset(handle) {
+                    AssetState::Ready(asset) => return asset,
+                    AssetState::Loading => tokio::time::sleep(Duration::from_millis(5)).await,
+                    other => panic!("carga terminada en {:?}", other),
+                }
+            }
+        })
+        .await
+        .expect("la carga no terminó")
+    }
+
+    #[tokio::test]
+    async fn queued_loads_start_by_priority_then_arrival() {
+        let mut server = TestServer::start().await;
+        let mut loader = AssetLoader::new(1);
+
+        // La primera ocupa el único hueco mientras se encolan las demás
+        let blocker = loader.enqueue_load(&server.url("blocker"), 0);
+        assert_eq!(server.next_request().await, "/blocker.obj");
+        let handles = [
+            loader.enqueue_load(&server.url("low"), 1),
+            loader.enqueue_load(&server.url("high"), 9),
+            loader.enqueue_load(&server.url("mid"), 5),
+            loader.enqueue_load(&server.url("high2"), 9),
+        ];
+        assert_eq!(loader.pending_count(), 4);
+        assert_eq!(loader.in_flight_count(), 1);
+
+        server.release.add_permits(handles.len() + 1);
+        let mut started = Vec::new();
+        for _ in 0..handles.len() {
+            started.push(server.next_request().await);
+        }
+        assert_eq!(started, ["/high.obj", "/high2.obj", "/mid.obj", "/low.obj"]);
+
+        for handle in std::iter::once(blocker).chain(handles) {
+            match &*wait_ready(&loader, handle).await {
+                LoadedAsset::Model(model) => assert_eq!(model.meshes[0].geometry.indices.len(), 3),
+                other => panic!("asset inesperado: {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_an_in_flight_load_starts_the_next_one() {
+        let mut server = TestServer::start().await;
+        let mut loader = AssetLoader::new(1);
+
+        let first = loader.enqueue_load(&server.url("first"), 5);
+        let second = loader.enqueue_load(&server.url("second"), 1);
+        // La primera ya pidió el archivo y espera la respuesta
+        assert_eq!(server.next_request().await, "/first.obj");
+        assert_eq!(loader.pending_count(), 1);
+
+        loader.cancel_load(first);
+        assert!(matches!(loader.get_asset(first), AssetState::Cancelled));
+        assert_eq!(loader.pending_count(), 0);
+        assert_eq!(loader.in_flight_count(), 1);
+        assert_eq!(server.next_request().await, "/second.obj");
+
+        server.release.add_permits(2);
+        wait_ready(&loader, second).await;
+        // La respuesta de la carga cancelada nunca llega a la caché
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!loader.shared.loaded.read().unwrap().contains_key(&server.url("first")));
+        assert_eq!(loader.in_flight_count(), 0);
+    }
+}
@@ -0,0 +1,200 @@
+//! # Atlas de texturas
+//!
+//! Los materiales que solo se diferencian en su textura de albedo se pueden
+//! dibujar con un único material si sus texturas comparten un atlas: cada
+//! textura ocupa una región del atlas y las UV de los meshes que la usan se
+//! llevan a esa región (`UVRegion`), así que los draw calls de esos
+//! materiales se agrupan.
+//!
+//! El empaquetado es por estantes: las texturas se ordenan de más alta a
+//! menos alta y se colocan de izquierda a derecha en filas cuya altura es la
+//! de la primera textura de la fila. El atlas es cuadrado y potencia de dos;
+//! empieza por el menor lado que puede alojar el área total y se dobla hasta
+//! `max_size`. Las texturas que no caben ni en `max_size` se quedan fuera.
+//!
+//! Las UV remapeadas solo son válidas en [0, 1]: las texturas que se repiten
+//! sobre la malla (UV fuera de rango) no deben ir al atlas.
+
+use glam::Vec2;
+use std::collections::HashMap;
+
+use super::backend::TextureImage;
+
+/// Píxeles de separación entre regiones para que el filtrado no mezcle
+/// texturas vecinas
+const ATLAS_PADDING: u32 = 1;
+
+/// Región de una textura dentro del atlas, en UV normalizadas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UVRegion {
+    /// Esquina de la región
+    pub offset: Vec2,
+    /// Tamaño de la región
+    pub scale: Vec2,
+}
+
+impl UVRegion {
+    /// Región que cubre todo el atlas
+    pub const FULL: Self = Self { offset: Vec2::ZERO, scale: Vec2::ONE };
+
+    /// Llevar una UV de la textura original a la región del atlas
+    pub fn apply(&self, uv: Vec2) -> Vec2 {
+        self.offset + uv * self.scale
+    }
+}
+
+/// Empaquetador de texturas en un atlas
+pub struct TextureAtlas;
+
+impl TextureAtlas {
+    /// Empaquetar texturas RGBA8 en un atlas cuadrado potencia de dos de lado
+    /// como mucho `max_size`. Devuelve el atlas y la región de cada textura
+    /// que cupo (None si no cupo ninguna)
+    pub fn pack(textures: Vec<(String, TextureImage)>, max_size: u32) -> Option<(TextureImage, HashMap<String, UVRegion>)> {
+        let max_size = max_size.max(1).next_power_of_two();
+        let mut textures: Vec<(String, TextureImage)> = textures.into_iter()
+            .filter(|(_, image)| {
+                image.width > 0
+                    && image.height > 0
+                    && image.pixels.len() == (image.width * image.height * 4) as usize
+                    && image.width + ATLAS_PADDING <= max_size
+                    && image.height + ATLAS_PADDING <= max_size
+            })
+            .collect();
+        if textures.is_empty() {
+            return None;
+        }
+        // Más altas primero; a igual altura, más anchas primero
+        textures.sort_by(|(_, a), (_, b)| b.height.cmp(&a.height).then(b.width.cmp(&a.width)));
+
+        let area: u64 = textures.iter()
+            .map(|(_, image)| (image.width + ATLAS_PADDING) as u64 * (image.height + ATLAS_PADDING) as u64)
+            .sum();
+        let widest = textures.iter().map(|(_, image)| image.width + ATLAS_PADDING).max().unwrap_or(1);
+        let mut size = ((area as f64).sqrt().ceil() as u32).max(widest).next_power_of_two().min(max_size);
+
+        // Doblar el lado hasta que quepan todas o se llegue al máximo
+        let placements = loop {
+            let placements = shelf_pack(&textures, size);
+            if placements.len() == textures.len() || size >= max_size {
+                break placements;
+            }
+            size *= 2;
+        };
+        if placements.is_empty() {
+            return None;
+        }
+
+        let mut pixels = vec![0u8; (size * size * 4) as usize];
+        let mut regions = HashMap::with_capacity(placements.len());
+        let srgb = textures.iter().any(|(_, image)| image.srgb);
+        for (index, x, y) in placements {
+            let (id, image) = &textures[index];
+            let row_bytes = (image.width * 4) as usize;
+            for row in 0..image.height {
+                let source = (row * image.width * 4) as usize;
+                let target = (((y + row) * size + x) * 4) as usize;
+                pixels[target..target + row_bytes].copy_from_slice(&image.pixels[source..source + row_bytes]);
+            }
+            regions.insert(id.clone(), UVRegion {
+                offset: Vec2::new(x as f32, y as f32) / size as f32,
+                scale: Vec2::new(image.width as f32, image.height as f32) / size as f32,
+            });
+        }
+
+        let atlas = TextureImage {
+            id: String::new(),
+            width: size,
+            height: size,
+            pixels,
+            srgb,
+            mips: Vec::new(),
+        };
+        Some((atlas, regions))
+    }
+}
+
+/// Colocar las texturas (ordenadas por altura) en estantes de un atlas de
+/// lado `size`. Devuelve índice y esquina de las que caben
+fn shelf_pack(textures: &[(String, TextureImage)], size: u32) -> Vec<(usize, u32, u32)> {
+    let mut placements = Vec::with_capacity(textures.len());
+    let (mut x, mut y, mut shelf_height) = (0u32, 0u32, 0u32);
+    for (index, (_, image)) in textures.iter().enumerate() {
+        let (width, height) = (image.width + ATLAS_PADDING, image.height + ATLAS_PADDING);
+        if x + width > size {
+            // Estante nuevo
+            y += shelf_height;
+            x = 0;
+            shelf_height = 0;
+        }
+        if y + height > size {
+            // Las siguientes son más bajas: pueden caber en el hueco del
+            // estante actual, pero no en uno nuevo
+            continue;
+        }
+        placements.push((index, x, y));
+        x += width;
+        shelf_height = shelf_height.max(height);
+    }
+    placements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Textura de un solo color; el rojo identifica a la textura
+    fn solid(index: u8, size: u32) -> (String, TextureImage) {
+        let id = format!("material-{}", index);
+        let pixels = [index, 255 - index, 0, 255].repeat((size * size) as usize);
+        (id.clone(), TextureImage { id, width: size, height: size, pixels, srgb: true, mips: Vec::new() })
+    }
+
+    /// Píxel del atlas en una UV
+    fn pixel(atlas: &TextureImage, uv: Vec2) -> [u8; 4] {
+        let x = (uv.x * atlas.width as f32) as u32;
+        let y = (uv.y * atlas.height as f32) as u32;
+        let start = ((y * atlas.width + x) * 4) as usize;
+        atlas.pixels[start..start + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn sixteen_64px_textures_share_one_512px_atlas() {
+        let textures: Vec<_> = (0..16).map(|index| solid(index, 64)).collect();
+        let (atlas, regions) = TextureAtlas::pack(textures, 512).expect("caben en el atlas");
+
+        assert_eq!((atlas.width, atlas.height), (512, 512));
+        assert!(atlas.srgb);
+        assert_eq!(regions.len(), 16);
+
+        let texel = 64.0 / 512.0;
+        let all: Vec<(u8, UVRegion)> = (0..16).map(|index| (index, regions[&format!("material-{}", index)])).collect();
+        for (index, region) in &all {
+            assert!(region.scale.abs_diff_eq(Vec2::splat(texel), 1e-6), "{:?}", region);
+            let end = region.offset + region.scale;
+            assert!(region.offset.min_element() >= 0.0 && end.max_element() <= 1.0, "{:?} fuera del atlas", region);
+
+            // Las UV de las esquinas y del centro caen en la textura original
+            for uv in [Vec2::ZERO, Vec2::splat(0.5), Vec2::splat(0.99)] {
+                assert_eq!(pixel(&atlas, region.apply(uv)), [*index, 255 - index, 0, 255], "textura {} en {:?}", index, uv);
+            }
+        }
+        // Ninguna región se solapa con otra
+        for (i, (_, a)) in all.iter().enumerate() {
+            for (_, b) in &all[i + 1..] {
+                let overlap = (a.offset.cmplt(b.offset + b.scale) & b.offset.cmplt(a.offset + a.scale)).all();
+                assert!(!overlap, "{:?} se solapa con {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn textures_too_big_for_the_maximum_are_left_out() {
+        let textures = vec![solid(1, 64), solid(2, 512)];
+        let (atlas, regions) = TextureAtlas::pack(textures, 512).unwrap();
+        assert_eq!(regions.len(), 1);
+        assert!(regions.contains_key("material-1"));
+        assert!(atlas.width <= 512);
+        assert!(TextureAtlas::pack(vec![solid(3, 512)], 512).is_none());
+    }
+}
@@ -0,0 +1,147 @@
+//! # Montaje de avatares
+//!
+//! Convierte un `AvatarConfig` (ver `crypto::avatar`) en una jerarquía de
+//! entidades: el cuerpo es la raíz y la cabeza, los brazos y las piernas
+//! cuelgan de él con `TransformComponent::parent`; el equipamiento cuelga de
+//! su parte. Cada parte lleva su malla en la capa de avatares y un
+//! `MaterialComponent` con el material propio, que además se registra en el
+//! `MaterialSystem`.
+
+use anyhow::{Result, anyhow};
+use glam::{Mat4, Quat};
+use std::collections::HashMap;
+use tracing::debug;
+
+use crate::crypto::avatar::{AvatarConfig, AvatarMaterial, AvatarSlot};
+use crate::ecs::{self, ECSSystem, EntityId};
+use crate::materials::{
+    Material, MaterialConfig, MaterialState, MaterialSystem, MaterialTextures, MaterialType,
+};
+
+/// Crea las entidades de un avatar y devuelve la del cuerpo (la raíz)
+pub async fn assemble_avatar(
+    config: &AvatarConfig,
+    material_system: &mut MaterialSystem,
+    world: &mut ECSSystem,
+) -> Result<EntityId> {
+    if config.part(AvatarSlot::Body).is_none() {
+        return Err(anyhow!("El avatar de {} no tiene cuerpo", config.wallet_address));
+    }
+
+    for material in &config.material_overrides {
+        material_system.create_material(material_from_override(material)).await
+            .map_err(|e| anyhow!("Error creando el material {}: {}", material.material_id, e))?;
+    }
+
+    // Crear primero las entidades para poder enlazar padres e hijos
+    let mut entities: HashMap<AvatarSlot, EntityId> = HashMap::with_capacity(config.parts.len());
+    for part in &config.parts {
+        let name = format!("avatar/{}/{:?}", config.wallet_address, part.slot);
+        entities.insert(part.slot, world.create_entity(name).await?);
+    }
+
+    for part in &config.parts {
+        let entity_id = entities[&part.slot];
+        let parent = part.slot.parent().and_then(|slot| entities.get(&slot).copied());
+        let children = config.parts.iter()
+            .filter(|child| child.slot.parent() == Some(part.slot))
+            .filter_map(|child| entities.get(&child.slot).copied())
+            .collect();
+
+        world.add_component(entity_id, Box::new(ecs::TransformComponent {
+            position: part.offset,
+            rotation: Quat::IDENTITY,
+            scale: part.scale,
+            matrix: Mat4::from_scale_rotation_translation(part.scale, Quat::IDENTITY, part.offset),
+            parent,
+            children,
+        })).await?;
+
+        // La geometría es la del mesh ya subido con ese ID
+        world.add_component(entity_id, Box::new(ecs::MeshComponent {
+            mesh_id: part.mesh_id.clone(),
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+            material_id: Some(part.material_id.clone()),
+            lod_level: 0,
+            lod_indices: Vec::new(),
+            joint_indices: Vec::new(),
+            joint_weights: Vec::new(),
+            render_layers: ecs::RENDER_LAYER_AVATAR,
+        })).await?;
+
+        if let Some(material) = config.material(&part.material_id) {
+            world.add_component(entity_id, Box::new(material_component(material))).await?;
+        }
+    }
+
+    debug!("🧍 Avatar de {} montado con {} partes", config.wallet_address, config.parts.len());
+    Ok(entities[&AvatarSlot::Body])
+}
+
+/// Material del sistema de materiales para un material propio del avatar
+fn material_from_override(material: &AvatarMaterial) -> Material {
+    Material {
+        id: material.material_id.clone(),
+        name: material.material_id.clone(),
+        material_type: MaterialType::PBR,
+        config: MaterialConfig {
+            base_color: material.base_color,
+            metallic: material.metallic,
+            roughness: material.roughness,
+            emissive: material.emissive,
+            transparency: None,
+            reflection: None,
+            refraction: None,
+            subsurface: None,
+            clearcoat: None,
+            sheen: None,
+            anisotropy: None,
+        },
+        shader_id: Some("pbr_standard".to_string()),
+        textures: MaterialTextures {
+            albedo: None,
+            normal: None,
+            metallic_roughness: None,
+            emissive: None,
+            ao: None,
+            height: None,
+            clearcoat: None,
+            sheen: None,
+            anisotropy: None,
+            translucency: None,
+        },
+        state: MaterialState {
+            active: true,
+            loaded: true,
+            compiled: false,
+            load_time: 0.0,
+        },
+    }
+}
+
+/// Componente con las propiedades que lee `material_desc_from_component`
+fn material_component(material: &AvatarMaterial) -> ecs::MaterialComponent {
+    let [r, g, b, a] = material.base_color;
+    let [er, eg, eb] = material.emissive;
+    let properties = [
+        ("base_color_r", r),
+        ("base_color_g", g),
+        ("base_color_b", b),
+        ("base_color_a", a),
+        ("emissive_r", er),
+        ("emissive_g", eg),
+        ("emissive_b", eb),
+        ("metallic", material.metallic),
+        ("roughness", material.roughness),
+    ];
+    ecs::MaterialComponent {
+        material_id: material.material_id.clone(),
+        material_type: ecs::MaterialType::PBR,
+        properties: properties.iter().map(|(name, value)| (name.to_string(), *value)).collect(),
+        textures: HashMap::new(),
+        shader: None,
+    }
+}
@@ -0,0 +1,151 @@
+//! # Lectura de imágenes de la GPU
+//!
+//! Copias asíncronas de texturas RGBA8 o BGRA8 a memoria. Cada lectura copia
+//! la textura a un buffer en el encoder en curso; tras enviarlo,
+//! `ImageReadbacks::finish` mapea los buffers y entrega cada imagen a su
+//! `ImageReadback` cuando la GPU termina. En nativo `finish` espera a la GPU
+//! si hay lecturas pendientes; en wasm el mapeo se resuelve en el bucle de
+//! eventos del navegador y la imagen llega en uno de los frames siguientes.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use anyhow::{Result, anyhow};
+use futures::channel::oneshot;
+
+use super::ImageData;
+
+/// Estados del mapeo de un buffer de lectura
+const READBACK_WAITING: u8 = 0;
+const READBACK_MAPPED: u8 = 1;
+const READBACK_FAILED: u8 = 2;
+
+/// Lectura copiada en un encoder y aún no entregada
+struct PendingReadback {
+    /// Buffer con las filas alineadas a `COPY_BYTES_PER_ROW_ALIGNMENT`
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    /// Bytes por fila en el buffer
+    padded_row: u32,
+    /// La textura está en BGRA y hay que intercambiar rojo y azul
+    bgra: bool,
+    /// Se pidió el mapeo del buffer
+    mapping: bool,
+    state: Arc<AtomicU8>,
+    sender: oneshot::Sender<Result<ImageData>>,
+}
+
+impl PendingReadback {
+    /// Píxeles RGBA8 sin el relleno de las filas
+    fn pixels(&self) -> Vec<u8> {
+        let unpadded_row = (self.width * 4) as usize;
+        let mapped = self.buffer.slice(..).get_mapped_range();
+        let mut pixels = Vec::with_capacity(unpadded_row * self.height as usize);
+        for row in mapped.chunks(self.padded_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_row]);
+        }
+        if self.bgra {
+            for texel in pixels.chunks_exact_mut(4) {
+                texel.swap(0, 2);
+            }
+        }
+        pixels
+    }
+}
+
+/// Lecturas de texturas en curso
+#[derive(Default)]
+pub struct ImageReadbacks {
+    pending: Vec<PendingReadback>,
+}
+
+impl ImageReadbacks {
+    /// Copiar una textura en el encoder; la imagen llega por `sender` tras
+    /// enviar el encoder y llamar a `finish`
+    pub fn record(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        sender: oneshot::Sender<Result<ImageData>>,
+    ) {
+        let bgra = match texture.format() {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => {
+                let _ = sender.send(Err(anyhow!("Formato sin lectura RGBA8: {:?}", format)));
+                return;
+            }
+        };
+        let (width, height) = (texture.width(), texture.height());
+        let padded_row = wgpu::util::align_to(width * 4, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("image-readback"),
+            size: (padded_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+        self.pending.push(PendingReadback {
+            buffer,
+            width,
+            height,
+            padded_row,
+            bgra,
+            mapping: false,
+            state: Arc::new(AtomicU8::new(READBACK_WAITING)),
+            sender,
+        });
+    }
+
+    /// Mapear las copias ya enviadas y entregar las terminadas
+    pub fn finish(&mut self, device: &wgpu::Device) {
+        if self.pending.is_empty() {
+            return;
+        }
+        for readback in self.pending.iter_mut().filter(|readback| !readback.mapping) {
+            readback.mapping = true;
+            let state = Arc::clone(&readback.state);
+            readback.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let next = if result.is_ok() { READBACK_MAPPED } else { READBACK_FAILED };
+                state.store(next, Ordering::Release);
+            });
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        device.poll(wgpu::Maintain::Wait);
+        #[cfg(target_arch = "wasm32")]
+        device.poll(wgpu::Maintain::Poll);
+
+        let mut waiting = Vec::new();
+        for readback in self.pending.drain(..) {
+            match readback.state.load(Ordering::Acquire) {
+                READBACK_MAPPED => {
+                    let image = ImageData {
+                        width: readback.width,
+                        height: readback.height,
+                        pixels: readback.pixels(),
+                    };
+                    readback.buffer.unmap();
+                    let _ = readback.sender.send(Ok(image));
+                }
+                READBACK_FAILED => {
+                    let _ = readback.sender.send(Err(anyhow!("No se pudo mapear la copia de la textura")));
+                }
+                _ => waiting.push(readback),
+            }
+        }
+        self.pending = waiting;
+    }
+}
+
@@ -0,0 +1,555 @@
+//! # Clustered shading en la GPU
+//!
+//! Buffers de luces y de clusters y compute pass que los rellena antes del
+//! pase principal. Cada invocación calcula la caja en espacio vista de un
+//! cluster con la proyección inversa y prueba contra ella la esfera de cada
+//! luz. El bind group del frame (grupo 1) enlaza los uniforms, las luces, el
+//! número de luces de cada cluster y sus índices; `cluster_light_range` de
+//! `SHADER_COMMON` localiza el cluster de un fragmento.
+//!
+//! Los contadores de desbordamiento se leen de vuelta sin bloquear, como
+//! los del occlusion culling: llegan a `FrameStats` con un frame de retraso.
+
+use bytemuck::{Pod, Zeroable};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::renderer::clustering::{ClusterFrame, ClusterStats, LocalLight, LocalLightKind};
+
+/// Invocaciones por workgroup de la asignación
+const CLUSTER_WORKGROUP_SIZE: u32 = 64;
+
+/// Luces con las que se crea el buffer de luces
+const INITIAL_LIGHT_CAPACITY: usize = 64;
+
+/// Contadores: clusters desbordados, luces descartadas, máximo por cluster y relleno
+const COUNTER_COUNT: usize = 4;
+
+/// Bytes de los contadores
+const COUNTERS_SIZE: u64 = (COUNTER_COUNT * std::mem::size_of::<u32>()) as u64;
+
+/// Estados del mapeo de la copia de los contadores
+const READBACK_WAITING: u8 = 0;
+const READBACK_MAPPED: u8 = 1;
+const READBACK_FAILED: u8 = 2;
+
+/// Asignación de luces a clusters. Usa los mismos structs que `SHADER_COMMON`
+const CLUSTER_SHADER: &str = r#"
+struct ClusterLight {
+    position: vec4<f32>,
+    color: vec4<f32>,
+    direction: vec4<f32>,
+    cone: vec4<f32>,
+};
+
+struct ClusterUniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    inverse_projection: mat4x4<f32>,
+    grid: vec4<u32>,
+    depth: vec4<f32>,
+    info: vec4<u32>,
+};
+
+@group(0) @binding(0) var<uniform> clusters: ClusterUniforms;
+@group(0) @binding(1) var<storage, read> lights: array<ClusterLight>;
+@group(0) @binding(2) var<storage, read_write> counts: array<u32>;
+@group(0) @binding(3) var<storage, read_write> indices: array<u32>;
+@group(0) @binding(4) var<storage, read_write> counters: array<atomic<u32>, 4>;
+
+fn slice_depth(slice: u32) -> f32 {
+    // Inversa de `slice = log(depth) * escala + sesgo`
+    return exp((f32(slice) - clusters.depth.w) / clusters.depth.z);
+}
+
+fn unproject(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let point = clusters.inverse_projection * vec4<f32>(ndc, depth, 1.0);
+    return point.xyz / point.w;
+}
+
+@compute @workgroup_size(64)
+fn cs_assign(@builtin(global_invocation_id) id: vec3<u32>) {
+    let grid = clusters.grid;
+    let cluster = id.x;
+    if (cluster >= grid.x * grid.y * grid.z) {
+        return;
+    }
+    let x = cluster % grid.x;
+    let y = (cluster / grid.x) % grid.y;
+    let z = cluster / (grid.x * grid.y);
+    let near = slice_depth(z);
+    let far = slice_depth(z + 1u);
+
+    // Caja de los rayos de las esquinas de la baldosa entre los dos planos
+    var box_min = vec3<f32>(3.4e38);
+    var box_max = vec3<f32>(-3.4e38);
+    for (var corner = 0u; corner < 4u; corner++) {
+        let uv = vec2<f32>(f32(x + (corner & 1u)), f32(y + (corner >> 1u))) / vec2<f32>(grid.xy);
+        let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+        let start = unproject(ndc, 0.0);
+        let end = unproject(ndc, 0.5);
+        let along = end.z - start.z;
+        for (var i = 0u; i < 2u; i++) {
+            let depth = select(near, far, i == 1u);
+            var t = 0.0;
+            if (abs(along) > 1e-6) {
+                t = (-depth - start.z) / along;
+            }
+            let point = start + (end - start) * t;
+            box_min = min(box_min, point);
+            box_max = max(box_max, point);
+        }
+    }
+
+    let cap = grid.w;
+    var total = 0u;
+    for (var i = 0u; i < clusters.info.x; i++) {
+        let light = lights[i];
+        let center = (clusters.view * vec4<f32>(light.position.xyz, 1.0)).xyz;
+        let closest = clamp(center, box_min, box_max);
+        let offset = closest - center;
+        if (dot(offset, offset) <= light.position.w * light.position.w) {
+            if (total < cap) {
+                indices[cluster * cap + total] = i;
+            }
+            total += 1u;
+        }
+    }
+    counts[cluster] = min(total, cap);
+    atomicMax(&counters[2], total);
+    if (total > cap) {
+        atomicAdd(&counters[0], 1u);
+        atomicAdd(&counters[1], total - cap);
+    }
+}
+"#;
+
+/// Uniforms del clustering
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ClusterUniforms {
+    view: [[f32; 4]; 4],
+    projection: [[f32; 4]; 4],
+    inverse_projection: [[f32; 4]; 4],
+    /// x/y/z = clusters por eje, w = luces máximas por cluster
+    grid: [u32; 4],
+    /// x = near, y = far, z/w = escala y sesgo del corte logarítmico
+    depth: [f32; 4],
+    /// x = luces del frame
+    info: [u32; 4],
+}
+
+/// Luz local en formato GPU
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct GpuLight {
+    /// xyz = posición en mundo, w = rango
+    position: [f32; 4],
+    /// rgb = color por intensidad, w = 1 para focos
+    color: [f32; 4],
+    /// xyz = dirección del foco, w = coseno del cono exterior
+    direction: [f32; 4],
+    /// x = coseno del cono interior
+    cone: [f32; 4],
+}
+
+impl From<&LocalLight> for GpuLight {
+    fn from(light: &LocalLight) -> Self {
+        let (spot, direction, inner_cos, outer_cos) = match light.kind {
+            LocalLightKind::Point => (0.0, glam::Vec3::NEG_Z, 1.0, -1.0),
+            LocalLightKind::Spot { direction, inner_cos, outer_cos } => {
+                (1.0, direction.try_normalize().unwrap_or(glam::Vec3::NEG_Z), inner_cos, outer_cos)
+            }
+        };
+        Self {
+            position: light.position.extend(light.range.max(0.0)).to_array(),
+            color: light.color.extend(spot).to_array(),
+            direction: direction.extend(outer_cos).to_array(),
+            // El interior no puede quedar fuera del exterior
+            cone: [inner_cos.max(outer_cos + 1e-4), 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// Recursos del clustered shading del backend wgpu
+pub struct ClusterResources {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+    uniforms: wgpu::Buffer,
+    lights: wgpu::Buffer,
+    /// Luces que caben en `lights`
+    light_capacity: usize,
+    /// Luces de cada cluster
+    counts: wgpu::Buffer,
+    /// Índices de luces, `max_lights_per_cluster` por cluster
+    indices: wgpu::Buffer,
+    /// (clusters, luces por cluster) para los que están creados los buffers
+    cluster_capacity: (usize, u32),
+    counters: wgpu::Buffer,
+    /// Copia de los contadores que se lee desde la CPU
+    readback: wgpu::Buffer,
+    /// La copia está mapeada (o pendiente de mapear)
+    readback_pending: bool,
+    /// Estado del mapeo de la copia
+    readback_state: Arc<AtomicU8>,
+    /// Clusters a asignar en el frame en curso (0 sin luces)
+    cluster_count: u32,
+    /// Luces del frame en curso
+    light_count: u32,
+    /// Contadores del último frame leído
+    last_stats: ClusterStats,
+    /// Los contadores se copiaron en el frame en curso
+    recorded: bool,
+}
+
+impl ClusterResources {
+    /// Crear los buffers para una rejilla mínima; crecen con el primer frame
+    pub fn new(device: &wgpu::Device) -> Self {
+        let compute_entry = |binding: u32, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer { ty, has_dynamic_offset: false, min_binding_size: None },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("light-cluster-layout"),
+            entries: &[
+                compute_entry(0, wgpu::BufferBindingType::Uniform),
+                compute_entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+                compute_entry(2, wgpu::BufferBindingType::Storage { read_only: false }),
+                compute_entry(3, wgpu::BufferBindingType::Storage { read_only: false }),
+                compute_entry(4, wgpu::BufferBindingType::Storage { read_only: false }),
+            ],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("light-cluster-shader"),
+            source: wgpu::ShaderSource::Wgsl(CLUSTER_SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("light-cluster-pipeline-layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("light-cluster"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: "cs_assign",
+            compilation_options: Default::default(),
+        });
+
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light-cluster-uniforms"),
+            size: std::mem::size_of::<ClusterUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let counters = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light-cluster-counters"),
+            size: COUNTERS_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light-cluster-counters-readback"),
+            size: COUNTERS_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let (counts, indices) = Self::create_cluster_buffers(device, 1, 1);
+
+        Self {
+            layout,
+            pipeline,
+            uniforms,
+            lights: Self::create_light_buffer(device, INITIAL_LIGHT_CAPACITY),
+            light_capacity: INITIAL_LIGHT_CAPACITY,
+            counts,
+            indices,
+            cluster_capacity: (1, 1),
+            counters,
+            readback,
+            readback_pending: false,
+            readback_state: Arc::new(AtomicU8::new(READBACK_WAITING)),
+            cluster_count: 0,
+            light_count: 0,
+            last_stats: ClusterStats::default(),
+            recorded: false,
+        }
+    }
+
+    fn create_light_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light-cluster-lights"),
+            size: (capacity * std::mem::size_of::<GpuLight>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_cluster_buffers(device: &wgpu::Device, clusters: usize, max_lights_per_cluster: u32) -> (wgpu::Buffer, wgpu::Buffer) {
+        let buffer = |label: &str, count: usize| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (count * std::mem::size_of::<u32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        (
+            buffer("light-cluster-counts", clusters),
+            buffer("light-cluster-indices", clusters * max_lights_per_cluster as usize),
+        )
+    }
+
+    /// Escribir las luces y los uniforms del frame y limpiar los contadores.
+    /// Sin clustering o sin luces el shader no recorre ninguna luz local.
+    /// Devuelve true si se recrearon buffers (hay que rehacer el bind group
+    /// del frame)
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, frame: Option<&ClusterFrame>, lights: &[LocalLight]) -> bool {
+        let Some(frame) = frame.filter(|_| !lights.is_empty()) else {
+            self.cluster_count = 0;
+            self.light_count = 0;
+            self.recorded = false;
+            queue.write_buffer(&self.uniforms, 0, bytemuck::bytes_of(&ClusterUniforms::zeroed()));
+            return false;
+        };
+
+        let mut recreated = false;
+        if lights.len() > self.light_capacity {
+            self.light_capacity = lights.len().next_power_of_two();
+            self.lights = Self::create_light_buffer(device, self.light_capacity);
+            recreated = true;
+        }
+        let grid = frame.grid;
+        let clusters = grid.cluster_count();
+        let (capacity, max_lights) = self.cluster_capacity;
+        if clusters > capacity || grid.max_lights_per_cluster > max_lights {
+            self.cluster_capacity = (clusters.max(capacity), grid.max_lights_per_cluster.max(max_lights));
+            let (counts, indices) = Self::create_cluster_buffers(device, self.cluster_capacity.0, self.cluster_capacity.1);
+            self.counts = counts;
+            self.indices = indices;
+            recreated = true;
+        }
+
+        let data: Vec<GpuLight> = lights.iter().map(GpuLight::from).collect();
+        queue.write_buffer(&self.lights, 0, bytemuck::cast_slice(&data));
+        let (scale, bias) = frame.slice_params();
+        let uniforms = ClusterUniforms {
+            view: frame.view.to_cols_array_2d(),
+            projection: frame.projection.to_cols_array_2d(),
+            inverse_projection: frame.projection.inverse().to_cols_array_2d(),
+            grid: [grid.size[0], grid.size[1], grid.size[2], grid.max_lights_per_cluster],
+            depth: [frame.near, frame.far, scale, bias],
+            info: [lights.len() as u32, 0, 0, 0],
+        };
+        queue.write_buffer(&self.uniforms, 0, bytemuck::bytes_of(&uniforms));
+        queue.write_buffer(&self.counters, 0, &[0u8; COUNTERS_SIZE as usize]);
+        self.cluster_count = clusters as u32;
+        self.light_count = lights.len() as u32;
+        self.recorded = false;
+        recreated
+    }
+
+    /// Grabar la asignación de luces. Los contadores se copian si la copia
+    /// anterior ya se leyó
+    pub fn record(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        if self.cluster_count == 0 {
+            return;
+        }
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("light-cluster-bind-group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: self.uniforms.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: self.lights.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: self.counts.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: self.indices.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: self.counters.as_entire_binding() },
+            ],
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("light-cluster"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(self.cluster_count.div_ceil(CLUSTER_WORKGROUP_SIZE), 1, 1);
+        }
+        if !self.readback_pending {
+            encoder.copy_buffer_to_buffer(&self.counters, 0, &self.readback, 0, COUNTERS_SIZE);
+            self.recorded = true;
+        }
+    }
+
+    /// Tras enviar el frame: pedir el mapeo de la copia de los contadores y
+    /// recoger la del frame anterior si ya terminó. No bloquea
+    pub fn finish(&mut self, device: &wgpu::Device) {
+        if self.recorded {
+            self.recorded = false;
+            self.readback_pending = true;
+            self.readback_state.store(READBACK_WAITING, Ordering::Release);
+            let state = Arc::clone(&self.readback_state);
+            self.readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let next = if result.is_ok() { READBACK_MAPPED } else { READBACK_FAILED };
+                state.store(next, Ordering::Release);
+            });
+        }
+        device.poll(wgpu::Maintain::Poll);
+        if !self.readback_pending {
+            return;
+        }
+        match self.readback_state.load(Ordering::Acquire) {
+            READBACK_MAPPED => {
+                {
+                    let mapped = self.readback.slice(..).get_mapped_range();
+                    let counts: &[u32] = bytemuck::cast_slice(&mapped);
+                    self.last_stats = ClusterStats {
+                        lights: 0,
+                        overflowed_clusters: counts[0],
+                        dropped_lights: counts[1],
+                        max_cluster_lights: counts[2],
+                    };
+                }
+                self.readback.unmap();
+                self.readback_pending = false;
+            }
+            // Se reintenta con los contadores del próximo frame
+            READBACK_FAILED => self.readback_pending = false,
+            _ => {}
+        }
+    }
+
+    /// Contadores del último frame leído, con las luces del frame en curso
+    pub fn stats(&self) -> ClusterStats {
+        if self.light_count == 0 {
+            return ClusterStats::default();
+        }
+        ClusterStats { lights: self.light_count, ..self.last_stats }
+    }
+
+    /// Entradas del bind group del frame (bindings 10 a 13)
+    pub fn bind_group_entries(&self) -> [wgpu::BindGroupEntry<'_>; 4] {
+        [
+            wgpu::BindGroupEntry { binding: 10, resource: self.uniforms.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 11, resource: self.lights.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 12, resource: self.counts.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 13, resource: self.indices.as_entire_binding() },
+        ]
+    }
+
+    /// Layout de los bindings 10 a 13 del grupo del frame
+    pub fn layout_entries() -> [wgpu::BindGroupLayoutEntry; 4] {
+        let storage = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding: 10,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ClusterUniforms>() as u64),
+                },
+                count: None,
+            },
+            storage(11),
+            storage(12),
+            storage(13),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::clustering::{LightClusterConfig, assign_lights};
+    use glam::{Mat4, Vec3};
+
+    /// Dispositivo headless, o None si la máquina no tiene adaptador
+    async fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+        adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("clustering-test-device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+        }, None).await.ok()
+    }
+
+    /// Cámara en el origen mirando a -Z con la rejilla por defecto
+    fn frame() -> ClusterFrame {
+        let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let projection = Mat4::perspective_rh(60f32.to_radians(), 16.0 / 9.0, 0.1, 100.0);
+        ClusterFrame::new(&LightClusterConfig::default(), view, projection, 0.1, 100.0)
+    }
+
+    /// Luz puntual blanca
+    fn point(position: Vec3, range: f32) -> LocalLight {
+        LocalLight { position, range, color: Vec3::ONE, kind: LocalLightKind::Point }
+    }
+
+    /// Asignar las luces en la GPU y esperar a sus contadores
+    fn run(device: &wgpu::Device, queue: &wgpu::Queue, frame: &ClusterFrame, lights: &[LocalLight]) -> ClusterStats {
+        let mut clusters = ClusterResources::new(device);
+        clusters.prepare(device, queue, Some(frame), lights);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        clusters.record(device, &mut encoder);
+        queue.submit(Some(encoder.finish()));
+        clusters.finish(device);
+        device.poll(wgpu::Maintain::Wait);
+        clusters.finish(device);
+        clusters.stats()
+    }
+
+    #[tokio::test]
+    async fn gpu_caps_500_lights_per_cluster_like_the_cpu() {
+        let Some((device, queue)) = device().await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        let frame = frame();
+        let center = Vec3::new(0.0, 0.0, -6.0);
+        // 500 luces pequeñas a menos de 1 m del centro: todas alcanzan su cluster
+        let lights: Vec<LocalLight> = (0..500)
+            .map(|i| {
+                let t = i as f32 / 500.0;
+                let angle = i as f32 * 2.399;
+                point(center + Vec3::new(angle.cos() * t, angle.sin() * t, (t - 0.5) * 0.5), 2.0)
+            })
+            .collect();
+
+        let stats = run(&device, &queue, &frame, &lights);
+        let cpu = assign_lights(&frame, &lights).stats;
+        let cap = frame.grid.max_lights_per_cluster;
+        assert_eq!(stats.lights, 500);
+        assert_eq!(stats.max_cluster_lights, 500);
+        assert!(stats.overflowed_clusters >= 1);
+        assert!(stats.dropped_lights >= 500 - cap);
+        // Los bordes de los clusters pueden diferir por redondeo
+        assert!(stats.overflowed_clusters.abs_diff(cpu.overflowed_clusters) <= cpu.overflowed_clusters / 10 + 1);
+    }
+
+    #[tokio::test]
+    async fn gpu_lights_outside_the_frustum_reach_no_cluster() {
+        let Some((device, queue)) = device().await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        let lights = [point(Vec3::new(0.0, 0.0, 5.0), 1.0), point(Vec3::new(30.0, 0.0, -5.0), 1.0)];
+        let stats = run(&device, &queue, &frame(), &lights);
+        assert_eq!(stats.lights, 2);
+        assert_eq!(stats.max_cluster_lights, 0);
+        assert_eq!(stats.overflowed_clusters, 0);
+    }
+}
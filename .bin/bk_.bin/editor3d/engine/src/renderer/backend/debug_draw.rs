@@ -0,0 +1,394 @@
+//! # Debug draw en la GPU
+//!
+//! Segmentos de `renderer::debug_draw` en un buffer de vértices dinámico
+//! que crece con el frame, dibujados como `LineList` sobre el destino del
+//! frame. Los segmentos con test de profundidad van primero en el buffer y
+//! usan un pipeline que lee el depth buffer de la escena: como el destino
+//! puede tener otra resolución que la interna, el fragment shader convierte
+//! su píxel al texel de profundidad y se descarta si la escena está
+//! delante. Los demás usan un pipeline sin profundidad.
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::renderer::debug_draw::DebugDrawFrame;
+
+/// Holgura del test de profundidad para que las líneas sobre una
+/// superficie no parpadeen
+const DEPTH_BIAS: f32 = 1e-4;
+
+/// Shader de los segmentos
+const DEBUG_DRAW_SHADER: &str = r#"
+struct DebugUniforms {
+    view_projection: mat4x4<f32>,
+    // xy = tamaño del destino en píxeles, z = holgura de profundidad
+    viewport: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> frame: DebugUniforms;
+@group(1) @binding(0) var scene_depth: texture_depth_2d;
+
+struct DebugVertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct DebugVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_debug(input: DebugVertexInput) -> DebugVertexOutput {
+    var out: DebugVertexOutput;
+    out.clip_position = frame.view_projection * vec4<f32>(input.position, 1.0);
+    out.color = input.color;
+    return out;
+}
+
+@fragment
+fn fs_debug(input: DebugVertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(input.color.rgb * input.color.a, input.color.a);
+}
+
+@fragment
+fn fs_debug_depth(input: DebugVertexOutput) -> @location(0) vec4<f32> {
+    let depth_size = vec2<f32>(textureDimensions(scene_depth));
+    let uv = input.clip_position.xy / frame.viewport.xy;
+    let texel = vec2<i32>(clamp(uv * depth_size, vec2<f32>(0.0), depth_size - 1.0));
+    if (input.clip_position.z > textureLoad(scene_depth, texel, 0) + frame.viewport.z) {
+        discard;
+    }
+    return vec4<f32>(input.color.rgb * input.color.a, input.color.a);
+}
+"#;
+
+/// Uniforms del pase
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct DebugUniforms {
+    view_projection: [[f32; 4]; 4],
+    viewport: [f32; 4],
+}
+
+/// Vértice en formato GPU
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct GpuDebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl GpuDebugVertex {
+    /// Atributos del vértice
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x4,
+    ];
+
+    /// Layout del buffer de vértices
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GpuDebugVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Recursos de debug draw del backend wgpu
+pub struct DebugDrawResources {
+    /// Layout del depth buffer de la escena (grupo 1)
+    depth_layout: wgpu::BindGroupLayout,
+    /// Pipeline con test de profundidad
+    depth_pipeline: wgpu::RenderPipeline,
+    /// Pipeline siempre encima
+    overlay_pipeline: wgpu::RenderPipeline,
+    /// Uniforms del pase
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    /// Vértices del frame
+    vertex_buffer: wgpu::Buffer,
+    /// Capacidad en vértices
+    vertex_capacity: usize,
+    /// Vértices con test de profundidad al principio del buffer
+    depth_tested: u32,
+    /// Vértices siempre encima tras ellos
+    overlay: u32,
+}
+
+impl DebugDrawResources {
+    /// Crear pipelines y buffers para dibujar sobre destinos de `format`
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("debug-draw-uniforms"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("debug-draw-depth"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("debug-draw-shader"),
+            source: wgpu::ShaderSource::Wgsl(DEBUG_DRAW_SHADER.into()),
+        });
+        let create_pipeline = |label: &str, layouts: &[&wgpu::BindGroupLayout], entry_point: &str| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: layouts,
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_debug",
+                    buffers: &[GpuDebugVertex::layout()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let depth_pipeline = create_pipeline("debug-draw-depth-pipeline", &[&uniform_layout, &depth_layout], "fs_debug_depth");
+        let overlay_pipeline = create_pipeline("debug-draw-overlay-pipeline", &[&uniform_layout], "fs_debug");
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("debug-draw-uniforms"),
+            size: std::mem::size_of::<DebugUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("debug-draw-uniforms"),
+            layout: &uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            depth_layout,
+            depth_pipeline,
+            overlay_pipeline,
+            uniform_buffer,
+            uniform_bind_group,
+            vertex_buffer: Self::create_vertices(device, 1024),
+            vertex_capacity: 1024,
+            depth_tested: 0,
+            overlay: 0,
+        }
+    }
+
+    /// Buffer de `capacity` vértices
+    fn create_vertices(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("debug-draw-vertices"),
+            size: (std::mem::size_of::<GpuDebugVertex>() * capacity) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Escribir los vértices del frame para un destino de `viewport`
+    /// píxeles. Devuelve los segmentos que se dibujarán
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, frame: Option<&DebugDrawFrame>, viewport: (u32, u32)) -> u32 {
+        self.depth_tested = 0;
+        self.overlay = 0;
+        let Some(frame) = frame.filter(|frame| !frame.is_empty()) else {
+            return 0;
+        };
+
+        let vertices: Vec<GpuDebugVertex> = frame.depth_tested.iter()
+            .chain(&frame.overlay)
+            .map(|vertex| GpuDebugVertex {
+                position: vertex.position.to_array(),
+                color: vertex.color.to_array(),
+            })
+            .collect();
+        let uniforms = DebugUniforms {
+            view_projection: frame.view_projection.to_cols_array_2d(),
+            viewport: [viewport.0.max(1) as f32, viewport.1.max(1) as f32, DEPTH_BIAS, 0.0],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+        if vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_vertices(device, self.vertex_capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+
+        self.depth_tested = frame.depth_tested.len() as u32;
+        self.overlay = frame.overlay.len() as u32;
+        (vertices.len() / 2) as u32
+    }
+
+    /// Grabar el pase sobre `target`. Sin `depth` (MSAA) todos los segmentos
+    /// se dibujan encima. Devuelve las draw calls
+    pub fn record(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        depth: Option<&wgpu::TextureView>,
+    ) -> u32 {
+        if self.depth_tested + self.overlay == 0 {
+            return 0;
+        }
+        let depth_bind_group = depth.filter(|_| self.depth_tested > 0).map(|depth| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("debug-draw-depth"),
+                layout: &self.depth_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                }],
+            })
+        });
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("debug-draw-pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+
+        let total = self.depth_tested + self.overlay;
+        match &depth_bind_group {
+            Some(depth_bind_group) => {
+                pass.set_pipeline(&self.depth_pipeline);
+                pass.set_bind_group(1, depth_bind_group, &[]);
+                pass.draw(0..self.depth_tested, 0..1);
+                if self.overlay > 0 {
+                    pass.set_pipeline(&self.overlay_pipeline);
+                    pass.draw(self.depth_tested..total, 0..1);
+                    return 2;
+                }
+                1
+            }
+            None => {
+                pass.set_pipeline(&self.overlay_pipeline);
+                pass.draw(0..total, 0..1);
+                1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::debug_draw::DebugDraw;
+    use glam::{Mat4, Vec3, Vec4};
+
+    /// Dispositivo headless, o None si la máquina no tiene adaptador
+    async fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+        adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("debug-draw-test-device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+        }, None).await.ok()
+    }
+
+    #[tokio::test]
+    async fn n_lines_fill_2n_vertices_of_the_buffer() {
+        let Some((device, queue)) = device().await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        let mut resources = DebugDrawResources::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let mut debug = DebugDraw::new();
+        debug.set_enabled(true);
+        // Más segmentos de los que caben en el buffer inicial
+        let lines = 3000;
+        for i in 0..lines {
+            debug.set_depth_test(i < 1000);
+            debug.line(Vec3::ZERO, Vec3::new(i as f32, 1.0, 0.0), Vec4::ONE);
+        }
+        let frame = debug.take_frame(Mat4::IDENTITY).unwrap();
+
+        assert_eq!(resources.prepare(&device, &queue, Some(&frame), (64, 64)), lines);
+        assert_eq!(resources.depth_tested + resources.overlay, 2 * lines);
+        assert_eq!(resources.depth_tested, 2000);
+        assert!(resources.vertex_capacity >= 2 * lines as usize);
+        let stride = std::mem::size_of::<GpuDebugVertex>() as u64;
+        assert_eq!(resources.vertex_buffer.size(), resources.vertex_capacity as u64 * stride);
+    }
+
+    #[tokio::test]
+    async fn empty_frames_draw_nothing_and_keep_the_buffer() {
+        let Some((device, queue)) = device().await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        let mut resources = DebugDrawResources::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let capacity = resources.vertex_capacity;
+        assert_eq!(resources.prepare(&device, &queue, None, (64, 64)), 0);
+        assert_eq!(resources.prepare(&device, &queue, Some(&DebugDrawFrame::default()), (64, 64)), 0);
+        assert_eq!(resources.vertex_capacity, capacity);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d { width: 64, height: 64, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        assert_eq!(resources.record(&device, &mut encoder, &view, None), 0);
+        queue.submit(Some(encoder.finish()));
+    }
+}
@@ -0,0 +1,296 @@
+//! # Decals en la GPU
+//!
+//! Atlas de texturas de decal y uniforms con los decals del frame. El atlas
+//! es un array 2D con una capa por textura, reescalada a `DECAL_ATLAS_SIZE`
+//! y con su cadena de mips; cuando se llena se reutiliza la capa usada hace
+//! más frames. El bind group del frame (grupo 1) enlaza los uniforms, el
+//! atlas y su sampler, y `apply_decals` de `SHADER_COMMON` recorre los bits
+//! de la máscara de decals de cada draw call.
+
+use anyhow::{Result, anyhow};
+use bytemuck::{Pod, Zeroable};
+use std::collections::HashMap;
+
+use super::TextureImage;
+use crate::ecs::DecalBlendMode;
+use crate::renderer::decals::{DecalDraw, MAX_DECALS};
+
+/// Lado de cada capa del atlas
+const DECAL_ATLAS_SIZE: u32 = 512;
+
+/// Capas del atlas (texturas de decal distintas que caben a la vez)
+const DECAL_ATLAS_LAYERS: u32 = 32;
+
+/// Formato del atlas
+const DECAL_ATLAS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Decal en formato GPU
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct GpuDecal {
+    world_to_decal: [[f32; 4]; 4],
+    color: [f32; 4],
+    /// x = capa del atlas (-1 sin textura), y = modo de mezcla
+    params: [f32; 4],
+}
+
+/// Capa ocupada del atlas
+struct AtlasSlot {
+    texture_id: String,
+    /// Último frame que la usó
+    last_used: u64,
+}
+
+/// Recursos de decals del backend wgpu
+pub struct DecalResources {
+    /// Uniforms con los decals del frame
+    buffer: wgpu::Buffer,
+    /// Atlas de texturas
+    atlas: wgpu::Texture,
+    atlas_view: wgpu::TextureView,
+    /// Sampler trilineal del atlas
+    sampler: wgpu::Sampler,
+    /// Capas del atlas
+    slots: Vec<Option<AtlasSlot>>,
+    /// Capa por textura
+    layers: HashMap<String, u32>,
+    /// Frame en curso
+    frame: u64,
+}
+
+impl DecalResources {
+    /// Crear el atlas vacío y el buffer de uniforms
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("decal-uniforms"),
+            size: (std::mem::size_of::<GpuDecal>() * MAX_DECALS) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("decal-atlas"),
+            size: wgpu::Extent3d {
+                width: DECAL_ATLAS_SIZE,
+                height: DECAL_ATLAS_SIZE,
+                depth_or_array_layers: DECAL_ATLAS_LAYERS,
+            },
+            mip_level_count: DECAL_ATLAS_SIZE.ilog2() + 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DECAL_ATLAS_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let atlas_view = atlas.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("decal-atlas"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("decal-sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            buffer,
+            atlas,
+            atlas_view,
+            sampler,
+            slots: (0..DECAL_ATLAS_LAYERS).map(|_| None).collect(),
+            layers: HashMap::new(),
+            frame: 0,
+        }
+    }
+
+    /// Copiar una textura al atlas. Reutiliza su capa si ya estaba, si no una
+    /// libre o la usada hace más frames; las usadas en el frame en curso no se
+    /// reemplazan
+    pub fn upload(&mut self, queue: &wgpu::Queue, texture: &TextureImage) -> Result<()> {
+        if texture.width == 0
+            || texture.height == 0
+            || texture.pixels.len() != (texture.width * texture.height * 4) as usize
+        {
+            return Err(anyhow!("Tamaño de textura inválido: {}", texture.id));
+        }
+
+        let layer = match self.layers.get(&texture.id) {
+            Some(&layer) => layer,
+            None => {
+                let free = self.slots.iter().position(Option::is_none);
+                let oldest = || {
+                    self.slots.iter().enumerate()
+                        .filter_map(|(index, slot)| slot.as_ref().map(|slot| (index, slot.last_used)))
+                        .filter(|(_, last_used)| *last_used < self.frame)
+                        .min_by_key(|(_, last_used)| *last_used)
+                        .map(|(index, _)| index)
+                };
+                let layer = free.or_else(oldest)
+                    .ok_or_else(|| anyhow!("Atlas de decals lleno: {}", texture.id))? as u32;
+                if let Some(evicted) = self.slots[layer as usize].take() {
+                    self.layers.remove(&evicted.texture_id);
+                }
+                layer
+            }
+        };
+
+        let mut size = DECAL_ATLAS_SIZE;
+        let mut level = resample(texture, size);
+        for mip_level in 0..self.atlas.mip_level_count() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.atlas,
+                    mip_level,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: layer },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &level,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size * 4),
+                    rows_per_image: Some(size),
+                },
+                wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            );
+            if size > 1 {
+                level = downsample(&level, size);
+                size /= 2;
+            }
+        }
+
+        self.slots[layer as usize] = Some(AtlasSlot { texture_id: texture.id.clone(), last_used: self.frame });
+        self.layers.insert(texture.id.clone(), layer);
+        Ok(())
+    }
+
+    /// Verificar si una textura está en el atlas
+    pub fn has(&self, texture_id: &str) -> bool {
+        self.layers.contains_key(texture_id)
+    }
+
+    /// Liberar la capa de una textura
+    pub fn remove(&mut self, texture_id: &str) {
+        if let Some(layer) = self.layers.remove(texture_id) {
+            self.slots[layer as usize] = None;
+        }
+    }
+
+    /// Escribir los decals del frame. Los de textura fuera del atlas se
+    /// omiten en el shader. Devuelve los decals aplicados
+    pub fn prepare(&mut self, queue: &wgpu::Queue, decals: &[DecalDraw]) -> u32 {
+        let mut applied = 0;
+        let data: Vec<GpuDecal> = decals.iter()
+            .take(MAX_DECALS)
+            .map(|decal| {
+                let layer = self.layers.get(&decal.texture_id).copied();
+                if let Some(layer) = layer {
+                    if let Some(slot) = &mut self.slots[layer as usize] {
+                        slot.last_used = self.frame;
+                    }
+                    applied += 1;
+                }
+                let blend_mode = match decal.blend_mode {
+                    DecalBlendMode::Alpha => 0.0,
+                    DecalBlendMode::Multiply => 1.0,
+                    DecalBlendMode::Additive => 2.0,
+                };
+                GpuDecal {
+                    world_to_decal: decal.world_to_decal.to_cols_array_2d(),
+                    color: decal.color.to_array(),
+                    params: [layer.map_or(-1.0, |layer| layer as f32), blend_mode, 0.0, 0.0],
+                }
+            })
+            .collect();
+        if !data.is_empty() {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&data));
+        }
+        self.frame += 1;
+        applied
+    }
+
+    /// Entradas del bind group del frame (bindings 7 a 9)
+    pub fn bind_group_entries(&self) -> [wgpu::BindGroupEntry<'_>; 3] {
+        [
+            wgpu::BindGroupEntry { binding: 7, resource: self.buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 8, resource: wgpu::BindingResource::TextureView(&self.atlas_view) },
+            wgpu::BindGroupEntry { binding: 9, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+        ]
+    }
+
+    /// Layout de los bindings 7 a 9 del grupo del frame
+    pub fn layout_entries() -> [wgpu::BindGroupLayoutEntry; 3] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding: 7,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new((std::mem::size_of::<GpuDecal>() * MAX_DECALS) as u64),
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 8,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 9,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ]
+    }
+}
+
+/// Reescalar una imagen RGBA8 a `size` x `size` con filtrado bilineal
+fn resample(texture: &TextureImage, size: u32) -> Vec<u8> {
+    let (width, height) = (texture.width as usize, texture.height as usize);
+    let texel = |x: usize, y: usize, channel: usize| texture.pixels[(y * width + x) * 4 + channel] as f32;
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        let v = ((y as f32 + 0.5) * height as f32 / size as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+        let (y0, fy) = (v.floor() as usize, v.fract());
+        let y1 = (y0 + 1).min(height - 1);
+        for x in 0..size {
+            let u = ((x as f32 + 0.5) * width as f32 / size as f32 - 0.5).clamp(0.0, (width - 1) as f32);
+            let (x0, fx) = (u.floor() as usize, u.fract());
+            let x1 = (x0 + 1).min(width - 1);
+            for channel in 0..4 {
+                let top = texel(x0, y0, channel) * (1.0 - fx) + texel(x1, y0, channel) * fx;
+                let bottom = texel(x0, y1, channel) * (1.0 - fx) + texel(x1, y1, channel) * fx;
+                pixels.push((top * (1.0 - fy) + bottom * fy).round() as u8);
+            }
+        }
+    }
+    pixels
+}
+
+/// Siguiente nivel de mip: media de cada bloque de 2x2
+fn downsample(pixels: &[u8], size: u32) -> Vec<u8> {
+    let size = size as usize;
+    let half = size / 2;
+    let mut out = Vec::with_capacity(half * half * 4);
+    for y in 0..half {
+        for x in 0..half {
+            for channel in 0..4 {
+                let sum: u32 = [(0, 0), (1, 0), (0, 1), (1, 1)].iter()
+                    .map(|(dx, dy)| pixels[((2 * y + dy) * size + 2 * x + dx) * 4 + channel] as u32)
+                    .sum();
+                out.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,333 @@
+//! # Entornos en la GPU
+//!
+//! Cubemaps de irradiancia y especular prefiltrado de cada entorno subido,
+//! la LUT de la BRDF y el pipeline del skybox. El bind group del frame
+//! (grupo 1) enlaza los mapas del entorno de la lista de dibujo; sin entorno
+//! usa cubemaps negros de 1x1. La LUT no depende del entorno y se calcula
+//! en CPU al crear el backend.
+
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use wgpu::util::DeviceExt;
+
+use super::wgpu_backend::{DEPTH_FORMAT, SHADER_COMMON};
+use crate::renderer::environment::{brdf_lut, f32_to_f16, CubeMap, EnvironmentMaps, CUBE_FACE_COUNT};
+
+/// Lado de la LUT de la BRDF
+const BRDF_LUT_SIZE: u32 = 64;
+
+/// Muestras por texel de la LUT de la BRDF
+const BRDF_LUT_SAMPLES: u32 = 256;
+
+/// Formato de los cubemaps del entorno
+const ENVIRONMENT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Skybox: un triángulo que cubre la pantalla en el plano lejano. La
+/// dirección de cada píxel sale de la vista-proyección inversa
+const SKYBOX_SHADER: &str = r#"
+struct SkyboxOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+@vertex
+fn vs_skybox(@builtin(vertex_index) index: u32) -> SkyboxOutput {
+    var out: SkyboxOutput;
+    let ndc = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_skybox(input: SkyboxOutput) -> @location(0) vec4<f32> {
+    let world = frame.inverse_view_projection * vec4<f32>(input.ndc, 1.0, 1.0);
+    let direction = normalize(world.xyz / world.w - frame.camera_position.xyz);
+    let radiance = textureSampleLevel(specular_map, environment_sampler, environment_direction(direction), 0.0).rgb;
+    return vec4<f32>(radiance * frame.environment.x, 1.0);
+}
+"#;
+
+/// Entorno en la GPU
+struct GpuEnvironment {
+    _irradiance: wgpu::Texture,
+    irradiance_view: wgpu::TextureView,
+    _specular: wgpu::Texture,
+    specular_view: wgpu::TextureView,
+    /// Nivel de mip de rugosidad 1
+    max_level: f32,
+}
+
+/// Recursos de entorno del backend wgpu
+pub struct EnvironmentResources {
+    /// Pipeline del skybox
+    skybox_pipeline: wgpu::RenderPipeline,
+    /// Sampler trilineal de los cubemaps y la LUT
+    sampler: wgpu::Sampler,
+    /// LUT de la BRDF (escala y sesgo del F0)
+    _brdf_lut: wgpu::Texture,
+    brdf_lut_view: wgpu::TextureView,
+    /// Cubemap negro para frames sin entorno
+    _black: wgpu::Texture,
+    black_view: wgpu::TextureView,
+    /// Entornos subidos
+    environments: HashMap<String, GpuEnvironment>,
+}
+
+impl EnvironmentResources {
+    /// Crear la LUT, el cubemap neutro y el pipeline del skybox
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        uniform_layout: &wgpu::BindGroupLayout,
+        frame_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let lut: Vec<u8> = brdf_lut(BRDF_LUT_SIZE, BRDF_LUT_SAMPLES).iter()
+            .flat_map(|texel| [f32_to_f16(texel.x), f32_to_f16(texel.y)])
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let brdf_lut = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("brdf-lut"),
+                size: wgpu::Extent3d { width: BRDF_LUT_SIZE, height: BRDF_LUT_SIZE, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rg16Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &lut,
+        );
+        let brdf_lut_view = brdf_lut.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let black_cube = CubeMap::from_fn(1, |_| glam::Vec3::ZERO);
+        let (black, black_view) = Self::create_cube(device, queue, "black-environment", &[black_cube]);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("environment-sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            skybox_pipeline: Self::create_skybox_pipeline(device, uniform_layout, frame_layout, format, sample_count),
+            sampler,
+            _brdf_lut: brdf_lut,
+            brdf_lut_view,
+            _black: black,
+            black_view,
+            environments: HashMap::new(),
+        }
+    }
+
+    /// Recrear el pipeline del skybox con otras muestras MSAA
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        frame_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        self.skybox_pipeline = Self::create_skybox_pipeline(device, uniform_layout, frame_layout, format, sample_count);
+    }
+
+    /// Crear el pipeline del skybox. No escribe profundidad: la geometría
+    /// que se dibuja después lo tapa
+    fn create_skybox_pipeline(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        frame_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("skybox-shader"),
+            source: wgpu::ShaderSource::Wgsl(format!("{}{}", SHADER_COMMON, SKYBOX_SHADER).into()),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("skybox-pipeline-layout"),
+            bind_group_layouts: &[uniform_layout, frame_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("skybox-pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_skybox",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_skybox",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// Crear un cubemap con un nivel de mip por elemento de `levels`
+    fn create_cube(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &str,
+        levels: &[CubeMap],
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let size = levels[0].size();
+        let data: Vec<u8> = levels.iter().flat_map(CubeMap::to_rgba16f).collect();
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d { width: size, height: size, depth_or_array_layers: CUBE_FACE_COUNT as u32 },
+                mip_level_count: levels.len() as u32,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: ENVIRONMENT_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            // Los niveles vienen con sus seis caras seguidas
+            wgpu::util::TextureDataOrder::MipMajor,
+            &data,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        (texture, view)
+    }
+
+    /// Subir los mapas de un entorno
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, maps: &EnvironmentMaps) -> Result<()> {
+        let Some(base) = maps.specular.first() else {
+            return Err(anyhow!("Entorno sin especular: {}", maps.id));
+        };
+        let limit = device.limits().max_texture_dimension_2d;
+        if base.size() > limit || maps.irradiance.size() > limit {
+            return Err(anyhow!("Entorno {} supera el límite de {}px", maps.id, limit));
+        }
+        // Cada nivel debe medir la mitad del anterior
+        let chained = maps.specular.iter().enumerate()
+            .all(|(level, cube)| cube.size() == (base.size() >> level).max(1));
+        if !chained {
+            return Err(anyhow!("Niveles del especular de {} sin tamaños de mip", maps.id));
+        }
+
+        let (irradiance, irradiance_view) = Self::create_cube(
+            device,
+            queue,
+            &format!("{}-irradiance", maps.id),
+            std::slice::from_ref(&maps.irradiance),
+        );
+        let (specular, specular_view) = Self::create_cube(device, queue, &format!("{}-specular", maps.id), &maps.specular);
+        self.environments.insert(maps.id.clone(), GpuEnvironment {
+            _irradiance: irradiance,
+            irradiance_view,
+            _specular: specular,
+            specular_view,
+            max_level: maps.max_specular_level(),
+        });
+        Ok(())
+    }
+
+    /// Verificar si un entorno está en la GPU
+    pub fn has(&self, environment_id: &str) -> bool {
+        self.environments.contains_key(environment_id)
+    }
+
+    /// Liberar un entorno
+    pub fn remove(&mut self, environment_id: &str) {
+        self.environments.remove(environment_id);
+    }
+
+    /// Nivel de mip de rugosidad 1 de un entorno subido
+    pub fn max_level(&self, environment_id: &str) -> Option<f32> {
+        self.environments.get(environment_id).map(|environment| environment.max_level)
+    }
+
+    /// Entradas del bind group del frame (bindings 3 a 6) para un entorno,
+    /// o los cubemaps negros si no está subido
+    pub fn bind_group_entries(&self, environment_id: Option<&str>) -> [wgpu::BindGroupEntry<'_>; 4] {
+        let environment = environment_id.and_then(|id| self.environments.get(id));
+        let (irradiance, specular) = environment
+            .map_or((&self.black_view, &self.black_view), |environment| {
+                (&environment.irradiance_view, &environment.specular_view)
+            });
+        [
+            wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(irradiance) },
+            wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(specular) },
+            wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&self.brdf_lut_view) },
+            wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+        ]
+    }
+
+    /// Layout de los bindings 3 a 6 del grupo del frame
+    pub fn layout_entries() -> [wgpu::BindGroupLayoutEntry; 4] {
+        let texture = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        };
+        [
+            texture(3, wgpu::TextureViewDimension::Cube),
+            texture(4, wgpu::TextureViewDimension::Cube),
+            texture(5, wgpu::TextureViewDimension::D2),
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ]
+    }
+
+    /// Dibujar el skybox en el pase principal. El grupo 1 ya está enlazado
+    pub fn record_skybox<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, draw_bind_group: &'a wgpu::BindGroup) {
+        pass.set_pipeline(&self.skybox_pipeline);
+        // El layout comparte el grupo 0 con el pipeline por defecto aunque no lo lea
+        pass.set_bind_group(0, draw_bind_group, &[0]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,318 @@
+//! # Grafo del frame
+//!
+//! Declara en un `RenderGraph` los pases de una lista de dibujo: un shadow
+//! pass por cascada, el pase principal sobre el destino HDR y la cadena de
+//! post-procesado hasta el destino del frame. Todos los efectos de la cadena
+//! se declaran; los desactivados no se consumen y el grafo los descarta. Los
+//! intermedios son transitorios, así que el aliasing reparte la memoria que
+//! antes ocupaban los destinos alternos fijos. Con VRS, un compute pass
+//! genera la imagen de tasas a partir del render HDR tras el pase principal.
+//! Con partículas y el depth buffer muestreable, un pase las mezcla sobre el
+//! render HDR leyendo la profundidad para suavizarlas; con MSAA se dibujan
+//! al final del pase principal.
+//! Con resolución dinámica el pase principal y la cadena trabajan a la
+//! resolución interna y el pase de salida la reescala al destino del frame.
+//! Las líneas de debug draw se dibujan sobre el destino del frame antes
+//! del texto; las que tienen test de profundidad leen el depth buffer si se
+//! puede muestrear. El texto y la UI se dibujan al final.
+//! Con occlusion culling y el depth buffer muestreable, el pase principal
+//! se graba en dos fases y reconstruye entre ellas la pirámide de
+//! profundidad, que persiste para el próximo frame.
+
+use super::DrawList;
+use crate::renderer::graph::{RenderGraph, ResourceHandle, ResourceState, TextureDesc, TextureFormat};
+use crate::renderer::postprocess::PostEffect;
+use crate::renderer::shadows::MAX_SHADOW_CASCADES;
+use crate::renderer::vrs::VRSPass;
+
+/// Pase de la cadena de post-procesado
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostPass {
+    /// Umbral de luminancia y reducción al primer mip del bloom
+    BloomPrefilter,
+    /// Reducción Kawase al siguiente mip
+    BloomDownsample,
+    /// Subida con filtro tienda mezclada con el mip del nivel
+    BloomUpsample,
+    /// Suma del bloom sobre la imagen
+    BloomComposite,
+    /// Exposición y tonemapping
+    Tonemap,
+    /// FXAA
+    Fxaa,
+    /// TAA: lee la historia anterior y escribe la nueva
+    Taa,
+    /// Copia al destino del frame
+    Output,
+}
+
+/// Payload de un pase del frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePass {
+    /// Profundidad de una cascada vista desde la luz
+    Shadow(usize),
+    /// Pase principal
+    Forward,
+    /// Partículas suaves sobre el render HDR
+    Particles,
+    /// Imagen de tasas del VRS a partir del render HDR y la profundidad
+    ShadingRate,
+    /// Pase de post-procesado
+    Post(PostPass),
+    /// Líneas de debug draw sobre el destino del frame
+    Debug,
+    /// Texto y UI sobre el destino del frame
+    Text,
+}
+
+/// Parámetros del grafo que dependen del backend
+#[derive(Debug, Clone, Copy)]
+pub struct FrameGraphOptions {
+    /// Ancho de renderizado interno
+    pub width: u32,
+    /// Alto de renderizado interno
+    pub height: u32,
+    /// Ancho del destino del frame
+    pub output_width: u32,
+    /// Alto del destino del frame
+    pub output_height: u32,
+    /// Historia del TAA que se escribe este frame
+    pub history_index: usize,
+    /// El depth buffer se puede muestrear (sin MSAA)
+    pub depth_readable: bool,
+}
+
+/// Recursos importados del frame
+#[derive(Debug, Clone)]
+pub struct FrameResources {
+    /// Destino del frame
+    pub output: ResourceHandle,
+    /// Depth buffer del pase principal
+    pub depth: ResourceHandle,
+    /// Capa del shadow map de cada cascada
+    pub shadow_maps: Vec<ResourceHandle>,
+    /// Historias del TAA
+    pub history: [ResourceHandle; 2],
+    /// Imagen de tasas del VRS (None sin VRS)
+    pub shading_rate: Option<ResourceHandle>,
+    /// Pirámide de profundidad del occlusion culling (None sin él)
+    pub hi_z: Option<ResourceHandle>,
+}
+
+/// Declarar los pases de `draw_list`
+pub fn build_frame_graph(draw_list: &DrawList, options: &FrameGraphOptions) -> (RenderGraph<FramePass>, FrameResources) {
+    let mut graph = RenderGraph::new();
+    let (width, height) = (options.width.max(1), options.height.max(1));
+    let full = TextureDesc::new_2d(width, height, TextureFormat::Rgba16Float);
+
+    let output_desc = TextureDesc::new_2d(options.output_width.max(1), options.output_height.max(1), TextureFormat::Rgba8Unorm);
+    let output = graph.import_texture("frame", output_desc, ResourceState::Undefined);
+    graph.set_output(output, ResourceState::Present);
+    let depth = graph.import_texture("depth", TextureDesc::new_2d(width, height, TextureFormat::Depth32Float), ResourceState::Undefined);
+    let history = [
+        graph.import_texture("taa-history-a", full, ResourceState::ShaderRead),
+        graph.import_texture("taa-history-b", full, ResourceState::ShaderRead),
+    ];
+
+    // Shadow maps: una capa importada por cascada
+    let mut shadow_maps = Vec::new();
+    if let (Some(_), Some(shadows)) = (&draw_list.light, &draw_list.shadows) {
+        let resolution = shadows.settings.resolution.max(1);
+        let desc = TextureDesc::new_2d(resolution, resolution, TextureFormat::Depth32Float);
+        for cascade in 0..shadows.cascades.len().min(MAX_SHADOW_CASCADES) {
+            let layer = graph.import_texture(&format!("shadow-map-{}", cascade), desc, ResourceState::ShaderRead);
+            graph.add_pass(&format!("shadow-cascade-{}", cascade), FramePass::Shadow(cascade))
+                .write(layer, ResourceState::DepthTarget);
+            shadow_maps.push(layer);
+        }
+    }
+
+    // La fase temprana lee la pirámide del frame anterior y la tardía la
+    // reescrita dentro del mismo pase
+    let hi_z = (draw_list.occlusion.is_some() && options.depth_readable)
+        .then(|| graph.import_texture("hi-z", TextureDesc::new_2d(width, height, TextureFormat::R32Float), ResourceState::ShaderRead));
+
+    let scene = graph.create_texture("scene", full);
+    let mut forward = graph.add_pass("forward", FramePass::Forward);
+    for &layer in &shadow_maps {
+        forward = forward.read(layer, ResourceState::ShaderRead);
+    }
+    forward = forward.write(scene, ResourceState::ColorTarget)
+        .write(depth, ResourceState::DepthTarget);
+    if let Some(hi_z) = hi_z {
+        forward.read(hi_z, ResourceState::ShaderRead)
+            .write(hi_z, ResourceState::StorageWrite);
+    }
+
+    if draw_list.particles.is_some() && options.depth_readable {
+        graph.add_pass("particles", FramePass::Particles)
+            .read(depth, ResourceState::ShaderRead)
+            .write(scene, ResourceState::ColorTarget);
+    }
+
+    // La imagen de tasas persiste para el próximo frame: nadie la lee en este
+    let shading_rate = draw_list.vrs.as_ref().map(|_| {
+        let (tiles_x, tiles_y) = VRSPass::tile_count(width, height);
+        let rates = graph.import_texture("shading-rate", TextureDesc::new_2d(tiles_x, tiles_y, TextureFormat::R32Uint), ResourceState::ShaderRead);
+        let mut pass = graph.add_pass("vrs-rates", FramePass::ShadingRate)
+            .read(scene, ResourceState::ShaderRead);
+        if options.depth_readable {
+            pass = pass.read(depth, ResourceState::ShaderRead);
+        }
+        pass.write(rates, ResourceState::StorageWrite).side_effect();
+        rates
+    });
+
+    let mut source = scene;
+    if let Some(post) = &draw_list.post {
+        let settings = &post.settings;
+        for slot in &settings.chain {
+            let result = match slot.effect {
+                PostEffect::Bloom => {
+                    // Cadena de mips hacia abajo desde media resolución
+                    let mut mips = Vec::new();
+                    for (level, (mip_width, mip_height)) in settings.bloom.mip_sizes(width, height).into_iter().enumerate() {
+                        let name = format!("bloom-down-{}", level);
+                        let mip = graph.create_texture(&name, TextureDesc::new_2d(mip_width, mip_height, TextureFormat::Rgba16Float));
+                        let (input, pass) = match mips.last() {
+                            Some(&(previous, _)) => (previous, PostPass::BloomDownsample),
+                            None => (source, PostPass::BloomPrefilter),
+                        };
+                        graph.add_pass(&name, FramePass::Post(pass))
+                            .read(input, ResourceState::ShaderRead)
+                            .write(mip, ResourceState::ColorTarget);
+                        mips.push((mip, (mip_width, mip_height)));
+                    }
+                    // Y de vuelta hacia arriba, mezclando cada nivel con el inferior
+                    let mut bloom = mips[mips.len() - 1].0;
+                    for (level, &(mip, (mip_width, mip_height))) in mips.iter().enumerate().rev().skip(1) {
+                        let name = format!("bloom-up-{}", level);
+                        let upsampled = graph.create_texture(&name, TextureDesc::new_2d(mip_width, mip_height, TextureFormat::Rgba16Float));
+                        graph.add_pass(&name, FramePass::Post(PostPass::BloomUpsample))
+                            .read(mip, ResourceState::ShaderRead)
+                            .read(bloom, ResourceState::ShaderRead)
+                            .write(upsampled, ResourceState::ColorTarget);
+                        bloom = upsampled;
+                    }
+                    let composite = graph.create_texture("bloom-composite", full);
+                    graph.add_pass("bloom-composite", FramePass::Post(PostPass::BloomComposite))
+                        .read(source, ResourceState::ShaderRead)
+                        .read(bloom, ResourceState::ShaderRead)
+                        .write(composite, ResourceState::ColorTarget);
+                    composite
+                }
+                PostEffect::Tonemap | PostEffect::Fxaa => {
+                    let (name, pass) = if slot.effect == PostEffect::Tonemap {
+                        ("tonemap", PostPass::Tonemap)
+                    } else {
+                        ("fxaa", PostPass::Fxaa)
+                    };
+                    let target = graph.create_texture(name, full);
+                    graph.add_pass(name, FramePass::Post(pass))
+                        .read(source, ResourceState::ShaderRead)
+                        .write(target, ResourceState::ColorTarget);
+                    target
+                }
+                PostEffect::Taa => {
+                    let target = history[options.history_index % 2];
+                    let previous = history[(options.history_index + 1) % 2];
+                    let mut taa = graph.add_pass("taa", FramePass::Post(PostPass::Taa))
+                        .read(source, ResourceState::ShaderRead)
+                        .read(previous, ResourceState::ShaderRead);
+                    if options.depth_readable {
+                        taa = taa.read(depth, ResourceState::ShaderRead);
+                    }
+                    taa.write(target, ResourceState::ColorTarget);
+                    target
+                }
+            };
+            if slot.enabled {
+                source = result;
+            }
+        }
+    }
+
+    graph.add_pass("output", FramePass::Post(PostPass::Output))
+        .read(source, ResourceState::ShaderRead)
+        .write(output, ResourceState::ColorTarget);
+
+    if let Some(debug) = draw_list.debug.as_ref().filter(|debug| !debug.is_empty()) {
+        let mut pass = graph.add_pass("debug-draw", FramePass::Debug);
+        if options.depth_readable && !debug.depth_tested.is_empty() {
+            pass = pass.read(depth, ResourceState::ShaderRead);
+        }
+        pass.write(output, ResourceState::ColorTarget);
+    }
+
+    if draw_list.text.as_ref().is_some_and(|text| !text.is_empty()) {
+        graph.add_pass("text", FramePass::Text)
+            .write(output, ResourceState::ColorTarget);
+    }
+
+    (graph, FrameResources { output, depth, shadow_maps, history, shading_rate, hi_z })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Mat4, Vec2};
+    use crate::renderer::bloom::BloomPass;
+    use crate::renderer::postprocess::{PostEffectSlot, PostProcessFrame, PostProcessSettings, TonemapOperator, DEFAULT_TAA_BLEND};
+
+    /// Lista con la cadena bloom → tonemap → FXAA
+    fn post_draw_list(bloom: bool) -> DrawList {
+        let slot = |effect, enabled| PostEffectSlot { effect, enabled };
+        DrawList {
+            post: Some(PostProcessFrame {
+                settings: PostProcessSettings {
+                    chain: vec![
+                        slot(PostEffect::Bloom, bloom),
+                        slot(PostEffect::Tonemap, true),
+                        slot(PostEffect::Fxaa, true),
+                    ],
+                    tonemap: TonemapOperator::Aces,
+                    exposure: 0.0,
+                    bloom: BloomPass::default(),
+                    taa_blend: DEFAULT_TAA_BLEND,
+                },
+                jitter: Vec2::ZERO,
+                view_projection: Mat4::IDENTITY,
+                previous_view_projection: Mat4::IDENTITY,
+                reset_history: false,
+            }),
+            ..DrawList::default()
+        }
+    }
+
+    fn compiled_passes(draw_list: &DrawList) -> (Vec<String>, Vec<String>) {
+        let (graph, _) = build_frame_graph(draw_list, &FrameGraphOptions {
+            width: 256,
+            height: 128,
+            output_width: 256,
+            output_height: 128,
+            history_index: 0,
+            depth_readable: true,
+        });
+        let compiled = graph.compile().unwrap();
+        let live = compiled.passes().iter().map(|pass| pass.name.clone()).collect();
+        (live, compiled.culled_passes().to_vec())
+    }
+
+    #[test]
+    fn disabling_bloom_culls_only_its_passes() {
+        let (with_bloom, culled) = compiled_passes(&post_draw_list(true));
+        assert!(culled.is_empty());
+        assert!(with_bloom.iter().any(|name| name == "bloom-composite"));
+
+        let (without_bloom, culled) = compiled_passes(&post_draw_list(false));
+        assert!(!culled.is_empty());
+        assert!(culled.iter().all(|name| name.starts_with("bloom-")));
+
+        // El resto de pases sigue en el grafo, en el mismo orden
+        let others: Vec<String> = with_bloom.into_iter()
+            .filter(|name| !name.starts_with("bloom-"))
+            .collect();
+        assert_eq!(without_bloom, others);
+        assert_eq!(without_bloom, ["forward", "tonemap", "fxaa", "output"]);
+    }
+}
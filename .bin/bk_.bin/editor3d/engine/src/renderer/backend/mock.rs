@@ -0,0 +1,524 @@
+//! # Backend Simulado
+//!
+//! Backend sin GPU que registra las llamadas de dibujo recibidas. Sirve para
+//! entornos sin adaptador gráfico y para inspeccionar lo que envía el renderer.
+//! Compila el mismo grafo de pases que el backend wgpu, sin grabarlo. El
+//! occlusion culling se simula en la CPU con un depth buffer reducido y las
+//! luces locales se asignan a los clusters también en la CPU. Los shaders
+//! recargados se validan con naga como en el backend wgpu. Las capturas y
+//! los destinos offscreen no se rasterizan: son imágenes del color de
+//! limpieza del frame.
+
+use anyhow::{Result, anyhow};
+use futures::channel::oneshot;
+use glam::{Vec3, Vec4};
+use std::collections::HashMap;
+
+use super::{DrawList, FrameStats, ImageData, ImageReadback, MaterialDesc, MaterialKind, RenderBackend, TextureImage};
+use super::frame::{build_frame_graph, FrameGraphOptions};
+use super::wgpu_backend::SHADER_COMMON;
+use crate::renderer::Mesh;
+use crate::renderer::clustering::{assign_lights, ClusterAssignment};
+use crate::renderer::culling::Aabb;
+use crate::renderer::decals::receiver_mask;
+use crate::renderer::morphing::resolve_weights;
+use crate::renderer::environment::EnvironmentMaps;
+use crate::renderer::dynamic_resolution::scaled_size;
+use crate::renderer::occlusion::{DepthBuffer, DepthPyramid};
+use crate::renderer::shader_reload::{material_entry_points, material_shader_name, validate_wgsl, ShaderError};
+use crate::renderer::text::FontAtlas;
+
+/// Lado mayor del depth buffer del occlusion culling simulado
+const MOCK_DEPTH_SIZE: u32 = 256;
+
+/// Backend simulado
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    /// Tamaño del destino
+    size: (u32, u32),
+    /// Escala de la resolución interna (None a resolución completa)
+    render_scale: Option<f32>,
+    /// Muestras MSAA pedidas (None sin cambios desde la creación)
+    sample_count: Option<u32>,
+    /// Índices por mesh subido
+    meshes: HashMap<String, (u32, u32)>,
+    /// Caja local por mesh subido
+    mesh_bounds: HashMap<String, Aabb>,
+    /// Posiciones e índices por mesh subido, para el occlusion culling
+    mesh_geometry: HashMap<String, (Vec<Vec3>, Vec<u32>)>,
+    /// IDs de los morph targets por mesh subido con targets
+    mesh_morph_targets: HashMap<String, Vec<String>>,
+    /// Tamaño por textura subida
+    textures: HashMap<String, (u32, u32)>,
+    /// Materiales subidos
+    materials: HashMap<String, MaterialDesc>,
+    /// Subidas de materiales recibidas
+    material_uploads: u64,
+    /// Shader recargado por tipo de material
+    material_shaders: HashMap<MaterialKind, String>,
+    /// Recargas de shaders aceptadas
+    shader_reloads: u64,
+    /// Niveles de especular por entorno subido
+    environments: HashMap<String, usize>,
+    /// Tamaño por textura de decal subida
+    decal_textures: HashMap<String, (u32, u32)>,
+    /// Tamaño del atlas por fuente subida
+    fonts: HashMap<String, (u32, u32)>,
+    /// Máscara de decals de cada draw call del último frame (simples y luego instanciadas)
+    last_decal_masks: Vec<u64>,
+    /// Última lista de dibujo recibida
+    last_draw_list: Option<DrawList>,
+    /// Pases del grafo del último frame, en orden de ejecución
+    last_passes: Vec<String>,
+    /// Pirámide de profundidad del último frame con occlusion culling
+    depth_pyramid: Option<DepthPyramid>,
+    /// Draw calls simples ocluidas en el último frame
+    last_occluded: Vec<bool>,
+    /// Luces de cada cluster en el último frame con luces locales
+    last_clusters: Option<ClusterAssignment>,
+    /// Capturas pedidas para el próximo frame
+    frame_captures: Vec<oneshot::Sender<Result<ImageData>>>,
+    /// Imagen por destino offscreen
+    render_textures: HashMap<String, ImageData>,
+    /// Destinos offscreen copiados a texturas externas, en orden
+    texture_copies: Vec<String>,
+    /// Frames renderizados
+    frames: u64,
+}
+
+/// Píxel RGBA8 sRGB de un color lineal, como lo escribe un destino sRGB
+fn srgb_pixel(color: Vec4) -> [u8; 4] {
+    let encode = |linear: f32| {
+        let linear = linear.clamp(0.0, 1.0);
+        let srgb = if linear <= 0.003_130_8 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        };
+        (srgb * 255.0).round() as u8
+    };
+    [encode(color.x), encode(color.y), encode(color.z), (color.w.clamp(0.0, 1.0) * 255.0).round() as u8]
+}
+
+impl MockBackend {
+    /// Crear backend simulado
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            size: (width, height),
+            ..Default::default()
+        }
+    }
+
+    /// Muestras MSAA pedidas con `set_sample_count` (1 si no se pidió ninguna)
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count.unwrap_or(1)
+    }
+
+    /// Última lista de dibujo recibida
+    pub fn last_draw_list(&self) -> Option<&DrawList> {
+        self.last_draw_list.as_ref()
+    }
+
+    /// Pases del grafo del último frame, en orden de ejecución
+    pub fn last_passes(&self) -> &[String] {
+        &self.last_passes
+    }
+
+    /// Máscara de decals de cada draw call del último frame: primero las
+    /// llamadas simples y después las instanciadas, en el orden de la lista
+    pub fn last_decal_masks(&self) -> &[u64] {
+        &self.last_decal_masks
+    }
+
+    /// Draw calls simples ocluidas en el último frame, en el orden de la lista
+    /// (vacío sin occlusion culling)
+    pub fn last_occluded(&self) -> &[bool] {
+        &self.last_occluded
+    }
+
+    /// Luces asignadas a cada cluster en el último frame (None sin luces
+    /// locales)
+    pub fn last_clusters(&self) -> Option<&ClusterAssignment> {
+        self.last_clusters.as_ref()
+    }
+
+    /// Material subido
+    pub fn material(&self, material_id: &str) -> Option<&MaterialDesc> {
+        self.materials.get(material_id)
+    }
+
+    /// Subidas de materiales recibidas
+    pub fn material_upload_count(&self) -> u64 {
+        self.material_uploads
+    }
+
+    /// Shader recargado de un tipo de material (None con el de por defecto)
+    pub fn material_shader(&self, kind: MaterialKind) -> Option<&str> {
+        self.material_shaders.get(&kind).map(String::as_str)
+    }
+
+    /// Recargas de shaders aceptadas
+    pub fn shader_reload_count(&self) -> u64 {
+        self.shader_reloads
+    }
+
+    /// Destinos offscreen copiados a texturas externas, en orden
+    pub fn texture_copies(&self) -> &[String] {
+        &self.texture_copies
+    }
+
+    /// Frames renderizados
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    /// Tamaño del destino
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Tamaño de renderizado interno (destino por la escala de resolución)
+    pub fn render_size(&self) -> (u32, u32) {
+        scaled_size(self.size.0, self.size.1, self.render_scale.unwrap_or(1.0))
+    }
+
+    /// Occlusion culling en dos fases como el del backend wgpu: las draw
+    /// calls simples se prueban con la cámara anterior contra la pirámide del
+    /// frame anterior, lo visible se rasteriza y las rechazadas se vuelven a
+    /// probar con la cámara actual contra la pirámide nueva. Devuelve qué
+    /// draw calls simples quedan ocluidas
+    fn occlusion_cull(&mut self, draw_list: &DrawList, stats: &mut FrameStats) -> Vec<bool> {
+        let Some(frame) = &draw_list.occlusion else {
+            self.depth_pyramid = None;
+            return Vec::new();
+        };
+
+        // Depth buffer reducido con la proporción del destino
+        let (width, height) = self.render_size();
+        let scale = (MOCK_DEPTH_SIZE as f32 / width.max(height).max(1) as f32).min(1.0);
+        let mut depth = DepthBuffer::new((width as f32 * scale) as u32, (height as f32 * scale) as u32);
+        let previous = self.depth_pyramid.take()
+            .filter(|pyramid| !frame.reset && pyramid.size() == (depth.width, depth.height));
+
+        // Las mallas con skin o morph se deforman en GPU: siempre visibles y sin rasterizar
+        let bounds: Vec<Option<Aabb>> = draw_list.calls.iter()
+            .map(|call| {
+                let local = self.mesh_bounds.get(&call.mesh_id).filter(|_| call.skin.is_none() && call.morph.is_none())?;
+                Some(local.transformed(&call.transform))
+            })
+            .collect();
+
+        // Fase temprana
+        let mut rejected: Vec<bool> = bounds.iter()
+            .map(|bounds| match (bounds, &previous) {
+                (Some(bounds), Some(pyramid)) => !pyramid.test(bounds, &frame.previous_view_projection),
+                _ => false,
+            })
+            .collect();
+        for (call, rejected) in draw_list.calls.iter().zip(&rejected) {
+            if let (false, None, None, Some((positions, indices))) = (*rejected, call.skin, call.morph, self.mesh_geometry.get(&call.mesh_id)) {
+                depth.rasterize(positions, indices, &(draw_list.view_projection * call.transform));
+            }
+        }
+        for draw in &draw_list.instanced {
+            if let Some((positions, indices)) = self.mesh_geometry.get(&draw.mesh_id) {
+                for instance in &draw.instances {
+                    depth.rasterize(positions, indices, &(draw_list.view_projection * instance.transform));
+                }
+            }
+        }
+        let pyramid = DepthPyramid::from_depth(&depth);
+
+        // Fase tardía: las rechazadas visibles ahora se recuperan
+        for (bounds, rejected) in bounds.iter().zip(rejected.iter_mut()) {
+            if let (Some(bounds), true) = (bounds, *rejected) {
+                *rejected = !pyramid.test(bounds, &draw_list.view_projection);
+            }
+        }
+
+        stats.occlusion_tested = bounds.iter().filter(|bounds| bounds.is_some()).count() as u32;
+        stats.occluded = rejected.iter().filter(|rejected| **rejected).count() as u32;
+        self.depth_pyramid = Some(pyramid);
+        rejected
+    }
+}
+
+impl RenderBackend for MockBackend {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width, height);
+    }
+
+    fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = Some(scale);
+    }
+
+    fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = Some(sample_count.max(1));
+    }
+
+    fn upload_mesh(&mut self, mesh: &Mesh) -> Result<()> {
+        let vertices = mesh.geometry.vertices.len() as u32;
+        let indices = mesh.geometry.indices.len() as u32;
+        self.meshes.insert(mesh.id.clone(), (vertices, indices));
+        let positions: Vec<_> = mesh.geometry.vertices.iter().map(|vertex| vertex.position).collect();
+        self.mesh_bounds.insert(mesh.id.clone(), Aabb::from_points(&positions));
+        self.mesh_geometry.insert(mesh.id.clone(), (positions, mesh.geometry.indices.clone()));
+        if mesh.geometry.morph_targets.is_empty() {
+            self.mesh_morph_targets.remove(&mesh.id);
+        } else {
+            let ids = mesh.geometry.morph_targets.iter().map(|target| target.id.clone()).collect();
+            self.mesh_morph_targets.insert(mesh.id.clone(), ids);
+        }
+        Ok(())
+    }
+
+    fn has_mesh(&self, mesh_id: &str) -> bool {
+        self.meshes.contains_key(mesh_id)
+    }
+
+    fn remove_mesh(&mut self, mesh_id: &str) {
+        self.meshes.remove(mesh_id);
+        self.mesh_bounds.remove(mesh_id);
+        self.mesh_geometry.remove(mesh_id);
+        self.mesh_morph_targets.remove(mesh_id);
+    }
+
+    fn upload_texture(&mut self, texture: &TextureImage) -> Result<()> {
+        if !texture.has_valid_size() {
+            return Err(anyhow!("Tamaño de textura inválido: {}", texture.id));
+        }
+        self.textures.insert(texture.id.clone(), (texture.width, texture.height));
+        Ok(())
+    }
+
+    fn has_texture(&self, texture_id: &str) -> bool {
+        self.textures.contains_key(texture_id)
+    }
+
+    fn remove_texture(&mut self, texture_id: &str) {
+        self.textures.remove(texture_id);
+    }
+
+    fn upload_material(&mut self, material: &MaterialDesc) -> Result<()> {
+        self.materials.insert(material.id.clone(), material.clone());
+        self.material_uploads += 1;
+        Ok(())
+    }
+
+    fn has_material(&self, material_id: &str) -> bool {
+        self.materials.contains_key(material_id)
+    }
+
+    fn remove_material(&mut self, material_id: &str) {
+        self.materials.remove(material_id);
+    }
+
+    fn upload_environment(&mut self, environment: &EnvironmentMaps) -> Result<()> {
+        if environment.specular.is_empty() {
+            return Err(anyhow!("Entorno sin especular: {}", environment.id));
+        }
+        self.environments.insert(environment.id.clone(), environment.specular.len());
+        Ok(())
+    }
+
+    fn has_environment(&self, environment_id: &str) -> bool {
+        self.environments.contains_key(environment_id)
+    }
+
+    fn remove_environment(&mut self, environment_id: &str) {
+        self.environments.remove(environment_id);
+    }
+
+    fn upload_decal_texture(&mut self, texture: &TextureImage) -> Result<()> {
+        if texture.pixels.len() != (texture.width * texture.height * 4) as usize {
+            return Err(anyhow!("Tamaño de textura inválido: {}", texture.id));
+        }
+        self.decal_textures.insert(texture.id.clone(), (texture.width, texture.height));
+        Ok(())
+    }
+
+    fn has_decal_texture(&self, texture_id: &str) -> bool {
+        self.decal_textures.contains_key(texture_id)
+    }
+
+    fn remove_decal_texture(&mut self, texture_id: &str) {
+        self.decal_textures.remove(texture_id);
+    }
+
+    fn upload_font(&mut self, atlas: &FontAtlas) -> Result<()> {
+        if atlas.pixels.len() != (atlas.width * atlas.height) as usize {
+            return Err(anyhow!("Tamaño de atlas inválido: {}", atlas.id));
+        }
+        self.fonts.insert(atlas.id.clone(), (atlas.width, atlas.height));
+        Ok(())
+    }
+
+    fn has_font(&self, font_id: &str) -> bool {
+        self.fonts.contains_key(font_id)
+    }
+
+    fn remove_font(&mut self, font_id: &str) {
+        self.fonts.remove(font_id);
+    }
+
+    fn reload_material_shader(&mut self, kind: MaterialKind, source: &str) -> std::result::Result<(), ShaderError> {
+        validate_wgsl(material_shader_name(kind), SHADER_COMMON, source, &material_entry_points(kind))?;
+        self.material_shaders.insert(kind, source.to_string());
+        self.shader_reloads += 1;
+        Ok(())
+    }
+
+    fn render(&mut self, draw_list: &DrawList) -> Result<FrameStats> {
+        let mut stats = FrameStats::default();
+        // Las mallas con skin o morph y los grupos instanciados no tienen una
+        // caja única: reciben todos los decals de sus capas
+        self.last_decal_masks = draw_list.calls.iter()
+            .map(|call| {
+                let bounds = self.mesh_bounds.get(&call.mesh_id)
+                    .filter(|_| call.skin.is_none() && call.morph.is_none())
+                    .map(|local| local.transformed(&call.transform));
+                receiver_mask(&draw_list.decals, call.layers, bounds.as_ref())
+            })
+            .chain(draw_list.instanced.iter().map(|draw| receiver_mask(&draw_list.decals, draw.layers, None)))
+            .collect();
+        stats.decals = draw_list.decal_texture_ids()
+            .filter(|id| self.decal_textures.contains_key(*id))
+            .count() as u32;
+        self.last_occluded = self.occlusion_cull(draw_list, &mut stats);
+        self.last_clusters = draw_list.clusters.as_ref()
+            .filter(|_| !draw_list.lights.is_empty())
+            .map(|frame| assign_lights(frame, &draw_list.lights));
+        if let Some(clusters) = &self.last_clusters {
+            stats.clustered_lights = clusters.stats.lights;
+            stats.overflowed_clusters = clusters.stats.overflowed_clusters;
+            stats.dropped_cluster_lights = clusters.stats.dropped_lights;
+            stats.max_cluster_lights = clusters.stats.max_cluster_lights;
+        }
+        for (i, call) in draw_list.calls.iter().enumerate() {
+            let (vertices, indices) = self.meshes.get(&call.mesh_id)
+                .ok_or_else(|| anyhow!("Mesh no subido: {}", call.mesh_id))?;
+            if self.last_occluded.get(i).copied().unwrap_or(false) {
+                continue;
+            }
+            stats.draw_calls += 1;
+            stats.vertices += vertices;
+            stats.triangles += indices / 3;
+            if call.skin.is_some_and(|entity| draw_list.skins.contains_key(&entity)) {
+                stats.skinned_vertices += vertices;
+            }
+            let morphed = call.morph
+                .and_then(|entity| draw_list.morphs.get(&entity))
+                .zip(self.mesh_morph_targets.get(&call.mesh_id))
+                .is_some_and(|(weights, target_ids)| !resolve_weights(target_ids, weights).is_empty());
+            if morphed {
+                stats.morphed_vertices += vertices;
+            }
+        }
+        for draw in &draw_list.instanced {
+            let (vertices, indices) = self.meshes.get(&draw.mesh_id)
+                .ok_or_else(|| anyhow!("Mesh no subido: {}", draw.mesh_id))?;
+            let instances = draw.instances.len() as u32;
+            stats.draw_calls += 1;
+            stats.vertices += vertices * instances;
+            stats.triangles += indices / 3 * instances;
+        }
+        if let Some(particles) = &draw_list.particles {
+            stats.draw_calls += particles.batches.len() as u32;
+            stats.particles = particles.particle_count() as u32;
+        }
+        // Una draw call por tipo de línea (con y sin test de profundidad)
+        if let Some(debug) = &draw_list.debug {
+            stats.draw_calls += [&debug.depth_tested, &debug.overlay].iter()
+                .filter(|vertices| !vertices.is_empty())
+                .count() as u32;
+            stats.debug_lines = debug.line_count() as u32;
+        }
+        // Los glifos de fuentes sin subir se omiten
+        for batch in draw_list.text.iter().flat_map(|text| text.batches.iter()) {
+            if batch.font_id.as_ref().map_or(true, |id| self.fonts.contains_key(id)) {
+                stats.draw_calls += 1;
+                stats.text_quads += batch.quads.len() as u32;
+            }
+        }
+
+        let (width, height) = self.render_size();
+        let (graph, _) = build_frame_graph(draw_list, &FrameGraphOptions {
+            width,
+            height,
+            output_width: self.size.0,
+            output_height: self.size.1,
+            history_index: (self.frames % 2) as usize,
+            depth_readable: true,
+        });
+        let compiled = graph.compile()?;
+        stats.render_passes = compiled.passes().len() as u32;
+        stats.culled_passes = compiled.culled_passes().len() as u32;
+        self.last_passes = compiled.passes().iter().map(|pass| pass.name.clone()).collect();
+
+        self.last_draw_list = Some(draw_list.clone());
+        self.frames += 1;
+        if !self.frame_captures.is_empty() {
+            let image = ImageData::filled(self.size.0, self.size.1, srgb_pixel(draw_list.clear_color));
+            for sender in self.frame_captures.drain(..) {
+                let _ = sender.send(Ok(image.clone()));
+            }
+        }
+        Ok(stats)
+    }
+
+    fn capture_next_frame(&mut self) -> ImageReadback {
+        let (sender, readback) = ImageReadback::channel();
+        self.frame_captures.push(sender);
+        readback
+    }
+
+    fn render_to_texture(&mut self, target_id: &str, draw_list: &DrawList, width: u32, height: u32) -> Result<FrameStats> {
+        let (width, height) = (width.max(1), height.max(1));
+        // Se registra como un frame más al tamaño del destino, sin las
+        // capturas del destino principal
+        let size = std::mem::replace(&mut self.size, (width, height));
+        let render_scale = self.render_scale.take();
+        let captures = std::mem::take(&mut self.frame_captures);
+        let stats = self.render(draw_list);
+        self.size = size;
+        self.render_scale = render_scale;
+        self.frame_captures = captures;
+
+        let stats = stats?;
+        self.render_textures.insert(
+            target_id.to_string(),
+            ImageData::filled(width, height, srgb_pixel(draw_list.clear_color)),
+        );
+        Ok(stats)
+    }
+
+    fn has_render_texture(&self, target_id: &str) -> bool {
+        self.render_textures.contains_key(target_id)
+    }
+
+    fn read_render_texture(&mut self, target_id: &str) -> Result<ImageReadback> {
+        let image = self.render_textures.get(target_id)
+            .ok_or_else(|| anyhow!("Destino offscreen no encontrado: {}", target_id))?;
+        let (sender, readback) = ImageReadback::channel();
+        let _ = sender.send(Ok(image.clone()));
+        Ok(readback)
+    }
+
+    fn remove_render_texture(&mut self, target_id: &str) {
+        self.render_textures.remove(target_id);
+    }
+
+    fn copy_render_texture(&mut self, target_id: &str, _destination: &wgpu::Texture) -> Result<()> {
+        if !self.render_textures.contains_key(target_id) {
+            return Err(anyhow!("Destino offscreen no encontrado: {}", target_id));
+        }
+        self.texture_copies.push(target_id.to_string());
+        Ok(())
+    }
+
+    fn poll_readbacks(&mut self) {}
+}
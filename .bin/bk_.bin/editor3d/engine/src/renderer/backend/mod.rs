@@ -0,0 +1,582 @@
+//! # Backends de Renderizado
+//!
+//! Interfaz común de llamadas de dibujo para los backends del renderer.
+//! `RendererSystem` construye una lista de dibujo por frame y la despacha a
+//! través de `RenderBackend`, sin depender de la API gráfica concreta.
+
+pub mod wgpu_backend;
+pub mod capture;
+pub mod clustering;
+pub mod debug_draw;
+pub mod decals;
+pub mod environment;
+pub mod frame;
+pub mod particles;
+pub mod pbr;
+pub mod post;
+pub mod text;
+pub mod mock;
+
+use anyhow::{Result, anyhow};
+use futures::channel::oneshot;
+use glam::{Mat4, Vec3, Vec4};
+use std::collections::HashMap;
+
+use super::Mesh;
+use super::clustering::{ClusterFrame, LocalLight};
+use super::debug_draw::DebugDrawFrame;
+use super::decals::DecalDraw;
+use super::environment::EnvironmentMaps;
+use super::occlusion::OcclusionFrame;
+use super::particles::ParticleFrame;
+use super::postprocess::PostProcessFrame;
+use super::shader_reload::ShaderError;
+use super::shadows::CascadedShadows;
+use super::text::{FontAtlas, TextFrame};
+use super::vrs::VRSFrame;
+use crate::animations::skinning::SkinPalette;
+use crate::animations::morphing::MorphWeights;
+
+/// Llamada de dibujo
+#[derive(Debug, Clone)]
+pub struct DrawCall {
+    /// ID del mesh
+    pub mesh_id: String,
+    /// Material
+    pub material_id: Option<String>,
+    /// Transformación del modelo
+    pub transform: Mat4,
+    /// Color base
+    pub base_color: Vec4,
+    /// Entidad cuya paleta de `DrawList::skins` deforma el mesh. Las mallas
+    /// con skin se dibujan con la transformación identidad: la paleta ya está
+    /// en espacio mundo
+    pub skin: Option<u64>,
+    /// Entidad cuyos pesos de `DrawList::morphs` deforman el mesh con sus
+    /// morph targets, antes del skinning
+    pub morph: Option<u64>,
+    /// Capas de render (ver `ecs::RENDER_LAYER_DEFAULT`)
+    pub layers: u32,
+    /// Entidad que la encoló (None para terreno y draw calls manuales)
+    pub entity: Option<u64>,
+    /// Cascadas de sombra en las que se dibuja, un bit por cascada
+    pub shadow_cascades: u32,
+}
+
+/// Todas las cascadas de sombra
+pub const SHADOW_CASCADES_ALL: u32 = u32::MAX;
+
+/// Datos por instancia de una draw call instanciada
+#[derive(Debug, Clone, Copy)]
+pub struct InstanceData {
+    /// Transformación del modelo
+    pub transform: Mat4,
+    /// Color que multiplica al del material
+    pub color: Vec4,
+}
+
+/// Draw call instanciada: un mesh y material dibujados una vez por instancia
+#[derive(Debug, Clone)]
+pub struct InstancedDraw {
+    /// ID del mesh
+    pub mesh_id: String,
+    /// Material
+    pub material_id: Option<String>,
+    /// Color base
+    pub base_color: Vec4,
+    /// Capas de render comunes a todas las instancias
+    pub layers: u32,
+    /// Instancias
+    pub instances: Vec<InstanceData>,
+}
+
+/// Tipo de pipeline de un material
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MaterialKind {
+    /// PBR metallic-roughness
+    Pbr,
+    /// Sin iluminación
+    Unlit,
+    /// Terreno: mezcla cuatro capas según un splat map
+    Terrain,
+}
+
+/// Parámetros escalares de un material
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialParams {
+    /// Color base
+    pub base_color: Vec4,
+    /// Emisivo
+    pub emissive: Vec3,
+    /// Metallic
+    pub metallic: f32,
+    /// Roughness
+    pub roughness: f32,
+    /// Escala del normal map
+    pub normal_scale: f32,
+    /// Intensidad de la oclusión ambiental
+    pub occlusion_strength: f32,
+    /// Alpha por debajo del cual se descarta el fragmento (0 desactiva)
+    pub alpha_cutoff: f32,
+    /// Dibujar ambas caras
+    pub double_sided: bool,
+}
+
+impl Default for MaterialParams {
+    fn default() -> Self {
+        Self {
+            base_color: Vec4::ONE,
+            emissive: Vec3::ZERO,
+            metallic: 0.0,
+            roughness: 1.0,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_cutoff: 0.0,
+            double_sided: false,
+        }
+    }
+}
+
+/// IDs de textura de cada slot de un material
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaterialTextureSlots {
+    /// Albedo (sRGB)
+    pub albedo: Option<String>,
+    /// Metallic en B, roughness en G
+    pub metallic_roughness: Option<String>,
+    /// Normal map en espacio tangente
+    pub normal: Option<String>,
+    /// Emisivo (sRGB)
+    pub emissive: Option<String>,
+    /// Oclusión ambiental en R
+    pub occlusion: Option<String>,
+    /// Splat map del terreno: peso de cada capa en R, G, B y A
+    pub splat: Option<String>,
+    /// Capas del terreno (sRGB)
+    pub layers: [Option<String>; 4],
+}
+
+impl MaterialTextureSlots {
+    /// Slots a partir de un mapa slot -> ID de textura
+    pub fn from_map(textures: &HashMap<String, String>) -> Self {
+        let slot = |names: &[&str]| names.iter().find_map(|name| textures.get(*name).cloned());
+        Self {
+            albedo: slot(&["base_color", "albedo", "diffuse"]),
+            metallic_roughness: slot(&["metallic_roughness"]),
+            normal: slot(&["normal"]),
+            emissive: slot(&["emissive"]),
+            occlusion: slot(&["occlusion", "ao"]),
+            splat: slot(&["splat"]),
+            layers: [slot(&["layer0"]), slot(&["layer1"]), slot(&["layer2"]), slot(&["layer3"])],
+        }
+    }
+
+    /// Verificar si algún slot usa una textura
+    pub fn uses(&self, texture_id: &str) -> bool {
+        self.iter().any(|id| id == texture_id)
+    }
+
+    /// IDs de textura asignados
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        [&self.albedo, &self.metallic_roughness, &self.normal, &self.emissive, &self.occlusion, &self.splat]
+            .into_iter()
+            .chain(self.layers.iter())
+            .filter_map(|slot| slot.as_deref())
+    }
+}
+
+/// Material listo para el backend
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialDesc {
+    /// ID del material
+    pub id: String,
+    /// Tipo de pipeline
+    pub kind: MaterialKind,
+    /// Parámetros
+    pub params: MaterialParams,
+    /// Texturas
+    pub textures: MaterialTextureSlots,
+}
+
+/// Imagen RGBA8 de una textura
+#[derive(Debug, Clone)]
+pub struct TextureImage {
+    /// ID de la textura
+    pub id: String,
+    /// Ancho
+    pub width: u32,
+    /// Alto
+    pub height: u32,
+    /// Píxeles RGBA8
+    pub pixels: Vec<u8>,
+    /// Color en espacio sRGB
+    pub srgb: bool,
+    /// Niveles de mip tras `pixels`, cada uno con la mitad de lado hasta
+    /// 1x1 (vacío: solo el nivel base)
+    pub mips: Vec<Vec<u8>>,
+}
+
+impl TextureImage {
+    /// Verificar que el nivel base y los mips tienen los bytes de su tamaño
+    /// y que los mips no pasan de 1x1
+    pub fn has_valid_size(&self) -> bool {
+        let level_bytes = |level: usize| {
+            let width = (self.width >> level).max(1);
+            let height = (self.height >> level).max(1);
+            (width * height * 4) as usize
+        };
+        self.width > 0
+            && self.height > 0
+            && self.mips.len() < (32 - self.width.max(self.height).leading_zeros()) as usize
+            && self.pixels.len() == level_bytes(0)
+            && self.mips.iter().enumerate().all(|(i, mip)| mip.len() == level_bytes(i + 1))
+    }
+}
+
+/// Imagen RGBA8 leída de la GPU
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageData {
+    /// Ancho
+    pub width: u32,
+    /// Alto
+    pub height: u32,
+    /// Píxeles RGBA8 por filas, de arriba abajo
+    pub pixels: Vec<u8>,
+}
+
+impl ImageData {
+    /// Imagen de un solo color
+    pub fn filled(width: u32, height: u32, color: [u8; 4]) -> Self {
+        Self {
+            width,
+            height,
+            pixels: color.repeat((width * height) as usize),
+        }
+    }
+
+    /// Color RGBA de un píxel
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let offset = ((y * self.width + x) * 4) as usize;
+        self.pixels.get(offset..offset + 4).map(|texel| [texel[0], texel[1], texel[2], texel[3]])
+    }
+
+    /// Copiar los píxeles a un `Uint8Array` de JavaScript
+    #[cfg(target_arch = "wasm32")]
+    pub fn to_uint8_array(&self) -> js_sys::Uint8Array {
+        js_sys::Uint8Array::from(self.pixels.as_slice())
+    }
+}
+
+/// Lectura de una imagen en curso; el backend la resuelve cuando la GPU
+/// termina la copia (ver `RenderBackend::poll_readbacks`)
+#[derive(Debug)]
+pub struct ImageReadback {
+    receiver: oneshot::Receiver<Result<ImageData>>,
+}
+
+impl ImageReadback {
+    /// Crear una lectura y el extremo con el que el backend la resuelve
+    pub fn channel() -> (oneshot::Sender<Result<ImageData>>, Self) {
+        let (sender, receiver) = oneshot::channel();
+        (sender, Self { receiver })
+    }
+
+    /// Recoger la imagen si ya llegó
+    pub fn try_take(&mut self) -> Option<Result<ImageData>> {
+        match self.receiver.try_recv() {
+            Ok(result) => result,
+            Err(_) => Some(Err(anyhow!("Lectura de imagen cancelada por el backend"))),
+        }
+    }
+}
+
+/// Luz direccional del frame
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    /// Dirección hacia la que apunta la luz
+    pub direction: Vec3,
+    /// Color por intensidad
+    pub color: Vec3,
+    /// Luz ambiente
+    pub ambient: Vec3,
+}
+
+/// Entorno HDR del frame: iluminación de imagen y skybox
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvironmentFrame {
+    /// ID de los mapas subidos con `upload_environment`
+    pub id: String,
+    /// Multiplicador de la luz del entorno
+    pub intensity: f32,
+    /// Giro del entorno alrededor de Y en radianes
+    pub rotation: f32,
+    /// Dibujar el entorno como fondo
+    pub skybox: bool,
+}
+
+/// Lista de dibujo de un frame
+#[derive(Debug, Clone)]
+pub struct DrawList {
+    /// Llamadas de dibujo
+    pub calls: Vec<DrawCall>,
+    /// Llamadas instanciadas (una draw call por grupo mesh/material)
+    pub instanced: Vec<InstancedDraw>,
+    /// Matriz vista-proyección de la cámara
+    pub view_projection: Mat4,
+    /// Posición de la cámara
+    pub camera_position: Vec3,
+    /// Color de limpieza
+    pub clear_color: Vec4,
+    /// Luz direccional (sin luz, luces locales ni entorno se dibuja sin iluminar)
+    pub light: Option<DirectionalLight>,
+    /// Luces puntuales y focos, de la más intensa a la más débil
+    pub lights: Vec<LocalLight>,
+    /// Rejilla de clusters de las luces locales (None sin luces locales)
+    pub clusters: Option<ClusterFrame>,
+    /// Entorno HDR (sin él no hay IBL ni skybox)
+    pub environment: Option<EnvironmentFrame>,
+    /// Decals del frame ya ordenados y recortados al presupuesto
+    pub decals: Vec<DecalDraw>,
+    /// Partículas del frame (None sin partículas vivas)
+    pub particles: Option<ParticleFrame>,
+    /// Cascadas de sombra de la luz direccional
+    pub shadows: Option<CascadedShadows>,
+    /// Cadena de post-procesado (sin ella solo se copia el render HDR al destino)
+    pub post: Option<PostProcessFrame>,
+    /// Variable rate shading (sin él no se genera la imagen de tasas)
+    pub vrs: Option<VRSFrame>,
+    /// Paletas de huesos de las draw calls con skin
+    pub skins: HashMap<u64, SkinPalette>,
+    /// Pesos de los morph targets de las draw calls con morph
+    pub morphs: HashMap<u64, MorphWeights>,
+    /// Texto y UI sobre la imagen final (None sin texto)
+    pub text: Option<TextFrame>,
+    /// Segmentos de debug draw sobre la imagen final (None sin segmentos)
+    pub debug: Option<DebugDrawFrame>,
+    /// Occlusion culling en dos fases (None desactivado)
+    pub occlusion: Option<OcclusionFrame>,
+}
+
+impl DrawList {
+    /// Agrupar las llamadas por (mesh, material, capas) y mover a `instanced` los
+    /// grupos con al menos `threshold` llamadas. Devuelve los grupos creados
+    pub fn batch_instances(&mut self, threshold: usize) -> usize {
+        let threshold = threshold.max(2);
+        let mut groups: HashMap<(String, Option<String>, u32), Vec<DrawCall>> = HashMap::new();
+        let mut order = Vec::new();
+        let mut skinned = Vec::new();
+        for call in self.calls.drain(..) {
+            // Cada malla con skin o morph tiene sus propios vértices deformados
+            if call.skin.is_some() || call.morph.is_some() {
+                skinned.push(call);
+                continue;
+            }
+            let key = (call.mesh_id.clone(), call.material_id.clone(), call.layers);
+            let group = groups.entry(key.clone()).or_default();
+            if group.is_empty() {
+                order.push(key);
+            }
+            group.push(call);
+        }
+
+        let mut batches = 0;
+        self.calls.extend(skinned);
+        for key in order {
+            let calls = groups.remove(&key).unwrap_or_default();
+            if calls.len() < threshold {
+                self.calls.extend(calls);
+                continue;
+            }
+            let (mesh_id, material_id, layers) = key;
+            self.instanced.push(InstancedDraw {
+                mesh_id,
+                material_id,
+                // El color base sale del material, común a todo el grupo
+                base_color: calls[0].base_color,
+                layers,
+                instances: calls.iter()
+                    .map(|call| InstanceData { transform: call.transform, color: Vec4::ONE })
+                    .collect(),
+            });
+            batches += 1;
+        }
+        batches
+    }
+
+    /// Meshes referenciados por la lista
+    pub fn mesh_ids(&self) -> impl Iterator<Item = &str> {
+        self.calls.iter().map(|call| call.mesh_id.as_str())
+            .chain(self.instanced.iter().map(|draw| draw.mesh_id.as_str()))
+    }
+
+    /// Fuentes del texto de la lista
+    pub fn font_ids(&self) -> impl Iterator<Item = &str> {
+        self.text.iter().flat_map(|text| text.font_ids())
+    }
+
+    /// Texturas de los decals de la lista
+    pub fn decal_texture_ids(&self) -> impl Iterator<Item = &str> {
+        self.decals.iter().map(|decal| decal.texture_id.as_str())
+    }
+
+    /// Materiales referenciados por la lista
+    pub fn material_ids(&self) -> impl Iterator<Item = &str> {
+        self.calls.iter().filter_map(|call| call.material_id.as_deref())
+            .chain(self.instanced.iter().filter_map(|draw| draw.material_id.as_deref()))
+    }
+}
+
+impl Default for DrawList {
+    fn default() -> Self {
+        Self {
+            calls: Vec::new(),
+            instanced: Vec::new(),
+            view_projection: Mat4::IDENTITY,
+            camera_position: Vec3::ZERO,
+            clear_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            light: None,
+            lights: Vec::new(),
+            clusters: None,
+            environment: None,
+            decals: Vec::new(),
+            particles: None,
+            shadows: None,
+            post: None,
+            vrs: None,
+            skins: HashMap::new(),
+            morphs: HashMap::new(),
+            text: None,
+            debug: None,
+            occlusion: None,
+        }
+    }
+}
+
+/// Estadísticas de un frame enviado al backend
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    /// Draw calls emitidas
+    pub draw_calls: u32,
+    /// Triángulos dibujados
+    pub triangles: u32,
+    /// Vértices dibujados
+    pub vertices: u32,
+    /// Draw calls del shadow pass
+    pub shadow_draw_calls: u32,
+    /// Pases del render graph ejecutados
+    pub render_passes: u32,
+    /// Pases del render graph descartados
+    pub culled_passes: u32,
+    /// Píxeles medios por invocación según la imagen de tasas del VRS (0 sin VRS)
+    pub average_shading_rate: f32,
+    /// Vértices deformados por el skinning
+    pub skinned_vertices: u32,
+    /// Vértices deformados por morph targets
+    pub morphed_vertices: u32,
+    /// Tiempo de GPU del último frame medido en milisegundos (0 sin timestamps)
+    pub gpu_time_ms: f32,
+    /// Decals aplicados (los de textura aún no subida se omiten)
+    pub decals: u32,
+    /// Partículas dibujadas
+    pub particles: u32,
+    /// Quads de texto y UI dibujados
+    pub text_quads: u32,
+    /// Segmentos de debug draw dibujados
+    pub debug_lines: u32,
+    /// Draw calls probadas por el occlusion culling
+    pub occlusion_tested: u32,
+    /// Draw calls descartadas por oclusión (con GPU, del frame anterior)
+    pub occluded: u32,
+    /// Luces locales asignadas a los clusters
+    pub clustered_lights: u32,
+    /// Clusters con más luces de las que caben (con GPU, del frame anterior)
+    pub overflowed_clusters: u32,
+    /// Referencias a luces descartadas en los clusters desbordados
+    pub dropped_cluster_lights: u32,
+    /// Mayor número de luces que alcanzan un mismo cluster
+    pub max_cluster_lights: u32,
+    /// Variantes de shader servidas desde la caché en disco (acumulado)
+    pub shader_cache_hits: u32,
+    /// Variantes de shader compiladas desde WGSL (acumulado)
+    pub shader_cache_misses: u32,
+}
+
+/// Backend de renderizado
+pub trait RenderBackend: Send {
+    /// Nombre del backend
+    fn name(&self) -> &str;
+    /// Redimensionar el destino de renderizado
+    fn resize(&mut self, width: u32, height: u32);
+    /// Cambiar la escala de la resolución interna respecto al destino
+    fn set_render_scale(&mut self, scale: f32);
+    /// Cambiar las muestras MSAA del pase principal (se ajustan a las que
+    /// soporte el adaptador) y recompilar los pipelines que dependen de ellas
+    fn set_sample_count(&mut self, sample_count: u32);
+    /// Subir un mesh a la GPU (o reemplazarlo si ya existía)
+    fn upload_mesh(&mut self, mesh: &Mesh) -> Result<()>;
+    /// Verificar si un mesh está en la GPU
+    fn has_mesh(&self, mesh_id: &str) -> bool;
+    /// Liberar un mesh
+    fn remove_mesh(&mut self, mesh_id: &str);
+    /// Subir una textura a la GPU (o reemplazarla si ya existía)
+    fn upload_texture(&mut self, texture: &TextureImage) -> Result<()>;
+    /// Verificar si una textura está en la GPU
+    fn has_texture(&self, texture_id: &str) -> bool;
+    /// Liberar una textura (los materiales que la usan conservan su bind
+    /// group hasta que se vuelvan a subir)
+    fn remove_texture(&mut self, texture_id: &str);
+    /// Subir un material. Si ya existía solo se reescriben sus uniforms,
+    /// salvo que cambien sus texturas o su tipo
+    fn upload_material(&mut self, material: &MaterialDesc) -> Result<()>;
+    /// Verificar si un material está en la GPU
+    fn has_material(&self, material_id: &str) -> bool;
+    /// Liberar un material
+    fn remove_material(&mut self, material_id: &str);
+    /// Subir los mapas de un entorno (o reemplazarlos si ya existía)
+    fn upload_environment(&mut self, environment: &EnvironmentMaps) -> Result<()>;
+    /// Verificar si un entorno está en la GPU
+    fn has_environment(&self, environment_id: &str) -> bool;
+    /// Liberar un entorno
+    fn remove_environment(&mut self, environment_id: &str);
+    /// Subir la textura de un decal al atlas de decals. Falla si el atlas
+    /// está lleno con texturas usadas en el frame en curso
+    fn upload_decal_texture(&mut self, texture: &TextureImage) -> Result<()>;
+    /// Verificar si la textura de un decal está en el atlas
+    fn has_decal_texture(&self, texture_id: &str) -> bool;
+    /// Liberar la textura de un decal
+    fn remove_decal_texture(&mut self, texture_id: &str);
+    /// Subir el atlas de una fuente (o reemplazarlo si ya existía)
+    fn upload_font(&mut self, atlas: &FontAtlas) -> Result<()>;
+    /// Verificar si una fuente está en la GPU
+    fn has_font(&self, font_id: &str) -> bool;
+    /// Liberar una fuente
+    fn remove_font(&mut self, font_id: &str);
+    /// Recompilar los pipelines de un tipo de material con un shader nuevo
+    /// (compilado tras las declaraciones comunes). Si no compila se
+    /// conservan los pipelines anteriores
+    fn reload_material_shader(&mut self, kind: MaterialKind, source: &str) -> std::result::Result<(), ShaderError>;
+    /// Renderizar una lista de dibujo
+    fn render(&mut self, draw_list: &DrawList) -> Result<FrameStats>;
+    /// Copiar el destino del próximo frame de `render`; la lectura se
+    /// resuelve cuando la GPU termina la copia
+    fn capture_next_frame(&mut self) -> ImageReadback;
+    /// Renderizar una lista de dibujo sobre el destino offscreen `target_id`
+    /// de `width` x `height`, sin tocar el destino principal. Si ya existe
+    /// con otro tamaño se recrea
+    fn render_to_texture(&mut self, target_id: &str, draw_list: &DrawList, width: u32, height: u32) -> Result<FrameStats>;
+    /// Verificar si existe un destino offscreen
+    fn has_render_texture(&self, target_id: &str) -> bool;
+    /// Leer un destino offscreen
+    fn read_render_texture(&mut self, target_id: &str) -> Result<ImageReadback>;
+    /// Liberar un destino offscreen
+    fn remove_render_texture(&mut self, target_id: &str);
+    /// Copiar un destino offscreen a una textura externa del mismo tamaño o
+    /// mayor y formato compatible (p. ej. la imagen de un ojo del swapchain
+    /// de XR)
+    fn copy_render_texture(&mut self, target_id: &str, destination: &wgpu::Texture) -> Result<()>;
+    /// Entregar las lecturas de imágenes que la GPU ya terminó
+    fn poll_readbacks(&mut self);
+}
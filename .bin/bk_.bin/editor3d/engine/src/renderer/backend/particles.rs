@@ -0,0 +1,586 @@
+//! # Partículas en la GPU
+//!
+//! Billboards instanciados: seis vértices por partícula generados en el
+//! vertex shader y una instancia por partícula con su posición, velocidad,
+//! edad y vida. Con simulación en GPU el vertex shader integra la velocidad
+//! y la aceleración del lote desde el estado de emisión; el color y el tamaño
+//! salen de las curvas muestreadas del lote. Los lotes tienen sus uniforms
+//! en un buffer con offsets dinámicos.
+//!
+//! Hay dos pipelines: el suave dibuja en su propio pase sin MSAA, lee la
+//! profundidad de la escena, descarta lo ocluido y desvanece lo cercano a la
+//! geometría; el duro se dibuja al final del pase principal con MSAA, con
+//! test de profundidad y sin desvanecimiento.
+
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use std::ops::Range;
+
+use super::wgpu_backend::DEPTH_FORMAT;
+use crate::animations::particles::{ParticleSimulation, CURVE_SAMPLES};
+use crate::renderer::particles::ParticleFrame;
+
+/// Shader de los billboards
+const PARTICLE_SHADER: &str = r#"
+struct ParticleUniforms {
+    view_projection: mat4x4<f32>,
+    // w = plano near
+    camera_right: vec4<f32>,
+    // w = plano far
+    camera_up: vec4<f32>,
+    // w = 1 si el shader integra el movimiento
+    acceleration: vec4<f32>,
+    // x = distancia de desvanecimiento
+    params: vec4<f32>,
+    // Tamaño de `particles::CURVE_SAMPLES`
+    colors: array<vec4<f32>, 16>,
+    sizes: array<vec4<f32>, 4>,
+};
+
+@group(0) @binding(0) var<uniform> batch: ParticleUniforms;
+@group(1) @binding(0) var scene_depth: texture_depth_2d;
+
+struct ParticleInput {
+    @location(0) position: vec3<f32>,
+    @location(1) age: f32,
+    @location(2) velocity: vec3<f32>,
+    @location(3) lifetime: f32,
+};
+
+struct ParticleOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) corner: vec2<f32>,
+};
+
+fn curve_size(index: u32) -> f32 {
+    return batch.sizes[index / 4u][index % 4u];
+}
+
+@vertex
+fn vs_particle(@builtin(vertex_index) vertex: u32, particle: ParticleInput) -> ParticleOutput {
+    var out: ParticleOutput;
+    let t = clamp(particle.age / max(particle.lifetime, 0.0001), 0.0, 1.0);
+    let sample = t * 15.0;
+    let i = u32(floor(sample));
+    let j = min(i + 1u, 15u);
+    let f = sample - f32(i);
+
+    var position = particle.position;
+    if (batch.acceleration.w > 0.5) {
+        position += particle.velocity * particle.age + 0.5 * batch.acceleration.xyz * particle.age * particle.age;
+    }
+
+    // Dos triángulos: (-1,-1) (1,-1) (1,1) y (-1,-1) (1,1) (-1,1)
+    let corner = vec2<f32>(
+        select(-1.0, 1.0, vertex == 1u || vertex == 2u || vertex == 4u),
+        select(-1.0, 1.0, vertex == 2u || vertex == 4u || vertex == 5u),
+    );
+    let size = mix(curve_size(i), curve_size(j), f);
+    let world = position + (batch.camera_right.xyz * corner.x + batch.camera_up.xyz * corner.y) * size * 0.5;
+    out.clip_position = batch.view_projection * vec4<f32>(world, 1.0);
+    out.color = mix(batch.colors[i], batch.colors[j], f);
+    out.corner = corner;
+    return out;
+}
+
+// Disco con el borde difuminado, en alfa premultiplicado
+fn sprite(input: ParticleOutput) -> vec4<f32> {
+    let r = length(input.corner);
+    if (r > 1.0) {
+        discard;
+    }
+    let alpha = input.color.a * (1.0 - r * r);
+    return vec4<f32>(input.color.rgb * alpha, alpha);
+}
+
+fn linear_depth(depth: f32) -> f32 {
+    let near = batch.camera_right.w;
+    let far = batch.camera_up.w;
+    return near * far / max(far - depth * (far - near), 0.0001);
+}
+
+@fragment
+fn fs_particle_soft(input: ParticleOutput) -> @location(0) vec4<f32> {
+    let depth = textureLoad(scene_depth, vec2<i32>(input.clip_position.xy), 0);
+    if (input.clip_position.z >= depth) {
+        discard;
+    }
+    var color = sprite(input);
+    if (batch.params.x > 0.0) {
+        color *= clamp((linear_depth(depth) - linear_depth(input.clip_position.z)) / batch.params.x, 0.0, 1.0);
+    }
+    return color;
+}
+
+@fragment
+fn fs_particle(input: ParticleOutput) -> @location(0) vec4<f32> {
+    return sprite(input);
+}
+"#;
+
+/// Uniforms de un lote
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ParticleUniforms {
+    view_projection: [[f32; 4]; 4],
+    camera_right: [f32; 4],
+    camera_up: [f32; 4],
+    acceleration: [f32; 4],
+    params: [f32; 4],
+    colors: [[f32; 4]; CURVE_SAMPLES],
+    sizes: [[f32; 4]; CURVE_SAMPLES / 4],
+}
+
+/// Partícula en formato GPU
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct GpuParticle {
+    position: [f32; 3],
+    age: f32,
+    velocity: [f32; 3],
+    lifetime: f32,
+}
+
+impl GpuParticle {
+    /// Atributos de la instancia
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32,
+        2 => Float32x3,
+        3 => Float32,
+    ];
+
+    /// Layout del buffer de partículas
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GpuParticle>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Recursos de partículas del backend wgpu
+pub struct ParticleResources {
+    /// Layout de los uniforms del lote (grupo 0)
+    uniform_layout: wgpu::BindGroupLayout,
+    /// Layout de la profundidad de la escena (grupo 1 del pipeline suave)
+    depth_layout: wgpu::BindGroupLayout,
+    /// Pipeline del pase de partículas suaves
+    soft_pipeline: wgpu::RenderPipeline,
+    /// Pipeline dentro del pase principal
+    hard_pipeline: wgpu::RenderPipeline,
+    /// Uniforms de los lotes
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    /// Separación entre uniforms de lotes consecutivos
+    uniform_stride: u64,
+    /// Capacidad en lotes
+    batch_capacity: usize,
+    /// Partículas del frame
+    instance_buffer: wgpu::Buffer,
+    /// Capacidad en partículas
+    instance_capacity: usize,
+    /// Rango de partículas de cada lote del frame
+    batches: Vec<Range<u32>>,
+}
+
+impl ParticleResources {
+    /// Crear pipelines y buffers. `sample_count` es el del pase principal
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle-uniforms"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ParticleUniforms>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle-depth"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("particle-shader"),
+            source: wgpu::ShaderSource::Wgsl(PARTICLE_SHADER.into()),
+        });
+        let soft_pipeline = Self::create_pipeline(
+            device,
+            &module,
+            &[&uniform_layout, &depth_layout],
+            "fs_particle_soft",
+            format,
+            1,
+            false,
+        );
+        let hard_pipeline = Self::create_pipeline(
+            device,
+            &module,
+            &[&uniform_layout],
+            "fs_particle",
+            format,
+            sample_count,
+            true,
+        );
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let uniform_stride = wgpu::util::align_to(std::mem::size_of::<ParticleUniforms>() as u64, alignment);
+        let (uniform_buffer, uniform_bind_group) = Self::create_uniforms(device, &uniform_layout, uniform_stride, 8);
+        let instance_buffer = Self::create_instances(device, 1024);
+
+        Self {
+            uniform_layout,
+            depth_layout,
+            soft_pipeline,
+            hard_pipeline,
+            uniform_buffer,
+            uniform_bind_group,
+            uniform_stride,
+            batch_capacity: 8,
+            instance_buffer,
+            instance_capacity: 1024,
+            batches: Vec::new(),
+        }
+    }
+
+    /// Recrear el pipeline del pase principal con otras muestras MSAA (el
+    /// de partículas suaves dibuja sin MSAA)
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32) {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("particle-shader"),
+            source: wgpu::ShaderSource::Wgsl(PARTICLE_SHADER.into()),
+        });
+        self.hard_pipeline = Self::create_pipeline(
+            device,
+            &module,
+            &[&self.uniform_layout],
+            "fs_particle",
+            format,
+            sample_count,
+            true,
+        );
+    }
+
+    /// Pipeline con mezcla alfa premultiplicada que no escribe profundidad
+    fn create_pipeline(
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layouts: &[&wgpu::BindGroupLayout],
+        fragment: &str,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_test: bool,
+    ) -> wgpu::RenderPipeline {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("particle-pipeline-layout"),
+            bind_group_layouts: layouts,
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("particle-pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: "vs_particle",
+                buffers: &[GpuParticle::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: fragment,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: depth_test.then(|| wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// Buffer de uniforms para `capacity` lotes y su bind group
+    fn create_uniforms(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        stride: u64,
+        capacity: usize,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particle-uniforms"),
+            size: stride * capacity as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particle-uniforms"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ParticleUniforms>() as u64),
+                }),
+            }],
+        });
+        (buffer, bind_group)
+    }
+
+    /// Buffer de `capacity` partículas
+    fn create_instances(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particles"),
+            size: (std::mem::size_of::<GpuParticle>() * capacity) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Escribir los uniforms y las partículas del frame. Devuelve las
+    /// partículas que se dibujarán
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: Option<&ParticleFrame>,
+        view_projection: &Mat4,
+    ) -> u32 {
+        self.batches.clear();
+        let Some(frame) = frame else {
+            return 0;
+        };
+
+        let mut uniforms = Vec::with_capacity(frame.batches.len());
+        let mut particles = Vec::with_capacity(frame.particle_count());
+        for batch in &frame.batches {
+            let start = particles.len() as u32;
+            particles.extend(batch.particles.iter().map(|particle| GpuParticle {
+                position: particle.position.to_array(),
+                age: particle.age,
+                velocity: particle.velocity.to_array(),
+                lifetime: particle.lifetime,
+            }));
+            self.batches.push(start..particles.len() as u32);
+
+            let integrate = if batch.simulation == ParticleSimulation::Gpu { 1.0 } else { 0.0 };
+            uniforms.push(ParticleUniforms {
+                view_projection: view_projection.to_cols_array_2d(),
+                camera_right: frame.camera_right.extend(frame.clip.0).to_array(),
+                camera_up: frame.camera_up.extend(frame.clip.1).to_array(),
+                acceleration: batch.acceleration.extend(integrate).to_array(),
+                params: [batch.soft_distance.max(0.0), 0.0, 0.0, 0.0],
+                colors: batch.colors.map(|color| color.to_array()),
+                sizes: std::array::from_fn(|i| std::array::from_fn(|j| batch.sizes[i * 4 + j])),
+            });
+        }
+
+        if uniforms.len() > self.batch_capacity {
+            self.batch_capacity = uniforms.len().next_power_of_two();
+            (self.uniform_buffer, self.uniform_bind_group) =
+                Self::create_uniforms(device, &self.uniform_layout, self.uniform_stride, self.batch_capacity);
+        }
+        let mut bytes = vec![0u8; self.uniform_stride as usize * uniforms.len()];
+        for (i, batch) in uniforms.iter().enumerate() {
+            let offset = i * self.uniform_stride as usize;
+            bytes[offset..offset + std::mem::size_of::<ParticleUniforms>()].copy_from_slice(bytemuck::bytes_of(batch));
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, &bytes);
+
+        if particles.len() > self.instance_capacity {
+            self.instance_capacity = particles.len().next_power_of_two();
+            self.instance_buffer = Self::create_instances(device, self.instance_capacity);
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&particles));
+        particles.len() as u32
+    }
+
+    /// Grabar el pase de partículas suaves sobre `scene` leyendo `depth`.
+    /// Devuelve las draw calls
+    pub fn record_soft(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+    ) -> u32 {
+        if self.batches.is_empty() {
+            return 0;
+        }
+        let depth_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particle-depth"),
+            layout: &self.depth_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(depth),
+            }],
+        });
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("particle-pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: scene,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.soft_pipeline);
+        pass.set_bind_group(1, &depth_bind_group, &[]);
+        self.draw(&mut pass)
+    }
+
+    /// Dibujar las partículas al final del pase principal (con MSAA, donde
+    /// la profundidad no se puede muestrear). Devuelve las draw calls
+    pub fn record_in_pass<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) -> u32 {
+        if self.batches.is_empty() {
+            return 0;
+        }
+        pass.set_pipeline(&self.hard_pipeline);
+        self.draw(pass)
+    }
+
+    /// Una draw call por lote con su offset de uniforms
+    fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) -> u32 {
+        pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        for (i, particles) in self.batches.iter().enumerate() {
+            let offset = (i as u64 * self.uniform_stride) as u32;
+            pass.set_bind_group(0, &self.uniform_bind_group, &[offset]);
+            pass.draw(0..6, particles.clone());
+        }
+        self.batches.len() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::wgpu_backend::HDR_FORMAT;
+    use crate::animations::particles::{ParticleEmitter, ParticleSystem};
+    use crate::animations::{
+        ColorConfig, ColorCurve, EmissionConfig, ParticleConfig, ParticleEvent, SizeConfig, SizeCurve, VelocityConfig,
+    };
+    use glam::Vec3;
+
+    /// Dispositivo headless, o None si la máquina no tiene adaptador
+    async fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+        adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("particles-test-device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+        }, None).await.ok()
+    }
+
+    /// Emisor de `emission_rate` partículas por segundo que viven 2 s
+    fn emitter(id: &str, emission_rate: f32) -> ParticleEmitter {
+        ParticleEmitter::new(id, ParticleConfig {
+            max_particles: 1000,
+            emission_rate,
+            particle_lifetime: 2.0,
+            velocity_config: VelocityConfig {
+                initial_velocity: [0.0, 1.0, 0.0],
+                velocity_variation: [0.5, 0.0, 0.5],
+                acceleration: [0.0, -9.81, 0.0],
+            },
+            color_config: ColorConfig {
+                initial_color: [1.0, 0.5, 0.0, 1.0],
+                final_color: [0.2, 0.2, 0.2, 0.0],
+                color_curve: ColorCurve::EaseOut,
+            },
+            size_config: SizeConfig {
+                initial_size: 0.05,
+                final_size: 0.2,
+                size_curve: SizeCurve::Linear,
+            },
+            soft_distance: 0.5,
+        }, Vec3::ZERO)
+    }
+
+    #[tokio::test]
+    async fn prepared_instances_match_the_live_particles() {
+        let Some((device, queue)) = device().await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        let mut resources = ParticleResources::new(&device, HDR_FORMAT, 1);
+        let mut system = ParticleSystem::new();
+        system.add_emitter(emitter("fuego", 100.0));
+        system.add_emitter(emitter("chispas", 0.0));
+
+        // Un segundo a 100/s: unas 100 partículas vivas en un solo lote
+        for _ in 0..60 {
+            system.update(1.0 / 60.0);
+        }
+        let continuous = system.live_particles();
+        assert!((98..=102).contains(&continuous), "{} partículas vivas", continuous);
+
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 1.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let view_projection = Mat4::perspective_rh(1.0, 1.0, 0.1, 100.0) * view;
+        let frame = ParticleFrame::new(system.batches(), &view, (0.1, 100.0)).unwrap();
+        assert_eq!(resources.prepare(&device, &queue, Some(&frame), &view_projection), continuous as u32);
+        assert_eq!(resources.batches, vec![0..continuous as u32]);
+
+        // La ráfaga del evento añade exactamente `particle_count` en el
+        // lote de su emisor
+        let event = ParticleEvent {
+            particle_system_id: "chispas".to_string(),
+            emission_config: EmissionConfig { particle_count: 40, emission_rate: 0.0, emission_duration: 0.0 },
+        };
+        assert_eq!(system.trigger(&event), Some(40));
+        let frame = ParticleFrame::new(system.batches(), &view, (0.1, 100.0)).unwrap();
+        assert_eq!(resources.prepare(&device, &queue, Some(&frame), &view_projection), continuous as u32 + 40);
+        let mut sizes: Vec<u32> = resources.batches.iter().map(|range| range.end - range.start).collect();
+        sizes.sort();
+        assert_eq!(sizes, [40, continuous as u32]);
+
+        // Pasada su vida no queda ninguna partícula que preparar
+        system.get_mut("fuego").unwrap().emitting = false;
+        for _ in 0..121 {
+            system.update(1.0 / 60.0);
+        }
+        assert_eq!(system.live_particles(), 0);
+        assert!(ParticleFrame::new(system.batches(), &view, (0.1, 100.0)).is_none());
+        assert_eq!(resources.prepare(&device, &queue, None, &view_projection), 0);
+    }
+}
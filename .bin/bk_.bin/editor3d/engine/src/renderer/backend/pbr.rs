@@ -0,0 +1,779 @@
+//! # Pipeline PBR
+//!
+//! Materiales del backend wgpu. Cada tipo de material tiene su layout de
+//! bind group (grupo 2) y sus pipelines; cada material, un buffer de uniforms
+//! y un bind group con sus texturas. Las texturas se cachean por ID y los
+//! slots sin textura usan texturas neutras de 1x1. Con un entorno en el
+//! frame, el PBR añade la iluminación de imagen con el split-sum. Los
+//! shaders iluminados aplican los decals del frame al albedo y suman las
+//! luces locales de su cluster. El shader de un tipo de material se puede
+//! sustituir en caliente: solo se reconstruyen sus pipelines.
+//!
+//! Cada variante de pipeline (`ShaderVariantKey`) se compila la primera vez
+//! que la usa un material, o todas al crear los recursos si se precompilan,
+//! y su módulo pasa por la caché de shaders en disco.
+
+use anyhow::{Result, anyhow};
+use bytemuck::{Pod, Zeroable};
+use std::collections::HashMap;
+use wgpu::util::DeviceExt;
+
+use super::{MaterialDesc, MaterialKind, MaterialParams, MaterialTextureSlots, TextureImage};
+use super::wgpu_backend::{GpuInstance, GpuVertex, DEPTH_FORMAT, SHADER_COMMON};
+use crate::renderer::shader_cache::{ShaderCache, ShaderVariantKey};
+use crate::renderer::shader_reload::{material_entry_points, material_shader_name, validate_wgsl, ShaderError};
+
+/// Shader de materiales (se compila tras `SHADER_COMMON`)
+const MATERIAL_SHADER: &str = r#"
+struct MaterialUniforms {
+    base_color: vec4<f32>,
+    emissive: vec4<f32>,
+    // x = metallic, y = roughness, z = escala de normales, w = oclusión
+    params: vec4<f32>,
+    // x = alpha cutoff
+    alpha: vec4<f32>,
+};
+
+@group(2) @binding(0) var<uniform> material: MaterialUniforms;
+@group(2) @binding(1) var material_sampler: sampler;
+@group(2) @binding(2) var albedo_map: texture_2d<f32>;
+@group(2) @binding(3) var metallic_roughness_map: texture_2d<f32>;
+@group(2) @binding(4) var normal_map: texture_2d<f32>;
+@group(2) @binding(5) var emissive_map: texture_2d<f32>;
+@group(2) @binding(6) var occlusion_map: texture_2d<f32>;
+
+const PI: f32 = 3.14159265;
+
+struct MaterialVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_material(input: VertexInput, instance: InstanceInput) -> MaterialVertexOutput {
+    var out: MaterialVertexOutput;
+    let model = instance_model(instance);
+    let world = model * vec4<f32>(input.position, 1.0);
+    out.clip_position = draw.view_projection * world;
+    out.normal = (model * vec4<f32>(input.normal, 0.0)).xyz;
+    out.color = input.color * instance.color;
+    out.world_position = world.xyz;
+    out.uv = input.uv;
+    return out;
+}
+
+// El marco tangente se deriva en pantalla: los vértices no llevan tangentes
+fn perturb_normal(normal: vec3<f32>, world_position: vec3<f32>, uv: vec2<f32>, sampled: vec3<f32>) -> vec3<f32> {
+    let tangent_normal = vec3<f32>(sampled.xy * material.params.z, sampled.z);
+    let dp1 = dpdx(world_position);
+    let dp2 = dpdy(world_position);
+    let duv1 = dpdx(uv);
+    let duv2 = dpdy(uv);
+    let dp2perp = cross(dp2, normal);
+    let dp1perp = cross(normal, dp1);
+    let t = dp2perp * duv1.x + dp1perp * duv2.x;
+    let b = dp2perp * duv1.y + dp1perp * duv2.y;
+    let scale = inverseSqrt(max(max(dot(t, t), dot(b, b)), 1e-12));
+    return normalize(mat3x3<f32>(t * scale, b * scale, normal) * tangent_normal);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    return (n_dot_v / (n_dot_v * (1.0 - k) + k)) * (n_dot_l / (n_dot_l * (1.0 - k) + k));
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Iluminación de imagen: irradiancia difusa más especular prefiltrado por
+// rugosidad con la escala y el sesgo del F0 de la LUT de la BRDF
+fn environment_lighting(
+    normal: vec3<f32>,
+    v: vec3<f32>,
+    n_dot_v: f32,
+    f0: vec3<f32>,
+    albedo: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+) -> vec3<f32> {
+    let r = reflect(-v, normal);
+    let f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+    let irradiance = textureSampleLevel(irradiance_map, environment_sampler, environment_direction(normal), 0.0).rgb;
+    let prefiltered = textureSampleLevel(
+        specular_map,
+        environment_sampler,
+        environment_direction(r),
+        roughness * frame.environment.w,
+    ).rgb;
+    let brdf = textureSampleLevel(brdf_lut, environment_sampler, vec2<f32>(n_dot_v, roughness), 0.0).rg;
+    let diffuse = (vec3<f32>(1.0) - f) * (1.0 - metallic) * irradiance * albedo;
+    let specular = prefiltered * (f * brdf.x + brdf.y);
+    return (diffuse + specular) * frame.environment.x;
+}
+
+// Luz directa de Cook-Torrance para una luz en la dirección `l`. PI
+// compensa la normalización del difuso para igualar el brillo del shader por
+// defecto
+fn direct_lighting(
+    normal: vec3<f32>,
+    v: vec3<f32>,
+    l: vec3<f32>,
+    n_dot_v: f32,
+    f0: vec3<f32>,
+    albedo: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+) -> vec3<f32> {
+    let h = normalize(v + l);
+    let n_dot_l = max(dot(normal, l), 0.0);
+    let n_dot_h = max(dot(normal, h), 0.0);
+    let f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+    let specular = distribution_ggx(n_dot_h, roughness) * geometry_smith(n_dot_v, n_dot_l, roughness) * f
+        / (4.0 * n_dot_v * max(n_dot_l, 1e-4));
+    let diffuse = (vec3<f32>(1.0) - f) * (1.0 - metallic) * albedo / PI;
+    return (diffuse + specular) * n_dot_l * PI;
+}
+
+// Luces locales del cluster del fragmento
+fn local_lighting(
+    world_position: vec3<f32>,
+    normal: vec3<f32>,
+    v: vec3<f32>,
+    n_dot_v: f32,
+    f0: vec3<f32>,
+    albedo: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+) -> vec3<f32> {
+    var color = vec3<f32>(0.0);
+    let range = cluster_light_range(world_position);
+    for (var i = 0u; i < range.x; i++) {
+        let light = cluster_lights[cluster_indices[range.y + i]];
+        let to_light = light.position.xyz - world_position;
+        let attenuation = local_light_attenuation(light, to_light);
+        if (attenuation > 0.0) {
+            let brdf = direct_lighting(normal, v, normalize(to_light), n_dot_v, f0, albedo, metallic, roughness);
+            color += brdf * light.color.rgb * attenuation;
+        }
+    }
+    return color;
+}
+
+@fragment
+fn fs_pbr(input: MaterialVertexOutput) -> @location(0) vec4<f32> {
+    // Todas las lecturas y los decals antes de cualquier discard o rama
+    let base = textureSample(albedo_map, material_sampler, input.uv) * material.base_color * input.color;
+    let metallic_roughness = textureSample(metallic_roughness_map, material_sampler, input.uv);
+    let sampled_normal = textureSample(normal_map, material_sampler, input.uv).xyz * 2.0 - 1.0;
+    let sampled_emissive = textureSample(emissive_map, material_sampler, input.uv).rgb * material.emissive.rgb;
+    let occlusion = textureSample(occlusion_map, material_sampler, input.uv).r;
+    let geometric_normal = normalize(input.normal);
+    let normal = perturb_normal(geometric_normal, input.world_position, input.uv, sampled_normal);
+    let decal = apply_decals(base.rgb, input.world_position, geometric_normal);
+    let albedo = vec4<f32>(decal.albedo, base.a);
+    let emissive = sampled_emissive + decal.emissive;
+    if (albedo.a < material.alpha.x) {
+        discard;
+    }
+
+    let metallic = clamp(metallic_roughness.b * material.params.x, 0.0, 1.0);
+    let roughness = clamp(metallic_roughness.g * material.params.y, 0.04, 1.0);
+    let ao = mix(1.0, occlusion, material.params.w);
+    let v = normalize(frame.camera_position.xyz - input.world_position);
+    let n_dot_v = max(dot(normal, v), 1e-4);
+    let f0 = mix(vec3<f32>(0.04), albedo.rgb, metallic);
+
+    var ambient = frame.ambient.rgb * albedo.rgb * ao;
+    if (frame.environment.x > 0.0) {
+        ambient = environment_lighting(normal, v, n_dot_v, f0, albedo.rgb, metallic, roughness) * ao;
+    }
+
+    let local_light = local_lighting(input.world_position, normal, v, n_dot_v, f0, albedo.rgb, metallic, roughness);
+
+    if (frame.light_direction.w == 0.0) {
+        // Sin luz direccional iluminan el entorno y las luces locales; sin
+        // ninguno de los dos se dibuja sin iluminar
+        if (frame.environment.x > 0.0 || clusters.info.x > 0u) {
+            return vec4<f32>(ambient + local_light + emissive, albedo.a);
+        }
+        return vec4<f32>(albedo.rgb * ao + emissive, albedo.a);
+    }
+
+    let l = -frame.light_direction.xyz;
+    let n_dot_l = max(dot(normal, l), 0.0);
+    var shadow = 1.0;
+    if (frame.shadow_params.z > 0.0 && n_dot_l > 0.0) {
+        shadow = shadow_factor(input.world_position, n_dot_l);
+    }
+    let direct = direct_lighting(normal, v, l, n_dot_v, f0, albedo.rgb, metallic, roughness) * frame.light_color.rgb * shadow;
+    return vec4<f32>(direct + local_light + ambient + emissive, albedo.a);
+}
+
+// El terreno usa los slots del grupo 2 en otro orden: el splat map en el del
+// albedo y las cuatro capas en los siguientes. Las capas se repiten
+// TERRAIN_LAYER_TILING veces sobre el splat map
+const TERRAIN_LAYER_TILING: f32 = 32.0;
+
+@fragment
+fn fs_terrain(input: MaterialVertexOutput) -> @location(0) vec4<f32> {
+    let layer_uv = input.uv * TERRAIN_LAYER_TILING;
+    let splat = textureSample(albedo_map, material_sampler, input.uv);
+    let layer0 = textureSample(metallic_roughness_map, material_sampler, layer_uv).rgb;
+    let layer1 = textureSample(normal_map, material_sampler, layer_uv).rgb;
+    let layer2 = textureSample(emissive_map, material_sampler, layer_uv).rgb;
+    let layer3 = textureSample(occlusion_map, material_sampler, layer_uv).rgb;
+    let weights = splat / max(splat.r + splat.g + splat.b + splat.a, 1e-4);
+    let blended = (layer0 * weights.r + layer1 * weights.g + layer2 * weights.b + layer3 * weights.a)
+        * material.base_color.rgb * input.color.rgb;
+    let normal = normalize(input.normal);
+    let decal = apply_decals(blended, input.world_position, normal);
+    let albedo = decal.albedo;
+    let local_light = local_diffuse_light(input.world_position, normal) * albedo;
+
+    if (frame.light_direction.w == 0.0) {
+        if (clusters.info.x == 0u) {
+            return vec4<f32>(albedo + decal.emissive, 1.0);
+        }
+        return vec4<f32>(local_light + ambient_light(normal) * albedo + decal.emissive, 1.0);
+    }
+
+    let n_dot_l = max(dot(normal, -frame.light_direction.xyz), 0.0);
+    var shadow = 1.0;
+    if (frame.shadow_params.z > 0.0 && n_dot_l > 0.0) {
+        shadow = shadow_factor(input.world_position, n_dot_l);
+    }
+    let direct = albedo * frame.light_color.rgb * n_dot_l * shadow;
+    return vec4<f32>(direct + local_light + ambient_light(normal) * albedo + decal.emissive, 1.0);
+}
+
+@fragment
+fn fs_unlit(input: MaterialVertexOutput) -> @location(0) vec4<f32> {
+    let albedo = textureSample(albedo_map, material_sampler, input.uv) * material.base_color * input.color;
+    if (albedo.a < material.alpha.x) {
+        discard;
+    }
+    return vec4<f32>(albedo.rgb + material.emissive.rgb, albedo.a);
+}
+"#;
+
+/// Uniforms de un material
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct MaterialUniforms {
+    base_color: [f32; 4],
+    emissive: [f32; 4],
+    params: [f32; 4],
+    alpha: [f32; 4],
+}
+
+impl From<&MaterialParams> for MaterialUniforms {
+    fn from(params: &MaterialParams) -> Self {
+        Self {
+            base_color: params.base_color.to_array(),
+            emissive: params.emissive.extend(0.0).to_array(),
+            params: [params.metallic, params.roughness, params.normal_scale, params.occlusion_strength],
+            alpha: [params.alpha_cutoff, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// Textura en la GPU
+struct GpuTexture {
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+/// Material en la GPU
+struct GpuMaterial {
+    kind: MaterialKind,
+    double_sided: bool,
+    textures: MaterialTextureSlots,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Recursos de materiales del backend wgpu
+pub struct MaterialResources {
+    /// Layout del grupo 2 por tipo de material
+    layouts: HashMap<MaterialKind, wgpu::BindGroupLayout>,
+    /// Layout de los pipelines por tipo de material
+    pipeline_layouts: HashMap<MaterialKind, wgpu::PipelineLayout>,
+    /// Código de los shaders por tipo de material (sin las declaraciones comunes)
+    sources: HashMap<MaterialKind, String>,
+    /// Pipelines compilados por tipo de material y doble cara
+    pipelines: HashMap<(MaterialKind, bool), wgpu::RenderPipeline>,
+    /// Caché de los módulos compilados
+    shader_cache: ShaderCache,
+    /// Formato del destino de los pipelines
+    format: wgpu::TextureFormat,
+    /// Muestras MSAA de los pipelines
+    sample_count: u32,
+    /// Sampler de las texturas de material
+    sampler: wgpu::Sampler,
+    /// Textura blanca para slots vacíos
+    white: GpuTexture,
+    /// Normal plana para materiales sin normal map
+    flat_normal: GpuTexture,
+    /// Texturas subidas
+    textures: HashMap<String, GpuTexture>,
+    /// Materiales subidos
+    materials: HashMap<String, GpuMaterial>,
+}
+
+impl MaterialResources {
+    /// Crear layouts y texturas neutras. Con `precompile` se compilan ya
+    /// todos los pipelines; si no, cada uno con su primer material
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        uniform_layout: &wgpu::BindGroupLayout,
+        frame_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        shader_cache: ShaderCache,
+        precompile: bool,
+    ) -> Self {
+        let mut layouts = HashMap::new();
+        let mut pipeline_layouts = HashMap::new();
+        let mut sources = HashMap::new();
+        for kind in [MaterialKind::Pbr, MaterialKind::Unlit, MaterialKind::Terrain] {
+            let layout = Self::create_layout(device, kind);
+            pipeline_layouts.insert(kind, device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("material-pipeline-layout"),
+                bind_group_layouts: &[uniform_layout, frame_layout, &layout],
+                push_constant_ranges: &[],
+            }));
+            layouts.insert(kind, layout);
+            sources.insert(kind, MATERIAL_SHADER.to_string());
+        }
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("material-sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mut resources = Self {
+            layouts,
+            pipeline_layouts,
+            sources,
+            pipelines: HashMap::new(),
+            shader_cache,
+            format,
+            sample_count,
+            sampler,
+            white: Self::create_texture(device, queue, "white-texture", 1, 1, &[255, 255, 255, 255], &[], false),
+            flat_normal: Self::create_texture(device, queue, "flat-normal-texture", 1, 1, &[128, 128, 255, 255], &[], false),
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+        };
+        if precompile {
+            resources.precompile(device);
+        }
+        resources
+    }
+
+    /// Variante de pipeline de un tipo de material
+    fn variant(&self, kind: MaterialKind, double_sided: bool) -> ShaderVariantKey {
+        ShaderVariantKey { kind, double_sided, format: self.format, sample_count: self.sample_count }
+    }
+
+    /// Compilar todas las variantes que aún no tienen pipeline
+    pub fn precompile(&mut self, device: &wgpu::Device) {
+        for key in ShaderVariantKey::material_variants(self.format, self.sample_count) {
+            self.ensure_pipeline(device, key.kind, key.double_sided);
+        }
+    }
+
+    /// Compilar el pipeline de una variante si aún no existe
+    fn ensure_pipeline(&mut self, device: &wgpu::Device, kind: MaterialKind, double_sided: bool) {
+        if self.pipelines.contains_key(&(kind, double_sided)) {
+            return;
+        }
+        let source = format!("{}{}", SHADER_COMMON, self.sources[&kind]);
+        let key = self.variant(kind, double_sided);
+        let pipeline = self.compile_variant(device, key, &source);
+        self.pipelines.insert((kind, double_sided), pipeline);
+    }
+
+    /// Compilar una variante con el código completo de su shader
+    fn compile_variant(&mut self, device: &wgpu::Device, key: ShaderVariantKey, source: &str) -> wgpu::RenderPipeline {
+        let module = self.shader_cache.shader_module(device, &key, material_shader_name(key.kind), source);
+        Self::create_pipeline(
+            device,
+            &module,
+            &self.pipeline_layouts[&key.kind],
+            key.kind,
+            key.double_sided,
+            key.format,
+            key.sample_count,
+        )
+    }
+
+    /// Cambiar las muestras MSAA y recompilar los pipelines ya compilados
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        if sample_count == self.sample_count {
+            return;
+        }
+        self.sample_count = sample_count;
+        let compiled: Vec<(MaterialKind, bool)> = self.pipelines.drain().map(|(key, _)| key).collect();
+        for (kind, double_sided) in compiled {
+            self.ensure_pipeline(device, kind, double_sided);
+        }
+    }
+
+    /// Caché de shaders de los materiales
+    pub fn shader_cache(&self) -> &ShaderCache {
+        &self.shader_cache
+    }
+
+    /// Sustituir el shader de un tipo de material y reconstruir sus dos
+    /// pipelines. Si el código no compila se conservan los anteriores
+    pub fn reload_shader(
+        &mut self,
+        device: &wgpu::Device,
+        kind: MaterialKind,
+        source: &str,
+    ) -> std::result::Result<(), ShaderError> {
+        let shader = material_shader_name(kind);
+        validate_wgsl(shader, SHADER_COMMON, source, &material_entry_points(kind))?;
+        if !self.pipeline_layouts.contains_key(&kind) {
+            return Err(ShaderError::new(shader, "tipo de material sin layout"));
+        }
+
+        // naga no comprueba la interfaz con los layouts: el error de wgpu se
+        // recoge en un scope en lugar de llegar al manejador global
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let full = format!("{}{}", SHADER_COMMON, source);
+        let pipelines = [false, true].map(|double_sided| {
+            let key = self.variant(kind, double_sided);
+            self.compile_variant(device, key, &full)
+        });
+        // En wasm el scope se resuelve en el bucle de eventos del navegador:
+        // allí basta la validación de naga
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(error) = futures::executor::block_on(device.pop_error_scope()) {
+            return Err(ShaderError::new(shader, error.to_string()));
+        }
+        #[cfg(target_arch = "wasm32")]
+        drop(device.pop_error_scope());
+
+        let [single_sided, double_sided] = pipelines;
+        self.pipelines.insert((kind, false), single_sided);
+        self.pipelines.insert((kind, true), double_sided);
+        self.sources.insert(kind, source.to_string());
+        Ok(())
+    }
+
+    /// Layout del grupo 2: el PBR y el terreno usan los cinco slots, el unlit
+    /// solo el albedo
+    fn create_layout(device: &wgpu::Device, kind: MaterialKind) -> wgpu::BindGroupLayout {
+        let texture_count = match kind {
+            MaterialKind::Pbr | MaterialKind::Terrain => 5,
+            MaterialKind::Unlit => 1,
+        };
+        let mut entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<MaterialUniforms>() as u64),
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ];
+        entries.extend((0..texture_count).map(|i| wgpu::BindGroupLayoutEntry {
+            binding: 2 + i,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }));
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(match kind {
+                MaterialKind::Pbr => "pbr-material-layout",
+                MaterialKind::Unlit => "unlit-material-layout",
+                MaterialKind::Terrain => "terrain-material-layout",
+            }),
+            entries: &entries,
+        })
+    }
+
+    /// Crear pipeline de un tipo de material
+    fn create_pipeline(
+        device: &wgpu::Device,
+        module: &wgpu::ShaderModule,
+        layout: &wgpu::PipelineLayout,
+        kind: MaterialKind,
+        double_sided: bool,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("material-pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: "vs_material",
+                buffers: &[GpuVertex::layout(), GpuInstance::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: match kind {
+                    MaterialKind::Pbr => "fs_pbr",
+                    MaterialKind::Unlit => "fs_unlit",
+                    MaterialKind::Terrain => "fs_terrain",
+                },
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: if double_sided { None } else { Some(wgpu::Face::Back) },
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// Crear una textura RGBA8 con sus datos y los niveles de mip que la
+    /// siguen. Cada nivel se copia con `write_texture` en la cola, sin
+    /// esperar a la GPU
+    fn create_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &str,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        mips: &[Vec<u8>],
+        srgb: bool,
+    ) -> GpuTexture {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1 + mips.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: if srgb {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            } else {
+                wgpu::TextureFormat::Rgba8Unorm
+            },
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (level, data) in std::iter::once(pixels).chain(mips.iter().map(Vec::as_slice)).enumerate() {
+            let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * level_width),
+                    rows_per_image: Some(level_height),
+                },
+                wgpu::Extent3d { width: level_width, height: level_height, depth_or_array_layers: 1 },
+            );
+        }
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        GpuTexture { _texture: texture, view }
+    }
+
+    /// Subir una textura. Los materiales que ya la referenciaban (o que
+    /// usaban la textura neutra a la espera de ella) se vuelven a enlazar
+    pub fn upload_texture(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, image: &TextureImage) -> Result<()> {
+        if !image.has_valid_size() {
+            return Err(anyhow!("Tamaño de textura inválido: {}", image.id));
+        }
+        let limit = device.limits().max_texture_dimension_2d;
+        if image.width > limit || image.height > limit {
+            return Err(anyhow!("Textura {} supera el límite de {}px", image.id, limit));
+        }
+
+        let texture = Self::create_texture(device, queue, &image.id, image.width, image.height, &image.pixels, &image.mips, image.srgb);
+        self.textures.insert(image.id.clone(), texture);
+
+        let affected: Vec<String> = self.materials.iter()
+            .filter(|(_, material)| material.textures.uses(&image.id))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in affected {
+            let material = &self.materials[&id];
+            let bind_group = self.create_bind_group(device, material.kind, &material.uniform_buffer, &material.textures);
+            if let Some(material) = self.materials.get_mut(&id) {
+                material.bind_group = bind_group;
+            }
+        }
+        Ok(())
+    }
+
+    /// Verificar si una textura está en la GPU
+    pub fn has_texture(&self, texture_id: &str) -> bool {
+        self.textures.contains_key(texture_id)
+    }
+
+    /// Liberar una textura
+    pub fn remove_texture(&mut self, texture_id: &str) {
+        self.textures.remove(texture_id);
+    }
+
+    /// Subir un material. Un cambio de parámetros solo reescribe el buffer de
+    /// uniforms; un cambio de texturas o de tipo rehace el bind group
+    pub fn upload_material(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, desc: &MaterialDesc) -> Result<()> {
+        self.ensure_pipeline(device, desc.kind, desc.params.double_sided);
+        let uniforms = MaterialUniforms::from(&desc.params);
+
+        if let Some(material) = self.materials.get_mut(&desc.id) {
+            queue.write_buffer(&material.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+            material.double_sided = desc.params.double_sided;
+            if material.kind == desc.kind && material.textures == desc.textures {
+                return Ok(());
+            }
+        } else {
+            let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{}-material", desc.id)),
+                contents: bytemuck::bytes_of(&uniforms),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+            let bind_group = self.create_bind_group(device, desc.kind, &uniform_buffer, &desc.textures);
+            self.materials.insert(desc.id.clone(), GpuMaterial {
+                kind: desc.kind,
+                double_sided: desc.params.double_sided,
+                textures: desc.textures.clone(),
+                uniform_buffer,
+                bind_group,
+            });
+            return Ok(());
+        }
+
+        let material = &self.materials[&desc.id];
+        let bind_group = self.create_bind_group(device, desc.kind, &material.uniform_buffer, &desc.textures);
+        if let Some(material) = self.materials.get_mut(&desc.id) {
+            material.kind = desc.kind;
+            material.textures = desc.textures.clone();
+            material.bind_group = bind_group;
+        }
+        Ok(())
+    }
+
+    /// Verificar si un material está en la GPU
+    pub fn has_material(&self, material_id: &str) -> bool {
+        self.materials.contains_key(material_id)
+    }
+
+    /// Liberar un material
+    pub fn remove_material(&mut self, material_id: &str) {
+        self.materials.remove(material_id);
+    }
+
+    /// Pipeline y bind group con los que dibujar un material
+    pub fn binding(&self, material_id: &str) -> Option<(&wgpu::RenderPipeline, &wgpu::BindGroup)> {
+        let material = self.materials.get(material_id)?;
+        let pipeline = self.pipelines.get(&(material.kind, material.double_sided))?;
+        Some((pipeline, &material.bind_group))
+    }
+
+    /// Crear el bind group de un material con sus texturas actuales
+    fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        kind: MaterialKind,
+        uniform_buffer: &wgpu::Buffer,
+        textures: &MaterialTextureSlots,
+    ) -> wgpu::BindGroup {
+        let view = |slot: &Option<String>, fallback: &'_ GpuTexture| {
+            slot.as_deref()
+                .and_then(|id| self.textures.get(id))
+                .map_or(&fallback.view, |texture| &texture.view)
+        };
+        let views = match kind {
+            MaterialKind::Pbr => vec![
+                view(&textures.albedo, &self.white),
+                view(&textures.metallic_roughness, &self.white),
+                view(&textures.normal, &self.flat_normal),
+                view(&textures.emissive, &self.white),
+                view(&textures.occlusion, &self.white),
+            ],
+            MaterialKind::Unlit => vec![view(&textures.albedo, &self.white)],
+            MaterialKind::Terrain => std::iter::once(&textures.splat)
+                .chain(textures.layers.iter())
+                .map(|slot| view(slot, &self.white))
+                .collect(),
+        };
+
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+        ];
+        entries.extend(views.into_iter().enumerate().map(|(i, view)| wgpu::BindGroupEntry {
+            binding: 2 + i as u32,
+            resource: wgpu::BindingResource::TextureView(view),
+        }));
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("material-bind-group"),
+            layout: &self.layouts[&kind],
+            entries: &entries,
+        })
+    }
+}
@@ -0,0 +1,482 @@
+//! # Post-procesado wgpu
+//!
+//! Ejecuta la cadena de `PostProcessSettings` sobre el render HDR del pase
+//! principal con triángulos a pantalla completa: bloom (umbral, cadena de
+//! mips Kawase y subida con filtro tienda, ver `renderer::bloom`), tonemapping, FXAA y TAA con
+//! reproyección por profundidad, recorte a la vecindad y rechazo por
+//! velocidad. El resultado se copia al destino del frame. Los pases los
+//! declara `frame::build_frame_graph`; aquí se graban con las texturas que
+//! el render graph asigna a cada uno.
+
+use bytemuck::{Pod, Zeroable};
+
+use super::frame::PostPass;
+use super::wgpu_backend::HDR_FORMAT;
+use crate::renderer::bloom::BLOOM_SHADER;
+use crate::renderer::postprocess::{PostProcessFrame, TonemapOperator};
+
+/// Shader de los pases de post-procesado
+const POST_SHADER: &str = r#"
+struct PostUniforms {
+    // xy = 1 / tamaño, zw = tamaño en píxeles
+    texel: vec4<f32>,
+    // x = exposición lineal, y = operador (0 ACES, 1 Reinhard)
+    params: vec4<f32>,
+    // x = peso de la historia, y = 1 con profundidad válida, z = píxeles de movimiento que anulan la historia
+    taa: vec4<f32>,
+    // x = umbral, y = intensidad, z = scatter, w = rodilla del umbral
+    bloom: vec4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    previous_view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> post: PostUniforms;
+@group(0) @binding(1) var source: texture_2d<f32>;
+@group(0) @binding(2) var linear_sampler: sampler;
+@group(0) @binding(3) var aux: texture_2d<f32>;
+@group(0) @binding(4) var depth: texture_depth_2d;
+
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+@fragment
+fn fs_copy(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, linear_sampler, input.uv);
+}
+
+fn tonemap_aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_tonemap(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source, linear_sampler, input.uv);
+    let exposed = color.rgb * post.params.x;
+    var mapped = tonemap_aces(exposed);
+    if (post.params.y > 0.5) {
+        mapped = exposed / (vec3<f32>(1.0) + exposed);
+    }
+    return vec4<f32>(mapped, color.a);
+}
+
+@fragment
+fn fs_fxaa(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = post.texel.xy;
+    let center = textureSample(source, linear_sampler, input.uv);
+    let luma_nw = luminance(textureSample(source, linear_sampler, input.uv + vec2<f32>(-1.0, -1.0) * texel).rgb);
+    let luma_ne = luminance(textureSample(source, linear_sampler, input.uv + vec2<f32>(1.0, -1.0) * texel).rgb);
+    let luma_sw = luminance(textureSample(source, linear_sampler, input.uv + vec2<f32>(-1.0, 1.0) * texel).rgb);
+    let luma_se = luminance(textureSample(source, linear_sampler, input.uv + vec2<f32>(1.0, 1.0) * texel).rgb);
+    let luma_m = luminance(center.rgb);
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // Dirección perpendicular al borde
+    var dir = vec2<f32>(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+    let reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * (1.0 / 8.0), 1.0 / 128.0);
+    let scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, vec2<f32>(-8.0), vec2<f32>(8.0)) * texel;
+
+    let a = 0.5 * (
+        textureSample(source, linear_sampler, input.uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        textureSample(source, linear_sampler, input.uv + dir * (2.0 / 3.0 - 0.5)).rgb
+    );
+    let b = a * 0.5 + 0.25 * (
+        textureSample(source, linear_sampler, input.uv - dir * 0.5).rgb +
+        textureSample(source, linear_sampler, input.uv + dir * 0.5).rgb
+    );
+    let luma_b = luminance(b);
+    if (luma_b < luma_min || luma_b > luma_max) {
+        return vec4<f32>(a, center.a);
+    }
+    return vec4<f32>(b, center.a);
+}
+
+@fragment
+fn fs_taa(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = post.texel.xy;
+    let current = textureSample(source, linear_sampler, input.uv);
+
+    // Caja de color de la vecindad 3x3 para recortar la historia
+    var box_min = current.rgb;
+    var box_max = current.rgb;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let neighbor = textureSample(source, linear_sampler, input.uv + vec2<f32>(f32(x), f32(y)) * texel).rgb;
+            box_min = min(box_min, neighbor);
+            box_max = max(box_max, neighbor);
+        }
+    }
+
+    // Reproyección del píxel con la profundidad y la cámara del frame anterior
+    var previous_uv = input.uv;
+    if (post.taa.y > 0.5) {
+        let size = vec2<i32>(textureDimensions(depth));
+        let pixel = clamp(vec2<i32>(input.uv * vec2<f32>(size)), vec2<i32>(0), size - vec2<i32>(1));
+        let ndc = vec4<f32>(input.uv.x * 2.0 - 1.0, 1.0 - input.uv.y * 2.0, textureLoad(depth, pixel, 0), 1.0);
+        let world = post.inverse_view_projection * ndc;
+        let previous_clip = post.previous_view_projection * vec4<f32>(world.xyz / world.w, 1.0);
+        let previous_ndc = previous_clip.xy / previous_clip.w;
+        previous_uv = vec2<f32>(previous_ndc.x * 0.5 + 0.5, 0.5 - previous_ndc.y * 0.5);
+    }
+    let history = clamp(textureSample(aux, linear_sampler, previous_uv).rgb, box_min, box_max);
+
+    // Rechazo: sin historia fuera de pantalla y menos peso cuanto más rápido se mueve el píxel
+    let velocity = length((input.uv - previous_uv) * post.texel.zw);
+    var weight = post.taa.x * clamp(1.0 - velocity / max(post.taa.z, 1e-4), 0.0, 1.0);
+    if (any(previous_uv < vec2<f32>(0.0)) || any(previous_uv > vec2<f32>(1.0))) {
+        weight = 0.0;
+    }
+    return vec4<f32>(mix(current.rgb, history, weight), current.a);
+}
+"#;
+
+/// Píxeles de movimiento por frame a partir de los que se descarta la historia
+const TAA_MAX_VELOCITY: f32 = 32.0;
+
+/// Uniforms comunes a los pases
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct PostUniforms {
+    texel: [f32; 4],
+    params: [f32; 4],
+    taa: [f32; 4],
+    bloom: [f32; 4],
+    inverse_view_projection: [[f32; 4]; 4],
+    previous_view_projection: [[f32; 4]; 4],
+}
+
+/// Pipelines de la cadena (destino HDR salvo `output`)
+struct PostPipelines {
+    bloom_prefilter: wgpu::RenderPipeline,
+    bloom_downsample: wgpu::RenderPipeline,
+    bloom_upsample: wgpu::RenderPipeline,
+    bloom_composite: wgpu::RenderPipeline,
+    tonemap: wgpu::RenderPipeline,
+    fxaa: wgpu::RenderPipeline,
+    taa: wgpu::RenderPipeline,
+    /// Copia final al formato del destino del frame
+    output: wgpu::RenderPipeline,
+}
+
+/// Texturas que persisten entre frames, dependientes del tamaño del destino
+struct PostTargets {
+    /// Historia del TAA (una se lee y la otra se escribe)
+    history: [wgpu::TextureView; 2],
+}
+
+/// Recursos del post-procesado
+pub(super) struct PostProcessor {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniforms: wgpu::Buffer,
+    pipelines: PostPipelines,
+    targets: PostTargets,
+    /// Profundidad de sustitución cuando el depth buffer no se puede muestrear
+    dummy_depth: wgpu::TextureView,
+    /// Índice de la historia que se escribe este frame
+    history_index: usize,
+    /// La historia contiene un frame válido
+    history_valid: bool,
+}
+
+impl PostProcessor {
+    /// Crear recursos para un destino de `output_format`
+    pub(super) fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat, width: u32, height: u32) -> Self {
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("post-layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<PostUniforms>() as u64),
+                    },
+                    count: None,
+                },
+                texture_entry(1, wgpu::TextureSampleType::Float { filterable: true }),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture_entry(3, wgpu::TextureSampleType::Float { filterable: true }),
+                texture_entry(4, wgpu::TextureSampleType::Depth),
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post-sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post-uniforms"),
+            size: std::mem::size_of::<PostUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("post-shader"),
+            source: wgpu::ShaderSource::Wgsl(format!("{}{}", POST_SHADER, BLOOM_SHADER).into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("post-pipeline-layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point: &str, format: wgpu::TextureFormat| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_fullscreen",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let pipelines = PostPipelines {
+            bloom_prefilter: pipeline("fs_bloom_prefilter", HDR_FORMAT),
+            bloom_downsample: pipeline("fs_bloom_downsample", HDR_FORMAT),
+            bloom_upsample: pipeline("fs_bloom_upsample", HDR_FORMAT),
+            bloom_composite: pipeline("fs_bloom_composite", HDR_FORMAT),
+            tonemap: pipeline("fs_tonemap", HDR_FORMAT),
+            fxaa: pipeline("fs_fxaa", HDR_FORMAT),
+            taa: pipeline("fs_taa", HDR_FORMAT),
+            output: pipeline("fs_copy", output_format),
+        };
+
+        let dummy_depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("post-dummy-depth"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: super::wgpu_backend::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            targets: Self::create_targets(device, width, height),
+            layout,
+            sampler,
+            uniforms,
+            pipelines,
+            dummy_depth,
+            history_index: 0,
+            history_valid: false,
+        }
+    }
+
+    /// Crear las texturas persistentes
+    fn create_targets(device: &wgpu::Device, width: u32, height: u32) -> PostTargets {
+        let (width, height) = (width.max(1), height.max(1));
+        let target = |label: &str, width: u32, height: u32| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: HDR_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            }).create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        PostTargets {
+            history: [target("post-history-a", width, height), target("post-history-b", width, height)],
+        }
+    }
+
+    /// Redimensionar las texturas persistentes (descarta la historia del TAA)
+    pub(super) fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = Self::create_targets(device, width, height);
+        self.history_valid = false;
+    }
+
+    /// Historia del TAA que se escribe este frame
+    pub(super) fn history_index(&self) -> usize {
+        self.history_index
+    }
+
+    /// Vista de una de las historias del TAA
+    pub(super) fn history_view(&self, index: usize) -> &wgpu::TextureView {
+        &self.targets.history[index % 2]
+    }
+
+    /// Bind group de un pase
+    fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+        aux: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("post-bind-group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: self.uniforms.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(source) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(aux) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(depth) },
+            ],
+        })
+    }
+
+    /// Escribir los uniforms del frame. `depth_readable` indica si el TAA
+    /// recibe el depth buffer del pase principal
+    pub(super) fn prepare(
+        &self,
+        queue: &wgpu::Queue,
+        frame: Option<&PostProcessFrame>,
+        depth_readable: bool,
+        size: (u32, u32),
+    ) {
+        let (width, height) = (size.0.max(1) as f32, size.1.max(1) as f32);
+        let taa_weight = match frame {
+            Some(frame) if self.history_valid && !frame.reset_history => frame.settings.taa_blend,
+            _ => 0.0,
+        };
+
+        let mut uniforms = PostUniforms::zeroed();
+        uniforms.texel = [1.0 / width, 1.0 / height, width, height];
+        uniforms.inverse_view_projection = glam::Mat4::IDENTITY.to_cols_array_2d();
+        uniforms.previous_view_projection = glam::Mat4::IDENTITY.to_cols_array_2d();
+        if let Some(frame) = frame {
+            let settings = &frame.settings;
+            let operator = match settings.tonemap {
+                TonemapOperator::Aces => 0.0,
+                TonemapOperator::Reinhard => 1.0,
+            };
+            let bloom = &settings.bloom;
+            uniforms.params = [settings.exposure_scale(), operator, 0.0, 0.0];
+            uniforms.bloom = [bloom.threshold, bloom.intensity, bloom.scatter, bloom.knee()];
+            uniforms.taa = [taa_weight, if depth_readable { 1.0 } else { 0.0 }, TAA_MAX_VELOCITY, 0.0];
+            uniforms.inverse_view_projection = frame.view_projection.inverse().to_cols_array_2d();
+            uniforms.previous_view_projection = frame.previous_view_projection.to_cols_array_2d();
+        }
+        queue.write_buffer(&self.uniforms, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Grabar un pase de la cadena. `inputs` son las texturas que el pase
+    /// lee en el orden en que las declara el grafo del frame
+    pub(super) fn execute(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pass: PostPass,
+        inputs: &[&wgpu::TextureView],
+        target: &wgpu::TextureView,
+    ) {
+        let Some(&source) = inputs.first() else {
+            return;
+        };
+        let aux = inputs.get(1).copied().unwrap_or(source);
+        let (pipeline, group) = match pass {
+            PostPass::BloomPrefilter => (&self.pipelines.bloom_prefilter, self.bind_group(device, source, source, &self.dummy_depth)),
+            PostPass::BloomDownsample => (&self.pipelines.bloom_downsample, self.bind_group(device, source, source, &self.dummy_depth)),
+            // Lee el mip del nivel y el resultado que sube del inferior
+            PostPass::BloomUpsample => (&self.pipelines.bloom_upsample, self.bind_group(device, source, aux, &self.dummy_depth)),
+            PostPass::BloomComposite => (&self.pipelines.bloom_composite, self.bind_group(device, source, aux, &self.dummy_depth)),
+            PostPass::Tonemap => (&self.pipelines.tonemap, self.bind_group(device, source, source, &self.dummy_depth)),
+            PostPass::Fxaa => (&self.pipelines.fxaa, self.bind_group(device, source, source, &self.dummy_depth)),
+            PostPass::Taa => {
+                // Lee la historia anterior (y la profundidad si se puede muestrear)
+                let depth = inputs.get(2).copied().unwrap_or(&self.dummy_depth);
+                (&self.pipelines.taa, self.bind_group(device, source, aux, depth))
+            }
+            PostPass::Output => (&self.pipelines.output, self.bind_group(device, source, source, &self.dummy_depth)),
+        };
+        Self::draw(encoder, pipeline, &group, target);
+    }
+
+    /// Cerrar el frame: la historia escrita pasa a ser la anterior
+    pub(super) fn finish(&mut self, taa_executed: bool) {
+        if taa_executed {
+            self.history_index = 1 - self.history_index;
+        }
+        self.history_valid = taa_executed;
+    }
+
+    /// Grabar un pase a pantalla completa
+    fn draw(
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+        target: &wgpu::TextureView,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("post-pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
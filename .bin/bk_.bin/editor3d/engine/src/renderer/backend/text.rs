@@ -0,0 +1,427 @@
+//! # Texto en la GPU
+//!
+//! Quads instanciados en píxeles del destino: seis vértices por quad
+//! generados en el vertex shader y una instancia por glifo o rectángulo de
+//! UI. Cada fuente tiene su atlas SDF en una textura R8; el fragment shader
+//! convierte la distancia muestreada a píxeles del destino para suavizar el
+//! borde un píxel y pinta el contorno con la misma distancia desplazada. Los
+//! rectángulos de UI muestrean una textura blanca de 1x1 y no usan el campo.
+
+use anyhow::{anyhow, Result};
+use bytemuck::{Pod, Zeroable};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::renderer::text::{FontAtlas, TextFrame};
+
+/// Shader de los quads de texto
+const TEXT_SHADER: &str = r#"
+struct TextUniforms {
+    // xy = tamaño del destino en píxeles
+    viewport: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> frame: TextUniforms;
+@group(1) @binding(0) var glyph_atlas: texture_2d<f32>;
+@group(1) @binding(1) var glyph_sampler: sampler;
+
+struct QuadInput {
+    // xy = esquina superior izquierda, zw = inferior derecha
+    @location(0) rect: vec4<f32>,
+    @location(1) uv: vec4<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) outline_color: vec4<f32>,
+    // x = alcance del campo en píxeles (0 sin campo), y = grosor del contorno
+    @location(4) params: vec4<f32>,
+};
+
+struct QuadOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) outline_color: vec4<f32>,
+    @location(3) params: vec2<f32>,
+};
+
+@vertex
+fn vs_text(@builtin(vertex_index) vertex: u32, quad: QuadInput) -> QuadOutput {
+    var out: QuadOutput;
+    // Dos triángulos: (0,0) (1,0) (1,1) y (0,0) (1,1) (0,1)
+    let corner = vec2<f32>(
+        select(0.0, 1.0, vertex == 1u || vertex == 2u || vertex == 4u),
+        select(0.0, 1.0, vertex == 2u || vertex == 4u || vertex == 5u),
+    );
+    let pixel = mix(quad.rect.xy, quad.rect.zw, corner);
+    let ndc = vec2<f32>(pixel.x / frame.viewport.x * 2.0 - 1.0, 1.0 - pixel.y / frame.viewport.y * 2.0);
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.uv = mix(quad.uv.xy, quad.uv.zw, corner);
+    out.color = quad.color;
+    out.outline_color = quad.outline_color;
+    out.params = quad.params.xy;
+    return out;
+}
+
+@fragment
+fn fs_text(input: QuadOutput) -> @location(0) vec4<f32> {
+    let sample = textureSample(glyph_atlas, glyph_sampler, input.uv).r;
+    var fill = 1.0;
+    var edge = 0.0;
+    if (input.params.x > 0.0) {
+        let distance = (sample - 0.5) * 2.0 * input.params.x;
+        fill = clamp(distance + 0.5, 0.0, 1.0);
+        edge = select(0.0, clamp(distance + input.params.y + 0.5, 0.0, 1.0), input.params.y > 0.0);
+    }
+    // Relleno sobre contorno, en alfa premultiplicado
+    let fill_alpha = input.color.a * fill;
+    let edge_alpha = input.outline_color.a * edge * (1.0 - fill_alpha);
+    return vec4<f32>(input.color.rgb * fill_alpha + input.outline_color.rgb * edge_alpha, fill_alpha + edge_alpha);
+}
+"#;
+
+/// Uniforms del pase
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct TextUniforms {
+    viewport: [f32; 4],
+}
+
+/// Quad en formato GPU
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct GpuTextQuad {
+    rect: [f32; 4],
+    uv: [f32; 4],
+    color: [f32; 4],
+    outline_color: [f32; 4],
+    params: [f32; 4],
+}
+
+impl GpuTextQuad {
+    /// Atributos de la instancia
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x4,
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32x4,
+    ];
+
+    /// Layout del buffer de quads
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GpuTextQuad>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Atlas de una fuente en la GPU
+struct GpuFont {
+    /// Textura R8 del campo (se conserva mientras la usa el bind group)
+    _texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Recursos de texto del backend wgpu
+pub struct TextResources {
+    /// Layout del atlas (grupo 1)
+    atlas_layout: wgpu::BindGroupLayout,
+    /// Pipeline sobre el destino del frame
+    pipeline: wgpu::RenderPipeline,
+    /// Sampler bilineal de los atlas
+    sampler: wgpu::Sampler,
+    /// Uniforms del pase
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    /// Atlas blanco de los rectángulos de UI
+    solid: GpuFont,
+    /// Atlas por fuente
+    fonts: HashMap<String, GpuFont>,
+    /// Quads del frame
+    instance_buffer: wgpu::Buffer,
+    /// Capacidad en quads
+    instance_capacity: usize,
+    /// Fuente (None para UI) y rango de quads de cada lote del frame
+    batches: Vec<(Option<String>, Range<u32>)>,
+}
+
+impl TextResources {
+    /// Crear pipeline y buffers para dibujar sobre destinos de `format`
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat) -> Self {
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("text-uniforms"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let atlas_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("text-atlas"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("text-shader"),
+            source: wgpu::ShaderSource::Wgsl(TEXT_SHADER.into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("text-pipeline-layout"),
+            bind_group_layouts: &[&uniform_layout, &atlas_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("text-pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_text",
+                buffers: &[GpuTextQuad::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_text",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("text-sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("text-uniforms"),
+            size: std::mem::size_of::<TextUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("text-uniforms"),
+            layout: &uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let solid = Self::create_font(device, queue, &atlas_layout, &sampler, "text-solid", 1, 1, &[255]);
+
+        Self {
+            atlas_layout,
+            pipeline,
+            sampler,
+            uniform_buffer,
+            uniform_bind_group,
+            solid,
+            fonts: HashMap::new(),
+            instance_buffer: Self::create_instances(device, 256),
+            instance_capacity: 256,
+            batches: Vec::new(),
+        }
+    }
+
+    /// Textura R8 con los píxeles de un atlas y su bind group
+    #[allow(clippy::too_many_arguments)]
+    fn create_font(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        label: &str,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> GpuFont {
+        let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+        GpuFont { _texture: texture, bind_group }
+    }
+
+    /// Buffer de `capacity` quads
+    fn create_instances(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("text-quads"),
+            size: (std::mem::size_of::<GpuTextQuad>() * capacity) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Subir el atlas de una fuente (o reemplazarlo)
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, atlas: &FontAtlas) -> Result<()> {
+        if atlas.width == 0 || atlas.height == 0 || atlas.pixels.len() != (atlas.width * atlas.height) as usize {
+            return Err(anyhow!("Tamaño de atlas inválido: {}", atlas.id));
+        }
+        let font = Self::create_font(
+            device,
+            queue,
+            &self.atlas_layout,
+            &self.sampler,
+            &format!("font-{}", atlas.id),
+            atlas.width,
+            atlas.height,
+            &atlas.pixels,
+        );
+        self.fonts.insert(atlas.id.clone(), font);
+        Ok(())
+    }
+
+    /// Verificar si una fuente está subida
+    pub fn has(&self, font_id: &str) -> bool {
+        self.fonts.contains_key(font_id)
+    }
+
+    /// Liberar una fuente
+    pub fn remove(&mut self, font_id: &str) {
+        self.fonts.remove(font_id);
+    }
+
+    /// Escribir los quads del frame para un destino de `viewport` píxeles.
+    /// Los lotes de fuentes sin subir se omiten. Devuelve los quads que se
+    /// dibujarán
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, frame: Option<&TextFrame>, viewport: (u32, u32)) -> u32 {
+        self.batches.clear();
+        let Some(frame) = frame else {
+            return 0;
+        };
+
+        let mut quads = Vec::with_capacity(frame.quad_count());
+        for batch in &frame.batches {
+            if batch.font_id.as_ref().is_some_and(|id| !self.fonts.contains_key(id)) {
+                continue;
+            }
+            let start = quads.len() as u32;
+            quads.extend(batch.quads.iter().map(|quad| GpuTextQuad {
+                rect: [quad.min.x, quad.min.y, quad.max.x, quad.max.y],
+                uv: [quad.uv_min.x, quad.uv_min.y, quad.uv_max.x, quad.uv_max.y],
+                color: quad.color.to_array(),
+                outline_color: quad.outline_color.to_array(),
+                params: [quad.spread, quad.outline, 0.0, 0.0],
+            }));
+            self.batches.push((batch.font_id.clone(), start..quads.len() as u32));
+        }
+        if quads.is_empty() {
+            return 0;
+        }
+
+        let uniforms = TextUniforms {
+            viewport: [viewport.0.max(1) as f32, viewport.1.max(1) as f32, 0.0, 0.0],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+        if quads.len() > self.instance_capacity {
+            self.instance_capacity = quads.len().next_power_of_two();
+            self.instance_buffer = Self::create_instances(device, self.instance_capacity);
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&quads));
+        quads.len() as u32
+    }
+
+    /// Grabar el pase de texto sobre `target`. Devuelve las draw calls
+    pub fn record(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) -> u32 {
+        if self.batches.is_empty() {
+            return 0;
+        }
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("text-pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        for (font_id, quads) in &self.batches {
+            let font = font_id.as_ref().and_then(|id| self.fonts.get(id)).unwrap_or(&self.solid);
+            pass.set_bind_group(1, &font.bind_group, &[]);
+            pass.draw(0..6, quads.clone());
+        }
+        self.batches.len() as u32
+    }
+}
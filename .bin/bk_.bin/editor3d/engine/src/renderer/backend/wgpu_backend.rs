@@ -0,0 +1,2752 @@
+//! # Backend wgpu
+//!
+//! Backend WebGPU/Vulkan sobre `wgpu`. Crea la instancia, el adaptador
+//! (prefiriendo la GPU discreta), el dispositivo y la swap chain de la
+//! superficie. Sin superficie renderiza a una textura offscreen que se puede
+//! leer con `read_pixels`. Con una luz direccional y cascadas en la lista de
+//! dibujo, renderiza antes los shadow maps y los muestrea con PCF. Las draw
+//! calls con un material subido usan el pipeline PBR de `pbr`. Los grupos
+//! instanciados se dibujan con una sola draw call leyendo las transformaciones
+//! de un buffer de instancias. El pase principal dibuja sobre un destino HDR
+//! que la cadena de `post` procesa y copia al destino del frame. El orden de
+//! los pases y las texturas intermedias salen del grafo de `frame`. Con VRS
+//! en la lista de dibujo, `vrs::VRSPass` genera la imagen de tasas tras el
+//! pase principal. Las mallas con skin se deforman antes en el compute pass
+//! de `skinning::SkinningPass` y se dibujan con sus vértices deformados; los
+//! morph targets se suman antes aún con `morphing::MorphPass`.
+//! Con `TIMESTAMP_QUERY` se mide el tiempo de GPU de los pases del frame y,
+//! con una escala de resolución, los pases hasta la salida trabajan a la
+//! resolución interna. Con un entorno HDR en la lista de dibujo, los
+//! materiales PBR se iluminan con sus mapas de `environment` y el pase
+//! principal empieza dibujando el skybox. Los decals de la lista se aplican
+//! en el mismo pase: cada draw call recibe la máscara de los decals que la
+//! alcanzan y los shaders pintan los fragmentos dentro de sus cajas. Las
+//! partículas se dibujan como billboards tras la geometría opaca con los
+//! pipelines de `particles`. Las líneas de `debug_draw` y después el texto
+//! y la UI de `text` se dibujan los últimos, sobre el destino del frame. Con occlusion culling en la lista de
+//! dibujo, las draw calls simples se dibujan con argumentos indirectos que
+//! escriben las dos fases de `occlusion::OcclusionPass`. Las luces puntuales
+//! y los focos se reparten en clusters con el compute pass de
+//! `clustering::ClusterResources` antes del pase principal. Las capturas del
+//! frame y de los destinos offscreen se leen de forma asíncrona con
+//! `capture::ImageReadbacks`.
+
+use anyhow::{Result, anyhow};
+use bytemuck::{Pod, Zeroable};
+use futures::channel::oneshot;
+use glam::{Mat4, Vec4};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{info, warn};
+use wgpu::util::DeviceExt;
+
+use super::{DrawList, FrameStats, ImageData, ImageReadback, MaterialDesc, MaterialKind, RenderBackend, TextureImage, SHADOW_CASCADES_ALL};
+use super::capture::ImageReadbacks;
+use super::clustering::ClusterResources;
+use super::debug_draw::DebugDrawResources;
+use super::decals::DecalResources;
+use super::environment::EnvironmentResources;
+use super::frame::{build_frame_graph, FrameGraphOptions, FramePass, FrameResources, PostPass};
+use super::particles::ParticleResources;
+use super::pbr::MaterialResources;
+use super::post::PostProcessor;
+use super::text::TextResources;
+use crate::renderer::Mesh;
+use crate::renderer::culling::Aabb;
+use crate::renderer::decals::receiver_mask;
+use crate::renderer::dynamic_resolution::{scaled_size, GpuFrameTimer};
+use crate::renderer::environment::EnvironmentMaps;
+use crate::renderer::graph::{CompiledGraph, ResourceDesc, ResourceHandle, TextureFormat};
+use crate::renderer::occlusion::{OcclusionItem, OcclusionPass, OcclusionPhase};
+use crate::renderer::shader_cache::ShaderCache;
+use crate::renderer::shader_reload::ShaderError;
+use crate::renderer::shadows::MAX_SHADOW_CASCADES;
+use crate::renderer::morphing::{MorphPass, MorphSource, MorphTargets};
+use crate::renderer::skinning::{self, SkinSource, SkinningPass};
+use crate::renderer::text::FontAtlas;
+use crate::renderer::vrs::VRSPass;
+
+/// Features que el backend solicita al dispositivo
+pub(crate) const REQUESTED_FEATURES: wgpu::Features = wgpu::Features::MULTI_DRAW_INDIRECT
+    .union(wgpu::Features::STORAGE_RESOURCE_BINDING_ARRAY)
+    .union(wgpu::Features::TIMESTAMP_QUERY);
+
+/// Formato del destino offscreen
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Formato del depth buffer
+pub(super) const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Formato del destino HDR del pase principal
+pub(super) const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Declaraciones comunes a los shaders del backend: uniforms por draw call
+/// (grupo 0), uniforms del frame, shadow maps, mapas del entorno, decals y
+/// clusters de luces (grupo 1), atributos por instancia y muestreo de
+/// sombras, del entorno, de los decals y de las luces locales
+pub(super) const SHADER_COMMON: &str = r#"
+struct DrawUniforms {
+    view_projection: mat4x4<f32>,
+    model: mat4x4<f32>,
+    base_color: vec4<f32>,
+    // x/y = bits bajos/altos de la máscara de decals que alcanzan la draw call
+    decals: vec4<u32>,
+};
+
+struct FrameUniforms {
+    camera_view: mat4x4<f32>,
+    camera_position: vec4<f32>,
+    light_view_projection: array<mat4x4<f32>, 4>,
+    cascade_splits: vec4<f32>,
+    // w = 1 si hay luz direccional
+    light_direction: vec4<f32>,
+    light_color: vec4<f32>,
+    ambient: vec4<f32>,
+    // x = bias constante, y = bias de pendiente, z = cascadas, w = tamaño de texel
+    shadow_params: vec4<f32>,
+    // x = 1 con PCF
+    shadow_filter: vec4<f32>,
+    // x = intensidad (0 sin entorno), y/z = seno/coseno del giro, w = mip de rugosidad 1
+    environment: vec4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> draw: DrawUniforms;
+@group(1) @binding(0) var<uniform> frame: FrameUniforms;
+@group(1) @binding(1) var shadow_map: texture_depth_2d_array;
+@group(1) @binding(2) var shadow_sampler: sampler_comparison;
+@group(1) @binding(3) var irradiance_map: texture_cube<f32>;
+@group(1) @binding(4) var specular_map: texture_cube<f32>;
+@group(1) @binding(5) var brdf_lut: texture_2d<f32>;
+@group(1) @binding(6) var environment_sampler: sampler;
+
+struct Decal {
+    world_to_decal: mat4x4<f32>,
+    color: vec4<f32>,
+    // x = capa del atlas (-1 sin textura), y = modo de mezcla
+    params: vec4<f32>,
+};
+
+// Tamaño de `decals::MAX_DECALS`
+struct DecalUniforms {
+    decals: array<Decal, 64>,
+};
+
+@group(1) @binding(7) var<uniform> decal_data: DecalUniforms;
+@group(1) @binding(8) var decal_atlas: texture_2d_array<f32>;
+@group(1) @binding(9) var decal_sampler: sampler;
+
+struct ClusterLight {
+    // xyz = posición en mundo, w = rango
+    position: vec4<f32>,
+    // rgb = color por intensidad, w = 1 para focos
+    color: vec4<f32>,
+    // xyz = dirección del foco, w = coseno del cono exterior
+    direction: vec4<f32>,
+    // x = coseno del cono interior
+    cone: vec4<f32>,
+};
+
+struct ClusterUniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    inverse_projection: mat4x4<f32>,
+    // x/y/z = clusters por eje, w = luces máximas por cluster
+    grid: vec4<u32>,
+    // x = near, y = far, z/w = escala y sesgo del corte logarítmico
+    depth: vec4<f32>,
+    // x = luces locales del frame
+    info: vec4<u32>,
+};
+
+@group(1) @binding(10) var<uniform> clusters: ClusterUniforms;
+@group(1) @binding(11) var<storage, read> cluster_lights: array<ClusterLight>;
+@group(1) @binding(12) var<storage, read> cluster_counts: array<u32>;
+@group(1) @binding(13) var<storage, read> cluster_indices: array<u32>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) color: vec4<f32>,
+};
+
+struct InstanceInput {
+    @location(4) model_0: vec4<f32>,
+    @location(5) model_1: vec4<f32>,
+    @location(6) model_2: vec4<f32>,
+    @location(7) model_3: vec4<f32>,
+    @location(8) color: vec4<f32>,
+};
+
+// Transformación de la draw call compuesta con la de la instancia
+fn instance_model(instance: InstanceInput) -> mat4x4<f32> {
+    return draw.model * mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+}
+
+// Girar el entorno equivale a girar la dirección de consulta en sentido contrario
+fn environment_direction(direction: vec3<f32>) -> vec3<f32> {
+    let s = frame.environment.y;
+    let c = frame.environment.z;
+    return vec3<f32>(c * direction.x - s * direction.z, direction.y, s * direction.x + c * direction.z);
+}
+
+// Luz ambiente: la irradiancia del entorno si hay uno y si no la constante
+fn ambient_light(normal: vec3<f32>) -> vec3<f32> {
+    if (frame.environment.x > 0.0) {
+        let irradiance = textureSampleLevel(irradiance_map, environment_sampler, environment_direction(normal), 0.0).rgb;
+        return irradiance * frame.environment.x;
+    }
+    return frame.ambient.rgb;
+}
+
+// Luces locales del cluster de un punto en mundo: número y primer índice
+fn cluster_light_range(world_position: vec3<f32>) -> vec2<u32> {
+    if (clusters.info.x == 0u) {
+        return vec2<u32>(0u);
+    }
+    let grid = clusters.grid;
+    let view_position = clusters.view * vec4<f32>(world_position, 1.0);
+    let clip = clusters.projection * view_position;
+    let ndc = clip.xy / max(clip.w, 1e-5);
+    let uv = clamp(vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5), vec2<f32>(0.0), vec2<f32>(0.9999));
+    let tile = vec2<u32>(uv * vec2<f32>(grid.xy));
+    let depth = max(-view_position.z, clusters.depth.x);
+    let slice = min(u32(max(log(depth) * clusters.depth.z + clusters.depth.w, 0.0)), grid.z - 1u);
+    let cluster = tile.x + grid.x * (tile.y + grid.y * slice);
+    return vec2<u32>(min(cluster_counts[cluster], grid.w), cluster * grid.w);
+}
+
+// Atenuación de una luz local: inversa del cuadrado con caída suave hasta
+// cero en el rango y, en los focos, transición entre los dos conos
+fn local_light_attenuation(light: ClusterLight, to_light: vec3<f32>) -> f32 {
+    let distance2 = dot(to_light, to_light);
+    let ratio = distance2 / max(light.position.w * light.position.w, 1e-4);
+    let window = clamp(1.0 - ratio * ratio, 0.0, 1.0);
+    var attenuation = window * window / max(distance2, 1e-4);
+    if (light.color.w > 0.0) {
+        let cos_angle = dot(-normalize(to_light), light.direction.xyz);
+        attenuation *= smoothstep(light.direction.w, light.cone.x, cos_angle);
+    }
+    return attenuation;
+}
+
+// Difusa de Lambert de las luces locales que alcanzan un punto
+fn local_diffuse_light(world_position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var diffuse = vec3<f32>(0.0);
+    let range = cluster_light_range(world_position);
+    for (var i = 0u; i < range.x; i++) {
+        let light = cluster_lights[cluster_indices[range.y + i]];
+        let to_light = light.position.xyz - world_position;
+        let n_dot_l = max(dot(normal, normalize(to_light)), 0.0);
+        diffuse += light.color.rgb * n_dot_l * local_light_attenuation(light, to_light);
+    }
+    return diffuse;
+}
+
+struct DecalSurface {
+    albedo: vec3<f32>,
+    emissive: vec3<f32>,
+};
+
+// Aplicar los decals de la draw call al albedo. Los fragmentos fuera de la
+// caja de un decal o que no miran a su proyector (+Z local) no se tocan
+fn apply_decals(albedo: vec3<f32>, world_position: vec3<f32>, normal: vec3<f32>) -> DecalSurface {
+    var surface = DecalSurface(albedo, vec3<f32>(0.0));
+    // Derivadas fuera del bucle: dentro el control de flujo no es uniforme
+    let dx = dpdx(world_position);
+    let dy = dpdy(world_position);
+    for (var word = 0u; word < 2u; word++) {
+        for (var bits = draw.decals[word]; bits != 0u; bits = bits & (bits - 1u)) {
+            let decal = decal_data.decals[word * 32u + firstTrailingBit(bits)];
+            if (decal.params.x < 0.0) {
+                continue;
+            }
+            let local = (decal.world_to_decal * vec4<f32>(world_position, 1.0)).xyz;
+            if (any(abs(local) > vec3<f32>(0.5))) {
+                continue;
+            }
+            let axis = normalize(vec3<f32>(decal.world_to_decal[0].z, decal.world_to_decal[1].z, decal.world_to_decal[2].z));
+            let facing = dot(normal, axis);
+            if (facing <= 0.0) {
+                continue;
+            }
+
+            let uv = vec2<f32>(local.x + 0.5, 0.5 - local.y);
+            let uv_dx = (decal.world_to_decal * vec4<f32>(dx, 0.0)).xy * vec2<f32>(1.0, -1.0);
+            let uv_dy = (decal.world_to_decal * vec4<f32>(dy, 0.0)).xy * vec2<f32>(1.0, -1.0);
+            let color = textureSampleGrad(decal_atlas, decal_sampler, uv, i32(decal.params.x), uv_dx, uv_dy) * decal.color;
+            // Se desvanece en las superficies casi paralelas a la proyección
+            let alpha = color.a * smoothstep(0.0, 0.25, facing);
+            switch (u32(decal.params.y)) {
+                case 1u: {
+                    surface.albedo *= mix(vec3<f32>(1.0), color.rgb, alpha);
+                }
+                case 2u: {
+                    surface.emissive += color.rgb * alpha;
+                }
+                default: {
+                    surface.albedo = mix(surface.albedo, color.rgb, alpha);
+                }
+            }
+        }
+    }
+    return surface;
+}
+
+fn shadow_factor(world_position: vec3<f32>, n_dot_l: f32) -> f32 {
+    let cascade_count = i32(frame.shadow_params.z);
+    let view_depth = -(frame.camera_view * vec4<f32>(world_position, 1.0)).z;
+
+    var cascade = cascade_count;
+    for (var i = 0; i < cascade_count; i++) {
+        if (view_depth <= frame.cascade_splits[i]) {
+            cascade = i;
+            break;
+        }
+    }
+    if (cascade >= cascade_count) {
+        return 1.0;
+    }
+
+    let light_clip = frame.light_view_projection[cascade] * vec4<f32>(world_position, 1.0);
+    let ndc = light_clip.xyz / light_clip.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, -ndc.y * 0.5 + 0.5);
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    // El bias crece con la pendiente de la superficie respecto a la luz
+    let tan_theta = clamp(sqrt(1.0 - n_dot_l * n_dot_l) / max(n_dot_l, 0.1), 0.0, 10.0);
+    let depth = ndc.z - frame.shadow_params.x * (1.0 + frame.shadow_params.y * tan_theta);
+
+    if (frame.shadow_filter.x == 0.0) {
+        return textureSampleCompareLevel(shadow_map, shadow_sampler, uv, cascade, depth);
+    }
+
+    // PCF 3x3
+    let texel = frame.shadow_params.w;
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, cascade, depth);
+        }
+    }
+    return lit / 9.0;
+}
+"#;
+
+/// Shader por defecto (se compila tras `SHADER_COMMON`)
+const DEFAULT_SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) world_position: vec3<f32>,
+};
+
+@vertex
+fn vs_main(input: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+    let model = instance_model(instance);
+    let world = model * vec4<f32>(input.position, 1.0);
+    out.clip_position = draw.view_projection * world;
+    out.normal = (model * vec4<f32>(input.normal, 0.0)).xyz;
+    out.color = input.color * draw.base_color * instance.color;
+    out.world_position = world.xyz;
+    return out;
+}
+
+@vertex
+fn vs_shadow(input: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    return draw.view_projection * instance_model(instance) * vec4<f32>(input.position, 1.0);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(input.normal);
+    let decal = apply_decals(input.color.rgb, input.world_position, normal);
+    let local_light = local_diffuse_light(input.world_position, normal);
+    if (frame.light_direction.w == 0.0) {
+        // Sin ninguna luz se dibuja sin iluminar
+        if (clusters.info.x == 0u) {
+            return vec4<f32>(decal.albedo + decal.emissive, input.color.a);
+        }
+        return vec4<f32>(decal.albedo * (ambient_light(normal) + local_light) + decal.emissive, input.color.a);
+    }
+    let n_dot_l = max(dot(normal, -frame.light_direction.xyz), 0.0);
+    var shadow = 1.0;
+    if (frame.shadow_params.z > 0.0 && n_dot_l > 0.0) {
+        shadow = shadow_factor(input.world_position, n_dot_l);
+    }
+    let light = ambient_light(normal) + frame.light_color.rgb * n_dot_l * shadow + local_light;
+    return vec4<f32>(decal.albedo * light + decal.emissive, input.color.a);
+}
+"#;
+
+/// Vértice en formato GPU
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct GpuVertex {
+    /// Posición
+    pub position: [f32; 3],
+    /// Normal
+    pub normal: [f32; 3],
+    /// UV
+    pub uv: [f32; 2],
+    /// Color
+    pub color: [f32; 4],
+}
+
+impl GpuVertex {
+    /// Atributos del vértice
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32x2,
+        3 => Float32x4,
+    ];
+
+    /// Layout del buffer de vértices
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GpuVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Datos por instancia en formato GPU
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct GpuInstance {
+    /// Columnas de la transformación
+    pub model: [[f32; 4]; 4],
+    /// Color
+    pub color: [f32; 4],
+}
+
+impl GpuInstance {
+    /// Instancia sin transformación adicional, usada por las draw calls simples
+    const IDENTITY: Self = Self {
+        model: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]],
+        color: [1.0; 4],
+    };
+
+    /// Atributos de la instancia
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4,
+    ];
+
+    /// Layout del buffer de instancias
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GpuInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Draw call del backend: una llamada simple o un grupo instanciado
+struct DrawItem<'a> {
+    mesh_id: &'a str,
+    material_id: Option<&'a str>,
+    transform: Mat4,
+    base_color: Vec4,
+    /// Rango en el buffer de instancias
+    instances: Range<u32>,
+    /// Entidad con skin cuyos vértices deformados se dibujan
+    skin: Option<u64>,
+    /// Entidad cuyos pesos de morph deforman el mesh
+    morph: Option<u64>,
+    /// Capas de render
+    layers: u32,
+    /// Cascadas de sombra en las que se dibuja, un bit por cascada
+    shadow_cascades: u32,
+}
+
+/// Fase del occlusion culling que graba un pase principal
+struct ForwardOcclusion<'a> {
+    pass: &'a OcclusionPass,
+    /// Hueco en los buffers del culling de cada draw call (None sin probar)
+    slots: &'a [Option<u32>],
+    phase: OcclusionPhase,
+}
+
+/// Aplanar una lista de dibujo en draw calls y datos de instancia. La
+/// instancia 0 es la identidad que comparten las llamadas simples
+fn draw_items(draw_list: &DrawList) -> (Vec<DrawItem<'_>>, Vec<GpuInstance>) {
+    let mut items = Vec::with_capacity(draw_list.calls.len() + draw_list.instanced.len());
+    let mut instances = vec![GpuInstance::IDENTITY];
+    for call in &draw_list.calls {
+        items.push(DrawItem {
+            mesh_id: &call.mesh_id,
+            material_id: call.material_id.as_deref(),
+            transform: call.transform,
+            base_color: call.base_color,
+            instances: 0..1,
+            skin: call.skin.filter(|entity| draw_list.skins.contains_key(entity)),
+            morph: call.morph.filter(|entity| draw_list.morphs.contains_key(entity)),
+            layers: call.layers,
+            shadow_cascades: call.shadow_cascades,
+        });
+    }
+    for draw in &draw_list.instanced {
+        let start = instances.len() as u32;
+        instances.extend(draw.instances.iter().map(|instance| GpuInstance {
+            model: instance.transform.to_cols_array_2d(),
+            color: instance.color.to_array(),
+        }));
+        items.push(DrawItem {
+            mesh_id: &draw.mesh_id,
+            material_id: draw.material_id.as_deref(),
+            transform: Mat4::IDENTITY,
+            base_color: draw.base_color,
+            instances: start..instances.len() as u32,
+            skin: None,
+            morph: None,
+            layers: draw.layers,
+            shadow_cascades: SHADOW_CASCADES_ALL,
+        });
+    }
+    (items, instances)
+}
+
+/// Buffer de instancias del frame
+struct InstanceBuffer {
+    buffer: wgpu::Buffer,
+    /// Capacidad en instancias
+    capacity: usize,
+}
+
+impl InstanceBuffer {
+    /// Crear buffer
+    fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("instances"),
+            size: (std::mem::size_of::<GpuInstance>() * capacity) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self { buffer, capacity }
+    }
+
+    /// Escribir instancias, creciendo si hace falta
+    fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, instances: &[GpuInstance]) {
+        if instances.len() > self.capacity {
+            *self = Self::new(device, instances.len().next_power_of_two());
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+    }
+}
+
+/// Uniforms por draw call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct DrawUniforms {
+    view_projection: [[f32; 4]; 4],
+    model: [[f32; 4]; 4],
+    base_color: [f32; 4],
+    decals: [u32; 4],
+}
+
+/// Uniforms por frame: luz direccional y cascadas de sombra
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct FrameUniforms {
+    camera_view: [[f32; 4]; 4],
+    camera_position: [f32; 4],
+    light_view_projection: [[[f32; 4]; 4]; MAX_SHADOW_CASCADES],
+    cascade_splits: [f32; 4],
+    light_direction: [f32; 4],
+    light_color: [f32; 4],
+    ambient: [f32; 4],
+    shadow_params: [f32; 4],
+    shadow_filter: [f32; 4],
+    environment: [f32; 4],
+    inverse_view_projection: [[f32; 4]; 4],
+}
+
+impl FrameUniforms {
+    /// Uniforms de una lista de dibujo. `environment_level` es el mip de
+    /// rugosidad 1 del entorno de la lista si está subido
+    fn from_draw_list(draw_list: &DrawList, shadow_resolution: u32, environment_level: Option<f32>) -> Self {
+        let mut uniforms = Self::zeroed();
+        uniforms.camera_position = draw_list.camera_position.extend(1.0).to_array();
+        uniforms.inverse_view_projection = draw_list.view_projection.inverse().to_cols_array_2d();
+        // El entorno ilumina también los frames sin luz direccional
+        if let (Some(environment), Some(level)) = (&draw_list.environment, environment_level) {
+            let (sin, cos) = environment.rotation.sin_cos();
+            uniforms.environment = [environment.intensity.max(0.0), sin, cos, level];
+        }
+        let Some(light) = &draw_list.light else {
+            return uniforms;
+        };
+        let direction = light.direction.try_normalize().unwrap_or(glam::Vec3::NEG_Y);
+        uniforms.light_direction = direction.extend(1.0).to_array();
+        uniforms.light_color = light.color.extend(1.0).to_array();
+        uniforms.ambient = light.ambient.extend(1.0).to_array();
+
+        if let Some(shadows) = &draw_list.shadows {
+            let count = shadows.cascades.len().min(MAX_SHADOW_CASCADES);
+            uniforms.camera_view = shadows.camera_view.to_cols_array_2d();
+            for i in 0..count {
+                uniforms.light_view_projection[i] = shadows.cascades[i].to_cols_array_2d();
+                uniforms.cascade_splits[i] = shadows.splits.get(i).copied().unwrap_or(f32::MAX);
+            }
+            uniforms.shadow_params = [
+                shadows.settings.constant_bias,
+                shadows.settings.slope_bias,
+                count as f32,
+                1.0 / shadow_resolution.max(1) as f32,
+            ];
+            uniforms.shadow_filter[0] = if shadows.settings.pcf { 1.0 } else { 0.0 };
+        }
+        uniforms
+    }
+}
+
+/// Buffer de uniforms por draw call con offsets dinámicos
+struct DynamicUniforms {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// Capacidad en draw calls
+    capacity: usize,
+}
+
+impl DynamicUniforms {
+    /// Crear buffer y bind group
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, stride: u64, capacity: usize, label: &str) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: stride * capacity as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<DrawUniforms>() as u64),
+                }),
+            }],
+        });
+        Self { buffer, bind_group, capacity }
+    }
+
+    /// Asegurar espacio para `count` draw calls
+    fn ensure(&mut self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout, stride: u64, count: usize, label: &str) {
+        if count > self.capacity {
+            *self = Self::new(device, layout, stride, count.next_power_of_two(), label);
+        }
+    }
+
+    /// Escribir uniforms consecutivos separados por `stride`
+    fn write(&self, queue: &wgpu::Queue, stride: u64, data: &[DrawUniforms]) {
+        if data.is_empty() {
+            return;
+        }
+        let mut bytes = vec![0u8; stride as usize * data.len()];
+        for (i, uniforms) in data.iter().enumerate() {
+            let offset = i * stride as usize;
+            bytes[offset..offset + std::mem::size_of::<DrawUniforms>()]
+                .copy_from_slice(bytemuck::bytes_of(uniforms));
+        }
+        queue.write_buffer(&self.buffer, 0, &bytes);
+    }
+}
+
+/// Shadow maps de las cascadas (un array de capas de profundidad)
+struct ShadowMaps {
+    /// Resolución de cada capa
+    resolution: u32,
+    /// Vista del array completo para el pase principal
+    array_view: wgpu::TextureView,
+    /// Vista de cada capa para el shadow pass
+    layer_views: Vec<wgpu::TextureView>,
+}
+
+/// Opciones de creación del backend
+#[derive(Debug, Clone)]
+pub struct WgpuBackendOptions {
+    /// Ancho del destino
+    pub width: u32,
+    /// Alto del destino
+    pub height: u32,
+    /// Muestras MSAA (1 = sin MSAA)
+    pub sample_count: u32,
+    /// Sincronización vertical
+    pub vsync: bool,
+    /// Tipo de dispositivo preferido
+    pub device_type: wgpu::DeviceType,
+    /// Directorio de la caché de shaders en disco (None sin caché)
+    pub shader_cache_dir: Option<PathBuf>,
+    /// Compilar todas las variantes de los materiales al crear el backend
+    pub precompile_shaders: bool,
+}
+
+impl Default for WgpuBackendOptions {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            sample_count: 1,
+            vsync: true,
+            device_type: wgpu::DeviceType::DiscreteGpu,
+            shader_cache_dir: None,
+            precompile_shaders: true,
+        }
+    }
+}
+
+/// Buffers de un mesh en la GPU
+struct GpuMesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+    vertex_count: u32,
+    /// Influencias por vértice (solo mallas con skin)
+    influence_buffer: Option<wgpu::Buffer>,
+    /// Morph targets (solo mallas con targets)
+    morph_targets: Option<MorphTargets>,
+    /// Caja local
+    bounds: Aabb,
+}
+
+/// Texturas físicas de los transitorios del render graph
+#[derive(Default)]
+struct TransientTextures {
+    textures: Vec<(ResourceDesc, Option<wgpu::TextureView>)>,
+}
+
+impl TransientTextures {
+    /// Crear o reutilizar las texturas que pide el grafo compilado
+    fn prepare(&mut self, device: &wgpu::Device, descs: &[ResourceDesc]) {
+        self.textures.truncate(descs.len());
+        for (index, desc) in descs.iter().enumerate() {
+            if self.textures.get(index).is_some_and(|(current, _)| current == desc) {
+                continue;
+            }
+            // El grafo del frame solo declara texturas transitorias
+            let view = match desc {
+                ResourceDesc::Texture(texture) => Some(device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("graph-transient"),
+                    size: wgpu::Extent3d {
+                        width: texture.width,
+                        height: texture.height,
+                        depth_or_array_layers: texture.layers,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: graph_format(texture.format),
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                }).create_view(&wgpu::TextureViewDescriptor::default())),
+                ResourceDesc::Buffer { .. } => None,
+            };
+            if index < self.textures.len() {
+                self.textures[index] = (*desc, view);
+            } else {
+                self.textures.push((*desc, view));
+            }
+        }
+    }
+
+    /// Vista de un recurso físico
+    fn view(&self, index: usize) -> Option<&wgpu::TextureView> {
+        self.textures.get(index).and_then(|(_, view)| view.as_ref())
+    }
+}
+
+/// Formato wgpu de una textura del grafo
+fn graph_format(format: TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8Unorm,
+        TextureFormat::Rgba16Float => HDR_FORMAT,
+        TextureFormat::Depth32Float => DEPTH_FORMAT,
+        TextureFormat::R32Uint => wgpu::TextureFormat::R32Uint,
+        TextureFormat::R32Float => wgpu::TextureFormat::R32Float,
+    }
+}
+
+/// Destino de renderizado del frame en curso
+enum FrameTarget {
+    /// Imagen de la swap chain
+    Surface(wgpu::SurfaceTexture, wgpu::TextureView),
+    /// Textura offscreen
+    Offscreen(wgpu::TextureView),
+}
+
+/// Backend wgpu
+pub struct WgpuBackend {
+    /// Instancia
+    instance: wgpu::Instance,
+    /// Adaptador
+    adapter: wgpu::Adapter,
+    /// Dispositivo
+    device: wgpu::Device,
+    /// Cola de comandos
+    queue: wgpu::Queue,
+    /// Superficie (None en modo headless)
+    surface: Option<wgpu::Surface<'static>>,
+    /// Configuración de la swap chain
+    surface_config: Option<wgpu::SurfaceConfiguration>,
+    /// Textura offscreen (modo headless)
+    offscreen: Option<wgpu::Texture>,
+    /// Formato del destino
+    format: wgpu::TextureFormat,
+    /// Muestras MSAA efectivas
+    sample_count: u32,
+    /// Depth buffer
+    depth_view: wgpu::TextureView,
+    /// Destino multisample que se resuelve sobre el frame (None sin MSAA)
+    msaa_view: Option<wgpu::TextureView>,
+    /// Tamaño del destino
+    size: (u32, u32),
+    /// Pipeline por defecto
+    pipeline: Arc<wgpu::RenderPipeline>,
+    /// Pipeline de profundidad del shadow pass
+    shadow_pipeline: wgpu::RenderPipeline,
+    /// Layout del bind group de uniforms
+    uniform_layout: wgpu::BindGroupLayout,
+    /// Uniforms del pase principal
+    draw_uniforms: DynamicUniforms,
+    /// Uniforms del shadow pass (una entrada por cascada y draw call)
+    shadow_uniforms: DynamicUniforms,
+    /// Transformaciones por instancia
+    instance_buffer: InstanceBuffer,
+    /// Separación entre uniforms de draw calls consecutivas
+    uniform_stride: u64,
+    /// Layout del bind group del frame
+    frame_layout: wgpu::BindGroupLayout,
+    /// Uniforms del frame
+    frame_buffer: wgpu::Buffer,
+    /// Sampler de comparación de los shadow maps
+    shadow_sampler: wgpu::Sampler,
+    /// Shadow maps
+    shadow_maps: ShadowMaps,
+    /// Mapas de entorno, LUT de la BRDF y skybox
+    environments: EnvironmentResources,
+    /// Atlas y uniforms de los decals
+    decals: DecalResources,
+    /// Luces locales y clusters
+    clusters: ClusterResources,
+    /// Pipelines y buffers de las partículas
+    particles: ParticleResources,
+    /// Atlas de las fuentes y quads de texto y UI
+    text: TextResources,
+    /// Líneas de debug draw
+    debug_draw: DebugDrawResources,
+    /// Bind group del frame (uniforms, shadow maps, entorno y decals)
+    frame_bind_group: wgpu::BindGroup,
+    /// Entorno enlazado en el bind group del frame
+    frame_environment: Option<String>,
+    /// El bind group del frame apunta a recursos reemplazados
+    frame_dirty: bool,
+    /// Meshes subidos
+    meshes: HashMap<String, GpuMesh>,
+    /// Materiales y texturas subidos
+    materials: MaterialResources,
+    /// Cadena de post-procesado
+    post: PostProcessor,
+    /// Texturas transitorias del render graph
+    transients: TransientTextures,
+    /// Imagen de tasas del VRS (se crea con el primer frame que la pide)
+    vrs: Option<VRSPass>,
+    /// Skinning en GPU (se crea con la primera malla con skin)
+    skinning: Option<SkinningPass>,
+    /// Morph targets (se crea con la primera malla deformada)
+    morphing: Option<MorphPass>,
+    /// Pirámide de profundidad y culling de oclusión (se crea con el primer
+    /// frame que lo pide)
+    occlusion: Option<OcclusionPass>,
+    /// Escala de la resolución interna respecto al destino
+    render_scale: f32,
+    /// Tiempo de GPU de los frames (None sin timestamps)
+    gpu_timer: Option<GpuFrameTimer>,
+    /// Frame en curso
+    current_frame: Option<FrameTarget>,
+    /// Capturas pedidas para el próximo frame
+    frame_captures: Vec<oneshot::Sender<Result<ImageData>>>,
+    /// Lecturas de imágenes en curso
+    readbacks: ImageReadbacks,
+    /// Destinos offscreen de `render_to_texture`
+    render_textures: HashMap<String, wgpu::Texture>,
+}
+
+impl WgpuBackend {
+    /// Crear backend sobre una superficie, o headless si no hay superficie
+    pub async fn new(
+        target: Option<wgpu::SurfaceTarget<'static>>,
+        options: WgpuBackendOptions,
+    ) -> Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let surface = match target {
+            Some(target) => Some(instance.create_surface(target)?),
+            None => None,
+        };
+
+        let adapter = Self::select_adapter(&instance, surface.as_ref(), options.device_type)
+            .ok_or_else(|| anyhow!("No se encontró un adaptador gráfico compatible"))?;
+        let info = adapter.get_info();
+        info!("Adaptador wgpu: {} ({:?}, {:?})", info.name, info.device_type, info.backend);
+
+        let available = adapter.features();
+        let missing = REQUESTED_FEATURES - available;
+        if !missing.is_empty() {
+            warn!("Features no soportadas por el adaptador: {:?}", missing);
+        }
+
+        let (device, queue) = adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("metaverso-device"),
+                required_features: REQUESTED_FEATURES & available,
+                required_limits: wgpu::Limits::default().using_resolution(adapter.limits()),
+            },
+            None,
+        ).await?;
+
+        Self::from_device(instance, adapter, device, queue, surface, options)
+    }
+
+    /// Crear backend sobre un dispositivo ya abierto (p. ej. el que exige el
+    /// runtime de XR). Sin superficie el destino principal es offscreen
+    pub fn from_device(
+        instance: wgpu::Instance,
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        surface: Option<wgpu::Surface<'static>>,
+        options: WgpuBackendOptions,
+    ) -> Result<Self> {
+        let (width, height) = (options.width.max(1), options.height.max(1));
+        let (surface_config, offscreen, format) = match &surface {
+            Some(surface) => {
+                let mut config = surface.get_default_config(&adapter, width, height)
+                    .ok_or_else(|| anyhow!("La superficie no es compatible con el adaptador"))?;
+                config.present_mode = if options.vsync {
+                    wgpu::PresentMode::AutoVsync
+                } else {
+                    wgpu::PresentMode::AutoNoVsync
+                };
+                // Las capturas copian la imagen de la swap chain
+                if surface.get_capabilities(&adapter).usages.contains(wgpu::TextureUsages::COPY_SRC) {
+                    config.usage |= wgpu::TextureUsages::COPY_SRC;
+                }
+                surface.configure(&device, &config);
+                let format = config.format;
+                (Some(config), None, format)
+            }
+            None => {
+                let texture = Self::create_offscreen(&device, OFFSCREEN_FORMAT, width, height);
+                (None, Some(texture), OFFSCREEN_FORMAT)
+            }
+        };
+
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("draw-uniforms-layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<DrawUniforms>() as u64),
+                },
+                count: None,
+            }],
+        });
+
+        let mut frame_entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<FrameUniforms>() as u64),
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+        ];
+        frame_entries.extend(EnvironmentResources::layout_entries());
+        frame_entries.extend(DecalResources::layout_entries());
+        frame_entries.extend(ClusterResources::layout_entries());
+        let frame_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("frame-layout"),
+            entries: &frame_entries,
+        });
+        let frame_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame-uniforms"),
+            size: std::mem::size_of::<FrameUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let shadow_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shadow-sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        // Hasta el primer frame con sombras basta con un shadow map mínimo
+        let shadow_maps = Self::create_shadow_maps(&device, 1);
+
+        let sample_count = Self::supported_sample_count(&adapter, HDR_FORMAT, options.sample_count);
+        let depth_view = Self::create_depth(&device, width, height, sample_count);
+        let msaa_view = Self::create_msaa(&device, HDR_FORMAT, width, height, sample_count);
+        let pipeline = Arc::new(Self::create_default_pipeline(&device, &uniform_layout, &frame_layout, HDR_FORMAT, sample_count));
+        let shadow_pipeline = Self::create_shadow_pipeline(&device, &uniform_layout);
+        let shader_cache = ShaderCache::new(options.shader_cache_dir.as_deref(), &adapter.get_info());
+        let materials = MaterialResources::new(
+            &device,
+            &queue,
+            &uniform_layout,
+            &frame_layout,
+            HDR_FORMAT,
+            sample_count,
+            shader_cache,
+            options.precompile_shaders,
+        );
+        let environments = EnvironmentResources::new(&device, &queue, &uniform_layout, &frame_layout, HDR_FORMAT, sample_count);
+        let decals = DecalResources::new(&device);
+        let clusters = ClusterResources::new(&device);
+        let particles = ParticleResources::new(&device, HDR_FORMAT, sample_count);
+        let text = TextResources::new(&device, &queue, format);
+        let debug_draw = DebugDrawResources::new(&device, format);
+        let frame_bind_group = Self::create_frame_bind_group(
+            &device,
+            &frame_layout,
+            &frame_buffer,
+            &shadow_sampler,
+            &shadow_maps,
+            &environments,
+            None,
+            &decals,
+            &clusters,
+        );
+        let post = PostProcessor::new(&device, format, width, height);
+        let gpu_timer = GpuFrameTimer::new(&device, &queue);
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let uniform_stride = wgpu::util::align_to(std::mem::size_of::<DrawUniforms>() as u64, alignment);
+        let draw_uniforms = DynamicUniforms::new(&device, &uniform_layout, uniform_stride, 64, "draw-uniforms");
+        let shadow_uniforms = DynamicUniforms::new(&device, &uniform_layout, uniform_stride, 64, "shadow-uniforms");
+        let instance_buffer = InstanceBuffer::new(&device, 1024);
+
+        Ok(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+            surface,
+            surface_config,
+            offscreen,
+            format,
+            sample_count,
+            depth_view,
+            msaa_view,
+            size: (width, height),
+            pipeline,
+            shadow_pipeline,
+            uniform_layout,
+            draw_uniforms,
+            shadow_uniforms,
+            instance_buffer,
+            uniform_stride,
+            frame_layout,
+            frame_buffer,
+            shadow_sampler,
+            shadow_maps,
+            environments,
+            decals,
+            clusters,
+            particles,
+            text,
+            debug_draw,
+            frame_bind_group,
+            frame_environment: None,
+            frame_dirty: false,
+            meshes: HashMap::new(),
+            materials,
+            post,
+            transients: TransientTextures::default(),
+            vrs: None,
+            skinning: None,
+            morphing: None,
+            occlusion: None,
+            render_scale: 1.0,
+            gpu_timer,
+            current_frame: None,
+            frame_captures: Vec::new(),
+            readbacks: ImageReadbacks::default(),
+            render_textures: HashMap::new(),
+        })
+    }
+
+    /// Seleccionar adaptador, prefiriendo el tipo de dispositivo indicado
+    fn select_adapter(
+        instance: &wgpu::Instance,
+        surface: Option<&wgpu::Surface<'static>>,
+        device_type: wgpu::DeviceType,
+    ) -> Option<wgpu::Adapter> {
+        let mut adapters: Vec<wgpu::Adapter> = instance.enumerate_adapters(wgpu::Backends::all())
+            .into_iter()
+            .filter(|adapter| surface.map_or(true, |s| adapter.is_surface_supported(s)))
+            .collect();
+
+        let preferred = adapters.iter()
+            .position(|adapter| adapter.get_info().device_type == device_type);
+        match preferred {
+            Some(index) => Some(adapters.swap_remove(index)),
+            None => adapters.into_iter().next(),
+        }
+    }
+
+    /// Crear textura offscreen
+    fn create_offscreen(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen-target"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Crear depth buffer (muestreable por el TAA si no hay MSAA)
+    fn create_depth(device: &wgpu::Device, width: u32, height: u32, sample_count: u32) -> wgpu::TextureView {
+        let usage = if sample_count > 1 {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        };
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth-buffer"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// Crear destino multisample (None si no hay MSAA)
+    fn create_msaa(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Option<wgpu::TextureView> {
+        if sample_count <= 1 {
+            return None;
+        }
+        Some(device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("msaa-target"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    /// Mayor número de muestras soportado que no supera el solicitado
+    fn supported_sample_count(adapter: &wgpu::Adapter, format: wgpu::TextureFormat, requested: u32) -> u32 {
+        let color = adapter.get_texture_format_features(format).flags;
+        let depth = adapter.get_texture_format_features(DEPTH_FORMAT).flags;
+        let mut count = requested.clamp(1, 16).next_power_of_two();
+        if count > requested.max(1) {
+            count /= 2;
+        }
+        while count > 1 && !(color.sample_count_supported(count) && depth.sample_count_supported(count)) {
+            count /= 2;
+        }
+        if count != requested.max(1) {
+            warn!("MSAA x{} no soportado, usando x{}", requested, count);
+        }
+        count
+    }
+
+    /// Crear los shadow maps de las cascadas
+    fn create_shadow_maps(device: &wgpu::Device, resolution: u32) -> ShadowMaps {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("shadow-maps"),
+            size: wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: MAX_SHADOW_CASCADES as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("shadow-maps-array"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..MAX_SHADOW_CASCADES as u32)
+            .map(|layer| texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("shadow-map-layer"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            }))
+            .collect();
+
+        ShadowMaps { resolution, array_view, layer_views }
+    }
+
+    /// Crear el bind group del frame: uniforms, shadow maps, los mapas del
+    /// entorno (los neutros si no hay) y los decals
+    #[allow(clippy::too_many_arguments)]
+    fn create_frame_bind_group(
+        device: &wgpu::Device,
+        frame_layout: &wgpu::BindGroupLayout,
+        frame_buffer: &wgpu::Buffer,
+        shadow_sampler: &wgpu::Sampler,
+        shadow_maps: &ShadowMaps,
+        environments: &EnvironmentResources,
+        environment_id: Option<&str>,
+        decals: &DecalResources,
+        clusters: &ClusterResources,
+    ) -> wgpu::BindGroup {
+        let mut entries = vec![
+            wgpu::BindGroupEntry { binding: 0, resource: frame_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&shadow_maps.array_view) },
+            wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(shadow_sampler) },
+        ];
+        entries.extend(environments.bind_group_entries(environment_id));
+        entries.extend(decals.bind_group_entries());
+        entries.extend(clusters.bind_group_entries());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("frame-bind-group"),
+            layout: frame_layout,
+            entries: &entries,
+        })
+    }
+
+    /// Crear pipeline de profundidad del shadow pass
+    fn create_shadow_pipeline(device: &wgpu::Device, uniform_layout: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shadow-shader"),
+            source: wgpu::ShaderSource::Wgsl(format!("{}{}", SHADER_COMMON, DEFAULT_SHADER).into()),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("shadow-pipeline-layout"),
+            bind_group_layouts: &[uniform_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("shadow-pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_shadow",
+                buffers: &[GpuVertex::layout(), GpuInstance::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                // Sin culling: las superficies de una cara también proyectan sombra
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /// Crear pipeline por defecto
+    fn create_default_pipeline(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        frame_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("default-shader"),
+            source: wgpu::ShaderSource::Wgsl(format!("{}{}", SHADER_COMMON, DEFAULT_SHADER).into()),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("default-pipeline-layout"),
+            bind_group_layouts: &[uniform_layout, frame_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("default-pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[GpuVertex::layout(), GpuInstance::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// Pipeline por defecto
+    pub fn default_pipeline(&self) -> Arc<wgpu::RenderPipeline> {
+        self.pipeline.clone()
+    }
+
+    /// Formato del destino
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// Formato del destino HDR sobre el que dibuja `submit_draw`
+    pub fn scene_format(&self) -> wgpu::TextureFormat {
+        HDR_FORMAT
+    }
+
+    /// Muestras MSAA efectivas
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Dispositivo
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    /// Cola de comandos
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// Información del adaptador
+    pub fn adapter_info(&self) -> wgpu::AdapterInfo {
+        self.adapter.get_info()
+    }
+
+    /// Features habilitadas en el dispositivo
+    pub fn features(&self) -> wgpu::Features {
+        self.device.features()
+    }
+
+    /// Instancia
+    pub fn instance(&self) -> &wgpu::Instance {
+        &self.instance
+    }
+
+    /// Tamaño de renderizado interno (destino por la escala de resolución)
+    pub fn render_size(&self) -> (u32, u32) {
+        scaled_size(self.size.0, self.size.1, self.render_scale)
+    }
+
+    /// Recrear los destinos que trabajan a la resolución interna
+    fn resize_render_targets(&mut self) {
+        let (width, height) = self.render_size();
+        self.depth_view = Self::create_depth(&self.device, width, height, self.sample_count);
+        self.msaa_view = Self::create_msaa(&self.device, HDR_FORMAT, width, height, self.sample_count);
+        self.post.resize(&self.device, width, height);
+        if let Some(vrs) = &mut self.vrs {
+            vrs.resize(&self.device, width, height);
+        }
+        if let Some(occlusion) = &mut self.occlusion {
+            occlusion.resize(&self.device, width, height);
+        }
+    }
+
+    /// Comenzar frame: adquiere el destino y devuelve el encoder del frame
+    pub fn begin_frame(&mut self) -> Result<wgpu::CommandEncoder> {
+        let target = match (&self.surface, &self.offscreen) {
+            (Some(surface), _) => {
+                let frame = match surface.get_current_texture() {
+                    Ok(frame) => frame,
+                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                        // Reconfigurar la swap chain y reintentar una vez
+                        if let Some(config) = &self.surface_config {
+                            surface.configure(&self.device, config);
+                        }
+                        surface.get_current_texture()?
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+                FrameTarget::Surface(frame, view)
+            }
+            (None, Some(texture)) => {
+                FrameTarget::Offscreen(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+            }
+            (None, None) => return Err(anyhow!("Backend wgpu sin destino de renderizado")),
+        };
+        self.current_frame = Some(target);
+
+        Ok(self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame-encoder"),
+        }))
+    }
+
+    /// Grabar las draw calls de una lista en el encoder del frame y aplicar
+    /// después el post-procesado. El pipeline debe dibujar en `scene_format`,
+    /// usar `DEPTH_FORMAT`, el mismo número de muestras que el backend y
+    /// el layout de bind groups del pipeline por defecto y el buffer de
+    /// instancias en el slot 1. Las draw calls con un material subido usan el
+    /// pipeline de su material.
+    pub fn submit_draw(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        draw_list: &DrawList,
+    ) -> Result<FrameStats> {
+        let mut stats = FrameStats::default();
+        let (items, instances) = draw_items(draw_list);
+        self.instance_buffer.write(&self.device, &self.queue, &instances);
+        if draw_list.light.is_some() {
+            self.prepare_shadow_maps(draw_list, &items);
+        }
+
+        // Los buffers de luces crecen con las luces y la rejilla
+        if self.clusters.prepare(&self.device, &self.queue, draw_list.clusters.as_ref(), &draw_list.lights) {
+            self.frame_dirty = true;
+        }
+        let clustering = self.clusters.stats();
+        stats.clustered_lights = clustering.lights;
+        stats.overflowed_clusters = clustering.overflowed_clusters;
+        stats.dropped_cluster_lights = clustering.dropped_lights;
+        stats.max_cluster_lights = clustering.max_cluster_lights;
+        stats.shader_cache_hits = self.materials.shader_cache().hits();
+        stats.shader_cache_misses = self.materials.shader_cache().misses();
+
+        // El bind group del frame se rehace al cambiar los shadow maps, el
+        // entorno o los buffers de luces
+        let environment_id = draw_list.environment.as_ref()
+            .map(|environment| environment.id.as_str())
+            .filter(|id| self.environments.has(id));
+        if self.frame_dirty || self.frame_environment.as_deref() != environment_id {
+            self.frame_bind_group = Self::create_frame_bind_group(
+                &self.device,
+                &self.frame_layout,
+                &self.frame_buffer,
+                &self.shadow_sampler,
+                &self.shadow_maps,
+                &self.environments,
+                environment_id,
+                &self.decals,
+                &self.clusters,
+            );
+            self.frame_environment = environment_id.map(str::to_string);
+            self.frame_dirty = false;
+        }
+        let environment_level = environment_id.and_then(|id| self.environments.max_level(id));
+        let frame = FrameUniforms::from_draw_list(draw_list, self.shadow_maps.resolution, environment_level);
+        self.queue.write_buffer(&self.frame_buffer, 0, bytemuck::bytes_of(&frame));
+
+        stats.decals = self.decals.prepare(&self.queue, &draw_list.decals);
+        stats.particles = self.particles.prepare(&self.device, &self.queue, draw_list.particles.as_ref(), &draw_list.view_projection);
+        stats.text_quads = self.text.prepare(&self.device, &self.queue, draw_list.text.as_ref(), self.size);
+        stats.debug_lines = self.debug_draw.prepare(&self.device, &self.queue, draw_list.debug.as_ref(), self.size);
+
+        // Escribir uniforms de todas las draw calls antes de grabar el pase
+        self.draw_uniforms.ensure(&self.device, &self.uniform_layout, self.uniform_stride, items.len(), "draw-uniforms");
+        let uniforms: Vec<DrawUniforms> = items.iter()
+            .map(|item| {
+                let mask = receiver_mask(&draw_list.decals, item.layers, self.item_bounds(item).as_ref());
+                DrawUniforms {
+                    view_projection: draw_list.view_projection.to_cols_array_2d(),
+                    model: item.transform.to_cols_array_2d(),
+                    base_color: item.base_color.to_array(),
+                    decals: [mask as u32, (mask >> 32) as u32, 0, 0],
+                }
+            })
+            .collect();
+        self.draw_uniforms.write(&self.queue, self.uniform_stride, &uniforms);
+
+        if self.current_frame.is_none() {
+            return Err(anyhow!("submit_draw llamado fuera de begin_frame/end_frame"));
+        }
+
+        let render_size = self.render_size();
+        let (graph, resources) = build_frame_graph(draw_list, &FrameGraphOptions {
+            width: render_size.0,
+            height: render_size.1,
+            output_width: self.size.0,
+            output_height: self.size.1,
+            history_index: self.post.history_index(),
+            depth_readable: self.msaa_view.is_none(),
+        });
+        let compiled = graph.compile()?;
+        self.transients.prepare(&self.device, compiled.physical_resources());
+
+        // El depth buffer se conserva si algún pase posterior lo muestrea (TAA)
+        let keep_depth = compiled.passes().iter()
+            .any(|pass| pass.payload != FramePass::Forward && pass.reads.contains(&resources.depth));
+        self.post.prepare(&self.queue, draw_list.post.as_ref(), keep_depth, render_size);
+        if let Some(frame) = &draw_list.vrs {
+            let (width, height) = render_size;
+            let vrs = self.vrs.get_or_insert_with(|| VRSPass::new(&self.device, width, height));
+            vrs.prepare(&self.queue, frame, keep_depth, render_size);
+        }
+
+        // Solo se prueban las draw calls simples con caja; cada una ocupa un
+        // hueco en los buffers del culling
+        let mut occlusion_slots = Vec::new();
+        if let (Some(frame), Some(_)) = (&draw_list.occlusion, resources.hi_z) {
+            let mut occlusion_items = Vec::new();
+            occlusion_slots = items.iter()
+                .map(|item| {
+                    let mesh = self.meshes.get(item.mesh_id)?;
+                    let bounds = self.item_bounds(item)?;
+                    occlusion_items.push(OcclusionItem { bounds, index_count: mesh.index_count });
+                    Some(occlusion_items.len() as u32 - 1)
+                })
+                .collect();
+            let (width, height) = render_size;
+            let occlusion = self.occlusion.get_or_insert_with(|| OcclusionPass::new(&self.device, width, height));
+            occlusion.resize(&self.device, width, height);
+            occlusion.prepare(&self.device, &self.queue, frame, &draw_list.view_projection, &occlusion_items);
+            let last = occlusion.stats();
+            stats.occlusion_tested = last.tested;
+            stats.occluded = last.occluded;
+        }
+
+        if let Some(timer) = &self.gpu_timer {
+            timer.begin(encoder);
+        }
+
+        // Deformar las mallas con morph targets y skin antes de los pases que las dibujan
+        stats.morphed_vertices = self.record_morphing(encoder, draw_list, &items);
+        stats.skinned_vertices = self.record_skinning(encoder, draw_list, &items);
+        // Repartir las luces locales antes de los pases que las leen
+        self.clusters.record(&self.device, encoder);
+
+        let mut taa_executed = false;
+        for pass in compiled.passes() {
+            match pass.payload {
+                FramePass::Shadow(cascade) => {
+                    stats.shadow_draw_calls += self.record_shadow_pass(encoder, &items, cascade);
+                }
+                FramePass::Forward if occlusion_slots.is_empty() => {
+                    let scene = self.graph_view(&compiled, &resources, pass.writes[0])?;
+                    self.record_forward_pass(encoder, pipeline, &items, draw_list, scene, keep_depth, None, &mut stats);
+                }
+                FramePass::Forward => {
+                    // Fase temprana, pirámide con su profundidad y fase tardía
+                    let Some(mut occlusion) = self.occlusion.take() else {
+                        continue;
+                    };
+                    let scene = self.graph_view(&compiled, &resources, pass.writes[0])?;
+                    occlusion.record_cull(&self.device, encoder, OcclusionPhase::Early);
+                    let early = ForwardOcclusion { pass: &occlusion, slots: &occlusion_slots, phase: OcclusionPhase::Early };
+                    self.record_forward_pass(encoder, pipeline, &items, draw_list, scene, keep_depth, Some(early), &mut stats);
+                    occlusion.record_pyramid(&self.device, encoder, &self.depth_view);
+                    occlusion.record_cull(&self.device, encoder, OcclusionPhase::Late);
+                    let late = ForwardOcclusion { pass: &occlusion, slots: &occlusion_slots, phase: OcclusionPhase::Late };
+                    self.record_forward_pass(encoder, pipeline, &items, draw_list, scene, keep_depth, Some(late), &mut stats);
+                    self.occlusion = Some(occlusion);
+                    // Las draw calls indirectas con cero instancias no dibujan nada
+                    stats.draw_calls = stats.draw_calls.saturating_sub(stats.occluded);
+                }
+                FramePass::Particles => {
+                    let scene = self.graph_view(&compiled, &resources, pass.writes[0])?;
+                    stats.draw_calls += self.particles.record_soft(&self.device, encoder, scene, &self.depth_view);
+                }
+                FramePass::ShadingRate => {
+                    let Some(mut vrs) = self.vrs.take() else {
+                        continue;
+                    };
+                    let scene = self.graph_view(&compiled, &resources, pass.reads[0])?;
+                    let depth = pass.reads.get(1).map(|_| &self.depth_view);
+                    vrs.record(&self.device, encoder, scene, depth);
+                    self.vrs = Some(vrs);
+                }
+                FramePass::Post(post_pass) => {
+                    let inputs = pass.reads.iter()
+                        .map(|&resource| self.graph_view(&compiled, &resources, resource))
+                        .collect::<Result<Vec<_>>>()?;
+                    let target = self.graph_view(&compiled, &resources, pass.writes[0])?;
+                    self.post.execute(&self.device, encoder, post_pass, &inputs, target);
+                    taa_executed |= post_pass == PostPass::Taa;
+                }
+                FramePass::Debug => {
+                    let target = self.graph_view(&compiled, &resources, pass.writes[0])?;
+                    // Sin lectura de profundidad en el grafo (MSAA) todo va encima
+                    let depth = pass.reads.first().map(|_| &self.depth_view);
+                    stats.draw_calls += self.debug_draw.record(&self.device, encoder, target, depth);
+                }
+                FramePass::Text => {
+                    let target = self.graph_view(&compiled, &resources, pass.writes[0])?;
+                    stats.draw_calls += self.text.record(encoder, target);
+                }
+            }
+        }
+        self.post.finish(taa_executed);
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.end(encoder);
+            stats.gpu_time_ms = timer.last_frame_time_ms();
+        }
+
+        stats.render_passes = compiled.passes().len() as u32;
+        stats.culled_passes = compiled.culled_passes().len() as u32;
+        if draw_list.vrs.is_some() {
+            stats.average_shading_rate = self.vrs.as_ref().map_or(1.0, VRSPass::average_shading_rate);
+        }
+        Ok(stats)
+    }
+
+    /// Caja en mundo de una draw call simple. Las mallas con skin o morph y
+    /// los grupos instanciados no tienen una caja única y reciben todos los
+    /// decals de sus capas
+    fn item_bounds(&self, item: &DrawItem<'_>) -> Option<Aabb> {
+        if item.skin.is_some() || item.morph.is_some() || item.instances != (0..1) {
+            return None;
+        }
+        self.meshes.get(item.mesh_id).map(|mesh| mesh.bounds.transformed(&item.transform))
+    }
+
+    /// Preparar los pesos de las draw calls con morph y grabar el compute
+    /// pass de morph targets. Devuelve los vértices deformados
+    fn record_morphing(&mut self, encoder: &mut wgpu::CommandEncoder, draw_list: &DrawList, items: &[DrawItem<'_>]) -> u32 {
+        if self.morphing.is_none() && items.iter().all(|item| item.morph.is_none()) {
+            return 0;
+        }
+        let morphing_pass = self.morphing.get_or_insert_with(|| MorphPass::new(&self.device));
+        morphing_pass.begin();
+        for item in items {
+            let Some(entity) = item.morph else {
+                continue;
+            };
+            let (Some(mesh), Some(weights)) = (self.meshes.get(item.mesh_id), draw_list.morphs.get(&entity)) else {
+                continue;
+            };
+            let Some(targets) = &mesh.morph_targets else {
+                continue;
+            };
+            let source = MorphSource {
+                mesh_id: item.mesh_id,
+                vertices: &mesh.vertex_buffer,
+                targets,
+                vertex_count: mesh.vertex_count,
+            };
+            morphing_pass.prepare(&self.device, &self.queue, entity, &source, weights);
+        }
+        morphing_pass.record(encoder)
+    }
+
+    /// Preparar las paletas de las draw calls con skin y grabar el compute
+    /// pass de skinning. Devuelve los vértices deformados
+    fn record_skinning(&mut self, encoder: &mut wgpu::CommandEncoder, draw_list: &DrawList, items: &[DrawItem<'_>]) -> u32 {
+        if self.skinning.is_none() && items.iter().all(|item| item.skin.is_none()) {
+            return 0;
+        }
+        let skinning_pass = self.skinning.get_or_insert_with(|| SkinningPass::new(&self.device));
+        skinning_pass.begin();
+        for item in items {
+            let Some(entity) = item.skin else {
+                continue;
+            };
+            let (Some(mesh), Some(palette)) = (self.meshes.get(item.mesh_id), draw_list.skins.get(&entity)) else {
+                continue;
+            };
+            let Some(influences) = &mesh.influence_buffer else {
+                continue;
+            };
+            // Las mallas con morph parten de sus vértices ya deformados
+            let vertices = item.morph
+                .and_then(|entity| self.morphing.as_ref()?.output(entity))
+                .unwrap_or(&mesh.vertex_buffer);
+            let source = SkinSource {
+                mesh_id: item.mesh_id,
+                vertices,
+                influences,
+                vertex_count: mesh.vertex_count,
+            };
+            skinning_pass.prepare(&self.device, &self.queue, entity, &source, palette);
+        }
+        skinning_pass.record(encoder)
+    }
+
+    /// Buffer de vértices que dibuja una draw call: el deformado si tiene
+    /// skin o morph targets
+    fn vertex_buffer<'a>(&'a self, item: &DrawItem<'_>, mesh: &'a GpuMesh) -> &'a wgpu::Buffer {
+        item.skin
+            .and_then(|entity| self.skinning.as_ref()?.output(entity))
+            .or_else(|| item.morph.and_then(|entity| self.morphing.as_ref()?.output(entity)))
+            .unwrap_or(&mesh.vertex_buffer)
+    }
+
+    /// Vista de un recurso del grafo del frame
+    fn graph_view<'a>(
+        &'a self,
+        compiled: &CompiledGraph<FramePass>,
+        resources: &FrameResources,
+        resource: ResourceHandle,
+    ) -> Result<&'a wgpu::TextureView> {
+        if resource == resources.output {
+            return match &self.current_frame {
+                Some(FrameTarget::Surface(_, view) | FrameTarget::Offscreen(view)) => Ok(view),
+                None => Err(anyhow!("Sin destino de frame")),
+            };
+        }
+        if resource == resources.depth {
+            return Ok(&self.depth_view);
+        }
+        if let Some(cascade) = resources.shadow_maps.iter().position(|&layer| layer == resource) {
+            return Ok(&self.shadow_maps.layer_views[cascade]);
+        }
+        if let Some(index) = resources.history.iter().position(|&history| history == resource) {
+            return Ok(self.post.history_view(index));
+        }
+        compiled.physical_index(resource)
+            .and_then(|index| self.transients.view(index))
+            .ok_or_else(|| anyhow!("Recurso del grafo sin textura: {}", compiled.resource_name(resource)))
+    }
+
+    /// Grabar el pase principal sobre `scene` (con MSAA, resolviendo sobre ella).
+    /// Con occlusion culling se graba dos veces: la fase temprana limpia los
+    /// destinos y dibuja lo no probado más lo visible en la pirámide anterior;
+    /// la tardía conserva los destinos y solo dibuja lo recuperado
+    #[allow(clippy::too_many_arguments)]
+    fn record_forward_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        items: &[DrawItem<'_>],
+        draw_list: &DrawList,
+        scene: &wgpu::TextureView,
+        keep_depth: bool,
+        occlusion: Option<ForwardOcclusion<'_>>,
+        stats: &mut FrameStats,
+    ) {
+        let (view, resolve_target) = match &self.msaa_view {
+            Some(msaa_view) => (msaa_view, Some(scene)),
+            None => (scene, None),
+        };
+
+        let late = occlusion.as_ref().is_some_and(|occlusion| occlusion.phase == OcclusionPhase::Late);
+        // La pirámide se construye con la profundidad de la fase temprana
+        let keep_depth = keep_depth || occlusion.as_ref().is_some_and(|occlusion| occlusion.phase == OcclusionPhase::Early);
+        let clear = draw_list.clear_color;
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(if late { "main-pass-late" } else { "main-pass" }),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: if late {
+                        wgpu::LoadOp::Load
+                    } else {
+                        wgpu::LoadOp::Clear(wgpu::Color {
+                            r: clear.x as f64,
+                            g: clear.y as f64,
+                            b: clear.z as f64,
+                            a: clear.w as f64,
+                        })
+                    },
+                    store: if resolve_target.is_some() {
+                        wgpu::StoreOp::Discard
+                    } else {
+                        wgpu::StoreOp::Store
+                    },
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: if late { wgpu::LoadOp::Load } else { wgpu::LoadOp::Clear(1.0) },
+                    store: if keep_depth { wgpu::StoreOp::Store } else { wgpu::StoreOp::Discard },
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_bind_group(1, &self.frame_bind_group, &[]);
+
+        // El skybox va primero para que las superficies con transparencia se
+        // mezclen con él; la geometría lo tapa al pasar el test de profundidad
+        if !late && self.frame_environment.is_some() && draw_list.environment.as_ref().is_some_and(|environment| environment.skybox) {
+            self.environments.record_skybox(&mut pass, &self.draw_uniforms.bind_group);
+            stats.draw_calls += 1;
+        }
+        pass.set_vertex_buffer(1, self.instance_buffer.buffer.slice(..));
+
+        // Solo se cambia de pipeline cuando cambia respecto a la draw call anterior
+        let mut current_pipeline: Option<&wgpu::RenderPipeline> = None;
+        for (i, item) in items.iter().enumerate() {
+            let slot = occlusion.as_ref().and_then(|occlusion| occlusion.slots.get(i).copied().flatten());
+            // La fase tardía solo redibuja draw calls probadas
+            if late && slot.is_none() {
+                continue;
+            }
+            let Some(mesh) = self.meshes.get(item.mesh_id) else {
+                warn!("Draw call con mesh no subido: {}", item.mesh_id);
+                continue;
+            };
+            let material = item.material_id.and_then(|id| self.materials.binding(id));
+            let call_pipeline = material.map_or(pipeline, |(material_pipeline, _)| material_pipeline);
+            if !current_pipeline.is_some_and(|current| std::ptr::eq(current, call_pipeline)) {
+                pass.set_pipeline(call_pipeline);
+                current_pipeline = Some(call_pipeline);
+            }
+            if let Some((_, bind_group)) = material {
+                pass.set_bind_group(2, bind_group, &[]);
+            }
+            let offset = (i as u64 * self.uniform_stride) as u32;
+            pass.set_bind_group(0, &self.draw_uniforms.bind_group, &[offset]);
+            pass.set_vertex_buffer(0, self.vertex_buffer(item, mesh).slice(..));
+            pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            match (&occlusion, slot) {
+                // El compute shader decide si la draw call tiene una instancia o ninguna
+                (Some(occlusion), Some(slot)) => {
+                    pass.draw_indexed_indirect(occlusion.pass.args_buffer(), occlusion.pass.args_offset(slot, occlusion.phase));
+                    if late {
+                        continue;
+                    }
+                }
+                _ => pass.draw_indexed(0..mesh.index_count, 0, item.instances.clone()),
+            }
+
+            let instance_count = item.instances.len() as u32;
+            stats.draw_calls += 1;
+            stats.triangles += mesh.index_count / 3 * instance_count;
+            stats.vertices += mesh.vertex_count * instance_count;
+        }
+
+        // Con MSAA la profundidad no se puede muestrear: las partículas se
+        // dibujan aquí, con test de profundidad y sin suavizar
+        if self.msaa_view.is_some() && !late {
+            stats.draw_calls += self.particles.record_in_pass(&mut pass);
+        }
+    }
+
+    /// Terminar frame: copia el destino para las capturas pedidas, envía el
+    /// encoder y presenta la imagen
+    pub fn end_frame(&mut self, mut encoder: wgpu::CommandEncoder) {
+        if !self.frame_captures.is_empty() {
+            let copyable = self.surface_config.as_ref()
+                .map_or(true, |config| config.usage.contains(wgpu::TextureUsages::COPY_SRC));
+            let texture = match &self.current_frame {
+                Some(FrameTarget::Surface(frame, _)) if copyable => Some(&frame.texture),
+                Some(FrameTarget::Offscreen(_)) => self.offscreen.as_ref(),
+                _ => None,
+            };
+            for sender in self.frame_captures.drain(..) {
+                match texture {
+                    Some(texture) => self.readbacks.record(&self.device, &mut encoder, texture, sender),
+                    None => {
+                        let _ = sender.send(Err(anyhow!("El destino del frame no admite copias")));
+                    }
+                }
+            }
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.readbacks.finish(&self.device);
+        if let Some(vrs) = &mut self.vrs {
+            vrs.finish(&self.device);
+        }
+        if let Some(occlusion) = &mut self.occlusion {
+            occlusion.finish(&self.device);
+        }
+        self.clusters.finish(&self.device);
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.finish(&self.device);
+        }
+        if let Some(FrameTarget::Surface(frame, _)) = self.current_frame.take() {
+            frame.present();
+        }
+    }
+
+    /// Leer los píxeles RGBA del destino offscreen
+    pub fn read_pixels(&self) -> Result<Vec<u8>> {
+        let texture = self.offscreen.as_ref()
+            .ok_or_else(|| anyhow!("read_pixels solo está disponible en modo headless"))?;
+        let (width, height) = self.size;
+
+        let unpadded_row = width * 4;
+        let padded_row = wgpu::util::align_to(unpadded_row, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: (padded_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("readback-encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+
+        let mapped = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((unpadded_row * height) as usize);
+        for row in mapped.chunks(padded_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_row as usize]);
+        }
+        drop(mapped);
+        buffer.unmap();
+
+        Ok(pixels)
+    }
+
+    /// Ajustar los shadow maps a la resolución pedida y escribir los
+    /// uniforms de cada cascada y draw call
+    fn prepare_shadow_maps(&mut self, draw_list: &DrawList, items: &[DrawItem<'_>]) {
+        let Some(shadows) = &draw_list.shadows else {
+            return;
+        };
+        let resolution = shadows.settings.resolution.clamp(1, self.device.limits().max_texture_dimension_2d);
+        if self.shadow_maps.resolution != resolution {
+            self.shadow_maps = Self::create_shadow_maps(&self.device, resolution);
+            self.frame_dirty = true;
+        }
+
+        let cascades = &shadows.cascades[..shadows.cascades.len().min(MAX_SHADOW_CASCADES)];
+        self.shadow_uniforms.ensure(
+            &self.device,
+            &self.uniform_layout,
+            self.uniform_stride,
+            cascades.len() * items.len(),
+            "shadow-uniforms",
+        );
+        let uniforms: Vec<DrawUniforms> = cascades.iter()
+            .flat_map(|cascade| items.iter().map(move |item| DrawUniforms {
+                view_projection: cascade.to_cols_array_2d(),
+                model: item.transform.to_cols_array_2d(),
+                base_color: item.base_color.to_array(),
+                decals: [0; 4],
+            }))
+            .collect();
+        self.shadow_uniforms.write(&self.queue, self.uniform_stride, &uniforms);
+    }
+
+    /// Renderizar la profundidad de una cascada vista desde la luz
+    fn record_shadow_pass(&self, encoder: &mut wgpu::CommandEncoder, items: &[DrawItem<'_>], cascade: usize) -> u32 {
+        let Some(layer_view) = self.shadow_maps.layer_views.get(cascade) else {
+            return 0;
+        };
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("shadow-pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: layer_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.shadow_pipeline);
+        pass.set_vertex_buffer(1, self.instance_buffer.buffer.slice(..));
+
+        let mut draw_calls = 0;
+        for (i, item) in items.iter().enumerate() {
+            if item.shadow_cascades & (1 << cascade) == 0 {
+                continue;
+            }
+            let Some(mesh) = self.meshes.get(item.mesh_id) else {
+                continue;
+            };
+            let offset = ((cascade * items.len() + i) as u64 * self.uniform_stride) as u32;
+            pass.set_bind_group(0, &self.shadow_uniforms.bind_group, &[offset]);
+            pass.set_vertex_buffer(0, self.vertex_buffer(item, mesh).slice(..));
+            pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            pass.draw_indexed(0..mesh.index_count, 0, item.instances.clone());
+            draw_calls += 1;
+        }
+        draw_calls
+    }
+}
+
+impl RenderBackend for WgpuBackend {
+    fn name(&self) -> &str {
+        "wgpu"
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        self.size = (width, height);
+
+        if let (Some(surface), Some(config)) = (&self.surface, &mut self.surface_config) {
+            config.width = width;
+            config.height = height;
+            surface.configure(&self.device, config);
+        }
+        if self.offscreen.is_some() {
+            self.offscreen = Some(Self::create_offscreen(&self.device, self.format, width, height));
+        }
+        self.resize_render_targets();
+    }
+
+    fn set_render_scale(&mut self, scale: f32) {
+        let scale = scale.clamp(0.1, 1.0);
+        if scale != self.render_scale {
+            self.render_scale = scale;
+            self.resize_render_targets();
+        }
+    }
+
+    fn set_sample_count(&mut self, sample_count: u32) {
+        let sample_count = Self::supported_sample_count(&self.adapter, HDR_FORMAT, sample_count);
+        if sample_count == self.sample_count {
+            return;
+        }
+        info!("MSAA x{} -> x{}", self.sample_count, sample_count);
+        self.sample_count = sample_count;
+        self.resize_render_targets();
+        self.pipeline = Arc::new(Self::create_default_pipeline(
+            &self.device,
+            &self.uniform_layout,
+            &self.frame_layout,
+            HDR_FORMAT,
+            sample_count,
+        ));
+        self.materials.set_sample_count(&self.device, sample_count);
+        self.environments.set_sample_count(&self.device, &self.uniform_layout, &self.frame_layout, HDR_FORMAT, sample_count);
+        self.particles.set_sample_count(&self.device, HDR_FORMAT, sample_count);
+    }
+
+    fn upload_mesh(&mut self, mesh: &Mesh) -> Result<()> {
+        if mesh.geometry.indices.is_empty() {
+            return Err(anyhow!("Mesh sin índices: {}", mesh.id));
+        }
+
+        let vertices: Vec<GpuVertex> = mesh.geometry.vertices.iter()
+            .map(|v| GpuVertex {
+                position: v.position.to_array(),
+                normal: v.normal.to_array(),
+                uv: [v.uv.x, v.uv.y],
+                color: v.color.to_array(),
+            })
+            .collect();
+
+        // Las mallas con skin o morph targets también se leen desde los compute passes
+        let influences = skinning::influences(&mesh.geometry);
+        let morph_targets = MorphTargets::new(&self.device, &mesh.id, &mesh.geometry, bytemuck::cast_slice(&vertices));
+        let vertex_usage = if influences.is_some() || morph_targets.is_some() {
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE
+        } else {
+            wgpu::BufferUsages::VERTEX
+        };
+        let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{}-vertices", mesh.id)),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: vertex_usage,
+        });
+        let influence_buffer = influences.map(|influences| {
+            self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{}-influences", mesh.id)),
+                contents: bytemuck::cast_slice(&influences),
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+        });
+        let index_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{}-indices", mesh.id)),
+            contents: bytemuck::cast_slice(&mesh.geometry.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        self.meshes.insert(mesh.id.clone(), GpuMesh {
+            vertex_buffer,
+            index_buffer,
+            index_count: mesh.geometry.indices.len() as u32,
+            vertex_count: vertices.len() as u32,
+            influence_buffer,
+            morph_targets,
+            bounds: Aabb::from_points(&mesh.geometry.vertices.iter().map(|v| v.position).collect::<Vec<_>>()),
+        });
+        if let Some(skinning) = &mut self.skinning {
+            skinning.invalidate_mesh(&mesh.id);
+        }
+        if let Some(morphing) = &mut self.morphing {
+            morphing.invalidate_mesh(&mesh.id);
+        }
+        Ok(())
+    }
+
+    fn has_mesh(&self, mesh_id: &str) -> bool {
+        self.meshes.contains_key(mesh_id)
+    }
+
+    fn remove_mesh(&mut self, mesh_id: &str) {
+        self.meshes.remove(mesh_id);
+        if let Some(skinning) = &mut self.skinning {
+            skinning.invalidate_mesh(mesh_id);
+        }
+        if let Some(morphing) = &mut self.morphing {
+            morphing.invalidate_mesh(mesh_id);
+        }
+    }
+
+    fn upload_texture(&mut self, texture: &TextureImage) -> Result<()> {
+        self.materials.upload_texture(&self.device, &self.queue, texture)
+    }
+
+    fn has_texture(&self, texture_id: &str) -> bool {
+        self.materials.has_texture(texture_id)
+    }
+
+    fn remove_texture(&mut self, texture_id: &str) {
+        self.materials.remove_texture(texture_id);
+    }
+
+    fn upload_material(&mut self, material: &MaterialDesc) -> Result<()> {
+        self.materials.upload_material(&self.device, &self.queue, material)
+    }
+
+    fn has_material(&self, material_id: &str) -> bool {
+        self.materials.has_material(material_id)
+    }
+
+    fn remove_material(&mut self, material_id: &str) {
+        self.materials.remove_material(material_id);
+    }
+
+    fn upload_environment(&mut self, environment: &EnvironmentMaps) -> Result<()> {
+        self.environments.upload(&self.device, &self.queue, environment)?;
+        self.frame_dirty |= self.frame_environment.as_deref() == Some(environment.id.as_str());
+        Ok(())
+    }
+
+    fn has_environment(&self, environment_id: &str) -> bool {
+        self.environments.has(environment_id)
+    }
+
+    fn remove_environment(&mut self, environment_id: &str) {
+        self.environments.remove(environment_id);
+        self.frame_dirty |= self.frame_environment.as_deref() == Some(environment_id);
+    }
+
+    fn upload_decal_texture(&mut self, texture: &TextureImage) -> Result<()> {
+        self.decals.upload(&self.queue, texture)
+    }
+
+    fn has_decal_texture(&self, texture_id: &str) -> bool {
+        self.decals.has(texture_id)
+    }
+
+    fn remove_decal_texture(&mut self, texture_id: &str) {
+        self.decals.remove(texture_id);
+    }
+
+    fn upload_font(&mut self, atlas: &FontAtlas) -> Result<()> {
+        self.text.upload(&self.device, &self.queue, atlas)
+    }
+
+    fn has_font(&self, font_id: &str) -> bool {
+        self.text.has(font_id)
+    }
+
+    fn remove_font(&mut self, font_id: &str) {
+        self.text.remove(font_id);
+    }
+
+    fn reload_material_shader(&mut self, kind: MaterialKind, source: &str) -> std::result::Result<(), ShaderError> {
+        self.materials.reload_shader(&self.device, kind, source)
+    }
+
+    fn render(&mut self, draw_list: &DrawList) -> Result<FrameStats> {
+        let mut encoder = self.begin_frame()?;
+        let pipeline = self.default_pipeline();
+        let stats = self.submit_draw(&mut encoder, &pipeline, draw_list);
+        self.end_frame(encoder);
+        stats
+    }
+
+    fn capture_next_frame(&mut self) -> ImageReadback {
+        let (sender, readback) = ImageReadback::channel();
+        self.frame_captures.push(sender);
+        readback
+    }
+
+    fn render_to_texture(&mut self, target_id: &str, draw_list: &DrawList, width: u32, height: u32) -> Result<FrameStats> {
+        let (width, height) = (width.max(1), height.max(1));
+        let texture = match self.render_textures.remove(target_id) {
+            Some(texture) if texture.width() == width && texture.height() == height => texture,
+            _ => Self::create_offscreen(&self.device, self.format, width, height),
+        };
+
+        // Los destinos internos pasan al tamaño del offscreen durante el
+        // frame; las capturas pedidas esperan al próximo frame principal y
+        // el historial temporal se reinicia al volver
+        let size = std::mem::replace(&mut self.size, (width, height));
+        let render_scale = std::mem::replace(&mut self.render_scale, 1.0);
+        let captures = std::mem::take(&mut self.frame_captures);
+        self.resize_render_targets();
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("render-to-texture-encoder"),
+        });
+        self.current_frame = Some(FrameTarget::Offscreen(texture.create_view(&wgpu::TextureViewDescriptor::default())));
+        let pipeline = self.default_pipeline();
+        let stats = self.submit_draw(&mut encoder, &pipeline, draw_list);
+        self.end_frame(encoder);
+
+        self.size = size;
+        self.render_scale = render_scale;
+        self.frame_captures = captures;
+        self.resize_render_targets();
+        self.render_textures.insert(target_id.to_string(), texture);
+        stats
+    }
+
+    fn has_render_texture(&self, target_id: &str) -> bool {
+        self.render_textures.contains_key(target_id)
+    }
+
+    fn read_render_texture(&mut self, target_id: &str) -> Result<ImageReadback> {
+        let texture = self.render_textures.get(target_id)
+            .ok_or_else(|| anyhow!("Destino offscreen no encontrado: {}", target_id))?;
+        let (sender, readback) = ImageReadback::channel();
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("render-texture-readback-encoder"),
+        });
+        self.readbacks.record(&self.device, &mut encoder, texture, sender);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.readbacks.finish(&self.device);
+        Ok(readback)
+    }
+
+    fn remove_render_texture(&mut self, target_id: &str) {
+        self.render_textures.remove(target_id);
+    }
+
+    fn copy_render_texture(&mut self, target_id: &str, destination: &wgpu::Texture) -> Result<()> {
+        let source = self.render_textures.get(target_id)
+            .ok_or_else(|| anyhow!("Destino offscreen no encontrado: {}", target_id))?;
+        if destination.width() < source.width() || destination.height() < source.height() {
+            return Err(anyhow!(
+                "Destino {}x{} menor que el offscreen {} ({}x{})",
+                destination.width(), destination.height(), target_id, source.width(), source.height(),
+            ));
+        }
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("render-texture-copy-encoder"),
+        });
+        encoder.copy_texture_to_texture(source.as_image_copy(), destination.as_image_copy(), source.size());
+        self.queue.submit(std::iter::once(encoder.finish()));
+        Ok(())
+    }
+
+    fn poll_readbacks(&mut self) {
+        self.readbacks.finish(&self.device);
+    }
+}
+
+/// Posición de la cámara por defecto
+pub const DEFAULT_EYE: glam::Vec3 = glam::Vec3::new(0.0, 0.0, 5.0);
+
+/// Matriz vista-proyección por defecto para el tamaño del destino
+pub fn default_view_projection(width: u32, height: u32) -> Mat4 {
+    let aspect = width.max(1) as f32 / height.max(1) as f32;
+    let projection = Mat4::perspective_rh(60f32.to_radians(), aspect, 0.1, 1000.0);
+    let view = Mat4::look_at_rh(DEFAULT_EYE, glam::Vec3::ZERO, glam::Vec3::Y);
+    projection * view
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{DrawCall, EnvironmentFrame, MaterialParams};
+    use crate::ecs::{DecalBlendMode, DecalComponent, RENDER_LAYER_DEFAULT, RENDER_LAYER_TERRAIN};
+    use crate::renderer::decals::{select_decals, DecalCandidate, MAX_DECALS};
+    use crate::renderer::environment::{EnvironmentSettings, EnvironmentSource, HdrImage};
+    use crate::renderer::{BoundingBox, BoundingSphere, Geometry, Vertex};
+    use glam::Vec3;
+
+    /// Backend headless sobre el adaptador de software si existe, o sobre
+    /// cualquiera; None si la máquina no tiene ninguno
+    async fn headless(width: u32, height: u32, sample_count: u32) -> Option<WgpuBackend> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let mut options = wgpu::RequestAdapterOptions {
+            force_fallback_adapter: true,
+            ..Default::default()
+        };
+        let adapter = match instance.request_adapter(&options).await {
+            Some(adapter) => adapter,
+            None => {
+                options.force_fallback_adapter = false;
+                instance.request_adapter(&options).await?
+            }
+        };
+        let (device, queue) = adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("test-device"),
+                required_features: REQUESTED_FEATURES & adapter.features(),
+                required_limits: wgpu::Limits::default().using_resolution(adapter.limits()),
+            },
+            None,
+        ).await.ok()?;
+        let options = WgpuBackendOptions {
+            width,
+            height,
+            sample_count,
+            precompile_shaders: false,
+            ..Default::default()
+        };
+        WgpuBackend::from_device(instance, adapter, device, queue, None, options).ok()
+    }
+
+    /// Mesh de un triángulo con los vértices dados en sentido antihorario
+    fn triangle(id: &str, corners: [Vec3; 3]) -> Mesh {
+        let min = corners[0].min(corners[1]).min(corners[2]);
+        let max = corners[0].max(corners[1]).max(corners[2]);
+        let center = (min + max) * 0.5;
+        Mesh {
+            id: id.to_string(),
+            name: id.to_string(),
+            geometry: Geometry {
+                vertices: corners.iter().map(|&position| Vertex {
+                    position,
+                    normal: Vec3::Z,
+                    tangent: Vec3::X,
+                    uv: Vec3::ZERO,
+                    color: Vec4::ONE,
+                }).collect(),
+                indices: vec![0, 1, 2],
+                bounding_box: BoundingBox { min, max },
+                bounding_sphere: BoundingSphere { center, radius: (max - center).length() },
+                joints: Vec::new(),
+                weights: Vec::new(),
+                morph_targets: Vec::new(),
+            },
+            material: None,
+            lod: Vec::new(),
+        }
+    }
+
+    /// Esfera UV de radio 1 centrada en el origen
+    fn sphere(id: &str, slices: u32, stacks: u32) -> Mesh {
+        let mut vertices = Vec::new();
+        for stack in 0..=stacks {
+            let phi = std::f32::consts::PI * stack as f32 / stacks as f32;
+            for slice in 0..=slices {
+                let theta = std::f32::consts::TAU * slice as f32 / slices as f32;
+                let normal = Vec3::new(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin());
+                vertices.push(Vertex {
+                    position: normal,
+                    normal,
+                    tangent: Vec3::new(-theta.sin(), 0.0, theta.cos()),
+                    uv: Vec3::new(slice as f32 / slices as f32, stack as f32 / stacks as f32, 0.0),
+                    color: Vec4::ONE,
+                });
+            }
+        }
+        // Antihorario visto desde fuera
+        let row = slices + 1;
+        let mut indices = Vec::new();
+        for stack in 0..stacks {
+            for slice in 0..slices {
+                let a = stack * row + slice;
+                let b = a + row;
+                indices.extend_from_slice(&[a, a + 1, b, a + 1, b + 1, b]);
+            }
+        }
+        Mesh {
+            id: id.to_string(),
+            name: id.to_string(),
+            geometry: Geometry {
+                vertices,
+                indices,
+                bounding_box: BoundingBox { min: Vec3::splat(-1.0), max: Vec3::ONE },
+                bounding_sphere: BoundingSphere { center: Vec3::ZERO, radius: 1.0 },
+                joints: Vec::new(),
+                weights: Vec::new(),
+                morph_targets: Vec::new(),
+            },
+            material: None,
+            lod: Vec::new(),
+        }
+    }
+
+    /// Rectángulo en z = 0.5 entre las esquinas (x, y) dadas, mirando a +Z
+    fn quad(id: &str, min: [f32; 2], max: [f32; 2]) -> Mesh {
+        let mut mesh = triangle(id, [
+            Vec3::new(min[0], min[1], 0.5),
+            Vec3::new(max[0], min[1], 0.5),
+            Vec3::new(max[0], max[1], 0.5),
+        ]);
+        let mut corner = mesh.geometry.vertices[0].clone();
+        corner.position = Vec3::new(min[0], max[1], 0.5);
+        mesh.geometry.vertices.push(corner);
+        mesh.geometry.indices.extend_from_slice(&[0, 2, 3]);
+        mesh
+    }
+
+    /// Píxel RGBA en (x, y) de una imagen de `width` de ancho
+    fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * width + x) * 4) as usize;
+        pixels[offset..offset + 4].try_into().unwrap()
+    }
+
+    #[tokio::test]
+    async fn headless_triangle_reads_back() {
+        let Some(mut backend) = headless(64, 64, 1).await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        let mesh = triangle("triangle", [
+            Vec3::new(-0.5, -0.5, 0.5),
+            Vec3::new(0.5, -0.5, 0.5),
+            Vec3::new(0.0, 0.5, 0.5),
+        ]);
+        backend.upload_mesh(&mesh).unwrap();
+
+        // Sin luces ni entorno el triángulo sale con su color base
+        let draw_list = DrawList {
+            view_projection: Mat4::IDENTITY,
+            clear_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            calls: vec![DrawCall {
+                mesh_id: "triangle".to_string(),
+                material_id: None,
+                transform: Mat4::IDENTITY,
+                base_color: Vec4::new(1.0, 0.0, 0.0, 1.0),
+                skin: None,
+                morph: None,
+                layers: crate::ecs::RENDER_LAYER_DEFAULT,
+                entity: None,
+                shadow_cascades: SHADOW_CASCADES_ALL,
+            }],
+            ..DrawList::default()
+        };
+        backend.render(&draw_list).unwrap();
+
+        let pixels = backend.read_pixels().unwrap();
+        assert_eq!(pixels.len(), 64 * 64 * 4);
+        assert_eq!(pixel(&pixels, 64, 32, 32), [255, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, 64, 2, 2), [0, 0, 0, 255]);
+    }
+
+    #[tokio::test]
+    async fn instanced_batches_draw_once_per_mesh_and_material() {
+        let Some(mut backend) = headless(64, 64, 1).await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        for id in ["left", "right"] {
+            backend.upload_mesh(&triangle(id, [
+                Vec3::new(-0.01, -0.01, 0.5),
+                Vec3::new(0.01, -0.01, 0.5),
+                Vec3::new(0.0, 0.01, 0.5),
+            ])).unwrap();
+        }
+        for (id, color) in [("red", Vec4::new(1.0, 0.0, 0.0, 1.0)), ("green", Vec4::new(0.0, 1.0, 0.0, 1.0))] {
+            backend.upload_material(&MaterialDesc {
+                id: id.to_string(),
+                kind: MaterialKind::Unlit,
+                params: MaterialParams { base_color: color, ..Default::default() },
+                textures: Default::default(),
+            }).unwrap();
+        }
+
+        // 5000 instancias repartidas en 2 meshes x 3 materiales (uno es el
+        // pipeline por defecto)
+        let materials = [None, Some("red"), Some("green")];
+        let mut draw_list = DrawList {
+            view_projection: Mat4::IDENTITY,
+            calls: (0..5000).map(|i| DrawCall {
+                mesh_id: if i % 2 == 0 { "left" } else { "right" }.to_string(),
+                material_id: materials[i / 2 % 3].map(str::to_string),
+                transform: Mat4::from_translation(Vec3::new((i % 100) as f32 / 50.0 - 1.0, (i / 100) as f32 / 25.0 - 1.0, 0.0)),
+                base_color: Vec4::ONE,
+                skin: None,
+                morph: None,
+                layers: crate::ecs::RENDER_LAYER_DEFAULT,
+                entity: None,
+                shadow_cascades: SHADOW_CASCADES_ALL,
+            }).collect(),
+            ..DrawList::default()
+        };
+        assert_eq!(draw_list.batch_instances(8), 6);
+        assert!(draw_list.calls.is_empty());
+        assert_eq!(draw_list.instanced.iter().map(|draw| draw.instances.len()).sum::<usize>(), 5000);
+
+        let stats = backend.render(&draw_list).unwrap();
+        assert_eq!(stats.draw_calls, 6);
+        assert_eq!(stats.triangles, 5000);
+    }
+
+    /// Subir un entorno equirectangular de 64x32 con `radiance` por dirección
+    /// y una esfera metálica pulida; la cámara la mira desde +Z
+    fn ibl_scene(backend: &mut WgpuBackend, radiance: impl Fn(Vec3) -> Vec3) -> DrawList {
+        let (width, height) = (64u32, 32u32);
+        let pixels = (0..height)
+            .flat_map(|row| (0..width).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                // Inversa de `EnvironmentSource::sample`
+                let azimuth = ((column as f32 + 0.5) / width as f32 - 0.5) * std::f32::consts::TAU;
+                let polar = (row as f32 + 0.5) / height as f32 * std::f32::consts::PI;
+                radiance(Vec3::new(polar.sin() * azimuth.cos(), polar.cos(), polar.sin() * azimuth.sin()))
+            })
+            .collect();
+        let source = EnvironmentSource::Equirectangular(HdrImage { width, height, pixels });
+        let settings = EnvironmentSettings { cube_size: 32, specular_levels: 4, irradiance_size: 8, sample_count: 16 };
+        backend.upload_environment(&EnvironmentMaps::precompute("sky", &source, &settings)).unwrap();
+
+        backend.upload_mesh(&sphere("sphere", 32, 16)).unwrap();
+        backend.upload_material(&MaterialDesc {
+            id: "chrome".to_string(),
+            kind: MaterialKind::Pbr,
+            params: MaterialParams { metallic: 1.0, roughness: 0.2, ..Default::default() },
+            textures: Default::default(),
+        }).unwrap();
+
+        let eye = Vec3::new(0.0, 0.0, 3.0);
+        let projection = Mat4::perspective_rh(60f32.to_radians(), 1.0, 0.1, 100.0);
+        DrawList {
+            view_projection: projection * Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y),
+            camera_position: eye,
+            clear_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            environment: Some(EnvironmentFrame { id: "sky".to_string(), intensity: 1.0, rotation: 0.0, skybox: false }),
+            calls: vec![DrawCall {
+                mesh_id: "sphere".to_string(),
+                material_id: Some("chrome".to_string()),
+                transform: Mat4::IDENTITY,
+                base_color: Vec4::ONE,
+                skin: None,
+                morph: None,
+                layers: crate::ecs::RENDER_LAYER_DEFAULT,
+                entity: None,
+                shadow_cascades: SHADOW_CASCADES_ALL,
+            }],
+            ..DrawList::default()
+        }
+    }
+
+    fn luminance(rgba: [u8; 4]) -> u32 {
+        rgba[..3].iter().map(|&channel| channel as u32).sum()
+    }
+
+    #[tokio::test]
+    async fn metallic_sphere_lit_only_by_ibl_is_not_black() {
+        let Some(mut backend) = headless(64, 64, 1).await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        let draw_list = ibl_scene(&mut backend, |_| Vec3::splat(0.5));
+        assert!(draw_list.light.is_none() && draw_list.lights.is_empty());
+        backend.render(&draw_list).unwrap();
+
+        let pixels = backend.read_pixels().unwrap();
+        // Centro y bordes de la esfera (radio de unos 19 píxeles)
+        for (x, y) in [(32, 32), (20, 32), (44, 32), (32, 20), (32, 44)] {
+            assert!(luminance(pixel(&pixels, 64, x, y)) > 30, "({}, {}) negro", x, y);
+        }
+        // Sin skybox el fondo es el color de limpieza
+        assert_eq!(pixel(&pixels, 64, 2, 2), [0, 0, 0, 255]);
+    }
+
+    #[tokio::test]
+    async fn rotating_the_environment_moves_the_reflection() {
+        let Some(mut backend) = headless(64, 64, 1).await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        // Cielo brillante hacia +X y casi negro hacia -X
+        let mut draw_list = ibl_scene(&mut backend, |direction| {
+            if direction.x > 0.0 { Vec3::splat(2.0) } else { Vec3::splat(0.02) }
+        });
+        let (left, right) = ((20, 32), (44, 32));
+
+        backend.render(&draw_list).unwrap();
+        let pixels = backend.read_pixels().unwrap();
+        let (left_before, right_before) = (pixel(&pixels, 64, left.0, left.1), pixel(&pixels, 64, right.0, right.1));
+        // El lado derecho de la esfera refleja +X
+        assert!(luminance(right_before) > luminance(left_before) + 60);
+
+        // Media vuelta: el reflejo brillante pasa al lado izquierdo
+        draw_list.environment.as_mut().unwrap().rotation = std::f32::consts::PI;
+        backend.render(&draw_list).unwrap();
+        let pixels = backend.read_pixels().unwrap();
+        let (left_after, right_after) = (pixel(&pixels, 64, left.0, left.1), pixel(&pixels, 64, right.0, right.1));
+        assert_ne!(left_after, left_before);
+        assert_ne!(right_after, right_before);
+        assert!(luminance(left_after) > luminance(right_after) + 60);
+    }
+
+    #[tokio::test]
+    async fn decal_paints_only_inside_its_box_and_layers() {
+        let Some(mut backend) = headless(64, 64, 1).await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        // Dos planos rojos: el izquierdo en la capa por defecto y el derecho
+        // en la de terreno, que el decal excluye
+        backend.upload_mesh(&quad("left", [-1.0, -1.0], [0.0, 1.0])).unwrap();
+        backend.upload_mesh(&quad("right", [0.0, -1.0], [1.0, 1.0])).unwrap();
+        backend.upload_decal_texture(&TextureImage {
+            id: "white".to_string(),
+            width: 4,
+            height: 4,
+            pixels: vec![255; 4 * 4 * 4],
+            srgb: true,
+            mips: Vec::new(),
+        }).unwrap();
+
+        // Caja de 1x1 centrada en el origen que proyecta verde sobre el plano
+        let decals = select_decals(vec![DecalCandidate {
+            entity: 1,
+            decal: DecalComponent {
+                texture_id: "white".to_string(),
+                size: Vec3::ONE,
+                color: Vec4::new(0.0, 1.0, 0.0, 1.0),
+                blend_mode: DecalBlendMode::Alpha,
+                fade_distance: 0.0,
+                layer_mask: RENDER_LAYER_DEFAULT,
+                priority: 0,
+            },
+            model: Mat4::from_translation(Vec3::new(0.0, 0.0, 0.5)),
+        }], Vec3::ZERO, MAX_DECALS);
+        let plane = |mesh_id: &str, layers: u32| DrawCall {
+            mesh_id: mesh_id.to_string(),
+            material_id: None,
+            transform: Mat4::IDENTITY,
+            base_color: Vec4::new(1.0, 0.0, 0.0, 1.0),
+            skin: None,
+            morph: None,
+            layers,
+            entity: None,
+            shadow_cascades: SHADOW_CASCADES_ALL,
+        };
+        let draw_list = DrawList {
+            view_projection: Mat4::IDENTITY,
+            clear_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            calls: vec![
+                plane("left", RENDER_LAYER_DEFAULT),
+                plane("right", RENDER_LAYER_TERRAIN),
+            ],
+            decals,
+            ..DrawList::default()
+        };
+        let stats = backend.render(&draw_list).unwrap();
+        assert_eq!(stats.decals, 1);
+
+        let pixels = backend.read_pixels().unwrap();
+        // Dentro de la caja (x en [-0.5, 0.5], y en [-0.5, 0.5]) el plano
+        // izquierdo es verde
+        for (x, y) in [(18, 32), (24, 20), (28, 44)] {
+            assert_eq!(pixel(&pixels, 64, x, y), [0, 255, 0, 255], "({}, {}) sin decal", x, y);
+        }
+        // Fuera de la caja sigue rojo
+        for (x, y) in [(8, 32), (24, 8), (24, 56)] {
+            assert_eq!(pixel(&pixels, 64, x, y), [255, 0, 0, 255], "({}, {}) pintado fuera de la caja", x, y);
+        }
+        // El plano de la capa excluida no se toca ni dentro de la caja
+        for (x, y) in [(36, 32), (46, 20), (40, 44)] {
+            assert_eq!(pixel(&pixels, 64, x, y), [255, 0, 0, 255], "({}, {}) capa excluida pintada", x, y);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! # Bloom
+//!
+//! Bloom físico sobre el render HDR con una cadena de mips. Un primer pase
+//! filtra los píxeles cuya luminancia supera `threshold` (con rodilla suave
+//! para que no aparezca un corte) y reduce a media resolución; le siguen
+//! reducciones Kawase duales hasta `mip_levels` niveles. La subida recorre
+//! la cadena al revés con un filtro tienda 3x3 y en cada nivel mezcla el mip
+//! con lo que sube del siguiente según `scatter`: con 0.0 solo queda el
+//! primer mip (halo estrecho) y con 1.0 domina el último (halo amplio). Como
+//! la mezcla es una media ponderada, la energía del bloom no depende del
+//! número de niveles. El resultado se suma al render HDR por `intensity`,
+//! antes del tonemapping.
+//!
+//! Los pases los declara `frame::build_frame_graph` como parte de la cadena
+//! de post-procesado; los shaders de este módulo se añaden a los de
+//! `backend::post` y comparten sus bindings.
+
+use serde::{Serialize, Deserialize};
+
+/// Niveles máximos de la cadena de mips
+pub const MAX_BLOOM_MIP_LEVELS: u8 = 8;
+
+/// Lado mínimo en píxeles del último mip de la cadena
+const MIN_MIP_SIZE: u32 = 2;
+
+/// Configuración del bloom
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BloomConfig {
+    /// Luminancia HDR a partir de la que un píxel contribuye
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+    /// Peso del bloom al sumarlo a la imagen
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+    /// Reparto entre halo estrecho (0.0) y amplio (1.0)
+    #[serde(default = "default_scatter")]
+    pub scatter: f32,
+    /// Niveles de la cadena de mips
+    #[serde(default = "default_mip_levels")]
+    pub mip_levels: u8,
+}
+
+fn default_threshold() -> f32 {
+    1.0
+}
+
+fn default_intensity() -> f32 {
+    0.3
+}
+
+fn default_scatter() -> f32 {
+    0.7
+}
+
+fn default_mip_levels() -> u8 {
+    6
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        Self {
+            threshold: default_threshold(),
+            intensity: default_intensity(),
+            scatter: default_scatter(),
+            mip_levels: default_mip_levels(),
+        }
+    }
+}
+
+/// Parámetros del bloom de un frame, validados
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloomPass {
+    /// Luminancia HDR a partir de la que un píxel contribuye
+    pub threshold: f32,
+    /// Peso del bloom al sumarlo a la imagen
+    pub intensity: f32,
+    /// Reparto entre halo estrecho (0.0) y amplio (1.0)
+    pub scatter: f32,
+    /// Niveles de la cadena de mips
+    pub mip_levels: u8,
+}
+
+impl Default for BloomPass {
+    fn default() -> Self {
+        Self::from_config(&BloomConfig::default())
+    }
+}
+
+impl BloomPass {
+    /// Parámetros a partir de la configuración, dentro de sus rangos
+    pub fn from_config(config: &BloomConfig) -> Self {
+        Self {
+            threshold: config.threshold.max(0.0),
+            intensity: config.intensity.max(0.0),
+            scatter: config.scatter.clamp(0.0, 1.0),
+            mip_levels: config.mip_levels.clamp(1, MAX_BLOOM_MIP_LEVELS),
+        }
+    }
+
+    /// Tamaños de la cadena de mips para un render de `width` x `height`,
+    /// empezando a media resolución. Se corta antes si un mip bajaría de
+    /// `MIN_MIP_SIZE` píxeles
+    pub fn mip_sizes(&self, width: u32, height: u32) -> Vec<(u32, u32)> {
+        let mut size = ((width / 2).max(1), (height / 2).max(1));
+        let mut sizes = vec![size];
+        while sizes.len() < self.mip_levels.max(1) as usize {
+            size = (size.0 / 2, size.1 / 2);
+            if size.0 < MIN_MIP_SIZE || size.1 < MIN_MIP_SIZE {
+                break;
+            }
+            sizes.push(size);
+        }
+        sizes
+    }
+
+    /// Rodilla de la curva de umbral: la contribución crece de forma
+    /// cuadrática entre `threshold - knee` y `threshold + knee`
+    pub fn knee(&self) -> f32 {
+        self.threshold * 0.5
+    }
+}
+
+/// Shaders del bloom. Usan los bindings de `backend::post`: `source` es el
+/// nivel que se lee y `aux` el mip inferior que sube
+pub(crate) const BLOOM_SHADER: &str = r#"
+fn bloom_threshold(color: vec3<f32>) -> vec3<f32> {
+    // x = umbral, w = rodilla
+    let threshold = post.bloom.x;
+    let knee = max(post.bloom.w, 1e-4);
+    let brightness = luminance(color);
+    var soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - threshold) / max(brightness, 1e-4);
+    return color * contribution;
+}
+
+// Media de Karis: pesa cada muestra por el inverso de su luminancia para
+// que un píxel aislado muy brillante no parpadee
+fn karis_weight(color: vec3<f32>) -> f32 {
+    return 1.0 / (1.0 + luminance(color));
+}
+
+@fragment
+fn fs_bloom_prefilter(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    var color = vec3<f32>(0.0);
+    var total = 0.0;
+    var offsets = array<vec2<f32>, 5>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    for (var i = 0u; i < 5u; i++) {
+        let tap = bloom_threshold(textureSample(source, linear_sampler, input.uv + offsets[i] * texel).rgb);
+        let weight = karis_weight(tap) * select(1.0, 4.0, i == 0u);
+        color += tap * weight;
+        total += weight;
+    }
+    return vec4<f32>(color / max(total, 1e-4), 1.0);
+}
+
+@fragment
+fn fs_bloom_downsample(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    // Kawase dual: centro y cuatro diagonales a medio texel del nivel anterior
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(source));
+    var color = textureSample(source, linear_sampler, input.uv).rgb * 4.0;
+    color += textureSample(source, linear_sampler, input.uv + vec2<f32>(-1.0, -1.0) * half_texel).rgb;
+    color += textureSample(source, linear_sampler, input.uv + vec2<f32>(1.0, -1.0) * half_texel).rgb;
+    color += textureSample(source, linear_sampler, input.uv + vec2<f32>(-1.0, 1.0) * half_texel).rgb;
+    color += textureSample(source, linear_sampler, input.uv + vec2<f32>(1.0, 1.0) * half_texel).rgb;
+    return vec4<f32>(color / 8.0, 1.0);
+}
+
+@fragment
+fn fs_bloom_upsample(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    // Tienda 3x3 sobre el mip inferior
+    let texel = 1.0 / vec2<f32>(textureDimensions(aux));
+    var lower = textureSample(aux, linear_sampler, input.uv).rgb * 4.0;
+    lower += textureSample(aux, linear_sampler, input.uv + vec2<f32>(-texel.x, 0.0)).rgb * 2.0;
+    lower += textureSample(aux, linear_sampler, input.uv + vec2<f32>(texel.x, 0.0)).rgb * 2.0;
+    lower += textureSample(aux, linear_sampler, input.uv + vec2<f32>(0.0, -texel.y)).rgb * 2.0;
+    lower += textureSample(aux, linear_sampler, input.uv + vec2<f32>(0.0, texel.y)).rgb * 2.0;
+    lower += textureSample(aux, linear_sampler, input.uv + vec2<f32>(-texel.x, -texel.y)).rgb;
+    lower += textureSample(aux, linear_sampler, input.uv + vec2<f32>(texel.x, -texel.y)).rgb;
+    lower += textureSample(aux, linear_sampler, input.uv + vec2<f32>(-texel.x, texel.y)).rgb;
+    lower += textureSample(aux, linear_sampler, input.uv + vec2<f32>(texel.x, texel.y)).rgb;
+    let current = textureSample(source, linear_sampler, input.uv).rgb;
+    // z = scatter
+    return vec4<f32>(mix(current, lower / 16.0, post.bloom.z), 1.0);
+}
+
+@fragment
+fn fs_bloom_composite(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source, linear_sampler, input.uv);
+    let bloom = textureSample(aux, linear_sampler, input.uv).rgb;
+    // y = intensidad
+    return vec4<f32>(color.rgb + bloom * post.bloom.y, color.a);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_is_clamped_and_the_chain_stops_at_the_minimum_size() {
+        let pass = BloomPass::from_config(&BloomConfig { threshold: -1.0, intensity: -0.5, scatter: 3.0, mip_levels: 20 });
+        assert_eq!(pass, BloomPass { threshold: 0.0, intensity: 0.0, scatter: 1.0, mip_levels: MAX_BLOOM_MIP_LEVELS });
+
+        let pass = BloomPass::default();
+        assert_eq!(pass.mip_sizes(64, 64), [(32, 32), (16, 16), (8, 8), (4, 4), (2, 2)]);
+        assert_eq!(pass.mip_sizes(1920, 1080), [(960, 540), (480, 270), (240, 135), (120, 67), (60, 33), (30, 16)]);
+        assert_eq!(pass.knee(), 0.5);
+    }
+}
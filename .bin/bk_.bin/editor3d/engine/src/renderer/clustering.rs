@@ -0,0 +1,354 @@
+//! # Clustered shading
+//!
+//! Iluminación de cientos de luces puntuales y focos sin recorrerlas todas
+//! en cada fragmento. El frustum de la cámara se divide en una rejilla 3D de
+//! clusters: `grid[0]` x `grid[1]` baldosas en pantalla y `grid[2]` cortes
+//! en profundidad, repartidos de forma logarítmica entre near y far para
+//! que los clusters cercanos no sean mucho más finos que los lejanos. Cada
+//! frame un compute shader del backend prueba la esfera de influencia de
+//! cada luz contra la caja en espacio vista de cada cluster y guarda los
+//! índices de las que lo alcanzan; el fragment shader solo recorre las luces
+//! de su cluster.
+//!
+//! Cada cluster tiene hueco para `max_lights_per_cluster` luces. Las que no
+//! caben se descartan en ese cluster (primero las más débiles: el renderer
+//! ordena las luces por intensidad) y se cuentan en `ClusterStats`; con GPU
+//! los contadores llegan con un frame de retraso.
+//!
+//! `assign_lights` hace la misma asignación en la CPU para el backend
+//! simulado.
+
+use glam::{Mat4, Vec3, Vec4};
+use serde::{Serialize, Deserialize};
+
+use super::culling::Aabb;
+
+/// Luces máximas por cluster admitidas en la configuración
+pub const MAX_LIGHTS_PER_CLUSTER: u32 = 256;
+
+/// Clusters máximos por eje admitidos en la configuración
+pub const MAX_CLUSTER_GRID: u32 = 64;
+
+/// Configuración del clustered shading
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LightClusterConfig {
+    /// Clusters en horizontal, en vertical y en profundidad
+    #[serde(default = "default_grid")]
+    pub grid: [u32; 3],
+    /// Luces que caben en un cluster; las demás se descartan en él
+    #[serde(default = "default_max_lights_per_cluster")]
+    pub max_lights_per_cluster: u32,
+}
+
+fn default_grid() -> [u32; 3] {
+    [16, 9, 24]
+}
+
+fn default_max_lights_per_cluster() -> u32 {
+    64
+}
+
+impl Default for LightClusterConfig {
+    fn default() -> Self {
+        Self {
+            grid: default_grid(),
+            max_lights_per_cluster: default_max_lights_per_cluster(),
+        }
+    }
+}
+
+/// Rejilla de clusters validada
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClusterGrid {
+    /// Clusters por eje
+    pub size: [u32; 3],
+    /// Luces que caben en un cluster
+    pub max_lights_per_cluster: u32,
+}
+
+impl ClusterGrid {
+    /// Rejilla a partir de la configuración, dentro de sus rangos
+    pub fn from_config(config: &LightClusterConfig) -> Self {
+        Self {
+            size: config.grid.map(|count| count.clamp(1, MAX_CLUSTER_GRID)),
+            max_lights_per_cluster: config.max_lights_per_cluster.clamp(1, MAX_LIGHTS_PER_CLUSTER),
+        }
+    }
+
+    /// Número de clusters
+    pub fn cluster_count(&self) -> usize {
+        self.size.iter().map(|&count| count as usize).product()
+    }
+
+    /// Índice lineal de un cluster (x varía más rápido)
+    pub fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        (x + self.size[0] * (y + self.size[1] * z)) as usize
+    }
+}
+
+/// Tipo de una luz local
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocalLightKind {
+    /// Luz puntual
+    Point,
+    /// Foco: ilumina dentro de un cono alrededor de `direction`
+    Spot {
+        /// Dirección hacia la que apunta
+        direction: Vec3,
+        /// Coseno del semiángulo hasta el que la luz es completa
+        inner_cos: f32,
+        /// Coseno del semiángulo a partir del que no ilumina
+        outer_cos: f32,
+    },
+}
+
+/// Luz puntual o foco del frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocalLight {
+    /// Posición en mundo
+    pub position: Vec3,
+    /// Distancia a la que la luz se anula
+    pub range: f32,
+    /// Color por intensidad
+    pub color: Vec3,
+    /// Tipo de luz
+    pub kind: LocalLightKind,
+}
+
+/// Clustering de un frame: rejilla y cámara sin jitter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClusterFrame {
+    /// Rejilla de clusters
+    pub grid: ClusterGrid,
+    /// Matriz de vista de la cámara
+    pub view: Mat4,
+    /// Proyección de la cámara
+    pub projection: Mat4,
+    /// Profundidad en vista del primer corte
+    pub near: f32,
+    /// Profundidad en vista del último corte
+    pub far: f32,
+}
+
+impl ClusterFrame {
+    /// Clustering de una cámara con planos `near` y `far`
+    pub fn new(config: &LightClusterConfig, view: Mat4, projection: Mat4, near: f32, far: f32) -> Self {
+        let near = near.max(1e-3);
+        Self {
+            grid: ClusterGrid::from_config(config),
+            view,
+            projection,
+            near,
+            far: far.max(near * 1.001),
+        }
+    }
+
+    /// Escala y sesgo del corte: `slice = log(depth) * escala + sesgo`
+    pub fn slice_params(&self) -> (f32, f32) {
+        let scale = self.grid.size[2] as f32 / (self.far / self.near).ln();
+        (scale, -self.near.ln() * scale)
+    }
+
+    /// Profundidad en vista donde empieza el corte `slice`
+    pub fn slice_depth(&self, slice: u32) -> f32 {
+        self.near * (self.far / self.near).powf(slice as f32 / self.grid.size[2] as f32)
+    }
+
+    /// Caja en espacio vista de un cluster: los rayos de las esquinas de su
+    /// baldosa cortados por los planos de su corte
+    pub fn cluster_bounds(&self, x: u32, y: u32, z: u32) -> Aabb {
+        let inverse_projection = self.projection.inverse();
+        let [columns, rows, _] = self.grid.size;
+        let depths = [self.slice_depth(z), self.slice_depth(z + 1)];
+        let mut points = Vec::with_capacity(8);
+        for corner in 0..4 {
+            let u = (x + (corner & 1)) as f32 / columns as f32;
+            let v = (y + (corner >> 1)) as f32 / rows as f32;
+            let ndc = (u * 2.0 - 1.0, 1.0 - v * 2.0);
+            let unproject = |depth: f32| {
+                let point = inverse_projection * Vec4::new(ndc.0, ndc.1, depth, 1.0);
+                point.truncate() / point.w
+            };
+            // Dos puntos del rayo de la esquina (sirve para perspectiva y ortográfica)
+            let start = unproject(0.0);
+            let end = unproject(0.5);
+            let along = end.z - start.z;
+            for depth in depths {
+                let t = if along.abs() > 1e-6 { (-depth - start.z) / along } else { 0.0 };
+                points.push(start + (end - start) * t);
+            }
+        }
+        Aabb::from_points(&points)
+    }
+
+    /// Cluster que contiene un punto en mundo, como en el fragment shader.
+    /// None detrás de la cámara
+    pub fn cluster_at(&self, position: Vec3) -> Option<usize> {
+        let view_position = self.view * position.extend(1.0);
+        let clip = self.projection * view_position;
+        if clip.w <= 1e-5 {
+            return None;
+        }
+        let [columns, rows, slices] = self.grid.size;
+        let u = (clip.x / clip.w * 0.5 + 0.5).clamp(0.0, 0.9999);
+        let v = (0.5 - clip.y / clip.w * 0.5).clamp(0.0, 0.9999);
+        let (scale, bias) = self.slice_params();
+        let depth = (-view_position.z).max(self.near);
+        let slice = ((depth.ln() * scale + bias).max(0.0) as u32).min(slices - 1);
+        Some(self.grid.index((u * columns as f32) as u32, (v * rows as f32) as u32, slice))
+    }
+}
+
+/// Contadores del clustering de un frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClusterStats {
+    /// Luces locales asignadas
+    pub lights: u32,
+    /// Clusters con más luces de las que caben
+    pub overflowed_clusters: u32,
+    /// Referencias a luces descartadas en los clusters desbordados
+    pub dropped_lights: u32,
+    /// Mayor número de luces que alcanzan un mismo cluster
+    pub max_cluster_lights: u32,
+}
+
+/// Luces asignadas a cada cluster
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClusterAssignment {
+    /// Luces de cada cluster (como mucho `max_lights_per_cluster`)
+    pub counts: Vec<u32>,
+    /// Índices de luces: `max_lights_per_cluster` huecos por cluster
+    pub indices: Vec<u32>,
+    /// Contadores del frame
+    pub stats: ClusterStats,
+}
+
+impl ClusterAssignment {
+    /// Índices de las luces de un cluster
+    pub fn cluster_lights(&self, cluster: usize, max_lights_per_cluster: u32) -> &[u32] {
+        let start = cluster * max_lights_per_cluster as usize;
+        &self.indices[start..start + self.counts[cluster] as usize]
+    }
+}
+
+/// Asignar las luces a los clusters en la CPU, con la misma prueba que el
+/// compute shader del backend
+pub fn assign_lights(frame: &ClusterFrame, lights: &[LocalLight]) -> ClusterAssignment {
+    let grid = frame.grid;
+    let cap = grid.max_lights_per_cluster as usize;
+    let mut assignment = ClusterAssignment {
+        counts: vec![0; grid.cluster_count()],
+        indices: vec![0; grid.cluster_count() * cap],
+        stats: ClusterStats { lights: lights.len() as u32, ..Default::default() },
+    };
+    let spheres: Vec<(Vec3, f32)> = lights.iter()
+        .map(|light| ((frame.view * light.position.extend(1.0)).truncate(), light.range.max(0.0)))
+        .collect();
+
+    for z in 0..grid.size[2] {
+        for y in 0..grid.size[1] {
+            for x in 0..grid.size[0] {
+                let cluster = grid.index(x, y, z);
+                let bounds = frame.cluster_bounds(x, y, z);
+                let mut total = 0;
+                for (i, (center, radius)) in spheres.iter().enumerate() {
+                    if sphere_intersects(&bounds, *center, *radius) {
+                        if total < cap {
+                            assignment.indices[cluster * cap + total] = i as u32;
+                        }
+                        total += 1;
+                    }
+                }
+                assignment.counts[cluster] = total.min(cap) as u32;
+                let stats = &mut assignment.stats;
+                stats.max_cluster_lights = stats.max_cluster_lights.max(total as u32);
+                if total > cap {
+                    stats.overflowed_clusters += 1;
+                    stats.dropped_lights += (total - cap) as u32;
+                }
+            }
+        }
+    }
+    assignment
+}
+
+/// Verificar si una esfera alcanza una caja
+fn sphere_intersects(bounds: &Aabb, center: Vec3, radius: f32) -> bool {
+    let closest = center.clamp(bounds.min, bounds.max);
+    closest.distance_squared(center) <= radius * radius
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Centro del grupo de luces de los tests
+    const CENTER: Vec3 = Vec3::new(0.0, 0.0, -6.0);
+
+    /// Cámara en el origen mirando a -Z con la rejilla por defecto
+    fn frame() -> ClusterFrame {
+        let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let projection = Mat4::perspective_rh(60f32.to_radians(), 16.0 / 9.0, 0.1, 100.0);
+        ClusterFrame::new(&LightClusterConfig::default(), view, projection, 0.1, 100.0)
+    }
+
+    /// Luz puntual blanca
+    fn point(position: Vec3, range: f32) -> LocalLight {
+        LocalLight { position, range, color: Vec3::ONE, kind: LocalLightKind::Point }
+    }
+
+    /// 500 luces pequeñas en espiral a menos de 1 m de `CENTER`; todas
+    /// alcanzan el cluster de `CENTER`
+    fn crowded_lights() -> Vec<LocalLight> {
+        (0..500)
+            .map(|i| {
+                let t = i as f32 / 500.0;
+                let angle = i as f32 * 2.399;
+                let offset = Vec3::new(angle.cos() * t, angle.sin() * t, (t - 0.5) * 0.5);
+                point(CENTER + offset, 2.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn crowded_clusters_are_capped_and_report_the_overflow() {
+        let frame = frame();
+        let cap = frame.grid.max_lights_per_cluster;
+        let assignment = assign_lights(&frame, &crowded_lights());
+
+        assert!(assignment.counts.iter().all(|&count| count <= cap));
+        let center = frame.cluster_at(CENTER).unwrap();
+        // Se quedan las primeras: el renderer las ordena por intensidad
+        let kept: Vec<u32> = (0..cap).collect();
+        assert_eq!(assignment.cluster_lights(center, cap), kept.as_slice());
+
+        let stats = assignment.stats;
+        assert_eq!(stats.lights, 500);
+        assert_eq!(stats.max_cluster_lights, 500);
+        assert!(stats.overflowed_clusters >= 1);
+        let full = assignment.counts.iter().filter(|&&count| count == cap).count() as u32;
+        assert!(stats.dropped_lights >= 500 - cap);
+        assert!(stats.overflowed_clusters <= full);
+    }
+
+    #[test]
+    fn lights_outside_the_frustum_reach_no_cluster() {
+        let frame = frame();
+        let cap = frame.grid.max_lights_per_cluster;
+        let lights = [
+            point(CENTER, 1.0),
+            // Detrás de la cámara y muy a un lado
+            point(Vec3::new(0.0, 0.0, 5.0), 1.0),
+            point(Vec3::new(30.0, 0.0, -5.0), 1.0),
+        ];
+        let assignment = assign_lights(&frame, &lights);
+
+        let reached = |light: u32| (0..frame.grid.cluster_count())
+            .filter(|&cluster| assignment.cluster_lights(cluster, cap).contains(&light))
+            .count();
+        assert!(reached(0) > 0);
+        assert_eq!(reached(1), 0);
+        assert_eq!(reached(2), 0);
+        assert_eq!(frame.cluster_at(Vec3::new(0.0, 0.0, 5.0)), None);
+    }
+}
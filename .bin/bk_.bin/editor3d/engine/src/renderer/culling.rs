@@ -0,0 +1,334 @@
+//! # Culling
+//!
+//! Frustum y distance culling del renderer. Las cajas envolventes de las
+//! entidades se indexan en un octree que se consulta con el frustum de la
+//! cámara activa en cada frame.
+
+use glam::{Mat4, Vec3, Vec4};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::ecs::EntityId;
+
+/// Caja envolvente alineada a los ejes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    /// Mínimo
+    pub min: Vec3,
+    /// Máximo
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Crear caja
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Caja que contiene todos los puntos
+    pub fn from_points(points: &[Vec3]) -> Self {
+        if points.is_empty() {
+            return Self::new(Vec3::ZERO, Vec3::ZERO);
+        }
+        points.iter().fold(
+            Self::new(Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |aabb, p| Self::new(aabb.min.min(*p), aabb.max.max(*p)),
+        )
+    }
+
+    /// Centro
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Semiextensión
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Caja en el espacio de la transformación (método de Arvo)
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        let center = matrix.transform_point3(self.center());
+        // Cada eje del resultado es la suma de las columnas absolutas escaladas
+        let abs = Mat4::from_cols(
+            matrix.x_axis.abs(),
+            matrix.y_axis.abs(),
+            matrix.z_axis.abs(),
+            Vec4::W,
+        );
+        let extent = abs.transform_vector3(self.half_extents());
+        Self::new(center - extent, center + extent)
+    }
+
+    /// Verificar si contiene completamente a otra caja
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.min).all() && self.max.cmpge(other.max).all()
+    }
+
+    /// Verificar si se solapa con otra caja
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    /// Distancia desde un punto (0 si está dentro)
+    pub fn distance_to(&self, point: Vec3) -> f32 {
+        point.clamp(self.min, self.max).distance(point)
+    }
+}
+
+/// Frustum de la cámara como seis planos (normal hacia el interior)
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    /// Planos (xyz = normal, w = distancia)
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extraer el frustum de una matriz vista-proyección (profundidad 0..1)
+    pub fn from_view_projection(view_projection: &Mat4) -> Self {
+        let r0 = view_projection.row(0);
+        let r1 = view_projection.row(1);
+        let r2 = view_projection.row(2);
+        let r3 = view_projection.row(3);
+
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2]
+            .map(|plane| plane / plane.truncate().length().max(f32::EPSILON));
+        Self { planes }
+    }
+
+    /// Verificar si una caja intersecta el frustum
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            // Vértice de la caja más adelantado según la normal del plano
+            let positive = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            normal.dot(positive) + plane.w >= 0.0
+        })
+    }
+}
+
+/// Configuración del culling
+#[derive(Debug, Clone)]
+pub struct CullingSettings {
+    /// Frustum culling
+    pub frustum_culling: bool,
+    /// Distance culling
+    pub distance_culling: bool,
+    /// Distancia máxima de dibujo
+    pub max_distance: f32,
+    /// Profundidad máxima del octree
+    pub max_depth: u32,
+    /// Tamaño mínimo de nodo del octree
+    pub min_node_size: f32,
+}
+
+impl Default for CullingSettings {
+    fn default() -> Self {
+        Self {
+            frustum_culling: true,
+            distance_culling: false,
+            max_distance: 1000.0,
+            max_depth: 8,
+            min_node_size: 1.0,
+        }
+    }
+}
+
+/// Nodo del octree
+#[derive(Debug, Clone)]
+struct OctreeNode {
+    /// Región del nodo
+    bounds: Aabb,
+    /// Profundidad
+    depth: u32,
+    /// Hijos (índices en el arena)
+    children: Option<[usize; 8]>,
+    /// Entidades alojadas en el nodo
+    items: Vec<EntityId>,
+}
+
+/// Octree de cajas envolventes de entidades
+#[derive(Debug, Clone)]
+pub struct SpatialIndex {
+    /// Nodos (el 0 es la raíz)
+    nodes: Vec<OctreeNode>,
+    /// Nodo y caja de cada entidad
+    locations: HashMap<EntityId, (usize, Aabb)>,
+    /// Profundidad máxima
+    max_depth: u32,
+    /// Tamaño mínimo de nodo
+    min_node_size: f32,
+}
+
+impl SpatialIndex {
+    /// Crear índice sobre una región del mundo
+    pub fn new(world_bounds: Aabb, max_depth: u32, min_node_size: f32) -> Self {
+        Self {
+            nodes: vec![OctreeNode { bounds: world_bounds, depth: 0, children: None, items: Vec::new() }],
+            locations: HashMap::new(),
+            max_depth,
+            min_node_size,
+        }
+    }
+
+    /// Insertar o actualizar la caja de una entidad
+    pub fn update(&mut self, entity: EntityId, aabb: Aabb) {
+        if let Some((_, current)) = self.locations.get(&entity) {
+            if *current == aabb {
+                return;
+            }
+            self.remove(entity);
+        }
+
+        // Descender mientras algún hijo contenga la caja completa; lo que queda
+        // fuera de la raíz se aloja en ella
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            let child_size = (node.bounds.max - node.bounds.min).min_element() * 0.5;
+            if node.depth >= self.max_depth || child_size < self.min_node_size {
+                break;
+            }
+            let existing = node.children;
+            let children = match existing {
+                Some(children) => children,
+                None => self.split(index),
+            };
+            match children.iter().find(|&&child| self.nodes[child].bounds.contains(&aabb)) {
+                Some(&child) => index = child,
+                None => break,
+            }
+        }
+
+        self.nodes[index].items.push(entity);
+        self.locations.insert(entity, (index, aabb));
+    }
+
+    /// Eliminar una entidad
+    pub fn remove(&mut self, entity: EntityId) {
+        if let Some((index, _)) = self.locations.remove(&entity) {
+            let items = &mut self.nodes[index].items;
+            if let Some(position) = items.iter().position(|e| *e == entity) {
+                items.swap_remove(position);
+            }
+        }
+    }
+
+    /// Entidades indexadas
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Verificar si está vacío
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Caja indexada de una entidad
+    pub fn bounds(&self, entity: EntityId) -> Option<Aabb> {
+        self.locations.get(&entity).map(|(_, aabb)| *aabb)
+    }
+
+    /// Entidades cuya caja intersecta el frustum
+    pub fn query_frustum(&self, frustum: &Frustum, out: &mut Vec<EntityId>) {
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            // La raíz aloja entidades fuera de su región: siempre se recorre
+            if index != 0 && !frustum.intersects(&node.bounds) {
+                continue;
+            }
+            for entity in &node.items {
+                if let Some((_, aabb)) = self.locations.get(entity) {
+                    if frustum.intersects(aabb) {
+                        out.push(*entity);
+                    }
+                }
+            }
+            if let Some(children) = node.children {
+                stack.extend(children);
+            }
+        }
+    }
+
+    /// Todas las entidades indexadas
+    pub fn all(&self, out: &mut Vec<EntityId>) {
+        out.extend(self.locations.keys().copied());
+    }
+
+    /// Vaciar índice
+    pub fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[0].children = None;
+        self.nodes[0].items.clear();
+        self.locations.clear();
+    }
+
+    /// Subdividir un nodo en ocho hijos
+    fn split(&mut self, index: usize) -> [usize; 8] {
+        let bounds = self.nodes[index].bounds;
+        let depth = self.nodes[index].depth + 1;
+        let center = bounds.center();
+        let first = self.nodes.len();
+
+        for octant in 0..8 {
+            let pick = |bit: usize, axis_min: f32, axis_center: f32, axis_max: f32| {
+                if octant & bit == 0 { (axis_min, axis_center) } else { (axis_center, axis_max) }
+            };
+            let (min_x, max_x) = pick(1, bounds.min.x, center.x, bounds.max.x);
+            let (min_y, max_y) = pick(2, bounds.min.y, center.y, bounds.max.y);
+            let (min_z, max_z) = pick(4, bounds.min.z, center.z, bounds.max.z);
+            self.nodes.push(OctreeNode {
+                bounds: Aabb::new(Vec3::new(min_x, min_y, min_z), Vec3::new(max_x, max_y, max_z)),
+                depth,
+                children: None,
+                items: Vec::new(),
+            });
+        }
+
+        let children = std::array::from_fn(|i| first + i);
+        self.nodes[index].children = Some(children);
+        children
+    }
+}
+
+/// Caché de cajas locales por mesh
+#[derive(Debug, Clone, Default)]
+pub struct MeshBoundsCache {
+    /// Huella y caja por mesh_id
+    entries: HashMap<String, (u64, Aabb)>,
+}
+
+impl MeshBoundsCache {
+    /// Caja local de un mesh, recalculada sólo si sus vértices cambiaron
+    pub fn get(&mut self, mesh_id: &str, vertices: &[Vec3]) -> Aabb {
+        let fingerprint = Self::fingerprint(vertices);
+        if let Some((cached, aabb)) = self.entries.get(mesh_id) {
+            if *cached == fingerprint {
+                return *aabb;
+            }
+        }
+        let aabb = Aabb::from_points(vertices);
+        self.entries.insert(mesh_id.to_string(), (fingerprint, aabb));
+        aabb
+    }
+
+    /// Eliminar un mesh
+    pub fn remove(&mut self, mesh_id: &str) {
+        self.entries.remove(mesh_id);
+    }
+
+    /// Huella de los vértices: número de vértices y una muestra espaciada
+    fn fingerprint(vertices: &[Vec3]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        vertices.len().hash(&mut hasher);
+        let step = (vertices.len() / 16).max(1);
+        for v in vertices.iter().step_by(step) {
+            v.x.to_bits().hash(&mut hasher);
+            v.y.to_bits().hash(&mut hasher);
+            v.z.to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
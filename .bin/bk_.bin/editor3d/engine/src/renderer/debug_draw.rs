@@ -0,0 +1,323 @@
+//! # Debug draw
+//!
+//! Capa de dibujo inmediato para depurar colisiones, navegación y cámaras:
+//! segmentos, cajas, esferas, cápsulas, ejes y frustums en mundo que se
+//! acumulan durante el frame y el renderer entrega al backend en la lista
+//! de dibujo. Los backends los dibujan como líneas sobre la imagen ya
+//! post-procesada, antes del texto. Cada primitiva se prueba contra la
+//! profundidad de la escena o se dibuja siempre encima según el estado de
+//! `set_depth_test` al pedirla; sin depth buffer muestreable (MSAA) todas
+//! se dibujan encima.
+//!
+//! Con la capa desactivada las llamadas vuelven al instante sin reservar
+//! memoria, así que pueden quedarse en el código de juego.
+
+use glam::{Mat4, Vec3, Vec4};
+
+use super::culling::Aabb;
+
+/// Segmentos con los que se aproxima un círculo
+pub const CIRCLE_SEGMENTS: u32 = 24;
+
+/// Colores de los ejes X, Y y Z
+const AXIS_COLORS: [Vec4; 3] = [
+    Vec4::new(1.0, 0.2, 0.2, 1.0),
+    Vec4::new(0.2, 1.0, 0.2, 1.0),
+    Vec4::new(0.3, 0.5, 1.0, 1.0),
+];
+
+/// Vértice de un segmento
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugVertex {
+    /// Posición en mundo
+    pub position: Vec3,
+    /// Color lineal con alfa
+    pub color: Vec4,
+}
+
+/// Segmentos de un frame: dos vértices por segmento
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DebugDrawFrame {
+    /// Segmentos probados contra la profundidad de la escena
+    pub depth_tested: Vec<DebugVertex>,
+    /// Segmentos dibujados siempre encima
+    pub overlay: Vec<DebugVertex>,
+    /// Matriz vista-proyección de la cámara sin jitter
+    pub view_projection: Mat4,
+}
+
+impl DebugDrawFrame {
+    /// Vértices del frame
+    pub fn vertex_count(&self) -> usize {
+        self.depth_tested.len() + self.overlay.len()
+    }
+
+    /// Segmentos del frame
+    pub fn line_count(&self) -> usize {
+        self.vertex_count() / 2
+    }
+
+    /// Verificar si no hay nada que dibujar
+    pub fn is_empty(&self) -> bool {
+        self.depth_tested.is_empty() && self.overlay.is_empty()
+    }
+}
+
+/// Capa de debug draw
+#[derive(Debug)]
+pub struct DebugDraw {
+    /// Las llamadas acumulan segmentos
+    enabled: bool,
+    /// Los segmentos nuevos se prueban contra la profundidad
+    depth_test: bool,
+    /// Segmentos acumulados para el próximo frame
+    frame: DebugDrawFrame,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DebugDraw {
+    /// Capa desactivada, con test de profundidad para cuando se active
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            depth_test: true,
+            frame: DebugDrawFrame::default(),
+        }
+    }
+
+    /// Activar o desactivar la capa. Al desactivarla se descarta y libera
+    /// lo acumulado
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.frame = DebugDrawFrame::default();
+        }
+    }
+
+    /// Verificar si la capa está activa
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Probar contra la profundidad de la escena las primitivas que se
+    /// pidan a partir de ahora (false: siempre encima)
+    pub fn set_depth_test(&mut self, depth_test: bool) {
+        self.depth_test = depth_test;
+    }
+
+    /// Test de profundidad de las primitivas nuevas
+    pub fn depth_test(&self) -> bool {
+        self.depth_test
+    }
+
+    /// Segmentos acumulados para el próximo frame
+    pub fn pending(&self) -> &DebugDrawFrame {
+        &self.frame
+    }
+
+    /// Entregar lo acumulado para un frame con `view_projection` y empezar
+    /// el siguiente vacío. None si no hay nada que dibujar
+    pub fn take_frame(&mut self, view_projection: Mat4) -> Option<DebugDrawFrame> {
+        if self.frame.is_empty() {
+            return None;
+        }
+        let mut frame = std::mem::take(&mut self.frame);
+        frame.view_projection = view_projection;
+        Some(frame)
+    }
+
+    /// Segmento de `a` a `b`
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: Vec4) {
+        if !self.enabled {
+            return;
+        }
+        let vertices = if self.depth_test { &mut self.frame.depth_tested } else { &mut self.frame.overlay };
+        vertices.push(DebugVertex { position: a, color });
+        vertices.push(DebugVertex { position: b, color });
+    }
+
+    /// Caja alineada con los ejes
+    pub fn aabb(&mut self, bounds: &Aabb, color: Vec4) {
+        if !self.enabled {
+            return;
+        }
+        let transform = Mat4::from_translation(bounds.center());
+        self.oriented_box(&transform, bounds.half_extents(), color);
+    }
+
+    /// Caja de semiejes `half_extents` centrada en el origen de `transform`
+    pub fn oriented_box(&mut self, transform: &Mat4, half_extents: Vec3, color: Vec4) {
+        if !self.enabled {
+            return;
+        }
+        let corner = |i: usize| {
+            let sign = Vec3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            );
+            transform.transform_point3(sign * half_extents)
+        };
+        let corners: [Vec3; 8] = std::array::from_fn(corner);
+        self.box_edges(&corners, color);
+    }
+
+    /// Esfera: un círculo en cada plano principal
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Vec4) {
+        if !self.enabled {
+            return;
+        }
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            self.circle(center, axis, radius, color);
+        }
+    }
+
+    /// Círculo de `radius` alrededor de `normal`
+    pub fn circle(&mut self, center: Vec3, normal: Vec3, radius: f32, color: Vec4) {
+        if !self.enabled {
+            return;
+        }
+        let (u, v) = normal.try_normalize().unwrap_or(Vec3::Y).any_orthonormal_pair();
+        self.arc(center, u * radius, v * radius, std::f32::consts::TAU, color);
+    }
+
+    /// Cápsula con el eje de `a` a `b`
+    pub fn capsule(&mut self, a: Vec3, b: Vec3, radius: f32, color: Vec4) {
+        if !self.enabled {
+            return;
+        }
+        let axis = (b - a).try_normalize().unwrap_or(Vec3::Y);
+        let (u, v) = axis.any_orthonormal_pair();
+        // Anillos de los extremos y cuatro generatrices
+        self.arc(a, u * radius, v * radius, std::f32::consts::TAU, color);
+        self.arc(b, u * radius, v * radius, std::f32::consts::TAU, color);
+        for side in [u, -u, v, -v] {
+            self.line(a + side * radius, b + side * radius, color);
+        }
+        // Semicírculos de las tapas en dos planos
+        for side in [u, v] {
+            self.arc(b, side * radius, axis * radius, std::f32::consts::PI, color);
+            self.arc(a, side * radius, -axis * radius, std::f32::consts::PI, color);
+        }
+    }
+
+    /// Ejes X (rojo), Y (verde) y Z (azul) de `transform`, de longitud `size`
+    pub fn axes(&mut self, transform: &Mat4, size: f32) {
+        if !self.enabled {
+            return;
+        }
+        let origin = transform.transform_point3(Vec3::ZERO);
+        for (axis, color) in [Vec3::X, Vec3::Y, Vec3::Z].into_iter().zip(AXIS_COLORS) {
+            self.line(origin, transform.transform_point3(axis * size), color);
+        }
+    }
+
+    /// Frustum de una cámara con matriz vista-proyección `view_projection`
+    /// (profundidad de 0 a 1)
+    pub fn frustum(&mut self, view_projection: &Mat4, color: Vec4) {
+        if !self.enabled {
+            return;
+        }
+        let inverse = view_projection.inverse();
+        let corners: [Vec3; 8] = std::array::from_fn(|i| {
+            let ndc = Vec3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { 0.0 } else { 1.0 },
+            );
+            inverse.project_point3(ndc)
+        });
+        self.box_edges(&corners, color);
+    }
+
+    /// Las doce aristas de un hexaedro con las esquinas indexadas por bits
+    /// (bit 0 = x, bit 1 = y, bit 2 = z)
+    fn box_edges(&mut self, corners: &[Vec3; 8], color: Vec4) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    /// Arco de `angle` radianes desde `from` hacia `towards` (ambos radios
+    /// perpendiculares de igual longitud)
+    fn arc(&mut self, center: Vec3, from: Vec3, towards: Vec3, angle: f32, color: Vec4) {
+        let segments = ((CIRCLE_SEGMENTS as f32 * angle / std::f32::consts::TAU).ceil() as u32).max(1);
+        let point = |i: u32| {
+            let theta = angle * i as f32 / segments as f32;
+            center + from * theta.cos() + towards * theta.sin()
+        };
+        let mut previous = point(0);
+        for i in 1..=segments {
+            let next = point(i);
+            self.line(previous, next, color);
+            previous = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Vec4 = Vec4::ONE;
+
+    /// Capa activa
+    fn enabled() -> DebugDraw {
+        let mut debug = DebugDraw::new();
+        debug.set_enabled(true);
+        debug
+    }
+
+    #[test]
+    fn n_lines_give_2n_vertices() {
+        let mut debug = enabled();
+        for i in 0..100 {
+            debug.set_depth_test(i % 2 == 0);
+            debug.line(Vec3::ZERO, Vec3::splat(i as f32), WHITE);
+        }
+        let frame = debug.take_frame(Mat4::IDENTITY).unwrap();
+        assert_eq!(frame.vertex_count(), 200);
+        assert_eq!(frame.line_count(), 100);
+        assert_eq!((frame.depth_tested.len(), frame.overlay.len()), (100, 100));
+        // El siguiente frame empieza vacío
+        assert!(debug.take_frame(Mat4::IDENTITY).is_none());
+    }
+
+    #[test]
+    fn primitives_emit_their_edges() {
+        let mut debug = enabled();
+        debug.aabb(&Aabb::from_points(&[Vec3::ZERO, Vec3::ONE]), WHITE);
+        assert_eq!(debug.pending().line_count(), 12);
+        debug.axes(&Mat4::IDENTITY, 1.0);
+        assert_eq!(debug.pending().line_count(), 15);
+        debug.sphere(Vec3::ZERO, 1.0, WHITE);
+        assert_eq!(debug.pending().line_count(), 15 + 3 * CIRCLE_SEGMENTS as usize);
+    }
+
+    #[test]
+    fn disabled_layer_allocates_nothing() {
+        let mut debug = DebugDraw::new();
+        debug.line(Vec3::ZERO, Vec3::X, WHITE);
+        debug.sphere(Vec3::ZERO, 1.0, WHITE);
+        debug.capsule(Vec3::ZERO, Vec3::Y, 0.5, WHITE);
+        debug.frustum(&Mat4::IDENTITY, WHITE);
+        assert_eq!(debug.pending().depth_tested.capacity(), 0);
+        assert_eq!(debug.pending().overlay.capacity(), 0);
+        assert!(debug.take_frame(Mat4::IDENTITY).is_none());
+
+        // Desactivar libera lo acumulado
+        let mut debug = enabled();
+        debug.line(Vec3::ZERO, Vec3::X, WHITE);
+        debug.set_enabled(false);
+        assert_eq!(debug.pending().depth_tested.capacity(), 0);
+    }
+}
@@ -0,0 +1,185 @@
+//! # Decals
+//!
+//! Proyectores de caja que pintan texturas sobre las superficies sin tocar
+//! sus mallas. Cada frame se recogen los `DecalComponent` del ECS, se
+//! descartan los que ya se han desvanecido por distancia, se ordenan por
+//! prioridad y se recortan al presupuesto global. Los backends los aplican
+//! en el pase principal (forward): cada draw call lleva una máscara con los
+//! decals cuya caja solapa la suya y cuya máscara de capas incluye las del
+//! receptor, y el fragmento solo se pinta si cae dentro de la caja.
+
+use glam::{Mat4, Vec3, Vec4};
+
+use super::culling::Aabb;
+use crate::ecs::{DecalBlendMode, DecalComponent, EntityId};
+
+/// Decals como máximo por frame (bits de la máscara de cada draw call)
+pub const MAX_DECALS: usize = 64;
+
+/// Fracción final de `fade_distance` en la que el decal se desvanece
+const FADE_RANGE: f32 = 0.2;
+
+/// Decal listo para el backend
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecalDraw {
+    /// Textura proyectada
+    pub texture_id: String,
+    /// Transformación de mundo a la caja unidad del decal
+    pub world_to_decal: Mat4,
+    /// Caja envolvente en mundo
+    pub bounds: Aabb,
+    /// Color con la opacidad ya atenuada por distancia
+    pub color: Vec4,
+    /// Modo de mezcla
+    pub blend_mode: DecalBlendMode,
+    /// Capas de render que recibe
+    pub layer_mask: u32,
+}
+
+/// Decal de una entidad con su transformación de mundo
+#[derive(Debug, Clone)]
+pub struct DecalCandidate {
+    /// Entidad del decal
+    pub entity: EntityId,
+    /// Componente
+    pub decal: DecalComponent,
+    /// Transformación del modelo
+    pub model: Mat4,
+}
+
+/// Opacidad por distancia: 1 hasta el último `FADE_RANGE` de `fade_distance`
+/// y 0 a partir de ella
+pub fn distance_fade(fade_distance: f32, distance: f32) -> f32 {
+    if fade_distance <= 0.0 {
+        return 1.0;
+    }
+    ((fade_distance - distance) / (fade_distance * FADE_RANGE)).clamp(0.0, 1.0)
+}
+
+/// Decals del frame: los visibles ordenados por prioridad (y a igual
+/// prioridad, los más cercanos primero) y recortados a `budget`
+pub fn select_decals(candidates: Vec<DecalCandidate>, camera_position: Vec3, budget: usize) -> Vec<DecalDraw> {
+    let budget = budget.min(MAX_DECALS);
+    let mut visible: Vec<(DecalCandidate, f32, f32)> = candidates
+        .into_iter()
+        .filter(|candidate| {
+            let decal = &candidate.decal;
+            !decal.texture_id.is_empty() && decal.layer_mask != 0 && decal.size.cmpgt(Vec3::ZERO).all()
+        })
+        .filter_map(|candidate| {
+            let distance = candidate.model.w_axis.truncate().distance(camera_position);
+            let opacity = candidate.decal.color.w * distance_fade(candidate.decal.fade_distance, distance);
+            (opacity > 0.0).then_some((candidate, distance, opacity))
+        })
+        .collect();
+
+    visible.sort_by(|(a, a_distance, _), (b, b_distance, _)| {
+        b.decal.priority.cmp(&a.decal.priority)
+            .then(a_distance.total_cmp(b_distance))
+            .then(a.entity.cmp(&b.entity))
+    });
+    visible.truncate(budget);
+
+    visible.into_iter()
+        .map(|(candidate, _, opacity)| {
+            let decal = candidate.decal;
+            let decal_to_world = candidate.model * Mat4::from_scale(decal.size);
+            DecalDraw {
+                world_to_decal: decal_to_world.inverse(),
+                bounds: Aabb::new(Vec3::splat(-0.5), Vec3::splat(0.5)).transformed(&decal_to_world),
+                color: decal.color.truncate().extend(opacity),
+                blend_mode: decal.blend_mode,
+                layer_mask: decal.layer_mask,
+                texture_id: decal.texture_id,
+            }
+        })
+        .collect()
+}
+
+/// Máscara de los decals que recibe una draw call: los que incluyen alguna
+/// de sus capas y, si se conoce su caja en mundo, la solapan
+pub fn receiver_mask(decals: &[DecalDraw], layers: u32, bounds: Option<&Aabb>) -> u64 {
+    decals.iter()
+        .take(MAX_DECALS)
+        .enumerate()
+        .filter(|(_, decal)| decal.layer_mask & layers != 0)
+        .filter(|(_, decal)| bounds.map_or(true, |bounds| decal.bounds.intersects(bounds)))
+        .fold(0, |mask, (index, _)| mask | 1 << index)
+}
+
+/// Coordenadas de textura de un punto de mundo dentro de la caja de un decal
+/// (None fuera de ella). Es el mismo recorte que hace el shader
+pub fn decal_uv(decal: &DecalDraw, point: Vec3) -> Option<[f32; 2]> {
+    let local = decal.world_to_decal.transform_point3(point);
+    if local.abs().cmpgt(Vec3::splat(0.5)).any() {
+        return None;
+    }
+    Some([local.x + 0.5, 0.5 - local.y])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::{RENDER_LAYER_DEFAULT, RENDER_LAYER_TERRAIN};
+
+    /// Decal de 1x1x1 en `position` con la prioridad dada
+    fn candidate(entity: EntityId, priority: i32, position: Vec3) -> DecalCandidate {
+        DecalCandidate {
+            entity,
+            decal: DecalComponent {
+                texture_id: format!("decal-{}", entity),
+                size: Vec3::ONE,
+                color: Vec4::ONE,
+                blend_mode: DecalBlendMode::Alpha,
+                fade_distance: 0.0,
+                layer_mask: RENDER_LAYER_DEFAULT,
+                priority,
+            },
+            model: Mat4::from_translation(position),
+        }
+    }
+
+    #[test]
+    fn exceeding_the_budget_drops_the_lowest_priority_first() {
+        // Los de menor prioridad están más cerca: la distancia solo desempata
+        let candidates = vec![
+            candidate(1, 0, Vec3::new(1.0, 0.0, 0.0)),
+            candidate(2, 5, Vec3::new(9.0, 0.0, 0.0)),
+            candidate(3, 1, Vec3::new(2.0, 0.0, 0.0)),
+            candidate(4, 5, Vec3::new(8.0, 0.0, 0.0)),
+            candidate(5, 3, Vec3::new(7.0, 0.0, 0.0)),
+        ];
+        let textures = |budget| {
+            select_decals(candidates.clone(), Vec3::ZERO, budget).into_iter()
+                .map(|decal| decal.texture_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(textures(5), ["decal-4", "decal-2", "decal-5", "decal-3", "decal-1"]);
+        assert_eq!(textures(3), ["decal-4", "decal-2", "decal-5"]);
+        assert_eq!(textures(1), ["decal-4"]);
+    }
+
+    #[test]
+    fn projection_stays_within_the_box_extents() {
+        let decal = select_decals(vec![candidate(1, 0, Vec3::ZERO)], Vec3::ZERO, MAX_DECALS).remove(0);
+        // Puntos del plano z = 0 que atraviesa la caja
+        assert_eq!(decal_uv(&decal, Vec3::ZERO), Some([0.5, 0.5]));
+        assert_eq!(decal_uv(&decal, Vec3::new(-0.5, 0.5, 0.0)), Some([0.0, 0.0]));
+        assert_eq!(decal_uv(&decal, Vec3::new(0.25, -0.25, 0.0)), Some([0.75, 0.75]));
+        for outside in [Vec3::new(0.6, 0.0, 0.0), Vec3::new(0.0, -0.6, 0.0), Vec3::new(0.0, 0.0, 0.6)] {
+            assert_eq!(decal_uv(&decal, outside), None);
+        }
+    }
+
+    #[test]
+    fn excluded_layers_do_not_receive_the_decal() {
+        let decals = select_decals(vec![candidate(1, 0, Vec3::ZERO)], Vec3::ZERO, MAX_DECALS);
+        let inside = Aabb::new(Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+        let far = Aabb::new(Vec3::splat(5.0), Vec3::splat(6.0));
+
+        assert_eq!(receiver_mask(&decals, RENDER_LAYER_DEFAULT, Some(&inside)), 1);
+        assert_eq!(receiver_mask(&decals, RENDER_LAYER_DEFAULT | RENDER_LAYER_TERRAIN, None), 1);
+        assert_eq!(receiver_mask(&decals, RENDER_LAYER_TERRAIN, Some(&inside)), 0);
+        assert_eq!(receiver_mask(&decals, RENDER_LAYER_DEFAULT, Some(&far)), 0);
+    }
+}
@@ -0,0 +1,327 @@
+//! # Resolución dinámica
+//!
+//! Ajusta la resolución interna de renderizado según el tiempo de GPU del
+//! frame. Si el frame tarda más que `target_frame_time_ms`, la escala baja
+//! `adjustment_rate`; si sobra margen, sube. El pase principal y la cadena de
+//! post-procesado trabajan a `resolución * escala` y el pase de salida
+//! reescala al destino del frame.
+//!
+//! La escala se mueve en pasos pequeños cada frame, pero el destino solo se
+//! recrea cuando se aleja más de `RESOLUTION_CHANGE_THRESHOLD` de la escala
+//! aplicada, para que la imagen no parpadee con cada medida.
+//!
+//! El tiempo de GPU se mide con dos timestamps (`wgpu::QuerySet`) al principio
+//! y al final de los pases del frame y se lee de vuelta sin bloquear, así que
+//! el controlador reacciona con uno o dos frames de retraso. Sin la feature
+//! `TIMESTAMP_QUERY` no hay medida y la escala no cambia.
+
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Diferencia mínima con la escala aplicada para recrear el destino
+pub const RESOLUTION_CHANGE_THRESHOLD: f32 = 0.05;
+
+/// Fracción del objetivo por debajo de la que la escala vuelve a subir. El
+/// margen evita oscilar alrededor del objetivo
+const SCALE_UP_HEADROOM: f32 = 0.85;
+
+/// Timestamps por frame (inicio y fin)
+const TIMESTAMP_COUNT: u32 = 2;
+
+/// Bytes de los timestamps resueltos
+const TIMESTAMP_SIZE: u64 = TIMESTAMP_COUNT as u64 * std::mem::size_of::<u64>() as u64;
+
+/// Estados del mapeo de la copia de los timestamps
+const READBACK_WAITING: u8 = 0;
+const READBACK_MAPPED: u8 = 1;
+const READBACK_FAILED: u8 = 2;
+
+/// Configuración de la resolución dinámica
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DynamicResolutionConfig {
+    /// Escala mínima de la resolución
+    pub min_scale: f32,
+    /// Escala máxima de la resolución
+    pub max_scale: f32,
+    /// Tiempo de GPU objetivo por frame en milisegundos
+    pub target_frame_time_ms: f32,
+    /// Cambio de escala por frame
+    pub adjustment_rate: f32,
+}
+
+impl Default for DynamicResolutionConfig {
+    fn default() -> Self {
+        Self {
+            min_scale: 0.5,
+            max_scale: 1.0,
+            target_frame_time_ms: 16.6,
+            adjustment_rate: 0.05,
+        }
+    }
+}
+
+impl DynamicResolutionConfig {
+    /// Rango de escalas válido (ordenado y dentro de (0, 1])
+    pub fn scale_range(&self) -> (f32, f32) {
+        let min = self.min_scale.clamp(0.1, 1.0);
+        let max = self.max_scale.clamp(0.1, 1.0);
+        (min.min(max), min.max(max))
+    }
+}
+
+/// Controlador de la escala de resolución
+#[derive(Debug, Clone)]
+pub struct DynamicResolution {
+    config: DynamicResolutionConfig,
+    /// Escala que persigue el controlador
+    current_scale: f32,
+    /// Escala del destino de renderizado
+    applied_scale: f32,
+}
+
+impl DynamicResolution {
+    /// Crear controlador empezando en la escala máxima
+    pub fn new(config: DynamicResolutionConfig) -> Self {
+        let (_, max) = config.scale_range();
+        Self {
+            config,
+            current_scale: max,
+            applied_scale: max,
+        }
+    }
+
+    /// Configuración del controlador
+    pub fn config(&self) -> &DynamicResolutionConfig {
+        &self.config
+    }
+
+    /// Escala que persigue el controlador
+    pub fn current_scale(&self) -> f32 {
+        self.current_scale
+    }
+
+    /// Escala del destino de renderizado
+    pub fn applied_scale(&self) -> f32 {
+        self.applied_scale
+    }
+
+    /// Registrar el tiempo de GPU de un frame. Devuelve la nueva escala del
+    /// destino cuando hay que recrearlo. Las medidas no positivas (sin
+    /// timestamps) se ignoran
+    pub fn update(&mut self, gpu_frame_time_ms: f32) -> Option<f32> {
+        if !gpu_frame_time_ms.is_finite() || gpu_frame_time_ms <= 0.0 {
+            return None;
+        }
+
+        let target = self.config.target_frame_time_ms.max(0.1);
+        let rate = self.config.adjustment_rate.abs();
+        if gpu_frame_time_ms > target {
+            self.current_scale -= rate;
+        } else if gpu_frame_time_ms < target * SCALE_UP_HEADROOM {
+            self.current_scale += rate;
+        }
+        let (min, max) = self.config.scale_range();
+        self.current_scale = self.current_scale.clamp(min, max);
+
+        // En los límites se aplica aunque el salto sea pequeño, para no
+        // quedarse a menos del umbral de la escala mínima o máxima
+        let at_bound = self.current_scale == min || self.current_scale == max;
+        let delta = (self.current_scale - self.applied_scale).abs();
+        if delta > RESOLUTION_CHANGE_THRESHOLD || (at_bound && delta > 0.0) {
+            self.applied_scale = self.current_scale;
+            Some(self.applied_scale)
+        } else {
+            None
+        }
+    }
+}
+
+/// Tamaño escalado de un destino, al menos de 1x1
+pub fn scaled_size(width: u32, height: u32, scale: f32) -> (u32, u32) {
+    let scale = scale.clamp(0.1, 1.0);
+    (
+        ((width as f32 * scale).round() as u32).max(1),
+        ((height as f32 * scale).round() as u32).max(1),
+    )
+}
+
+/// Medida del tiempo de GPU de los frames con timestamps
+pub struct GpuFrameTimer {
+    query_set: wgpu::QuerySet,
+    /// Destino de `resolve_query_set`
+    resolve: wgpu::Buffer,
+    /// Copia de los timestamps que se lee desde la CPU
+    readback: wgpu::Buffer,
+    /// La copia está mapeada (o pendiente de mapear)
+    readback_pending: bool,
+    /// Estado del mapeo de la copia
+    readback_state: Arc<AtomicU8>,
+    /// Nanosegundos por tick de timestamp
+    period: f32,
+    /// Los timestamps del frame en curso se copiaron a `readback`
+    recorded: bool,
+    /// Tiempo de GPU del último frame leído en milisegundos
+    last_frame_time_ms: f32,
+}
+
+impl GpuFrameTimer {
+    /// Crear el temporizador si el dispositivo admite timestamps
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("gpu-frame-timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: TIMESTAMP_COUNT,
+        });
+        let resolve = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu-frame-timestamps-resolve"),
+            size: TIMESTAMP_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu-frame-timestamps-readback"),
+            size: TIMESTAMP_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve,
+            readback,
+            readback_pending: false,
+            readback_state: Arc::new(AtomicU8::new(READBACK_WAITING)),
+            period: queue.get_timestamp_period(),
+            recorded: false,
+            last_frame_time_ms: 0.0,
+        })
+    }
+
+    /// Escribir un timestamp con un compute pass vacío (0 inicio, 1 fin)
+    fn write_timestamp(&self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        let (beginning, end) = if index == 0 { (Some(index), None) } else { (None, Some(index)) };
+        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("gpu-frame-timestamp"),
+            timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+                query_set: &self.query_set,
+                beginning_of_pass_write_index: beginning,
+                end_of_pass_write_index: end,
+            }),
+        });
+    }
+
+    /// Marcar el comienzo de los pases del frame
+    pub fn begin(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.write_timestamp(encoder, 0);
+    }
+
+    /// Marcar el final de los pases del frame y copiar los timestamps si la
+    /// copia anterior ya se leyó
+    pub fn end(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.write_timestamp(encoder, 1);
+        if !self.readback_pending {
+            encoder.resolve_query_set(&self.query_set, 0..TIMESTAMP_COUNT, &self.resolve, 0);
+            encoder.copy_buffer_to_buffer(&self.resolve, 0, &self.readback, 0, TIMESTAMP_SIZE);
+            self.recorded = true;
+        }
+    }
+
+    /// Tras enviar el frame: pedir el mapeo de la copia de los timestamps y
+    /// recoger la del frame anterior si ya terminó. No bloquea
+    pub fn finish(&mut self, device: &wgpu::Device) {
+        if self.recorded {
+            self.recorded = false;
+            self.readback_pending = true;
+            self.readback_state.store(READBACK_WAITING, Ordering::Release);
+            let state = Arc::clone(&self.readback_state);
+            self.readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let next = if result.is_ok() { READBACK_MAPPED } else { READBACK_FAILED };
+                state.store(next, Ordering::Release);
+            });
+        }
+        device.poll(wgpu::Maintain::Poll);
+        if !self.readback_pending {
+            return;
+        }
+        match self.readback_state.load(Ordering::Acquire) {
+            READBACK_MAPPED => {
+                {
+                    let mapped = self.readback.slice(..).get_mapped_range();
+                    let timestamps: &[u64] = bytemuck::cast_slice(&mapped);
+                    let ticks = timestamps[1].saturating_sub(timestamps[0]);
+                    self.last_frame_time_ms = ticks as f32 * self.period / 1_000_000.0;
+                }
+                self.readback.unmap();
+                self.readback_pending = false;
+            }
+            // Se reintenta con los timestamps del próximo frame
+            READBACK_FAILED => self.readback_pending = false,
+            _ => {}
+        }
+    }
+
+    /// Tiempo de GPU del último frame leído en milisegundos (0 sin medida)
+    pub fn last_frame_time_ms(&self) -> f32 {
+        self.last_frame_time_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tiempo de GPU de un frame limitado por píxeles: proporcional al área
+    fn gpu_time(load_ms: f32, scale: f32) -> f32 {
+        load_ms * scale * scale
+    }
+
+    #[test]
+    fn ramping_gpu_load_lowers_the_scale() {
+        let config = DynamicResolutionConfig::default();
+        let target = config.target_frame_time_ms;
+        let mut dynamic = DynamicResolution::new(config);
+        assert_eq!(dynamic.applied_scale(), 1.0);
+
+        // La carga sube de 10 a 40 ms a resolución completa en dos segundos
+        let mut changes = 0;
+        let mut frame_time = 0.0;
+        for frame in 0..120 {
+            let load = 10.0 + 30.0 * (frame as f32 / 119.0);
+            frame_time = gpu_time(load, dynamic.applied_scale());
+            if let Some(scale) = dynamic.update(frame_time) {
+                assert!((scale - dynamic.applied_scale()).abs() < f32::EPSILON);
+                changes += 1;
+            }
+        }
+        assert!(dynamic.applied_scale() < 1.0);
+        assert!(dynamic.applied_scale() >= 0.5);
+        assert!(changes > 0);
+        // La escala compensa la carga: el frame vuelve a estar cerca del objetivo
+        assert!(frame_time < target * 1.2, "{} ms con escala {}", frame_time, dynamic.applied_scale());
+
+        // Al bajar la carga la escala vuelve al máximo
+        for _ in 0..120 {
+            dynamic.update(gpu_time(8.0, dynamic.applied_scale()));
+        }
+        assert_eq!(dynamic.applied_scale(), 1.0);
+    }
+
+    #[test]
+    fn small_changes_do_not_recreate_the_target() {
+        let mut dynamic = DynamicResolution::new(DynamicResolutionConfig {
+            adjustment_rate: 0.02,
+            ..DynamicResolutionConfig::default()
+        });
+        // Un paso no supera el umbral; el tercero sí
+        assert_eq!(dynamic.update(30.0), None);
+        assert_eq!(dynamic.update(30.0), None);
+        assert!(dynamic.update(30.0).is_some_and(|scale| scale < 1.0));
+        // Sin timestamps no hay medida
+        assert_eq!(dynamic.update(0.0), None);
+    }
+}
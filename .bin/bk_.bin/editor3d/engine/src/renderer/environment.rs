@@ -0,0 +1,620 @@
+//! # Entornos HDR
+//!
+//! Precálculo en CPU de la iluminación de imagen (IBL). Un entorno se lee de
+//! un panorama equirectangular o de seis caras Radiance `.hdr` y se remuestrea
+//! a un cubemap. De él salen:
+//!
+//! - La irradiancia difusa: proyección en armónicos esféricos de orden 2
+//!   evaluada en un cubemap pequeño (ya dividida por PI).
+//! - El especular prefiltrado: un nivel de mip por rugosidad, de 0 (el
+//!   entorno sin filtrar, que también dibuja el skybox) a 1, integrando la
+//!   GGX por importancia sobre la cadena de mips del entorno.
+//! - La LUT de la BRDF del split-sum, común a todos los entornos.
+//!
+//! Las caras van en el orden de los cubemaps de WebGPU: +X, -X, +Y, -Y, +Z, -Z.
+
+use anyhow::{anyhow, bail, Result};
+use glam::{Vec2, Vec3};
+use serde::{Serialize, Deserialize};
+use std::f32::consts::PI;
+
+/// Caras de un cubemap
+pub const CUBE_FACE_COUNT: usize = 6;
+
+/// Resolución y muestreo del precálculo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentSettings {
+    /// Lado de cada cara del entorno sin filtrar (nivel 0 del especular)
+    pub cube_size: u32,
+    /// Niveles del especular prefiltrado, de rugosidad 0 a 1
+    pub specular_levels: u32,
+    /// Lado de cada cara del cubemap de irradiancia
+    pub irradiance_size: u32,
+    /// Muestras por texel del prefiltrado especular
+    pub sample_count: u32,
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        Self {
+            cube_size: 256,
+            specular_levels: 6,
+            irradiance_size: 32,
+            sample_count: 64,
+        }
+    }
+}
+
+/// Imagen HDR en radiancia lineal
+#[derive(Debug, Clone)]
+pub struct HdrImage {
+    /// Ancho
+    pub width: u32,
+    /// Alto
+    pub height: u32,
+    /// Píxeles RGB fila a fila, de arriba abajo
+    pub pixels: Vec<Vec3>,
+}
+
+impl HdrImage {
+    /// Leer una imagen Radiance RGBE (`.hdr`), plana o con RLE por canal.
+    /// Solo se admite la orientación habitual `-Y alto +X ancho`
+    pub fn from_radiance(data: &[u8]) -> Result<Self> {
+        let mut cursor = 0;
+        let mut line = || read_line(data, &mut cursor);
+
+        let magic = line()?;
+        if !magic.starts_with("#?RADIANCE") && !magic.starts_with("#?RGBE") {
+            bail!("No es una imagen Radiance HDR");
+        }
+        loop {
+            let header = line()?;
+            if header.is_empty() {
+                break;
+            }
+            if let Some(format) = header.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    bail!("Formato HDR no soportado: {}", format);
+                }
+            }
+        }
+        let resolution: Vec<&str> = line()?.split_whitespace().collect();
+        let (height, width) = match resolution.as_slice() {
+            ["-Y", height, "+X", width] => (height.parse::<u32>()?, width.parse::<u32>()?),
+            _ => bail!("Orientación HDR no soportada: {}", resolution.join(" ")),
+        };
+        if width == 0 || height == 0 {
+            bail!("Imagen HDR vacía");
+        }
+
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        let mut scanline = vec![[0u8; 4]; width as usize];
+        let mut rest = &data[cursor..];
+        for _ in 0..height {
+            rest = read_scanline(rest, &mut scanline)?;
+            pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_linear(rgbe)));
+        }
+        Ok(Self { width, height, pixels })
+    }
+
+    /// Píxel con coordenadas recortadas al borde
+    fn pixel(&self, x: i64, y: i64) -> Vec3 {
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        self.pixels[y * self.width as usize + x]
+    }
+
+    /// Muestreo bilineal en coordenadas normalizadas. Si `wrap_u`, U se repite
+    /// (panoramas) y si no se recorta al borde
+    pub fn sample(&self, uv: Vec2, wrap_u: bool) -> Vec3 {
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = uv.y * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let column = |x: f32| {
+            let x = x as i64;
+            if wrap_u { x.rem_euclid(self.width as i64) } else { x }
+        };
+        let (x0, x1) = (column(x0), column(x0 + 1.0));
+        let (y0, y1) = (y0 as i64, y0 as i64 + 1);
+        let top = self.pixel(x0, y0).lerp(self.pixel(x1, y0), fx);
+        let bottom = self.pixel(x0, y1).lerp(self.pixel(x1, y1), fx);
+        top.lerp(bottom, fy)
+    }
+}
+
+/// Leer una línea de la cabecera a partir de `cursor`
+fn read_line<'a>(data: &'a [u8], cursor: &mut usize) -> Result<&'a str> {
+    let end = data[*cursor..].iter().position(|&byte| byte == b'\n')
+        .ok_or_else(|| anyhow!("Cabecera HDR incompleta"))?;
+    let text = std::str::from_utf8(&data[*cursor..*cursor + end])?;
+    *cursor += end + 1;
+    Ok(text.trim_end_matches('\r'))
+}
+
+/// Leer una línea de píxeles RGBE. Devuelve los datos restantes
+fn read_scanline<'a>(data: &'a [u8], scanline: &mut [[u8; 4]]) -> Result<&'a [u8]> {
+    let width = scanline.len();
+    let truncated = || anyhow!("Datos HDR truncados");
+    let rle = (8..0x8000).contains(&width)
+        && data.len() >= 4
+        && data[0] == 2
+        && data[1] == 2
+        && ((data[2] as usize) << 8 | data[3] as usize) == width;
+
+    if !rle {
+        let bytes = data.get(..width * 4).ok_or_else(truncated)?;
+        for (pixel, rgbe) in scanline.iter_mut().zip(bytes.chunks_exact(4)) {
+            pixel.copy_from_slice(rgbe);
+        }
+        return Ok(&data[width * 4..]);
+    }
+
+    // RLE nuevo: cada canal por separado, en tramos repetidos o literales
+    let mut cursor = 4;
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *data.get(cursor).ok_or_else(truncated)? as usize;
+            cursor += 1;
+            if count > 128 {
+                let count = count - 128;
+                let value = *data.get(cursor).ok_or_else(truncated)?;
+                cursor += 1;
+                if x + count > width {
+                    bail!("Tramo RLE fuera de la línea HDR");
+                }
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value;
+                }
+                x += count;
+            } else {
+                if count == 0 || x + count > width {
+                    bail!("Tramo RLE inválido en la línea HDR");
+                }
+                let values = data.get(cursor..cursor + count).ok_or_else(truncated)?;
+                for (pixel, &value) in scanline[x..x + count].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+                cursor += count;
+                x += count;
+            }
+        }
+    }
+    Ok(&data[cursor..])
+}
+
+/// Radiancia lineal de un píxel RGBE
+fn rgbe_to_linear([r, g, b, e]: [u8; 4]) -> Vec3 {
+    if e == 0 {
+        return Vec3::ZERO;
+    }
+    let scale = 2f32.powi(e as i32 - 136);
+    Vec3::new(r as f32 + 0.5, g as f32 + 0.5, b as f32 + 0.5) * scale
+}
+
+/// Imagen de origen de un entorno
+#[derive(Debug, Clone)]
+pub enum EnvironmentSource {
+    /// Panorama equirectangular (U es el azimut desde +X hacia +Z, V va de
+    /// +Y a -Y)
+    Equirectangular(HdrImage),
+    /// Seis caras cuadradas del mismo tamaño
+    Cubemap(Box<[HdrImage; CUBE_FACE_COUNT]>),
+}
+
+impl EnvironmentSource {
+    /// Comprobar las dimensiones de la imagen
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            EnvironmentSource::Equirectangular(image) => {
+                if image.pixels.len() != (image.width * image.height) as usize {
+                    bail!("Panorama HDR de {}x{} con {} píxeles", image.width, image.height, image.pixels.len());
+                }
+            }
+            EnvironmentSource::Cubemap(faces) => {
+                let size = faces[0].width;
+                for face in faces.iter() {
+                    if face.width != size || face.height != size || face.pixels.len() != (size * size) as usize {
+                        bail!("Las caras del cubemap deben ser cuadradas y del mismo tamaño");
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Radiancia en una dirección de mundo
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let direction = direction.normalize_or_zero();
+        match self {
+            EnvironmentSource::Equirectangular(image) => {
+                let u = direction.z.atan2(direction.x) / (2.0 * PI) + 0.5;
+                let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+                image.sample(Vec2::new(u, v), true)
+            }
+            EnvironmentSource::Cubemap(faces) => {
+                let (face, uv) = direction_to_face(direction);
+                faces[face].sample((uv + 1.0) * 0.5, false)
+            }
+        }
+    }
+}
+
+/// Dirección de un punto de una cara, con `uv` en [-1, 1] (U a la derecha y
+/// V hacia abajo de la cara)
+pub fn face_direction(face: usize, uv: Vec2) -> Vec3 {
+    let (u, v) = (uv.x, uv.y);
+    let direction = match face {
+        0 => Vec3::new(1.0, -v, -u),
+        1 => Vec3::new(-1.0, -v, u),
+        2 => Vec3::new(u, 1.0, v),
+        3 => Vec3::new(u, -1.0, -v),
+        4 => Vec3::new(u, -v, 1.0),
+        _ => Vec3::new(-u, -v, -1.0),
+    };
+    direction.normalize()
+}
+
+/// Cara y coordenadas en [-1, 1] de una dirección (inversa de `face_direction`)
+pub fn direction_to_face(direction: Vec3) -> (usize, Vec2) {
+    let abs = direction.abs();
+    if abs.x >= abs.y && abs.x >= abs.z {
+        let inverse = 1.0 / abs.x.max(1e-20);
+        if direction.x > 0.0 {
+            (0, Vec2::new(-direction.z, -direction.y) * inverse)
+        } else {
+            (1, Vec2::new(direction.z, -direction.y) * inverse)
+        }
+    } else if abs.y >= abs.z {
+        let inverse = 1.0 / abs.y;
+        if direction.y > 0.0 {
+            (2, Vec2::new(direction.x, direction.z) * inverse)
+        } else {
+            (3, Vec2::new(direction.x, -direction.z) * inverse)
+        }
+    } else {
+        let inverse = 1.0 / abs.z;
+        if direction.z > 0.0 {
+            (4, Vec2::new(direction.x, -direction.y) * inverse)
+        } else {
+            (5, Vec2::new(-direction.x, -direction.y) * inverse)
+        }
+    }
+}
+
+/// Coordenadas en [-1, 1] del centro de un texel
+fn texel_uv(x: u32, y: u32, size: u32) -> Vec2 {
+    Vec2::new((x as f32 + 0.5) / size as f32, (y as f32 + 0.5) / size as f32) * 2.0 - 1.0
+}
+
+/// Cubemap de radiancia lineal
+#[derive(Debug, Clone)]
+pub struct CubeMap {
+    /// Lado de cada cara
+    size: u32,
+    /// Texels cara a cara, fila a fila
+    texels: Vec<Vec3>,
+}
+
+impl CubeMap {
+    /// Evaluar una función de la dirección en el centro de cada texel
+    pub fn from_fn(size: u32, mut radiance: impl FnMut(Vec3) -> Vec3) -> Self {
+        let size = size.max(1);
+        let mut texels = Vec::with_capacity(CUBE_FACE_COUNT * (size * size) as usize);
+        for face in 0..CUBE_FACE_COUNT {
+            for y in 0..size {
+                for x in 0..size {
+                    texels.push(radiance(face_direction(face, texel_uv(x, y, size))));
+                }
+            }
+        }
+        Self { size, texels }
+    }
+
+    /// Lado de cada cara
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Texels cara a cara
+    pub fn texels(&self) -> &[Vec3] {
+        &self.texels
+    }
+
+    fn texel(&self, face: usize, x: i64, y: i64) -> Vec3 {
+        let last = self.size as i64 - 1;
+        let (x, y) = (x.clamp(0, last) as usize, y.clamp(0, last) as usize);
+        let size = self.size as usize;
+        self.texels[face * size * size + y * size + x]
+    }
+
+    /// Muestreo bilineal dentro de la cara de la dirección (sin cruzar aristas)
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let (face, uv) = direction_to_face(direction);
+        let texel = (uv + 1.0) * 0.5 * self.size as f32 - 0.5;
+        let (x0, y0) = (texel.x.floor(), texel.y.floor());
+        let (fx, fy) = (texel.x - x0, texel.y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = self.texel(face, x0, y0).lerp(self.texel(face, x0 + 1, y0), fx);
+        let bottom = self.texel(face, x0, y0 + 1).lerp(self.texel(face, x0 + 1, y0 + 1), fx);
+        top.lerp(bottom, fy)
+    }
+
+    /// Reducir a la mitad promediando bloques de 2x2
+    pub fn downsample(&self) -> Self {
+        let size = (self.size / 2).max(1);
+        let mut texels = Vec::with_capacity(CUBE_FACE_COUNT * (size * size) as usize);
+        for face in 0..CUBE_FACE_COUNT {
+            for y in 0..size as i64 {
+                for x in 0..size as i64 {
+                    let sum = self.texel(face, x * 2, y * 2)
+                        + self.texel(face, x * 2 + 1, y * 2)
+                        + self.texel(face, x * 2, y * 2 + 1)
+                        + self.texel(face, x * 2 + 1, y * 2 + 1);
+                    texels.push(sum * 0.25);
+                }
+            }
+        }
+        Self { size, texels }
+    }
+
+    /// Texels en RGBA16F, listos para subir cara a cara
+    pub fn to_rgba16f(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.texels.len() * 8);
+        for texel in &self.texels {
+            for value in [texel.x, texel.y, texel.z, 1.0] {
+                bytes.extend_from_slice(&f32_to_f16(value).to_le_bytes());
+            }
+        }
+        bytes
+    }
+}
+
+/// Cadena de mips de un cubemap para muestrear por nivel de detalle
+struct CubeMipChain {
+    levels: Vec<CubeMap>,
+}
+
+impl CubeMipChain {
+    fn new(base: CubeMap) -> Self {
+        let mut levels = vec![base];
+        while let Some(last) = levels.last().filter(|level| level.size > 1) {
+            let next = last.downsample();
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    /// Muestreo trilineal
+    fn sample(&self, direction: Vec3, lod: f32) -> Vec3 {
+        let lod = lod.clamp(0.0, (self.levels.len() - 1) as f32);
+        let level = lod.floor() as usize;
+        let low = self.levels[level].sample(direction);
+        match self.levels.get(level + 1) {
+            Some(next) => low.lerp(next.sample(direction), lod - level as f32),
+            None => low,
+        }
+    }
+}
+
+/// Mapas precalculados de un entorno
+#[derive(Debug, Clone)]
+pub struct EnvironmentMaps {
+    /// ID del entorno
+    pub id: String,
+    /// Irradiancia difusa dividida por PI
+    pub irradiance: CubeMap,
+    /// Especular prefiltrado: nivel `i` con rugosidad `i / (niveles - 1)`.
+    /// El nivel 0 es el entorno sin filtrar
+    pub specular: Vec<CubeMap>,
+}
+
+impl EnvironmentMaps {
+    /// Precalcular irradiancia y especular. Es costoso: se ejecuta fuera del
+    /// hilo principal
+    pub fn precompute(id: impl Into<String>, source: &EnvironmentSource, settings: &EnvironmentSettings) -> Self {
+        let cube_size = settings.cube_size.max(1);
+        let base = CubeMap::from_fn(cube_size, |direction| source.sample(direction));
+        let chain = CubeMipChain::new(base);
+
+        // Irradiancia: armónicos proyectados desde un nivel pequeño
+        let projection_level = chain.levels.iter()
+            .position(|level| level.size <= 32)
+            .unwrap_or(chain.levels.len() - 1);
+        let sh = project_sh9(&chain.levels[projection_level]);
+        let irradiance = CubeMap::from_fn(settings.irradiance_size, |normal| sh_irradiance(&sh, normal) / PI);
+
+        // Especular: un nivel de mip por rugosidad
+        let levels = settings.specular_levels.clamp(1, cube_size.ilog2() + 1);
+        let samples = hammersley_set(settings.sample_count.max(1));
+        let texel_solid_angle = 4.0 * PI / (CUBE_FACE_COUNT as f32 * (cube_size * cube_size) as f32);
+        let mut specular = vec![chain.levels[0].clone()];
+        for level in 1..levels {
+            let roughness = level as f32 / (levels - 1) as f32;
+            let size = (cube_size >> level).max(1);
+            specular.push(CubeMap::from_fn(size, |normal| {
+                prefilter_ggx(&chain, &samples, normal, roughness, texel_solid_angle)
+            }));
+        }
+
+        Self { id: id.into(), irradiance, specular }
+    }
+
+    /// Nivel de mip de rugosidad 1
+    pub fn max_specular_level(&self) -> f32 {
+        (self.specular.len() - 1) as f32
+    }
+}
+
+/// Integrar la GGX alrededor de `normal` suponiendo vista y reflexión
+/// alineadas con la normal. Cada muestra lee el mip cuyo texel cubre el
+/// ángulo sólido de la muestra, lo que evita el ruido con pocas muestras
+fn prefilter_ggx(chain: &CubeMipChain, samples: &[Vec2], normal: Vec3, roughness: f32, texel_solid_angle: f32) -> Vec3 {
+    let mut total = Vec3::ZERO;
+    let mut weight = 0.0;
+    for &xi in samples {
+        let half = importance_sample_ggx(xi, normal, roughness);
+        let n_dot_h = normal.dot(half).max(0.0);
+        let light = 2.0 * n_dot_h * half - normal;
+        let n_dot_l = normal.dot(light);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        // Con V = N la pdf de la dirección de luz es D / 4
+        let pdf = distribution_ggx(n_dot_h, roughness) / 4.0 + 1e-4;
+        let sample_solid_angle = 1.0 / (samples.len() as f32 * pdf);
+        let lod = (0.5 * (sample_solid_angle / texel_solid_angle).log2() + 1.0).max(0.0);
+        total += chain.sample(light, lod) * n_dot_l;
+        weight += n_dot_l;
+    }
+    if weight > 0.0 { total / weight } else { chain.sample(normal, 0.0) }
+}
+
+/// Distribución GGX (rugosidad perceptual, como en el shader PBR)
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d).max(1e-8)
+}
+
+/// Vector medio muestreado según la GGX alrededor de `normal`
+fn importance_sample_ggx(xi: Vec2, normal: Vec3, roughness: f32) -> Vec3 {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = ((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let up = if normal.z.abs() < 0.999 { Vec3::Z } else { Vec3::X };
+    let tangent = up.cross(normal).normalize();
+    let bitangent = normal.cross(tangent);
+    (tangent * phi.cos() * sin_theta + bitangent * phi.sin() * sin_theta + normal * cos_theta).normalize()
+}
+
+/// Secuencia de Hammersley de `count` puntos
+fn hammersley_set(count: u32) -> Vec<Vec2> {
+    (0..count)
+        .map(|i| Vec2::new(i as f32 / count as f32, i.reverse_bits() as f32 / 4_294_967_296.0))
+        .collect()
+}
+
+/// Base de armónicos esféricos reales de orden 2
+fn sh9_basis(direction: Vec3) -> [f32; 9] {
+    let Vec3 { x, y, z } = direction;
+    [
+        0.282_095,
+        0.488_603 * y,
+        0.488_603 * z,
+        0.488_603 * x,
+        1.092_548 * x * y,
+        1.092_548 * y * z,
+        0.315_392 * (3.0 * z * z - 1.0),
+        1.092_548 * x * z,
+        0.546_274 * (x * x - y * y),
+    ]
+}
+
+/// Proyectar la radiancia de un cubemap en armónicos, ponderando cada texel
+/// por su ángulo sólido
+fn project_sh9(cube: &CubeMap) -> [Vec3; 9] {
+    let mut coefficients = [Vec3::ZERO; 9];
+    let mut total_weight = 0.0;
+    let size = cube.size;
+    for face in 0..CUBE_FACE_COUNT {
+        for y in 0..size {
+            for x in 0..size {
+                let uv = texel_uv(x, y, size);
+                let weight = 1.0 / (1.0 + uv.length_squared()).powf(1.5);
+                let radiance = cube.texel(face, x as i64, y as i64);
+                for (coefficient, basis) in coefficients.iter_mut().zip(sh9_basis(face_direction(face, uv))) {
+                    *coefficient += radiance * basis * weight;
+                }
+                total_weight += weight;
+            }
+        }
+    }
+    // Normalizar para que los pesos sumen el ángulo sólido de la esfera
+    let normalization = 4.0 * PI / total_weight;
+    coefficients.map(|coefficient| coefficient * normalization)
+}
+
+/// Irradiancia en una normal a partir de los armónicos de la radiancia
+fn sh_irradiance(sh: &[Vec3; 9], normal: Vec3) -> Vec3 {
+    // Convolución con el coseno por banda
+    const BANDS: [f32; 9] = [PI, 2.0 * PI / 3.0, 2.0 * PI / 3.0, 2.0 * PI / 3.0, PI / 4.0, PI / 4.0, PI / 4.0, PI / 4.0, PI / 4.0];
+    let basis = sh9_basis(normal);
+    let mut irradiance = Vec3::ZERO;
+    for i in 0..9 {
+        irradiance += sh[i] * BANDS[i] * basis[i];
+    }
+    irradiance.max(Vec3::ZERO)
+}
+
+/// LUT de la BRDF del split-sum: escala (R) y sesgo (G) del F0, con N·V en
+/// el eje X y la rugosidad en el Y
+pub fn brdf_lut(size: u32, sample_count: u32) -> Vec<Vec2> {
+    let size = size.max(1);
+    let samples = hammersley_set(sample_count.max(1));
+    let mut lut = Vec::with_capacity((size * size) as usize);
+    for y in 0..size {
+        let roughness = (y as f32 + 0.5) / size as f32;
+        // Geometría de Smith con el k de la IBL
+        let k = roughness * roughness / 2.0;
+        let g1 = |cos: f32| cos / (cos * (1.0 - k) + k);
+        for x in 0..size {
+            let n_dot_v = (x as f32 + 0.5) / size as f32;
+            let view = Vec3::new((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+            let mut scale_bias = Vec2::ZERO;
+            for &xi in &samples {
+                let half = importance_sample_ggx(xi, Vec3::Z, roughness);
+                let light = 2.0 * view.dot(half) * half - view;
+                let n_dot_l = light.z.max(0.0);
+                let n_dot_h = half.z.max(0.0);
+                let v_dot_h = view.dot(half).max(0.0);
+                if n_dot_l <= 0.0 {
+                    continue;
+                }
+                let visibility = g1(n_dot_v) * g1(n_dot_l) * v_dot_h / (n_dot_h * n_dot_v).max(1e-6);
+                let fresnel = (1.0 - v_dot_h).powi(5);
+                scale_bias += Vec2::new((1.0 - fresnel) * visibility, fresnel * visibility);
+            }
+            lut.push(scale_bias / samples.len() as f32);
+        }
+    }
+    lut
+}
+
+/// Convertir a half float (redondeo al par más cercano, con infinitos y
+/// subnormales)
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // Infinito o NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x0200 } else { 0 };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let midpoint = 1 << (shift - 1);
+        let round = remainder > midpoint || (remainder == midpoint && half & 1 == 1);
+        return sign | (half + round as u32) as u16;
+    }
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1fff;
+    let round = remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1);
+    // El acarreo del redondeo puede pasar al exponente, que es lo correcto
+    sign | (half + round as u32) as u16
+}
@@ -0,0 +1,442 @@
+//! # Cargador glTF
+//!
+//! Importa modelos glTF 2.0 (`.gltf` + `.bin` y `.glb`) a meshes, materiales
+//! PBR y texturas del renderer, junto con la jerarquía de nodos y los skins.
+//! En WASM, sin sistema de archivos, los modelos se cargan desde bytes.
+
+use anyhow::{Result, anyhow};
+use glam::{Mat4, Quat, Vec3, Vec4};
+use std::collections::HashMap;
+
+use super::{
+    BoundingBox, BoundingSphere, Geometry, Material, MaterialProperties, MaterialType, Mesh,
+    MorphTargetGeometry, Texture, TextureConfig, TextureFilter, TextureFormat, TextureType, TextureWrap, Vertex,
+};
+
+/// Modelo importado
+#[derive(Debug, Clone, Default)]
+pub struct ModelData {
+    /// Nombre del modelo
+    pub name: String,
+    /// Meshes (una por primitiva)
+    pub meshes: Vec<Mesh>,
+    /// Materiales
+    pub materials: Vec<Material>,
+    /// Texturas
+    pub textures: Vec<Texture>,
+    /// Nodos
+    pub nodes: Vec<ModelNode>,
+    /// Nodos raíz de la escena
+    pub roots: Vec<usize>,
+    /// Skins
+    pub skins: Vec<ModelSkin>,
+}
+
+/// Nodo del modelo
+#[derive(Debug, Clone)]
+pub struct ModelNode {
+    /// Nombre
+    pub name: String,
+    /// Nodo padre
+    pub parent: Option<usize>,
+    /// Nodos hijos
+    pub children: Vec<usize>,
+    /// Traslación local
+    pub translation: Vec3,
+    /// Rotación local
+    pub rotation: Quat,
+    /// Escala local
+    pub scale: Vec3,
+    /// Meshes del nodo
+    pub meshes: Vec<String>,
+    /// Skin del nodo
+    pub skin: Option<usize>,
+}
+
+/// Skin del modelo
+#[derive(Debug, Clone)]
+pub struct ModelSkin {
+    /// Nombre
+    pub name: String,
+    /// Nodos articulación
+    pub joints: Vec<usize>,
+    /// Matrices de bind inversas (una por articulación)
+    pub inverse_bind_matrices: Vec<Mat4>,
+    /// Nodo raíz del esqueleto
+    pub skeleton: Option<usize>,
+}
+
+impl ModelData {
+    /// Profundidad máxima de la jerarquía de nodos (1 = solo raíces)
+    pub fn hierarchy_depth(&self) -> usize {
+        fn depth(nodes: &[ModelNode], index: usize) -> usize {
+            1 + nodes[index].children.iter().map(|&child| depth(nodes, child)).max().unwrap_or(0)
+        }
+        self.roots.iter().map(|&root| depth(&self.nodes, root)).max().unwrap_or(0)
+    }
+
+    /// Matriz local de un nodo
+    pub fn local_matrix(&self, index: usize) -> Mat4 {
+        let node = &self.nodes[index];
+        Mat4::from_scale_rotation_translation(node.scale, node.rotation, node.translation)
+    }
+}
+
+/// Cargar un modelo desde archivo (`.gltf` con buffers externos o `.glb`)
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_gltf(path: &std::path::Path) -> Result<ModelData> {
+    let (document, buffers, images) = gltf::import(path)
+        .map_err(|e| anyhow!("Error importando glTF {}: {}", path.display(), e))?;
+    let name = path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "model".to_string());
+    build_model(&name, &document, &buffers, &images)
+}
+
+/// Cargar un modelo desde bytes (`.glb` o `.gltf` con buffers embebidos)
+pub fn load_gltf_slice(name: &str, bytes: &[u8]) -> Result<ModelData> {
+    let (document, buffers, images) = gltf::import_slice(bytes)
+        .map_err(|e| anyhow!("Error importando glTF {}: {}", name, e))?;
+    build_model(name, &document, &buffers, &images)
+}
+
+/// Construir el modelo a partir del documento y sus datos
+fn build_model(
+    name: &str,
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+    images: &[gltf::image::Data],
+) -> Result<ModelData> {
+    let mut model = ModelData {
+        name: name.to_string(),
+        ..Default::default()
+    };
+
+    // Materiales, registrando el uso de cada textura
+    let mut texture_roles: HashMap<usize, TextureType> = HashMap::new();
+    for material in document.materials() {
+        if let Some(index) = material.index() {
+            model.materials.push(import_material(name, index, &material, &mut texture_roles));
+        }
+    }
+
+    // Texturas
+    for texture in document.textures() {
+        let image = images.get(texture.source().index())
+            .ok_or_else(|| anyhow!("Imagen {} no encontrada", texture.source().index()))?;
+        let texture_type = texture_roles.get(&texture.index())
+            .cloned()
+            .unwrap_or_else(|| TextureType::Custom("gltf".to_string()));
+        model.textures.push(import_texture(name, texture.index(), texture_type, image));
+    }
+
+    // Meshes: una por primitiva
+    let mut mesh_ids: HashMap<usize, Vec<String>> = HashMap::new();
+    for mesh in document.meshes() {
+        for primitive in mesh.primitives() {
+            let imported = import_primitive(name, &mesh, &primitive, buffers)?;
+            mesh_ids.entry(mesh.index()).or_default().push(imported.id.clone());
+            model.meshes.push(imported);
+        }
+    }
+
+    // Nodos
+    for node in document.nodes() {
+        let (translation, rotation, scale) = node.transform().decomposed();
+        model.nodes.push(ModelNode {
+            name: node.name().map(str::to_string).unwrap_or_else(|| format!("node{}", node.index())),
+            parent: None,
+            children: node.children().map(|child| child.index()).collect(),
+            translation: Vec3::from(translation),
+            rotation: Quat::from_array(rotation),
+            scale: Vec3::from(scale),
+            meshes: node.mesh().and_then(|mesh| mesh_ids.get(&mesh.index()).cloned()).unwrap_or_default(),
+            skin: node.skin().map(|skin| skin.index()),
+        });
+    }
+    for index in 0..model.nodes.len() {
+        for child in model.nodes[index].children.clone() {
+            model.nodes[child].parent = Some(index);
+        }
+    }
+
+    model.roots = match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+        None => (0..model.nodes.len()).filter(|&i| model.nodes[i].parent.is_none()).collect(),
+    };
+
+    // Skins
+    for skin in document.skins() {
+        let reader = skin.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+        let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+        let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+            Some(matrices) => matrices.map(|m| Mat4::from_cols_array_2d(&m)).collect(),
+            // Sin matrices explícitas glTF define la identidad
+            None => vec![Mat4::IDENTITY; joints.len()],
+        };
+        model.skins.push(ModelSkin {
+            name: skin.name().map(str::to_string).unwrap_or_else(|| format!("skin{}", skin.index())),
+            joints,
+            inverse_bind_matrices,
+            skeleton: skin.skeleton().map(|node| node.index()),
+        });
+    }
+
+    Ok(model)
+}
+
+/// Importar un material PBR
+fn import_material(
+    model: &str,
+    index: usize,
+    material: &gltf::Material,
+    texture_roles: &mut HashMap<usize, TextureType>,
+) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let mut textures = HashMap::new();
+    let mut bind = |slot: &str, texture: gltf::Texture, texture_type: TextureType| {
+        textures.insert(slot.to_string(), texture_id(model, texture.index()));
+        texture_roles.entry(texture.index()).or_insert(texture_type);
+    };
+
+    if let Some(info) = pbr.base_color_texture() {
+        bind("base_color", info.texture(), TextureType::Diffuse);
+    }
+    if let Some(info) = pbr.metallic_roughness_texture() {
+        bind("metallic_roughness", info.texture(), TextureType::Metallic);
+    }
+    if let Some(normal) = material.normal_texture() {
+        bind("normal", normal.texture(), TextureType::Normal);
+    }
+    if let Some(occlusion) = material.occlusion_texture() {
+        bind("occlusion", occlusion.texture(), TextureType::Occlusion);
+    }
+    if let Some(info) = material.emissive_texture() {
+        bind("emissive", info.texture(), TextureType::Emissive);
+    }
+
+    Material {
+        id: material_id(model, index),
+        name: material.name().map(str::to_string).unwrap_or_else(|| format!("material{}", index)),
+        material_type: MaterialType::PBR,
+        properties: MaterialProperties {
+            base_color: Vec4::from_array(pbr.base_color_factor()),
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            emissive: Vec3::from(material.emissive_factor()),
+            normal_scale: material.normal_texture().map(|n| n.scale()).unwrap_or(1.0),
+            occlusion_strength: material.occlusion_texture().map(|o| o.strength()).unwrap_or(1.0),
+            alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+            double_sided: material.double_sided(),
+        },
+        textures,
+        shader: "pbr_standard".to_string(),
+    }
+}
+
+/// Importar una textura
+fn import_texture(model: &str, index: usize, texture_type: TextureType, image: &gltf::image::Data) -> Texture {
+    use gltf::image::Format;
+
+    let format = match image.format {
+        Format::R8 => TextureFormat::R8,
+        Format::R8G8 => TextureFormat::RG8,
+        Format::R8G8B8 => TextureFormat::RGB8,
+        Format::R8G8B8A8 => TextureFormat::RGBA8,
+        Format::R16 => TextureFormat::R16F,
+        Format::R16G16 => TextureFormat::RG16F,
+        Format::R16G16B16 => TextureFormat::RGB16F,
+        Format::R16G16B16A16 => TextureFormat::RGBA16F,
+        Format::R32G32B32FLOAT => TextureFormat::RGB32F,
+        Format::R32G32B32A32FLOAT => TextureFormat::RGBA32F,
+    };
+
+    Texture {
+        id: texture_id(model, index),
+        name: format!("texture{}", index),
+        texture_type,
+        config: TextureConfig {
+            width: image.width,
+            height: image.height,
+            format,
+            filter: TextureFilter::LinearMipmapLinear,
+            wrap: TextureWrap::Repeat,
+            mipmaps: true,
+        },
+        data: Some(image.pixels.clone()),
+    }
+}
+
+/// Importar una primitiva como mesh
+fn import_primitive(
+    model: &str,
+    mesh: &gltf::Mesh,
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Mesh> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let id = format!("{}#mesh{}/{}", model, mesh.index(), primitive.index());
+
+    let positions: Vec<Vec3> = reader.read_positions()
+        .ok_or_else(|| anyhow!("Primitiva sin posiciones: {}", id))?
+        .map(Vec3::from)
+        .collect();
+    let normals: Vec<Vec3> = reader.read_normals()
+        .map(|normals| normals.map(Vec3::from).collect())
+        .unwrap_or_default();
+    let tangents: Vec<Vec3> = reader.read_tangents()
+        .map(|tangents| tangents.map(|t| Vec3::new(t[0], t[1], t[2])).collect())
+        .unwrap_or_default();
+    let uvs: Vec<Vec3> = reader.read_tex_coords(0)
+        .map(|uvs| uvs.into_f32().map(|uv| Vec3::new(uv[0], uv[1], 0.0)).collect())
+        .unwrap_or_default();
+    let colors: Vec<Vec4> = reader.read_colors(0)
+        .map(|colors| colors.into_rgba_f32().map(Vec4::from_array).collect())
+        .unwrap_or_default();
+    // Influencias del skin: cuatro articulaciones y pesos por vértice
+    let joints: Vec<[u16; 4]> = reader.read_joints(0)
+        .map(|joints| joints.into_u16().collect())
+        .unwrap_or_default();
+    let weights: Vec<[f32; 4]> = reader.read_weights(0)
+        .map(|weights| weights.into_f32().collect())
+        .unwrap_or_default();
+    // Morph targets: el ID es el índice, como en los pesos de glTF
+    let morph_targets: Vec<MorphTargetGeometry> = reader.read_morph_targets()
+        .enumerate()
+        .map(|(index, (positions, normals, _))| MorphTargetGeometry {
+            id: index.to_string(),
+            position_deltas: positions.map(|deltas| deltas.map(Vec3::from).collect()).unwrap_or_default(),
+            normal_deltas: normals.map(|deltas| deltas.map(Vec3::from).collect()).unwrap_or_default(),
+        })
+        .collect();
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        // Primitiva no indexada: cada tres vértices forman un triángulo
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    let vertices: Vec<Vertex> = positions.iter()
+        .enumerate()
+        .map(|(i, position)| Vertex {
+            position: *position,
+            normal: normals.get(i).copied().unwrap_or(Vec3::Y),
+            tangent: tangents.get(i).copied().unwrap_or(Vec3::ZERO),
+            uv: uvs.get(i).copied().unwrap_or(Vec3::ZERO),
+            color: colors.get(i).copied().unwrap_or(Vec4::ONE),
+        })
+        .collect();
+
+    let bounds = primitive.bounding_box();
+    let (min, max) = (Vec3::from(bounds.min), Vec3::from(bounds.max));
+    let center = (min + max) * 0.5;
+
+    Ok(Mesh {
+        id,
+        name: mesh.name().map(str::to_string).unwrap_or_else(|| format!("mesh{}", mesh.index())),
+        geometry: Geometry {
+            vertices,
+            indices,
+            bounding_box: BoundingBox { min, max },
+            bounding_sphere: BoundingSphere { center, radius: (max - center).length() },
+            joints,
+            weights,
+            morph_targets,
+        },
+        material: primitive.material().index().map(|index| material_id(model, index)),
+        lod: Vec::new(),
+    })
+}
+
+/// ID de un material importado
+fn material_id(model: &str, index: usize) -> String {
+    format!("{}#material{}", model, index)
+}
+
+/// ID de una textura importada
+fn texture_id(model: &str, index: usize) -> String {
+    format!("{}#texture{}", model, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// GLB embebido: un triángulo compartido por dos meshes con materiales
+    /// rojo y verde, colgadas de una jerarquía raíz → cuerpo → mano
+    fn fixture_glb() -> Vec<u8> {
+        let mut bin: Vec<u8> = Vec::new();
+        for position in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            bin.extend(position.iter().flat_map(|c| c.to_le_bytes()));
+        }
+        bin.extend([0u16, 1, 2].iter().flat_map(|i| i.to_le_bytes()));
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let json = serde_json::json!({
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "name": "root", "children": [1] },
+                { "name": "body", "mesh": 0, "children": [2], "translation": [0.0, 1.0, 0.0] },
+                { "name": "hand", "mesh": 1, "translation": [0.5, 0.0, 0.0] }
+            ],
+            "meshes": [
+                { "name": "body", "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 }] },
+                { "name": "hand", "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "material": 1 }] }
+            ],
+            "materials": [
+                { "name": "red", "pbrMetallicRoughness": { "baseColorFactor": [1.0, 0.0, 0.0, 1.0] } },
+                { "name": "green", "pbrMetallicRoughness": { "baseColorFactor": [0.0, 1.0, 0.0, 0.5] } }
+            ],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] },
+                { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+            ],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+            ],
+            "buffers": [{ "byteLength": bin.len() }]
+        });
+        let mut json = serde_json::to_vec(&json).unwrap();
+        json.resize(json.len().next_multiple_of(4), b' ');
+
+        let total = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::with_capacity(total);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(total as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    #[test]
+    fn embedded_glb_imports_meshes_materials_and_hierarchy() {
+        let model = load_gltf_slice("fixture", &fixture_glb()).unwrap();
+
+        assert_eq!(model.meshes.len(), 2);
+        assert!(model.meshes.iter().all(|mesh| mesh.geometry.vertices.len() == 3 && mesh.geometry.indices == [0, 1, 2]));
+        assert_eq!(model.meshes[0].material.as_deref(), Some("fixture#material0"));
+
+        let base_colors: Vec<Vec4> = model.materials.iter().map(|m| m.properties.base_color).collect();
+        assert_eq!(base_colors, [Vec4::new(1.0, 0.0, 0.0, 1.0), Vec4::new(0.0, 1.0, 0.0, 0.5)]);
+
+        assert_eq!(model.roots, [0]);
+        assert_eq!(model.hierarchy_depth(), 3);
+        assert_eq!(model.nodes[2].parent, Some(1));
+        assert_eq!(model.nodes[2].meshes, ["fixture#mesh1/0"]);
+        assert_eq!(model.nodes[1].translation, Vec3::Y);
+    }
+
+    #[test]
+    fn truncated_glb_is_an_error() {
+        let glb = fixture_glb();
+        assert!(load_gltf_slice("fixture", &glb[..glb.len() / 2]).is_err());
+    }
+}
@@ -0,0 +1,597 @@
+//! # Render graph
+//!
+//! Grafo de los pases de un frame. Cada pase declara por handle los recursos
+//! (texturas y buffers) que lee y escribe. Al compilar, el grafo ordena los
+//! pases topológicamente, descarta los que no contribuyen a ninguna salida,
+//! reparte la memoria de las texturas transitorias entre recursos con vidas
+//! disjuntas y calcula las transiciones de estado antes de cada pase. El
+//! grafo no conoce el backend: cada pase lleva un payload que el backend
+//! interpreta al grabarlo.
+//!
+//! Dependencias: los escritores de un recurso se ejecutan en el orden en que
+//! se declararon y los pases que solo lo leen van después de todos ellos. Un
+//! recurso que se reescribe con otro contenido debe ser un recurso nuevo; el
+//! aliasing de transitorios evita que eso cueste memoria.
+
+use std::collections::{BTreeSet, HashSet};
+use thiserror::Error;
+
+/// Handle de un recurso del grafo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResourceHandle(usize);
+
+impl ResourceHandle {
+    /// Índice del recurso en el grafo
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Handle de un pase del grafo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PassHandle(usize);
+
+/// Formato de una textura del grafo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    /// Color de 8 bits por canal
+    Rgba8Unorm,
+    /// Color HDR
+    Rgba16Float,
+    /// Profundidad
+    Depth32Float,
+    /// Entero sin signo de un canal (imagen de tasas del VRS)
+    R32Uint,
+    /// Flotante de un canal (pirámide de profundidad del occlusion culling)
+    R32Float,
+}
+
+/// Descripción de una textura
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureDesc {
+    /// Ancho
+    pub width: u32,
+    /// Alto
+    pub height: u32,
+    /// Capas
+    pub layers: u32,
+    /// Formato
+    pub format: TextureFormat,
+}
+
+impl TextureDesc {
+    /// Textura 2D de una capa
+    pub fn new_2d(width: u32, height: u32, format: TextureFormat) -> Self {
+        Self { width: width.max(1), height: height.max(1), layers: 1, format }
+    }
+}
+
+/// Descripción de un recurso. Dos transitorios comparten memoria solo si
+/// sus descripciones son iguales
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceDesc {
+    /// Textura
+    Texture(TextureDesc),
+    /// Buffer de `size` bytes
+    Buffer { size: u64 },
+}
+
+/// Estado de un recurso entre pases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceState {
+    /// Contenido indefinido (transitorio antes de su primera escritura)
+    Undefined,
+    /// Destino de color
+    ColorTarget,
+    /// Destino de profundidad
+    DepthTarget,
+    /// Muestreado desde un shader
+    ShaderRead,
+    /// Escrito desde un compute shader
+    StorageWrite,
+    /// Origen de una copia
+    CopySource,
+    /// Destino de una copia
+    CopyDest,
+    /// Listo para presentar
+    Present,
+}
+
+/// Transición de estado de un recurso
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Barrier {
+    /// Recurso
+    pub resource: ResourceHandle,
+    /// Estado anterior
+    pub before: ResourceState,
+    /// Estado requerido
+    pub after: ResourceState,
+}
+
+/// Errores de construcción del grafo
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum GraphError {
+    /// Dependencias circulares entre pases
+    #[error("ciclo entre los pases: {}", .0.join(", "))]
+    Cycle(Vec<String>),
+    /// Un pase lee un transitorio que ningún pase escribe
+    #[error("el pase '{pass}' lee '{resource}' sin que ningún pase lo escriba")]
+    ReadBeforeWrite { pass: String, resource: String },
+}
+
+/// Recurso declarado
+#[derive(Debug, Clone)]
+struct ResourceNode {
+    name: String,
+    desc: ResourceDesc,
+    /// Estado inicial si es importado (None si es transitorio)
+    imported: Option<ResourceState>,
+    /// Estado final requerido si es una salida del frame
+    output: Option<ResourceState>,
+}
+
+/// Pase declarado
+#[derive(Debug, Clone)]
+struct PassNode<P> {
+    name: String,
+    payload: P,
+    reads: Vec<(ResourceHandle, ResourceState)>,
+    writes: Vec<(ResourceHandle, ResourceState)>,
+    /// El pase no se descarta aunque nadie lea lo que escribe
+    side_effect: bool,
+}
+
+/// Grafo de pases de un frame
+#[derive(Debug, Clone)]
+pub struct RenderGraph<P> {
+    resources: Vec<ResourceNode>,
+    passes: Vec<PassNode<P>>,
+}
+
+impl<P> Default for RenderGraph<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P> RenderGraph<P> {
+    /// Crear grafo vacío
+    pub fn new() -> Self {
+        Self { resources: Vec::new(), passes: Vec::new() }
+    }
+
+    /// Declarar un recurso transitorio: su memoria solo vive dentro del frame
+    pub fn create(&mut self, name: &str, desc: ResourceDesc) -> ResourceHandle {
+        self.push_resource(name, desc, None)
+    }
+
+    /// Declarar una textura transitoria
+    pub fn create_texture(&mut self, name: &str, desc: TextureDesc) -> ResourceHandle {
+        self.create(name, ResourceDesc::Texture(desc))
+    }
+
+    /// Importar un recurso externo al grafo (no se le asigna memoria)
+    pub fn import(&mut self, name: &str, desc: ResourceDesc, state: ResourceState) -> ResourceHandle {
+        self.push_resource(name, desc, Some(state))
+    }
+
+    /// Importar una textura externa
+    pub fn import_texture(&mut self, name: &str, desc: TextureDesc, state: ResourceState) -> ResourceHandle {
+        self.import(name, ResourceDesc::Texture(desc), state)
+    }
+
+    fn push_resource(&mut self, name: &str, desc: ResourceDesc, imported: Option<ResourceState>) -> ResourceHandle {
+        self.resources.push(ResourceNode { name: name.to_string(), desc, imported, output: None });
+        ResourceHandle(self.resources.len() - 1)
+    }
+
+    /// Marcar un recurso como salida del frame, que debe quedar en `state`
+    pub fn set_output(&mut self, resource: ResourceHandle, state: ResourceState) {
+        self.resources[resource.0].output = Some(state);
+    }
+
+    /// Añadir un pase
+    pub fn add_pass(&mut self, name: &str, payload: P) -> PassBuilder<'_, P> {
+        self.passes.push(PassNode {
+            name: name.to_string(),
+            payload,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            side_effect: false,
+        });
+        let pass = self.passes.len() - 1;
+        PassBuilder { graph: self, pass }
+    }
+
+    /// Nombre de un recurso
+    pub fn resource_name(&self, resource: ResourceHandle) -> &str {
+        &self.resources[resource.0].name
+    }
+
+    /// Descripción de un recurso
+    pub fn resource_desc(&self, resource: ResourceHandle) -> ResourceDesc {
+        self.resources[resource.0].desc
+    }
+
+    /// Número de pases declarados
+    pub fn pass_count(&self) -> usize {
+        self.passes.len()
+    }
+
+    /// Escritores de cada recurso en orden de declaración
+    fn writers(&self) -> Vec<Vec<usize>> {
+        let mut writers = vec![Vec::new(); self.resources.len()];
+        for (index, pass) in self.passes.iter().enumerate() {
+            for (resource, _) in &pass.writes {
+                if writers[resource.0].last() != Some(&index) {
+                    writers[resource.0].push(index);
+                }
+            }
+        }
+        writers
+    }
+
+    /// Pases de los que depende cada pase
+    fn dependencies(&self, writers: &[Vec<usize>]) -> Vec<BTreeSet<usize>> {
+        self.passes.iter().enumerate()
+            .map(|(index, pass)| {
+                let mut dependencies = BTreeSet::new();
+                for (resource, _) in &pass.writes {
+                    // Escritura tras escritura: el escritor declarado antes
+                    let resource_writers = &writers[resource.0];
+                    let position = resource_writers.iter().position(|&w| w == index).unwrap_or(0);
+                    if position > 0 {
+                        dependencies.insert(resource_writers[position - 1]);
+                    }
+                }
+                for (resource, _) in &pass.reads {
+                    let resource_writers = &writers[resource.0];
+                    if resource_writers.contains(&index) {
+                        // Lectura-escritura: ve lo escrito por los escritores anteriores
+                        dependencies.extend(resource_writers.iter().take_while(|&&w| w != index));
+                    } else {
+                        dependencies.extend(resource_writers.iter().copied());
+                    }
+                }
+                dependencies
+            })
+            .collect()
+    }
+
+    /// Compilar: orden, descarte, aliasing y transiciones
+    pub fn compile(self) -> Result<CompiledGraph<P>, GraphError> {
+        let writers = self.writers();
+        let dependencies = self.dependencies(&writers);
+
+        // Orden topológico (Kahn), respetando el orden de declaración entre pases independientes
+        let mut pending: Vec<usize> = dependencies.iter().map(|d| d.len()).collect();
+        let mut dependents = vec![Vec::new(); self.passes.len()];
+        for (index, pass_dependencies) in dependencies.iter().enumerate() {
+            for &dependency in pass_dependencies {
+                dependents[dependency].push(index);
+            }
+        }
+        let mut ready: BTreeSet<usize> = (0..self.passes.len()).filter(|&i| pending[i] == 0).collect();
+        let mut order = Vec::with_capacity(self.passes.len());
+        while let Some(index) = ready.pop_first() {
+            order.push(index);
+            for &dependent in &dependents[index] {
+                pending[dependent] -= 1;
+                if pending[dependent] == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+        if order.len() < self.passes.len() {
+            let cycle = (0..self.passes.len())
+                .filter(|&i| pending[i] > 0)
+                .map(|i| self.passes[i].name.clone())
+                .collect();
+            return Err(GraphError::Cycle(cycle));
+        }
+
+        // Descarte: solo viven los pases de los que depende alguna salida
+        let mut live = HashSet::new();
+        let mut stack: Vec<usize> = self.passes.iter().enumerate()
+            .filter(|(_, pass)| {
+                pass.side_effect || pass.writes.iter().any(|(r, _)| self.resources[r.0].output.is_some())
+            })
+            .map(|(index, _)| index)
+            .collect();
+        while let Some(index) = stack.pop() {
+            if live.insert(index) {
+                stack.extend(dependencies[index].iter().copied());
+            }
+        }
+
+        for &index in order.iter().filter(|i| live.contains(*i)) {
+            let pass = &self.passes[index];
+            for (resource, _) in &pass.reads {
+                let node = &self.resources[resource.0];
+                if node.imported.is_none() && writers[resource.0].is_empty() {
+                    return Err(GraphError::ReadBeforeWrite { pass: pass.name.clone(), resource: node.name.clone() });
+                }
+            }
+        }
+
+        let live_order: Vec<usize> = order.iter().copied().filter(|i| live.contains(i)).collect();
+        let (physical, physical_resources) = self.alias_transients(&live_order);
+
+        // Transiciones: estado de cada recurso según el orden final
+        let mut states: Vec<ResourceState> = self.resources.iter()
+            .map(|node| node.imported.unwrap_or(ResourceState::Undefined))
+            .collect();
+        let mut transition = |resource: ResourceHandle, after: ResourceState, barriers: &mut Vec<Barrier>| {
+            let before = states[resource.0];
+            if before != after {
+                barriers.push(Barrier { resource, before, after });
+                states[resource.0] = after;
+            }
+        };
+
+        let culled = order.iter()
+            .filter(|i| !live.contains(*i))
+            .map(|&i| self.passes[i].name.clone())
+            .collect();
+        let resources: Vec<(String, ResourceDesc)> = self.resources.iter()
+            .map(|node| (node.name.clone(), node.desc))
+            .collect();
+        let outputs: Vec<(ResourceHandle, ResourceState)> = self.resources.iter().enumerate()
+            .filter_map(|(index, node)| node.output.map(|state| (ResourceHandle(index), state)))
+            .collect();
+
+        let mut slots: Vec<Option<PassNode<P>>> = self.passes.into_iter().map(Some).collect();
+        let mut passes = Vec::with_capacity(live_order.len());
+        for index in live_order {
+            let Some(node) = slots[index].take() else {
+                continue;
+            };
+            let mut barriers = Vec::new();
+            // Un recurso leído y escrito por el mismo pase queda en el estado de escritura
+            for &(resource, state) in &node.reads {
+                if !node.writes.iter().any(|(w, _)| *w == resource) {
+                    transition(resource, state, &mut barriers);
+                }
+            }
+            for &(resource, state) in &node.writes {
+                transition(resource, state, &mut barriers);
+            }
+            passes.push(CompiledPass {
+                handle: PassHandle(index),
+                name: node.name,
+                payload: node.payload,
+                reads: node.reads.iter().map(|(r, _)| *r).collect(),
+                writes: node.writes.iter().map(|(r, _)| *r).collect(),
+                barriers,
+            });
+        }
+
+        let mut final_barriers = Vec::new();
+        for (resource, state) in outputs {
+            transition(resource, state, &mut final_barriers);
+        }
+
+        Ok(CompiledGraph { passes, culled, resources, physical, physical_resources, final_barriers })
+    }
+
+    /// Asignar memoria física a los transitorios usados por `order`. Un
+    /// transitorio reutiliza la de otro de igual descripción cuya vida
+    /// (primer a último pase que lo usa) ya terminó
+    fn alias_transients(&self, order: &[usize]) -> (Vec<Option<usize>>, Vec<ResourceDesc>) {
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.resources.len()];
+        for (position, &index) in order.iter().enumerate() {
+            let pass = &self.passes[index];
+            for (resource, _) in pass.reads.iter().chain(&pass.writes) {
+                let lifetime = lifetimes[resource.0].get_or_insert((position, position));
+                lifetime.1 = position;
+            }
+        }
+        // Las salidas viven hasta el final del frame
+        for (index, node) in self.resources.iter().enumerate() {
+            if node.output.is_some() {
+                if let Some(lifetime) = &mut lifetimes[index] {
+                    lifetime.1 = order.len();
+                }
+            }
+        }
+
+        let mut transients: Vec<(usize, (usize, usize))> = lifetimes.iter().enumerate()
+            .filter(|(index, _)| self.resources[*index].imported.is_none())
+            .filter_map(|(index, lifetime)| lifetime.map(|lifetime| (index, lifetime)))
+            .collect();
+        transients.sort_by_key(|(index, (first, _))| (*first, *index));
+
+        let mut physical = vec![None; self.resources.len()];
+        let mut physical_resources: Vec<ResourceDesc> = Vec::new();
+        // Último pase que usa cada recurso físico
+        let mut busy_until: Vec<usize> = Vec::new();
+        for (index, (first, last)) in transients {
+            let desc = self.resources[index].desc;
+            let free = (0..physical_resources.len())
+                .find(|&slot| physical_resources[slot] == desc && busy_until[slot] < first);
+            let slot = match free {
+                Some(slot) => slot,
+                None => {
+                    physical_resources.push(desc);
+                    busy_until.push(0);
+                    physical_resources.len() - 1
+                }
+            };
+            busy_until[slot] = last;
+            physical[index] = Some(slot);
+        }
+        (physical, physical_resources)
+    }
+}
+
+/// Constructor de las dependencias de un pase
+pub struct PassBuilder<'a, P> {
+    graph: &'a mut RenderGraph<P>,
+    pass: usize,
+}
+
+impl<P> PassBuilder<'_, P> {
+    /// Leer un recurso en `state` (normalmente `ShaderRead`)
+    pub fn read(self, resource: ResourceHandle, state: ResourceState) -> Self {
+        self.graph.passes[self.pass].reads.push((resource, state));
+        self
+    }
+
+    /// Escribir un recurso en `state` (destino de color, profundidad o copia)
+    pub fn write(self, resource: ResourceHandle, state: ResourceState) -> Self {
+        self.graph.passes[self.pass].writes.push((resource, state));
+        self
+    }
+
+    /// No descartar el pase aunque nadie lea lo que escribe
+    pub fn side_effect(self) -> Self {
+        self.graph.passes[self.pass].side_effect = true;
+        self
+    }
+
+    /// Handle del pase
+    pub fn handle(self) -> PassHandle {
+        PassHandle(self.pass)
+    }
+}
+
+/// Pase compilado, en orden de ejecución
+#[derive(Debug, Clone)]
+pub struct CompiledPass<P> {
+    /// Handle con el que se declaró
+    pub handle: PassHandle,
+    /// Nombre
+    pub name: String,
+    /// Payload del backend
+    pub payload: P,
+    /// Recursos leídos, en orden de declaración
+    pub reads: Vec<ResourceHandle>,
+    /// Recursos escritos, en orden de declaración
+    pub writes: Vec<ResourceHandle>,
+    /// Transiciones a aplicar antes del pase
+    pub barriers: Vec<Barrier>,
+}
+
+/// Grafo compilado listo para ejecutar
+#[derive(Debug, Clone)]
+pub struct CompiledGraph<P> {
+    /// Pases vivos en orden de ejecución
+    passes: Vec<CompiledPass<P>>,
+    /// Nombres de los pases descartados
+    culled: Vec<String>,
+    /// Nombre y descripción de cada recurso
+    resources: Vec<(String, ResourceDesc)>,
+    /// Recurso físico de cada transitorio usado
+    physical: Vec<Option<usize>>,
+    /// Descripción de cada recurso físico
+    physical_resources: Vec<ResourceDesc>,
+    /// Transiciones de las salidas al final del frame
+    final_barriers: Vec<Barrier>,
+}
+
+impl<P> CompiledGraph<P> {
+    /// Pases en orden de ejecución
+    pub fn passes(&self) -> &[CompiledPass<P>] {
+        &self.passes
+    }
+
+    /// Nombres de los pases descartados por no contribuir a ninguna salida
+    pub fn culled_passes(&self) -> &[String] {
+        &self.culled
+    }
+
+    /// Verificar si un pase sigue en el grafo
+    pub fn contains_pass(&self, name: &str) -> bool {
+        self.passes.iter().any(|pass| pass.name == name)
+    }
+
+    /// Nombre de un recurso
+    pub fn resource_name(&self, resource: ResourceHandle) -> &str {
+        &self.resources[resource.0].0
+    }
+
+    /// Descripción de un recurso
+    pub fn resource_desc(&self, resource: ResourceHandle) -> ResourceDesc {
+        self.resources[resource.0].1
+    }
+
+    /// Recurso físico de un transitorio (None si es importado o no se usa)
+    pub fn physical_index(&self, resource: ResourceHandle) -> Option<usize> {
+        self.physical[resource.0]
+    }
+
+    /// Recursos físicos que necesita el frame
+    pub fn physical_resources(&self) -> &[ResourceDesc] {
+        &self.physical_resources
+    }
+
+    /// Transiciones de las salidas al final del frame
+    pub fn final_barriers(&self) -> &[Barrier] {
+        &self.final_barriers
+    }
+
+    /// Total de transiciones del frame
+    pub fn barrier_count(&self) -> usize {
+        self.passes.iter().map(|pass| pass.barriers.len()).sum::<usize>() + self.final_barriers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(width: u32) -> TextureDesc {
+        TextureDesc::new_2d(width, 64, TextureFormat::Rgba16Float)
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut graph = RenderGraph::new();
+        let a = graph.create_texture("a", color(64));
+        let b = graph.create_texture("b", color(64));
+        graph.add_pass("first", ())
+            .read(b, ResourceState::ShaderRead)
+            .write(a, ResourceState::ColorTarget)
+            .side_effect();
+        graph.add_pass("second", ())
+            .read(a, ResourceState::ShaderRead)
+            .write(b, ResourceState::ColorTarget)
+            .side_effect();
+
+        let error = graph.compile().unwrap_err();
+        assert_eq!(error, GraphError::Cycle(vec!["first".to_string(), "second".to_string()]));
+    }
+
+    #[test]
+    fn transients_with_disjoint_lifetimes_share_memory() {
+        let mut graph = RenderGraph::new();
+        let output = graph.import_texture("frame", color(64), ResourceState::Undefined);
+        graph.set_output(output, ResourceState::Present);
+        let a = graph.create_texture("a", color(64));
+        let b = graph.create_texture("b", color(64));
+        let c = graph.create_texture("c", color(64));
+        // Misma vida que `c` pero otra descripción: nunca comparte con ella
+        let small = graph.create_texture("small", color(32));
+
+        graph.add_pass("write-a", ()).write(a, ResourceState::ColorTarget);
+        graph.add_pass("a-to-b", ())
+            .read(a, ResourceState::ShaderRead)
+            .write(b, ResourceState::ColorTarget);
+        graph.add_pass("b-to-c", ())
+            .read(b, ResourceState::ShaderRead)
+            .write(c, ResourceState::ColorTarget)
+            .write(small, ResourceState::ColorTarget);
+        graph.add_pass("output", ())
+            .read(c, ResourceState::ShaderRead)
+            .read(small, ResourceState::ShaderRead)
+            .write(output, ResourceState::ColorTarget);
+
+        let compiled = graph.compile().unwrap();
+        // `a` termina antes de que empiece `c`; `b` se solapa con ambas
+        assert_eq!(compiled.physical_index(c), compiled.physical_index(a));
+        assert_ne!(compiled.physical_index(b), compiled.physical_index(a));
+        assert_ne!(compiled.physical_index(small), compiled.physical_index(c));
+        assert_eq!(compiled.physical_index(output), None);
+        assert_eq!(compiled.physical_resources().len(), 3);
+    }
+}
@@ -0,0 +1,465 @@
+//! # Niveles de Detalle
+//!
+//! Simplificación de meshes por colapso de aristas con métricas de error
+//! cuadrático (Garland-Heckbert) y selección del nivel por distancia a la
+//! cámara. Los niveles reducidos solo cambian el index buffer: los vértices
+//! conservados son un subconjunto de los originales.
+
+use glam::{DVec3, Vec3};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use super::LODConfig;
+use crate::ecs::{EntityId, MeshComponent};
+
+/// Peso de los planos que fijan los bordes abiertos del mesh
+const BOUNDARY_WEIGHT: f64 = 1000.0;
+
+/// Coseno mínimo entre la normal de un triángulo antes y después de un
+/// colapso; por debajo el colapso daría la vuelta al triángulo
+const MIN_NORMAL_COSINE: f64 = 0.2;
+
+/// ID del mesh de un nivel de LOD
+pub fn lod_mesh_id(mesh_id: &str, level: u32) -> String {
+    if level == 0 {
+        mesh_id.to_string()
+    } else {
+        format!("{}#lod{}", mesh_id, level)
+    }
+}
+
+/// Cuádrica de error: matriz simétrica 4x4 guardada por su triángulo superior
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Cuádrica del plano `n·p + d = 0` con peso
+    fn from_plane(normal: DVec3, d: f64, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        Self([
+            a * a, a * b, a * c, a * d,
+            b * b, b * c, b * d,
+            c * c, c * d,
+            d * d,
+        ].map(|v| v * weight))
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a += b;
+        }
+    }
+
+    /// Error cuadrático de un punto
+    fn error(&self, p: DVec3) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+/// Colapso candidato en la cola de prioridad
+#[derive(Debug, Clone, Copy)]
+struct Collapse {
+    cost: f64,
+    /// Vértice que se elimina
+    from: u32,
+    /// Vértice que se conserva
+    to: u32,
+    /// Versiones de ambos vértices al calcular el coste
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // Invertido: BinaryHeap es un max-heap y queremos el menor coste
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Estado de la simplificación de un mesh
+struct Simplifier<'a> {
+    positions: Vec<DVec3>,
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    /// Triángulos que usan cada vértice (puede contener triángulos muertos)
+    vertex_triangles: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    removed: Vec<bool>,
+    heap: BinaryHeap<Collapse>,
+    source: &'a [u32],
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(positions: &[Vec3], indices: &'a [u32]) -> Self {
+        let positions: Vec<DVec3> = positions.iter().map(|p| p.as_dvec3()).collect();
+        let triangles: Vec<[u32; 3]> = indices.chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+            .collect();
+
+        let mut vertex_triangles = vec![Vec::new(); positions.len()];
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut edge_count: HashMap<(u32, u32), u32> = HashMap::new();
+        for (index, triangle) in triangles.iter().enumerate() {
+            let [a, b, c] = triangle.map(|v| positions[v as usize]);
+            let cross = (b - a).cross(c - a);
+            let area = cross.length();
+            if let Some(normal) = cross.try_normalize() {
+                // Ponderar por área evita que los triángulos pequeños dominen
+                let quadric = Quadric::from_plane(normal, -normal.dot(a), area);
+                for &v in triangle {
+                    quadrics[v as usize].add(&quadric);
+                }
+            }
+            for (i, &v) in triangle.iter().enumerate() {
+                vertex_triangles[v as usize].push(index);
+                let w = triangle[(i + 1) % 3];
+                *edge_count.entry((v.min(w), v.max(w))).or_insert(0) += 1;
+            }
+        }
+
+        // Bordes abiertos: plano perpendicular a la cara que contiene la arista
+        for triangle in &triangles {
+            let [a, b, c] = triangle.map(|v| positions[v as usize]);
+            let Some(face_normal) = (b - a).cross(c - a).try_normalize() else {
+                continue;
+            };
+            for i in 0..3 {
+                let (v, w) = (triangle[i], triangle[(i + 1) % 3]);
+                if edge_count[&(v.min(w), v.max(w))] != 1 {
+                    continue;
+                }
+                let (pv, pw) = (positions[v as usize], positions[w as usize]);
+                if let Some(normal) = (pw - pv).cross(face_normal).try_normalize() {
+                    let quadric = Quadric::from_plane(normal, -normal.dot(pv), BOUNDARY_WEIGHT);
+                    quadrics[v as usize].add(&quadric);
+                    quadrics[w as usize].add(&quadric);
+                }
+            }
+        }
+
+        let vertex_count = positions.len();
+        let mut simplifier = Self {
+            positions,
+            alive: vec![true; triangles.len()],
+            triangles,
+            vertex_triangles,
+            quadrics,
+            versions: vec![0; vertex_count],
+            removed: vec![false; vertex_count],
+            heap: BinaryHeap::new(),
+            source: indices,
+        };
+        for (v, w) in edge_count.into_keys() {
+            simplifier.push_edge(v, w);
+        }
+        simplifier
+    }
+
+    /// Encolar el colapso más barato de una arista
+    fn push_edge(&mut self, v: u32, w: u32) {
+        let mut quadric = self.quadrics[v as usize];
+        quadric.add(&self.quadrics[w as usize]);
+        let cost_keep_w = quadric.error(self.positions[w as usize]);
+        let cost_keep_v = quadric.error(self.positions[v as usize]);
+        let (from, to, cost) = if cost_keep_w <= cost_keep_v {
+            (v, w, cost_keep_w)
+        } else {
+            (w, v, cost_keep_v)
+        };
+        self.heap.push(Collapse {
+            cost,
+            from,
+            to,
+            versions: (self.versions[from as usize], self.versions[to as usize]),
+        });
+    }
+
+    /// Verificar que mover `from` a la posición de `to` no da la vuelta a ningún triángulo
+    fn preserves_orientation(&self, from: u32, to: u32) -> bool {
+        let target = self.positions[to as usize];
+        self.vertex_triangles[from as usize].iter()
+            .filter(|&&t| self.alive[t] && !self.triangles[t].contains(&to))
+            .all(|&t| {
+                let triangle = self.triangles[t];
+                let corners = triangle.map(|v| self.positions[v as usize]);
+                let moved = triangle.map(|v| if v == from { target } else { self.positions[v as usize] });
+                let before = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+                let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+                match (before.try_normalize(), after.try_normalize()) {
+                    (Some(before), Some(after)) => before.dot(after) >= MIN_NORMAL_COSINE,
+                    _ => false,
+                }
+            })
+    }
+
+    /// Colapsar `from` sobre `to`; devuelve los triángulos eliminados
+    fn collapse(&mut self, from: u32, to: u32) -> usize {
+        let mut removed_triangles = 0;
+        let from_triangles = std::mem::take(&mut self.vertex_triangles[from as usize]);
+        for &t in &from_triangles {
+            if !self.alive[t] {
+                continue;
+            }
+            if self.triangles[t].contains(&to) {
+                self.alive[t] = false;
+                removed_triangles += 1;
+            } else {
+                for v in self.triangles[t].iter_mut() {
+                    if *v == from {
+                        *v = to;
+                    }
+                }
+                self.vertex_triangles[to as usize].push(t);
+            }
+        }
+
+        let quadric = self.quadrics[from as usize];
+        self.quadrics[to as usize].add(&quadric);
+        self.removed[from as usize] = true;
+        self.versions[to as usize] += 1;
+        let alive = &self.alive;
+        self.vertex_triangles[to as usize].retain(|&t| alive[t]);
+
+        let neighbours: HashSet<u32> = self.vertex_triangles[to as usize].iter()
+            .flat_map(|&t| self.triangles[t])
+            .filter(|&v| v != to)
+            .collect();
+        for w in neighbours {
+            self.push_edge(to, w);
+        }
+        removed_triangles
+    }
+
+    /// Colapsar aristas hasta quedar en `target` triángulos o no poder seguir
+    fn run(mut self, target: usize) -> Vec<u32> {
+        let mut triangle_count = self.triangles.len();
+        while triangle_count > target {
+            let Some(candidate) = self.heap.pop() else {
+                break;
+            };
+            let (from, to) = (candidate.from as usize, candidate.to as usize);
+            if self.removed[from] || self.removed[to]
+                || candidate.versions != (self.versions[from], self.versions[to])
+            {
+                continue;
+            }
+            if !self.preserves_orientation(candidate.from, candidate.to) {
+                continue;
+            }
+            triangle_count -= self.collapse(candidate.from, candidate.to);
+        }
+
+        if triangle_count == self.source.len() / 3 {
+            return self.source.to_vec();
+        }
+        self.triangles.iter()
+            .zip(&self.alive)
+            .filter(|(_, alive)| **alive)
+            .flat_map(|(triangle, _)| *triangle)
+            .collect()
+    }
+}
+
+/// Simplificar un mesh a una fracción de sus triángulos. Devuelve el nuevo
+/// index buffer sobre los mismos vértices
+pub fn simplify(positions: &[Vec3], indices: &[u32], reduction_factor: f32) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    let target = (triangle_count as f32 * reduction_factor.clamp(0.0, 1.0)).round() as usize;
+    if target >= triangle_count || indices.iter().any(|&i| i as usize >= positions.len()) {
+        return indices.to_vec();
+    }
+    Simplifier::new(positions, indices).run(target)
+}
+
+/// Generar los index buffers reducidos de un mesh, uno por factor de reducción
+pub fn generate_lods(mesh: &mut MeshComponent, reduction_factors: &[f32]) {
+    mesh.lod_indices = reduction_factors.iter()
+        .map(|&factor| simplify(&mesh.vertices, &mesh.indices, factor))
+        .collect();
+}
+
+/// Selección de LOD por distancia con histéresis
+#[derive(Debug, Clone)]
+pub struct LodSelector {
+    /// Distancia a partir de la que se usa cada nivel reducido (nivel 1 en adelante)
+    distances: Vec<f32>,
+    /// Ancho de la banda de histéresis alrededor de cada umbral
+    hysteresis: f32,
+    /// Multiplicador global de las distancias
+    distance_bias: f32,
+    /// Nivel actual por entidad
+    current: HashMap<EntityId, u32>,
+}
+
+impl LodSelector {
+    /// Crear selector a partir de la configuración de LOD
+    pub fn new(config: &LODConfig) -> Self {
+        let mut distances: Vec<f32> = config.levels.iter().map(|level| level.distance).collect();
+        distances.sort_by(f32::total_cmp);
+        Self {
+            distances,
+            hysteresis: config.transition_distance.max(0.0),
+            distance_bias: 1.0,
+            current: HashMap::new(),
+        }
+    }
+
+    /// Factores de reducción de los niveles, ordenados por distancia
+    pub fn reduction_factors(config: &LODConfig) -> Vec<f32> {
+        let mut levels: Vec<_> = config.levels.iter().collect();
+        levels.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        levels.into_iter().map(|level| level.reduction_factor).collect()
+    }
+
+    /// Establecer el multiplicador global de distancias (menor que 1 adelanta
+    /// el paso a niveles reducidos)
+    pub fn set_distance_bias(&mut self, bias: f32) {
+        self.distance_bias = bias.max(0.01);
+    }
+
+    /// Multiplicador global de distancias
+    pub fn distance_bias(&self) -> f32 {
+        self.distance_bias
+    }
+
+    /// Umbral en el que empieza un nivel reducido (`level` >= 1)
+    fn threshold(&self, level: u32) -> f32 {
+        self.distances[level as usize - 1] * self.distance_bias
+    }
+
+    /// Nivel de una entidad a una distancia, con `available` niveles
+    /// reducidos. Solo se cambia de nivel al salir de la banda de histéresis
+    /// del umbral, de modo que oscilar en torno a él no produce saltos
+    pub fn select(&mut self, entity_id: EntityId, distance: f32, available: u32) -> u32 {
+        let max_level = available.min(self.distances.len() as u32);
+        let half_band = self.hysteresis * 0.5;
+        let mut level = self.current.get(&entity_id).copied().unwrap_or_else(|| {
+            // Primera vez: nivel exacto sin histéresis
+            (1..=max_level).take_while(|&l| distance >= self.threshold(l)).last().unwrap_or(0)
+        }).min(max_level);
+
+        while level < max_level && distance > self.threshold(level + 1) + half_band {
+            level += 1;
+        }
+        while level > 0 && distance < self.threshold(level) - half_band {
+            level -= 1;
+        }
+
+        self.current.insert(entity_id, level);
+        level
+    }
+
+    /// Olvidar las entidades que ya no se consideran
+    pub fn retain(&mut self, mut keep: impl FnMut(EntityId) -> bool) {
+        self.current.retain(|entity_id, _| keep(*entity_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::LODLevel;
+
+    /// Esfera UV cerrada de radio 1: `slices * (stacks - 1) * 2` triángulos
+    /// con un único vértice en cada polo
+    fn uv_sphere(slices: u32, stacks: u32) -> (Vec<Vec3>, Vec<u32>) {
+        let mut positions = vec![Vec3::Y];
+        for stack in 1..stacks {
+            let polar = std::f32::consts::PI * stack as f32 / stacks as f32;
+            for slice in 0..slices {
+                let azimuth = std::f32::consts::TAU * slice as f32 / slices as f32;
+                positions.push(Vec3::new(polar.sin() * azimuth.cos(), polar.cos(), -polar.sin() * azimuth.sin()));
+            }
+        }
+        positions.push(Vec3::NEG_Y);
+
+        let ring = |stack: u32, slice: u32| 1 + (stack - 1) * slices + slice % slices;
+        let south = positions.len() as u32 - 1;
+        let mut indices = Vec::new();
+        for slice in 0..slices {
+            indices.extend_from_slice(&[0, ring(1, slice), ring(1, slice + 1)]);
+            indices.extend_from_slice(&[south, ring(stacks - 1, slice + 1), ring(stacks - 1, slice)]);
+        }
+        for stack in 1..stacks - 1 {
+            for slice in 0..slices {
+                let (a, b) = (ring(stack, slice), ring(stack, slice + 1));
+                let (c, d) = (ring(stack + 1, slice), ring(stack + 1, slice + 1));
+                indices.extend_from_slice(&[a, c, d, a, d, b]);
+            }
+        }
+        (positions, indices)
+    }
+
+    fn lod_config(distances: &[f32], transition_distance: f32) -> LODConfig {
+        LODConfig {
+            enabled: true,
+            levels: distances.iter().map(|&distance| LODLevel {
+                distance,
+                reduction_factor: 0.5,
+                mesh_id: String::new(),
+            }).collect(),
+            transition_distance,
+        }
+    }
+
+    #[test]
+    fn sphere_is_simplified_to_a_quarter_of_its_triangles() {
+        let (positions, indices) = uv_sphere(100, 51);
+        assert_eq!(indices.len() / 3, 10_000);
+
+        let reduced = simplify(&positions, &indices, 0.25);
+        let triangles = reduced.len() / 3;
+        assert!((2375..=2625).contains(&triangles), "{} triángulos", triangles);
+
+        // Ningún triángulo queda del revés
+        for triangle in reduced.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+            let normal = (b - a).cross(c - a);
+            assert!(normal.dot(a + b + c) > 0.0, "triángulo invertido {:?}", triangle);
+        }
+    }
+
+    #[test]
+    fn selection_switches_levels_only_outside_the_hysteresis_band() {
+        let mut selector = LodSelector::new(&lod_config(&[20.0, 10.0], 2.0));
+
+        // La primera vez se elige el nivel exacto
+        assert_eq!(selector.select(1, 25.0, 2), 2);
+        assert_eq!(selector.select(2, 25.0, 1), 1);
+
+        // Umbral del nivel 1 en 10 m con banda de ±1 m
+        assert_eq!(selector.select(3, 5.0, 2), 0);
+        assert_eq!(selector.select(3, 10.5, 2), 0);
+        assert_eq!(selector.select(3, 11.5, 2), 1);
+        assert_eq!(selector.select(3, 9.5, 2), 1);
+        assert_eq!(selector.select(3, 10.5, 2), 1);
+        assert_eq!(selector.select(3, 8.5, 2), 0);
+
+        // El multiplicador de distancias adelanta los niveles reducidos
+        selector.set_distance_bias(0.5);
+        assert_eq!(selector.select(3, 6.5, 2), 1);
+
+        selector.retain(|entity_id| entity_id != 3);
+        assert_eq!(selector.select(3, 9.5, 2), 1);
+    }
+}
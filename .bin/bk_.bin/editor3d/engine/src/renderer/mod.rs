@@ -3,16 +3,80 @@
 //! Proporciona renderizado WebGL/WebGPU, PBR, efectos post-procesamiento
 //! y optimizaciones de rendimiento para el metaverso.
 
+pub mod assets;
+pub mod avatar;
+pub mod atlas;
+pub mod backend;
+pub mod bloom;
+pub mod gltf_loader;
+pub mod clustering;
+pub mod culling;
+pub mod debug_draw;
+pub mod decals;
+pub mod dynamic_resolution;
+pub mod environment;
+pub mod graph;
+pub mod lod;
+pub mod occlusion;
+pub mod particles;
+pub mod postprocess;
+pub mod shader_cache;
+pub mod shader_reload;
+pub mod shadows;
+pub mod skinning;
+pub mod morphing;
+pub mod streaming;
+pub mod text;
+pub mod vrs;
+pub mod xr;
+#[cfg(all(feature = "xr", not(target_arch = "wasm32")))]
+pub mod openxr;
+
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 use tokio::sync::mpsc;
 use tracing::{info, debug, error, warn};
 use anyhow::{Result, anyhow};
-use glam::{Vec3, Vec4, Mat4, Quat};
+use glam::{Vec2, Vec3, Vec4, Mat4, Quat};
 use wasm_bindgen::prelude::*;
 use web_sys::{WebGlRenderingContext, WebGl2RenderingContext, WebGlProgram, WebGlShader, WebGlBuffer, WebGlTexture};
 
+use assets::{AssetHandle, AssetLoader, AssetState};
+use atlas::{TextureAtlas, UVRegion};
+use backend::{
+    DirectionalLight, DrawCall, DrawList, EnvironmentFrame, ImageData, ImageReadback, MaterialDesc, MaterialKind, MaterialParams,
+    MaterialTextureSlots, RenderBackend, TextureImage, SHADOW_CASCADES_ALL,
+};
+use backend::mock::MockBackend;
+use backend::wgpu_backend::{WgpuBackend, WgpuBackendOptions, default_view_projection, DEFAULT_EYE};
+use clustering::{ClusterFrame, LightClusterConfig, LocalLight, LocalLightKind};
+use culling::{Aabb, Frustum, MeshBoundsCache, SpatialIndex};
+use debug_draw::DebugDraw;
+use decals::{select_decals, DecalCandidate};
+use dynamic_resolution::{scaled_size, DynamicResolution, DynamicResolutionConfig};
+use environment::EnvironmentMaps;
+use lod::{LodSelector, lod_mesh_id};
+use occlusion::OcclusionFrame;
+use particles::ParticleFrame;
+use postprocess::{PostEffect, PostProcessSettings, PostProcessStack};
+use shader_reload::{shader_kind, ShaderError, ShaderReloadCallback, ShaderReloadEvent, ShaderWatcher};
+use shadows::ShadowSettings;
+use streaming::{fallback_mip, mip_chain_image, TextureStreamer};
+use text::{layout_text, place_world_labels, FontAtlas, TextFrame, TextQuad, WorldLabel, DEFAULT_CHARSET};
+use vrs::{VRSConfig, VRSFrame};
+use xr::{XrGraphics, XrRuntime, XR_EYE_TARGETS};
+use crate::animations::particles::ParticleBatch;
+use crate::animations::skinning::SkinPalette;
+use crate::animations::morphing::MorphWeights;
+use crate::animations::MorphTarget;
+use crate::ecs::{
+    ECSSystem, EntityId, ComponentType, MeshComponent, TransformComponent, CameraComponent, CameraType, LightComponent, LightType,
+    DecalComponent, LabelComponent, MaterialComponent, TextStyle, RENDER_LAYER_DEFAULT,
+};
+use crate::materials::material_desc_from_component;
+
 /// Sistema de renderizado principal
 pub struct RendererSystem {
     /// Configuración del sistema
@@ -31,10 +95,94 @@ pub struct RendererSystem {
     meshes: Arc<RwLock<HashMap<String, Mesh>>>,
     /// Estadísticas del sistema
     stats: RendererStats,
+    /// Backend de renderizado
+    backend: Option<Box<dyn RenderBackend>>,
+    /// Superficie de destino para el backend wgpu
+    surface_target: Option<wgpu::SurfaceTarget<'static>>,
+    /// Dispositivo del runtime de XR sobre el que crear el backend wgpu
+    xr_graphics: Option<XrGraphics>,
+    /// Lista de dibujo del frame en curso
+    draw_list: DrawList,
+    /// Posición de la cámara activa
+    camera_position: Vec3,
+    /// Matriz de vista de la cámara activa
+    camera_view: Mat4,
+    /// Planos near y far de la cámara activa
+    camera_clip: (f32, f32),
+    /// La luz direccional activa proyecta sombras
+    light_casts_shadows: bool,
+    /// Entidades (ordenadas) que dibuja cada cascada de sombras en el próximo
+    /// frame (None: todas en todas)
+    shadow_casters: Option<Vec<Vec<EntityId>>>,
+    /// Clustered shading de las luces locales (None sin luces locales)
+    light_clustering: Option<LightClusterConfig>,
+    /// Se recogen las luces puntuales y los focos de la escena
+    local_light_types: (bool, bool),
+    /// Octree de cajas envolventes de las entidades con malla
+    spatial_index: SpatialIndex,
+    /// Cajas locales por mesh
+    mesh_bounds: MeshBoundsCache,
+    /// Selección de LOD por entidad
+    lod_selector: LodSelector,
+    /// Cadena de post-procesado
+    post_process: PostProcessStack,
+    /// Variable rate shading (None desactivado)
+    variable_rate_shading: Option<VRSConfig>,
+    /// Paletas de huesos del frame por entidad (del sistema de animaciones)
+    skin_palettes: HashMap<EntityId, SkinPalette>,
+    /// Pesos de los morph targets del frame por entidad (del sistema de animaciones)
+    morph_weights: HashMap<EntityId, MorphWeights>,
+    /// Partículas del frame por emisor (del sistema de animaciones)
+    particle_batches: Vec<ParticleBatch>,
+    /// Atlas de las fuentes cargadas
+    fonts: HashMap<String, FontAtlas>,
+    /// Etiquetas pedidas con `draw_text` para el próximo frame
+    pending_labels: Vec<(EntityId, String, TextStyle)>,
+    /// Texto y rectángulos de UI en pantalla del próximo frame
+    screen_text: TextFrame,
+    /// Líneas de depuración del próximo frame
+    debug_draw: DebugDraw,
+    /// Niveles de mip residentes de las texturas de los materiales
+    texture_streamer: TextureStreamer,
+    /// Cargas asíncronas de modelos y texturas
+    asset_loader: AssetLoader,
+    /// Directorio de shaders vigilado (None sin recarga desde disco)
+    shader_watcher: Option<ShaderWatcher>,
+    /// Shaders por recompilar, como (nombre, código)
+    pending_shaders: Vec<(String, String)>,
+    /// Último error de cada shader que no compila
+    shader_errors: HashMap<String, ShaderError>,
+    /// Callback del editor con cada recarga
+    shader_callback: Option<ShaderReloadCallback>,
+    /// Estilo del texto de errores de shader en pantalla (None sin overlay)
+    shader_error_overlay: Option<TextStyle>,
+    /// Resolución dinámica (None a resolución completa)
+    dynamic_resolution: Option<DynamicResolution>,
+    /// Tiempo de GPU del último frame medido por el backend
+    gpu_frame_time_ms: f32,
+    /// Siguiente ID de destino offscreen
+    next_render_texture: u64,
+    /// Materiales de atlas por ID
+    atlas_materials: HashMap<String, MaterialDesc>,
+    /// Material de atlas y región de cada material del ECS agrupado
+    atlas_regions: HashMap<String, (String, UVRegion)>,
+    /// Materiales y texturas con los que se construyeron los atlas
+    atlas_signature: Vec<(String, String)>,
     /// Estado del sistema
     running: bool,
 }
 
+/// Destino offscreen creado con `render_to_texture`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderTextureHandle(u64);
+
+impl RenderTextureHandle {
+    /// ID del destino en el backend
+    fn target_id(&self) -> String {
+        format!("render-texture-{}", self.0)
+    }
+}
+
 /// Configuración del sistema de renderizado
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RendererConfig {
@@ -48,6 +196,37 @@ pub struct RendererConfig {
     pub effects_config: EffectsConfig,
     /// Configuración de optimización
     pub optimization_config: OptimizationConfig,
+    /// Memoria de GPU para las texturas de los materiales; las de menor
+    /// prioridad bajan de nivel de mip para no superarla
+    #[serde(default = "default_texture_budget_bytes")]
+    pub texture_budget_bytes: u64,
+    /// Directorio de caché; los shaders compilados se guardan en su
+    /// subdirectorio `shaders/` (None sin caché en disco)
+    #[serde(default)]
+    pub shader_cache_dir: Option<PathBuf>,
+    /// Compilar todas las variantes de shader de los materiales durante la
+    /// carga en lugar de con su primer uso
+    #[serde(default = "default_precompile_on_startup")]
+    pub precompile_on_startup: bool,
+    /// Agrupar en atlas las texturas de albedo de los materiales del ECS
+    /// que solo se diferencian en ella, para dibujarlos con un material
+    #[serde(default)]
+    pub enable_atlas_packing: bool,
+    /// Lado máximo de un atlas de texturas
+    #[serde(default = "default_atlas_max_size")]
+    pub atlas_max_size: u32,
+}
+
+fn default_texture_budget_bytes() -> u64 {
+    512 * 1024 * 1024
+}
+
+fn default_precompile_on_startup() -> bool {
+    true
+}
+
+fn default_atlas_max_size() -> u32 {
+    2048
 }
 
 /// API de renderizado
@@ -56,6 +235,7 @@ pub enum RenderAPI {
     WebGL1,
     WebGL2,
     WebGPU,
+    Vulkan,
     Custom(String),
 }
 
@@ -66,6 +246,8 @@ pub struct QualityConfig {
     pub quality_level: QualityLevel,
     /// Resolución
     pub resolution: [u32; 2],
+    /// Sincronización vertical
+    pub vsync: bool,
     /// Antialiasing
     pub antialiasing: AntialiasingConfig,
     /// Sombras
@@ -128,7 +310,16 @@ pub struct CascadeConfig {
     /// Factor de división
     pub split_factor: f32,
     /// Bias
-    pub bias: f32,
+    pub bias: BiasConfig,
+}
+
+/// Configuración de bias de sombras
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BiasConfig {
+    /// Bias constante
+    pub constant_bias: f32,
+    /// Bias de pendiente
+    pub slope_bias: f32,
 }
 
 /// Configuración de LOD
@@ -246,6 +437,34 @@ pub struct OptimizationConfig {
     pub batching: bool,
     /// LOD
     pub lod: bool,
+    /// Distance culling
+    #[serde(default)]
+    pub distance_culling: bool,
+    /// Distancia máxima de dibujo (0 = sin límite)
+    #[serde(default)]
+    pub max_draw_distance: f32,
+    /// Mínimo de entidades con el mismo mesh y material para instanciarlas
+    #[serde(default = "default_instancing_threshold")]
+    pub instancing_threshold: u32,
+    /// Presupuesto global de decals por frame (como mucho `decals::MAX_DECALS`)
+    #[serde(default = "default_max_decals")]
+    pub max_decals: u32,
+    /// Cargas de assets simultáneas del `AssetLoader`
+    #[serde(default = "default_max_concurrent_loads")]
+    pub max_concurrent_loads: u32,
+}
+
+/// Por debajo de este tamaño de grupo compensan las draw calls individuales
+fn default_instancing_threshold() -> u32 {
+    8
+}
+
+fn default_max_decals() -> u32 {
+    32
+}
+
+fn default_max_concurrent_loads() -> u32 {
+    4
 }
 
 /// Contexto de renderizado
@@ -453,6 +672,8 @@ pub enum MaterialType {
     PBR,
     Unlit,
     Toon,
+    /// Capas mezcladas con un splat map (`splat`, `layer0`..`layer3`)
+    Terrain,
     Custom(String),
 }
 
@@ -674,6 +895,39 @@ pub struct Geometry {
     pub bounding_box: BoundingBox,
     /// Bounding sphere
     pub bounding_sphere: BoundingSphere,
+    /// Articulaciones por vértice (vacío si el mesh no tiene skin)
+    #[serde(default)]
+    pub joints: Vec<[u16; 4]>,
+    /// Pesos de las articulaciones por vértice
+    #[serde(default)]
+    pub weights: Vec<[f32; 4]>,
+    /// Morph targets del mesh (vacío si no tiene)
+    #[serde(default)]
+    pub morph_targets: Vec<MorphTargetGeometry>,
+}
+
+/// Morph target de un mesh: desplazamientos por vértice que se suman,
+/// ponderados, a la geometría base
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MorphTargetGeometry {
+    /// ID con el que los pesos de las animaciones se refieren al target
+    pub id: String,
+    /// Desplazamiento de la posición por vértice
+    pub position_deltas: Vec<Vec3>,
+    /// Desplazamiento de la normal por vértice (vacío si no cambia)
+    #[serde(default)]
+    pub normal_deltas: Vec<Vec3>,
+}
+
+impl From<&MorphTarget> for MorphTargetGeometry {
+    /// Target de un clip de morphing: sus vértices y normales son desplazamientos
+    fn from(target: &MorphTarget) -> Self {
+        Self {
+            id: target.id.clone(),
+            position_deltas: target.vertices.iter().copied().map(Vec3::from).collect(),
+            normal_deltas: target.normals.iter().copied().map(Vec3::from).collect(),
+        }
+    }
 }
 
 /// Vértice
@@ -737,12 +991,97 @@ pub struct RendererStats {
     pub loaded_textures: u32,
     /// Shaders compilados
     pub compiled_shaders: u32,
+    /// Objetos dibujados tras el culling
+    pub rendered_objects: u32,
+    /// Objetos descartados por el culling
+    pub culled_objects: u32,
+    /// Grupos mesh/material dibujados con instancing
+    #[serde(default)]
+    pub instanced_batches: u32,
+    /// Píxeles medios por invocación del fragment shader según la imagen de
+    /// tasas del VRS (1.0 = sombreado completo)
+    #[serde(default = "default_average_shading_rate")]
+    pub average_shading_rate: f32,
+    /// Vértices deformados por el skinning en GPU
+    #[serde(default)]
+    pub skinned_vertices: u32,
+    /// Vértices deformados por morph targets
+    #[serde(default)]
+    pub morphed_vertices: u32,
+    /// Tiempo de GPU del último frame medido en milisegundos (0 sin timestamps)
+    #[serde(default)]
+    pub gpu_frame_time_ms: f32,
+    /// Escala de la resolución interna de renderizado
+    #[serde(default = "default_render_scale")]
+    pub render_scale: f32,
+    /// Decals aplicados en el último frame
+    #[serde(default)]
+    pub rendered_decals: u32,
+    /// Partículas dibujadas en el último frame
+    #[serde(default)]
+    pub rendered_particles: u32,
+    /// Quads de texto y UI dibujados en el último frame
+    #[serde(default)]
+    pub rendered_text_quads: u32,
+    /// Segmentos de debug draw dibujados en el último frame
+    #[serde(default)]
+    pub rendered_debug_lines: u32,
+    /// Objetos probados por el occlusion culling en GPU
+    #[serde(default)]
+    pub occlusion_tested: u32,
+    /// Objetos ocultos tras otros según la pirámide de profundidad
+    #[serde(default)]
+    pub occluded_objects: u32,
+    /// Luces puntuales y focos repartidos en clusters
+    #[serde(default)]
+    pub clustered_lights: u32,
+    /// Clusters con más luces de las que caben (con GPU, del frame anterior)
+    #[serde(default)]
+    pub overflowed_clusters: u32,
+    /// Referencias a luces descartadas en los clusters desbordados
+    #[serde(default)]
+    pub dropped_cluster_lights: u32,
+    /// Mayor número de luces que alcanzan un mismo cluster
+    #[serde(default)]
+    pub max_cluster_lights: u32,
+    /// Bytes de GPU de las texturas de los materiales
+    #[serde(default)]
+    pub resident_texture_bytes: u64,
+    /// Niveles de mip desalojados por el presupuesto de texturas
+    #[serde(default)]
+    pub evicted_mip_count: u64,
+    /// Vistas de ojo entregadas al runtime de XR
+    #[serde(default)]
+    pub xr_eye_views: u64,
+    /// Variantes de shader servidas desde la caché en disco
+    #[serde(default)]
+    pub shader_cache_hits: u32,
+    /// Variantes de shader compiladas desde WGSL
+    #[serde(default)]
+    pub shader_cache_misses: u32,
+    /// Materiales del ECS dibujados con un material de atlas
+    #[serde(default)]
+    pub atlased_materials: u32,
+}
+
+/// Sin VRS cada píxel se sombrea una vez
+fn default_average_shading_rate() -> f32 {
+    1.0
+}
+
+/// Sin resolución dinámica se renderiza a resolución completa
+fn default_render_scale() -> f32 {
+    1.0
 }
 
 impl RendererSystem {
     /// Crear nuevo sistema de renderizado
     pub fn new(config: RendererConfig) -> Self {
         info!("Inicializando sistema de renderizado");
+        let lod_selector = LodSelector::new(&config.quality_config.lod);
+        let post_process = PostProcessStack::new(PostProcessSettings::from_config(&config.quality_config, &config.effects_config));
+        let asset_loader = AssetLoader::new(config.optimization_config.max_concurrent_loads as usize);
+        let texture_streamer = TextureStreamer::new(config.texture_budget_bytes);
         
         Self {
             config,
@@ -794,7 +1133,71 @@ impl RendererSystem {
                 gpu_memory: 0,
                 loaded_textures: 0,
                 compiled_shaders: 0,
+                rendered_objects: 0,
+                culled_objects: 0,
+                instanced_batches: 0,
+                average_shading_rate: 1.0,
+                skinned_vertices: 0,
+                morphed_vertices: 0,
+                gpu_frame_time_ms: 0.0,
+                render_scale: 1.0,
+                rendered_decals: 0,
+                rendered_particles: 0,
+                rendered_text_quads: 0,
+                rendered_debug_lines: 0,
+                occlusion_tested: 0,
+                occluded_objects: 0,
+                clustered_lights: 0,
+                overflowed_clusters: 0,
+                dropped_cluster_lights: 0,
+                max_cluster_lights: 0,
+                resident_texture_bytes: 0,
+                evicted_mip_count: 0,
+                xr_eye_views: 0,
+                shader_cache_hits: 0,
+                shader_cache_misses: 0,
+                atlased_materials: 0,
             },
+            backend: None,
+            surface_target: None,
+            xr_graphics: None,
+            draw_list: DrawList::default(),
+            camera_position: DEFAULT_EYE,
+            camera_view: Mat4::look_at_rh(DEFAULT_EYE, Vec3::ZERO, Vec3::Y),
+            camera_clip: (0.1, 1000.0),
+            light_casts_shadows: false,
+            shadow_casters: None,
+            light_clustering: Some(LightClusterConfig::default()),
+            local_light_types: (true, true),
+            spatial_index: SpatialIndex::new(
+                Aabb::new(Vec3::splat(-SPATIAL_INDEX_EXTENT), Vec3::splat(SPATIAL_INDEX_EXTENT)),
+                8,
+                1.0,
+            ),
+            mesh_bounds: MeshBoundsCache::default(),
+            lod_selector,
+            post_process,
+            variable_rate_shading: None,
+            skin_palettes: HashMap::new(),
+            morph_weights: HashMap::new(),
+            particle_batches: Vec::new(),
+            fonts: HashMap::new(),
+            pending_labels: Vec::new(),
+            screen_text: TextFrame::default(),
+            debug_draw: DebugDraw::new(),
+            texture_streamer,
+            asset_loader,
+            shader_watcher: None,
+            pending_shaders: Vec::new(),
+            shader_errors: HashMap::new(),
+            shader_callback: None,
+            shader_error_overlay: None,
+            dynamic_resolution: None,
+            gpu_frame_time_ms: 0.0,
+            next_render_texture: 0,
+            atlas_materials: HashMap::new(),
+            atlas_regions: HashMap::new(),
+            atlas_signature: Vec::new(),
             running: false,
         }
     }
@@ -828,13 +1231,456 @@ impl RendererSystem {
 
     /// Inicializar contexto
     async fn initialize_context(&mut self) -> Result<()> {
-        // Aquí se inicializaría el contexto WebGL/WebGPU
-        // Por ahora es una implementación simulada
-        
-        info!("Contexto de renderizado inicializado");
+        let [width, height] = self.config.quality_config.resolution;
+        self.context.api = self.config.render_api.clone();
+
+        // WebGPU y Vulkan comparten el backend wgpu; WebGL sigue simulado
+        self.backend = match &self.config.render_api {
+            RenderAPI::WebGPU | RenderAPI::Vulkan => {
+                let quality = &self.config.quality_config;
+                let sample_count = match quality.antialiasing.antialiasing_type {
+                    AntialiasingType::MSAA => quality.antialiasing.antialiasing_level.max(1),
+                    _ => 1,
+                };
+                let options = WgpuBackendOptions {
+                    width,
+                    height,
+                    sample_count,
+                    vsync: quality.vsync,
+                    shader_cache_dir: self.config.shader_cache_dir.clone(),
+                    precompile_shaders: self.config.precompile_on_startup,
+                    ..Default::default()
+                };
+                // Con XR el backend usa el dispositivo del runtime y el
+                // destino principal es offscreen, del formato del swapchain
+                let backend = match self.xr_graphics.take() {
+                    Some(XrGraphics { instance, adapter, device, queue }) => {
+                        WgpuBackend::from_device(instance, adapter, device, queue, None, options)?
+                    }
+                    None => WgpuBackend::new(self.surface_target.take(), options).await?,
+                };
+                Some(Box::new(backend) as Box<dyn RenderBackend>)
+            }
+            RenderAPI::Custom(name) if name == "mock" => {
+                Some(Box::new(MockBackend::new(width, height)) as Box<dyn RenderBackend>)
+            }
+            _ => None,
+        };
+        // La resolución dinámica puede configurarse antes que el backend
+        let scale = self.get_current_resolution_scale();
+        self.apply_render_scale(scale);
+        self.draw_list.view_projection = default_view_projection(width, height);
+
+        match &self.backend {
+            Some(backend) => info!("Contexto de renderizado inicializado (backend {})", backend.name()),
+            None => info!("Contexto de renderizado inicializado"),
+        }
+        Ok(())
+    }
+
+    /// Establecer la superficie de destino (antes de `initialize`)
+    pub fn set_surface_target(&mut self, target: wgpu::SurfaceTarget<'static>) {
+        self.surface_target = Some(target);
+    }
+
+    /// Crear el backend wgpu sobre el dispositivo del runtime de XR (antes
+    /// de `initialize`)
+    pub fn set_xr_graphics(&mut self, graphics: XrGraphics) {
+        self.xr_graphics = Some(graphics);
+    }
+
+    /// Establecer un backend de renderizado
+    pub fn set_backend(&mut self, backend: Box<dyn RenderBackend>) {
+        self.backend = Some(backend);
+    }
+
+    /// Obtener el backend activo
+    pub fn backend(&self) -> Option<&dyn RenderBackend> {
+        self.backend.as_deref()
+    }
+
+    /// Redimensionar el destino de renderizado
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.config.quality_config.resolution = [width, height];
+        if let Some(backend) = &mut self.backend {
+            backend.resize(width, height);
+        }
+        self.post_process.reset_history();
+    }
+
+    /// Cadena de post-procesado
+    pub fn post_process(&self) -> &PostProcessStack {
+        &self.post_process
+    }
+
+    /// Cadena de post-procesado (exposición, tonemapping, bloom)
+    pub fn post_process_mut(&mut self) -> &mut PostProcessStack {
+        &mut self.post_process
+    }
+
+    /// Activar o desactivar un efecto de post-procesado
+    pub fn set_post_effect_enabled(&mut self, effect: PostEffect, enabled: bool) -> bool {
+        self.post_process.set_enabled(effect, enabled)
+    }
+
+    /// Cambiar el orden de la cadena de post-procesado
+    pub fn set_post_effect_order(&mut self, order: &[PostEffect]) {
+        self.post_process.set_order(order);
+    }
+
+    /// Activar (Some) o desactivar (None) el bloom con cadena de mips, que
+    /// se aplica entre el pase principal y el tonemapping
+    pub fn set_bloom(&mut self, config: Option<bloom::BloomConfig>) {
+        self.post_process.set_bloom_config(config.as_ref());
+    }
+
+    /// Cambiar la rejilla del clustered shading. Con None no se dibujan las
+    /// luces puntuales ni los focos
+    pub fn set_light_clustering(&mut self, config: Option<LightClusterConfig>) {
+        self.light_clustering = config;
+    }
+
+    /// Configuración del clustered shading
+    pub fn light_clustering(&self) -> Option<&LightClusterConfig> {
+        self.light_clustering.as_ref()
+    }
+
+    /// Elegir qué luces locales de la escena se dibujan
+    pub fn set_local_light_types(&mut self, point: bool, spot: bool) {
+        self.local_light_types = (point, spot);
+    }
+
+    /// Activar (Some) o desactivar (None) el variable rate shading
+    pub fn set_variable_rate_shading(&mut self, config: Option<VRSConfig>) {
+        self.variable_rate_shading = config;
+    }
+
+    /// Configuración del variable rate shading
+    pub fn variable_rate_shading(&self) -> Option<&VRSConfig> {
+        self.variable_rate_shading.as_ref()
+    }
+
+    /// Activar (Some) o desactivar (None) la resolución dinámica. Se empieza
+    /// en la escala máxima; al desactivarla se vuelve a resolución completa
+    pub fn set_dynamic_resolution(&mut self, config: Option<DynamicResolutionConfig>) {
+        self.dynamic_resolution = config.map(DynamicResolution::new);
+        let scale = self.get_current_resolution_scale();
+        self.apply_render_scale(scale);
+    }
+
+    /// Configuración de la resolución dinámica
+    pub fn dynamic_resolution(&self) -> Option<&DynamicResolutionConfig> {
+        self.dynamic_resolution.as_ref().map(DynamicResolution::config)
+    }
+
+    /// Escala de la resolución interna con la que se renderiza
+    pub fn get_current_resolution_scale(&self) -> f32 {
+        self.dynamic_resolution.as_ref().map_or(1.0, DynamicResolution::applied_scale)
+    }
+
+    /// Recrear los destinos del backend a la escala indicada. El TAA no
+    /// puede reproyectar una historia de otra resolución
+    fn apply_render_scale(&mut self, scale: f32) {
+        if let Some(backend) = &mut self.backend {
+            backend.set_render_scale(scale);
+        }
+        if scale != self.stats.render_scale {
+            self.post_process.reset_history();
+        }
+        self.stats.render_scale = scale;
+    }
+
+    /// Establecer las paletas de huesos del frame. Las entidades con paleta
+    /// (o cuyo padre la tiene) y malla con articulaciones se deforman en GPU
+    pub fn set_skin_palettes(&mut self, palettes: &HashMap<EntityId, SkinPalette>) {
+        self.skin_palettes.clone_from(palettes);
+    }
+
+    /// Establecer los pesos de los morph targets del frame. Las entidades con
+    /// pesos (o cuyo padre los tiene) y mesh con morph targets se deforman
+    pub fn set_morph_weights(&mut self, weights: &HashMap<EntityId, MorphWeights>) {
+        self.morph_weights.clone_from(weights);
+    }
+
+    /// Establecer las partículas del próximo frame
+    pub fn set_particle_batches(&mut self, batches: Vec<ParticleBatch>) {
+        self.particle_batches = batches;
+    }
+
+    /// Registrar el atlas de una fuente (o reemplazarlo). Se sube al backend
+    /// la primera vez que se dibuja texto con ella
+    pub fn load_font(&mut self, atlas: FontAtlas) {
+        if let Some(backend) = &mut self.backend {
+            backend.remove_font(&atlas.id);
+        }
+        self.fonts.insert(atlas.id.clone(), atlas);
+    }
+
+    /// Generar y registrar el atlas SDF de una fuente TrueType/OpenType con
+    /// los caracteres de `text::DEFAULT_CHARSET`
+    pub fn load_font_from_bytes(&mut self, font_id: &str, bytes: &[u8], size_px: f32) -> Result<()> {
+        let atlas = FontAtlas::from_font_bytes(font_id, bytes, size_px, DEFAULT_CHARSET)?;
+        self.load_font(atlas);
         Ok(())
     }
 
+    /// Liberar una fuente
+    pub fn remove_font(&mut self, font_id: &str) {
+        self.fonts.remove(font_id);
+        if let Some(backend) = &mut self.backend {
+            backend.remove_font(font_id);
+        }
+    }
+
+    /// Dibujar en el próximo frame una etiqueta sobre una entidad, como la
+    /// de un `LabelComponent` sin desplazamiento
+    pub fn draw_text(&mut self, entity: EntityId, text: &str, style: &TextStyle) {
+        self.pending_labels.push((entity, text.to_string(), style.clone()));
+    }
+
+    /// Dibujar texto en pantalla en el próximo frame, con su esquina superior
+    /// izquierda en `position` (píxeles del destino) y `style.size` en
+    /// píxeles. Se omite si la fuente no está cargada
+    pub fn draw_screen_text(&mut self, text: &str, position: Vec2, style: &TextStyle) {
+        if let Some(atlas) = self.fonts.get(&style.font_id) {
+            let layout = layout_text(atlas, text, style.size);
+            self.screen_text.push_text(atlas, &layout, position, style.size, style);
+        }
+    }
+
+    /// Dibujar un rectángulo de UI de color sólido en el próximo frame
+    pub fn draw_ui_rect(&mut self, min: Vec2, max: Vec2, color: Vec4) {
+        self.screen_text.push(None, TextQuad {
+            min,
+            max,
+            uv_min: Vec2::ZERO,
+            uv_max: Vec2::ONE,
+            color,
+            outline_color: Vec4::ZERO,
+            spread: 0.0,
+            outline: 0.0,
+        });
+    }
+
+    /// Capa de debug draw
+    pub fn debug_draw(&self) -> &DebugDraw {
+        &self.debug_draw
+    }
+
+    /// Capa de debug draw para pedir líneas del próximo frame
+    pub fn debug_draw_mut(&mut self) -> &mut DebugDraw {
+        &mut self.debug_draw
+    }
+
+    /// Tamaño en píxeles de un texto con `style.size` en píxeles (None si la
+    /// fuente no está cargada)
+    pub fn measure_text(&self, text: &str, style: &TextStyle) -> Option<Vec2> {
+        self.fonts.get(&style.font_id).map(|atlas| layout_text(atlas, text, style.size).size)
+    }
+
+    /// Vigilar un directorio con shaders WGSL de materiales (`pbr.wgsl`,
+    /// `unlit.wgsl`, `terrain.wgsl`) y recompilarlos al cambiar
+    pub fn watch_shader_directory(&mut self, directory: impl Into<std::path::PathBuf>) {
+        let watcher = ShaderWatcher::new(directory);
+        info!("Vigilando shaders en {:?}", watcher.directory());
+        self.shader_watcher = Some(watcher);
+    }
+
+    /// Dejar de vigilar el directorio de shaders
+    pub fn unwatch_shader_directory(&mut self) {
+        self.shader_watcher = None;
+    }
+
+    /// Sustituir el código de un shader de material por nombre (`pbr`,
+    /// `unlit`, `terrain`). Se recompila en el próximo `update`; es la vía
+    /// de recarga en wasm, donde no hay directorio que vigilar
+    pub fn set_shader_source(&mut self, shader: &str, source: &str) {
+        self.pending_shaders.retain(|(pending, _)| pending != shader);
+        self.pending_shaders.push((shader.to_string(), source.to_string()));
+    }
+
+    /// Recibir cada recarga de shader, correcta o con su error
+    pub fn set_shader_reload_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&ShaderReloadEvent) + Send + Sync + 'static,
+    {
+        self.shader_callback = Some(Box::new(callback));
+    }
+
+    /// Errores de los shaders que no compilan, por nombre
+    pub fn shader_errors(&self) -> &HashMap<String, ShaderError> {
+        &self.shader_errors
+    }
+
+    /// Pintar (Some) o no (None) los errores de shader en pantalla con la
+    /// fuente y el tamaño de `style`
+    pub fn set_shader_error_overlay(&mut self, style: Option<TextStyle>) {
+        self.shader_error_overlay = style;
+    }
+
+    /// Recompilar los shaders cambiados en disco o pedidos por la API. Sin
+    /// backend quedan en cola
+    fn reload_shaders(&mut self) {
+        if let Some(watcher) = &mut self.shader_watcher {
+            for (shader, source) in watcher.poll() {
+                self.pending_shaders.retain(|(pending, _)| *pending != shader);
+                self.pending_shaders.push((shader, source));
+            }
+        }
+        let Some(backend) = &mut self.backend else {
+            return;
+        };
+
+        for (shader, source) in self.pending_shaders.drain(..) {
+            let Some(kind) = shader_kind(&shader) else {
+                warn!("Shader sin tipo de material: {}", shader);
+                continue;
+            };
+            let event = match backend.reload_material_shader(kind, &source) {
+                Ok(()) => {
+                    info!("Shader recargado: {}", shader);
+                    self.shader_errors.remove(&shader);
+                    ShaderReloadEvent::Reloaded { shader, kind }
+                }
+                Err(e) => {
+                    error!("Error compilando shader: {}", e);
+                    self.shader_errors.insert(shader, e.clone());
+                    ShaderReloadEvent::Failed(e)
+                }
+            };
+            if let Some(callback) = &self.shader_callback {
+                callback(&event);
+            }
+        }
+    }
+
+    /// Pintar los errores de shader sobre un fondo oscuro en la esquina
+    /// superior izquierda
+    fn draw_shader_error_overlay(&mut self) {
+        let Some(style) = self.shader_error_overlay.clone() else {
+            return;
+        };
+        if self.shader_errors.is_empty() {
+            return;
+        }
+        let mut errors: Vec<String> = self.shader_errors.values().map(ToString::to_string).collect();
+        errors.sort();
+        let text = errors.join("\n");
+        let Some(size) = self.measure_text(&text, &style) else {
+            return;
+        };
+        let margin = Vec2::splat(style.size * 0.5);
+        self.draw_ui_rect(Vec2::ZERO, size + margin * 2.0, Vec4::new(0.0, 0.0, 0.0, 0.75));
+        self.draw_screen_text(&text, margin, &style);
+    }
+
+    /// Pedir la carga asíncrona de un modelo (glTF, GLB, OBJ) o textura
+    /// (Basis) desde disco o HTTP. Mayor prioridad se carga antes
+    pub fn enqueue_load(&mut self, path: &str, priority: u8) -> AssetHandle {
+        self.asset_loader.enqueue_load(path, priority)
+    }
+
+    /// Cancelar una carga pendiente o en curso
+    pub fn cancel_load(&mut self, handle: AssetHandle) {
+        self.asset_loader.cancel_load(handle);
+    }
+
+    /// Estado de una carga
+    pub fn get_asset(&self, handle: AssetHandle) -> AssetState {
+        self.asset_loader.get_asset(handle)
+    }
+
+    /// Olvidar un handle cuyo asset ya se ha consumido
+    pub fn release_asset(&mut self, handle: AssetHandle) {
+        self.asset_loader.release(handle);
+    }
+
+    /// Establecer la matriz vista-proyección de la cámara
+    pub fn set_view_projection(&mut self, view_projection: Mat4) {
+        self.draw_list.view_projection = view_projection;
+    }
+
+    /// Matriz vista-proyección de la cámara del frame en curso
+    pub fn view_projection(&self) -> Mat4 {
+        self.draw_list.view_projection
+    }
+
+    /// Color lineal con el que se limpia el destino antes de dibujar (también
+    /// en `render_to_texture`)
+    pub fn set_clear_color(&mut self, color: Vec4) {
+        self.draw_list.clear_color = color;
+    }
+
+    /// Establecer la posición de la cámara (distance culling)
+    pub fn set_camera_position(&mut self, position: Vec3) {
+        self.camera_position = position;
+    }
+
+    /// Posición de la cámara del último frame enviado
+    pub fn camera_position(&self) -> Vec3 {
+        self.camera_position
+    }
+
+    /// Encolar una llamada de dibujo para el próximo frame
+    pub fn queue_draw(&mut self, mesh_id: &str, transform: Mat4) {
+        self.queue_draw_with_layers(mesh_id, transform, RENDER_LAYER_DEFAULT);
+    }
+
+    /// Encolar una llamada de dibujo en unas capas de render concretas
+    pub fn queue_draw_with_layers(&mut self, mesh_id: &str, transform: Mat4, layers: u32) {
+        let call = self.draw_call(mesh_id, transform, layers);
+        self.draw_list.calls.push(call);
+    }
+
+    /// Draw call de un mesh con el material y el color base del mesh
+    fn draw_call(&self, mesh_id: &str, transform: Mat4, layers: u32) -> DrawCall {
+        let meshes = self.meshes.read().unwrap();
+        let material_id = meshes.get(mesh_id).and_then(|mesh| mesh.material.clone());
+        drop(meshes);
+
+        let base_color = material_id.as_deref()
+            .and_then(|id| self.get_material(id))
+            .map(|material| material.properties.base_color)
+            .unwrap_or(Vec4::ONE);
+
+        DrawCall {
+            mesh_id: mesh_id.to_string(),
+            material_id,
+            transform,
+            base_color,
+            skin: None,
+            morph: None,
+            layers,
+            entity: None,
+            shadow_cascades: SHADOW_CASCADES_ALL,
+        }
+    }
+
+    /// Encolar una malla con skin deformada por la paleta de `entity_id`
+    pub fn queue_skinned_draw(&mut self, mesh_id: &str, entity_id: EntityId, palette: SkinPalette) {
+        self.queue_draw(mesh_id, Mat4::IDENTITY);
+        if let Some(call) = self.draw_list.calls.last_mut() {
+            call.skin = Some(entity_id);
+        }
+        self.draw_list.skins.insert(entity_id, palette);
+    }
+
+    /// Deformar la última draw call encolada con los morph targets de `entity_id`
+    fn attach_morph(&mut self, entity_id: EntityId) {
+        let Some(weights) = self.morph_weights.get(&entity_id) else {
+            return;
+        };
+        if let Some(call) = self.draw_list.calls.last_mut() {
+            call.morph = Some(entity_id);
+            self.draw_list.morphs.insert(entity_id, weights.clone());
+        }
+    }
+
+    /// Entidad con los pesos de morph de una malla: la suya o la de su padre
+    fn morph_entity(&self, entity_id: EntityId, parent: Option<EntityId>) -> Option<EntityId> {
+        [Some(entity_id), parent].into_iter().flatten().find(|id| self.morph_weights.contains_key(id))
+    }
+
     /// Inicializar pipeline
     async fn initialize_pipeline(&mut self) -> Result<()> {
         // Crear pasos del pipeline
@@ -973,20 +1819,556 @@ impl RendererSystem {
             return Ok(());
         }
 
-        let start_time = std::time::Instant::now();
+        // Lanzar las cargas que quedaron en cola fuera del runtime
+        self.asset_loader.update();
+
+        // Recompilar los shaders cambiados y pintar los errores pendientes
+        self.reload_shaders();
+        self.draw_shader_error_overlay();
 
         // Actualizar estadísticas
         self.update_stats(delta_time);
 
+        Ok(())
+    }
+
+    /// Renderizar frame
+    pub async fn render(&mut self) -> Result<()> {
+        if !self.running {
+            return Ok(());
+        }
+
+        let start_time = std::time::Instant::now();
+
         // Ejecutar pipeline
         self.execute_pipeline().await?;
 
+        // Ajustar la resolución interna con el tiempo de GPU medido
+        let rescale = self.dynamic_resolution.as_mut()
+            .and_then(|dynamic| dynamic.update(self.gpu_frame_time_ms));
+        if let Some(scale) = rescale {
+            debug!("Escala de resolución dinámica: {:.2} ({:.2} ms de GPU)", scale, self.gpu_frame_time_ms);
+            self.apply_render_scale(scale);
+        }
+
         // Actualizar tiempo de renderizado
         self.stats.render_time = start_time.elapsed().as_secs_f32();
 
         Ok(())
     }
 
+    /// Renderizar el frame encolado y leer la imagen final en RGBA8
+    pub async fn capture_frame(&mut self) -> Result<ImageData> {
+        if !self.running {
+            return Err(anyhow!("Renderer sin iniciar"));
+        }
+        let backend = self.backend.as_mut().ok_or_else(|| anyhow!("Renderer sin backend"))?;
+        let readback = backend.capture_next_frame();
+        self.render().await?;
+        self.wait_readback(readback).await
+    }
+
+    /// Renderizar la escena desde la cámara de `camera` sobre un destino
+    /// offscreen de `width` x `height`, sin tocar el destino principal (p. ej.
+    /// miniaturas de objetos). Dibuja las entidades con malla dentro de su
+    /// frustum al nivel de LOD 0, con las luces y el entorno del último
+    /// `submit_scene` y sin sombras, post-procesado, decals ni texto
+    pub fn render_to_texture(
+        &mut self,
+        world: &ECSSystem,
+        camera: EntityId,
+        width: u32,
+        height: u32,
+    ) -> Result<RenderTextureHandle> {
+        let camera_component = world.get_component::<CameraComponent>(camera, ComponentType::Camera)
+            .ok_or_else(|| anyhow!("La entidad {} no tiene cámara", camera))?;
+        let camera_transform = world.get_component::<TransformComponent>(camera, ComponentType::Transform);
+        let handle = RenderTextureHandle(self.next_render_texture);
+        self.render_view(world, &camera_component, camera_transform.as_ref(), width, height, &handle.target_id())?;
+        self.next_render_texture += 1;
+        Ok(handle)
+    }
+
+    /// Dibujar los dos ojos de un frame de XR con las cámaras de
+    /// `CameraSystem::update_vr` y copiarlos a las imágenes del runtime. La
+    /// escena se recorre una vez por ojo con su frustum, como en
+    /// `render_to_texture`; el frame del runtime lo cierra quien lo empezó
+    pub fn render_xr_frame(
+        &mut self,
+        world: &ECSSystem,
+        cameras: &[CameraComponent; 2],
+        runtime: &mut dyn XrRuntime,
+    ) -> Result<()> {
+        if !self.running {
+            return Err(anyhow!("Renderer sin iniciar"));
+        }
+        let (width, height) = runtime.eye_resolution();
+        for (eye, camera) in cameras.iter().enumerate() {
+            let target = XR_EYE_TARGETS[eye];
+            self.render_view(world, camera, None, width, height, target)?;
+            let texture = runtime.acquire_eye_texture(eye)?;
+            let backend = self.backend.as_mut().ok_or_else(|| anyhow!("Renderer sin backend"))?;
+            backend.copy_render_texture(target, texture)?;
+            self.stats.xr_eye_views += 1;
+        }
+        Ok(())
+    }
+
+    /// Dibujar la escena desde una cámara sobre el destino offscreen
+    /// `target_id`
+    fn render_view(
+        &mut self,
+        world: &ECSSystem,
+        camera_component: &CameraComponent,
+        camera_transform: Option<&TransformComponent>,
+        width: u32,
+        height: u32,
+        target_id: &str,
+    ) -> Result<()> {
+        let (width, height) = (width.max(1), height.max(1));
+        let (view, projection) = camera_matrices(camera_component, camera_transform, width, height);
+        let view_projection = projection * view;
+        let frustum = Frustum::from_view_projection(&view_projection);
+
+        let mut draw_list = DrawList {
+            view_projection,
+            camera_position: view.inverse().w_axis.truncate(),
+            clear_color: self.draw_list.clear_color,
+            light: self.draw_list.light,
+            lights: self.draw_list.lights.clone(),
+            environment: self.draw_list.environment.clone(),
+            ..DrawList::default()
+        };
+        draw_list.clusters = self.light_clustering.as_ref()
+            .filter(|_| !draw_list.lights.is_empty())
+            .map(|config| ClusterFrame::new(config, view, projection, camera_component.near_plane, camera_component.far_plane));
+
+        for entity_id in world.get_entities_with_component(ComponentType::Mesh) {
+            let Some(mesh) = world.get_component::<MeshComponent>(entity_id, ComponentType::Mesh) else {
+                continue;
+            };
+            let Some(transform) = world.get_component::<TransformComponent>(entity_id, ComponentType::Transform) else {
+                continue;
+            };
+            self.register_mesh_component(&mesh);
+            let model = Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position);
+            let bounds = self.mesh_bounds.get(&mesh.mesh_id, &mesh.vertices).transformed(&model);
+            if !frustum.intersects(&bounds) {
+                continue;
+            }
+            // Las mallas con skin usan la paleta del frame, ya en espacio mundo
+            let skin = (!mesh.joint_indices.is_empty())
+                .then(|| [Some(entity_id), transform.parent].into_iter().flatten()
+                    .find(|id| self.skin_palettes.contains_key(id)))
+                .flatten();
+            let mut call = match skin {
+                Some(skin) => {
+                    let mut call = self.draw_call(&mesh.mesh_id, Mat4::IDENTITY, mesh.render_layers);
+                    call.skin = Some(skin);
+                    draw_list.skins.insert(skin, self.skin_palettes[&skin].clone());
+                    call
+                }
+                None => self.draw_call(&mesh.mesh_id, model, mesh.render_layers),
+            };
+            if let Some(morph) = self.morph_entity(entity_id, transform.parent) {
+                call.morph = Some(morph);
+                draw_list.morphs.insert(morph, self.morph_weights[&morph].clone());
+            }
+            draw_list.calls.push(call);
+        }
+
+        self.upload_draw_resources(&draw_list)?;
+        let backend = self.backend.as_mut().ok_or_else(|| anyhow!("Renderer sin backend"))?;
+        backend.render_to_texture(target_id, &draw_list, width, height)?;
+        Ok(())
+    }
+
+    /// Leer la imagen RGBA8 de un destino offscreen
+    pub async fn read_render_texture(&mut self, handle: RenderTextureHandle) -> Result<ImageData> {
+        let backend = self.backend.as_mut().ok_or_else(|| anyhow!("Renderer sin backend"))?;
+        let readback = backend.read_render_texture(&handle.target_id())?;
+        self.wait_readback(readback).await
+    }
+
+    /// Liberar un destino offscreen
+    pub fn release_render_texture(&mut self, handle: RenderTextureHandle) {
+        if let Some(backend) = &mut self.backend {
+            backend.remove_render_texture(&handle.target_id());
+        }
+    }
+
+    /// Esperar una lectura de la GPU entregando las terminadas. En wasm el
+    /// mapeo se resuelve en el bucle de eventos del navegador, así que se le
+    /// cede el control entre intentos
+    async fn wait_readback(&mut self, mut readback: ImageReadback) -> Result<ImageData> {
+        for _ in 0..MAX_READBACK_POLLS {
+            if let Some(result) = readback.try_take() {
+                return result;
+            }
+            if let Some(backend) = &mut self.backend {
+                backend.poll_readbacks();
+            }
+            if let Some(result) = readback.try_take() {
+                return result;
+            }
+            yield_to_event_loop().await;
+        }
+        Err(anyhow!("La lectura de la imagen no terminó"))
+    }
+
+    /// Encolar las entidades con malla y transformación usando la cámara activa
+    pub fn submit_scene(&mut self, world: &ECSSystem) {
+        let [width, height] = self.config.quality_config.resolution;
+
+        // La cámara activa es la primera entidad con componente de cámara
+        let camera = world.get_entities_with_component(ComponentType::Camera)
+            .into_iter()
+            .find_map(|id| {
+                let camera = world.get_component::<CameraComponent>(id, ComponentType::Camera)?;
+                let transform = world.get_component::<TransformComponent>(id, ComponentType::Transform);
+                Some((camera, transform))
+            });
+        if let Some((camera, transform)) = camera {
+            let (view, projection) = camera_matrices(&camera, transform.as_ref(), width, height);
+            self.set_view_projection(projection * view);
+            self.set_camera_position(view.inverse().w_axis.truncate());
+            self.camera_view = view;
+            self.camera_clip = (camera.near_plane, camera.far_plane);
+        }
+
+        // Luz direccional: se prefiere la primera que proyecta sombras
+        let mut lights: Vec<(LightComponent, Option<TransformComponent>)> = world
+            .get_entities_with_component(ComponentType::Light)
+            .into_iter()
+            .filter_map(|id| {
+                let light = world.get_component::<LightComponent>(id, ComponentType::Light)?;
+                matches!(light.light_type, LightType::Directional)
+                    .then(|| (light, world.get_component::<TransformComponent>(id, ComponentType::Transform)))
+            })
+            .collect();
+        lights.sort_by_key(|(light, _)| !light.shadows);
+        self.light_casts_shadows = lights.first().map_or(false, |(light, _)| light.shadows);
+        self.draw_list.light = lights.first().map(|(light, transform)| DirectionalLight {
+            // La luz apunta hacia -Z de su transformación
+            direction: transform.as_ref().map_or(Vec3::NEG_Y, |t| t.rotation * Vec3::NEG_Z),
+            color: light.color * light.intensity,
+            ambient: Vec3::splat(DEFAULT_AMBIENT_LIGHT),
+        });
+
+        // Luces puntuales y focos, de la más intensa a la más débil: si un
+        // cluster se desborda se descartan las últimas
+        let (point, spot) = self.local_light_types;
+        let mut local_lights: Vec<LocalLight> = world
+            .get_entities_with_component(ComponentType::Light)
+            .into_iter()
+            .filter_map(|id| {
+                let light = world.get_component::<LightComponent>(id, ComponentType::Light)?;
+                let transform = world.get_component::<TransformComponent>(id, ComponentType::Transform)?;
+                let kind = match light.light_type {
+                    LightType::Point if point => LocalLightKind::Point,
+                    LightType::Spot if spot => {
+                        // El ángulo es el semiángulo del cono en grados
+                        let outer = light.angle.to_radians().clamp(0.0, std::f32::consts::FRAC_PI_2);
+                        LocalLightKind::Spot {
+                            direction: transform.rotation * Vec3::NEG_Z,
+                            inner_cos: (outer * (1.0 - SPOT_LIGHT_PENUMBRA)).cos(),
+                            outer_cos: outer.cos(),
+                        }
+                    }
+                    _ => return None,
+                };
+                (light.range > 0.0).then(|| LocalLight {
+                    position: transform.position,
+                    range: light.range,
+                    color: light.color * light.intensity,
+                    kind,
+                })
+            })
+            .collect();
+        local_lights.sort_by(|a, b| b.color.max_element().total_cmp(&a.color.max_element()));
+        self.draw_list.lights = local_lights;
+
+        // Decals: los de mayor prioridad dentro del presupuesto
+        let decals: Vec<DecalCandidate> = world.get_entities_with_component(ComponentType::Decal)
+            .into_iter()
+            .filter_map(|entity| {
+                let decal = world.get_component::<DecalComponent>(entity, ComponentType::Decal)?;
+                let transform = world.get_component::<TransformComponent>(entity, ComponentType::Transform)?;
+                let model = Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position);
+                Some(DecalCandidate { entity, decal, model })
+            })
+            .collect();
+        self.draw_list.decals = select_decals(
+            decals,
+            self.camera_position,
+            self.config.optimization_config.max_decals as usize,
+        );
+
+        self.update_material_atlases(world);
+
+        // Actualizar el octree con las cajas de las entidades con malla
+        // Mesh, modelo, nivel de LOD mínimo, niveles reducidos disponibles,
+        // entidad con la paleta de huesos, entidad con los pesos de morph y
+        // capas de render
+        let mut candidates: HashMap<EntityId, (String, Mat4, u32, u32, Option<EntityId>, Option<EntityId>, u32)> = HashMap::new();
+        for entity_id in world.get_entities_with_component(ComponentType::Mesh) {
+            let Some(mesh) = world.get_component::<MeshComponent>(entity_id, ComponentType::Mesh) else {
+                continue;
+            };
+            let Some(transform) = world.get_component::<TransformComponent>(entity_id, ComponentType::Transform) else {
+                continue;
+            };
+
+            self.register_mesh_component(&mesh);
+
+            let model = Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position);
+            let local = self.mesh_bounds.get(&mesh.mesh_id, &mesh.vertices);
+            self.spatial_index.update(entity_id, local.transformed(&model));
+            // Las primitivas adicionales de un nodo glTF usan la paleta del nodo padre
+            let skin = (!mesh.joint_indices.is_empty())
+                .then(|| [Some(entity_id), transform.parent].into_iter().flatten()
+                    .find(|id| self.skin_palettes.contains_key(id)))
+                .flatten();
+            let morph = self.morph_entity(entity_id, transform.parent);
+            candidates.insert(
+                entity_id,
+                (mesh.mesh_id, model, mesh.lod_level, mesh.lod_indices.len() as u32, skin, morph, mesh.render_layers),
+            );
+        }
+
+        let mut indexed = Vec::new();
+        self.spatial_index.all(&mut indexed);
+        for entity_id in indexed {
+            if !candidates.contains_key(&entity_id) {
+                self.spatial_index.remove(entity_id);
+            }
+        }
+
+        // Frustum culling contra el octree y distance culling
+        let optimization = &self.config.optimization_config;
+        let mut visible = Vec::with_capacity(candidates.len());
+        if optimization.frustum_culling {
+            let frustum = Frustum::from_view_projection(&self.draw_list.view_projection);
+            self.spatial_index.query_frustum(&frustum, &mut visible);
+        } else {
+            self.spatial_index.all(&mut visible);
+        }
+        if optimization.distance_culling && optimization.max_draw_distance > 0.0 {
+            let max_distance = optimization.max_draw_distance;
+            let camera_position = self.camera_position;
+            visible.retain(|entity_id| {
+                self.spatial_index.bounds(*entity_id)
+                    .map_or(false, |aabb| aabb.distance_to(camera_position) <= max_distance)
+            });
+        }
+
+        self.stats.rendered_objects = visible.len() as u32;
+        self.stats.culled_objects = (candidates.len() - visible.len()) as u32;
+
+        // Nivel de LOD por distancia desde la cámara a la caja de la entidad
+        let lod_enabled = self.config.quality_config.lod.enabled && self.config.optimization_config.lod;
+        let camera_position = self.camera_position;
+        for entity_id in visible {
+            if let Some((mesh_id, model, min_level, available, skin, morph, layers)) = candidates.get(&entity_id) {
+                let level = if lod_enabled && *available > 0 {
+                    let distance = self.spatial_index.bounds(entity_id)
+                        .map_or(0.0, |aabb| aabb.distance_to(camera_position));
+                    self.lod_selector.select(entity_id, distance, *available).max(*min_level).min(*available)
+                } else {
+                    0
+                };
+                let mesh_id = lod_mesh_id(mesh_id, level);
+                match skin.and_then(|skin| self.skin_palettes.get(&skin)).cloned() {
+                    Some(palette) => {
+                        self.queue_skinned_draw(&mesh_id, entity_id, palette);
+                        if let Some(call) = self.draw_list.calls.last_mut() {
+                            call.layers = *layers;
+                        }
+                    }
+                    None => self.queue_draw_with_layers(&mesh_id, *model, *layers),
+                }
+                if let Some(call) = self.draw_list.calls.last_mut() {
+                    call.entity = Some(entity_id);
+                }
+                if let Some(morph) = *morph {
+                    self.attach_morph(morph);
+                }
+            }
+        }
+        self.lod_selector.retain(|entity_id| candidates.contains_key(&entity_id));
+
+        // Etiquetas de los componentes y de `draw_text`, y después la UI en pantalla
+        let pending = std::mem::take(&mut self.pending_labels);
+        let labels: Vec<WorldLabel> = world.get_entities_with_component(ComponentType::Label)
+            .into_iter()
+            .filter_map(|entity| {
+                let label = world.get_component::<LabelComponent>(entity, ComponentType::Label)?;
+                let anchor = self.label_anchor(world, entity)? + label.offset;
+                Some(WorldLabel { text: label.text, style: label.style, anchor })
+            })
+            .chain(pending.into_iter().filter_map(|(entity, text, style)| {
+                Some(WorldLabel { anchor: self.label_anchor(world, entity)?, text, style })
+            }))
+            .collect();
+        let mut text = TextFrame::default();
+        place_world_labels(&mut text, &labels, &self.fonts, &self.camera_view, &self.draw_list.view_projection, (width, height));
+        text.batches.append(&mut self.screen_text.batches);
+        self.draw_list.text = (!text.is_empty()).then_some(text);
+    }
+
+    /// Registrar el mesh de un componente del ECS, una vez por mesh_id,
+    /// junto con sus niveles de LOD
+    fn register_mesh_component(&self, mesh: &MeshComponent) {
+        if self.meshes.read().unwrap().contains_key(&mesh.mesh_id) {
+            return;
+        }
+        let mut base = mesh_from_component(mesh);
+        // Los meshes de un material agrupado usan el atlas
+        if let Some((atlas_id, region)) = mesh.material_id.as_ref().and_then(|id| self.atlas_regions.get(id)) {
+            for vertex in &mut base.geometry.vertices {
+                let uv = region.apply(vertex.uv.truncate());
+                vertex.uv = uv.extend(vertex.uv.z);
+            }
+            base.material = Some(atlas_id.clone());
+        }
+        let mut meshes = self.meshes.write().unwrap();
+        for (i, indices) in mesh.lod_indices.iter().enumerate() {
+            let id = lod_mesh_id(&base.id, i as u32 + 1);
+            let mut geometry = base.geometry.clone();
+            geometry.indices = indices.clone();
+            meshes.insert(id.clone(), Mesh { id, geometry, lod: Vec::new(), ..base.clone() });
+        }
+        meshes.insert(base.id.clone(), base);
+    }
+
+    /// Agrupar en atlas los materiales del ECS que solo tienen textura de
+    /// albedo y comparten tipo y parámetros. Cada grupo de dos o más
+    /// materiales se dibuja con un material de atlas; los atlas se
+    /// reconstruyen cuando cambia qué materiales y texturas se agrupan
+    fn update_material_atlases(&mut self, world: &ECSSystem) {
+        let mut candidates: Vec<MaterialDesc> = Vec::new();
+        if self.config.enable_atlas_packing {
+            let mut seen = std::collections::HashSet::new();
+            for entity_id in world.get_entities_with_component(ComponentType::Material) {
+                let Some(component) = world.get_component::<MaterialComponent>(entity_id, ComponentType::Material) else {
+                    continue;
+                };
+                if !seen.insert(component.material_id.clone()) {
+                    continue;
+                }
+                let desc = material_desc_from_component(&component);
+                if desc.textures.albedo.is_some() && desc.textures.iter().count() == 1 {
+                    candidates.push(desc);
+                }
+            }
+        }
+        let mut signature: Vec<(String, String)> = candidates.iter()
+            .map(|desc| (desc.id.clone(), desc.textures.albedo.clone().unwrap_or_default()))
+            .collect();
+        signature.sort();
+        if signature == self.atlas_signature {
+            return;
+        }
+
+        // Deshacer los atlas anteriores y los meshes que los usaban o que
+        // pasan a usarlos, para que se vuelvan a registrar
+        for atlas_id in std::mem::take(&mut self.atlas_materials).into_keys() {
+            if let Some(backend) = &mut self.backend {
+                backend.remove_material(&atlas_id);
+            }
+            self.remove_texture(&atlas_id);
+        }
+        self.atlas_regions.clear();
+        let stale: Vec<String> = self.meshes.read().unwrap().values()
+            .filter(|mesh| mesh.material.as_ref().is_some_and(|material| {
+                material.starts_with(ATLAS_MATERIAL_PREFIX) || candidates.iter().any(|desc| &desc.id == material)
+            }))
+            .map(|mesh| mesh.id.clone())
+            .collect();
+        for mesh_id in stale {
+            self.remove_mesh(&mesh_id);
+        }
+
+        // Un grupo por tipo y parámetros
+        let mut groups: Vec<(MaterialKind, MaterialParams, Vec<MaterialDesc>)> = Vec::new();
+        for desc in candidates {
+            match groups.iter_mut().find(|(kind, params, _)| *kind == desc.kind && *params == desc.params) {
+                Some((_, _, members)) => members.push(desc),
+                None => groups.push((desc.kind, desc.params, vec![desc])),
+            }
+        }
+
+        for (kind, params, members) in groups.into_iter().filter(|(_, _, members)| members.len() > 1) {
+            let images: Vec<(String, TextureImage)> = {
+                let textures = self.textures.read().unwrap();
+                let mut seen = std::collections::HashSet::new();
+                members.iter()
+                    .filter_map(|desc| desc.textures.albedo.clone())
+                    .filter(|id| seen.insert(id.clone()))
+                    .filter_map(|id| textures.get(&id).and_then(|texture| texture_image(texture, true)).map(|image| (id, image)))
+                    .collect()
+            };
+            let Some((image, regions)) = TextureAtlas::pack(images, self.config.atlas_max_size) else {
+                continue;
+            };
+
+            let atlas_id = format!("{}{}", ATLAS_MATERIAL_PREFIX, self.atlas_materials.len());
+            let texture = Texture {
+                id: atlas_id.clone(),
+                name: atlas_id.clone(),
+                texture_type: TextureType::Diffuse,
+                config: TextureConfig {
+                    width: image.width,
+                    height: image.height,
+                    format: TextureFormat::RGBA8,
+                    filter: TextureFilter::Linear,
+                    wrap: TextureWrap::ClampToEdge,
+                    mipmaps: false,
+                },
+                data: Some(image.pixels),
+            };
+            self.texture_streamer.reload(&texture.id, texture.config.width, texture.config.height);
+            {
+                let mut textures = self.textures.write().unwrap();
+                textures.insert(texture.id.clone(), texture);
+                self.stats.loaded_textures = textures.len() as u32;
+            }
+
+            for desc in &members {
+                let region = desc.textures.albedo.as_ref().and_then(|id| regions.get(id));
+                if let Some(region) = region {
+                    self.atlas_regions.insert(desc.id.clone(), (atlas_id.clone(), *region));
+                }
+            }
+            self.atlas_materials.insert(atlas_id.clone(), MaterialDesc {
+                id: atlas_id.clone(),
+                kind,
+                params,
+                textures: MaterialTextureSlots { albedo: Some(atlas_id.clone()), ..Default::default() },
+            });
+            debug!("Atlas {}: {}x{} con {} texturas", atlas_id, image.width, image.height, regions.len());
+        }
+
+        self.atlas_signature = signature;
+        self.stats.atlased_materials = self.atlas_regions.len() as u32;
+    }
+
+    /// Material de atlas y región de un material del ECS agrupado
+    pub fn atlas_region(&self, material_id: &str) -> Option<(String, UVRegion)> {
+        self.atlas_regions.get(material_id).cloned()
+    }
+
+    /// Anclaje de las etiquetas de una entidad: el centro de la parte
+    /// superior de su caja, o su posición si no tiene malla
+    fn label_anchor(&self, world: &ECSSystem, entity: EntityId) -> Option<Vec3> {
+        if let Some(bounds) = self.spatial_index.bounds(entity) {
+            let center = (bounds.min + bounds.max) * 0.5;
+            return Some(Vec3::new(center.x, bounds.max.y, center.z));
+        }
+        world.get_component::<TransformComponent>(entity, ComponentType::Transform)
+            .map(|transform| transform.position)
+    }
+
     /// Ejecutar pipeline
     async fn execute_pipeline(&mut self) -> Result<()> {
         // Ordenar pasos por orden
@@ -1034,22 +2416,223 @@ impl RendererSystem {
 
     /// Limpiar buffers
     async fn clear_buffers(&mut self) -> Result<()> {
-        // Limpiar color, depth y stencil buffers
+        // El backend limpia al comenzar el pase principal
         debug!("Limpiando buffers");
+        self.stats.draw_calls = 0;
+        self.stats.triangles = 0;
+        self.stats.vertices = 0;
+        self.stats.skinned_vertices = 0;
+        self.stats.morphed_vertices = 0;
         Ok(())
     }
 
-    /// Renderizar shadow pass
+    /// Cascadas de sombra del frame en curso y dirección de la luz (None sin
+    /// sombras). Depende de la cámara y la luz que fija `submit_scene`
+    pub fn compute_shadow_cascades(&self) -> Option<(shadows::CascadedShadows, Vec3)> {
+        let settings = ShadowSettings::from_config(&self.config.quality_config.shadows)?;
+        let light = self.draw_list.light.as_ref().filter(|_| self.light_casts_shadows)?;
+        let (near, far) = self.camera_clip;
+        let cascades = shadows::compute_cascades(
+            settings,
+            self.camera_view,
+            &self.draw_list.view_projection,
+            near,
+            far,
+            light.direction,
+        );
+        Some((cascades, light.direction))
+    }
+
+    /// Entidades con draw call encolada en el frame en curso, ordenadas
+    pub fn queued_entities(&self) -> Vec<EntityId> {
+        let mut entities: Vec<EntityId> = self.draw_list.calls.iter().filter_map(|call| call.entity).collect();
+        entities.sort_unstable();
+        entities.dedup();
+        entities
+    }
+
+    /// Entidades (ordenadas) que dibuja cada cascada de sombras en el próximo
+    /// frame; las draw calls de otras entidades se saltan ese shadow map. Las
+    /// draw calls sin entidad (terreno, instancias) se dibujan en todas
+    pub fn set_shadow_casters(&mut self, casters: Option<Vec<Vec<EntityId>>>) {
+        self.shadow_casters = casters;
+    }
+
+    /// Renderizar shadow pass: calcula las cascadas que el backend dibuja
+    /// antes del pase principal y marca en qué cascadas entra cada draw call
     async fn render_shadow_pass(&mut self) -> Result<()> {
-        // Renderizar sombras
         debug!("Renderizando shadow pass");
+        self.draw_list.shadows = self.compute_shadow_cascades().map(|(cascades, _)| cascades);
+        if let Some(casters) = self.shadow_casters.take() {
+            for call in &mut self.draw_list.calls {
+                let Some(entity_id) = call.entity else {
+                    continue;
+                };
+                call.shadow_cascades = casters.iter()
+                    .enumerate()
+                    .filter(|(_, set)| set.binary_search(&entity_id).is_ok())
+                    .fold(0, |mask, (cascade, _)| mask | (1 << cascade));
+            }
+        }
         Ok(())
     }
 
     /// Renderizar geometry pass
     async fn render_geometry_pass(&mut self) -> Result<()> {
-        // Renderizar geometría
         debug!("Renderizando geometry pass");
+        let mut draw_list = DrawList {
+            calls: std::mem::take(&mut self.draw_list.calls),
+            instanced: std::mem::take(&mut self.draw_list.instanced),
+            skins: std::mem::take(&mut self.draw_list.skins),
+            morphs: std::mem::take(&mut self.draw_list.morphs),
+            decals: std::mem::take(&mut self.draw_list.decals),
+            particles: ParticleFrame::new(std::mem::take(&mut self.particle_batches), &self.camera_view, self.camera_clip),
+            text: std::mem::take(&mut self.draw_list.text),
+            camera_position: self.camera_position,
+            ..self.draw_list.clone()
+        };
+
+        // Jitter del TAA y cadena de post-procesado del frame, a la
+        // resolución interna
+        let [width, height] = self.config.quality_config.resolution;
+        let (width, height) = scaled_size(width, height, self.get_current_resolution_scale());
+        let (view_projection, post) = self.post_process.begin_frame(draw_list.view_projection, width, height);
+        draw_list.view_projection = view_projection;
+        // El VRS reproyecta con las mismas matrices sin jitter que el TAA
+        draw_list.vrs = self.variable_rate_shading.as_ref().map(|config| VRSFrame {
+            config: config.clone(),
+            view_projection: post.view_projection,
+            previous_view_projection: post.previous_view_projection,
+        });
+        // La fase temprana del occlusion culling prueba contra la pirámide
+        // del frame anterior con su matriz
+        draw_list.occlusion = self.config.optimization_config.occlusion_culling.then(|| OcclusionFrame {
+            previous_view_projection: post.previous_view_projection,
+            reset: post.reset_history,
+        });
+        // Los clusters se calculan con la cámara sin jitter
+        draw_list.clusters = self.light_clustering.as_ref()
+            .filter(|_| !draw_list.lights.is_empty())
+            .map(|config| {
+                let (near, far) = self.camera_clip;
+                let projection = post.view_projection * self.camera_view.inverse();
+                ClusterFrame::new(config, self.camera_view, projection, near, far)
+            });
+        // Las líneas de depuración se dibujan sin jitter, como el texto
+        draw_list.debug = self.debug_draw.take_frame(post.view_projection);
+        draw_list.post = Some(post);
+
+        // Agrupar por mesh y material en draw calls instanciadas
+        let optimization = &self.config.optimization_config;
+        self.stats.instanced_batches = if optimization.instancing {
+            draw_list.batch_instances(optimization.instancing_threshold as usize) as u32
+        } else {
+            0
+        };
+
+        // Prioridad del streaming de texturas: distancia de cada material a
+        // la cámara
+        self.texture_streamer.begin_frame();
+        let camera_position = self.camera_position;
+        let material_positions = draw_list.calls.iter()
+            .filter_map(|call| Some((call.material_id.as_deref()?, call.transform)))
+            .chain(draw_list.instanced.iter()
+                .filter_map(|draw| Some((draw.material_id.as_deref()?, &draw.instances)))
+                .flat_map(|(material_id, instances)| instances.iter().map(move |instance| (material_id, instance.transform))));
+        for (material_id, transform) in material_positions {
+            self.texture_streamer.touch_material(material_id, transform.w_axis.truncate().distance(camera_position));
+        }
+
+        self.upload_draw_resources(&draw_list)?;
+        let Some(backend) = &mut self.backend else {
+            return Ok(());
+        };
+        Self::stream_textures_to(backend.as_mut(), &self.textures, &mut self.texture_streamer);
+        self.stats.resident_texture_bytes = self.texture_streamer.resident_bytes();
+        self.stats.evicted_mip_count = self.texture_streamer.evicted_mip_count();
+
+        // Las texturas de los decals se copian al atlas la primera vez que se
+        // usan. Si no caben, el decal se omite este frame
+        let pending: Vec<TextureImage> = {
+            let textures = self.textures.read().unwrap();
+            let mut seen = std::collections::HashSet::new();
+            draw_list.decal_texture_ids()
+                .filter(|id| seen.insert(*id) && !backend.has_decal_texture(id))
+                .filter_map(|id| textures.get(id).and_then(|texture| texture_image(texture, true)))
+                .collect()
+        };
+        for image in pending {
+            if let Err(err) = backend.upload_decal_texture(&image) {
+                warn!("Decal omitido: {}", err);
+            }
+        }
+
+        // Los atlas de las fuentes se suben la primera vez que se usan
+        for font_id in draw_list.font_ids() {
+            if !backend.has_font(font_id) {
+                if let Some(atlas) = self.fonts.get(font_id) {
+                    backend.upload_font(atlas)?;
+                }
+            }
+        }
+
+        let frame = backend.render(&draw_list)?;
+        self.stats.draw_calls += frame.draw_calls + frame.shadow_draw_calls;
+        self.stats.triangles += frame.triangles;
+        self.stats.vertices += frame.vertices;
+        // Los backends sin imagen de tasas sombrean cada píxel
+        self.stats.average_shading_rate = if frame.average_shading_rate > 0.0 { frame.average_shading_rate } else { 1.0 };
+        self.stats.skinned_vertices += frame.skinned_vertices;
+        self.stats.morphed_vertices += frame.morphed_vertices;
+        self.gpu_frame_time_ms = frame.gpu_time_ms;
+        self.stats.gpu_frame_time_ms = frame.gpu_time_ms;
+        self.stats.rendered_decals = frame.decals;
+        self.stats.rendered_particles = frame.particles;
+        self.stats.rendered_text_quads = frame.text_quads;
+        self.stats.rendered_debug_lines = frame.debug_lines;
+        // Lo oculto por el occlusion culling cuenta como descartado
+        self.stats.occlusion_tested = frame.occlusion_tested;
+        self.stats.occluded_objects = frame.occluded;
+        self.stats.rendered_objects = self.stats.rendered_objects.saturating_sub(frame.occluded);
+        self.stats.culled_objects += frame.occluded;
+        self.stats.clustered_lights = frame.clustered_lights;
+        self.stats.overflowed_clusters = frame.overflowed_clusters;
+        self.stats.dropped_cluster_lights = frame.dropped_cluster_lights;
+        self.stats.max_cluster_lights = frame.max_cluster_lights;
+        self.stats.shader_cache_hits = frame.shader_cache_hits;
+        self.stats.shader_cache_misses = frame.shader_cache_misses;
+        Ok(())
+    }
+
+    /// Subir los meshes y materiales de una lista que aún no están en el
+    /// backend
+    fn upload_draw_resources(&mut self, draw_list: &DrawList) -> Result<()> {
+        let Some(backend) = &mut self.backend else {
+            return Ok(());
+        };
+
+        {
+            let meshes = self.meshes.read().unwrap();
+            for mesh_id in draw_list.mesh_ids() {
+                if !backend.has_mesh(mesh_id) {
+                    if let Some(mesh) = meshes.get(mesh_id) {
+                        backend.upload_mesh(mesh)?;
+                    }
+                }
+            }
+        }
+
+        // Los materiales del renderer (glTF) se suben la primera vez que se usan
+        let pending: Vec<MaterialDesc> = {
+            let materials = self.materials.read().unwrap();
+            draw_list.material_ids()
+                .filter(|id| !backend.has_material(id))
+                .filter_map(|id| materials.get(id).map(material_desc).or_else(|| self.atlas_materials.get(id).cloned()))
+                .collect()
+        };
+        for desc in pending {
+            Self::upload_material_to(backend.as_mut(), &self.textures, &mut self.texture_streamer, &desc)?;
+        }
         Ok(())
     }
 
@@ -1060,16 +2643,17 @@ impl RendererSystem {
         Ok(())
     }
 
-    /// Renderizar post-process
+    /// Renderizar post-process: el backend ejecuta la cadena junto al
+    /// geometry pass; aquí se avanza el estado temporal (jitter e historia)
     async fn render_post_process(&mut self) -> Result<()> {
-        // Aplicar efectos post-procesamiento
         debug!("Renderizando post-process");
+        self.post_process.end_frame();
         Ok(())
     }
 
-    /// Renderizar UI
+    /// Renderizar UI: el texto y la UI se colocan en `submit_scene` y el
+    /// backend los dibuja en el último pase del geometry pass
     async fn render_ui(&mut self) -> Result<()> {
-        // Renderizar interfaz de usuario
         debug!("Renderizando UI");
         Ok(())
     }
@@ -1087,6 +2671,174 @@ impl RendererSystem {
         materials.get(id).cloned()
     }
 
+    /// Factores de reducción de los niveles de LOD configurados (vacío si el LOD está deshabilitado)
+    pub fn lod_reduction_factors(&self) -> Vec<f32> {
+        if !self.config.quality_config.lod.enabled || !self.config.optimization_config.lod {
+            return Vec::new();
+        }
+        LodSelector::reduction_factors(&self.config.quality_config.lod)
+    }
+
+    /// Multiplicar globalmente las distancias de LOD (menor que 1 reduce el detalle antes)
+    pub fn set_lod_distance_bias(&mut self, bias: f32) {
+        self.lod_selector.set_distance_bias(bias);
+    }
+
+    /// Multiplicador global de las distancias de LOD
+    pub fn lod_distance_bias(&self) -> f32 {
+        self.lod_selector.distance_bias()
+    }
+
+    /// Distance culling: habilitado y distancia máxima de dibujo (0 = sin límite)
+    pub fn distance_culling(&self) -> (bool, f32) {
+        let optimization = &self.config.optimization_config;
+        (optimization.distance_culling, optimization.max_draw_distance)
+    }
+
+    /// Cambiar el distance culling; se aplica desde el próximo frame
+    pub fn set_distance_culling(&mut self, enabled: bool, max_draw_distance: f32) {
+        self.config.optimization_config.distance_culling = enabled;
+        self.config.optimization_config.max_draw_distance = max_draw_distance.max(0.0);
+    }
+
+    /// Resolución de cada cascada de sombras
+    pub fn shadow_resolution(&self) -> u32 {
+        self.config.quality_config.shadows.resolution
+    }
+
+    /// Cambiar la resolución de las cascadas; el backend recrea los shadow
+    /// maps en el próximo frame con sombras
+    pub fn set_shadow_resolution(&mut self, resolution: u32) {
+        self.config.quality_config.shadows.resolution = resolution.max(1);
+    }
+
+    /// Muestras MSAA del pase principal (1 sin MSAA)
+    pub fn msaa_samples(&self) -> u32 {
+        let antialiasing = &self.config.quality_config.antialiasing;
+        match antialiasing.antialiasing_type {
+            AntialiasingType::MSAA => antialiasing.antialiasing_level.max(1),
+            _ => 1,
+        }
+    }
+
+    /// Cambiar las muestras MSAA. Solo tiene efecto con antialiasing MSAA;
+    /// el backend recompila los pipelines del pase principal
+    pub fn set_msaa_samples(&mut self, samples: u32) {
+        let antialiasing = &mut self.config.quality_config.antialiasing;
+        if !matches!(antialiasing.antialiasing_type, AntialiasingType::MSAA) {
+            return;
+        }
+        antialiasing.antialiasing_level = samples.max(1);
+        if let Some(backend) = &mut self.backend {
+            backend.set_sample_count(samples.max(1));
+        }
+    }
+
+    /// Sacar de la caché del cargador los assets que ya no tienen handles.
+    /// Devuelve los que se desalojaron
+    pub fn evict_asset_cache(&mut self) -> usize {
+        self.asset_loader.evict_unreferenced()
+    }
+
+    /// Subir un material al backend junto con las texturas que aún no estén
+    /// en la GPU. Devuelve false si todavía no hay backend
+    pub fn upload_material(&mut self, desc: &MaterialDesc) -> Result<bool> {
+        let Some(backend) = &mut self.backend else {
+            return Ok(false);
+        };
+        Self::upload_material_to(backend.as_mut(), &self.textures, &mut self.texture_streamer, desc)?;
+        Ok(true)
+    }
+
+    /// Subir los mapas de un entorno al backend. Devuelve false si todavía
+    /// no hay backend
+    pub fn upload_environment(&mut self, maps: &EnvironmentMaps) -> Result<bool> {
+        let Some(backend) = &mut self.backend else {
+            return Ok(false);
+        };
+        backend.upload_environment(maps)?;
+        Ok(true)
+    }
+
+    /// Verificar si un entorno está en el backend
+    pub fn has_environment(&self, environment_id: &str) -> bool {
+        self.backend.as_ref().is_some_and(|backend| backend.has_environment(environment_id))
+    }
+
+    /// Liberar un entorno del backend
+    pub fn remove_environment(&mut self, environment_id: &str) {
+        if let Some(backend) = &mut self.backend {
+            backend.remove_environment(environment_id);
+        }
+    }
+
+    /// Entorno de los próximos frames (None sin IBL ni skybox)
+    pub fn set_environment(&mut self, environment: Option<EnvironmentFrame>) {
+        self.draw_list.environment = environment;
+    }
+
+    /// Liberar un material del backend (y del renderer si se creó con
+    /// `create_material`)
+    pub fn remove_material(&mut self, material_id: &str) {
+        if let Some(backend) = &mut self.backend {
+            backend.remove_material(material_id);
+        }
+        self.materials.write().unwrap().remove(material_id);
+    }
+
+    /// Las texturas nuevas se suben con su nivel de respaldo; el streaming
+    /// las sube a más resolución cuando se dibujan
+    fn upload_material_to(
+        backend: &mut dyn RenderBackend,
+        textures: &RwLock<HashMap<String, Texture>>,
+        streamer: &mut TextureStreamer,
+        desc: &MaterialDesc,
+    ) -> Result<()> {
+        let textures = textures.read().unwrap();
+        let slots = &desc.textures;
+        // Albedo, emisivo y capas del terreno se muestrean en sRGB; el resto
+        // son datos lineales
+        let srgb_ids: Vec<&Option<String>> = [&slots.albedo, &slots.emissive].into_iter()
+            .chain(slots.layers.iter())
+            .collect();
+        for id in slots.iter().filter(|id| !backend.has_texture(id)) {
+            let Some(texture) = textures.get(id) else {
+                continue;
+            };
+            let srgb = srgb_ids.iter().any(|slot| slot.as_deref() == Some(id));
+            match texture_image(texture, srgb) {
+                Some(image) => {
+                    let mip = fallback_mip(image.width, image.height);
+                    backend.upload_texture(&mip_chain_image(&image, mip))?;
+                    streamer.register(id, image.width, image.height, srgb);
+                    streamer.mark_resident(id, mip);
+                }
+                None => warn!("Textura {} sin datos RGBA8; se usa la textura neutra", id),
+            }
+        }
+        streamer.bind_material(&desc.id, slots.iter().map(str::to_string).collect());
+        backend.upload_material(desc)
+    }
+
+    /// Subir los niveles de mip que pide el reparto del presupuesto de
+    /// texturas. Una subida fallida se reintenta en el próximo frame
+    fn stream_textures_to(
+        backend: &mut dyn RenderBackend,
+        textures: &RwLock<HashMap<String, Texture>>,
+        streamer: &mut TextureStreamer,
+    ) {
+        let textures = textures.read().unwrap();
+        for request in streamer.plan() {
+            let Some(image) = textures.get(&request.texture_id).and_then(|texture| texture_image(texture, request.srgb)) else {
+                continue;
+            };
+            match backend.upload_texture(&mip_chain_image(&image, request.mip)) {
+                Ok(()) => streamer.mark_resident(&request.texture_id, request.mip),
+                Err(err) => warn!("Streaming de {} fallido: {}", request.texture_id, err),
+            }
+        }
+    }
+
     /// Crear shader
     pub async fn create_shader(&mut self, shader: Shader) -> Result<()> {
         let mut shaders = self.shaders.write().unwrap();
@@ -1103,6 +2855,8 @@ impl RendererSystem {
 
     /// Cargar textura
     pub async fn load_texture(&mut self, texture: Texture) -> Result<()> {
+        // Si ya estaba en la GPU, el streaming sube los datos nuevos
+        self.texture_streamer.reload(&texture.id, texture.config.width, texture.config.height);
         let mut textures = self.textures.write().unwrap();
         textures.insert(texture.id.clone(), texture);
         self.stats.loaded_textures = textures.len() as u32;
@@ -1115,13 +2869,45 @@ impl RendererSystem {
         textures.get(id).cloned()
     }
 
+    /// Nivel de mip residente de una textura en el backend (0 = completa;
+    /// None sin subir)
+    pub fn resident_texture_mip(&self, id: &str) -> Option<u32> {
+        self.texture_streamer.resident_mip(id)
+    }
+
     /// Crear mesh
     pub async fn create_mesh(&mut self, mesh: Mesh) -> Result<()> {
+        // Reemplazar los buffers si el mesh ya estaba subido
+        if let Some(backend) = &mut self.backend {
+            if backend.has_mesh(&mesh.id) {
+                backend.upload_mesh(&mesh)?;
+            }
+        }
         let mut meshes = self.meshes.write().unwrap();
         meshes.insert(mesh.id.clone(), mesh);
         Ok(())
     }
 
+    /// Liberar un mesh del renderer y del backend
+    pub fn remove_mesh(&mut self, mesh_id: &str) {
+        if let Some(backend) = &mut self.backend {
+            backend.remove_mesh(mesh_id);
+        }
+        self.meshes.write().unwrap().remove(mesh_id);
+    }
+
+    /// Liberar una textura del renderer y del backend
+    pub fn remove_texture(&mut self, texture_id: &str) {
+        if let Some(backend) = &mut self.backend {
+            backend.remove_texture(texture_id);
+            backend.remove_decal_texture(texture_id);
+        }
+        self.texture_streamer.remove(texture_id);
+        let mut textures = self.textures.write().unwrap();
+        textures.remove(texture_id);
+        self.stats.loaded_textures = textures.len() as u32;
+    }
+
     /// Obtener mesh
     pub fn get_mesh(&self, id: &str) -> Option<Mesh> {
         let meshes = self.meshes.read().unwrap();
@@ -1149,12 +2935,190 @@ impl RendererSystem {
         self.shaders.write().unwrap().clear();
         self.textures.write().unwrap().clear();
         self.meshes.write().unwrap().clear();
+        self.draw_list.calls.clear();
+        self.draw_list.instanced.clear();
+        self.draw_list.skins.clear();
+        self.draw_list.morphs.clear();
+        self.draw_list.environment = None;
+        self.draw_list.decals.clear();
+        self.draw_list.lights.clear();
+        self.skin_palettes.clear();
+        self.morph_weights.clear();
+        self.particle_batches.clear();
+        self.fonts.clear();
+        self.pending_labels.clear();
+        self.screen_text = TextFrame::default();
+        self.draw_list.text = None;
+        self.debug_draw.set_enabled(false);
+        self.texture_streamer.clear();
+        self.asset_loader.clear();
+        self.shader_watcher = None;
+        self.pending_shaders.clear();
+        self.shader_errors.clear();
+        self.backend = None;
         
         info!("Sistema de renderizado limpiado");
         Ok(())
     }
 }
 
+/// Semiextensión de la región cubierta por el octree de culling
+const SPATIAL_INDEX_EXTENT: f32 = 4096.0;
+
+/// Intensidad de la luz ambiente junto a la luz direccional
+const DEFAULT_AMBIENT_LIGHT: f32 = 0.15;
+
+/// Fracción del cono de un foco en la que la luz se desvanece hacia el borde
+const SPOT_LIGHT_PENUMBRA: f32 = 0.2;
+
+/// Intentos de recoger una lectura de la GPU antes de darla por perdida
+/// (p. ej. si el frame no llegó al backend)
+const MAX_READBACK_POLLS: u32 = 1000;
+
+/// Prefijo de los IDs de material y textura de los atlas
+const ATLAS_MATERIAL_PREFIX: &str = "atlas-";
+
+/// Ceder el control al bucle de eventos
+async fn yield_to_event_loop() {
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::task::yield_now().await;
+    // Un temporizador deja correr las tareas del navegador, no solo las microtareas
+    #[cfg(target_arch = "wasm32")]
+    {
+        let promise = js_sys::Promise::new(&mut |resolve, _| {
+            if let Some(window) = web_sys::window() {
+                let _ = window.set_timeout_with_callback(&resolve);
+            }
+        });
+        let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+    }
+}
+
+/// Matrices de vista y proyección de una cámara del ECS
+fn camera_matrices(
+    camera: &CameraComponent,
+    transform: Option<&TransformComponent>,
+    width: u32,
+    height: u32,
+) -> (Mat4, Mat4) {
+    let view = match transform {
+        Some(transform) => Mat4::from_rotation_translation(transform.rotation, transform.position).inverse(),
+        None => camera.view,
+    };
+
+    let aspect = if camera.aspect_ratio > 0.0 {
+        camera.aspect_ratio
+    } else {
+        width.max(1) as f32 / height.max(1) as f32
+    };
+    let projection = match camera.camera_type {
+        CameraType::Perspective => {
+            Mat4::perspective_rh(camera.fov.to_radians(), aspect, camera.near_plane, camera.far_plane)
+        }
+        CameraType::Orthographic | CameraType::Explicit => camera.projection,
+    };
+
+    (view, projection)
+}
+
+/// Convertir un componente de malla del ECS en un mesh del renderer
+fn mesh_from_component(component: &MeshComponent) -> Mesh {
+    let vertices: Vec<Vertex> = component.vertices.iter()
+        .enumerate()
+        .map(|(i, position)| Vertex {
+            position: *position,
+            normal: component.normals.get(i).copied().unwrap_or(Vec3::Y),
+            tangent: Vec3::ZERO,
+            uv: component.uvs.get(i).copied().unwrap_or(Vec3::ZERO),
+            color: Vec4::ONE,
+        })
+        .collect();
+
+    let (min, max) = component.vertices.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), p| (min.min(*p), max.max(*p)),
+    );
+    let (min, max) = if vertices.is_empty() { (Vec3::ZERO, Vec3::ZERO) } else { (min, max) };
+    let center = (min + max) * 0.5;
+
+    Mesh {
+        id: component.mesh_id.clone(),
+        name: component.mesh_id.clone(),
+        geometry: Geometry {
+            vertices,
+            indices: component.indices.clone(),
+            bounding_box: BoundingBox { min, max },
+            bounding_sphere: BoundingSphere { center, radius: (max - center).length() },
+            joints: component.joint_indices.clone(),
+            weights: component.joint_weights.clone(),
+            morph_targets: Vec::new(),
+        },
+        material: component.material_id.clone(),
+        lod: Vec::new(),
+    }
+}
+
+/// Material del renderer en el formato del backend
+fn material_desc(material: &Material) -> MaterialDesc {
+    let properties = &material.properties;
+    MaterialDesc {
+        id: material.id.clone(),
+        kind: match material.material_type {
+            MaterialType::Unlit => MaterialKind::Unlit,
+            MaterialType::Terrain => MaterialKind::Terrain,
+            _ => MaterialKind::Pbr,
+        },
+        params: MaterialParams {
+            base_color: properties.base_color,
+            emissive: properties.emissive,
+            metallic: properties.metallic,
+            roughness: properties.roughness,
+            normal_scale: properties.normal_scale,
+            occlusion_strength: properties.occlusion_strength,
+            alpha_cutoff: properties.alpha_cutoff,
+            double_sided: properties.double_sided,
+        },
+        textures: MaterialTextureSlots::from_map(&material.textures),
+    }
+}
+
+/// Píxeles RGBA8 de una textura (None si no hay datos o el formato no es de 8 bits)
+fn texture_image(texture: &Texture, srgb: bool) -> Option<TextureImage> {
+    let data = texture.data.as_ref()?;
+    let channels = match texture.config.format {
+        TextureFormat::RGBA8 => 4,
+        TextureFormat::RGB8 => 3,
+        TextureFormat::RG8 => 2,
+        TextureFormat::R8 => 1,
+        _ => return None,
+    };
+    let (width, height) = (texture.config.width, texture.config.height);
+    if data.len() != (width * height) as usize * channels {
+        return None;
+    }
+
+    let pixels = if channels == 4 {
+        data.clone()
+    } else {
+        data.chunks_exact(channels)
+            .flat_map(|p| match p {
+                [r, g, b] => [*r, *g, *b, 255],
+                [r, g] => [*r, *g, 0, 255],
+                [r] => [*r, *r, *r, 255],
+                _ => unreachable!(),
+            })
+            .collect()
+    };
+    Some(TextureImage {
+        id: texture.id.clone(),
+        width,
+        height,
+        pixels,
+        srgb,
+        mips: Vec::new(),
+    })
+}
+
 // Shaders por defecto (simulados)
 #[cfg(not(target_arch = "wasm32"))]
 mod shaders {
@@ -0,0 +1,494 @@
+//! # Morph targets en GPU
+//!
+//! Suma los desplazamientos de los morph targets activos sobre los vértices
+//! del mesh antes del skinning. Los targets de cada mesh se empaquetan una
+//! vez en un storage buffer (posición y normal por vértice, target tras
+//! target) y cada entidad deformada tiene su propio buffer de vértices
+//! (mismo layout que `GpuVertex`), que el skinning lee como fuente y los
+//! pases de dibujo como vértices.
+//!
+//! Por entidad se aplican como mucho `MAX_ACTIVE_MORPH_TARGETS` targets, con
+//! los pesos ya elegidos y normalizados por el sistema de animaciones. En
+//! wasm la suma se hace en CPU con las mismas reglas y el resultado se copia
+//! al buffer de la entidad, sin depender de los compute shaders del
+//! navegador.
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec3, Vec4};
+use std::collections::{HashMap, HashSet};
+
+use super::Geometry;
+use crate::animations::morphing::{MorphWeights, MAX_ACTIVE_MORPH_TARGETS};
+
+/// Floats por vértice en el buffer de vértices (`GpuVertex`)
+const VERTEX_FLOATS: usize = 12;
+
+/// Invocaciones por workgroup del compute shader
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Los targets se aplican en CPU en lugar de en un compute pass
+pub const CPU_MORPHING: bool = cfg!(target_arch = "wasm32");
+
+/// Desplazamiento de un vértice en un target, en formato GPU
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct GpuMorphDelta {
+    /// Desplazamiento de la posición (w sin uso)
+    pub position: [f32; 4],
+    /// Desplazamiento de la normal (w sin uso)
+    pub normal: [f32; 4],
+}
+
+/// IDs de los targets de una geometría y sus desplazamientos empaquetados,
+/// o None si no tiene morph targets. Los targets más cortos que la
+/// geometría no desplazan los vértices que les faltan
+pub fn morph_deltas(geometry: &Geometry) -> Option<(Vec<String>, Vec<GpuMorphDelta>)> {
+    if geometry.morph_targets.is_empty() {
+        return None;
+    }
+    let vertex_count = geometry.vertices.len();
+    let mut deltas = Vec::with_capacity(geometry.morph_targets.len() * vertex_count);
+    for target in &geometry.morph_targets {
+        deltas.extend((0..vertex_count).map(|i| GpuMorphDelta {
+            position: target.position_deltas.get(i).map_or([0.0; 4], |delta| delta.extend(0.0).to_array()),
+            normal: target.normal_deltas.get(i).map_or([0.0; 4], |delta| delta.extend(0.0).to_array()),
+        }));
+    }
+    let ids = geometry.morph_targets.iter().map(|target| target.id.clone()).collect();
+    Some((ids, deltas))
+}
+
+/// Índice en `target_ids` y peso de cada target activo que tiene el mesh
+pub fn resolve_weights(target_ids: &[String], weights: &MorphWeights) -> Vec<(u32, f32)> {
+    weights.weights.iter()
+        .filter_map(|weight| {
+            let index = target_ids.iter().position(|id| *id == weight.target_id)?;
+            Some((index as u32, weight.weight))
+        })
+        .take(MAX_ACTIVE_MORPH_TARGETS)
+        .collect()
+}
+
+/// Sumar los targets activos a vértices en el layout de `GpuVertex`, con las
+/// mismas reglas que el compute shader
+pub fn morph_vertices(source: &[f32], deltas: &[GpuMorphDelta], active: &[(u32, f32)], output: &mut Vec<f32>) {
+    output.clear();
+    output.extend_from_slice(source);
+    let vertex_count = source.len() / VERTEX_FLOATS;
+    for (index, vertex) in output.chunks_exact_mut(VERTEX_FLOATS).enumerate() {
+        let (mut position, normal) = (Vec3::from_slice(&vertex[0..3]), Vec3::from_slice(&vertex[3..6]));
+        let mut morphed_normal = normal;
+        for &(target, weight) in active {
+            let Some(delta) = deltas.get(target as usize * vertex_count + index) else {
+                continue;
+            };
+            position += Vec4::from(delta.position).truncate() * weight;
+            morphed_normal += Vec4::from(delta.normal).truncate() * weight;
+        }
+        position.write_to_slice(&mut vertex[0..3]);
+        morphed_normal.try_normalize().unwrap_or(normal).write_to_slice(&mut vertex[3..6]);
+    }
+}
+
+/// Uniforms de una entidad con morph targets
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct MorphUniforms {
+    vertex_count: u32,
+    active_count: u32,
+    _padding: [u32; 2],
+    /// Índice de cada target activo (cuatro por vector)
+    targets: [[u32; 4]; MAX_ACTIVE_MORPH_TARGETS / 4],
+    /// Peso de cada target activo
+    weights: [[f32; 4]; MAX_ACTIVE_MORPH_TARGETS / 4],
+}
+
+/// Compute shader de morph targets: una invocación por vértice
+const MORPHING_SHADER: &str = r#"
+struct MorphUniforms {
+    vertex_count: u32,
+    active_count: u32,
+    _padding: vec2<u32>,
+    targets: array<vec4<u32>, 2>,
+    weights: array<vec4<f32>, 2>,
+};
+
+struct Delta {
+    position: vec4<f32>,
+    normal: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> morph: MorphUniforms;
+@group(0) @binding(1) var<storage, read> source: array<f32>;
+@group(0) @binding(2) var<storage, read> deltas: array<Delta>;
+@group(0) @binding(3) var<storage, read_write> morphed: array<f32>;
+
+const VERTEX_FLOATS: u32 = 12u;
+
+@compute @workgroup_size(64)
+fn cs_morph(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= morph.vertex_count) {
+        return;
+    }
+    let base = index * VERTEX_FLOATS;
+    var position = vec3<f32>(source[base], source[base + 1u], source[base + 2u]);
+    let normal = vec3<f32>(source[base + 3u], source[base + 4u], source[base + 5u]);
+
+    var morphed_normal = normal;
+    for (var i = 0u; i < morph.active_count; i++) {
+        let target_index = morph.targets[i / 4u][i % 4u];
+        let weight = morph.weights[i / 4u][i % 4u];
+        let delta = deltas[target_index * morph.vertex_count + index];
+        position += delta.position.xyz * weight;
+        morphed_normal += delta.normal.xyz * weight;
+    }
+    morphed_normal = select(normal, normalize(morphed_normal), dot(morphed_normal, morphed_normal) > 0.0);
+
+    morphed[base] = position.x;
+    morphed[base + 1u] = position.y;
+    morphed[base + 2u] = position.z;
+    morphed[base + 3u] = morphed_normal.x;
+    morphed[base + 4u] = morphed_normal.y;
+    morphed[base + 5u] = morphed_normal.z;
+    for (var i = 6u; i < VERTEX_FLOATS; i++) {
+        morphed[base + i] = source[base + i];
+    }
+}
+"#;
+
+/// Morph targets de un mesh en la GPU
+pub struct MorphTargets {
+    /// ID de cada target, en el orden de los desplazamientos
+    pub target_ids: Vec<String>,
+    /// Desplazamientos de todos los targets (uso `STORAGE`)
+    pub deltas: wgpu::Buffer,
+    /// Vértices en bind pose y desplazamientos para el camino en CPU
+    cpu: Option<(Vec<f32>, Vec<GpuMorphDelta>)>,
+}
+
+impl MorphTargets {
+    /// Subir los targets de una geometría (None si no tiene). `vertices` son
+    /// los vértices del mesh en el layout de `GpuVertex`
+    pub fn new(device: &wgpu::Device, mesh_id: &str, geometry: &Geometry, vertices: &[f32]) -> Option<Self> {
+        use wgpu::util::DeviceExt;
+
+        let (target_ids, deltas) = morph_deltas(geometry)?;
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{}-morph-targets", mesh_id)),
+            contents: bytemuck::cast_slice(&deltas),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        Some(Self {
+            target_ids,
+            deltas: buffer,
+            cpu: CPU_MORPHING.then(|| (vertices.to_vec(), deltas)),
+        })
+    }
+}
+
+/// Buffers de la malla deformada de una entidad
+struct MorphedMesh {
+    mesh_id: String,
+    uniforms: wgpu::Buffer,
+    output: wgpu::Buffer,
+    /// Sin él la entidad se deforma en CPU
+    bind_group: Option<wgpu::BindGroup>,
+    vertex_count: u32,
+}
+
+/// Fuente de una malla con morph targets en la GPU
+pub struct MorphSource<'a> {
+    /// ID del mesh
+    pub mesh_id: &'a str,
+    /// Vértices en bind pose (con uso `STORAGE`)
+    pub vertices: &'a wgpu::Buffer,
+    /// Targets del mesh
+    pub targets: &'a MorphTargets,
+    /// Número de vértices
+    pub vertex_count: u32,
+}
+
+/// Pase de morph targets: un dispatch por entidad deformada
+pub struct MorphPass {
+    /// Layout y pipeline del compute pass (None con el camino en CPU)
+    compute: Option<(wgpu::BindGroupLayout, wgpu::ComputePipeline)>,
+    meshes: HashMap<u64, MorphedMesh>,
+    /// Entidades preparadas en el frame en curso
+    active: HashSet<u64>,
+    /// Vértices deformados en CPU, reutilizados entre entidades
+    scratch: Vec<f32>,
+}
+
+impl MorphPass {
+    /// Crear el pase
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            compute: (!CPU_MORPHING).then(|| Self::create_pipeline(device)),
+            meshes: HashMap::new(),
+            active: HashSet::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Layout y pipeline del compute shader
+    fn create_pipeline(device: &wgpu::Device) -> (wgpu::BindGroupLayout, wgpu::ComputePipeline) {
+        let storage = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("morphing-layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<MorphUniforms>() as u64),
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, false),
+            ],
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("morphing-shader"),
+            source: wgpu::ShaderSource::Wgsl(MORPHING_SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("morphing-pipeline-layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("morphing"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: "cs_morph",
+            compilation_options: Default::default(),
+        });
+        (layout, pipeline)
+    }
+
+    /// Empezar un frame: ninguna entidad está preparada
+    pub fn begin(&mut self) {
+        self.active.clear();
+    }
+
+    /// Escribir los pesos de una entidad, creando sus buffers si cambia el
+    /// mesh. Sin targets activos del mesh la entidad no se deforma
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        entity: u64,
+        source: &MorphSource<'_>,
+        weights: &MorphWeights,
+    ) {
+        let active = resolve_weights(&source.targets.target_ids, weights);
+        if active.is_empty() {
+            return;
+        }
+        let reusable = self.meshes.get(&entity).is_some_and(|mesh| {
+            mesh.mesh_id == source.mesh_id && mesh.vertex_count == source.vertex_count
+        });
+        if !reusable {
+            let morphed = self.create_mesh(device, source);
+            self.meshes.insert(entity, morphed);
+        }
+        let Some(morphed) = self.meshes.get(&entity) else {
+            return;
+        };
+        self.active.insert(entity);
+
+        if let Some((vertices, deltas)) = &source.targets.cpu {
+            morph_vertices(vertices, deltas, &active, &mut self.scratch);
+            queue.write_buffer(&morphed.output, 0, bytemuck::cast_slice(&self.scratch));
+            return;
+        }
+
+        let mut uniforms = MorphUniforms {
+            vertex_count: source.vertex_count,
+            active_count: active.len() as u32,
+            _padding: [0; 2],
+            targets: [[0; 4]; MAX_ACTIVE_MORPH_TARGETS / 4],
+            weights: [[0.0; 4]; MAX_ACTIVE_MORPH_TARGETS / 4],
+        };
+        for (i, (target, weight)) in active.into_iter().enumerate() {
+            uniforms.targets[i / 4][i % 4] = target;
+            uniforms.weights[i / 4][i % 4] = weight;
+        }
+        queue.write_buffer(&morphed.uniforms, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Buffers de la malla deformada de una entidad
+    fn create_mesh(&self, device: &wgpu::Device, source: &MorphSource<'_>) -> MorphedMesh {
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("morphing-uniforms"),
+            size: std::mem::size_of::<MorphUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // El skinning lee la salida como storage; en CPU se escribe con copias
+        let output = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{}-morphed", source.mesh_id)),
+            size: (source.vertex_count.max(1) as usize * VERTEX_FLOATS * std::mem::size_of::<f32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.compute.as_ref().map(|(layout, _)| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("morphing-bind-group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: uniforms.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: source.vertices.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 2, resource: source.targets.deltas.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 3, resource: output.as_entire_binding() },
+                ],
+            })
+        });
+
+        MorphedMesh {
+            mesh_id: source.mesh_id.to_string(),
+            uniforms,
+            output,
+            bind_group,
+            vertex_count: source.vertex_count,
+        }
+    }
+
+    /// Grabar el compute pass de las entidades preparadas y liberar las que
+    /// no se dibujan en este frame. Devuelve los vértices deformados
+    pub fn record(&mut self, encoder: &mut wgpu::CommandEncoder) -> u32 {
+        let active = &self.active;
+        self.meshes.retain(|entity, _| active.contains(entity));
+        let vertices = self.meshes.values().map(|morphed| morphed.vertex_count).sum();
+        let Some((_, pipeline)) = &self.compute else {
+            return vertices;
+        };
+        if self.meshes.is_empty() {
+            return 0;
+        }
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("morphing-pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(pipeline);
+        for morphed in self.meshes.values() {
+            if let Some(bind_group) = &morphed.bind_group {
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.dispatch_workgroups(morphed.vertex_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+            }
+        }
+        vertices
+    }
+
+    /// Buffer de vértices deformados de una entidad
+    pub fn output(&self, entity: u64) -> Option<&wgpu::Buffer> {
+        self.meshes.get(&entity).map(|morphed| &morphed.output)
+    }
+
+    /// Descartar las mallas deformadas de un mesh (al reemplazarlo o liberarlo)
+    pub fn invalidate_mesh(&mut self, mesh_id: &str) {
+        self.meshes.retain(|_, morphed| morphed.mesh_id != mesh_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tres vértices en el layout de `GpuVertex`: posición en X, normal +Z
+    /// y el resto marcado para ver que se copia
+    fn source() -> Vec<f32> {
+        (0..3)
+            .flat_map(|i| {
+                let mut vertex = [9.0; VERTEX_FLOATS];
+                vertex[0..3].copy_from_slice(&[i as f32, 0.0, 0.0]);
+                vertex[3..6].copy_from_slice(&[0.0, 0.0, 1.0]);
+                vertex
+            })
+            .collect()
+    }
+
+    /// Un target por desplazamiento, el mismo para todos los vértices
+    fn deltas(targets: &[Vec3]) -> Vec<GpuMorphDelta> {
+        targets.iter()
+            .flat_map(|delta| (0..3).map(move |_| GpuMorphDelta {
+                position: delta.extend(0.0).to_array(),
+                normal: [0.0; 4],
+            }))
+            .collect()
+    }
+
+    fn positions(vertices: &[f32]) -> Vec<Vec3> {
+        vertices.chunks_exact(VERTEX_FLOATS).map(|vertex| Vec3::from_slice(&vertex[0..3])).collect()
+    }
+
+    #[test]
+    fn two_targets_at_half_weight_average_their_deltas() {
+        let (a, b) = (Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 4.0, -2.0));
+        let ids = vec!["sonrisa".to_string(), "cejas".to_string()];
+        let weights = MorphWeights::from_samples([("sonrisa".to_string(), 0.5), ("cejas".to_string(), 0.5)]);
+        let active = resolve_weights(&ids, &weights);
+        assert_eq!(active.len(), 2);
+
+        let source = source();
+        let mut output = Vec::new();
+        morph_vertices(&source, &deltas(&[a, b]), &active, &mut output);
+        for (morphed, original) in positions(&output).into_iter().zip(positions(&source)) {
+            assert!(morphed.abs_diff_eq(original + (a + b) / 2.0, 1e-6), "{}", morphed);
+        }
+        // Lo que no es posición ni normal se copia sin tocar
+        assert!(output.chunks_exact(VERTEX_FLOATS).all(|vertex| vertex[6..].iter().all(|&value| value == 9.0)));
+    }
+
+    #[test]
+    fn full_weights_are_normalized_before_blending() {
+        // Dos targets completos pesan 0.5 cada uno: el mismo resultado que la media
+        let (a, b) = (Vec3::X, Vec3::Y);
+        let ids = vec!["a".to_string(), "b".to_string()];
+        let weights = MorphWeights::from_samples([("a".to_string(), 1.0), ("b".to_string(), 1.0)]);
+        let source = source();
+        let mut output = Vec::new();
+        morph_vertices(&source, &deltas(&[a, b]), &resolve_weights(&ids, &weights), &mut output);
+        assert!(positions(&output)[0].abs_diff_eq((a + b) / 2.0, 1e-6));
+
+        // Un target que el mesh no tiene no desplaza nada
+        let weights = MorphWeights::from_samples([("otro".to_string(), 1.0)]);
+        assert!(resolve_weights(&ids, &weights).is_empty());
+    }
+
+    #[test]
+    fn at_most_eight_targets_are_active() {
+        let ids: Vec<String> = (0..12).map(|i| format!("target_{}", i)).collect();
+        let weights = MorphWeights::from_samples(ids.iter().enumerate().map(|(i, id)| (id.clone(), 0.01 * (i + 1) as f32)));
+        let active = resolve_weights(&ids, &weights);
+        assert_eq!(active.len(), MAX_ACTIVE_MORPH_TARGETS);
+        // Se quedan los de mayor peso
+        assert!(active.iter().all(|&(index, _)| index >= 4));
+
+        let normals: Vec<GpuMorphDelta> = (0..12)
+            .flat_map(|_| (0..3).map(|_| GpuMorphDelta { position: [0.0; 4], normal: [1.0, 0.0, 0.0, 0.0] }))
+            .collect();
+        let mut output = Vec::new();
+        morph_vertices(&source(), &normals, &active, &mut output);
+        // La normal deformada sale normalizada
+        let normal = Vec3::from_slice(&output[3..6]);
+        assert!((normal.length() - 1.0).abs() < 1e-5);
+        assert!(normal.x > 0.0 && normal.z > 0.0);
+    }
+}
@@ -0,0 +1,892 @@
+//! # Occlusion culling
+//!
+//! Culling de oclusión en dos fases con una pirámide de profundidad (Hi-Z)
+//! de máximos. En la fase temprana un compute shader proyecta la caja de
+//! cada draw call con la cámara del frame anterior y la compara con la
+//! pirámide de ese frame; las que pasan se dibujan. Con la profundidad
+//! resultante se reconstruye la pirámide y la fase tardía vuelve a probar
+//! las rechazadas con la cámara actual: las que ahora son visibles (porque
+//! la cámara ha rodeado al oclusor o porque el oclusor se ha movido) se
+//! dibujan en el mismo frame, sin popping. La pirámide reconstruida es la
+//! que usa la fase temprana del frame siguiente.
+//!
+//! Los compute shaders escriben el número de instancias de draw calls
+//! indirectas, así que la CPU no espera a la GPU: los contadores de objetos
+//! probados y ocluidos se leen de vuelta sin bloquear y llegan a
+//! `RendererStats` con un frame de retraso. Solo se prueban las draw calls
+//! simples con caja conocida; los grupos instanciados y las mallas con skin
+//! se dibujan siempre. Hace falta un depth buffer muestreable (sin MSAA).
+//!
+//! `DepthPyramid` y `DepthBuffer` implementan la misma prueba en la CPU
+//! para el backend simulado.
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec2, Vec3};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use super::culling::Aabb;
+
+/// Invocaciones por workgroup del compute shader de culling
+const CULL_WORKGROUP_SIZE: u32 = 64;
+
+/// Lado de los workgroups de la construcción de la pirámide
+const PYRAMID_WORKGROUP_SIZE: u32 = 8;
+
+/// Bytes de los argumentos de una draw call indexada indirecta
+const DRAW_ARGS_SIZE: u64 = 5 * std::mem::size_of::<u32>() as u64;
+
+/// Contadores: probados, ocluidos, visibles en la fase tardía y relleno
+const COUNTER_COUNT: usize = 4;
+
+/// Bytes de los contadores
+const COUNTERS_SIZE: u64 = (COUNTER_COUNT * std::mem::size_of::<u32>()) as u64;
+
+/// Estados del mapeo de la copia de los contadores
+const READBACK_WAITING: u8 = 0;
+const READBACK_MAPPED: u8 = 1;
+const READBACK_FAILED: u8 = 2;
+
+/// Occlusion culling del frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OcclusionFrame {
+    /// Vista-proyección con la que se dibujó la pirámide del frame anterior
+    pub previous_view_projection: Mat4,
+    /// La pirámide anterior no sirve (primer frame o corte de cámara): la
+    /// fase temprana lo dibuja todo
+    pub reset: bool,
+}
+
+/// Fase de un pase de culling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OcclusionPhase {
+    /// Contra la pirámide del frame anterior
+    Early,
+    /// Las rechazadas contra la pirámide del frame actual
+    Late,
+}
+
+/// Contadores de un frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OcclusionStats {
+    /// Draw calls probadas
+    pub tested: u32,
+    /// Draw calls ocluidas en las dos fases
+    pub occluded: u32,
+    /// Draw calls rechazadas en la fase temprana y visibles en la tardía
+    pub late_visible: u32,
+}
+
+/// Rectángulo en pantalla y profundidad más cercana de una caja
+struct ScreenBounds {
+    /// Píxel mínimo y máximo en el nivel 0
+    min: (u32, u32),
+    max: (u32, u32),
+    nearest: f32,
+}
+
+/// Proyectar una caja sobre una pirámide de `size` píxeles. None si cruza
+/// el plano near (no se puede descartar)
+fn project_bounds(bounds: &Aabb, view_projection: &Mat4, size: (u32, u32)) -> Option<ScreenBounds> {
+    let mut uv_min = Vec2::ONE;
+    let mut uv_max = Vec2::ZERO;
+    let mut nearest = 1.0f32;
+    for corner in 0..8 {
+        let point = Vec3::new(
+            if corner & 1 == 0 { bounds.min.x } else { bounds.max.x },
+            if corner & 2 == 0 { bounds.min.y } else { bounds.max.y },
+            if corner & 4 == 0 { bounds.min.z } else { bounds.max.z },
+        );
+        let clip = *view_projection * point.extend(1.0);
+        if clip.w <= 1e-5 {
+            return None;
+        }
+        let ndc = clip.truncate() / clip.w;
+        let uv = Vec2::new(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        uv_min = uv_min.min(uv);
+        uv_max = uv_max.max(uv);
+        nearest = nearest.min(ndc.z);
+    }
+
+    let extent = Vec2::new(size.0 as f32, size.1 as f32);
+    let last = (size.0.saturating_sub(1), size.1.saturating_sub(1));
+    let texel = |uv: Vec2| {
+        let p = (uv.clamp(Vec2::ZERO, Vec2::ONE) * extent).floor();
+        ((p.x as u32).min(last.0), (p.y as u32).min(last.1))
+    };
+    Some(ScreenBounds { min: texel(uv_min), max: texel(uv_max), nearest })
+}
+
+/// Nivel de la pirámide donde un rectángulo de `extent` píxeles cubre como
+/// mucho dos texels por eje
+fn pyramid_level(min: (u32, u32), max: (u32, u32), level_count: usize) -> usize {
+    let extent = (max.0 - min.0).max(max.1 - min.1) + 1;
+    (extent.next_power_of_two().trailing_zeros() as usize).min(level_count.saturating_sub(1))
+}
+
+/// Tamaños de los niveles de una pirámide de `width` x `height`, como los
+/// mips de wgpu: cada nivel la mitad redondeada hacia abajo
+pub fn pyramid_sizes(width: u32, height: u32) -> Vec<(u32, u32)> {
+    let mut sizes = vec![(width.max(1), height.max(1))];
+    while let Some(&(w, h)) = sizes.last() {
+        if w == 1 && h == 1 {
+            break;
+        }
+        sizes.push(((w / 2).max(1), (h / 2).max(1)));
+    }
+    sizes
+}
+
+/// Depth buffer de la CPU (0 cerca, 1 lejos)
+#[derive(Debug, Clone)]
+pub struct DepthBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub depth: Vec<f32>,
+}
+
+impl DepthBuffer {
+    /// Depth buffer limpio al plano far
+    pub fn new(width: u32, height: u32) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        Self { width, height, depth: vec![1.0; (width * height) as usize] }
+    }
+
+    /// Rasterizar triángulos con test de profundidad. Los que cruzan el
+    /// plano near se omiten: un oclusor de menos nunca oculta nada visible
+    pub fn rasterize(&mut self, positions: &[Vec3], indices: &[u32], model_view_projection: &Mat4) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let screen: Vec<Option<Vec3>> = positions.iter()
+            .map(|position| {
+                let clip = *model_view_projection * position.extend(1.0);
+                (clip.w > 1e-5).then(|| {
+                    let ndc = clip.truncate() / clip.w;
+                    Vec3::new((ndc.x * 0.5 + 0.5) * width, (0.5 - ndc.y * 0.5) * height, ndc.z)
+                })
+            })
+            .collect();
+
+        for triangle in indices.chunks_exact(3) {
+            let vertex = |i: u32| screen.get(i as usize).copied().flatten();
+            let (Some(a), Some(b), Some(c)) = (vertex(triangle[0]), vertex(triangle[1]), vertex(triangle[2])) else {
+                continue;
+            };
+            let area = (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+            if area.abs() < 1e-8 {
+                continue;
+            }
+
+            let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as u32;
+            let max_x = (a.x.max(b.x).max(c.x).ceil().min(width) as u32).min(self.width);
+            let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as u32;
+            let max_y = (a.y.max(b.y).max(c.y).ceil().min(height) as u32).min(self.height);
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    // Baricéntricas en el centro del píxel; ambas orientaciones
+                    let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                    let w0 = ((b.x - px) * (c.y - py) - (b.y - py) * (c.x - px)) / area;
+                    let w1 = ((c.x - px) * (a.y - py) - (c.y - py) * (a.x - px)) / area;
+                    let w2 = 1.0 - w0 - w1;
+                    if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                        continue;
+                    }
+                    let z = w0 * a.z + w1 * b.z + w2 * c.z;
+                    let texel = &mut self.depth[(y * self.width + x) as usize];
+                    if (0.0..=1.0).contains(&z) && z < *texel {
+                        *texel = z;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Pirámide de profundidad de máximos en la CPU
+#[derive(Debug, Clone)]
+pub struct DepthPyramid {
+    /// Tamaño y texels de cada nivel
+    levels: Vec<((u32, u32), Vec<f32>)>,
+}
+
+impl DepthPyramid {
+    /// Construir la pirámide de un depth buffer. El último texel de cada eje
+    /// cubre también el texel sobrante de un nivel anterior impar
+    pub fn from_depth(buffer: &DepthBuffer) -> Self {
+        let sizes = pyramid_sizes(buffer.width, buffer.height);
+        let mut levels = vec![(sizes[0], buffer.depth.clone())];
+        for &(width, height) in &sizes[1..] {
+            let ((source_width, source_height), source) = levels.last().unwrap();
+            let (source_width, source_height) = (*source_width, *source_height);
+            let mut texels = vec![0.0f32; (width * height) as usize];
+            for y in 0..height {
+                let y_end = if y == height - 1 { source_height } else { (y * 2 + 2).min(source_height) };
+                for x in 0..width {
+                    let x_end = if x == width - 1 { source_width } else { (x * 2 + 2).min(source_width) };
+                    let mut farthest = 0.0f32;
+                    for sy in (y * 2).min(source_height - 1)..y_end {
+                        for sx in (x * 2).min(source_width - 1)..x_end {
+                            farthest = farthest.max(source[(sy * source_width + sx) as usize]);
+                        }
+                    }
+                    texels[(y * width + x) as usize] = farthest;
+                }
+            }
+            levels.push(((width, height), texels));
+        }
+        Self { levels }
+    }
+
+    /// Tamaño del nivel 0
+    pub fn size(&self) -> (u32, u32) {
+        self.levels[0].0
+    }
+
+    /// Número de niveles
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Verificar si una caja puede ser visible vista con `view_projection`
+    pub fn test(&self, bounds: &Aabb, view_projection: &Mat4) -> bool {
+        let Some(screen) = project_bounds(bounds, view_projection, self.size()) else {
+            return true;
+        };
+        let level = pyramid_level(screen.min, screen.max, self.levels.len());
+        let ((width, height), texels) = &self.levels[level];
+        let texel = |p: (u32, u32)| ((p.0 >> level).min(width - 1), (p.1 >> level).min(height - 1));
+        let (min, max) = (texel(screen.min), texel(screen.max));
+
+        let mut farthest = 0.0f32;
+        for y in min.1..=max.1 {
+            for x in min.0..=max.0 {
+                farthest = farthest.max(texels[(y * width + x) as usize]);
+            }
+        }
+        screen.nearest <= farthest
+    }
+}
+
+/// Shaders de la pirámide y del culling
+const OCCLUSION_SHADER: &str = r#"
+// Construcción de la pirámide: el nivel 0 copia la profundidad y cada nivel
+// siguiente guarda el máximo de los texels que cubre del anterior
+@group(0) @binding(0) var source_depth: texture_depth_2d;
+@group(0) @binding(1) var target_level: texture_storage_2d<r32float, write>;
+@group(0) @binding(2) var source_level: texture_2d<f32>;
+
+@compute @workgroup_size(8, 8)
+fn cs_from_depth(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(target_level);
+    if (any(id.xy >= size)) {
+        return;
+    }
+    let depth = textureLoad(source_depth, vec2<i32>(id.xy), 0);
+    textureStore(target_level, vec2<i32>(id.xy), vec4<f32>(depth, 0.0, 0.0, 0.0));
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(target_level);
+    if (any(id.xy >= size)) {
+        return;
+    }
+    let source_size = textureDimensions(source_level);
+    // El último texel de cada eje cubre el texel sobrante de un nivel impar
+    let start = min(id.xy * 2u, source_size - 1u);
+    let end = select(min(id.xy * 2u + 2u, source_size), source_size, id.xy == size - 1u);
+    var farthest = 0.0;
+    for (var y = start.y; y < end.y; y++) {
+        for (var x = start.x; x < end.x; x++) {
+            farthest = max(farthest, textureLoad(source_level, vec2<i32>(i32(x), i32(y)), 0).r);
+        }
+    }
+    textureStore(target_level, vec2<i32>(id.xy), vec4<f32>(farthest, 0.0, 0.0, 0.0));
+}
+"#;
+
+const CULL_SHADER: &str = r#"
+struct CullParams {
+    view_projection: mat4x4<f32>,
+    // x, y = tamaño del nivel 0; z = niveles; w = 1 si la pirámide es válida
+    pyramid: vec4<f32>,
+    // x = fase (0 temprana, 1 tardía); y = draw calls probadas
+    phase: vec4<u32>,
+};
+
+struct CullItem {
+    min: vec4<f32>,
+    max: vec4<f32>,
+};
+
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+};
+
+@group(0) @binding(0) var<uniform> params: CullParams;
+@group(0) @binding(1) var<storage, read> items: array<CullItem>;
+@group(0) @binding(2) var<storage, read_write> args: array<DrawArgs>;
+@group(0) @binding(3) var<storage, read_write> visibility: array<u32>;
+@group(0) @binding(4) var<storage, read_write> counters: array<atomic<u32>, 4>;
+@group(0) @binding(5) var pyramid: texture_2d<f32>;
+
+fn occluded(item: CullItem) -> bool {
+    if (params.pyramid.w == 0.0) {
+        return false;
+    }
+
+    var uv_min = vec2<f32>(1.0);
+    var uv_max = vec2<f32>(0.0);
+    var nearest = 1.0;
+    for (var corner = 0u; corner < 8u; corner++) {
+        let point = vec3<f32>(
+            select(item.min.x, item.max.x, (corner & 1u) != 0u),
+            select(item.min.y, item.max.y, (corner & 2u) != 0u),
+            select(item.min.z, item.max.z, (corner & 4u) != 0u),
+        );
+        let clip = params.view_projection * vec4<f32>(point, 1.0);
+        // Cruza el plano near: no se puede descartar
+        if (clip.w <= 1e-5) {
+            return false;
+        }
+        let ndc = clip.xyz / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        uv_min = min(uv_min, uv);
+        uv_max = max(uv_max, uv);
+        nearest = min(nearest, ndc.z);
+    }
+
+    let size = params.pyramid.xy;
+    let last = vec2<u32>(size) - 1u;
+    let min_texel = min(vec2<u32>(floor(clamp(uv_min, vec2<f32>(0.0), vec2<f32>(1.0)) * size)), last);
+    let max_texel = min(vec2<u32>(floor(clamp(uv_max, vec2<f32>(0.0), vec2<f32>(1.0)) * size)), last);
+
+    // Nivel donde el rectángulo cubre como mucho dos texels por eje
+    let extent = max(max_texel.x - min_texel.x, max_texel.y - min_texel.y) + 1u;
+    let level = min(firstLeadingBit(max(extent - 1u, 1u)) + select(1u, 0u, extent == 1u), u32(params.pyramid.z) - 1u);
+    let level_last = textureDimensions(pyramid, level) - 1u;
+    let start = min(min_texel >> vec2<u32>(level), level_last);
+    let end = min(max_texel >> vec2<u32>(level), level_last);
+
+    var farthest = 0.0;
+    for (var y = start.y; y <= end.y; y++) {
+        for (var x = start.x; x <= end.x; x++) {
+            farthest = max(farthest, textureLoad(pyramid, vec2<i32>(i32(x), i32(y)), i32(level)).r);
+        }
+    }
+    return nearest > farthest;
+}
+
+@compute @workgroup_size(64)
+fn cs_cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    let count = params.phase.y;
+    if (index >= count) {
+        return;
+    }
+
+    if (params.phase.x == 0u) {
+        atomicAdd(&counters[0], 1u);
+        let visible = !occluded(items[index]);
+        visibility[index] = select(0u, 1u, visible);
+        args[index].instance_count = select(0u, 1u, visible);
+        return;
+    }
+
+    // Las visibles en la fase temprana ya están dibujadas
+    var late = false;
+    if (visibility[index] == 0u) {
+        late = !occluded(items[index]);
+        if (late) {
+            atomicAdd(&counters[2], 1u);
+        } else {
+            atomicAdd(&counters[1], 1u);
+        }
+    }
+    args[count + index].instance_count = select(0u, 1u, late);
+}
+"#;
+
+/// Uniforms de una fase de culling
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct CullParams {
+    view_projection: [[f32; 4]; 4],
+    pyramid: [f32; 4],
+    phase: [u32; 4],
+}
+
+/// Caja de una draw call probada
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct CullItem {
+    min: [f32; 4],
+    max: [f32; 4],
+}
+
+/// Argumentos de `draw_indexed_indirect`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+/// Draw call que se prueba: su caja en mundo y sus índices
+#[derive(Debug, Clone, Copy)]
+pub struct OcclusionItem {
+    pub bounds: Aabb,
+    pub index_count: u32,
+}
+
+/// Pirámide de la GPU y sus vistas por nivel
+struct GpuPyramid {
+    size: (u32, u32),
+    /// Todos los niveles, para el culling
+    view: wgpu::TextureView,
+    /// Un nivel por vista, para construirla
+    level_views: Vec<wgpu::TextureView>,
+}
+
+/// Buffers de las draw calls probadas
+struct CullBuffers {
+    capacity: usize,
+    items: wgpu::Buffer,
+    /// Argumentos indirectos: la fase temprana y luego la tardía
+    args: wgpu::Buffer,
+    visibility: wgpu::Buffer,
+}
+
+/// Pirámide de profundidad y compute passes del occlusion culling
+pub struct OcclusionPass {
+    from_depth_layout: wgpu::BindGroupLayout,
+    downsample_layout: wgpu::BindGroupLayout,
+    cull_layout: wgpu::BindGroupLayout,
+    from_depth_pipeline: wgpu::ComputePipeline,
+    downsample_pipeline: wgpu::ComputePipeline,
+    cull_pipeline: wgpu::ComputePipeline,
+    /// Uniforms de la fase temprana y de la tardía
+    params: [wgpu::Buffer; 2],
+    counters: wgpu::Buffer,
+    /// Copia de los contadores que se lee desde la CPU
+    readback: wgpu::Buffer,
+    /// La copia está mapeada (o pendiente de mapear)
+    readback_pending: bool,
+    /// Estado del mapeo de la copia
+    readback_state: Arc<AtomicU8>,
+    pyramid: GpuPyramid,
+    /// La pirámide tiene la profundidad del frame anterior
+    pyramid_valid: bool,
+    buffers: CullBuffers,
+    /// Draw calls probadas en el frame en curso
+    item_count: u32,
+    /// Contadores del último frame leído
+    last_stats: OcclusionStats,
+    /// Los contadores se copiaron en el frame en curso
+    recorded: bool,
+}
+
+impl OcclusionPass {
+    /// Crear el pase para un depth buffer de `width` x `height`
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let compute_entry = |binding: u32, ty: wgpu::BindingType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        };
+        let storage_target = wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format: wgpu::TextureFormat::R32Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        };
+        let pyramid_texture = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        };
+        let storage = |read_only: bool| wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+
+        let from_depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("hi-z-from-depth-layout"),
+            entries: &[
+                compute_entry(0, wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                }),
+                compute_entry(1, storage_target),
+            ],
+        });
+        let downsample_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("hi-z-downsample-layout"),
+            entries: &[compute_entry(1, storage_target), compute_entry(2, pyramid_texture)],
+        });
+        let cull_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("occlusion-cull-layout"),
+            entries: &[
+                compute_entry(0, wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<CullParams>() as u64),
+                }),
+                compute_entry(1, storage(true)),
+                compute_entry(2, storage(false)),
+                compute_entry(3, storage(false)),
+                compute_entry(4, storage(false)),
+                compute_entry(5, pyramid_texture),
+            ],
+        });
+
+        let pyramid_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("hi-z-shader"),
+            source: wgpu::ShaderSource::Wgsl(OCCLUSION_SHADER.into()),
+        });
+        let cull_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("occlusion-cull-shader"),
+            source: wgpu::ShaderSource::Wgsl(CULL_SHADER.into()),
+        });
+        let pipeline = |label: &str, layout: &wgpu::BindGroupLayout, module: &wgpu::ShaderModule, entry_point: &str| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module,
+                entry_point,
+                compilation_options: Default::default(),
+            })
+        };
+        let from_depth_pipeline = pipeline("hi-z-from-depth", &from_depth_layout, &pyramid_module, "cs_from_depth");
+        let downsample_pipeline = pipeline("hi-z-downsample", &downsample_layout, &pyramid_module, "cs_downsample");
+        let cull_pipeline = pipeline("occlusion-cull", &cull_layout, &cull_module, "cs_cull");
+
+        let params_buffer = |label: &str| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: std::mem::size_of::<CullParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let counters = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("occlusion-counters"),
+            size: COUNTERS_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("occlusion-counters-readback"),
+            size: COUNTERS_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            from_depth_layout,
+            downsample_layout,
+            cull_layout,
+            from_depth_pipeline,
+            downsample_pipeline,
+            cull_pipeline,
+            params: [params_buffer("occlusion-params-early"), params_buffer("occlusion-params-late")],
+            counters,
+            readback,
+            readback_pending: false,
+            readback_state: Arc::new(AtomicU8::new(READBACK_WAITING)),
+            pyramid: Self::create_pyramid(device, width, height),
+            pyramid_valid: false,
+            buffers: Self::create_buffers(device, 64),
+            item_count: 0,
+            last_stats: OcclusionStats::default(),
+            recorded: false,
+        }
+    }
+
+    fn create_pyramid(device: &wgpu::Device, width: u32, height: u32) -> GpuPyramid {
+        let sizes = pyramid_sizes(width, height);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("hi-z"),
+            size: wgpu::Extent3d { width: sizes[0].0, height: sizes[0].1, depth_or_array_layers: 1 },
+            mip_level_count: sizes.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let level_views = (0..sizes.len() as u32)
+            .map(|level| texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("hi-z-level"),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            }))
+            .collect();
+        GpuPyramid {
+            size: sizes[0],
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            level_views,
+        }
+    }
+
+    fn create_buffers(device: &wgpu::Device, capacity: usize) -> CullBuffers {
+        let buffer = |label: &str, size: u64, usage: wgpu::BufferUsages| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let count = capacity as u64;
+        CullBuffers {
+            capacity,
+            items: buffer("occlusion-items", count * std::mem::size_of::<CullItem>() as u64, wgpu::BufferUsages::STORAGE),
+            args: buffer("occlusion-args", 2 * count * DRAW_ARGS_SIZE, wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT),
+            visibility: buffer("occlusion-visibility", count * std::mem::size_of::<u32>() as u64, wgpu::BufferUsages::STORAGE),
+        }
+    }
+
+    /// Redimensionar la pirámide; la profundidad anterior deja de servir
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if self.pyramid.size != pyramid_sizes(width, height)[0] {
+            self.pyramid = Self::create_pyramid(device, width, height);
+            self.pyramid_valid = false;
+        }
+    }
+
+    /// Escribir las cajas, los argumentos indirectos y los uniforms de las
+    /// dos fases y limpiar los contadores
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &OcclusionFrame,
+        view_projection: &Mat4,
+        items: &[OcclusionItem],
+    ) {
+        if frame.reset {
+            self.pyramid_valid = false;
+        }
+        if items.len() > self.buffers.capacity {
+            self.buffers = Self::create_buffers(device, items.len().next_power_of_two());
+        }
+        self.item_count = items.len() as u32;
+
+        let cull_items: Vec<CullItem> = items.iter()
+            .map(|item| CullItem { min: item.bounds.min.extend(0.0).to_array(), max: item.bounds.max.extend(0.0).to_array() })
+            .collect();
+        let args: Vec<DrawArgs> = items.iter()
+            .chain(items.iter())
+            .map(|item| DrawArgs { index_count: item.index_count, instance_count: 0, first_index: 0, base_vertex: 0, first_instance: 0 })
+            .collect();
+        queue.write_buffer(&self.buffers.items, 0, bytemuck::cast_slice(&cull_items));
+        queue.write_buffer(&self.buffers.args, 0, bytemuck::cast_slice(&args));
+
+        let (width, height) = self.pyramid.size;
+        let levels = self.pyramid.level_views.len() as f32;
+        let params = |view_projection: &Mat4, phase: u32, valid: bool| CullParams {
+            view_projection: view_projection.to_cols_array_2d(),
+            pyramid: [width as f32, height as f32, levels, if valid { 1.0 } else { 0.0 }],
+            phase: [phase, self.item_count, 0, 0],
+        };
+        // La fase temprana proyecta con la cámara con la que se construyó la pirámide
+        let early = params(&frame.previous_view_projection, 0, self.pyramid_valid);
+        let late = params(view_projection, 1, true);
+        queue.write_buffer(&self.params[0], 0, bytemuck::bytes_of(&early));
+        queue.write_buffer(&self.params[1], 0, bytemuck::bytes_of(&late));
+        queue.write_buffer(&self.counters, 0, &[0u8; COUNTERS_SIZE as usize]);
+        self.recorded = false;
+    }
+
+    /// Grabar una fase de culling. Tras la tardía se copian los contadores
+    /// si la copia anterior ya se leyó
+    pub fn record_cull(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, phase: OcclusionPhase) {
+        if self.item_count > 0 {
+            let params = match phase {
+                OcclusionPhase::Early => &self.params[0],
+                OcclusionPhase::Late => &self.params[1],
+            };
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("occlusion-cull-bind-group"),
+                layout: &self.cull_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: params.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: self.buffers.items.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 2, resource: self.buffers.args.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 3, resource: self.buffers.visibility.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 4, resource: self.counters.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&self.pyramid.view) },
+                ],
+            });
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("occlusion-cull"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.cull_pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(self.item_count.div_ceil(CULL_WORKGROUP_SIZE), 1, 1);
+        }
+        if phase == OcclusionPhase::Late && !self.readback_pending {
+            encoder.copy_buffer_to_buffer(&self.counters, 0, &self.readback, 0, COUNTERS_SIZE);
+            self.recorded = true;
+        }
+    }
+
+    /// Reconstruir la pirámide con la profundidad de la fase temprana
+    pub fn record_pyramid(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, depth: &wgpu::TextureView) {
+        let sizes = pyramid_sizes(self.pyramid.size.0, self.pyramid.size.1);
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("hi-z-build"),
+            timestamp_writes: None,
+        });
+        for (level, &(width, height)) in sizes.iter().enumerate() {
+            let bind_group = if level == 0 {
+                pass.set_pipeline(&self.from_depth_pipeline);
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("hi-z-from-depth"),
+                    layout: &self.from_depth_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(depth) },
+                        wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&self.pyramid.level_views[0]) },
+                    ],
+                })
+            } else {
+                if level == 1 {
+                    pass.set_pipeline(&self.downsample_pipeline);
+                }
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("hi-z-downsample"),
+                    layout: &self.downsample_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&self.pyramid.level_views[level]) },
+                        wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&self.pyramid.level_views[level - 1]) },
+                    ],
+                })
+            };
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(width.div_ceil(PYRAMID_WORKGROUP_SIZE), height.div_ceil(PYRAMID_WORKGROUP_SIZE), 1);
+        }
+        self.pyramid_valid = true;
+    }
+
+    /// Buffer de argumentos indirectos
+    pub fn args_buffer(&self) -> &wgpu::Buffer {
+        &self.buffers.args
+    }
+
+    /// Offset de los argumentos de la draw call probada `slot` en una fase
+    pub fn args_offset(&self, slot: u32, phase: OcclusionPhase) -> u64 {
+        let index = match phase {
+            OcclusionPhase::Early => slot,
+            OcclusionPhase::Late => self.item_count + slot,
+        };
+        index as u64 * DRAW_ARGS_SIZE
+    }
+
+    /// Tras enviar el frame: pedir el mapeo de la copia de los contadores y
+    /// recoger la del frame anterior si ya terminó. No bloquea
+    pub fn finish(&mut self, device: &wgpu::Device) {
+        if self.recorded {
+            self.recorded = false;
+            self.readback_pending = true;
+            self.readback_state.store(READBACK_WAITING, Ordering::Release);
+            let state = Arc::clone(&self.readback_state);
+            self.readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let next = if result.is_ok() { READBACK_MAPPED } else { READBACK_FAILED };
+                state.store(next, Ordering::Release);
+            });
+        }
+        device.poll(wgpu::Maintain::Poll);
+        if !self.readback_pending {
+            return;
+        }
+        match self.readback_state.load(Ordering::Acquire) {
+            READBACK_MAPPED => {
+                {
+                    let mapped = self.readback.slice(..).get_mapped_range();
+                    let counts: &[u32] = bytemuck::cast_slice(&mapped);
+                    self.last_stats = OcclusionStats { tested: counts[0], occluded: counts[1], late_visible: counts[2] };
+                }
+                self.readback.unmap();
+                self.readback_pending = false;
+            }
+            // Se reintenta con los contadores del próximo frame
+            READBACK_FAILED => self.readback_pending = false,
+            _ => {}
+        }
+    }
+
+    /// Contadores del último frame leído
+    pub fn stats(&self) -> OcclusionStats {
+        self.last_stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Vista-proyección desde `eye` mirando a `target` con 60 grados de campo
+    fn camera(eye: Vec3, target: Vec3) -> Mat4 {
+        Mat4::perspective_rh(60f32.to_radians(), 1.0, 0.1, 100.0) * Mat4::look_at_rh(eye, target, Vec3::Y)
+    }
+
+    /// Depth buffer de 64x64 con un muro de 4x4 en z = 0
+    fn wall_depth(view_projection: &Mat4) -> DepthBuffer {
+        let mut depth = DepthBuffer::new(64, 64);
+        let corners = [
+            Vec3::new(-2.0, -2.0, 0.0),
+            Vec3::new(2.0, -2.0, 0.0),
+            Vec3::new(2.0, 2.0, 0.0),
+            Vec3::new(-2.0, 2.0, 0.0),
+        ];
+        depth.rasterize(&corners, &[0, 1, 2, 0, 2, 3], view_projection);
+        depth
+    }
+
+    #[test]
+    fn pyramid_levels_keep_the_farthest_depth() {
+        let mut depth = DepthBuffer::new(5, 3);
+        depth.depth.iter_mut().enumerate().for_each(|(i, texel)| *texel = i as f32 / 15.0);
+        let pyramid = DepthPyramid::from_depth(&depth);
+        assert_eq!(pyramid.level_count(), 3);
+        assert_eq!(pyramid_sizes(5, 3), [(5, 3), (2, 1), (1, 1)]);
+        // El último texel de cada eje cubre también la fila y columna sobrantes
+        assert_eq!(pyramid.levels[1].1, [11.0 / 15.0, 14.0 / 15.0]);
+        assert_eq!(pyramid.levels[2].1, [14.0 / 15.0]);
+    }
+
+    #[test]
+    fn two_phases_recover_a_box_revealed_by_the_camera() {
+        let target = Vec3::new(0.0, 0.0, -3.0);
+        let behind = Aabb::new(Vec3::new(-0.5, -0.5, -3.5), Vec3::new(0.5, 0.5, -2.5));
+        let beside = Aabb::new(Vec3::new(2.5, -0.5, -3.5), Vec3::new(3.5, 0.5, -2.5));
+        let before = camera(Vec3::new(0.0, 0.0, 5.0), target);
+        let previous = DepthPyramid::from_depth(&wall_depth(&before));
+        assert!(!previous.test(&behind, &before));
+        assert!(previous.test(&beside, &before));
+
+        // La cámara rodea el muro: la fase temprana, con la pirámide y la
+        // cámara del frame anterior, aún la rechaza; la tardía, con la
+        // pirámide del frame actual, donde el muro ya no la tapa, la recupera
+        let after = camera(Vec3::new(6.0, 0.0, -3.0), target);
+        assert!(!previous.test(&behind, &before));
+        let current = DepthPyramid::from_depth(&wall_depth(&after));
+        assert!(current.test(&behind, &after));
+
+        // Una caja que cruza el plano near no se puede descartar
+        let straddling = Aabb::new(Vec3::new(-0.5, -0.5, 4.0), Vec3::new(0.5, 0.5, 6.0));
+        assert!(previous.test(&straddling, &before));
+    }
+}
@@ -0,0 +1,535 @@
+//! # Runtime OpenXR
+//!
+//! `XrRuntime` sobre OpenXR con Vulkan (`XR_KHR_vulkan_enable2`). OpenXR
+//! elige el dispositivo físico y crea la instancia y el dispositivo Vulkan;
+//! wgpu se levanta encima de ellos con `wgpu::hal`, de modo que las imágenes
+//! del swapchain del casco se pueden envolver como texturas de wgpu y
+//! recibir la copia de los destinos offscreen de cada ojo.
+//!
+//! Hay un swapchain por ojo en `XR_SWAPCHAIN_FORMAT`, el mismo formato que
+//! los destinos offscreen del backend. Las poses de los mandos salen de la
+//! acción de grip del perfil `khr/simple_controller`, que todos los runtimes
+//! traducen a sus mandos.
+
+use std::ffi::CString;
+
+use anyhow::{Result, anyhow};
+use ash::vk::{self, Handle};
+use glam::{Quat, Vec3};
+use openxr as xr;
+use tracing::{info, warn};
+use wgpu::hal::{api::Vulkan as V, Api};
+
+use super::backend::wgpu_backend::REQUESTED_FEATURES;
+use super::xr::{XrGraphics, XrRuntime};
+use crate::camera::xr::{EyeFov, EyeView, TrackedPose, TrackingOrigin, VRConfig, XrFrame};
+
+/// Configuración de vistas: estéreo de dos ojos
+const VIEW_TYPE: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
+
+/// Formato de las imágenes del swapchain
+pub const XR_SWAPCHAIN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Versión de Vulkan pedida
+const VK_TARGET_VERSION: u32 = vk::make_api_version(0, 1, 1, 0);
+
+/// Swapchain de un ojo y sus imágenes como texturas de wgpu
+struct EyeSwapchain {
+    handle: xr::Swapchain<xr::Vulkan>,
+    textures: Vec<wgpu::Texture>,
+    /// Imagen adquirida en el frame en curso
+    acquired: Option<usize>,
+}
+
+/// Acción de pose de los mandos
+struct ControllerTracking {
+    action_set: xr::ActionSet,
+    hands: [xr::Path; 2],
+    grip: xr::Action<xr::Posef>,
+    spaces: [xr::Space; 2],
+}
+
+/// Frame empezado con `begin_frame` y aún sin cerrar
+struct PendingFrame {
+    state: xr::FrameState,
+    views: Vec<xr::View>,
+}
+
+/// Runtime OpenXR
+pub struct OpenXrRuntime {
+    instance: xr::Instance,
+    session: xr::Session<xr::Vulkan>,
+    frame_waiter: xr::FrameWaiter,
+    frame_stream: xr::FrameStream<xr::Vulkan>,
+    blend_mode: xr::EnvironmentBlendMode,
+    /// Espacio de tracking en el que se dan las poses
+    space: xr::Space,
+    /// Espacio de la cabeza
+    view_space: xr::Space,
+    controllers: ControllerTracking,
+    swapchains: Vec<EyeSwapchain>,
+    resolution: (u32, u32),
+    /// La sesión está entre `begin` y `end`
+    session_running: bool,
+    pending: Option<PendingFrame>,
+    event_buffer: xr::EventDataBuffer,
+}
+
+impl OpenXrRuntime {
+    /// Abrir el runtime OpenXR del sistema y crear sobre su dispositivo
+    /// Vulkan el dispositivo wgpu del backend
+    pub fn new(config: &VRConfig) -> Result<(Self, XrGraphics)> {
+        let entry = unsafe { xr::Entry::load() }
+            .map_err(|e| anyhow!("No se pudo cargar el loader de OpenXR: {}", e))?;
+        let available = entry.enumerate_extensions()?;
+        if !available.khr_vulkan_enable2 {
+            return Err(anyhow!("El runtime de OpenXR no soporta XR_KHR_vulkan_enable2"));
+        }
+        let mut extensions = xr::ExtensionSet::default();
+        extensions.khr_vulkan_enable2 = true;
+        let instance = entry.create_instance(
+            &xr::ApplicationInfo {
+                application_name: "metaverso",
+                application_version: 0,
+                engine_name: "metaverso-engine",
+                engine_version: 0,
+            },
+            &extensions,
+            &[],
+        )?;
+        let properties = instance.properties()?;
+        info!("Runtime OpenXR: {} {}", properties.runtime_name, properties.runtime_version);
+
+        let system = instance.system(xr::FormFactor::HEAD_MOUNTED_DISPLAY)?;
+        let blend_mode = instance.enumerate_environment_blend_modes(system, VIEW_TYPE)?
+            .first()
+            .copied()
+            .ok_or_else(|| anyhow!("El runtime no ofrece modos de mezcla"))?;
+        let requirements = instance.graphics_requirements::<xr::Vulkan>(system)?;
+        let target = xr::Version::new(1, 1, 0);
+        if requirements.min_api_version_supported > target {
+            return Err(anyhow!("El runtime exige Vulkan {}", requirements.min_api_version_supported));
+        }
+
+        let (graphics, session_info) = create_vulkan_graphics(&instance, system)?;
+        let (session, frame_waiter, frame_stream) = unsafe {
+            instance.create_session::<xr::Vulkan>(system, &session_info)?
+        };
+
+        let space_type = match config.tracking_origin {
+            TrackingOrigin::Local => xr::ReferenceSpaceType::LOCAL,
+            TrackingOrigin::Stage => xr::ReferenceSpaceType::STAGE,
+        };
+        let space = session.create_reference_space(space_type, xr::Posef::IDENTITY)?;
+        let view_space = session.create_reference_space(xr::ReferenceSpaceType::VIEW, xr::Posef::IDENTITY)?;
+        let controllers = create_controller_tracking(&instance, &session)?;
+
+        let views = instance.enumerate_view_configuration_views(system, VIEW_TYPE)?;
+        let view = views.first().ok_or_else(|| anyhow!("El runtime no ofrece vistas estéreo"))?;
+        let resolution = (view.recommended_image_rect_width, view.recommended_image_rect_height);
+        let swapchains = (0..2)
+            .map(|eye| create_eye_swapchain(&session, &graphics.device, resolution, eye))
+            .collect::<Result<Vec<_>>>()?;
+        info!("Sesión OpenXR creada: {}x{} por ojo", resolution.0, resolution.1);
+
+        let runtime = Self {
+            instance,
+            session,
+            frame_waiter,
+            frame_stream,
+            blend_mode,
+            space,
+            view_space,
+            controllers,
+            swapchains,
+            resolution,
+            session_running: false,
+            pending: None,
+            event_buffer: xr::EventDataBuffer::new(),
+        };
+        Ok((runtime, graphics))
+    }
+
+    /// Atender los cambios de estado de la sesión
+    fn poll_events(&mut self) -> Result<()> {
+        while let Some(event) = self.instance.poll_event(&mut self.event_buffer)? {
+            match event {
+                xr::Event::SessionStateChanged(change) => match change.state() {
+                    xr::SessionState::READY => {
+                        self.session.begin(VIEW_TYPE)?;
+                        self.session_running = true;
+                        info!("Sesión OpenXR iniciada");
+                    }
+                    xr::SessionState::STOPPING => {
+                        self.session.end()?;
+                        self.session_running = false;
+                        info!("Sesión OpenXR detenida");
+                    }
+                    xr::SessionState::EXITING | xr::SessionState::LOSS_PENDING => {
+                        self.session_running = false;
+                        warn!("La sesión OpenXR terminó ({:?})", change.state());
+                    }
+                    _ => {}
+                },
+                xr::Event::InstanceLossPending(_) => {
+                    self.session_running = false;
+                    return Err(anyhow!("Se perdió la instancia de OpenXR"));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl XrRuntime for OpenXrRuntime {
+    fn name(&self) -> &str {
+        "openxr"
+    }
+
+    fn eye_resolution(&self) -> (u32, u32) {
+        self.resolution
+    }
+
+    fn begin_frame(&mut self) -> Result<XrFrame> {
+        self.poll_events()?;
+        if !self.session_running {
+            // Sin sesión activa no hay frames que esperar
+            return Ok(idle_frame());
+        }
+
+        let state = self.frame_waiter.wait()?;
+        self.frame_stream.begin()?;
+        let time = state.predicted_display_time;
+        let (_, views) = self.session.locate_views(VIEW_TYPE, time, &self.space)?;
+        if views.len() < 2 {
+            self.pending = Some(PendingFrame { state, views });
+            return Ok(XrFrame { should_render: false, ..idle_frame() });
+        }
+
+        let head = self.view_space.locate(&self.space, time)?;
+        let tracking = &self.controllers;
+        self.session.sync_actions(&[(&tracking.action_set).into()])?;
+        let mut controllers = [None, None];
+        for (hand, slot) in controllers.iter_mut().enumerate() {
+            if !tracking.grip.is_active(&self.session, tracking.hands[hand])? {
+                continue;
+            }
+            let location = tracking.spaces[hand].locate(&self.space, time)?;
+            let tracked = location.location_flags.contains(xr::SpaceLocationFlags::POSITION_VALID)
+                && location.location_flags.contains(xr::SpaceLocationFlags::ORIENTATION_VALID);
+            if tracked {
+                *slot = Some(tracked_pose(&location.pose));
+            }
+        }
+
+        let frame = XrFrame {
+            predicted_display_time: time.as_nanos(),
+            should_render: state.should_render,
+            views: [eye_view(&views[0]), eye_view(&views[1])],
+            head: tracked_pose(&head.pose),
+            controllers,
+        };
+        self.pending = Some(PendingFrame { state, views });
+        Ok(frame)
+    }
+
+    fn acquire_eye_texture(&mut self, eye: usize) -> Result<&wgpu::Texture> {
+        let swapchain = self.swapchains.get_mut(eye)
+            .ok_or_else(|| anyhow!("Ojo inexistente: {}", eye))?;
+        let index = match swapchain.acquired {
+            Some(index) => index,
+            None => {
+                let index = swapchain.handle.acquire_image()? as usize;
+                swapchain.handle.wait_image(xr::Duration::INFINITE)?;
+                swapchain.acquired = Some(index);
+                index
+            }
+        };
+        swapchain.textures.get(index)
+            .ok_or_else(|| anyhow!("Imagen de swapchain inexistente: {}", index))
+    }
+
+    fn end_frame(&mut self, _frame: &XrFrame) -> Result<()> {
+        let Some(pending) = self.pending.take() else {
+            return Ok(());
+        };
+        let mut submitted = 0;
+        for swapchain in &mut self.swapchains {
+            if swapchain.acquired.take().is_some() {
+                swapchain.handle.release_image()?;
+                submitted += 1;
+            }
+        }
+
+        let time = pending.state.predicted_display_time;
+        if submitted < self.swapchains.len() || pending.views.len() < 2 {
+            // Sin las dos imágenes el compositor no recibe capa
+            self.frame_stream.end(time, self.blend_mode, &[])?;
+            return Ok(());
+        }
+
+        let (width, height) = self.resolution;
+        let rect = xr::Rect2Di {
+            offset: xr::Offset2Di { x: 0, y: 0 },
+            extent: xr::Extent2Di { width: width as i32, height: height as i32 },
+        };
+        let views: Vec<xr::CompositionLayerProjectionView<xr::Vulkan>> = pending.views.iter()
+            .zip(&self.swapchains)
+            .map(|(view, swapchain)| {
+                xr::CompositionLayerProjectionView::new()
+                    .pose(view.pose)
+                    .fov(view.fov)
+                    .sub_image(
+                        xr::SwapchainSubImage::new()
+                            .swapchain(&swapchain.handle)
+                            .image_array_index(0)
+                            .image_rect(rect),
+                    )
+            })
+            .collect();
+        let layer = xr::CompositionLayerProjection::new().space(&self.space).views(&views);
+        self.frame_stream.end(time, self.blend_mode, &[&layer])?;
+        Ok(())
+    }
+}
+
+/// Frame sin sesión: no se dibuja y las vistas son las del origen
+fn idle_frame() -> XrFrame {
+    let view = EyeView {
+        pose: TrackedPose::IDENTITY,
+        fov: EyeFov { angle_left: -0.8, angle_right: 0.8, angle_up: 0.8, angle_down: -0.8 },
+    };
+    XrFrame {
+        predicted_display_time: 0,
+        should_render: false,
+        views: [view, view],
+        head: TrackedPose::IDENTITY,
+        controllers: [None, None],
+    }
+}
+
+/// Pose de OpenXR en tipos de glam
+fn tracked_pose(pose: &xr::Posef) -> TrackedPose {
+    let (p, o) = (pose.position, pose.orientation);
+    TrackedPose {
+        position: Vec3::new(p.x, p.y, p.z),
+        orientation: Quat::from_xyzw(o.x, o.y, o.z, o.w).normalize(),
+    }
+}
+
+/// Vista de OpenXR en tipos del motor
+fn eye_view(view: &xr::View) -> EyeView {
+    EyeView {
+        pose: tracked_pose(&view.pose),
+        fov: EyeFov {
+            angle_left: view.fov.angle_left,
+            angle_right: view.fov.angle_right,
+            angle_up: view.fov.angle_up,
+            angle_down: view.fov.angle_down,
+        },
+    }
+}
+
+/// Crear instancia y dispositivo Vulkan a través de OpenXR y levantar wgpu
+/// sobre ellos
+fn create_vulkan_graphics(
+    instance: &xr::Instance,
+    system: xr::SystemId,
+) -> Result<(XrGraphics, xr::vulkan::SessionCreateInfo)> {
+    let vk_entry = unsafe { ash::Entry::load() }
+        .map_err(|e| anyhow!("No se pudo cargar Vulkan: {}", e))?;
+    let flags = wgpu::InstanceFlags::empty();
+    let instance_extensions = <V as Api>::Instance::desired_extensions(&vk_entry, VK_TARGET_VERSION, flags)?;
+    let instance_extension_names: Vec<_> = instance_extensions.iter().map(|name| name.as_ptr()).collect();
+
+    let app_name = CString::new("metaverso")?;
+    let app_info = vk::ApplicationInfo::builder()
+        .application_name(&app_name)
+        .application_version(1)
+        .engine_name(&app_name)
+        .engine_version(1)
+        .api_version(VK_TARGET_VERSION);
+    let create_info = vk::InstanceCreateInfo::builder()
+        .application_info(&app_info)
+        .enabled_extension_names(&instance_extension_names);
+    let get_instance_proc_addr = vk_entry.static_fn().get_instance_proc_addr;
+
+    let vk_instance = unsafe {
+        let raw = instance.create_vulkan_instance(
+            system,
+            std::mem::transmute(get_instance_proc_addr),
+            &*create_info as *const _ as *const _,
+        )?
+        .map_err(vk::Result::from_raw)?;
+        ash::Instance::load(vk_entry.static_fn(), vk::Instance::from_raw(raw as _))
+    };
+    let physical_device = vk::PhysicalDevice::from_raw(unsafe {
+        instance.vulkan_graphics_device(system, vk_instance.handle().as_raw() as _)?
+    } as _);
+    let queue_family_index = unsafe { vk_instance.get_physical_device_queue_family_properties(physical_device) }
+        .into_iter()
+        .position(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+        .ok_or_else(|| anyhow!("El dispositivo de XR no tiene cola gráfica"))? as u32;
+
+    let hal_instance = unsafe {
+        <V as Api>::Instance::from_raw(
+            vk_entry.clone(),
+            vk_instance.clone(),
+            VK_TARGET_VERSION,
+            0,
+            None,
+            instance_extensions,
+            flags,
+            false,
+            Some(Box::new(())),
+        )?
+    };
+    let exposed = hal_instance.expose_adapter(physical_device)
+        .ok_or_else(|| anyhow!("wgpu no admite el dispositivo de XR"))?;
+    let features = REQUESTED_FEATURES & exposed.features;
+    let device_extensions = exposed.adapter.required_device_extensions(features);
+    let device_extension_names: Vec<_> = device_extensions.iter().map(|name| name.as_ptr()).collect();
+
+    let (open_device, vk_device) = {
+        let mut physical_features = exposed.adapter.physical_device_features(&device_extensions, features);
+        let queue_info = vk::DeviceQueueCreateInfo::builder()
+            .queue_family_index(queue_family_index)
+            .queue_priorities(&[1.0])
+            .build();
+        let queue_infos = [queue_info];
+        let device_info = physical_features
+            .add_to_device_create_builder(
+                vk::DeviceCreateInfo::builder()
+                    .queue_create_infos(&queue_infos)
+                    .enabled_extension_names(&device_extension_names),
+            )
+            .build();
+        let vk_device = unsafe {
+            let raw = instance.create_vulkan_device(
+                system,
+                std::mem::transmute(get_instance_proc_addr),
+                physical_device.as_raw() as _,
+                &device_info as *const _ as *const _,
+            )?
+            .map_err(vk::Result::from_raw)?;
+            ash::Device::load(vk_instance.fp_v1_0(), vk::Device::from_raw(raw as _))
+        };
+        let open_device = unsafe {
+            exposed.adapter.device_from_raw(
+                vk_device.clone(),
+                true,
+                &device_extensions,
+                features,
+                queue_family_index,
+                0,
+            )?
+        };
+        (open_device, vk_device)
+    };
+
+    let wgpu_instance = unsafe { wgpu::Instance::from_hal::<V>(hal_instance) };
+    let adapter = unsafe { wgpu_instance.create_adapter_from_hal(exposed) };
+    let (device, queue) = unsafe {
+        adapter.create_device_from_hal(
+            open_device,
+            &wgpu::DeviceDescriptor {
+                label: Some("metaverso-xr-device"),
+                required_features: features,
+                required_limits: wgpu::Limits::default().using_resolution(adapter.limits()),
+            },
+            None,
+        )?
+    };
+
+    let session_info = xr::vulkan::SessionCreateInfo {
+        instance: vk_instance.handle().as_raw() as _,
+        physical_device: physical_device.as_raw() as _,
+        device: vk_device.handle().as_raw() as _,
+        queue_family_index,
+        queue_index: 0,
+    };
+    let graphics = XrGraphics { instance: wgpu_instance, adapter, device, queue };
+    Ok((graphics, session_info))
+}
+
+/// Crear el swapchain de un ojo y envolver sus imágenes como texturas
+fn create_eye_swapchain(
+    session: &xr::Session<xr::Vulkan>,
+    device: &wgpu::Device,
+    (width, height): (u32, u32),
+    eye: usize,
+) -> Result<EyeSwapchain> {
+    let handle = session.create_swapchain(&xr::SwapchainCreateInfo {
+        create_flags: xr::SwapchainCreateFlags::EMPTY,
+        usage_flags: xr::SwapchainUsageFlags::COLOR_ATTACHMENT | xr::SwapchainUsageFlags::TRANSFER_DST,
+        format: vk::Format::R8G8B8A8_SRGB.as_raw() as _,
+        sample_count: 1,
+        width,
+        height,
+        face_count: 1,
+        array_size: 1,
+        mip_count: 1,
+    })?;
+
+    let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+    let label = if eye == 0 { "xr-swapchain-left" } else { "xr-swapchain-right" };
+    let textures = handle.enumerate_images()?
+        .into_iter()
+        .map(|image| unsafe {
+            let raw = <V as Api>::Device::texture_from_raw(
+                vk::Image::from_raw(image),
+                &wgpu::hal::TextureDescriptor {
+                    label: Some(label),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: XR_SWAPCHAIN_FORMAT,
+                    usage: wgpu::hal::TextureUses::COLOR_TARGET | wgpu::hal::TextureUses::COPY_DST,
+                    memory_flags: wgpu::hal::MemoryFlags::empty(),
+                    view_formats: Vec::new(),
+                },
+                None,
+            );
+            device.create_texture_from_hal::<V>(
+                raw,
+                &wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: XR_SWAPCHAIN_FORMAT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                },
+            )
+        })
+        .collect();
+
+    Ok(EyeSwapchain { handle, textures, acquired: None })
+}
+
+/// Acción de grip de los dos mandos con sus espacios
+fn create_controller_tracking(instance: &xr::Instance, session: &xr::Session<xr::Vulkan>) -> Result<ControllerTracking> {
+    let action_set = instance.create_action_set("metaverso", "Metaverso", 0)?;
+    let hands = [
+        instance.string_to_path("/user/hand/left")?,
+        instance.string_to_path("/user/hand/right")?,
+    ];
+    let grip = action_set.create_action::<xr::Posef>("grip_pose", "Pose de los mandos", &hands)?;
+    instance.suggest_interaction_profile_bindings(
+        instance.string_to_path("/interaction_profiles/khr/simple_controller")?,
+        &[
+            xr::Binding::new(&grip, instance.string_to_path("/user/hand/left/input/grip/pose")?),
+            xr::Binding::new(&grip, instance.string_to_path("/user/hand/right/input/grip/pose")?),
+        ],
+    )?;
+    session.attach_action_sets(&[&action_set])?;
+    let spaces = [
+        grip.create_space(session.clone(), hands[0], xr::Posef::IDENTITY)?,
+        grip.create_space(session.clone(), hands[1], xr::Posef::IDENTITY)?,
+    ];
+    Ok(ControllerTracking { action_set, hands, grip, spaces })
+}
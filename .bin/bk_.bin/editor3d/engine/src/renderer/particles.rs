@@ -0,0 +1,64 @@
+//! # Partículas
+//!
+//! Partículas del frame listas para el backend. Se dibujan como billboards
+//! instanciados orientados a la cámara, con mezcla alfa premultiplicada
+//! sobre el render HDR tras la geometría opaca. Para que la mezcla salga
+//! bien, los lotes y las partículas de cada lote se ordenan de atrás hacia
+//! delante. Con el depth buffer muestreable (sin MSAA) las partículas son
+//! suaves: se desvanecen al acercarse a la geometría que tienen detrás.
+
+use glam::{Mat4, Vec3};
+
+use crate::animations::particles::ParticleBatch;
+
+/// Partículas de un frame
+#[derive(Debug, Clone)]
+pub struct ParticleFrame {
+    /// Lotes ordenados de atrás hacia delante
+    pub batches: Vec<ParticleBatch>,
+    /// Eje derecho de la cámara en mundo
+    pub camera_right: Vec3,
+    /// Eje arriba de la cámara en mundo
+    pub camera_up: Vec3,
+    /// Planos near y far de la cámara (para linealizar la profundidad)
+    pub clip: (f32, f32),
+}
+
+impl ParticleFrame {
+    /// Frame de partículas vistas desde `camera_view`. None sin partículas
+    pub fn new(batches: Vec<ParticleBatch>, camera_view: &Mat4, clip: (f32, f32)) -> Option<Self> {
+        let camera = camera_view.inverse();
+        let forward = -camera.z_axis.truncate();
+        let origin = camera.w_axis.truncate();
+        let depth = |point: Vec3| (point - origin).dot(forward);
+
+        let mut batches: Vec<(f32, ParticleBatch)> = batches.into_iter()
+            .filter(|batch| !batch.particles.is_empty())
+            .map(|mut batch| {
+                let mut keyed: Vec<_> = batch.particles.iter()
+                    .map(|particle| (depth(batch.position(particle)), *particle))
+                    .collect();
+                keyed.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+                let farthest = keyed.first().map_or(0.0, |(key, _)| *key);
+                batch.particles = keyed.into_iter().map(|(_, particle)| particle).collect();
+                (farthest, batch)
+            })
+            .collect();
+        if batches.is_empty() {
+            return None;
+        }
+        batches.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        Some(Self {
+            batches: batches.into_iter().map(|(_, batch)| batch).collect(),
+            camera_right: camera.x_axis.truncate().normalize_or_zero(),
+            camera_up: camera.y_axis.truncate().normalize_or_zero(),
+            clip,
+        })
+    }
+
+    /// Partículas de todos los lotes
+    pub fn particle_count(&self) -> usize {
+        self.batches.iter().map(|batch| batch.particles.len()).sum()
+    }
+}
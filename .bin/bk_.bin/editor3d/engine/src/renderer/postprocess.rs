@@ -0,0 +1,271 @@
+//! # Post-procesado
+//!
+//! Cadena de efectos sobre el render HDR: bloom con cadena de mips, tonemapping
+//! ACES/Reinhard con exposición y antialiasing FXAA o TAA. El orden y la
+//! activación de cada efecto se cambian en tiempo de ejecución; el backend
+//! ejecuta la cadena en ese orden. El TAA desplaza la proyección un subpíxel
+//! por frame (secuencia de Halton) y acumula sobre un buffer de historia.
+
+use glam::{Mat4, Vec2, Vec3};
+use serde::{Serialize, Deserialize};
+
+use super::{AntialiasingType, EffectsConfig, QualityConfig};
+use super::bloom::{BloomConfig, BloomPass};
+
+/// Frames de la secuencia de jitter del TAA
+pub const TAA_JITTER_SAMPLES: u32 = 8;
+
+/// Peso por defecto de la historia del TAA
+pub const DEFAULT_TAA_BLEND: f32 = 0.9;
+
+/// Efecto de post-procesado
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PostEffect {
+    /// Umbral de luminancia, cadena de mips y suma sobre la imagen
+    Bloom,
+    /// Exposición y tonemapping a rango bajo
+    Tonemap,
+    /// Antialiasing espacial
+    Fxaa,
+    /// Antialiasing temporal
+    Taa,
+}
+
+/// Operador de tonemapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TonemapOperator {
+    /// Aproximación ACES filmic
+    Aces,
+    /// Reinhard simple
+    Reinhard,
+}
+
+/// Efecto de la cadena y si está activo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PostEffectSlot {
+    /// Efecto
+    pub effect: PostEffect,
+    /// Activo
+    pub enabled: bool,
+}
+
+/// Parámetros de la cadena de post-procesado
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostProcessSettings {
+    /// Efectos en orden de ejecución
+    pub chain: Vec<PostEffectSlot>,
+    /// Operador de tonemapping
+    pub tonemap: TonemapOperator,
+    /// Exposición en EV (la imagen se multiplica por 2^exposure)
+    pub exposure: f32,
+    /// Parámetros del bloom
+    pub bloom: BloomPass,
+    /// Peso de la historia del TAA (0 desactiva la acumulación)
+    pub taa_blend: f32,
+}
+
+impl PostProcessSettings {
+    /// Cadena a partir de la configuración del renderer
+    pub fn from_config(quality: &QualityConfig, effects: &EffectsConfig) -> Self {
+        let antialiasing = &quality.antialiasing;
+        let taa = antialiasing.taa || matches!(antialiasing.antialiasing_type, AntialiasingType::TAA);
+        let fxaa = antialiasing.fxaa || matches!(antialiasing.antialiasing_type, AntialiasingType::FXAA);
+        let bloom = &effects.bloom;
+
+        // El TAA trabaja en HDR antes del tonemapping; el FXAA sobre la imagen final
+        Self {
+            chain: vec![
+                PostEffectSlot { effect: PostEffect::Taa, enabled: taa },
+                PostEffectSlot { effect: PostEffect::Bloom, enabled: bloom.enabled },
+                PostEffectSlot { effect: PostEffect::Tonemap, enabled: true },
+                PostEffectSlot { effect: PostEffect::Fxaa, enabled: fxaa },
+            ],
+            tonemap: TonemapOperator::Aces,
+            exposure: if effects.color_grading.enabled { effects.color_grading.exposure } else { 0.0 },
+            // El radio de la configuración de efectos elige los niveles de la cadena
+            bloom: BloomPass::from_config(&BloomConfig {
+                threshold: bloom.threshold,
+                intensity: bloom.intensity,
+                mip_levels: bloom.radius.round().clamp(1.0, u8::MAX as f32) as u8,
+                ..BloomConfig::default()
+            }),
+            taa_blend: DEFAULT_TAA_BLEND,
+        }
+    }
+
+    /// Verificar si un efecto está activo
+    pub fn is_enabled(&self, effect: PostEffect) -> bool {
+        self.chain.iter().any(|slot| slot.effect == effect && slot.enabled)
+    }
+
+    /// Efectos activos en orden de ejecución
+    pub fn enabled_effects(&self) -> impl Iterator<Item = PostEffect> + '_ {
+        self.chain.iter().filter(|slot| slot.enabled).map(|slot| slot.effect)
+    }
+
+    /// Multiplicador lineal de la exposición
+    pub fn exposure_scale(&self) -> f32 {
+        self.exposure.exp2()
+    }
+}
+
+/// Datos de post-procesado de un frame, enviados al backend con la lista de dibujo
+#[derive(Debug, Clone)]
+pub struct PostProcessFrame {
+    /// Cadena de efectos
+    pub settings: PostProcessSettings,
+    /// Desplazamiento subpíxel del TAA en píxeles
+    pub jitter: Vec2,
+    /// Matriz vista-proyección sin jitter
+    pub view_projection: Mat4,
+    /// Matriz vista-proyección sin jitter del frame anterior
+    pub previous_view_projection: Mat4,
+    /// Descartar la historia del TAA (corte de cámara, redimensionado)
+    pub reset_history: bool,
+}
+
+/// Estado de la cadena de post-procesado entre frames
+#[derive(Debug, Clone)]
+pub struct PostProcessStack {
+    /// Parámetros de la cadena
+    settings: PostProcessSettings,
+    /// Frames renderizados (índice en la secuencia de jitter)
+    frame_index: u32,
+    /// Vista-proyección sin jitter del frame en curso
+    current_view_projection: Option<Mat4>,
+    /// Vista-proyección sin jitter del frame anterior
+    previous_view_projection: Option<Mat4>,
+    /// Descartar la historia en el próximo frame
+    reset_history: bool,
+}
+
+impl PostProcessStack {
+    /// Crear cadena
+    pub fn new(settings: PostProcessSettings) -> Self {
+        Self {
+            settings,
+            frame_index: 0,
+            current_view_projection: None,
+            previous_view_projection: None,
+            reset_history: true,
+        }
+    }
+
+    /// Parámetros de la cadena
+    pub fn settings(&self) -> &PostProcessSettings {
+        &self.settings
+    }
+
+    /// Activar o desactivar un efecto. Devuelve false si no está en la cadena
+    pub fn set_enabled(&mut self, effect: PostEffect, enabled: bool) -> bool {
+        let Some(slot) = self.settings.chain.iter_mut().find(|slot| slot.effect == effect) else {
+            return false;
+        };
+        if effect == PostEffect::Taa && enabled && !slot.enabled {
+            self.reset_history = true;
+        }
+        slot.enabled = enabled;
+        true
+    }
+
+    /// Reordenar la cadena. Los efectos no listados conservan su orden relativo al final
+    pub fn set_order(&mut self, order: &[PostEffect]) {
+        let mut chain: Vec<PostEffectSlot> = order.iter()
+            .filter_map(|effect| self.settings.chain.iter().find(|slot| slot.effect == *effect).copied())
+            .collect();
+        chain.dedup_by_key(|slot| slot.effect);
+        for slot in &self.settings.chain {
+            if !chain.iter().any(|s| s.effect == slot.effect) {
+                chain.push(*slot);
+            }
+        }
+        self.settings.chain = chain;
+    }
+
+    /// Cambiar el operador de tonemapping
+    pub fn set_tonemap(&mut self, operator: TonemapOperator) {
+        self.settings.tonemap = operator;
+    }
+
+    /// Cambiar la exposición (EV)
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.settings.exposure = exposure;
+    }
+
+    /// Cambiar umbral e intensidad del bloom
+    pub fn set_bloom(&mut self, threshold: f32, intensity: f32) {
+        self.settings.bloom.threshold = threshold.max(0.0);
+        self.settings.bloom.intensity = intensity.max(0.0);
+    }
+
+    /// Configurar el bloom y activarlo (Some) o desactivarlo (None)
+    pub fn set_bloom_config(&mut self, config: Option<&BloomConfig>) {
+        if let Some(config) = config {
+            self.settings.bloom = BloomPass::from_config(config);
+        }
+        self.set_enabled(PostEffect::Bloom, config.is_some());
+    }
+
+    /// Descartar la historia del TAA en el próximo frame
+    pub fn reset_history(&mut self) {
+        self.reset_history = true;
+    }
+
+    /// Jitter del frame en curso en píxeles (cero sin TAA)
+    pub fn jitter(&self) -> Vec2 {
+        if !self.settings.is_enabled(PostEffect::Taa) {
+            return Vec2::ZERO;
+        }
+        let index = self.frame_index % TAA_JITTER_SAMPLES + 1;
+        Vec2::new(halton(index, 2) - 0.5, halton(index, 3) - 0.5)
+    }
+
+    /// Preparar el frame: devuelve la vista-proyección con jitter y los datos para el backend
+    pub fn begin_frame(&mut self, view_projection: Mat4, width: u32, height: u32) -> (Mat4, PostProcessFrame) {
+        let jitter = self.jitter();
+        self.current_view_projection = Some(view_projection);
+        let frame = PostProcessFrame {
+            settings: self.settings.clone(),
+            jitter,
+            view_projection,
+            previous_view_projection: self.previous_view_projection.unwrap_or(view_projection),
+            reset_history: self.reset_history || self.previous_view_projection.is_none(),
+        };
+        (jitter_view_projection(view_projection, jitter, width, height), frame)
+    }
+
+    /// Cerrar el frame: avanza la secuencia de jitter y guarda la cámara para reproyectar
+    pub fn end_frame(&mut self) {
+        if let Some(view_projection) = self.current_view_projection.take() {
+            self.previous_view_projection = Some(view_projection);
+            self.frame_index = self.frame_index.wrapping_add(1);
+            self.reset_history = false;
+        }
+    }
+}
+
+/// Desplazar una vista-proyección `jitter` píxeles en pantalla
+pub fn jitter_view_projection(view_projection: Mat4, jitter: Vec2, width: u32, height: u32) -> Mat4 {
+    if jitter == Vec2::ZERO {
+        return view_projection;
+    }
+    // En NDC la pantalla mide 2 unidades y la Y crece hacia arriba
+    let offset = Vec3::new(
+        2.0 * jitter.x / width.max(1) as f32,
+        -2.0 * jitter.y / height.max(1) as f32,
+        0.0,
+    );
+    Mat4::from_translation(offset) * view_projection
+}
+
+/// Elemento `index` (desde 1) de la secuencia de Halton en `base`
+pub fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
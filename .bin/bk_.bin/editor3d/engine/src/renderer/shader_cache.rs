@@ -0,0 +1,188 @@
+//! # Caché de shaders
+//!
+//! Cada combinación de tipo de material, doble cara, formato y MSAA
+//! (`ShaderVariantKey`) es un pipeline distinto. Compilarlo la primera vez
+//! que se usa provoca tirones, así que el renderer puede precompilar todas
+//! las variantes conocidas durante la carga (`precompile_on_startup`) y
+//! guarda en disco lo ya compilado para que los arranques siguientes no
+//! repitan el trabajo.
+//!
+//! wgpu 0.20 no expone las cachés de pipelines del driver: lo que se guarda
+//! es el módulo de la variante ya analizado por naga, que se entrega a wgpu
+//! como IR y se salta el front-end WGSL. Los ficheros viven en
+//! `cache_dir/shaders/` y su nombre es el hash de la variante, del driver,
+//! de la versión de wgpu y del código del shader, así que un cambio de
+//! cualquiera de ellos invalida la entrada sin borrarla. En wasm no hay
+//! sistema de ficheros y la caché solo dura la sesión.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use super::backend::MaterialKind;
+
+/// Versión de wgpu con la que se generaron las entradas
+pub const WGPU_VERSION: &str = "0.20";
+
+/// Subdirectorio de la caché dentro de `cache_dir`
+const SHADER_CACHE_SUBDIR: &str = "shaders";
+
+/// Extensión de las entradas de la caché
+const CACHE_EXTENSION: &str = "bin";
+
+/// Variante de pipeline de material
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShaderVariantKey {
+    /// Tipo de material
+    pub kind: MaterialKind,
+    /// Sin culling de caras traseras
+    pub double_sided: bool,
+    /// Formato del destino
+    pub format: wgpu::TextureFormat,
+    /// Muestras MSAA
+    pub sample_count: u32,
+}
+
+impl ShaderVariantKey {
+    /// Todas las variantes de material para un destino
+    pub fn material_variants(format: wgpu::TextureFormat, sample_count: u32) -> Vec<Self> {
+        [MaterialKind::Pbr, MaterialKind::Unlit, MaterialKind::Terrain]
+            .into_iter()
+            .flat_map(|kind| [false, true].map(|double_sided| Self { kind, double_sided, format, sample_count }))
+            .collect()
+    }
+}
+
+/// Caché en disco de los shaders compilados
+pub struct ShaderCache {
+    /// Directorio de las entradas (None sin caché en disco)
+    dir: Option<PathBuf>,
+    /// Identidad del adaptador y su driver
+    driver: String,
+    /// Entradas cargadas por nombre de fichero
+    blobs: HashMap<String, Vec<u8>>,
+    /// Variantes servidas desde la caché
+    hits: u32,
+    /// Variantes compiladas desde WGSL
+    misses: u32,
+}
+
+impl ShaderCache {
+    /// Abrir la caché de `cache_dir` para un adaptador y cargar sus entradas
+    pub fn new(cache_dir: Option<&Path>, adapter: &wgpu::AdapterInfo) -> Self {
+        let dir = cache_dir
+            .filter(|_| !cfg!(target_arch = "wasm32"))
+            .map(|dir| dir.join(SHADER_CACHE_SUBDIR));
+        let driver = format!(
+            "{}|{:x}|{:x}|{:?}|{}|{}",
+            adapter.name, adapter.vendor, adapter.device, adapter.backend, adapter.driver, adapter.driver_info
+        );
+        let mut cache = Self { dir, driver, blobs: HashMap::new(), hits: 0, misses: 0 };
+        cache.load();
+        cache
+    }
+
+    /// Leer todas las entradas del directorio
+    fn load(&mut self) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            // Aún no hay caché
+            Err(_) => return,
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().and_then(|extension| extension.to_str()) != Some(CACHE_EXTENSION) {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|name| name.to_str()).map(str::to_string) else {
+                continue;
+            };
+            match std::fs::read(&path) {
+                Ok(blob) => {
+                    self.blobs.insert(name, blob);
+                }
+                Err(e) => warn!("No se pudo leer la caché de shaders {}: {}", path.display(), e),
+            }
+        }
+        debug!("Caché de shaders: {} entradas en {}", self.blobs.len(), dir.display());
+    }
+
+    /// Nombre de la entrada de una variante con su código
+    fn entry_name(&self, key: &ShaderVariantKey, source: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{:?}|{}|{}|", key, self.driver, WGPU_VERSION).as_bytes());
+        hasher.update(source.as_bytes());
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Módulo de una variante: el de la caché si lo hay o el resultado de
+    /// compilar `source`, que se guarda para el próximo arranque. Si naga no
+    /// acepta el código se entrega el WGSL a wgpu para que informe del error
+    pub fn shader_module(&mut self, device: &wgpu::Device, key: &ShaderVariantKey, label: &str, source: &str) -> wgpu::ShaderModule {
+        let name = self.entry_name(key, source);
+        let cached = self.blobs.get(&name).and_then(|blob| bincode::deserialize::<naga::Module>(blob).ok());
+        let module = match cached {
+            Some(module) => {
+                self.hits += 1;
+                module
+            }
+            None => {
+                self.misses += 1;
+                match naga::front::wgsl::parse_str(source) {
+                    Ok(module) => {
+                        self.store(name, &module);
+                        module
+                    }
+                    Err(_) => {
+                        return device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                            label: Some(label),
+                            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+                        });
+                    }
+                }
+            }
+        };
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+        })
+    }
+
+    /// Guardar una entrada en memoria y en disco
+    fn store(&mut self, name: String, module: &naga::Module) {
+        let blob = match bincode::serialize(module) {
+            Ok(blob) => blob,
+            Err(e) => {
+                warn!("No se pudo serializar el shader: {}", e);
+                return;
+            }
+        };
+        if let Some(dir) = &self.dir {
+            // Escribir aparte y renombrar: un arranque a medias no deja
+            // entradas truncadas
+            let path = dir.join(format!("{}.{}", name, CACHE_EXTENSION));
+            let temporary = path.with_extension("tmp");
+            let written = std::fs::create_dir_all(dir)
+                .and_then(|_| std::fs::write(&temporary, &blob))
+                .and_then(|_| std::fs::rename(&temporary, &path));
+            if let Err(e) = written {
+                warn!("No se pudo escribir la caché de shaders {}: {}", path.display(), e);
+            }
+        }
+        self.blobs.insert(name, blob);
+    }
+
+    /// Variantes servidas desde la caché
+    pub fn hits(&self) -> u32 {
+        self.hits
+    }
+
+    /// Variantes compiladas desde WGSL
+    pub fn misses(&self) -> u32 {
+        self.misses
+    }
+}
@@ -0,0 +1,272 @@
+//! # Recarga de shaders
+//!
+//! Recarga en caliente de los shaders WGSL de los materiales. Cada tipo de
+//! material tiene su fichero en el directorio vigilado (`pbr.wgsl`,
+//! `unlit.wgsl`, `terrain.wgsl`), que sustituye a su shader por defecto y se
+//! compila tras las declaraciones comunes del backend. En wasm no hay
+//! sistema de ficheros: el editor pasa el código con
+//! `RendererSystem::set_shader_source`.
+//!
+//! El código se valida en la CPU con naga antes de tocar el backend, así que
+//! los errores llevan la línea y la columna del fichero del usuario (sin
+//! contar las declaraciones comunes). Si falla, el backend conserva el
+//! pipeline anterior y el error se notifica al callback del editor. Los
+//! pipelines están indexados por tipo de material: solo se reconstruyen los
+//! del tipo afectado y los bind groups de los materiales no cambian.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+use super::backend::MaterialKind;
+
+/// Intervalo mínimo entre dos exploraciones del directorio vigilado
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Extensión de los ficheros de shader vigilados
+const SHADER_EXTENSION: &str = "wgsl";
+
+/// Error de compilación de un shader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderError {
+    /// Nombre del shader (el del fichero sin extensión)
+    pub shader: String,
+    /// Línea del error en el código del usuario, desde 1
+    pub line: Option<u32>,
+    /// Columna del error, desde 1
+    pub column: Option<u32>,
+    /// Mensaje del compilador
+    pub message: String,
+}
+
+impl ShaderError {
+    /// Error sin posición en el código
+    pub fn new(shader: &str, message: impl Into<String>) -> Self {
+        Self { shader: shader.to_string(), line: None, column: None, message: message.into() }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}.wgsl:{}:{}: {}", self.shader, line, column, self.message),
+            (Some(line), None) => write!(f, "{}.wgsl:{}: {}", self.shader, line, self.message),
+            _ => write!(f, "{}.wgsl: {}", self.shader, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// Resultado de recargar un shader, para el callback del editor
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShaderReloadEvent {
+    /// El shader compiló y los pipelines de su tipo de material se reconstruyeron
+    Reloaded {
+        shader: String,
+        kind: MaterialKind,
+    },
+    /// El shader no compiló; se conserva el pipeline anterior
+    Failed(ShaderError),
+}
+
+/// Callback que recibe cada recarga de shader
+pub type ShaderReloadCallback = Box<dyn Fn(&ShaderReloadEvent) + Send + Sync>;
+
+/// Tipo de material cuyo shader define un fichero
+pub fn shader_kind(shader: &str) -> Option<MaterialKind> {
+    match shader {
+        "pbr" => Some(MaterialKind::Pbr),
+        "unlit" => Some(MaterialKind::Unlit),
+        "terrain" => Some(MaterialKind::Terrain),
+        _ => None,
+    }
+}
+
+/// Nombre del fichero (sin extensión) del shader de un tipo de material
+pub fn material_shader_name(kind: MaterialKind) -> &'static str {
+    match kind {
+        MaterialKind::Pbr => "pbr",
+        MaterialKind::Unlit => "unlit",
+        MaterialKind::Terrain => "terrain",
+    }
+}
+
+/// Puntos de entrada que el shader de un tipo de material debe definir
+pub fn material_entry_points(kind: MaterialKind) -> [&'static str; 2] {
+    match kind {
+        MaterialKind::Pbr => ["vs_material", "fs_pbr"],
+        MaterialKind::Unlit => ["vs_material", "fs_unlit"],
+        MaterialKind::Terrain => ["vs_material", "fs_terrain"],
+    }
+}
+
+/// Validar `source` compilado tras `prelude` y comprobar que define
+/// `entry_points`. Las posiciones de los errores son relativas a `source`
+pub fn validate_wgsl(shader: &str, prelude: &str, source: &str, entry_points: &[&str]) -> Result<(), ShaderError> {
+    let full = format!("{}{}", prelude, source);
+    let prelude_lines = prelude.lines().count() as u32;
+    let located = |message: String, location: Option<naga::SourceLocation>| {
+        let mut error = ShaderError::new(shader, message);
+        // Un error dentro de las declaraciones comunes no tiene línea del usuario
+        if let Some(location) = location.filter(|location| location.line_number > prelude_lines) {
+            error.line = Some(location.line_number - prelude_lines);
+            error.column = Some(location.line_position);
+        }
+        error
+    };
+
+    let module = naga::front::wgsl::parse_str(&full)
+        .map_err(|error| located(error.message().to_string(), error.location(&full)))?;
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(&module)
+        .map_err(|error| {
+            // El error de validación anida la causa concreta
+            let mut message = error.as_inner().to_string();
+            let mut source = std::error::Error::source(error.as_inner());
+            while let Some(cause) = source {
+                message.push_str(": ");
+                message.push_str(&cause.to_string());
+                source = cause.source();
+            }
+            located(message, error.location(&full))
+        })?;
+
+    for entry_point in entry_points {
+        if !module.entry_points.iter().any(|entry| entry.name == *entry_point) {
+            return Err(ShaderError::new(shader, format!("falta el punto de entrada `{}`", entry_point)));
+        }
+    }
+    Ok(())
+}
+
+/// Vigilancia de un directorio de shaders por fecha de modificación
+#[derive(Debug)]
+pub struct ShaderWatcher {
+    /// Directorio vigilado
+    directory: PathBuf,
+    /// Fecha de modificación vista por fichero
+    modified: HashMap<PathBuf, SystemTime>,
+    /// Intervalo mínimo entre exploraciones
+    poll_interval: Duration,
+    /// Última exploración
+    last_poll: Option<Instant>,
+}
+
+impl ShaderWatcher {
+    /// Vigilar `directory`. La primera exploración entrega todos los shaders
+    /// que ya contiene
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            modified: HashMap::new(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            last_poll: None,
+        }
+    }
+
+    /// Cambiar el intervalo entre exploraciones
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Directorio vigilado
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Shaders nuevos o modificados desde la última exploración, como
+    /// (nombre, código). No explora si no ha pasado el intervalo
+    pub fn poll(&mut self) -> Vec<(String, String)> {
+        let now = Instant::now();
+        if self.last_poll.is_some_and(|last| now.duration_since(last) < self.poll_interval) {
+            return Vec::new();
+        }
+        self.last_poll = Some(now);
+
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("No se puede leer el directorio de shaders {:?}: {}", self.directory, e);
+                return Vec::new();
+            }
+        };
+
+        let mut changed = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some(SHADER_EXTENSION) {
+                continue;
+            }
+            let Some(shader) = path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string) else {
+                continue;
+            };
+            let Ok(modified) = entry.metadata().and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+            if self.modified.get(&path) == Some(&modified) {
+                continue;
+            }
+            // Un editor puede estar a mitad de escritura: se reintenta en la
+            // próxima exploración
+            match std::fs::read_to_string(&path) {
+                Ok(source) => {
+                    self.modified.insert(path, modified);
+                    changed.push((shader, source));
+                }
+                Err(e) => warn!("No se puede leer el shader {:?}: {}", path, e),
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Declaraciones comunes de tres líneas
+    const PRELUDE: &str = "struct Tint {\n    color: vec4<f32>,\n};\n";
+
+    #[test]
+    fn errors_carry_the_line_of_the_user_source() {
+        let valid = "@fragment\nfn fs_unlit() -> @location(0) vec4<f32> {\n    return Tint(vec4<f32>(1.0)).color;\n}\n";
+        assert_eq!(validate_wgsl("unlit", PRELUDE, valid, &["fs_unlit"]), Ok(()));
+
+        // Identificador desconocido en la tercera línea del usuario
+        let broken = valid.replace("vec4<f32>(1.0)", "colour");
+        let error = validate_wgsl("unlit", PRELUDE, &broken, &["fs_unlit"]).unwrap_err();
+        assert_eq!(error.shader, "unlit");
+        assert_eq!(error.line, Some(3));
+        assert!(error.column.is_some());
+        assert!(error.to_string().starts_with("unlit.wgsl:3:"), "{}", error);
+
+        let error = validate_wgsl("unlit", PRELUDE, valid, &["vs_material", "fs_unlit"]).unwrap_err();
+        assert_eq!(error.line, None);
+        assert!(error.message.contains("vs_material"));
+    }
+
+    #[test]
+    fn watcher_reports_new_and_modified_shaders_once() {
+        let directory = std::env::temp_dir().join(format!("shader-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("unlit.wgsl"), "// v1").unwrap();
+        std::fs::write(directory.join("notas.txt"), "no es un shader").unwrap();
+
+        let mut watcher = ShaderWatcher::new(&directory).with_poll_interval(Duration::ZERO);
+        assert_eq!(watcher.poll(), [("unlit".to_string(), "// v1".to_string())]);
+        assert!(watcher.poll().is_empty());
+
+        // La fecha de modificación tiene que cambiar aunque el sistema de
+        // ficheros la redondee
+        let file = std::fs::File::options().write(true).open(directory.join("unlit.wgsl")).unwrap();
+        std::fs::write(directory.join("unlit.wgsl"), "// v2").unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5)).unwrap();
+        assert_eq!(watcher.poll(), [("unlit".to_string(), "// v2".to_string())]);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+//! # Cascaded Shadow Maps
+//!
+//! Sombras de la luz direccional repartidas en cascadas. El frustum de la
+//! cámara se divide en tramos de profundidad y cada tramo recibe su propia
+//! proyección ortográfica desde la luz, ajustada a la rejilla de texels del
+//! shadow map para evitar parpadeos al mover la cámara.
+
+use glam::{Mat4, Vec3, Vec4};
+
+use super::ShadowConfig;
+
+/// Número máximo de cascadas
+pub const MAX_SHADOW_CASCADES: usize = 4;
+
+/// Distancia máxima cubierta por las sombras
+pub const DEFAULT_SHADOW_DISTANCE: f32 = 200.0;
+
+/// Margen detrás de cada cascada para capturar casters fuera del tramo
+const CASTER_MARGIN: f32 = 100.0;
+
+/// Parámetros de sombras del frame
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    /// Resolución de cada cascada
+    pub resolution: u32,
+    /// Número de cascadas
+    pub cascade_count: usize,
+    /// Mezcla entre división uniforme (0) y logarítmica (1)
+    pub split_factor: f32,
+    /// Bias constante
+    pub constant_bias: f32,
+    /// Bias de pendiente
+    pub slope_bias: f32,
+    /// Filtrado PCF
+    pub pcf: bool,
+}
+
+impl ShadowSettings {
+    /// Parámetros a partir de la configuración del renderer (None si están deshabilitadas)
+    pub fn from_config(config: &ShadowConfig) -> Option<Self> {
+        if !config.enabled || config.cascade.cascade_count == 0 {
+            return None;
+        }
+        Some(Self {
+            resolution: config.resolution.max(1),
+            cascade_count: (config.cascade.cascade_count as usize).min(MAX_SHADOW_CASCADES),
+            split_factor: config.cascade.split_factor.clamp(0.0, 1.0),
+            constant_bias: config.cascade.bias.constant_bias,
+            slope_bias: config.cascade.bias.slope_bias,
+            pcf: config.soft_shadows,
+        })
+    }
+}
+
+/// Cascadas calculadas para un frame
+#[derive(Debug, Clone)]
+pub struct CascadedShadows {
+    /// Matriz vista-proyección de la luz por cascada
+    pub cascades: Vec<Mat4>,
+    /// Profundidad en vista donde termina cada cascada
+    pub splits: Vec<f32>,
+    /// Matriz de vista de la cámara
+    pub camera_view: Mat4,
+    /// Parámetros
+    pub settings: ShadowSettings,
+}
+
+/// Profundidades de corte de las cascadas (esquema práctico de división)
+pub fn cascade_splits(near: f32, far: f32, count: usize, split_factor: f32) -> Vec<f32> {
+    let near = near.max(f32::EPSILON);
+    let far = far.max(near);
+    (1..=count)
+        .map(|i| {
+            let p = i as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+            uniform + (logarithmic - uniform) * split_factor
+        })
+        .collect()
+}
+
+/// Esquinas en mundo del tramo del frustum entre dos profundidades de vista
+pub fn frustum_slice_corners(
+    view_projection: &Mat4,
+    near: f32,
+    far: f32,
+    slice_near: f32,
+    slice_far: f32,
+) -> [Vec3; 8] {
+    let inverse = view_projection.inverse();
+    let unproject = |x: f32, y: f32, z: f32| {
+        let p = inverse * Vec4::new(x, y, z, 1.0);
+        p.truncate() / p.w
+    };
+
+    // Las aristas del frustum son lineales en la profundidad de vista
+    let range = (far - near).max(f32::EPSILON);
+    let t_near = (slice_near - near) / range;
+    let t_far = (slice_far - near) / range;
+
+    let mut corners = [Vec3::ZERO; 8];
+    for (i, (x, y)) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].into_iter().enumerate() {
+        let a = unproject(x, y, 0.0);
+        let b = unproject(x, y, 1.0);
+        corners[i] = a.lerp(b, t_near);
+        corners[i + 4] = a.lerp(b, t_far);
+    }
+    corners
+}
+
+/// Matriz vista-proyección de la luz que cubre un tramo del frustum
+pub fn cascade_view_projection(corners: &[Vec3; 8], light_direction: Vec3, resolution: u32) -> Mat4 {
+    let center = corners.iter().copied().sum::<Vec3>() / corners.len() as f32;
+    // Radio de la esfera envolvente: tamaño estable al rotar la cámara
+    let radius = corners.iter()
+        .map(|c| c.distance(center))
+        .fold(0.0f32, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let direction = light_direction.try_normalize().unwrap_or(Vec3::NEG_Y);
+    let up = if direction.abs().dot(Vec3::Y) > 0.99 { Vec3::Z } else { Vec3::Y };
+    let eye = center - direction * (radius + CASTER_MARGIN);
+    let view = Mat4::look_at_rh(eye, center, up);
+    let mut projection = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, 2.0 * radius + CASTER_MARGIN);
+
+    // Ajustar el origen a la rejilla de texels
+    let half_resolution = resolution.max(1) as f32 * 0.5;
+    let origin = (projection * view).transform_point3(Vec3::ZERO) * half_resolution;
+    let offset = (origin.round() - origin) / half_resolution;
+    projection.w_axis.x += offset.x;
+    projection.w_axis.y += offset.y;
+
+    projection * view
+}
+
+/// Calcular las cascadas de un frame
+pub fn compute_cascades(
+    settings: ShadowSettings,
+    camera_view: Mat4,
+    view_projection: &Mat4,
+    near: f32,
+    far: f32,
+    light_direction: Vec3,
+) -> CascadedShadows {
+    let shadow_far = far.min(DEFAULT_SHADOW_DISTANCE).max(near);
+    let splits = cascade_splits(near, shadow_far, settings.cascade_count, settings.split_factor);
+
+    let mut slice_near = near;
+    let cascades = splits.iter()
+        .map(|&slice_far| {
+            let corners = frustum_slice_corners(view_projection, near, far, slice_near, slice_far);
+            slice_near = slice_far;
+            cascade_view_projection(&corners, light_direction, settings.resolution)
+        })
+        .collect();
+
+    CascadedShadows {
+        cascades,
+        splits,
+        camera_view,
+        settings,
+    }
+}
@@ -0,0 +1,537 @@
+//! # Skinning en GPU
+//!
+//! Deforma las mallas con skin en un compute pass antes de los pases que las
+//! dibujan. Cada entidad con skin tiene su propio buffer de vértices
+//! deformados (mismo layout que `GpuVertex`), escrito a partir de los
+//! vértices del mesh en bind pose, las influencias por vértice (cuatro
+//! articulaciones y pesos) y la paleta de huesos que el sistema de
+//! animaciones calcula cada frame. El shadow pass y el pase principal leen
+//! ese buffer en lugar del del mesh, así que las sombras siguen a la pose.
+//!
+//! La paleta se sube como `mat4x4` por articulación; en modo dual quaternion
+//! las dos primeras columnas guardan la parte real y la dual.
+
+use bytemuck::{Pod, Zeroable};
+use std::collections::{HashMap, HashSet};
+
+use super::Geometry;
+use crate::animations::skinning::{SkinPalette, SkinningMode};
+
+/// Floats por vértice en el buffer de vértices (`GpuVertex`)
+const VERTEX_FLOATS: u64 = 12;
+
+/// Invocaciones por workgroup del compute shader
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Influencias de un vértice en formato GPU
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct GpuInfluence {
+    /// Índices de articulación en la paleta
+    pub joints: [u32; 4],
+    /// Pesos (los que valen 0 no influyen)
+    pub weights: [f32; 4],
+}
+
+/// Influencias por vértice de una geometría, o None si no tiene skin. Los
+/// vértices sin influencias quedan en bind pose
+pub fn influences(geometry: &Geometry) -> Option<Vec<GpuInfluence>> {
+    if geometry.joints.is_empty() || geometry.weights.is_empty() {
+        return None;
+    }
+    Some((0..geometry.vertices.len())
+        .map(|i| GpuInfluence {
+            joints: geometry.joints.get(i).map_or([0; 4], |joints| joints.map(u32::from)),
+            weights: geometry.weights.get(i).copied().unwrap_or([0.0; 4]),
+        })
+        .collect())
+}
+
+/// Uniforms de una malla con skin
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct SkinUniforms {
+    vertex_count: u32,
+    /// 0 = linear blend, 1 = dual quaternion
+    mode: u32,
+    joint_count: u32,
+    _padding: u32,
+}
+
+/// Compute shader de skinning: una invocación por vértice
+const SKINNING_SHADER: &str = r#"
+struct SkinUniforms {
+    vertex_count: u32,
+    mode: u32,
+    joint_count: u32,
+    _padding: u32,
+};
+
+struct Influence {
+    joints: vec4<u32>,
+    weights: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> skin: SkinUniforms;
+@group(0) @binding(1) var<storage, read> source: array<f32>;
+@group(0) @binding(2) var<storage, read> influences: array<Influence>;
+@group(0) @binding(3) var<storage, read> palette: array<mat4x4<f32>>;
+@group(0) @binding(4) var<storage, read_write> skinned: array<f32>;
+
+const VERTEX_FLOATS: u32 = 12u;
+
+fn rotate(real: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    return v + 2.0 * cross(real.xyz, cross(real.xyz, v) + real.w * v);
+}
+
+@compute @workgroup_size(64)
+fn cs_skin(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= skin.vertex_count) {
+        return;
+    }
+    let base = index * VERTEX_FLOATS;
+    let position = vec3<f32>(source[base], source[base + 1u], source[base + 2u]);
+    let normal = vec3<f32>(source[base + 3u], source[base + 4u], source[base + 5u]);
+    let influence = influences[index];
+
+    var skinned_position = vec3<f32>(0.0);
+    var skinned_normal = vec3<f32>(0.0);
+    var total = 0.0;
+    var real = vec4<f32>(0.0);
+    var dual = vec4<f32>(0.0);
+    var pivot = vec4<f32>(0.0);
+    for (var i = 0u; i < 4u; i++) {
+        let joint = influence.joints[i];
+        let weight = influence.weights[i];
+        if (weight <= 0.0 || joint >= skin.joint_count) {
+            continue;
+        }
+        let matrix = palette[joint];
+        if (skin.mode == 0u) {
+            skinned_position += (matrix * vec4<f32>(position, 1.0)).xyz * weight;
+            skinned_normal += (matrix * vec4<f32>(normal, 0.0)).xyz * weight;
+        } else {
+            // Se alinea el hemisferio con la primera articulación
+            if (total == 0.0) {
+                pivot = matrix[0];
+            }
+            let signed_weight = select(weight, -weight, dot(pivot, matrix[0]) < 0.0);
+            real += matrix[0] * signed_weight;
+            dual += matrix[1] * signed_weight;
+        }
+        total += weight;
+    }
+
+    if (total <= 0.0) {
+        skinned_position = position;
+        skinned_normal = normal;
+    } else if (skin.mode == 0u) {
+        skinned_position /= total;
+    } else {
+        let norm = length(real);
+        if (norm > 1e-6) {
+            real /= norm;
+            dual /= norm;
+            let translation = 2.0 * (real.w * dual.xyz - dual.w * real.xyz + cross(real.xyz, dual.xyz));
+            skinned_position = rotate(real, position) + translation;
+            skinned_normal = rotate(real, normal);
+        } else {
+            skinned_position = position;
+            skinned_normal = normal;
+        }
+    }
+    skinned_normal = select(normal, normalize(skinned_normal), dot(skinned_normal, skinned_normal) > 0.0);
+
+    skinned[base] = skinned_position.x;
+    skinned[base + 1u] = skinned_position.y;
+    skinned[base + 2u] = skinned_position.z;
+    skinned[base + 3u] = skinned_normal.x;
+    skinned[base + 4u] = skinned_normal.y;
+    skinned[base + 5u] = skinned_normal.z;
+    for (var i = 6u; i < VERTEX_FLOATS; i++) {
+        skinned[base + i] = source[base + i];
+    }
+}
+"#;
+
+/// Buffers de la malla deformada de una entidad
+struct SkinnedMesh {
+    mesh_id: String,
+    /// Buffer fuente enlazado (cambia si la entidad pasa a tener morph targets)
+    source: wgpu::Id<wgpu::Buffer>,
+    uniforms: wgpu::Buffer,
+    palette: wgpu::Buffer,
+    /// Articulaciones que caben en `palette`
+    palette_capacity: usize,
+    output: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    vertex_count: u32,
+}
+
+/// Fuente de una malla con skin en la GPU
+pub struct SkinSource<'a> {
+    /// ID del mesh
+    pub mesh_id: &'a str,
+    /// Vértices en bind pose, o los deformados por morph targets (con uso `STORAGE`)
+    pub vertices: &'a wgpu::Buffer,
+    /// Influencias por vértice
+    pub influences: &'a wgpu::Buffer,
+    /// Número de vértices
+    pub vertex_count: u32,
+}
+
+/// Pase de skinning: un dispatch por entidad con skin
+pub struct SkinningPass {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+    meshes: HashMap<u64, SkinnedMesh>,
+    /// Entidades preparadas en el frame en curso
+    active: HashSet<u64>,
+}
+
+impl SkinningPass {
+    /// Crear el pase
+    pub fn new(device: &wgpu::Device) -> Self {
+        let storage = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("skinning-layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<SkinUniforms>() as u64),
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, true),
+                storage(4, false),
+            ],
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("skinning-shader"),
+            source: wgpu::ShaderSource::Wgsl(SKINNING_SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("skinning-pipeline-layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("skinning"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: "cs_skin",
+            compilation_options: Default::default(),
+        });
+
+        Self {
+            layout,
+            pipeline,
+            meshes: HashMap::new(),
+            active: HashSet::new(),
+        }
+    }
+
+    /// Empezar un frame: ninguna entidad está preparada
+    pub fn begin(&mut self) {
+        self.active.clear();
+    }
+
+    /// Escribir la paleta de una entidad, creando sus buffers si cambia el
+    /// mesh o la paleta no cabe
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        entity: u64,
+        source: &SkinSource<'_>,
+        palette: &SkinPalette,
+    ) {
+        let joint_count = palette.matrices.len().max(1);
+        let reusable = self.meshes.get(&entity).is_some_and(|mesh| {
+            mesh.mesh_id == source.mesh_id && mesh.source == source.vertices.global_id() && mesh.vertex_count == source.vertex_count && mesh.palette_capacity >= joint_count
+        });
+        if !reusable {
+            let skinned = self.create_mesh(device, source, joint_count);
+            self.meshes.insert(entity, skinned);
+        }
+        let Some(skinned) = self.meshes.get(&entity) else {
+            return;
+        };
+
+        let matrices: Vec<[[f32; 4]; 4]> = match palette.mode {
+            SkinningMode::Linear => palette.matrices.iter().map(|matrix| matrix.to_cols_array_2d()).collect(),
+            SkinningMode::DualQuaternion => palette.dual_quaternions().iter()
+                .map(|dq| [dq.real.to_array(), dq.dual.to_array(), [0.0; 4], [0.0; 4]])
+                .collect(),
+        };
+        let uniforms = SkinUniforms {
+            vertex_count: source.vertex_count,
+            mode: match palette.mode {
+                SkinningMode::Linear => 0,
+                SkinningMode::DualQuaternion => 1,
+            },
+            joint_count: matrices.len() as u32,
+            _padding: 0,
+        };
+        queue.write_buffer(&skinned.uniforms, 0, bytemuck::bytes_of(&uniforms));
+        if !matrices.is_empty() {
+            queue.write_buffer(&skinned.palette, 0, bytemuck::cast_slice(&matrices));
+        }
+        self.active.insert(entity);
+    }
+
+    /// Buffers de la malla deformada de una entidad
+    fn create_mesh(&self, device: &wgpu::Device, source: &SkinSource<'_>, joint_count: usize) -> SkinnedMesh {
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("skinning-uniforms"),
+            size: std::mem::size_of::<SkinUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Se redondea a potencia de dos para no recrear la paleta si crece poco a poco
+        let palette_capacity = joint_count.next_power_of_two();
+        let palette = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("skinning-palette"),
+            size: (palette_capacity * std::mem::size_of::<[[f32; 4]; 4]>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let output = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{}-skinned", source.mesh_id)),
+            size: source.vertex_count.max(1) as u64 * VERTEX_FLOATS * std::mem::size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("skinning-bind-group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniforms.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: source.vertices.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: source.influences.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: palette.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: output.as_entire_binding() },
+            ],
+        });
+
+        SkinnedMesh {
+            mesh_id: source.mesh_id.to_string(),
+            source: source.vertices.global_id(),
+            uniforms,
+            palette,
+            palette_capacity,
+            output,
+            bind_group,
+            vertex_count: source.vertex_count,
+        }
+    }
+
+    /// Grabar el compute pass de las entidades preparadas y liberar las que
+    /// no se dibujan en este frame. Devuelve los vértices deformados
+    pub fn record(&mut self, encoder: &mut wgpu::CommandEncoder) -> u32 {
+        let active = &self.active;
+        self.meshes.retain(|entity, _| active.contains(entity));
+        if self.meshes.is_empty() {
+            return 0;
+        }
+
+        let mut vertices = 0;
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("skinning-pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        for skinned in self.meshes.values() {
+            pass.set_bind_group(0, &skinned.bind_group, &[]);
+            pass.dispatch_workgroups(skinned.vertex_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+            vertices += skinned.vertex_count;
+        }
+        vertices
+    }
+
+    /// Buffer de vértices deformados de una entidad
+    pub fn output(&self, entity: u64) -> Option<&wgpu::Buffer> {
+        self.meshes.get(&entity).map(|skinned| &skinned.output)
+    }
+
+    /// Descartar las mallas deformadas de un mesh (al reemplazarlo o liberarlo)
+    pub fn invalidate_mesh(&mut self, mesh_id: &str) {
+        self.meshes.retain(|_, skinned| skinned.mesh_id != mesh_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Mat4, Quat, Vec3};
+    use wgpu::util::DeviceExt;
+
+    /// Dispositivo headless, o None si la máquina no tiene adaptador
+    async fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+        adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("skinning-test-device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+        }, None).await.ok()
+    }
+
+    /// Cilindro de radio 0.1 a lo largo de Y, de 0 a 2, con un anillo de
+    /// cuatro vértices por cada altura de `rings`. El primer hueso va de 0 a
+    /// 1 y el segundo de 1 a 2; el anillo de la articulación se reparte
+    /// entre los dos
+    fn cylinder(rings: &[f32]) -> (Vec<f32>, Vec<GpuInfluence>) {
+        let mut vertices = Vec::new();
+        let mut influences = Vec::new();
+        for &y in rings {
+            for (x, z) in [(0.1, 0.0), (0.0, 0.1), (-0.1, 0.0), (0.0, -0.1)] {
+                let mut vertex = [0.0f32; VERTEX_FLOATS as usize];
+                vertex[..6].copy_from_slice(&[x, y, z, x * 10.0, 0.0, z * 10.0]);
+                vertices.extend_from_slice(&vertex);
+                let weights = match y {
+                    y if y < 1.0 => [1.0, 0.0, 0.0, 0.0],
+                    y if y > 1.0 => [0.0, 1.0, 0.0, 0.0],
+                    _ => [0.5, 0.5, 0.0, 0.0],
+                };
+                influences.push(GpuInfluence { joints: [0, 1, 0, 0], weights });
+            }
+        }
+        (vertices, influences)
+    }
+
+    /// Deformar el cilindro en la GPU y leer las posiciones resultantes
+    fn skin_on_gpu(device: &wgpu::Device, queue: &wgpu::Queue, rings: &[f32], palette: &SkinPalette) -> Vec<Vec3> {
+        let (vertices, influences) = cylinder(rings);
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("cylinder"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let influence_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("cylinder-influences"),
+            contents: bytemuck::cast_slice(&influences),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let vertex_count = influences.len() as u32;
+
+        let mut pass = SkinningPass::new(device);
+        pass.begin();
+        pass.prepare(device, queue, 1, &SkinSource {
+            mesh_id: "cylinder",
+            vertices: &vertex_buffer,
+            influences: &influence_buffer,
+            vertex_count,
+        }, palette);
+
+        let size = vertices.len() as u64 * std::mem::size_of::<f32>() as u64;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("skinning-readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        assert_eq!(pass.record(&mut encoder), vertex_count);
+        encoder.copy_buffer_to_buffer(pass.output(1).unwrap(), 0, &readback, 0, size);
+        queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().unwrap().unwrap();
+        let floats: Vec<f32> = bytemuck::cast_slice(&readback.slice(..).get_mapped_range()).to_vec();
+        floats.chunks(VERTEX_FLOATS as usize)
+            .map(|vertex| Vec3::new(vertex[0], vertex[1], vertex[2]))
+            .collect()
+    }
+
+    /// Paleta con el primer hueso en reposo y el segundo girado 90° en Z
+    /// alrededor de la articulación en (0, 1, 0)
+    fn bent_palette(mode: SkinningMode) -> SkinPalette {
+        let joint = Vec3::Y;
+        let bend = Mat4::from_translation(joint)
+            * Mat4::from_quat(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2))
+            * Mat4::from_translation(-joint);
+        SkinPalette { mode, matrices: vec![Mat4::IDENTITY, bend] }
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(actual.distance(expected) < 1e-4, "{} != {}", actual, expected);
+    }
+
+    #[tokio::test]
+    async fn two_bone_cylinder_bends_at_the_joint() {
+        let Some((device, queue)) = device().await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        let rings = [0.0, 0.5, 1.0, 1.5, 2.0];
+        let palette = bent_palette(SkinningMode::Linear);
+        let skinned = skin_on_gpu(&device, &queue, &rings, &palette);
+        let (vertices, influences) = cylinder(&rings);
+        assert_eq!(skinned.len(), rings.len() * 4);
+
+        for (index, &position) in skinned.iter().enumerate() {
+            let base = index * VERTEX_FLOATS as usize;
+            let rest = Vec3::new(vertices[base], vertices[base + 1], vertices[base + 2]);
+            let expected = match rest.y {
+                // El primer hueso no se mueve
+                y if y < 1.0 => rest,
+                // El segundo gira: (x, y, z) → (1 - y, 1 + x, z), hacia -X
+                y if y > 1.0 => Vec3::new(1.0 - rest.y, 1.0 + rest.x, rest.z),
+                // La articulación es la media de las dos poses
+                _ => Vec3::new(rest.x * 0.5, 1.0 + rest.x * 0.5, rest.z),
+            };
+            assert_close(position, expected);
+        }
+
+        // La punta queda a la altura de la articulación, un metro hacia -X
+        let tip: Vec3 = skinned[16..].iter().copied().sum::<Vec3>() / 4.0;
+        assert_close(tip, Vec3::new(-1.0, 1.0, 0.0));
+
+        // La GPU coincide con el skinning en CPU
+        for (index, &position) in skinned.iter().enumerate() {
+            let base = index * VERTEX_FLOATS as usize;
+            let rest = Vec3::new(vertices[base], vertices[base + 1], vertices[base + 2]);
+            let (cpu, _) = palette.skin_vertex(rest, Vec3::X, [0, 1, 0, 0], influences[index].weights);
+            assert_close(position, cpu);
+        }
+    }
+
+    #[tokio::test]
+    async fn dual_quaternion_bend_moves_the_rigid_parts_the_same() {
+        let Some((device, queue)) = device().await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return;
+        };
+        let rings = [0.0, 2.0];
+        let linear = skin_on_gpu(&device, &queue, &rings, &bent_palette(SkinningMode::Linear));
+        let dual = skin_on_gpu(&device, &queue, &rings, &bent_palette(SkinningMode::DualQuaternion));
+        for (linear, dual) in linear.iter().zip(&dual) {
+            assert_close(*dual, *linear);
+        }
+    }
+}
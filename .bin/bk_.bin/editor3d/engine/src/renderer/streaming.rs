@@ -0,0 +1,400 @@
+//! # Streaming de texturas
+//!
+//! Mantiene las texturas de los materiales dentro de un presupuesto de
+//! memoria de GPU. Cada textura se sube con la cadena de mips desde un nivel
+//! residente: el nivel 0 es la textura completa y cada nivel siguiente tiene
+//! la mitad de lado. Todas conservan al menos el nivel de respaldo (lado
+//! menor o igual que `MIN_RESIDENT_SIZE`), aunque eso supere el presupuesto.
+//!
+//! Cada frame el renderer marca las texturas de los materiales dibujados con
+//! la distancia a la cámara y `TextureStreamer::plan` reparte el presupuesto:
+//! primero las usadas más recientemente y, entre ellas, las más cercanas. Una
+//! textura que no cabe completa baja al mayor nivel que quepa; las de menor
+//! prioridad se desalojan hasta el nivel de respaldo para dejar sitio.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::backend::TextureImage;
+
+/// Lado máximo del nivel de respaldo que siempre queda residente
+pub const MIN_RESIDENT_SIZE: u32 = 32;
+
+/// Bytes por texel (RGBA8)
+const BYTES_PER_TEXEL: u64 = 4;
+
+/// Niveles de mip de una textura hasta 1x1
+pub fn mip_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Tamaño de un nivel de mip
+pub fn mip_size(width: u32, height: u32, mip: u32) -> (u32, u32) {
+    ((width >> mip).max(1), (height >> mip).max(1))
+}
+
+/// Primer nivel con el lado mayor dentro de `MIN_RESIDENT_SIZE`
+pub fn fallback_mip(width: u32, height: u32) -> u32 {
+    (0..mip_count(width, height))
+        .find(|&mip| {
+            let (w, h) = mip_size(width, height, mip);
+            w.max(h) <= MIN_RESIDENT_SIZE
+        })
+        .unwrap_or(0)
+}
+
+/// Bytes de la cadena de mips desde `from_mip` hasta 1x1
+pub fn chain_bytes(width: u32, height: u32, from_mip: u32) -> u64 {
+    (from_mip..mip_count(width, height))
+        .map(|mip| {
+            let (w, h) = mip_size(width, height, mip);
+            w as u64 * h as u64 * BYTES_PER_TEXEL
+        })
+        .sum()
+}
+
+/// Reducir una imagen RGBA8 a la mitad promediando bloques de 2x2 (los
+/// lados impares repiten la última fila o columna)
+pub fn downsample(pixels: &[u8], width: u32, height: u32) -> (Vec<u8>, u32, u32) {
+    let (out_width, out_height) = mip_size(width, height, 1);
+    let texel = |x: u32, y: u32, channel: usize| {
+        let x = x.min(width - 1) as usize;
+        let y = y.min(height - 1) as usize;
+        pixels[(y * width as usize + x) * 4 + channel] as u32
+    };
+    let mut out = Vec::with_capacity((out_width * out_height * 4) as usize);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            for channel in 0..4 {
+                let sum = texel(2 * x, 2 * y, channel)
+                    + texel(2 * x + 1, 2 * y, channel)
+                    + texel(2 * x, 2 * y + 1, channel)
+                    + texel(2 * x + 1, 2 * y + 1, channel);
+                out.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+    (out, out_width, out_height)
+}
+
+/// Imagen con la cadena de mips desde `mip`: el nivel `mip` como imagen base
+/// y los siguientes en `mips`
+pub fn mip_chain_image(image: &TextureImage, mip: u32) -> TextureImage {
+    let (mut pixels, mut width, mut height) = (image.pixels.clone(), image.width, image.height);
+    for _ in 0..mip.min(mip_count(width, height) - 1) {
+        (pixels, width, height) = downsample(&pixels, width, height);
+    }
+    let mut mips: Vec<Vec<u8>> = Vec::new();
+    let (mut level_width, mut level_height) = (width, height);
+    while level_width > 1 || level_height > 1 {
+        let (next, next_width, next_height) = downsample(mips.last().unwrap_or(&pixels), level_width, level_height);
+        mips.push(next);
+        (level_width, level_height) = (next_width, next_height);
+    }
+    TextureImage {
+        id: image.id.clone(),
+        width,
+        height,
+        pixels,
+        srgb: image.srgb,
+        mips,
+    }
+}
+
+/// Nivel que hay que subir para una textura
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamRequest {
+    /// ID de la textura
+    pub texture_id: String,
+    /// Nivel de mip residente pedido (0 = completa)
+    pub mip: u32,
+    /// Color en espacio sRGB
+    pub srgb: bool,
+}
+
+/// Estado de una textura gestionada
+#[derive(Debug, Clone)]
+struct StreamedTexture {
+    width: u32,
+    height: u32,
+    srgb: bool,
+    /// Nivel residente en el backend (None sin subir o con datos nuevos)
+    resident_mip: Option<u32>,
+    /// Menor distancia a la cámara en el frame `last_used`
+    distance: f32,
+    /// Último frame en que se dibujó
+    last_used: u64,
+}
+
+impl StreamedTexture {
+    /// Bytes en la GPU
+    fn resident_bytes(&self) -> u64 {
+        self.resident_mip.map_or(0, |mip| chain_bytes(self.width, self.height, mip))
+    }
+
+    /// Marcar como usada en `frame` a `distance` de la cámara
+    fn mark_used(&mut self, frame: u64, distance: f32) {
+        if self.last_used != frame {
+            self.last_used = frame;
+            self.distance = distance;
+        } else {
+            self.distance = self.distance.min(distance);
+        }
+    }
+}
+
+/// Reparto del presupuesto de texturas entre niveles de mip
+#[derive(Debug, Clone)]
+pub struct TextureStreamer {
+    /// Presupuesto de memoria de GPU en bytes
+    budget_bytes: u64,
+    /// Texturas gestionadas
+    textures: HashMap<String, StreamedTexture>,
+    /// Texturas de cada material
+    material_textures: HashMap<String, Vec<String>>,
+    /// Frame en curso
+    frame: u64,
+    /// Bytes residentes de todas las texturas
+    resident_bytes: u64,
+    /// Niveles de mip desalojados desde el inicio
+    evicted_mips: u64,
+}
+
+impl TextureStreamer {
+    /// Crear con un presupuesto en bytes
+    pub fn new(budget_bytes: u64) -> Self {
+        Self {
+            budget_bytes,
+            textures: HashMap::new(),
+            material_textures: HashMap::new(),
+            frame: 0,
+            resident_bytes: 0,
+            evicted_mips: 0,
+        }
+    }
+
+    /// Cambiar el presupuesto; se aplica en el próximo `plan`
+    pub fn set_budget(&mut self, budget_bytes: u64) {
+        self.budget_bytes = budget_bytes;
+    }
+
+    /// Presupuesto en bytes
+    pub fn budget(&self) -> u64 {
+        self.budget_bytes
+    }
+
+    /// Empezar a gestionar una textura. Queda sin nivel residente hasta
+    /// `mark_resident`
+    pub fn register(&mut self, texture_id: &str, width: u32, height: u32, srgb: bool) {
+        self.remove(texture_id);
+        self.textures.insert(texture_id.to_string(), StreamedTexture {
+            width,
+            height,
+            srgb,
+            resident_mip: None,
+            distance: f32::MAX,
+            last_used: 0,
+        });
+    }
+
+    /// Datos nuevos para una textura gestionada: `plan` la vuelve a pedir
+    /// con su prioridad actual
+    pub fn reload(&mut self, texture_id: &str, width: u32, height: u32) {
+        if let Some(texture) = self.textures.get_mut(texture_id) {
+            self.resident_bytes -= texture.resident_bytes();
+            texture.width = width;
+            texture.height = height;
+            texture.resident_mip = None;
+        }
+    }
+
+    /// Verificar si una textura está gestionada
+    pub fn contains(&self, texture_id: &str) -> bool {
+        self.textures.contains_key(texture_id)
+    }
+
+    /// Dejar de gestionar una textura
+    pub fn remove(&mut self, texture_id: &str) {
+        if let Some(texture) = self.textures.remove(texture_id) {
+            self.resident_bytes -= texture.resident_bytes();
+        }
+    }
+
+    /// Recordar las texturas de un material para `touch_material`
+    pub fn bind_material(&mut self, material_id: &str, texture_ids: Vec<String>) {
+        self.material_textures.insert(material_id.to_string(), texture_ids);
+    }
+
+    /// Empezar un frame: las marcas anteriores pasan a ser menos recientes
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Marcar una textura como usada en el frame a `distance` de la cámara
+    pub fn touch(&mut self, texture_id: &str, distance: f32) {
+        if let Some(texture) = self.textures.get_mut(texture_id) {
+            texture.mark_used(self.frame, distance);
+        }
+    }
+
+    /// Marcar las texturas de un material como usadas en el frame
+    pub fn touch_material(&mut self, material_id: &str, distance: f32) {
+        let Some(texture_ids) = self.material_textures.get(material_id) else {
+            return;
+        };
+        for texture_id in texture_ids {
+            if let Some(texture) = self.textures.get_mut(texture_id) {
+                texture.mark_used(self.frame, distance);
+            }
+        }
+    }
+
+    /// Repartir el presupuesto y devolver los niveles que cambian, de la
+    /// textura más prioritaria a la menos
+    pub fn plan(&self) -> Vec<StreamRequest> {
+        let mut order: Vec<(&String, &StreamedTexture)> = self.textures.iter().collect();
+        order.sort_by(|(a_id, a), (b_id, b)| {
+            b.last_used.cmp(&a.last_used)
+                .then(a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal))
+                .then(a_id.cmp(b_id))
+        });
+
+        // Los niveles de respaldo siempre están; el resto se reparte
+        let fallback_total: u64 = order.iter()
+            .map(|(_, texture)| chain_bytes(texture.width, texture.height, fallback_mip(texture.width, texture.height)))
+            .sum();
+        let mut remaining = self.budget_bytes.saturating_sub(fallback_total);
+
+        let mut requests = Vec::new();
+        for (texture_id, texture) in order {
+            let fallback = fallback_mip(texture.width, texture.height);
+            let fallback_bytes = chain_bytes(texture.width, texture.height, fallback);
+            let target = (0..fallback)
+                .find(|&mip| chain_bytes(texture.width, texture.height, mip) - fallback_bytes <= remaining)
+                .unwrap_or(fallback);
+            remaining -= chain_bytes(texture.width, texture.height, target) - fallback_bytes;
+            if texture.resident_mip != Some(target) {
+                requests.push(StreamRequest { texture_id: texture_id.clone(), mip: target, srgb: texture.srgb });
+            }
+        }
+        requests
+    }
+
+    /// Registrar que el backend tiene una textura desde el nivel `mip`
+    pub fn mark_resident(&mut self, texture_id: &str, mip: u32) {
+        let Some(texture) = self.textures.get_mut(texture_id) else {
+            return;
+        };
+        self.resident_bytes -= texture.resident_bytes();
+        if let Some(previous) = texture.resident_mip {
+            self.evicted_mips += mip.saturating_sub(previous) as u64;
+        }
+        texture.resident_mip = Some(mip);
+        self.resident_bytes += texture.resident_bytes();
+    }
+
+    /// Nivel residente de una textura (None sin subir)
+    pub fn resident_mip(&self, texture_id: &str) -> Option<u32> {
+        self.textures.get(texture_id).and_then(|texture| texture.resident_mip)
+    }
+
+    /// Bytes residentes de todas las texturas gestionadas
+    pub fn resident_bytes(&self) -> u64 {
+        self.resident_bytes
+    }
+
+    /// Niveles de mip desalojados desde el inicio
+    pub fn evicted_mip_count(&self) -> u64 {
+        self.evicted_mips
+    }
+
+    /// Olvidar todas las texturas y materiales
+    pub fn clear(&mut self) {
+        self.textures.clear();
+        self.material_textures.clear();
+        self.resident_bytes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lado de las texturas de los tests
+    const SIZE: u32 = 1024;
+
+    /// Presupuesto para los respaldos de `count` texturas y `full` completas
+    fn budget(count: u64, full: u64) -> u64 {
+        let fallback = chain_bytes(SIZE, SIZE, fallback_mip(SIZE, SIZE));
+        count * fallback + full * (chain_bytes(SIZE, SIZE, 0) - fallback)
+    }
+
+    /// Aplicar el reparto como haría el renderer con un backend que no falla
+    fn apply(streamer: &mut TextureStreamer) {
+        for request in streamer.plan() {
+            streamer.mark_resident(&request.texture_id, request.mip);
+        }
+    }
+
+    /// Diez texturas registradas con su respaldo, usadas a `distance(i)`
+    fn ten_textures(distance: impl Fn(usize) -> f32) -> TextureStreamer {
+        let mut streamer = TextureStreamer::new(budget(10, 5));
+        for i in 0..10 {
+            let id = format!("texture_{}", i);
+            streamer.register(&id, SIZE, SIZE, true);
+            streamer.mark_resident(&id, fallback_mip(SIZE, SIZE));
+        }
+        streamer.begin_frame();
+        for i in 0..10 {
+            streamer.touch(&format!("texture_{}", i), distance(i));
+        }
+        apply(&mut streamer);
+        streamer
+    }
+
+    #[test]
+    fn budget_for_five_keeps_the_five_closest_fully_resident() {
+        // Registradas en otro orden que el de distancia
+        let streamer = ten_textures(|i| ((i * 7) % 10) as f32 + 1.0);
+        let mut by_distance: Vec<usize> = (0..10).collect();
+        by_distance.sort_by_key(|&i| (i * 7) % 10);
+
+        for (rank, &i) in by_distance.iter().enumerate() {
+            let mip = streamer.resident_mip(&format!("texture_{}", i)).unwrap();
+            if rank < 5 {
+                assert_eq!(mip, 0, "texture_{} es de las cinco más cercanas", i);
+            } else {
+                assert!(mip > 0, "texture_{} no cabe completa", i);
+            }
+        }
+        assert!(streamer.resident_bytes() <= streamer.budget());
+        assert_eq!(streamer.evicted_mip_count(), 0);
+    }
+
+    #[test]
+    fn moving_away_evicts_down_to_the_fallback() {
+        let mut streamer = ten_textures(|i| i as f32 + 1.0);
+        let fallback = fallback_mip(SIZE, SIZE);
+
+        // La cámara se acerca a las otras cinco: las primeras dejan sitio
+        streamer.begin_frame();
+        for i in 0..10 {
+            streamer.touch(&format!("texture_{}", i), 10.0 - i as f32);
+        }
+        apply(&mut streamer);
+        for i in 0..10 {
+            let mip = streamer.resident_mip(&format!("texture_{}", i)).unwrap();
+            assert_eq!(mip == 0, i >= 5, "texture_{}", i);
+        }
+        assert!(streamer.resident_bytes() <= streamer.budget());
+        assert!(streamer.evicted_mip_count() >= 5);
+
+        // Sin presupuesto solo quedan los respaldos, aunque lo superen
+        streamer.set_budget(0);
+        apply(&mut streamer);
+        for i in 0..10 {
+            assert_eq!(streamer.resident_mip(&format!("texture_{}", i)), Some(fallback));
+        }
+        assert_eq!(streamer.resident_bytes(), budget(10, 0));
+    }
+}
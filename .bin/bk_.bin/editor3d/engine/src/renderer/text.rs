@@ -0,0 +1,556 @@
+//! # Texto
+//!
+//! Texto con fuentes SDF: cada glifo se rasteriza una vez con `fontdue` a un
+//! tamaño de referencia y se guarda en el atlas como campo de distancias con
+//! signo, así que se puede dibujar nítido a cualquier escala y con contorno
+//! desde la misma textura. El renderer coloca el texto del frame en píxeles
+//! del destino: las etiquetas en mundo se proyectan desde su entidad y el
+//! texto y los rectángulos de UI se dibujan donde se piden. Los backends lo
+//! dibujan al final del frame, sobre la imagen ya post-procesada.
+
+use anyhow::{anyhow, Result};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use std::collections::HashMap;
+
+use crate::ecs::TextStyle;
+
+/// Caracteres que se rasterizan por defecto: ASCII imprimible y los propios
+/// del castellano
+pub const DEFAULT_CHARSET: &str = concat!(
+    " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~",
+    "áéíóúÁÉÍÓÚñÑüÜ¿¡€",
+);
+
+/// Ancho del atlas en píxeles
+const ATLAS_WIDTH: u32 = 512;
+
+/// Carácter que sustituye a los que no están en el atlas
+const FALLBACK_CHAR: char = '?';
+
+/// Tamaño en píxeles por debajo del cual una etiqueta no se dibuja
+const MIN_LABEL_PIXELS: f32 = 1.0;
+
+/// Glifo del atlas. Las medidas están en píxeles del tamaño de referencia
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphInfo {
+    /// Avance horizontal tras el glifo
+    pub advance: f32,
+    /// Esquina superior izquierda del bitmap respecto al origen del glifo
+    /// en la línea base (Y hacia abajo), con el margen del campo incluido
+    pub offset: Vec2,
+    /// Tamaño del bitmap con el margen del campo
+    pub size: Vec2,
+    /// Esquina superior izquierda en el atlas (0..1)
+    pub uv_min: Vec2,
+    /// Esquina inferior derecha en el atlas (0..1)
+    pub uv_max: Vec2,
+}
+
+/// Atlas de una fuente SDF
+#[derive(Debug, Clone)]
+pub struct FontAtlas {
+    /// ID de la fuente (el `font_id` de los estilos)
+    pub id: String,
+    /// Tamaño de referencia al que se rasterizaron los glifos
+    pub size_px: f32,
+    /// Alcance del campo de distancias en píxeles del atlas
+    pub spread: f32,
+    /// Altura sobre la línea base
+    pub ascent: f32,
+    /// Distancia entre líneas base consecutivas
+    pub line_height: f32,
+    /// Ancho del atlas
+    pub width: u32,
+    /// Alto del atlas
+    pub height: u32,
+    /// Distancias codificadas en R8: 128 en el borde, más en el interior
+    pub pixels: Vec<u8>,
+    /// Glifos por carácter
+    pub glyphs: HashMap<char, GlyphInfo>,
+    /// Ajuste del avance entre pares de caracteres
+    pub kerning: HashMap<(char, char), f32>,
+}
+
+impl FontAtlas {
+    /// Generar el atlas de los caracteres de `charset` a partir de un
+    /// TrueType/OpenType
+    pub fn from_font_bytes(id: &str, bytes: &[u8], size_px: f32, charset: &str) -> Result<Self> {
+        if size_px <= 0.0 {
+            return Err(anyhow!("Tamaño de fuente inválido: {}", size_px));
+        }
+        let font = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())
+            .map_err(|err| anyhow!("Fuente '{}' inválida: {}", id, err))?;
+        let line = font.horizontal_line_metrics(size_px)
+            .ok_or_else(|| anyhow!("Fuente '{}' sin métricas horizontales", id))?;
+        let spread = (size_px / 8.0).ceil().max(2.0);
+        let padding = spread as usize;
+
+        let mut chars: Vec<char> = charset.chars().collect();
+        chars.push(FALLBACK_CHAR);
+        chars.sort_unstable();
+        chars.dedup();
+        chars.retain(|c| c.is_whitespace() || font.lookup_glyph_index(*c) != 0);
+
+        // Campos de distancias de cada glifo con su margen
+        let mut fields = Vec::with_capacity(chars.len());
+        for &c in &chars {
+            let (metrics, coverage) = font.rasterize(c, size_px);
+            let (width, height) = (metrics.width + padding * 2, metrics.height + padding * 2);
+            let field = if metrics.width == 0 || metrics.height == 0 {
+                Vec::new()
+            } else {
+                signed_distance_field(&coverage, metrics.width, metrics.height, padding, spread)
+            };
+            fields.push((c, metrics, width, height, field));
+        }
+
+        // Empaquetado por estantes de izquierda a derecha
+        let mut placements = Vec::with_capacity(fields.len());
+        let (mut x, mut y, mut shelf) = (0u32, 0u32, 0u32);
+        for (_, _, width, height, field) in &fields {
+            let (width, height) = (*width as u32, *height as u32);
+            if field.is_empty() {
+                placements.push((0, 0));
+                continue;
+            }
+            if width > ATLAS_WIDTH {
+                return Err(anyhow!("Glifo más ancho que el atlas en la fuente '{}'", id));
+            }
+            if x + width > ATLAS_WIDTH {
+                x = 0;
+                y += shelf;
+                shelf = 0;
+            }
+            placements.push((x, y));
+            x += width;
+            shelf = shelf.max(height);
+        }
+        let atlas_height = (y + shelf).max(1).next_power_of_two();
+
+        let mut pixels = vec![0u8; (ATLAS_WIDTH * atlas_height) as usize];
+        let mut glyphs = HashMap::with_capacity(fields.len());
+        let atlas_size = Vec2::new(ATLAS_WIDTH as f32, atlas_height as f32);
+        for ((c, metrics, width, height, field), (px, py)) in fields.iter().zip(placements) {
+            for row in 0..*height {
+                if field.is_empty() {
+                    break;
+                }
+                let start = (py as usize + row) * ATLAS_WIDTH as usize + px as usize;
+                pixels[start..start + width].copy_from_slice(&field[row * width..(row + 1) * width]);
+            }
+            let size = Vec2::new(*width as f32, *height as f32);
+            let uv_min = Vec2::new(px as f32, py as f32) / atlas_size;
+            glyphs.insert(*c, GlyphInfo {
+                advance: metrics.advance_width,
+                offset: Vec2::new(
+                    metrics.xmin as f32 - spread,
+                    -(metrics.ymin as f32 + metrics.height as f32) - spread,
+                ),
+                size: if field.is_empty() { Vec2::ZERO } else { size },
+                uv_min,
+                uv_max: uv_min + size / atlas_size,
+            });
+        }
+
+        let mut kerning = HashMap::new();
+        for &left in &chars {
+            for &right in &chars {
+                if let Some(kern) = font.horizontal_kern(left, right, size_px).filter(|kern| *kern != 0.0) {
+                    kerning.insert((left, right), kern);
+                }
+            }
+        }
+
+        Ok(Self {
+            id: id.to_string(),
+            size_px,
+            spread,
+            ascent: line.ascent,
+            line_height: line.new_line_size,
+            width: ATLAS_WIDTH,
+            height: atlas_height,
+            pixels,
+            glyphs,
+            kerning,
+        })
+    }
+
+    /// Glifo de un carácter, o el de sustitución si no está en el atlas
+    pub fn glyph(&self, c: char) -> Option<&GlyphInfo> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&FALLBACK_CHAR))
+    }
+}
+
+/// Campo de distancias con signo de un bitmap de cobertura, con `padding`
+/// píxeles de margen por lado. Cada píxel guarda la distancia al píxel más
+/// cercano del otro lado del borde, saturada a `spread`
+fn signed_distance_field(coverage: &[u8], width: usize, height: usize, padding: usize, spread: f32) -> Vec<u8> {
+    let inside = |x: isize, y: isize| {
+        x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height
+            && coverage[y as usize * width + x as usize] >= 128
+    };
+    let (field_width, field_height) = (width + padding * 2, height + padding * 2);
+    let radius = spread.ceil() as isize;
+    let mut field = vec![0u8; field_width * field_height];
+    for fy in 0..field_height {
+        for fx in 0..field_width {
+            let (x, y) = (fx as isize - padding as isize, fy as isize - padding as isize);
+            let is_inside = inside(x, y);
+            let mut nearest = spread * spread;
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let distance = (dx * dx + dy * dy) as f32;
+                    if distance < nearest && inside(x + dx, y + dy) != is_inside {
+                        nearest = distance;
+                    }
+                }
+            }
+            // El borde está a medio píxel entre dos píxeles de lados opuestos
+            let distance = (nearest.sqrt() - 0.5).max(0.0);
+            let signed = if is_inside { distance } else { -distance };
+            field[fy * field_width + fx] = ((0.5 + signed / (2.0 * spread)).clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+    field
+}
+
+/// Glifo colocado por `layout_text`, en píxeles respecto a la esquina
+/// superior izquierda del texto
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    /// Carácter
+    pub character: char,
+    /// Esquina superior izquierda
+    pub min: Vec2,
+    /// Esquina inferior derecha
+    pub max: Vec2,
+    /// Esquina superior izquierda en el atlas
+    pub uv_min: Vec2,
+    /// Esquina inferior derecha en el atlas
+    pub uv_max: Vec2,
+}
+
+/// Texto colocado
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextLayout {
+    /// Glifos visibles (los espacios solo avanzan)
+    pub glyphs: Vec<PositionedGlyph>,
+    /// Avance de cada carácter en orden, saltos de línea incluidos con 0
+    pub advances: Vec<f32>,
+    /// Ancho de la línea más larga y alto de todas las líneas
+    pub size: Vec2,
+}
+
+/// Colocar `text` con una altura de letra de `size_px` píxeles. Las líneas
+/// se separan con `\n` y el avance incluye el kerning entre pares
+pub fn layout_text(atlas: &FontAtlas, text: &str, size_px: f32) -> TextLayout {
+    let scale = size_px / atlas.size_px;
+    let mut layout = TextLayout::default();
+    let mut pen = Vec2::new(0.0, atlas.ascent * scale);
+    let mut width: f32 = 0.0;
+    let mut lines = 1;
+    let mut previous: Option<char> = None;
+
+    for c in text.chars() {
+        if c == '\n' {
+            width = width.max(pen.x);
+            pen = Vec2::new(0.0, pen.y + atlas.line_height * scale);
+            lines += 1;
+            previous = None;
+            layout.advances.push(0.0);
+            continue;
+        }
+        let Some(glyph) = atlas.glyph(c) else {
+            layout.advances.push(0.0);
+            continue;
+        };
+        let kern = previous
+            .and_then(|left| atlas.kerning.get(&(left, c)))
+            .copied()
+            .unwrap_or(0.0) * scale;
+        pen.x += kern;
+        if glyph.size != Vec2::ZERO {
+            let min = pen + glyph.offset * scale;
+            layout.glyphs.push(PositionedGlyph {
+                character: c,
+                min,
+                max: min + glyph.size * scale,
+                uv_min: glyph.uv_min,
+                uv_max: glyph.uv_max,
+            });
+        }
+        let advance = glyph.advance * scale;
+        layout.advances.push(kern + advance);
+        pen.x += advance;
+        previous = Some(c);
+    }
+
+    layout.size = Vec2::new(width.max(pen.x), atlas.line_height * scale * lines as f32);
+    layout
+}
+
+/// Píxeles del destino por unidad de mundo a una profundidad de vista
+/// `depth`. Con proyección ortográfica no depende de la profundidad
+pub fn pixels_per_unit(projection: &Mat4, viewport_height: u32, depth: f32) -> f32 {
+    let scale = projection.y_axis.y * viewport_height as f32 * 0.5;
+    if projection.z_axis.w == 0.0 {
+        scale
+    } else {
+        scale / depth.max(f32::EPSILON)
+    }
+}
+
+/// Altura en píxeles de una etiqueta a una profundidad de vista `depth`:
+/// con `scale_with_distance` el tamaño del estilo está en metros y encoge
+/// con la distancia; sin él son píxeles fijos
+pub fn label_pixel_size(style: &TextStyle, projection: &Mat4, viewport_height: u32, depth: f32) -> f32 {
+    if style.scale_with_distance {
+        style.size * pixels_per_unit(projection, viewport_height, depth)
+    } else {
+        style.size
+    }
+}
+
+/// Quad de texto o de UI en píxeles del destino
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextQuad {
+    /// Esquina superior izquierda
+    pub min: Vec2,
+    /// Esquina inferior derecha
+    pub max: Vec2,
+    /// Esquina superior izquierda en el atlas (se ignora en los rectángulos de UI)
+    pub uv_min: Vec2,
+    /// Esquina inferior derecha en el atlas
+    pub uv_max: Vec2,
+    /// Color del relleno
+    pub color: Vec4,
+    /// Color del contorno
+    pub outline_color: Vec4,
+    /// Alcance del campo en píxeles del destino (0 en los rectángulos de UI)
+    pub spread: f32,
+    /// Grosor del contorno en píxeles del destino
+    pub outline: f32,
+}
+
+/// Quads consecutivos de una fuente
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextBatch {
+    /// Fuente de los glifos (None para rectángulos de UI)
+    pub font_id: Option<String>,
+    /// Quads en orden de dibujo
+    pub quads: Vec<TextQuad>,
+}
+
+/// Texto y UI de un frame, en orden de dibujo
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextFrame {
+    /// Lotes en orden de dibujo
+    pub batches: Vec<TextBatch>,
+}
+
+impl TextFrame {
+    /// Añadir un quad, en el lote anterior si es de la misma fuente
+    pub fn push(&mut self, font_id: Option<&str>, quad: TextQuad) {
+        match self.batches.last_mut() {
+            Some(batch) if batch.font_id.as_deref() == font_id => batch.quads.push(quad),
+            _ => self.batches.push(TextBatch {
+                font_id: font_id.map(str::to_string),
+                quads: vec![quad],
+            }),
+        }
+    }
+
+    /// Añadir un texto colocado con su esquina superior izquierda en `origin`
+    pub fn push_text(&mut self, atlas: &FontAtlas, layout: &TextLayout, origin: Vec2, size_px: f32, style: &TextStyle) {
+        let scale = size_px / atlas.size_px;
+        let spread = atlas.spread * scale;
+        // El contorno no puede salir del alcance del campo
+        let outline = (style.outline_width * size_px).clamp(0.0, (spread - 1.0).max(0.0));
+        for glyph in &layout.glyphs {
+            self.push(Some(&atlas.id), TextQuad {
+                min: origin + glyph.min,
+                max: origin + glyph.max,
+                uv_min: glyph.uv_min,
+                uv_max: glyph.uv_max,
+                color: style.color,
+                outline_color: style.outline_color,
+                spread,
+                outline,
+            });
+        }
+    }
+
+    /// Quads de todos los lotes
+    pub fn quad_count(&self) -> usize {
+        self.batches.iter().map(|batch| batch.quads.len()).sum()
+    }
+
+    /// Fuentes usadas por el frame
+    pub fn font_ids(&self) -> impl Iterator<Item = &str> {
+        self.batches.iter().filter_map(|batch| batch.font_id.as_deref())
+    }
+
+    /// Verificar si no hay nada que dibujar
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+}
+
+/// Etiqueta en mundo pendiente de colocar
+#[derive(Debug, Clone)]
+pub struct WorldLabel {
+    /// Texto
+    pub text: String,
+    /// Estilo
+    pub style: TextStyle,
+    /// Punto de anclaje en mundo: centro de la base de la etiqueta
+    pub anchor: Vec3,
+}
+
+/// Colocar las etiquetas en mundo vistas con `view_projection`, de la más
+/// lejana a la más cercana para que las cercanas queden encima. Se omiten las
+/// que quedan detrás de la cámara, más allá de su distancia máxima, sin
+/// fuente cargada o de menos de un píxel
+pub fn place_world_labels(
+    frame: &mut TextFrame,
+    labels: &[WorldLabel],
+    fonts: &HashMap<String, FontAtlas>,
+    view: &Mat4,
+    view_projection: &Mat4,
+    viewport: (u32, u32),
+) {
+    let camera = view.inverse();
+    let forward = -camera.z_axis.truncate();
+    let origin = camera.w_axis.truncate();
+    let projection = *view_projection * camera;
+    let (width, height) = (viewport.0 as f32, viewport.1 as f32);
+
+    let mut placed: Vec<(f32, &WorldLabel, &FontAtlas, Vec2, f32)> = labels.iter()
+        .filter_map(|label| {
+            let atlas = fonts.get(&label.style.font_id)?;
+            let depth = (label.anchor - origin).dot(forward);
+            let clip = *view_projection * label.anchor.extend(1.0);
+            if depth <= 0.0 || clip.w <= 0.0 || label.text.is_empty() {
+                return None;
+            }
+            if label.style.max_distance > 0.0 && label.anchor.distance(origin) > label.style.max_distance {
+                return None;
+            }
+            let size_px = label_pixel_size(&label.style, &projection, viewport.1, depth);
+            if size_px < MIN_LABEL_PIXELS {
+                return None;
+            }
+            let ndc = clip.truncate() / clip.w;
+            let screen = Vec2::new((ndc.x + 1.0) * 0.5 * width, (1.0 - ndc.y) * 0.5 * height);
+            Some((depth, label, atlas, screen, size_px))
+        })
+        .collect();
+    placed.sort_by(|(a, ..), (b, ..)| b.total_cmp(a));
+
+    for (_, label, atlas, screen, size_px) in placed {
+        let layout = layout_text(atlas, &label.text, size_px);
+        // Centrada en horizontal y apoyada sobre el anclaje
+        let origin = screen - Vec2::new(layout.size.x * 0.5, layout.size.y);
+        if origin.x > width || origin.y > height || origin.x + layout.size.x < 0.0 || screen.y < 0.0 {
+            continue;
+        }
+        frame.push_text(atlas, &layout, origin, size_px, &label.style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Atlas a mano de 20 px con cuatro glifos de avance conocido y kerning
+    /// entre "A" y "V"
+    fn atlas() -> FontAtlas {
+        let glyph = |advance: f32, size: Vec2| GlyphInfo {
+            advance,
+            offset: Vec2::new(0.0, -size.y),
+            size,
+            uv_min: Vec2::ZERO,
+            uv_max: Vec2::splat(0.25),
+        };
+        FontAtlas {
+            id: "test".to_string(),
+            size_px: 20.0,
+            spread: 3.0,
+            ascent: 16.0,
+            line_height: 24.0,
+            width: ATLAS_WIDTH,
+            height: 64,
+            pixels: vec![0; ATLAS_WIDTH as usize * 64],
+            glyphs: HashMap::from([
+                ('A', glyph(10.0, Vec2::new(12.0, 18.0))),
+                ('V', glyph(9.0, Vec2::new(11.0, 18.0))),
+                ('i', glyph(4.0, Vec2::new(5.0, 18.0))),
+                (' ', glyph(5.0, Vec2::ZERO)),
+                (FALLBACK_CHAR, glyph(8.0, Vec2::new(9.0, 18.0))),
+            ]),
+            kerning: HashMap::from([(('A', 'V'), -2.0)]),
+        }
+    }
+
+    #[test]
+    fn layout_produces_the_expected_advances() {
+        let atlas = atlas();
+        // Al doble del tamaño de referencia
+        let layout = layout_text(&atlas, "AVi A", 40.0);
+        assert_eq!(layout.advances, [20.0, 14.0, 8.0, 10.0, 20.0]);
+        assert_eq!(layout.size, Vec2::new(72.0, 48.0));
+        // El espacio solo avanza
+        let characters: Vec<char> = layout.glyphs.iter().map(|glyph| glyph.character).collect();
+        assert_eq!(characters, ['A', 'V', 'i', 'A']);
+        assert_eq!(layout.glyphs[1].min.x, 20.0 - 4.0);
+        assert_eq!(layout.glyphs[3].min.x, 52.0);
+
+        // Los caracteres sin glifo usan el de sustitución y el salto de
+        // línea vuelve al margen sin kerning
+        let layout = layout_text(&atlas, "A\nVñ", 20.0);
+        assert_eq!(layout.advances, [10.0, 0.0, 9.0, 8.0]);
+        assert_eq!(layout.size, Vec2::new(17.0, 48.0));
+        assert_eq!(layout.glyphs[1].min, Vec2::new(0.0, 16.0 + 24.0 - 18.0));
+    }
+
+    /// Altura en píxeles de los glifos de una etiqueta a `distance` metros
+    /// delante de la cámara
+    fn label_height(style: &TextStyle, distance: f32) -> f32 {
+        let fonts = HashMap::from([("test".to_string(), atlas())]);
+        let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let projection = Mat4::perspective_rh(60f32.to_radians(), 800.0 / 600.0, 0.1, 500.0);
+        let label = WorldLabel {
+            text: "Alice".to_string(),
+            style: style.clone(),
+            anchor: Vec3::new(0.0, 0.0, -distance),
+        };
+        let mut frame = TextFrame::default();
+        place_world_labels(&mut frame, &[label], &fonts, &view, &(projection * view), (800, 600));
+        let quad = frame.batches[0].quads[0];
+        quad.max.y - quad.min.y
+    }
+
+    #[test]
+    fn world_labels_shrink_with_distance() {
+        let style = TextStyle {
+            font_id: "test".to_string(),
+            size: 0.5,
+            scale_with_distance: true,
+            ..Default::default()
+        };
+        let near = label_height(&style, 5.0);
+        let middle = label_height(&style, 10.0);
+        let far = label_height(&style, 20.0);
+        assert!(near > middle && middle > far);
+        assert!((near / middle - 2.0).abs() < 1e-3);
+        assert!((middle / far - 2.0).abs() < 1e-3);
+        // 0.5 m a 5 m con 60° de campo y 600 px de alto
+        let expected = 0.5 * 300.0 / (30f32.to_radians().tan() * 5.0);
+        assert!((near - expected * 18.0 / 20.0).abs() < 1e-2);
+
+        // Sin la opción el tamaño está en píxeles y no cambia
+        let fixed = TextStyle { size: 16.0, scale_with_distance: false, ..style };
+        assert!((label_height(&fixed, 5.0) - 16.0 * 18.0 / 20.0).abs() < 1e-4);
+        assert!((label_height(&fixed, 20.0) - 16.0 * 18.0 / 20.0).abs() < 1e-4);
+    }
+}
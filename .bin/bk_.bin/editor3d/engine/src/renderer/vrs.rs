@@ -0,0 +1,611 @@
+//! # Variable rate shading
+//!
+//! Genera cada frame una imagen de tasas de sombreado con una tasa por tile
+//! de `VRS_TILE_SIZE` píxeles. Un compute shader recorre el render HDR y el
+//! depth buffer del pase principal: la velocidad en pantalla se reconstruye
+//! reproyectando la profundidad con la cámara del frame anterior (como el
+//! TAA) y los bordes salen del gradiente de luminancia. Los tiles con bordes
+//! usan `fine_rate_for_edges`, los que se mueven rápido
+//! `coarse_rate_for_motion` y las zonas planas y quietas (cielo, terreno
+//! lejano) una tasa intermedia. La imagen describe la escena del frame
+//! recién dibujado y se aplica al siguiente.
+//!
+//! Un histograma de tasas por tile se lee de vuelta sin bloquear y alimenta
+//! `RendererStats::average_shading_rate` con un frame de retraso.
+//!
+//! ## Requisitos de hardware
+//!
+//! Adjuntar la imagen al pase principal necesita fragment shading rate con
+//! imagen de attachment (`VK_KHR_fragment_shading_rate` en Vulkan, VRS Tier 2
+//! en D3D12): NVIDIA Turing (GTX 16xx / RTX 20xx) o posterior, AMD RDNA2
+//! (RX 6000) o posterior e Intel Xe-HPG (Arc). Metal y WebGPU no lo ofrecen.
+//! wgpu 0.20 todavía no expone la feature `FRAGMENT_SHADING_RATE` ni un
+//! attachment de tasas en `RenderPassDescriptor` (su `occlusion_query_set`
+//! es para consultas de oclusión y no sirve para esto), así que
+//! `attachment_supported` devuelve false y la imagen solo se genera y se mide.
+//! Cuando wgpu exponga la feature, el backend la solicitará en
+//! `REQUESTED_FEATURES` y enlazará `VRSPass::rate_view` en el pase principal.
+
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Píxeles de lado de cada tile de la imagen de tasas
+pub const VRS_TILE_SIZE: u32 = 16;
+
+/// Entradas del histograma (una por código de tasa)
+const HISTOGRAM_BINS: usize = 16;
+
+/// Bytes del histograma
+const HISTOGRAM_SIZE: u64 = (HISTOGRAM_BINS * std::mem::size_of::<u32>()) as u64;
+
+/// Estados del mapeo de la copia del histograma
+const READBACK_WAITING: u8 = 0;
+const READBACK_MAPPED: u8 = 1;
+const READBACK_FAILED: u8 = 2;
+
+/// Tasa de sombreado: píxeles que cubre cada invocación del fragment shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShadingRate {
+    /// Una invocación por píxel
+    Rate1x1,
+    /// Una invocación por cada 1x2 píxeles
+    Rate1x2,
+    /// Una invocación por cada 2x1 píxeles
+    Rate2x1,
+    /// Una invocación por cada 2x2 píxeles
+    Rate2x2,
+    /// Una invocación por cada 2x4 píxeles
+    Rate2x4,
+    /// Una invocación por cada 4x2 píxeles
+    Rate4x2,
+    /// Una invocación por cada 4x4 píxeles
+    Rate4x4,
+}
+
+impl ShadingRate {
+    /// Todas las tasas, de la más fina a la más gruesa
+    pub const ALL: [ShadingRate; 7] = [
+        ShadingRate::Rate1x1,
+        ShadingRate::Rate1x2,
+        ShadingRate::Rate2x1,
+        ShadingRate::Rate2x2,
+        ShadingRate::Rate2x4,
+        ShadingRate::Rate4x2,
+        ShadingRate::Rate4x4,
+    ];
+
+    /// Ancho y alto en píxeles
+    pub fn size(self) -> (u32, u32) {
+        match self {
+            ShadingRate::Rate1x1 => (1, 1),
+            ShadingRate::Rate1x2 => (1, 2),
+            ShadingRate::Rate2x1 => (2, 1),
+            ShadingRate::Rate2x2 => (2, 2),
+            ShadingRate::Rate2x4 => (2, 4),
+            ShadingRate::Rate4x2 => (4, 2),
+            ShadingRate::Rate4x4 => (4, 4),
+        }
+    }
+
+    /// Píxeles por invocación
+    pub fn pixels(self) -> u32 {
+        let (width, height) = self.size();
+        width * height
+    }
+
+    /// log2 de los píxeles por invocación (0 a 4)
+    pub fn level(self) -> u32 {
+        self.pixels().trailing_zeros()
+    }
+
+    /// Código en la imagen de tasas: log2(ancho) << 2 | log2(alto), como
+    /// en Vulkan y D3D12
+    pub fn code(self) -> u32 {
+        let (width, height) = self.size();
+        (width.trailing_zeros() << 2) | height.trailing_zeros()
+    }
+
+    /// Tasa de un código de la imagen
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|rate| rate.code() == code)
+    }
+}
+
+/// Configuración del variable rate shading
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VRSConfig {
+    /// Tasa de los tiles con movimiento rápido
+    pub coarse_rate_for_motion: ShadingRate,
+    /// Tasa de los tiles con bordes
+    pub fine_rate_for_edges: ShadingRate,
+    /// Píxeles por frame a partir de los que un tile usa la tasa gruesa
+    #[serde(default = "default_velocity_threshold")]
+    pub velocity_threshold: f32,
+    /// Diferencia de luminancia entre vecinos a partir de la que un tile tiene bordes
+    #[serde(default = "default_edge_threshold")]
+    pub edge_threshold: f32,
+}
+
+fn default_velocity_threshold() -> f32 {
+    8.0
+}
+
+fn default_edge_threshold() -> f32 {
+    0.1
+}
+
+impl Default for VRSConfig {
+    fn default() -> Self {
+        Self {
+            coarse_rate_for_motion: ShadingRate::Rate4x4,
+            fine_rate_for_edges: ShadingRate::Rate1x1,
+            velocity_threshold: default_velocity_threshold(),
+            edge_threshold: default_edge_threshold(),
+        }
+    }
+}
+
+impl VRSConfig {
+    /// Tasa de un tile a partir de su velocidad máxima (píxeles por frame) y
+    /// su gradiente de luminancia máximo. Es la misma regla que aplica el
+    /// compute shader
+    pub fn select_rate(&self, velocity: f32, gradient: f32) -> ShadingRate {
+        let (fine, coarse) = (self.fine_rate_for_edges, self.coarse_rate_for_motion);
+        let coarse = if coarse.level() < fine.level() { fine } else { coarse };
+        let edges = smoothstep(0.0, self.edge_threshold.max(1e-4), gradient);
+        let motion = smoothstep(0.0, self.velocity_threshold.max(1e-4), velocity);
+        // Los bordes mandan; sin ellos el tile engrosa con el movimiento y
+        // las zonas planas quietas quedan a medio camino
+        let t = motion.max(0.5) * (1.0 - edges);
+        let level = (fine.level() as f32 + (coarse.level() as f32 - fine.level() as f32) * t).round() as u32;
+        rate_for_level(level, fine, coarse)
+    }
+}
+
+/// Tasa con `level` dentro del rango fine..coarse, conservando la forma de
+/// los extremos
+fn rate_for_level(level: u32, fine: ShadingRate, coarse: ShadingRate) -> ShadingRate {
+    if level <= fine.level() {
+        return fine;
+    }
+    if level >= coarse.level() {
+        return coarse;
+    }
+    match level {
+        1 => ShadingRate::Rate2x1,
+        2 => ShadingRate::Rate2x2,
+        3 => ShadingRate::Rate4x2,
+        _ => ShadingRate::Rate4x4,
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Píxeles medios por invocación de un histograma indexado por código de
+/// tasa (1.0 = sombreado completo, 16.0 = todo a 4x4). 1.0 si está vacío
+pub fn average_shading_rate(histogram: &[u32]) -> f32 {
+    let (mut tiles, mut pixels) = (0u64, 0u64);
+    for (code, &count) in histogram.iter().enumerate() {
+        if let Some(rate) = ShadingRate::from_code(code as u32) {
+            tiles += count as u64;
+            pixels += count as u64 * rate.pixels() as u64;
+        }
+    }
+    if tiles == 0 {
+        1.0
+    } else {
+        pixels as f32 / tiles as f32
+    }
+}
+
+/// Datos de VRS de un frame, enviados al backend con la lista de dibujo
+#[derive(Debug, Clone)]
+pub struct VRSFrame {
+    /// Configuración
+    pub config: VRSConfig,
+    /// Matriz vista-proyección sin jitter
+    pub view_projection: Mat4,
+    /// Matriz vista-proyección sin jitter del frame anterior
+    pub previous_view_projection: Mat4,
+}
+
+/// Compute shader de la imagen de tasas: un workgroup por tile
+const VRS_SHADER: &str = r#"
+struct VrsUniforms {
+    // xy = tamaño en píxeles, z = 1 con profundidad válida
+    size: vec4<f32>,
+    // x = umbral de velocidad, y = umbral de bordes, z = nivel fino, w = nivel grueso
+    thresholds: vec4<f32>,
+    // x = código fino, y = código grueso
+    codes: vec4<u32>,
+    inverse_view_projection: mat4x4<f32>,
+    previous_view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> vrs: VrsUniforms;
+@group(0) @binding(1) var scene: texture_2d<f32>;
+@group(0) @binding(2) var depth: texture_depth_2d;
+@group(0) @binding(3) var rate_image: texture_storage_2d<r32uint, write>;
+@group(0) @binding(4) var<storage, read_write> histogram: array<atomic<u32>, 16>;
+
+var<workgroup> tile_velocity: atomic<u32>;
+var<workgroup> tile_gradient: atomic<u32>;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn load_luma(pixel: vec2<i32>) -> f32 {
+    let clamped = clamp(pixel, vec2<i32>(0), vec2<i32>(vrs.size.xy) - vec2<i32>(1));
+    // Luminancia comprimida: los bordes se miden como se verán tras el tonemapping
+    let luma = luminance(textureLoad(scene, clamped, 0).rgb);
+    return luma / (1.0 + luma);
+}
+
+fn screen_velocity(pixel: vec2<i32>) -> f32 {
+    if (vrs.size.z < 0.5) {
+        return 0.0;
+    }
+    let uv = (vec2<f32>(pixel) + 0.5) / vrs.size.xy;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, textureLoad(depth, pixel, 0), 1.0);
+    let world = vrs.inverse_view_projection * ndc;
+    let previous = vrs.previous_view_projection * vec4<f32>(world.xyz / world.w, 1.0);
+    let previous_uv = vec2<f32>(previous.x / previous.w * 0.5 + 0.5, 0.5 - previous.y / previous.w * 0.5);
+    return length((uv - previous_uv) * vrs.size.xy);
+}
+
+fn rate_code(level: u32) -> u32 {
+    let fine = u32(vrs.thresholds.z);
+    let coarse = u32(vrs.thresholds.w);
+    if (level <= fine) {
+        return vrs.codes.x;
+    }
+    if (level >= coarse) {
+        return vrs.codes.y;
+    }
+    // 2x1, 2x2, 4x2 entre los extremos
+    var codes = array<u32, 5>(0u, 4u, 5u, 9u, 10u);
+    return codes[level];
+}
+
+@compute @workgroup_size(16, 16, 1)
+fn cs_rates(
+    @builtin(global_invocation_id) global: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) tile: vec3<u32>,
+) {
+    if (local_index == 0u) {
+        atomicStore(&tile_velocity, 0u);
+        atomicStore(&tile_gradient, 0u);
+    }
+    workgroupBarrier();
+
+    let pixel = vec2<i32>(global.xy);
+    if (f32(global.x) < vrs.size.x && f32(global.y) < vrs.size.y) {
+        let gradient = max(
+            abs(load_luma(pixel + vec2<i32>(1, 0)) - load_luma(pixel - vec2<i32>(1, 0))),
+            abs(load_luma(pixel + vec2<i32>(0, 1)) - load_luma(pixel - vec2<i32>(0, 1))),
+        );
+        // Valores positivos: el orden de sus bits coincide con el de los floats
+        atomicMax(&tile_gradient, bitcast<u32>(gradient));
+        atomicMax(&tile_velocity, bitcast<u32>(screen_velocity(pixel)));
+    }
+    workgroupBarrier();
+
+    if (local_index == 0u) {
+        let velocity = bitcast<f32>(atomicLoad(&tile_velocity));
+        let gradient = bitcast<f32>(atomicLoad(&tile_gradient));
+        let edges = smoothstep(0.0, max(vrs.thresholds.y, 1e-4), gradient);
+        let motion = smoothstep(0.0, max(vrs.thresholds.x, 1e-4), velocity);
+        let t = max(motion, 0.5) * (1.0 - edges);
+        let level = u32(round(mix(vrs.thresholds.z, vrs.thresholds.w, t)));
+        let code = rate_code(level);
+        textureStore(rate_image, vec2<i32>(tile.xy), vec4<u32>(code, 0u, 0u, 0u));
+        atomicAdd(&histogram[code], 1u);
+    }
+}
+"#;
+
+/// Uniforms del compute shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct VrsUniforms {
+    size: [f32; 4],
+    thresholds: [f32; 4],
+    codes: [u32; 4],
+    inverse_view_projection: [[f32; 4]; 4],
+    previous_view_projection: [[f32; 4]; 4],
+}
+
+/// Imagen de tasas y su vista
+struct RateImage {
+    /// Tiles en cada eje
+    tiles: (u32, u32),
+    view: wgpu::TextureView,
+}
+
+/// Pase que genera la imagen de tasas y mide su histograma
+pub struct VRSPass {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+    uniforms: wgpu::Buffer,
+    histogram: wgpu::Buffer,
+    /// Copia del histograma que se lee desde la CPU
+    readback: wgpu::Buffer,
+    /// La copia está mapeada (o pendiente de mapear)
+    readback_pending: bool,
+    /// Estado del mapeo de la copia
+    readback_state: Arc<AtomicU8>,
+    /// Profundidad de sustitución cuando el depth buffer no se puede muestrear
+    dummy_depth: wgpu::TextureView,
+    rate_image: RateImage,
+    /// Histograma del último frame leído
+    last_histogram: [u32; HISTOGRAM_BINS],
+    /// El pase se grabó en el frame en curso
+    recorded: bool,
+}
+
+impl VRSPass {
+    /// Crear el pase para un destino de `width` x `height` píxeles
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("vrs-layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<VrsUniforms>() as u64),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::R32Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(HISTOGRAM_SIZE),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("vrs-shader"),
+            source: wgpu::ShaderSource::Wgsl(VRS_SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("vrs-pipeline-layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("vrs-rates"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: "cs_rates",
+            compilation_options: Default::default(),
+        });
+
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vrs-uniforms"),
+            size: std::mem::size_of::<VrsUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let histogram = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vrs-histogram"),
+            size: HISTOGRAM_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vrs-histogram-readback"),
+            size: HISTOGRAM_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let dummy_depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("vrs-dummy-depth"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            rate_image: Self::create_rate_image(device, width, height),
+            layout,
+            pipeline,
+            uniforms,
+            histogram,
+            readback,
+            readback_pending: false,
+            readback_state: Arc::new(AtomicU8::new(READBACK_WAITING)),
+            dummy_depth,
+            last_histogram: [0; HISTOGRAM_BINS],
+            recorded: false,
+        }
+    }
+
+    /// Tiles de la imagen de tasas para un destino de `width` x `height`
+    pub fn tile_count(width: u32, height: u32) -> (u32, u32) {
+        (width.max(1).div_ceil(VRS_TILE_SIZE), height.max(1).div_ceil(VRS_TILE_SIZE))
+    }
+
+    /// Verificar si el dispositivo puede adjuntar la imagen al pase
+    /// principal. wgpu 0.20 no expone fragment shading rate (ver la
+    /// documentación del módulo)
+    pub fn attachment_supported(_features: wgpu::Features) -> bool {
+        false
+    }
+
+    fn create_rate_image(device: &wgpu::Device, width: u32, height: u32) -> RateImage {
+        let tiles = Self::tile_count(width, height);
+        let view = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("vrs-rate-image"),
+            size: wgpu::Extent3d { width: tiles.0, height: tiles.1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Uint,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default());
+        RateImage { tiles, view }
+    }
+
+    /// Redimensionar la imagen de tasas
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if self.rate_image.tiles != Self::tile_count(width, height) {
+            self.rate_image = Self::create_rate_image(device, width, height);
+        }
+    }
+
+    /// Imagen de tasas del último frame (códigos `ShadingRate::code`)
+    pub fn rate_view(&self) -> &wgpu::TextureView {
+        &self.rate_image.view
+    }
+
+    /// Escribir los uniforms del frame y limpiar el histograma
+    pub fn prepare(&mut self, queue: &wgpu::Queue, frame: &VRSFrame, depth_readable: bool, size: (u32, u32)) {
+        let config = &frame.config;
+        let (fine, coarse) = (config.fine_rate_for_edges, config.coarse_rate_for_motion);
+        // Una tasa fina más gruesa que la gruesa se trata como rango vacío
+        let coarse = if coarse.level() < fine.level() { fine } else { coarse };
+        let uniforms = VrsUniforms {
+            size: [size.0.max(1) as f32, size.1.max(1) as f32, if depth_readable { 1.0 } else { 0.0 }, 0.0],
+            thresholds: [config.velocity_threshold, config.edge_threshold, fine.level() as f32, coarse.level() as f32],
+            codes: [fine.code(), coarse.code(), 0, 0],
+            inverse_view_projection: frame.view_projection.inverse().to_cols_array_2d(),
+            previous_view_projection: frame.previous_view_projection.to_cols_array_2d(),
+        };
+        queue.write_buffer(&self.uniforms, 0, bytemuck::bytes_of(&uniforms));
+        queue.write_buffer(&self.histogram, 0, &[0u8; HISTOGRAM_SIZE as usize]);
+        self.recorded = false;
+    }
+
+    /// Grabar el compute pass sobre el render HDR y la profundidad (si se
+    /// puede muestrear) y copiar el histograma si la copia anterior ya se leyó
+    pub fn record(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &wgpu::TextureView,
+        depth: Option<&wgpu::TextureView>,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vrs-bind-group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: self.uniforms.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(scene) },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(depth.unwrap_or(&self.dummy_depth)),
+                },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&self.rate_image.view) },
+                wgpu::BindGroupEntry { binding: 4, resource: self.histogram.as_entire_binding() },
+            ],
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("vrs-pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(self.rate_image.tiles.0, self.rate_image.tiles.1, 1);
+        }
+        if !self.readback_pending {
+            encoder.copy_buffer_to_buffer(&self.histogram, 0, &self.readback, 0, HISTOGRAM_SIZE);
+            self.recorded = true;
+        }
+    }
+
+    /// Tras enviar el frame: pedir el mapeo de la copia del histograma y
+    /// recoger la del frame anterior si ya terminó. No bloquea
+    pub fn finish(&mut self, device: &wgpu::Device) {
+        if self.recorded {
+            self.recorded = false;
+            self.readback_pending = true;
+            self.readback_state.store(READBACK_WAITING, Ordering::Release);
+            let state = Arc::clone(&self.readback_state);
+            self.readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let next = if result.is_ok() { READBACK_MAPPED } else { READBACK_FAILED };
+                state.store(next, Ordering::Release);
+            });
+        }
+        device.poll(wgpu::Maintain::Poll);
+        if !self.readback_pending {
+            return;
+        }
+        match self.readback_state.load(Ordering::Acquire) {
+            READBACK_MAPPED => {
+                {
+                    let mapped = self.readback.slice(..).get_mapped_range();
+                    let counts: &[u32] = bytemuck::cast_slice(&mapped);
+                    self.last_histogram.copy_from_slice(&counts[..HISTOGRAM_BINS]);
+                }
+                self.readback.unmap();
+                self.readback_pending = false;
+            }
+            // Se reintenta con el histograma del próximo frame
+            READBACK_FAILED => self.readback_pending = false,
+            _ => {}
+        }
+    }
+
+    /// Tiles por código de tasa del último histograma leído
+    pub fn histogram(&self) -> &[u32] {
+        &self.last_histogram
+    }
+
+    /// Píxeles medios por invocación del último histograma leído
+    pub fn average_shading_rate(&self) -> f32 {
+        average_shading_rate(&self.last_histogram)
+    }
+}
@@ -0,0 +1,45 @@
+//! # Salida a cascos de XR
+//!
+//! `XrRuntime` abstrae el runtime que compone las imágenes en el casco. Cada
+//! frame el motor pide al runtime el frame (`begin_frame`), dibuja la escena
+//! una vez por ojo en un destino offscreen, copia cada destino a la imagen
+//! del ojo en el swapchain (`acquire_eye_texture`) y cierra el frame con
+//! `end_frame`, que entrega las dos vistas al compositor.
+//!
+//! La implementación sobre OpenXR está en `openxr` (feature `xr`, solo
+//! nativo). El runtime de OpenXR decide el dispositivo Vulkan, así que crea
+//! también el dispositivo wgpu (`XrGraphics`) sobre el que se construye el
+//! backend.
+
+use anyhow::Result;
+
+use crate::camera::xr::XrFrame;
+
+/// Destinos offscreen de los ojos izquierdo y derecho
+pub const XR_EYE_TARGETS: [&str; 2] = ["xr-eye-left", "xr-eye-right"];
+
+/// Runtime de XR que presenta las imágenes de los ojos
+pub trait XrRuntime: Send {
+    /// Nombre del runtime
+    fn name(&self) -> &str;
+    /// Resolución recomendada por ojo
+    fn eye_resolution(&self) -> (u32, u32);
+    /// Esperar al siguiente frame del compositor y empezarlo. Todo frame
+    /// empezado se cierra con `end_frame`, aunque no se dibuje
+    fn begin_frame(&mut self) -> Result<XrFrame>;
+    /// Adquirir la imagen del swapchain de un ojo (0 izquierdo, 1 derecho)
+    /// para el frame en curso
+    fn acquire_eye_texture(&mut self, eye: usize) -> Result<&wgpu::Texture>;
+    /// Cerrar el frame. Las imágenes adquiridas se liberan y se entregan
+    /// como vistas de una capa de proyección; sin imágenes el frame se
+    /// cierra vacío
+    fn end_frame(&mut self, frame: &XrFrame) -> Result<()>;
+}
+
+/// Dispositivo wgpu creado por el runtime de XR
+pub struct XrGraphics {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
@@ -0,0 +1,210 @@
+//! # Definiciones de escena
+//!
+//! Una escena es un conjunto con nombre de jerarquías de entidades guardadas
+//! como instantánea del mundo (`ecs::snapshot`), más sus metadatos: ambiente,
+//! skybox y puntos de aparición. La definición entera se serializa con bincode.
+//!
+//! `instantiate` comprueba toda la escena antes de crear nada: si un
+//! componente no se puede decodificar, un ID se repite o una jerarquía es
+//! inválida, la carga falla sin dejar entidades a medias en el mundo.
+
+use anyhow::{anyhow, bail, Result};
+use glam::{Quat, Vec3};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use super::{AmbientConfig, FogConfig};
+use crate::ecs::snapshot::{WorldSnapshot, SNAPSHOT_VERSION};
+use crate::ecs::{Component, ComponentType, ECSSystem, Entity, EntityId, TransformComponent};
+
+/// Punto de aparición del avatar
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnPoint {
+    /// Nombre del punto
+    pub name: String,
+    /// Posición
+    pub position: Vec3,
+    /// Orientación
+    pub rotation: Quat,
+}
+
+/// Ambiente de la escena
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneEnvironment {
+    /// Color de fondo
+    pub background_color: [f32; 4],
+    /// Niebla
+    pub fog: Option<FogConfig>,
+    /// Luz ambiente
+    pub ambient: AmbientConfig,
+    /// Gravedad
+    pub gravity: [f32; 3],
+}
+
+impl Default for SceneEnvironment {
+    fn default() -> Self {
+        Self {
+            background_color: [0.1, 0.1, 0.1, 1.0],
+            fog: None,
+            ambient: AmbientConfig {
+                color: [0.1, 0.1, 0.1],
+                intensity: 0.1,
+            },
+            gravity: [0.0, -9.81, 0.0],
+        }
+    }
+}
+
+/// Metadatos de la escena
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SceneMetadata {
+    /// Descripción
+    pub description: String,
+    /// Ambiente
+    pub environment: SceneEnvironment,
+    /// Textura del skybox
+    pub skybox: Option<String>,
+    /// Puntos de aparición
+    pub spawn_points: Vec<SpawnPoint>,
+}
+
+/// Definición serializable de una escena
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneDefinition {
+    /// Nombre de la escena
+    pub name: String,
+    /// Metadatos
+    pub metadata: SceneMetadata,
+    /// Entidades de la escena
+    pub snapshot: WorldSnapshot,
+}
+
+/// Entidad lista para crearse, con su ID definitivo y sus componentes
+pub struct PendingEntity {
+    /// Entidad sin componentes
+    pub entity: Entity,
+    /// Componentes decodificados
+    pub components: Vec<Box<dyn Component>>,
+}
+
+impl SceneDefinition {
+    /// Escena vacía
+    pub fn new(name: &str, metadata: SceneMetadata) -> Self {
+        Self {
+            name: name.to_string(),
+            metadata,
+            snapshot: WorldSnapshot::default(),
+        }
+    }
+
+    /// Escena con entidades capturadas del mundo
+    pub fn from_world(name: &str, metadata: SceneMetadata, world: &ECSSystem, entity_ids: &[EntityId]) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            metadata,
+            snapshot: world.snapshot_entities(entity_ids)?,
+        })
+    }
+
+    /// Buscar un punto de aparición
+    pub fn spawn_point(&self, name: &str) -> Option<&SpawnPoint> {
+        self.metadata.spawn_points.iter().find(|point| point.name == name)
+    }
+
+    /// Serializar la definición
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Leer una definición
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(data)?)
+    }
+
+    /// Decodificar y ordenar las entidades (padres antes que hijos) con IDs
+    /// nuevos de `reserve_id`. Las referencias de la jerarquía se traducen a
+    /// los IDs nuevos; un hijo que no pertenece a la escena se descarta y un
+    /// padre que no pertenece a ella es un error
+    pub fn instantiate(&self, mut reserve_id: impl FnMut() -> EntityId) -> Result<Vec<PendingEntity>> {
+        if self.snapshot.version != SNAPSHOT_VERSION {
+            bail!("Escena '{}': versión de instantánea {} no soportada", self.name, self.snapshot.version);
+        }
+
+        let mut index: HashMap<EntityId, usize> = HashMap::new();
+        for (i, entity) in self.snapshot.entities.iter().enumerate() {
+            if index.insert(entity.id, i).is_some() {
+                bail!("Escena '{}': entidad {} repetida", self.name, entity.id);
+            }
+        }
+
+        // Decodificar todo antes de reservar IDs
+        let mut transforms: Vec<Option<TransformComponent>> = Vec::with_capacity(index.len());
+        let mut others: Vec<Vec<Box<dyn Component>>> = Vec::with_capacity(index.len());
+        for entity in &self.snapshot.entities {
+            let mut transform = None;
+            let mut components = Vec::new();
+            for component in &entity.components {
+                if component.component_type == ComponentType::Transform {
+                    let decoded: TransformComponent = bincode::deserialize(&component.data)
+                        .map_err(|e| anyhow!("Escena '{}': transformación de la entidad {} inválida: {}", self.name, entity.id, e))?;
+                    transform = Some(decoded);
+                } else {
+                    components.push(component.restore()
+                        .map_err(|e| anyhow!("Escena '{}': componente de la entidad {} inválido: {}", self.name, entity.id, e))?);
+                }
+            }
+            transforms.push(transform);
+            others.push(components);
+        }
+
+        let parent_of = |i: usize| transforms[i].as_ref().and_then(|transform| transform.parent);
+        for (i, entity) in self.snapshot.entities.iter().enumerate() {
+            if let Some(parent) = parent_of(i) {
+                if !index.contains_key(&parent) {
+                    bail!("Escena '{}': la entidad {} tiene un padre desconocido ({})", self.name, entity.id, parent);
+                }
+            }
+        }
+
+        // Profundidad en la jerarquía; una cadena más larga que la escena es un ciclo
+        let mut depths = Vec::with_capacity(index.len());
+        for (i, entity) in self.snapshot.entities.iter().enumerate() {
+            let mut depth = 0;
+            let mut current = parent_of(i);
+            while let Some(parent) = current {
+                depth += 1;
+                if depth > index.len() {
+                    bail!("Escena '{}': jerarquía cíclica en la entidad {}", self.name, entity.id);
+                }
+                current = parent_of(index[&parent]);
+            }
+            depths.push(depth);
+        }
+
+        let ids: HashMap<EntityId, EntityId> = self.snapshot.entities.iter()
+            .map(|entity| (entity.id, reserve_id()))
+            .collect();
+
+        let mut order: Vec<usize> = (0..index.len()).collect();
+        order.sort_by_key(|&i| depths[i]);
+
+        let mut others: Vec<Option<Vec<Box<dyn Component>>>> = others.into_iter().map(Some).collect();
+        let pending = order.into_iter()
+            .map(|i| {
+                let snapshot = &self.snapshot.entities[i];
+                let mut components = others[i].take().unwrap_or_default();
+                if let Some(mut transform) = transforms[i].take() {
+                    transform.parent = transform.parent.map(|parent| ids[&parent]);
+                    transform.children = transform.children.iter().filter_map(|child| ids.get(child).copied()).collect();
+                    components.insert(0, Box::new(transform));
+                }
+                PendingEntity {
+                    entity: snapshot.to_entity(ids[&snapshot.id]),
+                    components,
+                }
+            })
+            .collect();
+
+        Ok(pending)
+    }
+}
@@ -2,13 +2,33 @@
 //! 
 //! Sistema de gestión de escenas 3D para el metaverso.
 //! Proporciona gestión de objetos, cámaras, luces y efectos.
-
-use std::collections::HashMap;
+//! `SceneSystem` carga escenas (`definition`) en el ECS repartiendo sus
+//! entidades entre varios frames, y mueve el avatar entre puntos de aparición
+//! con `transition`. Con `enable_partition` el mundo se parte en islas con
+//! su propio ECS, unidas por portales (`partition`). Los avatares generados
+//! a partir de un wallet se guardan por dirección para no recalcularlos.
+//! En nativo, `persistence` guarda las entidades de cada isla en una base de
+//! datos sled para mundos persistentes.
+
+pub mod definition;
+pub mod partition;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod persistence;
+pub mod transition;
+
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
 use tracing::{info, error, debug};
 
+use crate::crypto::avatar::{self, AvatarConfig};
+use crate::crypto::NFTMetadata;
+use crate::ecs::{self, ECSConfig, ECSSystem, EntityId, TransformComponent};
+use definition::{PendingEntity, SceneDefinition, SceneEnvironment};
+use partition::{IslandId, Portal, WorldPartition};
+use transition::{SceneTransition, TransitionKind};
+
 /// Escena principal del metaverso
 pub struct Scene {
     /// ID de la escena
@@ -812,6 +832,14 @@ pub struct SceneConfig {
     pub physics_config: PhysicsSceneConfig,
     /// Configuración de audio
     pub audio_config: AudioSceneConfig,
+    /// Entidades que se crean por frame al cargar una escena
+    #[serde(default = "default_streaming_entities_per_frame")]
+    pub streaming_entities_per_frame: usize,
+}
+
+/// Entidades por frame por defecto
+fn default_streaming_entities_per_frame() -> usize {
+    64
 }
 
 /// Configuración de niebla
@@ -963,6 +991,7 @@ impl Scene {
                         },
                     },
                 },
+                streaming_entities_per_frame: default_streaming_entities_per_frame(),
             },
             state: SceneState {
                 active: true,
@@ -1222,6 +1251,333 @@ impl Scene {
             active_objects: objects.values().filter(|obj| obj.active).count(),
             visible_objects: objects.values().filter(|obj| obj.visible).count(),
             scene_time: self.state.time,
+            loaded_scenes: 0,
+            pending_entities: 0,
+            islands: 0,
+        }
+    }
+}
+
+/// Modo de carga de una escena
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Descarga las escenas cargadas antes de cargar la nueva
+    Single,
+    /// Carga la escena junto a las que ya están
+    Additive,
+}
+
+/// Escena cargada en el ECS
+#[derive(Debug, Clone)]
+pub struct LoadedScene {
+    /// Nombre de la escena
+    pub name: String,
+    /// Entidades ya creadas
+    pub entities: Vec<EntityId>,
+    /// Entidades pendientes de crear
+    pub pending: usize,
+}
+
+/// Sistema de escenas
+pub struct SceneSystem {
+    /// Configuración del sistema
+    config: SceneConfig,
+    /// Escenas registradas
+    definitions: HashMap<String, SceneDefinition>,
+    /// Escenas cargadas, en orden de carga (la primera da el ambiente)
+    loaded: Vec<LoadedScene>,
+    /// Entidades pendientes de crear, con su escena
+    streaming: VecDeque<(String, PendingEntity)>,
+    /// Transición del avatar en curso
+    transition: Option<SceneTransition>,
+    /// Mundo partido en islas (None con un único mundo)
+    partition: Option<WorldPartition>,
+    /// Avatares generados por dirección de wallet (en minúsculas)
+    avatars: HashMap<String, AvatarConfig>,
+    /// Tiempo desde la inicialización
+    scene_time: f32,
+    /// Estado del sistema
+    running: bool,
+}
+
+impl SceneSystem {
+    /// Crea el sistema de escenas
+    pub fn new(config: &SceneConfig) -> Self {
+        info!("🌍 Inicializando sistema de escenas...");
+
+        Self {
+            config: config.clone(),
+            definitions: HashMap::new(),
+            loaded: Vec::new(),
+            streaming: VecDeque::new(),
+            transition: None,
+            partition: None,
+            avatars: HashMap::new(),
+            scene_time: 0.0,
+            running: false,
+        }
+    }
+
+    /// Inicializa el sistema
+    pub async fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.running = true;
+
+        info!("✅ Sistema de escenas inicializado correctamente");
+        Ok(())
+    }
+
+    /// Actualiza el tiempo de las escenas
+    pub async fn update(&mut self, delta_time: f32) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.running {
+            return Ok(());
+        }
+
+        self.scene_time += delta_time;
+        if let Some(partition) = &mut self.partition {
+            partition.update(delta_time).await?;
+        }
+        Ok(())
+    }
+
+    /// Parte el mundo en `island_count` islas, cada una con su ECS
+    pub async fn enable_partition(&mut self, island_count: u32, ecs_config: &ECSConfig) -> anyhow::Result<()> {
+        if let Some(partition) = &mut self.partition {
+            partition.cleanup().await?;
+        }
+        self.partition = Some(WorldPartition::new(island_count, ecs_config).await?);
+        Ok(())
+    }
+
+    /// Partición en islas
+    pub fn partition(&self) -> Option<&WorldPartition> {
+        self.partition.as_ref()
+    }
+
+    /// Partición en islas, para registrar portales y jugadores
+    pub fn partition_mut(&mut self) -> Option<&mut WorldPartition> {
+        self.partition.as_mut()
+    }
+
+    /// ECS de una isla
+    pub fn get_island(&self, id: IslandId) -> Option<&ECSSystem> {
+        self.partition.as_ref()?.island(id)
+    }
+
+    /// Lleva una entidad de la isla `from` a la salida del portal en `to`
+    pub async fn teleport_entity(&mut self, entity: EntityId, from: IslandId, to: IslandId, portal: &Portal) -> anyhow::Result<()> {
+        let partition = self.partition.as_mut()
+            .ok_or_else(|| anyhow::anyhow!("El mundo no está partido en islas"))?;
+        partition.teleport_entity(entity, from, to, portal).await
+    }
+
+    /// Registra una definición de escena (reemplaza la del mismo nombre)
+    pub fn register_scene(&mut self, definition: SceneDefinition) {
+        debug!("🌍 Escena registrada: {}", definition.name);
+        self.definitions.insert(definition.name.clone(), definition);
+    }
+
+    /// Lee una definición de escena de disco y la registra. Devuelve su nombre
+    pub async fn load_scene_file(&mut self, path: &str) -> anyhow::Result<String> {
+        let data = tokio::fs::read(path).await?;
+        let definition = SceneDefinition::from_bytes(&data)?;
+        let name = definition.name.clone();
+        self.register_scene(definition);
+        Ok(name)
+    }
+
+    /// Definición de una escena registrada
+    pub fn scene_definition(&self, name: &str) -> Option<&SceneDefinition> {
+        self.definitions.get(name)
+    }
+
+    /// Carga una escena registrada. Toda la escena se valida antes de tocar
+    /// el mundo; sus entidades se crean después poco a poco con
+    /// `stream_entities`
+    pub async fn load_scene(&mut self, name: &str, mode: LoadMode, world: &mut ECSSystem) -> anyhow::Result<()> {
+        let definition = self.definitions.get(name)
+            .ok_or_else(|| anyhow::anyhow!("Escena '{}' no registrada", name))?;
+        if mode == LoadMode::Additive && self.is_loaded(name) {
+            anyhow::bail!("La escena '{}' ya está cargada", name);
+        }
+
+        let pending = definition.instantiate(|| world.reserve_entity_id())?;
+
+        if mode == LoadMode::Single {
+            let names: Vec<String> = self.loaded.iter().map(|scene| scene.name.clone()).collect();
+            for loaded in names {
+                self.unload_scene(&loaded, world).await?;
+            }
+        }
+
+        info!("🌍 Cargando escena {} ({} entidades)", name, pending.len());
+        self.loaded.push(LoadedScene {
+            name: name.to_string(),
+            entities: Vec::with_capacity(pending.len()),
+            pending: pending.len(),
+        });
+        self.streaming.extend(pending.into_iter().map(|entity| (name.to_string(), entity)));
+        Ok(())
+    }
+
+    /// Avatar de un wallet: el de la caché o uno nuevo generado a partir de
+    /// sus NFTs
+    pub fn avatar_for_wallet(&mut self, wallet_address: &str, nfts: &[NFTMetadata]) -> &AvatarConfig {
+        let key = wallet_address.trim().to_ascii_lowercase();
+        self.avatars.entry(key).or_insert_with(|| {
+            debug!("🧍 Generando avatar de {}", wallet_address);
+            avatar::generate_avatar(wallet_address, nfts)
+        })
+    }
+
+    /// Avatar de un wallet ya generado
+    pub fn cached_avatar(&self, wallet_address: &str) -> Option<&AvatarConfig> {
+        self.avatars.get(&wallet_address.trim().to_ascii_lowercase())
+    }
+
+    /// Olvida el avatar de un wallet (p. ej. cuando cambian sus NFTs)
+    pub fn invalidate_avatar(&mut self, wallet_address: &str) -> Option<AvatarConfig> {
+        self.avatars.remove(&wallet_address.trim().to_ascii_lowercase())
+    }
+
+    /// Descarga una escena: destruye sus entidades y cancela las pendientes
+    pub async fn unload_scene(&mut self, name: &str, world: &mut ECSSystem) -> anyhow::Result<()> {
+        let position = self.loaded.iter().position(|scene| scene.name == name)
+            .ok_or_else(|| anyhow::anyhow!("La escena '{}' no está cargada", name))?;
+        let scene = self.loaded.remove(position);
+        self.streaming.retain(|(scene_name, _)| scene_name != name);
+        for entity_id in scene.entities {
+            world.destroy_entity(entity_id).await?;
+        }
+
+        info!("🌍 Escena descargada: {}", name);
+        Ok(())
+    }
+
+    /// Crea en el ECS hasta `streaming_entities_per_frame` entidades
+    /// pendientes. Devuelve cuántas se crearon
+    pub async fn stream_entities(&mut self, world: &mut ECSSystem) -> anyhow::Result<usize> {
+        let budget = self.config.streaming_entities_per_frame.max(1);
+        let mut spawned = 0;
+        while spawned < budget {
+            let Some((scene_name, pending)) = self.streaming.pop_front() else {
+                break;
+            };
+            let entity_id = pending.entity.id;
+            world.spawn_entity(pending.entity).await?;
+            for component in pending.components {
+                world.add_component(entity_id, component).await?;
+            }
+            if let Some(scene) = self.loaded.iter_mut().find(|scene| scene.name == scene_name) {
+                scene.entities.push(entity_id);
+                scene.pending = scene.pending.saturating_sub(1);
+                if scene.pending == 0 {
+                    info!("✅ Escena cargada: {}", scene.name);
+                }
+            }
+            spawned += 1;
+        }
+        Ok(spawned)
+    }
+
+    /// Empieza a llevar el avatar al punto de aparición `spawn_point` de la
+    /// escena `scene`
+    pub fn start_transition(&mut self, avatar: EntityId, scene: &str, spawn_point: &str, kind: TransitionKind) -> anyhow::Result<()> {
+        let target = self.definitions.get(scene)
+            .ok_or_else(|| anyhow::anyhow!("Escena '{}' no registrada", scene))?
+            .spawn_point(spawn_point)
+            .ok_or_else(|| anyhow::anyhow!("La escena '{}' no tiene el punto de aparición '{}'", scene, spawn_point))?
+            .clone();
+        debug!("🌍 Transición del avatar {} a {}/{}", avatar, scene, spawn_point);
+        self.transition = Some(SceneTransition::new(kind, avatar, target));
+        Ok(())
+    }
+
+    /// Avanza la transición en curso y mueve el avatar cuando toca
+    pub async fn update_transition(&mut self, world: &mut ECSSystem, delta_time: f32) -> anyhow::Result<()> {
+        let Some(transition) = &mut self.transition else {
+            return Ok(());
+        };
+        let avatar = transition.avatar;
+        if let Some(target) = transition.advance(delta_time).cloned() {
+            if let Some(mut transform) = world.get_component::<TransformComponent>(avatar, ecs::ComponentType::Transform) {
+                transform.position = target.position;
+                transform.rotation = target.rotation;
+                transform.matrix = glam::Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position);
+                world.update_component(avatar, Box::new(transform)).await?;
+            }
+        }
+        if transition.finished() {
+            self.transition = None;
+        }
+        Ok(())
+    }
+
+    /// Transición en curso
+    pub fn transition(&self) -> Option<&SceneTransition> {
+        self.transition.as_ref()
+    }
+
+    /// Opacidad del fundido de la transición (0 sin transición)
+    pub fn fade_amount(&self) -> f32 {
+        self.transition.as_ref().map_or(0.0, |transition| transition.fade_amount())
+    }
+
+    /// La escena está cargada (o cargándose)
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.loaded.iter().any(|scene| scene.name == name)
+    }
+
+    /// Escenas cargadas, en orden de carga
+    pub fn loaded_scenes(&self) -> &[LoadedScene] {
+        &self.loaded
+    }
+
+    /// Entidades ya creadas de una escena
+    pub fn scene_entities(&self, name: &str) -> Option<&[EntityId]> {
+        self.loaded.iter().find(|scene| scene.name == name).map(|scene| scene.entities.as_slice())
+    }
+
+    /// Ambiente activo: el de la primera escena cargada
+    pub fn environment(&self) -> Option<&SceneEnvironment> {
+        let scene = self.loaded.first()?;
+        self.definitions.get(&scene.name).map(|definition| &definition.metadata.environment)
+    }
+
+    /// Limpia el sistema
+    pub async fn cleanup(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        info!("🧹 Limpiando sistema de escenas...");
+
+        self.running = false;
+        self.loaded.clear();
+        self.streaming.clear();
+        self.transition = None;
+        self.avatars.clear();
+        if let Some(mut partition) = self.partition.take() {
+            partition.cleanup().await?;
+        }
+
+        info!("✅ Sistema de escenas limpiado correctamente");
+        Ok(())
+    }
+
+    /// Obtiene el estado de salud del sistema
+    pub async fn health_check(&self) -> bool {
+        self.running
+    }
+
+    /// Obtiene estadísticas del sistema
+    pub fn get_stats(&self) -> SceneStats {
+        SceneStats {
+            object_count: self.loaded.iter().map(|scene| scene.entities.len()).sum(),
+            camera_count: 0,
+            light_count: 0,
+            effect_count: 0,
+            active_objects: 0,
+            visible_objects: 0,
+            scene_time: self.scene_time,
+            loaded_scenes: self.loaded.len(),
+            pending_entities: self.streaming.len(),
+            islands: self.partition.as_ref().map_or(0, |partition| partition.island_ids().len()),
         }
     }
 }
@@ -1243,4 +1599,147 @@ pub struct SceneStats {
     pub visible_objects: usize,
     /// Tiempo de la escena
     pub scene_time: f32,
-}
\ No newline at end of file
+    /// Escenas cargadas en el ECS
+    pub loaded_scenes: usize,
+    /// Entidades pendientes de crear
+    pub pending_entities: usize,
+    /// Islas de la partición (0 sin partición)
+    pub islands: usize,
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::definition::SceneMetadata;
+    use crate::ecs::snapshot::{ComponentSnapshot, EntitySnapshot, WorldSnapshot};
+    use crate::ecs::{ComponentConfig, EntityConfig, EntityState, OptimizationConfig, SystemConfig};
+    use glam::{Mat4, Quat, Vec3};
+    use std::collections::HashSet;
+
+    fn ecs_config() -> ECSConfig {
+        ECSConfig {
+            enabled: true,
+            entity_config: EntityConfig { max_entities: 100, entity_pool: false, id_reuse: false },
+            component_config: ComponentConfig {
+                max_components_per_entity: 16,
+                component_cache: false,
+                auto_serialization: false,
+            },
+            system_config: SystemConfig { parallel_execution: false, system_priority: true, hot_reloading: false },
+            optimization_config: OptimizationConfig { cache_friendly: true, memory_pooling: false, batch_processing: false },
+            max_parallel_systems: 1,
+            system_hot_reloading: false,
+            systems_directory: "systems".into(),
+        }
+    }
+
+    /// Sistema de escenas que crea todas las entidades pendientes en un frame
+    fn scene_system() -> SceneSystem {
+        let config = Scene::new("config", "config").config;
+        SceneSystem::new(&SceneConfig { streaming_entities_per_frame: 100, ..config })
+    }
+
+    /// Escena de `count` entidades en fila; la primera es padre de la segunda
+    fn definition(name: &str, count: u64) -> SceneDefinition {
+        let entities = (1..=count)
+            .map(|id| {
+                let position = Vec3::new(id as f32, 0.0, 0.0);
+                let transform = TransformComponent {
+                    position,
+                    rotation: Quat::IDENTITY,
+                    scale: Vec3::ONE,
+                    matrix: Mat4::from_translation(position),
+                    parent: (id == 2).then_some(1),
+                    children: if id == 1 && count > 1 { vec![2] } else { Vec::new() },
+                };
+                EntitySnapshot {
+                    id,
+                    name: format!("{}-{}", name, id),
+                    state: EntityState { active: true, visible: true, selected: false, locked: false },
+                    metadata: HashMap::new(),
+                    components: vec![ComponentSnapshot::capture(&transform).unwrap()],
+                }
+            })
+            .collect();
+        SceneDefinition {
+            name: name.to_string(),
+            metadata: SceneMetadata::default(),
+            snapshot: WorldSnapshot { entities, ..WorldSnapshot::default() },
+        }
+    }
+
+    /// Crear las entidades pendientes y aplicarlas al mundo
+    async fn stream_all(scenes: &mut SceneSystem, world: &mut ECSSystem) {
+        while scenes.stream_entities(world).await.unwrap() > 0 {}
+        world.flush_commands();
+    }
+
+    fn transforms(world: &ECSSystem) -> HashSet<EntityId> {
+        world.get_entities_with_component(ecs::ComponentType::Transform).into_iter().collect()
+    }
+
+    #[tokio::test]
+    async fn additive_scenes_load_the_union_and_unload_one() {
+        let mut world = ECSSystem::new(ecs_config());
+        let mut scenes = scene_system();
+        scenes.register_scene(definition("plaza", 3));
+        scenes.register_scene(definition("museo", 2));
+
+        scenes.load_scene("plaza", LoadMode::Additive, &mut world).await.unwrap();
+        scenes.load_scene("museo", LoadMode::Additive, &mut world).await.unwrap();
+        stream_all(&mut scenes, &mut world).await;
+
+        let plaza: HashSet<EntityId> = scenes.scene_entities("plaza").unwrap().iter().copied().collect();
+        let museo: HashSet<EntityId> = scenes.scene_entities("museo").unwrap().iter().copied().collect();
+        assert_eq!(plaza.len(), 3);
+        assert_eq!(museo.len(), 2);
+        assert!(plaza.is_disjoint(&museo));
+        let union: HashSet<EntityId> = plaza.union(&museo).copied().collect();
+        assert_eq!(transforms(&world), union);
+        assert!(union.iter().all(|&id| world.get_entity(id).is_some()));
+
+        // La jerarquía se traduce a los IDs nuevos de cada escena
+        for scene in ["plaza", "museo"] {
+            let ids = scenes.scene_entities(scene).unwrap();
+            let child = ids.iter()
+                .filter_map(|&id| world.get_component::<TransformComponent>(id, ecs::ComponentType::Transform))
+                .find(|transform| transform.parent.is_some())
+                .unwrap();
+            assert!(ids.contains(&child.parent.unwrap()));
+        }
+
+        scenes.unload_scene("museo", &mut world).await.unwrap();
+        world.flush_commands();
+        assert!(!scenes.is_loaded("museo"));
+        assert!(scenes.is_loaded("plaza"));
+        assert_eq!(transforms(&world), plaza);
+        assert!(museo.iter().all(|&id| world.get_entity(id).is_none()));
+    }
+
+    #[tokio::test]
+    async fn corrupt_scene_leaves_no_partial_entities() {
+        let mut world = ECSSystem::new(ecs_config());
+        let mut scenes = scene_system();
+        scenes.register_scene(definition("plaza", 3));
+        scenes.load_scene("plaza", LoadMode::Additive, &mut world).await.unwrap();
+        stream_all(&mut scenes, &mut world).await;
+        let before = transforms(&world);
+
+        // Las primeras entidades son válidas; la última no se puede decodificar
+        let mut corrupt = definition("ruinas", 4);
+        let data = &mut corrupt.snapshot.entities[3].components[0].data;
+        data.truncate(data.len() / 2);
+        scenes.register_scene(corrupt);
+
+        assert!(scenes.load_scene("ruinas", LoadMode::Additive, &mut world).await.is_err());
+        assert!(!scenes.is_loaded("ruinas"));
+        assert_eq!(scenes.stream_entities(&mut world).await.unwrap(), 0);
+        world.flush_commands();
+        assert_eq!(transforms(&world), before);
+
+        // Una carga simple fallida tampoco descarga las escenas anteriores
+        assert!(scenes.load_scene("ruinas", LoadMode::Single, &mut world).await.is_err());
+        world.flush_commands();
+        assert!(scenes.is_loaded("plaza"));
+        assert_eq!(transforms(&world), before);
+    }
+}
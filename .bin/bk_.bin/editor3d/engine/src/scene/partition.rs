@@ -0,0 +1,407 @@
+//! # Partición del mundo en islas
+//!
+//! Cada isla del metaverso es un sub-mundo con su propio `ECSSystem`; las
+//! islas no comparten entidades, así que sus sistemas se ejecutan en paralelo
+//! en el pool de jobs (uno por isla) sin bloqueos entre ellas. Dentro de una
+//! isla los sistemas corren en secuencia para no anidar pools.
+//!
+//! Lo que es de todas las islas (hora del servidor, iluminación global) vive
+//! en el mundo raíz (`RootWorld`). Solo la partición lo modifica, entre
+//! frames; los sistemas de las islas lo leen con `ECSSystem::root_world`.
+//!
+//! Los portales llevan entidades de una isla a otra. Las entidades viajeras
+//! (los jugadores) se registran con `track_player`; cuando una se acerca a un
+//! portal de su isla se captura con `snapshot_entities`, viaja serializada
+//! como `WorldSnapshot` y se recrea con el mismo ID en la isla de destino,
+//! en la posición de salida del portal. Una entidad que llega por un portal
+//! no vuelve a activar ninguno hasta que sale de su radio.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use glam::Vec3;
+use serde::{Serialize, Deserialize};
+use tracing::{info, error, debug};
+
+use crate::ecs::{self, ECSConfig, ECSSystem, EntityId, TransformComponent};
+use crate::ecs::snapshot::WorldSnapshot;
+use crate::utils::JobSystem;
+use super::definition::SceneEnvironment;
+
+/// ID de isla
+pub type IslandId = u32;
+
+/// Radio de activación de un portal por defecto
+fn default_portal_radius() -> f32 {
+    1.5
+}
+
+/// Paso entre dos islas
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Portal {
+    /// Isla de entrada
+    pub from_island: IslandId,
+    /// Posición de entrada
+    pub from_position: Vec3,
+    /// Isla de salida
+    pub to_island: IslandId,
+    /// Posición de salida
+    pub to_position: Vec3,
+    /// Distancia a la entrada a la que se activa
+    #[serde(default = "default_portal_radius")]
+    pub radius: f32,
+}
+
+impl Portal {
+    /// La posición está dentro del radio de entrada
+    pub fn contains(&self, position: Vec3) -> bool {
+        position.distance_squared(self.from_position) <= self.radius * self.radius
+    }
+}
+
+/// Singletons compartidos por todas las islas
+#[derive(Debug, Clone, Default)]
+pub struct RootWorld {
+    /// Hora del servidor en segundos
+    pub server_time: f64,
+    /// Iluminación y ambiente globales
+    pub environment: SceneEnvironment,
+}
+
+/// Mundo raíz compartido; las islas solo lo leen
+pub type SharedRootWorld = Arc<RwLock<RootWorld>>;
+
+/// Mundo partido en islas
+pub struct WorldPartition {
+    /// Sub-mundo de cada isla
+    islands: HashMap<IslandId, ECSSystem>,
+    /// Singletons compartidos
+    root: SharedRootWorld,
+    /// Portales entre islas
+    portal_registry: Vec<Portal>,
+    /// Entidades que pueden cruzar portales, con su isla actual
+    players: HashMap<EntityId, IslandId>,
+    /// Entidades recién llegadas que aún no han salido del portal
+    arrived: HashSet<EntityId>,
+    /// Pool para actualizar las islas en paralelo
+    jobs: Option<JobSystem>,
+}
+
+impl WorldPartition {
+    /// Crea `island_count` islas (IDs 0..island_count) con la configuración
+    /// de ECS dada
+    pub async fn new(island_count: u32, config: &ECSConfig) -> anyhow::Result<Self> {
+        let root: SharedRootWorld = Arc::new(RwLock::new(RootWorld::default()));
+
+        // El paralelismo va entre islas, no entre los sistemas de cada una
+        let mut island_config = config.clone();
+        island_config.system_config.parallel_execution = false;
+
+        let mut islands = HashMap::with_capacity(island_count as usize);
+        for id in 0..island_count {
+            let mut world = ECSSystem::new(island_config.clone());
+            world.set_root_world(Some(root.clone()));
+            world.initialize().await?;
+            islands.insert(id, world);
+        }
+
+        // El hilo que actualiza la partición también toma jobs mientras espera
+        let workers = (island_count as usize).min(config.max_parallel_systems).saturating_sub(1);
+        let jobs = (workers > 0).then(|| JobSystem::new(workers));
+
+        info!("🌍 Mundo partido en {} islas", island_count);
+        Ok(Self {
+            islands,
+            root,
+            portal_registry: Vec::new(),
+            players: HashMap::new(),
+            arrived: HashSet::new(),
+            jobs,
+        })
+    }
+
+    /// Sub-mundo de una isla
+    pub fn island(&self, id: IslandId) -> Option<&ECSSystem> {
+        self.islands.get(&id)
+    }
+
+    /// Sub-mundo de una isla, para crear o modificar sus entidades
+    pub fn island_mut(&mut self, id: IslandId) -> Option<&mut ECSSystem> {
+        self.islands.get_mut(&id)
+    }
+
+    /// IDs de las islas
+    pub fn island_ids(&self) -> Vec<IslandId> {
+        let mut ids: Vec<IslandId> = self.islands.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Mundo raíz compartido
+    pub fn root_world(&self) -> &SharedRootWorld {
+        &self.root
+    }
+
+    /// Cambia la iluminación y el ambiente globales
+    pub fn set_environment(&mut self, environment: SceneEnvironment) {
+        self.root.write().unwrap().environment = environment;
+    }
+
+    /// Registra un portal. Sus dos islas deben existir
+    pub fn add_portal(&mut self, portal: Portal) -> anyhow::Result<()> {
+        for island in [portal.from_island, portal.to_island] {
+            if !self.islands.contains_key(&island) {
+                anyhow::bail!("La isla {} no existe", island);
+            }
+        }
+        self.portal_registry.push(portal);
+        Ok(())
+    }
+
+    /// Portales registrados
+    pub fn portals(&self) -> &[Portal] {
+        &self.portal_registry
+    }
+
+    /// Permite que una entidad de `island` cruce portales
+    pub fn track_player(&mut self, entity_id: EntityId, island: IslandId) {
+        self.players.insert(entity_id, island);
+    }
+
+    /// Deja de seguir una entidad
+    pub fn untrack_player(&mut self, entity_id: EntityId) {
+        self.players.remove(&entity_id);
+        self.arrived.remove(&entity_id);
+    }
+
+    /// Isla en la que está una entidad seguida
+    pub fn player_island(&self, entity_id: EntityId) -> Option<IslandId> {
+        self.players.get(&entity_id).copied()
+    }
+
+    /// Avanza la hora del servidor, actualiza todas las islas en paralelo y
+    /// después resuelve los portales
+    pub async fn update(&mut self, delta_time: f32) -> anyhow::Result<()> {
+        self.root.write().unwrap().server_time += delta_time as f64;
+
+        let mut worlds: Vec<(IslandId, &mut ECSSystem)> = self.islands.iter_mut().map(|(&id, world)| (id, world)).collect();
+        let mut errors: Vec<Option<String>> = vec![None; worlds.len()];
+        match &self.jobs {
+            Some(jobs) if worlds.len() > 1 => {
+                let batch = worlds.iter_mut()
+                    .zip(errors.iter_mut())
+                    .map(|((_, world), error)| {
+                        Box::new(move || {
+                            *error = futures::executor::block_on(world.update(delta_time)).err().map(|e| e.to_string());
+                        }) as Box<dyn FnOnce() + Send + '_>
+                    })
+                    .collect();
+                // Espera a que terminen todas las islas
+                jobs.execute_batch(batch);
+            }
+            _ => {
+                for ((_, world), error) in worlds.iter_mut().zip(errors.iter_mut()) {
+                    *error = world.update(delta_time).await.err().map(|e| e.to_string());
+                }
+            }
+        }
+        for ((id, _), error) in worlds.iter().zip(errors) {
+            if let Some(e) = error {
+                error!("Error actualizando la isla {}: {}", id, e);
+            }
+        }
+
+        self.check_portals().await
+    }
+
+    /// Lleva a su destino a los jugadores que están en un portal
+    async fn check_portals(&mut self) -> anyhow::Result<()> {
+        let mut crossings = Vec::new();
+        for (&entity_id, &island) in &self.players {
+            let Some(world) = self.islands.get(&island) else {
+                continue;
+            };
+            let Some(transform) = world.get_component::<TransformComponent>(entity_id, ecs::ComponentType::Transform) else {
+                continue;
+            };
+            let mut portals = self.portal_registry.iter()
+                .filter(|portal| portal.from_island == island && portal.contains(transform.position));
+            match portals.next() {
+                Some(portal) if !self.arrived.contains(&entity_id) => crossings.push((entity_id, portal.clone())),
+                Some(_) => {}
+                None => {
+                    self.arrived.remove(&entity_id);
+                }
+            }
+        }
+
+        for (entity_id, portal) in crossings {
+            self.teleport_entity(entity_id, portal.from_island, portal.to_island, &portal).await?;
+        }
+        Ok(())
+    }
+
+    /// Mueve una entidad de la isla `from` a la isla `to` y la deja en la
+    /// salida del portal. La entidad conserva su ID y sus componentes
+    pub async fn teleport_entity(&mut self, entity_id: EntityId, from: IslandId, to: IslandId, portal: &Portal) -> anyhow::Result<()> {
+        if from == to {
+            anyhow::bail!("La isla de origen y la de destino son la misma ({})", from);
+        }
+        if !self.islands.contains_key(&to) {
+            anyhow::bail!("La isla {} no existe", to);
+        }
+        let source = self.islands.get_mut(&from)
+            .ok_or_else(|| anyhow::anyhow!("La isla {} no existe", from))?;
+
+        // Los comandos pendientes (p. ej. la entidad recién creada) deben
+        // estar aplicados antes de capturarla
+        source.flush_commands();
+        let data = source.snapshot_entities(&[entity_id])?.to_bytes()?;
+        let snapshot = WorldSnapshot::from_bytes(&data)?;
+        let Some(entity) = snapshot.entities.first() else {
+            anyhow::bail!("La entidad {} no está en la isla {}", entity_id, from);
+        };
+        source.destroy_entity(entity_id).await?;
+
+        let target = self.islands.get_mut(&to).expect("isla de destino comprobada");
+        target.spawn_entity(entity.to_entity(entity_id)).await?;
+        for component in &entity.components {
+            let mut component = component.restore()?;
+            if let Some(transform) = component.as_any_mut().downcast_mut::<TransformComponent>() {
+                transform.position = portal.to_position;
+                transform.matrix = glam::Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position);
+            }
+            target.add_component(entity_id, component).await?;
+        }
+
+        if self.players.contains_key(&entity_id) {
+            self.players.insert(entity_id, to);
+            self.arrived.insert(entity_id);
+        }
+        debug!("🌍 Entidad {} de la isla {} a la isla {}", entity_id, from, to);
+        Ok(())
+    }
+
+    /// Entidades por isla
+    pub fn entity_counts(&self) -> HashMap<IslandId, usize> {
+        self.islands.iter().map(|(&id, world)| (id, world.get_stats().entity_count)).collect()
+    }
+
+    /// Limpia todas las islas
+    pub async fn cleanup(&mut self) -> anyhow::Result<()> {
+        for world in self.islands.values_mut() {
+            world.cleanup().await?;
+        }
+        self.players.clear();
+        self.arrived.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::{
+        CommandBuffer, ComponentConfig, ComponentType, EntityConfig, OptimizationConfig, SystemConfig,
+    };
+    use glam::{Mat4, Quat};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const ISLANDS: u32 = 7;
+    const ENTITIES_PER_ISLAND: usize = 10_000;
+    const FRAMES: usize = 3;
+
+    fn ecs_config() -> ECSConfig {
+        ECSConfig {
+            enabled: true,
+            entity_config: EntityConfig { max_entities: ENTITIES_PER_ISLAND, entity_pool: false, id_reuse: false },
+            component_config: ComponentConfig {
+                max_components_per_entity: 16,
+                component_cache: false,
+                auto_serialization: false,
+            },
+            system_config: SystemConfig { parallel_execution: true, system_priority: true, hot_reloading: false },
+            optimization_config: OptimizationConfig { cache_friendly: true, memory_pooling: false, batch_processing: false },
+            max_parallel_systems: ISLANDS as usize,
+            system_hot_reloading: false,
+            systems_directory: "systems".into(),
+        }
+    }
+
+    /// Sistema que cuenta las transformaciones que ve en su isla y comprueba
+    /// que el mundo raíz es legible desde ella
+    struct CountingSystem {
+        transforms: Arc<AtomicUsize>,
+        root_reads: Arc<AtomicUsize>,
+    }
+
+    impl ecs::ECSSystem for CountingSystem {
+        fn execute(&self, world: &ECSSystem, _commands: &mut CommandBuffer) -> anyhow::Result<()> {
+            let count = world.get_entities_with_component(ComponentType::Transform).len();
+            self.transforms.fetch_add(count, Ordering::Relaxed);
+            if world.root_world().is_some_and(|root| root.server_time > 0.0) {
+                self.root_reads.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(())
+        }
+
+        fn get_priority(&self) -> u32 {
+            50
+        }
+
+        fn get_name(&self) -> &str {
+            "CountingSystem"
+        }
+    }
+
+    #[tokio::test]
+    async fn seven_islands_update_ten_thousand_entities_each() {
+        let mut partition = WorldPartition::new(ISLANDS, &ecs_config()).await.unwrap();
+        let mut counters = Vec::new();
+
+        for island in partition.island_ids() {
+            let world = partition.island_mut(island).unwrap();
+            let counter = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+            world.add_system(Box::new(CountingSystem { transforms: counter.0.clone(), root_reads: counter.1.clone() }));
+            counters.push((island, counter));
+
+            for i in 0..ENTITIES_PER_ISLAND {
+                let entity = world.create_entity(format!("isla{}-{}", island, i)).await.unwrap();
+                let transform = TransformComponent {
+                    position: Vec3::new(i as f32, island as f32, 0.0),
+                    rotation: Quat::IDENTITY,
+                    scale: Vec3::ONE,
+                    matrix: Mat4::IDENTITY,
+                    parent: None,
+                    children: Vec::new(),
+                };
+                world.add_component(entity, Box::new(transform)).await.unwrap();
+            }
+        }
+
+        for _ in 0..FRAMES {
+            partition.update(1.0 / 60.0).await.unwrap();
+        }
+
+        let counts = partition.entity_counts();
+        assert_eq!(counts.len(), ISLANDS as usize);
+        assert!(counts.values().all(|&count| count == ENTITIES_PER_ISLAND), "{:?}", counts);
+        for (island, (transforms, root_reads)) in &counters {
+            assert_eq!(transforms.load(Ordering::Relaxed), FRAMES * ENTITIES_PER_ISLAND, "isla {}", island);
+            assert_eq!(root_reads.load(Ordering::Relaxed), FRAMES, "isla {}", island);
+
+            // El TransformSystem de cada isla recalculó todas sus matrices
+            let world = partition.island(*island).unwrap();
+            let entities = world.get_entities_with_component(ComponentType::Transform);
+            assert_eq!(entities.len(), ENTITIES_PER_ISLAND);
+            for entity in entities {
+                let transform = world.get_component::<TransformComponent>(entity, ComponentType::Transform).unwrap();
+                assert_eq!(transform.position.y, *island as f32, "la entidad {} cambió de isla", entity);
+                assert_eq!(transform.matrix, Mat4::from_translation(transform.position));
+            }
+        }
+        let server_time = partition.root_world().read().unwrap().server_time;
+        assert!((server_time - FRAMES as f64 / 60.0).abs() < 1e-6);
+
+        partition.cleanup().await.unwrap();
+    }
+}
@@ -0,0 +1,466 @@
+//! # Persistencia de escenas
+//!
+//! Base de datos binaria (sled) de mundos persistentes. Cada entidad se
+//! guarda bajo la clave `(isla, entidad)` en big endian, de modo que las
+//! entidades de una isla quedan contiguas y sus componentes se recorren por
+//! prefijo:
+//!
+//! - `entities`: nombre, estado, metadatos y tipos de componentes.
+//! - `components`: bytes de `Component::serialize` de cada componente, con
+//!   el tipo (bincode) tras la clave de la entidad.
+//! - `spatial`: índice espacial por celdas de `CELL_SIZE` metros.
+//!
+//! Los cambios se acumulan en lotes y `flush` los confirma en una sola
+//! transacción sobre los tres árboles; hasta entonces `load_entity` y
+//! `load_region` ven el estado confirmado. Al guardar solo se escriben los
+//! componentes cuyos bytes cambiaron desde la última escritura, y cada
+//! escritura sube la generación del componente.
+//!
+//! El índice espacial usa la posición del `TransformComponent` (la de mundo
+//! en las entidades raíz) y se mantiene en memoria como un vector ordenado
+//! por `(isla, celda, entidad)`.
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use sled::Transactional;
+use tracing::{debug, info};
+
+use crate::ecs::snapshot::{ComponentSnapshot, EntitySnapshot};
+use crate::ecs::{ComponentType, ECSCommand, ECSSystem, Entity, EntityId, EntityState, TransformComponent};
+use crate::renderer::culling::Aabb;
+use super::partition::IslandId;
+
+/// Lado de una celda del índice espacial en metros
+pub const CELL_SIZE: f32 = 32.0;
+
+/// Datos de una entidad salvo sus componentes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EntityRecord {
+    name: String,
+    state: EntityState,
+    metadata: HashMap<String, String>,
+    component_types: Vec<ComponentType>,
+}
+
+/// Última escritura de un componente
+#[derive(Debug, Clone, Copy)]
+struct SavedComponent {
+    generation: u64,
+    hash: u64,
+}
+
+/// Base de datos de escenas persistentes
+pub struct SceneDatabase {
+    db: sled::Db,
+    entities: sled::Tree,
+    components: sled::Tree,
+    spatial_tree: sled::Tree,
+    /// Índice espacial confirmado, ordenado
+    spatial: Vec<(IslandId, [i32; 3], EntityId)>,
+    /// Escrituras pendientes de `flush`
+    entity_batch: sled::Batch,
+    component_batch: sled::Batch,
+    spatial_batch: sled::Batch,
+    /// Celda pendiente de cada entidad guardada (None si se borra)
+    pending_cells: HashMap<(IslandId, EntityId), Option<[i32; 3]>>,
+    /// Generación y hash de la última escritura de cada componente
+    saved: HashMap<(IslandId, EntityId, ComponentType), SavedComponent>,
+    /// Última generación asignada
+    generation: u64,
+}
+
+impl SceneDatabase {
+    /// Abrir (o crear) la base de datos en un directorio
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let db = sled::open(path.as_ref())
+            .map_err(|e| anyhow!("Error abriendo la base de datos {}: {}", path.as_ref().display(), e))?;
+        Self::from_db(db)
+    }
+
+    /// Base de datos temporal que se borra al cerrarla
+    pub fn temporary() -> Result<Self> {
+        Self::from_db(sled::Config::new().temporary(true).open()?)
+    }
+
+    fn from_db(db: sled::Db) -> Result<Self> {
+        let entities = db.open_tree("entities")?;
+        let components = db.open_tree("components")?;
+        let spatial_tree = db.open_tree("spatial")?;
+
+        // Las claves del índice ya están ordenadas como el vector
+        let mut spatial = Vec::with_capacity(spatial_tree.len());
+        for entry in spatial_tree.iter() {
+            let (key, _) = entry?;
+            spatial.push(decode_spatial_key(&key)?);
+        }
+        info!("🗄️ Base de datos de escenas abierta ({} entidades)", entities.len());
+
+        Ok(Self {
+            db,
+            entities,
+            components,
+            spatial_tree,
+            spatial,
+            entity_batch: sled::Batch::default(),
+            component_batch: sled::Batch::default(),
+            spatial_batch: sled::Batch::default(),
+            pending_cells: HashMap::new(),
+            saved: HashMap::new(),
+            generation: 0,
+        })
+    }
+
+    /// Guardar una entidad del mundo: sus datos y los componentes que han
+    /// cambiado desde la última escritura. Devuelve cuántos componentes se
+    /// escribieron
+    pub fn save_entity(&mut self, island_id: IslandId, entity_id: EntityId, world: &ECSSystem) -> Result<usize> {
+        let snapshot = world.snapshot_entities(&[entity_id])?.entities.pop()
+            .ok_or_else(|| anyhow!("La entidad {} no existe", entity_id))?;
+        let key = entity_key(island_id, entity_id);
+
+        let record = EntityRecord {
+            name: snapshot.name.clone(),
+            state: snapshot.state.clone(),
+            metadata: snapshot.metadata.clone(),
+            component_types: snapshot.components.iter().map(|component| component.component_type.clone()).collect(),
+        };
+        self.entity_batch.insert(key.to_vec(), bincode::serialize(&record)?);
+
+        // Componentes que ya no tiene la entidad
+        let stored = self.stored_component_types(island_id, entity_id)?;
+        for component_type in stored.iter().filter(|stored| !record.component_types.contains(stored)) {
+            self.component_batch.remove(component_key(island_id, entity_id, component_type)?);
+            self.saved.remove(&(island_id, entity_id, component_type.clone()));
+        }
+
+        let mut written = 0;
+        for component in &snapshot.components {
+            let hash = hash_bytes(&component.data);
+            let saved_key = (island_id, entity_id, component.component_type.clone());
+            let db_key = component_key(island_id, entity_id, &component.component_type)?;
+            let unchanged = match self.saved.get(&saved_key) {
+                Some(saved) => saved.hash == hash,
+                // Sin escritura en esta sesión se compara con lo guardado
+                None => self.components.get(&db_key)?.is_some_and(|bytes| *bytes == component.data[..]),
+            };
+            if unchanged {
+                continue;
+            }
+            self.generation += 1;
+            self.saved.insert(saved_key, SavedComponent { generation: self.generation, hash });
+            self.component_batch.insert(db_key, component.data.clone());
+            written += 1;
+        }
+
+        self.pending_cells.insert((island_id, entity_id), entity_cell(&snapshot)?);
+        debug!("🗄️ Entidad {} de la isla {}: {} componentes escritos", entity_id, island_id, written);
+        Ok(written)
+    }
+
+    /// Borrar una entidad de la base de datos
+    pub fn remove_entity(&mut self, island_id: IslandId, entity_id: EntityId) -> Result<()> {
+        for component_type in self.stored_component_types(island_id, entity_id)? {
+            self.component_batch.remove(component_key(island_id, entity_id, &component_type)?);
+        }
+        self.entity_batch.remove(entity_key(island_id, entity_id).to_vec());
+        self.saved.retain(|(island, entity, _), _| (*island, *entity) != (island_id, entity_id));
+        self.pending_cells.insert((island_id, entity_id), None);
+        Ok(())
+    }
+
+    /// Generación de la última escritura de un componente en esta sesión
+    pub fn generation(&self, island_id: IslandId, entity_id: EntityId, component_type: &ComponentType) -> Option<u64> {
+        self.saved.get(&(island_id, entity_id, component_type.clone())).map(|saved| saved.generation)
+    }
+
+    /// Crear en el mundo una entidad guardada, con su ID y sus componentes
+    pub async fn load_entity(&self, island_id: IslandId, entity_id: EntityId, world: &mut ECSSystem) -> Result<()> {
+        let bytes = self.entities.get(entity_key(island_id, entity_id))?
+            .ok_or_else(|| anyhow!("La entidad {} de la isla {} no está guardada", entity_id, island_id))?;
+        let record: EntityRecord = bincode::deserialize(&bytes)?;
+
+        let mut components = Vec::with_capacity(record.component_types.len());
+        for component_type in &record.component_types {
+            let data = self.components.get(component_key(island_id, entity_id, component_type)?)?
+                .ok_or_else(|| anyhow!("Falta el componente {:?} de la entidad {}", component_type, entity_id))?;
+            let snapshot = ComponentSnapshot { component_type: component_type.clone(), data: data.to_vec() };
+            components.push(snapshot.restore()?);
+        }
+
+        // Los IDs nuevos no pueden coincidir con los cargados
+        world.advance_entity_ids(entity_id);
+        world.queue_command(ECSCommand::CreateEntity(Entity {
+            id: entity_id,
+            name: record.name,
+            components: Vec::new(),
+            state: record.state,
+            metadata: record.metadata,
+        })).await?;
+        for component in components {
+            world.queue_command(ECSCommand::AddComponent(entity_id, component)).await?;
+        }
+        Ok(())
+    }
+
+    /// Entidades guardadas de una isla cuya celda toca la caja
+    pub fn load_region(&self, island_id: IslandId, aabb: Aabb) -> Vec<EntityId> {
+        let (min, max) = (cell_of(aabb.min.to_array()), cell_of(aabb.max.to_array()));
+        let start = self.spatial.partition_point(|&(island, cell, _)| (island, cell[0]) < (island_id, min[0]));
+        let end = self.spatial.partition_point(|&(island, cell, _)| (island, cell[0]) <= (island_id, max[0]));
+        self.spatial[start..end].iter()
+            .filter(|(_, cell, _)| (1..3).all(|axis| (min[axis]..=max[axis]).contains(&cell[axis])))
+            .map(|&(_, _, entity_id)| entity_id)
+            .collect()
+    }
+
+    /// Entidades guardadas de una isla
+    pub fn island_entities(&self, island_id: IslandId) -> Result<Vec<EntityId>> {
+        self.entities.scan_prefix(island_id.to_be_bytes())
+            .keys()
+            .map(|key| Ok(decode_entity_key(&key?)?.1))
+            .collect()
+    }
+
+    /// Confirmar las escrituras pendientes en una transacción y llevarlas a
+    /// disco. Devuelve los bytes escritos
+    pub fn flush(&mut self) -> Result<usize> {
+        let mut spatial_batch = std::mem::take(&mut self.spatial_batch);
+        let mut spatial = self.spatial.clone();
+        for ((island_id, entity_id), cell) in self.pending_cells.drain() {
+            if let Some(index) = spatial.iter().position(|&(island, _, entity)| (island, entity) == (island_id, entity_id)) {
+                let (_, old_cell, _) = spatial.remove(index);
+                spatial_batch.remove(spatial_key(island_id, old_cell, entity_id).to_vec());
+            }
+            if let Some(cell) = cell {
+                spatial.push((island_id, cell, entity_id));
+                spatial_batch.insert(spatial_key(island_id, cell, entity_id).to_vec(), Vec::new());
+            }
+        }
+        spatial.sort_unstable();
+
+        let entity_batch = std::mem::take(&mut self.entity_batch);
+        let component_batch = std::mem::take(&mut self.component_batch);
+        (&self.entities, &self.components, &self.spatial_tree)
+            .transaction(|(entities, components, spatial_tree)| {
+                entities.apply_batch(&entity_batch)?;
+                components.apply_batch(&component_batch)?;
+                spatial_tree.apply_batch(&spatial_batch)?;
+                Ok::<(), sled::transaction::ConflictableTransactionError<()>>(())
+            })
+            .map_err(|e| anyhow!("Error confirmando la escena: {:?}", e))?;
+        self.spatial = spatial;
+
+        Ok(self.db.flush()?)
+    }
+
+    /// Tipos de los componentes guardados de una entidad
+    fn stored_component_types(&self, island_id: IslandId, entity_id: EntityId) -> Result<Vec<ComponentType>> {
+        let prefix = entity_key(island_id, entity_id);
+        self.components.scan_prefix(prefix)
+            .keys()
+            .map(|key| Ok(bincode::deserialize(&key?[prefix.len()..])?))
+            .collect()
+    }
+}
+
+/// Clave de una entidad: isla y entidad en big endian
+fn entity_key(island_id: IslandId, entity_id: EntityId) -> [u8; 12] {
+    let mut key = [0; 12];
+    key[..4].copy_from_slice(&island_id.to_be_bytes());
+    key[4..].copy_from_slice(&entity_id.to_be_bytes());
+    key
+}
+
+fn decode_entity_key(key: &[u8]) -> Result<(IslandId, EntityId)> {
+    if key.len() < 12 {
+        return Err(anyhow!("Clave de entidad corrupta"));
+    }
+    Ok((
+        IslandId::from_be_bytes(key[..4].try_into()?),
+        EntityId::from_be_bytes(key[4..12].try_into()?),
+    ))
+}
+
+/// Clave de un componente: la de la entidad y el tipo
+fn component_key(island_id: IslandId, entity_id: EntityId, component_type: &ComponentType) -> Result<Vec<u8>> {
+    let mut key = entity_key(island_id, entity_id).to_vec();
+    key.extend(bincode::serialize(component_type)?);
+    Ok(key)
+}
+
+/// Clave del índice espacial; el bit de signo de las celdas se invierte para
+/// que el orden de bytes coincida con el numérico
+fn spatial_key(island_id: IslandId, cell: [i32; 3], entity_id: EntityId) -> [u8; 24] {
+    let mut key = [0; 24];
+    key[..4].copy_from_slice(&island_id.to_be_bytes());
+    for (axis, value) in cell.iter().enumerate() {
+        key[4 + axis * 4..8 + axis * 4].copy_from_slice(&((*value as u32) ^ 0x8000_0000).to_be_bytes());
+    }
+    key[16..].copy_from_slice(&entity_id.to_be_bytes());
+    key
+}
+
+fn decode_spatial_key(key: &[u8]) -> Result<(IslandId, [i32; 3], EntityId)> {
+    if key.len() != 24 {
+        return Err(anyhow!("Clave del índice espacial corrupta"));
+    }
+    let axis = |index: usize| -> Result<i32> {
+        Ok((u32::from_be_bytes(key[4 + index * 4..8 + index * 4].try_into()?) ^ 0x8000_0000) as i32)
+    };
+    Ok((
+        IslandId::from_be_bytes(key[..4].try_into()?),
+        [axis(0)?, axis(1)?, axis(2)?],
+        EntityId::from_be_bytes(key[16..].try_into()?),
+    ))
+}
+
+/// Celda de un punto
+fn cell_of(position: [f32; 3]) -> [i32; 3] {
+    position.map(|value| (value / CELL_SIZE).floor() as i32)
+}
+
+/// Celda de la entidad según su transformación (None sin transformación)
+fn entity_cell(snapshot: &EntitySnapshot) -> Result<Option<[i32; 3]>> {
+    let Some(transform) = snapshot.components.iter().find(|component| component.component_type == ComponentType::Transform) else {
+        return Ok(None);
+    };
+    let transform: TransformComponent = bincode::deserialize(&transform.data)?;
+    Ok(Some(cell_of(transform.position.to_array())))
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::{
+        ComponentConfig, ECSConfig, EntityConfig, LabelComponent, OptimizationConfig, SystemConfig, TextStyle,
+    };
+    use glam::{Mat4, Quat, Vec3};
+
+    const ENTITIES: usize = 1000;
+    const ISLAND: IslandId = 3;
+
+    fn ecs_config() -> ECSConfig {
+        ECSConfig {
+            enabled: true,
+            entity_config: EntityConfig { max_entities: ENTITIES, entity_pool: false, id_reuse: false },
+            component_config: ComponentConfig {
+                max_components_per_entity: 16,
+                component_cache: false,
+                auto_serialization: false,
+            },
+            system_config: SystemConfig { parallel_execution: false, system_priority: true, hot_reloading: false },
+            optimization_config: OptimizationConfig { cache_friendly: true, memory_pooling: false, batch_processing: false },
+            max_parallel_systems: 1,
+            system_hot_reloading: false,
+            systems_directory: "systems".into(),
+        }
+    }
+
+    /// Entidad `i` con metadatos, una transformación repartida por varias
+    /// celdas y, en las pares, una etiqueta
+    async fn spawn(world: &mut ECSSystem, i: usize) -> EntityId {
+        let id = world.reserve_entity_id();
+        let metadata = HashMap::from([("index".to_string(), i.to_string())]);
+        world.spawn_entity(Entity {
+            id,
+            name: format!("entity-{}", i),
+            components: Vec::new(),
+            state: EntityState { active: true, visible: i % 3 != 0, selected: false, locked: i % 7 == 0 },
+            metadata,
+        }).await.unwrap();
+
+        let position = Vec3::new((i % 40) as f32 * 10.0 - 200.0, (i % 5) as f32, (i / 40) as f32 * 10.0);
+        let rotation = Quat::from_rotation_y(i as f32 * 0.01);
+        let scale = Vec3::splat(1.0 + (i % 4) as f32 * 0.5);
+        world.add_component(id, Box::new(TransformComponent {
+            position,
+            rotation,
+            scale,
+            matrix: Mat4::from_scale_rotation_translation(scale, rotation, position),
+            parent: None,
+            children: Vec::new(),
+        })).await.unwrap();
+        if i % 2 == 0 {
+            world.add_component(id, Box::new(LabelComponent {
+                text: format!("Cartel {}\nlínea 2", i),
+                style: TextStyle::default(),
+                offset: Vec3::Y * 0.5,
+            })).await.unwrap();
+        }
+        id
+    }
+
+    #[tokio::test]
+    async fn thousand_entities_survive_save_clear_and_load() {
+        let mut world = ECSSystem::new(ecs_config());
+        let mut ids = Vec::with_capacity(ENTITIES);
+        for i in 0..ENTITIES {
+            ids.push(spawn(&mut world, i).await);
+        }
+        world.flush_commands();
+        let original = world.snapshot_entities(&ids).unwrap();
+        assert_eq!(original.entities.len(), ENTITIES);
+
+        let mut db = SceneDatabase::temporary().unwrap();
+        let written: usize = ids.iter().map(|&id| db.save_entity(ISLAND, id, &world).unwrap()).sum();
+        assert_eq!(written, ENTITIES + ENTITIES / 2);
+        db.flush().unwrap();
+
+        // Sin cambios no se vuelve a escribir ningún componente
+        assert!(ids.iter().all(|&id| db.save_entity(ISLAND, id, &world).unwrap() == 0));
+        db.flush().unwrap();
+
+        for &id in &ids {
+            world.destroy_entity(id).await.unwrap();
+        }
+        world.flush_commands();
+        assert!(world.snapshot_entities(&ids).unwrap().entities.is_empty(), "el mundo debería quedar vacío");
+        assert!(world.get_entities_with_component(ComponentType::Transform).is_empty());
+
+        let mut stored = db.island_entities(ISLAND).unwrap();
+        stored.sort_unstable();
+        let mut expected = ids.clone();
+        expected.sort_unstable();
+        assert_eq!(stored, expected);
+        for &id in &stored {
+            db.load_entity(ISLAND, id, &mut world).await.unwrap();
+        }
+        world.flush_commands();
+
+        let loaded = world.snapshot_entities(&ids).unwrap();
+        assert_eq!(loaded.entities.len(), ENTITIES);
+        for (before, after) in original.entities.iter().zip(&loaded.entities) {
+            assert_eq!(after.id, before.id);
+            assert_eq!(after.name, before.name);
+            assert_eq!(after.metadata, before.metadata);
+            assert_eq!(
+                bincode::serialize(&after.state).unwrap(),
+                bincode::serialize(&before.state).unwrap(),
+                "estado de {}", before.name
+            );
+            assert_eq!(after.components.len(), before.components.len(), "componentes de {}", before.name);
+            for (a, b) in after.components.iter().zip(&before.components) {
+                assert_eq!(a.component_type, b.component_type);
+                assert_eq!(a.data, b.data, "{:?} de {}", b.component_type, before.name);
+            }
+        }
+
+        // La región de la primera celda contiene solo entidades guardadas en ella
+        let region = db.load_region(ISLAND, Aabb { min: Vec3::new(-200.0, 0.0, 0.0), max: Vec3::new(-193.0, 5.0, 30.0) });
+        assert!(!region.is_empty());
+        for id in region {
+            let transform: TransformComponent = world.get_component(id, ComponentType::Transform).unwrap();
+            assert_eq!(cell_of(transform.position.to_array()), [-7, 0, 0]);
+        }
+    }
+}
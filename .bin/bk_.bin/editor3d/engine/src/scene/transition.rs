@@ -0,0 +1,87 @@
+//! # Transiciones entre escenas
+//!
+//! Lleva el avatar a un punto de aparición. Con `Fade` la pantalla funde a
+//! negro durante la primera mitad, el avatar se teletransporta en el punto
+//! medio y la imagen vuelve en la segunda mitad. `Teleport` mueve el avatar
+//! en el siguiente frame sin fundido.
+
+use serde::{Serialize, Deserialize};
+
+use super::definition::SpawnPoint;
+use crate::ecs::EntityId;
+
+/// Tipo de transición
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TransitionKind {
+    /// Fundido a negro de `duration` segundos en total
+    Fade { duration: f32 },
+    /// Teletransporte inmediato
+    Teleport,
+}
+
+/// Transición en curso
+#[derive(Debug, Clone)]
+pub struct SceneTransition {
+    /// Tipo de transición
+    pub kind: TransitionKind,
+    /// Avatar que se mueve
+    pub avatar: EntityId,
+    /// Destino
+    pub target: SpawnPoint,
+    /// Tiempo transcurrido
+    elapsed: f32,
+    /// El avatar ya se movió
+    teleported: bool,
+}
+
+impl SceneTransition {
+    /// Crear transición
+    pub fn new(kind: TransitionKind, avatar: EntityId, target: SpawnPoint) -> Self {
+        Self {
+            kind,
+            avatar,
+            target,
+            elapsed: 0.0,
+            teleported: false,
+        }
+    }
+
+    /// Duración total
+    pub fn duration(&self) -> f32 {
+        match self.kind {
+            TransitionKind::Fade { duration } => duration.max(0.0),
+            TransitionKind::Teleport => 0.0,
+        }
+    }
+
+    /// Avanzar la transición. Devuelve el destino en el frame en que hay que
+    /// mover el avatar
+    pub fn advance(&mut self, delta_time: f32) -> Option<&SpawnPoint> {
+        self.elapsed += delta_time.max(0.0);
+        if !self.teleported && self.elapsed >= self.duration() * 0.5 {
+            self.teleported = true;
+            return Some(&self.target);
+        }
+        None
+    }
+
+    /// Opacidad del fundido: sube de 0 a 1 hasta la mitad y vuelve a 0
+    pub fn fade_amount(&self) -> f32 {
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        let t = (self.elapsed / duration).clamp(0.0, 1.0);
+        1.0 - (2.0 * t - 1.0).abs()
+    }
+
+    /// El avatar ya se movió
+    pub fn teleported(&self) -> bool {
+        self.teleported
+    }
+
+    /// La transición terminó
+    pub fn finished(&self) -> bool {
+        self.teleported && self.elapsed >= self.duration()
+    }
+}
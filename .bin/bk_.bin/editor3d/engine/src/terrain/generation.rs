@@ -0,0 +1,179 @@
+//! # Generación procedural de islas
+//!
+//! Alternativa cuando no hay heightmap: una isla en el centro del mundo y el
+//! resto repartidas en un anillo a su alrededor. Cada isla es una cúpula que
+//! cae suavemente hasta el fondo marino, modulada por ruido fractal con los
+//! parámetros de `NoiseConfig` (octavas, persistencia y lacunaridad).
+//! La altura es una función pura de (x, z), así que los trozos se generan en
+//! cualquier orden y sus bordes coinciden.
+
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+
+use crate::animations::{NoiseConfig, NoiseType};
+
+/// Configuración de la generación de islas
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IslandGenerationConfig {
+    /// Semilla
+    pub seed: u32,
+    /// Número de islas
+    pub island_count: u32,
+    /// Radio de cada isla en metros
+    pub island_radius: f32,
+    /// Altura máxima sobre el nivel del mar
+    pub peak_height: f32,
+    /// Profundidad del fondo marino
+    pub sea_depth: f32,
+    /// Frecuencia base del ruido (ciclos por metro)
+    pub frequency: f32,
+    /// Ruido fractal del relieve
+    pub noise: NoiseConfig,
+}
+
+impl Default for IslandGenerationConfig {
+    fn default() -> Self {
+        Self {
+            seed: 7,
+            island_count: 7,
+            island_radius: 900.0,
+            peak_height: 180.0,
+            sea_depth: 40.0,
+            frequency: 0.002,
+            noise: NoiseConfig {
+                noise_type: NoiseType::Perlin,
+                octaves: 5,
+                persistence: 0.5,
+                lacunarity: 2.0,
+            },
+        }
+    }
+}
+
+/// Isla generada
+#[derive(Debug, Clone, Copy)]
+pub struct Island {
+    /// Centro en el plano XZ
+    pub center: Vec2,
+    /// Radio en metros
+    pub radius: f32,
+}
+
+/// Generador de alturas de islas
+#[derive(Debug, Clone)]
+pub struct IslandGenerator {
+    config: IslandGenerationConfig,
+    islands: Vec<Island>,
+}
+
+impl IslandGenerator {
+    /// Colocar las islas en un mundo de `world_size` metros de lado
+    pub fn new(config: IslandGenerationConfig, world_size: f32) -> Self {
+        let count = config.island_count as usize;
+        let ring_radius = world_size * 0.3;
+        let islands = (0..count)
+            .map(|i| {
+                if i == 0 {
+                    return Island { center: Vec2::ZERO, radius: config.island_radius };
+                }
+                // Reparto en anillo con un desfase angular por semilla
+                let ring = (count - 1).max(1) as f32;
+                let jitter = hash(i as i32, 0, config.seed) * 0.5;
+                let angle = (i as f32 - 1.0 + jitter) / ring * std::f32::consts::TAU;
+                Island {
+                    center: Vec2::new(angle.cos(), angle.sin()) * ring_radius,
+                    radius: config.island_radius * (0.7 + 0.3 * hash(i as i32, 1, config.seed)),
+                }
+            })
+            .collect();
+        Self { config, islands }
+    }
+
+    /// Islas generadas
+    pub fn islands(&self) -> &[Island] {
+        &self.islands
+    }
+
+    /// Configuración del generador
+    pub fn config(&self) -> &IslandGenerationConfig {
+        &self.config
+    }
+
+    /// Altura en un punto del plano XZ (0 es el nivel del mar)
+    pub fn height(&self, x: f32, z: f32) -> f32 {
+        let point = Vec2::new(x, z);
+        // Máscara de la isla más cercana: 1 en el centro, 0 desde el radio
+        let mask = self.islands.iter()
+            .map(|island| {
+                let t = (point.distance(island.center) / island.radius).clamp(0.0, 1.0);
+                1.0 - t * t * (3.0 - 2.0 * t)
+            })
+            .fold(0.0f32, f32::max);
+        if mask <= 0.0 {
+            return -self.config.sea_depth;
+        }
+
+        let relief = 0.5 + 0.5 * self.fractal(point * self.config.frequency);
+        -self.config.sea_depth * (1.0 - mask) + self.config.peak_height * relief * mask
+    }
+
+    /// Ruido fractal en [-1, 1]
+    fn fractal(&self, point: Vec2) -> f32 {
+        let noise = &self.config.noise;
+        let (mut sum, mut amplitude, mut frequency, mut total) = (0.0, 1.0, 1.0, 0.0);
+        for octave in 0..noise.octaves.max(1) {
+            let seed = self.config.seed.wrapping_add(octave);
+            let value = match noise.noise_type {
+                NoiseType::Worley => worley(point * frequency, seed),
+                _ => gradient(point * frequency, seed),
+            };
+            sum += value * amplitude;
+            total += amplitude;
+            amplitude *= noise.persistence;
+            frequency *= noise.lacunarity;
+        }
+        if total > 0.0 { (sum / total).clamp(-1.0, 1.0) } else { 0.0 }
+    }
+}
+
+/// Hash de una celda entera en [0, 1)
+fn hash(x: i32, y: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ seed.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    (h & 0x00ff_ffff) as f32 / 0x0100_0000 as f32
+}
+
+/// Ruido de gradiente (Perlin) en aproximadamente [-1, 1]
+fn gradient(point: Vec2, seed: u32) -> f32 {
+    let cell = point.floor();
+    let local = point - cell;
+    let (cx, cy) = (cell.x as i32, cell.y as i32);
+    let corner = |dx: i32, dy: i32| {
+        let angle = hash(cx + dx, cy + dy, seed) * std::f32::consts::TAU;
+        Vec2::new(angle.cos(), angle.sin()).dot(local - Vec2::new(dx as f32, dy as f32))
+    };
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (u, v) = (fade(local.x), fade(local.y));
+    let bottom = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * u;
+    let top = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * u;
+    (bottom + (top - bottom) * v) * std::f32::consts::SQRT_2
+}
+
+/// Ruido celular (Worley) reescalado a [-1, 1]
+fn worley(point: Vec2, seed: u32) -> f32 {
+    let cell = point.floor();
+    let (cx, cy) = (cell.x as i32, cell.y as i32);
+    let mut nearest = f32::MAX;
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            let (x, y) = (cx + dx, cy + dy);
+            let feature = Vec2::new(x as f32 + hash(x, y, seed), y as f32 + hash(y, x, seed ^ 0x9e37));
+            nearest = nearest.min(point.distance(feature));
+        }
+    }
+    nearest.min(1.0) * 2.0 - 1.0
+}
@@ -0,0 +1,193 @@
+//! # Heightmaps
+//!
+//! Rejilla cuadrada de alturas en metros. La muestra `(column, row)` está en
+//! `origin + (column, row) * spacing` sobre el plano XZ; entre muestras la
+//! altura se interpola como la malla que se dibuja (dos triángulos por
+//! celda), de modo que `height` y `raycast` coinciden con lo que se ve y con
+//! el collider de física.
+
+use anyhow::{anyhow, Result};
+use glam::{Vec2, Vec3};
+
+/// Heightmap de `resolution` x `resolution` muestras
+#[derive(Debug, Clone)]
+pub struct Heightmap {
+    /// Muestras por lado
+    resolution: usize,
+    /// Lado del área cubierta en metros
+    size: f32,
+    /// Esquina -X -Z en el plano XZ
+    origin: Vec2,
+    /// Alturas fila a fila (de -Z a +Z)
+    heights: Vec<f32>,
+}
+
+impl Heightmap {
+    /// Crear a partir de sus alturas
+    pub fn new(resolution: usize, size: f32, origin: Vec2, heights: Vec<f32>) -> Result<Self> {
+        if resolution < 2 {
+            return Err(anyhow!("Un heightmap necesita al menos 2x2 muestras"));
+        }
+        if heights.len() != resolution * resolution {
+            return Err(anyhow!("Heightmap de {}x{} con {} alturas", resolution, resolution, heights.len()));
+        }
+        if size <= 0.0 {
+            return Err(anyhow!("Tamaño de heightmap inválido: {}", size));
+        }
+        Ok(Self { resolution, size, origin, heights })
+    }
+
+    /// Muestrear una función de altura en la rejilla
+    pub fn from_fn(resolution: usize, size: f32, origin: Vec2, height: impl Fn(f32, f32) -> f32) -> Self {
+        let resolution = resolution.max(2);
+        let spacing = size / (resolution - 1) as f32;
+        let heights = (0..resolution * resolution)
+            .map(|i| {
+                let (column, row) = (i % resolution, i / resolution);
+                height(origin.x + column as f32 * spacing, origin.y + row as f32 * spacing)
+            })
+            .collect();
+        Self { resolution, size, origin, heights }
+    }
+
+    /// Leer un heightmap RAW de 16 bits little-endian (el formato de
+    /// exportación habitual de los editores de terreno). 0 es `min_height` y
+    /// 65535 es `max_height`
+    pub fn from_r16(data: &[u8], size: f32, origin: Vec2, min_height: f32, max_height: f32) -> Result<Self> {
+        let samples = data.len() / 2;
+        let resolution = (samples as f64).sqrt() as usize;
+        if data.len() % 2 != 0 || resolution * resolution != samples {
+            return Err(anyhow!("El heightmap RAW de {} bytes no es cuadrado", data.len()));
+        }
+        let heights = data.chunks_exact(2)
+            .map(|bytes| {
+                let value = u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / u16::MAX as f32;
+                min_height + value * (max_height - min_height)
+            })
+            .collect();
+        Self::new(resolution, size, origin, heights)
+    }
+
+    /// Muestras por lado
+    pub fn resolution(&self) -> usize {
+        self.resolution
+    }
+
+    /// Lado del área cubierta en metros
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    /// Esquina -X -Z
+    pub fn origin(&self) -> Vec2 {
+        self.origin
+    }
+
+    /// Distancia entre muestras
+    pub fn spacing(&self) -> f32 {
+        self.size / (self.resolution - 1) as f32
+    }
+
+    /// Alturas fila a fila
+    pub fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    /// Altura de una muestra (fuera de rango se usa el borde)
+    pub fn sample(&self, column: usize, row: usize) -> f32 {
+        let last = self.resolution - 1;
+        self.heights[row.min(last) * self.resolution + column.min(last)]
+    }
+
+    /// Verificar si un punto del plano XZ cae dentro del heightmap
+    pub fn contains(&self, x: f32, z: f32) -> bool {
+        let local = Vec2::new(x, z) - self.origin;
+        local.x >= 0.0 && local.y >= 0.0 && local.x <= self.size && local.y <= self.size
+    }
+
+    /// Altura en un punto del plano XZ, interpolada en el triángulo de la
+    /// celda. Fuera del heightmap se usa el borde
+    pub fn height(&self, x: f32, z: f32) -> f32 {
+        let last = (self.resolution - 1) as f32;
+        let grid = ((Vec2::new(x, z) - self.origin) / self.spacing()).clamp(Vec2::ZERO, Vec2::splat(last));
+        let column = (grid.x.floor() as usize).min(self.resolution - 2);
+        let row = (grid.y.floor() as usize).min(self.resolution - 2);
+        let (fx, fz) = (grid.x - column as f32, grid.y - row as f32);
+
+        let h00 = self.sample(column, row);
+        let h10 = self.sample(column + 1, row);
+        let h01 = self.sample(column, row + 1);
+        let h11 = self.sample(column + 1, row + 1);
+        // Misma diagonal que los índices de la malla: (0,0)-(1,1)
+        if fx >= fz {
+            h00 + (h10 - h00) * fx + (h11 - h10) * fz
+        } else {
+            h00 + (h11 - h01) * fx + (h01 - h00) * fz
+        }
+    }
+
+    /// Normal por diferencias centrales alrededor de una muestra
+    pub fn normal(&self, column: usize, row: usize) -> Vec3 {
+        let last = self.resolution - 1;
+        let (left, right) = (column.saturating_sub(1), (column + 1).min(last));
+        let (down, up) = (row.saturating_sub(1), (row + 1).min(last));
+        let spacing = self.spacing();
+        let dx = (self.sample(right, row) - self.sample(left, row)) / ((right - left) as f32 * spacing);
+        let dz = (self.sample(column, up) - self.sample(column, down)) / ((up - down) as f32 * spacing);
+        Vec3::new(-dx, 1.0, -dz).normalize()
+    }
+
+    /// Pendiente (0 llano, 1 vertical) en una muestra
+    pub fn slope(&self, column: usize, row: usize) -> f32 {
+        1.0 - self.normal(column, row).y
+    }
+
+    /// Trazar un rayo contra el heightmap; fuera de él la superficie
+    /// continúa con la altura del borde
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<(f32, Vec3)> {
+        raycast_surface(origin, direction, max_distance, self.spacing() * 0.5, |x, z| self.height(x, z))
+    }
+}
+
+/// Trazar un rayo contra una superficie de alturas. Avanza en pasos de `step`
+/// hasta cruzar la superficie y afina el corte por bisección. Devuelve la
+/// distancia a lo largo de `direction` (normalizada) y el punto de impacto
+pub fn raycast_surface(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    step: f32,
+    height: impl Fn(f32, f32) -> f32,
+) -> Option<(f32, Vec3)> {
+    let direction = direction.try_normalize()?;
+    if step <= 0.0 {
+        return None;
+    }
+    let above = |t: f32| {
+        let point = origin + direction * t;
+        point.y - height(point.x, point.z)
+    };
+
+    if above(0.0) < 0.0 {
+        return Some((0.0, origin));
+    }
+    let mut previous = 0.0;
+    let mut t = 0.0;
+    while t < max_distance {
+        t = (t + step).min(max_distance);
+        if above(t) <= 0.0 {
+            let (mut low, mut high) = (previous, t);
+            for _ in 0..24 {
+                let middle = (low + high) * 0.5;
+                if above(middle) > 0.0 {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            return Some((high, origin + direction * high));
+        }
+        previous = t;
+    }
+    None
+}
@@ -0,0 +1,730 @@
+//! # Sistema de Terreno
+//!
+//! Terreno del mundo dividido en trozos cuadrados de `chunk_size` metros. Los
+//! trozos se cargan alrededor de la cámara hasta `load_radius` y se descargan
+//! pasado `load_radius + unload_margin`, unos pocos por frame para no
+//! provocar tirones. Cada trozo cargado tiene su heightmap, su malla y su
+//! material de terreno en el renderer (capas mezcladas con un splat map
+//! calculado por altura y pendiente) y un campo de alturas estático en la
+//! broad phase de física.
+//!
+//! Las alturas salen de un heightmap RAW de 16 bits si está configurado, o
+//! de la generación procedural de islas (`generation`) si no existe.
+
+pub mod generation;
+pub mod heightmap;
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use tracing::{info, debug, warn};
+
+use crate::ecs::RENDER_LAYER_TERRAIN;
+use crate::physics::{
+    CollisionConfig, CollisionFilter, CollisionMaterial, CollisionShape, PhysicsSystem,
+};
+use crate::renderer::{
+    BoundingBox, BoundingSphere, Geometry, Material, MaterialProperties, MaterialType, Mesh,
+    RendererSystem, Texture, TextureConfig, TextureFilter, TextureFormat, TextureType,
+    TextureWrap, Vertex,
+};
+use generation::{IslandGenerationConfig, IslandGenerator};
+use heightmap::{raycast_surface, Heightmap};
+
+/// Configuración del terreno
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerrainConfig {
+    /// Habilitado
+    pub enabled: bool,
+    /// Lado del mundo en metros, centrado en el origen
+    pub world_size: f32,
+    /// Lado de cada trozo en metros
+    pub chunk_size: f32,
+    /// Muestras por lado de cada trozo
+    pub chunk_resolution: u32,
+    /// Distancia de la cámara hasta la que se cargan trozos
+    pub load_radius: f32,
+    /// Distancia extra antes de descargar un trozo
+    pub unload_margin: f32,
+    /// Trozos que se generan como máximo por frame
+    pub max_chunk_loads_per_frame: usize,
+    /// Heightmap RAW de 16 bits que cubre el mundo entero
+    pub heightmap_path: Option<String>,
+    /// Altura del valor 0 del heightmap
+    pub heightmap_min_height: f32,
+    /// Altura del valor máximo del heightmap
+    pub heightmap_max_height: f32,
+    /// Generación procedural cuando no hay heightmap
+    pub generation: IslandGenerationConfig,
+    /// Mezcla de capas
+    pub splat: SplatConfig,
+    /// Registrar colliders de física para los trozos
+    pub collision: bool,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            world_size: 10_000.0,
+            chunk_size: 250.0,
+            chunk_resolution: 65,
+            load_radius: 1_000.0,
+            unload_margin: 250.0,
+            max_chunk_loads_per_frame: 2,
+            heightmap_path: None,
+            heightmap_min_height: -40.0,
+            heightmap_max_height: 200.0,
+            generation: IslandGenerationConfig::default(),
+            splat: SplatConfig::default(),
+            collision: true,
+        }
+    }
+}
+
+/// Reglas del splat map. Las capas son, en orden, arena, hierba, roca y nieve
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplatConfig {
+    /// Texturas de las cuatro capas (IDs de textura del renderer)
+    pub layer_textures: Vec<String>,
+    /// Altura por debajo de la cual hay arena
+    pub sand_height: f32,
+    /// Pendiente (0 llano, 1 vertical) a partir de la cual hay roca
+    pub rock_slope: f32,
+    /// Altura a partir de la cual hay nieve
+    pub snow_height: f32,
+}
+
+impl Default for SplatConfig {
+    fn default() -> Self {
+        Self {
+            layer_textures: Vec::new(),
+            sand_height: 4.0,
+            rock_slope: 0.3,
+            snow_height: 140.0,
+        }
+    }
+}
+
+/// Coordenada de un trozo: cubre [x, x + 1) * chunk_size en X y lo mismo en Z
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ChunkCoord {
+    pub x: i32,
+    pub z: i32,
+}
+
+/// Origen de las alturas
+#[derive(Debug, Clone)]
+pub enum TerrainSource {
+    /// Heightmap que cubre el mundo
+    Heightmap(Heightmap),
+    /// Islas procedurales
+    Procedural(IslandGenerator),
+}
+
+impl TerrainSource {
+    /// Altura en un punto del plano XZ
+    pub fn height(&self, x: f32, z: f32) -> f32 {
+        match self {
+            TerrainSource::Heightmap(heightmap) => heightmap.height(x, z),
+            TerrainSource::Procedural(generator) => generator.height(x, z),
+        }
+    }
+}
+
+/// Trozo de terreno cargado
+#[derive(Debug, Clone)]
+pub struct TerrainChunk {
+    /// Coordenada
+    pub coord: ChunkCoord,
+    /// Alturas del trozo
+    pub heightmap: Heightmap,
+    /// Recursos en el renderer y la física ya creados
+    pub registered: bool,
+}
+
+impl ChunkCoord {
+    /// ID del mesh, del material y del collider del trozo
+    pub fn resource_id(&self) -> String {
+        format!("terrain_{}_{}", self.x, self.z)
+    }
+
+    /// ID de la textura del splat map del trozo
+    pub fn splat_texture_id(&self) -> String {
+        format!("{}_splat", self.resource_id())
+    }
+}
+
+/// Cambios de un paso de streaming
+#[derive(Debug, Clone, Default)]
+pub struct ChunkChanges {
+    /// Trozos cargados
+    pub loaded: Vec<ChunkCoord>,
+    /// Trozos descargados
+    pub unloaded: Vec<ChunkCoord>,
+}
+
+/// Sistema de terreno
+pub struct TerrainSystem {
+    /// Configuración
+    config: TerrainConfig,
+    /// Origen de las alturas (None hasta inicializar)
+    source: Option<TerrainSource>,
+    /// Trozos cargados
+    chunks: HashMap<ChunkCoord, TerrainChunk>,
+    /// Trozos descargados cuyos recursos hay que liberar
+    released: Vec<ChunkCoord>,
+    /// Estadísticas
+    stats: TerrainStats,
+    /// Estado del sistema
+    running: bool,
+}
+
+impl TerrainSystem {
+    /// Crea el sistema de terreno
+    pub fn new(config: &TerrainConfig) -> Self {
+        info!("⛰️ Inicializando sistema de terreno...");
+
+        Self {
+            config: config.clone(),
+            source: None,
+            chunks: HashMap::new(),
+            released: Vec::new(),
+            stats: TerrainStats::default(),
+            running: false,
+        }
+    }
+
+    /// Inicializa el sistema: lee el heightmap o prepara la generación de
+    /// islas si no hay heightmap
+    pub async fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        let heightmap = match &self.config.heightmap_path {
+            Some(path) => match self.load_heightmap(path).await {
+                Ok(heightmap) => Some(heightmap),
+                Err(e) => {
+                    warn!("⚠️ Heightmap {} no disponible ({}); se generan islas", path, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        self.set_source(match heightmap {
+            Some(heightmap) => TerrainSource::Heightmap(heightmap),
+            None => TerrainSource::Procedural(IslandGenerator::new(self.config.generation.clone(), self.config.world_size)),
+        });
+        self.running = true;
+
+        info!("✅ Sistema de terreno inicializado correctamente");
+        Ok(())
+    }
+
+    /// Leer el heightmap RAW que cubre el mundo
+    async fn load_heightmap(&self, path: &str) -> anyhow::Result<Heightmap> {
+        let data = tokio::fs::read(path).await?;
+        let half = self.config.world_size * 0.5;
+        Heightmap::from_r16(
+            &data,
+            self.config.world_size,
+            Vec2::splat(-half),
+            self.config.heightmap_min_height,
+            self.config.heightmap_max_height,
+        )
+    }
+
+    /// Cambiar el origen de las alturas. Los trozos cargados se descargan
+    pub fn set_source(&mut self, source: TerrainSource) {
+        self.stats.procedural = matches!(source, TerrainSource::Procedural(_));
+        self.stats.island_count = match &source {
+            TerrainSource::Procedural(generator) => generator.islands().len(),
+            TerrainSource::Heightmap(_) => 0,
+        };
+        self.source = Some(source);
+        self.released.extend(self.chunks.drain().map(|(coord, _)| coord));
+    }
+
+    /// Origen de las alturas
+    pub fn source(&self) -> Option<&TerrainSource> {
+        self.source.as_ref()
+    }
+
+    /// Trozo que contiene un punto del plano XZ
+    pub fn chunk_at(&self, x: f32, z: f32) -> ChunkCoord {
+        let size = self.config.chunk_size.max(1.0);
+        ChunkCoord {
+            x: (x / size).floor() as i32,
+            z: (z / size).floor() as i32,
+        }
+    }
+
+    /// Esquina -X -Z de un trozo
+    pub fn chunk_origin(&self, coord: ChunkCoord) -> Vec2 {
+        Vec2::new(coord.x as f32, coord.z as f32) * self.config.chunk_size.max(1.0)
+    }
+
+    /// Distancia en el plano XZ de un punto al trozo más cercano a él
+    fn chunk_distance(&self, coord: ChunkCoord, point: Vec2) -> f32 {
+        let min = self.chunk_origin(coord);
+        let max = min + Vec2::splat(self.config.chunk_size.max(1.0));
+        point.clamp(min, max).distance(point)
+    }
+
+    /// El trozo está dentro de los límites del mundo
+    fn in_world(&self, coord: ChunkCoord) -> bool {
+        let half = self.config.world_size * 0.5;
+        let min = self.chunk_origin(coord);
+        let max = min + Vec2::splat(self.config.chunk_size.max(1.0));
+        max.x > -half && max.y > -half && min.x < half && min.y < half
+    }
+
+    /// Cargar y descargar trozos alrededor de la cámara. Se descargan todos
+    /// los trozos lejanos y se generan como máximo
+    /// `max_chunk_loads_per_frame` de los que faltan, los más cercanos antes
+    pub fn update_streaming(&mut self, camera_position: Vec3) -> ChunkChanges {
+        let mut changes = ChunkChanges::default();
+        if !self.running || self.source.is_none() {
+            return changes;
+        }
+        let camera = Vec2::new(camera_position.x, camera_position.z);
+
+        let unload_radius = self.config.load_radius + self.config.unload_margin.max(0.0);
+        let far: Vec<ChunkCoord> = self.chunks.keys()
+            .copied()
+            .filter(|coord| self.chunk_distance(*coord, camera) > unload_radius)
+            .collect();
+        for coord in far {
+            self.chunks.remove(&coord);
+            self.released.push(coord);
+            changes.unloaded.push(coord);
+        }
+
+        let reach = (self.config.load_radius / self.config.chunk_size.max(1.0)).ceil() as i32 + 1;
+        let center = self.chunk_at(camera.x, camera.y);
+        let mut missing: Vec<(f32, ChunkCoord)> = Vec::new();
+        for dz in -reach..=reach {
+            for dx in -reach..=reach {
+                let coord = ChunkCoord { x: center.x + dx, z: center.z + dz };
+                if self.chunks.contains_key(&coord) || !self.in_world(coord) {
+                    continue;
+                }
+                let distance = self.chunk_distance(coord, camera);
+                if distance <= self.config.load_radius {
+                    missing.push((distance, coord));
+                }
+            }
+        }
+        missing.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        for (_, coord) in missing.into_iter().take(self.config.max_chunk_loads_per_frame.max(1)) {
+            let chunk = self.generate_chunk(coord);
+            self.chunks.insert(coord, chunk);
+            self.released.retain(|released| *released != coord);
+            changes.loaded.push(coord);
+        }
+
+        if !changes.loaded.is_empty() || !changes.unloaded.is_empty() {
+            debug!("⛰️ Terreno: {} trozos cargados, {} descargados", changes.loaded.len(), changes.unloaded.len());
+        }
+        self.stats.loaded_chunks = self.chunks.len();
+        changes
+    }
+
+    /// Muestrear las alturas de un trozo
+    fn generate_chunk(&self, coord: ChunkCoord) -> TerrainChunk {
+        let resolution = self.config.chunk_resolution.max(2) as usize;
+        let origin = self.chunk_origin(coord);
+        let heightmap = match &self.source {
+            Some(source) => Heightmap::from_fn(resolution, self.config.chunk_size.max(1.0), origin, |x, z| source.height(x, z)),
+            None => Heightmap::from_fn(resolution, self.config.chunk_size.max(1.0), origin, |_, _| 0.0),
+        };
+        TerrainChunk { coord, heightmap, registered: false }
+    }
+
+    /// Crear en el renderer y la física los recursos de los trozos nuevos y
+    /// liberar los de los descargados
+    pub async fn sync(&mut self, renderer: &mut RendererSystem, physics: &mut PhysicsSystem) -> anyhow::Result<()> {
+        for coord in std::mem::take(&mut self.released) {
+            let id = coord.resource_id();
+            renderer.remove_mesh(&id);
+            renderer.remove_material(&id);
+            renderer.remove_texture(&coord.splat_texture_id());
+            physics.remove_static_collider(&id);
+        }
+
+        let pending: Vec<ChunkCoord> = self.chunks.values()
+            .filter(|chunk| !chunk.registered)
+            .map(|chunk| chunk.coord)
+            .collect();
+        for coord in pending {
+            let Some(chunk) = self.chunks.get(&coord) else {
+                continue;
+            };
+            let id = chunk.coord.resource_id();
+            renderer.load_texture(splat_texture(chunk, &self.config.splat)).await?;
+            renderer.create_material(terrain_material(chunk, &self.config.splat)).await?;
+            renderer.create_mesh(chunk_mesh(chunk)).await?;
+
+            if self.config.collision {
+                let heightmap = &chunk.heightmap;
+                let center = heightmap.origin() + Vec2::splat(heightmap.size() * 0.5);
+                let collision = CollisionConfig {
+                    shape: CollisionShape::Heightfield {
+                        heights: heightmap.heights().to_vec(),
+                        resolution: heightmap.resolution(),
+                        size: heightmap.size(),
+                    },
+                    filter: CollisionFilter { groups: u32::MAX, masks: u32::MAX, exceptions: Vec::new() },
+                    material: CollisionMaterial { friction: 0.8, restitution: 0.0, density: 1.0 },
+                };
+                if let Err(e) = physics.add_static_collider(&id, &collision, Vec3::new(center.x, 0.0, center.y)) {
+                    warn!("⚠️ Collider del terreno {} no registrado: {}", id, e);
+                }
+            }
+
+            if let Some(chunk) = self.chunks.get_mut(&coord) {
+                chunk.registered = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encolar el dibujo de los trozos cargados. Los vértices ya están en
+    /// espacio mundo y se dibujan en la capa de render del terreno
+    pub fn queue_draws(&self, renderer: &mut RendererSystem) {
+        for chunk in self.chunks.values().filter(|chunk| chunk.registered) {
+            renderer.queue_draw_with_layers(&chunk.coord.resource_id(), Mat4::IDENTITY, RENDER_LAYER_TERRAIN);
+        }
+    }
+
+    /// Altura del terreno en un punto del plano XZ. Usa el trozo cargado si
+    /// lo hay, para coincidir con la malla dibujada
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        if let Some(chunk) = self.chunks.get(&self.chunk_at(x, z)) {
+            return Some(chunk.heightmap.height(x, z));
+        }
+        self.source.as_ref().map(|source| source.height(x, z))
+    }
+
+    /// Trazar un rayo contra el terreno. Devuelve la distancia y el punto
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<(f32, Vec3)> {
+        self.source.as_ref()?;
+        let spacing = self.config.chunk_size.max(1.0) / (self.config.chunk_resolution.max(2) - 1) as f32;
+        raycast_surface(origin, direction, max_distance, spacing * 0.5, |x, z| self.height_at(x, z).unwrap_or(0.0))
+    }
+
+    /// Trozos cargados
+    pub fn loaded_chunks(&self) -> impl Iterator<Item = &TerrainChunk> {
+        self.chunks.values()
+    }
+
+    /// Verificar si un trozo está cargado
+    pub fn is_loaded(&self, coord: ChunkCoord) -> bool {
+        self.chunks.contains_key(&coord)
+    }
+
+    /// Coordenadas de los trozos cargados
+    pub fn loaded_coords(&self) -> HashSet<ChunkCoord> {
+        self.chunks.keys().copied().collect()
+    }
+
+    /// Configuración
+    pub fn config(&self) -> &TerrainConfig {
+        &self.config
+    }
+
+    /// Limpia el sistema
+    pub async fn cleanup(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        info!("🧹 Limpiando sistema de terreno...");
+
+        self.running = false;
+        self.chunks.clear();
+        self.released.clear();
+        self.source = None;
+
+        info!("✅ Sistema de terreno limpiado correctamente");
+        Ok(())
+    }
+
+    /// Obtiene el estado de salud del sistema
+    pub async fn health_check(&self) -> bool {
+        self.running || !self.config.enabled
+    }
+
+    /// Obtiene estadísticas del sistema
+    pub fn get_stats(&self) -> TerrainStats {
+        self.stats.clone()
+    }
+}
+
+/// Malla de un trozo en espacio mundo. Las UV van de 0 a 1 sobre el trozo
+/// (las del splat map) y la diagonal de cada celda va de (0, 0) a (1, 1),
+/// igual que la interpolación de `Heightmap::height`
+fn chunk_mesh(chunk: &TerrainChunk) -> Mesh {
+    let heightmap = &chunk.heightmap;
+    let resolution = heightmap.resolution();
+    let spacing = heightmap.spacing();
+    let last = (resolution - 1) as f32;
+
+    let mut vertices = Vec::with_capacity(resolution * resolution);
+    let (mut min, mut max) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+    for row in 0..resolution {
+        for column in 0..resolution {
+            let position = Vec3::new(
+                heightmap.origin().x + column as f32 * spacing,
+                heightmap.sample(column, row),
+                heightmap.origin().y + row as f32 * spacing,
+            );
+            min = min.min(position);
+            max = max.max(position);
+            vertices.push(Vertex {
+                position,
+                normal: heightmap.normal(column, row),
+                tangent: Vec3::X,
+                uv: Vec3::new(column as f32 / last, row as f32 / last, 0.0),
+                color: Vec4::ONE,
+            });
+        }
+    }
+
+    let mut indices = Vec::with_capacity((resolution - 1) * (resolution - 1) * 6);
+    for row in 0..resolution - 1 {
+        for column in 0..resolution - 1 {
+            let i00 = (row * resolution + column) as u32;
+            let i10 = i00 + 1;
+            let i01 = i00 + resolution as u32;
+            let i11 = i01 + 1;
+            indices.extend_from_slice(&[i00, i11, i10, i00, i01, i11]);
+        }
+    }
+
+    let center = (min + max) * 0.5;
+    let id = chunk.coord.resource_id();
+    Mesh {
+        id: id.clone(),
+        name: id.clone(),
+        geometry: Geometry {
+            vertices,
+            indices,
+            bounding_box: BoundingBox { min, max },
+            bounding_sphere: BoundingSphere { center, radius: (max - center).length() },
+            joints: Vec::new(),
+            weights: Vec::new(),
+            morph_targets: Vec::new(),
+        },
+        material: Some(id),
+        lod: Vec::new(),
+    }
+}
+
+/// Pesos de las capas (arena, hierba, roca, nieve) en una muestra
+fn splat_weights(height: f32, slope: f32, config: &SplatConfig) -> [f32; 4] {
+    let smoothstep = |edge0: f32, edge1: f32, x: f32| {
+        let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    };
+    let rock = smoothstep(config.rock_slope - 0.1, config.rock_slope + 0.1, slope);
+    let snow = smoothstep(config.snow_height - 20.0, config.snow_height + 20.0, height) * (1.0 - rock);
+    let sand = (1.0 - smoothstep(config.sand_height - 2.0, config.sand_height + 2.0, height)) * (1.0 - rock);
+    let grass = (1.0 - rock - snow - sand).max(0.0);
+    [sand, grass, rock, snow]
+}
+
+/// Splat map RGBA8 de un trozo, una muestra por texel
+fn splat_texture(chunk: &TerrainChunk, config: &SplatConfig) -> Texture {
+    let heightmap = &chunk.heightmap;
+    let resolution = heightmap.resolution();
+    let mut data = Vec::with_capacity(resolution * resolution * 4);
+    for row in 0..resolution {
+        for column in 0..resolution {
+            let weights = splat_weights(heightmap.sample(column, row), heightmap.slope(column, row), config);
+            let total: f32 = weights.iter().sum::<f32>().max(1e-4);
+            data.extend(weights.iter().map(|weight| (weight / total * 255.0).round() as u8));
+        }
+    }
+
+    let id = chunk.coord.splat_texture_id();
+    Texture {
+        id: id.clone(),
+        name: id,
+        texture_type: TextureType::Custom("splat".to_string()),
+        config: TextureConfig {
+            width: resolution as u32,
+            height: resolution as u32,
+            format: TextureFormat::RGBA8,
+            filter: TextureFilter::Linear,
+            wrap: TextureWrap::ClampToEdge,
+            mipmaps: false,
+        },
+        data: Some(data),
+    }
+}
+
+/// Material de terreno de un trozo: su splat map y las capas configuradas
+fn terrain_material(chunk: &TerrainChunk, config: &SplatConfig) -> Material {
+    let mut textures = HashMap::new();
+    textures.insert("splat".to_string(), chunk.coord.splat_texture_id());
+    for (index, texture) in config.layer_textures.iter().take(4).enumerate() {
+        textures.insert(format!("layer{}", index), texture.clone());
+    }
+
+    let id = chunk.coord.resource_id();
+    Material {
+        id: id.clone(),
+        name: id,
+        material_type: MaterialType::Terrain,
+        properties: MaterialProperties {
+            base_color: Vec4::ONE,
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive: Vec3::ZERO,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_cutoff: 0.0,
+            double_sided: false,
+        },
+        textures,
+        shader: "terrain".to_string(),
+    }
+}
+
+/// Estadísticas del sistema de terreno
+#[derive(Debug, Clone, Default)]
+pub struct TerrainStats {
+    /// Trozos cargados
+    pub loaded_chunks: usize,
+    /// Islas generadas (0 con heightmap)
+    pub island_count: usize,
+    /// Las alturas son procedurales
+    pub procedural: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mundo de 1 km en trozos de 100 m que se cargan todos en un paso
+    fn streaming_config() -> TerrainConfig {
+        TerrainConfig {
+            world_size: 1_000.0,
+            chunk_size: 100.0,
+            chunk_resolution: 5,
+            load_radius: 150.0,
+            unload_margin: 50.0,
+            max_chunk_loads_per_frame: 100,
+            collision: false,
+            ..TerrainConfig::default()
+        }
+    }
+
+    /// Distancia en XZ de la cámara al trozo, calculada aparte del sistema
+    fn distance_to(coord: ChunkCoord, camera: Vec3) -> f32 {
+        let (min_x, min_z) = (coord.x as f32 * 100.0, coord.z as f32 * 100.0);
+        let dx = (min_x - camera.x).max(camera.x - (min_x + 100.0)).max(0.0);
+        let dz = (min_z - camera.z).max(camera.z - (min_z + 100.0)).max(0.0);
+        (dx * dx + dz * dz).sqrt()
+    }
+
+    /// Trozos del mundo (de -5 a 4 en cada eje) a menos de `radius` de la cámara
+    fn chunks_within(camera: Vec3, radius: f32) -> HashSet<ChunkCoord> {
+        (-5..5)
+            .flat_map(|z| (-5..5).map(move |x| ChunkCoord { x, z }))
+            .filter(|coord| distance_to(*coord, camera) <= radius)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn streaming_follows_a_moving_camera() {
+        let mut terrain = TerrainSystem::new(&streaming_config());
+        terrain.initialize().await.unwrap();
+
+        let start = Vec3::new(50.0, 0.0, 50.0);
+        let changes = terrain.update_streaming(start);
+        let initial = chunks_within(start, 150.0);
+        assert_eq!(terrain.loaded_coords(), initial);
+        assert_eq!(changes.loaded.iter().copied().collect::<HashSet<_>>(), initial);
+        assert!(changes.unloaded.is_empty());
+
+        // Los trozos entre el radio de carga y el de descarga se conservan
+        let moved = Vec3::new(250.0, 0.0, 50.0);
+        let changes = terrain.update_streaming(moved);
+        let kept: HashSet<ChunkCoord> = initial.iter().copied()
+            .filter(|coord| distance_to(*coord, moved) <= 200.0)
+            .collect();
+        let expected: HashSet<ChunkCoord> = kept.union(&chunks_within(moved, 150.0)).copied().collect();
+        assert_eq!(terrain.loaded_coords(), expected);
+        assert_eq!(
+            changes.unloaded.iter().copied().collect::<HashSet<_>>(),
+            initial.difference(&kept).copied().collect(),
+        );
+        assert_eq!(
+            changes.loaded.iter().copied().collect::<HashSet<_>>(),
+            expected.difference(&initial).copied().collect(),
+        );
+        assert!(!changes.unloaded.is_empty() && !changes.loaded.is_empty());
+
+        // En el borde del mundo no se cargan trozos de fuera
+        let edge = Vec3::new(480.0, 0.0, 0.0);
+        terrain.update_streaming(edge);
+        assert!(terrain.loaded_coords().iter().all(|coord| coord.x < 5));
+        assert!(terrain.is_loaded(ChunkCoord { x: 4, z: 0 }));
+
+        // Sin movimiento no hay cambios
+        let changes = terrain.update_streaming(edge);
+        assert!(changes.loaded.is_empty() && changes.unloaded.is_empty());
+    }
+
+    #[tokio::test]
+    async fn streaming_loads_the_nearest_chunks_first() {
+        let mut terrain = TerrainSystem::new(&TerrainConfig {
+            max_chunk_loads_per_frame: 2,
+            ..streaming_config()
+        });
+        terrain.initialize().await.unwrap();
+
+        let camera = Vec3::new(50.0, 0.0, 50.0);
+        let changes = terrain.update_streaming(camera);
+        assert_eq!(changes.loaded.len(), 2);
+        assert_eq!(changes.loaded[0], ChunkCoord { x: 0, z: 0 });
+        while !terrain.update_streaming(camera).loaded.is_empty() {}
+        assert_eq!(terrain.loaded_coords(), chunks_within(camera, 150.0));
+    }
+
+    #[tokio::test]
+    async fn raycast_matches_the_generated_height() {
+        let mut terrain = TerrainSystem::new(&TerrainConfig {
+            chunk_size: 50.0,
+            chunk_resolution: 101,
+            load_radius: 60.0,
+            generation: IslandGenerationConfig {
+                island_count: 1,
+                island_radius: 300.0,
+                ..IslandGenerationConfig::default()
+            },
+            ..streaming_config()
+        });
+        terrain.initialize().await.unwrap();
+        terrain.update_streaming(Vec3::ZERO);
+        let Some(TerrainSource::Procedural(generator)) = terrain.source().cloned() else {
+            panic!("sin heightmap el terreno debe ser procedural");
+        };
+
+        for (x, z) in [(0.0, 0.0), (12.3, -7.9), (-31.4, 25.0), (40.2, 44.4), (-48.0, -3.3)] {
+            assert!(terrain.is_loaded(terrain.chunk_at(x, z)));
+            let analytic = generator.height(x, z);
+            let (distance, hit) = terrain.raycast(Vec3::new(x, 500.0, z), Vec3::NEG_Y, 1_000.0).unwrap();
+            assert!((hit.y - analytic).abs() < 0.05, "({}, {}): {} frente a {}", x, z, hit.y, analytic);
+            assert!((distance - (500.0 - analytic)).abs() < 0.05);
+            assert!((hit.x - x).abs() < 1e-3 && (hit.z - z).abs() < 1e-3);
+        }
+
+        // Un rayo oblicuo corta el terreno donde la altura coincide
+        let (_, hit) = terrain.raycast(Vec3::new(-40.0, 300.0, -40.0), Vec3::new(1.0, -4.0, 1.0), 1_000.0).unwrap();
+        assert!((hit.y - generator.height(hit.x, hit.z)).abs() < 0.05);
+
+        // Un rayo hacia arriba no toca el terreno
+        assert!(terrain.raycast(Vec3::new(0.0, 500.0, 0.0), Vec3::Y, 1_000.0).is_none());
+    }
+}
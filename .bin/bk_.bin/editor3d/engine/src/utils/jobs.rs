@@ -0,0 +1,154 @@
+//! # Sistema de Jobs
+//!
+//! Pool de hilos con robo de trabajo. Los jobs entran por una cola global
+//! (`Injector`) y cada worker los mueve por lotes a su deque local; un worker
+//! sin trabajo roba de la cola global o de los deques de los demás.
+
+use crossbeam::deque::{Injector, Steal, Stealer, Worker};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle, Thread};
+use std::time::Duration;
+use tracing::{debug, error};
+
+/// Job del pool
+pub type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Tiempo que un worker sin trabajo espera antes de volver a buscar
+const IDLE_PARK_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// Pool de hilos con robo de trabajo
+pub struct JobSystem {
+    /// Hilos de trabajo
+    workers: Vec<JoinHandle<()>>,
+    /// Cola global de jobs
+    queue: Arc<Injector<Job>>,
+    /// Handles para despertar a los workers
+    threads: Vec<Thread>,
+    /// Señal de parada
+    shutdown: Arc<AtomicBool>,
+}
+
+impl JobSystem {
+    /// Crear pool con `worker_count` hilos (al menos uno)
+    pub fn new(worker_count: usize) -> Self {
+        let worker_count = worker_count.max(1);
+        let queue = Arc::new(Injector::new());
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let locals: Vec<Worker<Job>> = (0..worker_count).map(|_| Worker::new_fifo()).collect();
+        let stealers: Arc<Vec<Stealer<Job>>> = Arc::new(locals.iter().map(Worker::stealer).collect());
+
+        let workers: Vec<JoinHandle<()>> = locals.into_iter()
+            .enumerate()
+            .map(|(index, local)| {
+                let queue = queue.clone();
+                let stealers = stealers.clone();
+                let shutdown = shutdown.clone();
+                thread::Builder::new()
+                    .name(format!("job-worker-{}", index))
+                    .spawn(move || Self::worker_loop(local, &queue, &stealers, &shutdown))
+                    .expect("No se pudo crear el hilo de trabajo")
+            })
+            .collect();
+        let threads = workers.iter().map(|handle| handle.thread().clone()).collect();
+
+        debug!("Sistema de jobs iniciado con {} workers", worker_count);
+        Self { workers, queue, threads, shutdown }
+    }
+
+    /// Número de workers
+    pub fn worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Encolar un job
+    pub fn submit(&self, job: Job) {
+        self.queue.push(job);
+        self.wake_workers();
+    }
+
+    /// Ejecutar un lote de jobs y esperar a que terminen todos. Los jobs
+    /// pueden tomar prestado del llamador porque el lote no sale de aquí
+    /// hasta que su contador de pendientes llega a cero; mientras espera,
+    /// el llamador también ejecuta jobs de la cola.
+    pub fn execute_batch<'scope>(&self, jobs: Vec<Box<dyn FnOnce() + Send + 'scope>>) {
+        let pending = Arc::new(AtomicUsize::new(jobs.len()));
+
+        for job in jobs {
+            let pending = pending.clone();
+            let wrapped: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+                // Un job que falla no debe dejar al lote esperando para siempre
+                run_guarded(job);
+                pending.fetch_sub(1, Ordering::AcqRel);
+            });
+            // SAFETY: el job se ejecuta antes de que `execute_batch` retorne
+            // (se espera a que `pending` llegue a cero), así que nada de lo que
+            // toma prestado con `'scope` se usa después de liberarse.
+            let job: Job = unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Job>(wrapped) };
+            self.queue.push(job);
+        }
+        self.wake_workers();
+
+        while pending.load(Ordering::Acquire) > 0 {
+            match self.queue.steal() {
+                Steal::Success(job) => run_guarded(job),
+                Steal::Retry => {}
+                Steal::Empty => thread::yield_now(),
+            }
+        }
+    }
+
+    /// Despertar a los workers dormidos
+    fn wake_workers(&self) {
+        for thread in &self.threads {
+            thread.unpark();
+        }
+    }
+
+    /// Bucle de un worker
+    fn worker_loop(local: Worker<Job>, queue: &Injector<Job>, stealers: &[Stealer<Job>], shutdown: &AtomicBool) {
+        while !shutdown.load(Ordering::Acquire) {
+            match Self::find_job(&local, queue, stealers) {
+                Some(job) => run_guarded(job),
+                None => thread::park_timeout(IDLE_PARK_TIMEOUT),
+            }
+        }
+    }
+
+    /// Buscar trabajo: deque local, lote de la cola global o robo a otro worker
+    fn find_job(local: &Worker<Job>, queue: &Injector<Job>, stealers: &[Stealer<Job>]) -> Option<Job> {
+        local.pop().or_else(|| {
+            std::iter::repeat_with(|| {
+                queue.steal_batch_and_pop(local)
+                    .or_else(|| stealers.iter().map(Stealer::steal).collect())
+            })
+            .find(|steal| !steal.is_retry())
+            .and_then(Steal::success)
+        })
+    }
+}
+
+/// Ejecutar un job conteniendo su pánico
+fn run_guarded<F: FnOnce()>(job: F) {
+    if catch_unwind(AssertUnwindSafe(job)).is_err() {
+        error!("Un job terminó con pánico");
+    }
+}
+
+impl Default for JobSystem {
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+impl Drop for JobSystem {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Release);
+        self.wake_workers();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -12,6 +12,10 @@ use anyhow::{Result, anyhow};
 use nalgebra::{Vector3, Vector4, Matrix4, Quaternion, UnitQuaternion};
 use serde_json::Value;
 
+pub mod jobs;
+
+pub use jobs::JobSystem;
+
 /// Sistema de utilidades principal
 pub struct UtilsSystem {
     /// Configuración del sistema
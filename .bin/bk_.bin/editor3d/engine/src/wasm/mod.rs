@@ -14,6 +14,10 @@ use wasm_bindgen::JsCast;
 use web_sys::{WebAssembly, Module, Instance, Memory, Table, Global};
 use js_sys::{Object, Reflect, Function, Array, Uint8Array};
 
+pub mod scripting;
+
+pub use scripting::{ScriptRuntime, SharedScriptRuntime};
+
 /// Configuración de WebAssembly
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WasmConfig {
@@ -27,6 +31,13 @@ pub struct WasmConfig {
     pub bulk_memory: bool,
     /// Configuración de reference types
     pub reference_types: bool,
+    /// Operaciones por frame entre todos los scripts Rhai (0 = sin límite)
+    #[serde(default = "default_scripting_max_ops_per_frame")]
+    pub scripting_max_ops_per_frame: u64,
+}
+
+fn default_scripting_max_ops_per_frame() -> u64 {
+    100_000
 }
 
 /// Sistema WebAssembly principal
@@ -43,6 +54,8 @@ pub struct WasmSystem {
     stats: WasmStats,
     /// Estado del sistema
     running: bool,
+    /// Runtime de scripts de objetos
+    script_runtime: SharedScriptRuntime,
 }
 
 /// Módulo WebAssembly
@@ -247,6 +260,8 @@ impl WasmSystem {
     pub fn new(config: WasmConfig) -> Self {
         info!("🔧 Creando sistema WASM...");
         
+        let script_runtime = ScriptRuntime::shared(config.scripting_max_ops_per_frame);
+
         Self {
             config,
             modules: Arc::new(RwLock::new(HashMap::new())),
@@ -261,6 +276,7 @@ impl WasmSystem {
                 execution_time: 0.0,
             },
             running: false,
+            script_runtime,
         }
     }
 
@@ -432,6 +448,11 @@ impl WasmSystem {
         let instances = self.instances.read().unwrap();
         instances.get(id).cloned()
     }
+
+    /// Runtime de scripts de objetos, para el `ScriptSystem` del ECS
+    pub fn script_runtime(&self) -> SharedScriptRuntime {
+        self.script_runtime.clone()
+    }
 }
 
 #[wasm_bindgen]
@@ -450,6 +471,7 @@ impl MetaversoWasm {
             simd: true,
             bulk_memory: true,
             reference_types: true,
+            scripting_max_ops_per_frame: default_scripting_max_ops_per_frame(),
         };
         
         Self {
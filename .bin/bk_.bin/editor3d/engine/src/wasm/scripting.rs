@@ -0,0 +1,466 @@
+//! # Scripts de objetos
+//!
+//! Runtime de Rhai para programar el comportamiento de los objetos del mundo
+//! sin recompilar WASM. El `ScriptSystem` del ECS le pasa cada frame las
+//! entidades con un `ScriptComponent` de tipo `Rhai`:
+//!
+//! - El código se compila y se ejecuta una vez al cargarlo (o al cambiar),
+//!   con un `Scope` propio de la entidad que se conserva entre frames. La
+//!   variable `entity` del scope es el ID de la entidad.
+//! - Si el código deja en el scope una closure `update`, se llama cada frame
+//!   con el delta en segundos. Las variables que captura son el estado del
+//!   script.
+//!
+//! ```rhai
+//! let angle = 0.0;
+//! let update = |dt| {
+//!     angle += 45.0 * dt;
+//!     set_rotation(entity, 0.0, angle, 0.0);
+//! };
+//! ```
+//!
+//! API del ECS: `create_entity(name)`, `set_position(entity, x, y, z)`,
+//! `get_position(entity)` (mapa con `x`, `y`, `z`), `set_rotation` y
+//! `get_rotation` en grados y `add_timer(secs, callback)`. Los scripts leen
+//! las transformaciones del inicio del frame más sus propios cambios; las
+//! escrituras se encolan en el `CommandBuffer` del sistema.
+//!
+//! Sandbox: todos los scripts comparten un presupuesto de operaciones por
+//! frame (`scripting_max_ops_per_frame`) y los strings, arrays y mapas
+//! tienen tamaño máximo.
+
+use anyhow::{Result, anyhow};
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use rhai::{Dynamic, Engine, FnPtr, Map, Scope, AST, FLOAT, INT};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+use crate::ecs::{
+    CommandBuffer, ComponentType, ECSSystem, Entity, EntityId, EntityState, ScriptComponent, ScriptType,
+    TransformComponent,
+};
+
+/// Tamaño máximo de un string en los scripts
+const MAX_STRING_SIZE: usize = 64 * 1024;
+/// Elementos máximos de un array en los scripts
+const MAX_ARRAY_SIZE: usize = 10_000;
+/// Entradas máximas de un mapa en los scripts
+const MAX_MAP_SIZE: usize = 10_000;
+/// Profundidad máxima de llamadas
+const MAX_CALL_LEVELS: usize = 32;
+
+/// Runtime compartido entre el `WasmSystem` y el `ScriptSystem` del ECS
+pub type SharedScriptRuntime = Arc<Mutex<ScriptRuntime>>;
+
+/// Estado del mundo visible para los scripts durante un frame
+#[derive(Default)]
+struct ScriptContext {
+    /// Transformaciones del inicio del frame con los cambios de los scripts
+    transforms: HashMap<EntityId, TransformComponent>,
+    /// Entidades cuya transformación cambió
+    dirty: BTreeSet<EntityId>,
+    /// Entidades creadas en el frame, en orden
+    spawned: Vec<Entity>,
+    /// Temporizadores pedidos por el script en ejecución
+    new_timers: Vec<ScriptTimer>,
+}
+
+impl ScriptContext {
+    /// Cambiar la transformación de una entidad conocida
+    fn modify(&mut self, entity: INT, change: impl FnOnce(&mut TransformComponent)) {
+        let entity_id = entity as EntityId;
+        let Some(transform) = self.transforms.get_mut(&entity_id) else {
+            debug!("Script: entidad {} sin transformación", entity);
+            return;
+        };
+        change(transform);
+        transform.matrix = Mat4::from_scale_rotation_translation(transform.scale, transform.rotation, transform.position);
+        self.dirty.insert(entity_id);
+    }
+}
+
+/// Temporizador de un script
+#[derive(Clone)]
+struct ScriptTimer {
+    /// Segundos que faltan
+    remaining: f32,
+    /// Función a llamar
+    callback: FnPtr,
+}
+
+/// Script cargado de una entidad
+struct ScriptInstance {
+    /// Código compilado
+    code: String,
+    /// AST del código
+    ast: AST,
+    /// Variables del script entre frames
+    scope: Scope<'static>,
+    /// Temporizadores pendientes
+    timers: Vec<ScriptTimer>,
+}
+
+/// Runtime de scripts Rhai
+pub struct ScriptRuntime {
+    /// Motor de Rhai con la API del ECS registrada
+    engine: Engine,
+    /// Estado del mundo del frame en curso
+    context: Arc<Mutex<ScriptContext>>,
+    /// Scripts cargados por entidad
+    instances: HashMap<EntityId, ScriptInstance>,
+    /// Operaciones por frame entre todos los scripts (0 = sin límite)
+    max_ops_per_frame: u64,
+    /// Operaciones consumidas en el frame por las llamadas terminadas
+    frame_ops: Arc<AtomicU64>,
+    /// Operaciones de la llamada en curso
+    call_ops: Arc<AtomicU64>,
+}
+
+impl ScriptRuntime {
+    /// Runtime con un presupuesto de operaciones por frame
+    pub fn new(max_ops_per_frame: u64) -> Self {
+        let context = Arc::new(Mutex::new(ScriptContext::default()));
+        let frame_ops = Arc::new(AtomicU64::new(0));
+        let call_ops = Arc::new(AtomicU64::new(0));
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_ops_per_frame);
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        engine.set_max_array_size(MAX_ARRAY_SIZE);
+        engine.set_max_map_size(MAX_MAP_SIZE);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+
+        // El límite de Rhai es por llamada; el del frame se comprueba aquí
+        let (used, current) = (frame_ops.clone(), call_ops.clone());
+        engine.on_progress(move |ops| {
+            current.store(ops, Ordering::Relaxed);
+            (max_ops_per_frame > 0 && used.load(Ordering::Relaxed) + ops > max_ops_per_frame)
+                .then(|| Dynamic::from("presupuesto de operaciones del frame agotado"))
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("create_entity", move |name: &str| -> INT {
+            let mut context = ctx.lock().unwrap();
+            let entity_id = crate::ecs::next_entity_id();
+            context.spawned.push(Entity {
+                id: entity_id,
+                name: name.to_string(),
+                components: Vec::new(),
+                state: EntityState {
+                    active: true,
+                    visible: true,
+                    selected: false,
+                    locked: false,
+                },
+                metadata: HashMap::new(),
+            });
+            context.transforms.insert(entity_id, identity_transform());
+            context.dirty.insert(entity_id);
+            entity_id as INT
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("set_position", move |entity: INT, x: FLOAT, y: FLOAT, z: FLOAT| {
+            ctx.lock().unwrap().modify(entity, |transform| transform.position = Vec3::new(x, y, z));
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("get_position", move |entity: INT| -> Map {
+            let context = ctx.lock().unwrap();
+            let position = context.transforms.get(&(entity as EntityId))
+                .map_or(Vec3::ZERO, |transform| transform.position);
+            vector_map(position)
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("set_rotation", move |entity: INT, x: FLOAT, y: FLOAT, z: FLOAT| {
+            let rotation = Quat::from_euler(EulerRot::YXZ, y.to_radians(), x.to_radians(), z.to_radians());
+            ctx.lock().unwrap().modify(entity, |transform| transform.rotation = rotation);
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("get_rotation", move |entity: INT| -> Map {
+            let context = ctx.lock().unwrap();
+            let rotation = context.transforms.get(&(entity as EntityId))
+                .map_or(Quat::IDENTITY, |transform| transform.rotation);
+            let (y, x, z) = rotation.to_euler(EulerRot::YXZ);
+            vector_map(Vec3::new(x.to_degrees(), y.to_degrees(), z.to_degrees()))
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("add_timer", move |secs: FLOAT, callback: FnPtr| {
+            ctx.lock().unwrap().new_timers.push(ScriptTimer { remaining: secs.max(0.0), callback });
+        });
+
+        Self {
+            engine,
+            context,
+            instances: HashMap::new(),
+            max_ops_per_frame,
+            frame_ops,
+            call_ops,
+        }
+    }
+
+    /// Runtime compartido
+    pub fn shared(max_ops_per_frame: u64) -> SharedScriptRuntime {
+        Arc::new(Mutex::new(Self::new(max_ops_per_frame)))
+    }
+
+    /// Operaciones por frame entre todos los scripts
+    pub fn max_ops_per_frame(&self) -> u64 {
+        self.max_ops_per_frame
+    }
+
+    /// Scripts cargados
+    pub fn loaded_count(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Ejecutar un frame de los scripts Rhai del mundo y encolar sus cambios
+    pub fn run_frame(&mut self, world: &ECSSystem, commands: &mut CommandBuffer, delta_time: f32) {
+        self.frame_ops.store(0, Ordering::Relaxed);
+        {
+            let mut context = self.context.lock().unwrap();
+            context.transforms = world.get_entities_with_component(ComponentType::Transform)
+                .into_iter()
+                .filter_map(|entity_id| {
+                    world.get_component::<TransformComponent>(entity_id, ComponentType::Transform)
+                        .map(|transform| (entity_id, transform))
+                })
+                .collect();
+            context.dirty.clear();
+            context.spawned.clear();
+            context.new_timers.clear();
+        }
+
+        let mut scripted = Vec::new();
+        for entity_id in world.get_entities_with_component(ComponentType::Script) {
+            let Some(script) = world.get_component::<ScriptComponent>(entity_id, ComponentType::Script) else {
+                continue;
+            };
+            if !matches!(script.script_type, ScriptType::Rhai) || !script.state.running {
+                continue;
+            }
+            scripted.push(entity_id);
+
+            let result = self.load(entity_id, &script.code).and_then(|_| self.tick(entity_id, delta_time));
+            let error = result.err().map(|error| error.to_string());
+            if let Some(error) = &error {
+                warn!("Script {} de la entidad {}: {}", script.script_id, entity_id, error);
+            }
+            if !script.state.loaded || script.state.error != error {
+                let mut script = script;
+                script.state.loaded = self.instances.contains_key(&entity_id);
+                script.state.error = error;
+                commands.update_component(entity_id, Box::new(script));
+            }
+        }
+        self.instances.retain(|entity_id, _| scripted.contains(entity_id));
+
+        let mut context = self.context.lock().unwrap();
+        let spawned: Vec<Entity> = context.spawned.drain(..).collect();
+        for entity in spawned {
+            let entity_id = entity.id;
+            context.dirty.remove(&entity_id);
+            let transform = context.transforms.get(&entity_id).cloned().unwrap_or_else(identity_transform);
+            commands.spawn_entity(entity);
+            commands.add_component(entity_id, Box::new(transform));
+        }
+        for entity_id in std::mem::take(&mut context.dirty) {
+            if let Some(transform) = context.transforms.get(&entity_id) {
+                commands.update_component(entity_id, Box::new(transform.clone()));
+            }
+        }
+    }
+
+    /// Compilar y ejecutar el código si es nuevo o cambió
+    fn load(&mut self, entity_id: EntityId, code: &str) -> Result<()> {
+        if self.instances.get(&entity_id).map_or(false, |instance| instance.code == code) {
+            return Ok(());
+        }
+        self.instances.remove(&entity_id);
+
+        let ast = self.engine.compile(code).map_err(|error| anyhow!("{}", error))?;
+        let mut scope = Scope::new();
+        scope.push("entity", entity_id as INT);
+        let result = self.engine.run_ast_with_scope(&mut scope, &ast);
+        let timers = self.take_new_timers();
+        result.map_err(|error| anyhow!("{}", error))?;
+
+        self.instances.insert(entity_id, ScriptInstance {
+            code: code.to_string(),
+            ast,
+            scope,
+            timers,
+        });
+        Ok(())
+    }
+
+    /// Disparar los temporizadores vencidos y llamar a `update`
+    fn tick(&mut self, entity_id: EntityId, delta_time: f32) -> Result<()> {
+        let Some(instance) = self.instances.get_mut(&entity_id) else {
+            return Ok(());
+        };
+
+        let mut due = Vec::new();
+        instance.timers.retain_mut(|timer| {
+            timer.remaining -= delta_time;
+            if timer.remaining <= 0.0 {
+                due.push(timer.callback.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        let update = instance.scope.get_value::<FnPtr>("update");
+        let ast = instance.ast.clone();
+        let mut result = Ok(());
+        for callback in due {
+            result = result.and(self.call(&ast, &callback, ()));
+        }
+        if let Some(update) = update {
+            result = result.and(self.call(&ast, &update, (delta_time as FLOAT,)));
+        }
+
+        let timers = self.take_new_timers();
+        if let Some(instance) = self.instances.get_mut(&entity_id) {
+            instance.timers.extend(timers);
+        }
+        result
+    }
+
+    /// Llamar a una función del script contando sus operaciones en el frame
+    fn call(&self, ast: &AST, function: &FnPtr, args: impl rhai::FuncArgs) -> Result<()> {
+        self.call_ops.store(0, Ordering::Relaxed);
+        let result = function.call::<Dynamic>(&self.engine, ast, args);
+        self.frame_ops.fetch_add(self.call_ops.load(Ordering::Relaxed), Ordering::Relaxed);
+        result.map(|_| ()).map_err(|error| anyhow!("{}", error))
+    }
+
+    /// Sacar los temporizadores pedidos durante la última ejecución
+    fn take_new_timers(&self) -> Vec<ScriptTimer> {
+        std::mem::take(&mut self.context.lock().unwrap().new_timers)
+    }
+
+    /// Descargar todos los scripts
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        *self.context.lock().unwrap() = ScriptContext::default();
+    }
+}
+
+/// Transformación en el origen de una entidad creada por un script
+fn identity_transform() -> TransformComponent {
+    TransformComponent {
+        position: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+        matrix: Mat4::IDENTITY,
+        parent: None,
+        children: Vec::new(),
+    }
+}
+
+/// Mapa de Rhai con las componentes de un vector
+fn vector_map(vector: Vec3) -> Map {
+    let mut map = Map::new();
+    map.insert("x".into(), Dynamic::from_float(vector.x));
+    map.insert("y".into(), Dynamic::from_float(vector.y));
+    map.insert("z".into(), Dynamic::from_float(vector.z));
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::{
+        ComponentConfig, ECSConfig, EntityConfig, OptimizationConfig, ScriptState, SystemConfig,
+    };
+
+    /// Delta de cada tick en segundos
+    const DELTA: f32 = 0.1;
+    const TICKS: usize = 20;
+    /// Tolerancia en grados
+    const EPSILON_DEGREES: f32 = 1e-2;
+
+    /// Script que gira su entidad a 45 °/s alrededor de Y
+    const ROTATE_45: &str = r#"
+        let angle = 0.0;
+        let update = |dt| {
+            angle += 45.0 * dt;
+            set_rotation(entity, 0.0, angle, 0.0);
+        };
+    "#;
+
+    fn ecs_config() -> ECSConfig {
+        ECSConfig {
+            enabled: true,
+            entity_config: EntityConfig { max_entities: 16, entity_pool: false, id_reuse: false },
+            component_config: ComponentConfig {
+                max_components_per_entity: 16,
+                component_cache: false,
+                auto_serialization: false,
+            },
+            system_config: SystemConfig { parallel_execution: false, system_priority: true, hot_reloading: false },
+            optimization_config: OptimizationConfig { cache_friendly: true, memory_pooling: false, batch_processing: false },
+            max_parallel_systems: 1,
+            system_hot_reloading: false,
+            systems_directory: "systems".into(),
+        }
+    }
+
+    /// Giro alrededor de Y de la transformación de una entidad, en grados
+    fn yaw_degrees(world: &ECSSystem, entity_id: EntityId) -> f32 {
+        let transform: TransformComponent = world.get_component(entity_id, ComponentType::Transform).unwrap();
+        let (yaw, pitch, roll) = transform.rotation.to_euler(EulerRot::YXZ);
+        assert!(pitch.abs() < 1e-4 && roll.abs() < 1e-4, "solo gira alrededor de Y");
+        yaw.to_degrees()
+    }
+
+    /// Un tick del `ScriptSystem`: ejecutar los scripts y aplicar sus
+    /// comandos al mundo
+    async fn tick(runtime: &SharedScriptRuntime, world: &mut ECSSystem) {
+        let mut commands = CommandBuffer::new();
+        runtime.lock().unwrap().run_frame(world, &mut commands, DELTA);
+        for command in commands.drain() {
+            world.queue_command(command).await.unwrap();
+        }
+        world.flush_commands();
+    }
+
+    #[tokio::test]
+    async fn rotation_script_advances_the_transform_each_tick() {
+        let mut world = ECSSystem::new(ecs_config());
+        let runtime = ScriptRuntime::shared(100_000);
+
+        let entity_id = world.create_entity("molino".to_string()).await.unwrap();
+        world.add_component(entity_id, Box::new(identity_transform())).await.unwrap();
+        world.add_component(entity_id, Box::new(ScriptComponent {
+            script_id: "rotar_45".to_string(),
+            script_type: ScriptType::Rhai,
+            code: ROTATE_45.to_string(),
+            state: ScriptState { loaded: false, running: true, error: None },
+            pending_calls: Vec::new(),
+        })).await.unwrap();
+        world.flush_commands();
+
+        let mut previous = 0.0;
+        for step in 1..=TICKS {
+            tick(&runtime, &mut world).await;
+            let yaw = yaw_degrees(&world, entity_id);
+            assert!(yaw > previous, "tick {}: {}° no avanza desde {}°", step, yaw, previous);
+            let expected = 45.0 * DELTA * step as f32;
+            assert!((yaw - expected).abs() < EPSILON_DEGREES, "tick {}: {}° en vez de {}°", step, yaw, expected);
+            previous = yaw;
+        }
+
+        let script: ScriptComponent = world.get_component(entity_id, ComponentType::Script).unwrap();
+        assert!(script.state.loaded);
+        assert_eq!(script.state.error, None);
+        assert_eq!(runtime.lock().unwrap().loaded_count(), 1);
+    }
+}
@@ -0,0 +1,9 @@
+//! Errores de compilación de `#[derive(Component)]`. Las salidas esperadas
+//! están junto a cada caso en `tests/ui/*.stderr`; se regeneran con
+//! `TRYBUILD=overwrite cargo test --test derive_component`
+
+#[test]
+fn derive_component_reports_missing_bounds() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,93 @@
+//! Descubrimiento de peers con la DHT entre nodos en el mismo proceso
+//!
+//! Los mensajes que cada nodo deja en su outbox se entregan al destinatario
+//! por su `PeerId`, como haría el canal punto a punto de `NetworkingSystem`.
+//! Una ronda de búsqueda es una entrega de peticiones y otra de respuestas.
+
+use libp2p::multiaddr::Multiaddr;
+use libp2p::PeerId;
+use metaverso_engine::networking::dht::{Dht, DhtContact, K};
+use std::time::{Duration, Instant};
+
+const NODES: usize = 5;
+const MAX_ROUNDS: u32 = 3;
+
+fn contact(port: u16) -> DhtContact {
+    let peer = PeerId::random();
+    let address: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}/p2p/{}", port, peer).parse().unwrap();
+    DhtContact { peer, address }
+}
+
+/// Entregar los outboxes hasta que no quede nada en vuelo. Devuelve el
+/// número de entregas
+fn deliver_all(nodes: &mut [Dht], contacts: &[DhtContact], now: Instant) -> u32 {
+    let mut deliveries = 0;
+    loop {
+        let messages: Vec<_> = nodes.iter_mut().flat_map(|node| node.drain_outbox()).collect();
+        if messages.is_empty() {
+            return deliveries;
+        }
+        deliveries += 1;
+        for (to, message) in messages {
+            let index = contacts.iter().position(|contact| contact.peer == to.peer).expect("destinatario conocido");
+            nodes[index].handle_message(message.sender.peer, message, now);
+        }
+    }
+}
+
+#[test]
+fn five_nodes_discover_each_other_within_three_rounds() {
+    let now = Instant::now();
+    let contacts: Vec<DhtContact> = (0..NODES).map(|i| contact(4000 + i as u16)).collect();
+    let mut nodes: Vec<Dht> = contacts.iter()
+        .map(|contact| Dht::new(contact.clone(), K, Duration::from_secs(60)))
+        .collect();
+
+    // Todos conocen solo al primero, que no conoce a nadie
+    for node in &mut nodes[1..] {
+        assert!(node.add_contact(contacts[0].clone()));
+        node.bootstrap(now);
+    }
+    let deliveries = deliver_all(&mut nodes, &contacts, now);
+    assert!(deliveries <= 2 * MAX_ROUNDS, "{} entregas", deliveries);
+
+    for (i, node) in nodes.iter_mut().enumerate() {
+        node.tick(now);
+        for (j, other) in contacts.iter().enumerate() {
+            if i != j {
+                assert!(node.routing_table().contains(&other.peer), "el nodo {} no conoce al {}", i, j);
+            }
+        }
+        let stats = node.stats();
+        assert_eq!(stats.routing_table_size, NODES - 1);
+        assert_eq!(stats.active_lookups, 0);
+        if i > 0 {
+            assert_eq!(stats.completed_lookups, 1);
+            assert!(stats.last_lookup_rounds <= MAX_ROUNDS, "nodo {}: {} rondas", i, stats.last_lookup_rounds);
+        }
+    }
+}
+
+#[test]
+fn values_put_by_one_node_are_found_by_another() {
+    let now = Instant::now();
+    let contacts: Vec<DhtContact> = (0..NODES).map(|i| contact(5000 + i as u16)).collect();
+    let mut nodes: Vec<Dht> = contacts.iter()
+        .map(|contact| Dht::new(contact.clone(), K, Duration::from_secs(60)))
+        .collect();
+    for node in &mut nodes[1..] {
+        node.add_contact(contacts[0].clone());
+        node.bootstrap(now);
+    }
+    deliver_all(&mut nodes, &contacts, now);
+
+    let key = [7u8; 32];
+    nodes[4].put_value(key, b"world-record".to_vec(), now);
+    deliver_all(&mut nodes, &contacts, now);
+
+    let found = nodes[1].get_value(key, now).or_else(|| {
+        deliver_all(&mut nodes, &contacts, now);
+        nodes[1].get_value(key, now)
+    });
+    assert_eq!(found, Some(b"world-record".to_vec()));
+}
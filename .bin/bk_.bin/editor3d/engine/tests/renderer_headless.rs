@@ -0,0 +1,1137 @@
+//! Renderizado headless de escenas del ECS con el backend wgpu
+//!
+//! Cada test abre el adaptador de software si la máquina lo tiene (o
+//! cualquier otro) y dibuja offscreen. Sin ningún adaptador el test se omite.
+//! Los tests de culling solo envían la escena y no necesitan adaptador.
+
+use anyhow::Result;
+use glam::{Mat4, Quat, Vec3, Vec4};
+use metaverso_engine::camera::xr::{self as camera_xr, EyeFov, EyeView, TrackedPose, XrFrame};
+use metaverso_engine::ecs::{
+    self, CameraComponent, CameraType, ComponentConfig, ECSConfig, EntityConfig, LightComponent,
+    LightType, MaterialComponent, MeshComponent, OptimizationConfig, SystemConfig, TransformComponent,
+    RENDER_LAYER_DEFAULT,
+};
+use metaverso_engine::materials::MaterialSystem;
+use metaverso_engine::renderer::backend::wgpu_backend::{WgpuBackend, WgpuBackendOptions};
+use metaverso_engine::renderer::backend::{ImageData, MaterialKind};
+use metaverso_engine::renderer::{
+    AntialiasingConfig, AntialiasingType, BiasConfig, BloomConfig, CascadeConfig, ColorGradingConfig,
+    DepthOfFieldConfig, EffectsConfig, LODConfig, Material, MaterialProperties, MaterialType,
+    MotionBlurConfig, QualityConfig, QualityLevel, RenderAPI, RendererConfig, RendererSystem,
+    SSAOConfig, ShadowConfig, Texture, TextureConfig, TextureFilter, TextureFormat, TextureType, TextureWrap,
+};
+use metaverso_engine::renderer::postprocess::PostEffect;
+use metaverso_engine::renderer::shader_reload::ShaderReloadEvent;
+use metaverso_engine::renderer::streaming::{chain_bytes, fallback_mip};
+use metaverso_engine::renderer::xr::{XrGraphics, XrRuntime};
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_2;
+use std::path::PathBuf;
+
+/// Lado de la imagen de los tests
+const SIZE: u32 = 64;
+
+fn create_ecs_config() -> ECSConfig {
+    ECSConfig {
+        enabled: true,
+        entity_config: EntityConfig {
+            max_entities: 100,
+            entity_pool: false,
+            id_reuse: false,
+        },
+        component_config: ComponentConfig {
+            max_components_per_entity: 16,
+            component_cache: false,
+            auto_serialization: false,
+        },
+        system_config: SystemConfig {
+            parallel_execution: false,
+            system_priority: true,
+            hot_reloading: false,
+        },
+        optimization_config: OptimizationConfig {
+            cache_friendly: true,
+            memory_pooling: false,
+            batch_processing: false,
+        },
+        max_parallel_systems: 1,
+        system_hot_reloading: false,
+        systems_directory: PathBuf::from("systems"),
+    }
+}
+
+/// Configuración sin sombras, LOD ni efectos; el backend lo pone el test
+fn create_renderer_config(sample_count: u32) -> RendererConfig {
+    RendererConfig {
+        enabled: true,
+        render_api: RenderAPI::Custom("headless".to_string()),
+        quality_config: QualityConfig {
+            quality_level: QualityLevel::Medium,
+            resolution: [SIZE, SIZE],
+            vsync: false,
+            antialiasing: AntialiasingConfig {
+                antialiasing_type: if sample_count > 1 { AntialiasingType::MSAA } else { AntialiasingType::None },
+                antialiasing_level: sample_count,
+                fxaa: false,
+                taa: false,
+            },
+            shadows: ShadowConfig {
+                enabled: false,
+                resolution: 1024,
+                cascade: CascadeConfig {
+                    cascade_count: 1,
+                    split_factor: 0.5,
+                    bias: BiasConfig { constant_bias: 0.0, slope_bias: 0.0 },
+                },
+                soft_shadows: false,
+            },
+            lod: LODConfig {
+                enabled: false,
+                levels: Vec::new(),
+                transition_distance: 0.0,
+            },
+        },
+        effects_config: EffectsConfig {
+            bloom: BloomConfig { enabled: false, intensity: 0.0, threshold: 1.0, radius: 1.0 },
+            ssao: SSAOConfig { enabled: false, radius: 0.0, bias: 0.0, intensity: 0.0 },
+            motion_blur: MotionBlurConfig { enabled: false, intensity: 0.0, samples: 0 },
+            depth_of_field: DepthOfFieldConfig { enabled: false, focal_distance: 0.0, aperture: 0.0, bokeh: false },
+            color_grading: ColorGradingConfig { enabled: false, lut: None, exposure: 0.0, contrast: 1.0, saturation: 1.0 },
+        },
+        optimization_config: metaverso_engine::renderer::OptimizationConfig {
+            frustum_culling: true,
+            occlusion_culling: false,
+            instancing: false,
+            batching: false,
+            lod: false,
+            distance_culling: false,
+            max_draw_distance: 0.0,
+            instancing_threshold: 8,
+            max_decals: 0,
+            max_concurrent_loads: 1,
+        },
+        texture_budget_bytes: 64 * 1024 * 1024,
+        shader_cache_dir: None,
+        precompile_on_startup: false,
+        enable_atlas_packing: false,
+        atlas_max_size: 256,
+    }
+}
+
+/// Backend headless sobre el adaptador de software si existe, o sobre
+/// cualquiera; None si la máquina no tiene ninguno
+async fn headless_backend(sample_count: u32) -> Option<WgpuBackend> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let mut options = wgpu::RequestAdapterOptions {
+        force_fallback_adapter: true,
+        ..Default::default()
+    };
+    let adapter = match instance.request_adapter(&options).await {
+        Some(adapter) => adapter,
+        None => {
+            options.force_fallback_adapter = false;
+            instance.request_adapter(&options).await?
+        }
+    };
+    let (device, queue) = adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("test-device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::default().using_resolution(adapter.limits()),
+        },
+        None,
+    ).await.ok()?;
+    let options = WgpuBackendOptions {
+        width: SIZE,
+        height: SIZE,
+        sample_count,
+        precompile_shaders: false,
+        ..Default::default()
+    };
+    WgpuBackend::from_device(instance, adapter, device, queue, None, options).ok()
+}
+
+/// Renderer iniciado sobre el backend headless, con las muestras MSAA de
+/// la configuración
+async fn headless_renderer(config: RendererConfig) -> Option<RendererSystem> {
+    let backend = headless_backend(config.quality_config.antialiasing.antialiasing_level.max(1)).await?;
+    let mut renderer = RendererSystem::new(config);
+    renderer.initialize().await.ok()?;
+    renderer.set_backend(Box::new(backend));
+    Some(renderer)
+}
+
+/// Material sin iluminación de un color
+async fn create_unlit_material(renderer: &mut RendererSystem, id: &str, base_color: Vec4) -> Result<()> {
+    renderer.create_material(Material {
+        id: id.to_string(),
+        name: id.to_string(),
+        material_type: MaterialType::Unlit,
+        properties: MaterialProperties {
+            base_color,
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive: Vec3::ZERO,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_cutoff: 0.0,
+            double_sided: false,
+        },
+        textures: HashMap::new(),
+        shader: "unlit".to_string(),
+    }).await
+}
+
+fn transform_at(position: Vec3) -> TransformComponent {
+    posed_transform(position, Quat::IDENTITY)
+}
+
+fn posed_transform(position: Vec3, rotation: Quat) -> TransformComponent {
+    TransformComponent {
+        position,
+        rotation,
+        scale: Vec3::ONE,
+        matrix: Mat4::from_rotation_translation(rotation, position),
+        parent: None,
+        children: Vec::new(),
+    }
+}
+
+/// Orientación de una cámara en `eye` que mira a `target`
+fn looking_at(eye: Vec3, target: Vec3) -> Quat {
+    let (_, rotation, _) = Mat4::look_at_rh(eye, target, Vec3::Y).inverse().to_scale_rotation_translation();
+    rotation
+}
+
+/// Cámara en perspectiva de 60 grados en `eye` mirando a `target`
+async fn add_camera(world: &mut ecs::ECSSystem, eye: Vec3, target: Vec3) -> Result<ecs::EntityId> {
+    let camera = world.create_entity("camera".to_string()).await?;
+    world.add_component(camera, Box::new(posed_transform(eye, looking_at(eye, target)))).await?;
+    world.add_component(camera, Box::new(CameraComponent {
+        camera_type: CameraType::Perspective,
+        fov: 60.0,
+        aspect_ratio: 0.0,
+        near_plane: 0.1,
+        far_plane: 100.0,
+        projection: Mat4::IDENTITY,
+        view: Mat4::IDENTITY,
+    })).await?;
+    Ok(camera)
+}
+
+/// Píxel de la imagen donde la cámara de `add_camera` ve `point`
+fn project(eye: Vec3, target: Vec3, point: Vec3) -> (u32, u32) {
+    let projection = Mat4::perspective_rh(60f32.to_radians(), 1.0, 0.1, 100.0);
+    let ndc = (projection * Mat4::look_at_rh(eye, target, Vec3::Y)).project_point3(point);
+    let x = (ndc.x * 0.5 + 0.5) * SIZE as f32;
+    let y = (0.5 - ndc.y * 0.5) * SIZE as f32;
+    (x as u32, y as u32)
+}
+
+/// Malla plana en el plano XY, de cara a +Z, con las esquinas dadas en
+/// sentido antihorario (3 para un triángulo, 4 para un quad). Sin material
+/// se dibuja en blanco con el pipeline por defecto
+fn flat_mesh(mesh_id: &str, material_id: Option<&str>, corners: &[Vec3]) -> MeshComponent {
+    let indices = if corners.len() == 4 { vec![0, 1, 2, 0, 2, 3] } else { vec![0, 1, 2] };
+    MeshComponent {
+        mesh_id: mesh_id.to_string(),
+        vertices: corners.to_vec(),
+        normals: vec![Vec3::Z; corners.len()],
+        uvs: vec![Vec3::ZERO; corners.len()],
+        indices,
+        material_id: material_id.map(str::to_string),
+        lod_level: 0,
+        lod_indices: Vec::new(),
+        joint_indices: Vec::new(),
+        joint_weights: Vec::new(),
+        render_layers: RENDER_LAYER_DEFAULT,
+    }
+}
+
+/// Caja de lado `2 * half` centrada en el origen, con las caras hacia fuera
+fn box_mesh(mesh_id: &str, half: f32) -> MeshComponent {
+    let mut vertices = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+    for normal in [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z] {
+        // u × v = normal: las esquinas quedan en sentido antihorario vistas desde fuera
+        let u = normal.any_orthonormal_vector();
+        let v = normal.cross(u);
+        let first = vertices.len() as u32;
+        for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            vertices.push((normal + u * a + v * b) * half);
+            normals.push(normal);
+        }
+        indices.extend([0, 1, 2, 0, 2, 3].map(|i| first + i));
+    }
+    MeshComponent {
+        mesh_id: mesh_id.to_string(),
+        uvs: vec![Vec3::ZERO; vertices.len()],
+        vertices,
+        normals,
+        indices,
+        material_id: None,
+        lod_level: 0,
+        lod_indices: Vec::new(),
+        joint_indices: Vec::new(),
+        joint_weights: Vec::new(),
+        render_layers: RENDER_LAYER_DEFAULT,
+    }
+}
+
+/// Escena con una cámara en (0, 0, 5) mirando a -Z, un quad rojo en z = 0 y
+/// detrás, en z = -1, un triángulo azul mayor que lo tapa
+async fn overlapping_scene(renderer: &mut RendererSystem) -> Result<ecs::ECSSystem> {
+    create_unlit_material(renderer, "red", Vec4::new(1.0, 0.0, 0.0, 1.0)).await?;
+    create_unlit_material(renderer, "blue", Vec4::new(0.0, 0.0, 1.0, 1.0)).await?;
+
+    let mut world = ecs::ECSSystem::new(create_ecs_config());
+    add_camera(&mut world, Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO).await?;
+
+    let far = world.create_entity("far".to_string()).await?;
+    world.add_component(far, Box::new(transform_at(Vec3::new(0.0, 0.0, -1.0)))).await?;
+    world.add_component(far, Box::new(flat_mesh("triangle", Some("blue"), &[
+        Vec3::new(-1.5, -1.5, 0.0),
+        Vec3::new(1.5, -1.5, 0.0),
+        Vec3::new(0.0, 1.5, 0.0),
+    ]))).await?;
+
+    let near = world.create_entity("near".to_string()).await?;
+    world.add_component(near, Box::new(transform_at(Vec3::ZERO))).await?;
+    world.add_component(near, Box::new(flat_mesh("quad", Some("red"), &[
+        Vec3::new(-0.5, -0.5, 0.0),
+        Vec3::new(0.5, -0.5, 0.0),
+        Vec3::new(0.5, 0.5, 0.0),
+        Vec3::new(-0.5, 0.5, 0.0),
+    ]))).await?;
+
+    world.flush_commands();
+    Ok(world)
+}
+
+/// Píxeles del borde del triángulo azul sobre el fondo: ni fondo ni azul
+/// pleno, mezcla de las muestras del MSAA
+fn blended_edge_pixels(image: &ImageData, full_blue: u8) -> usize {
+    image.pixels.chunks(4)
+        .filter(|texel| texel[0] == 0 && texel[1] == 0 && texel[2] > 8 && texel[2] < full_blue.saturating_sub(8))
+        .count()
+}
+
+#[tokio::test]
+async fn ecs_entities_render_with_depth_and_msaa_resolve() -> Result<()> {
+    let mut images = Vec::new();
+    for sample_count in [1, 4] {
+        let Some(mut renderer) = headless_renderer(create_renderer_config(sample_count)).await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return Ok(());
+        };
+        let world = overlapping_scene(&mut renderer).await?;
+        renderer.submit_scene(&world);
+        let image = renderer.capture_frame().await?;
+        assert_eq!((image.width, image.height), (SIZE, SIZE));
+        assert_eq!(renderer.get_stats().rendered_objects, 2);
+        images.push(image);
+    }
+
+    for image in &images {
+        // El quad rojo está delante del triángulo azul
+        let center = image.pixel(SIZE / 2, SIZE / 2).unwrap();
+        assert!(center[0] > 128 && center[2] == 0, "centro {:?}", center);
+        // Bajo el quad solo está el triángulo
+        let below = image.pixel(SIZE / 2, SIZE * 5 / 8).unwrap();
+        assert!(below[2] > 128 && below[0] == 0, "bajo el quad {:?}", below);
+        // Fuera de las dos mallas queda el color de fondo
+        assert_eq!(image.pixel(1, 1).unwrap(), [0, 0, 0, 255]);
+    }
+
+    // Sin MSAA cada píxel es fondo o triángulo; con 4 muestras los bordes
+    // inclinados se resuelven con valores intermedios
+    let full_blue = images[0].pixel(SIZE / 2, SIZE * 5 / 8).unwrap()[2];
+    assert_eq!(blended_edge_pixels(&images[0], full_blue), 0);
+    assert!(blended_edge_pixels(&images[1], full_blue) > 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn box_casts_a_cascaded_shadow_on_the_plane() -> Result<()> {
+    let mut config = create_renderer_config(1);
+    config.quality_config.shadows = ShadowConfig {
+        enabled: true,
+        resolution: 1024,
+        cascade: CascadeConfig {
+            cascade_count: 2,
+            split_factor: 0.5,
+            bias: BiasConfig { constant_bias: 0.001, slope_bias: 1.0 },
+        },
+        soft_shadows: false,
+    };
+    let Some(mut renderer) = headless_renderer(config).await else {
+        eprintln!("Sin adaptador wgpu: se omite el test");
+        return Ok(());
+    };
+
+    let eye = Vec3::new(0.0, 4.0, 6.0);
+    let mut world = ecs::ECSSystem::new(create_ecs_config());
+    add_camera(&mut world, eye, Vec3::ZERO).await?;
+
+    // Luz cenital: su -Z apunta hacia -Y
+    let sun = world.create_entity("sun".to_string()).await?;
+    world.add_component(sun, Box::new(posed_transform(Vec3::new(0.0, 10.0, 0.0), Quat::from_rotation_x(-FRAC_PI_2)))).await?;
+    world.add_component(sun, Box::new(LightComponent {
+        light_type: LightType::Directional,
+        color: Vec3::ONE,
+        intensity: 1.0,
+        range: 0.0,
+        angle: 0.0,
+        shadows: true,
+        shadow_config: ecs::ShadowConfig { resolution: 1024, bias: 0.001, soft_shadows: false },
+    })).await?;
+
+    // Suelo de 10 x 10 en y = 0 (el quad XY girado hacia +Y)
+    let ground = world.create_entity("ground".to_string()).await?;
+    world.add_component(ground, Box::new(posed_transform(Vec3::ZERO, Quat::from_rotation_x(-FRAC_PI_2)))).await?;
+    world.add_component(ground, Box::new(flat_mesh("ground", None, &[
+        Vec3::new(-5.0, -5.0, 0.0),
+        Vec3::new(5.0, -5.0, 0.0),
+        Vec3::new(5.0, 5.0, 0.0),
+        Vec3::new(-5.0, 5.0, 0.0),
+    ]))).await?;
+
+    // Caja de 1 m flotando medio metro sobre el suelo, sin tapar a la
+    // cámara el suelo que tiene debajo
+    let cube = world.create_entity("box".to_string()).await?;
+    world.add_component(cube, Box::new(transform_at(Vec3::new(0.0, 1.0, 0.0)))).await?;
+    world.add_component(cube, Box::new(box_mesh("box", 0.5))).await?;
+    world.flush_commands();
+
+    renderer.submit_scene(&world);
+    let image = renderer.capture_frame().await?;
+
+    let (x, y) = project(eye, Vec3::ZERO, Vec3::new(0.0, 0.0, 0.2));
+    let shadowed = image.pixel(x, y).unwrap();
+    let (x, y) = project(eye, Vec3::ZERO, Vec3::new(2.0, 0.0, 2.0));
+    let lit = image.pixel(x, y).unwrap();
+    assert!(
+        u32::from(shadowed[0]) + 32 < u32::from(lit[0]),
+        "sombra {:?}, iluminado {:?}", shadowed, lit,
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn frustum_culling_skips_meshes_behind_the_camera() -> Result<()> {
+    let mut renderer = RendererSystem::new(create_renderer_config(1));
+    renderer.initialize().await?;
+
+    let mut config = create_ecs_config();
+    config.entity_config.max_entities = 2000;
+    let mut world = ecs::ECSSystem::new(config);
+    add_camera(&mut world, Vec3::ZERO, Vec3::NEG_Z).await?;
+
+    // 500 triángulos en una rejilla a 20 m delante de la cámara y otros 500
+    // en la misma rejilla a 20 m detrás
+    for i in 0..1000 {
+        let z = if i < 500 { -20.0 } else { 20.0 };
+        let cell = i % 500;
+        let position = Vec3::new((cell % 25) as f32 * 0.4 - 4.8, (cell / 25) as f32 * 0.4 - 4.0, z);
+        let entity = world.create_entity(format!("mesh{}", i)).await?;
+        world.add_component(entity, Box::new(transform_at(position))).await?;
+        world.add_component(entity, Box::new(flat_mesh("triangle", None, &[
+            Vec3::new(-0.1, -0.1, 0.0),
+            Vec3::new(0.1, -0.1, 0.0),
+            Vec3::new(0.0, 0.1, 0.0),
+        ]))).await?;
+    }
+    world.flush_commands();
+
+    renderer.submit_scene(&world);
+    let stats = renderer.get_stats();
+    assert_eq!(stats.rendered_objects, 500);
+    assert_eq!(stats.culled_objects, 500);
+    Ok(())
+}
+
+/// Escena con un muro rojo de 4x4 en z = 0 y detrás, en z = -3, una caja
+/// verde que el muro tapa desde la cámara en (0, 0, 5). Devuelve el mundo y
+/// la cámara
+async fn walled_scene(renderer: &mut RendererSystem) -> Result<(ecs::ECSSystem, ecs::EntityId)> {
+    create_unlit_material(renderer, "red", Vec4::new(1.0, 0.0, 0.0, 1.0)).await?;
+    create_unlit_material(renderer, "green", Vec4::new(0.0, 1.0, 0.0, 1.0)).await?;
+
+    let mut world = ecs::ECSSystem::new(create_ecs_config());
+    let camera = add_camera(&mut world, Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -3.0)).await?;
+
+    let wall = world.create_entity("wall".to_string()).await?;
+    world.add_component(wall, Box::new(transform_at(Vec3::ZERO))).await?;
+    world.add_component(wall, Box::new(flat_mesh("wall", Some("red"), &[
+        Vec3::new(-2.0, -2.0, 0.0),
+        Vec3::new(2.0, -2.0, 0.0),
+        Vec3::new(2.0, 2.0, 0.0),
+        Vec3::new(-2.0, 2.0, 0.0),
+    ]))).await?;
+
+    let hidden = world.create_entity("hidden".to_string()).await?;
+    world.add_component(hidden, Box::new(transform_at(Vec3::new(0.0, 0.0, -3.0)))).await?;
+    world.add_component(hidden, Box::new(MeshComponent {
+        material_id: Some("green".to_string()),
+        ..box_mesh("hidden", 0.5)
+    })).await?;
+
+    world.flush_commands();
+    Ok((world, camera))
+}
+
+/// Llevar la cámara a un lado del muro, mirando a la caja de `walled_scene`
+async fn move_around_the_wall(world: &mut ecs::ECSSystem, camera: ecs::EntityId) -> Result<(Vec3, Vec3)> {
+    let (eye, target) = (Vec3::new(6.0, 0.0, -3.0), Vec3::new(0.0, 0.0, -3.0));
+    world.update_component(camera, Box::new(posed_transform(eye, looking_at(eye, target)))).await?;
+    world.flush_commands();
+    Ok((eye, target))
+}
+
+#[tokio::test]
+async fn occluded_box_is_skipped_and_reappears_in_the_same_frame() -> Result<()> {
+    // El backend simulado hace las dos fases en la CPU y da los contadores
+    // del mismo frame
+    let mut config = create_renderer_config(1);
+    config.render_api = RenderAPI::Custom("mock".to_string());
+    config.optimization_config.occlusion_culling = true;
+    let mut renderer = RendererSystem::new(config);
+    renderer.initialize().await?;
+    let (mut world, camera) = walled_scene(&mut renderer).await?;
+
+    // Sin pirámide del frame anterior se dibuja todo
+    renderer.submit_scene(&world);
+    renderer.render().await?;
+    let first = renderer.get_stats();
+    assert_eq!(first.rendered_objects, 2);
+    assert_eq!(first.occluded_objects, 0);
+
+    // Con la pirámide del primer frame la caja queda oculta tras el muro
+    renderer.submit_scene(&world);
+    renderer.render().await?;
+    let hidden = renderer.get_stats();
+    assert_eq!(hidden.occlusion_tested, 2);
+    assert_eq!(hidden.occluded_objects, 1);
+    assert_eq!(hidden.rendered_objects, 1);
+    assert_eq!(hidden.draw_calls, first.draw_calls - 1);
+
+    // Al rodear el muro la fase temprana aún la rechaza con la cámara
+    // anterior, pero la tardía la recupera en el mismo frame
+    move_around_the_wall(&mut world, camera).await?;
+    renderer.submit_scene(&world);
+    renderer.render().await?;
+    let around = renderer.get_stats();
+    assert_eq!(around.occluded_objects, 0);
+    assert_eq!(around.rendered_objects, 2);
+    assert_eq!(around.draw_calls, first.draw_calls);
+    Ok(())
+}
+
+#[tokio::test]
+async fn box_behind_the_wall_shows_up_when_the_camera_moves() -> Result<()> {
+    let mut config = create_renderer_config(1);
+    config.optimization_config.occlusion_culling = true;
+    let Some(mut renderer) = headless_renderer(config).await else {
+        eprintln!("Sin adaptador wgpu: se omite el test");
+        return Ok(());
+    };
+    let (mut world, camera) = walled_scene(&mut renderer).await?;
+
+    // Dos frames para que la fase temprana tenga pirámide: solo se ve el muro
+    for _ in 0..2 {
+        renderer.submit_scene(&world);
+        let image = renderer.capture_frame().await?;
+        let center = image.pixel(SIZE / 2, SIZE / 2).unwrap();
+        assert!(center[0] > 128 && center[1] == 0, "centro {:?}", center);
+    }
+
+    // El primer frame tras rodear el muro ya muestra la caja
+    let (eye, target) = move_around_the_wall(&mut world, camera).await?;
+    renderer.submit_scene(&world);
+    let image = renderer.capture_frame().await?;
+    let (x, y) = project(eye, target, target);
+    let center = image.pixel(x, y).unwrap();
+    assert!(center[1] > 128 && center[0] == 0, "caja {:?}", center);
+    Ok(())
+}
+
+/// Material PBR dieléctrico blanco con la rugosidad dada
+fn pbr_component(material_id: &str, roughness: f32) -> MaterialComponent {
+    MaterialComponent {
+        material_id: material_id.to_string(),
+        material_type: ecs::MaterialType::PBR,
+        properties: HashMap::from([("metallic".to_string(), 0.0), ("roughness".to_string(), roughness)]),
+        textures: HashMap::new(),
+        shader: None,
+    }
+}
+
+/// Colores iguales salvo el redondeo entre píxeles vecinos
+fn same_color(a: [u8; 4], b: [u8; 4]) -> bool {
+    a.iter().zip(b).all(|(x, y)| x.abs_diff(y) <= 2)
+}
+
+#[tokio::test]
+async fn shared_material_is_uploaded_once_and_roughness_reaches_the_next_frame() -> Result<()> {
+    let Some(mut renderer) = headless_renderer(create_renderer_config(1)).await else {
+        eprintln!("Sin adaptador wgpu: se omite el test");
+        return Ok(());
+    };
+    let mut materials = MaterialSystem::new();
+    materials.initialize().await.map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    let eye = Vec3::new(0.0, 0.0, 5.0);
+    let mut world = ecs::ECSSystem::new(create_ecs_config());
+    add_camera(&mut world, eye, Vec3::ZERO).await?;
+
+    // Luz desde la cámara: su -Z apunta hacia los quads
+    let sun = world.create_entity("sun".to_string()).await?;
+    world.add_component(sun, Box::new(transform_at(Vec3::new(0.0, 0.0, 10.0)))).await?;
+    world.add_component(sun, Box::new(LightComponent {
+        light_type: LightType::Directional,
+        color: Vec3::ONE,
+        intensity: 1.0,
+        range: 0.0,
+        angle: 0.0,
+        shadows: false,
+        shadow_config: ecs::ShadowConfig { resolution: 1024, bias: 0.001, soft_shadows: false },
+    })).await?;
+
+    // Dos quads con el mismo material
+    let mut quads = Vec::new();
+    for x in [-0.6, 0.6] {
+        let quad = world.create_entity(format!("quad{}", quads.len())).await?;
+        world.add_component(quad, Box::new(transform_at(Vec3::new(x, 0.0, 0.0)))).await?;
+        world.add_component(quad, Box::new(flat_mesh("quad", Some("shared"), &[
+            Vec3::new(-0.5, -0.5, 0.0),
+            Vec3::new(0.5, -0.5, 0.0),
+            Vec3::new(0.5, 0.5, 0.0),
+            Vec3::new(-0.5, 0.5, 0.0),
+        ]))).await?;
+        world.add_component(quad, Box::new(pbr_component("shared", 1.0))).await?;
+        quads.push(quad);
+    }
+    world.flush_commands();
+
+    // Las dos entidades cuentan como un único material, que se sube una vez
+    assert_eq!(materials.sync_components(&world), 1);
+    materials.update(&mut renderer).await.map_err(|e| anyhow::anyhow!("{}", e))?;
+    assert_eq!(materials.sync_components(&world), 0);
+
+    renderer.submit_scene(&world);
+    let rough = renderer.capture_frame().await?;
+    let left = project(eye, Vec3::ZERO, Vec3::new(-0.6, 0.0, 0.0));
+    let right = project(eye, Vec3::ZERO, Vec3::new(0.6, 0.0, 0.0));
+    let before = rough.pixel(left.0, left.1).unwrap();
+    assert!(before[0] > 0, "quad sin iluminar {:?}", before);
+    assert!(same_color(before, rough.pixel(right.0, right.1).unwrap()));
+
+    // Con la luz alineada con la vista, bajar la rugosidad concentra el
+    // especular y aclara el siguiente frame
+    for &quad in &quads {
+        world.update_component(quad, Box::new(pbr_component("shared", 0.2))).await?;
+    }
+    world.flush_commands();
+    assert_eq!(materials.sync_components(&world), 1);
+    materials.update(&mut renderer).await.map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    renderer.submit_scene(&world);
+    let smooth = renderer.capture_frame().await?;
+    let after = smooth.pixel(left.0, left.1).unwrap();
+    assert!(u32::from(after[0]) > u32::from(before[0]) + 16, "rugoso {:?}, pulido {:?}", before, after);
+    assert!(same_color(after, smooth.pixel(right.0, right.1).unwrap()));
+    Ok(())
+}
+
+/// Escena con la cámara en (0, 0, 5) y un quad sin iluminar de `color` con
+/// las esquinas (min, -1) y (max, 1) en z = 0
+async fn quad_scene(renderer: &mut RendererSystem, color: Vec4, min_x: f32, max_x: f32) -> Result<ecs::ECSSystem> {
+    create_unlit_material(renderer, "quad", color).await?;
+    let mut world = ecs::ECSSystem::new(create_ecs_config());
+    add_camera(&mut world, Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO).await?;
+    let quad = world.create_entity("quad".to_string()).await?;
+    world.add_component(quad, Box::new(transform_at(Vec3::ZERO))).await?;
+    world.add_component(quad, Box::new(flat_mesh("quad", Some("quad"), &[
+        Vec3::new(min_x, -1.0, 0.0),
+        Vec3::new(max_x, -1.0, 0.0),
+        Vec3::new(max_x, 1.0, 0.0),
+        Vec3::new(min_x, 1.0, 0.0),
+    ]))).await?;
+    world.flush_commands();
+    Ok(world)
+}
+
+#[tokio::test]
+async fn bright_quad_blooms_beyond_its_rect() -> Result<()> {
+    let mut outside = Vec::new();
+    for bloom in [false, true] {
+        let mut config = create_renderer_config(1);
+        config.effects_config.bloom = BloomConfig { enabled: bloom, intensity: 1.0, threshold: 1.0, radius: 5.0 };
+        let Some(mut renderer) = headless_renderer(config).await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return Ok(());
+        };
+        let world = quad_scene(&mut renderer, Vec4::new(8.0, 8.0, 8.0, 1.0), -0.5, 0.5).await?;
+        renderer.submit_scene(&world);
+        let image = renderer.capture_frame().await?;
+
+        // Cuatro píxeles a la derecha del borde del quad
+        let (x, y) = project(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::new(0.5, 0.0, 0.0));
+        assert!(image.pixel(x - 2, y).unwrap()[0] > 200);
+        outside.push(image.pixel(x + 4, y).unwrap()[0]);
+    }
+    assert_eq!(outside[0], 0);
+    assert!(outside[1] > 8, "sin bloom fuera del quad: {:?}", outside);
+    Ok(())
+}
+
+/// Suma de los canales de color de un píxel
+fn luminance(rgba: [u8; 4]) -> u32 {
+    rgba[..3].iter().map(|&channel| channel as u32).sum()
+}
+
+#[tokio::test]
+async fn light_above_the_white_point_brightens_its_neighbours() -> Result<()> {
+    // Bombilla de 0.3 m (unos 3 píxeles) a 5 veces el blanco, sin nada detrás
+    let center = project(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::ZERO);
+    let neighbours = [
+        (center.0 + 4, center.1),
+        (center.0 - 4, center.1),
+        (center.0, center.1 + 4),
+        (center.0, center.1 - 4),
+    ];
+    let mut frames = Vec::new();
+    for bloom in [false, true] {
+        let mut config = create_renderer_config(1);
+        config.effects_config.bloom = BloomConfig { enabled: bloom, intensity: 1.0, threshold: 1.0, radius: 5.0 };
+        let Some(mut renderer) = headless_renderer(config).await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return Ok(());
+        };
+        create_unlit_material(&mut renderer, "bulb", Vec4::new(5.0, 5.0, 5.0, 1.0)).await?;
+        let mut world = ecs::ECSSystem::new(create_ecs_config());
+        add_camera(&mut world, Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO).await?;
+        let bulb = world.create_entity("bulb".to_string()).await?;
+        world.add_component(bulb, Box::new(transform_at(Vec3::ZERO))).await?;
+        world.add_component(bulb, Box::new(flat_mesh("bulb", Some("bulb"), &[
+            Vec3::new(-0.15, -0.15, 0.0),
+            Vec3::new(0.15, -0.15, 0.0),
+            Vec3::new(0.15, 0.15, 0.0),
+            Vec3::new(-0.15, 0.15, 0.0),
+        ]))).await?;
+        world.flush_commands();
+
+        renderer.submit_scene(&world);
+        let image = renderer.capture_frame().await?;
+        assert!(luminance(image.pixel(center.0, center.1).unwrap()) > 600);
+        frames.push(neighbours.map(|(x, y)| luminance(image.pixel(x, y).unwrap())));
+    }
+
+    // Sin bloom los vecinos son fondo; con él el halo los aclara a todos
+    let (plain, bloomed) = (frames[0], frames[1]);
+    assert_eq!(plain, [0; 4]);
+    for (before, after) in plain.iter().zip(&bloomed) {
+        assert!(after > before, "vecinos sin halo: {:?}", bloomed);
+    }
+    Ok(())
+}
+
+/// Shader de materiales sin iluminar que pinta todo de verde. La línea 14
+/// devuelve el color
+const GREEN_UNLIT_SHADER: &str = "struct SwapOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+@vertex
+fn vs_material(input: VertexInput, instance: InstanceInput) -> SwapOutput {
+    var out: SwapOutput;
+    out.clip_position = draw.view_projection * instance_model(instance) * vec4<f32>(input.position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_unlit(input: SwapOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+}
+";
+
+/// Actualizar el renderer (recompila los shaders pendientes), dibujar un
+/// frame y devolver su píxel central
+async fn center_pixel(renderer: &mut RendererSystem, world: &ecs::ECSSystem) -> Result<[u8; 4]> {
+    renderer.update(1.0 / 60.0).await?;
+    renderer.submit_scene(world);
+    let image = renderer.capture_frame().await?;
+    Ok(image.pixel(SIZE / 2, SIZE / 2).unwrap())
+}
+
+#[tokio::test]
+async fn shader_swaps_apply_next_frame_and_broken_ones_keep_the_old_pipeline() -> Result<()> {
+    let Some(mut renderer) = headless_renderer(create_renderer_config(1)).await else {
+        eprintln!("Sin adaptador wgpu: se omite el test");
+        return Ok(());
+    };
+    let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let received = std::sync::Arc::clone(&events);
+    renderer.set_shader_reload_callback(move |event| received.lock().unwrap().push(event.clone()));
+
+    let world = quad_scene(&mut renderer, Vec4::new(1.0, 0.0, 0.0, 1.0), -1.0, 1.0).await?;
+    let red = center_pixel(&mut renderer, &world).await?;
+    assert!(red[0] > 200 && red[1] == 0, "antes {:?}", red);
+
+    // Un shader válido cambia el siguiente frame
+    renderer.set_shader_source("unlit", GREEN_UNLIT_SHADER);
+    let green = center_pixel(&mut renderer, &world).await?;
+    assert!(green[1] > 200 && green[0] == 0, "tras el cambio {:?}", green);
+    assert_eq!(events.lock().unwrap().as_slice(), [ShaderReloadEvent::Reloaded {
+        shader: "unlit".to_string(),
+        kind: MaterialKind::Unlit,
+    }]);
+
+    // Uno roto conserva el pipeline anterior y da la línea del error
+    renderer.set_shader_source("unlit", &GREEN_UNLIT_SHADER.replace("0.0, 1.0, 0.0, 1.0", "0.0, verde, 0.0, 1.0"));
+    let kept = center_pixel(&mut renderer, &world).await?;
+    assert_eq!(kept, green);
+    let error = match events.lock().unwrap().last() {
+        Some(ShaderReloadEvent::Failed(error)) => error.clone(),
+        other => panic!("se esperaba un error de compilación: {:?}", other),
+    };
+    assert_eq!(error.shader, "unlit");
+    assert_eq!(error.line, Some(14));
+    assert!(error.message.contains("verde"), "{}", error);
+    assert!(error.to_string().starts_with("unlit.wgsl:14:"), "{}", error);
+    assert_eq!(renderer.shader_errors().get("unlit"), Some(&error));
+    Ok(())
+}
+
+#[tokio::test]
+async fn taa_converges_a_jittered_edge_over_eight_frames() -> Result<()> {
+    // Borde derecho del quad a 0.4 píxeles dentro de la columna 32: el centro
+    // del píxel queda fuera sin jitter
+    let edge = (32.4 / 32.0 - 1.0) * 5.0 * 30f32.to_radians().tan();
+    let (x, y) = (32, 32);
+
+    let mut edge_values = Vec::new();
+    for taa in [false, true] {
+        let mut config = create_renderer_config(1);
+        config.quality_config.antialiasing.taa = taa;
+        let Some(mut renderer) = headless_renderer(config).await else {
+            eprintln!("Sin adaptador wgpu: se omite el test");
+            return Ok(());
+        };
+        let world = quad_scene(&mut renderer, Vec4::new(1.0, 0.0, 0.0, 1.0), -1.0, edge).await?;
+
+        let mut values = Vec::new();
+        let mut last = None;
+        for _ in 0..8 {
+            renderer.submit_scene(&world);
+            let image = renderer.capture_frame().await?;
+            values.push(image.pixel(x, y).unwrap()[0]);
+            last = Some(image);
+        }
+        let last = last.unwrap();
+        // Lejos del borde no hay mezcla ni estela
+        assert!(last.pixel(x - 6, y).unwrap()[0] > 128);
+        assert_eq!(last.pixel(x + 6, y).unwrap()[0], 0);
+        edge_values.push((values, last.pixel(x - 6, y).unwrap()[0]));
+    }
+
+    // Sin TAA el píxel del borde es binario y no cambia entre frames
+    let (plain, _) = &edge_values[0];
+    assert!(plain.iter().all(|&value| value == plain[0]));
+    assert_eq!(plain[0], 0);
+
+    // Con TAA el jitter cubre el píxel en parte de los frames y la historia
+    // lo acumula hasta un valor intermedio
+    let (accumulated, full) = &edge_values[1];
+    assert_eq!(accumulated[0], 0);
+    let converged = *accumulated.last().unwrap();
+    assert!(converged > 16 && converged < full - 16, "borde {:?}, pleno {}", accumulated, full);
+    Ok(())
+}
+
+#[tokio::test]
+async fn texture_budget_for_five_keeps_the_five_closest_resident() -> Result<()> {
+    const TEXTURE_SIZE: u32 = 256;
+    let full = chain_bytes(TEXTURE_SIZE, TEXTURE_SIZE, 0);
+    let fallback = chain_bytes(TEXTURE_SIZE, TEXTURE_SIZE, fallback_mip(TEXTURE_SIZE, TEXTURE_SIZE));
+
+    let mut config = create_renderer_config(1);
+    config.render_api = RenderAPI::Custom("mock".to_string());
+    config.texture_budget_bytes = 10 * fallback + 5 * (full - fallback);
+    let mut renderer = RendererSystem::new(config);
+    renderer.initialize().await?;
+
+    // Diez quads con su textura a 2..11 m de la cámara, creados de lejos a cerca
+    let mut world = ecs::ECSSystem::new(create_ecs_config());
+    add_camera(&mut world, Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -5.0)).await?;
+    for i in (0..10).rev() {
+        let id = format!("texture_{}", i);
+        renderer.load_texture(Texture {
+            id: id.clone(),
+            name: id.clone(),
+            texture_type: TextureType::Diffuse,
+            config: TextureConfig {
+                width: TEXTURE_SIZE,
+                height: TEXTURE_SIZE,
+                format: TextureFormat::RGBA8,
+                filter: TextureFilter::LinearMipmapLinear,
+                wrap: TextureWrap::Repeat,
+                mipmaps: true,
+            },
+            data: Some(vec![i as u8 * 20; (TEXTURE_SIZE * TEXTURE_SIZE * 4) as usize]),
+        }).await?;
+        let material = format!("material_{}", i);
+        renderer.create_material(Material {
+            id: material.clone(),
+            name: material.clone(),
+            material_type: MaterialType::Unlit,
+            properties: MaterialProperties {
+                base_color: Vec4::ONE,
+                metallic: 0.0,
+                roughness: 1.0,
+                emissive: Vec3::ZERO,
+                normal_scale: 1.0,
+                occlusion_strength: 1.0,
+                alpha_cutoff: 0.0,
+                double_sided: false,
+            },
+            textures: HashMap::from([("base_color".to_string(), id)]),
+            shader: "unlit".to_string(),
+        }).await?;
+
+        let quad = world.create_entity(format!("quad_{}", i)).await?;
+        let position = Vec3::new((i as f32 - 4.5) * 0.1, 0.0, 3.0 - i as f32);
+        world.add_component(quad, Box::new(transform_at(position))).await?;
+        world.add_component(quad, Box::new(flat_mesh(&format!("quad_{}", i), Some(&material), &[
+            Vec3::new(-0.1, -0.1, 0.0),
+            Vec3::new(0.1, -0.1, 0.0),
+            Vec3::new(0.1, 0.1, 0.0),
+            Vec3::new(-0.1, 0.1, 0.0),
+        ]))).await?;
+    }
+    world.flush_commands();
+
+    renderer.submit_scene(&world);
+    renderer.render().await?;
+    for i in 0..10 {
+        let mip = renderer.resident_texture_mip(&format!("texture_{}", i));
+        if i < 5 {
+            assert_eq!(mip, Some(0), "texture_{} es de las cinco más cercanas", i);
+        } else {
+            assert!(mip.is_some_and(|mip| mip > 0), "texture_{} no cabe completa: {:?}", i, mip);
+        }
+    }
+    let stats = renderer.get_stats();
+    assert_eq!(stats.resident_texture_bytes, 5 * full + 5 * fallback);
+    assert_eq!(stats.evicted_mip_count, 0);
+    Ok(())
+}
+
+/// Color lineal de limpieza de los tests de captura: naranja con el verde
+/// en 128 tras codificar en sRGB
+const CLEAR_COLOR: Vec4 = Vec4::new(1.0, 0.2158, 0.0, 1.0);
+/// `CLEAR_COLOR` en sRGB
+const CLEAR_PIXEL: [u8; 4] = [255, 128, 0, 255];
+
+/// Verificar que un píxel es `expected` con una tolerancia por canal
+fn assert_pixel_near(pixel: [u8; 4], expected: [u8; 4], tolerance: u8) {
+    assert!(
+        pixel.iter().zip(expected).all(|(&value, expected)| value.abs_diff(expected) <= tolerance),
+        "{:?} frente a {:?}", pixel, expected,
+    );
+}
+
+#[tokio::test]
+async fn capture_of_a_clear_frame_returns_the_clear_color() -> Result<()> {
+    // Backend simulado: la captura es exactamente el color de limpieza
+    let mut config = create_renderer_config(1);
+    config.render_api = RenderAPI::Custom("mock".to_string());
+    let mut renderer = RendererSystem::new(config);
+    renderer.initialize().await?;
+    renderer.set_clear_color(CLEAR_COLOR);
+    let image = renderer.capture_frame().await?;
+    assert_eq!((image.width, image.height), (SIZE, SIZE));
+    assert!(image.pixels.chunks(4).all(|texel| texel == CLEAR_PIXEL));
+
+    // En la GPU, sin tonemapping que cambie el color
+    let Some(mut renderer) = headless_renderer(create_renderer_config(1)).await else {
+        eprintln!("Sin adaptador wgpu: se omite el test");
+        return Ok(());
+    };
+    renderer.set_post_effect_enabled(PostEffect::Tonemap, false);
+    renderer.set_clear_color(CLEAR_COLOR);
+    let mut world = ecs::ECSSystem::new(create_ecs_config());
+    add_camera(&mut world, Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO).await?;
+    world.flush_commands();
+    renderer.submit_scene(&world);
+    let image = renderer.capture_frame().await?;
+    assert_eq!((image.width, image.height), (SIZE, SIZE));
+    assert_eq!(image.pixels.len(), (SIZE * SIZE * 4) as usize);
+    for (x, y) in [(0, 0), (SIZE / 2, SIZE / 2), (SIZE - 1, SIZE - 1)] {
+        assert_pixel_near(image.pixel(x, y).unwrap(), CLEAR_PIXEL, 2);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn render_to_texture_fills_a_256_buffer_with_the_mesh() -> Result<()> {
+    const TEXTURE_SIZE: u32 = 256;
+    let Some(mut renderer) = headless_renderer(create_renderer_config(1)).await else {
+        eprintln!("Sin adaptador wgpu: se omite el test");
+        return Ok(());
+    };
+    renderer.set_clear_color(CLEAR_COLOR);
+    create_unlit_material(&mut renderer, "item", Vec4::new(0.0, 0.0, 1.0, 1.0)).await?;
+
+    // Un objeto de la tienda y su cámara de miniatura, separada de la principal
+    let mut world = ecs::ECSSystem::new(create_ecs_config());
+    add_camera(&mut world, Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO).await?;
+    let thumbnail = add_camera(&mut world, Vec3::new(0.0, 0.0, 3.0), Vec3::ZERO).await?;
+    let item = world.create_entity("item".to_string()).await?;
+    world.add_component(item, Box::new(transform_at(Vec3::ZERO))).await?;
+    world.add_component(item, Box::new(flat_mesh("item", Some("item"), &[
+        Vec3::new(-0.5, -0.5, 0.0),
+        Vec3::new(0.5, -0.5, 0.0),
+        Vec3::new(0.5, 0.5, 0.0),
+        Vec3::new(-0.5, 0.5, 0.0),
+    ]))).await?;
+    world.flush_commands();
+    renderer.submit_scene(&world);
+
+    let handle = renderer.render_to_texture(&world, thumbnail, TEXTURE_SIZE, TEXTURE_SIZE)?;
+    let image = renderer.read_render_texture(handle).await?;
+    assert_eq!((image.width, image.height), (TEXTURE_SIZE, TEXTURE_SIZE));
+    assert_eq!(image.pixels.len(), (TEXTURE_SIZE * TEXTURE_SIZE * 4) as usize);
+
+    // El objeto en el centro y el fondo en las esquinas
+    let center = image.pixel(TEXTURE_SIZE / 2, TEXTURE_SIZE / 2).unwrap();
+    assert!(center[2] > 128 && center[0] < 64, "centro {:?}", center);
+    let background = image.pixel(1, 1).unwrap();
+    assert!(background[0] > 128 && background[2] < 64, "fondo {:?}", background);
+    let covered = image.pixels.chunks(4).filter(|texel| texel[2] > 128).count();
+    assert!(covered > 1000, "{} píxeles del objeto", covered);
+    Ok(())
+}
+
+/// Runtime de XR simulado: swapchains de una imagen por ojo y registro de
+/// los ojos entregados en cada frame
+struct MockXrRuntime {
+    eyes: [wgpu::Texture; 2],
+    frames_begun: usize,
+    acquired: Vec<usize>,
+    /// Ojos entregados al cerrar cada frame
+    submitted: Vec<Vec<usize>>,
+}
+
+impl XrRuntime for MockXrRuntime {
+    fn name(&self) -> &str {
+        "mock-xr"
+    }
+
+    fn eye_resolution(&self) -> (u32, u32) {
+        (SIZE, SIZE)
+    }
+
+    fn begin_frame(&mut self) -> Result<XrFrame> {
+        self.frames_begun += 1;
+        // Ojos a ±32 mm con 45° a cada lado
+        let fov = EyeFov { angle_left: -0.785, angle_right: 0.785, angle_up: 0.785, angle_down: -0.785 };
+        let eye = |x: f32| EyeView { pose: TrackedPose { position: Vec3::new(x, 0.0, 0.0), orientation: Quat::IDENTITY }, fov };
+        Ok(XrFrame {
+            predicted_display_time: self.frames_begun as i64 * 11_111_111,
+            should_render: true,
+            views: [eye(-0.032), eye(0.032)],
+            head: TrackedPose::IDENTITY,
+            controllers: [None, None],
+        })
+    }
+
+    fn acquire_eye_texture(&mut self, eye: usize) -> Result<&wgpu::Texture> {
+        let texture = self.eyes.get(eye).ok_or_else(|| anyhow::anyhow!("Ojo inexistente: {}", eye))?;
+        if !self.acquired.contains(&eye) {
+            self.acquired.push(eye);
+        }
+        Ok(texture)
+    }
+
+    fn end_frame(&mut self, _frame: &XrFrame) -> Result<()> {
+        self.submitted.push(std::mem::take(&mut self.acquired));
+        Ok(())
+    }
+}
+
+/// Dispositivo del runtime simulado y sus imágenes de los ojos, en el
+/// formato de los destinos offscreen; None sin adaptador
+async fn mock_xr_runtime() -> Option<(MockXrRuntime, XrGraphics)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+    let (device, queue) = adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("xr-test-device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::default().using_resolution(adapter.limits()),
+        },
+        None,
+    ).await.ok()?;
+    let eye = |label: &str| device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d { width: SIZE, height: SIZE, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let eyes = [eye("xr-swapchain-left"), eye("xr-swapchain-right")];
+    let runtime = MockXrRuntime { eyes, frames_begun: 0, acquired: Vec::new(), submitted: Vec::new() };
+    Some((runtime, XrGraphics { instance, adapter, device, queue }))
+}
+
+#[tokio::test]
+async fn xr_frames_submit_both_eye_views() -> Result<()> {
+    const FRAMES: usize = 3;
+    let Some((mut runtime, graphics)) = mock_xr_runtime().await else {
+        eprintln!("Sin adaptador wgpu: se omite el test");
+        return Ok(());
+    };
+    let mut config = create_renderer_config(1);
+    config.render_api = RenderAPI::Vulkan;
+    let mut renderer = RendererSystem::new(config);
+    renderer.set_xr_graphics(graphics);
+    renderer.initialize().await?;
+    create_unlit_material(&mut renderer, "blue", Vec4::new(0.0, 0.0, 1.0, 1.0)).await?;
+
+    let mut world = ecs::ECSSystem::new(create_ecs_config());
+    let quad = world.create_entity("quad".to_string()).await?;
+    world.add_component(quad, Box::new(transform_at(Vec3::new(0.0, 0.0, -3.0)))).await?;
+    world.add_component(quad, Box::new(flat_mesh("quad", Some("blue"), &[
+        Vec3::new(-0.5, -0.5, 0.0),
+        Vec3::new(0.5, -0.5, 0.0),
+        Vec3::new(0.5, 0.5, 0.0),
+        Vec3::new(-0.5, 0.5, 0.0),
+    ]))).await?;
+    world.flush_commands();
+    renderer.submit_scene(&world);
+
+    // El bucle de `render_xr` del motor con la cabeza en el origen
+    for _ in 0..FRAMES {
+        let frame = runtime.begin_frame()?;
+        let cameras = camera_xr::eye_cameras(&frame.views, Mat4::IDENTITY, 0.1, 100.0);
+        renderer.render_xr_frame(&world, &cameras, &mut runtime)?;
+        runtime.end_frame(&frame)?;
+    }
+
+    assert_eq!(runtime.frames_begun, FRAMES);
+    assert_eq!(runtime.submitted, vec![vec![0, 1]; FRAMES]);
+    assert_eq!(renderer.get_stats().xr_eye_views, 2 * FRAMES as u64);
+    Ok(())
+}
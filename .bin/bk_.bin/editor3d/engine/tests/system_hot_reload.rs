@@ -0,0 +1,233 @@
+//! Recarga en caliente de sistemas del ECS
+//!
+//! `library_system_is_reloaded` compila un sistema mínimo como biblioteca
+//! dinámica contra este mismo motor, lo carga desde el directorio vigilado,
+//! lo reconstruye con otra lógica y comprueba que el contador sigue
+//! avanzando con la nueva. La primera compilación del motor como dependencia
+//! tarda; las siguientes reutilizan `target/hot-reload`.
+
+use anyhow::Result;
+use metaverso_engine::ecs::{
+    self, CommandBuffer, ComponentConfig, ComponentType, ECSConfig, EntityConfig, LabelComponent,
+    OptimizationConfig, SystemConfig, TextStyle,
+};
+use glam::Vec3;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+fn create_config(hot_reloading: bool, systems_directory: &Path) -> ECSConfig {
+    ECSConfig {
+        enabled: true,
+        entity_config: EntityConfig {
+            max_entities: 100,
+            entity_pool: false,
+            id_reuse: false,
+        },
+        component_config: ComponentConfig {
+            max_components_per_entity: 16,
+            component_cache: false,
+            auto_serialization: false,
+        },
+        system_config: SystemConfig {
+            parallel_execution: false,
+            system_priority: true,
+            hot_reloading,
+        },
+        optimization_config: OptimizationConfig {
+            cache_friendly: true,
+            memory_pooling: false,
+            batch_processing: false,
+        },
+        max_parallel_systems: 1,
+        system_hot_reloading: hot_reloading,
+        systems_directory: systems_directory.to_path_buf(),
+    }
+}
+
+/// Valor del contador guardado en el texto de la etiqueta
+fn counter(world: &ecs::ECSSystem, entity_id: ecs::EntityId) -> u32 {
+    world.get_component::<LabelComponent>(entity_id, ComponentType::Label)
+        .and_then(|label| label.text.parse().ok())
+        .expect("la entidad tiene un contador")
+}
+
+/// Sistema en proceso que anota si se cerró
+struct ProbeSystem {
+    shut_down: Arc<AtomicBool>,
+}
+
+impl ecs::ECSSystem for ProbeSystem {
+    fn execute(&self, _world: &ecs::ECSSystem, _commands: &mut CommandBuffer) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_priority(&self) -> u32 {
+        100
+    }
+
+    fn get_name(&self) -> &str {
+        "ProbeSystem"
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        self.shut_down.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[test]
+fn reload_shuts_down_the_replaced_system() -> Result<()> {
+    let mut world = ecs::ECSSystem::new(create_config(false, Path::new("systems")));
+    let old = Arc::new(AtomicBool::new(false));
+    let new = Arc::new(AtomicBool::new(false));
+    world.add_system(Box::new(ProbeSystem { shut_down: old.clone() }));
+
+    world.reload_system("ProbeSystem", Box::new(ProbeSystem { shut_down: new.clone() }))?;
+    assert!(old.load(Ordering::SeqCst));
+    assert!(!new.load(Ordering::SeqCst));
+
+    assert!(world.reload_system("Missing", Box::new(ProbeSystem { shut_down: new.clone() })).is_err());
+    Ok(())
+}
+
+#[cfg(unix)]
+mod library {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::time::Duration;
+
+    const ENGINE_DIR: &str = env!("CARGO_MANIFEST_DIR");
+
+    /// Código del sistema: suma `step` al contador de cada etiqueta
+    fn system_source(step: u32) -> String {
+        format!(
+            r#"
+use metaverso_engine::ecs::{{self, CommandBuffer, ComponentType, LabelComponent}};
+
+struct CounterSystem;
+
+impl ecs::ECSSystem for CounterSystem {{
+    fn execute(&self, world: &ecs::ECSSystem, commands: &mut CommandBuffer) -> anyhow::Result<()> {{
+        for entity_id in world.get_entities_with_component(ComponentType::Label) {{
+            if let Some(mut label) = world.get_component::<LabelComponent>(entity_id, ComponentType::Label) {{
+                let value: u32 = label.text.parse()?;
+                label.text = (value + {step}).to_string();
+                commands.update_component(entity_id, Box::new(label));
+            }}
+        }}
+        Ok(())
+    }}
+
+    fn get_priority(&self) -> u32 {{
+        100
+    }}
+
+    fn get_name(&self) -> &str {{
+        "CounterSystem"
+    }}
+}}
+
+#[no_mangle]
+pub fn create_system() -> Box<dyn ecs::ECSSystem> {{
+    Box::new(CounterSystem)
+}}
+"#
+        )
+    }
+
+    /// Compilar el sistema con `step` y copiarlo al directorio vigilado
+    fn build_system(crate_dir: &Path, systems_dir: &Path, step: u32) -> PathBuf {
+        std::fs::write(crate_dir.join("src/lib.rs"), system_source(step)).unwrap();
+
+        // Mismo compilador, perfil y versiones de dependencias que el test
+        let target_dir = Path::new(ENGINE_DIR).join("target/hot-reload");
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let status = Command::new(cargo)
+            .arg("build")
+            .arg("--manifest-path")
+            .arg(crate_dir.join("Cargo.toml"))
+            .env("CARGO_TARGET_DIR", &target_dir)
+            .status()
+            .expect("no se pudo lanzar cargo");
+        assert!(status.success(), "la biblioteca del sistema no compila");
+
+        let file_name = format!(
+            "{}counter_system.{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_EXTENSION,
+        );
+        let destination = systems_dir.join(&file_name);
+        std::fs::copy(target_dir.join("debug").join(&file_name), &destination).unwrap();
+        destination
+    }
+
+    /// Crate `cdylib` del sistema en un directorio temporal
+    fn create_system_crate(root: &Path) -> PathBuf {
+        let crate_dir = root.join("counter_system");
+        std::fs::create_dir_all(crate_dir.join("src")).unwrap();
+        std::fs::write(
+            crate_dir.join("Cargo.toml"),
+            format!(
+                r#"[package]
+name = "counter_system"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+metaverso-engine = {{ path = "{}" }}
+anyhow = "1.0"
+
+[workspace]
+"#,
+                ENGINE_DIR,
+            ),
+        )
+        .unwrap();
+        let lockfile = Path::new(ENGINE_DIR).join("Cargo.lock");
+        if lockfile.exists() {
+            std::fs::copy(lockfile, crate_dir.join("Cargo.lock")).unwrap();
+        }
+        crate_dir
+    }
+
+    #[tokio::test]
+    async fn library_system_is_reloaded() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("metaverso-hot-reload-{}", std::process::id()));
+        let systems_dir = root.join("systems");
+        std::fs::create_dir_all(&systems_dir)?;
+        let crate_dir = create_system_crate(&root);
+        build_system(&crate_dir, &systems_dir, 1);
+
+        let mut world = ecs::ECSSystem::new(create_config(true, &systems_dir));
+        world.initialize().await?;
+        let entity_id = world.create_entity("Contador".to_string()).await?;
+        world.add_component(entity_id, Box::new(LabelComponent {
+            text: "0".to_string(),
+            style: TextStyle::default(),
+            offset: Vec3::ZERO,
+        })).await?;
+
+        // El primer frame carga la biblioteca y suma 1
+        world.update(0.016).await?;
+        assert_eq!(counter(&world, entity_id), 1);
+        world.update(0.016).await?;
+        assert_eq!(counter(&world, entity_id), 2);
+
+        // La versión reconstruida suma 10 desde el frame siguiente a la exploración
+        build_system(&crate_dir, &systems_dir, 10);
+        tokio::time::sleep(ecs::hot_reload::DEFAULT_POLL_INTERVAL + Duration::from_millis(100)).await;
+        world.update(0.016).await?;
+        assert_eq!(counter(&world, entity_id), 12);
+        world.update(0.016).await?;
+        assert_eq!(counter(&world, entity_id), 22);
+
+        drop(world);
+        let _ = std::fs::remove_dir_all(&root);
+        Ok(())
+    }
+}
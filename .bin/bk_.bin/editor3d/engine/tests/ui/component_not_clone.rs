@@ -0,0 +1,10 @@
+// Sin `Clone` no hay `clone_box`
+use metaverso_engine::ecs::Component;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Component)]
+struct Inventory {
+    items: Vec<String>,
+}
+
+fn main() {}
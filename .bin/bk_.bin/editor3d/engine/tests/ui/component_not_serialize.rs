@@ -0,0 +1,10 @@
+// Sin `Serialize` no se puede guardar en instantáneas ni replicar
+use metaverso_engine::ecs::Component;
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Component)]
+struct Inventory {
+    items: Vec<String>,
+}
+
+fn main() {}
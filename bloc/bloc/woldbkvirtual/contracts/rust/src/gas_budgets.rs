@@ -0,0 +1,28 @@
+//! Gas budgets for the WCVToken constructor and messages, in `ref_time`
+//! weight units.
+//!
+//! The end-to-end tests (`cargo test --features e2e-tests`) dry-run the
+//! constructor and each message against a node and fail when one consumes
+//! more than its budget.
+//! Raising a budget is a deliberate change that shows up in review.
+
+/// `new` instantiating the token
+pub const NEW: u64 = 10_000_000_000;
+
+/// `transfer` between two regular accounts
+pub const TRANSFER: u64 = 5_000_000_000;
+
+/// `transfer_from` spending an existing allowance
+pub const TRANSFER_FROM: u64 = 6_000_000_000;
+
+/// `approve` of a new allowance
+pub const APPROVE: u64 = 3_000_000_000;
+
+/// `mint` by the owner to a new account
+pub const MINT: u64 = 5_000_000_000;
+
+/// `burn` by the owner from its own balance
+pub const BURN: u64 = 5_000_000_000;
+
+/// `bridge_transfer` by the owner to a new account
+pub const BRIDGE_TRANSFER: u64 = 6_000_000_000;
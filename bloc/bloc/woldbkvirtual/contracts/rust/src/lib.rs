@@ -1,5 +1,10 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+pub mod gas_budgets;
+
+#[cfg(feature = "dry-run")]
+pub mod dry_run;
+
 #[ink::contract]
 mod wcv_token {
     use ink::storage::traits::StorageLayout;
@@ -30,6 +35,14 @@ mod wcv_token {
         daily_transfers: ink::storage::Mapping<AccountId, Balance>,
         /// Last transfer day per account
         last_transfer_day: ink::storage::Mapping<AccountId, u64>,
+        /// Mapping of authorized pausers
+        pausers: ink::storage::Mapping<AccountId, bool>,
+        /// Emergency pause flag
+        paused: bool,
+        /// Timestamp after which transfers resume on their own (0 = no expiry)
+        pause_expires_at: u64,
+        /// Duration of each pause in milliseconds (0 = until unpaused)
+        pause_duration: u64,
     }
 
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode, TypeInfo)]
@@ -43,6 +56,7 @@ mod wcv_token {
         InvalidAmount,
         InvalidAddress,
         TransferAlreadyProcessed,
+        ContractPaused,
     }
 
     pub type Result<T> = core::result::Result<T, Error>;
@@ -85,6 +99,21 @@ mod wcv_token {
         reason: String,
     }
 
+    #[ink(event)]
+    #[derive(Debug)]
+    pub struct ContractPaused {
+        #[ink(topic)]
+        by: AccountId,
+        expires_at: u64,
+    }
+
+    #[ink(event)]
+    #[derive(Debug)]
+    pub struct ContractUnpaused {
+        #[ink(topic)]
+        by: AccountId,
+    }
+
     #[ink(event)]
     #[derive(Debug)]
     pub struct BridgeTransfer {
@@ -115,14 +144,19 @@ mod wcv_token {
                 daily_transfer_limit: 10_000_000_000, // 10M WCV
                 daily_transfers: ink::storage::Mapping::default(),
                 last_transfer_day: ink::storage::Mapping::default(),
+                pausers: ink::storage::Mapping::default(),
+                paused: false,
+                pause_expires_at: 0,
+                pause_duration: 0,
             };
             
             // Set initial balance for owner
             instance.balances.insert(owner, &total_supply);
             
-            // Set owner as minter and burner
+            // Set owner as minter, burner and pauser
             instance.minters.insert(owner, &true);
             instance.burners.insert(owner, &true);
+            instance.pausers.insert(owner, &true);
             
             // Emit initial mint event
             Self::env().emit_event(TokensMinted {
@@ -149,6 +183,10 @@ mod wcv_token {
         /// Transfers tokens from the caller to the specified account
         #[ink(message)]
         pub fn transfer(&mut self, to: AccountId, value: Balance) -> Result<()> {
+            if self._transfers_paused() {
+                return Err(Error::ContractPaused);
+            }
+            
             let from = self.env().caller();
             self._transfer(from, to, value)
         }
@@ -156,6 +194,10 @@ mod wcv_token {
         /// Transfers tokens from one account to another using allowance
         #[ink(message)]
         pub fn transfer_from(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<()> {
+            if self._transfers_paused() {
+                return Err(Error::ContractPaused);
+            }
+            
             let caller = self.env().caller();
             let allowance = self.allowances.get((from, caller)).unwrap_or(0);
             
@@ -191,6 +233,10 @@ mod wcv_token {
         /// Mints new tokens (only authorized minters)
         #[ink(message)]
         pub fn mint(&mut self, to: AccountId, amount: Balance, reason: String) -> Result<()> {
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+            
             let caller = self.env().caller();
             
             if !self.minters.get(caller).unwrap_or(false) && caller != self.owner {
@@ -221,6 +267,10 @@ mod wcv_token {
         /// Burns tokens (only authorized burners)
         #[ink(message)]
         pub fn burn(&mut self, from: AccountId, amount: Balance, reason: String) -> Result<()> {
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+            
             let caller = self.env().caller();
             
             if !self.burners.get(caller).unwrap_or(false) && caller != self.owner {
@@ -255,6 +305,10 @@ mod wcv_token {
         /// Bridge transfer (only bridge operators)
         #[ink(message)]
         pub fn bridge_transfer(&mut self, from: AccountId, to: AccountId, amount: Balance, source_chain: String) -> Result<()> {
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+            
             let caller = self.env().caller();
             
             if !self.burners.get(caller).unwrap_or(false) && 
@@ -359,6 +413,93 @@ mod wcv_token {
             Ok(())
         }
 
+        /// Pause the contract (only authorized pausers). Mint, burn and bridge
+        /// transfers stay stopped until `unpause`; with a pause duration set,
+        /// regular transfers resume on their own once it expires
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            
+            if !self.pausers.get(caller).unwrap_or(false) {
+                return Err(Error::NotAuthorized);
+            }
+            
+            let expires_at = if self.pause_duration > 0 {
+                self.env().block_timestamp().saturating_add(self.pause_duration)
+            } else {
+                0
+            };
+            self.paused = true;
+            self.pause_expires_at = expires_at;
+            
+            Self::env().emit_event(ContractPaused {
+                by: caller,
+                expires_at,
+            });
+            
+            Ok(())
+        }
+
+        /// Unpause the contract (only authorized pausers)
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            
+            if !self.pausers.get(caller).unwrap_or(false) {
+                return Err(Error::NotAuthorized);
+            }
+            
+            self.paused = false;
+            self.pause_expires_at = 0;
+            
+            Self::env().emit_event(ContractUnpaused { by: caller });
+            
+            Ok(())
+        }
+
+        /// Returns whether the contract is paused and when transfers resume (0 = no expiry)
+        #[ink(message)]
+        pub fn pause_status(&self) -> (bool, u64) {
+            (self.paused, self.pause_expires_at)
+        }
+
+        /// Add pauser (only owner)
+        #[ink(message)]
+        pub fn add_pauser(&mut self, pauser: AccountId) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotAuthorized);
+            }
+            
+            if pauser == AccountId::from([0u8; 32]) {
+                return Err(Error::InvalidAddress);
+            }
+            
+            self.pausers.insert(pauser, &true);
+            Ok(())
+        }
+
+        /// Remove pauser (only owner)
+        #[ink(message)]
+        pub fn remove_pauser(&mut self, pauser: AccountId) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotAuthorized);
+            }
+            
+            self.pausers.insert(pauser, &false);
+            Ok(())
+        }
+
+        /// Set how long each pause lasts in milliseconds, 0 for no expiry (only owner)
+        #[ink(message)]
+        pub fn set_pause_duration(&mut self, duration: u64) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotAuthorized);
+            }
+            
+            self.pause_duration = duration;
+            Ok(())
+        }
+
         /// Get token statistics
         #[ink(message)]
         pub fn get_token_stats(&self) -> (Balance, Balance, Balance, Balance, Balance) {
@@ -371,15 +512,21 @@ mod wcv_token {
             )
         }
 
+        /// Check whether regular transfers are stopped by the pause
+        fn _transfers_paused(&self) -> bool {
+            if !self.paused {
+                return false;
+            }
+            self.pause_expires_at == 0 || self.env().block_timestamp() <= self.pause_expires_at
+        }
+
         /// Internal transfer function
         fn _transfer(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<()> {
             if value > self.max_transfer_amount {
                 return Err(Error::TransferLimitExceeded);
             }
             
-            if !self._check_daily_limit(from, value) {
-                return Err(Error::DailyLimitExceeded);
-            }
+            self._check_daily_limit(from, value)?;
             
             let from_balance = self.balances.get(from).unwrap_or(0);
             if from_balance < value {
@@ -391,7 +538,7 @@ mod wcv_token {
             let to_balance = self.balances.get(to).unwrap_or(0);
             self.balances.insert(to, &(to_balance + value));
             
-            self._update_daily_transfer(from, value);
+            self._update_daily_transfer(from, value)?;
             
             Self::env().emit_event(Transfer {
                 from: Some(from),
@@ -402,31 +549,38 @@ mod wcv_token {
             Ok(())
         }
 
+        /// Current day number from the block timestamp
+        fn _current_day(&self) -> u64 {
+            self.env().block_timestamp() / (24 * 60 * 60 * 1000) // days
+        }
+
+        /// Amount already sent by an account today (a new day starts at 0)
+        fn _daily_tally(&self, account: AccountId) -> Balance {
+            if self.last_transfer_day.get(account).unwrap_or(0) != self._current_day() {
+                return 0;
+            }
+            self.daily_transfers.get(account).unwrap_or(0)
+        }
+
         /// Check daily transfer limit
-        fn _check_daily_limit(&self, account: AccountId, amount: Balance) -> bool {
-            let today = self.env().block_timestamp() / (24 * 60 * 60 * 1000); // days
-            let last_day = self.last_transfer_day.get(account).unwrap_or(0);
-            
-            if last_day != today {
-                return amount <= self.daily_transfer_limit;
+        fn _check_daily_limit(&self, account: AccountId, amount: Balance) -> Result<()> {
+            let total = self._daily_tally(account)
+                .checked_add(amount)
+                .ok_or(Error::InvalidAmount)?;
+            if total > self.daily_transfer_limit {
+                return Err(Error::DailyLimitExceeded);
             }
-            
-            let daily_transfers = self.daily_transfers.get(account).unwrap_or(0);
-            daily_transfers + amount <= self.daily_transfer_limit
+            Ok(())
         }
 
         /// Update daily transfer count
-        fn _update_daily_transfer(&mut self, account: AccountId, amount: Balance) {
-            let today = self.env().block_timestamp() / (24 * 60 * 60 * 1000); // days
-            let last_day = self.last_transfer_day.get(account).unwrap_or(0);
-            
-            if last_day != today {
-                self.daily_transfers.insert(account, &amount);
-                self.last_transfer_day.insert(account, &today);
-            } else {
-                let daily_transfers = self.daily_transfers.get(account).unwrap_or(0);
-                self.daily_transfers.insert(account, &(daily_transfers + amount));
-            }
+        fn _update_daily_transfer(&mut self, account: AccountId, amount: Balance) -> Result<()> {
+            let total = self._daily_tally(account)
+                .checked_add(amount)
+                .ok_or(Error::InvalidAmount)?;
+            self.daily_transfers.insert(account, &total);
+            self.last_transfer_day.insert(account, &self._current_day());
+            Ok(())
         }
     }
 
@@ -466,5 +620,217 @@ mod wcv_token {
             assert_eq!(contract.balance_of(accounts.bob), 1000);
             assert_eq!(contract.total_supply(), 30_000_000_000 + 1000);
         }
+
+        #[ink::test]
+        fn non_pauser_cannot_pause() {
+            let mut contract = WCVToken::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.pause(), Err(Error::NotAuthorized));
+            assert_eq!(contract.pause_status(), (false, 0));
+        }
+
+        #[ink::test]
+        fn transfers_revert_while_paused() {
+            let mut contract = WCVToken::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.pause().is_ok());
+            
+            assert_eq!(contract.transfer(accounts.bob, 1000), Err(Error::ContractPaused));
+            assert_eq!(contract.mint(accounts.bob, 1000, "Test".to_string()), Err(Error::ContractPaused));
+            assert_eq!(contract.balance_of(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn transfers_resume_after_pause_expiry() {
+            let mut contract = WCVToken::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert!(contract.set_pause_duration(500).is_ok());
+            assert!(contract.pause().is_ok());
+            assert_eq!(contract.pause_status(), (true, 1_500));
+            
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_500);
+            assert_eq!(contract.transfer(accounts.bob, 1000), Err(Error::ContractPaused));
+            
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_501);
+            assert!(contract.transfer(accounts.bob, 1000).is_ok());
+            assert_eq!(contract.balance_of(accounts.bob), 1000);
+            
+            // Privileged operations wait for an explicit unpause
+            assert_eq!(contract.mint(accounts.bob, 1000, "Test".to_string()), Err(Error::ContractPaused));
+        }
+
+        #[ink::test]
+        fn unpause_before_expiry() {
+            let mut contract = WCVToken::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert!(contract.set_pause_duration(500).is_ok());
+            assert!(contract.pause().is_ok());
+            
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_200);
+            assert!(contract.unpause().is_ok());
+            assert_eq!(contract.pause_status(), (false, 0));
+            assert!(contract.transfer(accounts.bob, 1000).is_ok());
+            assert!(contract.mint(accounts.bob, 1000, "Test".to_string()).is_ok());
+            assert_eq!(contract.balance_of(accounts.bob), 2000);
+        }
+
+        #[ink::test]
+        fn daily_tally_overflow_is_rejected() {
+            let mut contract = WCVToken::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.set_transfer_limits(Balance::MAX, Balance::MAX).is_ok());
+            let large = Balance::MAX - contract.total_supply();
+            assert!(contract.mint(accounts.bob, large, "Test".to_string()).is_ok());
+            
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contract.transfer(accounts.charlie, large).is_ok());
+            
+            // The tally would wrap past Balance::MAX
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert!(contract.transfer(accounts.bob, 30_000_000_001).is_ok());
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.transfer(accounts.charlie, 30_000_000_001), Err(Error::InvalidAmount));
+            assert_eq!(contract.balance_of(accounts.bob), 30_000_000_001);
+        }
+
+        #[ink::test]
+        fn minters_are_bound_by_the_daily_limit() {
+            let mut contract = WCVToken::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.set_transfer_limits(1000, 1000).is_ok());
+            assert!(contract.transfer(accounts.bob, 1000).is_ok());
+            assert_eq!(contract.transfer(accounts.bob, 1), Err(Error::DailyLimitExceeded));
+        }
+
+        #[ink::test]
+        fn daily_limit_resets_across_midnight() {
+            const DAY: u64 = 24 * 60 * 60 * 1000;
+            let mut contract = WCVToken::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.set_transfer_limits(1000, 1000).is_ok());
+            assert!(contract.mint(accounts.bob, 1500, "Test".to_string()).is_ok());
+            
+            // 23:59:59 of the first day
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(DAY - 1000);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contract.transfer(accounts.charlie, 1000).is_ok());
+            assert_eq!(contract.transfer(accounts.charlie, 1), Err(Error::DailyLimitExceeded));
+            
+            // 00:00:01 of the next day: a failed transfer does not count
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(DAY + 1000);
+            assert_eq!(contract.transfer(accounts.charlie, 800), Err(Error::InsufficientBalance));
+            assert!(contract.transfer(accounts.charlie, 500).is_ok());
+            
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.mint(accounts.bob, 1000, "Test".to_string()).is_ok());
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contract.transfer(accounts.charlie, 500).is_ok());
+            assert_eq!(contract.transfer(accounts.charlie, 1), Err(Error::DailyLimitExceeded));
+            assert_eq!(contract.balance_of(accounts.charlie), 2000);
+        }
+    }
+
+    /// Gas regression tests against a node (`cargo test --features e2e-tests`,
+    /// with `substrate-contracts-node` on the path or `CONTRACTS_NODE` set)
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use crate::dry_run::GasEstimate;
+        use crate::gas_budgets;
+        use ink_e2e::build_message;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        fn assert_within_budget(message: &str, estimate: GasEstimate, budget: u64) {
+            assert!(
+                estimate.within(budget),
+                "{} consumed {} ref_time, over its budget of {}",
+                message,
+                estimate.consumed,
+                budget
+            );
+            assert!(estimate.required >= estimate.consumed);
+        }
+
+        #[ink_e2e::test]
+        async fn messages_stay_within_gas_budget(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let dry_run = client
+                .instantiate_dry_run("wcv_contracts", &ink_e2e::alice(), WCVTokenRef::new(), 0, None)
+                .await;
+            let estimate = GasEstimate {
+                consumed: dry_run.gas_consumed.ref_time(),
+                required: dry_run.gas_required.ref_time(),
+            };
+            assert_within_budget("new", estimate, gas_budgets::NEW);
+
+            let token = client
+                .instantiate("wcv_contracts", &ink_e2e::alice(), WCVTokenRef::new(), 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+            let transfer = build_message::<WCVTokenRef>(token.clone())
+                .call(|token| token.transfer(bob, 1000));
+            let dry_run = client.call_dry_run(&ink_e2e::alice(), &transfer, 0, None).await;
+            assert_within_budget("transfer", GasEstimate::of_call(&dry_run)?, gas_budgets::TRANSFER);
+            assert_eq!(dry_run.return_value(), Ok(()));
+
+            let approve = build_message::<WCVTokenRef>(token.clone())
+                .call(|token| token.approve(bob, 1000));
+            let dry_run = client.call_dry_run(&ink_e2e::alice(), &approve, 0, None).await;
+            assert_within_budget("approve", GasEstimate::of_call(&dry_run)?, gas_budgets::APPROVE);
+            assert_eq!(dry_run.return_value(), Ok(()));
+
+            client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+            let transfer_from = build_message::<WCVTokenRef>(token.clone())
+                .call(|token| token.transfer_from(alice, bob, 1000));
+            let dry_run = client.call_dry_run(&ink_e2e::bob(), &transfer_from, 0, None).await;
+            assert_within_budget("transfer_from", GasEstimate::of_call(&dry_run)?, gas_budgets::TRANSFER_FROM);
+            assert_eq!(dry_run.return_value(), Ok(()));
+
+            let mint = build_message::<WCVTokenRef>(token.clone())
+                .call(|token| token.mint(bob, 1000, "Gas".to_string()));
+            let dry_run = client.call_dry_run(&ink_e2e::alice(), &mint, 0, None).await;
+            assert_within_budget("mint", GasEstimate::of_call(&dry_run)?, gas_budgets::MINT);
+            assert_eq!(dry_run.return_value(), Ok(()));
+
+            let burn = build_message::<WCVTokenRef>(token.clone())
+                .call(|token| token.burn(alice, 1000, "Gas".to_string()));
+            let dry_run = client.call_dry_run(&ink_e2e::alice(), &burn, 0, None).await;
+            assert_within_budget("burn", GasEstimate::of_call(&dry_run)?, gas_budgets::BURN);
+            assert_eq!(dry_run.return_value(), Ok(()));
+
+            let bridge_transfer = build_message::<WCVTokenRef>(token.clone())
+                .call(|token| token.bridge_transfer(alice, bob, 1000, "BSC".to_string()));
+            let dry_run = client.call_dry_run(&ink_e2e::alice(), &bridge_transfer, 0, None).await;
+            assert_within_budget("bridge_transfer", GasEstimate::of_call(&dry_run)?, gas_budgets::BRIDGE_TRANSFER);
+            assert_eq!(dry_run.return_value(), Ok(()));
+
+            // A call that returns an error reverts and has no estimate
+            let overdraft = build_message::<WCVTokenRef>(token.clone())
+                .call(|token| token.transfer(alice, Balance::MAX));
+            let dry_run = client.call_dry_run(&ink_e2e::bob(), &overdraft, 0, None).await;
+            assert!(GasEstimate::of_call(&dry_run).is_err());
+
+            Ok(())
+        }
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,58 @@
+//! Off-chain gas estimation for WCVToken calls (`--features dry-run`).
+//!
+//! Clients estimate a call by dry-running it through the node's
+//! `ContractsApi` runtime API and submit the transaction with the reported
+//! `gas_required` as its gas limit. The contract deliberately has no
+//! `gas_estimate` message:
+//! - a message can only time its own body with `gas_left()`, which misses the
+//!   call overhead and the storage deposit the real transaction pays, so it
+//!   under-reports what the caller has to provide;
+//! - sent as a transaction, the estimate executes and commits the wrapped
+//!   call and charges for it, so it is a second entry point to every message
+//!   rather than a quote;
+//! - dispatching arbitrary messages through one selector grows the code size
+//!   and the cost of every other call.
+//!
+//! A dry run executes on throwaway state, reports both figures and costs
+//! nothing, so estimation stays off-chain.
+
+use ink::env::DefaultEnvironment;
+use ink_e2e::CallDryRunResult;
+
+/// Weight of a dry-run call, in `ref_time` units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasEstimate {
+    /// Weight the call consumed
+    pub consumed: u64,
+    /// Gas limit to submit the call with; above `consumed` when the call
+    /// needs weight it refunds before returning
+    pub required: u64,
+}
+
+impl GasEstimate {
+    /// Estimate of a dry-run message call, or why the call would fail
+    pub fn of_call<V: scale::Decode>(
+        dry_run: &CallDryRunResult<DefaultEnvironment, V>,
+    ) -> Result<Self, String> {
+        let exec_result = &dry_run.exec_result;
+        let returned = exec_result
+            .result
+            .as_ref()
+            .map_err(|error| format!("dry run failed: {:?}", error))?;
+        if returned.did_revert() {
+            return Err(format!(
+                "call reverted: {}",
+                String::from_utf8_lossy(&exec_result.debug_message)
+            ));
+        }
+        Ok(Self {
+            consumed: exec_result.gas_consumed.ref_time(),
+            required: exec_result.gas_required.ref_time(),
+        })
+    }
+
+    /// Whether the call fits one of the `gas_budgets`
+    pub fn within(&self, budget: u64) -> bool {
+        self.consumed <= budget
+    }
+}
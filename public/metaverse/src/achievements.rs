@@ -0,0 +1,291 @@
+//! Gestor de Logros para Metaverso
+//! Progresión de jugadores con insignias NFT
+//!
+//! Cada evento de juego actualiza el progreso del jugador (compras, stake,
+//! islas visitadas) y después se evalúan los logros que aún no tiene. Por
+//! cada logro nuevo se mintea su insignia con `NFTManager::mint` y se emite
+//! `AchievementUnlocked`. Los logros ganados se guardan por jugador, así que
+//! un mismo logro nunca se mintea dos veces. Las insignias no cuentan como
+//! NFTs en propiedad: ganar logros no desbloquea otros en cadena.
+
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::nfts::{NFTManager, BADGE_COLLECTION};
+use crate::blockchain::staking::Balance;
+
+/// Condición para ganar un logro
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AchievementCriteria {
+    /// Primera compra en el marketplace
+    FirstPurchase,
+    /// Cantidad en stake igual o mayor
+    StakedAmount(Balance),
+    /// Número de islas distintas visitadas
+    VisitedIslands(u32),
+    /// Número de NFTs en propiedad, sin contar las insignias de logros
+    OwnedNFTCount(u32),
+}
+
+/// Logro con su insignia
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Achievement {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub criteria: AchievementCriteria,
+    pub nft_metadata_uri: String,
+}
+
+/// Evento de juego que puede desbloquear logros
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum GameEvent {
+    /// Compra en el marketplace
+    Purchase { item_id: String, price: Balance },
+    /// Tokens puestos en stake
+    Staked { amount: Balance },
+    /// Tokens retirados del stake
+    Unstaked { amount: Balance },
+    /// Llegada a una isla
+    IslandVisited { island: String },
+    /// El jugador recibió un NFT (se cuentan los suyos en `NFTManager`)
+    NFTAcquired { token_id: String },
+}
+
+/// Evento emitido al desbloquear un logro
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AchievementUnlocked {
+    pub player: String,
+    pub achievement_id: String,
+    pub nft_id: String,
+}
+
+/// Progreso de un jugador
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayerProgress {
+    pub purchases: u32,
+    pub staked: Balance,
+    pub visited_islands: HashSet<String>,
+}
+
+/// Gestor de logros
+#[derive(Debug, Default)]
+pub struct AchievementManager {
+    achievements: Vec<Achievement>,
+    progress: HashMap<String, PlayerProgress>,
+    /// Jugador -> IDs de logros ganados
+    earned: HashMap<String, Vec<String>>,
+    unlocked_events: Vec<AchievementUnlocked>,
+}
+
+impl AchievementManager {
+    /// Crear gestor sin logros
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registrar un logro (reemplaza el del mismo ID)
+    pub fn register_achievement(&mut self, achievement: Achievement) {
+        match self.achievements.iter_mut().find(|existing| existing.id == achievement.id) {
+            Some(existing) => *existing = achievement,
+            None => self.achievements.push(achievement),
+        }
+    }
+
+    /// Logros registrados
+    pub fn achievements(&self) -> &[Achievement] {
+        &self.achievements
+    }
+
+    /// Progreso de un jugador
+    pub fn progress(&self, player: &str) -> Option<&PlayerProgress> {
+        self.progress.get(player)
+    }
+
+    /// IDs de los logros ganados por un jugador
+    pub fn earned_achievements(&self, player: &str) -> &[String] {
+        self.earned.get(player).map_or(&[], |earned| earned.as_slice())
+    }
+
+    /// Aplicar un evento al progreso del jugador y mintear las insignias de
+    /// los logros que gana con él. Devuelve los logros desbloqueados
+    pub fn check_achievements(
+        &mut self,
+        nfts: &mut NFTManager,
+        player: &str,
+        event: &GameEvent,
+    ) -> Result<Vec<AchievementUnlocked>, String> {
+        let progress = self.progress.entry(player.to_string()).or_default();
+        match event {
+            GameEvent::Purchase { .. } => progress.purchases = progress.purchases.saturating_add(1),
+            GameEvent::Staked { amount } => progress.staked = progress.staked.saturating_add(*amount),
+            GameEvent::Unstaked { amount } => progress.staked = progress.staked.saturating_sub(*amount),
+            GameEvent::IslandVisited { island } => {
+                progress.visited_islands.insert(island.clone());
+            }
+            GameEvent::NFTAcquired { .. } => {}
+        }
+
+        // Se evalúan todos los criterios antes de mintear la primera insignia
+        let earned = self.earned.entry(player.to_string()).or_default();
+        let owned_nfts = nfts.owned_count_excluding(player, BADGE_COLLECTION);
+        let met: Vec<&Achievement> = self.achievements.iter()
+            .filter(|achievement| !earned.contains(&achievement.id))
+            .filter(|achievement| match &achievement.criteria {
+                AchievementCriteria::FirstPurchase => progress.purchases >= 1,
+                AchievementCriteria::StakedAmount(amount) => progress.staked >= *amount,
+                AchievementCriteria::VisitedIslands(count) => progress.visited_islands.len() as u32 >= *count,
+                AchievementCriteria::OwnedNFTCount(count) => owned_nfts >= *count,
+            })
+            .collect();
+
+        let mut unlocked = Vec::new();
+        for achievement in met {
+            let nft_id = nfts.mint(player, &achievement.nft_metadata_uri)?;
+            // Se marca ganado en cuanto existe la insignia
+            earned.push(achievement.id.clone());
+            unlocked.push(AchievementUnlocked {
+                player: player.to_string(),
+                achievement_id: achievement.id.clone(),
+                nft_id,
+            });
+        }
+
+        self.unlocked_events.extend(unlocked.iter().cloned());
+        Ok(unlocked)
+    }
+
+    /// Sacar los logros desbloqueados desde la última llamada
+    pub fn drain_unlocked(&mut self) -> Vec<AchievementUnlocked> {
+        std::mem::take(&mut self.unlocked_events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::BlockchainConfig;
+
+    /// Jugador con los 3 NFTs de muestra de `NFTManager::initialize`
+    const COLLECTOR: &str = "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6";
+    const NEWCOMER: &str = "0x00000000000000000000000000000000000000b7";
+
+    fn achievement(id: &str, criteria: AchievementCriteria) -> Achievement {
+        Achievement {
+            id: id.to_string(),
+            title: id.to_string(),
+            description: String::new(),
+            criteria,
+            nft_metadata_uri: format!("ipfs://badges/{}", id),
+        }
+    }
+
+    fn setup(achievements: Vec<Achievement>) -> (AchievementManager, NFTManager) {
+        let mut manager = AchievementManager::new();
+        for achievement in achievements {
+            manager.register_achievement(achievement);
+        }
+        let mut nfts = NFTManager::new(&BlockchainConfig::default());
+        nfts.initialize().unwrap();
+        (manager, nfts)
+    }
+
+    fn unlocked_ids(unlocked: &[AchievementUnlocked]) -> Vec<&str> {
+        unlocked.iter().map(|event| event.achievement_id.as_str()).collect()
+    }
+
+    fn purchase() -> GameEvent {
+        GameEvent::Purchase { item_id: "LISTING_001".to_string(), price: 1 }
+    }
+
+    fn visit(island: &str) -> GameEvent {
+        GameEvent::IslandVisited { island: island.to_string() }
+    }
+
+    #[test]
+    fn first_purchase_unlocks_on_the_first_purchase() {
+        let (mut manager, mut nfts) = setup(vec![achievement("shopper", AchievementCriteria::FirstPurchase)]);
+
+        assert!(manager.check_achievements(&mut nfts, NEWCOMER, &visit("forest")).unwrap().is_empty());
+        let unlocked = manager.check_achievements(&mut nfts, NEWCOMER, &purchase()).unwrap();
+        assert_eq!(unlocked_ids(&unlocked), ["shopper"]);
+        assert_eq!(nfts.owned_count(NEWCOMER), 1);
+        assert_eq!(manager.drain_unlocked().len(), 1);
+    }
+
+    #[test]
+    fn staked_amount_counts_unstakes() {
+        let (mut manager, mut nfts) = setup(vec![achievement("whale", AchievementCriteria::StakedAmount(1_000))]);
+
+        manager.check_achievements(&mut nfts, NEWCOMER, &GameEvent::Staked { amount: 600 }).unwrap();
+        manager.check_achievements(&mut nfts, NEWCOMER, &GameEvent::Unstaked { amount: 200 }).unwrap();
+        let unlocked = manager.check_achievements(&mut nfts, NEWCOMER, &GameEvent::Staked { amount: 500 }).unwrap();
+        assert!(unlocked.is_empty());
+        assert_eq!(manager.progress(NEWCOMER).unwrap().staked, 900);
+
+        let unlocked = manager.check_achievements(&mut nfts, NEWCOMER, &GameEvent::Staked { amount: 100 }).unwrap();
+        assert_eq!(unlocked_ids(&unlocked), ["whale"]);
+    }
+
+    #[test]
+    fn visited_islands_counts_distinct_islands() {
+        let (mut manager, mut nfts) = setup(vec![achievement("explorer", AchievementCriteria::VisitedIslands(3))]);
+
+        for island in ["forest", "ocean", "forest", "ocean"] {
+            assert!(manager.check_achievements(&mut nfts, NEWCOMER, &visit(island)).unwrap().is_empty());
+        }
+        let unlocked = manager.check_achievements(&mut nfts, NEWCOMER, &visit("desert")).unwrap();
+        assert_eq!(unlocked_ids(&unlocked), ["explorer"]);
+    }
+
+    #[test]
+    fn owned_nft_count_ignores_badges() {
+        let (mut manager, mut nfts) = setup(vec![
+            achievement("shopper", AchievementCriteria::FirstPurchase),
+            achievement("explorer", AchievementCriteria::VisitedIslands(1)),
+            achievement("collector", AchievementCriteria::OwnedNFTCount(3)),
+            achievement("hoarder", AchievementCriteria::OwnedNFTCount(4)),
+        ]);
+
+        let acquired = GameEvent::NFTAcquired { token_id: "1".to_string() };
+        let unlocked = manager.check_achievements(&mut nfts, COLLECTOR, &acquired).unwrap();
+        assert_eq!(unlocked_ids(&unlocked), ["collector"]);
+
+        // Con 4 NFTs contando insignias, pero solo 3 que no lo son
+        manager.check_achievements(&mut nfts, COLLECTOR, &purchase()).unwrap();
+        assert_eq!(nfts.owned_count(COLLECTOR), 5);
+        assert!(manager.check_achievements(&mut nfts, COLLECTOR, &acquired).unwrap().is_empty());
+        assert!(!manager.earned_achievements(COLLECTOR).contains(&"hoarder".to_string()));
+    }
+
+    #[test]
+    fn badges_minted_in_one_event_do_not_cascade() {
+        let (mut manager, mut nfts) = setup(vec![
+            achievement("shopper", AchievementCriteria::FirstPurchase),
+            achievement("first_nft", AchievementCriteria::OwnedNFTCount(1)),
+        ]);
+
+        let unlocked = manager.check_achievements(&mut nfts, NEWCOMER, &purchase()).unwrap();
+        assert_eq!(unlocked_ids(&unlocked), ["shopper"]);
+        let unlocked = manager.check_achievements(&mut nfts, NEWCOMER, &visit("forest")).unwrap();
+        assert!(unlocked.is_empty());
+        assert_eq!(nfts.owned_count(NEWCOMER), 1);
+    }
+
+    #[test]
+    fn replayed_event_mints_a_single_badge() {
+        let (mut manager, mut nfts) = setup(vec![
+            achievement("shopper", AchievementCriteria::FirstPurchase),
+            achievement("explorer", AchievementCriteria::VisitedIslands(1)),
+        ]);
+
+        let unlocked = manager.check_achievements(&mut nfts, NEWCOMER, &purchase()).unwrap();
+        assert_eq!(unlocked_ids(&unlocked), ["shopper"]);
+        for _ in 0..3 {
+            assert!(manager.check_achievements(&mut nfts, NEWCOMER, &purchase()).unwrap().is_empty());
+        }
+        assert_eq!(manager.earned_achievements(NEWCOMER), ["shopper".to_string()]);
+        assert_eq!(nfts.owned_count(NEWCOMER), 1);
+        assert_eq!(manager.drain_unlocked().len(), 1);
+    }
+}
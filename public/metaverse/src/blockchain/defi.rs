@@ -1,10 +1,24 @@
 //! Gestor de DeFi para Metaverso
 //! Maneja staking, yield farming, liquidity pools y otros protocolos DeFi
+//!
+//! Las posiciones de liquidez guardan la relación de precios `token_a/token_b`
+//! al entrar. En un AMM de producto constante, si la relación pasa a ser `k`
+//! veces la de entrada, la posición vale `2*sqrt(k)/(1+k)` veces lo que
+//! valdrían los mismos tokens sin aportar: esa diferencia es la pérdida
+//! impermanente, que solo se recupera si el precio vuelve al de entrada.
 
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+use super::staking::Balance;
+
+/// Unidades mínimas por token
+const TOKEN_UNIT: f64 = 1e18;
+
+/// Días por año para anualizar el APR
+const DAYS_PER_YEAR: f64 = 365.0;
+
 /// Pool de liquidez
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LiquidityPool {
@@ -22,6 +36,18 @@ pub struct LiquidityPool {
     pub island: String,
 }
 
+/// Posición de liquidez de un proveedor en un pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidityPosition {
+    pub pool_id: String,
+    pub token_a: String,
+    pub token_b: String,
+    /// Precio de `token_a` en unidades de `token_b` al aportar la liquidez
+    pub entry_price_ratio: f64,
+    /// LP tokens recibidos
+    pub liquidity_tokens: Balance,
+}
+
 /// Posición de staking
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StakingPosition {
@@ -80,6 +106,8 @@ pub struct DeFiManager {
     staking_positions: HashMap<String, StakingPosition>,
     farming_positions: HashMap<String, FarmingPosition>,
     loans: HashMap<String, LoanInfo>,
+    liquidity_positions: Vec<LiquidityPosition>,
+    price_feeds: HashMap<String, f64>,
     current_network: String,
     is_initialized: bool,
 }
@@ -93,6 +121,8 @@ impl DeFiManager {
             staking_positions: HashMap::new(),
             farming_positions: HashMap::new(),
             loans: HashMap::new(),
+            liquidity_positions: Vec::new(),
+            price_feeds: HashMap::new(),
             current_network: config.default_network.clone(),
             is_initialized: false,
         }
@@ -277,36 +307,20 @@ impl DeFiManager {
             .map_err(|_| JsValue::from_str("Error al serializar pool"))
     }
 
-    /// Añadir liquidez
+    /// Añadir liquidez. Devuelve los LP tokens acuñados
     pub fn add_liquidity(&mut self, pool_id: &str, token_a_amount: &str, token_b_amount: &str) -> Result<String, JsValue> {
-        let pool = self.liquidity_pools.get_mut(pool_id)
-            .ok_or_else(|| JsValue::from_str("Pool no encontrado"))?;
-
-        // Simular añadir liquidez
-        let lp_tokens_minted = "100000000000000000000".to_string(); // 100 LP tokens
-
-        // Actualizar reservas del pool
-        let token_a_amount_u128 = token_a_amount.parse::<u128>()
+        let token_a_amount = token_a_amount.parse::<Balance>()
             .map_err(|_| JsValue::from_str("Error al parsear cantidad token A"))?;
-        let token_b_amount_u128 = token_b_amount.parse::<u128>()
+        let token_b_amount = token_b_amount.parse::<Balance>()
             .map_err(|_| JsValue::from_str("Error al parsear cantidad token B"))?;
+        self.open_liquidity_position(pool_id, token_a_amount, token_b_amount)
+            .map(|position| position.liquidity_tokens.to_string())
+            .map_err(|e| JsValue::from_str(&e))
+    }
 
-        let reserve_a = pool.reserve_a.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear reserva A"))?;
-        let reserve_b = pool.reserve_b.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear reserva B"))?;
-
-        pool.reserve_a = (reserve_a + token_a_amount_u128).to_string();
-        pool.reserve_b = (reserve_b + token_b_amount_u128).to_string();
-
-        let total_supply = pool.total_supply.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear supply total"))?;
-        let lp_tokens_minted_u128 = lp_tokens_minted.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear LP tokens"))?;
-
-        pool.total_supply = (total_supply + lp_tokens_minted_u128).to_string();
-
-        Ok(lp_tokens_minted)
+    /// Obtener posiciones de liquidez
+    pub fn get_liquidity_positions(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.liquidity_positions).unwrap_or_default()
     }
 
     /// Remover liquidez
@@ -499,4 +513,188 @@ impl DeFiManager {
     pub fn is_initialized(&self) -> bool {
         self.is_initialized
     }
-} 
\ No newline at end of file
+}
+
+impl DeFiManager {
+    /// Aportar `token_a_amount` y `token_b_amount` a un pool. Los LP tokens
+    /// acuñados son proporcionales al menor de los dos aportes respecto a su
+    /// reserva, y la posición guarda la relación de precios actual
+    pub fn open_liquidity_position(&mut self, pool_id: &str, token_a_amount: Balance, token_b_amount: Balance) -> Result<LiquidityPosition, String> {
+        if token_a_amount == 0 || token_b_amount == 0 {
+            return Err("Cantidad de liquidez vacía".to_string());
+        }
+        let entry_price_ratio = self.current_price_ratio(pool_id)?;
+        let pool = self.liquidity_pools.get_mut(pool_id)
+            .ok_or_else(|| "Pool no encontrado".to_string())?;
+
+        let parse = |value: &str, name: &str| value.parse::<Balance>()
+            .map_err(|_| format!("Error al parsear {}", name));
+        let reserve_a = parse(&pool.reserve_a, "reserva A")?;
+        let reserve_b = parse(&pool.reserve_b, "reserva B")?;
+        let total_supply = parse(&pool.total_supply, "supply total")?;
+
+        let liquidity_tokens = if total_supply == 0 || reserve_a == 0 || reserve_b == 0 {
+            (token_a_amount as f64 * token_b_amount as f64).sqrt() as Balance
+        } else {
+            let share_a = token_a_amount as f64 / reserve_a as f64;
+            let share_b = token_b_amount as f64 / reserve_b as f64;
+            (share_a.min(share_b) * total_supply as f64) as Balance
+        };
+        if liquidity_tokens == 0 {
+            return Err("Liquidez insuficiente".to_string());
+        }
+
+        pool.reserve_a = reserve_a.checked_add(token_a_amount)
+            .ok_or_else(|| "Desbordamiento de reserva A".to_string())?
+            .to_string();
+        pool.reserve_b = reserve_b.checked_add(token_b_amount)
+            .ok_or_else(|| "Desbordamiento de reserva B".to_string())?
+            .to_string();
+        pool.total_supply = (total_supply + liquidity_tokens).to_string();
+
+        let position = LiquidityPosition {
+            pool_id: pool_id.to_string(),
+            token_a: pool.token_a.clone(),
+            token_b: pool.token_b.clone(),
+            entry_price_ratio,
+            liquidity_tokens,
+        };
+        self.liquidity_positions.push(position.clone());
+        Ok(position)
+    }
+
+    /// Pérdida impermanente de `position` si la relación de precios pasa a
+    /// ser `current_price_ratio`: `2*sqrt(k)/(1+k) - 1` con
+    /// `k = current/entry`. Es 0 en el precio de entrada, negativa en
+    /// cualquier otro y tiende a -1 cuando un token pierde todo su valor
+    pub fn calculate_impermanent_loss(position: &LiquidityPosition, current_price_ratio: f64) -> f64 {
+        if position.entry_price_ratio <= 0.0 || !position.entry_price_ratio.is_finite() {
+            return 0.0;
+        }
+        if current_price_ratio <= 0.0 {
+            return -1.0;
+        }
+        let k = current_price_ratio / position.entry_price_ratio;
+        if !k.is_finite() {
+            return -1.0;
+        }
+        (2.0 * k.sqrt() / (1.0 + k) - 1.0).min(0.0)
+    }
+
+    /// Precio actual de `token_a` en unidades de `token_b` de un pool
+    pub fn current_price_ratio(&self, pool_id: &str) -> Result<f64, String> {
+        let pool = self.liquidity_pools.get(pool_id)
+            .ok_or_else(|| "Pool no encontrado".to_string())?;
+        let price_a = self.token_price(&pool.token_a)
+            .ok_or_else(|| format!("Sin precio para {}", pool.token_a))?;
+        let price_b = self.token_price(&pool.token_b)
+            .filter(|price| *price > 0.0)
+            .ok_or_else(|| format!("Sin precio para {}", pool.token_b))?;
+        Ok(price_a / price_b)
+    }
+
+    /// Valor en USD de la parte de las reservas del pool que corresponde a
+    /// los LP tokens de `position`, con los precios actuales
+    pub fn get_position_value_usd(&self, position: &LiquidityPosition) -> f64 {
+        let pool = match self.liquidity_pools.get(&position.pool_id) {
+            Some(pool) => pool,
+            None => return 0.0,
+        };
+        let parse = |value: &str| value.parse::<Balance>().unwrap_or(0) as f64;
+        let total_supply = parse(&pool.total_supply);
+        if total_supply <= 0.0 {
+            return 0.0;
+        }
+        let share = position.liquidity_tokens as f64 / total_supply;
+        let value_a = parse(&pool.reserve_a) / TOKEN_UNIT * self.token_price(&pool.token_a).unwrap_or(0.0);
+        let value_b = parse(&pool.reserve_b) / TOKEN_UNIT * self.token_price(&pool.token_b).unwrap_or(0.0);
+        share * (value_a + value_b)
+    }
+
+    /// Comisiones estimadas en USD tras `days_held` días con un APR en
+    /// porcentaje (como `LiquidityPool::apr`), sin interés compuesto
+    pub fn estimate_fees_earned(&self, position: &LiquidityPosition, apr: f64, days_held: f64) -> f64 {
+        if apr <= 0.0 || days_held <= 0.0 {
+            return 0.0;
+        }
+        self.get_position_value_usd(position) * apr / 100.0 * days_held / DAYS_PER_YEAR
+    }
+
+    /// Posiciones de liquidez abiertas
+    pub fn liquidity_positions(&self) -> &[LiquidityPosition] {
+        &self.liquidity_positions
+    }
+
+    /// Establecer los precios en USD por símbolo de token
+    pub fn set_price_feeds(&mut self, price_feeds: &HashMap<String, f64>) {
+        self.price_feeds.clone_from(price_feeds);
+    }
+
+    /// Precio en USD de un token. Los feeds usan el símbolo tal cual o con el
+    /// sufijo `_TOKEN`
+    fn token_price(&self, token_symbol: &str) -> Option<f64> {
+        self.price_feeds.get(token_symbol)
+            .or_else(|| self.price_feeds.get(&format!("{}_TOKEN", token_symbol)))
+            .copied()
+    }
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn position(entry_price_ratio: f64) -> LiquidityPosition {
+        LiquidityPosition {
+            pool_id: "GREEN_ETH_POOL".to_string(),
+            token_a: "GREEN".to_string(),
+            token_b: "ETH".to_string(),
+            entry_price_ratio,
+            liquidity_tokens: 1,
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn diverged_price_always_loses(entry in 1e-6f64..1e6, factor in 1e-6f64..1e6) {
+            // Divergencia apreciable: con k muy cerca de 1 la pérdida cae por
+            // debajo de la precisión de f64
+            prop_assume!((factor.ln()).abs() > 1e-3);
+            let loss = DeFiManager::calculate_impermanent_loss(&position(entry), entry * factor);
+            prop_assert!(loss < 0.0, "k = {} da pérdida {}", factor, loss);
+            prop_assert!(loss >= -1.0);
+        }
+
+        #[test]
+        fn returning_to_entry_price_has_no_loss(entry in 1e-6f64..1e6) {
+            prop_assert_eq!(DeFiManager::calculate_impermanent_loss(&position(entry), entry), 0.0);
+        }
+    }
+
+    #[test]
+    fn doubling_the_price_matches_the_closed_form() {
+        // k = 2: 2*sqrt(2)/3 - 1
+        let loss = DeFiManager::calculate_impermanent_loss(&position(0.5), 1.0);
+        assert!((loss - (2.0 * 2f64.sqrt() / 3.0 - 1.0)).abs() < 1e-12);
+        assert_eq!(DeFiManager::calculate_impermanent_loss(&position(0.5), 0.0), -1.0);
+    }
+
+    #[test]
+    fn add_liquidity_binding_returns_minted_lp_tokens() {
+        let mut manager = DeFiManager::new(&crate::blockchain::BlockchainConfig::default());
+        manager.initialize().unwrap();
+        manager.set_price_feeds(&HashMap::from([
+            ("GREEN".to_string(), 3.0),
+            ("ETH".to_string(), 3000.0),
+        ]));
+
+        // 1% de las reservas: 1% del supply de LP tokens
+        let minted = manager.add_liquidity("GREEN_ETH_POOL", "10000000000000000000000", "10000000000000000000")
+            .unwrap();
+        assert_eq!(minted, "10000000000000000000");
+        let position = &manager.liquidity_positions()[0];
+        assert_eq!(position.liquidity_tokens.to_string(), minted);
+        assert!((position.entry_price_ratio - 0.001).abs() < 1e-12);
+
+        assert!(manager.open_liquidity_position("GREEN_ETH_POOL", 0, 1).is_err());
+    }
+}
@@ -1,10 +1,17 @@
 //! Gestor de Governance para Metaverso
 //! Maneja propuestas, votaciones y decisiones DAO
+//!
+//! Las propuestas `ParameterChange` cambian un parámetro de los gestores al
+//! ejecutarse: la clave debe estar en `GOVERNABLE_PARAMETERS` y el valor ser
+//! del tipo que admite, y el `ParameterRegistry` lleva el cambio al gestor
+//! que lo registró. Cada cambio aplicado emite un `ParameterUpdated`.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+use crate::crypto::vrf::{self, VRFProof};
+
 /// Propuesta de governance
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Proposal {
@@ -15,6 +22,8 @@ pub struct Proposal {
     pub island: String,
     pub category: ProposalCategory,
     pub status: ProposalStatus,
+    #[serde(default)]
+    pub created_at: u64,
     pub start_time: u64,
     pub end_time: u64,
     pub voting_power_required: u64,
@@ -24,6 +33,18 @@ pub struct Proposal {
     pub votes_abstain: u64,
     pub executed: bool,
     pub execution_time: Option<u64>,
+    #[serde(default)]
+    pub queued_at: Option<u64>,
+    #[serde(default)]
+    pub eta: Option<u64>,
+}
+
+/// Identificador de propuesta
+pub type ProposalId = String;
+
+/// Identificador de la propuesta número `number` (`PROP_001`, ...)
+pub fn proposal_key(number: u64) -> ProposalId {
+    format!("PROP_{:03}", number)
 }
 
 /// Categoría de propuesta
@@ -37,13 +58,16 @@ pub enum ProposalCategory {
     TreasuryAllocation,
     Partnership,
     TechnicalUpgrade,
+    /// Cambiar un parámetro gobernable al ejecutarse
+    ParameterChange { key: String, value: serde_json::Value },
 }
 
 /// Estado de propuesta
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ProposalStatus {
     Active,
     Pending,
+    Queued,
     Executed,
     Defeated,
     Expired,
@@ -93,6 +117,254 @@ pub struct GovernanceInfo {
     pub execution_delay: u64,
 }
 
+/// Configuración de governance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GovernanceConfig {
+    /// Segundos entre la aprobación de una propuesta y su ejecución
+    pub execution_timelock_secs: u64,
+    /// Direcciones del multisig de guardianes
+    pub guardians: Vec<String>,
+    /// Firmas de guardianes necesarias para vetar
+    pub guardian_threshold: usize,
+    /// Cómo se pondera el poder de voto
+    #[serde(default)]
+    pub voting_mechanism: VotingMechanism,
+    /// Fracción del stake total que la convicción a favor debe alcanzar
+    /// para aprobar (solo con `VotingMechanism::Conviction`)
+    #[serde(default = "default_conviction_threshold")]
+    pub conviction_threshold: f64,
+}
+
+fn default_conviction_threshold() -> f64 {
+    0.1
+}
+
+impl Default for GovernanceConfig {
+    fn default() -> Self {
+        Self {
+            execution_timelock_secs: 172800, // 2 días
+            guardians: Vec::new(),
+            guardian_threshold: 1,
+            voting_mechanism: VotingMechanism::default(),
+            conviction_threshold: default_conviction_threshold(),
+        }
+    }
+}
+
+/// Ponderación del poder de voto
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VotingMechanism {
+    /// Tokens en el momento del voto
+    #[default]
+    Snapshot,
+    /// Convicción: el peso crece con el tiempo que el stake lleva bloqueado
+    /// desde la creación de la propuesta, `stake * (1 - 0.5^días)`
+    Conviction,
+}
+
+/// Segundos en los que la convicción recorre la mitad de la distancia al stake
+pub const CONVICTION_HALF_LIFE_SECS: u64 = 86400;
+
+/// Historial de stake por cuenta para calcular la convicción
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConvictionTracker {
+    /// (timestamp, stake tras el cambio) de cada cuenta, en orden temporal
+    pub stake_history: HashMap<String, Vec<(u64, u64)>>,
+}
+
+impl ConvictionTracker {
+    /// Registrar el stake de una cuenta tras un cambio en `timestamp`
+    pub fn record_stake(&mut self, account: &str, timestamp: u64, balance: u64) {
+        let history = self.stake_history.entry(account.to_string()).or_default();
+        // Un cambio con timestamp anterior al último se aplica en el último
+        let timestamp = history.last().map_or(timestamp, |&(last, _)| timestamp.max(last));
+        match history.last_mut() {
+            Some(last) if last.0 == timestamp => last.1 = balance,
+            _ => history.push((timestamp, balance)),
+        }
+    }
+
+    /// Stake de una cuenta en `timestamp`
+    pub fn stake_at(&self, account: &str, timestamp: u64) -> u64 {
+        self.stake_history.get(account)
+            .and_then(|history| history.iter().take_while(|(t, _)| *t <= timestamp).last())
+            .map_or(0, |(_, balance)| *balance)
+    }
+
+    /// Stake total de todas las cuentas en `timestamp`
+    pub fn total_stake_at(&self, timestamp: u64) -> u64 {
+        self.stake_history.keys()
+            .map(|account| self.stake_at(account, timestamp))
+            .fold(0u64, u64::saturating_add)
+    }
+
+    /// Convicción de una cuenta entre `since` y `now`: la integral de la
+    /// curva de stake con un decaimiento exponencial de vida media
+    /// `CONVICTION_HALF_LIFE_SECS`. Con stake constante S durante d días
+    /// vale `S * (1 - 0.5^d)`, así que un stake reciente pesa menos que
+    /// uno igual que ya llevaba tiempo bloqueado
+    pub fn conviction(&self, account: &str, since: u64, now: u64) -> f64 {
+        if now <= since {
+            return 0.0;
+        }
+        let Some(history) = self.stake_history.get(account) else {
+            return 0.0;
+        };
+
+        let mut conviction = 0.0;
+        let mut stake = self.stake_at(account, since) as f64;
+        let mut from = since;
+        let changes = history.iter().filter(|(t, _)| *t > since && *t <= now);
+        for &(timestamp, balance) in changes.chain(std::iter::once(&(now, 0))) {
+            // Tramo de stake constante: la convicción se acerca al stake
+            let decay = 0.5f64.powf((timestamp - from) as f64 / CONVICTION_HALF_LIFE_SECS as f64);
+            conviction = conviction * decay + stake * (1.0 - decay);
+            stake = balance as f64;
+            from = timestamp;
+        }
+        conviction
+    }
+}
+
+/// Cola de propuestas aprobadas ordenada por instante de ejecución
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimelockQueue {
+    pub queued: BTreeMap<u64, Vec<ProposalId>>,
+}
+
+impl TimelockQueue {
+    /// Encolar una propuesta para ejecutarse en `eta`
+    pub fn enqueue(&mut self, eta: u64, proposal_id: ProposalId) {
+        self.queued.entry(eta).or_insert_with(Vec::new).push(proposal_id);
+    }
+
+    /// Retirar una propuesta de la cola
+    pub fn remove(&mut self, eta: u64, proposal_id: &str) -> bool {
+        let Some(ids) = self.queued.get_mut(&eta) else {
+            return false;
+        };
+        let before = ids.len();
+        ids.retain(|id| id != proposal_id);
+        let removed = ids.len() != before;
+        if ids.is_empty() {
+            self.queued.remove(&eta);
+        }
+        removed
+    }
+
+    /// Retirar las propuestas cuyo instante de ejecución ya pasó
+    pub fn drain_ready(&mut self, now: u64) -> Vec<ProposalId> {
+        let pending = self.queued.split_off(&(now.saturating_add(1)));
+        let ready = std::mem::replace(&mut self.queued, pending);
+        ready.into_values().flatten().collect()
+    }
+
+    /// Número de propuestas en cola
+    pub fn len(&self) -> usize {
+        self.queued.values().map(Vec::len).sum()
+    }
+
+    /// Verificar si la cola está vacía
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+}
+
+/// Petición de aleatoriedad VRF. Su ID es el nonce de la prueba, así que
+/// cada prueba se acepta una sola vez
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RandomnessRequest {
+    pub request_id: u64,
+    pub seed: [u8; 32],
+    pub fulfilled: bool,
+}
+
+/// Receptor de la aleatoriedad de las peticiones cumplidas
+pub type RandomnessCallback = Box<dyn FnMut(u64, [u8; 32])>;
+
+/// Valores que admite un parámetro gobernable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterKind {
+    /// Entero en puntos básicos entre 0 y 10000
+    BasisPoints,
+    /// Fracción entre 0 y 1
+    Rate,
+    /// Cantidad de tokens en unidades mínimas (entero o cadena decimal)
+    Amount,
+}
+
+/// Parámetros que governance puede cambiar
+pub const GOVERNABLE_PARAMETERS: &[(&str, ParameterKind)] = &[
+    ("marketplace.fee_basis_points", ParameterKind::BasisPoints),
+    ("staking.reward_rate", ParameterKind::Rate),
+    ("token.max_transfer_amount", ParameterKind::Amount),
+];
+
+/// Comprobar que un cambio está permitido y que el valor es del tipo del
+/// parámetro
+pub fn validate_parameter_change(key: &str, value: &serde_json::Value) -> Result<(), String> {
+    let (_, kind) = GOVERNABLE_PARAMETERS.iter()
+        .find(|(name, _)| *name == key)
+        .ok_or_else(|| format!("Parámetro no gobernable: {}", key))?;
+    let valid = match kind {
+        ParameterKind::BasisPoints => value.as_u64().is_some_and(|bps| bps <= 10_000),
+        ParameterKind::Rate => value.as_f64().is_some_and(|rate| (0.0..=1.0).contains(&rate)),
+        ParameterKind::Amount => value.as_u64().is_some()
+            || value.as_str().is_some_and(|amount| amount.parse::<u128>().is_ok()),
+    };
+    if !valid {
+        return Err(format!("Valor no válido para {}: {}", key, value));
+    }
+    Ok(())
+}
+
+/// Aplica el valor nuevo de un parámetro en su gestor
+pub type ParameterSetter = Box<dyn Fn(&serde_json::Value) + Send>;
+
+/// Gestores de los parámetros gobernables y su valor actual
+#[derive(Default)]
+pub struct ParameterRegistry {
+    setters: HashMap<String, ParameterSetter>,
+    values: HashMap<String, serde_json::Value>,
+}
+
+impl ParameterRegistry {
+    /// Registrar el gestor de un parámetro con su valor actual (reemplaza
+    /// el anterior)
+    pub fn register(&mut self, key: &str, current: serde_json::Value, setter: ParameterSetter) {
+        self.setters.insert(key.to_string(), setter);
+        self.values.insert(key.to_string(), current);
+    }
+
+    /// Hay un gestor para el parámetro
+    pub fn is_registered(&self, key: &str) -> bool {
+        self.setters.contains_key(key)
+    }
+
+    /// Valor actual de un parámetro
+    pub fn value(&self, key: &str) -> Option<&serde_json::Value> {
+        self.values.get(key)
+    }
+
+    /// Validar y aplicar un cambio. Devuelve el valor anterior
+    pub fn apply(&mut self, key: &str, value: &serde_json::Value) -> Result<serde_json::Value, String> {
+        validate_parameter_change(key, value)?;
+        let setter = self.setters.get(key)
+            .ok_or_else(|| format!("Parámetro sin gestor registrado: {}", key))?;
+        setter(value);
+        Ok(self.values.insert(key.to_string(), value.clone()).unwrap_or(serde_json::Value::Null))
+    }
+}
+
+/// Evento de un parámetro cambiado por una propuesta
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterUpdated {
+    pub key: String,
+    pub old_value: serde_json::Value,
+    pub new_value: serde_json::Value,
+    pub proposal_id: ProposalId,
+}
+
 /// Gestor de Governance
 #[wasm_bindgen]
 pub struct GovernanceManager {
@@ -101,6 +373,18 @@ pub struct GovernanceManager {
     delegations: HashMap<String, Delegation>,
     governance_info: GovernanceInfo,
     user_voting_power: HashMap<String, u64>,
+    config: GovernanceConfig,
+    timelock_queue: TimelockQueue,
+    veto_approvals: HashMap<ProposalId, HashSet<String>>,
+    conviction_tracker: ConvictionTracker,
+    vrf_secret_key: Option<Vec<u8>>,
+    vrf_public_key: Option<Vec<u8>>,
+    randomness_requests: BTreeMap<u64, RandomnessRequest>,
+    next_randomness_request: u64,
+    randomness_outputs: HashMap<u64, [u8; 32]>,
+    randomness_callback: Option<RandomnessCallback>,
+    parameter_registry: ParameterRegistry,
+    parameter_events: Vec<ParameterUpdated>,
     current_network: String,
     is_initialized: bool,
 }
@@ -124,6 +408,18 @@ impl GovernanceManager {
                 execution_delay: 86400, // 1 día
             },
             user_voting_power: HashMap::new(),
+            config: GovernanceConfig::default(),
+            timelock_queue: TimelockQueue::default(),
+            veto_approvals: HashMap::new(),
+            conviction_tracker: ConvictionTracker::default(),
+            vrf_secret_key: None,
+            vrf_public_key: None,
+            randomness_requests: BTreeMap::new(),
+            next_randomness_request: 1,
+            randomness_outputs: HashMap::new(),
+            randomness_callback: None,
+            parameter_registry: ParameterRegistry::default(),
+            parameter_events: Vec::new(),
             current_network: config.default_network.clone(),
             is_initialized: false,
         }
@@ -153,6 +449,7 @@ impl GovernanceManager {
                 island: "forest".to_string(),
                 category: ProposalCategory::IslandDevelopment,
                 status: ProposalStatus::Active,
+                created_at: current_time - 172800,
                 start_time: current_time - 86400, // Hace 1 día
                 end_time: current_time + 518400, // En 6 días
                 voting_power_required: 10000000000000000000000, // 10K tokens
@@ -162,6 +459,8 @@ impl GovernanceManager {
                 votes_abstain: 5000000000000000000000, // 5K tokens
                 executed: false,
                 execution_time: None,
+                queued_at: None,
+                eta: None,
             },
             Proposal {
                 id: "PROP_002".to_string(),
@@ -171,6 +470,7 @@ impl GovernanceManager {
                 island: "forest".to_string(),
                 category: ProposalCategory::TokenEconomics,
                 status: ProposalStatus::Active,
+                created_at: current_time - 259200,
                 start_time: current_time - 172800, // Hace 2 días
                 end_time: current_time + 432000, // En 5 días
                 voting_power_required: 10000000000000000000000, // 10K tokens
@@ -180,6 +480,8 @@ impl GovernanceManager {
                 votes_abstain: 5000000000000000000000, // 5K tokens
                 executed: false,
                 execution_time: None,
+                queued_at: None,
+                eta: None,
             },
             Proposal {
                 id: "PROP_003".to_string(),
@@ -189,6 +491,7 @@ impl GovernanceManager {
                 island: "global".to_string(),
                 category: ProposalCategory::FeatureRequest,
                 status: ProposalStatus::Pending,
+                created_at: current_time,
                 start_time: current_time + 86400, // En 1 día
                 end_time: current_time + 604800, // En 7 días
                 voting_power_required: 10000000000000000000000, // 10K tokens
@@ -198,6 +501,8 @@ impl GovernanceManager {
                 votes_abstain: 0,
                 executed: false,
                 execution_time: None,
+                queued_at: None,
+                eta: None,
             },
             Proposal {
                 id: "PROP_004".to_string(),
@@ -207,6 +512,7 @@ impl GovernanceManager {
                 island: "global".to_string(),
                 category: ProposalCategory::SecurityUpdate,
                 status: ProposalStatus::Executed,
+                created_at: current_time - 1296000,
                 start_time: current_time - 1209600, // Hace 14 días
                 end_time: current_time - 518400, // Hace 6 días
                 voting_power_required: 10000000000000000000000, // 10K tokens
@@ -216,6 +522,8 @@ impl GovernanceManager {
                 votes_abstain: 2000000000000000000000, // 2K tokens
                 executed: true,
                 execution_time: Some(current_time - 432000), // Hace 5 días
+                queued_at: None,
+                eta: None,
             },
         ];
 
@@ -238,6 +546,12 @@ impl GovernanceManager {
         let user_address = "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6";
         self.user_voting_power.insert(user_address.to_string(), 50000000000000000000000); // 50K tokens
 
+        let current_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.conviction_tracker.record_stake(user_address, current_time, 50000000000000000000000);
+
         self.governance_info.total_voters = 1;
         Ok(())
     }
@@ -258,7 +572,7 @@ impl GovernanceManager {
             .unwrap()
             .as_secs();
 
-        let proposal_id = format!("PROP_{:03}", self.proposals.len() + 1);
+        let proposal_id = proposal_key(self.proposals.len() as u64 + 1);
         
         let category_enum = match category.to_lowercase().as_str() {
             "island_development" => ProposalCategory::IslandDevelopment,
@@ -280,6 +594,7 @@ impl GovernanceManager {
             island: island.to_string(),
             category: category_enum,
             status: ProposalStatus::Pending,
+            created_at: current_time,
             start_time: current_time + self.governance_info.execution_delay,
             end_time: current_time + self.governance_info.execution_delay + self.governance_info.voting_period,
             voting_power_required: self.governance_info.proposal_threshold,
@@ -289,6 +604,8 @@ impl GovernanceManager {
             votes_abstain: 0,
             executed: false,
             execution_time: None,
+            queued_at: None,
+            eta: None,
         };
 
         self.proposals.insert(proposal_id.clone(), proposal);
@@ -299,109 +616,48 @@ impl GovernanceManager {
 
     /// Votar en propuesta
     pub fn vote(&mut self, proposal_id: &str, vote_type: &str, reason: Option<&str>) -> Result<(), JsValue> {
-        let proposal = self.proposals.get_mut(proposal_id)
-            .ok_or_else(|| JsValue::from_str("Propuesta no encontrada"))?;
-
-        if proposal.status != ProposalStatus::Active {
-            return Err(JsValue::from_str("La propuesta no está activa para votación"));
-        }
-
         let current_time = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
-        if current_time < proposal.start_time || current_time > proposal.end_time {
-            return Err(JsValue::from_str("Fuera del período de votación"));
-        }
-
         let user_address = "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6";
-        let user_voting_power = self.user_voting_power.get(user_address)
-            .copied()
-            .unwrap_or(0);
-
-        if user_voting_power == 0 {
-            return Err(JsValue::from_str("No tienes poder de voto"));
-        }
-
-        // Verificar si ya votó
-        let existing_votes = self.votes.get(proposal_id).unwrap_or(&Vec::new());
-        if existing_votes.iter().any(|vote| vote.voter == user_address) {
-            return Err(JsValue::from_str("Ya has votado en esta propuesta"));
-        }
-
-        let vote_type_enum = match vote_type.to_lowercase().as_str() {
-            "for" => VoteType::For,
-            "against" => VoteType::Against,
-            "abstain" => VoteType::Abstain,
-            _ => return Err(JsValue::from_str("Tipo de voto no válido")),
-        };
-
-        let vote = Vote {
-            proposal_id: proposal_id.to_string(),
-            voter: user_address.to_string(),
-            vote_type: vote_type_enum,
-            voting_power: user_voting_power,
-            timestamp: current_time,
-            reason: reason.map(|r| r.to_string()),
-        };
-
-        // Actualizar votos de la propuesta
-        match vote_type_enum {
-            VoteType::For => proposal.votes_for += user_voting_power,
-            VoteType::Against => proposal.votes_against += user_voting_power,
-            VoteType::Abstain => proposal.votes_abstain += user_voting_power,
-        }
-
-        proposal.total_votes += user_voting_power;
-
-        // Guardar voto
-        self.votes.entry(proposal_id.to_string())
-            .or_insert_with(Vec::new)
-            .push(vote);
-
-        Ok(())
+        self.vote_at(user_address, proposal_id, vote_type, reason, current_time)
+            .map_err(|e| JsValue::from_str(&e))
     }
 
-    /// Ejecutar propuesta
+    /// Ejecutar propuesta cuyo timelock ya venció
     pub fn execute_proposal(&mut self, proposal_id: &str) -> Result<(), JsValue> {
-        let proposal = self.proposals.get_mut(proposal_id)
-            .ok_or_else(|| JsValue::from_str("Propuesta no encontrada"))?;
-
-        if proposal.executed {
-            return Err(JsValue::from_str("La propuesta ya fue ejecutada"));
-        }
-
-        if proposal.status != ProposalStatus::Active {
-            return Err(JsValue::from_str("La propuesta no está activa"));
-        }
-
         let current_time = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
-        if current_time < proposal.end_time + self.governance_info.execution_delay {
-            return Err(JsValue::from_str("Aún no se puede ejecutar la propuesta"));
-        }
+        self.execute_proposal_at(proposal_id, current_time)
+            .map_err(|e| JsValue::from_str(&e))
+    }
 
-        // Verificar quorum
-        if proposal.total_votes < self.governance_info.quorum_required {
-            proposal.status = ProposalStatus::Defeated;
-            return Err(JsValue::from_str("No se alcanzó el quorum requerido"));
-        }
+    /// Vetar propuesta en timelock (firma de un guardián del multisig).
+    /// Devuelve `true` cuando se alcanza el umbral y la propuesta queda cancelada
+    pub fn veto(&mut self, proposal_id: &str, guardian: &str) -> Result<bool, JsValue> {
+        let current_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
 
-        // Verificar mayoría
-        if proposal.votes_for <= proposal.votes_against {
-            proposal.status = ProposalStatus::Defeated;
-            return Err(JsValue::from_str("La propuesta fue rechazada"));
-        }
+        self.veto_at(proposal_id, guardian, current_time)
+            .map_err(|e| JsValue::from_str(&e))
+    }
 
-        proposal.status = ProposalStatus::Executed;
-        proposal.executed = true;
-        proposal.execution_time = Some(current_time);
+    /// Procesar cola del timelock con la hora actual
+    pub fn process_timelock(&mut self) -> JsValue {
+        let current_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
 
-        Ok(())
+        let executed = self.process_timelock_queue(current_time);
+        serde_wasm_bindgen::to_value(&executed).unwrap_or_default()
     }
 
     /// Obtener propuesta
@@ -439,6 +695,7 @@ impl GovernanceManager {
             "treasury_allocation" => ProposalCategory::TreasuryAllocation,
             "partnership" => ProposalCategory::Partnership,
             "technical_upgrade" => ProposalCategory::TechnicalUpgrade,
+            "parameter_change" => ProposalCategory::ParameterChange { key: String::new(), value: serde_json::Value::Null },
             _ => return serde_wasm_bindgen::to_value(&Vec::<&Proposal>::new()).unwrap_or_default(),
         };
 
@@ -455,6 +712,11 @@ impl GovernanceManager {
         serde_wasm_bindgen::to_value(votes).unwrap_or_default()
     }
 
+    /// Obtener los cambios de parámetros aplicados por propuestas
+    pub fn get_parameter_events(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.parameter_events).unwrap_or_default()
+    }
+
     /// Obtener poder de voto del usuario
     pub fn get_user_voting_power(&self) -> Result<u64, JsValue> {
         let user_address = "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6";
@@ -463,6 +725,17 @@ impl GovernanceManager {
             .ok_or_else(|| JsValue::from_str("Usuario no encontrado"))
     }
 
+    /// Obtener la convicción acumulada por un votante en la propuesta
+    /// número `proposal_id`
+    pub fn get_conviction_score(&self, voter: &str, proposal_id: u64) -> f64 {
+        let current_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        self.conviction_score_at(voter, &proposal_key(proposal_id), current_time)
+    }
+
     /// Delegar votos
     pub fn delegate_votes(&mut self, delegate_address: &str, amount: u64) -> Result<(), JsValue> {
         let delegator_address = "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6";
@@ -502,6 +775,11 @@ impl GovernanceManager {
             self.user_voting_power.insert(delegate_address.to_string(), amount);
         }
 
+        for address in [delegator_address, delegate_address] {
+            let power = self.user_voting_power.get(address).copied().unwrap_or(0);
+            self.conviction_tracker.record_stake(address, current_time, power);
+        }
+
         Ok(())
     }
 
@@ -563,4 +841,702 @@ impl GovernanceManager {
     pub fn is_initialized(&self) -> bool {
         self.is_initialized
     }
-} 
\ No newline at end of file
+}
+
+impl GovernanceManager {
+    /// Configuración de governance
+    pub fn config(&self) -> &GovernanceConfig {
+        &self.config
+    }
+
+    /// Actualizar configuración de governance
+    pub fn set_governance_config(&mut self, config: GovernanceConfig) {
+        self.config = config;
+    }
+
+    /// Cola del timelock
+    pub fn timelock_queue(&self) -> &TimelockQueue {
+        &self.timelock_queue
+    }
+
+    /// Historial de stake para la convicción
+    pub fn conviction_tracker(&self) -> &ConvictionTracker {
+        &self.conviction_tracker
+    }
+
+    /// Registrar el stake de una cuenta tras un cambio en `timestamp`. Es
+    /// también su poder de voto
+    pub fn record_stake_change(&mut self, account: &str, timestamp: u64, balance: u64) {
+        self.user_voting_power.insert(account.to_string(), balance);
+        self.conviction_tracker.record_stake(account, timestamp, balance);
+    }
+
+    /// Votar en el instante `now`. Una propuesta pendiente pasa a activa
+    /// al abrirse su período de votación
+    pub fn vote_at(
+        &mut self,
+        voter: &str,
+        proposal_id: &str,
+        vote_type: &str,
+        reason: Option<&str>,
+        now: u64,
+    ) -> Result<(), String> {
+        let proposal = self.proposals.get_mut(proposal_id)
+            .ok_or_else(|| "Propuesta no encontrada".to_string())?;
+
+        if proposal.status == ProposalStatus::Pending && now >= proposal.start_time {
+            proposal.status = ProposalStatus::Active;
+            self.governance_info.active_proposals += 1;
+        }
+
+        if proposal.status != ProposalStatus::Active {
+            return Err("La propuesta no está activa para votación".to_string());
+        }
+
+        if now < proposal.start_time || now >= proposal.end_time {
+            return Err("Fuera del período de votación".to_string());
+        }
+
+        let voting_power = self.user_voting_power.get(voter)
+            .copied()
+            .unwrap_or(0);
+
+        if voting_power == 0 {
+            return Err("No tienes poder de voto".to_string());
+        }
+
+        // Con convicción pesa el stake acumulado desde la creación de la propuesta
+        let voting_power = match self.config.voting_mechanism {
+            VotingMechanism::Snapshot => voting_power,
+            VotingMechanism::Conviction => {
+                self.conviction_tracker.conviction(voter, proposal.created_at, now) as u64
+            }
+        };
+
+        // Verificar si ya votó
+        let already_voted = self.votes.get(proposal_id)
+            .is_some_and(|votes| votes.iter().any(|vote| vote.voter == voter));
+        if already_voted {
+            return Err("Ya has votado en esta propuesta".to_string());
+        }
+
+        let vote_type = match vote_type.to_lowercase().as_str() {
+            "for" => VoteType::For,
+            "against" => VoteType::Against,
+            "abstain" => VoteType::Abstain,
+            _ => return Err("Tipo de voto no válido".to_string()),
+        };
+
+        // Actualizar votos de la propuesta
+        match vote_type {
+            VoteType::For => proposal.votes_for += voting_power,
+            VoteType::Against => proposal.votes_against += voting_power,
+            VoteType::Abstain => proposal.votes_abstain += voting_power,
+        }
+
+        proposal.total_votes += voting_power;
+
+        // Guardar voto
+        self.votes.entry(proposal_id.to_string())
+            .or_default()
+            .push(Vote {
+                proposal_id: proposal_id.to_string(),
+                voter: voter.to_string(),
+                vote_type,
+                voting_power,
+                timestamp: now,
+                reason: reason.map(|r| r.to_string()),
+            });
+
+        Ok(())
+    }
+
+    /// Convicción de un votante en una propuesta en el instante `now`,
+    /// acumulada desde la creación de la propuesta y como mucho hasta su cierre
+    pub fn conviction_score_at(&self, voter: &str, proposal_id: &str, now: u64) -> f64 {
+        let Some(proposal) = self.proposals.get(proposal_id) else {
+            return 0.0;
+        };
+        self.conviction_tracker.conviction(voter, proposal.created_at, now.min(proposal.end_time))
+    }
+
+    /// Cerrar las propuestas activas cuya votación terminó: las que alcanzaron
+    /// quorum y mayoría entran en el timelock y el resto queda derrotada
+    pub fn finalize_proposals(&mut self, now: u64) -> Vec<ProposalId> {
+        let closed: Vec<ProposalId> = self.proposals.iter()
+            .filter(|(_, p)| p.status == ProposalStatus::Active && now >= p.end_time)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        let mut queued = Vec::new();
+        for id in closed {
+            if self.queue_if_passed(&id, now) {
+                queued.push(id);
+            } else if let Some(proposal) = self.proposals.get_mut(&id) {
+                proposal.status = ProposalStatus::Defeated;
+            }
+        }
+        queued
+    }
+
+    /// Encolar la propuesta si su votación cerró con quorum y mayoría
+    fn queue_if_passed(&mut self, proposal_id: &str, now: u64) -> bool {
+        let Some(proposal) = self.proposals.get_mut(proposal_id) else {
+            return false;
+        };
+
+        // Mientras la votación sigue abierta el resultado puede cambiar
+        if now < proposal.end_time {
+            return false;
+        }
+
+        let reached = match self.config.voting_mechanism {
+            VotingMechanism::Snapshot => proposal.total_votes >= self.governance_info.quorum_required,
+            VotingMechanism::Conviction => {
+                // La convicción a favor se compara con el stake total al cierre
+                let total_stake = self.conviction_tracker.total_stake_at(proposal.end_time);
+                total_stake > 0
+                    && proposal.votes_for as f64 / total_stake as f64 >= self.config.conviction_threshold
+            }
+        };
+        if proposal.status != ProposalStatus::Active
+            || !reached
+            || proposal.votes_for <= proposal.votes_against
+        {
+            return false;
+        }
+
+        let eta = now.saturating_add(self.config.execution_timelock_secs);
+        proposal.status = ProposalStatus::Queued;
+        proposal.queued_at = Some(now);
+        proposal.eta = Some(eta);
+        self.timelock_queue.enqueue(eta, proposal_id.to_string());
+        true
+    }
+
+    /// Cerrar las votaciones vencidas y ejecutar y retirar de la cola las
+    /// propuestas cuyo timelock venció
+    pub fn process_timelock_queue(&mut self, now: u64) -> Vec<ProposalId> {
+        self.finalize_proposals(now);
+
+        let mut executed = Vec::new();
+        for id in self.timelock_queue.drain_ready(now) {
+            // Las vetadas o ya ejecutadas no se vuelven a ejecutar
+            let Some(proposal) = self.proposals.get_mut(&id) else {
+                continue;
+            };
+            if proposal.executed || proposal.status != ProposalStatus::Queued {
+                continue;
+            }
+            // Un cambio de parámetro que ya no se puede aplicar se cancela
+            if self.apply_parameter_change(&id).is_err() {
+                let proposal = self.proposals.get_mut(&id).expect("propuesta encontrada");
+                proposal.status = ProposalStatus::Cancelled;
+                proposal.queued_at = None;
+                proposal.eta = None;
+                self.veto_approvals.remove(&id);
+                continue;
+            }
+            let proposal = self.proposals.get_mut(&id).expect("propuesta encontrada");
+            proposal.status = ProposalStatus::Executed;
+            proposal.executed = true;
+            proposal.execution_time = Some(now);
+            self.veto_approvals.remove(&id);
+            executed.push(id);
+        }
+        executed
+    }
+
+    /// Registrar el gestor de un parámetro gobernable con su valor actual
+    pub fn register_parameter(&mut self, key: &str, current: serde_json::Value, setter: ParameterSetter) {
+        self.parameter_registry.register(key, current, setter);
+    }
+
+    /// Parámetros gobernables registrados
+    pub fn parameter_registry(&self) -> &ParameterRegistry {
+        &self.parameter_registry
+    }
+
+    /// Cambios de parámetros aplicados por propuestas
+    pub fn parameter_events(&self) -> &[ParameterUpdated] {
+        &self.parameter_events
+    }
+
+    /// Crear una propuesta que cambia un parámetro gobernable al ejecutarse.
+    /// El cambio se valida ya al crearla
+    pub fn create_parameter_change_proposal(
+        &mut self,
+        title: &str,
+        description: &str,
+        key: &str,
+        value: serde_json::Value,
+    ) -> Result<ProposalId, String> {
+        validate_parameter_change(key, &value)?;
+        if !self.parameter_registry.is_registered(key) {
+            return Err(format!("Parámetro sin gestor registrado: {}", key));
+        }
+        let proposal_id = self.create_proposal(title, description, "global", "technical_upgrade")
+            .map_err(|e| e.as_string().unwrap_or_else(|| "Error al crear la propuesta".to_string()))?;
+        let proposal = self.proposals.get_mut(&proposal_id).expect("propuesta recién creada");
+        proposal.category = ProposalCategory::ParameterChange { key: key.to_string(), value };
+        Ok(proposal_id)
+    }
+
+    /// Ejecutar una propuesta de cambio de parámetro cuyo timelock venció:
+    /// aplica el cambio en su gestor y la marca ejecutada
+    pub fn execute_parameter_change(&mut self, proposal_id: &str) -> Result<(), String> {
+        let current_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        self.execute_parameter_change_at(proposal_id, current_time)
+    }
+
+    /// Ejecutar una propuesta de cambio de parámetro en el instante `now`
+    pub fn execute_parameter_change_at(&mut self, proposal_id: &str, now: u64) -> Result<(), String> {
+        let proposal = self.proposals.get(proposal_id)
+            .ok_or_else(|| "Propuesta no encontrada".to_string())?;
+        if !matches!(proposal.category, ProposalCategory::ParameterChange { .. }) {
+            return Err("La propuesta no cambia ningún parámetro".to_string());
+        }
+        self.execute_proposal_at(proposal_id, now)
+    }
+
+    /// Ejecutar en el instante `now` una propuesta en cola cuyo timelock
+    /// venció. Una propuesta se ejecuta una sola vez
+    pub fn execute_proposal_at(&mut self, proposal_id: &str, now: u64) -> Result<(), String> {
+        let proposal = self.proposals.get(proposal_id)
+            .ok_or_else(|| "Propuesta no encontrada".to_string())?;
+        if proposal.executed {
+            return Err("La propuesta ya fue ejecutada".to_string());
+        }
+        if proposal.status != ProposalStatus::Queued {
+            return Err("La propuesta no está en el timelock".to_string());
+        }
+        let eta = proposal.eta.unwrap_or(u64::MAX);
+        if now < eta {
+            return Err("El timelock de la propuesta aún no venció".to_string());
+        }
+
+        self.apply_parameter_change(proposal_id)?;
+        let proposal = self.proposals.get_mut(proposal_id).expect("propuesta encontrada");
+        proposal.status = ProposalStatus::Executed;
+        proposal.executed = true;
+        proposal.execution_time = Some(now);
+        self.timelock_queue.remove(eta, proposal_id);
+        self.veto_approvals.remove(proposal_id);
+        Ok(())
+    }
+
+    /// Aplicar el cambio de parámetro de una propuesta y emitir su evento
+    /// (nada si la propuesta no cambia parámetros)
+    fn apply_parameter_change(&mut self, proposal_id: &str) -> Result<(), String> {
+        let Some(ProposalCategory::ParameterChange { key, value }) = self.proposals.get(proposal_id).map(|p| &p.category) else {
+            return Ok(());
+        };
+        let (key, value) = (key.clone(), value.clone());
+        let old_value = self.parameter_registry.apply(&key, &value)?;
+        self.parameter_events.push(ParameterUpdated {
+            key,
+            old_value,
+            new_value: value,
+            proposal_id: proposal_id.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Registrar la firma de veto de un guardián en el instante `now`
+    pub fn veto_at(&mut self, proposal_id: &str, guardian: &str, now: u64) -> Result<bool, String> {
+        if !self.config.guardians.iter().any(|g| g.eq_ignore_ascii_case(guardian)) {
+            return Err("Solo los guardianes pueden vetar".to_string());
+        }
+
+        let proposal = self.proposals.get_mut(proposal_id)
+            .ok_or_else(|| "Propuesta no encontrada".to_string())?;
+
+        if proposal.status != ProposalStatus::Queued {
+            return Err("La propuesta no está en el timelock".to_string());
+        }
+
+        let eta = proposal.eta.unwrap_or(0);
+        if now >= eta {
+            return Err("El período de veto terminó".to_string());
+        }
+
+        let approvals = self.veto_approvals.entry(proposal_id.to_string()).or_default();
+        approvals.insert(guardian.to_lowercase());
+        if approvals.len() < self.config.guardian_threshold.max(1) {
+            return Ok(false);
+        }
+
+        // Cancelación permanente: sale de la cola y no puede volver a encolarse
+        proposal.status = ProposalStatus::Cancelled;
+        proposal.queued_at = None;
+        proposal.eta = None;
+        self.timelock_queue.remove(eta, proposal_id);
+        self.veto_approvals.remove(proposal_id);
+        Ok(true)
+    }
+
+    /// Fijar la clave privada VRF del contrato. Devuelve la clave pública
+    /// con la que se verifican sus pruebas
+    pub fn set_vrf_secret_key(&mut self, secret_key: Vec<u8>) -> Result<Vec<u8>, String> {
+        let public_key = vrf::derive_public_key(&secret_key)?;
+        self.vrf_secret_key = Some(secret_key);
+        self.vrf_public_key = Some(public_key.clone());
+        Ok(public_key)
+    }
+
+    /// Clave pública VRF del contrato
+    pub fn vrf_public_key(&self) -> Option<&[u8]> {
+        self.vrf_public_key.as_deref()
+    }
+
+    /// Registrar quién recibe la aleatoriedad de las peticiones cumplidas
+    pub fn set_randomness_callback(&mut self, callback: RandomnessCallback) {
+        self.randomness_callback = Some(callback);
+    }
+
+    /// Abrir una petición de aleatoriedad para `seed` y probarla con la clave
+    /// del contrato. La prueba lleva el ID de la petición como nonce
+    pub fn request_randomness(&mut self, seed: [u8; 32]) -> Result<VRFProof, String> {
+        let secret_key = self.vrf_secret_key.as_ref()
+            .ok_or_else(|| "Clave VRF del contrato no configurada".to_string())?;
+        let request_id = self.next_randomness_request;
+        let proof = vrf::prove(secret_key, request_id, &seed)?;
+
+        self.next_randomness_request += 1;
+        self.randomness_requests.insert(request_id, RandomnessRequest { request_id, seed, fulfilled: false });
+        Ok(proof)
+    }
+
+    /// Salida de una prueba VRF si es válida para `seed` y `public_key`
+    pub fn verify_randomness(seed: &[u8; 32], proof: &VRFProof, public_key: &[u8]) -> Option<[u8; 32]> {
+        vrf::verify(public_key, seed, proof).map(|output| output.0)
+    }
+
+    /// Cumplir la petición de una prueba: debe estar pendiente y la prueba
+    /// ser válida para su semilla con la clave del contrato. Una prueba ya
+    /// usada, o la de otra petición, se rechaza
+    pub fn fulfill_randomness(&mut self, proof: &VRFProof) -> Result<[u8; 32], String> {
+        let public_key = self.vrf_public_key.as_ref()
+            .ok_or_else(|| "Clave VRF del contrato no configurada".to_string())?;
+        let request = self.randomness_requests.get_mut(&proof.nonce)
+            .ok_or_else(|| "Petición de aleatoriedad no encontrada".to_string())?;
+        if request.fulfilled {
+            return Err("La petición de aleatoriedad ya fue cumplida".to_string());
+        }
+
+        let output = Self::verify_randomness(&request.seed, proof, public_key)
+            .ok_or_else(|| "Prueba VRF inválida".to_string())?;
+        request.fulfilled = true;
+        self.on_randomness_fulfilled(proof.nonce, output);
+        Ok(output)
+    }
+
+    /// Guardar la salida de una petición cumplida y avisar al receptor
+    fn on_randomness_fulfilled(&mut self, request_id: u64, output: [u8; 32]) {
+        self.randomness_outputs.insert(request_id, output);
+        if let Some(callback) = self.randomness_callback.as_mut() {
+            callback(request_id, output);
+        }
+    }
+
+    /// Petición de aleatoriedad por ID
+    pub fn randomness_request(&self, request_id: u64) -> Option<&RandomnessRequest> {
+        self.randomness_requests.get(&request_id)
+    }
+
+    /// Aleatoriedad de una petición cumplida
+    pub fn randomness_output(&self, request_id: u64) -> Option<[u8; 32]> {
+        self.randomness_outputs.get(&request_id).copied()
+    }
+
+    /// Peticiones de aleatoriedad aún sin cumplir
+    pub fn pending_randomness_requests(&self) -> impl Iterator<Item = &RandomnessRequest> {
+        self.randomness_requests.values().filter(|request| !request.fulfilled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::BlockchainConfig;
+    use crate::blockchain::marketplace::{MarketplaceManager, DEFAULT_FEE_BASIS_POINTS, FEE_BASIS_POINTS_PARAMETER};
+    use serde_json::json;
+
+    const GUARDIANS: [&str; 3] = ["0xa11ce", "0xb0b", "0xc4r01"];
+    const TIMELOCK: u64 = 3600;
+
+    /// Gestor con timelock de una hora y veto de 2 de 3 guardianes
+    fn manager() -> GovernanceManager {
+        let mut manager = GovernanceManager::new(&BlockchainConfig::default());
+        manager.set_governance_config(GovernanceConfig {
+            execution_timelock_secs: TIMELOCK,
+            guardians: GUARDIANS.iter().map(|g| g.to_string()).collect(),
+            guardian_threshold: 2,
+            ..GovernanceConfig::default()
+        });
+        manager
+    }
+
+    /// Propuesta activa que cierra en `end_time` con quorum y mayoría a favor
+    fn insert_passing_proposal(manager: &mut GovernanceManager, id: &str, end_time: u64) {
+        let quorum = manager.governance_info.quorum_required;
+        manager.proposals.insert(id.to_string(), Proposal {
+            id: id.to_string(),
+            title: "Propuesta de prueba".to_string(),
+            description: String::new(),
+            proposer: GUARDIANS[0].to_string(),
+            island: "global".to_string(),
+            category: ProposalCategory::CommunityInitiative,
+            status: ProposalStatus::Active,
+            created_at: 0,
+            start_time: 0,
+            end_time,
+            voting_power_required: 0,
+            total_votes: quorum,
+            votes_for: quorum,
+            votes_against: 0,
+            votes_abstain: 0,
+            executed: false,
+            execution_time: None,
+            queued_at: None,
+            eta: None,
+        });
+    }
+
+    #[test]
+    fn execution_waits_for_the_timelock() {
+        let mut manager = manager();
+        insert_passing_proposal(&mut manager, "PROP_001", 1000);
+
+        // Al cerrar la votación entra en cola, no se ejecuta
+        assert!(manager.process_timelock_queue(1000).is_empty());
+        let proposal = &manager.proposals["PROP_001"];
+        assert_eq!(proposal.status, ProposalStatus::Queued);
+        assert_eq!(proposal.eta, Some(1000 + TIMELOCK));
+
+        assert!(manager.execute_proposal_at("PROP_001", 1000 + TIMELOCK - 1).is_err());
+        assert!(manager.process_timelock_queue(1000 + TIMELOCK - 1).is_empty());
+        assert!(!manager.proposals["PROP_001"].executed);
+
+        assert_eq!(manager.process_timelock_queue(1000 + TIMELOCK), vec!["PROP_001".to_string()]);
+        let proposal = &manager.proposals["PROP_001"];
+        assert_eq!(proposal.status, ProposalStatus::Executed);
+        assert_eq!(proposal.execution_time, Some(1000 + TIMELOCK));
+        assert!(manager.timelock_queue().is_empty());
+    }
+
+    #[test]
+    fn guardian_threshold_veto_cancels_a_queued_proposal() {
+        let mut manager = manager();
+        insert_passing_proposal(&mut manager, "PROP_001", 1000);
+        manager.process_timelock_queue(1000);
+
+        assert!(manager.veto_at("PROP_001", "0xdead", 2000).is_err());
+        assert_eq!(manager.veto_at("PROP_001", GUARDIANS[0], 2000), Ok(false));
+        // La misma firma repetida no cuenta dos veces
+        assert_eq!(manager.veto_at("PROP_001", &GUARDIANS[0].to_uppercase(), 2000), Ok(false));
+        assert_eq!(manager.proposals["PROP_001"].status, ProposalStatus::Queued);
+
+        assert_eq!(manager.veto_at("PROP_001", GUARDIANS[1], 2000), Ok(true));
+        assert_eq!(manager.proposals["PROP_001"].status, ProposalStatus::Cancelled);
+        assert!(manager.timelock_queue().is_empty());
+
+        // Ni el timelock ni una ejecución manual la reviven
+        assert!(manager.process_timelock_queue(1000 + TIMELOCK).is_empty());
+        assert!(manager.execute_proposal_at("PROP_001", 1000 + TIMELOCK).is_err());
+        assert!(!manager.proposals["PROP_001"].executed);
+    }
+
+    #[test]
+    fn veto_after_the_eta_is_rejected() {
+        let mut manager = manager();
+        insert_passing_proposal(&mut manager, "PROP_001", 1000);
+        manager.process_timelock_queue(1000);
+
+        assert!(manager.veto_at("PROP_001", GUARDIANS[0], 1000 + TIMELOCK).is_err());
+        assert_eq!(manager.proposals["PROP_001"].status, ProposalStatus::Queued);
+    }
+
+    #[test]
+    fn second_execution_of_the_same_proposal_is_rejected() {
+        let mut manager = manager();
+        insert_passing_proposal(&mut manager, "PROP_001", 1000);
+        manager.process_timelock_queue(1000);
+
+        // El binding usa la hora real, muy posterior al eta
+        manager.execute_proposal("PROP_001").unwrap();
+        assert!(manager.proposals["PROP_001"].executed);
+        assert_eq!(
+            manager.execute_proposal_at("PROP_001", 1000 + 2 * TIMELOCK),
+            Err("La propuesta ya fue ejecutada".to_string()),
+        );
+        assert!(manager.process_timelock_queue(1000 + 2 * TIMELOCK).is_empty());
+    }
+
+    /// Gestor inicializado con los parámetros del marketplace registrados
+    /// como lo hace `BlockchainManager::initialize`
+    fn manager_with_marketplace(marketplace: &MarketplaceManager) -> GovernanceManager {
+        let mut manager = manager();
+        manager.initialize().unwrap();
+        for (key, value, setter) in marketplace.governable_parameters() {
+            manager.register_parameter(&key, value, setter);
+        }
+        manager
+    }
+
+    #[test]
+    fn fee_change_goes_through_vote_timelock_and_execution() {
+        let marketplace = MarketplaceManager::new(&BlockchainConfig::default());
+        let mut manager = manager_with_marketplace(&marketplace);
+        let proposal_id = manager.create_parameter_change_proposal(
+            "Comisión del 1%",
+            "Bajar la comisión del marketplace",
+            FEE_BASIS_POINTS_PARAMETER,
+            json!(100),
+        ).unwrap();
+        let (start, end) = {
+            let proposal = &manager.proposals[&proposal_id];
+            (proposal.start_time, proposal.end_time)
+        };
+
+        let voter = "0xba11e7";
+        let quorum = manager.governance_info.quorum_required;
+        manager.record_stake_change(voter, start - 1, quorum);
+
+        assert!(manager.vote_at(voter, &proposal_id, "for", None, start - 1).is_err());
+        manager.vote_at(voter, &proposal_id, "for", None, start).unwrap();
+        assert!(manager.vote_at(voter, &proposal_id, "for", None, start + 1).is_err());
+
+        // El cierre la encola; el cambio espera al timelock
+        assert!(manager.process_timelock_queue(end).is_empty());
+        assert_eq!(manager.proposals[&proposal_id].status, ProposalStatus::Queued);
+        assert_eq!(marketplace.fee_basis_points(), DEFAULT_FEE_BASIS_POINTS);
+
+        let eta = manager.proposals[&proposal_id].eta.unwrap();
+        assert_eq!(eta, end + TIMELOCK);
+        assert!(manager.execute_parameter_change_at(&proposal_id, eta - 1).is_err());
+        manager.execute_parameter_change_at(&proposal_id, eta).unwrap();
+
+        assert_eq!(marketplace.fee_basis_points(), 100);
+        assert_eq!(manager.parameter_registry().value(FEE_BASIS_POINTS_PARAMETER), Some(&json!(100)));
+        assert_eq!(manager.parameter_events(), &[ParameterUpdated {
+            key: FEE_BASIS_POINTS_PARAMETER.to_string(),
+            old_value: json!(DEFAULT_FEE_BASIS_POINTS),
+            new_value: json!(100),
+            proposal_id: proposal_id.clone(),
+        }]);
+    }
+
+    #[test]
+    fn keys_outside_the_governable_list_are_rejected() {
+        let marketplace = MarketplaceManager::new(&BlockchainConfig::default());
+        let mut manager = manager_with_marketplace(&marketplace);
+        let proposals = manager.proposals.len();
+
+        // Ni registrando un gestor para la clave
+        manager.register_parameter("marketplace.fee_recipient", json!(null), Box::new(|_| {}));
+        let error = manager.create_parameter_change_proposal("", "", "marketplace.fee_recipient", json!("0xbad"))
+            .unwrap_err();
+        assert!(error.contains("no gobernable"), "{}", error);
+        assert!(manager.parameter_registry.apply("marketplace.fee_recipient", &json!("0xbad")).is_err());
+
+        // Clave gobernable con valor fuera de rango o sin gestor registrado
+        assert!(manager.create_parameter_change_proposal("", "", FEE_BASIS_POINTS_PARAMETER, json!(10_001)).is_err());
+        assert!(manager.create_parameter_change_proposal("", "", "staking.reward_rate", json!(0.05)).is_err());
+
+        assert_eq!(manager.proposals.len(), proposals);
+        assert!(manager.parameter_events().is_empty());
+        assert_eq!(marketplace.fee_basis_points(), DEFAULT_FEE_BASIS_POINTS);
+    }
+
+    #[test]
+    fn randomness_requests_are_fulfilled_once_with_a_valid_proof() {
+        let mut manager = manager();
+        let public_key = manager.set_vrf_secret_key(vec![7; 32]).unwrap();
+        let fulfilled = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = std::rc::Rc::clone(&fulfilled);
+        manager.set_randomness_callback(Box::new(move |request_id, output| sink.borrow_mut().push((request_id, output))));
+
+        let seed = [42; 32];
+        let proof = manager.request_randomness(seed).unwrap();
+        assert_eq!(GovernanceManager::verify_randomness(&seed, &proof, &public_key), Some(proof.output.0));
+
+        // Una prueba alterada no cumple la petición, que sigue pendiente
+        let mut tampered = proof.clone();
+        tampered.proof[40] ^= 1;
+        assert!(manager.fulfill_randomness(&tampered).is_err());
+        assert_eq!(manager.pending_randomness_requests().count(), 1);
+
+        assert_eq!(manager.fulfill_randomness(&proof), Ok(proof.output.0));
+        assert_eq!(manager.randomness_output(proof.nonce), Some(proof.output.0));
+        assert_eq!(*fulfilled.borrow(), vec![(proof.nonce, proof.output.0)]);
+
+        assert_eq!(
+            manager.fulfill_randomness(&proof),
+            Err("La petición de aleatoriedad ya fue cumplida".to_string()),
+        );
+        assert_eq!(fulfilled.borrow().len(), 1);
+    }
+
+    #[test]
+    fn proof_for_another_request_is_rejected() {
+        let mut manager = manager();
+        manager.set_vrf_secret_key(vec![7; 32]).unwrap();
+        let first = manager.request_randomness([1; 32]).unwrap();
+        let second = manager.request_randomness([2; 32]).unwrap();
+
+        // La prueba de la primera con el nonce de la segunda
+        let swapped = VRFProof { nonce: second.nonce, ..first.clone() };
+        assert!(manager.fulfill_randomness(&swapped).is_err());
+        // Ni la de la segunda si el contrato cambió de clave
+        manager.set_vrf_secret_key(vec![8; 32]).unwrap();
+        assert!(manager.fulfill_randomness(&second).is_err());
+        assert_eq!(manager.pending_randomness_requests().count(), 2);
+    }
+
+    #[test]
+    fn long_time_stakers_outweigh_a_late_whale() {
+        const DAY: u64 = 86400;
+        let end = 7 * DAY;
+        let outcome = |mechanism: VotingMechanism| {
+            let mut manager = manager();
+            manager.set_governance_config(GovernanceConfig {
+                voting_mechanism: mechanism,
+                ..manager.config().clone()
+            });
+            insert_passing_proposal(&mut manager, &proposal_key(1), end);
+            let proposal = manager.proposals.get_mut(&proposal_key(1)).unwrap();
+            (proposal.total_votes, proposal.votes_for) = (0, 0);
+
+            // Dos cuentas con stake desde la creación y una ballena que
+            // compra 5 veces su stake una hora antes del cierre. Entre las
+            // tres superan el quorum
+            let unit = manager.governance_info.quorum_required / 100;
+            manager.record_stake_change("0xa11ce", 0, 100 * unit);
+            manager.record_stake_change("0xb0b", 0, 100 * unit);
+            manager.record_stake_change("0x3a1e", end - 3600, 1000 * unit);
+            for (voter, vote) in [("0xa11ce", "for"), ("0xb0b", "for"), ("0x3a1e", "against")] {
+                manager.vote_at(voter, &proposal_key(1), vote, None, end - 1).unwrap();
+            }
+            manager.process_timelock_queue(end);
+            (manager, unit as f64)
+        };
+
+        let (conviction, unit) = outcome(VotingMechanism::Conviction);
+        let alice = conviction.get_conviction_score("0xa11ce", 1) / unit;
+        let whale = conviction.get_conviction_score("0x3a1e", 1) / unit;
+        assert!((alice - 100.0 * (1.0 - 0.5f64.powi(7))).abs() < 1e-6, "{}", alice);
+        assert!(whale < 30.0, "{}", whale);
+        assert_eq!(conviction.proposals[&proposal_key(1)].status, ProposalStatus::Queued);
+
+        // Con snapshot manda el stake del momento del voto
+        let (snapshot, _) = outcome(VotingMechanism::Snapshot);
+        let proposal = &snapshot.proposals[&proposal_key(1)];
+        assert!(proposal.total_votes >= snapshot.governance_info.quorum_required);
+        assert_eq!(proposal.status, ProposalStatus::Defeated);
+    }
+}
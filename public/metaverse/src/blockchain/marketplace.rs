@@ -1,10 +1,15 @@
 //! Gestor de Marketplace para Metaverso
 //! Maneja compra, venta y subastas de NFTs y tokens
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU16, Ordering};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+use super::governance::ParameterSetter;
+use super::tokens::TokenManager;
+
 /// Listado en el marketplace
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarketplaceListing {
@@ -103,6 +108,323 @@ pub struct MarketplaceTransaction {
     pub island: String,
 }
 
+/// Identificador de orden del libro de órdenes
+pub type OrderId = u64;
+
+/// Lado de una orden
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderSide {
+    Bid,
+    Ask,
+}
+
+/// Orden límite en el libro de órdenes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+    pub id: OrderId,
+    pub owner: String,
+    pub asset_id: String,
+    pub side: OrderSide,
+    pub price: u64,
+    pub quantity: u64,
+    pub remaining_quantity: u64,
+    pub timestamp: u64,
+}
+
+/// Evento emitido por el motor de emparejamiento
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeExecuted {
+    pub buy_order_id: OrderId,
+    pub sell_order_id: OrderId,
+    pub price: u64,
+    pub quantity: u64,
+    pub timestamp: u64,
+}
+
+/// Libro de órdenes con prioridad precio-tiempo
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrderBook {
+    pub bids: BTreeMap<u64, VecDeque<Order>>,
+    pub asks: BTreeMap<u64, VecDeque<Order>>,
+    locations: HashMap<OrderId, (OrderSide, u64)>,
+}
+
+impl OrderBook {
+    /// Crear libro de órdenes vacío
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insertar una orden de compra y ejecutar el emparejamiento
+    pub fn place_bid(&mut self, order: Order) -> Result<Vec<TradeExecuted>, String> {
+        self.place(order, OrderSide::Bid)
+    }
+
+    /// Insertar una orden de venta y ejecutar el emparejamiento
+    pub fn place_ask(&mut self, order: Order) -> Result<Vec<TradeExecuted>, String> {
+        self.place(order, OrderSide::Ask)
+    }
+
+    /// Cancelar una orden en reposo
+    pub fn cancel_order(&mut self, order_id: OrderId) -> Result<Order, String> {
+        let (side, price) = self.locations.remove(&order_id)
+            .ok_or_else(|| "Orden no encontrada".to_string())?;
+
+        let levels = match side {
+            OrderSide::Bid => &mut self.bids,
+            OrderSide::Ask => &mut self.asks,
+        };
+        let level = levels.get_mut(&price)
+            .ok_or_else(|| "Nivel de precio inconsistente".to_string())?;
+        let position = level.iter().position(|o| o.id == order_id)
+            .ok_or_else(|| "Orden inconsistente en el nivel de precio".to_string())?;
+        let order = level.remove(position).expect("posición válida");
+        if level.is_empty() {
+            levels.remove(&price);
+        }
+
+        Ok(order)
+    }
+
+    /// Mejor precio de compra
+    pub fn best_bid(&self) -> Option<u64> {
+        self.bids.keys().next_back().copied()
+    }
+
+    /// Mejor precio de venta
+    pub fn best_ask(&self) -> Option<u64> {
+        self.asks.keys().next().copied()
+    }
+
+    /// Obtener una orden en reposo
+    pub fn get_order(&self, order_id: OrderId) -> Option<&Order> {
+        let (side, price) = self.locations.get(&order_id)?;
+        let levels = match side {
+            OrderSide::Bid => &self.bids,
+            OrderSide::Ask => &self.asks,
+        };
+        levels.get(price)?.iter().find(|o| o.id == order_id)
+    }
+
+    /// Verificar los invariantes del libro: sin cruce, sin niveles vacíos,
+    /// índice coherente y colas ordenadas por tiempo de llegada
+    pub fn check_invariants(&self) -> Result<(), String> {
+        if let (Some(bid), Some(ask)) = (self.best_bid(), self.best_ask()) {
+            if bid >= ask {
+                return Err(format!("Libro cruzado: compra {} >= venta {}", bid, ask));
+            }
+        }
+
+        let mut resting = 0;
+        for (side, levels) in [(OrderSide::Bid, &self.bids), (OrderSide::Ask, &self.asks)] {
+            for (price, level) in levels {
+                if level.is_empty() {
+                    return Err(format!("Nivel vacío en precio {}", price));
+                }
+                let mut last_id = 0;
+                for order in level {
+                    if order.side != side || order.price != *price {
+                        return Err(format!("Orden {} en nivel incorrecto", order.id));
+                    }
+                    if order.remaining_quantity == 0 || order.remaining_quantity > order.quantity {
+                        return Err(format!("Cantidad restante inválida en orden {}", order.id));
+                    }
+                    if order.id <= last_id {
+                        return Err(format!("Prioridad temporal rota en precio {}", price));
+                    }
+                    if self.locations.get(&order.id) != Some(&(side, *price)) {
+                        return Err(format!("Índice inconsistente para orden {}", order.id));
+                    }
+                    last_id = order.id;
+                    resting += 1;
+                }
+            }
+        }
+
+        if resting != self.locations.len() {
+            return Err("El índice contiene órdenes inexistentes".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Emparejar e insertar. Primero se calcula el plan completo de ejecuciones
+    /// sin modificar el libro y sólo después se aplica, de modo que un error
+    /// deja el libro intacto. Una orden que se cruzaría con otra del mismo
+    /// propietario se rechaza entera.
+    fn place(&mut self, mut order: Order, side: OrderSide) -> Result<Vec<TradeExecuted>, String> {
+        if order.quantity == 0 {
+            return Err("La cantidad debe ser mayor que cero".to_string());
+        }
+        if order.price == 0 {
+            return Err("El precio debe ser mayor que cero".to_string());
+        }
+        if self.locations.contains_key(&order.id) {
+            return Err("Identificador de orden duplicado".to_string());
+        }
+
+        order.side = side;
+        order.remaining_quantity = order.quantity;
+
+        // Calcular ejecuciones: (precio del nivel, cantidad) en prioridad precio-tiempo
+        let mut fills: Vec<(u64, u64)> = Vec::new();
+        let mut remaining = order.quantity;
+        {
+            let crossing: Box<dyn Iterator<Item = (&u64, &VecDeque<Order>)>> = match side {
+                OrderSide::Bid => Box::new(self.asks.range(..=order.price)),
+                OrderSide::Ask => Box::new(self.bids.range(order.price..).rev()),
+            };
+            'levels: for (price, level) in crossing {
+                for resting in level {
+                    if remaining == 0 {
+                        break 'levels;
+                    }
+                    if resting.owner == order.owner {
+                        return Err("La orden se cruzaría con una orden propia".to_string());
+                    }
+                    let quantity = remaining.min(resting.remaining_quantity);
+                    fills.push((*price, quantity));
+                    remaining -= quantity;
+                }
+            }
+        }
+
+        // Aplicar el plan
+        let opposite = match side {
+            OrderSide::Bid => &mut self.asks,
+            OrderSide::Ask => &mut self.bids,
+        };
+        let mut trades = Vec::with_capacity(fills.len());
+        for (price, quantity) in fills {
+            let level = opposite.get_mut(&price).expect("nivel calculado en el plan");
+            let resting = level.front_mut().expect("orden calculada en el plan");
+            resting.remaining_quantity -= quantity;
+            order.remaining_quantity -= quantity;
+
+            let (buy_order_id, sell_order_id) = match side {
+                OrderSide::Bid => (order.id, resting.id),
+                OrderSide::Ask => (resting.id, order.id),
+            };
+            trades.push(TradeExecuted {
+                buy_order_id,
+                sell_order_id,
+                price,
+                quantity,
+                timestamp: order.timestamp,
+            });
+
+            if resting.remaining_quantity == 0 {
+                let filled = level.pop_front().expect("orden calculada en el plan");
+                self.locations.remove(&filled.id);
+                if level.is_empty() {
+                    opposite.remove(&price);
+                }
+            }
+        }
+
+        // El resto queda en reposo en el libro
+        if order.remaining_quantity > 0 {
+            self.locations.insert(order.id, (side, order.price));
+            let levels = match side {
+                OrderSide::Bid => &mut self.bids,
+                OrderSide::Ask => &mut self.asks,
+            };
+            levels.entry(order.price).or_insert_with(VecDeque::new).push_back(order);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Base de las comisiones en puntos básicos (100% = 10000)
+pub const ROYALTY_BASIS_POINTS: u16 = 10_000;
+
+/// Parámetro de governance con la comisión del marketplace
+pub const FEE_BASIS_POINTS_PARAMETER: &str = "marketplace.fee_basis_points";
+
+/// Comisión del marketplace por defecto (2,5%)
+pub const DEFAULT_FEE_BASIS_POINTS: u16 = 250;
+
+/// `amount * basis_points / 10000` redondeado hacia abajo, sin desbordar
+pub fn basis_points_of(amount: u128, basis_points: u16) -> u128 {
+    let bps = basis_points.min(ROYALTY_BASIS_POINTS) as u128;
+    let base = ROYALTY_BASIS_POINTS as u128;
+    amount / base * bps + amount % base * bps / base
+}
+
+/// Clave del contrato de NFTs en `NetworkConfig::contracts`
+pub const NFT_CONTRACT_KEY: &str = "nft";
+
+/// Selector de `royaltyInfo(uint256,uint256)` (EIP-2981)
+pub const ROYALTY_INFO_SELECTOR: [u8; 4] = [0x2a, 0x55, 0x20, 0x5a];
+
+/// Royalty de creador de un NFT
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoyaltyInfo {
+    pub recipient: String,
+    pub fee_basis_points: u16,
+}
+
+impl RoyaltyInfo {
+    /// Royalty de una venta: `price * fee_basis_points / 10000` redondeado
+    /// hacia abajo, sin desbordar para ningún precio
+    pub fn royalty_amount(&self, price: u128) -> u128 {
+        basis_points_of(price, self.fee_basis_points)
+    }
+}
+
+/// Reparto de una venta entre creador, marketplace y vendedor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaleSettlement {
+    pub nft_id: String,
+    pub seller: String,
+    pub price: String,
+    pub royalty_recipient: Option<String>,
+    pub royalty_amount: String,
+    #[serde(default)]
+    pub fee_recipient: Option<String>,
+    #[serde(default)]
+    pub fee_amount: String,
+    pub seller_amount: String,
+    pub transaction_hash: String,
+}
+
+/// Lectura on-chain de `royaltyInfo`: recibe el contrato y el calldata y
+/// devuelve el resultado ABI crudo (None si la llamada falla)
+pub trait RoyaltyInfoReader {
+    fn call(&self, contract: &str, calldata: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Calldata de `royaltyInfo(tokenId, salePrice)`
+pub fn encode_royalty_info_call(token_id: u128, sale_price: u128) -> Vec<u8> {
+    let mut calldata = Vec::with_capacity(4 + 64);
+    calldata.extend_from_slice(&ROYALTY_INFO_SELECTOR);
+    for value in [token_id, sale_price] {
+        calldata.extend_from_slice(&[0u8; 16]);
+        calldata.extend_from_slice(&value.to_be_bytes());
+    }
+    calldata
+}
+
+/// Decodificar `(address receiver, uint256 royaltyAmount)`. Se rechaza un
+/// importe mayor que el precio de venta
+pub fn decode_royalty_info_result(data: &[u8], sale_price: u128) -> Result<(String, u128), String> {
+    if data.len() < 64 {
+        return Err("Resultado de royaltyInfo demasiado corto".to_string());
+    }
+    if data[..12].iter().any(|&b| b != 0) || data[32..48].iter().any(|&b| b != 0) {
+        return Err("Resultado de royaltyInfo mal formado".to_string());
+    }
+    let recipient = format!("0x{}", hex::encode(&data[12..32]));
+    let mut amount = [0u8; 16];
+    amount.copy_from_slice(&data[48..64]);
+    let amount = u128::from_be_bytes(amount);
+    if amount > sale_price {
+        return Err("Royalty on-chain mayor que el precio de venta".to_string());
+    }
+    Ok((recipient, amount))
+}
+
 /// Gestor de Marketplace
 #[wasm_bindgen]
 pub struct MarketplaceManager {
@@ -112,6 +434,17 @@ pub struct MarketplaceManager {
     transactions: Vec<MarketplaceTransaction>,
     user_listings: HashMap<String, Vec<String>>,
     user_bids: HashMap<String, Vec<String>>,
+    order_books: HashMap<String, OrderBook>,
+    order_assets: HashMap<OrderId, String>,
+    trade_events: Vec<TradeExecuted>,
+    next_order_id: OrderId,
+    royalty_registry: HashMap<String, RoyaltyInfo>,
+    royalty_reader: Option<Box<dyn RoyaltyInfoReader>>,
+    nft_contracts: HashMap<String, String>,
+    /// Comisión del marketplace, compartida con el setter de governance
+    fee_basis_points: Arc<AtomicU16>,
+    /// Cuenta que cobra la comisión (sin ella no se cobra)
+    fee_recipient: Option<String>,
     current_network: String,
     is_initialized: bool,
 }
@@ -127,6 +460,15 @@ impl MarketplaceManager {
             transactions: Vec::new(),
             user_listings: HashMap::new(),
             user_bids: HashMap::new(),
+            order_books: HashMap::new(),
+            order_assets: HashMap::new(),
+            trade_events: Vec::new(),
+            next_order_id: 1,
+            royalty_registry: HashMap::new(),
+            royalty_reader: None,
+            nft_contracts: Self::nft_contracts_of(config),
+            fee_basis_points: Arc::new(AtomicU16::new(DEFAULT_FEE_BASIS_POINTS)),
+            fee_recipient: None,
             current_network: config.default_network.clone(),
             is_initialized: false,
         }
@@ -599,6 +941,128 @@ impl MarketplaceManager {
         Ok(auction.highest_bidder.clone())
     }
 
+    /// Colocar orden de compra de `owner` en el libro de órdenes del activo.
+    /// (`place_bid` queda reservado para las pujas de subasta.)
+    pub fn place_bid_order(&mut self, owner: &str, asset_id: &str, price: u64, quantity: u64) -> Result<OrderId, JsValue> {
+        self.place_order(owner, asset_id, OrderSide::Bid, price, quantity)
+    }
+
+    /// Colocar orden de venta de `owner` en el libro de órdenes del activo
+    pub fn place_ask_order(&mut self, owner: &str, asset_id: &str, price: u64, quantity: u64) -> Result<OrderId, JsValue> {
+        self.place_order(owner, asset_id, OrderSide::Ask, price, quantity)
+    }
+
+    /// Cancelar orden del libro (solo su propietario)
+    pub fn cancel_order(&mut self, caller: &str, order_id: OrderId) -> Result<(), JsValue> {
+        let asset_id = self.order_assets.get(&order_id)
+            .ok_or_else(|| JsValue::from_str("Orden no encontrada"))?;
+        let book = self.order_books.get_mut(asset_id)
+            .ok_or_else(|| JsValue::from_str("Libro de órdenes no encontrado"))?;
+
+        let owner = book.get_order(order_id).map(|order| order.owner.as_str());
+        if !owner.is_some_and(|owner| owner.eq_ignore_ascii_case(caller)) {
+            return Err(JsValue::from_str("Solo el propietario puede cancelar la orden"));
+        }
+
+        book.cancel_order(order_id).map_err(|e| JsValue::from_str(&e))?;
+        self.order_assets.remove(&order_id);
+
+        Ok(())
+    }
+
+    /// Obtener libro de órdenes de un activo
+    pub fn get_order_book(&self, asset_id: &str) -> JsValue {
+        match self.order_books.get(asset_id) {
+            Some(book) => serde_wasm_bindgen::to_value(book).unwrap_or_default(),
+            None => JsValue::NULL,
+        }
+    }
+
+    /// Obtener eventos de ejecución de operaciones
+    pub fn get_trade_events(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.trade_events).unwrap_or_default()
+    }
+
+    /// Colocar orden y ejecutar el motor de emparejamiento
+    fn place_order(&mut self, owner: &str, asset_id: &str, side: OrderSide, price: u64, quantity: u64) -> Result<OrderId, JsValue> {
+        if owner.is_empty() {
+            return Err(JsValue::from_str("La orden necesita un propietario"));
+        }
+        // Las direcciones se comparan sin distinguir mayúsculas
+        let owner = owner.to_lowercase();
+        let current_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let order_id = self.next_order_id;
+        let order = Order {
+            id: order_id,
+            owner: owner.clone(),
+            asset_id: asset_id.to_string(),
+            side,
+            price,
+            quantity,
+            remaining_quantity: quantity,
+            timestamp: current_time,
+        };
+
+        let book = self.order_books.entry(asset_id.to_string()).or_insert_with(OrderBook::new);
+
+        // Propietarios de las órdenes en reposo antes de emparejar
+        let crossing = match side {
+            OrderSide::Bid => book.asks.range(..=price),
+            OrderSide::Ask => book.bids.range(price..),
+        };
+        let counterparties: HashMap<OrderId, String> = crossing
+            .flat_map(|(_, level)| level.iter())
+            .map(|o| (o.id, o.owner.clone()))
+            .collect();
+
+        let trades = match side {
+            OrderSide::Bid => book.place_bid(order),
+            OrderSide::Ask => book.place_ask(order),
+        }
+        .map_err(|e| JsValue::from_str(&e))?;
+
+        self.next_order_id += 1;
+        if book.get_order(order_id).is_some() {
+            self.order_assets.insert(order_id, asset_id.to_string());
+        }
+
+        for trade in &trades {
+            let (maker_id, buyer, seller) = match side {
+                OrderSide::Bid => (trade.sell_order_id, owner.to_string(), counterparties[&trade.sell_order_id].clone()),
+                OrderSide::Ask => (trade.buy_order_id, counterparties[&trade.buy_order_id].clone(), owner.to_string()),
+            };
+            if book.get_order(maker_id).is_none() {
+                self.order_assets.remove(&maker_id);
+            }
+
+            // Persistir en el historial de transacciones del marketplace
+            let total_price = trade.price as u128 * trade.quantity as u128;
+            self.transactions.push(MarketplaceTransaction {
+                id: format!("TX_{}", self.transactions.len() + 1),
+                listing_id: format!("ORDER_{}_{}", trade.buy_order_id, trade.sell_order_id),
+                buyer,
+                seller,
+                item_type: ItemType::Token,
+                item_id: asset_id.to_string(),
+                price: total_price.to_string(),
+                price_usd: total_price as f64 * 3000.0 / 1e18,
+                currency: "ETH".to_string(),
+                quantity: trade.quantity,
+                transaction_hash: format!("0x{}", hex::encode(&[0u8; 32])),
+                timestamp: trade.timestamp,
+                island: String::new(),
+            });
+        }
+
+        self.trade_events.extend(trades);
+
+        Ok(order_id)
+    }
+
     /// Obtener listados
     pub fn get_listings(&self) -> JsValue {
         let listing_list: Vec<&MarketplaceListing> = self.listings.values().collect();
@@ -686,6 +1150,7 @@ impl MarketplaceManager {
     /// Actualizar configuración
     pub fn update_config(&mut self, config: &crate::blockchain::BlockchainConfig) -> Result<(), JsValue> {
         self.current_network = config.default_network.clone();
+        self.nft_contracts = Self::nft_contracts_of(config);
         Ok(())
     }
 
@@ -693,4 +1158,523 @@ impl MarketplaceManager {
     pub fn is_initialized(&self) -> bool {
         self.is_initialized
     }
-} 
\ No newline at end of file
+}
+
+impl MarketplaceManager {
+    /// Contrato de NFTs de cada red
+    fn nft_contracts_of(config: &crate::blockchain::BlockchainConfig) -> HashMap<String, String> {
+        config.networks.iter()
+            .filter_map(|(name, network)| {
+                network.contracts.get(NFT_CONTRACT_KEY).map(|address| (name.clone(), address.clone()))
+            })
+            .collect()
+    }
+
+    /// Registrar la royalty de un NFT. Solo puede hacerlo el contrato de NFTs
+    /// de la red actual
+    pub fn register_royalty(&mut self, caller: &str, nft_id: &str, recipient: &str, fee_basis_points: u16) -> Result<(), String> {
+        let nft_contract = self.nft_contracts.get(&self.current_network)
+            .ok_or_else(|| "Contrato de NFTs no configurado en la red actual".to_string())?;
+        if !nft_contract.eq_ignore_ascii_case(caller) {
+            return Err("Solo el contrato de NFTs puede registrar royalties".to_string());
+        }
+        if fee_basis_points > ROYALTY_BASIS_POINTS {
+            return Err("La royalty no puede superar el 100%".to_string());
+        }
+        if recipient.is_empty() {
+            return Err("Destinatario de la royalty vacío".to_string());
+        }
+
+        self.royalty_registry.insert(nft_id.to_string(), RoyaltyInfo {
+            recipient: recipient.to_string(),
+            fee_basis_points,
+        });
+        Ok(())
+    }
+
+    /// Comisión del marketplace en puntos básicos
+    pub fn fee_basis_points(&self) -> u16 {
+        self.fee_basis_points.load(Ordering::Relaxed)
+    }
+
+    /// Cuenta que cobra la comisión del marketplace
+    pub fn set_fee_recipient(&mut self, recipient: Option<String>) {
+        self.fee_recipient = recipient;
+    }
+
+    /// Parámetros que governance puede cambiar, con su valor actual y el
+    /// setter que los aplica
+    pub fn governable_parameters(&self) -> Vec<(String, serde_json::Value, ParameterSetter)> {
+        let fee = Arc::clone(&self.fee_basis_points);
+        let setter: ParameterSetter = Box::new(move |value| {
+            if let Some(bps) = value.as_u64() {
+                fee.store(bps.min(ROYALTY_BASIS_POINTS as u64) as u16, Ordering::Relaxed);
+            }
+        });
+        vec![(FEE_BASIS_POINTS_PARAMETER.to_string(), serde_json::json!(self.fee_basis_points()), setter)]
+    }
+
+    /// Royalty registrada de un NFT
+    pub fn royalty_info(&self, nft_id: &str) -> Option<&RoyaltyInfo> {
+        self.royalty_registry.get(nft_id)
+    }
+
+    /// Establecer el lector on-chain de `royaltyInfo`
+    pub fn set_royalty_reader(&mut self, reader: Box<dyn RoyaltyInfoReader>) {
+        self.royalty_reader = Some(reader);
+    }
+
+    /// Destinatario e importe de la royalty de una venta: primero el registro
+    /// local y, si no hay entrada, `royaltyInfo` del contrato de NFTs
+    pub fn royalty_for_sale(&self, nft_id: &str, price: u128) -> Result<Option<(String, u128)>, String> {
+        if let Some(info) = self.royalty_registry.get(nft_id) {
+            return Ok(Some((info.recipient.clone(), info.royalty_amount(price))));
+        }
+
+        let (Some(reader), Some(contract)) = (&self.royalty_reader, self.nft_contracts.get(&self.current_network)) else {
+            return Ok(None);
+        };
+        // El tokenId on-chain es el ID del NFT en decimal
+        let Ok(token_id) = nft_id.parse::<u128>() else {
+            return Ok(None);
+        };
+        match reader.call(contract, &encode_royalty_info_call(token_id, price)) {
+            Some(result) => decode_royalty_info_result(&result, price).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Ejecutar la venta de un NFT listado: `buyer` paga la royalty al
+    /// creador, la comisión al marketplace (si tiene cuenta de cobro) y el
+    /// resto al vendedor, en la moneda del listado
+    pub fn execute_sale(&mut self, buyer: &str, nft_id: &str, price: &str, tokens: &mut TokenManager) -> Result<SaleSettlement, String> {
+        let price = price.parse::<u128>()
+            .map_err(|_| "Error al parsear precio".to_string())?;
+        let listing_id = self.listings.values()
+            .find(|l| l.is_active && l.available_quantity > 0 && matches!(l.item_type, ItemType::NFT) && l.item_id == nft_id)
+            .map(|l| l.id.clone())
+            .ok_or_else(|| "NFT no listado".to_string())?;
+        let (seller, currency) = {
+            let listing = &self.listings[&listing_id];
+            (listing.seller.clone(), listing.currency.clone())
+        };
+
+        let royalty = self.royalty_for_sale(nft_id, price)?;
+        let royalty_amount = royalty.as_ref().map_or(0, |(_, amount)| *amount);
+        let fee_amount = if self.fee_recipient.is_some() { basis_points_of(price, self.fee_basis_points()) } else { 0 };
+        let seller_amount = royalty_amount.checked_add(fee_amount)
+            .and_then(|deductions| price.checked_sub(deductions))
+            .ok_or_else(|| "La royalty y la comisión superan el precio".to_string())?;
+
+        // Comprobar el saldo antes de mover nada para no dejar pagos a medias
+        if tokens.balance_of(&currency).unwrap_or(0) < price {
+            return Err("Saldo insuficiente".to_string());
+        }
+        if let Some((recipient, amount)) = royalty.as_ref().filter(|(_, amount)| *amount > 0) {
+            tokens.transfer_tokens(recipient, &currency, &amount.to_string())
+                .map_err(|e| e.as_string().unwrap_or_else(|| "Error al pagar la royalty".to_string()))?;
+        }
+        if let Some(recipient) = self.fee_recipient.as_ref().filter(|_| fee_amount > 0) {
+            tokens.transfer_tokens(recipient, &currency, &fee_amount.to_string())
+                .map_err(|e| e.as_string().unwrap_or_else(|| "Error al pagar la comisión".to_string()))?;
+        }
+        let tx_hash = tokens.transfer_tokens(&seller, &currency, &seller_amount.to_string())
+            .map_err(|e| e.as_string().unwrap_or_else(|| "Error al pagar al vendedor".to_string()))?;
+
+        let current_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let listing = self.listings.get_mut(&listing_id).expect("listado encontrado");
+        listing.available_quantity -= 1;
+        if listing.available_quantity == 0 {
+            listing.is_active = false;
+        }
+        let transaction = MarketplaceTransaction {
+            id: format!("TX_{}", self.transactions.len() + 1),
+            listing_id,
+            buyer: buyer.to_string(),
+            seller: seller.clone(),
+            item_type: ItemType::NFT,
+            item_id: nft_id.to_string(),
+            price: price.to_string(),
+            price_usd: listing.price_usd,
+            currency,
+            quantity: 1,
+            transaction_hash: tx_hash.clone(),
+            timestamp: current_time,
+            island: listing.island.clone(),
+        };
+        self.transactions.push(transaction);
+
+        Ok(SaleSettlement {
+            nft_id: nft_id.to_string(),
+            seller,
+            price: price.to_string(),
+            royalty_recipient: royalty.map(|(recipient, _)| recipient),
+            royalty_amount: royalty_amount.to_string(),
+            fee_recipient: self.fee_recipient.clone().filter(|_| fee_amount > 0),
+            fee_amount: fee_amount.to_string(),
+            seller_amount: seller_amount.to_string(),
+            transaction_hash: tx_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::BlockchainConfig;
+    use proptest::prelude::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Operación aleatoria sobre el libro
+    #[derive(Debug, Clone)]
+    enum Op {
+        Place { side: OrderSide, owner: u8, price: u64, quantity: u64 },
+        Cancel { index: usize },
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            3 => (any::<bool>(), 0u8..4, 1u64..20, 1u64..10).prop_map(|(bid, owner, price, quantity)| Op::Place {
+                side: if bid { OrderSide::Bid } else { OrderSide::Ask },
+                owner,
+                price,
+                quantity,
+            }),
+            1 => any::<usize>().prop_map(|index| Op::Cancel { index }),
+        ]
+    }
+
+    fn order(id: OrderId, owner: u8, price: u64, quantity: u64) -> Order {
+        Order {
+            id,
+            owner: format!("0x{:040x}", owner),
+            asset_id: "WCV".to_string(),
+            side: OrderSide::Bid,
+            price,
+            quantity,
+            remaining_quantity: quantity,
+            timestamp: id,
+        }
+    }
+
+    /// Modelo de referencia: órdenes en reposo por orden de llegada y
+    /// emparejamiento por barrido lineal en prioridad precio-tiempo
+    #[derive(Default)]
+    struct Model {
+        resting: Vec<Order>,
+    }
+
+    impl Model {
+        fn place(&mut self, mut incoming: Order, side: OrderSide) -> Result<Vec<TradeExecuted>, String> {
+            incoming.side = side;
+            let mut candidates: Vec<usize> = (0..self.resting.len())
+                .filter(|&i| {
+                    let resting = &self.resting[i];
+                    match side {
+                        OrderSide::Bid => resting.side == OrderSide::Ask && resting.price <= incoming.price,
+                        OrderSide::Ask => resting.side == OrderSide::Bid && resting.price >= incoming.price,
+                    }
+                })
+                .collect();
+            candidates.sort_by_key(|&i| {
+                let resting = &self.resting[i];
+                let price_rank = match side {
+                    OrderSide::Bid => resting.price,
+                    OrderSide::Ask => u64::MAX - resting.price,
+                };
+                (price_rank, resting.id)
+            });
+
+            let mut remaining = incoming.quantity;
+            let mut fills = Vec::new();
+            for i in candidates {
+                if remaining == 0 {
+                    break;
+                }
+                if self.resting[i].owner == incoming.owner {
+                    return Err("propia".to_string());
+                }
+                let quantity = remaining.min(self.resting[i].remaining_quantity);
+                fills.push((i, quantity));
+                remaining -= quantity;
+            }
+
+            let mut trades = Vec::new();
+            for (i, quantity) in fills {
+                let resting = &mut self.resting[i];
+                resting.remaining_quantity -= quantity;
+                let (buy_order_id, sell_order_id) = match side {
+                    OrderSide::Bid => (incoming.id, resting.id),
+                    OrderSide::Ask => (resting.id, incoming.id),
+                };
+                trades.push(TradeExecuted {
+                    buy_order_id,
+                    sell_order_id,
+                    price: resting.price,
+                    quantity,
+                    timestamp: incoming.timestamp,
+                });
+            }
+            self.resting.retain(|o| o.remaining_quantity > 0);
+            if remaining > 0 {
+                incoming.remaining_quantity = remaining;
+                self.resting.push(incoming);
+            }
+            Ok(trades)
+        }
+    }
+
+    fn summary(trades: &[TradeExecuted]) -> Vec<(OrderId, OrderId, u64, u64)> {
+        trades.iter().map(|t| (t.buy_order_id, t.sell_order_id, t.price, t.quantity)).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn random_orders_keep_invariants_and_price_time_priority(ops in prop::collection::vec(op(), 1..200)) {
+            let mut book = OrderBook::new();
+            let mut model = Model::default();
+            let mut next_id = 1;
+
+            for op in ops {
+                match op {
+                    Op::Place { side, owner, price, quantity } => {
+                        let incoming = order(next_id, owner, price, quantity);
+                        next_id += 1;
+                        let before = book.clone();
+                        let result = match side {
+                            OrderSide::Bid => book.place_bid(incoming.clone()),
+                            OrderSide::Ask => book.place_ask(incoming.clone()),
+                        };
+                        let expected = model.place(incoming, side);
+                        match (result, expected) {
+                            (Ok(trades), Ok(expected)) => {
+                                prop_assert_eq!(summary(&trades), summary(&expected));
+                            }
+                            (Err(_), Err(_)) => {
+                                // Una orden rechazada no toca el libro
+                                prop_assert_eq!(
+                                    serde_json::to_value(&book).unwrap(),
+                                    serde_json::to_value(&before).unwrap()
+                                );
+                            }
+                            (result, expected) => {
+                                prop_assert!(false, "libro {:?} y modelo {:?} difieren", result.is_ok(), expected.is_ok());
+                            }
+                        }
+                    }
+                    Op::Cancel { index } => {
+                        if model.resting.is_empty() {
+                            prop_assert!(book.cancel_order(next_id).is_err());
+                            continue;
+                        }
+                        let id = model.resting.remove(index % model.resting.len()).id;
+                        prop_assert!(book.cancel_order(id).is_ok());
+                        prop_assert!(book.cancel_order(id).is_err());
+                    }
+                }
+
+                prop_assert_eq!(book.check_invariants(), Ok(()));
+                for resting in &model.resting {
+                    let order = book.get_order(resting.id);
+                    prop_assert_eq!(order.map(|o| o.remaining_quantity), Some(resting.remaining_quantity));
+                }
+                prop_assert_eq!(book.locations.len(), model.resting.len());
+            }
+        }
+    }
+
+    #[test]
+    fn partial_fills_follow_price_then_time() {
+        let mut book = OrderBook::new();
+        book.place_ask(order(1, 1, 10, 5)).unwrap();
+        book.place_ask(order(2, 2, 9, 3)).unwrap();
+        book.place_ask(order(3, 3, 10, 4)).unwrap();
+
+        let trades = book.place_bid(order(4, 0, 10, 10)).unwrap();
+        assert_eq!(summary(&trades), vec![(4, 2, 9, 3), (4, 1, 10, 5), (4, 3, 10, 2)]);
+        assert_eq!(book.get_order(3).map(|o| o.remaining_quantity), Some(2));
+        assert_eq!(book.best_ask(), Some(10));
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn self_trade_is_rejected_without_touching_the_book() {
+        let mut book = OrderBook::new();
+        book.place_ask(order(1, 2, 9, 3)).unwrap();
+        book.place_ask(order(2, 1, 10, 5)).unwrap();
+
+        assert!(book.place_bid(order(3, 1, 10, 6)).is_err());
+        assert_eq!(book.get_order(1).map(|o| o.remaining_quantity), Some(3));
+        assert!(book.get_order(3).is_none());
+        assert_eq!(book.check_invariants(), Ok(()));
+    }
+
+    const NFT_CONTRACT: &str = "0x00000000000000000000000000000000000000c7";
+    const CREATOR: &str = "0x00000000000000000000000000000000000000a1";
+    const BUYER: &str = "0x00000000000000000000000000000000000000b7";
+    const TREASURY: &str = "0x00000000000000000000000000000000000000fe";
+
+    /// (contrato, calldata) de cada llamada a `royaltyInfo`
+    type RoyaltyCalls = Rc<RefCell<Vec<(String, Vec<u8>)>>>;
+
+    /// Lector de `royaltyInfo` con una respuesta fija que anota sus llamadas
+    struct FakeRoyaltyReader {
+        result: Option<Vec<u8>>,
+        calls: RoyaltyCalls,
+    }
+
+    impl RoyaltyInfoReader for FakeRoyaltyReader {
+        fn call(&self, contract: &str, calldata: &[u8]) -> Option<Vec<u8>> {
+            self.calls.borrow_mut().push((contract.to_string(), calldata.to_vec()));
+            self.result.clone()
+        }
+    }
+
+    /// Resultado ABI de `royaltyInfo`
+    fn royalty_result(recipient: &str, amount: u128) -> Vec<u8> {
+        let mut data = vec![0u8; 12];
+        data.extend(hex::decode(&recipient[2..]).unwrap());
+        data.extend([0u8; 16]);
+        data.extend(amount.to_be_bytes());
+        data
+    }
+
+    /// Marketplace con contrato de NFTs en la red actual y el NFT `nft_id`
+    /// listado en GREEN, y los balances del comprador
+    fn sale_setup(nft_id: &str, price: u128) -> (MarketplaceManager, TokenManager) {
+        let mut config = BlockchainConfig::default();
+        config.networks.get_mut(&config.default_network).unwrap()
+            .contracts.insert(NFT_CONTRACT_KEY.to_string(), NFT_CONTRACT.to_string());
+        let mut marketplace = MarketplaceManager::new(&config);
+        marketplace.create_listing("nft", nft_id, &price.to_string(), "GREEN", 1, "", "forest").unwrap();
+        let mut tokens = TokenManager::new(&config);
+        tokens.load_user_tokens(BUYER).unwrap();
+        (marketplace, tokens)
+    }
+
+    proptest! {
+        #[test]
+        fn sale_splits_the_whole_price(
+            price in 1u128..=1_000_000_000_000_000_000_000,
+            royalty_bps in 0u16..=ROYALTY_BASIS_POINTS,
+            fee_bps in 0u16..=ROYALTY_BASIS_POINTS,
+        ) {
+            let (mut marketplace, mut tokens) = sale_setup("FOREST_CREATURE_042", price);
+            marketplace.register_royalty(NFT_CONTRACT, "FOREST_CREATURE_042", CREATOR, royalty_bps).unwrap();
+            marketplace.fee_basis_points.store(fee_bps, Ordering::Relaxed);
+            marketplace.set_fee_recipient(Some(TREASURY.to_string()));
+            let balance = tokens.balance_of("GREEN").unwrap();
+
+            match marketplace.execute_sale(BUYER, "FOREST_CREATURE_042", &price.to_string(), &mut tokens) {
+                Ok(settlement) => {
+                    let royalty: u128 = settlement.royalty_amount.parse().unwrap();
+                    let fee: u128 = settlement.fee_amount.parse().unwrap();
+                    let seller: u128 = settlement.seller_amount.parse().unwrap();
+                    prop_assert_eq!(royalty + fee + seller, price);
+                    prop_assert_eq!(royalty, basis_points_of(price, royalty_bps));
+                    prop_assert_eq!(fee, basis_points_of(price, fee_bps));
+                    prop_assert_eq!(tokens.balance_of("GREEN").unwrap(), balance - price);
+                    prop_assert_eq!(&marketplace.transactions[0].buyer, BUYER);
+                }
+                // Solo si royalty y comisión juntas pasan del 100%
+                Err(_) => {
+                    prop_assert!(royalty_bps + fee_bps > ROYALTY_BASIS_POINTS);
+                    prop_assert_eq!(tokens.balance_of("GREEN").unwrap(), balance);
+                    prop_assert!(marketplace.transactions.is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn royalty_info_call_is_abi_encoded() {
+        let calldata = encode_royalty_info_call(42, 1_000);
+        assert_eq!(calldata.len(), 4 + 64);
+        assert_eq!(calldata[..4], ROYALTY_INFO_SELECTOR);
+        assert_eq!(hex::encode(&calldata[4..36]), format!("{:064x}", 42));
+        assert_eq!(hex::encode(&calldata[36..]), format!("{:064x}", 1_000));
+
+        let calldata = encode_royalty_info_call(u128::MAX, 0);
+        assert_eq!(hex::encode(&calldata[4..36]), format!("{:0>64}", "f".repeat(32)));
+    }
+
+    #[test]
+    fn royalty_info_result_is_decoded_and_validated() {
+        let data = royalty_result(CREATOR, 50);
+        assert_eq!(decode_royalty_info_result(&data, 1_000), Ok((CREATOR.to_string(), 50)));
+        // Un importe igual al precio es válido; mayor, no
+        assert!(decode_royalty_info_result(&royalty_result(CREATOR, 1_000), 1_000).is_ok());
+        assert!(decode_royalty_info_result(&royalty_result(CREATOR, 1_001), 1_000).is_err());
+
+        assert!(decode_royalty_info_result(&data[..63], 1_000).is_err());
+        for dirty in [0, 11, 32, 47] {
+            let mut data = data.clone();
+            data[dirty] = 1;
+            assert!(decode_royalty_info_result(&data, 1_000).is_err(), "relleno sucio en {}", dirty);
+        }
+    }
+
+    #[test]
+    fn sale_falls_back_to_royalty_info_on_chain() {
+        let (mut marketplace, mut tokens) = sale_setup("42", 1_000);
+        let calls = RoyaltyCalls::default();
+        marketplace.set_royalty_reader(Box::new(FakeRoyaltyReader {
+            result: Some(royalty_result(CREATOR, 50)),
+            calls: Rc::clone(&calls),
+        }));
+
+        let settlement = marketplace.execute_sale(BUYER, "42", "1000", &mut tokens).unwrap();
+        assert_eq!(settlement.royalty_recipient.as_deref(), Some(CREATOR));
+        assert_eq!(settlement.royalty_amount, "50");
+        assert_eq!(settlement.seller_amount, "950");
+        assert_eq!(*calls.borrow(), vec![(NFT_CONTRACT.to_string(), encode_royalty_info_call(42, 1_000))]);
+    }
+
+    #[test]
+    fn failed_royalty_info_call_pays_the_seller_in_full() {
+        let (mut marketplace, mut tokens) = sale_setup("42", 1_000);
+        let calls = RoyaltyCalls::default();
+        marketplace.set_royalty_reader(Box::new(FakeRoyaltyReader { result: None, calls: Rc::clone(&calls) }));
+
+        let settlement = marketplace.execute_sale(BUYER, "42", "1000", &mut tokens).unwrap();
+        assert_eq!(calls.borrow().len(), 1);
+        assert_eq!(settlement.royalty_recipient, None);
+        assert_eq!(settlement.royalty_amount, "0");
+        assert_eq!(settlement.seller_amount, "1000");
+    }
+
+    #[test]
+    fn malformed_royalty_info_aborts_the_sale() {
+        let (mut marketplace, mut tokens) = sale_setup("42", 1_000);
+        let balance = tokens.balance_of("GREEN").unwrap();
+        marketplace.set_royalty_reader(Box::new(FakeRoyaltyReader {
+            result: Some(royalty_result(CREATOR, 1_001)),
+            calls: Rc::default(),
+        }));
+
+        assert!(marketplace.execute_sale(BUYER, "42", "1000", &mut tokens).is_err());
+        assert_eq!(tokens.balance_of("GREEN"), Some(balance));
+        assert!(marketplace.transactions.is_empty());
+    }
+
+    #[test]
+    fn registered_royalty_skips_the_on_chain_call() {
+        let (mut marketplace, _) = sale_setup("42", 1_000);
+        let calls = RoyaltyCalls::default();
+        marketplace.set_royalty_reader(Box::new(FakeRoyaltyReader {
+            result: Some(royalty_result(CREATOR, 50)),
+            calls: Rc::clone(&calls),
+        }));
+        marketplace.register_royalty(NFT_CONTRACT, "42", TREASURY, 1_000).unwrap();
+
+        assert_eq!(marketplace.royalty_for_sale("42", 1_000), Ok(Some((TREASURY.to_string(), 100))));
+        // Un ID no numérico no tiene tokenId on-chain
+        assert_eq!(marketplace.royalty_for_sale("FOREST_CREATURE_001", 1_000), Ok(None));
+        assert!(calls.borrow().is_empty());
+    }
+}
@@ -171,6 +171,11 @@ impl BlockchainManager {
         self.governance_manager.initialize()?;
         self.marketplace_manager.initialize()?;
         self.staking_manager.initialize()?;
+
+        // Los gestores registran sus parámetros gobernables
+        for (key, value, setter) in self.marketplace_manager.governable_parameters() {
+            self.governance_manager.register_parameter(&key, value, setter);
+        }
         
         // Cargar precios iniciales
         self.update_price_feeds()?;
@@ -241,6 +246,8 @@ impl BlockchainManager {
         self.price_feeds.insert("TECH_TOKEN".to_string(), 0.3);
         self.price_feeds.insert("FIRE_TOKEN".to_string(), 0.35);
         self.price_feeds.insert("COSMIC_TOKEN".to_string(), 0.4);
+        self.staking_manager.set_price_feeds(&self.price_feeds);
+        self.defi_manager.set_price_feeds(&self.price_feeds);
         
         Ok(())
     }
@@ -340,6 +347,29 @@ impl BlockchainManager {
         Ok(())
     }
 
+    /// Ejecutar la venta de un NFT a la wallet conectada pagando la royalty
+    /// del creador
+    pub fn execute_sale(&mut self, nft_id: &str, price: &str) -> Result<JsValue, JsValue> {
+        let buyer = self.wallet_address.clone()
+            .ok_or_else(|| JsValue::from_str("Wallet no conectada"))?;
+        let settlement = self.marketplace_manager.execute_sale(&buyer, nft_id, price, &mut self.token_manager)
+            .map_err(|e| JsValue::from_str(&e))?;
+        serde_wasm_bindgen::to_value(&settlement)
+            .map_err(|_| JsValue::from_str("Error al serializar la venta"))
+    }
+
+    /// Registrar la royalty de un NFT (solo desde el contrato de NFTs)
+    pub fn register_royalty(&mut self, caller: &str, nft_id: &str, recipient: &str, fee_basis_points: u16) -> Result<(), JsValue> {
+        self.marketplace_manager.register_royalty(caller, nft_id, recipient, fee_basis_points)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Ejecutar una propuesta de cambio de parámetro aprobada
+    pub fn execute_parameter_change(&mut self, proposal_id: &str) -> Result<(), JsValue> {
+        self.governance_manager.execute_parameter_change(proposal_id)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
     /// Obtener manager de tokens
     pub fn get_token_manager(&self) -> &tokens::TokenManager {
         &self.token_manager
@@ -371,6 +401,19 @@ impl BlockchainManager {
     }
 }
 
+impl BlockchainManager {
+    /// Registrar un parámetro gobernable con el setter que lo aplica. La
+    /// clave debe estar en `governance::GOVERNABLE_PARAMETERS`
+    pub fn register_governable_parameter(&mut self, key: &str, setter: impl Fn(&serde_json::Value) + Send + 'static) {
+        self.governance_manager.register_parameter(key, serde_json::Value::Null, Box::new(setter));
+    }
+
+    /// Manager de governance, para crear y ejecutar propuestas
+    pub fn governance_manager_mut(&mut self) -> &mut governance::GovernanceManager {
+        &mut self.governance_manager
+    }
+}
+
 impl Drop for BlockchainManager {
     fn drop(&mut self) {
         // Limpiar recursos
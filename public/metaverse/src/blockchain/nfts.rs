@@ -3,6 +3,7 @@
 
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use wasm_bindgen::prelude::*;
 
 /// Metadatos de NFT
@@ -464,12 +465,12 @@ impl NFTManager {
             "0".to_string()
         };
 
-        if let Some(nft) in self.user_nfts.get_mut(&key) {
+        if let Some(nft) = self.user_nfts.get_mut(&key) {
             nft.is_staked = false;
             nft.staking_rewards = None;
         }
 
-        if let Some(nft) in self.all_nfts.get_mut(&key) {
+        if let Some(nft) = self.all_nfts.get_mut(&key) {
             nft.is_staked = false;
             nft.staking_rewards = None;
         }
@@ -528,4 +529,135 @@ impl NFTManager {
     pub fn is_initialized(&self) -> bool {
         self.is_initialized
     }
-} 
\ No newline at end of file
+}
+
+/// Colección de las insignias de logros
+pub const BADGE_COLLECTION: &str = "BADGE";
+
+impl NFTManager {
+    /// Mintear una insignia para `owner` con los metadatos publicados en
+    /// `metadata_uri`. La colección de insignias se crea con la primera.
+    /// Devuelve el token ID
+    pub fn mint(&mut self, owner: &str, metadata_uri: &str) -> Result<String, String> {
+        let collection = self.collections.entry(BADGE_COLLECTION.to_string()).or_insert_with(|| NFTCollection {
+            name: "Achievement Badges".to_string(),
+            symbol: BADGE_COLLECTION.to_string(),
+            description: "Badges earned by completing achievements".to_string(),
+            island: String::new(),
+            total_supply: u64::MAX,
+            minted_count: 0,
+            contract_address: "0x8901234567890123456789012345678901234567".to_string(),
+            creator: "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6".to_string(),
+            royalty_percentage: 0,
+        });
+        if collection.minted_count >= collection.total_supply {
+            return Err("Suministro de la colección agotado".to_string());
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        collection.minted_count += 1;
+        let token_id = collection.minted_count.to_string();
+        let nft = NFTInfo {
+            token_id: token_id.clone(),
+            contract_address: collection.contract_address.clone(),
+            metadata: NFTMetadata {
+                name: format!("{} #{}", collection.name, token_id),
+                description: collection.description.clone(),
+                image: metadata_uri.to_string(),
+                attributes: Vec::new(),
+                island: collection.island.clone(),
+                rarity: NFTRarity::Common,
+                level: 1,
+                experience: 0,
+                power: 0,
+                abilities: Vec::new(),
+                created_at: now,
+                creator: collection.creator.clone(),
+            },
+            owner: owner.to_string(),
+            is_staked: false,
+            staking_rewards: None,
+            last_transfer: now,
+            market_price: None,
+            market_price_usd: None,
+        };
+        self.all_nfts.insert(format!("{}_{}", collection.contract_address, token_id), nft);
+        Ok(token_id)
+    }
+
+    /// Número de NFTs de `owner`
+    pub fn owned_count(&self, owner: &str) -> u32 {
+        self.all_nfts.values().filter(|nft| nft.owner == owner).count() as u32
+    }
+
+    /// Número de NFTs de `owner` fuera de la colección `excluded`
+    pub fn owned_count_excluding(&self, owner: &str, excluded: &str) -> u32 {
+        let excluded = self.collections.get(excluded).map(|collection| collection.contract_address.as_str());
+        self.all_nfts.values()
+            .filter(|nft| nft.owner == owner && Some(nft.contract_address.as_str()) != excluded)
+            .count() as u32
+    }
+
+    /// Airdrop por sorteo: elige `winners` participantes distintos con la
+    /// aleatoriedad VRF de governance y mintea a cada uno un NFT de la
+    /// colección. Con la misma aleatoriedad el resultado es siempre el mismo,
+    /// así que cualquiera puede comprobar el sorteo con la prueba publicada.
+    /// Devuelve (ganador, token ID) en orden de sorteo
+    pub fn lottery_airdrop(
+        &mut self,
+        collection_symbol: &str,
+        participants: &[String],
+        winners: usize,
+        randomness: [u8; 32],
+        metadata: &NFTMetadata,
+    ) -> Result<Vec<(String, String)>, String> {
+        let collection = self.collections.get_mut(collection_symbol)
+            .ok_or_else(|| "Colección no encontrada".to_string())?;
+        // Participantes sin repetir y en un orden que no depende de quién los pasa
+        let mut pool: Vec<&String> = participants.iter().collect();
+        pool.sort();
+        pool.dedup();
+        let winners = winners.min(pool.len());
+        if collection.minted_count.saturating_add(winners as u64) > collection.total_supply {
+            return Err("El airdrop supera el suministro de la colección".to_string());
+        }
+
+        // Fisher-Yates parcial con un índice derivado de la aleatoriedad por ronda
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut drawn = Vec::with_capacity(winners);
+        for round in 0..winners {
+            let digest = Sha256::new()
+                .chain_update(randomness)
+                .chain_update((round as u64).to_be_bytes())
+                .finalize();
+            let value = u64::from_be_bytes(digest[..8].try_into().unwrap_or_default());
+            let pick = round + (value % (pool.len() - round) as u64) as usize;
+            pool.swap(round, pick);
+
+            let winner = pool[round].clone();
+            collection.minted_count += 1;
+            let token_id = collection.minted_count.to_string();
+            let nft = NFTInfo {
+                token_id: token_id.clone(),
+                contract_address: collection.contract_address.clone(),
+                metadata: metadata.clone(),
+                owner: winner.clone(),
+                is_staked: false,
+                staking_rewards: None,
+                last_transfer: now,
+                market_price: None,
+                market_price_usd: None,
+            };
+            self.all_nfts.insert(format!("{}_{}", collection.contract_address, token_id), nft);
+            drawn.push((winner, token_id));
+        }
+
+        Ok(drawn)
+    }
+}
@@ -1,49 +1,143 @@
 //! Gestor de Staking para Metaverso
 //! Maneja staking de tokens, NFTs y recompensas
+//!
+//! Cada pool acepta un token de stake y reparte uno o varios tokens de
+//! recompensa con una emisión fija por segundo. El reparto sigue el modelo de
+//! Synthetix: cada token de recompensa acumula `reward_per_share` (recompensa
+//! por unidad en stake desde la creación del pool) y cada usuario guarda su
+//! deuda `stake * reward_per_share` al cambiar su stake. Lo pendiente es
+//! siempre `stake * reward_per_share - deuda`, así que el coste de un stake,
+//! unstake o claim no depende del número de usuarios.
 
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+/// Identificador de pool
+pub type PoolId = String;
+
+/// Cantidad de tokens en su unidad mínima (18 decimales)
+pub type Balance = u128;
+
+/// Escala de `reward_per_share` para no perder las fracciones por unidad
+pub const REWARD_PRECISION: Balance = 1_000_000_000_000;
+
+/// Unidades mínimas por token
+const TOKEN_UNIT: f64 = 1e18;
+
+/// Usuario por defecto de las llamadas desde JS
+const DEFAULT_USER: &str = "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6";
+
+/// Token de recompensa de un pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RewardToken {
+    pub token_symbol: String,
+    /// Emisión por segundo, repartida entre todo el stake del pool
+    pub reward_per_second: Balance,
+    /// Recompensa acumulada por unidad en stake, escalada por `REWARD_PRECISION`
+    pub reward_per_share: Balance,
+    /// Recompensas pagadas en claims
+    pub total_distributed: Balance,
+}
+
 /// Pool de staking
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StakingPool {
-    pub id: String,
+    pub pool_id: PoolId,
     pub name: String,
-    pub token_symbol: String,
-    pub total_staked: String,
-    pub total_staked_usd: f64,
-    pub total_rewards_distributed: String,
-    pub total_rewards_distributed_usd: f64,
-    pub apr: f32,
+    pub stake_token: String,
+    pub reward_tokens: Vec<RewardToken>,
+    pub total_staked: Balance,
+    /// Emisión por segundo sumando todos los tokens de recompensa
+    pub reward_per_second: Balance,
+    /// Último instante en el que se actualizaron los acumuladores
+    pub last_update_time: u64,
     pub lock_period: u64,
-    pub min_stake_amount: String,
-    pub max_stake_amount: Option<String>,
-    pub reward_token: String,
-    pub reward_rate: f32,
+    pub min_stake_amount: Balance,
+    pub max_stake_amount: Option<Balance>,
     pub island: String,
     pub is_active: bool,
     pub created_at: u64,
 }
 
-/// Posición de staking
+impl StakingPool {
+    /// Avanzar los acumuladores hasta `now`. Sin stake no se emite nada
+    fn update_rewards(&mut self, now: u64) {
+        let elapsed = now.saturating_sub(self.last_update_time) as Balance;
+        if elapsed > 0 && self.total_staked > 0 {
+            for reward in &mut self.reward_tokens {
+                let emitted = elapsed.saturating_mul(reward.reward_per_second);
+                reward.reward_per_share = reward.reward_per_share
+                    .saturating_add(emitted.saturating_mul(REWARD_PRECISION) / self.total_staked);
+            }
+        }
+        self.last_update_time = self.last_update_time.max(now);
+    }
+}
+
+/// Configuración de un token de recompensa al crear un pool
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct StakingPosition {
-    pub id: String,
+pub struct RewardTokenConfig {
+    pub token_symbol: String,
+    pub reward_per_second: Balance,
+}
+
+/// Configuración de un pool nuevo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolConfig {
+    pub name: String,
+    pub stake_token: String,
+    pub reward_tokens: Vec<RewardTokenConfig>,
+    #[serde(default)]
+    pub lock_period: u64,
+    #[serde(default)]
+    pub min_stake_amount: Balance,
+    #[serde(default)]
+    pub max_stake_amount: Option<Balance>,
+    #[serde(default)]
+    pub island: String,
+}
+
+/// Stake de un usuario en un pool
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserStake {
     pub user_address: String,
-    pub pool_id: String,
-    pub amount_staked: String,
-    pub amount_staked_usd: f64,
-    pub rewards_earned: String,
-    pub rewards_earned_usd: f64,
-    pub rewards_claimed: String,
-    pub rewards_claimed_usd: f64,
-    pub start_time: u64,
-    pub end_time: Option<u64>,
+    pub pool_id: PoolId,
+    pub amount: Balance,
+    /// `amount * reward_per_share` de cada token de recompensa en el último cambio
+    pub reward_debt: Vec<Balance>,
+    /// Recompensas devengadas antes del último cambio y aún sin reclamar
+    pub accrued_rewards: Vec<Balance>,
     pub lock_end_time: u64,
-    pub is_locked: bool,
-    pub is_active: bool,
-    pub apr_at_stake: f32,
+}
+
+impl UserStake {
+    /// Recompensas pendientes de cada token con los acumuladores del pool
+    fn pending(&self, pool: &StakingPool) -> Vec<Balance> {
+        pool.reward_tokens.iter().enumerate()
+            .map(|(index, reward)| {
+                let earned = self.amount.saturating_mul(reward.reward_per_share) / REWARD_PRECISION;
+                let debt = self.reward_debt.get(index).copied().unwrap_or(0);
+                let accrued = self.accrued_rewards.get(index).copied().unwrap_or(0);
+                accrued.saturating_add(earned.saturating_sub(debt))
+            })
+            .collect()
+    }
+
+    /// Pasar lo pendiente a `accrued_rewards`, cambiar el stake a `amount` y
+    /// rehacer la deuda con los acumuladores actuales
+    fn settle(&mut self, pool: &StakingPool, amount: Balance) {
+        self.accrued_rewards = self.pending(pool);
+        self.amount = amount;
+        self.reset_debt(pool);
+    }
+
+    /// Deuda del stake actual con los acumuladores del pool
+    fn reset_debt(&mut self, pool: &StakingPool) {
+        self.reward_debt = pool.reward_tokens.iter()
+            .map(|reward| self.amount.saturating_mul(reward.reward_per_share) / REWARD_PRECISION)
+            .collect();
+    }
 }
 
 /// Información de recompensas
@@ -79,13 +173,28 @@ pub enum StakingAction {
     Restake,
 }
 
+/// Estadísticas de staking
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StakingStats {
+    pub total_pools: usize,
+    pub active_pools: usize,
+    pub total_stakers: usize,
+    /// Valor en USD del stake de todos los pools según `price_feeds`
+    pub total_value_locked_usd: f64,
+    /// Valor en USD de las recompensas pagadas según `price_feeds`
+    pub total_rewards_distributed_usd: f64,
+}
+
 /// Gestor de Staking
 #[wasm_bindgen]
 pub struct StakingManager {
-    pools: HashMap<String, StakingPool>,
-    positions: HashMap<String, StakingPosition>,
+    pools: HashMap<PoolId, StakingPool>,
+    /// Stake por pool y usuario
+    stakes: HashMap<PoolId, HashMap<String, UserStake>>,
     rewards: HashMap<String, Vec<RewardInfo>>,
     history: Vec<StakingHistory>,
+    /// Precio en USD por símbolo de token
+    price_feeds: HashMap<String, f64>,
     current_network: String,
     is_initialized: bool,
 }
@@ -96,9 +205,10 @@ impl StakingManager {
     pub fn new(config: &crate::blockchain::BlockchainConfig) -> Self {
         Self {
             pools: HashMap::new(),
-            positions: HashMap::new(),
+            stakes: HashMap::new(),
             rewards: HashMap::new(),
             history: Vec::new(),
+            price_feeds: HashMap::new(),
             current_network: config.default_network.clone(),
             is_initialized: false,
         }
@@ -119,101 +229,29 @@ impl StakingManager {
             .unwrap()
             .as_secs();
 
-        let pools = vec![
-            StakingPool {
-                id: "GREEN_STAKING_POOL".to_string(),
-                name: "Green Token Staking Pool".to_string(),
-                token_symbol: "GREEN".to_string(),
-                total_staked: "500000000000000000000000".to_string(), // 500K GREEN
-                total_staked_usd: 50000.0,
-                total_rewards_distributed: "25000000000000000000000".to_string(), // 25K GREEN
-                total_rewards_distributed_usd: 2500.0,
-                apr: 12.5,
-                lock_period: 2592000, // 30 días
-                min_stake_amount: "1000000000000000000000".to_string(), // 1000 GREEN
-                max_stake_amount: None,
-                reward_token: "GREEN".to_string(),
-                reward_rate: 0.125,
-                island: "forest".to_string(),
-                is_active: true,
-                created_at: current_time - 2592000, // Hace 30 días
-            },
-            StakingPool {
-                id: "BLUE_STAKING_POOL".to_string(),
-                name: "Blue Token Staking Pool".to_string(),
-                token_symbol: "BLUE".to_string(),
-                total_staked: "300000000000000000000000".to_string(), // 300K BLUE
-                total_staked_usd: 45000.0,
-                total_rewards_distributed: "15000000000000000000000".to_string(), // 15K BLUE
-                total_rewards_distributed_usd: 2250.0,
-                apr: 15.0,
-                lock_period: 2592000, // 30 días
-                min_stake_amount: "1000000000000000000000".to_string(), // 1000 BLUE
-                max_stake_amount: None,
-                reward_token: "BLUE".to_string(),
-                reward_rate: 0.15,
-                island: "ocean".to_string(),
-                is_active: true,
-                created_at: current_time - 2592000, // Hace 30 días
-            },
-            StakingPool {
-                id: "WHITE_STAKING_POOL".to_string(),
-                name: "White Token Staking Pool".to_string(),
-                token_symbol: "WHITE".to_string(),
-                total_staked: "200000000000000000000000".to_string(), // 200K WHITE
-                total_staked_usd: 40000.0,
-                total_rewards_distributed: "10000000000000000000000".to_string(), // 10K WHITE
-                total_rewards_distributed_usd: 2000.0,
-                apr: 18.0,
-                lock_period: 5184000, // 60 días
-                min_stake_amount: "1000000000000000000000".to_string(), // 1000 WHITE
-                max_stake_amount: None,
-                reward_token: "WHITE".to_string(),
-                reward_rate: 0.18,
-                island: "mountain".to_string(),
-                is_active: true,
-                created_at: current_time - 2592000, // Hace 30 días
-            },
-            StakingPool {
-                id: "GOLD_STAKING_POOL".to_string(),
-                name: "Gold Token Staking Pool".to_string(),
-                token_symbol: "GOLD".to_string(),
-                total_staked: "150000000000000000000000".to_string(), // 150K GOLD
-                total_staked_usd: 37500.0,
-                total_rewards_distributed: "7500000000000000000000".to_string(), // 7.5K GOLD
-                total_rewards_distributed_usd: 1875.0,
-                apr: 20.0,
-                lock_period: 7776000, // 90 días
-                min_stake_amount: "1000000000000000000000".to_string(), // 1000 GOLD
-                max_stake_amount: None,
-                reward_token: "GOLD".to_string(),
-                reward_rate: 0.20,
-                island: "desert".to_string(),
-                is_active: true,
-                created_at: current_time - 2592000, // Hace 30 días
-            },
-            StakingPool {
-                id: "TECH_STAKING_POOL".to_string(),
-                name: "Tech Token Staking Pool".to_string(),
-                token_symbol: "TECH".to_string(),
-                total_staked: "100000000000000000000000".to_string(), // 100K TECH
-                total_staked_usd: 30000.0,
-                total_rewards_distributed: "5000000000000000000000".to_string(), // 5K TECH
-                total_rewards_distributed_usd: 1500.0,
-                apr: 25.0,
-                lock_period: 10368000, // 120 días
-                min_stake_amount: "1000000000000000000000".to_string(), // 1000 TECH
-                max_stake_amount: None,
-                reward_token: "TECH".to_string(),
-                reward_rate: 0.25,
-                island: "city".to_string(),
-                is_active: true,
-                created_at: current_time - 2592000, // Hace 30 días
-            },
+        // (token, nombre, isla, bloqueo, emisión por segundo)
+        let pools = [
+            ("GREEN", "Green Token Staking Pool", "forest", 2592000, 1_981_000_000_000_000u128), // ~62.5K GREEN/año
+            ("BLUE", "Blue Token Staking Pool", "ocean", 2592000, 1_427_000_000_000_000),        // ~45K BLUE/año
+            ("WHITE", "White Token Staking Pool", "mountain", 5184000, 1_141_000_000_000_000),   // ~36K WHITE/año
+            ("GOLD", "Gold Token Staking Pool", "desert", 7776000, 951_000_000_000_000),         // ~30K GOLD/año
+            ("TECH", "Tech Token Staking Pool", "city", 10368000, 793_000_000_000_000),          // ~25K TECH/año
         ];
 
-        for pool in pools {
-            self.pools.insert(pool.id.clone(), pool);
+        for (token, name, island, lock_period, reward_per_second) in pools {
+            let config = PoolConfig {
+                name: name.to_string(),
+                stake_token: token.to_string(),
+                reward_tokens: vec![RewardTokenConfig {
+                    token_symbol: token.to_string(),
+                    reward_per_second,
+                }],
+                lock_period,
+                min_stake_amount: 1_000_000_000_000_000_000_000, // 1000 tokens
+                max_stake_amount: None,
+                island: island.to_string(),
+            };
+            self.insert_pool(format!("{}_STAKING_POOL", token), config, current_time - 2592000); // Hace 30 días
         }
 
         Ok(())
@@ -226,48 +264,10 @@ impl StakingManager {
             .unwrap()
             .as_secs();
 
-        let user_address = "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6";
-
-        let positions = vec![
-            StakingPosition {
-                id: "POS_001".to_string(),
-                user_address: user_address.to_string(),
-                pool_id: "GREEN_STAKING_POOL".to_string(),
-                amount_staked: "10000000000000000000000".to_string(), // 10K GREEN
-                amount_staked_usd: 1000.0,
-                rewards_earned: "500000000000000000000".to_string(), // 500 GREEN
-                rewards_earned_usd: 50.0,
-                rewards_claimed: "200000000000000000000".to_string(), // 200 GREEN
-                rewards_claimed_usd: 20.0,
-                start_time: current_time - 2592000, // Hace 30 días
-                end_time: None,
-                lock_end_time: current_time + 2592000, // En 30 días
-                is_locked: true,
-                is_active: true,
-                apr_at_stake: 12.5,
-            },
-            StakingPosition {
-                id: "POS_002".to_string(),
-                user_address: user_address.to_string(),
-                pool_id: "BLUE_STAKING_POOL".to_string(),
-                amount_staked: "5000000000000000000000".to_string(), // 5K BLUE
-                amount_staked_usd: 750.0,
-                rewards_earned: "250000000000000000000".to_string(), // 250 BLUE
-                rewards_earned_usd: 37.5,
-                rewards_claimed: "100000000000000000000".to_string(), // 100 BLUE
-                rewards_claimed_usd: 15.0,
-                start_time: current_time - 1728000, // Hace 20 días
-                end_time: None,
-                lock_end_time: current_time + 3456000, // En 40 días
-                is_locked: true,
-                is_active: true,
-                apr_at_stake: 15.0,
-            },
-        ];
-
-        for position in positions {
-            self.positions.insert(position.id.clone(), position);
-        }
+        self.stake_at(DEFAULT_USER, "GREEN_STAKING_POOL", 10_000_000_000_000_000_000_000, current_time - 2592000) // 10K GREEN hace 30 días
+            .map_err(|e| JsValue::from_str(&e))?;
+        self.stake_at(DEFAULT_USER, "BLUE_STAKING_POOL", 5_000_000_000_000_000_000_000, current_time - 1728000) // 5K BLUE hace 20 días
+            .map_err(|e| JsValue::from_str(&e))?;
 
         Ok(())
     }
@@ -283,7 +283,7 @@ impl StakingManager {
         let island_pools: Vec<&StakingPool> = self.pools.values()
             .filter(|pool| pool.island == island && pool.is_active)
             .collect();
-        
+
         serde_wasm_bindgen::to_value(&island_pools).unwrap_or_default()
     }
 
@@ -291,314 +291,428 @@ impl StakingManager {
     pub fn get_pool(&self, pool_id: &str) -> Result<JsValue, JsValue> {
         let pool = self.pools.get(pool_id)
             .ok_or_else(|| JsValue::from_str("Pool no encontrado"))?;
-        
+
         serde_wasm_bindgen::to_value(pool)
             .map_err(|_| JsValue::from_str("Error al serializar pool"))
     }
 
-    /// Hacer stake
+    /// Hacer stake en un pool. Devuelve el stake total del usuario en el pool
     pub fn stake(&mut self, pool_id: &str, amount: &str) -> Result<String, JsValue> {
-        let pool = self.pools.get_mut(pool_id)
-            .ok_or_else(|| JsValue::from_str("Pool no encontrado"))?;
+        let amount = amount.parse::<Balance>()
+            .map_err(|_| JsValue::from_str("Error al parsear cantidad"))?;
+        self.stake_at(DEFAULT_USER, pool_id, amount, Self::now())
+            .map(|staked| staked.to_string())
+            .map_err(|e| JsValue::from_str(&e))
+    }
 
-        if !pool.is_active {
-            return Err(JsValue::from_str("El pool no está activo"));
-        }
+    /// Retirar stake de un pool. Devuelve el stake restante del usuario
+    pub fn unstake(&mut self, pool_id: &str, amount: &str) -> Result<String, JsValue> {
+        let amount = amount.parse::<Balance>()
+            .map_err(|_| JsValue::from_str("Error al parsear cantidad"))?;
+        self.unstake_at(DEFAULT_USER, pool_id, amount, Self::now())
+            .map(|remaining| remaining.to_string())
+            .map_err(|e| JsValue::from_str(&e))
+    }
 
-        let user_address = "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6";
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    /// Reclamar recompensas de un pool. Devuelve el importe por token
+    pub fn claim_rewards(&mut self, pool_id: &str) -> Result<JsValue, JsValue> {
+        let claimed: HashMap<String, String> = self.claim_rewards_at(DEFAULT_USER, pool_id, Self::now())
+            .map_err(|e| JsValue::from_str(&e))?
+            .into_iter()
+            .map(|(token, amount)| (token, amount.to_string()))
+            .collect();
+        serde_wasm_bindgen::to_value(&claimed)
+            .map_err(|_| JsValue::from_str("Error al serializar recompensas"))
+    }
 
-        let stake_amount = amount.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear cantidad"))?;
-        let min_stake = pool.min_stake_amount.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear cantidad mínima"))?;
+    /// Obtener recompensas pendientes del usuario en un pool
+    pub fn get_pending_rewards(&self, pool_id: &str) -> JsValue {
+        let pending: HashMap<String, String> = self.pending_rewards_at(DEFAULT_USER, pool_id, Self::now())
+            .into_iter()
+            .map(|(token, amount)| (token, amount.to_string()))
+            .collect();
+        serde_wasm_bindgen::to_value(&pending).unwrap_or_default()
+    }
 
-        if stake_amount < min_stake {
-            return Err(JsValue::from_str("Cantidad menor al mínimo requerido"));
-        }
+    /// Obtener posiciones del usuario
+    pub fn get_user_positions(&self) -> JsValue {
+        let user_positions: Vec<&UserStake> = self.stakes.values()
+            .filter_map(|stakes| stakes.get(DEFAULT_USER))
+            .filter(|stake| stake.amount > 0)
+            .collect();
 
-        if let Some(max_stake) = &pool.max_stake_amount {
-            let max_stake_amount = max_stake.parse::<u128>()
-                .map_err(|_| JsValue::from_str("Error al parsear cantidad máxima"))?;
-            if stake_amount > max_stake_amount {
-                return Err(JsValue::from_str("Cantidad mayor al máximo permitido"));
-            }
-        }
+        serde_wasm_bindgen::to_value(&user_positions).unwrap_or_default()
+    }
 
-        // Calcular valor en USD
-        let stake_amount_usd = match pool.token_symbol.as_str() {
-            "GREEN" => stake_amount as f64 * 0.1 / 1e18,
-            "BLUE" => stake_amount as f64 * 0.15 / 1e18,
-            "WHITE" => stake_amount as f64 * 0.2 / 1e18,
-            "GOLD" => stake_amount as f64 * 0.25 / 1e18,
-            "TECH" => stake_amount as f64 * 0.3 / 1e18,
-            _ => stake_amount as f64 / 1e18,
-        };
+    /// Obtener posiciones activas
+    pub fn get_active_positions(&self) -> JsValue {
+        let active_positions: Vec<&UserStake> = self.stakes.values()
+            .flat_map(|stakes| stakes.values())
+            .filter(|stake| stake.amount > 0)
+            .collect();
 
-        // Crear posición de staking
-        let position_id = format!("POS_{:03}", self.positions.len() + 1);
-        let position = StakingPosition {
-            id: position_id.clone(),
-            user_address: user_address.to_string(),
-            pool_id: pool_id.to_string(),
-            amount_staked: amount.to_string(),
-            amount_staked_usd: stake_amount_usd,
-            rewards_earned: "0".to_string(),
-            rewards_earned_usd: 0.0,
-            rewards_claimed: "0".to_string(),
-            rewards_claimed_usd: 0.0,
-            start_time: current_time,
-            end_time: None,
-            lock_end_time: current_time + pool.lock_period,
-            is_locked: true,
-            is_active: true,
-            apr_at_stake: pool.apr,
-        };
+        serde_wasm_bindgen::to_value(&active_positions).unwrap_or_default()
+    }
 
-        self.positions.insert(position_id.clone(), position);
+    /// Obtener historial de staking
+    pub fn get_staking_history(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.history).unwrap_or_default()
+    }
 
-        // Actualizar pool
-        let total_staked = pool.total_staked.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear total staked"))?;
-        pool.total_staked = (total_staked + stake_amount).to_string();
-        pool.total_staked_usd += stake_amount_usd;
+    /// Obtener recompensas del usuario
+    pub fn get_user_rewards(&self) -> JsValue {
+        let user_rewards = self.rewards.get(DEFAULT_USER).cloned().unwrap_or_default();
 
-        // Crear entrada en historial
-        let history_entry = StakingHistory {
-            id: format!("HIST_{}", self.history.len() + 1),
-            user_address: user_address.to_string(),
-            pool_id: pool_id.to_string(),
-            action: StakingAction::Stake,
-            amount: amount.to_string(),
-            amount_usd: stake_amount_usd,
-            timestamp: current_time,
-            transaction_hash: Some(format!("0x{}", hex::encode(&[0u8; 32]))),
-        };
+        serde_wasm_bindgen::to_value(&user_rewards).unwrap_or_default()
+    }
 
-        self.history.push(history_entry);
+    /// Obtener estadísticas de staking
+    pub fn get_staking_stats(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.staking_stats()).unwrap_or_default()
+    }
 
-        Ok(position_id)
+    /// Actualizar red
+    pub fn update_network(&mut self, network: &str) -> Result<(), JsValue> {
+        self.current_network = network.to_string();
+        Ok(())
     }
 
-    /// Hacer unstake
-    pub fn unstake(&mut self, position_id: &str) -> Result<(String, String), JsValue> {
-        let position = self.positions.get_mut(position_id)
-            .ok_or_else(|| JsValue::from_str("Posición no encontrada"))?;
+    /// Actualizar configuración
+    pub fn update_config(&mut self, config: &crate::blockchain::BlockchainConfig) -> Result<(), JsValue> {
+        self.current_network = config.default_network.clone();
+        Ok(())
+    }
 
-        if !position.is_active {
-            return Err(JsValue::from_str("La posición no está activa"));
-        }
+    /// Verificar si está inicializado
+    pub fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
 
-        let current_time = std::time::SystemTime::now()
+impl StakingManager {
+    /// Instante actual en segundos
+    fn now() -> u64 {
+        std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
-            .as_secs();
+            .as_secs()
+    }
+
+    /// Crear un pool. Devuelve su ID
+    pub fn create_pool(&mut self, config: PoolConfig) -> Result<PoolId, String> {
+        self.create_pool_at(config, Self::now())
+    }
 
-        if position.is_locked && current_time < position.lock_end_time {
-            return Err(JsValue::from_str("La posición aún está bloqueada"));
+    /// Crear un pool que empieza a emitir en `now`
+    pub fn create_pool_at(&mut self, config: PoolConfig, now: u64) -> Result<PoolId, String> {
+        if config.stake_token.is_empty() {
+            return Err("Token de stake vacío".to_string());
+        }
+        if config.reward_tokens.is_empty() {
+            return Err("El pool necesita al menos un token de recompensa".to_string());
+        }
+        if let Some(max) = config.max_stake_amount {
+            if max < config.min_stake_amount {
+                return Err("La cantidad máxima es menor que la mínima".to_string());
+            }
+        }
+        let mut symbols: Vec<&str> = config.reward_tokens.iter().map(|r| r.token_symbol.as_str()).collect();
+        symbols.sort_unstable();
+        symbols.dedup();
+        if symbols.len() != config.reward_tokens.len() {
+            return Err("Token de recompensa repetido".to_string());
         }
 
-        // Calcular recompensas pendientes
-        let pending_rewards = self.calculate_pending_rewards(position)?;
-
-        position.is_active = false;
-        position.end_time = Some(current_time);
-
-        // Actualizar pool
-        if let Some(pool) = self.pools.get_mut(&position.pool_id) {
-            let total_staked = pool.total_staked.parse::<u128>()
-                .map_err(|_| JsValue::from_str("Error al parsear total staked"))?;
-            let position_staked = position.amount_staked.parse::<u128>()
-                .map_err(|_| JsValue::from_str("Error al parsear cantidad staked"))?;
-            
-            pool.total_staked = (total_staked - position_staked).to_string();
-            pool.total_staked_usd -= position.amount_staked_usd;
+        let pool_id = format!("POOL_{:03}", self.pools.len() + 1);
+        if self.pools.contains_key(&pool_id) {
+            return Err("Ya existe un pool con ese ID".to_string());
         }
+        self.insert_pool(pool_id.clone(), config, now);
+        Ok(pool_id)
+    }
 
-        // Crear entrada en historial
-        let history_entry = StakingHistory {
-            id: format!("HIST_{}", self.history.len() + 1),
-            user_address: position.user_address.clone(),
-            pool_id: position.pool_id.clone(),
-            action: StakingAction::Unstake,
-            amount: position.amount_staked.clone(),
-            amount_usd: position.amount_staked_usd,
-            timestamp: current_time,
-            transaction_hash: Some(format!("0x{}", hex::encode(&[0u8; 32]))),
+    /// Registrar un pool con el ID indicado
+    fn insert_pool(&mut self, pool_id: PoolId, config: PoolConfig, now: u64) {
+        let reward_tokens: Vec<RewardToken> = config.reward_tokens.into_iter()
+            .map(|reward| RewardToken {
+                token_symbol: reward.token_symbol,
+                reward_per_second: reward.reward_per_second,
+                reward_per_share: 0,
+                total_distributed: 0,
+            })
+            .collect();
+        let pool = StakingPool {
+            pool_id: pool_id.clone(),
+            name: config.name,
+            stake_token: config.stake_token,
+            reward_per_second: reward_tokens.iter().map(|r| r.reward_per_second).sum(),
+            reward_tokens,
+            total_staked: 0,
+            last_update_time: now,
+            lock_period: config.lock_period,
+            min_stake_amount: config.min_stake_amount,
+            max_stake_amount: config.max_stake_amount,
+            island: config.island,
+            is_active: true,
+            created_at: now,
         };
+        self.pools.insert(pool_id, pool);
+    }
 
-        self.history.push(history_entry);
-
-        Ok((position.amount_staked.clone(), pending_rewards))
+    /// Pool por ID
+    pub fn pool(&self, pool_id: &str) -> Option<&StakingPool> {
+        self.pools.get(pool_id)
     }
 
-    /// Reclamar recompensas
-    pub fn claim_rewards(&mut self, position_id: &str) -> Result<String, JsValue> {
-        let position = self.positions.get_mut(position_id)
-            .ok_or_else(|| JsValue::from_str("Posición no encontrada"))?;
+    /// Stake de un usuario en un pool
+    pub fn user_stake(&self, user: &str, pool_id: &str) -> Option<&UserStake> {
+        self.stakes.get(pool_id).and_then(|stakes| stakes.get(user))
+    }
 
-        if !position.is_active {
-            return Err(JsValue::from_str("La posición no está activa"));
+    /// Hacer stake de `amount` en `now`. Devuelve el stake total del usuario
+    /// en el pool
+    pub fn stake_at(&mut self, user: &str, pool_id: &str, amount: Balance, now: u64) -> Result<Balance, String> {
+        let pool = self.pools.get_mut(pool_id)
+            .ok_or_else(|| "Pool no encontrado".to_string())?;
+        if !pool.is_active {
+            return Err("El pool no está activo".to_string());
         }
-
-        // Calcular recompensas pendientes
-        let pending_rewards = self.calculate_pending_rewards(position)?;
-        
-        if pending_rewards == "0" {
-            return Err(JsValue::from_str("No hay recompensas para reclamar"));
+        if amount == 0 {
+            return Err("Cantidad de stake nula".to_string());
         }
 
-        // Actualizar recompensas reclamadas
-        let claimed_rewards = position.rewards_claimed.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear recompensas reclamadas"))?;
-        let pending_rewards_u128 = pending_rewards.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear recompensas pendientes"))?;
-        
-        position.rewards_claimed = (claimed_rewards + pending_rewards_u128).to_string();
-
-        // Calcular valor en USD
-        let pending_rewards_usd = match position.pool_id.as_str() {
-            "GREEN_STAKING_POOL" => pending_rewards_u128 as f64 * 0.1 / 1e18,
-            "BLUE_STAKING_POOL" => pending_rewards_u128 as f64 * 0.15 / 1e18,
-            "WHITE_STAKING_POOL" => pending_rewards_u128 as f64 * 0.2 / 1e18,
-            "GOLD_STAKING_POOL" => pending_rewards_u128 as f64 * 0.25 / 1e18,
-            "TECH_STAKING_POOL" => pending_rewards_u128 as f64 * 0.3 / 1e18,
-            _ => pending_rewards_u128 as f64 / 1e18,
-        };
-
-        position.rewards_claimed_usd += pending_rewards_usd;
-
-        // Actualizar pool
-        if let Some(pool) = self.pools.get_mut(&position.pool_id) {
-            let total_rewards = pool.total_rewards_distributed.parse::<u128>()
-                .map_err(|_| JsValue::from_str("Error al parsear total recompensas"))?;
-            
-            pool.total_rewards_distributed = (total_rewards + pending_rewards_u128).to_string();
-            pool.total_rewards_distributed_usd += pending_rewards_usd;
+        let current = self.stakes.get(pool_id)
+            .and_then(|stakes| stakes.get(user))
+            .map_or(0, |stake| stake.amount);
+        let new_amount = current.checked_add(amount)
+            .ok_or_else(|| "Desbordamiento del stake".to_string())?;
+        if new_amount < pool.min_stake_amount {
+            return Err("Cantidad menor al mínimo requerido".to_string());
+        }
+        if pool.max_stake_amount.is_some_and(|max| new_amount > max) {
+            return Err("Cantidad mayor al máximo permitido".to_string());
         }
 
-        // Crear entrada en historial
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        // Los acumuladores se cierran con el stake anterior antes de cambiarlo
+        pool.update_rewards(now);
+        let stake = self.stakes.entry(pool_id.to_string()).or_default()
+            .entry(user.to_string())
+            .or_insert_with(|| UserStake {
+                user_address: user.to_string(),
+                pool_id: pool_id.to_string(),
+                ..Default::default()
+            });
+        stake.settle(pool, new_amount);
+        stake.lock_end_time = now.saturating_add(pool.lock_period);
+        pool.total_staked += amount;
+
+        let amount_usd = Self::usd_value(&self.price_feeds, &pool.stake_token, amount);
+        self.record_history(user, pool_id, StakingAction::Stake, amount, amount_usd, now);
+        Ok(new_amount)
+    }
 
-        let history_entry = StakingHistory {
-            id: format!("HIST_{}", self.history.len() + 1),
-            user_address: position.user_address.clone(),
-            pool_id: position.pool_id.clone(),
-            action: StakingAction::ClaimRewards,
-            amount: pending_rewards.clone(),
-            amount_usd: pending_rewards_usd,
-            timestamp: current_time,
-            transaction_hash: Some(format!("0x{}", hex::encode(&[0u8; 32]))),
-        };
+    /// Retirar `amount` del stake en `now`. Las recompensas devengadas quedan
+    /// pendientes de reclamar. Devuelve el stake restante del usuario
+    pub fn unstake_at(&mut self, user: &str, pool_id: &str, amount: Balance, now: u64) -> Result<Balance, String> {
+        let pool = self.pools.get_mut(pool_id)
+            .ok_or_else(|| "Pool no encontrado".to_string())?;
+        let stake = self.stakes.get_mut(pool_id)
+            .and_then(|stakes| stakes.get_mut(user))
+            .filter(|stake| stake.amount > 0)
+            .ok_or_else(|| "No hay stake en el pool".to_string())?;
+        if amount == 0 || amount > stake.amount {
+            return Err("Cantidad de unstake inválida".to_string());
+        }
+        if now < stake.lock_end_time {
+            return Err("La posición aún está bloqueada".to_string());
+        }
 
-        self.history.push(history_entry);
+        pool.update_rewards(now);
+        let remaining = stake.amount - amount;
+        stake.settle(pool, remaining);
+        pool.total_staked -= amount;
 
-        Ok(pending_rewards)
+        let amount_usd = Self::usd_value(&self.price_feeds, &pool.stake_token, amount);
+        self.record_history(user, pool_id, StakingAction::Unstake, amount, amount_usd, now);
+        Ok(remaining)
     }
 
-    /// Calcular recompensas pendientes
-    fn calculate_pending_rewards(&self, position: &StakingPosition) -> Result<String, JsValue> {
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    /// Recompensas pendientes de un usuario en un pool en `now`, por token
+    pub fn pending_rewards_at(&self, user: &str, pool_id: &str, now: u64) -> Vec<(String, Balance)> {
+        let (Some(pool), Some(stake)) = (self.pools.get(pool_id), self.user_stake(user, pool_id)) else {
+            return Vec::new();
+        };
+        let mut pool = pool.clone();
+        pool.update_rewards(now);
+        pool.reward_tokens.iter()
+            .map(|reward| reward.token_symbol.clone())
+            .zip(stake.pending(&pool))
+            .collect()
+    }
 
-        let staked_amount = position.amount_staked.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear cantidad staked"))?;
-        let claimed_rewards = position.rewards_claimed.parse::<u128>()
-            .map_err(|_| JsValue::from_str("Error al parsear recompensas reclamadas"))?;
+    /// Reclamar las recompensas de un usuario en un pool en `now`:
+    /// `stake * reward_per_share - deuda` más lo devengado antes del último
+    /// cambio de stake. Devuelve el importe pagado por token
+    pub fn claim_rewards_at(&mut self, user: &str, pool_id: &str, now: u64) -> Result<Vec<(String, Balance)>, String> {
+        let pool = self.pools.get_mut(pool_id)
+            .ok_or_else(|| "Pool no encontrado".to_string())?;
+        let stake = self.stakes.get_mut(pool_id)
+            .and_then(|stakes| stakes.get_mut(user))
+            .ok_or_else(|| "No hay stake en el pool".to_string())?;
+
+        pool.update_rewards(now);
+        let pending = stake.pending(pool);
+        if pending.iter().all(|&amount| amount == 0) {
+            return Err("No hay recompensas para reclamar".to_string());
+        }
+        stake.accrued_rewards = vec![0; pool.reward_tokens.len()];
+        stake.reset_debt(pool);
 
-        let time_staked = current_time - position.start_time;
-        let days_staked = time_staked as f64 / 86400.0; // Convertir a días
+        let mut claimed = Vec::new();
+        for (reward, amount) in pool.reward_tokens.iter_mut().zip(pending) {
+            if amount == 0 {
+                continue;
+            }
+            reward.total_distributed = reward.total_distributed.saturating_add(amount);
+            claimed.push((reward.token_symbol.clone(), amount));
+        }
 
-        let annual_reward_rate = position.apr_at_stake / 100.0;
-        let daily_reward_rate = annual_reward_rate / 365.0;
-        let total_rewards = staked_amount as f64 * daily_reward_rate * days_staked;
-        let pending_rewards = total_rewards - claimed_rewards as f64;
+        let island = pool.island.clone();
+        let mut claimed_usd = 0.0;
+        for (token, amount) in &claimed {
+            let amount_usd = Self::usd_value(&self.price_feeds, token, *amount);
+            claimed_usd += amount_usd;
+            self.rewards.entry(user.to_string()).or_default().push(RewardInfo {
+                token_symbol: token.clone(),
+                amount: amount.to_string(),
+                amount_usd,
+                timestamp: now,
+                source: "staking".to_string(),
+                island: island.clone(),
+            });
+        }
+        // El historial agrega el importe de todos los tokens de recompensa
+        let total: Balance = claimed.iter().map(|(_, amount)| *amount).sum();
+        self.record_history(user, pool_id, StakingAction::ClaimRewards, total, claimed_usd, now);
+        Ok(claimed)
+    }
 
-        Ok((pending_rewards * 1e18) as u128.to_string())
+    /// Establecer los precios en USD por símbolo de token
+    pub fn set_price_feeds(&mut self, price_feeds: &HashMap<String, f64>) {
+        self.price_feeds.clone_from(price_feeds);
     }
 
-    /// Obtener posiciones del usuario
-    pub fn get_user_positions(&self) -> JsValue {
-        let user_address = "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6";
-        let user_positions: Vec<&StakingPosition> = self.positions.values()
-            .filter(|pos| pos.user_address == user_address)
-            .collect();
-        
-        serde_wasm_bindgen::to_value(&user_positions).unwrap_or_default()
+    /// Estadísticas de staking con los precios actuales
+    pub fn staking_stats(&self) -> StakingStats {
+        let total_value_locked_usd = self.pools.values()
+            .map(|pool| Self::usd_value(&self.price_feeds, &pool.stake_token, pool.total_staked))
+            .sum();
+        let total_rewards_distributed_usd = self.pools.values()
+            .flat_map(|pool| pool.reward_tokens.iter())
+            .map(|reward| Self::usd_value(&self.price_feeds, &reward.token_symbol, reward.total_distributed))
+            .sum();
+        let total_stakers = self.stakes.values()
+            .flat_map(|stakes| stakes.values())
+            .filter(|stake| stake.amount > 0)
+            .map(|stake| stake.user_address.as_str())
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+
+        StakingStats {
+            total_pools: self.pools.len(),
+            active_pools: self.pools.values().filter(|pool| pool.is_active).count(),
+            total_stakers,
+            total_value_locked_usd,
+            total_rewards_distributed_usd,
+        }
     }
 
-    /// Obtener posiciones activas
-    pub fn get_active_positions(&self) -> JsValue {
-        let active_positions: Vec<&StakingPosition> = self.positions.values()
-            .filter(|pos| pos.is_active)
-            .collect();
-        
-        serde_wasm_bindgen::to_value(&active_positions).unwrap_or_default()
+    /// Valor en USD de `amount` unidades mínimas de un token. Los feeds usan
+    /// el símbolo tal cual o con el sufijo `_TOKEN`; sin precio vale 0
+    fn usd_value(price_feeds: &HashMap<String, f64>, token_symbol: &str, amount: Balance) -> f64 {
+        let price = price_feeds.get(token_symbol)
+            .or_else(|| price_feeds.get(&format!("{}_TOKEN", token_symbol)))
+            .copied()
+            .unwrap_or(0.0);
+        amount as f64 / TOKEN_UNIT * price
     }
 
-    /// Obtener historial de staking
-    pub fn get_staking_history(&self) -> JsValue {
-        serde_wasm_bindgen::to_value(&self.history).unwrap_or_default()
+    /// Añadir una entrada al historial
+    fn record_history(&mut self, user: &str, pool_id: &str, action: StakingAction, amount: Balance, amount_usd: f64, now: u64) {
+        self.history.push(StakingHistory {
+            id: format!("HIST_{}", self.history.len() + 1),
+            user_address: user.to_string(),
+            pool_id: pool_id.to_string(),
+            action,
+            amount: amount.to_string(),
+            amount_usd,
+            timestamp: now,
+            transaction_hash: Some(format!("0x{}", hex::encode([0u8; 32]))),
+        });
     }
+}
 
-    /// Obtener recompensas del usuario
-    pub fn get_user_rewards(&self) -> JsValue {
-        let user_address = "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6";
-        let user_rewards = self.rewards.get(user_address).unwrap_or(&Vec::new());
-        
-        serde_wasm_bindgen::to_value(user_rewards).unwrap_or_default()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "0xa11ce";
+    const BOB: &str = "0xb0b";
+
+    /// Pool con bloqueo `lock_period` que emite 120 RWD por segundo desde t = 0
+    fn manager_with_pool(lock_period: u64) -> (StakingManager, PoolId) {
+        let mut manager = StakingManager::new(&crate::blockchain::BlockchainConfig::default());
+        let pool_id = manager.create_pool_at(PoolConfig {
+            name: "Test".to_string(),
+            stake_token: "STK".to_string(),
+            reward_tokens: vec![RewardTokenConfig {
+                token_symbol: "RWD".to_string(),
+                reward_per_second: 120,
+            }],
+            lock_period,
+            min_stake_amount: 1,
+            max_stake_amount: None,
+            island: "forest".to_string(),
+        }, 0).unwrap();
+        (manager, pool_id)
     }
 
-    /// Obtener estadísticas de staking
-    pub fn get_staking_stats(&self) -> JsValue {
-        let total_pools = self.pools.len();
-        let active_pools = self.pools.values().filter(|pool| pool.is_active).count();
-        let total_positions = self.positions.len();
-        let active_positions = self.positions.values().filter(|pos| pos.is_active).count();
-        let total_staked_usd: f64 = self.pools.values().map(|pool| pool.total_staked_usd).sum();
-        let total_rewards_usd: f64 = self.pools.values().map(|pool| pool.total_rewards_distributed_usd).sum();
-
-        let stats = serde_json::json!({
-            "total_pools": total_pools,
-            "active_pools": active_pools,
-            "total_positions": total_positions,
-            "active_positions": active_positions,
-            "total_staked_usd": total_staked_usd,
-            "total_rewards_usd": total_rewards_usd,
-            "pools_by_island": {
-                "forest": self.get_pools_by_island("forest"),
-                "ocean": self.get_pools_by_island("ocean"),
-                "mountain": self.get_pools_by_island("mountain"),
-                "desert": self.get_pools_by_island("desert"),
-                "city": self.get_pools_by_island("city"),
-            }
-        });
+    #[test]
+    fn rewards_follow_each_users_share_over_time() {
+        let (mut manager, pool) = manager_with_pool(0);
 
-        serde_wasm_bindgen::to_value(&stats).unwrap_or_default()
-    }
+        // t 0..10: Alice sola con 300  -> Alice 1200
+        // t 10..20: Alice 300, Bob 100 -> Alice 900, Bob 300
+        // t 20..30: Alice 100, Bob 100 -> Alice 600, Bob 600
+        assert_eq!(manager.stake_at(ALICE, &pool, 300, 0), Ok(300));
+        assert_eq!(manager.stake_at(BOB, &pool, 100, 10), Ok(100));
+        assert_eq!(manager.unstake_at(ALICE, &pool, 200, 20), Ok(100));
+        assert_eq!(manager.pool(&pool).unwrap().total_staked, 200);
 
-    /// Actualizar red
-    pub fn update_network(&mut self, network: &str) -> Result<(), JsValue> {
-        self.current_network = network.to_string();
-        Ok(())
-    }
+        // Lo devengado antes del unstake sigue pendiente
+        assert_eq!(manager.pending_rewards_at(ALICE, &pool, 20), vec![("RWD".to_string(), 2100)]);
+        assert_eq!(manager.pending_rewards_at(BOB, &pool, 20), vec![("RWD".to_string(), 300)]);
 
-    /// Actualizar configuración
-    pub fn update_config(&mut self, config: &crate::blockchain::BlockchainConfig) -> Result<(), JsValue> {
-        self.current_network = config.default_network.clone();
-        Ok(())
+        assert_eq!(manager.claim_rewards_at(ALICE, &pool, 30), Ok(vec![("RWD".to_string(), 2700)]));
+        assert_eq!(manager.claim_rewards_at(BOB, &pool, 30), Ok(vec![("RWD".to_string(), 900)]));
+
+        // Todo lo emitido en 30 s se ha pagado y no queda nada por reclamar
+        assert_eq!(manager.pool(&pool).unwrap().reward_tokens[0].total_distributed, 120 * 30);
+        assert!(manager.claim_rewards_at(ALICE, &pool, 30).is_err());
+        assert_eq!(manager.pending_rewards_at(BOB, &pool, 30), vec![("RWD".to_string(), 0)]);
+
+        // Tras el claim se sigue devengando con el stake restante
+        assert_eq!(manager.pending_rewards_at(ALICE, &pool, 40), vec![("RWD".to_string(), 600)]);
     }
 
-    /// Verificar si está inicializado
-    pub fn is_initialized(&self) -> bool {
-        self.is_initialized
+    #[test]
+    fn locked_stake_cannot_be_withdrawn() {
+        let (mut manager, pool) = manager_with_pool(100);
+        manager.stake_at(ALICE, &pool, 300, 0).unwrap();
+
+        assert!(manager.unstake_at(ALICE, &pool, 100, 99).is_err());
+        assert_eq!(manager.user_stake(ALICE, &pool).unwrap().amount, 300);
+        assert_eq!(manager.unstake_at(ALICE, &pool, 100, 100), Ok(200));
+        assert!(manager.unstake_at(ALICE, &pool, 201, 100).is_err());
     }
-} 
\ No newline at end of file
+}
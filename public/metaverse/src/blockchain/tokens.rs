@@ -266,6 +266,7 @@ impl TokenManager {
         if balance_amount < transfer_amount {
             return Err(JsValue::from_str("Saldo insuficiente"));
         }
+        let token_address = balance.token_address.clone();
 
         // Simular transacción
         let tx_hash = format!("0x{}", hex::encode(&[0u8; 32]));
@@ -284,7 +285,7 @@ impl TokenManager {
             hash: tx_hash.clone(),
             from: "0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6".to_string(),
             to: to_address.to_string(),
-            token_address: balance.token_address.clone(),
+            token_address,
             symbol: symbol.to_string(),
             amount: amount.to_string(),
             amount_usd: self.tokens.get(symbol).and_then(|t| t.price_usd)
@@ -390,4 +391,11 @@ impl TokenManager {
     pub fn is_initialized(&self) -> bool {
         self.is_initialized
     }
-} 
\ No newline at end of file
+}
+
+impl TokenManager {
+    /// Saldo del usuario en un token
+    pub fn balance_of(&self, symbol: &str) -> Option<u128> {
+        self.user_balances.get(symbol)?.balance.parse().ok()
+    }
+}
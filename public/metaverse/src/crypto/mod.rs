@@ -0,0 +1,6 @@
+//! Criptografía del Metaverso
+//! Primitivas usadas por los gestores de blockchain
+
+pub mod vrf;
+
+pub use self::vrf::{VRFOutput, VRFProof};
@@ -0,0 +1,226 @@
+//! Funciones aleatorias verificables (ECVRF sobre secp256k1)
+//! La salida solo la puede calcular quien tiene la clave privada y cualquiera
+//! la comprueba con la clave pública, así que nadie puede elegirla ni
+//! falsificarla. El nonce entra en el mensaje firmado: una prueba solo vale
+//! para la petición que la generó
+//!
+//! Implementa ECVRF-SECP256K1-SHA256-TAI (RFC 9381 con el sufijo 0xFE de la
+//! suite secp256k1) en Rust puro, así que también compila a wasm32.
+
+use k256::elliptic_curve::bigint::ArrayEncoding;
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use k256::elliptic_curve::{Curve, PrimeField};
+use k256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar, Secp256k1, SecretKey, U256};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Identificador de la suite ECVRF usada por todas las pruebas
+const SUITE: u8 = 0xFE;
+
+/// Bytes de un punto comprimido
+const POINT_LEN: usize = 33;
+
+/// Bytes del desafío `c`
+const CHALLENGE_LEN: usize = 16;
+
+/// Bytes de la prueba: Gamma, c y s
+pub const PROOF_LEN: usize = POINT_LEN + CHALLENGE_LEN + 32;
+
+/// Salida pseudoaleatoria de una prueba VRF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VRFOutput(pub [u8; 32]);
+
+/// Prueba VRF de un mensaje con su salida
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VRFProof {
+    /// Nonce de la petición incluido en el mensaje probado
+    pub nonce: u64,
+    /// Prueba ECVRF (pi)
+    pub proof: Vec<u8>,
+    /// Salida derivada de la prueba
+    pub output: VRFOutput,
+}
+
+/// Mensaje probado: nonce en big endian seguido de la semilla
+fn message(nonce: u64, seed: &[u8; 32]) -> Vec<u8> {
+    let mut alpha = nonce.to_be_bytes().to_vec();
+    alpha.extend_from_slice(seed);
+    alpha
+}
+
+fn encode_point(point: &ProjectivePoint) -> Vec<u8> {
+    point.to_affine().to_encoded_point(true).as_bytes().to_vec()
+}
+
+/// Punto comprimido válido distinto del infinito
+fn decode_point(bytes: &[u8]) -> Option<ProjectivePoint> {
+    if bytes.len() != POINT_LEN {
+        return None;
+    }
+    let encoded = EncodedPoint::from_bytes(bytes).ok()?;
+    Option::<AffinePoint>::from(AffinePoint::from_encoded_point(&encoded)).map(ProjectivePoint::from)
+}
+
+/// Escalar de una clave privada de 32 bytes en big endian
+fn secret_scalar(secret_key: &[u8]) -> Result<Scalar, String> {
+    if secret_key.len() != 32 {
+        return Err(format!("Clave VRF de {} bytes", secret_key.len()));
+    }
+    SecretKey::from_slice(secret_key)
+        .map(|key| *key.to_nonzero_scalar())
+        .map_err(|_| "Clave VRF inválida".to_string())
+}
+
+/// Punto H del mensaje por try-and-increment
+fn hash_to_curve(public_key: &[u8], alpha: &[u8]) -> Option<ProjectivePoint> {
+    (0u8..=255).find_map(|counter| {
+        let digest = Sha256::new()
+            .chain_update([SUITE, 0x01])
+            .chain_update(public_key)
+            .chain_update(alpha)
+            .chain_update([counter, 0x00])
+            .finalize();
+        let mut candidate = [0x02; POINT_LEN];
+        candidate[1..].copy_from_slice(&digest);
+        decode_point(&candidate)
+    })
+}
+
+/// Desafío `c` de los puntos de la prueba, truncado a 128 bits
+fn challenge(points: [&ProjectivePoint; 5]) -> Scalar {
+    let mut hasher = Sha256::new().chain_update([SUITE, 0x02]);
+    for point in points {
+        hasher.update(encode_point(point));
+    }
+    let digest = hasher.chain_update([0x00]).finalize();
+    let mut bytes = FieldBytes::default();
+    bytes[32 - CHALLENGE_LEN..].copy_from_slice(&digest[..CHALLENGE_LEN]);
+    // Menor que 2^128, siempre dentro del orden del grupo
+    Scalar::from_repr(bytes).unwrap()
+}
+
+/// Nonce determinista de la prueba (RFC 6979 sobre el punto H)
+fn proof_nonce(secret: &Scalar, h: &ProjectivePoint) -> Scalar {
+    let digest = Sha256::digest(encode_point(h));
+    let h1 = <Scalar as Reduce<U256>>::reduce_bytes(&digest);
+    let k = rfc6979::generate_k::<Sha256, _>(
+        &secret.to_bytes(),
+        &Secp256k1::ORDER.to_be_byte_array(),
+        &h1.to_bytes(),
+        &[],
+    );
+    Scalar::from_repr(k).unwrap()
+}
+
+fn proof_to_output(gamma: &ProjectivePoint) -> VRFOutput {
+    let digest = Sha256::new()
+        .chain_update([SUITE, 0x03])
+        .chain_update(encode_point(gamma))
+        .chain_update([0x00])
+        .finalize();
+    VRFOutput(digest.into())
+}
+
+/// Clave pública comprimida de una clave privada
+pub fn derive_public_key(secret_key: &[u8]) -> Result<Vec<u8>, String> {
+    let secret = secret_scalar(secret_key)?;
+    Ok(encode_point(&(ProjectivePoint::GENERATOR * secret)))
+}
+
+/// Probar `seed` para la petición `nonce`
+pub fn prove(secret_key: &[u8], nonce: u64, seed: &[u8; 32]) -> Result<VRFProof, String> {
+    let secret = secret_scalar(secret_key)?;
+    let public_key = encode_point(&(ProjectivePoint::GENERATOR * secret));
+    let h = hash_to_curve(&public_key, &message(nonce, seed))
+        .ok_or_else(|| "Error generando la prueba VRF: mensaje sin punto".to_string())?;
+
+    let gamma = h * secret;
+    let k = proof_nonce(&secret, &h);
+    let y = ProjectivePoint::GENERATOR * secret;
+    let c = challenge([&y, &h, &gamma, &(ProjectivePoint::GENERATOR * k), &(h * k)]);
+    let s = k + c * secret;
+
+    let mut proof = encode_point(&gamma);
+    proof.extend_from_slice(&c.to_bytes()[32 - CHALLENGE_LEN..]);
+    proof.extend_from_slice(&s.to_bytes());
+    Ok(VRFProof { nonce, proof, output: proof_to_output(&gamma) })
+}
+
+/// Salida de una prueba si es válida para `seed` y `public_key` y coincide
+/// con la que declara
+pub fn verify(public_key: &[u8], seed: &[u8; 32], proof: &VRFProof) -> Option<VRFOutput> {
+    let y = decode_point(public_key)?;
+    if proof.proof.len() != PROOF_LEN {
+        return None;
+    }
+    let (gamma, rest) = proof.proof.split_at(POINT_LEN);
+    let (c, s) = rest.split_at(CHALLENGE_LEN);
+    let gamma = decode_point(gamma)?;
+    let mut c_bytes = FieldBytes::default();
+    c_bytes[32 - CHALLENGE_LEN..].copy_from_slice(c);
+    let c = Scalar::from_repr(c_bytes).unwrap();
+    let mut s_bytes = FieldBytes::default();
+    s_bytes.copy_from_slice(s);
+    let s = Option::<Scalar>::from(Scalar::from_repr(s_bytes))?;
+
+    let h = hash_to_curve(public_key, &message(proof.nonce, seed))?;
+    let u = ProjectivePoint::GENERATOR * s - y * c;
+    let v = h * s - gamma * c;
+    if challenge([&y, &h, &gamma, &u, &v]) != c {
+        return None;
+    }
+    Some(proof_to_output(&gamma)).filter(|output| *output == proof.output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: [u8; 32] = [7; 32];
+    const SEED: [u8; 32] = [42; 32];
+
+    #[test]
+    fn valid_proof_verifies_with_a_deterministic_output() {
+        let public_key = derive_public_key(&SECRET).unwrap();
+        assert_eq!(public_key.len(), POINT_LEN);
+
+        let proof = prove(&SECRET, 1, &SEED).unwrap();
+        assert_eq!(proof.proof.len(), PROOF_LEN);
+        assert_eq!(verify(&public_key, &SEED, &proof), Some(proof.output));
+        assert_eq!(prove(&SECRET, 1, &SEED).unwrap(), proof);
+        // Otro nonce da otra salida
+        assert_ne!(prove(&SECRET, 2, &SEED).unwrap().output, proof.output);
+    }
+
+    #[test]
+    fn tampered_proof_is_rejected() {
+        let public_key = derive_public_key(&SECRET).unwrap();
+        let proof = prove(&SECRET, 1, &SEED).unwrap();
+
+        for index in [1, POINT_LEN, POINT_LEN + CHALLENGE_LEN, PROOF_LEN - 1] {
+            let mut tampered = proof.clone();
+            tampered.proof[index] ^= 1;
+            assert_eq!(verify(&public_key, &SEED, &tampered), None, "byte {} alterado", index);
+        }
+
+        let mut other_output = proof.clone();
+        other_output.output.0[0] ^= 1;
+        assert_eq!(verify(&public_key, &SEED, &other_output), None);
+
+        let mut other_nonce = proof.clone();
+        other_nonce.nonce = 2;
+        assert_eq!(verify(&public_key, &SEED, &other_nonce), None);
+        assert_eq!(verify(&public_key, &[0; 32], &proof), None);
+
+        let other_key = derive_public_key(&[8; 32]).unwrap();
+        assert_eq!(verify(&other_key, &SEED, &proof), None);
+    }
+
+    #[test]
+    fn invalid_secret_keys_are_rejected() {
+        assert!(derive_public_key(&[0; 32]).is_err());
+        assert!(derive_public_key(&[0xff; 32]).is_err());
+        assert!(prove(&SECRET[..31], 1, &SEED).is_err());
+    }
+}
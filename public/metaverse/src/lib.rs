@@ -1,6 +1,7 @@
 //! Metaverso Crypto World Virtual 3D - Motor Principal
 //! Sistema completo de metaverso descentralizado con audio, blockchain y exploración 3D
 
+pub mod achievements;
 pub mod audio;
 pub mod blockchain;
 pub mod crypto;